@@ -4,7 +4,8 @@ use std::sync::Arc;
 
 use toolify_rs::auth::{authenticate, build_allowed_key_set};
 use toolify_rs::config::{
-    AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, ServerConfig, UpstreamServiceConfig,
+    AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, PathStyle, ServerConfig, StreamSupport,
+    ToolSchemaValidation, UpstreamAuthConfig, UpstreamServiceConfig,
 };
 use toolify_rs::error::CanonicalError;
 use toolify_rs::fc::detector::StreamingFcDetector;
@@ -13,8 +14,10 @@ use toolify_rs::protocol::canonical::{
     CanonicalMessage, CanonicalPart, CanonicalRequest, CanonicalRole, CanonicalToolChoice,
     GenerationParams, IngressApi,
 };
+use toolify_rs::routing::path_matcher::{split_gemini_action, PathMatcher};
 use toolify_rs::routing::{ModelRouter, RouteTarget};
 use toolify_rs::state::{AppState, SessionClass};
+use toolify_rs::stream::sse::sse_raw_frame_stream;
 use toolify_rs::transport::{HttpTransport, PreparedUpstream};
 
 fn make_upstream(index: usize, provider: &str, is_default: bool) -> UpstreamServiceConfig {
@@ -31,6 +34,17 @@ fn make_upstream(index: usize, provider: &str, is_default: bool) -> UpstreamServ
         proxy: None,
         proxy_stream: None,
         proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
     }
 }
 
@@ -48,8 +62,16 @@ fn build_state(openai_count: usize, anthropic_count: usize) -> AppState {
         upstream_services,
         client_authentication: ClientAuthConfig {
             allowed_keys: vec!["client-key".to_string()],
+            admin_keys: Vec::new(),
+            key_model_maps: Vec::new(),
         },
         features: FeaturesConfig::default(),
+        virtual_models: Vec::new(),
+        routing_rules: Vec::new(),
+        cascade_models: Vec::new(),
+        admin_authentication: None,
+        fallback_chains: Vec::new(),
+        tenants: Vec::new(),
     };
 
     let model_router = ModelRouter::new(&config);
@@ -137,6 +159,7 @@ fn bench_route_resolution(c: &mut Criterion) {
     let open_err = CanonicalError::Upstream {
         status: 503,
         message: "temporarily unavailable".to_string(),
+        retry_after: None,
     };
     for _ in 0..5 {
         degraded_state.record_upstream_failure(0, model, &open_err);
@@ -209,8 +232,16 @@ fn bench_authentication(c: &mut Criterion) {
         upstream_services: vec![],
         client_authentication: ClientAuthConfig {
             allowed_keys: vec!["client-key".to_string()],
+            admin_keys: Vec::new(),
+            key_model_maps: Vec::new(),
         },
         features: FeaturesConfig::default(),
+        virtual_models: Vec::new(),
+        routing_rules: Vec::new(),
+        cascade_models: Vec::new(),
+        admin_authentication: None,
+        fallback_chains: Vec::new(),
+        tenants: Vec::new(),
     };
     let single_keys = build_allowed_key_set(&single_cfg);
     let mut single_headers = http::HeaderMap::new();
@@ -238,8 +269,16 @@ fn bench_authentication(c: &mut Criterion) {
         upstream_services: vec![],
         client_authentication: ClientAuthConfig {
             allowed_keys: multi_allowed,
+            admin_keys: Vec::new(),
+            key_model_maps: Vec::new(),
         },
         features: FeaturesConfig::default(),
+        virtual_models: Vec::new(),
+        routing_rules: Vec::new(),
+        cascade_models: Vec::new(),
+        admin_authentication: None,
+        fallback_chains: Vec::new(),
+        tenants: Vec::new(),
     };
     let multi_keys = build_allowed_key_set(&multi_cfg);
     let mut multi_headers = http::HeaderMap::new();
@@ -442,6 +481,85 @@ fn bench_fc_trigger_scan(c: &mut Criterion) {
     });
 }
 
+/// Upstream chunks as a passthrough stream delivers them: several frames per
+/// chunk, with one frame split across each chunk boundary.
+fn passthrough_sse_chunks(chunks: usize, frames_per_chunk: usize) -> Vec<bytes::Bytes> {
+    let frame = "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"hello\"}}]}\n\n";
+    let body = frame.repeat(chunks * frames_per_chunk);
+    let chunk_len = body.len() / chunks + frame.len() / 2;
+    body.as_bytes()
+        .chunks(chunk_len)
+        .map(bytes::Bytes::copy_from_slice)
+        .collect()
+}
+
+/// The pre-slicing splitter: every frame copied through one `BytesMut`.
+fn split_frames_copying<S>(source: S) -> impl futures_util::Stream<Item = bytes::Bytes>
+where
+    S: futures_util::Stream<Item = Result<bytes::Bytes, std::convert::Infallible>> + Unpin,
+{
+    use futures_util::StreamExt;
+    futures_util::stream::unfold(
+        (source, bytes::BytesMut::with_capacity(4096)),
+        |(mut source, mut buffer)| async move {
+            loop {
+                if let Some(pos) = memchr::memmem::find(&buffer, b"\n\n") {
+                    let frame = buffer.split_to(pos + 2).freeze();
+                    return Some((frame, (source, buffer)));
+                }
+                let Ok(chunk) = source.next().await?;
+                buffer.extend_from_slice(&chunk);
+            }
+        },
+    )
+}
+
+fn bench_passthrough_frame_split(c: &mut Criterion) {
+    use futures_util::StreamExt;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("runtime");
+    let chunks = passthrough_sse_chunks(64, 16);
+    let source = || {
+        futures_util::stream::iter(
+            chunks
+                .clone()
+                .into_iter()
+                .map(Ok::<_, std::convert::Infallible>),
+        )
+    };
+
+    c.bench_function("passthrough_frame_split_sliced_64x16", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let frames = sse_raw_frame_stream(source());
+                futures_util::pin_mut!(frames);
+                let mut count = 0usize;
+                while let Some(frame) = frames.next().await {
+                    black_box(frame);
+                    count += 1;
+                }
+                count
+            })
+        });
+    });
+    c.bench_function("passthrough_frame_split_copying_64x16", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let frames = split_frames_copying(source());
+                futures_util::pin_mut!(frames);
+                let mut count = 0usize;
+                while let Some(frame) = frames.next().await {
+                    black_box(frame);
+                    count += 1;
+                }
+                count
+            })
+        });
+    });
+}
+
 fn bench_fc_parser(c: &mut Criterion) {
     let trigger = prompt::get_trigger_signal();
     let payload_function_call = fc_parse_payload_function_call();
@@ -537,6 +655,38 @@ fn bench_no_tools_model_switch(c: &mut Criterion) {
     });
 }
 
+fn bench_dispatch_path_match(c: &mut Criterion) {
+    let matcher = PathMatcher::shared();
+    let paths = [
+        "/v1/chat/completions",
+        "/v1/messages",
+        "/v1/responses",
+        "/v1beta/models/gemini-2.5-flash:streamGenerateContent",
+        "/v1/files/file-abc123/content",
+        "/not/a/route",
+    ];
+
+    c.bench_function("dispatch_path_match_mixed_6", |b| {
+        b.iter(|| {
+            for path in paths {
+                black_box(matcher.lookup(black_box(path)));
+            }
+        });
+    });
+
+    c.bench_function("dispatch_path_match_gemini_action", |b| {
+        b.iter(|| {
+            let tail = matcher
+                .lookup(black_box(
+                    "/v1beta/models/gemini-2.5-flash:streamGenerateContent",
+                ))
+                .expect("gemini route")
+                .tail;
+            black_box(split_gemini_action(tail))
+        });
+    });
+}
+
 criterion_group!(
     benches,
     bench_route_resolution,
@@ -545,8 +695,10 @@ criterion_group!(
     bench_model_router_single_candidate,
     bench_start_candidate_index,
     bench_fc_trigger_scan,
+    bench_passthrough_frame_split,
     bench_fc_parser,
     bench_fc_detector,
-    bench_no_tools_model_switch
+    bench_no_tools_model_switch,
+    bench_dispatch_path_match
 );
 criterion_main!(benches);
@@ -798,10 +798,103 @@ fn bench_stream_transcode_sequence_matrix(c: &mut Criterion) {
     }
 }
 
+/// Large non-streaming response bodies: ~64 KiB of text (a newline every 80
+/// bytes) plus one tool call with a 256-field argument object.
+fn sample_large_response_bodies() -> [(&'static str, ProviderKind, Vec<u8>); 4] {
+    let line = format!("{}\n", "word ".repeat(16)).replace("word ", "words ");
+    let text = serde_json::to_string(&line.repeat(800)).expect("text json");
+    let args = serde_json::Value::Object(
+        (0..256)
+            .map(|i| {
+                (
+                    format!("field_{i}"),
+                    serde_json::json!({"value": i, "label": "x"}),
+                )
+            })
+            .collect(),
+    );
+    let args_string = serde_json::to_string(&args.to_string()).expect("args json");
+    let openai = format!(
+        r#"{{"id":"chatcmpl-1","object":"chat.completion","created":1,"model":"m","choices":[{{"index":0,"message":{{"role":"assistant","content":{text},"tool_calls":[{{"id":"call_1","type":"function","function":{{"name":"search","arguments":{args_string}}}}}]}},"finish_reason":"tool_calls"}}],"usage":{{"prompt_tokens":1,"completion_tokens":2,"total_tokens":3}}}}"#
+    );
+    let anthropic = format!(
+        r#"{{"id":"msg_1","type":"message","role":"assistant","model":"m","content":[{{"type":"text","text":{text}}},{{"type":"tool_use","id":"toolu_1","name":"search","input":{args}}}],"stop_reason":"tool_use","usage":{{"input_tokens":1,"output_tokens":2}}}}"#
+    );
+    let gemini = format!(
+        r#"{{"candidates":[{{"content":{{"role":"model","parts":[{{"text":{text}}},{{"functionCall":{{"name":"search","args":{args}}}}}]}},"finishReason":"STOP","index":0}}],"usageMetadata":{{"promptTokenCount":1,"candidatesTokenCount":2,"totalTokenCount":3}},"modelVersion":"m"}}"#
+    );
+    let responses = format!(
+        r#"{{"id":"resp_1","object":"response","created_at":1,"model":"m","status":"completed","output":[{{"type":"message","id":"msg_1","role":"assistant","content":[{{"type":"output_text","text":{text},"annotations":[]}}]}},{{"type":"function_call","id":"fc_1","call_id":"call_1","name":"search","arguments":{args_string}}}],"usage":{{"input_tokens":1,"output_tokens":2,"total_tokens":3}}}}"#
+    );
+    [
+        ("openai", ProviderKind::OpenAi, openai.into_bytes()),
+        ("anthropic", ProviderKind::Anthropic, anthropic.into_bytes()),
+        ("gemini", ProviderKind::Gemini, gemini.into_bytes()),
+        (
+            "responses",
+            ProviderKind::OpenAiResponses,
+            responses.into_bytes(),
+        ),
+    ]
+}
+
+fn decode_response_via_wire(provider: ProviderKind, body: &[u8]) -> usize {
+    let decoded = match provider {
+        ProviderKind::OpenAi | ProviderKind::GeminiOpenAi => {
+            let wire: openai_chat::OpenAiChatResponse =
+                serde_json::from_slice(body).expect("openai wire");
+            openai_chat::response_decoder::decode_openai_chat_response_owned(wire)
+        }
+        ProviderKind::Anthropic => {
+            let wire: anthropic::AnthropicResponse =
+                serde_json::from_slice(body).expect("anthropic wire");
+            anthropic::response_decoder::decode_anthropic_response_owned(wire)
+        }
+        ProviderKind::Gemini => {
+            let wire: gemini::GeminiResponse = serde_json::from_slice(body).expect("gemini wire");
+            gemini::response_decoder::decode_gemini_response_owned(wire)
+        }
+        ProviderKind::OpenAiResponses => {
+            let wire: openai_responses::ResponsesOutput =
+                serde_json::from_slice(body).expect("responses wire");
+            openai_responses::response_decoder::decode_responses_output_owned(wire)
+        }
+    };
+    decoded.expect("decode").content.len()
+}
+
+fn decode_response_fast(provider: ProviderKind, body: &[u8]) -> usize {
+    let decoded = match provider {
+        ProviderKind::OpenAi | ProviderKind::GeminiOpenAi => {
+            openai_chat::response_decoder::try_decode_openai_chat_response_bytes(body)
+        }
+        ProviderKind::Anthropic => {
+            anthropic::response_decoder::try_decode_anthropic_response_bytes(body)
+        }
+        ProviderKind::Gemini => gemini::response_decoder::try_decode_gemini_response_bytes(body),
+        ProviderKind::OpenAiResponses => {
+            openai_responses::response_decoder::try_decode_responses_output_bytes(body)
+        }
+    };
+    decoded.expect("fast decode").content.len()
+}
+
+fn bench_response_decode(c: &mut Criterion) {
+    for (name, provider, body) in sample_large_response_bodies() {
+        c.bench_function(&format!("response_decode_wire_{name}_64k"), |b| {
+            b.iter(|| black_box(decode_response_via_wire(provider, black_box(&body))));
+        });
+        c.bench_function(&format!("response_decode_fast_{name}_64k"), |b| {
+            b.iter(|| black_box(decode_response_fast(provider, black_box(&body))));
+        });
+    }
+}
+
 criterion_group!(
     benches,
     bench_transcode,
     bench_stream_transcode_matrix,
-    bench_stream_transcode_sequence_matrix
+    bench_stream_transcode_sequence_matrix,
+    bench_response_decode
 );
 criterion_main!(benches);
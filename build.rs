@@ -0,0 +1,16 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    compile_grpc_protos();
+}
+
+/// Generate the `toolify.v1` messages and `Completion` service stubs with the
+/// vendored `protoc`, so building with `--features grpc` needs no system one.
+#[cfg(feature = "grpc")]
+fn compile_grpc_protos() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc");
+    std::env::set_var("PROTOC", protoc);
+    tonic_prost_build::configure()
+        .build_transport(false)
+        .compile_protos(&["proto/toolify/v1/completion.proto"], &["proto"])
+        .expect("compile toolify.v1 protos");
+}
@@ -0,0 +1,627 @@
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use serde_json::{json, Value};
+
+use crate::config::diff::{diff_configs, ConfigChange};
+use crate::config::{parse_config, AppConfig, UpstreamServiceConfig};
+use crate::error::{into_axum_response, CanonicalError};
+#[cfg(feature = "analytics")]
+use crate::observability::analytics::{AnalyticsReport, AnalyticsStore};
+use crate::observability::tail::TailFilter;
+use crate::protocol::canonical::IngressApi;
+use crate::state::AppState;
+use crate::stream::memory_budget;
+use crate::transport::upstream_base_url;
+
+/// Admin errors use the `OpenAI` error shape.
+const INGRESS: IngressApi = IngressApi::OpenAiChat;
+/// Upper bound for one upstream reachability probe during config validation.
+const CONFIG_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Authenticate an admin request, returning the response to send on failure.
+///
+/// The admin API answers 404 when no admin credentials are configured.
+fn admin_rejection(state: &AppState, request: &Parts) -> Option<Response> {
+    admin_body_rejection(state, request, &[])
+}
+
+/// [`admin_rejection`] for a request whose body was read; signed requests
+/// must have signed this body.
+fn admin_body_rejection(state: &AppState, request: &Parts, body: &[u8]) -> Option<Response> {
+    let admin_auth = state.admin_auth();
+    if !admin_auth.is_enabled() {
+        return Some(StatusCode::NOT_FOUND.into_response());
+    }
+    admin_auth
+        .authenticate(&request.method, &request.uri, &request.headers, body)
+        .err()
+        .map(|err| into_axum_response(&err, INGRESS))
+}
+
+/// List upstream routes that are failing, throttled (429/529), or cooling down.
+#[must_use]
+pub fn cooldowns_handler(State(state): State<Arc<AppState>>, request: &Parts) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+
+    let data: Vec<Value> = state
+        .route_cooldowns()
+        .into_iter()
+        .map(|route| {
+            json!({
+                "upstream": state.upstream_name(route.upstream_index),
+                "provider": state.config.upstream_services[route.upstream_index].provider,
+                "model": route.model_group,
+                "consecutive_failures": route.consecutive_failures,
+                "throttle_status": route.throttle_status,
+                "cooling_down": route.cooldown_remaining_secs > 0,
+                "cooldown_remaining_secs": route.cooldown_remaining_secs,
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "object": "list",
+        "data": data,
+    }))
+    .into_response()
+}
+
+/// Sliding-window streaming TTFB and decode tokens/sec per upstream + model.
+#[must_use]
+pub fn latency_handler(State(state): State<Arc<AppState>>, request: &Parts) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+
+    let data: Vec<Value> = state
+        .route_latency_stats()
+        .into_iter()
+        .map(|route| {
+            json!({
+                "upstream": state.upstream_name(route.upstream_index),
+                "provider": state.config.upstream_services[route.upstream_index].provider,
+                "model": route.model_group,
+                "samples": route.samples,
+                "ttfb_ms_avg": route.ttfb_ms_avg,
+                "ttfb_ms_p90": route.ttfb_ms_p90,
+                "tokens_per_sec_avg": route.tokens_per_sec_avg,
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "object": "list",
+        "data": data,
+    }))
+    .into_response()
+}
+
+/// Route latency and cooldown gauges, quality-retry counters, and FC parse
+/// failure counters in the Prometheus text format.
+#[must_use]
+pub fn metrics_handler(State(state): State<Arc<AppState>>, request: &Parts) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+
+    let latency = state.route_latency_stats();
+    let mut out = String::new();
+    out.push_str(
+        "# HELP toolify_upstream_ttfb_ms Mean streaming time to first byte over the sliding window.\n\
+         # TYPE toolify_upstream_ttfb_ms gauge\n",
+    );
+    for route in &latency {
+        let labels = metric_labels(&state, route.upstream_index, &route.model_group);
+        let _ = writeln!(
+            out,
+            "toolify_upstream_ttfb_ms{{{labels}}} {}",
+            route.ttfb_ms_avg
+        );
+    }
+    out.push_str(
+        "# HELP toolify_upstream_ttfb_p90_ms 90th percentile streaming time to first byte.\n\
+         # TYPE toolify_upstream_ttfb_p90_ms gauge\n",
+    );
+    for route in &latency {
+        let labels = metric_labels(&state, route.upstream_index, &route.model_group);
+        let _ = writeln!(
+            out,
+            "toolify_upstream_ttfb_p90_ms{{{labels}}} {}",
+            route.ttfb_ms_p90
+        );
+    }
+    out.push_str(
+        "# HELP toolify_upstream_decode_tokens_per_second Mean streaming decode rate.\n\
+         # TYPE toolify_upstream_decode_tokens_per_second gauge\n",
+    );
+    for route in &latency {
+        let Some(rate) = route.tokens_per_sec_avg else {
+            continue;
+        };
+        let labels = metric_labels(&state, route.upstream_index, &route.model_group);
+        let _ = writeln!(
+            out,
+            "toolify_upstream_decode_tokens_per_second{{{labels}}} {rate:.3}"
+        );
+    }
+    out.push_str(
+        "# HELP toolify_upstream_cooldown_seconds Remaining route cooldown.\n\
+         # TYPE toolify_upstream_cooldown_seconds gauge\n",
+    );
+    for route in state.route_cooldowns() {
+        let labels = metric_labels(&state, route.upstream_index, &route.model_group);
+        let _ = writeln!(
+            out,
+            "toolify_upstream_cooldown_seconds{{{labels}}} {}",
+            route.cooldown_remaining_secs
+        );
+    }
+    out.push_str(
+        "# HELP toolify_request_too_large_total Requests rejected for a body over max_request_bytes.\n\
+         # TYPE toolify_request_too_large_total counter\n",
+    );
+    for rejections in state.global_request_rejections() {
+        let _ = writeln!(
+            out,
+            "toolify_request_too_large_total{{limit=\"global\",ingress=\"{}\"}} {}",
+            rejections.ingress, rejections.count
+        );
+    }
+    for rejections in state.upstream_request_rejections() {
+        let _ = writeln!(
+            out,
+            "toolify_request_too_large_total{{limit=\"upstream\",upstream=\"{}\"}} {}",
+            escape_label_value(state.upstream_name(rejections.upstream_index)),
+            rejections.count
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP toolify_tail_watchers Connected /admin/tail watchers.\n\
+         # TYPE toolify_tail_watchers gauge\n\
+         toolify_tail_watchers {}",
+        state.live_tail().watcher_count()
+    );
+
+    if let Some(journal) = state.request_journal() {
+        let _ = writeln!(
+            out,
+            "# HELP toolify_journal_dropped_records_total Journal records dropped while the file could not be written.\n\
+             # TYPE toolify_journal_dropped_records_total counter\n\
+             toolify_journal_dropped_records_total {}",
+            journal.dropped_records()
+        );
+    }
+
+    if let Some(max_buffered_bytes) = state.memory_budget() {
+        let _ = writeln!(
+            out,
+            "# HELP toolify_buffered_bytes Bytes held by stream, tool call detection, and aggregation buffers.\n\
+             # TYPE toolify_buffered_bytes gauge\n\
+             toolify_buffered_bytes {}\n\
+             # HELP toolify_memory_budget_bytes Buffered bytes above which new streams are refused.\n\
+             # TYPE toolify_memory_budget_bytes gauge\n\
+             toolify_memory_budget_bytes {max_buffered_bytes}\n\
+             # HELP toolify_memory_budget_rejections_total Streaming requests refused over the memory budget.\n\
+             # TYPE toolify_memory_budget_rejections_total counter\n\
+             toolify_memory_budget_rejections_total {}",
+            memory_budget::buffered_bytes(),
+            memory_budget::rejections()
+        );
+    }
+
+    if let Some(quality_retry) = state.quality_retry() {
+        out.push_str(
+            "# HELP toolify_quality_retries_total Answers re-run on another candidate for low quality.\n\
+             # TYPE toolify_quality_retries_total counter\n",
+        );
+        for retries in quality_retry.retry_counts() {
+            let labels = metric_labels(&state, retries.upstream_index, &retries.model_group);
+            let _ = writeln!(
+                out,
+                "toolify_quality_retries_total{{{labels},reason=\"{}\"}} {}",
+                retries.reason, retries.count
+            );
+        }
+    }
+
+    if let Some(failures) = state.fc_parse_failures() {
+        out.push_str(
+            "# HELP toolify_fc_parse_failures_total Injected tool blocks that failed to parse and were streamed as text.\n\
+             # TYPE toolify_fc_parse_failures_total counter\n",
+        );
+        for failure in failures.counts() {
+            let _ = writeln!(
+                out,
+                "toolify_fc_parse_failures_total{{provider=\"{}\",model=\"{}\"}} {}",
+                failure.provider,
+                escape_label_value(&failure.model),
+                failure.count
+            );
+        }
+    }
+
+    (
+        [(
+            http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        out,
+    )
+        .into_response()
+}
+
+/// Recorded conversation traces, most recently updated first.
+///
+/// Answers 404 unless `features.conversation_traces` is set.
+#[must_use]
+pub fn traces_handler(State(state): State<Arc<AppState>>, request: &Parts) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+    let Some(traces) = state.conversation_traces() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    Json(json!({
+        "object": "list",
+        "data": traces.list(),
+    }))
+    .into_response()
+}
+
+/// Redacted samples of streamed tool blocks that failed to parse, most
+/// recent first.
+///
+/// Answers 404 unless `features.fc_parse_failures` is set.
+#[must_use]
+pub fn fc_parse_failures_handler(State(state): State<Arc<AppState>>, request: &Parts) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+    let Some(failures) = state.fc_parse_failures() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    Json(json!({
+        "object": "list",
+        "data": failures.samples(),
+    }))
+    .into_response()
+}
+
+/// Live request lifecycle events as SSE.
+///
+/// Query parameters narrow the view: `events` (comma separated `started`,
+/// `routed`, `first_byte`, `finished`, `failed`), `ingress`, `model`, and
+/// `upstream`.
+#[must_use]
+pub fn tail_handler(State(state): State<Arc<AppState>>, request: &Parts) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+    let filter = match TailFilter::from_query(request.uri.query().unwrap_or_default()) {
+        Ok(filter) => filter,
+        Err(message) => {
+            return into_axum_response(&CanonicalError::InvalidRequest(message), INGRESS)
+        }
+    };
+    let mut response = Response::new(Body::from_stream(state.live_tail().watch(filter)));
+    let response_headers = response.headers_mut();
+    response_headers.insert(
+        http::header::CONTENT_TYPE,
+        HeaderValue::from_static("text/event-stream"),
+    );
+    response_headers.insert(
+        http::header::CACHE_CONTROL,
+        HeaderValue::from_static("no-cache"),
+    );
+    response
+}
+
+/// Usage reports from the analytics store.
+///
+/// Query parameters: `report` (`top_models`, `key_spend`, or
+/// `upstream_errors`), `hours` to look back (default 24), and `limit` on the
+/// returned rows (default 20). Answers 404 unless built with the `analytics`
+/// feature and `features.analytics` is set.
+pub async fn analytics_handler(State(state): State<Arc<AppState>>, request: &Parts) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+    #[cfg(feature = "analytics")]
+    if let Some(store) = state.analytics() {
+        return analytics_report(Arc::clone(store), request.uri.query()).await;
+    }
+    StatusCode::NOT_FOUND.into_response()
+}
+
+#[cfg(feature = "analytics")]
+async fn analytics_report(store: Arc<AnalyticsStore>, query: Option<&str>) -> Response {
+    let (name, report, hours, limit) = match parse_analytics_query(query.unwrap_or_default()) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            return into_axum_response(&CanonicalError::InvalidRequest(message), INGRESS)
+        }
+    };
+    let since_ms = hours.saturating_mul(3_600_000);
+    match tokio::task::spawn_blocking(move || store.report(report, since_ms, limit)).await {
+        Ok(Ok(data)) => Json(json!({
+            "object": "list",
+            "report": name,
+            "data": data,
+        }))
+        .into_response(),
+        Ok(Err(err)) => {
+            tracing::warn!(error = %err, "analytics report failed");
+            into_axum_response(
+                &CanonicalError::Internal(format!("analytics report failed: {err}")),
+                INGRESS,
+            )
+        }
+        Err(err) => into_axum_response(
+            &CanonicalError::Internal(format!("analytics report task failed: {err}")),
+            INGRESS,
+        ),
+    }
+}
+
+#[cfg(feature = "analytics")]
+fn parse_analytics_query(query: &str) -> Result<(String, AnalyticsReport, u64, usize), String> {
+    let mut name = None;
+    let mut hours = 24;
+    let mut limit = 20;
+    for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        match &*key {
+            "report" => name = Some(value.into_owned()),
+            "hours" => {
+                hours = value
+                    .parse()
+                    .map_err(|_| format!("invalid hours '{value}'"))?;
+            }
+            "limit" => {
+                limit = value
+                    .parse()
+                    .map_err(|_| format!("invalid limit '{value}'"))?;
+            }
+            _ => {}
+        }
+    }
+    let name = name.ok_or("missing report parameter")?;
+    let report = name.parse()?;
+    Ok((name, report, hours, limit))
+}
+
+/// Clear the `/v1/models` cache, including upstreams marked down after a
+/// failed listing, and rebuild it now.
+///
+/// Reports each upstream's listing afterwards.
+pub async fn models_refresh_handler(
+    State(state): State<Arc<AppState>>,
+    request: &Parts,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+
+    let data: Vec<Value> = state
+        .bust_models_cache()
+        .await
+        .into_iter()
+        .map(|upstream| {
+            json!({
+                "upstream": state.upstream_name(upstream.upstream_index),
+                "listed_models": upstream.listed_models,
+                "down": upstream.down_remaining_secs > 0,
+                "down_remaining_secs": upstream.down_remaining_secs,
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "object": "list",
+        "data": data,
+    }))
+    .into_response()
+}
+
+/// One conversation as a single JSON document: every recorded turn with the
+/// messages it added (tool results included), its response (tool calls
+/// included), and the models and upstreams that served it.
+#[must_use]
+pub fn trace_handler(
+    State(state): State<Arc<AppState>>,
+    session: &str,
+    request: &Parts,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+    let Some(traces) = state.conversation_traces() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match traces.get(session) {
+        Some(document) => Json(document).into_response(),
+        None => into_axum_response(
+            &CanonicalError::Upstream {
+                status: 404,
+                message: format!("No trace recorded for session '{session}'"),
+                retry_after: None,
+            },
+            INGRESS,
+        ),
+    }
+}
+
+/// The client and upstream ids of a recently served response, found by
+/// either id.
+#[must_use]
+pub fn response_id_handler(
+    State(state): State<Arc<AppState>>,
+    id: &str,
+    request: &Parts,
+) -> Response {
+    if let Some(response) = admin_rejection(&state, request) {
+        return response;
+    }
+    match state.response_id_mapping(id) {
+        Some(mapping) => Json(json!({
+            "id": mapping.ids.client_id,
+            "upstream_id": mapping.ids.upstream_id,
+            "upstream": &*mapping.upstream,
+        }))
+        .into_response(),
+        None => into_axum_response(
+            &CanonicalError::Upstream {
+                status: 404,
+                message: format!("No recent response with id '{id}'"),
+                retry_after: None,
+            },
+            INGRESS,
+        ),
+    }
+}
+
+fn metric_labels(state: &AppState, upstream_index: usize, model: &str) -> String {
+    format!(
+        "upstream=\"{}\",model=\"{}\"",
+        escape_label_value(state.upstream_name(upstream_index)),
+        escape_label_value(model)
+    )
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Validate a candidate YAML config without applying it.
+///
+/// Runs full config validation, probes every candidate upstream's `base_url`
+/// (any HTTP response counts as reachable), and diffs against the running config.
+pub async fn config_validate_handler(
+    State(state): State<Arc<AppState>>,
+    request: &Parts,
+    body: &[u8],
+) -> Response {
+    if let Some(response) = admin_body_rejection(&state, request, body) {
+        return response;
+    }
+    let candidate = match parse_candidate_config(body) {
+        Ok(candidate) => candidate,
+        Err(err) => return into_axum_response(&err, INGRESS),
+    };
+
+    let probes: Vec<Value> = futures_util::future::join_all(
+        candidate
+            .upstream_services
+            .iter()
+            .map(|upstream| probe_upstream(&state, upstream)),
+    )
+    .await;
+    let reachable = probes.iter().all(|probe| probe["reachable"] == true);
+    let mut payload = changes_payload(&diff_configs(&state.config, &candidate));
+    payload["valid"] = Value::Bool(true);
+    payload["reachable"] = Value::Bool(reachable);
+    payload["probes"] = Value::Array(probes);
+    Json(payload).into_response()
+}
+
+/// Validate a candidate YAML config and atomically swap it in for new requests.
+#[must_use]
+pub fn config_apply_handler(
+    State(state): State<Arc<AppState>>,
+    request: &Parts,
+    body: &[u8],
+) -> Response {
+    if let Some(response) = admin_body_rejection(&state, request, body) {
+        return response;
+    }
+    let candidate = match parse_candidate_config(body) {
+        Ok(candidate) => candidate,
+        Err(err) => return into_axum_response(&err, INGRESS),
+    };
+
+    let changes = diff_configs(&state.config, &candidate);
+    // TLS files may have been rotated in place, so reapplying an unchanged
+    // config still reloads them.
+    let reload_tls = candidate
+        .upstream_services
+        .iter()
+        .any(|upstream| upstream.tls.is_some());
+    let apply = !changes.is_empty() || reload_tls;
+    if apply {
+        let next = state.swap_config(candidate);
+        if next.config.server.warmup_upstreams {
+            tokio::spawn(async move { next.warm_up_upstreams().await });
+        }
+        tracing::info!(changes = changes.len(), "applied config from admin API");
+    }
+    let mut payload = changes_payload(&changes);
+    payload["applied"] = Value::Bool(apply);
+    Json(payload).into_response()
+}
+
+fn parse_candidate_config(body: &[u8]) -> Result<AppConfig, CanonicalError> {
+    let text = std::str::from_utf8(body).map_err(|_| {
+        CanonicalError::InvalidRequest("Config body must be UTF-8 YAML".to_string())
+    })?;
+    parse_config(text).map_err(|err| CanonicalError::InvalidRequest(err.to_string()))
+}
+
+fn changes_payload(changes: &[ConfigChange]) -> Value {
+    let restart_required: Vec<&str> = changes
+        .iter()
+        .filter(|change| change.requires_restart())
+        .map(|change| change.path.as_str())
+        .collect();
+    let changes: Vec<Value> = changes
+        .iter()
+        .map(|change| {
+            json!({
+                "path": change.path,
+                "from": change.from,
+                "to": change.to,
+            })
+        })
+        .collect();
+    json!({
+        "changes": changes,
+        "restart_required": restart_required,
+    })
+}
+
+async fn probe_upstream(state: &AppState, upstream: &UpstreamServiceConfig) -> Value {
+    let no_headers = HeaderMap::new();
+    let base_url = upstream_base_url(upstream);
+    let probe = state.transport.send_request(
+        &base_url,
+        http::Method::GET,
+        &no_headers,
+        bytes::Bytes::new(),
+        upstream.proxy.as_deref(),
+    );
+    let (status, error) = match tokio::time::timeout(CONFIG_PROBE_TIMEOUT, probe).await {
+        Ok(Ok(response)) => (Some(response.status().as_u16()), None),
+        Ok(Err(err)) => (None, Some(err.to_string())),
+        Err(_) => (None, Some("probe timed out".to_string())),
+    };
+    json!({
+        "upstream": upstream.name,
+        "base_url": upstream.base_url,
+        "reachable": status.is_some(),
+        "status": status,
+        "error": error,
+    })
+}
@@ -0,0 +1,436 @@
+//! `OpenAI` Batch API emulation (`/v1/batches`).
+//!
+//! Batches run in the background against the regular ingress handlers, so
+//! every request is routed, FC-injected, and journaled like a live call.
+//! The input file is uploaded with the create call, either as
+//! `input_file_content` in the JSON body or as the raw JSONL body
+//! (`Content-Type: application/jsonl`). Results are served as JSONL from
+//! `/v1/batches/{id}/output` and `/v1/batches/{id}/errors`.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+
+use crate::api::{openai_chat, openai_completions, openai_responses};
+use crate::batch::{
+    parse_batch_input, BatchHandle, BatchInputLine, BatchRecord, BatchResultKind, BatchStatus,
+    BatchStore, COMPLETION_WINDOW, SUPPORTED_ENDPOINTS,
+};
+use crate::error::{into_axum_response, CanonicalError};
+use crate::protocol::canonical::IngressApi;
+use crate::state::AppState;
+use crate::util::unix_now_secs;
+
+const INGRESS: IngressApi = IngressApi::OpenAiChat;
+/// Persist progress after this many finished requests.
+const PERSIST_EVERY: u64 = 16;
+
+/// Headers from the create call that batch requests are replayed with.
+const FORWARDED_HEADERS: &[http::header::HeaderName] =
+    &[http::header::AUTHORIZATION, http::header::USER_AGENT];
+
+/// Authenticate a batch API call, returning the store or the response to send.
+///
+/// The batch API answers 404 when no batch storage is configured.
+fn authorized_batch_store(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Arc<BatchStore>, Box<Response>> {
+    let Some(store) = state.batch_store() else {
+        return Err(Box::new(StatusCode::NOT_FOUND.into_response()));
+    };
+    state
+        .authenticate(INGRESS, headers)
+        .map_err(|err| Box::new(into_axum_response(&err, INGRESS)))?;
+    Ok(Arc::clone(store))
+}
+
+fn invalid_request(message: impl Into<String>) -> Response {
+    into_axum_response(&CanonicalError::InvalidRequest(message.into()), INGRESS)
+}
+
+fn batch_not_found(id: &str) -> Response {
+    into_axum_response(
+        &CanonicalError::Upstream {
+            status: 404,
+            message: format!("No batch found with id '{id}'"),
+            retry_after: None,
+        },
+        INGRESS,
+    )
+}
+
+/// `POST /v1/batches`
+pub async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    let store = match authorized_batch_store(&state, &headers) {
+        Ok(store) => store,
+        Err(response) => return *response,
+    };
+
+    let is_json = headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value.starts_with("application/json"));
+    let (endpoint, metadata, input) = if is_json {
+        let request: Value = match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => return invalid_request(format!("Invalid JSON body: {e}")),
+        };
+        if let Some(window) = request.get("completion_window").and_then(Value::as_str) {
+            if window != COMPLETION_WINDOW {
+                return invalid_request(format!("completion_window must be '{COMPLETION_WINDOW}'"));
+            }
+        }
+        if request.get("input_file_id").is_some_and(|id| !id.is_null()) {
+            return invalid_request(
+                "input_file_id is not supported; send the JSONL as input_file_content",
+            );
+        }
+        let Some(input) = request.get("input_file_content").and_then(Value::as_str) else {
+            return invalid_request("input_file_content is required");
+        };
+        let endpoint = request
+            .get("endpoint")
+            .and_then(Value::as_str)
+            .unwrap_or("/v1/chat/completions")
+            .to_string();
+        let metadata = request.get("metadata").filter(|m| !m.is_null()).cloned();
+        (endpoint, metadata, bytes::Bytes::from(input.to_string()))
+    } else {
+        let endpoint = first_line_url(&body).unwrap_or_default();
+        (endpoint, None, body)
+    };
+
+    if !SUPPORTED_ENDPOINTS.contains(&endpoint.as_str()) {
+        return invalid_request(format!(
+            "Unsupported batch endpoint '{endpoint}'; expected one of {}",
+            SUPPORTED_ENDPOINTS.join(", ")
+        ));
+    }
+    let lines = match parse_batch_input(&input, &endpoint) {
+        Ok(lines) => lines,
+        Err(message) => return invalid_request(message),
+    };
+
+    let create_store = Arc::clone(&store);
+    let request_count = lines.len();
+    let created = tokio::task::spawn_blocking(move || {
+        create_store.create(&endpoint, metadata, &input, request_count)
+    })
+    .await;
+    let handle = match created {
+        Ok(Ok(handle)) => handle,
+        Ok(Err(err)) => {
+            return into_axum_response(
+                &CanonicalError::Internal(format!("Failed to store batch: {err}")),
+                INGRESS,
+            )
+        }
+        Err(err) => return into_axum_response(&CanonicalError::Internal(err.to_string()), INGRESS),
+    };
+
+    let mut replay_headers = HeaderMap::new();
+    for name in FORWARDED_HEADERS {
+        if let Some(value) = headers.get(name) {
+            replay_headers.insert(name.clone(), value.clone());
+        }
+    }
+    let record = handle.snapshot();
+    tokio::spawn(run_batch(state, store, handle, lines, replay_headers));
+    Json(record).into_response()
+}
+
+fn first_line_url(body: &[u8]) -> Option<String> {
+    let line = body
+        .split(|byte| *byte == b'\n')
+        .find(|line| !line.trim_ascii().is_empty())?;
+    let value: Value = serde_json::from_slice(line).ok()?;
+    value.get("url")?.as_str().map(str::to_string)
+}
+
+/// `GET /v1/batches`
+pub fn list_handler(State(state): State<Arc<AppState>>, headers: &HeaderMap) -> Response {
+    let store = match authorized_batch_store(&state, headers) {
+        Ok(store) => store,
+        Err(response) => return *response,
+    };
+    let data = store.list();
+    Json(json!({
+        "object": "list",
+        "first_id": data.first().map(|batch| batch.id.clone()),
+        "last_id": data.last().map(|batch| batch.id.clone()),
+        "has_more": false,
+        "data": data,
+    }))
+    .into_response()
+}
+
+/// `GET /v1/batches/{id}`
+pub fn retrieve_handler(
+    State(state): State<Arc<AppState>>,
+    id: &str,
+    headers: &HeaderMap,
+) -> Response {
+    let store = match authorized_batch_store(&state, headers) {
+        Ok(store) => store,
+        Err(response) => return *response,
+    };
+    match store.get(id) {
+        Some(handle) => Json(handle.snapshot()).into_response(),
+        None => batch_not_found(id),
+    }
+}
+
+/// `POST /v1/batches/{id}/cancel`
+pub async fn cancel_handler(
+    State(state): State<Arc<AppState>>,
+    id: &str,
+    headers: &HeaderMap,
+) -> Response {
+    let store = match authorized_batch_store(&state, headers) {
+        Ok(store) => store,
+        Err(response) => return *response,
+    };
+    let Some(handle) = store.get(id) else {
+        return batch_not_found(id);
+    };
+    let record = store.cancel(&handle);
+    persist_in_background(&store, record.clone()).await;
+    Json(record).into_response()
+}
+
+/// `GET /v1/batches/{id}/output` and `GET /v1/batches/{id}/errors`
+pub async fn results_handler(
+    State(state): State<Arc<AppState>>,
+    id: &str,
+    kind: BatchResultKind,
+    headers: &HeaderMap,
+) -> Response {
+    let store = match authorized_batch_store(&state, headers) {
+        Ok(store) => store,
+        Err(response) => return *response,
+    };
+    if store.get(id).is_none() {
+        return batch_not_found(id);
+    }
+    let id = id.to_string();
+    match tokio::task::spawn_blocking(move || store.read_results(&id, kind)).await {
+        Ok(Ok(results)) => (
+            StatusCode::OK,
+            [(
+                http::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/jsonl"),
+            )],
+            Body::from(results),
+        )
+            .into_response(),
+        Ok(Err(err)) => into_axum_response(
+            &CanonicalError::Internal(format!("Failed to read batch results: {err}")),
+            INGRESS,
+        ),
+        Err(err) => into_axum_response(&CanonicalError::Internal(err.to_string()), INGRESS),
+    }
+}
+
+async fn persist_in_background(store: &Arc<BatchStore>, record: BatchRecord) {
+    let store = Arc::clone(store);
+    let id = record.id.clone();
+    let outcome = tokio::task::spawn_blocking(move || store.persist(&record)).await;
+    if !matches!(outcome, Ok(Ok(()))) {
+        tracing::warn!(batch = %id, "failed to persist batch state");
+    }
+}
+
+/// Outcome of one batch request, ready to append to a result file.
+struct LineResult {
+    kind: BatchResultKind,
+    line: Vec<u8>,
+}
+
+async fn run_batch(
+    state: Arc<AppState>,
+    store: Arc<BatchStore>,
+    handle: Arc<BatchHandle>,
+    lines: Vec<BatchInputLine>,
+    headers: HeaderMap,
+) {
+    let started = handle.update(|record| {
+        if record.status == BatchStatus::Validating {
+            record.status = BatchStatus::InProgress;
+            record.in_progress_at = Some(unix_now_secs());
+        }
+    });
+    persist_in_background(&store, started.clone()).await;
+    let batch_id = started.id.clone();
+    let endpoint = started.endpoint.clone();
+    let expires_at = started.expires_at;
+    let concurrency = state.config.server.batch_max_concurrency.max(1);
+
+    let mut results = futures_util::stream::iter(lines.into_iter().enumerate())
+        .map(|(index, line)| {
+            let state = Arc::clone(&state);
+            let handle = Arc::clone(&handle);
+            let headers = headers.clone();
+            let endpoint = endpoint.clone();
+            let batch_id = batch_id.clone();
+            async move {
+                if handle.cancel_requested() || unix_now_secs() >= expires_at {
+                    return None;
+                }
+                Some(run_line(state, &batch_id, index, &endpoint, line, headers).await)
+            }
+        })
+        .buffer_unordered(concurrency);
+
+    let mut finished: u64 = 0;
+    while let Some(result) = results.next().await {
+        let Some(result) = result else {
+            continue;
+        };
+        let append_store = Arc::clone(&store);
+        let append_id = batch_id.clone();
+        let kind = result.kind;
+        let appended = tokio::task::spawn_blocking(move || {
+            append_store.append_results(&append_id, kind, &result.line)
+        })
+        .await;
+        if !matches!(appended, Ok(Ok(()))) {
+            tracing::warn!(batch = %batch_id, "failed to append batch result");
+        }
+        let record = handle.update(|record| match kind {
+            BatchResultKind::Output => record.request_counts.completed += 1,
+            BatchResultKind::Errors => record.request_counts.failed += 1,
+        });
+        finished += 1;
+        if finished.is_multiple_of(PERSIST_EVERY) {
+            persist_in_background(&store, record).await;
+        }
+    }
+
+    let now = unix_now_secs();
+    let record = handle.update(|record| {
+        record.finalizing_at = Some(now);
+        if handle.cancel_requested() {
+            record.status = BatchStatus::Cancelled;
+            record.cancelled_at = Some(now);
+        } else if now >= record.expires_at {
+            record.status = BatchStatus::Expired;
+            record.expired_at = Some(now);
+        } else {
+            record.status = BatchStatus::Completed;
+            record.completed_at = Some(now);
+        }
+    });
+    tracing::info!(
+        batch = %record.id,
+        status = ?record.status,
+        completed = record.request_counts.completed,
+        failed = record.request_counts.failed,
+        "batch finished"
+    );
+    persist_in_background(&store, record).await;
+}
+
+async fn run_line(
+    state: Arc<AppState>,
+    batch_id: &str,
+    index: usize,
+    endpoint: &str,
+    line: BatchInputLine,
+    mut headers: HeaderMap,
+) -> LineResult {
+    headers.insert(
+        http::header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    let body = match serde_json::to_vec(&line.body) {
+        Ok(body) => bytes::Bytes::from(body),
+        Err(e) => {
+            return error_line(
+                batch_id,
+                index,
+                &line.custom_id,
+                "invalid_body",
+                &e.to_string(),
+            )
+        }
+    };
+    let response = match endpoint {
+        "/v1/responses" => openai_responses::handler(State(state), headers, body).await,
+        "/v1/completions" => openai_completions::handler(State(state), headers, body).await,
+        _ => openai_chat::handler(State(state), headers, body).await,
+    };
+
+    let status = response.status();
+    let body = match axum::body::to_bytes(response.into_body(), usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            return error_line(
+                batch_id,
+                index,
+                &line.custom_id,
+                "read_failed",
+                &e.to_string(),
+            )
+        }
+    };
+    let body: Value = serde_json::from_slice(&body)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned()));
+    let request_id = body.get("id").cloned().unwrap_or(Value::Null);
+    let kind = if status.is_success() {
+        BatchResultKind::Output
+    } else {
+        BatchResultKind::Errors
+    };
+    encode_line(
+        kind,
+        &json!({
+            "id": batch_request_id(batch_id, index),
+            "custom_id": line.custom_id,
+            "response": {
+                "status_code": status.as_u16(),
+                "request_id": request_id,
+                "body": body,
+            },
+            "error": null,
+        }),
+    )
+}
+
+fn error_line(
+    batch_id: &str,
+    index: usize,
+    custom_id: &str,
+    code: &str,
+    message: &str,
+) -> LineResult {
+    encode_line(
+        BatchResultKind::Errors,
+        &json!({
+            "id": batch_request_id(batch_id, index),
+            "custom_id": custom_id,
+            "response": null,
+            "error": { "code": code, "message": message },
+        }),
+    )
+}
+
+fn encode_line(kind: BatchResultKind, value: &Value) -> LineResult {
+    let mut line = serde_json::to_vec(value).unwrap_or_default();
+    line.push(b'\n');
+    LineResult { kind, line }
+}
+
+fn batch_request_id(batch_id: &str, index: usize) -> String {
+    let suffix = batch_id.strip_prefix("batch_").unwrap_or(batch_id);
+    format!("batch_req_{suffix}_{index}")
+}
@@ -0,0 +1,55 @@
+use axum::response::Response;
+
+use crate::protocol::canonical::{CanonicalPart, IngressApi};
+use crate::routing::cascade::{CascadeModel, CascadeTier};
+
+use super::codec::decode_response_from_provider;
+use super::stream_aggregate::ingress_wire_provider;
+
+/// Response header naming the cascade tier that served the answer.
+const CASCADE_TIER_HEADER: &str = "x-toolify-cascade-tier";
+
+/// Buffer a complete client-format draft response and run the cascade checks.
+///
+/// # Errors
+///
+/// Returns the rejection reason when the draft failed, could not be decoded,
+/// or did not pass the checks.
+pub(crate) async fn check_draft_response(
+    response: Response,
+    ingress: IngressApi,
+    cascade: &CascadeModel,
+) -> Result<Response, &'static str> {
+    if !response.status().is_success() {
+        return Err("draft_error");
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|_| "draft_error")?;
+    let canonical = decode_response_from_provider(ingress_wire_provider(ingress), &body)
+        .map_err(|_| "draft_undecodable")?;
+    let mut text = String::new();
+    let mut has_tool_calls = false;
+    for part in &canonical.content {
+        match part {
+            CanonicalPart::Text(delta) => text.push_str(delta),
+            CanonicalPart::ToolCall { .. } => has_tool_calls = true,
+            _ => {}
+        }
+    }
+    if let Some(reason) = cascade.draft_rejection(&text, has_tool_calls) {
+        return Err(reason);
+    }
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+    Ok(Response::from_parts(parts, axum::body::Body::from(body)))
+}
+
+/// Record which cascade tier served `response`.
+pub(crate) fn mark_cascade_tier(mut response: Response, tier: CascadeTier) -> Response {
+    response.headers_mut().insert(
+        CASCADE_TIER_HEADER,
+        http::HeaderValue::from_static(tier.as_str()),
+    );
+    response
+}
@@ -1,25 +1,46 @@
-use std::sync::LazyLock;
-
+use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
-use crate::protocol::canonical::ProviderKind;
-
-static TOOL_CALLS_FINDER: LazyLock<memchr::memmem::Finder<'static>> =
-    LazyLock::new(|| memchr::memmem::Finder::new(br#""tool_calls""#));
-static CONTENT_FINDER: LazyLock<memchr::memmem::Finder<'static>> =
-    LazyLock::new(|| memchr::memmem::Finder::new(br#""content":""#));
-static REFUSAL_FINDER: LazyLock<memchr::memmem::Finder<'static>> =
-    LazyLock::new(|| memchr::memmem::Finder::new(br#""refusal":""#));
+use crate::protocol::canonical::{CanonicalRequest, ProviderKind};
 
-#[inline]
-fn looks_like_openai_text_only_response(body: &[u8]) -> bool {
-    TOOL_CALLS_FINDER.find(body).is_none()
-        && (CONTENT_FINDER.find(body).is_some() || REFUSAL_FINDER.find(body).is_some())
-}
+use super::dropped_params::{note_unsupported_generation_params, unsupported_params};
+use super::output_tokens::anthropic_output_tokens;
+use super::sampling::normalize_sampling_params;
 
+/// Encode `canonical` for `provider`, first adapting its sampling
+/// parameters, output token limit, and optional parameters to what the
+/// provider accepts.
 pub(crate) fn encode_for_provider(
     provider: ProviderKind,
-    canonical: &crate::protocol::canonical::CanonicalRequest,
+    canonical: &CanonicalRequest,
+    features: &FeaturesConfig,
 ) -> Result<bytes::Bytes, CanonicalError> {
+    let mut generation = normalize_sampling_params(
+        &canonical.generation,
+        canonical.ingress_api,
+        provider,
+        features.sampling_normalization,
+    )?;
+    if provider == ProviderKind::Anthropic {
+        if let Some(max_tokens) = anthropic_output_tokens(canonical, &features.model_output_tokens)
+        {
+            generation
+                .get_or_insert_with(|| canonical.generation.clone())
+                .max_tokens = Some(max_tokens);
+        }
+    }
+    let dropped = unsupported_params(canonical, provider, features.unsupported_params)?;
+    note_unsupported_generation_params(canonical, provider);
+    let adjusted = (generation.is_some() || !dropped.is_empty()).then(|| {
+        let mut adjusted = CanonicalRequest {
+            generation: generation.unwrap_or_else(|| canonical.generation.clone()),
+            ..canonical.clone()
+        };
+        for param in &dropped {
+            adjusted.provider_extensions_mut().remove(*param);
+        }
+        adjusted
+    });
+    let canonical = adjusted.as_ref().unwrap_or(canonical);
     match provider {
         ProviderKind::OpenAi | ProviderKind::GeminiOpenAi => {
             let wire =
@@ -57,12 +78,12 @@ pub(crate) fn decode_response_from_provider(
 ) -> Result<crate::protocol::canonical::CanonicalResponse, CanonicalError> {
     match provider {
         ProviderKind::OpenAi | ProviderKind::GeminiOpenAi => {
-            if looks_like_openai_text_only_response(body) {
-                if let Some(decoded) =
-                    crate::protocol::openai_chat::response_decoder::try_decode_openai_chat_text_response_bytes(body)
-                {
-                    return Ok(decoded);
-                }
+            if let Some(decoded) =
+                crate::protocol::openai_chat::response_decoder::try_decode_openai_chat_response_bytes(
+                    body,
+                )
+            {
+                return Ok(decoded);
             }
 
             let wire: crate::protocol::openai_chat::OpenAiChatResponse =
@@ -72,6 +93,13 @@ pub(crate) fn decode_response_from_provider(
             crate::protocol::openai_chat::response_decoder::decode_openai_chat_response_owned(wire)
         }
         ProviderKind::Anthropic => {
+            if let Some(decoded) =
+                crate::protocol::anthropic::response_decoder::try_decode_anthropic_response_bytes(
+                    body,
+                )
+            {
+                return Ok(decoded);
+            }
             let wire: crate::protocol::anthropic::AnthropicResponse = serde_json::from_slice(body)
                 .map_err(|e| {
                     CanonicalError::Translation(format!("Failed to parse Anthropic response: {e}"))
@@ -79,6 +107,11 @@ pub(crate) fn decode_response_from_provider(
             crate::protocol::anthropic::response_decoder::decode_anthropic_response_owned(wire)
         }
         ProviderKind::Gemini => {
+            if let Some(decoded) =
+                crate::protocol::gemini::response_decoder::try_decode_gemini_response_bytes(body)
+            {
+                return Ok(decoded);
+            }
             let wire: crate::protocol::gemini::GeminiResponse = serde_json::from_slice(body)
                 .map_err(|e| {
                     CanonicalError::Translation(format!("Failed to parse Gemini response: {e}"))
@@ -86,6 +119,13 @@ pub(crate) fn decode_response_from_provider(
             crate::protocol::gemini::response_decoder::decode_gemini_response_owned(wire)
         }
         ProviderKind::OpenAiResponses => {
+            if let Some(decoded) =
+                crate::protocol::openai_responses::response_decoder::try_decode_responses_output_bytes(
+                    body,
+                )
+            {
+                return Ok(decoded);
+            }
             let wire: crate::protocol::openai_responses::ResponsesOutput =
                 serde_json::from_slice(body).map_err(|e| {
                     CanonicalError::Translation(format!(
@@ -0,0 +1,145 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::response::Response;
+use futures_util::Stream;
+use serde_json::Value;
+
+use crate::observability::traces::{TraceTurn, TRACE_SESSION_HEADER};
+use crate::protocol::canonical::IngressApi;
+
+use super::stream_aggregate::aggregate_stream_response;
+
+/// Largest response body kept for a trace; larger turns record no response.
+const MAX_TRACE_RESPONSE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Record `turn` with the response body once it has been fully delivered.
+///
+/// SSE bodies are aggregated into the non-streaming `ingress` format first.
+/// The response names the conversation in the `x-toolify-trace-session`
+/// header; a body dropped before completion is recorded with status 499.
+pub(crate) fn tap_trace_response(
+    response: Response,
+    turn: TraceTurn,
+    ingress: IngressApi,
+    client_model: &str,
+) -> Response {
+    let status = response.status().as_u16();
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let (mut parts, body) = response.into_parts();
+    if let Ok(session) = http::HeaderValue::from_str(&turn.session_id()) {
+        parts.headers.insert(TRACE_SESSION_HEADER, session);
+    }
+    let tap = TraceTapStream {
+        inner: body.into_data_stream(),
+        capture: Some(TraceCapture {
+            turn,
+            status,
+            is_sse,
+            ingress,
+            client_model: client_model.to_string(),
+            body: Vec::new(),
+            overflowed: false,
+        }),
+    };
+    Response::from_parts(parts, axum::body::Body::from_stream(tap))
+}
+
+struct TraceCapture {
+    turn: TraceTurn,
+    status: u16,
+    is_sse: bool,
+    ingress: IngressApi,
+    client_model: String,
+    body: Vec<u8>,
+    overflowed: bool,
+}
+
+impl TraceCapture {
+    fn feed(&mut self, chunk: &[u8]) {
+        if self.overflowed {
+            return;
+        }
+        if self.body.len() + chunk.len() > MAX_TRACE_RESPONSE_BYTES {
+            self.overflowed = true;
+            self.body = Vec::new();
+        } else {
+            self.body.extend_from_slice(chunk);
+        }
+    }
+
+    fn finish(self) {
+        if self.overflowed {
+            self.turn.finish(self.status, None);
+            return;
+        }
+        if !self.is_sse || !(200..300).contains(&self.status) {
+            let response = serde_json::from_slice(&self.body).ok();
+            self.turn.finish(self.status, response);
+            return;
+        }
+        tokio::spawn(async move {
+            let stream = Response::builder()
+                .header(http::header::CONTENT_TYPE, "text/event-stream")
+                .body(axum::body::Body::from(self.body))
+                .unwrap_or_default();
+            let response =
+                match aggregate_stream_response(stream, self.ingress, &self.client_model).await {
+                    Ok(aggregated) => axum::body::to_bytes(aggregated.into_body(), usize::MAX)
+                        .await
+                        .ok()
+                        .and_then(|body| serde_json::from_slice::<Value>(&body).ok()),
+                    Err(err) => Some(serde_json::json!({ "error": err.to_string() })),
+                };
+            self.turn.finish(self.status, response);
+        });
+    }
+}
+
+pin_project_lite::pin_project! {
+    struct TraceTapStream<S> {
+        #[pin]
+        inner: S,
+        capture: Option<TraceCapture>,
+    }
+
+    impl<S> PinnedDrop for TraceTapStream<S> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if let Some(capture) = this.capture.take() {
+                // Client went away before the body completed.
+                capture.turn.finish(499, None);
+            }
+        }
+    }
+}
+
+impl<S, E> Stream for TraceTapStream<S>
+where
+    S: Stream<Item = Result<bytes::Bytes, E>>,
+{
+    type Item = Result<bytes::Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let polled = this.inner.poll_next(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(capture) = this.capture.as_mut() {
+                    capture.feed(chunk);
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(capture) = this.capture.take() {
+                    capture.finish();
+                }
+            }
+            Poll::Ready(Some(Err(_))) | Poll::Pending => {}
+        }
+        polled
+    }
+}
@@ -0,0 +1,287 @@
+use axum::response::Response;
+use smallvec::SmallVec;
+
+use crate::config::UnsupportedParamPolicy;
+use crate::error::CanonicalError;
+use crate::protocol::canonical::{CanonicalRequest, ProviderKind};
+use crate::protocol::mapping::{provider_supports_param, DEGRADABLE_PARAMS};
+use crate::state::request_context;
+use crate::stream::stop_sequences::provider_supports_stop_sequences;
+
+/// Response header listing client parameters stripped for the upstream.
+const DROPPED_PARAMS_HEADER: &str = "x-toolify-dropped-params";
+/// Response header listing every client parameter the upstream could not
+/// take, each as `name=dropped` or `name=emulated`.
+const UNSUPPORTED_PARAMS_HEADER: &str = "x-toolify-unsupported-params";
+
+/// How a client parameter the upstream cannot take was handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ParamHandling {
+    Dropped,
+    Emulated,
+}
+
+impl ParamHandling {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Dropped => "dropped",
+            Self::Emulated => "emulated",
+        }
+    }
+}
+
+/// Client parameters adjusted for the upstream across the routes of one
+/// attempt.
+#[derive(Debug, Default)]
+pub(crate) struct DroppedParams {
+    /// Optional parameters stripped under [`UnsupportedParamPolicy::Drop`].
+    pub(crate) stripped: Vec<&'static str>,
+    /// Every parameter the upstream could not take, in the order first seen.
+    pub(crate) unsupported: Vec<(&'static str, ParamHandling)>,
+}
+
+impl DroppedParams {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.stripped.is_empty() && self.unsupported.is_empty()
+    }
+
+    fn note(&mut self, param: &'static str, handling: ParamHandling) {
+        match self.unsupported.iter_mut().find(|(name, _)| *name == param) {
+            // Emulation on a later step wins over the encoder dropping it.
+            Some((_, seen)) if handling == ParamHandling::Emulated => *seen = handling,
+            Some(_) => {}
+            None => self.unsupported.push((param, handling)),
+        }
+    }
+}
+
+/// Record that the proxy emulates `param` for the current upstream.
+pub(crate) fn note_emulated_param(param: &'static str) {
+    request_context::note_attempt(|attempt| {
+        attempt.dropped_params.note(param, ParamHandling::Emulated);
+    });
+}
+
+/// Record the generation parameters of `canonical` that the encoder for
+/// `provider` has no field for and leaves out.
+pub(crate) fn note_unsupported_generation_params(
+    canonical: &CanonicalRequest,
+    provider: ProviderKind,
+) {
+    let generation = &canonical.generation;
+    let openai_chat = matches!(provider, ProviderKind::OpenAi | ProviderKind::GeminiOpenAi);
+    let unsupported = [
+        (
+            "frequency_penalty",
+            !openai_chat && generation.frequency_penalty.is_some(),
+        ),
+        (
+            "presence_penalty",
+            !openai_chat && generation.presence_penalty.is_some(),
+        ),
+        (
+            "n",
+            !openai_chat && provider != ProviderKind::Gemini && generation.n.is_some_and(|n| n > 1),
+        ),
+        (
+            "stop",
+            !provider_supports_stop_sequences(provider)
+                && generation
+                    .stop
+                    .as_ref()
+                    .is_some_and(|stop| !stop.is_empty()),
+        ),
+    ];
+    if !unsupported.iter().any(|(_, unsupported)| *unsupported) {
+        return;
+    }
+    request_context::note_attempt(|attempt| {
+        let dropped = &mut attempt.dropped_params;
+        for (param, _) in unsupported.iter().filter(|(_, unsupported)| *unsupported) {
+            dropped.note(param, ParamHandling::Dropped);
+        }
+    });
+}
+
+/// Parameters of `canonical` that must be stripped before encoding it for
+/// `provider`.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::InvalidRequest`] when `policy` is
+/// [`UnsupportedParamPolicy::Reject`] and the request carries a parameter
+/// `provider` does not support.
+pub(crate) fn unsupported_params(
+    canonical: &CanonicalRequest,
+    provider: ProviderKind,
+    policy: UnsupportedParamPolicy,
+) -> Result<SmallVec<[&'static str; 4]>, CanonicalError> {
+    if policy == UnsupportedParamPolicy::Passthrough {
+        return Ok(SmallVec::new());
+    }
+    let extensions = canonical.provider_extensions_ref();
+    let unsupported: SmallVec<[&'static str; 4]> = DEGRADABLE_PARAMS
+        .into_iter()
+        .filter(|param| extensions.contains_key(*param))
+        .filter(|param| !provider_supports_param(provider, param))
+        .collect();
+    if unsupported.is_empty() {
+        return Ok(unsupported);
+    }
+    if policy == UnsupportedParamPolicy::Reject {
+        return Err(CanonicalError::InvalidRequest(format!(
+            "{} not supported by the {provider:?} upstream",
+            unsupported.join(", ")
+        )));
+    }
+    request_context::note_attempt(|attempt| {
+        let dropped = &mut attempt.dropped_params;
+        for param in &unsupported {
+            if !dropped.stripped.contains(param) {
+                dropped.stripped.push(param);
+            }
+            dropped.note(param, ParamHandling::Dropped);
+        }
+    });
+    Ok(unsupported)
+}
+
+/// List `dropped` parameters on `response`.
+pub(crate) fn mark_dropped_params(mut response: Response, dropped: &DroppedParams) -> Response {
+    if !dropped.stripped.is_empty() {
+        if let Ok(value) = http::HeaderValue::from_str(&dropped.stripped.join(", ")) {
+            response.headers_mut().insert(DROPPED_PARAMS_HEADER, value);
+        }
+    }
+    if !dropped.unsupported.is_empty() {
+        let listed = dropped
+            .unsupported
+            .iter()
+            .map(|(param, handling)| format!("{param}={}", handling.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Ok(value) = http::HeaderValue::from_str(&listed) {
+            response
+                .headers_mut()
+                .insert(UNSUPPORTED_PARAMS_HEADER, value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::protocol::canonical::{CanonicalToolChoice, GenerationParams, IngressApi};
+    use crate::state::request_context::RequestContext;
+
+    /// Run `future` as one attempt, returning what it dropped.
+    async fn track_dropped_params<F: std::future::Future>(future: F) -> (F::Output, DroppedParams) {
+        let (output, _) = request_context::scope(RequestContext::default(), async {
+            let output = future.await;
+            (output, request_context::finish_attempt().dropped_params)
+        })
+        .await;
+        output
+    }
+
+    fn request_with(params: &[&str]) -> CanonicalRequest {
+        let mut canonical = CanonicalRequest {
+            request_id: uuid::Uuid::nil(),
+            ingress_api: IngressApi::OpenAiChat,
+            model: "m".to_string(),
+            stream: false,
+            system_prompt: None,
+            messages: Vec::new(),
+            tools: Arc::from([]),
+            tool_choice: CanonicalToolChoice::Auto,
+            generation: GenerationParams::default(),
+            provider_extensions: None,
+        };
+        for param in params {
+            canonical
+                .provider_extensions_mut()
+                .insert((*param).to_string(), serde_json::json!(1));
+        }
+        canonical
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_params_follow_policy_and_provider() {
+        let canonical = request_with(&["seed", "top_logprobs"]);
+        let (result, dropped) = track_dropped_params(async {
+            let openai = unsupported_params(
+                &canonical,
+                ProviderKind::OpenAi,
+                UnsupportedParamPolicy::Drop,
+            )
+            .unwrap();
+            assert!(openai.is_empty());
+            let responses = unsupported_params(
+                &canonical,
+                ProviderKind::OpenAiResponses,
+                UnsupportedParamPolicy::Drop,
+            )
+            .unwrap();
+            assert_eq!(responses.as_slice(), ["seed"]);
+            unsupported_params(
+                &canonical,
+                ProviderKind::Anthropic,
+                UnsupportedParamPolicy::Drop,
+            )
+        })
+        .await;
+        assert_eq!(result.unwrap().as_slice(), ["seed", "top_logprobs"]);
+        assert_eq!(dropped.stripped, ["seed", "top_logprobs"]);
+
+        assert!(unsupported_params(
+            &canonical,
+            ProviderKind::Anthropic,
+            UnsupportedParamPolicy::Passthrough
+        )
+        .unwrap()
+        .is_empty());
+        assert!(matches!(
+            unsupported_params(
+                &canonical,
+                ProviderKind::Gemini,
+                UnsupportedParamPolicy::Reject
+            ),
+            Err(CanonicalError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_params_header_lists_dropped_and_emulated() {
+        let mut canonical = request_with(&["logit_bias"]);
+        canonical.generation.frequency_penalty = Some(0.5);
+        canonical.generation.n = Some(1);
+        canonical.generation.stop = Some(vec!["END".to_string()]);
+        let ((), dropped) = track_dropped_params(async {
+            unsupported_params(
+                &canonical,
+                ProviderKind::OpenAiResponses,
+                UnsupportedParamPolicy::Drop,
+            )
+            .unwrap();
+            note_unsupported_generation_params(&canonical, ProviderKind::OpenAiResponses);
+            note_emulated_param("stop");
+        })
+        .await;
+
+        let response = mark_dropped_params(Response::new(axum::body::Body::empty()), &dropped);
+        assert_eq!(response.headers()[DROPPED_PARAMS_HEADER], "logit_bias");
+        assert_eq!(
+            response.headers()[UNSUPPORTED_PARAMS_HEADER],
+            "logit_bias=dropped, frequency_penalty=dropped, stop=emulated"
+        );
+
+        let ((), dropped) = track_dropped_params(async {
+            note_unsupported_generation_params(&canonical, ProviderKind::OpenAi);
+        })
+        .await;
+        assert!(dropped.is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 use axum::http::HeaderMap;
 
+use crate::api::common::passthrough::upstream_error;
 use crate::error::CanonicalError;
 use crate::protocol::canonical::ProviderKind;
 use crate::state::AppState;
@@ -26,9 +27,9 @@ pub(crate) struct PreparedUpstreamIoRequest<'a> {
     url: std::borrow::Cow<'a, str>,
     parsed_url: Option<&'a url::Url>,
     parsed_hyper_uri: Option<&'a http::Uri>,
-    proxy_url: Option<&'a str>,
+    proxy_url: Option<std::sync::Arc<str>>,
     preconfigured_proxy_client: Option<&'a reqwest::Client>,
-    upstream_headers: &'a HeaderMap,
+    upstream_headers: std::borrow::Cow<'a, HeaderMap>,
     provider: ProviderKind,
 }
 
@@ -40,9 +41,9 @@ impl PreparedUpstreamIoRequest<'_> {
             url: self.url.as_ref(),
             parsed_url: self.parsed_url,
             parsed_hyper_uri: self.parsed_hyper_uri,
-            proxy_url: self.proxy_url,
+            proxy_url: self.proxy_url.as_deref(),
             preconfigured_proxy_client: self.preconfigured_proxy_client,
-            upstream_headers: self.upstream_headers,
+            upstream_headers: &self.upstream_headers,
             provider: self.provider,
             client_model,
         }
@@ -62,13 +63,21 @@ pub(crate) fn prepare_upstream_io_request<'a>(
         url: build_upstream_url_prepared(prepared_upstream, actual_model, stream),
         parsed_url: static_parsed_upstream_url(prepared_upstream, actual_model, stream),
         parsed_hyper_uri: static_parsed_upstream_uri(prepared_upstream, actual_model, stream),
+        preconfigured_proxy_client: state
+            .transport
+            .preconfigured_proxy_client(proxy_url.as_deref()),
         proxy_url,
-        preconfigured_proxy_client: state.transport.preconfigured_proxy_client(proxy_url),
         upstream_headers: build_provider_headers_prepared(prepared_upstream),
         provider: prepared_upstream.provider_kind(),
     }
 }
 
+/// Send a non-streaming upstream request and collect the success body.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::Upstream`] for non-success upstream statuses and
+/// transport errors when the request or body read fails.
 #[inline]
 pub(crate) async fn send_non_streaming_bytes(
     state: &AppState,
@@ -79,7 +88,7 @@ pub(crate) async fn send_non_streaming_bytes(
     preconfigured_proxy_client: Option<&reqwest::Client>,
     upstream_headers: &HeaderMap,
     upstream_body: bytes::Bytes,
-) -> Result<(http::StatusCode, bytes::Bytes), CanonicalError> {
+) -> Result<bytes::Bytes, CanonicalError> {
     if state.transport.hyper_passthrough_enabled_for(proxy_url) {
         use http_body_util::BodyExt as _;
 
@@ -100,13 +109,16 @@ pub(crate) async fn send_non_streaming_bytes(
                 .await?
         };
         let status = response.status();
-        let (_, body) = response.into_parts();
+        let (parts, body) = response.into_parts();
         let body_bytes = body
             .collect()
             .await
             .map(http_body_util::Collected::to_bytes)
             .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
-        return Ok((status, body_bytes));
+        if !status.is_success() {
+            return Err(upstream_error(status, &parts.headers, &body_bytes));
+        }
+        return Ok(body_bytes);
     }
 
     let response = if let Some(parsed_url) = parsed_url {
@@ -135,9 +147,13 @@ pub(crate) async fn send_non_streaming_bytes(
             .await?
     };
     let status = response.status();
+    let headers = (!status.is_success()).then(|| response.headers().clone());
     let body_bytes = response
         .bytes()
         .await
         .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
-    Ok((status, body_bytes))
+    if let Some(headers) = headers {
+        return Err(upstream_error(status, &headers, &body_bytes));
+    }
+    Ok(body_bytes)
 }
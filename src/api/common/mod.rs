@@ -1,31 +1,74 @@
 //! Shared API helpers reused across ingress handlers.
 
+mod cascade;
 mod codec;
+mod conversation_trace;
+mod dropped_params;
 mod io;
+mod moderation;
 mod non_streaming;
+mod output_postprocess;
+mod output_tokens;
 mod passthrough;
 mod probe;
+mod protocol_switch;
+mod quality_retry;
+mod request_capabilities;
+mod response_ids;
+mod response_model;
+mod responses_reasoning;
+mod route_latency;
+mod sampling;
+mod stream_aggregate;
+mod stream_stats;
+mod stream_synthesis;
+mod stream_usage;
 mod streaming;
+mod tool_schema;
+mod virtual_model;
 
 pub(crate) use crate::json_scan::{
     find_top_level_field_value_range, parse_json_string_end, parse_json_value_end, skip_ws,
 };
+pub(crate) use cascade::{check_draft_response, mark_cascade_tier};
 pub(crate) use codec::{decode_response_from_provider, encode_for_provider};
+pub(crate) use conversation_trace::tap_trace_response;
+pub(crate) use dropped_params::{mark_dropped_params, DroppedParams};
 pub(crate) use io::{
     prepare_upstream_io_request, send_non_streaming_bytes, PreparedUpstreamIoRequest,
     UpstreamIoRequest,
 };
+pub(crate) use moderation::{annotate_moderation, moderate_request};
 pub(crate) use non_streaming::{
     handle_non_streaming_common, handle_non_streaming_preencoded_common,
 };
+pub(crate) use output_postprocess::{
+    client_assistant_prefill, client_stop_sequences, postprocess_output_response,
+};
 pub(crate) use passthrough::{
     is_protocol_passthrough, passthrough_non_streaming_bytes, passthrough_non_streaming_uri_bytes,
     passthrough_non_streaming_url_bytes, passthrough_streaming_bytes,
-    passthrough_streaming_uri_bytes, passthrough_streaming_url_bytes, sanitize_upstream_error,
+    passthrough_streaming_uri_bytes, passthrough_streaming_url_bytes,
 };
 pub(crate) use probe::{
     find_common_probe_field_ranges, parse_common_request_probe, parse_optional_bool_token,
     raw_tools_field_has_items, raw_tools_token_has_items,
     rewrite_model_field_in_json_body_with_range, CommonProbeRanges, CommonRequestProbe,
 };
+pub(crate) use quality_retry::check_response_quality;
+pub(crate) use request_capabilities::required_capabilities;
+pub(crate) use response_ids::mark_upstream_response_id;
+pub(crate) use response_model::rewrite_response_model;
+pub(crate) use responses_reasoning::strip_responses_reasoning;
+pub(crate) use route_latency::{tap_route_latency, RouteLatencyProbe};
+pub(crate) use stream_aggregate::{aggregate_stream_response, streaming_request_body};
+pub(crate) use stream_stats::{append_stream_stats, stream_stats_requested, StreamStats};
+pub(crate) use stream_synthesis::{
+    non_streaming_request_body, synthesize_stream_response, SyntheticStreamPacing,
+};
+pub(crate) use stream_usage::{stream_usage_requested, strip_unrequested_stream_usage};
 pub(crate) use streaming::handle_streaming_request;
+pub(crate) use tool_schema::{
+    enforce_tool_schemas, strict_tool_specs, tool_schema_retry_request, ToolSchemaViolation,
+};
+pub(crate) use virtual_model::{apply_virtual_model_request, postprocess_virtual_model_response};
@@ -0,0 +1,235 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::response::Response;
+use serde_json::Value;
+
+use crate::config::{ModerationAction, ModerationConfig};
+use crate::error::CanonicalError;
+use crate::protocol::canonical::IngressApi;
+use crate::state::{AppState, ModerationVerdict};
+
+/// Response header listing the flagged categories in `annotate` mode.
+const MODERATION_HEADER: &str = "x-toolify-moderation";
+
+/// Moderate the latest user message of `body` before it is routed.
+///
+/// Returns the header value to annotate the response with when the input is
+/// flagged in `annotate` mode.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::InvalidRequest`] when the input is flagged in
+/// `block` mode, or the moderation failure when `fail_open` is off.
+pub(crate) async fn moderate_request(
+    state: &AppState,
+    moderation: &ModerationConfig,
+    ingress: IngressApi,
+    body: &bytes::Bytes,
+) -> Result<Option<http::HeaderValue>, CanonicalError> {
+    let Some(text) = latest_user_text(ingress, body) else {
+        return Ok(None);
+    };
+    let verdict = if let Some(verdict) = state.cached_moderation(&text) {
+        verdict
+    } else {
+        match request_verdict(state, moderation, &text).await {
+            Ok(verdict) => {
+                let verdict = Arc::new(verdict);
+                state.cache_moderation(&text, Arc::clone(&verdict));
+                verdict
+            }
+            Err(err) if moderation.fail_open => {
+                tracing::warn!(error = %err, "moderation check failed; allowing request");
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        }
+    };
+    if !verdict.flagged {
+        return Ok(None);
+    }
+    let categories = verdict.categories.join(",");
+    match moderation.action {
+        ModerationAction::Block => Err(CanonicalError::InvalidRequest(format!(
+            "Input flagged by moderation: {categories}"
+        ))),
+        ModerationAction::Annotate => {
+            Ok(http::HeaderValue::from_str(&format!("flagged; categories={categories}")).ok())
+        }
+    }
+}
+
+/// Add the moderation annotation, if any, to `response`.
+pub(crate) fn annotate_moderation(
+    mut response: Response,
+    annotation: Option<http::HeaderValue>,
+) -> Response {
+    if let Some(value) = annotation {
+        response.headers_mut().insert(MODERATION_HEADER, value);
+    }
+    response
+}
+
+async fn request_verdict(
+    state: &AppState,
+    moderation: &ModerationConfig,
+    text: &str,
+) -> Result<ModerationVerdict, CanonicalError> {
+    let url = url::Url::parse(&moderation.endpoint)
+        .map_err(|e| CanonicalError::Config(format!("Invalid moderation endpoint: {e}")))?;
+    let mut payload = serde_json::json!({ "input": text });
+    if let Some(model) = &moderation.model {
+        payload["model"] = Value::String(model.clone());
+    }
+    let mut headers = http::HeaderMap::new();
+    headers.insert(
+        http::header::CONTENT_TYPE,
+        http::HeaderValue::from_static("application/json"),
+    );
+    if !moderation.api_key.is_empty() {
+        if let Ok(value) = http::HeaderValue::from_str(&format!("Bearer {}", moderation.api_key)) {
+            headers.insert(http::header::AUTHORIZATION, value);
+        }
+    }
+    let body = bytes::Bytes::from(payload.to_string());
+
+    let exchange = async {
+        let response = state
+            .transport
+            .send_request_url(&url, http::Method::POST, &headers, body, None)
+            .await?;
+        let status = response.status();
+        let bytes = response.bytes().await.map_err(|e| {
+            CanonicalError::Transport(format!("Failed to read moderation response: {e}"))
+        })?;
+        if !status.is_success() {
+            return Err(CanonicalError::Upstream {
+                status: status.as_u16(),
+                message: format!(
+                    "Moderation endpoint failed: {}",
+                    String::from_utf8_lossy(&bytes)
+                ),
+                retry_after: None,
+            });
+        }
+        Ok(bytes)
+    };
+    let bytes = tokio::time::timeout(Duration::from_millis(moderation.timeout_ms), exchange)
+        .await
+        .map_err(|_| CanonicalError::Timeout("Moderation endpoint timed out".to_string()))??;
+    parse_verdict(&bytes, &moderation.categories)
+}
+
+/// Read `results[0]` of an `OpenAI`-style moderation response.
+///
+/// With `categories` configured only those count; otherwise the endpoint's
+/// `flagged` decides.
+fn parse_verdict(body: &[u8], categories: &[String]) -> Result<ModerationVerdict, CanonicalError> {
+    let payload: Value = serde_json::from_slice(body).map_err(|e| {
+        CanonicalError::Translation(format!("Invalid moderation response JSON: {e}"))
+    })?;
+    let result = payload.pointer("/results/0").ok_or_else(|| {
+        CanonicalError::Translation("Moderation response has no results".to_string())
+    })?;
+    let hit: Vec<String> = result
+        .get("categories")
+        .and_then(Value::as_object)
+        .map(|flags| {
+            flags
+                .iter()
+                .filter(|(name, flagged)| {
+                    flagged.as_bool() == Some(true)
+                        && (categories.is_empty() || categories.contains(name))
+                })
+                .map(|(name, _)| name.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    let flagged = if categories.is_empty() {
+        result
+            .get("flagged")
+            .and_then(Value::as_bool)
+            .unwrap_or(!hit.is_empty())
+    } else {
+        !hit.is_empty()
+    };
+    Ok(ModerationVerdict {
+        flagged,
+        categories: hit,
+    })
+}
+
+/// Text of the latest user message, or `None` when there is none.
+fn latest_user_text(ingress: IngressApi, body: &[u8]) -> Option<String> {
+    let payload: Value = serde_json::from_slice(body).ok()?;
+    let (messages, content_field) = match ingress {
+        IngressApi::OpenAiChat | IngressApi::Anthropic => (payload.get("messages")?, "content"),
+        IngressApi::OpenAiResponses => match payload.get("input")? {
+            Value::String(text) => return non_empty(text.clone()),
+            input => (input, "content"),
+        },
+        IngressApi::Gemini => (payload.get("contents")?, "parts"),
+    };
+    let message = messages.as_array()?.iter().rev().find(|message| {
+        match message.get("role").and_then(Value::as_str) {
+            Some(role) => role == "user",
+            // Gemini contents and Responses input items may omit the role.
+            None => matches!(ingress, IngressApi::Gemini | IngressApi::OpenAiResponses),
+        }
+    })?;
+    let text = match message.get(content_field)? {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+    non_empty(text)
+}
+
+fn non_empty(text: String) -> Option<String> {
+    (!text.trim().is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_user_text_per_ingress() {
+        let chat = br#"{"messages":[{"role":"user","content":"first"},{"role":"assistant","content":"ok"},{"role":"user","content":[{"type":"text","text":"second"}]}]}"#;
+        assert_eq!(
+            latest_user_text(IngressApi::OpenAiChat, chat).as_deref(),
+            Some("second")
+        );
+        let responses = br#"{"input":"hello"}"#;
+        assert_eq!(
+            latest_user_text(IngressApi::OpenAiResponses, responses).as_deref(),
+            Some("hello")
+        );
+        let gemini = br#"{"contents":[{"parts":[{"text":"a"},{"text":"b"}]}]}"#;
+        assert_eq!(
+            latest_user_text(IngressApi::Gemini, gemini).as_deref(),
+            Some("a\nb")
+        );
+        let anthropic = br#"{"messages":[{"role":"assistant","content":"hi"}]}"#;
+        assert!(latest_user_text(IngressApi::Anthropic, anthropic).is_none());
+    }
+
+    #[test]
+    fn test_parse_verdict_honors_configured_categories() {
+        let body = br#"{"results":[{"flagged":true,"categories":{"hate":false,"violence":true}}]}"#;
+        let verdict = parse_verdict(body, &[]).unwrap();
+        assert!(verdict.flagged);
+        assert_eq!(verdict.categories, vec!["violence"]);
+
+        let verdict = parse_verdict(body, &["hate".to_string()]).unwrap();
+        assert!(!verdict.flagged);
+        assert!(verdict.categories.is_empty());
+
+        assert!(parse_verdict(br#"{"results":[]}"#, &[]).is_err());
+    }
+}
@@ -7,10 +7,15 @@ use crate::protocol::canonical::{
     IngressApi,
 };
 
+use crate::routing::session::SessionClass;
+use crate::stream::prefill::{echoed_prefill, strip_prefill_echo};
+
+use super::response_ids::note_body_response_ids;
+use super::responses_reasoning::strip_reasoning_parts;
 use super::{
     decode_response_from_provider, encode_for_provider, is_protocol_passthrough,
-    rewrite_model_field_in_json_body_with_range, sanitize_upstream_error, send_non_streaming_bytes,
-    UpstreamIoRequest,
+    prepare_upstream_io_request, rewrite_model_field_in_json_body_with_range,
+    send_non_streaming_bytes, PreparedUpstreamIoRequest, UpstreamIoRequest,
 };
 
 #[inline]
@@ -60,7 +65,8 @@ where
     F: Fn(&CanonicalResponse, &str) -> Result<Response, CanonicalError> + Copy,
 {
     if !fc_active || !ctx.state.config.features.enable_fc_error_retry {
-        let upstream_body = encode_for_provider(ctx.provider, upstream_canonical)?;
+        let upstream_body =
+            encode_for_provider(ctx.provider, upstream_canonical, &ctx.state.config.features)?;
         return handle_non_streaming_preencoded_common(
             ctx,
             upstream_body,
@@ -75,11 +81,18 @@ where
 
     let mut retry_canonical: Option<CanonicalRequest> = None;
     let mut retry_ctx = fc::retry::RetryContext::new(&ctx.state.config.features);
+    // Upstream that later retries go to once an attempt asks for an alternate.
+    let mut alternate: Option<PreparedUpstreamIoRequest<'_>> = None;
+    let base_ctx = ctx;
 
     loop {
+        let ctx = alternate
+            .as_ref()
+            .map_or(base_ctx, |prepared| prepared.io_ctx(base_ctx.client_model));
         let current_canonical = retry_canonical.as_ref().unwrap_or(upstream_canonical);
-        let upstream_body = encode_for_provider(ctx.provider, current_canonical)?;
-        let (status, body_bytes) = send_non_streaming_bytes(
+        let upstream_body =
+            encode_for_provider(ctx.provider, current_canonical, &ctx.state.config.features)?;
+        let body_bytes = send_non_streaming_bytes(
             ctx.state,
             ctx.url,
             ctx.parsed_url,
@@ -91,15 +104,10 @@ where
         )
         .await?;
 
-        if !status.is_success() {
-            return Err(CanonicalError::Upstream {
-                status: status.as_u16(),
-                message: sanitize_upstream_error(&body_bytes),
-            });
-        }
         let maybe_fc_trigger = fc::response_text_contains_trigger(&body_bytes);
 
         if !maybe_fc_trigger && is_protocol_passthrough(ctx.provider, ingress) {
+            note_body_response_ids(ctx.provider, &body_bytes, None);
             if passthrough_enabled {
                 return Ok(ok_json_response(body_bytes));
             }
@@ -111,6 +119,13 @@ where
         }
 
         let mut upstream_response = decode_response_from_provider(ctx.provider, &body_bytes)?;
+        note_body_response_ids(ctx.provider, &body_bytes, Some(&upstream_response.id));
+        if ctx.state.config.features.strip_responses_reasoning {
+            strip_reasoning_parts(&mut upstream_response);
+        }
+        if let Some(prefill) = echoed_prefill(ctx.provider, ingress) {
+            strip_prefill_echo(&mut upstream_response, &prefill);
+        }
 
         // FC post-processing with optional retry for parse/validation failures.
         if maybe_fc_trigger {
@@ -139,10 +154,21 @@ where
                         original_text,
                     } => {
                         if retry_ctx.should_continue(trigger_found, true) {
+                            let attempt = retry_ctx.next_attempt().cloned().unwrap_or_default();
+                            tracing::warn!(
+                                attempt = retry_ctx.current_attempt + 1,
+                                max_attempts = retry_ctx.max_attempts,
+                                provider = ?ctx.provider,
+                                model = %current_canonical.model,
+                                trigger_found,
+                                alternate_upstream = attempt.alternate_upstream,
+                                error = %error,
+                                "FC response failed to parse; retrying"
+                            );
                             let retry_prompt = fc::retry::build_retry_prompt(
                                 &error,
                                 &original_text,
-                                retry_ctx.retry_template.as_deref(),
+                                retry_ctx.next_template(),
                             );
                             let retry_target =
                                 retry_canonical.get_or_insert_with(|| upstream_canonical.clone());
@@ -151,9 +177,33 @@ where
                                 &original_text,
                                 &retry_prompt,
                             );
+                            if let Some(temperature) = attempt.temperature {
+                                retry_target.generation.temperature = Some(temperature);
+                            }
+                            let next_upstream = if attempt.alternate_upstream {
+                                alternate_upstream(base_ctx, ctx.url, upstream_canonical.stream)
+                            } else {
+                                None
+                            };
+                            if let Some((_, actual_model)) = &next_upstream {
+                                (*actual_model).clone_into(&mut retry_target.model);
+                            }
                             retry_ctx.increment();
+                            if let Some(jitter) = retry_ctx.next_jitter() {
+                                tokio::time::sleep(jitter).await;
+                            }
+                            if let Some((prepared, _)) = next_upstream {
+                                alternate = Some(prepared);
+                            }
                             continue;
                         }
+                        tracing::warn!(
+                            attempts = retry_ctx.current_attempt,
+                            provider = ?ctx.provider,
+                            trigger_found,
+                            error = %error,
+                            "FC response failed to parse; not retrying"
+                        );
                         // Retry disabled/exhausted; pass through upstream response.
                     }
                 }
@@ -164,6 +214,30 @@ where
     }
 }
 
+/// An upstream other than the one at `current_url` serving
+/// `ctx.client_model`, for an FC error retry, with the model name it serves
+/// it under.
+fn alternate_upstream<'a>(
+    ctx: UpstreamIoRequest<'a>,
+    current_url: &str,
+    stream: bool,
+) -> Option<(PreparedUpstreamIoRequest<'a>, &'a str)> {
+    let routes = ctx
+        .state
+        .resolve_routes_with_policy(ctx.client_model, 0, SessionClass::Anchored)
+        .ok()?;
+    routes.into_iter().find_map(|route| {
+        let prepared = prepare_upstream_io_request(
+            ctx.state,
+            &ctx.state.prepared_upstreams[route.upstream_index],
+            route.actual_model,
+            stream,
+        );
+        (prepared.io_ctx(ctx.client_model).url != current_url)
+            .then_some((prepared, route.actual_model))
+    })
+}
+
 pub(crate) async fn handle_non_streaming_preencoded_common<F>(
     ctx: UpstreamIoRequest<'_>,
     upstream_body: bytes::Bytes,
@@ -176,7 +250,7 @@ pub(crate) async fn handle_non_streaming_preencoded_common<F>(
 where
     F: Fn(&CanonicalResponse, &str) -> Result<Response, CanonicalError> + Copy,
 {
-    let (status, body_bytes) = send_non_streaming_bytes(
+    let body_bytes = send_non_streaming_bytes(
         ctx.state,
         ctx.url,
         ctx.parsed_url,
@@ -188,12 +262,6 @@ where
     )
     .await?;
 
-    if !status.is_success() {
-        return Err(CanonicalError::Upstream {
-            status: status.as_u16(),
-            message: sanitize_upstream_error(&body_bytes),
-        });
-    }
     let maybe_fc_trigger = if fc_active {
         fc::response_text_contains_trigger(&body_bytes)
     } else {
@@ -203,6 +271,7 @@ where
     if is_protocol_passthrough(ctx.provider, ingress) {
         let should_passthrough = if fc_active { !maybe_fc_trigger } else { true };
         if should_passthrough {
+            note_body_response_ids(ctx.provider, &body_bytes, None);
             if passthrough_enabled {
                 return Ok(ok_json_response(body_bytes));
             }
@@ -215,6 +284,13 @@ where
     }
 
     let mut upstream_response = decode_response_from_provider(ctx.provider, &body_bytes)?;
+    note_body_response_ids(ctx.provider, &body_bytes, Some(&upstream_response.id));
+    if ctx.state.config.features.strip_responses_reasoning {
+        strip_reasoning_parts(&mut upstream_response);
+    }
+    if let Some(prefill) = echoed_prefill(ctx.provider, ingress) {
+        strip_prefill_echo(&mut upstream_response, &prefill);
+    }
     if fc_active && maybe_fc_trigger {
         fc::apply_fc_postprocess_once(&mut upstream_response, saved_tools)?;
     }
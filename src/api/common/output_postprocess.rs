@@ -0,0 +1,320 @@
+use std::collections::VecDeque;
+
+use axum::response::Response;
+use futures_util::{Stream, StreamExt};
+use serde_json::Value;
+
+use crate::error::CanonicalError;
+use crate::json_scan::{find_top_level_field_value_range, parse_json_value_end, skip_ws};
+use crate::protocol::canonical::{
+    CanonicalPart, CanonicalResponse, CanonicalStopReason, IngressApi,
+};
+use crate::stream::text_pipeline::TextPipeline;
+use crate::stream::{sse_frame_stream, StreamTranscoder};
+
+use super::codec::decode_response_from_provider;
+use super::stream_aggregate::{encode_client_response, ingress_wire_provider, response_id};
+
+/// Stop sequences the client asked for in `body`.
+#[must_use]
+pub(crate) fn client_stop_sequences(ingress: IngressApi, body: &[u8]) -> Vec<String> {
+    // Every stop field name starts with `stop`; skip parsing bodies without one.
+    if memchr::memmem::find(body, b"\"stop").is_none() {
+        return Vec::new();
+    }
+    let Ok(payload) = serde_json::from_slice::<Value>(body) else {
+        return Vec::new();
+    };
+    let stop = match ingress {
+        IngressApi::OpenAiChat => payload.get("stop"),
+        IngressApi::Anthropic => payload.get("stop_sequences"),
+        IngressApi::Gemini => payload
+            .pointer("/generationConfig/stopSequences")
+            .or_else(|| payload.pointer("/generation_config/stop_sequences")),
+        // The Responses API has no stop sequences.
+        IngressApi::OpenAiResponses => None,
+    };
+    match stop {
+        Some(Value::String(stop)) => vec![stop.clone()],
+        Some(Value::Array(stops)) => stops
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Text of the trailing assistant turn an Anthropic or Gemini client sent in
+/// `body` to prefill the reply.
+#[must_use]
+pub(crate) fn client_assistant_prefill(ingress: IngressApi, body: &[u8]) -> Option<String> {
+    let (messages_key, role, parts_key): (&[u8], &[u8], _) = match ingress {
+        IngressApi::Anthropic => (b"messages", b"\"assistant\"", "content"),
+        IngressApi::Gemini => (b"contents", b"\"model\"", "parts"),
+        IngressApi::OpenAiChat | IngressApi::OpenAiResponses => return None,
+    };
+    // Skip bodies that cannot hold an assistant turn.
+    memchr::memmem::find(body, role)?;
+    // Only the final turn is parsed, and only once its role matches.
+    let messages = find_top_level_field_value_range(body, messages_key).ok()??;
+    let last = last_array_element(&body[messages])?;
+    let role_range = find_top_level_field_value_range(last, b"role").ok()??;
+    if &last[role_range] != role {
+        return None;
+    }
+    let last = serde_json::from_slice::<Value>(last).ok()?;
+    let prefill: String = match last.get(parts_key)? {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter(|part| {
+                ingress == IngressApi::Gemini
+                    || part.get("type").and_then(Value::as_str) == Some("text")
+            })
+            .filter(|part| part.get("thought").and_then(Value::as_bool) != Some(true))
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .collect(),
+        _ => return None,
+    };
+    (!prefill.is_empty()).then_some(prefill)
+}
+
+/// The bytes of the last element of the JSON array `array`.
+fn last_array_element(array: &[u8]) -> Option<&[u8]> {
+    let mut i = skip_ws(array, 0);
+    if array.get(i) != Some(&b'[') {
+        return None;
+    }
+    i += 1;
+    let mut last: Option<std::ops::Range<usize>> = None;
+    loop {
+        i = skip_ws(array, i);
+        match array.get(i) {
+            Some(b']') => return last.map(|range| &array[range]),
+            None => return None,
+            Some(b',') => {
+                i += 1;
+                continue;
+            }
+            Some(_) => {}
+        }
+        let element_end = parse_json_value_end(array, i).ok()?;
+        last = Some(i..element_end);
+        i = element_end;
+    }
+}
+
+/// Run the assistant text of a client response through `pipeline`.
+///
+/// The response is decoded into canonical form and re-encoded in the
+/// `ingress` format; SSE bodies are processed frame by frame. Error responses
+/// and bodies that cannot be decoded are returned unchanged.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::Transport`] when a non-streaming body cannot be
+/// read, or [`CanonicalError::Translation`] when it cannot be re-encoded.
+pub(crate) async fn postprocess_output_response(
+    response: Response,
+    ingress: IngressApi,
+    client_model: &str,
+    pipeline: TextPipeline,
+) -> Result<Response, CanonicalError> {
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+
+    if is_sse {
+        let frames = postprocess_sse_body(body, ingress, client_model.to_string(), pipeline);
+        return Ok(Response::from_parts(
+            parts,
+            axum::body::Body::from_stream(frames),
+        ));
+    }
+
+    let body_bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
+    let Ok(mut canonical) =
+        decode_response_from_provider(ingress_wire_provider(ingress), &body_bytes)
+    else {
+        return Ok(Response::from_parts(
+            parts,
+            axum::body::Body::from(body_bytes),
+        ));
+    };
+    postprocess_canonical_response(&mut canonical, pipeline);
+    let (_, encoded) = encode_client_response(&canonical, ingress, client_model)?.into_parts();
+    Ok(Response::from_parts(parts, encoded))
+}
+
+fn postprocess_canonical_response(canonical: &mut CanonicalResponse, mut pipeline: TextPipeline) {
+    let mut last_text = None;
+    for (index, part) in canonical.content.iter_mut().enumerate() {
+        if let CanonicalPart::Text(text) = part {
+            *text = pipeline.push(text);
+            last_text = Some(index);
+        }
+    }
+    let tail = pipeline.finish();
+    if let Some(CanonicalPart::Text(text)) =
+        last_text.and_then(|index| canonical.content.get_mut(index))
+    {
+        text.push_str(&tail);
+    }
+    canonical
+        .content
+        .retain(|part| !matches!(part, CanonicalPart::Text(text) if text.is_empty()));
+    if pipeline.stopped() {
+        canonical.stop_reason = CanonicalStopReason::StopSequence;
+    }
+}
+
+struct SsePostprocess<S> {
+    frames: std::pin::Pin<Box<S>>,
+    decoder: StreamTranscoder,
+    encoder: Option<StreamTranscoder>,
+    pipeline: TextPipeline,
+    ingress: IngressApi,
+    client_model: String,
+    pending: VecDeque<bytes::Bytes>,
+    finished: bool,
+}
+
+fn postprocess_sse_body(
+    body: axum::body::Body,
+    ingress: IngressApi,
+    client_model: String,
+    pipeline: TextPipeline,
+) -> impl Stream<Item = Result<bytes::Bytes, std::convert::Infallible>> + Send + 'static {
+    let state = SsePostprocess {
+        frames: Box::pin(sse_frame_stream(body.into_data_stream())),
+        decoder: StreamTranscoder::new(
+            ingress_wire_provider(ingress),
+            ingress,
+            client_model.clone(),
+            String::new(),
+        ),
+        encoder: None,
+        pipeline,
+        ingress,
+        client_model,
+        pending: VecDeque::new(),
+        finished: false,
+    };
+    futures_util::stream::unfold(state, |mut state| async move {
+        let mut decoded = Vec::with_capacity(8);
+        let mut processed = Vec::with_capacity(8);
+        loop {
+            if let Some(frame) = state.pending.pop_front() {
+                return Some((Ok(frame), state));
+            }
+            if state.finished {
+                return None;
+            }
+            let Some(frame) = state.frames.next().await else {
+                state.finished = true;
+                if let Some(tail) = state.encoder.as_mut().and_then(StreamTranscoder::finish) {
+                    state.pending.push_back(bytes::Bytes::from(tail));
+                }
+                continue;
+            };
+            let encoder = state.encoder.get_or_insert_with(|| {
+                StreamTranscoder::new(
+                    ingress_wire_provider(state.ingress),
+                    state.ingress,
+                    state.client_model.clone(),
+                    response_id(state.ingress, &frame.data).unwrap_or_default(),
+                )
+            });
+            state
+                .decoder
+                .decode_upstream_frame_into(&frame, &mut decoded);
+            for event in decoded.drain(..) {
+                state.pipeline.apply_event(event, &mut processed);
+            }
+            for event in processed.drain(..) {
+                if let Some(bytes) = encoder.encode_client_event_bytes(&event) {
+                    state.pending.push_back(bytes);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_stop_sequences_per_ingress() {
+        assert_eq!(
+            client_stop_sequences(IngressApi::OpenAiChat, br#"{"stop":"END"}"#),
+            vec!["END"]
+        );
+        assert_eq!(
+            client_stop_sequences(IngressApi::Anthropic, br#"{"stop_sequences":["a","b"]}"#),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            client_stop_sequences(
+                IngressApi::Gemini,
+                br#"{"generationConfig":{"stopSequences":["x"]}}"#
+            ),
+            vec!["x"]
+        );
+        assert!(client_stop_sequences(IngressApi::OpenAiResponses, br#"{"stop":"x"}"#).is_empty());
+    }
+
+    #[test]
+    fn test_client_assistant_prefill_per_ingress() {
+        assert_eq!(
+            client_assistant_prefill(
+                IngressApi::Anthropic,
+                br#"{"messages":[{"role":"user","content":"hi"},{"role":"assistant","content":"{"}]}"#
+            )
+            .as_deref(),
+            Some("{")
+        );
+        assert_eq!(
+            client_assistant_prefill(
+                IngressApi::Anthropic,
+                br#"{"messages":[{"role":"assistant","content":[{"type":"text","text":"Sure"}]}]}"#
+            )
+            .as_deref(),
+            Some("Sure")
+        );
+        assert_eq!(
+            client_assistant_prefill(
+                IngressApi::Gemini,
+                br#"{"contents":[{"role":"user","parts":[{"text":"hi"}]},{"role":"model","parts":[{"text":"Once"}]}]}"#
+            )
+            .as_deref(),
+            Some("Once")
+        );
+        assert!(client_assistant_prefill(
+            IngressApi::Anthropic,
+            br#"{"messages":[{"role":"assistant","content":"a"},{"role":"user","content":"b"}]}"#
+        )
+        .is_none());
+        // A model turn earlier in the history is not a prefill.
+        assert!(client_assistant_prefill(
+            IngressApi::Gemini,
+            br#"{"contents":[{"role":"model","parts":[{"text":"a"}]},{"parts":[{"text":"b"}],"role":"user"}]}"#
+        )
+        .is_none());
+        assert!(client_assistant_prefill(
+            IngressApi::OpenAiChat,
+            br#"{"messages":[{"role":"assistant","content":"a"}]}"#
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use crate::config::ModelOutputTokens;
+use crate::observability::token_counter::estimate_request_tokens;
+use crate::protocol::anthropic::encoder::thinking_budget_tokens;
+use crate::protocol::canonical::CanonicalRequest;
+
+/// Anthropic `max_tokens` for `canonical` from the configured limits of its
+/// upstream model.
+///
+/// A client limit is lowered to the model's `max_output_tokens`. Without one,
+/// the model's default is used, plus any thinking budget, capped at what the
+/// estimated prompt leaves of the context window. Returns `None` when the
+/// model has no configured limits or the request can be sent unchanged.
+pub(crate) fn anthropic_output_tokens(
+    canonical: &CanonicalRequest,
+    limits: &BTreeMap<String, ModelOutputTokens>,
+) -> Option<u64> {
+    let limits = limits.get(&canonical.model)?;
+    let limit = match canonical.generation.max_tokens {
+        Some(requested) => requested.min(limits.max_output_tokens),
+        None => {
+            let default = limits
+                .default_output_tokens
+                .unwrap_or(limits.max_output_tokens)
+                .saturating_add(thinking_budget_tokens(canonical).unwrap_or(0))
+                .min(limits.max_output_tokens);
+            let remaining = limits.context_window.map_or(u64::MAX, |window| {
+                window.saturating_sub(estimate_request_tokens(canonical))
+            });
+            default.min(remaining).max(1)
+        }
+    };
+    if canonical.generation.max_tokens == Some(limit) {
+        return None;
+    }
+    tracing::debug!(
+        model = %canonical.model,
+        requested = ?canonical.generation.max_tokens,
+        max_tokens = limit,
+        "set Anthropic max_tokens from model output limits"
+    );
+    Some(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::protocol::canonical::{
+        CanonicalMessage, CanonicalPart, CanonicalRole, CanonicalToolChoice, GenerationParams,
+        IngressApi,
+    };
+
+    fn request(model: &str, max_tokens: Option<u64>, prompt: &str) -> CanonicalRequest {
+        CanonicalRequest {
+            request_id: uuid::Uuid::nil(),
+            ingress_api: IngressApi::OpenAiChat,
+            model: model.to_string(),
+            stream: false,
+            system_prompt: None,
+            messages: vec![CanonicalMessage {
+                role: CanonicalRole::User,
+                parts: vec![CanonicalPart::Text(prompt.to_string())].into(),
+                name: None,
+                tool_call_id: None,
+                provider_extensions: None,
+            }],
+            tools: Arc::from([]),
+            tool_choice: CanonicalToolChoice::Auto,
+            generation: GenerationParams {
+                max_tokens,
+                ..GenerationParams::default()
+            },
+            provider_extensions: None,
+        }
+    }
+
+    #[test]
+    fn test_output_tokens_follow_model_limits_and_context() {
+        let limits = BTreeMap::from([
+            (
+                "claude-large".to_string(),
+                ModelOutputTokens {
+                    max_output_tokens: 64_000,
+                    default_output_tokens: Some(8_192),
+                    context_window: Some(200_000),
+                },
+            ),
+            (
+                "claude-small".to_string(),
+                ModelOutputTokens {
+                    max_output_tokens: 4_096,
+                    default_output_tokens: None,
+                    context_window: Some(1_000),
+                },
+            ),
+        ]);
+        let output = |model, max_tokens, prompt: &str| {
+            anthropic_output_tokens(&request(model, max_tokens, prompt), &limits)
+        };
+
+        assert_eq!(output("claude-large", None, "hi"), Some(8_192));
+        assert_eq!(output("claude-large", Some(100_000), "hi"), Some(64_000));
+        assert_eq!(output("claude-large", Some(1_000), "hi"), None);
+        assert_eq!(output("unlisted", None, "hi"), None);
+        // 2400 bytes estimate to 600 prompt tokens, leaving 400 of the window.
+        assert_eq!(output("claude-small", None, &"x".repeat(2_400)), Some(400));
+        assert_eq!(output("claude-small", None, &"x".repeat(8_000)), Some(1));
+
+        let mut thinking = request("claude-large", None, "hi");
+        thinking.provider_extensions_mut().insert(
+            "thinking".to_string(),
+            serde_json::json!({ "type": "enabled", "budget_tokens": 10_000 }),
+        );
+        assert_eq!(anthropic_output_tokens(&thinking, &limits), Some(18_192));
+    }
+}
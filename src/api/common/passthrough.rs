@@ -2,8 +2,10 @@ use axum::http::HeaderMap;
 use axum::response::Response;
 
 use crate::error::CanonicalError;
+use crate::observability::upstream_errors::note_upstream_error;
 use crate::protocol::canonical::{IngressApi, ProviderKind};
 use crate::state::AppState;
+use crate::stream::json_array::json_array_to_sse;
 
 pub(crate) fn is_protocol_passthrough(provider: ProviderKind, ingress: IngressApi) -> bool {
     matches!(
@@ -17,6 +19,22 @@ pub(crate) fn is_protocol_passthrough(provider: ProviderKind, ingress: IngressAp
     )
 }
 
+/// Whether a streamed answer is a JSON array rather than SSE, as Gemini
+/// `streamGenerateContent` sends without `alt=sse`.
+fn is_json_array_stream(content_type: &http::HeaderValue) -> bool {
+    content_type.as_bytes().starts_with(b"application/json")
+}
+
+/// The content type a passthrough stream is served with; JSON arrays are
+/// re-framed as SSE.
+fn stream_content_type(content_type: http::HeaderValue) -> http::HeaderValue {
+    if is_json_array_stream(&content_type) {
+        http::HeaderValue::from_static("text/event-stream")
+    } else {
+        content_type
+    }
+}
+
 /// Raw non-streaming passthrough: forward request JSON and return upstream body bytes directly.
 pub(crate) async fn passthrough_non_streaming_bytes(
     state: &AppState,
@@ -87,14 +105,12 @@ async fn build_passthrough_non_streaming_response(
     let status = response.status();
 
     if !status.is_success() {
+        let headers = response.headers().clone();
         let body_bytes = response
             .bytes()
             .await
             .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
-        return Err(CanonicalError::Upstream {
-            status: status.as_u16(),
-            message: sanitize_upstream_error(&body_bytes),
-        });
+        return Err(upstream_error(status, &headers, &body_bytes));
     }
 
     let content_type = response.headers().get(http::header::CONTENT_TYPE).cloned();
@@ -115,7 +131,7 @@ async fn build_passthrough_non_streaming_hyper_response(
 
     let status = response.status();
     let content_type = response.headers().get(http::header::CONTENT_TYPE).cloned();
-    let (_, body) = response.into_parts();
+    let (parts, body) = response.into_parts();
 
     if !status.is_success() {
         let body_bytes = body
@@ -123,10 +139,7 @@ async fn build_passthrough_non_streaming_hyper_response(
             .await
             .map(http_body_util::Collected::to_bytes)
             .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
-        return Err(CanonicalError::Upstream {
-            status: status.as_u16(),
-            message: sanitize_upstream_error(&body_bytes),
-        });
+        return Err(upstream_error(status, &parts.headers, &body_bytes));
     }
 
     let body = axum::body::Body::new(body);
@@ -208,14 +221,12 @@ async fn build_passthrough_streaming_response(
 ) -> Result<Response, CanonicalError> {
     let status = response.status();
     if !status.is_success() {
+        let headers = response.headers().clone();
         let body_bytes = response
             .bytes()
             .await
             .map_err(|e| CanonicalError::Transport(format!("Failed to read error body: {e}")))?;
-        return Err(CanonicalError::Upstream {
-            status: status.as_u16(),
-            message: sanitize_upstream_error(&body_bytes),
-        });
+        return Err(upstream_error(status, &headers, &body_bytes));
     }
 
     let content_type = response
@@ -223,8 +234,10 @@ async fn build_passthrough_streaming_response(
         .get(http::header::CONTENT_TYPE)
         .cloned()
         .unwrap_or_else(|| http::HeaderValue::from_static("text/event-stream"));
+    let json_array = is_json_array_stream(&content_type);
+    let content_type = stream_content_type(content_type);
     let byte_stream = response.bytes_stream();
-    let body = axum::body::Body::from_stream(byte_stream);
+    let body = axum::body::Body::from_stream(json_array_to_sse(byte_stream, json_array));
 
     let mut passthrough = Response::new(body);
     *passthrough.status_mut() = status;
@@ -253,20 +266,25 @@ async fn build_passthrough_streaming_hyper_response(
         .cloned()
         .unwrap_or_else(|| http::HeaderValue::from_static("text/event-stream"));
 
-    let (_, body) = response.into_parts();
+    let (parts, body) = response.into_parts();
     if !status.is_success() {
         let collected = body
             .collect()
             .await
             .map_err(|e| CanonicalError::Transport(format!("Failed to read error body: {e}")))?;
         let body_bytes = collected.to_bytes();
-        return Err(CanonicalError::Upstream {
-            status: status.as_u16(),
-            message: sanitize_upstream_error(&body_bytes),
-        });
+        return Err(upstream_error(status, &parts.headers, &body_bytes));
     }
 
-    let body = axum::body::Body::new(body);
+    let body = if is_json_array_stream(&content_type) {
+        axum::body::Body::from_stream(json_array_to_sse(
+            axum::body::Body::new(body).into_data_stream(),
+            true,
+        ))
+    } else {
+        axum::body::Body::new(body)
+    };
+    let content_type = stream_content_type(content_type);
 
     let mut passthrough = Response::new(body);
     *passthrough.status_mut() = status;
@@ -283,6 +301,23 @@ async fn build_passthrough_streaming_hyper_response(
     Ok(passthrough)
 }
 
+/// Build the canonical error for a non-success upstream response.
+///
+/// Carries the sanitized message and any `retry-after` hint so ingress error
+/// shapes can forward it to clients.
+pub(crate) fn upstream_error(
+    status: http::StatusCode,
+    headers: &http::HeaderMap,
+    body: &[u8],
+) -> CanonicalError {
+    note_upstream_error(status, headers, body);
+    CanonicalError::Upstream {
+        status: status.as_u16(),
+        message: sanitize_upstream_error(body),
+        retry_after: crate::transport::parse_retry_after_secs(headers),
+    }
+}
+
 /// Sanitize an upstream error body to avoid leaking internal details.
 ///
 /// Attempts to extract just the `error.message` field from JSON responses.
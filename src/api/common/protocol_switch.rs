@@ -0,0 +1,247 @@
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_util::Stream;
+
+use crate::error::category_from_upstream_status;
+use crate::protocol::canonical::ProviderKind;
+use crate::protocol::error_shapes::{
+    anthropic_error_payload, gemini_error_payload, openai_error_payload,
+};
+
+use super::passthrough::sanitize_upstream_error;
+
+/// Bytes of a non-SSE payload kept for its error message.
+const MAX_SWITCHED_PAYLOAD_BYTES: usize = 64 * 1024;
+/// Status reported when the payload does not carry one.
+const DEFAULT_SWITCH_STATUS: u16 = 502;
+
+/// Watch an upstream SSE `byte_stream` for a plain JSON body in place of the
+/// next frame.
+///
+/// Some OpenAI-compatible gateways give up on a stream by writing a bare
+/// `{"error":...}` document, or answer with JSON from the start. No SSE line
+/// starts with `{`, so such a payload is collected to the end of the body and
+/// replaced by an error frame in the `provider`'s own format, which the
+/// transcoder surfaces as a stream error instead of a silent end.
+pub(super) fn guard_protocol_switch<S, E>(
+    byte_stream: S,
+    provider: ProviderKind,
+) -> ProtocolSwitchGuard<S>
+where
+    S: Stream<Item = Result<bytes::Bytes, E>>,
+{
+    ProtocolSwitchGuard {
+        inner: byte_stream,
+        provider,
+        line_start: true,
+        tail: Vec::with_capacity(3),
+        switched: None,
+        done: false,
+    }
+}
+
+pin_project_lite::pin_project! {
+    pub(super) struct ProtocolSwitchGuard<S> {
+        #[pin]
+        inner: S,
+        provider: ProviderKind,
+        // Whether the next byte starts a line.
+        line_start: bool,
+        // Last bytes passed through, to tell whether they end a frame.
+        tail: Vec<u8>,
+        // The non-SSE payload, once one started.
+        switched: Option<Vec<u8>>,
+        done: bool,
+    }
+}
+
+impl<S, E> Stream for ProtocolSwitchGuard<S>
+where
+    S: Stream<Item = Result<bytes::Bytes, E>>,
+{
+    type Item = Result<bytes::Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            if *this.done {
+                return Poll::Ready(None);
+            }
+            let chunk = match ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(err)) => {
+                    *this.done = true;
+                    // The error frame says more than the transport error.
+                    if let Some(payload) = this.switched.take() {
+                        return Poll::Ready(Some(Ok(error_frame(
+                            *this.provider,
+                            &payload,
+                            ends_frame(this.tail),
+                        ))));
+                    }
+                    return Poll::Ready(Some(Err(err)));
+                }
+                None => {
+                    *this.done = true;
+                    let Some(payload) = this.switched.take() else {
+                        return Poll::Ready(None);
+                    };
+                    return Poll::Ready(Some(Ok(error_frame(
+                        *this.provider,
+                        &payload,
+                        ends_frame(this.tail),
+                    ))));
+                }
+            };
+            if let Some(payload) = this.switched.as_mut() {
+                let room = MAX_SWITCHED_PAYLOAD_BYTES.saturating_sub(payload.len());
+                payload.extend_from_slice(&chunk[..chunk.len().min(room)]);
+                continue;
+            }
+            let Some(offset) = switch_offset(&chunk, *this.line_start) else {
+                *this.line_start = chunk.last().map_or(*this.line_start, |byte| *byte == b'\n');
+                remember_tail(this.tail, &chunk);
+                return Poll::Ready(Some(Ok(chunk)));
+            };
+            let rest = &chunk[offset..];
+            *this.switched = Some(rest[..rest.len().min(MAX_SWITCHED_PAYLOAD_BYTES)].to_vec());
+            if offset > 0 {
+                remember_tail(this.tail, &chunk[..offset]);
+                return Poll::Ready(Some(Ok(chunk.slice(..offset))));
+            }
+        }
+    }
+}
+
+/// Offset of the first line in `chunk` that starts a JSON document.
+fn switch_offset(chunk: &[u8], line_start: bool) -> Option<usize> {
+    if line_start && chunk.first() == Some(&b'{') {
+        return Some(0);
+    }
+    memchr::memchr_iter(b'\n', chunk)
+        .map(|pos| pos + 1)
+        .find(|&start| chunk.get(start) == Some(&b'{'))
+}
+
+fn remember_tail(tail: &mut Vec<u8>, bytes: &[u8]) {
+    let keep = bytes.len().min(3);
+    tail.extend_from_slice(&bytes[bytes.len() - keep..]);
+    let excess = tail.len().saturating_sub(3);
+    tail.drain(..excess);
+}
+
+/// Whether the bytes passed through so far end on a frame boundary.
+fn ends_frame(tail: &[u8]) -> bool {
+    tail.is_empty() || tail.ends_with(b"\n\n") || tail.ends_with(b"\n\r\n")
+}
+
+/// An SSE error frame in `provider`'s format for the non-SSE `payload`.
+fn error_frame(provider: ProviderKind, payload: &[u8], at_boundary: bool) -> bytes::Bytes {
+    tracing::warn!(?provider, "upstream stream switched to a non-SSE body");
+    let status = payload_status(payload);
+    let cat = category_from_upstream_status(status);
+    let message = sanitize_upstream_error(payload);
+    let (event, data) = match provider {
+        ProviderKind::OpenAi | ProviderKind::GeminiOpenAi => {
+            (None, openai_error_payload(cat, &message))
+        }
+        ProviderKind::Anthropic => (Some("error"), anthropic_error_payload(cat, &message)),
+        ProviderKind::OpenAiResponses => (
+            Some("error"),
+            serde_json::json!({ "type": "error", "message": message }),
+        ),
+        ProviderKind::Gemini => {
+            let status =
+                http::StatusCode::from_u16(status).unwrap_or(http::StatusCode::BAD_GATEWAY);
+            (None, gemini_error_payload(cat, status, &message))
+        }
+    };
+    let mut frame = String::new();
+    if !at_boundary {
+        frame.push('\n');
+    }
+    if let Some(event) = event {
+        frame.push_str("event: ");
+        frame.push_str(event);
+        frame.push('\n');
+    }
+    frame.push_str("data: ");
+    frame.push_str(&data.to_string());
+    frame.push_str("\n\n");
+    bytes::Bytes::from(frame)
+}
+
+/// HTTP status named by a JSON error payload.
+fn payload_status(payload: &[u8]) -> u16 {
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(payload) else {
+        return DEFAULT_SWITCH_STATUS;
+    };
+    let status = [
+        json.get("error").and_then(|error| error.get("code")),
+        json.get("status"),
+        json.get("code"),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(serde_json::Value::as_u64)
+    .filter_map(|code| u16::try_from(code).ok())
+    .find(|code| (400..600).contains(code));
+    status.unwrap_or(DEFAULT_SWITCH_STATUS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    async fn guarded(provider: ProviderKind, chunks: &[&'static str]) -> String {
+        let chunks: Vec<Result<bytes::Bytes, std::io::Error>> = chunks
+            .iter()
+            .map(|chunk| Ok(bytes::Bytes::from_static(chunk.as_bytes())))
+            .collect();
+        let out: Vec<_> = guard_protocol_switch(futures_util::stream::iter(chunks), provider)
+            .collect()
+            .await;
+        out.into_iter()
+            .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_json_error_after_frames_becomes_an_error_frame() {
+        let body = guarded(
+            ProviderKind::OpenAi,
+            &[
+                "data: {\"id\":\"c1\",\"choices\":[]}\n\n{\"error\":{\"message\":\"over",
+                "loaded\",\"code\":429}}",
+            ],
+        )
+        .await;
+        let (frame, error) = body.split_once("\n\n").unwrap();
+        assert_eq!(frame, "data: {\"id\":\"c1\",\"choices\":[]}");
+        let data: serde_json::Value =
+            serde_json::from_str(error.trim().strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(data["error"]["message"], "overloaded");
+        assert_eq!(data["error"]["type"], "rate_limit_error");
+    }
+
+    #[tokio::test]
+    async fn test_json_body_from_the_start_becomes_an_anthropic_error_event() {
+        let body = guarded(
+            ProviderKind::Anthropic,
+            &["{\"error\":{\"message\":\"bad gateway\"}}"],
+        )
+        .await;
+        assert!(body.starts_with("event: error\ndata: "));
+        assert!(body.contains("\"message\":\"bad gateway\""));
+        assert!(body.ends_with("\n\n"));
+    }
+
+    #[tokio::test]
+    async fn test_sse_streams_pass_through_unchanged() {
+        let chunks = ["event: ping\ndata: {\"a\":1}\n", "\ndata: [DONE]\n\n"];
+        let body = guarded(ProviderKind::Anthropic, &chunks).await;
+        assert_eq!(body, chunks.concat());
+    }
+}
@@ -0,0 +1,45 @@
+use axum::response::Response;
+
+use crate::protocol::canonical::{CanonicalPart, IngressApi};
+use crate::routing::quality_retry::QualityRetryPolicy;
+
+use super::codec::decode_response_from_provider;
+use super::stream_aggregate::ingress_wire_provider;
+
+/// Buffer a complete client-format response and check its quality.
+///
+/// Returns the response, rebuilt from the buffered body, with the reason it
+/// must be retried. Error responses and bodies that cannot be decoded are
+/// never retried.
+pub(crate) async fn check_response_quality(
+    response: Response,
+    ingress: IngressApi,
+    policy: &QualityRetryPolicy,
+) -> (Response, Option<&'static str>) {
+    if !response.status().is_success() {
+        return (response, None);
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, usize::MAX).await else {
+        return (Response::from_parts(parts, axum::body::Body::empty()), None);
+    };
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+    let issue = decode_response_from_provider(ingress_wire_provider(ingress), &body)
+        .ok()
+        .and_then(|canonical| {
+            let mut text = String::new();
+            let mut has_tool_calls = false;
+            for part in &canonical.content {
+                match part {
+                    CanonicalPart::Text(delta) => text.push_str(delta),
+                    CanonicalPart::ToolCall { .. } => has_tool_calls = true,
+                    _ => {}
+                }
+            }
+            policy.issue(&text, has_tool_calls, canonical.usage.output_tokens)
+        });
+    (
+        Response::from_parts(parts, axum::body::Body::from(body)),
+        issue,
+    )
+}
@@ -0,0 +1,101 @@
+use serde_json::Value;
+
+use crate::protocol::canonical::IngressApi;
+use crate::routing::capabilities::Capabilities;
+
+/// Capabilities a client request in `body` needs from the serving model.
+#[must_use]
+pub(crate) fn required_capabilities(
+    ingress: IngressApi,
+    body: &[u8],
+    has_tools: bool,
+) -> Capabilities {
+    let mut required = if has_tools {
+        Capabilities::TOOLS
+    } else {
+        Capabilities::NONE
+    };
+    // Image parts and JSON formats all spell out `image` or `json`; skip
+    // parsing bodies without either.
+    if memchr::memmem::find(body, b"image").is_none()
+        && memchr::memmem::find(body, b"json").is_none()
+    {
+        return required;
+    }
+    let Ok(payload) = serde_json::from_slice::<Value>(body) else {
+        return required;
+    };
+    if has_image_part(&payload) {
+        required = required.union(Capabilities::VISION);
+    }
+    if requests_json_mode(ingress, &payload) {
+        required = required.union(Capabilities::JSON_MODE);
+    }
+    required
+}
+
+fn has_image_part(value: &Value) -> bool {
+    match value {
+        Value::Object(object) => {
+            let typed_image = object
+                .get("type")
+                .and_then(Value::as_str)
+                .is_some_and(|kind| matches!(kind, "image_url" | "input_image" | "image"));
+            let image_blob = ["mimeType", "mime_type"].iter().any(|key| {
+                object
+                    .get(*key)
+                    .and_then(Value::as_str)
+                    .is_some_and(|mime| mime.starts_with("image/"))
+            });
+            typed_image || image_blob || object.values().any(has_image_part)
+        }
+        Value::Array(items) => items.iter().any(has_image_part),
+        _ => false,
+    }
+}
+
+fn requests_json_mode(ingress: IngressApi, payload: &Value) -> bool {
+    let json_format = |format: Option<&Value>| {
+        format
+            .and_then(|format| format.get("type"))
+            .and_then(Value::as_str)
+            .is_some_and(|kind| matches!(kind, "json_object" | "json_schema"))
+    };
+    match ingress {
+        IngressApi::OpenAiChat => json_format(payload.get("response_format")),
+        IngressApi::OpenAiResponses => json_format(payload.pointer("/text/format")),
+        IngressApi::Gemini => payload
+            .pointer("/generationConfig/responseMimeType")
+            .or_else(|| payload.pointer("/generation_config/response_mime_type"))
+            .and_then(Value::as_str)
+            .is_some_and(|mime| mime == "application/json"),
+        IngressApi::Anthropic => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_capabilities_per_ingress() {
+        let chat = br#"{"messages":[{"role":"user","content":[
+            {"type":"text","text":"what is this?"},
+            {"type":"image_url","image_url":{"url":"data:image/png;base64,AAAA"}}]}],
+            "response_format":{"type":"json_object"}}"#;
+        assert_eq!(
+            required_capabilities(IngressApi::OpenAiChat, chat, false),
+            Capabilities::VISION.union(Capabilities::JSON_MODE)
+        );
+
+        let gemini = br#"{"contents":[{"parts":[
+            {"inlineData":{"mimeType":"image/jpeg","data":"AAAA"}}]}]}"#;
+        assert_eq!(
+            required_capabilities(IngressApi::Gemini, gemini, true),
+            Capabilities::VISION.union(Capabilities::TOOLS)
+        );
+
+        let anthropic = br#"{"messages":[{"role":"user","content":"describe a json file"}]}"#;
+        assert!(required_capabilities(IngressApi::Anthropic, anthropic, false).is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+use axum::response::Response;
+use futures_util::{Stream, StreamExt};
+
+use crate::json_scan::find_top_level_field_value_range;
+use crate::protocol::canonical::{IngressApi, ProviderKind};
+use crate::state::{note_response_ids, ResponseIds};
+use crate::stream::SseParser;
+
+use super::stream_aggregate::response_id;
+
+const UPSTREAM_RESPONSE_ID_HEADER: &str = "x-upstream-response-id";
+/// Upstream stream bytes read ahead for the first frame and its response id.
+const MAX_RESPONSE_ID_PEEK_BYTES: usize = 16 * 1024;
+
+/// Name the upstream's id for the response in the `x-upstream-response-id`
+/// header.
+pub(crate) fn mark_upstream_response_id(mut response: Response, ids: &ResponseIds) -> Response {
+    if let Ok(value) = http::HeaderValue::from_str(&ids.upstream_id) {
+        response
+            .headers_mut()
+            .insert(UPSTREAM_RESPONSE_ID_HEADER, value);
+    }
+    response
+}
+
+/// Note the id of a non-streaming upstream `body` against `client_id`, the id
+/// of the response sent to the client, or the upstream id itself when the
+/// body is forwarded unchanged.
+pub(super) fn note_body_response_ids(provider: ProviderKind, body: &[u8], client_id: Option<&str>) {
+    let field: &[u8] = match provider {
+        ProviderKind::Gemini => b"responseId",
+        _ => b"id",
+    };
+    let Some(upstream_id) = find_top_level_field_value_range(body, field)
+        .ok()
+        .flatten()
+        .and_then(|range| body.get(range))
+        .and_then(|value| serde_json::from_slice::<String>(value).ok())
+    else {
+        return;
+    };
+    note_response_ids(client_id.unwrap_or(&upstream_id), &upstream_id);
+}
+
+/// Read `byte_stream` up to its first SSE frame and note the response id the
+/// upstream put there against `client_id`, the id transcoded frames carry.
+///
+/// Returns a stream yielding every chunk of the original.
+pub(super) async fn note_stream_response_ids<S, E>(
+    byte_stream: S,
+    provider: ProviderKind,
+    client_id: &str,
+) -> impl Stream<Item = Result<bytes::Bytes, E>> + Send + 'static
+where
+    S: Stream<Item = Result<bytes::Bytes, E>> + Send + 'static,
+    E: Send + 'static,
+{
+    let mut byte_stream = Box::pin(byte_stream);
+    let mut head = Vec::new();
+    let mut parser = SseParser::new();
+    let mut peeked = 0;
+    while peeked < MAX_RESPONSE_ID_PEEK_BYTES {
+        let Some(chunk) = byte_stream.next().await else {
+            break;
+        };
+        let Ok(bytes) = &chunk else {
+            head.push(chunk);
+            break;
+        };
+        peeked += bytes.len();
+        let frames = parser.feed(&String::from_utf8_lossy(bytes));
+        head.push(chunk);
+        if let Some(frame) = frames.first() {
+            if let Some(upstream_id) = response_id(provider_wire_ingress(provider), &frame.data) {
+                note_response_ids(client_id, &upstream_id);
+            }
+            break;
+        }
+    }
+    futures_util::stream::iter(head).chain(byte_stream)
+}
+
+/// The ingress whose wire format `provider` streams in.
+fn provider_wire_ingress(provider: ProviderKind) -> IngressApi {
+    match provider {
+        ProviderKind::OpenAi | ProviderKind::GeminiOpenAi => IngressApi::OpenAiChat,
+        ProviderKind::OpenAiResponses => IngressApi::OpenAiResponses,
+        ProviderKind::Anthropic => IngressApi::Anthropic,
+        ProviderKind::Gemini => IngressApi::Gemini,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::request_context::{self, RequestContext};
+
+    #[tokio::test]
+    async fn test_stream_response_id_is_read_from_the_first_frame() {
+        let chunks: Vec<Result<bytes::Bytes, std::io::Error>> = vec![
+            Ok(bytes::Bytes::from_static(
+                b"event: message_start\ndata: {\"type\":\"message_start\",",
+            )),
+            Ok(bytes::Bytes::from_static(
+                b"\"message\":{\"id\":\"msg_01\"}}\n\nevent: ping\n",
+            )),
+            Ok(bytes::Bytes::from_static(b"data: {\"type\":\"ping\"}\n\n")),
+        ];
+        let (stream, outcome) = request_context::scope(
+            RequestContext::default(),
+            note_stream_response_ids(
+                futures_util::stream::iter(chunks),
+                ProviderKind::Anthropic,
+                "chatcmpl-7",
+            ),
+        )
+        .await;
+        let ids = outcome.response_ids.unwrap();
+        assert_eq!(ids.client_id, "chatcmpl-7");
+        assert_eq!(ids.upstream_id, "msg_01");
+        let replayed: Vec<_> = stream.collect().await;
+        assert_eq!(replayed.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_body_response_id_defaults_to_the_upstream_id() {
+        let ((), outcome) = request_context::scope(RequestContext::default(), async {
+            note_body_response_ids(
+                ProviderKind::Gemini,
+                br#"{"candidates":[],"responseId":"gem-9"}"#,
+                None,
+            );
+        })
+        .await;
+        let ids = outcome.response_ids.unwrap();
+        assert_eq!(ids.client_id, "gem-9");
+        assert_eq!(ids.upstream_id, "gem-9");
+    }
+}
@@ -0,0 +1,289 @@
+use axum::response::Response;
+use futures_util::StreamExt;
+
+use crate::error::CanonicalError;
+use crate::json_scan::find_top_level_field_value_range;
+use crate::protocol::canonical::IngressApi;
+use crate::stream::sse::sse_raw_frame_stream;
+
+/// Locations of the model name inside client-facing payloads, per ingress.
+///
+/// Each entry is a path of nested object keys; the first path that resolves
+/// to a JSON string is rewritten.
+const OPENAI_CHAT_MODEL_PATHS: &[&[&[u8]]] = &[&[b"model"]];
+const OPENAI_RESPONSES_MODEL_PATHS: &[&[&[u8]]] = &[&[b"model"], &[b"response", b"model"]];
+const ANTHROPIC_MODEL_PATHS: &[&[&[u8]]] = &[&[b"model"], &[b"message", b"model"]];
+const GEMINI_MODEL_PATHS: &[&[&[u8]]] = &[&[b"modelVersion"]];
+
+#[inline]
+fn model_paths(ingress: IngressApi) -> &'static [&'static [&'static [u8]]] {
+    match ingress {
+        IngressApi::OpenAiChat => OPENAI_CHAT_MODEL_PATHS,
+        IngressApi::OpenAiResponses => OPENAI_RESPONSES_MODEL_PATHS,
+        IngressApi::Anthropic => ANTHROPIC_MODEL_PATHS,
+        IngressApi::Gemini => GEMINI_MODEL_PATHS,
+    }
+}
+
+/// Rewrite the model reported by a finished client response to `client_model`.
+///
+/// Applies to non-streaming JSON bodies and to every frame of SSE bodies, so
+/// raw passthrough responses report the alias the client asked for instead of
+/// the upstream's real model name.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::Transport`] when a non-streaming body cannot be
+/// read or is over `max_body_bytes`.
+pub(crate) async fn rewrite_response_model(
+    response: Response,
+    ingress: IngressApi,
+    client_model: &str,
+    max_body_bytes: usize,
+) -> Result<Response, CanonicalError> {
+    if client_model.is_empty() || !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let quoted_model = bytes::Bytes::from(
+        serde_json::to_vec(client_model)
+            .map_err(|e| CanonicalError::Internal(format!("Failed to encode model: {e}")))?,
+    );
+    let (mut parts, body) = response.into_parts();
+
+    if is_sse {
+        let frames = sse_raw_frame_stream(body.into_data_stream()).map(move |frame| {
+            Ok::<_, std::convert::Infallible>(
+                rewrite_model_in_sse_frame(&frame, ingress, &quoted_model).unwrap_or(frame),
+            )
+        });
+        return Ok(Response::from_parts(
+            parts,
+            axum::body::Body::from_stream(frames),
+        ));
+    }
+
+    let body_bytes = axum::body::to_bytes(body, max_body_bytes)
+        .await
+        .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
+    let body_bytes = match rewrite_model_in_json_payload(&body_bytes, ingress, &quoted_model) {
+        Some(rewritten) => {
+            parts.headers.remove(http::header::CONTENT_LENGTH);
+            bytes::Bytes::from(rewritten)
+        }
+        None => body_bytes,
+    };
+    Ok(Response::from_parts(
+        parts,
+        axum::body::Body::from(body_bytes),
+    ))
+}
+
+/// Rewrite the model field of a JSON payload; arrays (Gemini JSON streams)
+/// are rewritten element by element.
+///
+/// Returns `None` when nothing needed to change.
+pub(crate) fn rewrite_model_in_json_payload(
+    payload: &[u8],
+    ingress: IngressApi,
+    quoted_model: &[u8],
+) -> Option<Vec<u8>> {
+    let start = crate::json_scan::skip_ws(payload, 0);
+    if payload.get(start) == Some(&b'[') {
+        return rewrite_model_in_json_array(payload, start, ingress, quoted_model);
+    }
+    let paths = model_paths(ingress);
+    paths
+        .iter()
+        .find_map(|path| rewrite_string_at_path(payload, path, quoted_model))
+}
+
+fn rewrite_model_in_json_array(
+    payload: &[u8],
+    start: usize,
+    ingress: IngressApi,
+    quoted_model: &[u8],
+) -> Option<Vec<u8>> {
+    let mut out: Option<Vec<u8>> = None;
+    let mut copied_up_to = 0usize;
+    let mut i = start + 1;
+    loop {
+        i = crate::json_scan::skip_ws(payload, i);
+        match payload.get(i) {
+            Some(b']') | None => break,
+            Some(b',') => {
+                i += 1;
+                continue;
+            }
+            Some(_) => {}
+        }
+        let element_end = crate::json_scan::parse_json_value_end(payload, i).ok()?;
+        if let Some(rewritten) =
+            rewrite_model_in_json_payload(&payload[i..element_end], ingress, quoted_model)
+        {
+            let buffer = out.get_or_insert_with(|| Vec::with_capacity(payload.len()));
+            buffer.extend_from_slice(&payload[copied_up_to..i]);
+            buffer.extend_from_slice(&rewritten);
+            copied_up_to = element_end;
+        }
+        i = element_end;
+    }
+    let mut buffer = out?;
+    buffer.extend_from_slice(&payload[copied_up_to..]);
+    Some(buffer)
+}
+
+fn rewrite_string_at_path(payload: &[u8], path: &[&[u8]], quoted_model: &[u8]) -> Option<Vec<u8>> {
+    let (first, rest) = path.split_first()?;
+    let range = find_top_level_field_value_range(payload, first).ok()??;
+    if rest.is_empty() {
+        let value = &payload[range.clone()];
+        if value.first() != Some(&b'"') || value == quoted_model {
+            return None;
+        }
+        let mut out = Vec::with_capacity(payload.len() - value.len() + quoted_model.len());
+        out.extend_from_slice(&payload[..range.start]);
+        out.extend_from_slice(quoted_model);
+        out.extend_from_slice(&payload[range.end..]);
+        return Some(out);
+    }
+
+    let nested = rewrite_string_at_path(&payload[range.clone()], rest, quoted_model)?;
+    let mut out = Vec::with_capacity(payload.len() - range.len() + nested.len());
+    out.extend_from_slice(&payload[..range.start]);
+    out.extend_from_slice(&nested);
+    out.extend_from_slice(&payload[range.end..]);
+    Some(out)
+}
+
+/// Byte range of the JSON object payload of a raw SSE frame's single
+/// `data:` line; `None` for frames without one (e.g. `[DONE]`).
+pub(crate) fn sse_frame_json_object_range(frame: &[u8]) -> Option<std::ops::Range<usize>> {
+    let data_start = if frame.starts_with(b"data:") {
+        0
+    } else {
+        memchr::memmem::find(frame, b"\ndata:")? + 1
+    };
+    let mut payload_start = data_start + b"data:".len();
+    if frame.get(payload_start) == Some(&b' ') {
+        payload_start += 1;
+    }
+    let payload_end = payload_start + memchr::memchr(b'\n', &frame[payload_start..])?;
+    let payload_end = if frame.get(payload_end - 1) == Some(&b'\r') {
+        payload_end - 1
+    } else {
+        payload_end
+    };
+    if frame.get(payload_start) != Some(&b'{') {
+        return None;
+    }
+    Some(payload_start..payload_end)
+}
+
+/// Rewrite the model field inside one raw SSE frame with a single `data:` line.
+///
+/// Returns `None` for frames without a JSON payload or without a model field.
+pub(crate) fn rewrite_model_in_sse_frame(
+    frame: &[u8],
+    ingress: IngressApi,
+    quoted_model: &[u8],
+) -> Option<bytes::Bytes> {
+    let std::ops::Range {
+        start: payload_start,
+        end: payload_end,
+    } = sse_frame_json_object_range(frame)?;
+    let payload = &frame[payload_start..payload_end];
+
+    let rewritten = rewrite_model_in_json_payload(payload, ingress, quoted_model)?;
+    let mut out = Vec::with_capacity(frame.len() - payload.len() + rewritten.len());
+    out.extend_from_slice(&frame[..payload_start]);
+    out.extend_from_slice(&rewritten);
+    out.extend_from_slice(&frame[payload_end..]);
+    Some(bytes::Bytes::from(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALIAS: &[u8] = br#""smart""#;
+
+    fn json_field<'a>(value: &'a serde_json::Value, pointer: &str) -> Option<&'a str> {
+        value.pointer(pointer).and_then(serde_json::Value::as_str)
+    }
+
+    #[test]
+    fn test_rewrite_openai_chat_payload_model() {
+        let body = br#"{"id":"x","model":"claude-3-5-sonnet","choices":[]}"#;
+        let out = rewrite_model_in_json_payload(body, IngressApi::OpenAiChat, ALIAS).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json_field(&json, "/model"), Some("smart"));
+    }
+
+    #[test]
+    fn test_rewrite_skips_when_model_already_matches() {
+        let body = br#"{"id":"x","model":"smart"}"#;
+        assert!(rewrite_model_in_json_payload(body, IngressApi::OpenAiChat, ALIAS).is_none());
+    }
+
+    #[test]
+    fn test_rewrite_anthropic_message_start_frame() {
+        let frame = b"event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"model\":\"claude-3-5-sonnet\"}}\n\n";
+        let out = rewrite_model_in_sse_frame(frame, IngressApi::Anthropic, ALIAS).unwrap();
+        let text = std::str::from_utf8(&out).unwrap();
+        assert!(text.starts_with("event: message_start\ndata: {"));
+        assert!(text.ends_with("}\n\n"));
+        assert!(text.contains(r#""model":"smart""#));
+        assert!(!text.contains("claude-3-5-sonnet"));
+    }
+
+    #[test]
+    fn test_rewrite_responses_nested_response_model_frame() {
+        let frame = b"event: response.created\r\ndata: {\"type\":\"response.created\",\"response\":{\"id\":\"resp_1\",\"model\":\"gpt-5\"}}\r\n\r\n";
+        let out = rewrite_model_in_sse_frame(frame, IngressApi::OpenAiResponses, ALIAS).unwrap();
+        let text = std::str::from_utf8(&out).unwrap();
+        assert!(text.contains(r#""model":"smart""#));
+        assert!(text.ends_with("}\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_rewrite_gemini_json_stream_array() {
+        let body = br#"[{"candidates":[],"modelVersion":"gemini-2.5-pro"},{"candidates":[],"modelVersion":"gemini-2.5-pro"}]"#;
+        let out = rewrite_model_in_json_payload(body, IngressApi::Gemini, ALIAS).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json_field(&json, "/0/modelVersion"), Some("smart"));
+        assert_eq!(json_field(&json, "/1/modelVersion"), Some("smart"));
+    }
+
+    #[test]
+    fn test_rewrite_sse_frame_ignores_done_marker() {
+        assert!(
+            rewrite_model_in_sse_frame(b"data: [DONE]\n\n", IngressApi::OpenAiChat, ALIAS)
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_non_streaming_body_over_the_limit_is_not_buffered() {
+        let body = br#"{"id":"x","model":"claude-3-5-sonnet","choices":[]}"#;
+        let response = || Response::new(axum::body::Body::from(&body[..]));
+
+        let rewritten = rewrite_response_model(response(), IngressApi::OpenAiChat, "smart", 1024)
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(rewritten.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(bytes.windows(ALIAS.len()).any(|window| window == ALIAS));
+
+        let err = rewrite_response_model(response(), IngressApi::OpenAiChat, "smart", 16)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CanonicalError::Transport(_)));
+    }
+}
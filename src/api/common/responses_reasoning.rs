@@ -0,0 +1,197 @@
+use axum::response::Response;
+use futures_util::{Stream, StreamExt};
+
+use crate::error::CanonicalError;
+use crate::json_scan::find_top_level_field_value_range;
+use crate::protocol::canonical::{CanonicalPart, CanonicalResponse};
+use crate::stream::sse::sse_raw_frame_stream;
+
+use super::response_model::sse_frame_json_object_range;
+
+/// Drop `reasoning` items from a decoded Responses upstream response.
+pub(crate) fn strip_reasoning_parts(response: &mut CanonicalResponse) {
+    response
+        .content
+        .retain(|part| !matches!(part, CanonicalPart::ReasoningItem { .. }));
+}
+
+/// Apply [`strip_reasoning_frames`] to an upstream stream only when `strip` is set.
+pub(crate) fn strip_reasoning_frames_if<S, E>(
+    byte_stream: S,
+    strip: bool,
+) -> impl Stream<Item = Result<bytes::Bytes, E>> + Send + 'static
+where
+    S: Stream<Item = Result<bytes::Bytes, E>> + Send + 'static,
+    E: std::fmt::Debug + Send + 'static,
+{
+    if strip {
+        strip_reasoning_frames(byte_stream).left_stream()
+    } else {
+        byte_stream.right_stream()
+    }
+}
+
+/// Drop reasoning events from a Responses upstream SSE stream before it is
+/// transcoded, and reasoning items from its terminal `response` object.
+fn strip_reasoning_frames<S, E>(
+    byte_stream: S,
+) -> impl Stream<Item = Result<bytes::Bytes, E>> + Send + 'static
+where
+    S: Stream<Item = Result<bytes::Bytes, E>> + Send + 'static,
+    E: std::fmt::Debug + Send + 'static,
+{
+    sse_raw_frame_stream(byte_stream)
+        .filter_map(|frame| std::future::ready(strip_reasoning_from_sse_frame(frame)))
+        .map(Ok)
+}
+
+/// Drop reasoning items and events from a finished Responses client response.
+///
+/// Covers raw passthrough responses, which never go through the canonical
+/// decoder, for both non-streaming JSON bodies and SSE bodies.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::Transport`] when a non-streaming body cannot be read.
+pub(crate) async fn strip_responses_reasoning(
+    response: Response,
+) -> Result<Response, CanonicalError> {
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let (mut parts, body) = response.into_parts();
+
+    if is_sse {
+        let frames = strip_reasoning_frames::<_, axum::Error>(body.into_data_stream());
+        parts.headers.remove(http::header::CONTENT_LENGTH);
+        return Ok(Response::from_parts(
+            parts,
+            axum::body::Body::from_stream(frames),
+        ));
+    }
+
+    let body_bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
+    let body_bytes = match strip_reasoning_output_items(&body_bytes) {
+        Some(stripped) => {
+            parts.headers.remove(http::header::CONTENT_LENGTH);
+            bytes::Bytes::from(stripped)
+        }
+        None => body_bytes,
+    };
+    Ok(Response::from_parts(
+        parts,
+        axum::body::Body::from(body_bytes),
+    ))
+}
+
+/// `None` drops the frame: reasoning deltas and reasoning item boundaries.
+/// Frames embedding a full `response` lose its reasoning output items.
+fn strip_reasoning_from_sse_frame(frame: bytes::Bytes) -> Option<bytes::Bytes> {
+    let Some(payload_range) = sse_frame_json_object_range(&frame) else {
+        return Some(frame);
+    };
+    let payload = &frame[payload_range.clone()];
+    if memchr::memmem::find(payload, b"reasoning").is_none() {
+        return Some(frame);
+    }
+    let event_type = top_level_string(payload, b"type").unwrap_or_default();
+    if event_type.starts_with(b"response.reasoning") {
+        return None;
+    }
+    if matches!(
+        event_type,
+        b"response.output_item.added" | b"response.output_item.done"
+    ) {
+        let is_reasoning_item = find_top_level_field_value_range(payload, b"item")
+            .ok()
+            .flatten()
+            .and_then(|item| top_level_string(&payload[item], b"type"))
+            == Some(b"reasoning".as_slice());
+        return (!is_reasoning_item).then_some(frame);
+    }
+    let Some(stripped) = strip_reasoning_output_items(payload) else {
+        return Some(frame);
+    };
+    let mut out = Vec::with_capacity(frame.len());
+    out.extend_from_slice(&frame[..payload_range.start]);
+    out.extend_from_slice(&stripped);
+    out.extend_from_slice(&frame[payload_range.end..]);
+    Some(bytes::Bytes::from(out))
+}
+
+/// Remove `reasoning` entries from the `output` array of a Responses object,
+/// or of the `response` object a stream event wraps.
+///
+/// Returns `None` when nothing needed to change.
+fn strip_reasoning_output_items(payload: &[u8]) -> Option<Vec<u8>> {
+    memchr::memmem::find(payload, br#""reasoning""#)?;
+    let mut value: serde_json::Value = serde_json::from_slice(payload).ok()?;
+    let output = match value.get_mut("response") {
+        Some(response) => response.get_mut("output"),
+        None => value.get_mut("output"),
+    }?
+    .as_array_mut()?;
+    let before = output.len();
+    output.retain(|item| item.get("type").and_then(serde_json::Value::as_str) != Some("reasoning"));
+    if output.len() == before {
+        return None;
+    }
+    serde_json::to_vec(&value).ok()
+}
+
+/// The raw (still escaped) contents of a top-level string field.
+fn top_level_string<'a>(payload: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    let range = find_top_level_field_value_range(payload, key).ok()??;
+    payload[range]
+        .strip_prefix(b"\"")
+        .and_then(|value| value.strip_suffix(b"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_reasoning_frames_and_output_items() {
+        let delta = bytes::Bytes::from_static(
+            b"event: response.reasoning_summary_text.delta\ndata: {\"type\":\"response.reasoning_summary_text.delta\",\"output_index\":0,\"delta\":\"think\"}\n\n",
+        );
+        assert!(strip_reasoning_from_sse_frame(delta).is_none());
+
+        let added = bytes::Bytes::from_static(
+            b"data: {\"type\":\"response.output_item.added\",\"output_index\":0,\"item\":{\"type\":\"reasoning\",\"id\":\"rs_1\",\"summary\":[]}}\n\n",
+        );
+        assert!(strip_reasoning_from_sse_frame(added).is_none());
+
+        let text = bytes::Bytes::from_static(
+            b"data: {\"type\":\"response.output_text.delta\",\"delta\":\"reasoning is fine here\"}\n\n",
+        );
+        assert_eq!(
+            strip_reasoning_from_sse_frame(text.clone()).as_ref(),
+            Some(&text)
+        );
+
+        let completed = bytes::Bytes::from_static(
+            b"event: response.completed\ndata: {\"type\":\"response.completed\",\"response\":{\"id\":\"resp_1\",\"output\":[{\"type\":\"reasoning\",\"id\":\"rs_1\",\"summary\":[],\"encrypted_content\":\"gAAA\"},{\"type\":\"message\",\"id\":\"msg_1\",\"role\":\"assistant\",\"content\":[]}]}}\n\n",
+        );
+        let stripped = strip_reasoning_from_sse_frame(completed).unwrap();
+        let stripped = std::str::from_utf8(&stripped).unwrap();
+        assert!(stripped.starts_with("event: response.completed\ndata: {"));
+        assert!(stripped.ends_with("}\n\n"));
+        assert!(!stripped.contains("encrypted_content"));
+        assert!(stripped.contains("\"msg_1\""));
+
+        let body = br#"{"id":"resp_1","output":[{"type":"reasoning","id":"rs_1","summary":[]}]}"#;
+        let stripped: serde_json::Value =
+            serde_json::from_slice(&strip_reasoning_output_items(body).unwrap()).unwrap();
+        assert_eq!(stripped["output"], serde_json::json!([]));
+        assert!(strip_reasoning_output_items(br#"{"id":"resp_1","output":[]}"#).is_none());
+    }
+}
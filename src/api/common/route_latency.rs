@@ -0,0 +1,89 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::response::Response;
+use futures_util::Stream;
+
+use crate::observability::journal::UsageScanner;
+use crate::state::AppState;
+
+/// Where a streamed response's latency sample is recorded.
+pub(crate) struct RouteLatencyProbe {
+    pub(crate) state: Arc<AppState>,
+    pub(crate) upstream_index: usize,
+    pub(crate) model_group: String,
+    pub(crate) started: Instant,
+}
+
+/// Measure TTFB and decode tokens/sec of a successful SSE response.
+///
+/// TTFB runs from `probe.started` to the first body chunk; the decode rate
+/// divides the reported output tokens by the time from the first chunk to the
+/// end of the stream. Non-streaming, failed, and abandoned responses are not recorded.
+pub(crate) fn tap_route_latency(response: Response, probe: RouteLatencyProbe) -> Response {
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    if !is_sse || !response.status().is_success() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let tap = LatencyTapStream {
+        inner: body.into_data_stream(),
+        probe: Some(probe),
+        first_chunk: None,
+        scanner: UsageScanner::new(true),
+    };
+    Response::from_parts(parts, axum::body::Body::from_stream(tap))
+}
+
+pin_project_lite::pin_project! {
+    struct LatencyTapStream<S> {
+        #[pin]
+        inner: S,
+        probe: Option<RouteLatencyProbe>,
+        first_chunk: Option<Instant>,
+        scanner: UsageScanner,
+    }
+}
+
+impl<S, E> Stream for LatencyTapStream<S>
+where
+    S: Stream<Item = Result<bytes::Bytes, E>>,
+{
+    type Item = Result<bytes::Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let polled = this.inner.poll_next(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(chunk))) if !chunk.is_empty() => {
+                if this.first_chunk.is_none() {
+                    *this.first_chunk = Some(Instant::now());
+                }
+                this.scanner.feed(chunk);
+            }
+            Poll::Ready(None) => {
+                if let (Some(probe), Some(first_chunk)) = (this.probe.take(), *this.first_chunk) {
+                    let usage = this.scanner.finish();
+                    probe.state.record_route_latency(
+                        probe.upstream_index,
+                        &probe.model_group,
+                        first_chunk.saturating_duration_since(probe.started),
+                        first_chunk.elapsed(),
+                        usage.output_tokens,
+                    );
+                }
+            }
+            Poll::Ready(Some(Err(_))) => {
+                this.probe.take();
+            }
+            Poll::Ready(Some(Ok(_))) | Poll::Pending => {}
+        }
+        polled
+    }
+}
@@ -0,0 +1,154 @@
+use crate::config::SamplingNormalization;
+use crate::error::CanonicalError;
+use crate::protocol::canonical::{GenerationParams, IngressApi, ProviderKind};
+use crate::protocol::mapping::{ingress_max_temperature, provider_max_temperature, MAX_TOP_P};
+
+/// Adapt `temperature` / `top_p` to the range `provider` accepts.
+///
+/// Returns `None` when `generation` can be sent unchanged. Every adjustment
+/// is logged.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::InvalidRequest`] when `strategy` is
+/// [`SamplingNormalization::Reject`] and a value is out of range.
+pub(crate) fn normalize_sampling_params(
+    generation: &GenerationParams,
+    ingress: IngressApi,
+    provider: ProviderKind,
+    strategy: SamplingNormalization,
+) -> Result<Option<GenerationParams>, CanonicalError> {
+    let max_temperature = provider_max_temperature(provider);
+    let temperature = generation
+        .temperature
+        .map(|value| {
+            let target = match strategy {
+                SamplingNormalization::Scale => {
+                    value * max_temperature / ingress_max_temperature(ingress)
+                }
+                SamplingNormalization::Clamp | SamplingNormalization::Reject => value,
+            };
+            normalize_value(
+                "temperature",
+                value,
+                target,
+                max_temperature,
+                provider,
+                strategy,
+            )
+        })
+        .transpose()?;
+    let top_p = generation
+        .top_p
+        .map(|value| normalize_value("top_p", value, value, MAX_TOP_P, provider, strategy))
+        .transpose()?;
+
+    if temperature == generation.temperature && top_p == generation.top_p {
+        return Ok(None);
+    }
+    Ok(Some(GenerationParams {
+        temperature,
+        top_p,
+        ..generation.clone()
+    }))
+}
+
+fn normalize_value(
+    param: &str,
+    value: f64,
+    target: f64,
+    max: f64,
+    provider: ProviderKind,
+    strategy: SamplingNormalization,
+) -> Result<f64, CanonicalError> {
+    if strategy == SamplingNormalization::Reject && !(0.0..=max).contains(&value) {
+        return Err(CanonicalError::InvalidRequest(format!(
+            "{param} {value} is outside the range [0, {max}] accepted by {provider:?}"
+        )));
+    }
+    let normalized = target.clamp(0.0, max);
+    if normalized.to_bits() != value.to_bits() {
+        tracing::info!(
+            provider = ?provider,
+            param,
+            from = value,
+            to = normalized,
+            "adjusted sampling parameter for upstream range"
+        );
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(temperature: f64, top_p: f64) -> GenerationParams {
+        GenerationParams {
+            temperature: Some(temperature),
+            top_p: Some(top_p),
+            ..GenerationParams::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize_sampling_params_per_strategy() {
+        let in_range = params(0.7, 0.9);
+        assert!(normalize_sampling_params(
+            &in_range,
+            IngressApi::OpenAiChat,
+            ProviderKind::Anthropic,
+            SamplingNormalization::Clamp
+        )
+        .unwrap()
+        .is_none());
+
+        let hot = params(1.6, 1.2);
+        let clamped = normalize_sampling_params(
+            &hot,
+            IngressApi::OpenAiChat,
+            ProviderKind::Anthropic,
+            SamplingNormalization::Clamp,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(clamped.temperature, Some(1.0));
+        assert_eq!(clamped.top_p, Some(1.0));
+
+        let scaled = normalize_sampling_params(
+            &hot,
+            IngressApi::OpenAiChat,
+            ProviderKind::Anthropic,
+            SamplingNormalization::Scale,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(scaled.temperature, Some(0.8));
+
+        let scaled_up = normalize_sampling_params(
+            &params(0.5, 0.5),
+            IngressApi::Anthropic,
+            ProviderKind::OpenAi,
+            SamplingNormalization::Scale,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(scaled_up.temperature, Some(1.0));
+
+        let err = normalize_sampling_params(
+            &hot,
+            IngressApi::OpenAiChat,
+            ProviderKind::Anthropic,
+            SamplingNormalization::Reject,
+        )
+        .unwrap_err();
+        assert!(matches!(err, CanonicalError::InvalidRequest(_)));
+        assert!(normalize_sampling_params(
+            &hot,
+            IngressApi::OpenAiChat,
+            ProviderKind::OpenAi,
+            SamplingNormalization::Reject
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use axum::response::{IntoResponse, Json, Response};
+use futures_util::StreamExt;
+use serde_json::Value;
+
+use crate::error::CanonicalError;
+use crate::protocol::anthropic::response_encoder::encode_anthropic_response;
+use crate::protocol::canonical::{
+    CanonicalPart, CanonicalResponse, CanonicalStopReason, CanonicalStreamEvent, CanonicalUsage,
+    IngressApi, ProviderExtensions, ProviderKind,
+};
+use crate::protocol::gemini::response_encoder::encode_gemini_response;
+use crate::protocol::openai_chat::response_encoder::encode_openai_chat_response;
+use crate::protocol::openai_responses::response_encoder::encode_responses_output;
+use crate::stream::memory_budget::BufferCharge;
+use crate::stream::{sse_frame_stream, StreamTranscoder};
+use crate::util::raw_value_from_string;
+
+/// Turn a non-streaming request body into the equivalent streaming request.
+///
+/// Gemini selects streaming by URL, so its body is returned unchanged.
+/// `OpenAI` Chat additionally asks for the usage chunk so it can be reported.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::InvalidRequest`] when the body is not a JSON object.
+pub(crate) fn streaming_request_body(
+    body: &bytes::Bytes,
+    ingress: IngressApi,
+) -> Result<bytes::Bytes, CanonicalError> {
+    if ingress == IngressApi::Gemini {
+        return Ok(body.clone());
+    }
+    let mut payload: serde_json::Map<String, Value> = serde_json::from_slice(body)
+        .map_err(|e| CanonicalError::InvalidRequest(format!("Invalid JSON body: {e}")))?;
+    payload.insert("stream".to_string(), Value::Bool(true));
+    if ingress == IngressApi::OpenAiChat {
+        payload.insert(
+            "stream_options".to_string(),
+            serde_json::json!({ "include_usage": true }),
+        );
+    }
+    serde_json::to_vec(&payload)
+        .map(bytes::Bytes::from)
+        .map_err(|e| CanonicalError::Internal(format!("Failed to encode request: {e}")))
+}
+
+/// Collect a client-format SSE response into one non-streaming response.
+///
+/// Text, reasoning, tool calls, usage, and the stop reason are assembled into
+/// a canonical response and re-encoded in the `ingress` format. Error and
+/// non-SSE responses are returned unchanged.
+///
+/// # Errors
+///
+/// Returns the stream's in-band error, or [`CanonicalError::Translation`] when
+/// tool call arguments are not JSON or the response cannot be encoded.
+pub(crate) async fn aggregate_stream_response(
+    response: Response,
+    ingress: IngressApi,
+    client_model: &str,
+) -> Result<Response, CanonicalError> {
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    if !is_sse || !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let mut decoder = StreamTranscoder::new(
+        ingress_wire_provider(ingress),
+        ingress,
+        client_model.to_string(),
+        String::new(),
+    );
+    let mut aggregate = StreamAggregate::default();
+    let mut frames = std::pin::pin!(sse_frame_stream(response.into_body().into_data_stream()));
+    let mut events = Vec::with_capacity(8);
+    // The aggregate grows with the stream until it is encoded.
+    let mut charge = BufferCharge::default();
+    let mut aggregated_bytes = 0;
+    while let Some(frame) = frames.next().await {
+        aggregated_bytes += frame.data.len();
+        charge.track(aggregated_bytes);
+        if aggregate.id.is_empty() {
+            aggregate.id = response_id(ingress, &frame.data).unwrap_or_default();
+        }
+        decoder.decode_upstream_frame_into(&frame, &mut events);
+        for event in events.drain(..) {
+            aggregate.apply(event)?;
+        }
+    }
+
+    let canonical = aggregate.into_response(client_model)?;
+    encode_client_response(&canonical, ingress, client_model)
+}
+
+/// Encode a canonical response in the non-streaming format of `ingress`.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::Translation`] when the response cannot be encoded.
+pub(super) fn encode_client_response(
+    canonical: &CanonicalResponse,
+    ingress: IngressApi,
+    client_model: &str,
+) -> Result<Response, CanonicalError> {
+    match ingress {
+        IngressApi::OpenAiChat => {
+            Ok(Json(encode_openai_chat_response(canonical, client_model)?).into_response())
+        }
+        IngressApi::OpenAiResponses => {
+            Ok(Json(encode_responses_output(canonical, client_model)?).into_response())
+        }
+        IngressApi::Anthropic => {
+            Ok(Json(encode_anthropic_response(canonical, client_model)?).into_response())
+        }
+        IngressApi::Gemini => Ok(Json(encode_gemini_response(canonical)?).into_response()),
+    }
+}
+
+/// The provider whose wire format matches what `ingress` clients receive.
+pub(super) fn ingress_wire_provider(ingress: IngressApi) -> ProviderKind {
+    match ingress {
+        IngressApi::OpenAiChat => ProviderKind::OpenAi,
+        IngressApi::OpenAiResponses => ProviderKind::OpenAiResponses,
+        IngressApi::Anthropic => ProviderKind::Anthropic,
+        IngressApi::Gemini => ProviderKind::Gemini,
+    }
+}
+
+pub(super) fn response_id(ingress: IngressApi, data: &str) -> Option<String> {
+    let payload: Value = serde_json::from_str(data).ok()?;
+    let pointer = match ingress {
+        IngressApi::OpenAiChat => "/id",
+        IngressApi::OpenAiResponses => "/response/id",
+        IngressApi::Anthropic => "/message/id",
+        IngressApi::Gemini => "/responseId",
+    };
+    payload.pointer(pointer)?.as_str().map(str::to_string)
+}
+
+struct AggregatedToolCall {
+    index: usize,
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+#[derive(Default)]
+struct StreamAggregate {
+    id: String,
+    reasoning: String,
+    text: String,
+    tool_calls: Vec<AggregatedToolCall>,
+    usage: CanonicalUsage,
+    stop_reason: Option<CanonicalStopReason>,
+}
+
+impl StreamAggregate {
+    fn apply(&mut self, event: CanonicalStreamEvent) -> Result<(), CanonicalError> {
+        match event {
+            CanonicalStreamEvent::TextDelta(delta) => self.text.push_str(&delta),
+            CanonicalStreamEvent::ReasoningDelta(delta) => self.reasoning.push_str(&delta),
+            CanonicalStreamEvent::ToolCallStart { index, id, name } => {
+                self.tool_calls.push(AggregatedToolCall {
+                    index,
+                    id,
+                    name,
+                    arguments: String::new(),
+                });
+            }
+            CanonicalStreamEvent::ToolCallArgsDelta { index, delta } => {
+                if let Some(call) = self
+                    .tool_calls
+                    .iter_mut()
+                    .rev()
+                    .find(|call| call.index == index)
+                {
+                    call.arguments.push_str(&delta);
+                }
+            }
+            CanonicalStreamEvent::Usage(usage) => {
+                self.usage.input_tokens = usage.input_tokens.or(self.usage.input_tokens);
+                self.usage.output_tokens = usage.output_tokens.or(self.usage.output_tokens);
+                self.usage.total_tokens = usage.total_tokens.or(self.usage.total_tokens);
+                self.usage.cached_input_tokens =
+                    usage.cached_input_tokens.or(self.usage.cached_input_tokens);
+                self.usage.cache_creation_input_tokens = usage
+                    .cache_creation_input_tokens
+                    .or(self.usage.cache_creation_input_tokens);
+                self.usage.reasoning_tokens =
+                    usage.reasoning_tokens.or(self.usage.reasoning_tokens);
+            }
+            CanonicalStreamEvent::MessageEnd { stop_reason } => {
+                self.stop_reason = Some(stop_reason);
+            }
+            CanonicalStreamEvent::Error { status, message } => {
+                return Err(CanonicalError::Upstream {
+                    status,
+                    message,
+                    retry_after: None,
+                });
+            }
+            CanonicalStreamEvent::MessageStart { .. }
+            | CanonicalStreamEvent::ToolCallEnd { .. }
+            | CanonicalStreamEvent::ToolResult { .. }
+            | CanonicalStreamEvent::Done => {}
+        }
+        Ok(())
+    }
+
+    fn into_response(self, client_model: &str) -> Result<CanonicalResponse, CanonicalError> {
+        let mut content = Vec::with_capacity(2 + self.tool_calls.len());
+        if !self.reasoning.is_empty() {
+            content.push(CanonicalPart::ReasoningText(self.reasoning));
+        }
+        if !self.text.is_empty() {
+            content.push(CanonicalPart::Text(self.text));
+        }
+        let has_tool_calls = !self.tool_calls.is_empty();
+        for call in self.tool_calls {
+            let arguments = if call.arguments.trim().is_empty() {
+                "{}".to_string()
+            } else {
+                call.arguments
+            };
+            content.push(CanonicalPart::ToolCall {
+                id: call.id,
+                name: call.name,
+                arguments: raw_value_from_string(arguments, "aggregated stream tool call")?,
+            });
+        }
+        let stop_reason = self.stop_reason.unwrap_or(if has_tool_calls {
+            CanonicalStopReason::ToolCalls
+        } else {
+            CanonicalStopReason::EndOfTurn
+        });
+        Ok(CanonicalResponse {
+            id: self.id,
+            model: client_model.to_string(),
+            content,
+            stop_reason,
+            usage: self.usage,
+            citations: Vec::new(),
+            provider_extensions: ProviderExtensions::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_openai_chat_stream_is_aggregated_with_tool_calls_and_usage() {
+        let sse = concat!(
+            "data: {\"id\":\"chatcmpl-9\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"finish_reason\":null}]}\n\n",
+            "data: {\"id\":\"chatcmpl-9\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"},\"finish_reason\":null}]}\n\n",
+            "data: {\"id\":\"chatcmpl-9\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"lookup\",\"arguments\":\"{\\\"q\\\":\"}}]},\"finish_reason\":null}]}\n\n",
+            "data: {\"id\":\"chatcmpl-9\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"x\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+            "data: {\"id\":\"chatcmpl-9\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[],\"usage\":{\"prompt_tokens\":7,\"completion_tokens\":3,\"total_tokens\":10}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let response = Response::builder()
+            .header(http::header::CONTENT_TYPE, "text/event-stream")
+            .body(axum::body::Body::from(sse))
+            .unwrap();
+
+        let aggregated = aggregate_stream_response(response, IngressApi::OpenAiChat, "client-m")
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(aggregated.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let payload: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(payload["id"], "chatcmpl-9");
+        assert_eq!(payload["object"], "chat.completion");
+        assert_eq!(payload["model"], "client-m");
+        let choice = &payload["choices"][0];
+        assert_eq!(choice["message"]["content"], "Hello");
+        assert_eq!(
+            choice["message"]["tool_calls"][0]["function"]["arguments"],
+            "{\"q\":\"x\"}"
+        );
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert_eq!(payload["usage"]["total_tokens"], 10);
+    }
+
+    #[test]
+    fn test_streaming_request_body_sets_stream_flag() {
+        let body = bytes::Bytes::from_static(br#"{"model":"m","stream":false}"#);
+        let chat: Value =
+            serde_json::from_slice(&streaming_request_body(&body, IngressApi::OpenAiChat).unwrap())
+                .unwrap();
+        assert_eq!(chat["stream"], true);
+        assert_eq!(chat["stream_options"]["include_usage"], true);
+        let anthropic: Value =
+            serde_json::from_slice(&streaming_request_body(&body, IngressApi::Anthropic).unwrap())
+                .unwrap();
+        assert_eq!(anthropic["stream"], true);
+        assert!(anthropic.get("stream_options").is_none());
+    }
+}
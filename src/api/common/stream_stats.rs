@@ -0,0 +1,184 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::response::Response;
+use bytes::Bytes;
+use futures_util::Stream;
+use serde_json::json;
+
+use crate::observability::journal::UsageScanner;
+use crate::stream::sse::sse_raw_frame_stream;
+
+/// Request header opting a stream into the final `toolify_stats` frame.
+pub(crate) const STREAM_STATS_HEADER: &str = "x-toolify-stream-stats";
+
+/// Whether the client asked for stream stats with `x-toolify-stream-stats`.
+pub(crate) fn stream_stats_requested(headers: &http::HeaderMap) -> bool {
+    headers
+        .get(STREAM_STATS_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            let value = value.trim();
+            value == "1" || value.eq_ignore_ascii_case("true")
+        })
+}
+
+/// What the `toolify_stats` frame reports besides timing and usage.
+pub(crate) struct StreamStats {
+    pub(crate) started: Instant,
+    /// Serving upstream, when policy allows naming it.
+    pub(crate) upstream: Option<String>,
+    pub(crate) retries: u32,
+}
+
+/// Send a `data: {"toolify_stats": {...}}` frame at the end of a successful
+/// SSE response, before its `[DONE]` frame if it has one.
+///
+/// The frame carries the total and first-byte latency from `stats.started`,
+/// the retry count, the token usage reported by the stream, and the upstream
+/// name when set.
+pub(crate) fn append_stream_stats(response: Response, stats: StreamStats) -> Response {
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    if !is_sse || !response.status().is_success() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let trailer = StatsTrailerStream {
+        inner: sse_raw_frame_stream(body.into_data_stream()),
+        stats: Some(stats),
+        first_chunk: None,
+        scanner: UsageScanner::new(true),
+        done_frame: None,
+    };
+    Response::from_parts(parts, axum::body::Body::from_stream(trailer))
+}
+
+pin_project_lite::pin_project! {
+    struct StatsTrailerStream<S> {
+        #[pin]
+        inner: S,
+        stats: Option<StreamStats>,
+        first_chunk: Option<Instant>,
+        scanner: UsageScanner,
+        // `[DONE]` frame held back until the stats frame is sent.
+        done_frame: Option<Bytes>,
+    }
+}
+
+impl<S> Stream for StatsTrailerStream<S>
+where
+    S: Stream<Item = Bytes>,
+{
+    type Item = Result<Bytes, std::convert::Infallible>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        if let Some(done) = this.done_frame.take() {
+            return Poll::Ready(Some(Ok(done)));
+        }
+        let frame = match this.inner.poll_next(cx) {
+            Poll::Ready(Some(frame)) => frame,
+            Poll::Ready(None) => {
+                let trailer = this
+                    .stats
+                    .take()
+                    .map(|stats| stats_frame(&stats, *this.first_chunk, this.scanner));
+                return Poll::Ready(trailer.map(Ok));
+            }
+            Poll::Pending => return Poll::Pending,
+        };
+        if this.first_chunk.is_none() {
+            *this.first_chunk = Some(Instant::now());
+        }
+        if is_done_frame(&frame) {
+            if let Some(stats) = this.stats.take() {
+                *this.done_frame = Some(frame);
+                return Poll::Ready(Some(Ok(stats_frame(
+                    &stats,
+                    *this.first_chunk,
+                    this.scanner,
+                ))));
+            }
+        }
+        this.scanner.feed(&frame);
+        Poll::Ready(Some(Ok(frame)))
+    }
+}
+
+fn is_done_frame(frame: &[u8]) -> bool {
+    let frame = frame.trim_ascii();
+    frame
+        .strip_prefix(b"data:")
+        .is_some_and(|data| data.trim_ascii() == b"[DONE]")
+}
+
+fn stats_frame(
+    stats: &StreamStats,
+    first_chunk: Option<Instant>,
+    scanner: &mut UsageScanner,
+) -> Bytes {
+    let usage = scanner.finish();
+    let mut payload = json!({
+        "latency_ms": u64::try_from(stats.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        "ttfb_ms": first_chunk.map(|first_chunk| {
+            u64::try_from(first_chunk.saturating_duration_since(stats.started).as_millis())
+                .unwrap_or(u64::MAX)
+        }),
+        "retries": stats.retries,
+        "usage": {
+            "input_tokens": usage.input_tokens,
+            "output_tokens": usage.output_tokens,
+        },
+    });
+    if let Some(upstream) = &stats.upstream {
+        payload["upstream"] = json!(upstream);
+    }
+    Bytes::from(format!("data: {}\n\n", json!({ "toolify_stats": payload })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stats_frame_precedes_done_and_reports_usage() {
+        let sse = concat!(
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"hi\"}}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":3,\"completion_tokens\":2}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let response = Response::builder()
+            .header(http::header::CONTENT_TYPE, "text/event-stream")
+            .body(axum::body::Body::from(sse))
+            .unwrap();
+        let stats = StreamStats {
+            started: Instant::now(),
+            upstream: Some("openai".to_string()),
+            retries: 1,
+        };
+        let body = append_stream_stats(response, stats).into_body();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let text = std::str::from_utf8(&bytes).unwrap();
+        let (before_done, after_done) = text.split_once("data: [DONE]").unwrap();
+        assert_eq!(after_done, "\n\n");
+        let frame = before_done.trim_end().rsplit("\n\n").next().unwrap();
+        let payload: serde_json::Value =
+            serde_json::from_str(frame.strip_prefix("data: ").unwrap()).unwrap();
+        let stats = &payload["toolify_stats"];
+        assert_eq!(stats["upstream"], "openai");
+        assert_eq!(stats["retries"], 1);
+        assert_eq!(stats["usage"]["input_tokens"], 3);
+        assert_eq!(stats["usage"]["output_tokens"], 2);
+        assert!(stats["ttfb_ms"].is_u64());
+
+        let mut headers = http::HeaderMap::new();
+        assert!(!stream_stats_requested(&headers));
+        headers.insert(STREAM_STATS_HEADER, http::HeaderValue::from_static("TRUE"));
+        assert!(stream_stats_requested(&headers));
+    }
+}
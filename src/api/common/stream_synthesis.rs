@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use axum::response::Response;
+use serde_json::Value;
+
+use crate::error::CanonicalError;
+use crate::protocol::canonical::{
+    CanonicalPart, CanonicalResponse, CanonicalRole, CanonicalStreamEvent, IngressApi,
+    REASONING_SUMMARY_SEPARATOR,
+};
+use crate::stream::StreamTranscoder;
+
+use super::codec::decode_response_from_provider;
+use super::stream_aggregate::ingress_wire_provider;
+use super::streaming::sse_ok_response;
+
+/// Turn a streaming request body into the equivalent non-streaming request.
+///
+/// Gemini selects streaming by URL, so its body is returned unchanged.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::InvalidRequest`] when the body is not a JSON object.
+pub(crate) fn non_streaming_request_body(
+    body: &bytes::Bytes,
+    ingress: IngressApi,
+) -> Result<bytes::Bytes, CanonicalError> {
+    if ingress == IngressApi::Gemini {
+        return Ok(body.clone());
+    }
+    let mut payload: serde_json::Map<String, Value> = serde_json::from_slice(body)
+        .map_err(|e| CanonicalError::InvalidRequest(format!("Invalid JSON body: {e}")))?;
+    payload.insert("stream".to_string(), Value::Bool(false));
+    payload.remove("stream_options");
+    serde_json::to_vec(&payload)
+        .map(bytes::Bytes::from)
+        .map_err(|e| CanonicalError::Internal(format!("Failed to encode request: {e}")))
+}
+
+/// How a complete response is replayed as a stream.
+pub(crate) struct SyntheticStreamPacing {
+    /// Characters per text/reasoning delta.
+    pub(crate) chunk_chars: usize,
+    /// Pause between frames.
+    pub(crate) interval: Duration,
+    /// Emit usage; `OpenAI` Chat clients only get it with `include_usage`.
+    pub(crate) include_usage: bool,
+}
+
+/// Replay a complete client-format response as an SSE stream.
+///
+/// Text and reasoning are split into deltas of `pacing.chunk_chars`
+/// characters; tool calls are sent whole. Error responses are returned
+/// unchanged.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::Transport`] when the body cannot be read, or
+/// [`CanonicalError::Translation`] when it cannot be decoded.
+pub(crate) async fn synthesize_stream_response(
+    response: Response,
+    ingress: IngressApi,
+    client_model: &str,
+    pacing: SyntheticStreamPacing,
+) -> Result<Response, CanonicalError> {
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
+    let canonical = decode_response_from_provider(ingress_wire_provider(ingress), &body)?;
+
+    let mut transcoder = StreamTranscoder::new(
+        ingress_wire_provider(ingress),
+        ingress,
+        client_model.to_string(),
+        canonical.id.clone(),
+    );
+    let mut frames: VecDeque<bytes::Bytes> = synthetic_events(canonical, &pacing)
+        .iter()
+        .filter_map(|event| transcoder.encode_client_event_bytes(event))
+        .collect();
+    frames.extend(transcoder.finish().map(bytes::Bytes::from));
+
+    let interval = pacing.interval;
+    let paced =
+        futures_util::stream::unfold((frames, true), move |(mut frames, first)| async move {
+            let frame = frames.pop_front()?;
+            if !first && !interval.is_zero() {
+                tokio::time::sleep(interval).await;
+            }
+            Some((Ok::<_, std::convert::Infallible>(frame), (frames, false)))
+        });
+    Ok(sse_ok_response(axum::body::Body::from_stream(paced)))
+}
+
+fn synthetic_events(
+    canonical: CanonicalResponse,
+    pacing: &SyntheticStreamPacing,
+) -> Vec<CanonicalStreamEvent> {
+    let mut events = vec![CanonicalStreamEvent::MessageStart {
+        role: CanonicalRole::Assistant,
+    }];
+    let mut tool_index = 0usize;
+    for part in canonical.content {
+        match part {
+            CanonicalPart::ReasoningText(text) => events.extend(
+                text_chunks(&text, pacing.chunk_chars)
+                    .map(|chunk| CanonicalStreamEvent::ReasoningDelta(chunk.to_string())),
+            ),
+            CanonicalPart::ReasoningItem { summary, .. } => {
+                let text = summary.join(REASONING_SUMMARY_SEPARATOR);
+                events.extend(
+                    text_chunks(&text, pacing.chunk_chars)
+                        .map(|chunk| CanonicalStreamEvent::ReasoningDelta(chunk.to_string())),
+                );
+            }
+            CanonicalPart::Text(text) | CanonicalPart::Refusal(text) => events.extend(
+                text_chunks(&text, pacing.chunk_chars)
+                    .map(|chunk| CanonicalStreamEvent::TextDelta(chunk.to_string())),
+            ),
+            CanonicalPart::ToolCall {
+                id,
+                name,
+                arguments,
+            } => {
+                events.push(CanonicalStreamEvent::ToolCallStart {
+                    index: tool_index,
+                    id: id.clone(),
+                    name: name.clone(),
+                });
+                events.push(CanonicalStreamEvent::ToolCallArgsDelta {
+                    index: tool_index,
+                    delta: arguments.get().to_string(),
+                });
+                events.push(CanonicalStreamEvent::ToolCallEnd {
+                    index: tool_index,
+                    call_id: Some(id),
+                    call_name: Some(name),
+                });
+                tool_index += 1;
+            }
+            CanonicalPart::ImageUrl { .. } | CanonicalPart::ToolResult { .. } => {}
+        }
+    }
+    if pacing.include_usage {
+        events.push(CanonicalStreamEvent::Usage(canonical.usage));
+    }
+    events.push(CanonicalStreamEvent::MessageEnd {
+        stop_reason: canonical.stop_reason,
+    });
+    events.push(CanonicalStreamEvent::Done);
+    events
+}
+
+/// Split `text` into pieces of at most `chunk_chars` characters.
+fn text_chunks(text: &str, chunk_chars: usize) -> impl Iterator<Item = &str> {
+    let chunk_chars = chunk_chars.max(1);
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest
+            .char_indices()
+            .nth(chunk_chars)
+            .map_or(rest.len(), |(end, _)| end);
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_chunks_respect_char_boundaries() {
+        let chunks: Vec<&str> = text_chunks("héllo wörld", 4).collect();
+        assert_eq!(chunks, vec!["héll", "o wö", "rld"]);
+        assert_eq!(text_chunks("", 4).count(), 0);
+        assert_eq!(text_chunks("ab", 0).count(), 2);
+    }
+}
@@ -0,0 +1,95 @@
+use axum::response::Response;
+use futures_util::StreamExt;
+
+use crate::json_scan::{find_top_level_field_value_range, skip_ws};
+use crate::stream::sse::sse_raw_frame_stream;
+
+use super::response_model::sse_frame_json_object_range;
+use super::streaming::TranscodedStream;
+
+/// Whether an `OpenAI` Chat request asks for the final usage chunk via
+/// `stream_options.include_usage`.
+pub(crate) fn stream_usage_requested(body: &[u8]) -> bool {
+    let Ok(Some(options)) = find_top_level_field_value_range(body, b"stream_options") else {
+        return false;
+    };
+    let options = &body[options];
+    matches!(
+        find_top_level_field_value_range(options, b"include_usage"),
+        Ok(Some(range)) if options.get(range.start..range.end) == Some(b"true".as_slice())
+    )
+}
+
+/// Drop the usage-only chunk from a transcoded `OpenAI` Chat stream when the
+/// client did not ask for it.
+///
+/// Transcoded streams always carry usage (it is requested from `OpenAI`
+/// upstreams and synthesized from canonical usage otherwise); passthrough
+/// streams already follow the client's own `stream_options`.
+pub(crate) fn strip_unrequested_stream_usage(response: Response) -> Response {
+    if response.extensions().get::<TranscodedStream>().is_none() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let frames = sse_raw_frame_stream(body.into_data_stream())
+        .filter(|frame| std::future::ready(!is_usage_only_chunk(frame)))
+        .map(Ok::<_, std::convert::Infallible>);
+    Response::from_parts(parts, axum::body::Body::from_stream(frames))
+}
+
+/// A chat completion chunk with empty `choices` and a `usage` object.
+fn is_usage_only_chunk(frame: &[u8]) -> bool {
+    let Some(payload) = sse_frame_json_object_range(frame) else {
+        return false;
+    };
+    let payload = &frame[payload];
+    let Ok(Some(choices)) = find_top_level_field_value_range(payload, b"choices") else {
+        return false;
+    };
+    let choices = &payload[choices];
+    let empty_choices =
+        choices.first() == Some(&b'[') && choices.get(skip_ws(choices, 1)) == Some(&b']');
+    empty_choices
+        && matches!(
+            find_top_level_field_value_range(payload, b"usage"),
+            Ok(Some(range)) if payload.get(range.start) == Some(&b'{')
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_usage_requested_reads_include_usage() {
+        assert!(stream_usage_requested(
+            br#"{"model":"m","stream":true,"stream_options":{"include_usage":true}}"#
+        ));
+        assert!(!stream_usage_requested(
+            br#"{"model":"m","stream":true,"stream_options":{"include_usage":false}}"#
+        ));
+        assert!(!stream_usage_requested(br#"{"model":"m","stream":true}"#));
+    }
+
+    #[tokio::test]
+    async fn test_strip_drops_only_usage_chunk_of_transcoded_stream() {
+        let sse = concat!(
+            "data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"hi\"}}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":3}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let mut response = Response::new(axum::body::Body::from(sse));
+        response.extensions_mut().insert(TranscodedStream);
+        let body = strip_unrequested_stream_usage(response).into_body();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let text = std::str::from_utf8(&bytes).unwrap();
+        assert!(text.contains("\"hi\""));
+        assert!(text.contains("[DONE]"));
+        assert!(!text.contains("usage"));
+
+        let passthrough = Response::new(axum::body::Body::from(sse));
+        let body = strip_unrequested_stream_usage(passthrough).into_body();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        assert_eq!(bytes, sse.as_bytes());
+    }
+}
@@ -1,14 +1,22 @@
 use axum::response::Response;
 use futures_util::StreamExt;
 use smallvec::SmallVec;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 
+use crate::api::common::dropped_params::note_emulated_param;
 use crate::api::common::io::UpstreamIoRequest;
-use crate::api::common::passthrough::{is_protocol_passthrough, sanitize_upstream_error};
+use crate::api::common::passthrough::{is_protocol_passthrough, upstream_error};
+use crate::api::common::protocol_switch::guard_protocol_switch;
+use crate::api::common::response_ids::note_stream_response_ids;
+use crate::api::common::responses_reasoning::strip_reasoning_frames_if;
 use crate::error::CanonicalError;
 use crate::fc;
+use crate::fc::parse_failures::FcParseFailureSink;
 use crate::protocol::canonical::{CanonicalToolSpec, IngressApi, ProviderKind};
-use crate::stream::sse::{sse_frame_stream, sse_raw_frame_stream};
+use crate::stream::json_array::json_array_to_sse;
+use crate::stream::prefill::echoed_prefill;
+use crate::stream::sse::{coalesce_sse_frames, sse_frame_stream, sse_raw_frame_stream};
+use crate::stream::stop_sequences::{emulated_stop_sequences, provider_supports_stop_sequences};
 use crate::stream::transcoder::StreamTranscoder;
 use crate::stream::{parse_sse_frame_bytes, StreamingFcProcessor};
 
@@ -99,6 +107,19 @@ fn frame_might_start_fc_with_parsed_bytes(
     }
 }
 
+/// Whether a passthrough frame carries a native tool call, checked before the
+/// FC processor exists to observe decoded tool-call events.
+#[inline]
+fn frame_has_native_tool_call(ingress: IngressApi, raw: &[u8]) -> bool {
+    let marker: &[u8] = match ingress {
+        IngressApi::OpenAiChat => b"\"tool_calls\":[",
+        IngressApi::OpenAiResponses => b"\"type\":\"function_call\"",
+        IngressApi::Anthropic => b"\"type\":\"tool_use\"",
+        IngressApi::Gemini => b"\"functionCall\":",
+    };
+    memchr::memmem::find(raw, marker).is_some()
+}
+
 fn try_start_passthrough_fc_processor(
     raw_frame: &bytes::Bytes,
     provider_kind: ProviderKind,
@@ -149,7 +170,7 @@ fn try_start_passthrough_fc_processor(
 }
 
 #[inline]
-fn sse_ok_response(body: axum::body::Body) -> Response {
+pub(super) fn sse_ok_response(body: axum::body::Body) -> Response {
     sse_ok_response_with_content_type(body, http::HeaderValue::from_static("text/event-stream"))
 }
 
@@ -218,6 +239,31 @@ pub(crate) async fn handle_streaming_request(
     fc_active: bool,
     saved_tools: &[CanonicalToolSpec],
 ) -> Result<Response, CanonicalError> {
+    // Prompt-injected function calling parses the raw text itself, so stop
+    // sequences are only emulated on plain streams.
+    let fc_options = FcStreamOptions {
+        dedup_native_tool_calls: ctx.state.config.features.dedup_native_tool_calls,
+        incremental_tool_args: ctx.state.config.features.stream_tool_call_arguments
+            && matches!(
+                ingress,
+                IngressApi::OpenAiChat | IngressApi::OpenAiResponses
+            ),
+        parse_failures: fc_active
+            .then(|| ctx.state.fc_parse_failures())
+            .flatten()
+            .map(|failures| failures.sink(ctx.provider, ctx.client_model)),
+    };
+    let stop_sequences = (!fc_active)
+        .then(|| {
+            emulated_stop_sequences(
+                ctx.state.config.features.stop_sequence_emulation,
+                ctx.provider,
+            )
+        })
+        .flatten();
+    if stop_sequences.is_some() && !provider_supports_stop_sequences(ctx.provider) {
+        note_emulated_param("stop");
+    }
     if ctx
         .state
         .transport
@@ -252,7 +298,7 @@ pub(crate) async fn handle_streaming_request(
             .get(http::header::CONTENT_TYPE)
             .cloned()
             .unwrap_or_else(|| http::HeaderValue::from_static("text/event-stream"));
-        let (_, body) = response.into_parts();
+        let (parts, body) = response.into_parts();
 
         if !status.is_success() {
             let body_bytes = body
@@ -262,27 +308,46 @@ pub(crate) async fn handle_streaming_request(
                 .map_err(|e| {
                     CanonicalError::Transport(format!("Failed to read error body: {e}"))
                 })?;
-            return Err(CanonicalError::Upstream {
-                status: status.as_u16(),
-                message: sanitize_upstream_error(&body_bytes),
-            });
+            return Err(upstream_error(status, &parts.headers, &body_bytes));
         }
 
-        if !fc_active && is_protocol_passthrough(ctx.provider, ingress) {
+        if !fc_active && stop_sequences.is_none() && is_protocol_passthrough(ctx.provider, ingress)
+        {
+            if ctx.provider == ProviderKind::Gemini {
+                // An upstream that ignores `alt=sse` answers with a JSON array.
+                let body =
+                    axum::body::Body::from_stream(json_array_to_sse(body.into_data_stream(), true));
+                return Ok(sse_ok_response(body));
+            }
             return Ok(sse_ok_response_with_content_type(
                 axum::body::Body::new(body),
                 content_type,
             ));
         }
 
+        let byte_stream = guard_protocol_switch(
+            json_array_to_sse(
+                body.into_data_stream(),
+                ctx.provider == ProviderKind::Gemini,
+            ),
+            ctx.provider,
+        );
+        let byte_stream = strip_reasoning_frames_if(
+            byte_stream,
+            ctx.provider == ProviderKind::OpenAiResponses
+                && ctx.state.config.features.strip_responses_reasoning,
+        );
+        let byte_stream = note_stream_response_ids(byte_stream, ctx.provider, &response_id).await;
         return Ok(build_transcoded_stream_response(
-            body.into_data_stream(),
+            byte_stream,
             ctx.provider,
             ingress,
             ctx.client_model,
             response_id,
             fc_active,
             saved_tools,
+            stop_sequences,
+            fc_options,
         ));
     }
 
@@ -314,22 +379,33 @@ pub(crate) async fn handle_streaming_request(
 
     let status = response.status();
     if !status.is_success() {
+        let headers = response.headers().clone();
         let body_bytes = response
             .bytes()
             .await
             .map_err(|e| CanonicalError::Transport(format!("Failed to read error body: {e}")))?;
-        return Err(CanonicalError::Upstream {
-            status: status.as_u16(),
-            message: sanitize_upstream_error(&body_bytes),
-        });
+        return Err(upstream_error(status, &headers, &body_bytes));
     }
 
     let byte_stream = response.bytes_stream();
-    if !fc_active && is_protocol_passthrough(ctx.provider, ingress) {
-        let body = axum::body::Body::from_stream(byte_stream);
+    if !fc_active && stop_sequences.is_none() && is_protocol_passthrough(ctx.provider, ingress) {
+        let body = axum::body::Body::from_stream(json_array_to_sse(
+            byte_stream,
+            ctx.provider == ProviderKind::Gemini,
+        ));
         return Ok(sse_ok_response(body));
     }
 
+    let byte_stream = guard_protocol_switch(
+        json_array_to_sse(byte_stream, ctx.provider == ProviderKind::Gemini),
+        ctx.provider,
+    );
+    let byte_stream = strip_reasoning_frames_if(
+        byte_stream,
+        ctx.provider == ProviderKind::OpenAiResponses
+            && ctx.state.config.features.strip_responses_reasoning,
+    );
+    let byte_stream = note_stream_response_ids(byte_stream, ctx.provider, &response_id).await;
     Ok(build_transcoded_stream_response(
         byte_stream,
         ctx.provider,
@@ -338,9 +414,32 @@ pub(crate) async fn handle_streaming_request(
         response_id,
         fc_active,
         saved_tools,
+        stop_sequences,
+        fc_options,
     ))
 }
 
+/// How a stream with prompt-injected function calling handles tool calls.
+pub(crate) struct FcStreamOptions {
+    pub(crate) dedup_native_tool_calls: bool,
+    pub(crate) incremental_tool_args: bool,
+    pub(crate) parse_failures: Option<FcParseFailureSink>,
+}
+
+impl FcStreamOptions {
+    fn configure(self, processor: StreamingFcProcessor) -> StreamingFcProcessor {
+        processor
+            .with_native_tool_call_dedup(self.dedup_native_tool_calls)
+            .with_incremental_tool_args(self.incremental_tool_args)
+            .with_parse_failure_sink(self.parse_failures)
+    }
+}
+
+/// Marks SSE responses re-encoded by a [`StreamTranscoder`] rather than
+/// forwarded from the upstream byte for byte.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TranscodedStream;
+
 pub(crate) fn build_transcoded_stream_response<E>(
     byte_stream: impl futures_util::Stream<Item = Result<bytes::Bytes, E>> + Send + 'static,
     provider: ProviderKind,
@@ -349,28 +448,34 @@ pub(crate) fn build_transcoded_stream_response<E>(
     response_id: String,
     fc_active: bool,
     saved_tools: &[CanonicalToolSpec],
+    stop_sequences: Option<Arc<[String]>>,
+    fc_options: FcStreamOptions,
 ) -> Response
 where
     E: std::fmt::Debug + Send + 'static,
 {
-    if fc_active {
-        return build_fc_transcoded_stream_response(
+    let mut response = if fc_active {
+        build_fc_transcoded_stream_response(
             byte_stream,
             provider,
             ingress,
             client_model,
             response_id,
             saved_tools,
-        );
-    }
-
-    build_non_fc_transcoded_stream_response(
-        byte_stream,
-        provider,
-        ingress,
-        client_model,
-        response_id,
-    )
+            fc_options,
+        )
+    } else {
+        build_non_fc_transcoded_stream_response(
+            byte_stream,
+            provider,
+            ingress,
+            client_model,
+            response_id,
+            stop_sequences,
+        )
+    };
+    response.extensions_mut().insert(TranscodedStream);
+    response
 }
 
 fn build_fc_transcoded_stream_response<E>(
@@ -380,11 +485,17 @@ fn build_fc_transcoded_stream_response<E>(
     client_model: &str,
     response_id: String,
     saved_tools: &[CanonicalToolSpec],
+    fc_options: FcStreamOptions,
 ) -> Response
 where
     E: std::fmt::Debug + Send + 'static,
 {
     if is_protocol_passthrough(provider, ingress) {
+        let FcStreamOptions {
+            dedup_native_tool_calls,
+            incremental_tool_args,
+            parse_failures,
+        } = fc_options;
         let output_stream = futures_util::stream::unfold(
             (
                 Box::pin(sse_raw_frame_stream(byte_stream)),
@@ -392,21 +503,25 @@ where
                 Vec::<bytes::Bytes>::with_capacity(8),
                 PendingBytes::with_capacity(8),
                 false,
+                false,
                 provider,
                 ingress,
                 client_model.to_string(),
                 response_id,
+                parse_failures,
             ),
-            |(
+            move |(
                 mut sse_stream,
                 mut processor,
                 mut frame_chunks,
                 mut pending,
                 mut finalized,
+                mut native_tool_calls_seen,
                 provider_kind,
                 ingress_api,
                 model,
                 response_id,
+                parse_failures,
             )| async move {
                 loop {
                     if let Some(chunk) = pending.pop_front() {
@@ -418,10 +533,12 @@ where
                                 frame_chunks,
                                 pending,
                                 finalized,
+                                native_tool_calls_seen,
                                 provider_kind,
                                 ingress_api,
                                 model,
                                 response_id,
+                                parse_failures,
                             ),
                         ));
                     }
@@ -445,14 +562,18 @@ where
                                     frame_chunks,
                                     pending,
                                     finalized,
+                                    native_tool_calls_seen,
                                     provider_kind,
                                     ingress_api,
                                     model,
                                     response_id,
+                                    parse_failures,
                                 ),
                             ));
                         }
 
+                        native_tool_calls_seen |= dedup_native_tool_calls
+                            && frame_has_native_tool_call(ingress_api, raw_frame.as_ref());
                         if !raw_frame.as_ref().contains(&b'<') {
                             return Some((
                                 raw_frame,
@@ -462,10 +583,12 @@ where
                                     frame_chunks,
                                     pending,
                                     finalized,
+                                    native_tool_calls_seen,
                                     provider_kind,
                                     ingress_api,
                                     model,
                                     response_id,
+                                    parse_failures,
                                 ),
                             ));
                         }
@@ -479,6 +602,13 @@ where
                             &mut frame_chunks,
                         ) {
                             move_byte_chunks_to_pending(&mut frame_chunks, &mut pending);
+                            let mut proc = proc
+                                .with_native_tool_call_dedup(dedup_native_tool_calls)
+                                .with_incremental_tool_args(incremental_tool_args)
+                                .with_parse_failure_sink(parse_failures.clone());
+                            if native_tool_calls_seen {
+                                proc.mark_native_tool_calls_seen();
+                            }
                             processor = Some(proc);
                         } else {
                             return Some((
@@ -489,16 +619,18 @@ where
                                     frame_chunks,
                                     pending,
                                     finalized,
+                                    native_tool_calls_seen,
                                     provider_kind,
                                     ingress_api,
                                     model,
                                     response_id,
+                                    parse_failures,
                                 ),
                             ));
                         }
                     } else {
                         if let Some(proc) = processor.as_mut() {
-                            proc.finalize_into_bytes(&mut frame_chunks);
+                            proc.finalize_into_bytes_offloaded(&mut frame_chunks).await;
                             move_byte_chunks_to_pending(&mut frame_chunks, &mut pending);
                         }
                         finalized = true;
@@ -508,7 +640,7 @@ where
         );
 
         let body = axum::body::Body::from_stream(
-            output_stream.map(Ok::<bytes::Bytes, std::convert::Infallible>),
+            coalesce_sse_frames(output_stream).map(Ok::<bytes::Bytes, std::convert::Infallible>),
         );
         return sse_ok_response(body);
     }
@@ -521,6 +653,7 @@ where
             client_model,
             response_id,
             saved_tools,
+            fc_options,
         );
     }
 
@@ -531,6 +664,7 @@ where
         client_model,
         response_id,
         saved_tools,
+        fc_options,
     )
 }
 
@@ -541,18 +675,20 @@ fn build_fc_transcoded_stream_response_openai_upstream<E>(
     client_model: &str,
     response_id: String,
     saved_tools: &[CanonicalToolSpec],
+    fc_options: FcStreamOptions,
 ) -> Response
 where
     E: std::fmt::Debug + Send + 'static,
 {
     let transcoder =
-        StreamTranscoder::new(provider, ingress, client_model.to_string(), response_id);
-    let processor = StreamingFcProcessor::new(
+        StreamTranscoder::new(provider, ingress, client_model.to_string(), response_id)
+            .with_prefill_echo(echoed_prefill(provider, ingress));
+    let processor = fc_options.configure(StreamingFcProcessor::new(
         transcoder,
         true,
         saved_tools,
         fc::prompt::get_trigger_signal(),
-    );
+    ));
     let output_stream = futures_util::stream::unfold(
         (
             Box::pin(sse_raw_frame_stream(byte_stream)),
@@ -572,7 +708,7 @@ where
                 if let Some(raw_frame) = sse_stream.as_mut().next().await {
                     proc.process_raw_frame_into_bytes(raw_frame.as_ref(), &mut frame_chunks);
                 } else {
-                    proc.finalize_into_bytes(&mut frame_chunks);
+                    proc.finalize_into_bytes_offloaded(&mut frame_chunks).await;
                     finalized = true;
                 }
                 if let Some(chunk) = emit_from_byte_chunks(&mut frame_chunks, &mut pending) {
@@ -583,7 +719,7 @@ where
     );
 
     let body = axum::body::Body::from_stream(
-        output_stream.map(Ok::<bytes::Bytes, std::convert::Infallible>),
+        coalesce_sse_frames(output_stream).map(Ok::<bytes::Bytes, std::convert::Infallible>),
     );
     sse_ok_response(body)
 }
@@ -595,19 +731,21 @@ fn build_fc_transcoded_stream_response_generic<E>(
     client_model: &str,
     response_id: String,
     saved_tools: &[CanonicalToolSpec],
+    fc_options: FcStreamOptions,
 ) -> Response
 where
     E: std::fmt::Debug + Send + 'static,
 {
     let transcoder =
-        StreamTranscoder::new(provider, ingress, client_model.to_string(), response_id);
+        StreamTranscoder::new(provider, ingress, client_model.to_string(), response_id)
+            .with_prefill_echo(echoed_prefill(provider, ingress));
     let sse_events = sse_frame_stream(byte_stream);
-    let processor = StreamingFcProcessor::new(
+    let processor = fc_options.configure(StreamingFcProcessor::new(
         transcoder,
         true,
         saved_tools,
         fc::prompt::get_trigger_signal(),
-    );
+    ));
 
     let output_stream = futures_util::stream::unfold(
         (
@@ -628,7 +766,7 @@ where
                 if let Some(frame) = sse_stream.as_mut().next().await {
                     proc.process_frame_into_bytes(&frame, &mut frame_chunks);
                 } else {
-                    proc.finalize_into_bytes(&mut frame_chunks);
+                    proc.finalize_into_bytes_offloaded(&mut frame_chunks).await;
                     finalized = true;
                 }
                 if let Some(chunk) = emit_from_byte_chunks(&mut frame_chunks, &mut pending) {
@@ -639,7 +777,7 @@ where
     );
 
     let body = axum::body::Body::from_stream(
-        output_stream.map(Ok::<bytes::Bytes, std::convert::Infallible>),
+        coalesce_sse_frames(output_stream).map(Ok::<bytes::Bytes, std::convert::Infallible>),
     );
     sse_ok_response(body)
 }
@@ -650,13 +788,16 @@ fn build_non_fc_transcoded_stream_response<E>(
     ingress: IngressApi,
     client_model: &str,
     response_id: String,
+    stop_sequences: Option<Arc<[String]>>,
 ) -> Response
 where
     E: std::fmt::Debug + Send + 'static,
 {
+    let transcoder =
+        StreamTranscoder::new(provider, ingress, client_model.to_string(), response_id)
+            .with_prefill_echo(echoed_prefill(provider, ingress))
+            .with_stop_sequences(stop_sequences);
     if matches!(provider, ProviderKind::OpenAi | ProviderKind::GeminiOpenAi) {
-        let transcoder =
-            StreamTranscoder::new(provider, ingress, client_model.to_string(), response_id);
         let output_stream = futures_util::stream::unfold(
             (
                 Box::pin(sse_raw_frame_stream(byte_stream)),
@@ -697,6 +838,9 @@ where
                             &mut decode_buffer,
                             &mut frame_chunks,
                         );
+                        // The client's stop sequence ended the stream; stop
+                        // reading from the upstream.
+                        done = transcoder.stop_sequence_hit();
                         if let Some(chunk) = emit_from_byte_chunks(&mut frame_chunks, &mut pending)
                         {
                             return Some((
@@ -713,19 +857,30 @@ where
                         }
                     } else {
                         done = true;
+                        if let Some(frame) = transcoder.finish() {
+                            return Some((
+                                bytes::Bytes::from(frame),
+                                (
+                                    sse_stream,
+                                    transcoder,
+                                    decode_buffer,
+                                    frame_chunks,
+                                    pending,
+                                    done,
+                                ),
+                            ));
+                        }
                     }
                 }
             },
         );
 
         let body = axum::body::Body::from_stream(
-            output_stream.map(Ok::<bytes::Bytes, std::convert::Infallible>),
+            coalesce_sse_frames(output_stream).map(Ok::<bytes::Bytes, std::convert::Infallible>),
         );
         return sse_ok_response(body);
     }
 
-    let transcoder =
-        StreamTranscoder::new(provider, ingress, client_model.to_string(), response_id);
     let sse_events = Box::pin(sse_frame_stream(byte_stream));
     let output_stream = futures_util::stream::unfold(
         (
@@ -767,6 +922,7 @@ where
                         &mut decode_buffer,
                         &mut frame_chunks,
                     );
+                    done = transcoder.stop_sequence_hit();
                     if let Some(chunk) = emit_from_byte_chunks(&mut frame_chunks, &mut pending) {
                         return Some((
                             chunk,
@@ -782,13 +938,26 @@ where
                     }
                 } else {
                     done = true;
+                    if let Some(frame) = transcoder.finish() {
+                        return Some((
+                            bytes::Bytes::from(frame),
+                            (
+                                sse_stream,
+                                transcoder,
+                                decode_buffer,
+                                frame_chunks,
+                                pending,
+                                done,
+                            ),
+                        ));
+                    }
                 }
             }
         },
     );
 
     let body = axum::body::Body::from_stream(
-        output_stream.map(Ok::<bytes::Bytes, std::convert::Infallible>),
+        coalesce_sse_frames(output_stream).map(Ok::<bytes::Bytes, std::convert::Infallible>),
     );
     sse_ok_response(body)
 }
@@ -0,0 +1,512 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use axum::response::Response;
+use futures_util::{Stream, StreamExt};
+use rustc_hash::FxHashMap;
+use serde_json::{json, Map, Value};
+
+use crate::config::ToolSchemaValidation;
+use crate::error::CanonicalError;
+use crate::fc::retry::build_tool_schema_retry_prompt;
+use crate::fc::validator::validate_tool_call;
+use crate::protocol::canonical::{
+    CanonicalPart, CanonicalStreamEvent, CanonicalToolFunction, CanonicalToolSpec, IngressApi,
+};
+use crate::stream::sse::encode_sse_event;
+use crate::stream::{sse_frame_stream, StreamTranscoder};
+
+use super::codec::decode_response_from_provider;
+use super::stream_aggregate::{ingress_wire_provider, response_id};
+
+/// Status of the error that replaces a rejected answer.
+const TOOL_SCHEMA_ERROR_STATUS: u16 = 500;
+
+/// Tools in the client request `body` declared with `strict: true`.
+///
+/// Gemini has no strict tool mode, so its requests never have any.
+#[must_use]
+pub(crate) fn strict_tool_specs(ingress: IngressApi, body: &[u8]) -> Vec<CanonicalToolSpec> {
+    if ingress == IngressApi::Gemini || memchr::memmem::find(body, b"\"strict\"").is_none() {
+        return Vec::new();
+    }
+    let Ok(payload) = serde_json::from_slice::<Value>(body) else {
+        return Vec::new();
+    };
+    let Some(tools) = payload.get("tools").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+    tools
+        .iter()
+        .filter_map(|tool| {
+            let (function, schema_field) = match ingress {
+                IngressApi::OpenAiChat => (tool.get("function")?, "parameters"),
+                IngressApi::OpenAiResponses => {
+                    if tool.get("type").and_then(Value::as_str) != Some("function") {
+                        return None;
+                    }
+                    (tool, "parameters")
+                }
+                IngressApi::Anthropic => (tool, "input_schema"),
+                IngressApi::Gemini => return None,
+            };
+            if function.get("strict") != Some(&Value::Bool(true)) {
+                return None;
+            }
+            Some(CanonicalToolSpec {
+                function: CanonicalToolFunction {
+                    name: function.get("name")?.as_str()?.to_string(),
+                    description: None,
+                    parameters: function
+                        .get(schema_field)
+                        .cloned()
+                        .unwrap_or_else(|| json!({})),
+                },
+            })
+        })
+        .collect()
+}
+
+/// Tool calls of an answer that break a `strict` schema.
+#[derive(Debug, Clone)]
+pub(crate) struct ToolSchemaViolation {
+    /// The offending calls, one `name(arguments)` per line.
+    calls: String,
+    /// Validation errors, `; `-separated.
+    details: String,
+}
+
+impl ToolSchemaViolation {
+    /// Validate `calls`, `(name, raw JSON arguments)` pairs, against the
+    /// `strict` tools; calls of other tools are not checked.
+    fn find<'a>(
+        calls: impl IntoIterator<Item = (&'a str, &'a str)>,
+        tools: &[CanonicalToolSpec],
+    ) -> Option<Self> {
+        let mut rejected = Self {
+            calls: String::new(),
+            details: String::new(),
+        };
+        for (name, arguments) in calls {
+            if !tools.iter().any(|tool| tool.function.name == name) {
+                continue;
+            }
+            let errors = match serde_json::from_str::<Value>(arguments) {
+                Ok(arguments) => match validate_tool_call(name, &arguments, tools) {
+                    Ok(()) => continue,
+                    Err(errors) => errors.iter().map(ToString::to_string).collect(),
+                },
+                Err(err) => vec![format!("{name}: arguments are not valid JSON: {err}")],
+            };
+            if !rejected.calls.is_empty() {
+                rejected.calls.push('\n');
+            }
+            rejected.calls.push_str(&format!("{name}({arguments})"));
+            for error in errors {
+                if !rejected.details.is_empty() {
+                    rejected.details.push_str("; ");
+                }
+                rejected.details.push_str(&error);
+            }
+        }
+        (!rejected.calls.is_empty()).then_some(rejected)
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "tool call arguments do not match the strict tool schema: {}",
+            self.details
+        )
+    }
+
+    /// The error a client receives for the rejected answer.
+    #[must_use]
+    pub(crate) fn into_error(self) -> CanonicalError {
+        CanonicalError::FcParse(self.message())
+    }
+}
+
+/// Check the tool calls of a client `response` against the `strict` tools,
+/// as `mode` configures for the upstream that answered.
+///
+/// Streams are passed through until a tool call starts; the rest of the
+/// message is held back and released once its calls validate, or replaced by
+/// an error event. A complete answer that fails fails the request, or with
+/// [`ToolSchemaValidation::Retry`] is returned carrying a
+/// [`ToolSchemaViolation`] extension for the caller to re-ask the model.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::FcParse`] for a rejected complete answer.
+pub(crate) async fn enforce_tool_schemas(
+    response: Response,
+    ingress: IngressApi,
+    tools: &Arc<[CanonicalToolSpec]>,
+    mode: ToolSchemaValidation,
+) -> Result<Response, CanonicalError> {
+    if mode == ToolSchemaValidation::Off || tools.is_empty() || !response.status().is_success() {
+        return Ok(response);
+    }
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+    if is_sse {
+        let frames = guard_sse_body(body, ingress, Arc::clone(tools));
+        return Ok(Response::from_parts(
+            parts,
+            axum::body::Body::from_stream(frames),
+        ));
+    }
+
+    let body = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
+    let violation = decode_response_from_provider(ingress_wire_provider(ingress), &body)
+        .ok()
+        .and_then(|canonical| {
+            let calls = canonical.content.iter().filter_map(|part| match part {
+                CanonicalPart::ToolCall {
+                    name, arguments, ..
+                } => Some((name.as_str(), arguments.get())),
+                _ => None,
+            });
+            ToolSchemaViolation::find(calls, tools)
+        });
+    let mut response = Response::from_parts(parts, axum::body::Body::from(body));
+    match violation {
+        None => Ok(response),
+        Some(violation) if mode == ToolSchemaValidation::Retry => {
+            response.extensions_mut().insert(violation);
+            Ok(response)
+        }
+        Some(violation) => Err(violation.into_error()),
+    }
+}
+
+/// The client request `body` extended with the rejected tool calls and a
+/// turn asking the model to repeat them with valid arguments.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::InvalidRequest`] when `body` is not a JSON
+/// object, or [`CanonicalError::Internal`] when it cannot be re-encoded.
+pub(crate) fn tool_schema_retry_request(
+    body: &[u8],
+    ingress: IngressApi,
+    violation: &ToolSchemaViolation,
+) -> Result<bytes::Bytes, CanonicalError> {
+    let mut request: Map<String, Value> = serde_json::from_slice(body)
+        .map_err(|e| CanonicalError::InvalidRequest(format!("Invalid JSON body: {e}")))?;
+    let prompt = build_tool_schema_retry_prompt(&violation.details, &violation.calls);
+    let (field, turns) = match ingress {
+        IngressApi::OpenAiChat | IngressApi::Anthropic => (
+            "messages",
+            [
+                json!({ "role": "assistant", "content": violation.calls }),
+                json!({ "role": "user", "content": prompt }),
+            ],
+        ),
+        IngressApi::OpenAiResponses => (
+            "input",
+            [
+                json!({ "role": "assistant", "content": violation.calls }),
+                json!({ "role": "user", "content": prompt }),
+            ],
+        ),
+        IngressApi::Gemini => (
+            "contents",
+            [
+                json!({ "role": "model", "parts": [{ "text": violation.calls }] }),
+                json!({ "role": "user", "parts": [{ "text": prompt }] }),
+            ],
+        ),
+    };
+    let history = request
+        .entry(field)
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if let Value::String(text) = history {
+        // Responses `input` may be a bare prompt.
+        *history = json!([{ "role": "user", "content": text }]);
+    }
+    if let Value::Array(history) = history {
+        history.extend(turns);
+    }
+    serde_json::to_vec(&request)
+        .map(bytes::Bytes::from)
+        .map_err(|e| CanonicalError::Internal(format!("Failed to encode request: {e}")))
+}
+
+struct SseGuard<S> {
+    frames: std::pin::Pin<Box<S>>,
+    decoder: StreamTranscoder,
+    encoder: Option<StreamTranscoder>,
+    ingress: IngressApi,
+    tools: Arc<[CanonicalToolSpec]>,
+    /// Name and arguments so far of the strict tool calls started, by index.
+    calls: FxHashMap<usize, (String, String)>,
+    /// Frames held back since the first strict tool call started.
+    held: Vec<bytes::Bytes>,
+    pending: VecDeque<bytes::Bytes>,
+    finished: bool,
+}
+
+impl<S> SseGuard<S> {
+    /// Release the held frames, or queue an error event in their place.
+    fn settle(&mut self) {
+        if self.calls.is_empty() {
+            return;
+        }
+        let mut calls: Vec<_> = self.calls.drain().collect();
+        calls.sort_unstable_by_key(|(index, _)| *index);
+        let violation = ToolSchemaViolation::find(
+            calls
+                .iter()
+                .map(|(_, (name, arguments))| (name.as_str(), arguments.as_str())),
+            &self.tools,
+        );
+        let Some(violation) = violation else {
+            self.pending.extend(self.held.drain(..));
+            return;
+        };
+        tracing::warn!(details = %violation.details, "streamed tool call breaks its strict schema");
+        self.held.clear();
+        self.finished = true;
+        let error = CanonicalStreamEvent::Error {
+            status: TOOL_SCHEMA_ERROR_STATUS,
+            message: violation.message(),
+        };
+        if let Some(frame) = self
+            .encoder
+            .as_mut()
+            .and_then(|encoder| encoder.encode_client_event_bytes(&error))
+        {
+            self.pending.push_back(frame);
+        }
+    }
+}
+
+fn guard_sse_body(
+    body: axum::body::Body,
+    ingress: IngressApi,
+    tools: Arc<[CanonicalToolSpec]>,
+) -> impl Stream<Item = Result<bytes::Bytes, std::convert::Infallible>> + Send + 'static {
+    let state = SseGuard {
+        frames: Box::pin(sse_frame_stream(body.into_data_stream())),
+        decoder: StreamTranscoder::new(
+            ingress_wire_provider(ingress),
+            ingress,
+            String::new(),
+            String::new(),
+        ),
+        encoder: None,
+        ingress,
+        tools,
+        calls: FxHashMap::default(),
+        held: Vec::new(),
+        pending: VecDeque::new(),
+        finished: false,
+    };
+    futures_util::stream::unfold(state, |mut state| async move {
+        let mut decoded = Vec::with_capacity(8);
+        loop {
+            if let Some(frame) = state.pending.pop_front() {
+                return Some((Ok(frame), state));
+            }
+            if state.finished {
+                return None;
+            }
+            let Some(frame) = state.frames.next().await else {
+                state.settle();
+                state.pending.extend(state.held.drain(..));
+                state.finished = true;
+                continue;
+            };
+            let ingress = state.ingress;
+            state.encoder.get_or_insert_with(|| {
+                StreamTranscoder::new(
+                    ingress_wire_provider(ingress),
+                    ingress,
+                    String::new(),
+                    response_id(ingress, &frame.data).unwrap_or_default(),
+                )
+            });
+            state
+                .decoder
+                .decode_upstream_frame_into(&frame, &mut decoded);
+            let mut message_ended = false;
+            for event in decoded.drain(..) {
+                match event {
+                    CanonicalStreamEvent::ToolCallStart { index, name, .. }
+                        if state.tools.iter().any(|tool| tool.function.name == name) =>
+                    {
+                        state.calls.insert(index, (name, String::new()));
+                    }
+                    CanonicalStreamEvent::ToolCallArgsDelta { index, delta } => {
+                        if let Some((_, arguments)) = state.calls.get_mut(&index) {
+                            arguments.push_str(&delta);
+                        }
+                    }
+                    CanonicalStreamEvent::MessageEnd { .. } | CanonicalStreamEvent::Done => {
+                        message_ended = true;
+                    }
+                    _ => {}
+                }
+            }
+            let bytes = bytes::Bytes::from(encode_sse_event(&frame));
+            if state.calls.is_empty() {
+                state.pending.push_back(bytes);
+            } else {
+                state.held.push(bytes);
+                if message_ended {
+                    state.settle();
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather_tools() -> Arc<[CanonicalToolSpec]> {
+        Arc::from(strict_tool_specs(
+            IngressApi::OpenAiChat,
+            br#"{"tools":[
+                {"type":"function","function":{"name":"get_weather","strict":true,
+                 "parameters":{"type":"object","properties":{"city":{"type":"string"}},
+                 "required":["city"],"additionalProperties":false}}},
+                {"type":"function","function":{"name":"loose","parameters":{}}}
+            ]}"#,
+        ))
+    }
+
+    fn sse_response(frames: &[&str]) -> Response {
+        let body: String = frames
+            .iter()
+            .map(|data| format!("data: {data}\n\n"))
+            .collect();
+        Response::builder()
+            .header(http::header::CONTENT_TYPE, "text/event-stream")
+            .body(axum::body::Body::from(body))
+            .unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_only_strict_tools_are_collected() {
+        let tools = weather_tools();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].function.name, "get_weather");
+        assert!(strict_tool_specs(
+            IngressApi::Anthropic,
+            br#"{"tools":[{"name":"a","strict":false,"input_schema":{}}]}"#
+        )
+        .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_complete_answer_is_rejected_or_marked_for_retry() {
+        let body = r#"{"id":"c1","object":"chat.completion","created":1,"model":"m","choices":[{"index":0,"message":{"role":"assistant","content":null,"tool_calls":[{"id":"t1","type":"function","function":{"name":"get_weather","arguments":"{\"town\":\"Paris\"}"}}]},"finish_reason":"tool_calls"}]}"#;
+        let response = || {
+            Response::builder()
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(axum::body::Body::from(body))
+                .unwrap()
+        };
+        let tools = weather_tools();
+
+        let err = enforce_tool_schemas(
+            response(),
+            IngressApi::OpenAiChat,
+            &tools,
+            ToolSchemaValidation::Error,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("missing required property 'city'"));
+
+        let marked = enforce_tool_schemas(
+            response(),
+            IngressApi::OpenAiChat,
+            &tools,
+            ToolSchemaValidation::Retry,
+        )
+        .await
+        .unwrap();
+        let violation = marked.extensions().get::<ToolSchemaViolation>().unwrap();
+        let retry = tool_schema_retry_request(
+            br#"{"model":"m","input":"weather in Paris?"}"#,
+            IngressApi::OpenAiResponses,
+            violation,
+        )
+        .unwrap();
+        let retry: Value = serde_json::from_slice(&retry).unwrap();
+        assert_eq!(retry["input"][0]["content"], "weather in Paris?");
+        assert_eq!(
+            retry["input"][1]["content"],
+            r#"get_weather({"town":"Paris"})"#
+        );
+        assert!(retry["input"][2]["content"]
+            .as_str()
+            .unwrap()
+            .contains("unexpected property 'town'"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_holds_tool_calls_until_they_validate() {
+        let tools = weather_tools();
+        let frames = |arguments: &str| {
+            vec![
+                r#"{"id":"c1","object":"chat.completion.chunk","created":1,"model":"m","choices":[{"index":0,"delta":{"role":"assistant","content":"Checking."}}]}"#.to_string(),
+                r#"{"id":"c1","object":"chat.completion.chunk","created":1,"model":"m","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"t1","type":"function","function":{"name":"get_weather","arguments":""}}]}}]}"#.to_string(),
+                format!(
+                    r#"{{"id":"c1","object":"chat.completion.chunk","created":1,"model":"m","choices":[{{"index":0,"delta":{{"tool_calls":[{{"index":0,"function":{{"arguments":{}}}}}]}}}}]}}"#,
+                    serde_json::to_string(arguments).unwrap()
+                ),
+                r#"{"id":"c1","object":"chat.completion.chunk","created":1,"model":"m","choices":[{"index":0,"delta":{},"finish_reason":"tool_calls"}]}"#.to_string(),
+                "[DONE]".to_string(),
+            ]
+        };
+
+        let valid = frames(r#"{"city":"Paris"}"#);
+        let valid: Vec<&str> = valid.iter().map(String::as_str).collect();
+        let response = enforce_tool_schemas(
+            sse_response(&valid),
+            IngressApi::OpenAiChat,
+            &tools,
+            ToolSchemaValidation::Error,
+        )
+        .await
+        .unwrap();
+        let text = body_text(response).await;
+        assert!(text.contains(r#"\"city\":\"Paris\""#));
+        assert!(text.ends_with("data: [DONE]\n\n"));
+
+        let invalid = frames(r#"{"city":7}"#);
+        let invalid: Vec<&str> = invalid.iter().map(String::as_str).collect();
+        let response = enforce_tool_schemas(
+            sse_response(&invalid),
+            IngressApi::OpenAiChat,
+            &tools,
+            ToolSchemaValidation::Retry,
+        )
+        .await
+        .unwrap();
+        let text = body_text(response).await;
+        assert!(text.contains("Checking."));
+        assert!(!text.contains("tool_calls"));
+        assert!(text.contains("expected type 'string'"));
+        assert!(!text.contains("[DONE]"));
+    }
+}
@@ -0,0 +1,495 @@
+use axum::response::Response;
+use futures_util::StreamExt;
+use serde_json::{Map, Value};
+
+use crate::error::CanonicalError;
+use crate::protocol::canonical::IngressApi;
+use crate::routing::virtual_models::VirtualModel;
+use crate::stream::sse::sse_raw_frame_stream;
+
+/// Rewrite a client request for a virtual model: route to the target model,
+/// prepend the system prompt, and fill unset sampling parameters.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::InvalidRequest`] when the body is not a JSON object.
+pub(crate) fn apply_virtual_model_request(
+    body: &[u8],
+    ingress: IngressApi,
+    virtual_model: &VirtualModel,
+) -> Result<bytes::Bytes, CanonicalError> {
+    let mut request: Map<String, Value> = serde_json::from_slice(body)
+        .map_err(|e| CanonicalError::InvalidRequest(format!("Invalid JSON body: {e}")))?;
+
+    if ingress != IngressApi::Gemini {
+        // Gemini carries the model in the URL path.
+        request.insert(
+            "model".to_string(),
+            Value::String(virtual_model.target.clone()),
+        );
+    }
+    if let Some(prompt) = virtual_model.system_prompt.as_deref() {
+        prepend_system_prompt(&mut request, ingress, prompt);
+    }
+    apply_defaults(&mut request, ingress, virtual_model);
+
+    serde_json::to_vec(&request)
+        .map(bytes::Bytes::from)
+        .map_err(|e| CanonicalError::Internal(format!("Failed to encode request: {e}")))
+}
+
+fn prepend_system_prompt(request: &mut Map<String, Value>, ingress: IngressApi, prompt: &str) {
+    match ingress {
+        IngressApi::OpenAiChat => {
+            let system = serde_json::json!({ "role": "system", "content": prompt });
+            match request.get_mut("messages") {
+                Some(Value::Array(messages)) => messages.insert(0, system),
+                _ => {
+                    request.insert("messages".to_string(), Value::Array(vec![system]));
+                }
+            }
+        }
+        IngressApi::OpenAiResponses => prepend_text_field(request, "instructions", prompt),
+        IngressApi::Anthropic => match request.get_mut("system") {
+            Some(Value::Array(blocks)) => {
+                blocks.insert(0, serde_json::json!({ "type": "text", "text": prompt }));
+            }
+            _ => prepend_text_field(request, "system", prompt),
+        },
+        IngressApi::Gemini => {
+            let key = if request.contains_key("system_instruction") {
+                "system_instruction"
+            } else {
+                "systemInstruction"
+            };
+            let part = serde_json::json!({ "text": prompt });
+            match request
+                .get_mut(key)
+                .and_then(|value| value.get_mut("parts"))
+            {
+                Some(Value::Array(parts)) => parts.insert(0, part),
+                _ => {
+                    request.insert(key.to_string(), serde_json::json!({ "parts": [part] }));
+                }
+            }
+        }
+    }
+}
+
+fn prepend_text_field(request: &mut Map<String, Value>, key: &str, prompt: &str) {
+    let combined = match request.get(key).and_then(Value::as_str) {
+        Some(existing) if !existing.is_empty() => format!("{prompt}\n\n{existing}"),
+        _ => prompt.to_string(),
+    };
+    request.insert(key.to_string(), Value::String(combined));
+}
+
+/// Wire field name and default value for one sampling parameter.
+type DefaultField = (&'static str, Option<Value>);
+
+fn apply_defaults(
+    request: &mut Map<String, Value>,
+    ingress: IngressApi,
+    virtual_model: &VirtualModel,
+) {
+    let defaults = &virtual_model.defaults;
+    let stop = defaults
+        .stop
+        .as_ref()
+        .map(|stop| Value::Array(stop.iter().cloned().map(Value::String).collect()));
+    let max_tokens = defaults.max_tokens.map(Value::from);
+    let temperature = defaults.temperature.map(Value::from);
+    let top_p = defaults.top_p.map(Value::from);
+
+    let (target, fields): (&mut Map<String, Value>, [DefaultField; 4]) = match ingress {
+        IngressApi::OpenAiChat => {
+            let max_tokens = max_tokens.filter(|_| !request.contains_key("max_completion_tokens"));
+            (
+                request,
+                [
+                    ("temperature", temperature),
+                    ("top_p", top_p),
+                    ("max_tokens", max_tokens),
+                    ("stop", stop),
+                ],
+            )
+        }
+        // The Responses API has no stop sequences.
+        IngressApi::OpenAiResponses => (
+            request,
+            [
+                ("temperature", temperature),
+                ("top_p", top_p),
+                ("max_output_tokens", max_tokens),
+                ("stop", None),
+            ],
+        ),
+        IngressApi::Anthropic => (
+            request,
+            [
+                ("temperature", temperature),
+                ("top_p", top_p),
+                ("max_tokens", max_tokens),
+                ("stop_sequences", stop),
+            ],
+        ),
+        IngressApi::Gemini => {
+            let key = if request.contains_key("generation_config") {
+                "generation_config"
+            } else {
+                "generationConfig"
+            };
+            let Value::Object(config) = request
+                .entry(key)
+                .or_insert_with(|| Value::Object(Map::new()))
+            else {
+                return;
+            };
+            (
+                config,
+                [
+                    ("temperature", temperature),
+                    ("topP", top_p),
+                    ("maxOutputTokens", max_tokens),
+                    ("stopSequences", stop),
+                ],
+            )
+        }
+    };
+    for (field, value) in fields {
+        if let Some(value) = value {
+            target.entry(field).or_insert(value);
+        }
+    }
+}
+
+/// Apply a virtual model's output post-processing to a client response.
+///
+/// Non-streaming bodies are processed per complete text field; SSE bodies
+/// per text delta (see [`VirtualModel::postprocess_delta`]).
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::Transport`] when a non-streaming body cannot be read.
+pub(crate) async fn postprocess_virtual_model_response(
+    response: Response,
+    ingress: IngressApi,
+    virtual_model: std::sync::Arc<VirtualModel>,
+) -> Result<Response, CanonicalError> {
+    if !virtual_model.has_postprocess() || !response.status().is_success() {
+        return Ok(response);
+    }
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+
+    if is_sse {
+        let mut trimmed_outputs: Vec<u64> = Vec::new();
+        let frames = sse_raw_frame_stream(body.into_data_stream()).map(move |frame| {
+            Ok::<_, std::convert::Infallible>(
+                postprocess_sse_frame(&frame, ingress, &virtual_model, &mut trimmed_outputs)
+                    .unwrap_or(frame),
+            )
+        });
+        return Ok(Response::from_parts(
+            parts,
+            axum::body::Body::from_stream(frames),
+        ));
+    }
+
+    let body_bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
+    let Ok(mut payload) = serde_json::from_slice::<Value>(&body_bytes) else {
+        return Ok(Response::from_parts(
+            parts,
+            axum::body::Body::from(body_bytes),
+        ));
+    };
+    if let Value::Array(chunks) = &mut payload {
+        // Gemini JSON-array streams carry deltas, not complete texts.
+        let mut trimmed_outputs = Vec::new();
+        for chunk in chunks {
+            postprocess_stream_event(chunk, ingress, &virtual_model, &mut trimmed_outputs);
+        }
+    } else {
+        postprocess_complete_payload(&mut payload, ingress, &virtual_model);
+    }
+    let encoded = serde_json::to_vec(&payload)
+        .map_err(|e| CanonicalError::Internal(format!("Failed to encode response: {e}")))?;
+    Ok(Response::from_parts(parts, axum::body::Body::from(encoded)))
+}
+
+fn postprocess_complete_payload(payload: &mut Value, ingress: IngressApi, model: &VirtualModel) {
+    let apply = |text: &mut Value| {
+        if let Some(current) = text.as_str() {
+            *text = Value::String(model.postprocess_text(current));
+        }
+    };
+    match ingress {
+        IngressApi::OpenAiChat => {
+            for choice in array_mut(payload, "choices") {
+                if let Some(content) = choice.pointer_mut("/message/content") {
+                    apply(content);
+                }
+            }
+        }
+        IngressApi::OpenAiResponses => {
+            for item in array_mut(payload, "output") {
+                for part in array_mut(item, "content") {
+                    if part.get("type").and_then(Value::as_str) == Some("output_text") {
+                        if let Some(text) = part.get_mut("text") {
+                            apply(text);
+                        }
+                    }
+                }
+            }
+        }
+        IngressApi::Anthropic => {
+            for block in array_mut(payload, "content") {
+                if block.get("type").and_then(Value::as_str) == Some("text") {
+                    if let Some(text) = block.get_mut("text") {
+                        apply(text);
+                    }
+                }
+            }
+        }
+        IngressApi::Gemini => {
+            for candidate in array_mut(payload, "candidates") {
+                for part in gemini_text_parts(candidate) {
+                    apply(part);
+                }
+            }
+        }
+    }
+}
+
+fn postprocess_stream_event(
+    event: &mut Value,
+    ingress: IngressApi,
+    model: &VirtualModel,
+    trimmed_outputs: &mut Vec<u64>,
+) {
+    let mut apply_delta = |output: u64, text: &mut Value| {
+        let Some(current) = text.as_str() else {
+            return;
+        };
+        let mut trimmed = trimmed_outputs.contains(&output);
+        let processed = model.postprocess_delta(current, &mut trimmed);
+        if trimmed && !trimmed_outputs.contains(&output) {
+            trimmed_outputs.push(output);
+        }
+        *text = Value::String(processed);
+    };
+    match ingress {
+        IngressApi::OpenAiChat => {
+            for choice in array_mut(event, "choices") {
+                let index = choice.get("index").and_then(Value::as_u64).unwrap_or(0);
+                if let Some(content) = choice.pointer_mut("/delta/content") {
+                    apply_delta(index, content);
+                }
+            }
+        }
+        IngressApi::OpenAiResponses => {
+            let output_index = event
+                .get("output_index")
+                .and_then(Value::as_u64)
+                .unwrap_or(0);
+            match event.get("type").and_then(Value::as_str) {
+                Some("response.output_text.delta") => {
+                    if let Some(delta) = event.get_mut("delta") {
+                        apply_delta(output_index, delta);
+                    }
+                }
+                Some("response.output_text.done") => {
+                    if let Some(text) = event.get_mut("text") {
+                        if let Some(current) = text.as_str() {
+                            *text = Value::String(model.postprocess_text(current));
+                        }
+                    }
+                }
+                Some("response.completed" | "response.incomplete") => {
+                    if let Some(response) = event.get_mut("response") {
+                        postprocess_complete_payload(response, ingress, model);
+                    }
+                }
+                _ => {}
+            }
+        }
+        IngressApi::Anthropic => {
+            if event.get("type").and_then(Value::as_str) == Some("content_block_delta")
+                && event.pointer("/delta/type").and_then(Value::as_str) == Some("text_delta")
+            {
+                let index = event.get("index").and_then(Value::as_u64).unwrap_or(0);
+                if let Some(text) = event.pointer_mut("/delta/text") {
+                    apply_delta(index, text);
+                }
+            }
+        }
+        IngressApi::Gemini => {
+            for candidate in array_mut(event, "candidates") {
+                let index = candidate.get("index").and_then(Value::as_u64).unwrap_or(0);
+                for part in gemini_text_parts(candidate) {
+                    apply_delta(index, part);
+                }
+            }
+        }
+    }
+}
+
+fn postprocess_sse_frame(
+    frame: &[u8],
+    ingress: IngressApi,
+    model: &VirtualModel,
+    trimmed_outputs: &mut Vec<u64>,
+) -> Option<bytes::Bytes> {
+    let text = std::str::from_utf8(frame).ok()?;
+    let mut out = String::with_capacity(text.len());
+    let mut rewritten = false;
+    for line in text.trim_end_matches(['\r', '\n']).lines() {
+        if let Some(payload) = line.strip_prefix("data:").map(str::trim_start) {
+            if payload.starts_with('{') {
+                let mut event: Value = serde_json::from_str(payload).ok()?;
+                postprocess_stream_event(&mut event, ingress, model, trimmed_outputs);
+                out.push_str("data: ");
+                out.push_str(&serde_json::to_string(&event).ok()?);
+                out.push('\n');
+                rewritten = true;
+                continue;
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    if !rewritten {
+        return None;
+    }
+    out.push('\n');
+    Some(bytes::Bytes::from(out))
+}
+
+fn array_mut<'a>(value: &'a mut Value, key: &str) -> impl Iterator<Item = &'a mut Value> {
+    value
+        .get_mut(key)
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+}
+
+/// Visible (non-thought) text parts of a Gemini candidate.
+fn gemini_text_parts(candidate: &mut Value) -> impl Iterator<Item = &mut Value> {
+    candidate
+        .pointer_mut("/content/parts")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter(|part| part.get("thought").and_then(Value::as_bool) != Some(true))
+        .filter_map(|part| part.get_mut("text"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::routing::virtual_models::VirtualModels;
+
+    fn virtual_model() -> std::sync::Arc<VirtualModel> {
+        let config: AppConfig = serde_yaml::from_str(
+            r#"
+upstream_services: []
+client_authentication: { allowed_keys: ["k"] }
+virtual_models:
+  - name: brief
+    model: gpt-4o-mini
+    system_prompt: "Be brief."
+    defaults: { temperature: 0.2, max_tokens: 128, stop: ["END"] }
+    postprocess:
+      - { type: trim_at, markers: ["@@"] }
+"#,
+        )
+        .unwrap();
+        std::sync::Arc::clone(VirtualModels::new(&config).get("brief").unwrap())
+    }
+
+    fn rewrite(body: &str, ingress: IngressApi) -> Value {
+        let out = apply_virtual_model_request(body.as_bytes(), ingress, &virtual_model()).unwrap();
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    #[test]
+    fn test_request_rewrite_per_ingress() {
+        let chat = rewrite(
+            r#"{"model":"brief","messages":[{"role":"user","content":"hi"}],"temperature":1}"#,
+            IngressApi::OpenAiChat,
+        );
+        assert_eq!(chat["model"], "gpt-4o-mini");
+        assert_eq!(chat["messages"][0]["content"], "Be brief.");
+        assert_eq!(chat["temperature"], 1);
+        assert_eq!(chat["max_tokens"], 128);
+        assert_eq!(chat["stop"][0], "END");
+
+        let anthropic = rewrite(
+            r#"{"model":"brief","system":[{"type":"text","text":"ctx"}],"messages":[]}"#,
+            IngressApi::Anthropic,
+        );
+        assert_eq!(anthropic["system"][0]["text"], "Be brief.");
+        assert_eq!(anthropic["system"][1]["text"], "ctx");
+        assert_eq!(anthropic["stop_sequences"][0], "END");
+
+        let responses = rewrite(
+            r#"{"model":"brief","input":"hi","instructions":"Use French."}"#,
+            IngressApi::OpenAiResponses,
+        );
+        assert_eq!(responses["instructions"], "Be brief.\n\nUse French.");
+        assert_eq!(responses["max_output_tokens"], 128);
+        assert!(responses.get("stop").is_none());
+
+        let gemini = rewrite(
+            r#"{"contents":[],"generationConfig":{"maxOutputTokens":8}}"#,
+            IngressApi::Gemini,
+        );
+        assert!(gemini.get("model").is_none());
+        assert_eq!(gemini["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        assert_eq!(gemini["generationConfig"]["maxOutputTokens"], 8);
+        assert_eq!(gemini["generationConfig"]["temperature"], 0.2);
+    }
+
+    #[test]
+    fn test_sse_frames_trimmed_per_output() {
+        let model = virtual_model();
+        let mut trimmed = Vec::new();
+        let first = postprocess_sse_frame(
+            b"event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"ok@@tail\"}}\n\n",
+            IngressApi::Anthropic,
+            &model,
+            &mut trimmed,
+        )
+        .unwrap();
+        let first = std::str::from_utf8(&first).unwrap();
+        assert!(first.starts_with("event: content_block_delta\ndata: {"));
+        assert!(first.contains(r#""text":"ok""#));
+        assert!(first.ends_with("}\n\n"));
+
+        let second = postprocess_sse_frame(
+            b"data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"more\"}}\n\n",
+            IngressApi::Anthropic,
+            &model,
+            &mut trimmed,
+        )
+        .unwrap();
+        assert!(std::str::from_utf8(&second)
+            .unwrap()
+            .contains(r#""text":"""#));
+        assert!(postprocess_sse_frame(
+            b"data: [DONE]\n\n",
+            IngressApi::OpenAiChat,
+            &model,
+            &mut trimmed
+        )
+        .is_none());
+    }
+}
@@ -91,7 +91,10 @@ pub(crate) async fn run_channel_b_fast_path_uri_url<'a>(
         let candidate_prepared_upstream = &state.prepared_upstreams[candidate_route.upstream_index];
         let candidate_provider = candidate_prepared_upstream.provider_kind();
         if !is_protocol_passthrough(candidate_provider, config.ingress) {
-            if last_passthrough_err.is_some() && !plan.stream_requested {
+            // The raw body only fits passthrough candidates: hand the rest of
+            // the failover to the canonical flow, which re-encodes the request
+            // for each remaining candidate's provider.
+            if last_passthrough_err.is_some() {
                 plan.state.route = candidate_route;
                 plan.state.provider = candidate_provider;
                 last_passthrough_err = None;
@@ -99,7 +102,8 @@ pub(crate) async fn run_channel_b_fast_path_uri_url<'a>(
             }
             continue;
         }
-        let proxy_url = candidate_prepared_upstream.proxy_for(plan.stream_requested);
+        let proxy = candidate_prepared_upstream.proxy_for(plan.stream_requested);
+        let proxy_url = proxy.as_deref();
         let parsed_passthrough_uri = if state.transport.hyper_passthrough_enabled_for(proxy_url) {
             (config.uri_getter)(candidate_prepared_upstream)
         } else {
@@ -142,7 +146,7 @@ pub(crate) async fn run_channel_b_fast_path_uri_url<'a>(
                 parsed_passthrough_url,
                 url: candidate_url,
                 proxy_url,
-                upstream_headers,
+                upstream_headers: &upstream_headers,
                 passthrough_body,
             };
             match handle_no_auto_fallback_attempt(state, &plan, route_idx, candidate_route, attempt)
@@ -166,7 +170,7 @@ pub(crate) async fn run_channel_b_fast_path_uri_url<'a>(
             parsed_passthrough_url,
             url: candidate_url,
             proxy_url,
-            upstream_headers,
+            upstream_headers: &upstream_headers,
             passthrough_body,
         };
         let native_result = dispatch_attempt(state, attempt).await;
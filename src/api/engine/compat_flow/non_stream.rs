@@ -51,9 +51,10 @@ pub(crate) async fn run_non_stream_with_fallback<S: CompatFlowSpec>(
         input.route.actual_model,
         input.upstream_canonical.stream,
     );
-    let proxy_url = input
+    let proxy = input
         .prepared_upstream
         .proxy_for(input.upstream_canonical.stream);
+    let proxy_url = proxy.as_deref();
     let upstream_headers = build_provider_headers_prepared(input.prepared_upstream);
     let io_ctx = UpstreamIoRequest {
         state: input.state.as_ref(),
@@ -62,7 +63,7 @@ pub(crate) async fn run_non_stream_with_fallback<S: CompatFlowSpec>(
         parsed_hyper_uri: parsed_uri,
         proxy_url,
         preconfigured_proxy_client: input.state.transport.preconfigured_proxy_client(proxy_url),
-        upstream_headers,
+        upstream_headers: &upstream_headers,
         provider: input.provider,
         client_model: input.client_model,
     };
@@ -31,7 +31,7 @@ pub(crate) async fn try_raw_inject_fast_path<S: CompatFlowSpec>(
     let Some(raw_fast) = S::try_raw_inject_fast_path(
         body,
         route.actual_model,
-        &state.config.features,
+        state.fc_prompt_template(route.upstream_index, client_model, body),
         probe_ranges,
     )?
     else {
@@ -44,7 +44,8 @@ pub(crate) async fn try_raw_inject_fast_path<S: CompatFlowSpec>(
         static_parsed_upstream_url(prepared_upstream, route.actual_model, raw_fast.stream);
     let inject_hyper_uri =
         static_parsed_upstream_uri(prepared_upstream, route.actual_model, raw_fast.stream);
-    let proxy_url = prepared_upstream.proxy_for(raw_fast.stream);
+    let proxy = prepared_upstream.proxy_for(raw_fast.stream);
+    let proxy_url = proxy.as_deref();
     let inject_headers = build_provider_headers_prepared(prepared_upstream);
     let io_ctx = UpstreamIoRequest {
         state,
@@ -53,7 +54,7 @@ pub(crate) async fn try_raw_inject_fast_path<S: CompatFlowSpec>(
         parsed_hyper_uri: inject_hyper_uri,
         proxy_url,
         preconfigured_proxy_client: state.transport.preconfigured_proxy_client(proxy_url),
-        upstream_headers: inject_headers,
+        upstream_headers: &inject_headers,
         provider,
         client_model,
     };
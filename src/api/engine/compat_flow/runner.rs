@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::sync::Arc;
 
 use axum::http::HeaderMap;
@@ -5,22 +6,47 @@ use axum::response::Response;
 use smallvec::{smallvec, SmallVec};
 
 use crate::api::common::{
-    is_protocol_passthrough, passthrough_non_streaming_bytes, passthrough_non_streaming_uri_bytes,
-    passthrough_non_streaming_url_bytes, passthrough_streaming_bytes,
-    passthrough_streaming_uri_bytes, passthrough_streaming_url_bytes,
-    rewrite_model_field_in_json_body_with_range,
+    aggregate_stream_response, annotate_moderation, append_stream_stats,
+    apply_virtual_model_request, check_draft_response, check_response_quality,
+    client_assistant_prefill, client_stop_sequences, enforce_tool_schemas, is_protocol_passthrough,
+    mark_cascade_tier, mark_dropped_params, mark_upstream_response_id, moderate_request,
+    non_streaming_request_body, passthrough_non_streaming_bytes,
+    passthrough_non_streaming_uri_bytes, passthrough_non_streaming_url_bytes,
+    passthrough_streaming_bytes, passthrough_streaming_uri_bytes, passthrough_streaming_url_bytes,
+    postprocess_output_response, postprocess_virtual_model_response, required_capabilities,
+    rewrite_model_field_in_json_body_with_range, rewrite_response_model, stream_stats_requested,
+    stream_usage_requested, streaming_request_body, strict_tool_specs, strip_responses_reasoning,
+    strip_unrequested_stream_usage, synthesize_stream_response, tap_route_latency,
+    tap_trace_response, tool_schema_retry_request, CommonRequestProbe, DroppedParams,
+    RouteLatencyProbe, StreamStats, SyntheticStreamPacing, ToolSchemaViolation,
 };
 use crate::api::engine::channel_b::core::{ChannelBFastPathOutcome, ChannelBPlan, ChannelBState};
 use crate::api::engine::fallback_common::run_preencoded_retry;
 use crate::api::engine::pipeline::{
     bootstrap_flow, prepare_upstream_io_request, CommonProbeRanges, UpstreamIoRequest,
 };
-use crate::error::CanonicalError;
+#[cfg(feature = "analytics")]
+use crate::auth::{extract_api_key, key_fingerprint};
+use crate::config::StopSequenceEmulation;
+use crate::error::{format_error, CanonicalError};
 use crate::fc;
-use crate::protocol::canonical::CanonicalToolSpec;
-use crate::routing::session;
+#[cfg(feature = "analytics")]
+use crate::observability::analytics::RequestFacts;
+use crate::observability::upstream_errors::new_reference as new_upstream_error_reference;
+use crate::protocol::canonical::{CanonicalToolSpec, IngressApi};
+use crate::routing::capabilities::{self, Capabilities};
+use crate::routing::cascade::{CascadeModel, CascadeTier};
+use crate::routing::virtual_models::VirtualModel;
 use crate::routing::RouteTarget;
-use crate::state::AppState;
+use crate::routing::{rules, session};
+use crate::state::request_context::{self, AttemptOutcome, RequestContext, RequestOutcome};
+use crate::state::{note_served_upstream, AppState};
+use crate::stream::broadcast::STREAM_SESSION_HEADER;
+use crate::stream::memory_budget;
+use crate::stream::resumable::LAST_EVENT_ID_HEADER;
+use crate::stream::text_pipeline::TextPipeline;
+use crate::transport::anthropic_beta::client_anthropic_betas;
+use crate::transport::openai_organization::client_openai_organization;
 
 use super::bootstrap::probe_messages_range;
 use super::non_stream::{run_non_stream_with_fallback, NonStreamFallbackInput};
@@ -34,6 +60,14 @@ struct SingleCandidateCtx<'a> {
     fc_decision: crate::state::FcDecision,
 }
 
+/// One run of the compat flow, with what it noted about the upstreams it
+/// tried.
+struct Attempt {
+    result: Result<Response, CanonicalError>,
+    served_upstream: Option<usize>,
+    dropped_params: DroppedParams,
+}
+
 struct BootstrapResolved<'a> {
     route_candidates: SmallVec<[RouteTarget<'a>; 4]>,
     route: RouteTarget<'a>,
@@ -56,18 +90,689 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
     requested_model_override: Option<&str>,
     stream_requested_override: Option<bool>,
 ) -> Result<Response, CanonicalError> {
-    let mut request_seq: Option<u64> = None;
-
     state.authenticate(S::INGRESS, &headers)?;
-
+    let resumable_streams = state.resumable_streams().map(Arc::clone);
+    if let Some(streams) = &resumable_streams {
+        // A reconnecting client continues its stream instead of asking again.
+        let resumed = headers
+            .get(LAST_EVENT_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|last_event_id| streams.resume(last_event_id));
+        if let Some(response) = resumed {
+            return Ok(response);
+        }
+    }
     let probe = S::parse_probe(&body)?;
-    let requested_model = requested_model_override.unwrap_or(probe.model.as_ref());
+    if let Some(max_buffered_bytes) = state.memory_budget() {
+        let stream_requested = stream_requested_override.unwrap_or(probe.stream.unwrap_or(false));
+        if stream_requested && memory_budget::over_budget(max_buffered_bytes) {
+            memory_budget::note_rejection();
+            tracing::warn!(
+                buffered_bytes = memory_budget::buffered_bytes(),
+                max_buffered_bytes,
+                "streaming request refused over the memory budget"
+            );
+            return Err(CanonicalError::Overloaded {
+                message: "Server is over its memory budget; retry shortly".to_string(),
+                retry_after: Some(1),
+            });
+        }
+    }
+    let moderation_annotation = match &state.config.features.moderation {
+        Some(moderation) => moderate_request(&state, moderation, S::INGRESS, &body).await?,
+        None => None,
+    };
+
+    let client_model = requested_model_override.unwrap_or(probe.model.as_ref());
+    // A key model map renames the model before any other lookup; responses
+    // keep reporting the model the client asked for.
+    let key_model = state
+        .key_model(S::INGRESS, &headers, client_model)
+        .map(Arc::clone);
+    let routed_model = key_model
+        .as_deref()
+        .map_or(client_model, |key_model| key_model.target.as_str());
+    let response = if let Some(virtual_model) = state.virtual_model(routed_model) {
+        let virtual_model = Arc::clone(virtual_model);
+        let body = apply_virtual_model_request(&body, S::INGRESS, &virtual_model)?;
+        let probe = S::parse_probe(&body)?;
+        run_routed_compat_handler::<S>(
+            &state,
+            &headers,
+            &body,
+            &probe,
+            client_model,
+            stream_requested_override,
+            Some(virtual_model),
+        )
+        .await?
+    } else if let Some(cascade) = state.cascade_model(routed_model) {
+        let cascade = Arc::clone(cascade);
+        run_cascade_compat_handler::<S>(
+            &state,
+            &headers,
+            &body,
+            &probe,
+            client_model,
+            stream_requested_override,
+            &cascade,
+        )
+        .await?
+    } else if let Some(key_model) = key_model {
+        let body = apply_virtual_model_request(&body, S::INGRESS, &key_model)?;
+        let probe = S::parse_probe(&body)?;
+        run_routed_compat_handler::<S>(
+            &state,
+            &headers,
+            &body,
+            &probe,
+            client_model,
+            stream_requested_override,
+            Some(key_model),
+        )
+        .await?
+    } else {
+        run_routed_compat_handler::<S>(
+            &state,
+            &headers,
+            &body,
+            &probe,
+            client_model,
+            stream_requested_override,
+            None,
+        )
+        .await?
+    };
+    let response = annotate_moderation(response, moderation_annotation);
+    Ok(match resumable_streams {
+        Some(streams) => streams.publish(response),
+        None => response,
+    })
+}
+
+/// Serve a cascade model: run the draft model non-streaming and, when its
+/// answer fails the cascade checks, re-run the request on the verify model.
+///
+/// An accepted draft is replayed as a synthesized stream for streaming
+/// clients.
+async fn run_cascade_compat_handler<S: CompatFlowSpec>(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    body: &bytes::Bytes,
+    probe: &CommonRequestProbe<'_>,
+    client_model: &str,
+    stream_requested_override: Option<bool>,
+    cascade: &CascadeModel,
+) -> Result<Response, CanonicalError> {
+    let stream_requested = stream_requested_override.unwrap_or(probe.stream.unwrap_or(false));
+    let mut draft_body = apply_virtual_model_request(body, S::INGRESS, &cascade.draft)?;
+    if stream_requested {
+        draft_body = non_streaming_request_body(&draft_body, S::INGRESS)?;
+    }
+    let draft_probe = S::parse_probe(&draft_body)?;
+    let draft = run_routed_compat_handler::<S>(
+        state,
+        headers,
+        &draft_body,
+        &draft_probe,
+        client_model,
+        Some(false),
+        Some(Arc::clone(&cascade.draft)),
+    )
+    .await;
+    let rejection = match draft {
+        Ok(response) => match check_draft_response(response, S::INGRESS, cascade).await {
+            Ok(response) if stream_requested => {
+                let pacing = synthetic_stream_pacing::<S>(state, body);
+                let response =
+                    synthesize_stream_response(response, S::INGRESS, client_model, pacing).await?;
+                return Ok(mark_cascade_tier(response, CascadeTier::Draft));
+            }
+            Ok(response) => return Ok(mark_cascade_tier(response, CascadeTier::Draft)),
+            Err(reason) => reason,
+        },
+        Err(_) => "draft_error",
+    };
+    tracing::info!(
+        cascade = %cascade.name,
+        draft = %cascade.draft.target,
+        verify = %cascade.verify.target,
+        reason = rejection,
+        "cascade draft rejected; re-running on verify model"
+    );
+    let verify_body = apply_virtual_model_request(body, S::INGRESS, &cascade.verify)?;
+    let verify_probe = S::parse_probe(&verify_body)?;
+    let response = run_routed_compat_handler::<S>(
+        state,
+        headers,
+        &verify_body,
+        &verify_probe,
+        client_model,
+        stream_requested_override,
+        Some(Arc::clone(&cascade.verify)),
+    )
+    .await?;
+    Ok(mark_cascade_tier(response, CascadeTier::Verify))
+}
+
+fn synthetic_stream_pacing<S: CompatFlowSpec>(
+    state: &AppState,
+    body: &bytes::Bytes,
+) -> SyntheticStreamPacing {
+    let features = &state.config.features;
+    SyntheticStreamPacing {
+        chunk_chars: features.synthetic_stream_chunk_chars,
+        interval: std::time::Duration::from_millis(features.synthetic_stream_interval_ms),
+        include_usage: S::INGRESS != IngressApi::OpenAiChat || stream_usage_requested(body),
+    }
+}
+
+/// Run a parsed request, re-asking the model while an upstream configured
+/// with `tool_schema_validation: retry` answers with tool calls that break
+/// their `strict` schemas.
+async fn run_routed_compat_handler<S: CompatFlowSpec>(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    body: &bytes::Bytes,
+    probe: &CommonRequestProbe<'_>,
+    client_model: &str,
+    stream_requested_override: Option<bool>,
+    virtual_model: Option<Arc<VirtualModel>>,
+) -> Result<Response, CanonicalError> {
+    let mut response = run_routed_attempt::<S>(
+        state,
+        headers,
+        body,
+        probe,
+        client_model,
+        stream_requested_override,
+        virtual_model.clone(),
+    )
+    .await?;
+    let max_attempts = state.config.features.fc_error_retry_max_attempts;
+    let mut attempts = 0;
+    let mut retry_body: Option<bytes::Bytes> = None;
+    while let Some(violation) = response.extensions_mut().remove::<ToolSchemaViolation>() {
+        if attempts >= max_attempts {
+            return Err(violation.into_error());
+        }
+        attempts += 1;
+        tracing::warn!(
+            model = %client_model,
+            attempt = attempts,
+            "tool call arguments break their strict schema; asking the model again"
+        );
+        let retry = retry_body.insert(tool_schema_retry_request(
+            retry_body.as_ref().unwrap_or(body),
+            S::INGRESS,
+            &violation,
+        )?);
+        let retry_probe = S::parse_probe(retry)?;
+        response = run_routed_attempt::<S>(
+            state,
+            headers,
+            retry,
+            &retry_probe,
+            client_model,
+            stream_requested_override,
+            virtual_model.clone(),
+        )
+        .await?;
+    }
+    Ok(response)
+}
+
+/// Run a parsed request, adapting the response mode to the upstream.
+///
+/// A non-streaming request that can route to a stream-only upstream is
+/// streamed and re-aggregated; a streaming request that can route to a
+/// non-stream-only upstream is served as a stream synthesized from the
+/// complete response.
+async fn run_routed_attempt<S: CompatFlowSpec>(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    body: &bytes::Bytes,
+    probe: &CommonRequestProbe<'_>,
+    client_model: &str,
+    stream_requested_override: Option<bool>,
+    virtual_model: Option<Arc<VirtualModel>>,
+) -> Result<Response, CanonicalError> {
     let stream_requested = stream_requested_override.unwrap_or(probe.stream.unwrap_or(false));
+    let routed_model = virtual_model
+        .as_deref()
+        .map_or(client_model, |virtual_model| virtual_model.target.as_str());
+    if stream_requested && state.requires_non_streaming_upstream(routed_model) {
+        let non_stream_body = non_streaming_request_body(body, S::INGRESS)?;
+        let non_stream_probe = S::parse_probe(&non_stream_body)?;
+        let response = run_parsed_compat_handler::<S>(
+            state,
+            headers,
+            &non_stream_body,
+            &non_stream_probe,
+            client_model,
+            Some(false),
+            virtual_model,
+        )
+        .await?;
+        // A rejected answer is re-asked before anything is streamed.
+        if response.extensions().get::<ToolSchemaViolation>().is_some() {
+            return Ok(response);
+        }
+        let pacing = synthetic_stream_pacing::<S>(state, body);
+        return synthesize_stream_response(response, S::INGRESS, client_model, pacing).await;
+    }
+    if !stream_requested && state.requires_streaming_upstream(routed_model) {
+        let stream_body = streaming_request_body(body, S::INGRESS)?;
+        let stream_probe = S::parse_probe(&stream_body)?;
+        let response = run_parsed_compat_handler::<S>(
+            state,
+            headers,
+            &stream_body,
+            &stream_probe,
+            client_model,
+            Some(true),
+            virtual_model,
+        )
+        .await?;
+        return aggregate_stream_response(response, S::INGRESS, client_model).await;
+    }
+    run_parsed_compat_handler::<S>(
+        state,
+        headers,
+        body,
+        probe,
+        client_model,
+        stream_requested_override,
+        virtual_model,
+    )
+    .await
+}
+
+/// Run the flow for a parsed request and apply client-facing response rewrites.
+///
+/// `client_model` is the model the client asked for; with a virtual model the
+/// request is routed to its target and responses report `client_model`.
+async fn run_parsed_compat_handler<S: CompatFlowSpec>(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    body: &bytes::Bytes,
+    probe: &CommonRequestProbe<'_>,
+    client_model: &str,
+    stream_requested_override: Option<bool>,
+    virtual_model: Option<Arc<VirtualModel>>,
+) -> Result<Response, CanonicalError> {
+    let requested_model = virtual_model
+        .as_deref()
+        .map_or(client_model, |virtual_model| virtual_model.target.as_str());
+    let stream_requested = stream_requested_override.unwrap_or(probe.stream.unwrap_or(false));
+    let journal_entry = state
+        .request_journal()
+        .map(|journal| journal.begin(S::INGRESS, client_model));
+    let mut tail_request = state
+        .live_tail()
+        .begin(S::INGRESS, client_model, stream_requested);
+    let trace_turn = state.conversation_traces().map(|traces| {
+        let sticky_material = session::route_sticky_material(
+            body.as_ref(),
+            probe_messages_range(probe.ranges.as_ref()),
+            &state.config.features.sticky_hash,
+        );
+        let session = state.route_session_hash(S::INGRESS, headers, client_model, &sticky_material);
+        traces.begin(
+            session,
+            S::INGRESS,
+            body,
+            client_model,
+            requested_model,
+            stream_requested,
+        )
+    });
+    let priority = state.request_priority(S::INGRESS, headers);
+    let permit = state.admit_request(priority).await;
+    let started = std::time::Instant::now();
+    let stop_sequences = (stream_requested
+        && state.config.features.stop_sequence_emulation != StopSequenceEmulation::Off)
+        .then(|| client_stop_sequences(S::INGRESS, body))
+        .filter(|stops| !stops.is_empty())
+        .map(Arc::from);
+    let client_prefill = client_assistant_prefill(S::INGRESS, body).map(Arc::from);
+    let capability_requirements = if state.has_model_capabilities() {
+        required_capabilities(S::INGRESS, body, probe.has_tools)
+    } else {
+        Capabilities::NONE
+    };
+    let rule_proxy = state
+        .routing_rules()
+        .proxy(rules::active_rule(), client_model, headers);
+    let strict_tools: Arc<[CanonicalToolSpec]> = if probe.has_tools {
+        Arc::from(strict_tool_specs(S::INGRESS, body))
+    } else {
+        Arc::from([])
+    };
+    let retry_policy = state.quality_retry().filter(|_| !stream_requested);
+    let mut excluded_upstreams: Vec<usize> = Vec::new();
+    let mut rejected = None;
+    let context = RequestContext {
+        stop_sequences,
+        prefill: client_prefill,
+        anthropic_betas: client_anthropic_betas(headers),
+        openai_organization: client_openai_organization(headers),
+        priority,
+        required_capabilities: capability_requirements,
+        proxy: rule_proxy,
+        forward_response_headers: state.forward_response_headers(),
+    };
+    // The outcome spans quality retries, so a retry that fails leaves the ids
+    // of the rejected answer it surfaces.
+    let (attempt, outcome) = request_context::scope(
+        context,
+        // Boxed so the context does not grow every handler future by the size
+        // of the whole compat flow.
+        Box::pin(async {
+            loop {
+                request_context::begin_attempt(Arc::from(excluded_upstreams.as_slice()));
+                let result = run_compat_flow::<S>(
+                    state,
+                    headers,
+                    body,
+                    probe,
+                    requested_model,
+                    stream_requested,
+                )
+                .await;
+                let AttemptOutcome {
+                    served_upstream,
+                    dropped_params,
+                } = request_context::finish_attempt();
+                let result = match (result, served_upstream) {
+                    (Ok(response), Some(upstream_index)) if !strict_tools.is_empty() => {
+                        enforce_tool_schemas(
+                            response,
+                            S::INGRESS,
+                            &strict_tools,
+                            state.tool_schema_validation(upstream_index),
+                        )
+                        .await
+                    }
+                    (result, _) => result,
+                };
+                let attempt = Attempt {
+                    result,
+                    served_upstream,
+                    dropped_params,
+                };
+                let policy =
+                    retry_policy.filter(|policy| excluded_upstreams.len() < policy.max_retries);
+                match (policy, attempt) {
+                    (
+                        Some(policy),
+                        Attempt {
+                            result: Ok(response),
+                            served_upstream: Some(upstream_index),
+                            dropped_params,
+                        },
+                    ) => {
+                        let (response, issue) =
+                            check_response_quality(response, S::INGRESS, policy).await;
+                        let attempt = Attempt {
+                            result: Ok(response),
+                            served_upstream: Some(upstream_index),
+                            dropped_params,
+                        };
+                        let Some(reason) = issue else {
+                            break attempt;
+                        };
+                        policy.record_retry(upstream_index, requested_model, reason);
+                        tracing::info!(
+                            upstream = %state.upstream_name(upstream_index),
+                            model = %requested_model,
+                            reason,
+                            "low-quality answer; retrying on the next candidate"
+                        );
+                        excluded_upstreams.push(upstream_index);
+                        rejected = Some(attempt);
+                    }
+                    (_, attempt) => {
+                        // A retry that fails, e.g. without another candidate, surfaces
+                        // the rejected answer instead.
+                        if attempt.result.is_err() {
+                            if let Some(rejected) = rejected.take() {
+                                break rejected;
+                            }
+                        }
+                        break attempt;
+                    }
+                }
+            }
+        }),
+    )
+    .await;
+    let Attempt {
+        mut result,
+        served_upstream,
+        dropped_params,
+    } = attempt;
+    let RequestOutcome {
+        response_ids,
+        upstream_error,
+        retries,
+        forwarded_headers,
+    } = outcome;
+    let mut hidden_upstream_error = None;
+    if state.config.features.upstream_error_references {
+        if let (Err(CanonicalError::Upstream { message, .. }), Some(mut detail)) =
+            (&mut result, upstream_error)
+        {
+            // Single-candidate paths record no failure to name the upstream;
+            // they are noted as served before sending.
+            if detail.upstream.is_empty() {
+                if let Some(upstream_index) = served_upstream {
+                    detail.upstream = state.upstream_name(upstream_index).to_string();
+                }
+            }
+            let reference = new_upstream_error_reference();
+            tracing::warn!(
+                reference = %reference,
+                upstream = %detail.upstream,
+                status = detail.status,
+                body = %detail.body,
+                "upstream error answered with a reference"
+            );
+            *message = format!("Upstream request failed (reference: {reference})");
+            hidden_upstream_error = Some((reference, detail));
+        }
+    }
+    if let (Some(tail_request), Some(upstream_index)) = (&mut tail_request, served_upstream) {
+        tail_request.routed(state.upstream_name(upstream_index));
+    }
+    if !dropped_params.is_empty() {
+        result = result.map(|response| mark_dropped_params(response, &dropped_params));
+    }
+    if !forwarded_headers.is_empty() {
+        result = result.map(|mut response| {
+            response.headers_mut().extend(forwarded_headers);
+            response
+        });
+    }
+    if let (Some(ids), Some(upstream_index)) = (response_ids, served_upstream) {
+        result = result.map(|response| {
+            let response = mark_upstream_response_id(response, &ids);
+            state.record_response_ids(ids, upstream_index);
+            response
+        });
+    }
+    if let Some(upstream_index) = served_upstream.filter(|_| stream_requested) {
+        result = result.map(|response| {
+            tap_route_latency(
+                response,
+                RouteLatencyProbe {
+                    state: Arc::clone(state),
+                    upstream_index,
+                    model_group: requested_model.to_string(),
+                    started,
+                },
+            )
+        });
+    }
+    if let Some(postprocess) = state.output_postprocess() {
+        let pipeline = TextPipeline::new(
+            Arc::clone(postprocess),
+            client_stop_sequences(S::INGRESS, body),
+        );
+        result = match result {
+            Ok(response) => {
+                postprocess_output_response(response, S::INGRESS, client_model, pipeline).await
+            }
+            other => other,
+        };
+    }
+    let rewrite_model = virtual_model.is_some() || state.config.features.rewrite_response_model;
+    if let Some(virtual_model) = virtual_model {
+        result = match result {
+            Ok(response) => {
+                postprocess_virtual_model_response(response, S::INGRESS, virtual_model).await
+            }
+            other => other,
+        };
+    }
+    let result = match result {
+        Ok(response) if rewrite_model => {
+            let max_body_bytes = state.config.server.max_response_bytes;
+            rewrite_response_model(response, S::INGRESS, client_model, max_body_bytes).await
+        }
+        other => other,
+    };
+    let result = match result {
+        Ok(response)
+            if S::INGRESS == IngressApi::OpenAiResponses
+                && state.config.features.strip_responses_reasoning =>
+        {
+            strip_responses_reasoning(response).await
+        }
+        other => other,
+    };
+    // Usage is stripped after the journal tap so it is still accounted.
+    let strip_usage =
+        S::INGRESS == IngressApi::OpenAiChat && stream_requested && !stream_usage_requested(body);
+    let result = match journal_entry {
+        Some(entry) => match result {
+            Ok(response) => Ok(entry.tap_response(response)),
+            Err(err) => {
+                if let Some((reference, detail)) = hidden_upstream_error {
+                    entry.record_upstream_error(&reference, detail);
+                }
+                entry.finish(format_error(&err, S::INGRESS).0.as_u16());
+                Err(err)
+            }
+        },
+        None => result,
+    };
+    #[cfg(feature = "analytics")]
+    let result = match state.analytics() {
+        Some(analytics) => {
+            let entry = analytics.entry(
+                RequestFacts {
+                    ingress: S::INGRESS,
+                    client_key: extract_api_key(S::INGRESS, headers)
+                        .ok()
+                        .map(key_fingerprint),
+                    model: client_model.to_string(),
+                    upstream: served_upstream.map(|index| state.upstream_name(index).to_string()),
+                    routing_rule: rules::active_rule()
+                        .and_then(|rule| state.routing_rules().name(rule))
+                        .map(str::to_string),
+                    stream: stream_requested,
+                    quality_retries: u32::try_from(excluded_upstreams.len()).unwrap_or(u32::MAX),
+                },
+                started,
+            );
+            match result {
+                Ok(response) => Ok(entry.tap_response(response)),
+                Err(err) => {
+                    entry.finish(format_error(&err, S::INGRESS).0.as_u16());
+                    Err(err)
+                }
+            }
+        }
+        None => result,
+    };
+    let result = match trace_turn {
+        Some(mut turn) => {
+            turn.set_upstream(served_upstream.map(|index| state.upstream_name(index)));
+            match result {
+                Ok(response) => Ok(tap_trace_response(response, turn, S::INGRESS, client_model)),
+                Err(err) => {
+                    let (status, body) = format_error(&err, S::INGRESS);
+                    turn.finish(status.as_u16(), Some(body));
+                    Err(err)
+                }
+            }
+        }
+        None => result,
+    };
+    let result = match result {
+        Ok(response) if stream_requested && stream_stats_requested(headers) => {
+            let upstream = served_upstream
+                .filter(|_| state.config.features.stream_stats_upstream)
+                .map(|index| state.upstream_name(index).to_string());
+            Ok(append_stream_stats(
+                response,
+                StreamStats {
+                    started,
+                    upstream,
+                    retries,
+                },
+            ))
+        }
+        other => other,
+    };
+    let result = match result {
+        Ok(response) if strip_usage => Ok(strip_unrequested_stream_usage(response)),
+        other => other,
+    };
+    let broadcast_session = headers
+        .get(STREAM_SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| stream_requested && state.config.features.enable_stream_broadcast);
+    let result = match (result, broadcast_session) {
+        (Ok(response), Some(session)) => Ok(state.stream_broadcasts().publish(response, session)),
+        (result, _) => result,
+    };
+    let result = match tail_request {
+        Some(tail_request) => match result {
+            Ok(response) => Ok(tail_request.tap_response(response)),
+            Err(err) => {
+                let status = format_error(&err, S::INGRESS).0.as_u16();
+                tail_request.failed(status, err.to_string());
+                Err(err)
+            }
+        },
+        None => result,
+    };
+    match permit {
+        Some(permit) => result.map(|response| permit.hold_until_body_ends(response)),
+        None => result,
+    }
+}
+
+async fn run_compat_flow<S: CompatFlowSpec>(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    body: &bytes::Bytes,
+    probe: &CommonRequestProbe<'_>,
+    requested_model: &str,
+    stream_requested: bool,
+) -> Result<Response, CanonicalError> {
+    let mut request_seq: Option<u64> = None;
     let single_candidate_ctx =
         resolve_single_candidate_ctx(state.as_ref(), requested_model, probe.has_tools)?;
+    if let Some(single_ctx) = single_candidate_ctx.as_ref() {
+        state.check_upstream_request_size(single_ctx.route.upstream_index, body.len())?;
+        note_served_upstream(single_ctx.route.upstream_index);
+    }
     if let Some(response) = try_single_candidate_fast_path::<S>(
-        &state,
-        &body,
+        state,
+        body,
         requested_model,
         stream_requested,
         probe.has_tools,
@@ -87,14 +792,20 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
             fc_decision: single_ctx.fc_decision,
         }
     } else {
-        bootstrap_multi_candidate_flow::<S>(
+        let mut resolved = bootstrap_multi_candidate_flow::<S>(
             state.as_ref(),
-            &headers,
-            &body,
+            headers,
+            body,
             requested_model,
             probe.ranges.as_ref(),
             probe.has_tools,
-        )?
+        )?;
+        state.check_upstream_request_size(resolved.route.upstream_index, body.len())?;
+        // Failover skips upstreams whose `max_request_bytes` the body is over.
+        resolved.route_candidates.retain(|candidate| {
+            state.fits_upstream_request_size(candidate.upstream_index, body.len())
+        });
+        resolved
     };
     let route_candidates = resolved.route_candidates;
     let mut route = resolved.route;
@@ -117,7 +828,7 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
         auto_fallback_allowed,
         stream_requested,
     };
-    match S::run_channel_b_fast_path(&state, &body, &mut request_seq, channel_b_plan).await {
+    match S::run_channel_b_fast_path(state, body, &mut request_seq, channel_b_plan).await {
         ChannelBFastPathOutcome::Continue(next_state) => {
             route = next_state.route;
             provider = next_state.provider;
@@ -127,6 +838,8 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
         ChannelBFastPathOutcome::Error(err) => return Err(err),
     }
 
+    // Failover paths re-note the route that finally succeeds.
+    note_served_upstream(route.upstream_index);
     let prepared_upstream = &state.prepared_upstreams[route.upstream_index];
     let request_seq_opt = request_seq;
     let client_model = requested_model;
@@ -134,7 +847,7 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
     if !probe.has_tools && !stream_requested {
         return S::run_no_tools_non_stream(NoToolsCtx {
             state: state.as_ref(),
-            body: &body,
+            body,
             model_value_range: probe
                 .ranges
                 .as_ref()
@@ -156,7 +869,7 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
         && S::supports_wire_inject_provider(provider);
     if let Some(response) = try_raw_inject_fast_path::<S>(
         state.as_ref(),
-        &body,
+        body,
         prepared_upstream,
         route,
         provider,
@@ -171,7 +884,7 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
     }
     let request_seq = request_seq_opt.unwrap_or_else(|| state.next_request_seq());
 
-    let mut wire_request = S::parse_wire_request(&body)?;
+    let mut wire_request = S::parse_wire_request(body)?;
     S::set_request_context(&mut wire_request, requested_model, stream_requested);
 
     if fc_active
@@ -181,7 +894,10 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
     {
         let mut inject_wire = wire_request;
         S::set_wire_model(&mut inject_wire, route.actual_model);
-        let inject_saved_tools = S::apply_wire_inject(&mut inject_wire, &state.config.features)?;
+        let inject_saved_tools = S::apply_wire_inject(
+            &mut inject_wire,
+            state.fc_prompt_template(route.upstream_index, client_model, body),
+        )?;
         let inject_fc_active = S::wire_inject_fc_active(&inject_saved_tools);
         let inject_stream = S::wire_stream_requested(&inject_wire);
         let inject_body = S::encode_wire(&inject_wire)?;
@@ -225,8 +941,13 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
     };
     upstream_canonical.model.clear();
     upstream_canonical.model.push_str(route.actual_model);
+    fc::normalize_system_messages(&mut upstream_canonical, &state.config.features);
     let saved_tools: Arc<[CanonicalToolSpec]> = if fc_active {
-        fc::apply_fc_inject_take_tools(&mut upstream_canonical, &state.config.features)?
+        fc::apply_fc_inject_take_tools(
+            &mut upstream_canonical,
+            &state.config.features,
+            state.fc_prompt_template(route.upstream_index, client_model, body),
+        )?
     } else {
         Arc::from([])
     };
@@ -246,8 +967,8 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
 
     if upstream_canonical.stream {
         return run_stream_failover(StreamFailoverInput::<S> {
-            state: &state,
-            body: &body,
+            state,
+            body,
             requested_model,
             probe_ranges: probe.ranges.as_ref(),
             route_candidates: &route_candidates,
@@ -265,8 +986,8 @@ pub(crate) async fn run_compat_handler_with_route<S: CompatFlowSpec>(
     }
 
     run_non_stream_with_fallback(NonStreamFallbackInput::<S> {
-        state: &state,
-        body: &body,
+        state,
+        body,
         wire_request: wire_request.as_ref(),
         requested_model,
         probe_ranges: probe.ranges.as_ref(),
@@ -451,6 +1172,16 @@ fn resolve_single_candidate_ctx<'a>(
     requested_model: &'a str,
     has_tools: bool,
 ) -> Result<Option<SingleCandidateCtx<'a>>, CanonicalError> {
+    // Routing rules, capability constraints, quality-retry exclusions, and
+    // fallback chains apply during route resolution, which this fast path
+    // bypasses.
+    if rules::active_rule().is_some()
+        || !capabilities::required().is_empty()
+        || request_context::has_exclusions()
+        || state.has_fallback_chain(requested_model)
+    {
+        return Ok(None);
+    }
     let Some(route) = state
         .model_router
         .resolve_if_single_candidate(requested_model)?
@@ -495,19 +1226,24 @@ fn bootstrap_multi_candidate_flow<'a, S: CompatFlowSpec>(
     } else {
         session::SessionClass::Portable
     };
-    let prompt_prefix = if hash_required {
-        session::route_prompt_prefix_bytes(body.as_ref(), messages_range)
+    let sticky_material = if hash_required {
+        session::route_sticky_material(
+            body.as_ref(),
+            messages_range,
+            &state.config.features.sticky_hash,
+        )
     } else {
-        &[]
+        Cow::Borrowed(&[][..])
     };
     let flow = bootstrap_flow(
         state,
         S::INGRESS,
         headers,
         requested_model,
-        prompt_prefix,
+        &sticky_material,
         session_class,
         has_tools,
+        state.file_pinned_upstream(body.as_ref()),
     )?;
     Ok(BootstrapResolved {
         route_candidates: flow.route_candidates,
@@ -5,6 +5,7 @@ use smallvec::SmallVec;
 
 use crate::api::common::CommonProbeRanges;
 use crate::api::engine::pipeline::{encode_for_provider, UpstreamIoRequest};
+use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
 use crate::protocol::canonical::{CanonicalRequest, CanonicalToolSpec, ProviderKind};
 use crate::routing::RouteTarget;
@@ -68,7 +69,8 @@ pub(crate) async fn run_stream_failover<S: CompatFlowSpec>(
             candidate_route.actual_model,
             candidate_canonical.stream,
         );
-        let proxy_url = candidate_prepared_upstream.proxy_for(candidate_canonical.stream);
+        let proxy = candidate_prepared_upstream.proxy_for(candidate_canonical.stream);
+        let proxy_url = proxy.as_deref();
         let candidate_headers = build_provider_headers_prepared(candidate_prepared_upstream);
         let io_ctx = UpstreamIoRequest {
             state: input.state.as_ref(),
@@ -77,7 +79,7 @@ pub(crate) async fn run_stream_failover<S: CompatFlowSpec>(
             parsed_hyper_uri: candidate_hyper_uri,
             proxy_url,
             preconfigured_proxy_client: input.state.transport.preconfigured_proxy_client(proxy_url),
-            upstream_headers: candidate_headers,
+            upstream_headers: &candidate_headers,
             provider: candidate_provider,
             client_model: input.client_model,
         };
@@ -86,6 +88,7 @@ pub(crate) async fn run_stream_failover<S: CompatFlowSpec>(
             candidate_provider,
             candidate_route.actual_model,
             &candidate_canonical,
+            &input.state.config.features,
         )?;
         let attempt_result = S::handle_streaming(
             io_ctx,
@@ -176,6 +179,7 @@ fn encoded_body_for_candidate<'a>(
     provider: ProviderKind,
     model: &'a str,
     canonical: &CanonicalRequest,
+    features: &FeaturesConfig,
 ) -> Result<bytes::Bytes, CanonicalError> {
     if let Some((_, _, cached_body)) = cache.iter().find(|(cached_provider, cached_model, _)| {
         *cached_provider == provider && *cached_model == model
@@ -183,7 +187,7 @@ fn encoded_body_for_candidate<'a>(
         return Ok(cached_body.clone());
     }
 
-    let encoded = encode_for_provider(provider, canonical)?;
+    let encoded = encode_for_provider(provider, canonical, features)?;
     cache.push((provider, model, encoded.clone()));
     Ok(encoded)
 }
@@ -214,9 +218,14 @@ mod tests {
     fn encoded_body_cache_reuses_non_consecutive_provider_model_pair() {
         let mut cache: SmallVec<[(ProviderKind, &str, bytes::Bytes); 4]> = SmallVec::new();
         let mut canonical = sample_canonical("gpt-4.1");
-        let first =
-            encoded_body_for_candidate(&mut cache, ProviderKind::OpenAi, "gpt-4.1", &canonical)
-                .expect("encode first");
+        let first = encoded_body_for_candidate(
+            &mut cache,
+            ProviderKind::OpenAi,
+            "gpt-4.1",
+            &canonical,
+            &FeaturesConfig::default(),
+        )
+        .expect("encode first");
 
         canonical.model = "gpt-4.1-mini".to_string();
         let _second = encoded_body_for_candidate(
@@ -224,13 +233,19 @@ mod tests {
             ProviderKind::OpenAi,
             "gpt-4.1-mini",
             &canonical,
+            &FeaturesConfig::default(),
         )
         .expect("encode second");
 
         canonical.model = "gpt-4.1".to_string();
-        let third =
-            encoded_body_for_candidate(&mut cache, ProviderKind::OpenAi, "gpt-4.1", &canonical)
-                .expect("reuse first");
+        let third = encoded_body_for_candidate(
+            &mut cache,
+            ProviderKind::OpenAi,
+            "gpt-4.1",
+            &canonical,
+            &FeaturesConfig::default(),
+        )
+        .expect("reuse first");
 
         assert_eq!(cache.len(), 2);
         assert_eq!(first, third);
@@ -246,6 +261,7 @@ mod tests {
             ProviderKind::OpenAi,
             "shared-model",
             &canonical,
+            &FeaturesConfig::default(),
         )
         .expect("encode openai");
         let _anthropic = encoded_body_for_candidate(
@@ -253,6 +269,7 @@ mod tests {
             ProviderKind::Anthropic,
             "shared-model",
             &canonical,
+            &FeaturesConfig::default(),
         )
         .expect("encode anthropic");
         let _openai_again = encoded_body_for_candidate(
@@ -260,6 +277,7 @@ mod tests {
             ProviderKind::OpenAi,
             "shared-model",
             &canonical,
+            &FeaturesConfig::default(),
         )
         .expect("reuse openai");
 
@@ -2,8 +2,8 @@ use std::sync::Arc;
 
 use axum::response::Response;
 
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::canonical::{CanonicalRequest, CanonicalToolSpec, IngressApi, ProviderKind};
 use crate::routing::RouteTarget;
 use crate::state::AppState;
@@ -76,7 +76,7 @@ pub(crate) trait CompatFlowSpec {
     fn set_wire_model(wire_request: &mut Self::WireRequest, actual_model: &str);
     fn apply_wire_inject(
         wire_request: &mut Self::WireRequest,
-        features: &FeaturesConfig,
+        prompt_template: Option<&PromptTemplate>,
     ) -> Result<Vec<CanonicalToolSpec>, CanonicalError>;
     fn wire_stream_requested(wire_request: &Self::WireRequest) -> bool;
     fn encode_wire(wire_request: &Self::WireRequest) -> Result<bytes::Bytes, CanonicalError>;
@@ -120,7 +120,7 @@ pub(crate) trait CompatFlowSpec {
     fn try_raw_inject_fast_path(
         _body: &bytes::Bytes,
         _actual_model: &str,
-        _features: &FeaturesConfig,
+        _prompt_template: Option<&PromptTemplate>,
         _probe_ranges: Option<&CommonProbeRanges>,
     ) -> Result<Option<RawInjectPayload>, CanonicalError> {
         Ok(None)
@@ -10,6 +10,7 @@ use crate::api::ingress::openai_chat::io::handle_non_streaming as openai_chat_ha
 use crate::api::ingress::openai_chat::parse::parse_openai_chat_request_wire;
 use crate::api::ingress::openai_responses::io::handle_non_streaming as openai_responses_handle_non_streaming;
 use crate::error::CanonicalError;
+use crate::fc;
 use crate::protocol::anthropic::decoder::decode_anthropic_request_owned;
 use crate::protocol::anthropic::AnthropicRequest;
 use crate::protocol::canonical::IngressApi;
@@ -300,7 +301,9 @@ pub(crate) async fn run_openai_responses_no_tools_non_stream<'a>(
                     "Invalid OpenAI Responses request body: {e}"
                 ))
             })?;
-            cached_upstream_canonical = Some(decode_responses_request_owned(request, request_id)?);
+            let mut decoded = decode_responses_request_owned(request, request_id)?;
+            fc::normalize_system_messages(&mut decoded, &state.config.features);
+            cached_upstream_canonical = Some(decoded);
         }
         let upstream_canonical = cached_upstream_canonical
             .as_mut()
@@ -346,8 +349,9 @@ pub(crate) async fn run_openai_responses_no_tools_non_stream<'a>(
                             "Invalid OpenAI Responses request body: {e}"
                         ))
                     })?;
-                    cached_upstream_canonical =
-                        Some(decode_responses_request_owned(request, request_id)?);
+                    let mut decoded = decode_responses_request_owned(request, request_id)?;
+                    fc::normalize_system_messages(&mut decoded, &state.config.features);
+                    cached_upstream_canonical = Some(decoded);
                 }
                 let upstream_canonical = cached_upstream_canonical
                     .as_mut()
@@ -113,8 +113,11 @@ where
 {
     let io_ctx = io_target.io_ctx(client_model);
     if canonical_request.stream {
-        let upstream_body =
-            crate::api::engine::pipeline::encode_for_provider(provider, canonical_request)?;
+        let upstream_body = crate::api::engine::pipeline::encode_for_provider(
+            provider,
+            canonical_request,
+            &io_ctx.state.config.features,
+        )?;
         return stream_handler(io_ctx, upstream_body, request_seq, fc_active, saved_tools).await;
     }
     non_stream_handler(io_ctx, canonical_request, fc_active, saved_tools).await
@@ -28,6 +28,9 @@ pub(crate) struct FlowBootstrap<'a> {
 
 /// Resolve route candidates and FC policy for an ingress request.
 ///
+/// When `pinned_upstream` is set (the request references a file stored on
+/// that upstream), its candidates are moved to the front of the order.
+///
 /// # Errors
 ///
 /// Returns [`CanonicalError::InvalidRequest`] when no route can be resolved.
@@ -39,13 +42,19 @@ pub(crate) fn bootstrap_flow<'a>(
     prompt_prefix: &[u8],
     session_class: SessionClass,
     has_tools: bool,
+    pinned_upstream: Option<usize>,
 ) -> Result<FlowBootstrap<'a>, CanonicalError> {
     let route_hash = if state.model_router.requires_request_hash_for_ordering(model) {
         state.route_sticky_hash(ingress, headers, model, prompt_prefix)
     } else {
         0
     };
-    let route_candidates = state.resolve_routes_with_policy(model, route_hash, session_class)?;
+    let mut route_candidates =
+        state.resolve_routes_with_policy(model, route_hash, session_class)?;
+    if let Some(pinned) = pinned_upstream {
+        // Stable partition: pinned-upstream candidates first, others keep their order.
+        route_candidates.sort_by_key(|candidate| candidate.upstream_index != pinned);
+    }
     let route = *route_candidates
         .first()
         .ok_or_else(|| CanonicalError::InvalidRequest(format!("No upstream for '{model}'")))?;
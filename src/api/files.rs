@@ -0,0 +1,321 @@
+//! `OpenAI` Files API passthrough (`/v1/files`).
+//!
+//! Uploads are forwarded to one `OpenAI`-compatible upstream and the returned
+//! file id is bound to it, so later retrieve/delete/content calls and
+//! Responses requests that reference the file reach the backend that stores
+//! it. The upload target is the upstream serving the model named in the
+//! `x-toolify-model` header, falling back to the default upstream.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde_json::{json, Value};
+
+use crate::error::{into_axum_response, CanonicalError};
+use crate::protocol::canonical::{IngressApi, ProviderKind};
+use crate::state::{AppState, SessionClass};
+
+const INGRESS: IngressApi = IngressApi::OpenAiChat;
+/// Names the model whose upstream should store an uploaded file.
+pub const MODEL_HINT_HEADER: &str = "x-toolify-model";
+
+/// Operation on a single uploaded file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAction {
+    Retrieve,
+    Delete,
+    Content,
+}
+
+impl FileAction {
+    fn method(self) -> Method {
+        match self {
+            Self::Retrieve | Self::Content => Method::GET,
+            Self::Delete => Method::DELETE,
+        }
+    }
+}
+
+fn stores_files(state: &AppState, upstream_index: usize) -> bool {
+    matches!(
+        state.prepared_upstreams[upstream_index].provider_kind(),
+        ProviderKind::OpenAi | ProviderKind::OpenAiResponses
+    )
+}
+
+fn file_upstreams(state: &AppState) -> impl Iterator<Item = usize> + '_ {
+    (0..state.prepared_upstreams.len()).filter(|&index| stores_files(state, index))
+}
+
+fn no_file_upstream() -> Response {
+    into_axum_response(
+        &CanonicalError::InvalidRequest(
+            "No OpenAI-compatible upstream is configured to store files".to_string(),
+        ),
+        INGRESS,
+    )
+}
+
+/// Pick the upstream that stores a new upload.
+fn upload_upstream(state: &AppState, headers: &HeaderMap) -> Option<usize> {
+    let hinted_model = headers
+        .get(MODEL_HINT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|model| !model.is_empty());
+    if let Some(model) = hinted_model {
+        if let Ok(routes) = state.resolve_routes_with_policy(model, 0, SessionClass::Portable) {
+            if let Some(route) = routes
+                .iter()
+                .find(|route| stores_files(state, route.upstream_index))
+            {
+                return Some(route.upstream_index);
+            }
+        }
+    }
+    let upstreams = &state.config.upstream_services;
+    file_upstreams(state).min_by_key(|&index| !upstreams[index].is_default)
+}
+
+async fn forward(
+    state: &AppState,
+    upstream_index: usize,
+    method: Method,
+    path_suffix: &str,
+    query: Option<&str>,
+    content_type: Option<&HeaderValue>,
+    body: bytes::Bytes,
+) -> Result<reqwest::Response, CanonicalError> {
+    let base_url = state.prepared_upstreams[upstream_index].base_url();
+    let url = match query {
+        Some(query) => format!("{base_url}/files{path_suffix}?{query}"),
+        None => format!("{base_url}/files{path_suffix}"),
+    };
+    let prepared = &state.prepared_upstreams[upstream_index];
+    let mut headers = prepared.static_headers().clone();
+    headers.remove(http::header::CONTENT_TYPE);
+    if let Some(content_type) = content_type {
+        headers.insert(http::header::CONTENT_TYPE, content_type.clone());
+    }
+    state
+        .transport
+        .send_request(
+            &url,
+            method,
+            &headers,
+            body,
+            prepared.proxy_for(false).as_deref(),
+        )
+        .await
+}
+
+fn response_parts(response: &reqwest::Response) -> http::response::Builder {
+    let mut builder = Response::builder().status(response.status());
+    for name in [
+        http::header::CONTENT_TYPE,
+        http::header::CONTENT_DISPOSITION,
+    ] {
+        if let Some(value) = response.headers().get(&name) {
+            builder = builder.header(name, value.clone());
+        }
+    }
+    builder
+}
+
+fn streamed_response(response: reqwest::Response) -> Response {
+    response_parts(&response)
+        .body(Body::from_stream(response.bytes_stream()))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
+
+async fn buffered_response(
+    response: reqwest::Response,
+) -> Result<(Response<()>, bytes::Bytes), CanonicalError> {
+    let head = response_parts(&response)
+        .body(())
+        .map_err(|e| CanonicalError::Internal(e.to_string()))?;
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| CanonicalError::Transport(format!("Failed to read upstream body: {e}")))?;
+    Ok((head, body))
+}
+
+/// `POST /v1/files`
+pub async fn upload_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    if let Err(err) = state.authenticate(INGRESS, &headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+    let Some(upstream_index) = upload_upstream(&state, &headers) else {
+        return no_file_upstream();
+    };
+
+    let content_type = headers.get(http::header::CONTENT_TYPE);
+    let result = async {
+        let response = forward(
+            &state,
+            upstream_index,
+            Method::POST,
+            "",
+            None,
+            content_type,
+            body,
+        )
+        .await?;
+        buffered_response(response).await
+    }
+    .await;
+    let (head, body) = match result {
+        Ok(parts) => parts,
+        Err(err) => return into_axum_response(&err, INGRESS),
+    };
+
+    if head.status().is_success() {
+        let file_id = serde_json::from_slice::<Value>(&body)
+            .ok()
+            .and_then(|file| file.get("id")?.as_str().map(str::to_string));
+        if let Some(file_id) = file_id {
+            tracing::debug!(
+                file_id,
+                upstream = state.upstream_name(upstream_index),
+                "bound uploaded file to upstream"
+            );
+            state.bind_file_upstream(&file_id, upstream_index);
+        }
+    }
+    head.map(|()| Body::from(body))
+}
+
+/// `GET /v1/files`: the merged listing of every file-capable upstream.
+pub async fn list_handler(
+    State(state): State<Arc<AppState>>,
+    headers: &HeaderMap,
+    query: Option<&str>,
+) -> Response {
+    if let Err(err) = state.authenticate(INGRESS, headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+
+    let mut files = Vec::new();
+    let mut has_more = false;
+    let mut listed_any = false;
+    let mut last_failure: Option<Response> = None;
+    for upstream_index in file_upstreams(&state) {
+        let result = async {
+            let response = forward(
+                &state,
+                upstream_index,
+                Method::GET,
+                "",
+                query,
+                None,
+                bytes::Bytes::new(),
+            )
+            .await?;
+            buffered_response(response).await
+        }
+        .await;
+        let (head, body) = match result {
+            Ok(parts) => parts,
+            Err(err) => {
+                last_failure = Some(into_axum_response(&err, INGRESS));
+                continue;
+            }
+        };
+        if !head.status().is_success() {
+            last_failure = Some(head.map(|()| Body::from(body)));
+            continue;
+        }
+        let Ok(mut listing) = serde_json::from_slice::<Value>(&body) else {
+            continue;
+        };
+        listed_any = true;
+        has_more |= listing.get("has_more").and_then(Value::as_bool) == Some(true);
+        if let Some(Value::Array(data)) = listing.get_mut("data").map(Value::take) {
+            for file in &data {
+                if let Some(file_id) = file.get("id").and_then(Value::as_str) {
+                    state.bind_file_upstream(file_id, upstream_index);
+                }
+            }
+            files.extend(data);
+        }
+    }
+
+    if !listed_any {
+        return last_failure.unwrap_or_else(no_file_upstream);
+    }
+    axum::Json(json!({ "object": "list", "data": files, "has_more": has_more })).into_response()
+}
+
+/// `GET|DELETE /v1/files/{id}` and `GET /v1/files/{id}/content`.
+///
+/// Unbound ids (uploaded before a restart or directly to the backend) are
+/// looked up on each file-capable upstream until one does not answer 404.
+pub async fn file_handler(
+    State(state): State<Arc<AppState>>,
+    file_id: &str,
+    action: FileAction,
+    headers: &HeaderMap,
+) -> Response {
+    if let Err(err) = state.authenticate(INGRESS, headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+
+    let bound = state.file_upstream(file_id);
+    let candidates: Vec<usize> = match bound {
+        Some(upstream_index) => vec![upstream_index],
+        None => file_upstreams(&state).collect(),
+    };
+    if candidates.is_empty() {
+        return no_file_upstream();
+    }
+
+    let path_suffix = match action {
+        FileAction::Content => format!("/{file_id}/content"),
+        FileAction::Retrieve | FileAction::Delete => format!("/{file_id}"),
+    };
+    let last = candidates.len() - 1;
+    for (position, upstream_index) in candidates.into_iter().enumerate() {
+        let response = match forward(
+            &state,
+            upstream_index,
+            action.method(),
+            &path_suffix,
+            None,
+            None,
+            bytes::Bytes::new(),
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(err) if position < last => {
+                tracing::debug!(error = %err, "file lookup failed on upstream; trying next");
+                continue;
+            }
+            Err(err) => return into_axum_response(&err, INGRESS),
+        };
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND && position < last {
+            continue;
+        }
+        if status.is_success() {
+            match action {
+                FileAction::Delete => state.unbind_file_upstream(file_id),
+                FileAction::Retrieve | FileAction::Content if bound.is_none() => {
+                    state.bind_file_upstream(file_id, upstream_index);
+                }
+                FileAction::Retrieve | FileAction::Content => {}
+            }
+        } else if status == StatusCode::NOT_FOUND && bound.is_some() {
+            state.unbind_file_upstream(file_id);
+        }
+        return streamed_response(response);
+    }
+    no_file_upstream()
+}
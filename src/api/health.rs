@@ -1,7 +1,9 @@
 use std::sync::Arc;
 
 use axum::extract::State;
-use axum::response::Json;
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
 use serde_json::{json, Value};
 
 use crate::state::AppState;
@@ -18,10 +20,122 @@ pub fn health_handler(State(state): State<Arc<AppState>>) -> Json<Value> {
             "features": {
                 "enable_function_calling": config.features.enable_function_calling,
                 "log_level": config.features.log_level,
-                "convert_developer_to_system": config.features.convert_developer_to_system,
+                "convert_developer_to_system": config.features.fold_system_messages,
                 "enable_fc_error_retry": config.features.enable_fc_error_retry,
                 "fc_error_retry_max_attempts": config.features.fc_error_retry_max_attempts,
             }
         }
     }))
 }
+
+/// Readiness handler, distinct from the liveness check above.
+///
+/// Answers 503 while startup warm-up (`server.warmup_upstreams`) is running,
+/// then 200 with the per-upstream warm-up outcome.
+pub fn ready_handler(State(state): State<Arc<AppState>>) -> Response {
+    if !state.is_ready() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "warming_up" })),
+        )
+            .into_response();
+    }
+    let upstreams: Vec<Value> = state
+        .upstream_warmups()
+        .into_iter()
+        .map(|warmup| {
+            json!({
+                "upstream": state.upstream_name(warmup.upstream_index),
+                "reachable": warmup.status.is_some(),
+                "status": warmup.status,
+                "error": warmup.error,
+                "elapsed_ms": warmup.elapsed_ms,
+            })
+        })
+        .collect();
+    Json(json!({
+        "status": "ready",
+        "warmup": upstreams,
+    }))
+    .into_response()
+}
+
+/// Kubernetes liveness probe: answers 200 whenever the process can serve HTTP.
+#[must_use]
+pub fn healthz_handler() -> Response {
+    Json(json!({ "status": "ok" })).into_response()
+}
+
+/// Kubernetes startup probe: 503 until startup warm-up has finished.
+#[must_use]
+pub fn startupz_handler(State(state): State<Arc<AppState>>) -> Response {
+    if state.is_ready() {
+        Json(json!({ "status": "started" })).into_response()
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "starting" })),
+        )
+            .into_response()
+    }
+}
+
+/// Kubernetes readiness probe.
+///
+/// Ready once startup has finished and at least one upstream is healthy, i.e.
+/// has no route cooling down in the breaker. Callers presenting admin
+/// credentials also get the per-upstream detail.
+#[must_use]
+pub fn readyz_handler(State(state): State<Arc<AppState>>, request: &Parts) -> Response {
+    let cooldowns = state.route_cooldowns();
+    let cooling_routes = |upstream_index: usize| {
+        cooldowns
+            .iter()
+            .filter(|route| route.upstream_index == upstream_index)
+            .filter(|route| route.cooldown_remaining_secs > 0)
+            .map(|route| route.model_group.as_str())
+            .collect::<Vec<_>>()
+    };
+    let upstream_count = state.config.upstream_services.len();
+    let healthy_count = (0..upstream_count)
+        .filter(|&upstream_index| cooling_routes(upstream_index).is_empty())
+        .count();
+
+    let (status, label) = if !state.is_ready() {
+        (StatusCode::SERVICE_UNAVAILABLE, "starting")
+    } else if healthy_count == 0 {
+        (StatusCode::SERVICE_UNAVAILABLE, "no_healthy_upstream")
+    } else {
+        (StatusCode::OK, "ready")
+    };
+    let mut body = json!({
+        "status": label,
+        "healthy_upstreams": healthy_count,
+        "upstreams_total": upstream_count,
+    });
+
+    let admin_auth = state.admin_auth();
+    if admin_auth.is_enabled()
+        && admin_auth
+            .authenticate(&request.method, &request.uri, &request.headers, &[])
+            .is_ok()
+    {
+        let warmups = state.upstream_warmups();
+        let upstreams: Vec<Value> = (0..upstream_count)
+            .map(|upstream_index| {
+                let cooling = cooling_routes(upstream_index);
+                let warmup = warmups
+                    .iter()
+                    .find(|warmup| warmup.upstream_index == upstream_index);
+                json!({
+                    "upstream": state.upstream_name(upstream_index),
+                    "healthy": cooling.is_empty(),
+                    "cooling_down_models": cooling,
+                    "warmup_reachable": warmup.map(|warmup| warmup.status.is_some()),
+                })
+            })
+            .collect();
+        body["upstreams"] = Value::Array(upstreams);
+    }
+    (status, Json(body)).into_response()
+}
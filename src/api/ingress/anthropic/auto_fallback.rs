@@ -19,6 +19,7 @@ use crate::api::ingress::anthropic::io::{
 
 pub(crate) struct AnthropicAutoFallbackCtx<'a> {
     pub(crate) state: &'a AppState,
+    pub(crate) body: &'a bytes::Bytes,
     pub(crate) wire_request: &'a AnthropicRequest,
     pub(crate) route: RouteTarget<'a>,
     pub(crate) client_model: &'a str,
@@ -63,8 +64,11 @@ async fn run_anthropic_wire_retry(
     let mut inject_wire = ctx.wire_request.clone();
     inject_wire.model.clear();
     inject_wire.model.push_str(ctx.route.actual_model);
-    let inject_saved_tools =
-        apply_fc_inject_anthropic_wire(&mut inject_wire, &ctx.state.config.features)?;
+    let inject_saved_tools = apply_fc_inject_anthropic_wire(
+        &mut inject_wire,
+        ctx.state
+            .fc_prompt_template(ctx.route.upstream_index, ctx.requested_model, ctx.body),
+    )?;
     let inject_stream = inject_wire.stream.unwrap_or(false);
     let inject_body = serde_json::to_vec(&inject_wire)
         .map(bytes::Bytes::from)
@@ -97,8 +101,12 @@ async fn run_anthropic_canonical_retry(
     let mut inject_canonical = decode_anthropic_request(ctx.wire_request, ctx.request_id)?;
     inject_canonical.model.clear();
     inject_canonical.model.push_str(ctx.route.actual_model);
-    let inject_saved_tools =
-        fc::apply_fc_inject_take_tools(&mut inject_canonical, &ctx.state.config.features)?;
+    let inject_saved_tools = fc::apply_fc_inject_take_tools(
+        &mut inject_canonical,
+        &ctx.state.config.features,
+        ctx.state
+            .fc_prompt_template(ctx.route.upstream_index, ctx.requested_model, ctx.body),
+    )?;
     let io_target = prepare_upstream_io_request(
         ctx.state,
         prepared_upstream,
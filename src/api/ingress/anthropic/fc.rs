@@ -1,14 +1,14 @@
 use std::collections::HashMap;
 
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
 use crate::fc;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::anthropic::{AnthropicMessage, AnthropicRequest, AnthropicTool};
 use crate::protocol::canonical::{CanonicalToolChoice, CanonicalToolFunction, CanonicalToolSpec};
 
 pub(crate) fn apply_fc_inject_anthropic_wire(
     request: &mut AnthropicRequest,
-    features: &FeaturesConfig,
+    prompt_template: Option<&PromptTemplate>,
 ) -> Result<Vec<CanonicalToolSpec>, CanonicalError> {
     let tool_choice = decode_anthropic_wire_tool_choice(request.tool_choice.as_ref());
     if matches!(tool_choice, CanonicalToolChoice::None) {
@@ -16,11 +16,7 @@ pub(crate) fn apply_fc_inject_anthropic_wire(
     }
 
     let saved_tools = decode_anthropic_wire_tools(request.tools.take());
-    let fc_prompt = fc::prompt::generate_fc_prompt(
-        &saved_tools,
-        &tool_choice,
-        features.prompt_template.as_deref(),
-    )?;
+    let fc_prompt = fc::prompt::generate_fc_prompt(&saved_tools, &tool_choice, prompt_template)?;
 
     let mut tool_call_index: HashMap<String, (String, String)> = HashMap::new();
     for msg in &request.messages {
@@ -57,13 +53,10 @@ pub(crate) fn apply_fc_inject_anthropic_wire(
         }
     }
 
-    let existing_system = extract_anthropic_system_text(request.system.as_ref());
-    let system_prompt = if existing_system.is_empty() {
-        fc_prompt
-    } else {
-        format!("{existing_system}\n{fc_prompt}")
-    };
-    request.system = Some(serde_json::Value::String(system_prompt));
+    request.system = Some(append_anthropic_system_text(
+        request.system.take(),
+        fc_prompt,
+    ));
 
     let mut transformed: Vec<AnthropicMessage> = Vec::with_capacity(request.messages.len());
     for mut msg in std::mem::take(&mut request.messages) {
@@ -234,23 +227,23 @@ fn decode_anthropic_wire_tool_choice(
     }
 }
 
-fn extract_anthropic_system_text(system: Option<&serde_json::Value>) -> String {
+/// Append `text` to an Anthropic `system` value.
+///
+/// A block array keeps its blocks, `cache_control` included, and gains a
+/// trailing text block, so a cached system prefix stays cacheable.
+fn append_anthropic_system_text(
+    system: Option<serde_json::Value>,
+    text: String,
+) -> serde_json::Value {
     match system {
-        Some(serde_json::Value::String(s)) => s.clone(),
-        Some(serde_json::Value::Array(blocks)) => blocks
-            .iter()
-            .filter_map(|b| {
-                if b.get("type").and_then(serde_json::Value::as_str) == Some("text") {
-                    b.get("text")
-                        .and_then(serde_json::Value::as_str)
-                        .map(std::string::ToString::to_string)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>()
-            .join("\n"),
-        _ => String::new(),
+        Some(serde_json::Value::String(existing)) if !existing.is_empty() => {
+            serde_json::Value::String(format!("{existing}\n{text}"))
+        }
+        Some(serde_json::Value::Array(mut blocks)) if !blocks.is_empty() => {
+            blocks.push(serde_json::json!({ "type": "text", "text": text }));
+            serde_json::Value::Array(blocks)
+        }
+        _ => serde_json::Value::String(text),
     }
 }
 
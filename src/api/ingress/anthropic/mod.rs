@@ -23,8 +23,6 @@ pub(crate) mod spec;
 use self::fc::apply_fc_inject_anthropic_wire;
 use self::flow::handler_inner;
 #[cfg(test)]
-use crate::config::FeaturesConfig;
-#[cfg(test)]
 use crate::protocol::anthropic::AnthropicTool;
 use crate::state::AppState;
 
@@ -83,8 +81,7 @@ mod tests {
             extra: serde_json::Map::new(),
         };
 
-        let saved_tools =
-            apply_fc_inject_anthropic_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_anthropic_wire(&mut req, None).unwrap();
         assert_eq!(saved_tools.len(), 1);
         assert!(req.tools.is_none());
         assert!(req.tool_choice.is_none());
@@ -112,6 +109,47 @@ mod tests {
         assert!(user_text.contains("get_weather"));
     }
 
+    #[test]
+    fn test_apply_fc_inject_anthropic_wire_keeps_system_blocks() {
+        let system = serde_json::json!([
+            {"type":"text","text":"first"},
+            {"type":"text","text":"cached","cache_control":{"type":"ephemeral"}}
+        ]);
+        let mut req = AnthropicRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            max_tokens: 256,
+            system: Some(system.clone()),
+            messages: vec![AnthropicMessage {
+                role: "user".to_string(),
+                content: serde_json::Value::String("hi".to_string()),
+            }],
+            tools: Some(vec![AnthropicTool {
+                name: "get_weather".to_string(),
+                description: Some("weather".to_string()),
+                input_schema: serde_json::json!({
+                    "type":"object",
+                    "properties":{"city":{"type":"string"}}
+                }),
+            }]),
+            tool_choice: None,
+            stream: Some(false),
+            temperature: None,
+            top_p: None,
+            stop_sequences: None,
+            extra: serde_json::Map::new(),
+        };
+
+        apply_fc_inject_anthropic_wire(&mut req, None).unwrap();
+        let blocks = req.system.as_ref().and_then(|v| v.as_array()).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[..2], system.as_array().unwrap()[..]);
+        assert_eq!(blocks[2]["type"], "text");
+        assert!(blocks[2]["text"]
+            .as_str()
+            .unwrap()
+            .contains(crate::fc::prompt::get_trigger_signal()));
+    }
+
     #[test]
     fn test_apply_fc_inject_anthropic_wire_skips_when_tool_choice_none() {
         let mut req = AnthropicRequest {
@@ -138,8 +176,7 @@ mod tests {
             extra: serde_json::Map::new(),
         };
 
-        let saved_tools =
-            apply_fc_inject_anthropic_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_anthropic_wire(&mut req, None).unwrap();
         assert!(saved_tools.is_empty());
         assert_eq!(
             req.system,
@@ -10,8 +10,8 @@ use crate::api::engine::failover::{
     run_anthropic_fc_non_stream, run_anthropic_no_tools_non_stream,
 };
 use crate::api::engine::pipeline::{CommonRequestProbe, UpstreamIoRequest};
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::anthropic::decoder::{
     decode_anthropic_request, decode_anthropic_request_owned,
 };
@@ -62,9 +62,9 @@ impl CompatFlowSpec for AnthropicSpec {
 
     fn apply_wire_inject(
         wire_request: &mut Self::WireRequest,
-        features: &FeaturesConfig,
+        prompt_template: Option<&PromptTemplate>,
     ) -> Result<Vec<CanonicalToolSpec>, CanonicalError> {
-        apply_fc_inject_anthropic_wire(wire_request, features)
+        apply_fc_inject_anthropic_wire(wire_request, prompt_template)
     }
 
     fn wire_stream_requested(wire_request: &Self::WireRequest) -> bool {
@@ -122,6 +122,7 @@ impl CompatFlowSpec for AnthropicSpec {
         run_anthropic_auto_fallback(
             AnthropicAutoFallbackCtx {
                 state: input.state,
+                body: input.body,
                 wire_request: input.wire_request,
                 route: input.route,
                 client_model: input.client_model,
@@ -19,6 +19,7 @@ use crate::api::ingress::gemini::io::{
 
 pub(crate) struct GeminiAutoFallbackCtx<'a> {
     pub(crate) state: &'a AppState,
+    pub(crate) body: &'a bytes::Bytes,
     pub(crate) wire_request: &'a GeminiRequest,
     pub(crate) route: RouteTarget<'a>,
     pub(crate) model: &'a str,
@@ -61,8 +62,11 @@ async fn run_gemini_wire_retry(
     prepared_upstream: &crate::transport::PreparedUpstream,
 ) -> Result<Response, CanonicalError> {
     let mut inject_wire = ctx.wire_request.clone();
-    let inject_saved_tools =
-        apply_fc_inject_gemini_wire(&mut inject_wire, &ctx.state.config.features)?;
+    let inject_saved_tools = apply_fc_inject_gemini_wire(
+        &mut inject_wire,
+        ctx.state
+            .fc_prompt_template(ctx.route.upstream_index, ctx.model, ctx.body),
+    )?;
     let inject_body = serde_json::to_vec(&inject_wire)
         .map(bytes::Bytes::from)
         .map_err(|e| CanonicalError::Translation(format!("Serialization error: {e}")))?;
@@ -95,8 +99,12 @@ async fn run_gemini_canonical_retry(
     inject_canonical.stream = ctx.is_stream;
     inject_canonical.model.clear();
     inject_canonical.model.push_str(ctx.route.actual_model);
-    let inject_saved_tools =
-        fc::apply_fc_inject_take_tools(&mut inject_canonical, &ctx.state.config.features)?;
+    let inject_saved_tools = fc::apply_fc_inject_take_tools(
+        &mut inject_canonical,
+        &ctx.state.config.features,
+        ctx.state
+            .fc_prompt_template(ctx.route.upstream_index, ctx.model, ctx.body),
+    )?;
     let io_target = prepare_upstream_io_request(
         ctx.state,
         prepared_upstream,
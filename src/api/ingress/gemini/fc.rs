@@ -1,8 +1,8 @@
 use std::collections::{HashMap, VecDeque};
 
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
 use crate::fc;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::canonical::{CanonicalToolChoice, CanonicalToolFunction, CanonicalToolSpec};
 use crate::protocol::gemini::{
     GeminiContent, GeminiPart, GeminiRequest, GeminiToolConfig, GeminiToolDeclaration,
@@ -10,7 +10,7 @@ use crate::protocol::gemini::{
 
 pub(crate) fn apply_fc_inject_gemini_wire(
     request: &mut GeminiRequest,
-    features: &FeaturesConfig,
+    prompt_template: Option<&PromptTemplate>,
 ) -> Result<Vec<CanonicalToolSpec>, CanonicalError> {
     let tool_choice = decode_gemini_wire_tool_choice(request.tool_config.as_ref());
     if matches!(tool_choice, CanonicalToolChoice::None) {
@@ -18,11 +18,7 @@ pub(crate) fn apply_fc_inject_gemini_wire(
     }
 
     let saved_tools = decode_gemini_wire_tools(request.tools.take());
-    let fc_prompt = fc::prompt::generate_fc_prompt(
-        &saved_tools,
-        &tool_choice,
-        features.prompt_template.as_deref(),
-    )?;
+    let fc_prompt = fc::prompt::generate_fc_prompt(&saved_tools, &tool_choice, prompt_template)?;
 
     let mut call_args_by_name: HashMap<String, VecDeque<String>> = HashMap::new();
     for content in &request.contents {
@@ -6,22 +6,39 @@ use axum::response::Response;
 use crate::api::engine::compat_flow::run_compat_handler_with_route;
 use crate::error::CanonicalError;
 use crate::state::AppState;
+use crate::stream::json_array::sse_to_json_array;
 
-use super::spec::{parse_model_action, GeminiSpec};
+use super::spec::{requests_json_array, GeminiSpec};
 
 pub(super) async fn handler_inner(
     state: Arc<AppState>,
-    model_action: &str,
+    model: &str,
+    is_stream: bool,
+    query: Option<&str>,
     headers: HeaderMap,
     body: bytes::Bytes,
 ) -> Result<Response, CanonicalError> {
-    let action = parse_model_action(model_action);
-    run_compat_handler_with_route::<GeminiSpec>(
+    let response = run_compat_handler_with_route::<GeminiSpec>(
         state,
         headers,
         body,
-        Some(action.model),
-        Some(action.is_stream),
+        Some(model),
+        Some(is_stream),
     )
-    .await
+    .await?;
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"text/event-stream"));
+    if !(is_stream && is_sse && requests_json_array(query)) {
+        return Ok(response);
+    }
+    let (mut parts, body) = response.into_parts();
+    parts.headers.insert(
+        http::header::CONTENT_TYPE,
+        http::HeaderValue::from_static("application/json"),
+    );
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+    let body = axum::body::Body::from_stream(sse_to_json_array(body.into_data_stream()));
+    Ok(Response::from_parts(parts, body))
 }
@@ -20,8 +20,6 @@ pub(crate) mod spec;
 use self::fc::apply_fc_inject_gemini_wire;
 use self::flow::handler_inner;
 #[cfg(test)]
-use crate::config::FeaturesConfig;
-#[cfg(test)]
 use crate::protocol::gemini::{GeminiContent, GeminiPart, GeminiToolConfig, GeminiToolDeclaration};
 use crate::state::AppState;
 
@@ -29,11 +27,13 @@ const INGRESS: IngressApi = IngressApi::Gemini;
 
 pub async fn handler_from_action(
     state: Arc<AppState>,
-    model_action: &str,
+    model: &str,
+    is_stream: bool,
+    query: Option<&str>,
     headers: HeaderMap,
     body: bytes::Bytes,
 ) -> Response {
-    match handler_inner(state, model_action, headers, body).await {
+    match handler_inner(state, model, is_stream, query, headers, body).await {
         Ok(response) => response,
         Err(err) => into_axum_response(&err, INGRESS),
     }
@@ -89,8 +89,7 @@ mod tests {
             generation_config: None,
         };
 
-        let saved_tools =
-            apply_fc_inject_gemini_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_gemini_wire(&mut req, None).unwrap();
         assert_eq!(saved_tools.len(), 1);
         assert!(req.tools.is_none());
         assert!(req.tool_config.is_none());
@@ -161,8 +160,7 @@ mod tests {
             generation_config: None,
         };
 
-        let saved_tools =
-            apply_fc_inject_gemini_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_gemini_wire(&mut req, None).unwrap();
         assert!(saved_tools.is_empty());
         assert!(req.tools.is_some());
         assert!(req.tool_config.is_some());
@@ -9,8 +9,8 @@ use crate::api::engine::compat_flow::{
 };
 use crate::api::engine::failover::{run_gemini_fc_non_stream, run_gemini_no_tools_non_stream};
 use crate::api::engine::pipeline::{CommonRequestProbe, UpstreamIoRequest};
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::canonical::{CanonicalRequest, CanonicalToolSpec, IngressApi, ProviderKind};
 use crate::protocol::gemini::decoder::{decode_gemini_request, decode_gemini_request_owned};
 use crate::protocol::gemini::GeminiRequest;
@@ -85,9 +85,9 @@ impl CompatFlowSpec for GeminiSpec {
 
     fn apply_wire_inject(
         wire_request: &mut Self::WireRequest,
-        features: &FeaturesConfig,
+        prompt_template: Option<&PromptTemplate>,
     ) -> Result<Vec<CanonicalToolSpec>, CanonicalError> {
-        apply_fc_inject_gemini_wire(&mut wire_request.request, features)
+        apply_fc_inject_gemini_wire(&mut wire_request.request, prompt_template)
     }
 
     fn wire_stream_requested(wire_request: &Self::WireRequest) -> bool {
@@ -167,6 +167,7 @@ impl CompatFlowSpec for GeminiSpec {
         run_gemini_auto_fallback(
             GeminiAutoFallbackCtx {
                 state: input.state,
+                body: input.body,
                 wire_request: &input.wire_request.request,
                 route: input.route,
                 model: input.requested_model,
@@ -210,25 +211,9 @@ impl CompatFlowSpec for GeminiSpec {
     }
 }
 
-pub(super) struct GeminiAction<'a> {
-    pub(super) model: &'a str,
-    pub(super) is_stream: bool,
-}
-
+/// Whether a `streamGenerateContent` client asked for a JSON-array stream
+/// (`alt=json`) rather than SSE; without `alt` it is served SSE.
 #[must_use]
-pub(super) fn parse_model_action(model_action: &str) -> GeminiAction<'_> {
-    match model_action.rsplit_once(':') {
-        Some((model_name, "streamGenerateContent")) => GeminiAction {
-            model: model_name,
-            is_stream: true,
-        },
-        Some((model_name, _)) => GeminiAction {
-            model: model_name,
-            is_stream: false,
-        },
-        None => GeminiAction {
-            model: model_action,
-            is_stream: false,
-        },
-    }
+pub(super) fn requests_json_array(query: Option<&str>) -> bool {
+    query.is_some_and(|query| query.split('&').any(|pair| pair == "alt=json"))
 }
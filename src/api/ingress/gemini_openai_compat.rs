@@ -0,0 +1,159 @@
+//! Gemini's `OpenAI`-compatible surface (`/v1beta/openai/chat/completions`).
+//!
+//! Requests are `OpenAI` chat bodies plus Gemini extensions. The extensions are
+//! folded into portable chat fields before the body runs through the chat
+//! ingress, so any upstream provider can serve these clients.
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Response;
+use serde_json::{Map, Value};
+
+use crate::api::openai_chat;
+use crate::error::{into_axum_response, CanonicalError};
+use crate::protocol::canonical::IngressApi;
+use crate::state::AppState;
+
+const INGRESS: IngressApi = IngressApi::OpenAiChat;
+
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    match normalize_compat_request(&body) {
+        Ok(Some(normalized)) => openai_chat::handler(State(state), headers, normalized).await,
+        Ok(None) => openai_chat::handler(State(state), headers, body).await,
+        Err(err) => into_axum_response(&err, INGRESS),
+    }
+}
+
+/// Fold Gemini compat extensions into plain chat fields.
+///
+/// - `extra_body` keys are merged into the top level (the `OpenAI` SDK's
+///   semantics), except `google`.
+/// - `google.thinking_config.thinking_budget` becomes `reasoning_effort` when
+///   the client did not set one.
+/// - `reasoning_effort: "none"` (Gemini's "thinking off") is dropped so the
+///   upstream default applies.
+/// - Remaining `google` options have no portable equivalent and are dropped.
+///
+/// Returns `None` when the body needs no changes.
+fn normalize_compat_request(body: &[u8]) -> Result<Option<bytes::Bytes>, CanonicalError> {
+    if memchr::memmem::find(body, b"extra_body").is_none()
+        && memchr::memmem::find(body, b"google").is_none()
+        && memchr::memmem::find(body, b"reasoning_effort").is_none()
+    {
+        return Ok(None);
+    }
+
+    let mut request: Map<String, Value> = serde_json::from_slice(body)
+        .map_err(|e| CanonicalError::InvalidRequest(format!("Invalid JSON body: {e}")))?;
+
+    let mut google = request.remove("google");
+    match request.remove("extra_body") {
+        Some(Value::Object(extra_body)) => {
+            for (key, value) in extra_body {
+                if key == "google" {
+                    google = Some(value);
+                } else {
+                    request.entry(key).or_insert(value);
+                }
+            }
+        }
+        Some(Value::Null) | None => {}
+        Some(_) => {
+            return Err(CanonicalError::InvalidRequest(
+                "'extra_body' must be an object".to_string(),
+            ))
+        }
+    }
+
+    if let Some(Value::Object(google)) = google {
+        let thinking_budget = google
+            .get("thinking_config")
+            .and_then(|config| config.get("thinking_budget"))
+            .and_then(Value::as_i64);
+        if let Some(budget) = thinking_budget {
+            if !request.contains_key("reasoning_effort") {
+                if let Some(effort) = reasoning_effort_for_thinking_budget(budget) {
+                    request.insert("reasoning_effort".to_string(), Value::from(effort));
+                }
+            }
+        }
+        let dropped: Vec<&str> = google
+            .keys()
+            .map(String::as_str)
+            .filter(|key| *key != "thinking_config")
+            .collect();
+        if !dropped.is_empty() {
+            tracing::debug!(
+                options = ?dropped,
+                "dropping Gemini-only extra_body.google options"
+            );
+        }
+    }
+
+    if request.get("reasoning_effort").and_then(Value::as_str) == Some("none") {
+        request.remove("reasoning_effort");
+    }
+
+    serde_json::to_vec(&request)
+        .map(|encoded| Some(bytes::Bytes::from(encoded)))
+        .map_err(|e| CanonicalError::Internal(format!("Failed to encode chat request: {e}")))
+}
+
+/// Map a Gemini thinking token budget onto an `OpenAI` reasoning effort.
+///
+/// `0` disables thinking and `-1` means dynamic; both leave the upstream default.
+fn reasoning_effort_for_thinking_budget(budget: i64) -> Option<&'static str> {
+    match budget {
+        i64::MIN..=0 => None,
+        1..=1024 => Some("low"),
+        1025..=8192 => Some("medium"),
+        _ => Some("high"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(body: &str) -> Value {
+        let normalized = normalize_compat_request(body.as_bytes())
+            .unwrap()
+            .expect("body should be rewritten");
+        serde_json::from_slice(&normalized).unwrap()
+    }
+
+    #[test]
+    fn test_extra_body_google_thinking_budget_maps_to_reasoning_effort() {
+        let out = normalize(
+            r#"{"model":"gemini-2.5-flash","messages":[],"extra_body":{"google":{"thinking_config":{"thinking_budget":2048,"include_thoughts":true},"cached_content":"c/1"},"top_k":4}}"#,
+        );
+        assert_eq!(out["reasoning_effort"], "medium");
+        assert_eq!(out["top_k"], 4);
+        assert!(out.get("extra_body").is_none());
+        assert!(out.get("google").is_none());
+    }
+
+    #[test]
+    fn test_explicit_reasoning_effort_wins_and_none_is_dropped() {
+        let out = normalize(
+            r#"{"model":"m","messages":[],"reasoning_effort":"high","google":{"thinking_config":{"thinking_budget":512}}}"#,
+        );
+        assert_eq!(out["reasoning_effort"], "high");
+
+        let out = normalize(r#"{"model":"m","messages":[],"reasoning_effort":"none"}"#);
+        assert!(out.get("reasoning_effort").is_none());
+    }
+
+    #[test]
+    fn test_plain_chat_body_is_untouched() {
+        let body = br#"{"model":"m","messages":[{"role":"user","content":"hi"}]}"#;
+        assert!(normalize_compat_request(body).unwrap().is_none());
+        assert!(normalize_compat_request(br#"{"model":"m","extra_body":1}"#).is_err());
+    }
+}
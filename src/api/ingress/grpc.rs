@@ -0,0 +1,236 @@
+//! gRPC ingress: the `toolify.v1.Completion` service.
+//!
+//! `Complete` and `CompleteStream` take the canonical messages of
+//! `proto/toolify/v1/completion.proto` (protobuf codec, via tonic) and run
+//! through the `OpenAI` chat ingress, so routing, FC injection, and
+//! observability are shared with HTTP clients. Request metadata is read as
+//! HTTP headers (e.g. `authorization`), and proxy errors end the call with
+//! the status their [`CanonicalError`] maps to.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::response::Response;
+use futures_util::{stream, Stream, StreamExt};
+use tonic::metadata::MetadataMap;
+use tonic::Status;
+
+use crate::api::common::decode_response_from_provider;
+use crate::api::openai_chat::flow::handler_inner;
+use crate::error::{into_grpc_status, CanonicalError};
+use crate::protocol::canonical::{CanonicalStreamEvent, IngressApi, ProviderKind};
+use crate::protocol::grpc::completion_server::{Completion, CompletionServer};
+use crate::protocol::grpc::{
+    decode_grpc_request, encode_grpc_response, encode_grpc_stream_event, CompleteRequest,
+    CompleteResponse, StreamEvent,
+};
+use crate::protocol::openai_chat::encoder::encode_openai_chat_request;
+use crate::state::AppState;
+use crate::stream::{sse_frame_stream, SseEvent, StreamTranscoder};
+
+pub(crate) const SERVICE_PATH_PREFIX: &str = "/toolify.v1.Completion/";
+
+/// Whether a request looks like a gRPC call (content type `application/grpc*`).
+#[must_use]
+pub(crate) fn is_grpc_request(headers: &HeaderMap) -> bool {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/grpc"))
+}
+
+/// Serve one `toolify.v1.Completion` RPC.
+pub(crate) async fn serve(state: Arc<AppState>, request: Request<Body>) -> Response {
+    let limit = state.max_request_bytes();
+    let mut server =
+        CompletionServer::new(CompletionService { state }).max_decoding_message_size(limit);
+    let Ok(response) = tower_service::Service::call(&mut server, request).await;
+    response.map(Body::new)
+}
+
+struct CompletionService {
+    state: Arc<AppState>,
+}
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<StreamEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Completion for CompletionService {
+    async fn complete(
+        &self,
+        request: tonic::Request<CompleteRequest>,
+    ) -> Result<tonic::Response<CompleteResponse>, Status> {
+        let (parts, body) = self.forward(request, false).await?.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .map_err(|err| Status::unavailable(err.to_string()))?;
+        if !parts.status.is_success() {
+            return Err(into_grpc_status(&error_response(
+                parts.status,
+                &parts.headers,
+                &body,
+            )));
+        }
+        let canonical = decode_response_from_provider(ProviderKind::OpenAi, &body)
+            .map_err(|err| into_grpc_status(&err))?;
+        Ok(with_metadata(
+            encode_grpc_response(canonical),
+            &parts.headers,
+        ))
+    }
+
+    type CompleteStreamStream = EventStream;
+
+    async fn complete_stream(
+        &self,
+        request: tonic::Request<CompleteRequest>,
+    ) -> Result<tonic::Response<Self::CompleteStreamStream>, Status> {
+        let (parts, body) = self.forward(request, true).await?.into_parts();
+        if !parts.status.is_success() {
+            let body = axum::body::to_bytes(body, usize::MAX)
+                .await
+                .unwrap_or_default();
+            return Err(into_grpc_status(&error_response(
+                parts.status,
+                &parts.headers,
+                &body,
+            )));
+        }
+        Ok(with_metadata(stream_events(body), &parts.headers))
+    }
+}
+
+impl CompletionService {
+    /// Run the call through the `OpenAI` chat ingress.
+    async fn forward(
+        &self,
+        request: tonic::Request<CompleteRequest>,
+        stream: bool,
+    ) -> Result<Response, Status> {
+        let (metadata, _, message) = request.into_parts();
+        // The chat flow assigns the request id when it decodes the body.
+        let canonical = decode_grpc_request(message, stream, uuid::Uuid::nil())
+            .map_err(|err| into_grpc_status(&err))?;
+        let wire = encode_openai_chat_request(&canonical).map_err(|err| into_grpc_status(&err))?;
+        let body = serde_json::to_vec(&wire)
+            .map(bytes::Bytes::from)
+            .map_err(|e| {
+                into_grpc_status(&CanonicalError::Translation(format!(
+                    "Serialization error: {e}"
+                )))
+            })?;
+        let mut headers = metadata.into_headers();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        handler_inner(Arc::clone(&self.state), headers, body)
+            .await
+            .map_err(|err| into_grpc_status(&err))
+    }
+}
+
+/// The error behind a non-2xx chat ingress response, e.g. a passed-through
+/// upstream error.
+fn error_response(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> CanonicalError {
+    let message = serde_json::from_slice::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| {
+            value
+                .pointer("/error/message")
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| String::from_utf8_lossy(body).into_owned());
+    CanonicalError::Upstream {
+        status: status.as_u16(),
+        message,
+        retry_after: headers
+            .get(http::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok()?.parse().ok()),
+    }
+}
+
+/// Pass the proxy's `x-*` response headers (request id, serving upstream,
+/// rate limits) on as initial metadata.
+fn with_metadata<T>(message: T, headers: &HeaderMap) -> tonic::Response<T> {
+    let mut forwarded = HeaderMap::new();
+    for (name, value) in headers {
+        if name.as_str().starts_with("x-") {
+            forwarded.append(name.clone(), value.clone());
+        }
+    }
+    let mut response = tonic::Response::new(message);
+    *response.metadata_mut() = MetadataMap::from_headers(forwarded);
+    response
+}
+
+struct EventStreamState {
+    frames: Pin<Box<dyn Stream<Item = SseEvent> + Send>>,
+    decoder: StreamTranscoder,
+    events: Vec<CanonicalStreamEvent>,
+    pending: VecDeque<Result<StreamEvent, Status>>,
+    finished: bool,
+}
+
+/// Decode the chat ingress's SSE body into canonical events and re-encode
+/// them as `StreamEvent`s. An in-band error event ends the call with its
+/// status.
+fn stream_events(body: Body) -> EventStream {
+    let state = EventStreamState {
+        frames: Box::pin(sse_frame_stream(body.into_data_stream())),
+        decoder: StreamTranscoder::new(
+            ProviderKind::OpenAi,
+            IngressApi::OpenAiChat,
+            String::new(),
+            String::new(),
+        ),
+        events: Vec::with_capacity(8),
+        pending: VecDeque::new(),
+        finished: false,
+    };
+    Box::pin(stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(item) = state.pending.pop_front() {
+                return Some((item, state));
+            }
+            if state.finished {
+                return None;
+            }
+            let Some(frame) = state.frames.next().await else {
+                state.finished = true;
+                continue;
+            };
+            state
+                .decoder
+                .decode_upstream_frame_into(&frame, &mut state.events);
+            for event in state.events.drain(..) {
+                match event {
+                    CanonicalStreamEvent::Done => state.finished = true,
+                    CanonicalStreamEvent::Error { status, message } => {
+                        state.finished = true;
+                        state
+                            .pending
+                            .push_back(Err(into_grpc_status(&CanonicalError::Upstream {
+                                status,
+                                message,
+                                retry_after: None,
+                            })));
+                    }
+                    event => {
+                        if let Some(event) = encode_grpc_stream_event(event) {
+                            state.pending.push_back(Ok(event));
+                        }
+                    }
+                }
+                if state.finished {
+                    break;
+                }
+            }
+            state.events.clear();
+        }
+    }))
+}
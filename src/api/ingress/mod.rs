@@ -1,4 +1,8 @@
 pub mod anthropic;
 pub mod gemini;
+pub mod gemini_openai_compat;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod openai_chat;
+pub mod openai_completions;
 pub mod openai_responses;
@@ -67,7 +67,8 @@ async fn run_openai_chat_wire_retry(
         try_build_openai_simple_fc_inject_body_from_raw(
             ctx.body,
             ctx.route.actual_model,
-            &ctx.state.config.features,
+            ctx.state
+                .fc_prompt_template(ctx.route.upstream_index, ctx.requested_model, ctx.body),
             ctx.probe_ranges,
         )?
     {
@@ -84,8 +85,11 @@ async fn run_openai_chat_wire_retry(
     let mut inject_wire = ctx.wire_request.clone();
     inject_wire.model.clear();
     inject_wire.model.push_str(ctx.route.actual_model);
-    let inject_saved_tools =
-        apply_fc_inject_openai_wire(&mut inject_wire, &ctx.state.config.features)?;
+    let inject_saved_tools = apply_fc_inject_openai_wire(
+        &mut inject_wire,
+        ctx.state
+            .fc_prompt_template(ctx.route.upstream_index, ctx.requested_model, ctx.body),
+    )?;
     let inject_stream = inject_wire.stream.unwrap_or(false);
     let inject_body = serde_json::to_vec(&inject_wire)
         .map(bytes::Bytes::from)
@@ -147,8 +151,12 @@ async fn run_openai_chat_canonical_retry(
         decode_openai_chat_request(ctx.wire_request, ctx.state.request_uuid(ctx.request_seq))?;
     inject_canonical.model.clear();
     inject_canonical.model.push_str(ctx.route.actual_model);
-    let inject_saved_tools =
-        fc::apply_fc_inject_take_tools(&mut inject_canonical, &ctx.state.config.features)?;
+    let inject_saved_tools = fc::apply_fc_inject_take_tools(
+        &mut inject_canonical,
+        &ctx.state.config.features,
+        ctx.state
+            .fc_prompt_template(ctx.route.upstream_index, ctx.requested_model, ctx.body),
+    )?;
     let io_target = prepare_upstream_io_request(
         ctx.state,
         prepared_upstream,
@@ -2,9 +2,9 @@ use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::sync::{Arc, LazyLock};
 
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
 use crate::fc;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::canonical::CanonicalToolSpec;
 use crate::util::{mix_u64, sampled_bytes_hash};
 
@@ -30,6 +30,7 @@ static SIMPLE_INJECT_CACHE: LazyLock<
 #[derive(Clone)]
 struct SimpleInjectCacheEntry {
     key_hash: u64,
+    template_fingerprint: u64,
     tools_token: Arc<[u8]>,
     tool_choice_token: Option<Arc<[u8]>>,
     saved_tools: Arc<[CanonicalToolSpec]>,
@@ -58,10 +59,16 @@ fn simple_inject_set_index(key_hash: u64) -> usize {
 
 fn simple_inject_cache_get(
     key_hash: u64,
+    template_fingerprint: u64,
     tools_token: &[u8],
     tool_choice_token: Option<&[u8]>,
 ) -> Option<SimpleInjectArtifacts> {
-    if let Some(hit) = simple_inject_thread_cache_get(key_hash, tools_token, tool_choice_token) {
+    if let Some(hit) = simple_inject_thread_cache_get(
+        key_hash,
+        template_fingerprint,
+        tools_token,
+        tool_choice_token,
+    ) {
         return Some(hit);
     }
 
@@ -69,6 +76,7 @@ fn simple_inject_cache_get(
     let mut guard = set.lock();
     let pos = guard.iter().rposition(|entry| {
         entry.key_hash == key_hash
+            && entry.template_fingerprint == template_fingerprint
             && entry.tools_token.as_ref() == tools_token
             && simple_inject_tool_choice_token_eq(
                 entry.tool_choice_token.as_deref(),
@@ -94,6 +102,7 @@ fn simple_inject_cache_get(
 
 fn simple_inject_cache_insert(
     key_hash: u64,
+    template_fingerprint: u64,
     tools_token: &[u8],
     tool_choice_token: Option<&[u8]>,
     saved_tools: Arc<[CanonicalToolSpec]>,
@@ -112,6 +121,7 @@ fn simple_inject_cache_insert(
     let mut guard = set.lock();
     if let Some(pos) = guard.iter().position(|entry| {
         entry.key_hash == key_hash
+            && entry.template_fingerprint == template_fingerprint
             && entry.tools_token.as_ref() == tools_token
             && simple_inject_tool_choice_token_eq(
                 entry.tool_choice_token.as_deref(),
@@ -127,6 +137,7 @@ fn simple_inject_cache_insert(
 
     let entry = SimpleInjectCacheEntry {
         key_hash,
+        template_fingerprint,
         tools_token: Arc::from(tools_token),
         tool_choice_token: tool_choice_token.map(Arc::from),
         saved_tools,
@@ -139,6 +150,7 @@ fn simple_inject_cache_insert(
 #[inline]
 fn simple_inject_thread_cache_get(
     key_hash: u64,
+    template_fingerprint: u64,
     tools_token: &[u8],
     tool_choice_token: Option<&[u8]>,
 ) -> Option<SimpleInjectArtifacts> {
@@ -146,6 +158,7 @@ fn simple_inject_thread_cache_get(
         let guard = slot.borrow();
         let entry = guard.as_ref()?;
         if entry.key_hash != key_hash
+            || entry.template_fingerprint != template_fingerprint
             || entry.tools_token.as_ref() != tools_token
             || !simple_inject_tool_choice_token_eq(
                 entry.tool_choice_token.as_deref(),
@@ -185,14 +198,17 @@ fn simple_inject_cacheable(tools_token: &[u8], tool_choice_token: Option<&[u8]>)
 pub(super) fn resolve_simple_inject_artifacts(
     tools_token: &[u8],
     tool_choice_token: Option<&[u8]>,
-    features: &FeaturesConfig,
+    prompt_template: Option<&PromptTemplate>,
 ) -> Result<Option<SimpleInjectArtifacts>, CanonicalError> {
     let cacheable = simple_inject_cacheable(tools_token, tool_choice_token);
+    let template_fingerprint = prompt_template.map_or(0, PromptTemplate::fingerprint);
     let mut key_hash: Option<u64> = None;
     if cacheable {
         let hash = simple_inject_key_hash(tools_token, tool_choice_token);
         key_hash = Some(hash);
-        if let Some(hit) = simple_inject_cache_get(hash, tools_token, tool_choice_token) {
+        if let Some(hit) =
+            simple_inject_cache_get(hash, template_fingerprint, tools_token, tool_choice_token)
+        {
             return Ok(Some(hit));
         }
     }
@@ -218,7 +234,7 @@ pub(super) fn resolve_simple_inject_artifacts(
     let prompt_artifacts = Arc::new(fc::prompt::generate_fc_prompt_artifacts(
         &saved_tools_vec,
         &tool_choice,
-        features.prompt_template.as_deref(),
+        prompt_template,
     )?);
     let saved_tools = Arc::<[CanonicalToolSpec]>::from(saved_tools_vec);
 
@@ -227,6 +243,7 @@ pub(super) fn resolve_simple_inject_artifacts(
             key_hash.unwrap_or_else(|| simple_inject_key_hash(tools_token, tool_choice_token));
         simple_inject_cache_insert(
             hash,
+            template_fingerprint,
             tools_token,
             tool_choice_token,
             Arc::clone(&saved_tools),
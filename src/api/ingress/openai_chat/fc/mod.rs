@@ -5,9 +5,9 @@ use std::collections::HashMap;
 
 use serde_json::Value;
 
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
 use crate::fc;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::canonical::{CanonicalToolChoice, CanonicalToolFunction, CanonicalToolSpec};
 use crate::protocol::openai_chat::{
     OpenAiChatRequest, OpenAiMessage, OpenAiTool, OpenAiToolChoice,
@@ -18,13 +18,13 @@ pub(crate) type OpenAiSimpleInjectBuild = raw_inject::OpenAiSimpleInjectBuild;
 pub(crate) fn try_build_openai_simple_fc_inject_body_from_raw(
     body: &bytes::Bytes,
     actual_model: &str,
-    features: &FeaturesConfig,
+    prompt_template: Option<&PromptTemplate>,
     ranges_hint: Option<&crate::api::common::CommonProbeRanges>,
 ) -> Result<Option<OpenAiSimpleInjectBuild>, CanonicalError> {
     raw_inject::try_build_openai_simple_fc_inject_body_from_raw(
         body,
         actual_model,
-        features,
+        prompt_template,
         ranges_hint,
     )
 }
@@ -51,7 +51,7 @@ pub(crate) fn messages_inner_bounds_if_simple(messages_token: &[u8]) -> Option<(
 
 pub(crate) fn apply_fc_inject_openai_wire(
     request: &mut OpenAiChatRequest,
-    features: &FeaturesConfig,
+    prompt_template: Option<&PromptTemplate>,
 ) -> Result<Vec<CanonicalToolSpec>, CanonicalError> {
     if openai_chat_prefers_structured_output(request) {
         return Ok(Vec::new());
@@ -63,11 +63,8 @@ pub(crate) fn apply_fc_inject_openai_wire(
     }
 
     let saved_tools = decode_openai_wire_tools(request.tools.take());
-    let fc_prompt_artifacts = fc::prompt::generate_fc_prompt_artifacts(
-        &saved_tools,
-        &tool_choice,
-        features.prompt_template.as_deref(),
-    )?;
+    let fc_prompt_artifacts =
+        fc::prompt::generate_fc_prompt_artifacts(&saved_tools, &tool_choice, prompt_template)?;
     let fc_prompt = fc_prompt_artifacts.prompt();
 
     if is_simple_fc_inject_openai_request(&request.messages) {
@@ -80,6 +77,7 @@ pub(crate) fn apply_fc_inject_openai_wire(
                 tool_calls: None,
                 tool_call_id: None,
                 refusal: None,
+                annotations: None,
             },
         );
         request.tools = None;
@@ -196,6 +194,7 @@ pub(crate) fn apply_fc_inject_openai_wire(
             tool_calls: None,
             tool_call_id: None,
             refusal: None,
+            annotations: None,
         },
     );
 
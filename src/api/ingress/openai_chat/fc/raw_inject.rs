@@ -6,8 +6,8 @@ use crate::api::common::{
     find_common_probe_field_ranges, parse_json_string_end, parse_json_value_end,
     parse_optional_bool_token, raw_tools_token_has_items, skip_ws, CommonProbeRanges,
 };
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
+use crate::fc::prompt::PromptTemplate;
 use crate::json_scan::find_top_level_field_value_range;
 use crate::protocol::canonical::CanonicalToolSpec;
 use crate::util::{mix_u64, sampled_bytes_hash};
@@ -31,6 +31,7 @@ struct SimpleInjectBodyCacheEntry {
     key_hash: u64,
     source_body: Arc<[u8]>,
     actual_model: Arc<str>,
+    template_fingerprint: u64,
     inject_body: bytes::Bytes,
     saved_tools: Arc<[CanonicalToolSpec]>,
     inject_stream: bool,
@@ -71,6 +72,7 @@ fn simple_inject_body_set_index(key_hash: u64) -> usize {
 fn simple_inject_body_cache_get(
     source_body: &[u8],
     actual_model: &str,
+    template_fingerprint: u64,
     key_hash_hint: Option<u64>,
 ) -> Option<OpenAiSimpleInjectBuild> {
     if !simple_inject_body_cacheable(source_body, actual_model) {
@@ -78,7 +80,12 @@ fn simple_inject_body_cache_get(
     }
     let key_hash =
         key_hash_hint.unwrap_or_else(|| simple_inject_body_key_hash(source_body, actual_model));
-    if let Some(cached) = simple_inject_body_thread_cache_get(source_body, actual_model, key_hash) {
+    if let Some(cached) = simple_inject_body_thread_cache_get(
+        source_body,
+        actual_model,
+        template_fingerprint,
+        key_hash,
+    ) {
         return Some(cached);
     }
 
@@ -86,6 +93,7 @@ fn simple_inject_body_cache_get(
     let mut guard = set.lock();
     let pos = guard.iter().rposition(|entry| {
         entry.key_hash == key_hash
+            && entry.template_fingerprint == template_fingerprint
             && entry.actual_model.as_ref() == actual_model
             && entry.source_body.as_ref() == source_body
     })?;
@@ -113,6 +121,7 @@ fn simple_inject_body_cache_get(
 fn simple_inject_body_cache_insert(
     source_body: &[u8],
     actual_model: &str,
+    template_fingerprint: u64,
     inject_body: &bytes::Bytes,
     saved_tools: &Arc<[CanonicalToolSpec]>,
     inject_stream: bool,
@@ -127,6 +136,7 @@ fn simple_inject_body_cache_insert(
     let mut guard = set.lock();
     if let Some(pos) = guard.iter().position(|entry| {
         entry.key_hash == key_hash
+            && entry.template_fingerprint == template_fingerprint
             && entry.actual_model.as_ref() == actual_model
             && entry.source_body.as_ref() == source_body
     }) {
@@ -139,6 +149,7 @@ fn simple_inject_body_cache_insert(
         key_hash,
         source_body: Arc::from(source_body),
         actual_model: Arc::from(actual_model),
+        template_fingerprint,
         inject_body: inject_body.clone(),
         saved_tools: Arc::clone(saved_tools),
         inject_stream,
@@ -151,12 +162,14 @@ fn simple_inject_body_cache_insert(
 fn simple_inject_body_thread_cache_get(
     source_body: &[u8],
     actual_model: &str,
+    template_fingerprint: u64,
     key_hash: u64,
 ) -> Option<OpenAiSimpleInjectBuild> {
     SIMPLE_INJECT_BODY_LAST_HIT.with(|slot| {
         let guard = slot.borrow();
         let entry = guard.as_ref()?;
         if entry.key_hash != key_hash
+            || entry.template_fingerprint != template_fingerprint
             || entry.actual_model.as_ref() != actual_model
             || entry.source_body.as_ref() != source_body
         {
@@ -180,7 +193,7 @@ fn simple_inject_body_thread_cache_set(entry: &SimpleInjectBodyCacheEntry) {
 pub(crate) fn try_build_openai_simple_fc_inject_body_from_raw(
     body: &bytes::Bytes,
     actual_model: &str,
-    features: &FeaturesConfig,
+    prompt_template: Option<&PromptTemplate>,
     ranges_hint: Option<&CommonProbeRanges>,
 ) -> Result<Option<OpenAiSimpleInjectBuild>, CanonicalError> {
     let body_slice = body.as_ref();
@@ -191,7 +204,13 @@ pub(crate) fn try_build_openai_simple_fc_inject_body_from_raw(
                 .then(|| simple_inject_body_key_hash(body_slice, actual_model))
         });
 
-    if let Some(cached) = simple_inject_body_cache_get(body_slice, actual_model, body_key_hash) {
+    let template_fingerprint = prompt_template.map_or(0, PromptTemplate::fingerprint);
+    if let Some(cached) = simple_inject_body_cache_get(
+        body_slice,
+        actual_model,
+        template_fingerprint,
+        body_key_hash,
+    ) {
         return Ok(Some(cached));
     }
 
@@ -238,7 +257,7 @@ pub(crate) fn try_build_openai_simple_fc_inject_body_from_raw(
         .as_ref()
         .map(|range| &body_slice[range.clone()][..]);
     let Some((saved_tools, fc_prompt_artifacts)) =
-        resolve_simple_inject_artifacts(tools_token, tool_choice_token, features)?
+        resolve_simple_inject_artifacts(tools_token, tool_choice_token, prompt_template)?
     else {
         return Ok(None);
     };
@@ -265,6 +284,7 @@ pub(crate) fn try_build_openai_simple_fc_inject_body_from_raw(
     simple_inject_body_cache_insert(
         body_slice,
         actual_model,
+        template_fingerprint,
         &inject_body,
         &saved_tools,
         inject_stream,
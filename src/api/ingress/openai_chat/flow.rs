@@ -9,7 +9,7 @@ use crate::state::AppState;
 
 use super::spec::OpenAiChatSpec;
 
-pub(super) async fn handler_inner(
+pub(crate) async fn handler_inner(
     state: Arc<AppState>,
     headers: HeaderMap,
     body: bytes::Bytes,
@@ -49,7 +49,6 @@ mod tests {
         rewrite_model_field_in_json_body_with_range, route_prompt_prefix_bytes,
         try_build_openai_simple_fc_inject_body_from_raw,
     };
-    use crate::config::FeaturesConfig;
     use crate::protocol::openai_chat::{
         OpenAiChatRequest, OpenAiMessage, OpenAiTool, OpenAiToolChoice, OpenAiToolChoiceFunction,
         OpenAiToolChoiceFunctionCall, OpenAiToolFunction,
@@ -144,6 +143,7 @@ mod tests {
                     tool_calls: None,
                     tool_call_id: None,
                     refusal: None,
+                    annotations: None,
                 },
                 OpenAiMessage {
                     role: "assistant".to_string(),
@@ -159,6 +159,7 @@ mod tests {
                     }]),
                     tool_call_id: None,
                     refusal: None,
+                    annotations: None,
                 },
                 OpenAiMessage {
                     role: "tool".to_string(),
@@ -167,6 +168,7 @@ mod tests {
                     tool_calls: None,
                     tool_call_id: Some("call_1".to_string()),
                     refusal: None,
+                    annotations: None,
                 },
             ],
             tools: Some(vec![OpenAiTool {
@@ -199,8 +201,7 @@ mod tests {
             extra: serde_json::Map::new(),
         };
 
-        let saved_tools =
-            apply_fc_inject_openai_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_openai_wire(&mut req, None).unwrap();
         assert_eq!(saved_tools.len(), 1);
         assert!(req.tools.is_none());
         assert!(req.tool_choice.is_none());
@@ -245,6 +246,7 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 refusal: None,
+                annotations: None,
             }],
             tools: Some(vec![OpenAiTool {
                 type_: "function".to_string(),
@@ -268,8 +270,7 @@ mod tests {
             extra: serde_json::Map::new(),
         };
 
-        let saved_tools =
-            apply_fc_inject_openai_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_openai_wire(&mut req, None).unwrap();
         assert_eq!(saved_tools.len(), 1);
         assert_eq!(req.messages[0].role, "system");
         let system_text = req.messages[0]
@@ -291,6 +292,7 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 refusal: None,
+                annotations: None,
             }],
             tools: Some(vec![OpenAiTool {
                 type_: "function".to_string(),
@@ -314,8 +316,7 @@ mod tests {
             extra: serde_json::Map::new(),
         };
 
-        let saved_tools =
-            apply_fc_inject_openai_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_openai_wire(&mut req, None).unwrap();
         assert!(saved_tools.is_empty());
         assert!(req.tools.as_ref().is_some_and(|tools| tools.len() == 1));
         assert!(matches!(
@@ -338,13 +339,8 @@ mod tests {
             }"#,
         );
 
-        let built = try_build_openai_simple_fc_inject_body_from_raw(
-            &body,
-            "m1",
-            &FeaturesConfig::default(),
-            None,
-        )
-        .unwrap();
+        let built =
+            try_build_openai_simple_fc_inject_body_from_raw(&body, "m1", None, None).unwrap();
         assert!(built.is_none());
     }
 
@@ -359,6 +355,7 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 refusal: None,
+                annotations: None,
             }],
             tools: Some(vec![OpenAiTool {
                 type_: "function".to_string(),
@@ -386,8 +383,7 @@ mod tests {
             serde_json::json!({"type":"json_schema","json_schema":{"name":"x","schema":{"type":"object"}}}),
         );
 
-        let saved_tools =
-            apply_fc_inject_openai_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_openai_wire(&mut req, None).unwrap();
         assert!(saved_tools.is_empty());
         assert!(req.tools.as_ref().is_some_and(|tools| tools.len() == 1));
         assert!(matches!(
@@ -410,13 +406,8 @@ mod tests {
             }"#,
         );
 
-        let built = try_build_openai_simple_fc_inject_body_from_raw(
-            &body,
-            "m1",
-            &FeaturesConfig::default(),
-            None,
-        )
-        .unwrap();
+        let built =
+            try_build_openai_simple_fc_inject_body_from_raw(&body, "m1", None, None).unwrap();
         assert!(built.is_none());
     }
 
@@ -10,8 +10,8 @@ use crate::api::engine::failover::{
     run_openai_chat_fc_non_stream, run_openai_chat_no_tools_non_stream,
 };
 use crate::api::engine::pipeline::{CommonProbeRanges, CommonRequestProbe, UpstreamIoRequest};
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::canonical::{CanonicalRequest, CanonicalToolSpec, IngressApi, ProviderKind};
 use crate::protocol::openai_chat::decoder::{
     decode_openai_chat_request, decode_openai_chat_request_owned,
@@ -60,9 +60,9 @@ impl CompatFlowSpec for OpenAiChatSpec {
 
     fn apply_wire_inject(
         wire_request: &mut Self::WireRequest,
-        features: &FeaturesConfig,
+        prompt_template: Option<&PromptTemplate>,
     ) -> Result<Vec<CanonicalToolSpec>, CanonicalError> {
-        apply_fc_inject_openai_wire(wire_request, features)
+        apply_fc_inject_openai_wire(wire_request, prompt_template)
     }
 
     fn wire_stream_requested(wire_request: &Self::WireRequest) -> bool {
@@ -174,19 +174,24 @@ impl CompatFlowSpec for OpenAiChatSpec {
     fn try_raw_inject_fast_path(
         body: &bytes::Bytes,
         actual_model: &str,
-        features: &FeaturesConfig,
+        prompt_template: Option<&PromptTemplate>,
         probe_ranges: Option<&CommonProbeRanges>,
     ) -> Result<Option<RawInjectPayload>, CanonicalError> {
-        try_build_openai_simple_fc_inject_body_from_raw(body, actual_model, features, probe_ranges)
-            .map(|result| {
-                result.map(
-                    |(inject_body, inject_saved_tools, inject_stream)| RawInjectPayload {
-                        body: inject_body,
-                        saved_tools: inject_saved_tools,
-                        stream: inject_stream,
-                        fc_active: true,
-                    },
-                )
-            })
+        try_build_openai_simple_fc_inject_body_from_raw(
+            body,
+            actual_model,
+            prompt_template,
+            probe_ranges,
+        )
+        .map(|result| {
+            result.map(
+                |(inject_body, inject_saved_tools, inject_stream)| RawInjectPayload {
+                    body: inject_body,
+                    saved_tools: inject_saved_tools,
+                    stream: inject_stream,
+                    fc_active: true,
+                },
+            )
+        })
     }
 }
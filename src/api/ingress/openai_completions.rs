@@ -0,0 +1,316 @@
+//! Legacy `OpenAI` text completions (`/v1/completions`).
+//!
+//! Requests are rewritten into a single-user-message chat completion and run
+//! through the chat ingress; responses and stream chunks are converted back
+//! into the `text_completion` shape.
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Response;
+use futures_util::StreamExt;
+use serde_json::{json, Map, Value};
+
+use crate::api::openai_chat;
+use crate::error::{into_axum_response, CanonicalError};
+use crate::protocol::canonical::IngressApi;
+use crate::state::AppState;
+use crate::stream::sse::sse_raw_frame_stream;
+
+const INGRESS: IngressApi = IngressApi::OpenAiChat;
+
+/// Sampling and control fields that carry over to chat completions unchanged.
+const PASSTHROUGH_FIELDS: &[&str] = &[
+    "model",
+    "max_tokens",
+    "temperature",
+    "top_p",
+    "n",
+    "stop",
+    "presence_penalty",
+    "frequency_penalty",
+    "logit_bias",
+    "seed",
+    "user",
+    "stream",
+    "stream_options",
+];
+
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    let (chat_body, echo_prompt) = match completion_to_chat_request(&body) {
+        Ok(converted) => converted,
+        Err(err) => return into_axum_response(&err, INGRESS),
+    };
+    let response = openai_chat::handler(State(state), headers, chat_body).await;
+    match chat_response_to_completion(response, echo_prompt).await {
+        Ok(response) => response,
+        Err(err) => into_axum_response(&err, INGRESS),
+    }
+}
+
+/// Map a legacy completions request onto a chat completions request.
+///
+/// Returns the chat body and, when `echo` is set, the prompt to prepend to
+/// the generated text.
+fn completion_to_chat_request(
+    body: &[u8],
+) -> Result<(bytes::Bytes, Option<String>), CanonicalError> {
+    let request: Map<String, Value> = serde_json::from_slice(body)
+        .map_err(|e| CanonicalError::InvalidRequest(format!("Invalid JSON body: {e}")))?;
+
+    let prompt = match request.get("prompt") {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(prompt)) => prompt.clone(),
+        Some(Value::Array(prompts)) => match prompts.as_slice() {
+            [] => String::new(),
+            [Value::String(prompt)] => prompt.clone(),
+            [Value::String(_), ..] => {
+                return Err(CanonicalError::InvalidRequest(
+                    "Batched prompts are not supported; send one prompt per request".to_string(),
+                ))
+            }
+            _ => {
+                return Err(CanonicalError::InvalidRequest(
+                    "Token-array prompts are not supported; send the prompt as text".to_string(),
+                ))
+            }
+        },
+        Some(_) => {
+            return Err(CanonicalError::InvalidRequest(
+                "'prompt' must be a string".to_string(),
+            ))
+        }
+    };
+
+    let mut messages = Vec::with_capacity(2);
+    if let Some(suffix) = request
+        .get("suffix")
+        .and_then(Value::as_str)
+        .filter(|suffix| !suffix.is_empty())
+    {
+        messages.push(json!({
+            "role": "system",
+            "content": format!(
+                "Continue the user's text. Your output will be inserted between the user's text and the following suffix, so it must lead naturally into it. Output only the inserted text.\n\nSuffix:\n{suffix}"
+            ),
+        }));
+    }
+    messages.push(json!({ "role": "user", "content": prompt }));
+
+    let mut chat = Map::with_capacity(PASSTHROUGH_FIELDS.len() + 1);
+    for field in PASSTHROUGH_FIELDS {
+        if let Some(value) = request.get(*field) {
+            chat.insert((*field).to_string(), value.clone());
+        }
+    }
+    chat.insert("messages".to_string(), Value::Array(messages));
+
+    let echo = request.get("echo").and_then(Value::as_bool) == Some(true);
+    let chat_body = serde_json::to_vec(&chat)
+        .map_err(|e| CanonicalError::Internal(format!("Failed to encode chat request: {e}")))?;
+    Ok((bytes::Bytes::from(chat_body), echo.then_some(prompt)))
+}
+
+async fn chat_response_to_completion(
+    response: Response,
+    echo_prompt: Option<String>,
+) -> Result<Response, CanonicalError> {
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(http::header::CONTENT_LENGTH);
+
+    if is_sse {
+        // Echoed prompt text is emitted once, ahead of the first chunk per choice.
+        let mut echo_pending: Option<(String, Vec<u64>)> =
+            echo_prompt.map(|prompt| (prompt, Vec::new()));
+        let frames = sse_raw_frame_stream(body.into_data_stream()).map(move |frame| {
+            Ok::<_, std::convert::Infallible>(
+                convert_sse_frame(&frame, echo_pending.as_mut()).unwrap_or(frame),
+            )
+        });
+        return Ok(Response::from_parts(
+            parts,
+            axum::body::Body::from_stream(frames),
+        ));
+    }
+
+    let body_bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| CanonicalError::Transport(format!("Failed to read response body: {e}")))?;
+    let chat: Value = serde_json::from_slice(&body_bytes).map_err(|e| {
+        CanonicalError::Translation(format!("Invalid chat completion response: {e}"))
+    })?;
+    let completion = chat_completion_to_text_completion(&chat, echo_prompt.as_deref());
+    let encoded = serde_json::to_vec(&completion)
+        .map_err(|e| CanonicalError::Internal(format!("Failed to encode completion: {e}")))?;
+    Ok(Response::from_parts(parts, axum::body::Body::from(encoded)))
+}
+
+fn chat_completion_to_text_completion(chat: &Value, echo_prompt: Option<&str>) -> Value {
+    let choices: Vec<Value> = chat
+        .get("choices")
+        .and_then(Value::as_array)
+        .map(|choices| {
+            choices
+                .iter()
+                .map(|choice| {
+                    let content = choice
+                        .pointer("/message/content")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    let text = match echo_prompt {
+                        Some(prompt) => format!("{prompt}{content}"),
+                        None => content.to_string(),
+                    };
+                    text_choice(choice, text)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut completion = completion_envelope(chat, choices);
+    if let Some(usage) = chat.get("usage") {
+        completion.insert("usage".to_string(), usage.clone());
+    }
+    Value::Object(completion)
+}
+
+fn convert_sse_frame(
+    frame: &[u8],
+    echo_pending: Option<&mut (String, Vec<u64>)>,
+) -> Option<bytes::Bytes> {
+    let text = std::str::from_utf8(frame).ok()?;
+    let payload = text
+        .lines()
+        .find_map(|line| line.strip_prefix("data:"))
+        .map(str::trim)?;
+    if !payload.starts_with('{') {
+        return None;
+    }
+    let chunk: Value = serde_json::from_str(payload).ok()?;
+    let chunk_choices = chunk.get("choices")?.as_array()?;
+
+    let mut echo_pending = echo_pending;
+    let choices: Vec<Value> = chunk_choices
+        .iter()
+        .map(|choice| {
+            let content = choice
+                .pointer("/delta/content")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let index = choice.get("index").and_then(Value::as_u64).unwrap_or(0);
+            let text = match echo_pending.as_deref_mut() {
+                Some((prompt, echoed)) if !echoed.contains(&index) => {
+                    echoed.push(index);
+                    format!("{prompt}{content}")
+                }
+                _ => content.to_string(),
+            };
+            text_choice(choice, text)
+        })
+        .collect();
+
+    let mut completion = completion_envelope(&chunk, choices);
+    if let Some(usage) = chunk.get("usage").filter(|usage| !usage.is_null()) {
+        completion.insert("usage".to_string(), usage.clone());
+    }
+    let encoded = serde_json::to_string(&Value::Object(completion)).ok()?;
+    Some(bytes::Bytes::from(format!("data: {encoded}\n\n")))
+}
+
+fn completion_envelope(chat: &Value, choices: Vec<Value>) -> Map<String, Value> {
+    let mut completion = Map::with_capacity(6);
+    for field in ["id", "created", "model", "system_fingerprint"] {
+        if let Some(value) = chat.get(field) {
+            completion.insert(field.to_string(), value.clone());
+        }
+    }
+    completion.insert(
+        "object".to_string(),
+        Value::String("text_completion".to_string()),
+    );
+    completion.insert("choices".to_string(), Value::Array(choices));
+    completion
+}
+
+fn text_choice(choice: &Value, text: String) -> Value {
+    json!({
+        "text": text,
+        "index": choice.get("index").cloned().unwrap_or(Value::from(0)),
+        "logprobs": Value::Null,
+        "finish_reason": choice.get("finish_reason").cloned().unwrap_or(Value::Null),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_request_maps_prompt_suffix_and_echo() {
+        let body = br#"{"model":"m","prompt":["def add(a, b):"],"suffix":"\n\nprint(add(1, 2))","echo":true,"max_tokens":16,"best_of":2}"#;
+        let (chat_body, echo) = completion_to_chat_request(body).unwrap();
+        let chat: Value = serde_json::from_slice(&chat_body).unwrap();
+        assert_eq!(chat["model"], "m");
+        assert_eq!(chat["max_tokens"], 16);
+        assert!(chat.get("best_of").is_none());
+        assert_eq!(chat["messages"][0]["role"], "system");
+        assert!(chat["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("print(add(1, 2))"));
+        assert_eq!(chat["messages"][1]["content"], "def add(a, b):");
+        assert_eq!(echo.as_deref(), Some("def add(a, b):"));
+
+        assert!(completion_to_chat_request(br#"{"model":"m","prompt":["a","b"]}"#).is_err());
+        assert!(completion_to_chat_request(br#"{"model":"m","prompt":[1,2,3]}"#).is_err());
+    }
+
+    #[test]
+    fn test_chat_completion_converted_to_text_completion() {
+        let chat = json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1,
+            "model": "m",
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": " world" }, "finish_reason": "length" }],
+            "usage": { "prompt_tokens": 1, "completion_tokens": 2, "total_tokens": 3 }
+        });
+        let completion = chat_completion_to_text_completion(&chat, Some("hello"));
+        assert_eq!(completion["object"], "text_completion");
+        assert_eq!(completion["choices"][0]["text"], "hello world");
+        assert_eq!(completion["choices"][0]["finish_reason"], "length");
+        assert_eq!(completion["usage"]["total_tokens"], 3);
+    }
+
+    #[test]
+    fn test_stream_chunk_converted_and_echo_emitted_once() {
+        let frame = b"data: {\"id\":\"c\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"},\"finish_reason\":null}]}\n\n";
+        let mut echo = ("hel".to_string(), Vec::new());
+
+        let first = convert_sse_frame(frame, Some(&mut echo)).unwrap();
+        let first = std::str::from_utf8(&first).unwrap();
+        assert!(first.starts_with("data: {"));
+        assert!(first.contains(r#""object":"text_completion""#));
+        assert!(first.contains(r#""text":"hello""#));
+
+        let second = convert_sse_frame(frame, Some(&mut echo)).unwrap();
+        assert!(std::str::from_utf8(&second)
+            .unwrap()
+            .contains(r#""text":"lo""#));
+
+        assert!(convert_sse_frame(b"data: [DONE]\n\n", None).is_none());
+    }
+}
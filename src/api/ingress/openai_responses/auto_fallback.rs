@@ -19,6 +19,7 @@ use crate::api::ingress::openai_responses::io::{
 
 pub(crate) struct OpenAiResponsesAutoFallbackCtx<'a> {
     pub(crate) state: &'a AppState,
+    pub(crate) body: &'a bytes::Bytes,
     pub(crate) wire_request: &'a ResponsesRequest,
     pub(crate) route: RouteTarget<'a>,
     pub(crate) client_model: &'a str,
@@ -63,8 +64,11 @@ async fn run_openai_responses_wire_retry(
     let mut inject_wire = ctx.wire_request.clone();
     inject_wire.model.clear();
     inject_wire.model.push_str(ctx.route.actual_model);
-    let inject_saved_tools =
-        apply_fc_inject_responses_wire(&mut inject_wire, &ctx.state.config.features)?;
+    let inject_saved_tools = apply_fc_inject_responses_wire(
+        &mut inject_wire,
+        ctx.state
+            .fc_prompt_template(ctx.route.upstream_index, ctx.requested_model, ctx.body),
+    )?;
     let inject_fc_active = !inject_saved_tools.is_empty();
     let inject_stream = inject_wire.stream.unwrap_or(false);
     let inject_body = serde_json::to_vec(&inject_wire)
@@ -108,8 +112,13 @@ async fn run_openai_responses_canonical_retry(
     let mut inject_canonical = decode_responses_request(ctx.wire_request, ctx.request_id)?;
     inject_canonical.model.clear();
     inject_canonical.model.push_str(ctx.route.actual_model);
-    let inject_saved_tools =
-        fc::apply_fc_inject_take_tools(&mut inject_canonical, &ctx.state.config.features)?;
+    fc::normalize_system_messages(&mut inject_canonical, &ctx.state.config.features);
+    let inject_saved_tools = fc::apply_fc_inject_take_tools(
+        &mut inject_canonical,
+        &ctx.state.config.features,
+        ctx.state
+            .fc_prompt_template(ctx.route.upstream_index, ctx.requested_model, ctx.body),
+    )?;
     let inject_fc_active = !inject_saved_tools.is_empty();
     let io_target = prepare_upstream_io_request(
         ctx.state,
@@ -1,9 +1,9 @@
 use rustc_hash::FxHashMap;
 use serde::Deserialize;
 
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
 use crate::fc;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::canonical::{CanonicalToolChoice, CanonicalToolFunction, CanonicalToolSpec};
 use crate::protocol::openai_responses::{ResponsesRequest, ResponsesTool};
 
@@ -27,7 +27,7 @@ pub(crate) fn responses_tools_token_has_function(token: &[u8]) -> bool {
 
 pub(crate) fn apply_fc_inject_responses_wire(
     request: &mut ResponsesRequest,
-    features: &FeaturesConfig,
+    prompt_template: Option<&PromptTemplate>,
 ) -> Result<Vec<CanonicalToolSpec>, CanonicalError> {
     if responses_prefers_structured_output(request) {
         return Ok(Vec::new());
@@ -47,11 +47,7 @@ pub(crate) fn apply_fc_inject_responses_wire(
         };
         return Ok(saved_tools);
     }
-    let fc_prompt = fc::prompt::generate_fc_prompt(
-        &saved_tools,
-        &tool_choice,
-        features.prompt_template.as_deref(),
-    )?;
+    let fc_prompt = fc::prompt::generate_fc_prompt(&saved_tools, &tool_choice, prompt_template)?;
 
     request.instructions = Some(match request.instructions.take() {
         Some(existing) => format!("{existing}\n{fc_prompt}"),
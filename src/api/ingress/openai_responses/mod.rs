@@ -27,8 +27,6 @@ use self::flow::handler_inner;
 #[cfg(test)]
 use self::parse::parse_openai_responses_probe;
 #[cfg(test)]
-use crate::config::FeaturesConfig;
-#[cfg(test)]
 use crate::protocol::openai_responses::ResponsesTool;
 use crate::state::AppState;
 
@@ -89,8 +87,7 @@ mod tests {
             extra: serde_json::Map::new(),
         };
 
-        let saved_tools =
-            apply_fc_inject_responses_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_responses_wire(&mut req, None).unwrap();
         assert_eq!(saved_tools.len(), 1);
         assert!(req
             .instructions
@@ -163,8 +160,7 @@ mod tests {
             extra: serde_json::Map::new(),
         };
 
-        let saved_tools =
-            apply_fc_inject_responses_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_responses_wire(&mut req, None).unwrap();
         assert!(saved_tools.is_empty());
         assert_eq!(req.instructions.as_deref(), Some("sys"));
         assert_eq!(req.tool_choice, Some(serde_json::json!("required")));
@@ -202,8 +198,7 @@ mod tests {
             extra: serde_json::Map::new(),
         };
 
-        let saved_tools =
-            apply_fc_inject_responses_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_responses_wire(&mut req, None).unwrap();
         assert!(saved_tools.is_empty());
         assert_eq!(req.instructions.as_deref(), Some("sys"));
         assert_eq!(req.tool_choice, Some(serde_json::json!("none")));
@@ -245,8 +240,7 @@ mod tests {
             serde_json::json!({"format":{"type":"json_schema","schema":{"type":"object"}}}),
         );
 
-        let saved_tools =
-            apply_fc_inject_responses_wire(&mut req, &FeaturesConfig::default()).unwrap();
+        let saved_tools = apply_fc_inject_responses_wire(&mut req, None).unwrap();
         assert!(saved_tools.is_empty());
         assert_eq!(req.instructions.as_deref(), Some("sys"));
         assert_eq!(req.tool_choice, Some(serde_json::json!("auto")));
@@ -10,8 +10,8 @@ use crate::api::engine::failover::{
     run_openai_responses_fc_non_stream, run_openai_responses_no_tools_non_stream,
 };
 use crate::api::engine::pipeline::{CommonRequestProbe, UpstreamIoRequest};
-use crate::config::FeaturesConfig;
 use crate::error::CanonicalError;
+use crate::fc::prompt::PromptTemplate;
 use crate::protocol::canonical::{CanonicalRequest, CanonicalToolSpec, IngressApi, ProviderKind};
 use crate::protocol::openai_responses::decoder::{
     decode_responses_request, decode_responses_request_owned,
@@ -62,9 +62,9 @@ impl CompatFlowSpec for OpenAiResponsesSpec {
 
     fn apply_wire_inject(
         wire_request: &mut Self::WireRequest,
-        features: &FeaturesConfig,
+        prompt_template: Option<&PromptTemplate>,
     ) -> Result<Vec<CanonicalToolSpec>, CanonicalError> {
-        apply_fc_inject_responses_wire(wire_request, features)
+        apply_fc_inject_responses_wire(wire_request, prompt_template)
     }
 
     fn wire_stream_requested(wire_request: &Self::WireRequest) -> bool {
@@ -122,6 +122,7 @@ impl CompatFlowSpec for OpenAiResponsesSpec {
         run_openai_responses_auto_fallback(
             OpenAiResponsesAutoFallbackCtx {
                 state: input.state,
+                body: input.body,
                 wire_request: input.wire_request,
                 route: input.route,
                 client_model: input.client_model,
@@ -0,0 +1,333 @@
+//! Anthropic Message Batches API passthrough (`/v1/messages/batches`).
+//!
+//! Batches are created on a native Anthropic upstream, the one serving the
+//! model of the batch's first request, falling back to the default Anthropic
+//! upstream, and the returned batch id is bound to it so later retrieve,
+//! cancel, delete and results calls reach the backend that runs it. Other
+//! providers have no batches endpoint and are never used.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde_json::{json, Value};
+
+use crate::error::{into_axum_response, CanonicalError};
+use crate::protocol::canonical::{IngressApi, ProviderKind};
+use crate::state::{AppState, SessionClass};
+use crate::transport::anthropic_beta::{
+    client_anthropic_betas, negotiate_anthropic_beta, ANTHROPIC_BETA,
+};
+
+const INGRESS: IngressApi = IngressApi::Anthropic;
+
+/// Operation on a single message batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageBatchAction {
+    Retrieve,
+    Delete,
+    Cancel,
+    Results,
+}
+
+impl MessageBatchAction {
+    fn method(self) -> Method {
+        match self {
+            Self::Retrieve | Self::Results => Method::GET,
+            Self::Delete => Method::DELETE,
+            Self::Cancel => Method::POST,
+        }
+    }
+
+    fn path_suffix(self, batch_id: &str) -> String {
+        match self {
+            Self::Retrieve | Self::Delete => format!("/{batch_id}"),
+            Self::Cancel => format!("/{batch_id}/cancel"),
+            Self::Results => format!("/{batch_id}/results"),
+        }
+    }
+}
+
+fn runs_batches(state: &AppState, upstream_index: usize) -> bool {
+    state.prepared_upstreams[upstream_index].provider_kind() == ProviderKind::Anthropic
+}
+
+fn batch_upstreams(state: &AppState) -> impl Iterator<Item = usize> + '_ {
+    (0..state.prepared_upstreams.len()).filter(|&index| runs_batches(state, index))
+}
+
+fn no_batch_upstream() -> Response {
+    into_axum_response(
+        &CanonicalError::InvalidRequest(
+            "No Anthropic upstream is configured to run message batches".to_string(),
+        ),
+        INGRESS,
+    )
+}
+
+/// Pick the upstream that runs a new batch.
+fn create_upstream(state: &AppState, body: &[u8]) -> Option<usize> {
+    let model = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|batch| {
+            batch
+                .pointer("/requests/0/params/model")?
+                .as_str()
+                .map(str::to_string)
+        });
+    if let Some(model) = model {
+        if let Ok(routes) = state.resolve_routes_with_policy(&model, 0, SessionClass::Portable) {
+            if let Some(route) = routes
+                .iter()
+                .find(|route| runs_batches(state, route.upstream_index))
+            {
+                return Some(route.upstream_index);
+            }
+        }
+    }
+    let upstreams = &state.config.upstream_services;
+    batch_upstreams(state).min_by_key(|&index| !upstreams[index].is_default)
+}
+
+async fn forward(
+    state: &AppState,
+    upstream_index: usize,
+    client_headers: &HeaderMap,
+    method: Method,
+    path_suffix: &str,
+    query: Option<&str>,
+    body: bytes::Bytes,
+) -> Result<reqwest::Response, CanonicalError> {
+    let upstream = &state.config.upstream_services[upstream_index];
+    let prepared = &state.prepared_upstreams[upstream_index];
+    let base_url = prepared.base_url();
+    let url = match query {
+        Some(query) => format!("{base_url}/messages/batches{path_suffix}?{query}"),
+        None => format!("{base_url}/messages/batches{path_suffix}"),
+    };
+    let mut headers = prepared.static_headers().clone();
+    let client_betas = client_anthropic_betas(client_headers);
+    if let Some(beta) = negotiate_anthropic_beta(client_betas.as_deref(), &upstream.anthropic_betas)
+    {
+        headers.insert(ANTHROPIC_BETA, beta);
+    }
+    state
+        .transport
+        .send_request(
+            &url,
+            method,
+            &headers,
+            body,
+            prepared.proxy_for(false).as_deref(),
+        )
+        .await
+}
+
+/// Relay an upstream answer as it streams; results are JSONL that may be
+/// large.
+fn streamed_response(response: reqwest::Response) -> Response {
+    let mut builder = Response::builder().status(response.status());
+    if let Some(value) = response.headers().get(http::header::CONTENT_TYPE) {
+        builder = builder.header(http::header::CONTENT_TYPE, value.clone());
+    }
+    builder
+        .body(Body::from_stream(response.bytes_stream()))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
+
+/// `POST /v1/messages/batches`
+pub async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    if let Err(err) = state.authenticate(INGRESS, &headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+    let Some(upstream_index) = create_upstream(&state, &body) else {
+        return no_batch_upstream();
+    };
+
+    let result = async {
+        let response = forward(
+            &state,
+            upstream_index,
+            &headers,
+            Method::POST,
+            "",
+            None,
+            body,
+        )
+        .await?;
+        let status = response.status();
+        let content_type = response.headers().get(http::header::CONTENT_TYPE).cloned();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| CanonicalError::Transport(format!("Failed to read upstream body: {e}")))?;
+        Ok::<_, CanonicalError>((status, content_type, body))
+    }
+    .await;
+    let (status, content_type, body) = match result {
+        Ok(parts) => parts,
+        Err(err) => return into_axum_response(&err, INGRESS),
+    };
+
+    if status.is_success() {
+        let batch_id = serde_json::from_slice::<Value>(&body)
+            .ok()
+            .and_then(|batch| batch.get("id")?.as_str().map(str::to_string));
+        if let Some(batch_id) = batch_id {
+            tracing::debug!(
+                batch_id,
+                upstream = state.upstream_name(upstream_index),
+                "bound message batch to upstream"
+            );
+            state.bind_message_batch_upstream(&batch_id, upstream_index);
+        }
+    }
+    let mut builder = Response::builder().status(status);
+    if let Some(content_type) = content_type {
+        builder = builder.header(http::header::CONTENT_TYPE, content_type);
+    }
+    builder
+        .body(Body::from(body))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
+
+/// `GET /v1/messages/batches`: the merged listing of every Anthropic upstream.
+pub async fn list_handler(
+    State(state): State<Arc<AppState>>,
+    headers: &HeaderMap,
+    query: Option<&str>,
+) -> Response {
+    if let Err(err) = state.authenticate(INGRESS, headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+
+    let mut batches = Vec::new();
+    let mut has_more = false;
+    let mut listed_any = false;
+    let mut last_failure: Option<Response> = None;
+    for upstream_index in batch_upstreams(&state) {
+        let response = match forward(
+            &state,
+            upstream_index,
+            headers,
+            Method::GET,
+            "",
+            query,
+            bytes::Bytes::new(),
+        )
+        .await
+        {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                last_failure = Some(streamed_response(response));
+                continue;
+            }
+            Err(err) => {
+                last_failure = Some(into_axum_response(&err, INGRESS));
+                continue;
+            }
+        };
+        let Some(mut listing) = response
+            .bytes()
+            .await
+            .ok()
+            .and_then(|body| serde_json::from_slice::<Value>(&body).ok())
+        else {
+            continue;
+        };
+        listed_any = true;
+        has_more |= listing.get("has_more").and_then(Value::as_bool) == Some(true);
+        if let Some(Value::Array(data)) = listing.get_mut("data").map(Value::take) {
+            for batch in &data {
+                if let Some(batch_id) = batch.get("id").and_then(Value::as_str) {
+                    state.bind_message_batch_upstream(batch_id, upstream_index);
+                }
+            }
+            batches.extend(data);
+        }
+    }
+
+    if !listed_any {
+        return last_failure.unwrap_or_else(no_batch_upstream);
+    }
+    let first_id = batches.first().and_then(|batch| batch.get("id")).cloned();
+    let last_id = batches.last().and_then(|batch| batch.get("id")).cloned();
+    axum::Json(json!({
+        "data": batches,
+        "has_more": has_more,
+        "first_id": first_id,
+        "last_id": last_id,
+    }))
+    .into_response()
+}
+
+/// `GET|DELETE /v1/messages/batches/{id}`, `POST .../{id}/cancel` and
+/// `GET .../{id}/results`.
+///
+/// Unbound ids (created before a restart or directly on the backend) are
+/// looked up on each Anthropic upstream until one does not answer 404.
+pub async fn batch_handler(
+    State(state): State<Arc<AppState>>,
+    batch_id: &str,
+    action: MessageBatchAction,
+    headers: &HeaderMap,
+) -> Response {
+    if let Err(err) = state.authenticate(INGRESS, headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+
+    let bound = state.message_batch_upstream(batch_id);
+    let candidates: Vec<usize> = match bound {
+        Some(upstream_index) => vec![upstream_index],
+        None => batch_upstreams(&state).collect(),
+    };
+    if candidates.is_empty() {
+        return no_batch_upstream();
+    }
+
+    let path_suffix = action.path_suffix(batch_id);
+    let last = candidates.len() - 1;
+    for (position, upstream_index) in candidates.into_iter().enumerate() {
+        let response = match forward(
+            &state,
+            upstream_index,
+            headers,
+            action.method(),
+            &path_suffix,
+            None,
+            bytes::Bytes::new(),
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(err) if position < last => {
+                tracing::debug!(error = %err, "batch lookup failed on upstream; trying next");
+                continue;
+            }
+            Err(err) => return into_axum_response(&err, INGRESS),
+        };
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND && position < last {
+            continue;
+        }
+        if status.is_success() {
+            match action {
+                MessageBatchAction::Delete => state.unbind_message_batch_upstream(batch_id),
+                _ if bound.is_none() => {
+                    state.bind_message_batch_upstream(batch_id, upstream_index);
+                }
+                _ => {}
+            }
+        } else if status == StatusCode::NOT_FOUND && bound.is_some() {
+            state.unbind_message_batch_upstream(batch_id);
+        }
+        return streamed_response(response);
+    }
+    no_batch_upstream()
+}
@@ -1,7 +1,17 @@
+pub mod admin;
+pub mod batches;
 pub(crate) mod common;
 pub(crate) mod engine;
+pub mod files;
 pub mod health;
 pub mod ingress;
+pub mod message_batches;
 pub mod models;
+pub mod operations;
+pub mod streams;
 
-pub use ingress::{anthropic, gemini, openai_chat, openai_responses};
+#[cfg(feature = "grpc")]
+pub use ingress::grpc;
+pub use ingress::{
+    anthropic, gemini, gemini_openai_compat, openai_chat, openai_completions, openai_responses,
+};
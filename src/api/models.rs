@@ -5,8 +5,11 @@ use axum::http::HeaderMap;
 use axum::response::{IntoResponse, Response};
 use axum::{body::Body, http::StatusCode};
 
-use crate::error::into_axum_response;
+use serde_json::{json, Value};
+
+use crate::error::{into_axum_response, ErrorCategory};
 use crate::protocol::canonical::IngressApi;
+use crate::protocol::error_shapes::{gemini_error_payload, openai_error_payload};
 
 use crate::state::AppState;
 
@@ -17,7 +20,7 @@ pub async fn handler(State(state): State<Arc<AppState>>, headers: &HeaderMap) ->
     if let Err(err) = state.authenticate(INGRESS, headers) {
         return into_axum_response(&err, INGRESS);
     }
-    state.maybe_refresh_models_cache().await;
+    state.maybe_refresh_models_cache();
 
     (
         StatusCode::OK,
@@ -30,12 +33,123 @@ pub async fn handler(State(state): State<Arc<AppState>>, headers: &HeaderMap) ->
         .into_response()
 }
 
+/// One model of the `OpenAI` listing, as `GET /v1/models/{model}` returns it.
+#[must_use]
+pub async fn retrieve_handler(
+    State(state): State<Arc<AppState>>,
+    id: &str,
+    headers: &HeaderMap,
+) -> Response {
+    const INGRESS: IngressApi = IngressApi::OpenAiChat;
+    if let Err(err) = state.authenticate(INGRESS, headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+    state.maybe_refresh_models_cache();
+
+    let body: Value = serde_json::from_slice(&state.models_response_body()).unwrap_or_default();
+    let model = body
+        .get("data")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .find(|model| model.get("id").and_then(Value::as_str) == Some(id));
+    match model {
+        Some(model) => json_response(model),
+        None => {
+            let body = openai_error_payload(
+                ErrorCategory::NotFound,
+                &format!("The model '{id}' does not exist"),
+            );
+            (StatusCode::NOT_FOUND, axum::Json(body)).into_response()
+        }
+    }
+}
+
+/// List the routable models in Gemini `models.list` format.
+#[must_use]
+pub async fn gemini_list_handler(
+    State(state): State<Arc<AppState>>,
+    headers: &HeaderMap,
+) -> Response {
+    const INGRESS: IngressApi = IngressApi::Gemini;
+    if let Err(err) = state.authenticate(INGRESS, headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+    state.maybe_refresh_models_cache();
+
+    let models: Vec<Value> = listed_model_ids(&state)
+        .iter()
+        .map(|id| gemini_model(id))
+        .collect();
+    json_response(&json!({ "models": models }))
+}
+
+/// One routable model in Gemini `models.get` format.
+#[must_use]
+pub async fn gemini_get_handler(
+    State(state): State<Arc<AppState>>,
+    name: &str,
+    headers: &HeaderMap,
+) -> Response {
+    const INGRESS: IngressApi = IngressApi::Gemini;
+    if let Err(err) = state.authenticate(INGRESS, headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+    state.maybe_refresh_models_cache();
+
+    let name = name.strip_prefix("models/").unwrap_or(name);
+    if !listed_model_ids(&state).iter().any(|id| id == name) {
+        let status = StatusCode::NOT_FOUND;
+        let body = gemini_error_payload(
+            ErrorCategory::NotFound,
+            status,
+            &format!("Model '{name}' is not found"),
+        );
+        return (status, axum::Json(body)).into_response();
+    }
+    json_response(&gemini_model(name))
+}
+
+/// Model ids of the cached `OpenAI` listing.
+fn listed_model_ids(state: &AppState) -> Vec<String> {
+    let body: Value = serde_json::from_slice(&state.models_response_body()).unwrap_or_default();
+    body.get("data")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|model| model.get("id").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect()
+}
+
+fn gemini_model(id: &str) -> Value {
+    json!({
+        "name": format!("models/{id}"),
+        "baseModelId": id,
+        "displayName": id,
+        "supportedGenerationMethods": ["generateContent", "streamGenerateContent"],
+    })
+}
+
+fn json_response(body: &Value) -> Response {
+    (
+        StatusCode::OK,
+        [(
+            axum::http::header::CONTENT_TYPE,
+            axum::http::HeaderValue::from_static("application/json"),
+        )],
+        Body::from(body.to_string()),
+    )
+        .into_response()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::auth::build_allowed_key_set;
     use crate::config::{
-        AppConfig, ClientAuthConfig, FeaturesConfig, ServerConfig, UpstreamServiceConfig,
+        AppConfig, ClientAuthConfig, FeaturesConfig, PathStyle, ServerConfig, StreamSupport,
+        ToolSchemaValidation, UpstreamAuthConfig, UpstreamServiceConfig,
     };
     use crate::routing::ModelRouter;
     use crate::transport::{HttpTransport, PreparedUpstream};
@@ -58,6 +172,17 @@ mod tests {
                     proxy: None,
                     proxy_stream: None,
                     proxy_non_stream: None,
+                    stream_support: StreamSupport::Both,
+                    tool_schema_validation: ToolSchemaValidation::Off,
+                    path_style: PathStyle::Auto,
+                    anthropic_betas: Vec::new(),
+                    organization: None,
+                    project: None,
+                    trust_client_organization: false,
+                    max_request_bytes: None,
+                    auth: UpstreamAuthConfig::default(),
+                    tls: None,
+                    bind_address: None,
                 },
                 UpstreamServiceConfig {
                     name: "svc_two".into(),
@@ -72,12 +197,31 @@ mod tests {
                     proxy: None,
                     proxy_stream: None,
                     proxy_non_stream: None,
+                    stream_support: StreamSupport::Both,
+                    tool_schema_validation: ToolSchemaValidation::Off,
+                    path_style: PathStyle::Auto,
+                    anthropic_betas: Vec::new(),
+                    organization: None,
+                    project: None,
+                    trust_client_organization: false,
+                    max_request_bytes: None,
+                    auth: UpstreamAuthConfig::default(),
+                    tls: None,
+                    bind_address: None,
                 },
             ],
             client_authentication: ClientAuthConfig {
                 allowed_keys: vec!["test-key".into()],
+                admin_keys: Vec::new(),
+                key_model_maps: Vec::new(),
             },
             features: FeaturesConfig::default(),
+            virtual_models: Vec::new(),
+            routing_rules: Vec::new(),
+            cascade_models: Vec::new(),
+            admin_authentication: None,
+            fallback_chains: Vec::new(),
+            tenants: Vec::new(),
         };
         let model_router = ModelRouter::new(&config);
         let prepared_upstreams = config
@@ -0,0 +1,186 @@
+//! Long-poll mode for non-streaming requests (`features.long_poll`).
+//!
+//! A non-streaming model request sent with `x-toolify-long-poll: true` runs
+//! in a background task. When it is still running after the threshold, the
+//! client gets `202 Accepted` with an operation id instead, and polls
+//! `GET /v1/operations/{id}`: each poll waits for the result up to the poll
+//! wait, then answers with the finished response or with another `202`
+//! progress heartbeat. Clients behind gateways that cut requests off after a
+//! fixed time never hold one request open for the whole generation.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use serde_json::json;
+use tokio::task::AbortHandle;
+
+use crate::error::{format_error, into_axum_response, CanonicalError};
+use crate::json_scan::find_top_level_field_value_range;
+use crate::protocol::canonical::IngressApi;
+use crate::routing::rules;
+use crate::state::{AppState, Operation, OperationResult};
+
+/// Request header opting a non-streaming request into long polling.
+pub(crate) const LONG_POLL_HEADER: &str = "x-toolify-long-poll";
+/// Response bytes kept per operation; larger responses fail the operation.
+const MAX_RESULT_BYTES: usize = 64 * 1024 * 1024;
+
+/// Whether long polling is enabled and the client asked for it with
+/// `x-toolify-long-poll`.
+pub(crate) fn long_poll_requested(state: &AppState, headers: &HeaderMap) -> bool {
+    state.operations().is_some()
+        && headers
+            .get(LONG_POLL_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                let value = value.trim();
+                value == "1" || value.eq_ignore_ascii_case("true")
+            })
+}
+
+/// Whether a JSON request body sets `"stream": true`.
+pub(crate) fn body_requests_stream(body: &[u8]) -> bool {
+    find_top_level_field_value_range(body, b"stream")
+        .ok()
+        .flatten()
+        .is_some_and(|range| &body[range] == b"true")
+}
+
+/// Serve a non-streaming model request, handing the client an operation id
+/// once `handler` runs past the threshold.
+pub(crate) async fn serve<F>(
+    state: &AppState,
+    ingress: IngressApi,
+    base_path: &str,
+    handler: F,
+) -> Response
+where
+    F: Future<Output = Response> + Send + 'static,
+{
+    let Some(operations) = state.operations() else {
+        return handler.await;
+    };
+    let started = Instant::now();
+    let rule = rules::active_rule();
+    let mut task = tokio::spawn(rules::scope(rule, async move {
+        buffer_result(handler.await, ingress).await
+    }));
+    let mut abort = AbortOnDrop(Some(task.abort_handle()));
+    if let Ok(joined) = tokio::time::timeout(operations.threshold(), &mut task).await {
+        return task_result(joined, ingress).to_response();
+    }
+    let Some(operation) = operations.start(ingress, started) else {
+        return task_result(task.await, ingress).to_response();
+    };
+    abort.0 = None;
+    let background = Arc::clone(&operation);
+    tokio::spawn(async move {
+        background.finish(task_result(task.await, ingress));
+    });
+    tracing::debug!(operation = %operation.id(), "request continues as a long-poll operation");
+    let mut response = in_progress(&operation);
+    let location = format!("{base_path}/v1/operations/{}", operation.id());
+    if let Ok(location) = HeaderValue::from_str(&location) {
+        response
+            .headers_mut()
+            .insert(http::header::LOCATION, location);
+    }
+    response
+}
+
+/// `GET /v1/operations/{id}`
+pub async fn poll_handler(
+    State(state): State<Arc<AppState>>,
+    id: &str,
+    headers: &HeaderMap,
+) -> Response {
+    let Some(operations) = state.operations() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let operation = operations.get(id);
+    let ingress = operation
+        .as_ref()
+        .map_or(IngressApi::OpenAiChat, |operation| operation.ingress());
+    if let Err(err) = state.authenticate(ingress, headers) {
+        return into_axum_response(&err, ingress);
+    }
+    let Some(operation) = operation else {
+        return into_axum_response(
+            &CanonicalError::Upstream {
+                status: 404,
+                message: format!("No operation found with id '{id}'"),
+                retry_after: None,
+            },
+            ingress,
+        );
+    };
+    match operation.wait(operations.poll_wait()).await {
+        Some(result) => result.to_response(),
+        None => in_progress(&operation),
+    }
+}
+
+/// `202 Accepted` with the operation's id and how long it has been running.
+fn in_progress(operation: &Operation) -> Response {
+    let elapsed_ms = u64::try_from(operation.elapsed().as_millis()).unwrap_or(u64::MAX);
+    let body = json!({
+        "id": operation.id(),
+        "object": "operation",
+        "status": "in_progress",
+        "created_at": operation.created_at(),
+        "elapsed_ms": elapsed_ms,
+    });
+    (StatusCode::ACCEPTED, Json(body)).into_response()
+}
+
+async fn buffer_result(response: Response, ingress: IngressApi) -> OperationResult {
+    let (parts, body) = response.into_parts();
+    match axum::body::to_bytes(body, MAX_RESULT_BYTES).await {
+        Ok(body) => OperationResult {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        },
+        Err(err) => failed_result(&format!("Failed to read the response: {err}"), ingress),
+    }
+}
+
+fn task_result(
+    joined: Result<OperationResult, tokio::task::JoinError>,
+    ingress: IngressApi,
+) -> OperationResult {
+    joined.unwrap_or_else(|err| {
+        tracing::warn!(error = %err, "long-poll request task failed");
+        failed_result("Request task failed", ingress)
+    })
+}
+
+fn failed_result(message: &str, ingress: IngressApi) -> OperationResult {
+    let (status, body) = format_error(&CanonicalError::Internal(message.to_string()), ingress);
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        http::header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    OperationResult {
+        status,
+        headers,
+        body: body.to_string().into(),
+    }
+}
+
+/// Aborts the request task when the client goes away before the request
+/// was handed to an operation.
+struct AbortOnDrop(Option<AbortHandle>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if let Some(task) = &self.0 {
+            task.abort();
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Attach to a client stream in progress (`GET /v1/streams/{session}`).
+//!
+//! Streams are published when `features.enable_stream_broadcast` is on and
+//! the streaming request carried an `x-toolify-session` header. Subscribers
+//! receive the frames already sent to the original client, then live frames,
+//! in that client's ingress format.
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+
+use crate::error::{into_axum_response, CanonicalError};
+use crate::protocol::canonical::IngressApi;
+use crate::state::AppState;
+
+const INGRESS: IngressApi = IngressApi::OpenAiChat;
+
+/// `GET /v1/streams/{session}`
+#[must_use]
+pub fn attach_handler(
+    State(state): State<Arc<AppState>>,
+    session: &str,
+    headers: &HeaderMap,
+) -> Response {
+    if !state.config.features.enable_stream_broadcast {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Err(err) = state.authenticate(INGRESS, headers) {
+        return into_axum_response(&err, INGRESS);
+    }
+    let Some(stream) = state.stream_broadcasts().subscribe(session) else {
+        return into_axum_response(
+            &CanonicalError::Upstream {
+                status: 404,
+                message: format!("No stream in progress for session '{session}'"),
+                retry_after: None,
+            },
+            INGRESS,
+        );
+    };
+    let mut response = Response::new(Body::from_stream(stream));
+    let response_headers = response.headers_mut();
+    response_headers.insert(
+        http::header::CONTENT_TYPE,
+        HeaderValue::from_static("text/event-stream"),
+    );
+    response_headers.insert(
+        http::header::CACHE_CONTROL,
+        HeaderValue::from_static("no-cache"),
+    );
+    response
+}
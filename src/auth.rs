@@ -1,17 +1,160 @@
-use crate::config::AppConfig;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{AppConfig, KEY_HASH_PREFIX};
 use crate::error::CanonicalError;
 use crate::protocol::canonical::IngressApi;
+use crate::util::hex;
 use http::header::{HeaderName, AUTHORIZATION};
-use rustc_hash::FxHashSet;
+use parking_lot::Mutex;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, hmac};
+use rustc_hash::{FxHashMap, FxHashSet};
 
 const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
 const X_GOOG_API_KEY: HeaderName = HeaderName::from_static("x-goog-api-key");
+/// Unix seconds at which a signed admin request was issued.
+pub const ADMIN_TIMESTAMP_HEADER: HeaderName = HeaderName::from_static("x-toolify-timestamp");
+/// Single-use value of a signed admin request.
+pub const ADMIN_NONCE_HEADER: HeaderName = HeaderName::from_static("x-toolify-nonce");
+/// Hex HMAC-SHA256 of a signed admin request, see [`sign_admin_request`].
+pub const ADMIN_SIGNATURE_HEADER: HeaderName = HeaderName::from_static("x-toolify-signature");
+/// Hex SHA-256 of a signed admin request's body, see [`admin_body_digest`].
+pub const ADMIN_CONTENT_SHA256_HEADER: HeaderName = HeaderName::from_static("x-content-sha256");
+const MAX_ADMIN_NONCE_BYTES: usize = 128;
+
+type KeyDigest = [u8; 32];
 
 /// Compact key index used in hot-path authentication.
 pub enum AllowedClientKeys {
     Empty,
-    Single { raw: Box<str>, bearer: Box<str> },
-    Multiple(FxHashSet<String>),
+    /// A single plaintext key, compared against the header value directly.
+    Single {
+        raw: Box<str>,
+        bearer: Box<str>,
+    },
+    Multiple(HashedKeys),
+}
+
+/// Allowed keys held as SHA-256 digests under the deployment's one salt, so
+/// a presented key is hashed once and looked up. Plaintext config keys are
+/// digested with the same salt when the index is built.
+pub struct HashedKeys {
+    salt: Box<[u8]>,
+    digests: FxHashSet<KeyDigest>,
+}
+
+impl HashedKeys {
+    /// Whether `key` is one of the allowed keys. Only salted digests are
+    /// probed, so lookup timing says nothing about the keys themselves.
+    #[must_use]
+    pub fn contains(&self, key: &str) -> bool {
+        self.digests
+            .contains(&salted_digest(&self.salt, key.as_bytes()))
+    }
+
+    /// Number of distinct allowed keys.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.digests.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Hash `key` with a fresh random salt into the `sha256:<salt>:<digest>`
+/// form accepted in `allowed_keys` and `admin_keys`.
+#[must_use]
+pub fn hash_api_key(key: &str) -> String {
+    hash_api_key_with_salt(key, &hex(&random_salt()))
+}
+
+/// Hash `key` with `salt`, the one every hashed `allowed_keys` entry of a
+/// deployment shares.
+#[must_use]
+pub fn hash_api_key_with_salt(key: &str, salt: &str) -> String {
+    let digest = salted_digest(salt.as_bytes(), key.as_bytes());
+    format!("{KEY_HASH_PREFIX}{salt}:{}", hex(&digest))
+}
+
+/// Whether the configured entries `a` and `b` may hold the same key:
+/// equal plaintext, a plaintext key matching the other's hash, or hashes
+/// with the same salt and digest.
+#[must_use]
+pub(crate) fn key_entries_overlap(a: &str, b: &str) -> bool {
+    match (parse_key_hash(a), parse_key_hash(b)) {
+        (Some(a), Some(b)) => a == b,
+        (Some(_), None) => key_entry_matches(a, b),
+        (None, Some(_)) => key_entry_matches(b, a),
+        (None, None) => a == b,
+    }
+}
+
+/// 16 bytes from the system CSPRNG.
+fn random_salt() -> [u8; 16] {
+    let mut salt = [0_u8; 16];
+    // The salt only needs to differ between deployments; should the system
+    // source fail, a zero salt still yields working digests.
+    let _ = SystemRandom::new().fill(&mut salt);
+    salt
+}
+
+/// Short unsalted SHA-256 prefix that tells client keys apart in analytics
+/// without storing them.
+#[must_use]
+pub fn key_fingerprint(key: &str) -> String {
+    hex(&salted_digest(b"", key.as_bytes())[..6])
+}
+
+/// Whether `presented` matches the configured key `entry`, either a
+/// `sha256:` hash or plaintext.
+pub(crate) fn key_entry_matches(entry: &str, presented: &str) -> bool {
+    match parse_key_hash(entry) {
+        Some((salt, digest)) => {
+            constant_time_eq(&digest, &salted_digest(salt, presented.as_bytes()))
+        }
+        None => constant_time_eq(entry.as_bytes(), presented.as_bytes()),
+    }
+}
+
+/// Salt and digest of a `sha256:<salt>:<hex digest>` entry.
+fn parse_key_hash(entry: &str) -> Option<(&[u8], KeyDigest)> {
+    let (salt, digest_hex) = entry.strip_prefix(KEY_HASH_PREFIX)?.rsplit_once(':')?;
+    Some((salt.as_bytes(), decode_digest(digest_hex)?))
+}
+
+/// A SHA-256 digest written as 64 hex digits.
+fn decode_digest(digest_hex: &str) -> Option<KeyDigest> {
+    let mut digest = [0_u8; 32];
+    if digest_hex.len() != 64 {
+        return None;
+    }
+    for (byte, pair) in digest.iter_mut().zip(digest_hex.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+fn salted_digest(salt: &[u8], key: &[u8]) -> KeyDigest {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(salt);
+    context.update(key);
+    let mut out = [0_u8; 32];
+    out.copy_from_slice(context.finish().as_ref());
+    out
+}
+
+/// Compare without exiting early on the first differing byte; only a length
+/// mismatch returns sooner.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0_u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
 }
 
 /// Extract the API key from request headers, based on the ingress API convention.
@@ -103,9 +246,9 @@ pub fn authenticate(
         AllowedClientKeys::Single { raw, bearer } => {
             authenticate_single_key(ingress, headers, raw.as_ref(), bearer.as_ref())
         }
-        AllowedClientKeys::Multiple(allowed_set) => {
+        AllowedClientKeys::Multiple(allowed_keys) => {
             let client_key = extract_api_key(ingress, headers)?;
-            if allowed_set.contains(client_key) {
+            if allowed_keys.contains(client_key) {
                 Ok(())
             } else {
                 Err(CanonicalError::Auth("Invalid API key".to_string()))
@@ -125,25 +268,25 @@ fn authenticate_single_key(
     let bearer_key_bytes = bearer_key.as_bytes();
     match ingress {
         IngressApi::Anthropic => match headers.get(X_API_KEY) {
-            Some(value) if value.as_bytes() == raw_key_bytes => Ok(()),
+            Some(value) if constant_time_eq(value.as_bytes(), raw_key_bytes) => Ok(()),
             Some(_) => Err(CanonicalError::Auth("Invalid API key".to_string())),
             None => Err(CanonicalError::Auth("Missing API key".to_string())),
         },
         IngressApi::OpenAiChat | IngressApi::OpenAiResponses => match headers.get(AUTHORIZATION) {
-            Some(value) if value.as_bytes() == bearer_key_bytes => Ok(()),
+            Some(value) if constant_time_eq(value.as_bytes(), bearer_key_bytes) => Ok(()),
             Some(_) => Err(CanonicalError::Auth("Invalid API key".to_string())),
             None => Err(CanonicalError::Auth("Missing API key".to_string())),
         },
         IngressApi::Gemini => {
             if let Some(value) = headers.get(X_GOOG_API_KEY) {
-                return if value.as_bytes() == raw_key_bytes {
+                return if constant_time_eq(value.as_bytes(), raw_key_bytes) {
                     Ok(())
                 } else {
                     Err(CanonicalError::Auth("Invalid API key".to_string()))
                 };
             }
             match headers.get(AUTHORIZATION) {
-                Some(value) if value.as_bytes() == bearer_key_bytes => Ok(()),
+                Some(value) if constant_time_eq(value.as_bytes(), bearer_key_bytes) => Ok(()),
                 Some(_) => Err(CanonicalError::Auth("Invalid API key".to_string())),
                 None => Err(CanonicalError::Auth("Missing API key".to_string())),
             }
@@ -151,27 +294,241 @@ fn authenticate_single_key(
     }
 }
 
-/// Build a hash-set index for allowed client keys.
+/// Authenticate an admin request (`Authorization: Bearer <key>`) against
+/// the configured admin keys, plaintext or `sha256:` hashes.
+///
+/// # Errors
+///
+/// Returns `CanonicalError::Auth` when the admin key is missing or invalid.
+pub fn authenticate_admin(
+    headers: &http::HeaderMap,
+    admin_keys: &[String],
+) -> Result<(), CanonicalError> {
+    let admin_key = extract_api_key(IngressApi::OpenAiChat, headers)?;
+    let matched = admin_keys.iter().fold(false, |matched, key| {
+        matched | key_entry_matches(key, admin_key)
+    });
+    if matched {
+        Ok(())
+    } else {
+        Err(CanonicalError::Auth("Invalid admin key".to_string()))
+    }
+}
+
+/// Admin API credentials: `admin_authentication` when configured, otherwise
+/// `client_authentication.admin_keys`.
+pub struct AdminAuthenticator {
+    tokens: Vec<String>,
+    replay: Option<ReplayGuard>,
+}
+
+/// Single-use nonces of signed admin requests.
+struct ReplayGuard {
+    max_skew_secs: u64,
+    capacity: usize,
+    /// Nonce -> unix second after which its timestamp is out of the window.
+    nonces: Arc<Mutex<FxHashMap<Box<str>, u64>>>,
+}
+
+impl AdminAuthenticator {
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Self {
+        match &config.admin_authentication {
+            Some(admin) => Self {
+                tokens: admin.tokens.clone(),
+                replay: admin.replay_protection.map(|replay| ReplayGuard {
+                    max_skew_secs: replay.max_skew_secs,
+                    capacity: replay.nonce_cache_entries,
+                    nonces: Arc::default(),
+                }),
+            },
+            None => Self {
+                tokens: config.client_authentication.admin_keys.clone(),
+                replay: None,
+            },
+        }
+    }
+
+    /// Whether any admin credential is configured; the admin API answers 404
+    /// otherwise.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// Keep the nonces `previous` has seen, so swapping the config does not
+    /// let recent signed requests be replayed.
+    pub(crate) fn inherit_nonces(&mut self, previous: &Self) {
+        if let (Some(replay), Some(previous)) = (&mut self.replay, &previous.replay) {
+            replay.nonces = Arc::clone(&previous.nonces);
+        }
+    }
+
+    /// Authenticate an admin request: a bearer token, or with replay
+    /// protection a signed request whose nonce was not seen before and whose
+    /// `x-content-sha256` matches `body`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CanonicalError::Auth` when the credentials are missing,
+    /// invalid, stale, or replayed, or the body is not the signed one.
+    pub fn authenticate(
+        &self,
+        method: &http::Method,
+        uri: &http::Uri,
+        headers: &http::HeaderMap,
+        body: &[u8],
+    ) -> Result<(), CanonicalError> {
+        let Some(replay) = &self.replay else {
+            return authenticate_admin(headers, &self.tokens);
+        };
+        let header = |name: &HeaderName| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| CanonicalError::Auth(format!("Missing {name} header")))
+        };
+        let timestamp = header(&ADMIN_TIMESTAMP_HEADER)?;
+        let nonce = header(&ADMIN_NONCE_HEADER)?;
+        let body_sha256 = header(&ADMIN_CONTENT_SHA256_HEADER)?;
+        let signature = decode_digest(header(&ADMIN_SIGNATURE_HEADER)?)
+            .ok_or_else(|| CanonicalError::Auth("Invalid admin signature".to_string()))?;
+        let issued: u64 = timestamp.parse().map_err(|_| {
+            CanonicalError::Auth(format!("Invalid {ADMIN_TIMESTAMP_HEADER} header"))
+        })?;
+        if nonce.is_empty() || nonce.len() > MAX_ADMIN_NONCE_BYTES {
+            return Err(CanonicalError::Auth(format!(
+                "{ADMIN_NONCE_HEADER} must be 1 to {MAX_ADMIN_NONCE_BYTES} bytes"
+            )));
+        }
+        if !body_sha256.eq_ignore_ascii_case(&admin_body_digest(body)) {
+            return Err(CanonicalError::Auth(format!(
+                "Admin request body does not match {ADMIN_CONTENT_SHA256_HEADER}"
+            )));
+        }
+        let now = unix_now_secs();
+        if now.abs_diff(issued) > replay.max_skew_secs {
+            return Err(CanonicalError::Auth(
+                "Admin request timestamp is outside the allowed clock skew".to_string(),
+            ));
+        }
+        let path = uri
+            .path_and_query()
+            .map_or_else(|| uri.path(), http::uri::PathAndQuery::as_str);
+        let matched = self.tokens.iter().fold(false, |matched, token| {
+            let expected = admin_signature(
+                token,
+                [timestamp, nonce, method.as_str(), path, body_sha256],
+            );
+            matched | constant_time_eq(&expected, &signature)
+        });
+        if !matched {
+            return Err(CanonicalError::Auth("Invalid admin signature".to_string()));
+        }
+        replay.claim(nonce, issued.saturating_add(replay.max_skew_secs), now)
+    }
+}
+
+impl ReplayGuard {
+    fn claim(&self, nonce: &str, expires: u64, now: u64) -> Result<(), CanonicalError> {
+        let mut nonces = self.nonces.lock();
+        if nonces.get(nonce).is_some_and(|&seen| seen >= now) {
+            return Err(CanonicalError::Auth(
+                "Admin nonce was already used".to_string(),
+            ));
+        }
+        if nonces.len() >= self.capacity {
+            nonces.retain(|_, &mut seen| seen >= now);
+            if nonces.len() >= self.capacity {
+                return Err(CanonicalError::Auth(
+                    "Too many recent admin requests; retry later".to_string(),
+                ));
+            }
+        }
+        nonces.insert(Box::from(nonce), expires);
+        Ok(())
+    }
+}
+
+/// Hex `x-content-sha256` value of a signed admin request with `body`.
 #[must_use]
-pub fn build_allowed_key_set(config: &AppConfig) -> AllowedClientKeys {
-    let mut allowed_set: FxHashSet<String> = config
-        .client_authentication
-        .allowed_keys
-        .iter()
-        .cloned()
-        .collect();
+pub fn admin_body_digest(body: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, body).as_ref())
+}
+
+/// Hex `x-toolify-signature` value of a signed admin request.
+///
+/// The signed string is `{timestamp}\n{nonce}\n{METHOD}\n{path}\n{body_sha256}`,
+/// where `body_sha256` is the [`admin_body_digest`] sent in
+/// `x-content-sha256`.
+#[must_use]
+pub fn sign_admin_request(
+    token: &str,
+    timestamp: &str,
+    nonce: &str,
+    method: &str,
+    path: &str,
+    body_sha256: &str,
+) -> String {
+    hex(&admin_signature(
+        token,
+        [timestamp, nonce, method, path, body_sha256],
+    ))
+}
+
+fn admin_signature(token: &str, parts: [&str; 5]) -> KeyDigest {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, token.as_bytes());
+    let mut context = hmac::Context::with_key(&key);
+    for (index, part) in parts.into_iter().enumerate() {
+        if index > 0 {
+            context.update(b"\n");
+        }
+        context.update(part.as_bytes());
+    }
+    let mut out = [0_u8; 32];
+    out.copy_from_slice(context.sign().as_ref());
+    out
+}
+
+fn unix_now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
 
-    match allowed_set.len() {
-        0 => AllowedClientKeys::Empty,
-        1 => match allowed_set.drain().next() {
-            Some(single_key) => AllowedClientKeys::Single {
+/// Build the index of allowed client keys.
+///
+/// A lone plaintext key is compared against the header as is; otherwise
+/// every key, plaintext or `sha256:` hash, is indexed by its digest under the
+/// salt the hashed entries share (validation rejects mixed salts), or a
+/// random one when all keys are plaintext. Entries hashed with another salt
+/// match nothing.
+#[must_use]
+pub fn build_allowed_key_set(config: &AppConfig) -> AllowedClientKeys {
+    let allowed_keys = &config.client_authentication.allowed_keys;
+    let distinct: FxHashSet<&str> = allowed_keys.iter().map(String::as_str).collect();
+    match distinct.into_iter().collect::<Vec<_>>()[..] {
+        [] => return AllowedClientKeys::Empty,
+        [single_key] if parse_key_hash(single_key).is_none() => {
+            return AllowedClientKeys::Single {
                 bearer: format!("Bearer {single_key}").into_boxed_str(),
-                raw: single_key.into_boxed_str(),
-            },
-            None => AllowedClientKeys::Empty,
-        },
-        _ => AllowedClientKeys::Multiple(allowed_set),
+                raw: Box::from(single_key),
+            };
+        }
+        _ => {}
     }
+    let salt: Box<[u8]> = allowed_keys
+        .iter()
+        .find_map(|key| parse_key_hash(key))
+        .map_or_else(|| Box::from(random_salt()), |(salt, _)| Box::from(salt));
+    let digests = allowed_keys
+        .iter()
+        .filter_map(|key| match parse_key_hash(key) {
+            Some((own, digest)) => (own == &*salt).then_some(digest),
+            None => Some(salted_digest(&salt, key.as_bytes())),
+        })
+        .collect();
+    AllowedClientKeys::Multiple(HashedKeys { salt, digests })
 }
 
 #[cfg(test)]
@@ -183,8 +540,18 @@ mod tests {
         AppConfig {
             server: ServerConfig::default(),
             upstream_services: vec![],
-            client_authentication: ClientAuthConfig { allowed_keys },
+            client_authentication: ClientAuthConfig {
+                allowed_keys,
+                admin_keys: Vec::new(),
+                key_model_maps: Vec::new(),
+            },
             features: FeaturesConfig::default(),
+            virtual_models: Vec::new(),
+            routing_rules: Vec::new(),
+            cascade_models: Vec::new(),
+            admin_authentication: None,
+            fallback_chains: Vec::new(),
+            tenants: Vec::new(),
         }
     }
 
@@ -285,10 +652,11 @@ mod tests {
         let config = make_config(vec!["a".to_string(), "b".to_string(), "a".to_string()]);
         let index = build_allowed_key_set(&config);
         match index {
-            AllowedClientKeys::Multiple(set) => {
-                assert!(set.contains("a"));
-                assert!(set.contains("b"));
-                assert_eq!(set.len(), 2);
+            AllowedClientKeys::Multiple(keys) => {
+                assert!(keys.contains("a"));
+                assert!(keys.contains("b"));
+                assert!(!keys.contains("c"));
+                assert_eq!(keys.len(), 2);
             }
             _ => panic!("expected multiple-key index"),
         }
@@ -307,6 +675,123 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hashed_keys_authenticate_alongside_plaintext() {
+        let hashed = hash_api_key("hashed-key");
+        assert!(hashed.starts_with(KEY_HASH_PREFIX));
+        assert_ne!(hash_api_key("hashed-key"), hashed);
+
+        let index = build_allowed_key_set(&make_config(vec![hashed.clone()]));
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-api-key", "hashed-key".parse().unwrap());
+        assert!(authenticate(IngressApi::Anthropic, &headers, &index).is_ok());
+        headers.insert("x-api-key", hashed.parse().unwrap());
+        assert!(authenticate(IngressApi::Anthropic, &headers, &index).is_err());
+
+        let index = build_allowed_key_set(&make_config(vec![hashed, "legacy".to_string()]));
+        headers.insert("x-api-key", "legacy".parse().unwrap());
+        assert!(authenticate(IngressApi::Anthropic, &headers, &index).is_ok());
+
+        // Keys hashed with the deployment salt share one digest set.
+        let index = build_allowed_key_set(&make_config(vec![
+            hash_api_key_with_salt("a", "deploy"),
+            hash_api_key_with_salt("b", "deploy"),
+            "c".to_string(),
+        ]));
+        match &index {
+            AllowedClientKeys::Multiple(keys) => assert_eq!(keys.len(), 3),
+            _ => panic!("expected hashed index"),
+        }
+        for key in ["a", "b", "c"] {
+            headers.insert("x-api-key", key.parse().unwrap());
+            assert!(authenticate(IngressApi::Anthropic, &headers, &index).is_ok());
+        }
+        headers.insert("x-api-key", "d".parse().unwrap());
+        assert!(authenticate(IngressApi::Anthropic, &headers, &index).is_err());
+
+        let mut admin_headers = http::HeaderMap::new();
+        admin_headers.insert("authorization", "Bearer admin".parse().unwrap());
+        assert!(authenticate_admin(&admin_headers, &[hash_api_key("admin")]).is_ok());
+        assert!(authenticate_admin(&admin_headers, &[hash_api_key("other")]).is_err());
+    }
+
+    #[test]
+    fn test_signed_admin_requests_are_single_use_and_time_bound() {
+        let mut config = make_config(vec!["client".to_string()]);
+        config.admin_authentication = Some(crate::config::AdminAuthConfig {
+            tokens: vec!["admin-token".to_string()],
+            replay_protection: Some(crate::config::AdminReplayProtection {
+                max_skew_secs: 60,
+                nonce_cache_entries: 2,
+            }),
+        });
+        let admin_auth = AdminAuthenticator::new(&config);
+        let method = http::Method::GET;
+        let uri: http::Uri = "/admin/metrics?x=1".parse().unwrap();
+        let signed_body = |token: &str, timestamp: u64, nonce: &str, path: &str, body: &[u8]| {
+            let timestamp = timestamp.to_string();
+            let body_sha256 = admin_body_digest(body);
+            let mut headers = http::HeaderMap::new();
+            headers.insert(
+                ADMIN_SIGNATURE_HEADER,
+                sign_admin_request(token, &timestamp, nonce, "GET", path, &body_sha256)
+                    .parse()
+                    .unwrap(),
+            );
+            headers.insert(ADMIN_TIMESTAMP_HEADER, timestamp.parse().unwrap());
+            headers.insert(ADMIN_NONCE_HEADER, nonce.parse().unwrap());
+            headers.insert(ADMIN_CONTENT_SHA256_HEADER, body_sha256.parse().unwrap());
+            headers
+        };
+        let signed = |token: &str, timestamp: u64, nonce: &str, path: &str| {
+            signed_body(token, timestamp, nonce, path, b"")
+        };
+        let now = unix_now_secs();
+        let check =
+            |headers: &http::HeaderMap| admin_auth.authenticate(&method, &uri, headers, b"");
+
+        let first = signed("admin-token", now, "n1", "/admin/metrics?x=1");
+        assert!(check(&first).is_ok());
+        assert!(check(&first).is_err(), "replayed nonce");
+        assert!(check(&signed("admin-token", now - 30, "n2", "/admin/metrics?x=1")).is_ok());
+        assert!(check(&signed(
+            "admin-token",
+            now - 120,
+            "n3",
+            "/admin/metrics?x=1"
+        ))
+        .is_err());
+        assert!(check(&signed("admin-token", now, "n4", "/admin/cooldowns")).is_err());
+        assert!(check(&signed("client", now, "n5", "/admin/metrics?x=1")).is_err());
+        // The body is signed: a replaced body fails, with or without a
+        // matching digest header.
+        let mut body_signed = signed_body("admin-token", now, "n7", "/admin/metrics?x=1", b"a");
+        assert!(admin_auth
+            .authenticate(&method, &uri, &body_signed, b"b")
+            .is_err());
+        body_signed.insert(
+            ADMIN_CONTENT_SHA256_HEADER,
+            admin_body_digest(b"b").parse().unwrap(),
+        );
+        assert!(admin_auth
+            .authenticate(&method, &uri, &body_signed, b"b")
+            .is_err());
+        // Both cached nonces are still live, so a third is refused.
+        assert!(check(&signed("admin-token", now, "n6", "/admin/metrics?x=1")).is_err());
+
+        let mut bearer = http::HeaderMap::new();
+        bearer.insert("authorization", "Bearer admin-token".parse().unwrap());
+        assert!(check(&bearer).is_err());
+        config
+            .admin_authentication
+            .as_mut()
+            .unwrap()
+            .replay_protection = None;
+        let mut swapped = AdminAuthenticator::new(&config);
+        swapped.inherit_nonces(&admin_auth);
+        assert!(swapped.authenticate(&method, &uri, &bearer, b"").is_ok());
+    }
+
     #[test]
     fn test_build_allowed_key_set_empty() {
         let config = make_config(vec![]);
@@ -0,0 +1,470 @@
+//! Disk-backed store for emulated `OpenAI` batches (`/v1/batches`).
+//!
+//! Each batch lives in its own directory under the storage root:
+//! `batch.json` (the batch object), `input.jsonl`, and the `output.jsonl` /
+//! `errors.jsonl` result files appended as requests finish. Batches that were
+//! still running when the process stopped are marked failed (or cancelled)
+//! on the next start; completed results stay retrievable.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::util::unix_now_secs;
+
+const BATCH_FILE: &str = "batch.json";
+const INPUT_FILE: &str = "input.jsonl";
+const OUTPUT_FILE: &str = "output.jsonl";
+const ERRORS_FILE: &str = "errors.jsonl";
+
+/// Only the `OpenAI` completion window is accepted.
+pub const COMPLETION_WINDOW: &str = "24h";
+const COMPLETION_WINDOW_SECS: u64 = 24 * 60 * 60;
+/// Upper bound on requests per batch, matching `OpenAI`.
+pub const MAX_BATCH_REQUESTS: usize = 50_000;
+
+/// Endpoints a batch may target.
+pub const SUPPORTED_ENDPOINTS: &[&str] =
+    &["/v1/chat/completions", "/v1/responses", "/v1/completions"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Validating,
+    Failed,
+    InProgress,
+    Finalizing,
+    Completed,
+    Expired,
+    Cancelling,
+    Cancelled,
+}
+
+impl BatchStatus {
+    #[must_use]
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            BatchStatus::Failed
+                | BatchStatus::Completed
+                | BatchStatus::Expired
+                | BatchStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchRequestCounts {
+    pub total: u64,
+    pub completed: u64,
+    pub failed: u64,
+}
+
+/// The `OpenAI` batch object, plus the URLs results are served from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRecord {
+    pub id: String,
+    pub object: String,
+    pub endpoint: String,
+    pub errors: Option<serde_json::Value>,
+    pub input_file_id: Option<String>,
+    pub completion_window: String,
+    pub status: BatchStatus,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    pub created_at: u64,
+    pub in_progress_at: Option<u64>,
+    pub expires_at: u64,
+    pub finalizing_at: Option<u64>,
+    pub completed_at: Option<u64>,
+    pub failed_at: Option<u64>,
+    pub expired_at: Option<u64>,
+    pub cancelling_at: Option<u64>,
+    pub cancelled_at: Option<u64>,
+    pub request_counts: BatchRequestCounts,
+    pub metadata: Option<serde_json::Value>,
+    /// Results are served here instead of through `/v1/files`.
+    pub output_url: String,
+    pub error_url: String,
+}
+
+/// One validated line of a batch input file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchInputLine {
+    pub custom_id: String,
+    pub method: String,
+    pub url: String,
+    pub body: serde_json::Value,
+}
+
+/// A batch tracked by the store.
+pub struct BatchHandle {
+    record: Mutex<BatchRecord>,
+    cancel_requested: AtomicBool,
+}
+
+impl BatchHandle {
+    #[must_use]
+    pub fn snapshot(&self) -> BatchRecord {
+        self.record.lock().clone()
+    }
+
+    #[must_use]
+    pub fn id(&self) -> String {
+        self.record.lock().id.clone()
+    }
+
+    #[must_use]
+    pub fn cancel_requested(&self) -> bool {
+        self.cancel_requested.load(Ordering::Relaxed)
+    }
+
+    /// Apply `update` to the batch object and return the updated copy.
+    pub fn update(&self, update: impl FnOnce(&mut BatchRecord)) -> BatchRecord {
+        let mut record = self.record.lock();
+        update(&mut record);
+        record.clone()
+    }
+}
+
+/// Parse and validate a batch input file.
+///
+/// # Errors
+///
+/// Returns a message naming the first offending line (1-based).
+pub fn parse_batch_input(input: &[u8], endpoint: &str) -> Result<Vec<BatchInputLine>, String> {
+    let mut lines = Vec::new();
+    let mut custom_ids = rustc_hash::FxHashSet::default();
+    for (index, raw) in input.split(|byte| *byte == b'\n').enumerate() {
+        let line_no = index + 1;
+        if raw.trim_ascii().is_empty() {
+            continue;
+        }
+        let line: BatchInputLine = serde_json::from_slice(raw)
+            .map_err(|e| format!("line {line_no}: invalid batch request: {e}"))?;
+        if !line.method.eq_ignore_ascii_case("POST") {
+            return Err(format!("line {line_no}: method must be POST"));
+        }
+        if line.url != endpoint {
+            return Err(format!(
+                "line {line_no}: url '{}' does not match batch endpoint '{endpoint}'",
+                line.url
+            ));
+        }
+        if !line.body.is_object() {
+            return Err(format!("line {line_no}: body must be a JSON object"));
+        }
+        if line.body.get("stream").and_then(serde_json::Value::as_bool) == Some(true) {
+            return Err(format!(
+                "line {line_no}: streaming is not supported in batches"
+            ));
+        }
+        if !custom_ids.insert(line.custom_id.clone()) {
+            return Err(format!(
+                "line {line_no}: duplicate custom_id '{}'",
+                line.custom_id
+            ));
+        }
+        lines.push(line);
+        if lines.len() > MAX_BATCH_REQUESTS {
+            return Err(format!(
+                "batch exceeds the maximum of {MAX_BATCH_REQUESTS} requests"
+            ));
+        }
+    }
+    if lines.is_empty() {
+        return Err("batch input file has no requests".to_string());
+    }
+    Ok(lines)
+}
+
+/// Which result file to read or append to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchResultKind {
+    Output,
+    Errors,
+}
+
+impl BatchResultKind {
+    fn file_name(self) -> &'static str {
+        match self {
+            BatchResultKind::Output => OUTPUT_FILE,
+            BatchResultKind::Errors => ERRORS_FILE,
+        }
+    }
+}
+
+/// Batch registry persisted under a storage directory.
+pub struct BatchStore {
+    dir: PathBuf,
+    batches: Mutex<FxHashMap<String, Arc<BatchHandle>>>,
+}
+
+impl BatchStore {
+    /// Open the store, loading batches persisted by earlier runs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error when the storage directory cannot be created or read.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut batches = FxHashMap::default();
+        for entry in fs::read_dir(&dir)? {
+            let batch_dir = entry?.path();
+            let Ok(raw) = fs::read(batch_dir.join(BATCH_FILE)) else {
+                continue;
+            };
+            let Ok(mut record) = serde_json::from_slice::<BatchRecord>(&raw) else {
+                tracing::warn!(path = %batch_dir.display(), "skipping unreadable batch record");
+                continue;
+            };
+            if !record.status.is_terminal() {
+                mark_interrupted(&mut record, unix_now_secs());
+                write_record(&batch_dir, &record)?;
+            }
+            batches.insert(
+                record.id.clone(),
+                Arc::new(BatchHandle {
+                    record: Mutex::new(record),
+                    cancel_requested: AtomicBool::new(false),
+                }),
+            );
+        }
+        Ok(Self {
+            dir,
+            batches: Mutex::new(batches),
+        })
+    }
+
+    /// Persist a new batch and its input file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error when the batch directory or files cannot be written.
+    pub fn create(
+        &self,
+        endpoint: &str,
+        metadata: Option<serde_json::Value>,
+        input: &[u8],
+        request_count: usize,
+    ) -> io::Result<Arc<BatchHandle>> {
+        let id = format!("batch_{:016x}{:08x}", fastrand::u64(..), fastrand::u32(..));
+        let batch_dir = self.dir.join(&id);
+        fs::create_dir_all(&batch_dir)?;
+        let mut input_file = File::create(batch_dir.join(INPUT_FILE))?;
+        input_file.write_all(input)?;
+        input_file.sync_all()?;
+
+        let created_at = unix_now_secs();
+        let record = BatchRecord {
+            output_url: format!("/v1/batches/{id}/output"),
+            error_url: format!("/v1/batches/{id}/errors"),
+            id: id.clone(),
+            object: "batch".to_string(),
+            endpoint: endpoint.to_string(),
+            errors: None,
+            input_file_id: None,
+            completion_window: COMPLETION_WINDOW.to_string(),
+            status: BatchStatus::Validating,
+            output_file_id: None,
+            error_file_id: None,
+            created_at,
+            in_progress_at: None,
+            expires_at: created_at + COMPLETION_WINDOW_SECS,
+            finalizing_at: None,
+            completed_at: None,
+            failed_at: None,
+            expired_at: None,
+            cancelling_at: None,
+            cancelled_at: None,
+            request_counts: BatchRequestCounts {
+                total: request_count as u64,
+                ..BatchRequestCounts::default()
+            },
+            metadata,
+        };
+        write_record(&batch_dir, &record)?;
+        let handle = Arc::new(BatchHandle {
+            record: Mutex::new(record),
+            cancel_requested: AtomicBool::new(false),
+        });
+        self.batches.lock().insert(id, Arc::clone(&handle));
+        Ok(handle)
+    }
+
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<Arc<BatchHandle>> {
+        self.batches.lock().get(id).cloned()
+    }
+
+    /// All batches, newest first.
+    #[must_use]
+    pub fn list(&self) -> Vec<BatchRecord> {
+        let mut records: Vec<BatchRecord> = self
+            .batches
+            .lock()
+            .values()
+            .map(|handle| handle.snapshot())
+            .collect();
+        records.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        records
+    }
+
+    /// Request cancellation; returns the updated batch object.
+    pub fn cancel(&self, handle: &BatchHandle) -> BatchRecord {
+        handle.cancel_requested.store(true, Ordering::Relaxed);
+        handle.update(|record| {
+            if !record.status.is_terminal() && record.status != BatchStatus::Cancelling {
+                record.status = BatchStatus::Cancelling;
+                record.cancelling_at = Some(unix_now_secs());
+            }
+        })
+    }
+
+    /// Write the current batch object to disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error when the record cannot be written.
+    pub fn persist(&self, record: &BatchRecord) -> io::Result<()> {
+        write_record(&self.dir.join(&record.id), record)
+    }
+
+    /// Read the validated input lines of a batch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error when the input file cannot be read or no longer validates.
+    pub fn read_input(&self, record: &BatchRecord) -> io::Result<Vec<BatchInputLine>> {
+        let input = fs::read(self.dir.join(&record.id).join(INPUT_FILE))?;
+        parse_batch_input(&input, &record.endpoint)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+    }
+
+    /// Append JSON lines to a result file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error when the result file cannot be appended to.
+    pub fn append_results(&self, id: &str, kind: BatchResultKind, lines: &[u8]) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(id).join(kind.file_name()))?;
+        file.write_all(lines)
+    }
+
+    /// Read a result file; missing files read as empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error when the result file exists but cannot be read.
+    pub fn read_results(&self, id: &str, kind: BatchResultKind) -> io::Result<Vec<u8>> {
+        match fs::read(self.dir.join(id).join(kind.file_name())) {
+            Ok(bytes) => Ok(bytes),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn mark_interrupted(record: &mut BatchRecord, now: u64) {
+    if record.status == BatchStatus::Cancelling {
+        record.status = BatchStatus::Cancelled;
+        record.cancelled_at = Some(now);
+        return;
+    }
+    record.status = BatchStatus::Failed;
+    record.failed_at = Some(now);
+    record.errors = Some(serde_json::json!({
+        "object": "list",
+        "data": [{
+            "code": "batch_interrupted",
+            "message": "The server restarted before this batch finished; completed results are kept.",
+            "param": null,
+            "line": null,
+        }]
+    }));
+}
+
+fn write_record(batch_dir: &Path, record: &BatchRecord) -> io::Result<()> {
+    let encoded = serde_json::to_vec_pretty(record).map_err(io::Error::other)?;
+    let tmp_path = batch_dir.join("batch.json.tmp");
+    let mut tmp = File::create(&tmp_path)?;
+    tmp.write_all(&encoded)?;
+    tmp.sync_all()?;
+    fs::rename(tmp_path, batch_dir.join(BATCH_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "toolify-batches-{name}-{}-{}",
+            std::process::id(),
+            fastrand::u64(..)
+        ))
+    }
+
+    const INPUT: &[u8] = b"{\"custom_id\":\"a\",\"method\":\"POST\",\"url\":\"/v1/chat/completions\",\"body\":{\"model\":\"m\",\"messages\":[]}}\n\n{\"custom_id\":\"b\",\"method\":\"POST\",\"url\":\"/v1/chat/completions\",\"body\":{\"model\":\"m\",\"messages\":[]}}\n";
+
+    #[test]
+    fn test_parse_batch_input_validates_lines() {
+        let lines = parse_batch_input(INPUT, "/v1/chat/completions").unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].custom_id, "b");
+
+        let err = parse_batch_input(INPUT, "/v1/responses").unwrap_err();
+        assert!(err.starts_with("line 1:"), "{err}");
+
+        let duplicate = [INPUT, INPUT].concat();
+        let err = parse_batch_input(&duplicate, "/v1/chat/completions").unwrap_err();
+        assert!(err.contains("duplicate custom_id"), "{err}");
+
+        let streaming = b"{\"custom_id\":\"a\",\"method\":\"POST\",\"url\":\"/v1/chat/completions\",\"body\":{\"stream\":true}}";
+        assert!(parse_batch_input(streaming, "/v1/chat/completions").is_err());
+    }
+
+    #[test]
+    fn test_store_persists_and_marks_interrupted_batches_on_reopen() {
+        let dir = temp_store_dir("reopen");
+        let store = BatchStore::open(&dir).unwrap();
+        let handle = store
+            .create("/v1/chat/completions", None, INPUT, 2)
+            .unwrap();
+        let id = handle.id();
+        let record = handle.update(|record| {
+            record.status = BatchStatus::InProgress;
+            record.request_counts.completed = 1;
+        });
+        store.persist(&record).unwrap();
+        store
+            .append_results(&id, BatchResultKind::Output, b"{\"custom_id\":\"a\"}\n")
+            .unwrap();
+        drop(store);
+
+        let reopened = BatchStore::open(&dir).unwrap();
+        let record = reopened.get(&id).unwrap().snapshot();
+        assert_eq!(record.status, BatchStatus::Failed);
+        assert_eq!(record.request_counts.completed, 1);
+        assert_eq!(
+            reopened.read_results(&id, BatchResultKind::Output).unwrap(),
+            b"{\"custom_id\":\"a\"}\n"
+        );
+        assert!(reopened
+            .read_results(&id, BatchResultKind::Errors)
+            .unwrap()
+            .is_empty());
+        assert_eq!(reopened.read_input(&record).unwrap().len(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
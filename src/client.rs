@@ -0,0 +1,506 @@
+//! Typed async client for a running toolify-rs proxy.
+//!
+//! Requests and responses use the same wire types as the proxy itself
+//! ([`crate::protocol`]); streaming endpoints are decoded with
+//! [`sse_frame_stream`](crate::stream::sse_frame_stream).
+//!
+//! ```no_run
+//! # async fn demo(request: toolify_rs::protocol::openai_chat::OpenAiChatRequest) -> Result<(), toolify_rs::client::ClientError> {
+//! use futures_util::StreamExt;
+//! use toolify_rs::client::ToolifyClient;
+//!
+//! let client = ToolifyClient::new("http://127.0.0.1:8000").with_api_key("sk-client");
+//! let mut chunks = client.chat_stream(&request).await?;
+//! while let Some(chunk) = chunks.next().await {
+//!     println!("{:?}", chunk?.choices);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::pin::Pin;
+
+use futures_util::{Stream, StreamExt};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::protocol::anthropic::{AnthropicRequest, AnthropicResponse, AnthropicStreamEvent};
+use crate::protocol::openai_chat::{OpenAiChatRequest, OpenAiChatResponse, OpenAiStreamChunk};
+use crate::protocol::openai_responses::{ResponsesOutput, ResponsesRequest, ResponsesStreamEvent};
+use crate::stream::sse::is_done_event;
+use crate::stream::sse_frame_stream;
+
+/// Anthropic API version sent on `/v1/messages` requests.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Errors returned by [`ToolifyClient`].
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+    /// The proxy answered with a non-success status; `body` is its error
+    /// payload as sent.
+    #[error("proxy returned {status}: {body}")]
+    Status { status: StatusCode, body: String },
+    #[error("failed to decode response: {0}")]
+    Decode(#[from] serde_json::Error),
+    /// The stream carried an error event in place of a chunk.
+    #[error("stream error: {0}")]
+    Stream(String),
+}
+
+/// Items of a streaming response.
+pub type EventStream<T> = Pin<Box<dyn Stream<Item = Result<T, ClientError>> + Send>>;
+
+/// `GET /v1/models` listing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelList {
+    pub data: Vec<ModelEntry>,
+}
+
+/// One model in a [`ModelList`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelEntry {
+    pub id: String,
+    #[serde(default)]
+    pub owned_by: String,
+}
+
+/// Client for the proxy's model and admin endpoints.
+///
+/// Cheap to clone; clones share one connection pool.
+#[derive(Debug, Clone)]
+pub struct ToolifyClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    admin_credential: Option<AdminCredential>,
+}
+
+#[derive(Debug, Clone)]
+enum AdminCredential {
+    Bearer(String),
+    #[cfg(feature = "server")]
+    Signed(String),
+}
+
+impl ToolifyClient {
+    /// Client for the proxy at `base_url`, including any `server.base_path`,
+    /// e.g. `http://127.0.0.1:8000` or `https://gateway.internal/toolify`.
+    #[must_use]
+    pub fn new(base_url: impl Into<String>) -> Self {
+        let mut base_url = base_url.into();
+        while base_url.ends_with('/') {
+            base_url.pop();
+        }
+        Self {
+            http: reqwest::Client::new(),
+            base_url,
+            api_key: None,
+            admin_credential: None,
+        }
+    }
+
+    /// Use `http` for requests, e.g. to set timeouts or a proxy.
+    #[must_use]
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Client key from `client_authentication.allowed_keys`, sent the way
+    /// each endpoint expects (`Authorization: Bearer` or `x-api-key`).
+    #[must_use]
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Admin key from `client_authentication.admin_keys`, or an
+    /// `admin_authentication` token without replay protection.
+    #[must_use]
+    pub fn with_admin_key(mut self, admin_key: impl Into<String>) -> Self {
+        self.admin_credential = Some(AdminCredential::Bearer(admin_key.into()));
+        self
+    }
+
+    /// `admin_authentication` token used to sign each admin request, as
+    /// required when `replay_protection` is set.
+    #[cfg(feature = "server")]
+    #[must_use]
+    pub fn with_admin_signing_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_credential = Some(AdminCredential::Signed(token.into()));
+        self
+    }
+
+    /// `POST /v1/chat/completions`.
+    ///
+    /// # Errors
+    ///
+    /// See [`ClientError`].
+    pub async fn chat(
+        &self,
+        request: &OpenAiChatRequest,
+    ) -> Result<OpenAiChatResponse, ClientError> {
+        let request = OpenAiChatRequest {
+            stream: Some(false),
+            ..request.clone()
+        };
+        let response = self
+            .send(self.bearer(self.post("/v1/chat/completions", &request)))
+            .await?;
+        decode_json(response).await
+    }
+
+    /// `POST /v1/chat/completions` with `stream: true`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the request is rejected; errors after the stream starts
+    /// are yielded as items.
+    pub async fn chat_stream(
+        &self,
+        request: &OpenAiChatRequest,
+    ) -> Result<EventStream<OpenAiStreamChunk>, ClientError> {
+        let request = OpenAiChatRequest {
+            stream: Some(true),
+            ..request.clone()
+        };
+        let response = self
+            .send(self.bearer(self.post("/v1/chat/completions", &request)))
+            .await?;
+        Ok(decode_events(response, |_| None))
+    }
+
+    /// `POST /v1/responses`.
+    ///
+    /// # Errors
+    ///
+    /// See [`ClientError`].
+    pub async fn responses(
+        &self,
+        request: &ResponsesRequest,
+    ) -> Result<ResponsesOutput, ClientError> {
+        let request = ResponsesRequest {
+            stream: Some(false),
+            ..request.clone()
+        };
+        let response = self
+            .send(self.bearer(self.post("/v1/responses", &request)))
+            .await?;
+        decode_json(response).await
+    }
+
+    /// `POST /v1/responses` with `stream: true`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the request is rejected; errors after the stream starts
+    /// are yielded as items.
+    pub async fn responses_stream(
+        &self,
+        request: &ResponsesRequest,
+    ) -> Result<EventStream<ResponsesStreamEvent>, ClientError> {
+        let request = ResponsesRequest {
+            stream: Some(true),
+            ..request.clone()
+        };
+        let response = self
+            .send(self.bearer(self.post("/v1/responses", &request)))
+            .await?;
+        Ok(decode_events(response, |event| match event {
+            ResponsesStreamEvent::Error { message } => Some(message.clone()),
+            _ => None,
+        }))
+    }
+
+    /// `POST /v1/messages`.
+    ///
+    /// # Errors
+    ///
+    /// See [`ClientError`].
+    pub async fn messages(
+        &self,
+        request: &AnthropicRequest,
+    ) -> Result<AnthropicResponse, ClientError> {
+        let request = AnthropicRequest {
+            stream: Some(false),
+            ..request.clone()
+        };
+        let response = self
+            .send(self.x_api_key(self.post("/v1/messages", &request)))
+            .await?;
+        decode_json(response).await
+    }
+
+    /// `POST /v1/messages` with `stream: true`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the request is rejected; errors after the stream starts
+    /// are yielded as items.
+    pub async fn messages_stream(
+        &self,
+        request: &AnthropicRequest,
+    ) -> Result<EventStream<AnthropicStreamEvent>, ClientError> {
+        let request = AnthropicRequest {
+            stream: Some(true),
+            ..request.clone()
+        };
+        let response = self
+            .send(self.x_api_key(self.post("/v1/messages", &request)))
+            .await?;
+        Ok(decode_events(response, |event| match event {
+            AnthropicStreamEvent::Error { error } => Some(error.message.clone()),
+            _ => None,
+        }))
+    }
+
+    /// `GET /v1/models`.
+    ///
+    /// # Errors
+    ///
+    /// See [`ClientError`].
+    pub async fn models(&self) -> Result<ModelList, ClientError> {
+        let request = self.http.get(self.url("/v1/models"));
+        let response = self.send(self.bearer(request)).await?;
+        decode_json(response).await
+    }
+
+    /// Call an admin endpoint such as `/admin/cooldowns` or
+    /// `/admin/analytics?report=usage` and return its JSON body.
+    ///
+    /// # Errors
+    ///
+    /// See [`ClientError`].
+    pub async fn admin(
+        &self,
+        method: Method,
+        path_and_query: &str,
+        body: Option<&Value>,
+    ) -> Result<Value, ClientError> {
+        let mut request = self.http.request(method.clone(), self.url(path_and_query));
+        let body = body.map(|body| body.to_string().into_bytes());
+        if let Some(body) = &body {
+            request = request
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.clone());
+        }
+        request = self.admin_auth(request, &method, path_and_query, body.as_deref());
+        let response = self.send(request).await?;
+        decode_json(response).await
+    }
+
+    /// `GET /admin/cooldowns`.
+    ///
+    /// # Errors
+    ///
+    /// See [`ClientError`].
+    pub async fn admin_cooldowns(&self) -> Result<Value, ClientError> {
+        self.admin(Method::GET, "/admin/cooldowns", None).await
+    }
+
+    /// `POST /admin/config/apply` with a complete YAML config.
+    ///
+    /// # Errors
+    ///
+    /// See [`ClientError`].
+    pub async fn admin_apply_config(&self, config_yaml: &str) -> Result<Value, ClientError> {
+        let path = "/admin/config/apply";
+        let request = self
+            .http
+            .post(self.url(path))
+            .header(CONTENT_TYPE, "application/yaml")
+            .body(config_yaml.to_string());
+        let response = self
+            .send(self.admin_auth(request, &Method::POST, path, Some(config_yaml.as_bytes())))
+            .await?;
+        decode_json(response).await
+    }
+
+    /// `POST /admin/models/refresh`.
+    ///
+    /// # Errors
+    ///
+    /// See [`ClientError`].
+    pub async fn admin_refresh_models(&self) -> Result<Value, ClientError> {
+        self.admin(Method::POST, "/admin/models/refresh", None)
+            .await
+    }
+
+    fn url(&self, path_and_query: &str) -> String {
+        format!("{}{path_and_query}", self.base_url)
+    }
+
+    fn post(&self, path: &str, body: &impl Serialize) -> RequestBuilder {
+        self.http.post(self.url(path)).json(body)
+    }
+
+    fn bearer(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    fn x_api_key(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = request.header("anthropic-version", ANTHROPIC_VERSION);
+        match &self.api_key {
+            Some(key) => request.header("x-api-key", key),
+            None => request,
+        }
+    }
+
+    #[cfg_attr(not(feature = "server"), allow(unused_variables))]
+    fn admin_auth(
+        &self,
+        request: RequestBuilder,
+        method: &Method,
+        path_and_query: &str,
+        body: Option<&[u8]>,
+    ) -> RequestBuilder {
+        match &self.admin_credential {
+            None => request,
+            Some(AdminCredential::Bearer(key)) => {
+                request.header(AUTHORIZATION, format!("Bearer {key}"))
+            }
+            #[cfg(feature = "server")]
+            Some(AdminCredential::Signed(token)) => {
+                use crate::auth::{
+                    admin_body_digest, sign_admin_request, ADMIN_CONTENT_SHA256_HEADER,
+                    ADMIN_NONCE_HEADER, ADMIN_SIGNATURE_HEADER, ADMIN_TIMESTAMP_HEADER,
+                };
+
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_secs())
+                    .to_string();
+                let nonce = format!("{:032x}", fastrand::u128(..));
+                let path = format!("{}{path_and_query}", base_path(&self.base_url));
+                let body_sha256 = admin_body_digest(body.unwrap_or_default());
+                let signature = sign_admin_request(
+                    token,
+                    &timestamp,
+                    &nonce,
+                    method.as_str(),
+                    &path,
+                    &body_sha256,
+                );
+                request
+                    .header(ADMIN_TIMESTAMP_HEADER, timestamp)
+                    .header(ADMIN_NONCE_HEADER, nonce)
+                    .header(ADMIN_CONTENT_SHA256_HEADER, body_sha256)
+                    .header(ADMIN_SIGNATURE_HEADER, signature)
+            }
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ClientError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(ClientError::Status { status, body })
+    }
+}
+
+/// Path part of `base_url`, which signed admin requests cover.
+#[cfg(feature = "server")]
+fn base_path(base_url: &str) -> &str {
+    let after_scheme = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest);
+    after_scheme
+        .find('/')
+        .map_or("", |index| &after_scheme[index..])
+}
+
+async fn decode_json<T: DeserializeOwned>(response: Response) -> Result<T, ClientError> {
+    let body = response.bytes().await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Decode each SSE `data` payload as `T`, up to `[DONE]`. Events that
+/// `stream_error` recognizes, and `{"error": {...}}` payloads that are not a
+/// `T`, are yielded as [`ClientError::Stream`].
+fn decode_events<T>(response: Response, stream_error: fn(&T) -> Option<String>) -> EventStream<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let events = sse_frame_stream(response.bytes_stream())
+        .take_while(|event| std::future::ready(!is_done_event(event)))
+        .filter(|event| std::future::ready(!event.data.is_empty()))
+        .map(move |event| {
+            let item: T = match serde_json::from_str(&event.data) {
+                Ok(item) => item,
+                Err(err) => return Err(error_payload(&event.data).unwrap_or(err.into())),
+            };
+            match stream_error(&item) {
+                Some(message) => Err(ClientError::Stream(message)),
+                None => Ok(item),
+            }
+        });
+    Box::pin(events)
+}
+
+/// The message of an `{"error": {"message": ...}}` stream payload.
+fn error_payload(data: &str) -> Option<ClientError> {
+    let payload: Value = serde_json::from_str(data).ok()?;
+    let error = payload.get("error")?;
+    let message = error
+        .get("message")
+        .and_then(Value::as_str)
+        .map_or_else(|| error.to_string(), str::to_string);
+    Some(ClientError::Stream(message))
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum::Router;
+
+    #[tokio::test]
+    async fn test_chat_stream_decodes_chunks_and_mid_stream_errors() {
+        let app = Router::new().route(
+            "/toolify/v1/chat/completions",
+            post(|headers: HeaderMap, body: String| async move {
+                assert_eq!(headers["authorization"], "Bearer sk-client");
+                assert!(body.contains(r#""stream":true"#));
+                concat!(
+                    "data: {\"id\":\"c1\",\"object\":\"chat.completion.chunk\",\"created\":1,",
+                    "\"model\":\"m\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"hi\"},",
+                    "\"finish_reason\":null}]}\n\n",
+                    "data: {\"error\":{\"message\":\"upstream reset\"}}\n\n",
+                    "data: [DONE]\n\n",
+                )
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client =
+            ToolifyClient::new(format!("http://{addr}/toolify/")).with_api_key("sk-client");
+        let request: OpenAiChatRequest =
+            serde_json::from_value(serde_json::json!({ "model": "m", "messages": [] })).unwrap();
+        let items: Vec<_> = client.chat_stream(&request).await.unwrap().collect().await;
+        assert_eq!(items.len(), 2);
+        let chunk = items[0].as_ref().unwrap();
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("hi"));
+        assert!(
+            matches!(&items[1], Err(ClientError::Stream(message)) if message == "upstream reset")
+        );
+
+        let err = client.models().await.unwrap_err();
+        assert!(
+            matches!(err, ClientError::Status { status, .. } if status == StatusCode::NOT_FOUND)
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Structural diff between two configurations, used by the admin config rollout.
+
+use serde_json::{Map, Value};
+
+use super::AppConfig;
+
+/// Keys whose values are secrets; changes are reported but values are hidden.
+const REDACTED_KEYS: &[&str] = &["api_key", "allowed_keys", "admin_keys", "tokens"];
+const REDACTED: &str = "<redacted>";
+
+/// Settings read once at startup; changing them only takes effect after a restart.
+const RESTART_ONLY_PATHS: &[&str] = &[
+    "server.port",
+    "server.host",
+    "server.base_path",
+    "server.runtime_worker_threads",
+    "server.runtime_max_blocking_threads",
+    "server.runtime_thread_stack_size_kb",
+    "server.tcp_reuse_port_listener_count",
+    "server.runtime_topology",
+    "server.journal_path",
+    "server.journal_flush_interval_ms",
+    "server.journal_compact_interval_secs",
+    "server.batch_storage_dir",
+    "server.batch_max_concurrency",
+    "features.log_level",
+];
+
+/// One changed setting. `from`/`to` are `None` when the setting was added/removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    pub path: String,
+    pub from: Option<Value>,
+    pub to: Option<Value>,
+}
+
+impl ConfigChange {
+    /// Whether the change only takes effect after a restart.
+    #[must_use]
+    pub fn requires_restart(&self) -> bool {
+        RESTART_ONLY_PATHS.contains(&self.path.as_str())
+    }
+}
+
+/// List the settings that differ between `running` and `candidate`.
+///
+/// Lists of named entries (`upstream_services`, `virtual_models`) are matched
+/// by `name`, so reordering is not reported as a change. Secret values are
+/// redacted.
+#[must_use]
+pub fn diff_configs(running: &AppConfig, candidate: &AppConfig) -> Vec<ConfigChange> {
+    let running = serde_json::to_value(running).unwrap_or(Value::Null);
+    let candidate = serde_json::to_value(candidate).unwrap_or(Value::Null);
+    let mut changes = Vec::new();
+    diff_values(String::new(), &running, &candidate, &mut changes);
+    changes
+}
+
+fn diff_values(path: String, from: &Value, to: &Value, changes: &mut Vec<ConfigChange>) {
+    if from == to {
+        return;
+    }
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => diff_objects(&path, from, to, changes),
+        (Value::Array(from), Value::Array(to)) if is_named_list(from) && is_named_list(to) => {
+            for entry in from {
+                let name = entry_name(entry);
+                let entry_path = format!("{path}[{name}]");
+                match to.iter().find(|candidate| entry_name(candidate) == name) {
+                    Some(candidate) => diff_values(entry_path, entry, candidate, changes),
+                    None => changes.push(change(&entry_path, Some(entry), None)),
+                }
+            }
+            for entry in to {
+                let name = entry_name(entry);
+                if !from.iter().any(|running| entry_name(running) == name) {
+                    changes.push(change(&format!("{path}[{name}]"), None, Some(entry)));
+                }
+            }
+        }
+        _ => changes.push(change(&path, Some(from), Some(to))),
+    }
+}
+
+fn diff_objects(
+    path: &str,
+    from: &Map<String, Value>,
+    to: &Map<String, Value>,
+    changes: &mut Vec<ConfigChange>,
+) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+    for (key, from_value) in from {
+        match to.get(key) {
+            Some(to_value) => diff_values(join(key), from_value, to_value, changes),
+            None => changes.push(change(&join(key), Some(from_value), None)),
+        }
+    }
+    for (key, to_value) in to {
+        if !from.contains_key(key) {
+            changes.push(change(&join(key), None, Some(to_value)));
+        }
+    }
+}
+
+fn is_named_list(entries: &[Value]) -> bool {
+    entries
+        .iter()
+        .all(|entry| entry.get("name").is_some_and(Value::is_string))
+}
+
+fn entry_name(entry: &Value) -> &str {
+    entry
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+fn change(path: &str, from: Option<&Value>, to: Option<&Value>) -> ConfigChange {
+    let secret = path
+        .rsplit('.')
+        .next()
+        .is_some_and(|key| REDACTED_KEYS.contains(&key));
+    let redact_value = |value: &Value| {
+        if secret {
+            Value::String(REDACTED.to_string())
+        } else {
+            redact(value)
+        }
+    };
+    ConfigChange {
+        path: path.to_string(),
+        from: from.map(redact_value),
+        to: to.map(redact_value),
+    }
+}
+
+fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if REDACTED_KEYS.contains(&key.as_str()) {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_config;
+
+    const BASE: &str = r#"
+upstream_services:
+  - name: a
+    base_url: https://a.example.com/v1
+    api_key: secret-a
+    models: [gpt-4o]
+    is_default: true
+  - name: b
+    base_url: https://b.example.com/v1
+    api_key: secret-b
+    models: [gpt-4o-mini]
+client_authentication:
+  allowed_keys: [client-1]
+"#;
+
+    #[test]
+    fn test_diff_matches_named_entries_and_redacts_secrets() {
+        let running = parse_config(BASE).unwrap();
+        let mut candidate = running.clone();
+        candidate.upstream_services.swap(0, 1);
+        assert!(diff_configs(&running, &candidate).is_empty());
+
+        candidate
+            .upstream_services
+            .retain(|upstream| upstream.name == "a");
+        candidate.upstream_services[0].api_key = "rotated".to_string();
+        candidate.server.port = 9000;
+        candidate.features.rewrite_response_model = true;
+
+        let changes = diff_configs(&running, &candidate);
+        let paths: Vec<&str> = changes.iter().map(|change| change.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "features.rewrite_response_model",
+                "server.port",
+                "upstream_services[a].api_key",
+                "upstream_services[b]",
+            ]
+        );
+        assert!(!changes[0].requires_restart());
+        assert!(changes[1].requires_restart());
+        assert_eq!(changes[2].to, Some(Value::String(REDACTED.to_string())));
+        assert_eq!(changes[3].to, None);
+        assert_eq!(
+            changes[3].from.as_ref().unwrap()["api_key"],
+            Value::String(REDACTED.to_string())
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! Renamed config fields.
+//!
+//! The loader still accepts a renamed field under its old name for one
+//! release, reporting a [`Deprecation`] for each use, and
+//! `toolify migrate-config` rewrites old files to the new names.
+
+use std::fmt;
+
+use serde_yaml::Value;
+
+/// A field that moved to a new name within the same section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenamedField {
+    /// Dotted path of the mapping holding the field; sequences on the way,
+    /// such as `upstream_services`, apply the rename to every entry.
+    pub section: &'static str,
+    pub old: &'static str,
+    pub new: &'static str,
+    /// Release whose loader stops accepting `old`.
+    pub removed_in: &'static str,
+}
+
+/// Renames still accepted by the loader. Entries are dropped in their
+/// `removed_in` release.
+pub const RENAMED_FIELDS: &[RenamedField] = &[RenamedField {
+    section: "features",
+    old: "convert_developer_to_system",
+    new: "fold_system_messages",
+    removed_in: "0.2.0",
+}];
+
+/// One use of a deprecated field name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    /// Dotted path of the old field, e.g. `features.convert_developer_to_system`.
+    pub field: String,
+    pub replacement: String,
+    pub removed_in: &'static str,
+    /// The new field was set as well, so the old value was dropped.
+    pub ignored: bool,
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is deprecated and will be removed in {}; use `{}`",
+            self.field, self.removed_in, self.replacement
+        )?;
+        if self.ignored {
+            write!(
+                f,
+                " (its value was ignored because `{}` is also set)",
+                self.replacement
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Move every deprecated field of `root` to its new name.
+///
+/// When both names are set the new one wins and the old one is removed.
+pub fn migrate_value(root: &mut Value) -> Vec<Deprecation> {
+    let mut deprecations = Vec::new();
+    for rename in RENAMED_FIELDS {
+        let section: Vec<&str> = rename.section.split('.').collect();
+        rename_in(root, &section, rename, &mut deprecations);
+    }
+    deprecations
+}
+
+fn rename_in(
+    value: &mut Value,
+    section: &[&str],
+    rename: &RenamedField,
+    deprecations: &mut Vec<Deprecation>,
+) {
+    match value {
+        Value::Sequence(items) => {
+            for item in items {
+                rename_in(item, section, rename, deprecations);
+            }
+        }
+        Value::Mapping(map) => match section.split_first() {
+            Some((key, rest)) => {
+                if let Some(child) = map.get_mut(*key) {
+                    rename_in(child, rest, rename, deprecations);
+                }
+            }
+            None => {
+                let Some(old_value) = map.remove(rename.old) else {
+                    return;
+                };
+                let ignored = map.contains_key(rename.new);
+                if !ignored {
+                    map.insert(Value::from(rename.new), old_value);
+                }
+                deprecations.push(Deprecation {
+                    field: format!("{}.{}", rename.section, rename.old),
+                    replacement: format!("{}.{}", rename.section, rename.new),
+                    removed_in: rename.removed_in,
+                    ignored,
+                });
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Upgraded config text and the deprecated fields it replaced.
+#[derive(Debug)]
+pub struct MigratedConfig {
+    pub contents: String,
+    pub deprecations: Vec<Deprecation>,
+    /// Comments and layout were lost because the old names could not be
+    /// renamed in place, e.g. in flow-style mappings.
+    pub reformatted: bool,
+}
+
+/// Rename deprecated fields in config text.
+///
+/// Keys are renamed line by line so comments and layout survive. When that
+/// does not yield the same document as [`migrate_value`], the migrated
+/// document is written out as plain YAML instead.
+///
+/// # Errors
+///
+/// Returns the YAML error when `contents` does not parse.
+pub fn migrate_config_text(contents: &str) -> Result<MigratedConfig, serde_yaml::Error> {
+    let mut migrated: Value = serde_yaml::from_str(contents)?;
+    let deprecations = migrate_value(&mut migrated);
+    if deprecations.is_empty() {
+        return Ok(MigratedConfig {
+            contents: contents.to_string(),
+            deprecations,
+            reformatted: false,
+        });
+    }
+    let renamed = rename_keys_in_text(contents);
+    if serde_yaml::from_str::<Value>(&renamed).is_ok_and(|value| value == migrated) {
+        return Ok(MigratedConfig {
+            contents: renamed,
+            deprecations,
+            reformatted: false,
+        });
+    }
+    Ok(MigratedConfig {
+        contents: serde_yaml::to_string(&migrated)?,
+        deprecations,
+        reformatted: true,
+    })
+}
+
+/// Rename old keys written in block style, tracking the path of mapping keys
+/// by indentation. Sequence entries do not add to the path.
+fn rename_keys_in_text(contents: &str) -> String {
+    let mut out = String::with_capacity(contents.len());
+    // (column, key) of the enclosing mapping keys.
+    let mut path: Vec<(usize, &str)> = Vec::new();
+    // Lines indented past this column belong to a block scalar.
+    let mut block_scalar_at: Option<usize> = None;
+    for line in contents.split_inclusive('\n') {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let body = line[indent..].trim_end();
+        if body.is_empty() || body.starts_with('#') {
+            out.push_str(line);
+            continue;
+        }
+        if block_scalar_at.is_some_and(|column| indent > column) {
+            out.push_str(line);
+            continue;
+        }
+        block_scalar_at = None;
+        let mut column = indent;
+        let mut key_text = body;
+        while let Some(rest) = key_text.strip_prefix("- ") {
+            let trimmed = rest.trim_start_matches(' ');
+            column += key_text.len() - trimmed.len();
+            key_text = trimmed;
+        }
+        let Some((key, value)) = key_text.split_once(':') else {
+            out.push_str(line);
+            continue;
+        };
+        if key.is_empty()
+            || key.contains([' ', '"', '\'', '{', '['])
+            || !(value.is_empty() || value.starts_with(' '))
+        {
+            out.push_str(line);
+            continue;
+        }
+        while path.last().is_some_and(|&(parent, _)| parent >= column) {
+            path.pop();
+        }
+        let value = value.trim_start();
+        if value.starts_with('|') || value.starts_with('>') {
+            block_scalar_at = Some(column);
+        }
+        let rename = RENAMED_FIELDS.iter().find(|rename| {
+            rename.old == key
+                && rename
+                    .section
+                    .split('.')
+                    .eq(path.iter().map(|&(_, parent)| parent))
+        });
+        match rename {
+            Some(rename) => {
+                out.push_str(&line[..column]);
+                out.push_str(rename.new);
+                out.push_str(&line[column + key.len()..]);
+            }
+            None => out.push_str(line),
+        }
+        path.push((column, key));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_field_names_are_renamed_in_place() {
+        let old = "\
+server:
+  port: 8000
+features:
+  # Fold system messages.
+  convert_developer_to_system: false   # keep this comment
+  prompt_template: |
+    convert_developer_to_system: not a key
+";
+        let migrated = migrate_config_text(old).unwrap();
+        assert!(!migrated.reformatted);
+        assert_eq!(
+            migrated.contents,
+            old.replacen(
+                "  convert_developer_to_system: false",
+                "  fold_system_messages: false",
+                1
+            )
+        );
+        assert_eq!(
+            migrated.deprecations,
+            [Deprecation {
+                field: "features.convert_developer_to_system".to_string(),
+                replacement: "features.fold_system_messages".to_string(),
+                removed_in: "0.2.0",
+                ignored: false,
+            }]
+        );
+
+        let both = "features: {convert_developer_to_system: false, fold_system_messages: true}\n";
+        let migrated = migrate_config_text(both).unwrap();
+        assert!(migrated.reformatted);
+        assert!(migrated.deprecations[0].ignored);
+        assert_eq!(
+            migrated.contents,
+            "features:\n  fold_system_messages: true\n"
+        );
+
+        let current = "features:\n  fold_system_messages: true\n";
+        let migrated = migrate_config_text(current).unwrap();
+        assert!(migrated.deprecations.is_empty());
+        assert_eq!(migrated.contents, current);
+    }
+}
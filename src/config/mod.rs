@@ -1,8 +1,12 @@
+pub mod diff;
+pub mod migrate;
 pub mod validation;
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 
+use self::migrate::{migrate_value, Deprecation};
 use self::validation::validate_config;
 
 /// Error type for configuration loading and validation.
@@ -36,6 +40,239 @@ impl fmt::Display for FcMode {
     }
 }
 
+/// Which response modes an upstream service can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamSupport {
+    #[default]
+    Both,
+    /// Only streams; `stream: false` requests are streamed upstream and
+    /// re-aggregated into one response.
+    StreamOnly,
+    /// Never streams; `stream: true` requests get an SSE stream synthesized
+    /// from the complete response.
+    NonStreamOnly,
+}
+
+/// How an upstream's `base_url` is joined with endpoint paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PathStyle {
+    /// Drop a pasted endpoint path (`/chat/completions`, `/responses`,
+    /// `/messages`, `/models`) and give a bare host the provider's API
+    /// version path (`/v1`, `/v1beta` for Gemini, `/v1beta/openai` for
+    /// gemini-openai).
+    Auto,
+    /// Append endpoint paths to `base_url` exactly as configured.
+    #[default]
+    AsIs,
+}
+
+/// How reuse-port listeners are spread over Tokio runtimes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeTopology {
+    /// Every listener runs on the one process-wide runtime.
+    #[default]
+    Shared,
+    /// Each listener gets its own single-threaded runtime on a thread pinned
+    /// to a core, so connections never migrate between cores.
+    PerListener,
+}
+
+/// Handling of tool calls whose arguments do not match the client's `strict`
+/// tool schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolSchemaValidation {
+    /// Pass tool calls through unchecked.
+    #[default]
+    Off,
+    /// Answer with a validation error; streams end with an error event in
+    /// place of the tool call.
+    Error,
+    /// Re-ask the model with the validation errors, like the FC error retry,
+    /// and answer with a validation error once attempts run out. Streams are
+    /// handled as with `error`.
+    Retry,
+}
+
+/// How requests to an upstream service are authenticated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpstreamAuthScheme {
+    /// `Authorization: Bearer <api_key>`.
+    Bearer,
+    /// `api_key` in the `auth.header` header.
+    ApiKeyHeader,
+    /// HMAC-SHA256 signature over the timestamp, method, path and body
+    /// digest, keyed by `api_key`.
+    Hmac,
+    /// No credentials.
+    None,
+}
+
+/// Upstream authentication; without a `scheme` the provider's usual key
+/// header is used.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamAuthConfig {
+    #[serde(default)]
+    pub scheme: Option<UpstreamAuthScheme>,
+    /// Header carrying `api_key` for `api-key-header`; defaults to the
+    /// provider's key header (`api-key` for OpenAI-compatible upstreams).
+    #[serde(default)]
+    pub header: Option<String>,
+    /// Sent as `x-key-id` alongside `hmac` signatures.
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
+
+/// Mutual TLS for an upstream service. Files are PEM and are re-read
+/// whenever the config is (re)applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamTlsConfig {
+    /// Certificate chain presented to the upstream.
+    #[serde(default)]
+    pub client_cert: Option<String>,
+    /// Private key for `client_cert`.
+    #[serde(default)]
+    pub client_key: Option<String>,
+    /// CA certificates trusted in addition to the public web roots.
+    #[serde(default)]
+    pub ca_bundle: Option<String>,
+}
+
+/// When streamed responses are cut at the client's stop sequences by the
+/// proxy instead of the upstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StopSequenceEmulation {
+    /// Only for upstream protocols that cannot carry stop sequences.
+    #[default]
+    Auto,
+    /// For every stream, covering upstreams that ignore stop sequences.
+    Always,
+    Off,
+}
+
+/// How out-of-range `temperature` / `top_p` values are adapted to the
+/// upstream provider's accepted range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplingNormalization {
+    /// Clamp values into the provider's range.
+    #[default]
+    Clamp,
+    /// Rescale `temperature` from the client protocol's range to the
+    /// provider's, then clamp.
+    Scale,
+    /// Fail the request with a 400 instead of changing it.
+    Reject,
+}
+
+/// What to do with `seed` / `logprobs` / `top_logprobs` / `logit_bias` when
+/// the upstream provider does not support them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UnsupportedParamPolicy {
+    /// Strip them and list them in the `x-toolify-dropped-params` header.
+    #[default]
+    Drop,
+    /// Send them anyway.
+    Passthrough,
+    /// Fail the request with a 400.
+    Reject,
+}
+
+/// A feature a model must support to serve a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelCapability {
+    /// Image inputs.
+    Vision,
+    /// Native tool calling; not needed on upstreams that inject tools.
+    Tools,
+    /// JSON response formats.
+    JsonMode,
+}
+
+/// Output token limits of one upstream model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelOutputTokens {
+    /// Largest output limit the model accepts; larger client limits are lowered.
+    pub max_output_tokens: u64,
+    /// Limit sent when the client set none; `max_output_tokens` when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_output_tokens: Option<u64>,
+    /// Context window shared by prompt and output. A defaulted limit is capped
+    /// at what the estimated prompt leaves of it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u64>,
+}
+
+/// One step of the response output post-processing chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputPostprocessStep {
+    /// Cut the text at the first of the client's `stop` sequences, for
+    /// upstreams that ignore them.
+    EnforceStop,
+    /// Drop whitespace at the end of the text.
+    TrimTrailingWhitespace,
+    /// Replace regex matches; matches do not span lines.
+    RegexReplace {
+        pattern: String,
+        #[serde(default)]
+        replacement: String,
+    },
+}
+
+/// What happens to requests whose input is flagged by moderation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Reject the request with a 400 error.
+    #[default]
+    Block,
+    /// Forward the request and report the verdict in `x-toolify-moderation`.
+    Annotate,
+}
+
+/// Pre-flight moderation of the latest user message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationConfig {
+    /// `OpenAI`-compatible moderation endpoint, e.g.
+    /// `https://api.openai.com/v1/moderations` or a local classifier serving
+    /// the same API.
+    pub endpoint: String,
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub action: ModerationAction,
+    /// Only these categories count as flagged; empty uses the endpoint's `flagged`.
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default = "default_moderation_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Let requests through when the moderation endpoint fails or times out.
+    #[serde(default = "default_true")]
+    pub fail_open: bool,
+    /// Verdicts cached by content hash.
+    #[serde(default = "default_moderation_cache_entries")]
+    pub cache_entries: usize,
+}
+
+fn default_moderation_timeout_ms() -> u64 {
+    3000
+}
+
+fn default_moderation_cache_entries() -> usize {
+    4096
+}
+
 /// Server configuration.
 #[derive(Debug, Clone, Serialize)]
 pub struct ServerConfig {
@@ -51,6 +288,18 @@ pub struct ServerConfig {
     pub http_pool_idle_timeout_secs: u64,
     #[serde(default = "default_models_cache_ttl_secs")]
     pub models_cache_ttl_secs: u64,
+    /// Skip listing models from an upstream this long after a listing failed (0 disables).
+    #[serde(default = "default_models_cache_negative_ttl_secs")]
+    pub models_cache_negative_ttl_secs: u64,
+    /// Largest model request body accepted; larger requests are answered with 413.
+    #[serde(default = "default_max_request_bytes")]
+    pub max_request_bytes: usize,
+    /// Largest batch or file upload body accepted; `max_request_bytes` when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_upload_bytes: Option<usize>,
+    /// Largest non-streaming upstream response buffered to rewrite it.
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub runtime_worker_threads: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -67,6 +316,100 @@ pub struct ServerConfig {
     pub http_force_h2c_upstream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tcp_reuse_port_listener_count: Option<usize>,
+    #[serde(default)]
+    pub runtime_topology: RuntimeTopology,
+    /// Append-only request journal for crash-safe usage accounting.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub journal_path: Option<String>,
+    #[serde(default = "default_journal_flush_interval_ms")]
+    pub journal_flush_interval_ms: u64,
+    /// Fold completed journal records into totals this often (0 disables).
+    #[serde(default = "default_journal_compact_interval_secs")]
+    pub journal_compact_interval_secs: u64,
+    /// Directory for `/v1/batches` state and results; the batch API is off when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_storage_dir: Option<String>,
+    #[serde(default = "default_batch_max_concurrency")]
+    pub batch_max_concurrency: usize,
+    /// Pre-connect to every upstream at startup; `/health/ready` reports 503 until done.
+    #[serde(default)]
+    pub warmup_upstreams: bool,
+    /// Upper bound for each upstream's warm-up request.
+    #[serde(default = "default_warmup_timeout_secs")]
+    pub warmup_timeout_secs: u64,
+    /// Reuse resolved upstream addresses this long, in place of record TTLs (0 disables).
+    #[serde(default = "default_dns_cache_ttl_secs")]
+    pub dns_cache_ttl_secs: u64,
+    /// Reuse failed upstream lookups this long (0 disables).
+    #[serde(default = "default_dns_negative_cache_ttl_secs")]
+    pub dns_negative_cache_ttl_secs: u64,
+    /// Upstream fault injection; rejected unless built with the `chaos` feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chaos: Option<ChaosConfig>,
+    /// CORS for browser clients; cross-origin requests get no CORS headers when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cors: Option<CorsConfig>,
+}
+
+/// Origins allowed to call the proxy from a browser, and what they may send
+/// and read. `ingress` overrides fields per ingress API, keyed by
+/// `openai_chat` (also `/v1/completions` and the Gemini OpenAI-compatible
+/// route), `openai_responses`, `anthropic`, or `gemini`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Exact origins such as `https://app.example.com`, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    #[serde(default = "default_cors_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    /// Response headers scripts may read besides the CORS-safelisted ones.
+    #[serde(default)]
+    pub expose_headers: Vec<String>,
+    /// How long browsers may cache a preflight answer.
+    #[serde(default = "default_cors_max_age_secs")]
+    pub max_age_secs: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ingress: BTreeMap<String, CorsIngressConfig>,
+}
+
+/// Keys accepted under `server.cors.ingress`.
+pub const CORS_INGRESS_KEYS: [&str; 4] = ["openai_chat", "openai_responses", "anthropic", "gemini"];
+
+/// Per-ingress CORS overrides; unset fields keep the top-level value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsIngressConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_origins: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_headers: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_methods: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expose_headers: Option<Vec<String>>,
+}
+
+/// Faults injected into upstream requests to exercise failover and error
+/// shaping. Rates are probabilities in `0..=1`, rolled per attempt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChaosConfig {
+    /// Fail the attempt as if the upstream refused the connection.
+    #[serde(default)]
+    pub connect_failure_rate: f64,
+    /// Hold the response back for `slow_first_byte_ms`.
+    #[serde(default)]
+    pub slow_first_byte_rate: f64,
+    #[serde(default = "default_chaos_slow_first_byte_ms")]
+    pub slow_first_byte_ms: u64,
+    /// Cut an SSE response off after `truncate_after_bytes`. Any non-zero
+    /// rate sends all traffic through the reqwest client.
+    #[serde(default)]
+    pub truncate_stream_rate: f64,
+    #[serde(default = "default_chaos_truncate_after_bytes")]
+    pub truncate_after_bytes: usize,
 }
 
 fn default_port() -> u16 {
@@ -87,6 +430,57 @@ fn default_http_pool_idle_timeout_secs() -> u64 {
 fn default_models_cache_ttl_secs() -> u64 {
     300
 }
+fn default_models_cache_negative_ttl_secs() -> u64 {
+    60
+}
+fn default_max_request_bytes() -> usize {
+    2 * 1024 * 1024
+}
+fn default_max_response_bytes() -> usize {
+    64 * 1024 * 1024
+}
+fn default_journal_flush_interval_ms() -> u64 {
+    1000
+}
+fn default_journal_compact_interval_secs() -> u64 {
+    3600
+}
+fn default_batch_max_concurrency() -> usize {
+    4
+}
+fn default_cors_allowed_headers() -> Vec<String> {
+    [
+        "authorization",
+        "content-type",
+        "x-api-key",
+        "x-goog-api-key",
+        "anthropic-version",
+        "anthropic-beta",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+fn default_cors_allowed_methods() -> Vec<String> {
+    ["GET", "POST", "DELETE"].map(String::from).to_vec()
+}
+fn default_cors_max_age_secs() -> u64 {
+    600
+}
+fn default_warmup_timeout_secs() -> u64 {
+    10
+}
+fn default_dns_cache_ttl_secs() -> u64 {
+    30
+}
+fn default_dns_negative_cache_ttl_secs() -> u64 {
+    5
+}
+fn default_chaos_slow_first_byte_ms() -> u64 {
+    2000
+}
+fn default_chaos_truncate_after_bytes() -> usize {
+    256
+}
 
 #[derive(Debug, Deserialize)]
 struct ServerConfigWire {
@@ -102,6 +496,14 @@ struct ServerConfigWire {
     http_pool_idle_timeout_secs: u64,
     #[serde(default = "default_models_cache_ttl_secs")]
     models_cache_ttl_secs: u64,
+    #[serde(default = "default_models_cache_negative_ttl_secs")]
+    models_cache_negative_ttl_secs: u64,
+    #[serde(default = "default_max_request_bytes")]
+    max_request_bytes: usize,
+    #[serde(default)]
+    max_upload_bytes: Option<usize>,
+    #[serde(default = "default_max_response_bytes")]
+    max_response_bytes: usize,
     #[serde(default)]
     runtime_worker_threads: Option<RuntimeThreadsSetting>,
     #[serde(default)]
@@ -118,6 +520,30 @@ struct ServerConfigWire {
     http_force_h2c_upstream: bool,
     #[serde(default)]
     tcp_reuse_port_listener_count: Option<usize>,
+    #[serde(default)]
+    runtime_topology: RuntimeTopology,
+    #[serde(default)]
+    journal_path: Option<String>,
+    #[serde(default = "default_journal_flush_interval_ms")]
+    journal_flush_interval_ms: u64,
+    #[serde(default = "default_journal_compact_interval_secs")]
+    journal_compact_interval_secs: u64,
+    #[serde(default)]
+    batch_storage_dir: Option<String>,
+    #[serde(default = "default_batch_max_concurrency")]
+    batch_max_concurrency: usize,
+    #[serde(default)]
+    warmup_upstreams: bool,
+    #[serde(default = "default_warmup_timeout_secs")]
+    warmup_timeout_secs: u64,
+    #[serde(default = "default_dns_cache_ttl_secs")]
+    dns_cache_ttl_secs: u64,
+    #[serde(default = "default_dns_negative_cache_ttl_secs")]
+    dns_negative_cache_ttl_secs: u64,
+    #[serde(default)]
+    chaos: Option<ChaosConfig>,
+    #[serde(default)]
+    cors: Option<CorsConfig>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -151,6 +577,10 @@ impl<'de> Deserialize<'de> for ServerConfig {
             http_pool_max_idle_per_host: wire.http_pool_max_idle_per_host,
             http_pool_idle_timeout_secs: wire.http_pool_idle_timeout_secs,
             models_cache_ttl_secs: wire.models_cache_ttl_secs,
+            models_cache_negative_ttl_secs: wire.models_cache_negative_ttl_secs,
+            max_request_bytes: wire.max_request_bytes,
+            max_upload_bytes: wire.max_upload_bytes,
+            max_response_bytes: wire.max_response_bytes,
             // missing => Some(default), explicit null => None
             runtime_worker_threads: runtime_threads_or_default(
                 wire.runtime_worker_threads.as_ref(),
@@ -166,10 +596,34 @@ impl<'de> Deserialize<'de> for ServerConfig {
             http_use_env_proxy: wire.http_use_env_proxy,
             http_force_h2c_upstream: wire.http_force_h2c_upstream,
             tcp_reuse_port_listener_count: wire.tcp_reuse_port_listener_count,
+            runtime_topology: wire.runtime_topology,
+            journal_path: wire.journal_path,
+            journal_flush_interval_ms: wire.journal_flush_interval_ms,
+            journal_compact_interval_secs: wire.journal_compact_interval_secs,
+            batch_storage_dir: wire.batch_storage_dir,
+            batch_max_concurrency: wire.batch_max_concurrency,
+            warmup_upstreams: wire.warmup_upstreams,
+            warmup_timeout_secs: wire.warmup_timeout_secs,
+            dns_cache_ttl_secs: wire.dns_cache_ttl_secs,
+            dns_negative_cache_ttl_secs: wire.dns_negative_cache_ttl_secs,
+            chaos: wire.chaos,
+            cors: wire.cors,
         })
     }
 }
 
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self {
+            connect_failure_rate: 0.0,
+            slow_first_byte_rate: 0.0,
+            slow_first_byte_ms: default_chaos_slow_first_byte_ms(),
+            truncate_stream_rate: 0.0,
+            truncate_after_bytes: default_chaos_truncate_after_bytes(),
+        }
+    }
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
@@ -179,6 +633,10 @@ impl Default for ServerConfig {
             http_pool_max_idle_per_host: default_http_pool_max_idle_per_host(),
             http_pool_idle_timeout_secs: default_http_pool_idle_timeout_secs(),
             models_cache_ttl_secs: default_models_cache_ttl_secs(),
+            models_cache_negative_ttl_secs: default_models_cache_negative_ttl_secs(),
+            max_request_bytes: default_max_request_bytes(),
+            max_upload_bytes: None,
+            max_response_bytes: default_max_response_bytes(),
             runtime_worker_threads: None,
             runtime_max_blocking_threads: Some(8),
             runtime_thread_stack_size_kb: None,
@@ -187,6 +645,18 @@ impl Default for ServerConfig {
             http_use_env_proxy: false,
             http_force_h2c_upstream: false,
             tcp_reuse_port_listener_count: None,
+            runtime_topology: RuntimeTopology::Shared,
+            journal_path: None,
+            journal_flush_interval_ms: default_journal_flush_interval_ms(),
+            journal_compact_interval_secs: default_journal_compact_interval_secs(),
+            batch_storage_dir: None,
+            batch_max_concurrency: default_batch_max_concurrency(),
+            warmup_upstreams: false,
+            warmup_timeout_secs: default_warmup_timeout_secs(),
+            dns_cache_ttl_secs: default_dns_cache_ttl_secs(),
+            dns_negative_cache_ttl_secs: default_dns_negative_cache_ttl_secs(),
+            chaos: None,
+            cors: None,
         }
     }
 }
@@ -198,8 +668,18 @@ pub struct UpstreamServiceConfig {
     #[serde(default = "default_provider")]
     pub provider: String,
     pub base_url: String,
+    #[serde(default)]
+    pub path_style: PathStyle,
+    #[serde(default)]
     pub api_key: String,
     #[serde(default)]
+    pub auth: UpstreamAuthConfig,
+    #[serde(default)]
+    pub tls: Option<UpstreamTlsConfig>,
+    /// Local IP address outgoing connections to this upstream bind to.
+    #[serde(default)]
+    pub bind_address: Option<String>,
+    #[serde(default)]
     pub models: Vec<String>,
     #[serde(default)]
     pub description: String,
@@ -215,16 +695,101 @@ pub struct UpstreamServiceConfig {
     pub proxy_stream: Option<String>,
     #[serde(default)]
     pub proxy_non_stream: Option<String>,
+    #[serde(default)]
+    pub stream_support: StreamSupport,
+    /// What to do when a tool call's arguments break a `strict` tool schema
+    /// the client sent.
+    #[serde(default)]
+    pub tool_schema_validation: ToolSchemaValidation,
+    /// `anthropic-beta` flags clients may enable on this Anthropic upstream;
+    /// flags not listed here are dropped.
+    #[serde(default)]
+    pub anthropic_betas: Vec<String>,
+    /// `OpenAI-Organization` sent to this OpenAI upstream.
+    #[serde(default)]
+    pub organization: Option<String>,
+    /// `OpenAI-Project` sent to this OpenAI upstream.
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Forward the client's `OpenAI-Organization` header, overriding
+    /// `organization` when the client sends one.
+    #[serde(default)]
+    pub trust_client_organization: bool,
+    /// Largest request body routed to this upstream, below
+    /// `server.max_request_bytes`; larger requests are answered with 413.
+    #[serde(default)]
+    pub max_request_bytes: Option<usize>,
 }
 
 fn default_provider() -> String {
     "openai".to_string()
 }
 
+/// Prefix of a salted key hash, `sha256:<salt>:<hex SHA-256 of salt then key>`,
+/// accepted in place of a plaintext key in `allowed_keys` and `admin_keys`.
+pub const KEY_HASH_PREFIX: &str = "sha256:";
+
 /// Client authentication configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientAuthConfig {
+    /// Plaintext keys or `sha256:` hashes (see `toolify hash-key`).
     pub allowed_keys: Vec<String>,
+    /// Keys accepted by the `/admin/*` endpoints when `admin_authentication`
+    /// is not set; the admin API is disabled when both are absent.
+    #[serde(default)]
+    pub admin_keys: Vec<String>,
+    /// Per-key model renames applied before alias and virtual model lookup.
+    #[serde(default)]
+    pub key_model_maps: Vec<KeyModelMapConfig>,
+}
+
+/// Admin API authentication, kept apart from `client_authentication` so a
+/// leaked client key never opens the `/admin/*` endpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminAuthConfig {
+    /// Plaintext tokens or `sha256:` hashes sent as a bearer token. With
+    /// `replay_protection` they must be plaintext, since they key the
+    /// request signatures.
+    pub tokens: Vec<String>,
+    /// Require signed, single-use requests instead of a bearer token.
+    #[serde(default)]
+    pub replay_protection: Option<AdminReplayProtection>,
+}
+
+/// Signed admin requests: `x-toolify-timestamp` (unix seconds),
+/// `x-toolify-nonce`, and `x-toolify-signature`, the hex HMAC-SHA256 under
+/// a token of `{timestamp}\n{nonce}\n{METHOD}\n{path and query}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminReplayProtection {
+    /// Largest accepted difference between the request timestamp and the
+    /// server clock, either way.
+    #[serde(default = "default_admin_max_skew_secs")]
+    pub max_skew_secs: u64,
+    /// Nonces remembered until their timestamp leaves the skew window;
+    /// signed requests are refused while the cache is full.
+    #[serde(default = "default_admin_nonce_cache_entries")]
+    pub nonce_cache_entries: usize,
+}
+
+fn default_admin_max_skew_secs() -> u64 {
+    300
+}
+
+fn default_admin_nonce_cache_entries() -> usize {
+    100_000
+}
+
+/// Models a client key asks for that are served as other models.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyModelMapConfig {
+    /// An entry of `allowed_keys`, written the same way.
+    pub key: String,
+    /// Requested model to the model, alias, virtual, or cascade model served
+    /// in its place. Responses still report the requested model.
+    pub models: BTreeMap<String, String>,
 }
 
 /// Feature flags and settings.
@@ -234,8 +799,17 @@ pub struct FeaturesConfig {
     pub enable_function_calling: bool,
     #[serde(default = "default_log_level")]
     pub log_level: String,
+    /// Fold `developer`/`system` messages from the conversation into the
+    /// top-level system prompt for every ingress and upstream protocol.
+    /// Formerly `convert_developer_to_system`.
     #[serde(default = "default_true")]
-    pub convert_developer_to_system: bool,
+    pub fold_system_messages: bool,
+    /// Order of the top-level system prompt and folded system messages.
+    #[serde(default)]
+    pub system_prompt_precedence: SystemPromptPrecedence,
+    /// Joins the top-level system prompt and folded system messages.
+    #[serde(default = "default_system_prompt_separator")]
+    pub system_prompt_separator: String,
     #[serde(default)]
     pub enable_fc_error_retry: bool,
     #[serde(default = "default_fc_retry_max")]
@@ -244,6 +818,140 @@ pub struct FeaturesConfig {
     pub prompt_template: Option<String>,
     #[serde(default)]
     pub fc_error_retry_prompt_template: Option<String>,
+    /// Per-retry overrides: retry `n` uses entry `n - 1`, and the last entry
+    /// covers any later retries.
+    #[serde(default)]
+    pub fc_error_retry_attempts: Vec<FcRetryAttemptConfig>,
+    /// Upper bound of the random delay before each FC error retry, in
+    /// milliseconds.
+    #[serde(default)]
+    pub fc_error_retry_jitter_ms: u64,
+    /// Named FC prompt templates, selected per upstream or per requested model.
+    #[serde(default)]
+    pub prompt_templates: BTreeMap<String, PromptTemplateConfig>,
+    /// Upstream service name -> template selection.
+    #[serde(default)]
+    pub upstream_prompt_templates: BTreeMap<String, PromptTemplateSelection>,
+    /// Client-requested model or alias -> template selection; wins over the upstream's.
+    #[serde(default)]
+    pub model_prompt_templates: BTreeMap<String, PromptTemplateSelection>,
+    /// Upstream model name -> capabilities it supports. Models not listed are
+    /// assumed to support all of them.
+    #[serde(default)]
+    pub model_capabilities: BTreeMap<String, Vec<ModelCapability>>,
+    /// Upstream model name -> output token limits, used to fill and cap the
+    /// `max_tokens` Anthropic upstreams require.
+    #[serde(default)]
+    pub model_output_tokens: BTreeMap<String, ModelOutputTokens>,
+    /// Language of the built-in FC prompt when no custom template applies.
+    #[serde(default)]
+    pub prompt_locale: PromptLocale,
+    /// Upstream service name -> built-in FC prompt language.
+    #[serde(default)]
+    pub upstream_prompt_locales: BTreeMap<String, PromptLocale>,
+    /// Report the client-requested model (e.g. an alias) in response `model` fields.
+    #[serde(default)]
+    pub rewrite_response_model: bool,
+    /// Serve the `toolify.v1.Completion` gRPC service over HTTP/2 (`grpc` feature).
+    #[serde(default)]
+    pub enable_grpc_ingress: bool,
+    /// Try routes with the lowest observed streaming TTFB first within each failover tier.
+    #[serde(default)]
+    pub latency_aware_routing: bool,
+    /// Let clients attach to a stream in progress via `GET /v1/streams/{session}`
+    /// when it was requested with an `x-toolify-session` header.
+    #[serde(default)]
+    pub enable_stream_broadcast: bool,
+    /// Name the serving upstream in the `toolify_stats` frame sent at the
+    /// end of streams requested with `x-toolify-stream-stats: true`.
+    #[serde(default)]
+    pub stream_stats_upstream: bool,
+    /// Answer upstream errors with a reference id instead of the upstream's
+    /// message; the status, headers, and body excerpt are kept in the
+    /// request journal and the log under that id.
+    #[serde(default)]
+    pub upstream_error_references: bool,
+    /// Characters per text delta when synthesizing a stream for a
+    /// `non_stream_only` upstream.
+    #[serde(default = "default_synthetic_stream_chunk_chars")]
+    pub synthetic_stream_chunk_chars: usize,
+    /// Delay between synthesized stream frames, in milliseconds.
+    #[serde(default = "default_synthetic_stream_interval_ms")]
+    pub synthetic_stream_interval_ms: u64,
+    /// Pre-flight moderation of user input; disabled when absent.
+    #[serde(default)]
+    pub moderation: Option<ModerationConfig>,
+    /// Response-quality retries; disabled when absent.
+    #[serde(default)]
+    pub quality_retry: Option<QualityRetryConfig>,
+    /// Per-conversation traces served by the admin API; disabled when absent.
+    #[serde(default)]
+    pub conversation_traces: Option<ConversationTraceConfig>,
+    /// Steps applied in order to the assistant text of every response,
+    /// incrementally for streams.
+    #[serde(default)]
+    pub output_postprocess: Vec<OutputPostprocessStep>,
+    #[serde(default)]
+    pub stop_sequence_emulation: StopSequenceEmulation,
+    #[serde(default)]
+    pub sampling_normalization: SamplingNormalization,
+    #[serde(default)]
+    pub unsupported_params: UnsupportedParamPolicy,
+    /// Drop injected-XML tool calls from a streamed response that also
+    /// carried native tool calls, so clients never receive both.
+    #[serde(default = "default_true")]
+    pub dedup_native_tool_calls: bool,
+    /// Stream injected tool-call arguments to OpenAI Chat and Responses
+    /// clients while the call is still being generated.
+    #[serde(default)]
+    pub stream_tool_call_arguments: bool,
+    /// Drop `reasoning` items (and their `encrypted_content`) from `OpenAI`
+    /// Responses upstreams instead of forwarding them to Responses clients
+    /// and as reasoning to other clients.
+    #[serde(default)]
+    pub strip_responses_reasoning: bool,
+    /// How sticky routing and conversation traces fingerprint a request.
+    #[serde(default)]
+    pub sticky_hash: StickyHashConfig,
+    /// Upload large inline base64 files to the upstream's files API and
+    /// reference them instead; disabled when absent.
+    #[serde(default)]
+    pub inline_file_upload: Option<InlineFileUploadConfig>,
+    /// Number client stream frames with SSE `id:` fields and replay them to
+    /// clients reconnecting with `Last-Event-ID`; disabled when absent.
+    #[serde(default)]
+    pub resumable_streams: Option<ResumableStreamConfig>,
+    /// Size limits on the tool definitions rendered into the FC prompt;
+    /// unlimited when absent.
+    #[serde(default)]
+    pub tool_definition_limits: Option<ToolDefinitionLimits>,
+    /// Keep redacted samples of streamed tool blocks that failed to parse and
+    /// were flushed as text; disabled when absent.
+    #[serde(default)]
+    pub fc_parse_failures: Option<FcParseFailureConfig>,
+    /// Per-request analytics in an embedded SQLite database, with canned
+    /// reports on the admin API; disabled when absent. Needs a build with the
+    /// `analytics` feature.
+    #[serde(default)]
+    pub analytics: Option<AnalyticsConfig>,
+    /// Honor the client `x-priority` header with provider service tiers and
+    /// an optional local admission queue; the header is ignored when absent.
+    #[serde(default)]
+    pub request_priority: Option<RequestPriorityConfig>,
+    /// Upstream response headers copied to client responses, as-is or
+    /// renamed, e.g. rate limit headers for client-side schedulers.
+    #[serde(default)]
+    pub forward_response_headers: Vec<ForwardResponseHeaderConfig>,
+    /// Answer slow non-streaming requests sent with `x-toolify-long-poll:
+    /// true` with an operation id to poll at `/v1/operations/{id}`; disabled
+    /// when absent.
+    #[serde(default)]
+    pub long_poll: Option<LongPollConfig>,
+    /// Refuse new streaming requests with a 503 while stream, tool call
+    /// detection, and aggregation buffers hold more than the budget;
+    /// unlimited when absent.
+    #[serde(default)]
+    pub memory_budget: Option<MemoryBudgetConfig>,
 }
 
 fn default_true() -> bool {
@@ -252,24 +960,607 @@ fn default_true() -> bool {
 fn default_log_level() -> String {
     "INFO".to_string()
 }
+fn default_system_prompt_separator() -> String {
+    "\n".to_string()
+}
 fn default_fc_retry_max() -> u32 {
     3
 }
 
+fn default_synthetic_stream_chunk_chars() -> usize {
+    32
+}
+
+fn default_synthetic_stream_interval_ms() -> u64 {
+    10
+}
+
 impl Default for FeaturesConfig {
     fn default() -> Self {
         Self {
             enable_function_calling: true,
             log_level: default_log_level(),
-            convert_developer_to_system: true,
+            fold_system_messages: true,
+            system_prompt_precedence: SystemPromptPrecedence::TopLevelFirst,
+            system_prompt_separator: default_system_prompt_separator(),
             enable_fc_error_retry: false,
             fc_error_retry_max_attempts: default_fc_retry_max(),
             prompt_template: None,
             fc_error_retry_prompt_template: None,
+            fc_error_retry_attempts: Vec::new(),
+            fc_error_retry_jitter_ms: 0,
+            prompt_templates: BTreeMap::new(),
+            upstream_prompt_templates: BTreeMap::new(),
+            model_prompt_templates: BTreeMap::new(),
+            model_capabilities: BTreeMap::new(),
+            model_output_tokens: BTreeMap::new(),
+            prompt_locale: PromptLocale::En,
+            upstream_prompt_locales: BTreeMap::new(),
+            rewrite_response_model: false,
+            enable_grpc_ingress: false,
+            latency_aware_routing: false,
+            enable_stream_broadcast: false,
+            stream_stats_upstream: false,
+            upstream_error_references: false,
+            synthetic_stream_chunk_chars: default_synthetic_stream_chunk_chars(),
+            synthetic_stream_interval_ms: default_synthetic_stream_interval_ms(),
+            moderation: None,
+            quality_retry: None,
+            conversation_traces: None,
+            output_postprocess: Vec::new(),
+            stop_sequence_emulation: StopSequenceEmulation::Auto,
+            sampling_normalization: SamplingNormalization::Clamp,
+            unsupported_params: UnsupportedParamPolicy::Drop,
+            dedup_native_tool_calls: true,
+            stream_tool_call_arguments: false,
+            strip_responses_reasoning: false,
+            sticky_hash: StickyHashConfig::default(),
+            inline_file_upload: None,
+            resumable_streams: None,
+            tool_definition_limits: None,
+            fc_parse_failures: None,
+            analytics: None,
+            request_priority: None,
+            forward_response_headers: Vec::new(),
+            long_poll: None,
+            memory_budget: None,
         }
     }
 }
 
+/// Extraction of inline base64 files into uploads on upstreams that accept
+/// file references (`anthropic`, `gemini`, `openai-responses`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InlineFileUploadConfig {
+    /// Smallest base64 payload, in bytes, that is uploaded.
+    #[serde(default = "default_inline_file_min_bytes")]
+    pub min_bytes: usize,
+    /// Upstream service names to apply to; every eligible upstream when empty.
+    #[serde(default)]
+    pub upstreams: Vec<String>,
+    /// Reuse the upload of identical content for this long. Gemini deletes
+    /// uploaded files after 48 hours.
+    #[serde(default = "default_inline_file_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+}
+
+fn default_inline_file_min_bytes() -> usize {
+    256 * 1024
+}
+
+fn default_inline_file_cache_ttl_secs() -> u64 {
+    24 * 3600
+}
+
+/// Which part of a request body sticky routing hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StickyHashStrategy {
+    /// The parsed body without `ignore_fields`, keys sorted and the
+    /// conversation cut to its first turn, so a retry that re-serializes the
+    /// body or changes an ignored knob keeps its upstream.
+    #[default]
+    Canonical,
+    /// The first 256 bytes of the raw conversation field, as sent. Skips
+    /// parsing the body.
+    PromptPrefix,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StickyHashConfig {
+    #[serde(default)]
+    pub strategy: StickyHashStrategy,
+    /// Body fields left out of the `canonical` hash; dotted paths reach into
+    /// nested objects (`metadata.timestamp`).
+    #[serde(default = "default_sticky_hash_ignore_fields")]
+    pub ignore_fields: Vec<String>,
+}
+
+impl Default for StickyHashConfig {
+    fn default() -> Self {
+        Self {
+            strategy: StickyHashStrategy::default(),
+            ignore_fields: default_sticky_hash_ignore_fields(),
+        }
+    }
+}
+
+fn default_sticky_hash_ignore_fields() -> Vec<String> {
+    [
+        "stream",
+        "stream_options",
+        "temperature",
+        "top_p",
+        "top_k",
+        "seed",
+        "max_tokens",
+        "max_completion_tokens",
+        "max_output_tokens",
+        "user",
+        "metadata.timestamp",
+        "metadata.created_at",
+        "generationConfig.temperature",
+        "generationConfig.topP",
+        "generationConfig.topK",
+        "generationConfig.maxOutputTokens",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
+}
+
+/// How the `{tools_list}` placeholder renders the available tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolListStyle {
+    /// Numbered entries with per-parameter detail (the built-in prompt's format).
+    #[default]
+    Detailed,
+    /// One line per tool: name, parameter signature, and description.
+    Compact,
+    /// The tool specs as a pretty-printed JSON array.
+    Json,
+}
+
+/// Caps on tool definitions checked before they are rendered into the FC
+/// prompt. A limit of 0 is not enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolDefinitionLimits {
+    /// Most tools one request may declare.
+    #[serde(default)]
+    pub max_tools: usize,
+    /// Most bytes of one tool's serialized parameter schema.
+    #[serde(default)]
+    pub max_schema_bytes: usize,
+    /// Most bytes the rendered FC prompt may add to the request.
+    #[serde(default)]
+    pub max_prompt_bytes: usize,
+    /// What happens when `max_prompt_bytes` is exceeded. The other limits
+    /// always reject.
+    #[serde(default)]
+    pub on_exceed: ToolLimitAction,
+}
+
+/// Handling of a rendered FC prompt over `max_prompt_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolLimitAction {
+    /// Reject the request with a 400.
+    #[default]
+    Reject,
+    /// Shorten tool descriptions until the prompt fits; reject when even
+    /// empty descriptions do not.
+    TruncateDescriptions,
+}
+
+/// Which comes first when system messages are folded into the system prompt:
+/// the top-level prompt (`instructions`, Anthropic `system`, Gemini
+/// `system_instruction`) or the `developer`/`system` conversation messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemPromptPrecedence {
+    #[default]
+    TopLevelFirst,
+    MessagesFirst,
+}
+
+/// Language of the built-in FC prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptLocale {
+    #[default]
+    En,
+    Zh,
+    Ja,
+    Es,
+    /// Detect the language from the conversation text.
+    Auto,
+}
+
+/// A named FC prompt template.
+///
+/// `template` must contain `{tools_list}` and `{trigger_signal}`; any other
+/// `{name}` placeholder is filled from `variables`, which a selection may
+/// override.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PromptTemplateConfig {
+    pub template: String,
+    #[serde(default)]
+    pub tool_list_style: ToolListStyle,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+}
+
+/// Reference to a named prompt template plus variable overrides.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PromptTemplateSelection {
+    pub template: String,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+}
+
+/// A client-visible model that wraps a real model (or alias) with a fixed
+/// system prompt, default sampling parameters, and output post-processing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VirtualModelConfig {
+    pub name: String,
+    /// Model or alias the request is routed to.
+    pub model: String,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub defaults: VirtualModelDefaults,
+    #[serde(default)]
+    pub postprocess: Vec<PostprocessStep>,
+}
+
+/// Sampling parameters applied when the client request leaves them unset.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VirtualModelDefaults {
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+}
+
+/// One output post-processing step, applied in order to assistant text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PostprocessStep {
+    RegexReplace {
+        pattern: String,
+        #[serde(default)]
+        replacement: String,
+    },
+    /// Cut the output at the first occurrence of any marker.
+    TrimAt { markers: Vec<String> },
+}
+
+/// A client-visible model served by a cheap draft model, re-run on the
+/// verify model when the draft's answer fails `checks`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CascadeModelConfig {
+    pub name: String,
+    /// Model or alias tried first.
+    pub draft: String,
+    /// Model or alias serving the request when the draft fails.
+    pub verify: String,
+    #[serde(default)]
+    pub checks: CascadeChecks,
+}
+
+/// An ordered list of other models or aliases serving `model` when its own
+/// routes fail or are cooling down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FallbackChainConfig {
+    pub model: String,
+    /// Models or aliases tried in order after `model`, possibly on other
+    /// upstreams.
+    pub fallbacks: Vec<String>,
+}
+
+/// Checks a draft answer must pass; responses with tool calls always pass.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CascadeChecks {
+    /// Minimum characters of assistant text.
+    #[serde(default)]
+    pub min_chars: Option<usize>,
+    /// Regexes that mark the text as a refusal.
+    #[serde(default)]
+    pub refusal_patterns: Vec<String>,
+    /// The text must parse as JSON.
+    #[serde(default)]
+    pub require_json: bool,
+}
+
+/// Overrides for one FC error retry attempt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FcRetryAttemptConfig {
+    /// Retry prompt for this attempt, e.g. a more forceful one; falls back to
+    /// `fc_error_retry_prompt_template`.
+    #[serde(default)]
+    pub prompt_template: Option<String>,
+    /// Sampling temperature for this attempt.
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// Send this attempt to another upstream serving the model, when there
+    /// is one.
+    #[serde(default)]
+    pub alternate_upstream: bool,
+}
+
+/// Re-run requests whose non-streaming answer is empty, a bare refusal, or
+/// reports zero output tokens on the next failover candidate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QualityRetryConfig {
+    /// Retries before the last answer is returned as it is.
+    #[serde(default = "default_quality_max_retries")]
+    pub max_retries: u32,
+    /// Regexes that mark the answer as a refusal when they match the whole
+    /// trimmed text.
+    #[serde(default)]
+    pub refusal_patterns: Vec<String>,
+}
+
+fn default_quality_max_retries() -> u32 {
+    1
+}
+
+/// Record every turn of a conversation, keyed by its sticky session hash, for
+/// export via `GET /admin/traces/{session}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConversationTraceConfig {
+    /// Conversations kept; the least recently updated one is dropped first.
+    #[serde(default = "default_trace_max_conversations")]
+    pub max_conversations: usize,
+    /// Turns kept per conversation; the oldest are dropped first.
+    #[serde(default = "default_trace_max_turns")]
+    pub max_turns: usize,
+    /// Forget a conversation this long after its last turn.
+    #[serde(default = "default_trace_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+fn default_trace_max_conversations() -> usize {
+    256
+}
+
+fn default_trace_max_turns() -> usize {
+    200
+}
+
+fn default_trace_ttl_secs() -> u64 {
+    3600
+}
+
+/// Replay buffers behind `features.resumable_streams`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResumableStreamConfig {
+    /// Reconnection delay advertised in a leading `retry:` field, in
+    /// milliseconds; `0` omits the field.
+    #[serde(default = "default_resumable_retry_ms")]
+    pub retry_ms: u64,
+    /// Frames kept per stream; the oldest are dropped first.
+    #[serde(default = "default_resumable_buffer_frames")]
+    pub buffer_frames: usize,
+    /// Keep a finished stream's frames this long for late reconnects.
+    #[serde(default = "default_resumable_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+/// Operations behind `features.long_poll`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LongPollConfig {
+    /// Answer with an operation id once a request has run this long, in
+    /// milliseconds.
+    #[serde(default = "default_long_poll_threshold_ms")]
+    pub threshold_ms: u64,
+    /// Hold a poll of an unfinished operation this long before answering
+    /// with a progress heartbeat, in milliseconds.
+    #[serde(default = "default_long_poll_wait_ms")]
+    pub poll_wait_ms: u64,
+    /// Keep a finished operation's result this long.
+    #[serde(default = "default_long_poll_result_ttl_secs")]
+    pub result_ttl_secs: u64,
+}
+
+fn default_long_poll_threshold_ms() -> u64 {
+    20_000
+}
+
+fn default_long_poll_wait_ms() -> u64 {
+    25_000
+}
+
+fn default_long_poll_result_ttl_secs() -> u64 {
+    600
+}
+
+/// Process-wide buffer budget behind `features.memory_budget`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryBudgetConfig {
+    /// Bytes stream buffers may hold before new streams are refused.
+    pub max_buffered_bytes: usize,
+}
+
+fn default_resumable_retry_ms() -> u64 {
+    3000
+}
+
+fn default_resumable_buffer_frames() -> usize {
+    2048
+}
+
+fn default_resumable_ttl_secs() -> u64 {
+    60
+}
+
+/// Samples of unparseable tool blocks served by the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FcParseFailureConfig {
+    /// Samples kept; the oldest are dropped first.
+    #[serde(default = "default_fc_failure_max_samples")]
+    pub max_samples: usize,
+    /// Leading bytes of the failed buffer kept per sample.
+    #[serde(default = "default_fc_failure_sample_bytes")]
+    pub sample_bytes: usize,
+}
+
+fn default_fc_failure_max_samples() -> usize {
+    64
+}
+
+fn default_fc_failure_sample_bytes() -> usize {
+    1024
+}
+
+/// SQLite sink for request analytics, queried via `GET /admin/analytics`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnalyticsConfig {
+    /// Database file, created when missing.
+    pub path: String,
+    /// Interval between batched writes of recorded requests.
+    #[serde(default = "default_analytics_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Delete rows older than this many days; 0 keeps everything.
+    #[serde(default = "default_analytics_retention_days")]
+    pub retention_days: u64,
+    /// Client-requested model name -> USD prices used for per-key spend.
+    #[serde(default)]
+    pub prices: BTreeMap<String, ModelPrice>,
+}
+
+/// Who may ask for `x-priority: high` and how many model requests run at once.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequestPriorityConfig {
+    /// Client keys granted high priority; other keys asking for it are served
+    /// at normal priority.
+    #[serde(default)]
+    pub high_priority_keys: Vec<String>,
+    /// Queue model requests beyond this many in flight and admit waiting
+    /// requests highest priority first; unlimited when absent.
+    #[serde(default)]
+    pub max_concurrent_requests: Option<usize>,
+}
+
+/// Response headers never forwarded from an upstream: credentials, cookies,
+/// and headers describing the upstream connection or body encoding.
+pub const UNFORWARDABLE_RESPONSE_HEADERS: &[&str] = &[
+    "authorization",
+    "connection",
+    "content-encoding",
+    "content-length",
+    "content-type",
+    "cookie",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "set-cookie",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "www-authenticate",
+];
+
+/// A `features.forward_response_headers` entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForwardResponseHeaderConfig {
+    /// Lowercase header name; a trailing `*` matches any suffix, e.g.
+    /// `x-ratelimit-*`.
+    pub name: String,
+    /// Name sent to the client; kept as-is when absent. Ends in `*` exactly
+    /// when `name` does, which takes the matched suffix.
+    #[serde(default)]
+    pub rename: Option<String>,
+}
+
+/// USD price of one model per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+fn default_analytics_flush_interval_ms() -> u64 {
+    1000
+}
+
+fn default_analytics_retention_days() -> u64 {
+    30
+}
+
+/// Request-based routing override, evaluated in order before model routing.
+///
+/// The first rule whose matchers all hold forces its `upstream` or `model`
+/// and sends the request through its `proxy`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoutingRuleConfig {
+    pub name: String,
+    #[serde(rename = "match")]
+    pub matcher: RoutingRuleMatch,
+    /// Send matching requests only to this upstream (by name).
+    #[serde(default)]
+    pub upstream: Option<String>,
+    /// Route matching requests as this model or alias group.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Proxy for matching requests (`http`, `https`, `socks5`, or `socks5h`
+    /// for DNS through the proxy), replacing the upstream's own proxies.
+    #[serde(default)]
+    pub proxy: Option<String>,
+}
+
+/// Conditions of a routing rule; every configured one must hold.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoutingRuleMatch {
+    /// Path prefix below the server base path, stripped before routing
+    /// (e.g. `/research` serves `/research/v1/chat/completions`).
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    #[serde(default)]
+    pub header: Option<HeaderMatch>,
+    /// Requested model or alias. Known only once the body is read, so rules
+    /// matching on it can only select a `proxy`.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// Exact match on a request header value; the name is case-insensitive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderMatch {
+    pub name: String,
+    pub value: String,
+}
+
 /// Top-level application configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
@@ -277,8 +1568,88 @@ pub struct AppConfig {
     pub server: ServerConfig,
     pub upstream_services: Vec<UpstreamServiceConfig>,
     pub client_authentication: ClientAuthConfig,
+    /// Replaces `client_authentication.admin_keys` for the admin API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_authentication: Option<AdminAuthConfig>,
     #[serde(default)]
     pub features: FeaturesConfig,
+    #[serde(default)]
+    pub virtual_models: Vec<VirtualModelConfig>,
+    #[serde(default)]
+    pub routing_rules: Vec<RoutingRuleConfig>,
+    #[serde(default)]
+    pub cascade_models: Vec<CascadeModelConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_chains: Vec<FallbackChainConfig>,
+    /// Logical tenants served from this process under their own base paths.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tenants: Vec<TenantConfig>,
+}
+
+/// A tenant: its own base path, client keys, upstream subset, and feature
+/// overrides, served with a separate routing table.
+///
+/// Tenants do not inherit `virtual_models`, `routing_rules`,
+/// `cascade_models`, `fallback_chains`, `key_model_maps`, or admin access;
+/// the admin API is only served under `server.base_path`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfig {
+    pub name: String,
+    /// Absolute path prefix, e.g. `/team-a`, independent of `server.base_path`.
+    pub base_path: String,
+    pub allowed_keys: Vec<String>,
+    /// Names of the upstream services this tenant routes to; empty means all.
+    #[serde(default)]
+    pub upstream_services: Vec<String>,
+    /// `features` settings that replace the top-level ones for this tenant.
+    #[serde(default, skip_serializing_if = "serde_yaml::Mapping::is_empty")]
+    pub features: serde_yaml::Mapping,
+}
+
+impl AppConfig {
+    /// The config `tenant`'s requests are served with.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::Yaml`] when the tenant's `features` overrides do
+    /// not fit [`FeaturesConfig`].
+    pub fn tenant_config(&self, tenant: &TenantConfig) -> Result<AppConfig, ConfigError> {
+        let mut features = serde_yaml::to_value(&self.features)?;
+        if let serde_yaml::Value::Mapping(features) = &mut features {
+            for (key, value) in &tenant.features {
+                features.insert(key.clone(), value.clone());
+            }
+        }
+        let upstream_services = self
+            .upstream_services
+            .iter()
+            .filter(|upstream| {
+                tenant.upstream_services.is_empty()
+                    || tenant.upstream_services.contains(&upstream.name)
+            })
+            .cloned()
+            .collect();
+        Ok(AppConfig {
+            server: ServerConfig {
+                base_path: tenant.base_path.clone(),
+                ..self.server.clone()
+            },
+            upstream_services,
+            client_authentication: ClientAuthConfig {
+                allowed_keys: tenant.allowed_keys.clone(),
+                admin_keys: Vec::new(),
+                key_model_maps: Vec::new(),
+            },
+            admin_authentication: None,
+            features: serde_yaml::from_value(features)?,
+            virtual_models: Vec::new(),
+            routing_rules: Vec::new(),
+            cascade_models: Vec::new(),
+            fallback_chains: Vec::new(),
+            tenants: Vec::new(),
+        })
+    }
 }
 
 /// Load configuration from a YAML file and validate it.
@@ -289,11 +1660,65 @@ pub struct AppConfig {
 /// when parsing fails, or [`ConfigError::Validation`] when semantic validation fails.
 pub fn load_config(path: &str) -> Result<AppConfig, ConfigError> {
     let contents = std::fs::read_to_string(path)?;
-    let config: AppConfig = serde_yaml::from_str(&contents)?;
-    validate_config(&config)?;
+    parse_config(&contents)
+}
+
+/// Load a configuration file like [`load_config`], returning the deprecated
+/// field names it used instead of logging them.
+///
+/// # Errors
+///
+/// Returns the same errors as [`load_config`].
+pub fn load_config_with_deprecations(
+    path: &str,
+) -> Result<(AppConfig, Vec<Deprecation>), ConfigError> {
+    let contents = std::fs::read_to_string(path)?;
+    parse_config_with_deprecations(&contents)
+}
+
+/// Parse and validate configuration from YAML text.
+///
+/// # Errors
+///
+/// Returns [`ConfigError::Yaml`] when parsing fails or
+/// [`ConfigError::Validation`] when semantic validation fails.
+///
+/// Deprecated field names are accepted and logged as warnings.
+pub fn parse_config(contents: &str) -> Result<AppConfig, ConfigError> {
+    let (config, deprecations) = parse_config_with_deprecations(contents)?;
+    for deprecation in &deprecations {
+        tracing::warn!(
+            field = %deprecation.field,
+            replacement = %deprecation.replacement,
+            removed_in = deprecation.removed_in,
+            ignored = deprecation.ignored,
+            "deprecated config field: {deprecation}"
+        );
+    }
     Ok(config)
 }
 
+/// Parse and validate configuration from YAML text, returning the deprecated
+/// field names it used.
+///
+/// # Errors
+///
+/// Returns the same errors as [`parse_config`].
+pub fn parse_config_with_deprecations(
+    contents: &str,
+) -> Result<(AppConfig, Vec<Deprecation>), ConfigError> {
+    let mut document: serde_yaml::Value = serde_yaml::from_str(contents)?;
+    let deprecations = migrate_value(&mut document);
+    // Without deprecated names, parse the text itself so errors keep their location.
+    let config: AppConfig = if deprecations.is_empty() {
+        serde_yaml::from_str(contents)?
+    } else {
+        serde_yaml::from_value(document)?
+    };
+    validate_config(&config)?;
+    Ok((config, deprecations))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
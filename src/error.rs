@@ -12,10 +12,26 @@ pub enum CanonicalError {
     Auth(String),
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
+    #[error("Request too large: {0}")]
+    RequestTooLarge(String),
     #[error("Upstream error: status={status}, message={message}")]
-    Upstream { status: u16, message: String },
+    Upstream {
+        status: u16,
+        message: String,
+        /// Upstream `retry-after` hint in seconds, forwarded to the client.
+        retry_after: Option<u64>,
+    },
     #[error("Transport error: {0}")]
     Transport(String),
+    #[error("Upstream timeout: {0}")]
+    Timeout(String),
+    /// The proxy itself turned the request away until load drops.
+    #[error("Overloaded: {message}")]
+    Overloaded {
+        message: String,
+        /// Seconds the client should wait before retrying.
+        retry_after: Option<u64>,
+    },
     #[error("Protocol translation error: {0}")]
     Translation(String),
     #[error("FC parse error: {0}")]
@@ -30,7 +46,11 @@ pub enum ErrorCategory {
     InvalidRequest,
     Authentication,
     Permission,
+    NotFound,
+    RequestTooLarge,
     RateLimit,
+    Timeout,
+    Overloaded,
     ServerError,
     Unknown,
 }
@@ -39,10 +59,14 @@ pub enum ErrorCategory {
 #[must_use]
 pub fn category_from_upstream_status(status: u16) -> ErrorCategory {
     match status {
-        400 => ErrorCategory::InvalidRequest,
+        400 | 422 => ErrorCategory::InvalidRequest,
         401 => ErrorCategory::Authentication,
         403 => ErrorCategory::Permission,
+        404 => ErrorCategory::NotFound,
+        408 | 504 => ErrorCategory::Timeout,
+        413 => ErrorCategory::RequestTooLarge,
         429 => ErrorCategory::RateLimit,
+        503 | 529 => ErrorCategory::Overloaded,
         500..=599 => ErrorCategory::ServerError,
         _ => ErrorCategory::Unknown,
     }
@@ -53,7 +77,10 @@ impl CanonicalError {
     pub fn category(&self) -> ErrorCategory {
         match self {
             CanonicalError::InvalidRequest(_) => ErrorCategory::InvalidRequest,
+            CanonicalError::RequestTooLarge(_) => ErrorCategory::RequestTooLarge,
             CanonicalError::Auth(_) => ErrorCategory::Authentication,
+            CanonicalError::Timeout(_) => ErrorCategory::Timeout,
+            CanonicalError::Overloaded { .. } => ErrorCategory::Overloaded,
             CanonicalError::Config(_)
             | CanonicalError::Transport(_)
             | CanonicalError::Translation(_)
@@ -62,18 +89,37 @@ impl CanonicalError {
             CanonicalError::Upstream { status, .. } => category_from_upstream_status(*status),
         }
     }
+
+    /// Seconds the client should wait before retrying, when the upstream or
+    /// the proxy said so.
+    #[must_use]
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            CanonicalError::Upstream { retry_after, .. }
+            | CanonicalError::Overloaded { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
 // Category -> HTTP status code
 // ---------------------------------------------------------------------------
 
-fn http_status_for_category(cat: ErrorCategory) -> http::StatusCode {
+fn http_status_for_category(cat: ErrorCategory, ingress: IngressApi) -> http::StatusCode {
     match cat {
         ErrorCategory::InvalidRequest => http::StatusCode::BAD_REQUEST,
         ErrorCategory::Authentication => http::StatusCode::UNAUTHORIZED,
         ErrorCategory::Permission => http::StatusCode::FORBIDDEN,
+        ErrorCategory::NotFound => http::StatusCode::NOT_FOUND,
+        ErrorCategory::RequestTooLarge => http::StatusCode::PAYLOAD_TOO_LARGE,
         ErrorCategory::RateLimit => http::StatusCode::TOO_MANY_REQUESTS,
+        ErrorCategory::Timeout => http::StatusCode::GATEWAY_TIMEOUT,
+        // Anthropic clients key their overload backoff off the non-standard 529.
+        ErrorCategory::Overloaded if ingress == IngressApi::Anthropic => {
+            http::StatusCode::from_u16(529).unwrap_or(http::StatusCode::SERVICE_UNAVAILABLE)
+        }
+        ErrorCategory::Overloaded => http::StatusCode::SERVICE_UNAVAILABLE,
         ErrorCategory::ServerError | ErrorCategory::Unknown => {
             http::StatusCode::INTERNAL_SERVER_ERROR
         }
@@ -91,7 +137,7 @@ pub fn format_error(
     ingress: IngressApi,
 ) -> (http::StatusCode, serde_json::Value) {
     let cat = err.category();
-    let status = http_status_for_category(cat);
+    let status = http_status_for_category(cat, ingress);
     let message = err.to_string();
 
     let body = match ingress {
@@ -108,17 +154,168 @@ pub fn format_error(
 // ---------------------------------------------------------------------------
 
 /// Convert a `CanonicalError` into an axum response for a specific ingress.
+///
+/// Upstream `retry-after` hints are propagated as a `retry-after` header.
+#[cfg(feature = "server")]
 #[must_use]
 pub fn into_axum_response(err: &CanonicalError, ingress: IngressApi) -> axum::response::Response {
     use axum::response::IntoResponse;
     let (status, body) = format_error(err, ingress);
-    (status, axum::Json(body)).into_response()
+    let mut response = (status, axum::Json(body)).into_response();
+    if let Some(retry_after) = err.retry_after() {
+        response.headers_mut().insert(
+            http::header::RETRY_AFTER,
+            http::HeaderValue::from(retry_after),
+        );
+    }
+    response
 }
 
 /// Default `IntoResponse` implementation uses `OpenAiChat` as the fallback ingress.
 /// Real handlers should call [`into_axum_response`] with the correct ingress instead.
+#[cfg(feature = "server")]
 impl axum::response::IntoResponse for CanonicalError {
     fn into_response(self) -> axum::response::Response {
         into_axum_response(&self, IngressApi::OpenAiChat)
     }
 }
+
+// ---------------------------------------------------------------------------
+// gRPC integration
+// ---------------------------------------------------------------------------
+
+/// Convert a `CanonicalError` into the status that ends a gRPC call.
+///
+/// Upstream `retry-after` hints are propagated as `retry-after` metadata.
+#[cfg(feature = "grpc")]
+#[must_use]
+pub fn into_grpc_status(err: &CanonicalError) -> tonic::Status {
+    let code = match err.category() {
+        ErrorCategory::InvalidRequest => tonic::Code::InvalidArgument,
+        ErrorCategory::Authentication => tonic::Code::Unauthenticated,
+        ErrorCategory::Permission => tonic::Code::PermissionDenied,
+        ErrorCategory::NotFound => tonic::Code::NotFound,
+        ErrorCategory::RequestTooLarge | ErrorCategory::RateLimit => tonic::Code::ResourceExhausted,
+        ErrorCategory::Timeout => tonic::Code::DeadlineExceeded,
+        ErrorCategory::Overloaded => tonic::Code::Unavailable,
+        ErrorCategory::ServerError | ErrorCategory::Unknown => tonic::Code::Internal,
+    };
+    let mut status = tonic::Status::new(code, err.to_string());
+    if let Some(retry_after) = err.retry_after() {
+        status.metadata_mut().insert(
+            "retry-after",
+            tonic::metadata::MetadataValue::from(retry_after),
+        );
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstream(status: u16, retry_after: Option<u64>) -> CanonicalError {
+        CanonicalError::Upstream {
+            status,
+            message: "upstream failed".to_string(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn test_format_error_rate_limit_shapes() {
+        let err = upstream(429, Some(7));
+        let (status, body) = format_error(&err, IngressApi::OpenAiChat);
+        assert_eq!(status, http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+        assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+
+        let (_, body) = format_error(&err, IngressApi::Anthropic);
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+
+        let (_, body) = format_error(&err, IngressApi::Gemini);
+        assert_eq!(body["error"]["status"], "RESOURCE_EXHAUSTED");
+        assert_eq!(body["error"]["code"], 429);
+    }
+
+    #[test]
+    fn test_format_error_overloaded_uses_529_for_anthropic() {
+        let err = upstream(529, None);
+        let (status, body) = format_error(&err, IngressApi::Anthropic);
+        assert_eq!(status.as_u16(), 529);
+        assert_eq!(body["error"]["type"], "overloaded_error");
+
+        let (status, body) = format_error(&err, IngressApi::Gemini);
+        assert_eq!(status, http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["error"]["status"], "UNAVAILABLE");
+
+        let err = CanonicalError::Overloaded {
+            message: "over budget".to_string(),
+            retry_after: Some(1),
+        };
+        let (status, body) = format_error(&err, IngressApi::OpenAiChat);
+        assert_eq!(status, http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["error"]["message"], "Overloaded: over budget");
+        assert_eq!(err.retry_after(), Some(1));
+    }
+
+    #[test]
+    fn test_format_error_timeout_shapes() {
+        let err = CanonicalError::Timeout("operation timed out".to_string());
+        let (status, body) = format_error(&err, IngressApi::OpenAiResponses);
+        assert_eq!(status, http::StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["error"]["code"], "timeout");
+
+        let (_, body) = format_error(&err, IngressApi::Anthropic);
+        assert_eq!(body["error"]["type"], "timeout_error");
+
+        let (_, body) = format_error(&err, IngressApi::Gemini);
+        assert_eq!(body["error"]["status"], "DEADLINE_EXCEEDED");
+    }
+
+    #[test]
+    fn test_format_error_validation_and_auth_shapes() {
+        let err = CanonicalError::InvalidRequest("missing model".to_string());
+        let (status, body) = format_error(&err, IngressApi::Anthropic);
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+
+        let err = CanonicalError::Auth("bad key".to_string());
+        let (status, body) = format_error(&err, IngressApi::Gemini);
+        assert_eq!(status, http::StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"]["status"], "UNAUTHENTICATED");
+
+        let (_, body) = format_error(&upstream(403, None), IngressApi::Anthropic);
+        assert_eq!(body["error"]["type"], "permission_error");
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_into_axum_response_propagates_retry_after() {
+        let response = into_axum_response(&upstream(429, Some(12)), IngressApi::Anthropic);
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers().get(http::header::RETRY_AFTER),
+            Some(&http::HeaderValue::from_static("12"))
+        );
+
+        let response = into_axum_response(&upstream(500, None), IngressApi::OpenAiChat);
+        assert!(response.headers().get(http::header::RETRY_AFTER).is_none());
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn test_into_grpc_status_maps_categories() {
+        let status = into_grpc_status(&upstream(429, Some(12)));
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "12");
+
+        let status = into_grpc_status(&upstream(529, None));
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert!(status.metadata().get("retry-after").is_none());
+
+        let status = into_grpc_status(&CanonicalError::Auth("bad key".to_string()));
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        assert_eq!(status.message(), "Auth error: bad key");
+    }
+}
@@ -66,7 +66,10 @@ pub fn allow_auto_inject_fallback(
 /// 2) on capability error, retry once with FC inject mode
 #[must_use]
 pub fn should_auto_fallback_to_inject(err: &CanonicalError) -> bool {
-    let crate::error::CanonicalError::Upstream { status, message } = err else {
+    let crate::error::CanonicalError::Upstream {
+        status, message, ..
+    } = err
+    else {
         return false;
     };
     if !matches!(*status, 400 | 404 | 422 | 501) {
@@ -107,6 +110,7 @@ pub fn should_auto_fallback_to_inject(err: &CanonicalError) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{PathStyle, StreamSupport, ToolSchemaValidation, UpstreamAuthConfig};
 
     fn make_upstream(fc_mode: FcMode) -> UpstreamServiceConfig {
         UpstreamServiceConfig {
@@ -122,6 +126,17 @@ mod tests {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         }
     }
 
@@ -177,6 +192,7 @@ mod tests {
         let err = CanonicalError::Upstream {
             status: 400,
             message: "This model does not support tools".to_string(),
+            retry_after: None,
         };
         assert!(should_auto_fallback_to_inject(&err));
     }
@@ -186,6 +202,7 @@ mod tests {
         let err = CanonicalError::Upstream {
             status: 500,
             message: "This model does not support tools".to_string(),
+            retry_after: None,
         };
         assert!(!should_auto_fallback_to_inject(&err));
     }
@@ -195,6 +212,7 @@ mod tests {
         let err = CanonicalError::Upstream {
             status: 400,
             message: "rate limit exceeded".to_string(),
+            retry_after: None,
         };
         assert!(!should_auto_fallback_to_inject(&err));
     }
@@ -2,6 +2,8 @@ use std::sync::LazyLock;
 
 use memchr::{memchr, memmem};
 
+use crate::stream::memory_budget::BufferCharge;
+
 // Streaming function-call trigger detector.
 //
 // Ports the Python `StreamingFunctionCallDetector` to Rust. The detector is a
@@ -62,6 +64,8 @@ pub struct StreamingFcDetector {
     max_buffer_size: usize,
     /// Whether `<function_calls>` opening tag has appeared after trigger.
     saw_function_calls_open: bool,
+    /// Share of the process-wide buffer budget held by `buffer`.
+    charge: BufferCharge,
 }
 
 const THINK_OPEN: &str = "<think>";
@@ -172,6 +176,7 @@ impl StreamingFcDetector {
             think_depth: 0,
             max_buffer_size: DEFAULT_MAX_BUFFER,
             saw_function_calls_open: false,
+            charge: BufferCharge::default(),
         }
     }
 
@@ -186,6 +191,12 @@ impl StreamingFcDetector {
         self.trigger_signal
     }
 
+    /// Text buffered so far; from the trigger onward once it was found.
+    #[must_use]
+    pub fn buffered(&self) -> &str {
+        &self.buffer
+    }
+
     // -- public API ---------------------------------------------------------
 
     /// Feed a new text delta into the detector and obtain the resulting action.
@@ -194,14 +205,16 @@ impl StreamingFcDetector {
             return DetectorAction::Buffer;
         }
 
-        match self.state {
+        let action = match self.state {
             DetectorState::Detecting => self.feed_detecting(text),
             DetectorState::ToolParsing => self.feed_tool_parsing(text),
             DetectorState::Completed => {
                 // After completion, pass everything through.
                 DetectorAction::PassThrough(text.to_string())
             }
-        }
+        };
+        self.charge.track(self.buffer.capacity());
+        action
     }
 
     /// Feed an owned text delta into the detector.
@@ -223,11 +236,13 @@ impl StreamingFcDetector {
             return DetectorAction::PassThrough(text);
         }
 
-        match self.state {
+        let action = match self.state {
             DetectorState::Detecting => self.feed_detecting(&text),
             DetectorState::ToolParsing => self.feed_tool_parsing(&text),
             DetectorState::Completed => DetectorAction::PassThrough(text),
-        }
+        };
+        self.charge.track(self.buffer.capacity());
+        action
     }
 
     /// Call when the stream ends. Returns any remaining buffered content.
@@ -236,6 +251,7 @@ impl StreamingFcDetector {
             return None;
         }
         let remaining = std::mem::take(&mut self.buffer);
+        self.charge.track(0);
         Some(remaining)
     }
 
@@ -6,7 +6,7 @@ use crate::protocol::canonical::{
 use std::sync::Arc;
 
 use super::preprocess::preprocess_messages_owned;
-use super::prompt;
+use super::prompt::{self, PromptTemplate};
 
 /// Prepare a canonical request for FC injection.
 ///
@@ -21,8 +21,9 @@ use super::prompt;
 pub fn apply_fc_inject(
     canonical: &mut CanonicalRequest,
     features: &FeaturesConfig,
+    prompt_template: Option<&PromptTemplate>,
 ) -> Result<(), CanonicalError> {
-    let _ = apply_fc_inject_take_tools(canonical, features)?;
+    let _ = apply_fc_inject_take_tools(canonical, features, prompt_template)?;
     Ok(())
 }
 
@@ -37,6 +38,7 @@ pub fn apply_fc_inject(
 pub fn apply_fc_inject_take_tools(
     canonical: &mut CanonicalRequest,
     features: &FeaturesConfig,
+    prompt_template: Option<&PromptTemplate>,
 ) -> Result<Arc<[CanonicalToolSpec]>, CanonicalError> {
     if request_prefers_structured_output(canonical) {
         return Ok(Arc::<[CanonicalToolSpec]>::from([]));
@@ -51,7 +53,7 @@ pub fn apply_fc_inject_take_tools(
     let fc_prompt = prompt::generate_fc_prompt(
         saved_tools.as_ref(),
         &canonical.tool_choice,
-        features.prompt_template.as_deref(),
+        prompt_template,
     )?;
 
     canonical.system_prompt = Some(match &canonical.system_prompt {
@@ -60,7 +62,7 @@ pub fn apply_fc_inject_take_tools(
     });
 
     let messages = std::mem::take(&mut canonical.messages);
-    canonical.messages = preprocess_messages_owned(messages, features.convert_developer_to_system);
+    canonical.messages = preprocess_messages_owned(messages, features.fold_system_messages);
 
     canonical.tool_choice = CanonicalToolChoice::None;
 
@@ -166,7 +168,7 @@ mod tests {
         };
 
         let features = FeaturesConfig::default();
-        apply_fc_inject(&mut request, &features).unwrap();
+        apply_fc_inject(&mut request, &features, None).unwrap();
 
         let sp = request.system_prompt.as_ref().unwrap();
         assert!(sp.starts_with("You are helpful.\n"));
@@ -198,7 +200,7 @@ mod tests {
         };
 
         let features = FeaturesConfig::default();
-        apply_fc_inject(&mut request, &features).unwrap();
+        apply_fc_inject(&mut request, &features, None).unwrap();
 
         assert!(request.system_prompt.is_some());
         assert!(request.tools.is_empty());
@@ -227,7 +229,7 @@ mod tests {
         };
 
         let features = FeaturesConfig::default();
-        let saved_tools = apply_fc_inject_take_tools(&mut request, &features).unwrap();
+        let saved_tools = apply_fc_inject_take_tools(&mut request, &features, None).unwrap();
 
         assert!(saved_tools.is_empty());
         assert_eq!(request.system_prompt.as_deref(), Some("base"));
@@ -263,7 +265,7 @@ mod tests {
         };
 
         let features = FeaturesConfig::default();
-        let saved_tools = apply_fc_inject_take_tools(&mut request, &features).unwrap();
+        let saved_tools = apply_fc_inject_take_tools(&mut request, &features, None).unwrap();
 
         assert!(saved_tools.is_empty());
         assert_eq!(request.system_prompt.as_deref(), Some("base"));
@@ -296,7 +298,7 @@ mod tests {
         };
 
         let features = FeaturesConfig::default();
-        let saved_tools = apply_fc_inject_take_tools(&mut request, &features).unwrap();
+        let saved_tools = apply_fc_inject_take_tools(&mut request, &features, None).unwrap();
 
         assert!(saved_tools.is_empty());
         assert_eq!(request.system_prompt.as_deref(), Some("base"));
@@ -0,0 +1,195 @@
+//! Size limits on tool definitions before they are rendered into the FC prompt.
+
+use std::borrow::Cow;
+
+use crate::config::{ToolDefinitionLimits, ToolLimitAction};
+use crate::error::CanonicalError;
+use crate::protocol::canonical::CanonicalToolSpec;
+
+const TRUNCATION_MARKER: &str = "...";
+
+/// Render the FC prompt for `tools` with `render`, enforcing `limits`.
+///
+/// Tool count and per-tool schema size always reject. A prompt over
+/// `max_prompt_bytes` either rejects or, with
+/// [`ToolLimitAction::TruncateDescriptions`], is re-rendered with every
+/// description cut to the longest length that fits.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::InvalidRequest`] when a limit is exceeded, and
+/// any error from `render`.
+pub fn render_within_limits(
+    tools: &[CanonicalToolSpec],
+    limits: &ToolDefinitionLimits,
+    render: impl Fn(&[CanonicalToolSpec]) -> Result<String, CanonicalError>,
+) -> Result<String, CanonicalError> {
+    if limits.max_tools > 0 && tools.len() > limits.max_tools {
+        return Err(CanonicalError::InvalidRequest(format!(
+            "Request declares {} tools; at most {} are allowed",
+            tools.len(),
+            limits.max_tools
+        )));
+    }
+    if limits.max_schema_bytes > 0 {
+        for tool in tools {
+            let schema_bytes = serde_json::to_vec(&tool.function.parameters).map_or(0, |v| v.len());
+            if schema_bytes > limits.max_schema_bytes {
+                return Err(CanonicalError::InvalidRequest(format!(
+                    "Parameter schema of tool '{}' is {schema_bytes} bytes; at most {} are allowed",
+                    tool.function.name, limits.max_schema_bytes
+                )));
+            }
+        }
+    }
+
+    let prompt = render(tools)?;
+    if limits.max_prompt_bytes == 0 || prompt.len() <= limits.max_prompt_bytes {
+        return Ok(prompt);
+    }
+    let too_large = || {
+        CanonicalError::InvalidRequest(format!(
+            "Tool definitions add {} bytes to the prompt; at most {} are allowed",
+            prompt.len(),
+            limits.max_prompt_bytes
+        ))
+    };
+    if limits.on_exceed == ToolLimitAction::Reject {
+        return Err(too_large());
+    }
+
+    // Binary search the longest description length that fits.
+    let longest = tools
+        .iter()
+        .filter_map(|tool| tool.function.description.as_deref())
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let mut best = None;
+    let (mut low, mut high) = (0, longest);
+    while low < high {
+        let cap = low + (high - low) / 2;
+        let candidate = render(&truncate_descriptions(tools, cap))?;
+        if candidate.len() <= limits.max_prompt_bytes {
+            best = Some(candidate);
+            low = cap + 1;
+        } else {
+            high = cap;
+        }
+    }
+    match best {
+        Some(prompt) => Ok(prompt),
+        None => Err(too_large()),
+    }
+}
+
+fn truncate_descriptions(tools: &[CanonicalToolSpec], cap: usize) -> Vec<CanonicalToolSpec> {
+    tools
+        .iter()
+        .map(|tool| {
+            let mut tool = tool.clone();
+            if let Some(description) = tool.function.description.as_mut() {
+                if let Cow::Owned(short) = truncate(description, cap) {
+                    *description = short;
+                }
+            }
+            tool
+        })
+        .collect()
+}
+
+fn truncate(text: &str, cap: usize) -> Cow<'_, str> {
+    if text.len() <= cap {
+        return Cow::Borrowed(text);
+    }
+    if cap == 0 {
+        return Cow::Owned(String::new());
+    }
+    let mut end = cap.saturating_sub(TRUNCATION_MARKER.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    Cow::Owned(format!("{}{TRUNCATION_MARKER}", &text[..end]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::canonical::CanonicalToolFunction;
+
+    fn tool(name: &str, description: &str) -> CanonicalToolSpec {
+        CanonicalToolSpec {
+            function: CanonicalToolFunction {
+                name: name.to_string(),
+                description: Some(description.to_string()),
+                parameters: serde_json::json!({"type": "object", "properties": {}}),
+            },
+        }
+    }
+
+    fn render(tools: &[CanonicalToolSpec]) -> Result<String, CanonicalError> {
+        Ok(tools
+            .iter()
+            .map(|tool| {
+                format!(
+                    "{}: {}\n",
+                    tool.function.name,
+                    tool.function.description.as_deref().unwrap_or("")
+                )
+            })
+            .collect())
+    }
+
+    fn limits(max_prompt_bytes: usize, on_exceed: ToolLimitAction) -> ToolDefinitionLimits {
+        ToolDefinitionLimits {
+            max_tools: 2,
+            max_schema_bytes: 64,
+            max_prompt_bytes,
+            on_exceed,
+        }
+    }
+
+    #[test]
+    fn test_count_and_schema_limits_reject() {
+        let tools = vec![tool("a", ""), tool("b", ""), tool("c", "")];
+        let err =
+            render_within_limits(&tools, &limits(0, ToolLimitAction::Reject), render).unwrap_err();
+        assert!(matches!(err, CanonicalError::InvalidRequest(msg) if msg.contains("3 tools")));
+
+        let mut big = tool("big", "");
+        big.function.parameters = serde_json::json!({"description": "x".repeat(100)});
+        let err = render_within_limits(
+            &[big],
+            &limits(0, ToolLimitAction::TruncateDescriptions),
+            render,
+        )
+        .unwrap_err();
+        assert!(matches!(err, CanonicalError::InvalidRequest(msg) if msg.contains("'big'")));
+    }
+
+    #[test]
+    fn test_prompt_limit_rejects_or_truncates_descriptions() {
+        let tools = vec![tool("a", &"long ".repeat(40)), tool("b", "short")];
+        assert!(
+            render_within_limits(&tools, &limits(80, ToolLimitAction::Reject), render).is_err()
+        );
+
+        let prompt = render_within_limits(
+            &tools,
+            &limits(80, ToolLimitAction::TruncateDescriptions),
+            render,
+        )
+        .unwrap();
+        assert!(prompt.len() <= 80, "{prompt}");
+        assert!(prompt.contains("long long"));
+        assert!(prompt.contains("..."));
+        assert!(prompt.contains("b: short"));
+
+        assert!(render_within_limits(
+            &tools,
+            &limits(4, ToolLimitAction::TruncateDescriptions),
+            render
+        )
+        .is_err());
+    }
+}
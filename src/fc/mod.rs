@@ -1,4 +1,5 @@
 pub mod detector;
+pub mod parse_failures;
 pub mod parser;
 pub mod prompt;
 pub mod retry;
@@ -6,8 +7,10 @@ pub mod validator;
 
 mod action;
 mod inject;
+mod limits;
 mod postprocess;
 mod preprocess;
+mod templates;
 
 pub use action::{
     allow_auto_inject_fallback, decide_fc_action, get_fc_mode, should_auto_fallback_to_inject,
@@ -18,4 +21,5 @@ pub use postprocess::{
     apply_fc_postprocess_once, extract_response_text, extract_response_text_if_trigger,
     process_fc_response, response_text_contains_trigger, FcResult,
 };
-pub use preprocess::{preprocess_messages, preprocess_messages_owned};
+pub use preprocess::{normalize_system_messages, preprocess_messages, preprocess_messages_owned};
+pub use templates::{unresolved_placeholders, PromptTemplates};
@@ -0,0 +1,243 @@
+//! Samples of injected tool blocks that failed to parse.
+//!
+//! With `features.fc_parse_failures` set, every streamed tool block that the
+//! D5 fallback flushes to the client as text is counted per provider and
+//! model, and its leading bytes are kept, redacted, in a ring buffer.
+//! `GET /admin/fc-parse-failures` lists the samples and
+//! `toolify_fc_parse_failures_total` on `GET /admin/metrics` counts them, so
+//! prompt templates can be fixed for the models that break them.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde_json::{json, Value};
+
+use crate::config::FcParseFailureConfig;
+use crate::protocol::canonical::ProviderKind;
+
+const CDATA_OPEN: &str = "<![CDATA[";
+const CDATA_CLOSE: &str = "]]>";
+const MASK: char = '*';
+
+/// Failure count for one provider and model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FcParseFailureCount {
+    pub provider: &'static str,
+    pub model: String,
+    pub count: u64,
+}
+
+/// Counted failures and the most recent redacted samples.
+pub struct FcParseFailures {
+    max_samples: usize,
+    sample_bytes: usize,
+    counts: Mutex<FxHashMap<(ProviderKind, Arc<str>), u64>>,
+    samples: Mutex<VecDeque<Value>>,
+}
+
+impl FcParseFailures {
+    #[must_use]
+    pub fn new(config: &FcParseFailureConfig) -> Self {
+        Self {
+            max_samples: config.max_samples,
+            sample_bytes: config.sample_bytes,
+            counts: Mutex::new(FxHashMap::default()),
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// A recorder for the tool blocks of one response.
+    #[must_use]
+    pub fn sink(self: &Arc<Self>, provider: ProviderKind, model: &str) -> FcParseFailureSink {
+        FcParseFailureSink {
+            failures: Arc::clone(self),
+            provider,
+            model: Arc::from(model),
+        }
+    }
+
+    /// Count a tool block from `provider` for a `model` request that failed
+    /// to parse, keeping a redacted sample of its start.
+    pub fn record(&self, provider: ProviderKind, model: &Arc<str>, buffer: &str) {
+        *self
+            .counts
+            .lock()
+            .entry((provider, Arc::clone(model)))
+            .or_insert(0) += 1;
+        if self.max_samples == 0 {
+            return;
+        }
+
+        let sample = json!({
+            "ts_ms": unix_now_ms(),
+            "provider": provider_label(provider),
+            "model": &**model,
+            "bytes": buffer.len(),
+            "truncated": buffer.len() > self.sample_bytes,
+            "sample": redact(head(buffer, self.sample_bytes)),
+        });
+        let mut samples = self.samples.lock();
+        while samples.len() >= self.max_samples {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Kept samples, most recent first.
+    #[must_use]
+    pub fn samples(&self) -> Vec<Value> {
+        self.samples.lock().iter().rev().cloned().collect()
+    }
+
+    /// Failure counts, ordered by provider and model.
+    #[must_use]
+    pub fn counts(&self) -> Vec<FcParseFailureCount> {
+        let mut counts: Vec<FcParseFailureCount> = self
+            .counts
+            .lock()
+            .iter()
+            .map(|((provider, model), count)| FcParseFailureCount {
+                provider: provider_label(*provider),
+                model: model.to_string(),
+                count: *count,
+            })
+            .collect();
+        counts.sort_by(|a, b| (a.provider, &a.model).cmp(&(b.provider, &b.model)));
+        counts
+    }
+}
+
+/// Records the failed tool blocks of one response.
+#[derive(Clone)]
+pub struct FcParseFailureSink {
+    failures: Arc<FcParseFailures>,
+    provider: ProviderKind,
+    model: Arc<str>,
+}
+
+impl FcParseFailureSink {
+    pub fn record(&self, buffer: &str) {
+        self.failures.record(self.provider, &self.model, buffer);
+    }
+}
+
+fn provider_label(provider: ProviderKind) -> &'static str {
+    match provider {
+        ProviderKind::OpenAi => "openai",
+        ProviderKind::OpenAiResponses => "openai-responses",
+        ProviderKind::Anthropic => "anthropic",
+        ProviderKind::Gemini => "gemini",
+        ProviderKind::GeminiOpenAi => "gemini-openai",
+    }
+}
+
+fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+        })
+}
+
+/// The first `max_bytes` of `text`, cut back to a char boundary.
+fn head(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Mask letters and digits outside markup so the sample keeps the block's
+/// tags and punctuation but none of its text. CDATA contents are masked
+/// even though they sit inside `<...>`.
+fn redact(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut in_tag = false;
+    while let Some(ch) = rest.chars().next() {
+        if !in_tag && rest.starts_with(CDATA_OPEN) {
+            out.push_str(CDATA_OPEN);
+            rest = &rest[CDATA_OPEN.len()..];
+            let end = rest.find(CDATA_CLOSE).unwrap_or(rest.len());
+            out.extend(rest[..end].chars().map(mask));
+            rest = &rest[end..];
+            if let Some(after) = rest.strip_prefix(CDATA_CLOSE) {
+                out.push_str(CDATA_CLOSE);
+                rest = after;
+            }
+            continue;
+        }
+        match ch {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ => {}
+        }
+        out.push(if in_tag { ch } else { mask(ch) });
+        rest = &rest[ch.len_utf8()..];
+    }
+    out
+}
+
+fn mask(ch: char) -> char {
+    if ch.is_alphanumeric() {
+        MASK
+    } else {
+        ch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failures(max_samples: usize, sample_bytes: usize) -> FcParseFailures {
+        FcParseFailures::new(&FcParseFailureConfig {
+            max_samples,
+            sample_bytes,
+        })
+    }
+
+    #[test]
+    fn test_redact_keeps_markup_and_masks_values() {
+        let redacted = redact(
+            "<function_call><tool>get_weather</tool><args_json><![CDATA[{\"city\": \"Paris 75\"}]]></args_json>oops",
+        );
+        assert_eq!(
+            redacted,
+            "<function_call><tool>***_*******</tool><args_json><![CDATA[{\"****\": \"***** **\"}]]></args_json>****"
+        );
+        assert_eq!(redact("<![CDATA[secret"), "<![CDATA[******");
+    }
+
+    #[test]
+    fn test_record_counts_and_keeps_latest_truncated_samples() {
+        let failures = failures(2, 9);
+        let model: Arc<str> = Arc::from("m");
+        for buffer in ["<a>one</a>", "<a>two</a>", "<b>é</b>"] {
+            failures.record(ProviderKind::Anthropic, &model, buffer);
+        }
+
+        assert_eq!(
+            failures.counts(),
+            vec![FcParseFailureCount {
+                provider: "anthropic",
+                model: "m".to_string(),
+                count: 3,
+            }]
+        );
+        let samples = failures.samples();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0]["sample"], "<b>*</b>");
+        assert_eq!(samples[0]["truncated"], false);
+        assert_eq!(samples[1]["sample"], "<a>***</a");
+        assert_eq!(samples[1]["truncated"], true);
+        assert_eq!(samples[1]["provider"], "anthropic");
+    }
+}
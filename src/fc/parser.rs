@@ -415,7 +415,7 @@ fn extract_first_args_tag_text(block: &str) -> Option<&str> {
 }
 
 #[inline]
-fn extract_first_call_id(block: &str) -> Option<Box<str>> {
+pub(crate) fn extract_first_call_id(block: &str) -> Option<Box<str>> {
     let trimmed = block.trim_start();
     if let Some(rest) = trimmed.strip_prefix("<id>") {
         let end = rest.find("</id>")?;
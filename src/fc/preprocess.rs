@@ -1,9 +1,25 @@
 use std::collections::HashMap;
 
-use crate::protocol::canonical::{CanonicalMessage, CanonicalPart, CanonicalRole};
+use crate::config::{FeaturesConfig, SystemPromptPrecedence};
+use crate::protocol::canonical::{
+    CanonicalMessage, CanonicalPart, CanonicalRequest, CanonicalRole,
+};
 
 use super::prompt;
 
+/// Fold `developer`/`system` conversation messages into the top-level system
+/// prompt when `fold_system_messages` is enabled, so every upstream
+/// protocol sees the same system text in the configured order.
+pub fn normalize_system_messages(canonical: &mut CanonicalRequest, features: &FeaturesConfig) {
+    if !features.fold_system_messages {
+        return;
+    }
+    canonical.fold_system_messages(
+        features.system_prompt_precedence == SystemPromptPrecedence::MessagesFirst,
+        &features.system_prompt_separator,
+    );
+}
+
 /// Build an index mapping `tool_call_id` -> (name, `arguments_json`) from
 /// assistant messages in the conversation history.
 fn build_tool_call_index(messages: &[CanonicalMessage]) -> HashMap<String, (String, String)> {
@@ -310,4 +326,106 @@ mod tests {
         assert_eq!(name, "search");
         assert!(args.contains("test"));
     }
+
+    fn decode_responses_with_developer() -> CanonicalRequest {
+        let request: crate::protocol::openai_responses::ResponsesRequest =
+            serde_json::from_value(serde_json::json!({
+                "model": "gpt-4o",
+                "instructions": "Top level.",
+                "input": [
+                    {"role": "developer", "content": "Dev rule."},
+                    {"role": "user", "content": "hi"}
+                ]
+            }))
+            .unwrap();
+        crate::protocol::openai_responses::decoder::decode_responses_request(
+            &request,
+            uuid::Uuid::nil(),
+        )
+        .unwrap()
+    }
+
+    #[cfg(feature = "server")]
+    fn encode_json(
+        provider: crate::protocol::canonical::ProviderKind,
+        canonical: &CanonicalRequest,
+    ) -> serde_json::Value {
+        let body = crate::api::common::encode_for_provider(
+            provider,
+            canonical,
+            &crate::config::FeaturesConfig::default(),
+        )
+        .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_normalize_system_messages_for_each_upstream_protocol() {
+        use crate::protocol::canonical::ProviderKind;
+
+        let mut canonical = decode_responses_with_developer();
+        normalize_system_messages(&mut canonical, &FeaturesConfig::default());
+        assert_eq!(
+            canonical.system_prompt.as_deref(),
+            Some("Top level.\nDev rule.")
+        );
+        assert_eq!(canonical.messages.len(), 1);
+
+        let chat = encode_json(ProviderKind::OpenAi, &canonical);
+        assert_eq!(chat["messages"][0]["role"], "system");
+        assert_eq!(chat["messages"][0]["content"], "Top level.\nDev rule.");
+        assert_eq!(chat["messages"].as_array().unwrap().len(), 2);
+
+        let responses = encode_json(ProviderKind::OpenAiResponses, &canonical);
+        assert_eq!(responses["instructions"], "Top level.\nDev rule.");
+
+        let anthropic = encode_json(ProviderKind::Anthropic, &canonical);
+        assert_eq!(anthropic["system"], "Top level.\nDev rule.");
+
+        let gemini = encode_json(ProviderKind::Gemini, &canonical);
+        assert_eq!(
+            gemini["systemInstruction"]["parts"][0]["text"],
+            "Top level.\nDev rule."
+        );
+    }
+
+    #[test]
+    fn test_normalize_system_messages_precedence_and_separator() {
+        let features = FeaturesConfig {
+            system_prompt_precedence: SystemPromptPrecedence::MessagesFirst,
+            system_prompt_separator: "\n\n".to_string(),
+            ..FeaturesConfig::default()
+        };
+        let mut canonical = decode_responses_with_developer();
+        normalize_system_messages(&mut canonical, &features);
+        assert_eq!(
+            canonical.system_prompt.as_deref(),
+            Some("Dev rule.\n\nTop level.")
+        );
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_developer_messages_kept_in_place_when_disabled() {
+        use crate::protocol::canonical::ProviderKind;
+
+        let features = FeaturesConfig {
+            fold_system_messages: false,
+            ..FeaturesConfig::default()
+        };
+        let mut canonical = decode_responses_with_developer();
+        normalize_system_messages(&mut canonical, &features);
+        assert_eq!(canonical.messages[0].role, CanonicalRole::System);
+
+        let responses = encode_json(ProviderKind::OpenAiResponses, &canonical);
+        assert_eq!(responses["instructions"], "Top level.");
+        assert_eq!(responses["input"][0]["role"], "developer");
+
+        // Anthropic and Gemini have no in-conversation system role, so the
+        // developer text is still merged rather than dropped.
+        let anthropic = encode_json(ProviderKind::Anthropic, &canonical);
+        assert_eq!(anthropic["system"], "Top level.\nDev rule.");
+        assert_eq!(anthropic["messages"].as_array().unwrap().len(), 1);
+    }
 }
@@ -1,6 +1,8 @@
 use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
 use std::sync::{Arc, LazyLock};
 
+use crate::config::{PromptLocale, ToolDefinitionLimits, ToolListStyle};
 use crate::error::CanonicalError;
 use crate::protocol::canonical::{CanonicalToolChoice, CanonicalToolSpec};
 use parking_lot::RwLock;
@@ -48,7 +50,7 @@ impl PromptArtifacts {
 struct PromptCacheEntry {
     tools: Vec<CanonicalToolSpec>,
     tool_choice: CanonicalToolChoice,
-    custom_template: Option<String>,
+    template: Option<PromptTemplate>,
     prompt: Arc<str>,
     openai_system_message_json: Arc<[u8]>,
 }
@@ -69,11 +71,11 @@ impl PromptCache {
         &self,
         tools: &[CanonicalToolSpec],
         tool_choice: &CanonicalToolChoice,
-        custom_template: Option<&str>,
+        template: Option<&PromptTemplate>,
     ) -> Option<PromptArtifacts> {
         let entry = self.entries.iter().rfind(|entry| {
             entry.tool_choice == *tool_choice
-                && entry.custom_template.as_deref() == custom_template
+                && entry.template.as_ref() == template
                 && entry.tools == tools
         })?;
         Some(PromptArtifacts {
@@ -86,12 +88,12 @@ impl PromptCache {
         &mut self,
         tools: &[CanonicalToolSpec],
         tool_choice: &CanonicalToolChoice,
-        custom_template: Option<&str>,
+        template: Option<&PromptTemplate>,
         artifacts: &PromptArtifacts,
     ) {
         if let Some(pos) = self.entries.iter().position(|entry| {
             entry.tool_choice == *tool_choice
-                && entry.custom_template.as_deref() == custom_template
+                && entry.template.as_ref() == template
                 && entry.tools == tools
         }) {
             self.entries.remove(pos);
@@ -104,13 +106,158 @@ impl PromptCache {
         self.entries.push_back(PromptCacheEntry {
             tools: tools.to_vec(),
             tool_choice: tool_choice.clone(),
-            custom_template: custom_template.map(ToOwned::to_owned),
+            template: template.cloned(),
             prompt: Arc::clone(&artifacts.prompt),
             openai_system_message_json: Arc::clone(&artifacts.openai_system_message_json),
         });
     }
 }
 
+/// Placeholders every FC prompt template fills itself.
+pub const BUILTIN_PLACEHOLDERS: [&str; 2] = ["tools_list", "trigger_signal"];
+
+/// A custom FC prompt template with its user variables already interpolated.
+///
+/// Only the built-in `{tools_list}` and `{trigger_signal}` placeholders are
+/// left for per-request rendering.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    text: String,
+    tool_list_style: ToolListStyle,
+    locale: PromptLocale,
+    tool_limits: Option<ToolDefinitionLimits>,
+    fingerprint: u64,
+}
+
+impl PromptTemplate {
+    #[must_use]
+    pub fn new(text: impl Into<String>, tool_list_style: ToolListStyle) -> Self {
+        Self::with_locale(text, tool_list_style, PromptLocale::En)
+    }
+
+    /// Like [`PromptTemplate::new`], with `tool_choice` constraints written in `locale`.
+    #[must_use]
+    pub fn with_locale(
+        text: impl Into<String>,
+        tool_list_style: ToolListStyle,
+        locale: PromptLocale,
+    ) -> Self {
+        let mut template = Self {
+            text: text.into(),
+            tool_list_style,
+            locale,
+            tool_limits: None,
+            fingerprint: 0,
+        };
+        template.fingerprint = template.compute_fingerprint();
+        template
+    }
+
+    /// The built-in prompt for `locale` as a template, so tool limits can
+    /// be attached to it.
+    #[must_use]
+    pub fn builtin(locale: PromptLocale) -> Self {
+        localized_prompt_template(locale)
+            .cloned()
+            .unwrap_or_else(|| {
+                Self::with_locale(
+                    DEFAULT_PROMPT_TEMPLATE.as_str(),
+                    ToolListStyle::Detailed,
+                    PromptLocale::En,
+                )
+            })
+    }
+
+    /// Enforce `limits` on the tools rendered with this template.
+    #[must_use]
+    pub fn with_tool_limits(mut self, limits: ToolDefinitionLimits) -> Self {
+        self.tool_limits = Some(limits);
+        self.fingerprint = self.compute_fingerprint();
+        self
+    }
+
+    fn compute_fingerprint(&self) -> u64 {
+        let mut hasher = rustc_hash::FxHasher::default();
+        self.text.hash(&mut hasher);
+        self.tool_list_style.hash(&mut hasher);
+        self.locale.hash(&mut hasher);
+        self.tool_limits.hash(&mut hasher);
+        // Zero is reserved for "no template" in cache keys.
+        hasher.finish().max(1)
+    }
+
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    #[must_use]
+    pub fn tool_list_style(&self) -> ToolListStyle {
+        self.tool_list_style
+    }
+
+    #[must_use]
+    pub fn tool_limits(&self) -> Option<&ToolDefinitionLimits> {
+        self.tool_limits.as_ref()
+    }
+
+    /// Content hash used to key caches of rendered prompts.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+}
+
+/// Iterate the `{identifier}` placeholders of a template, in order.
+///
+/// Braces around anything that is not a plain identifier (for example JSON
+/// examples) are not placeholders.
+pub fn template_placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template.match_indices('{').filter_map(move |(start, _)| {
+        let rest = &template[start + 1..];
+        let end = rest.find('}')?;
+        let name = &rest[..end];
+        is_placeholder_name(name).then_some(name)
+    })
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace `{name}` placeholders that have a value in `lookup`; others stay literal.
+pub fn interpolate_template<'a>(
+    template: &str,
+    lookup: impl Fn(&str) -> Option<&'a str>,
+) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut cursor = 0;
+    for (start, _) in template.match_indices('{') {
+        if start < cursor {
+            continue;
+        }
+        let rest = &template[start + 1..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let name = &rest[..end];
+        if !is_placeholder_name(name) {
+            continue;
+        }
+        if let Some(value) = lookup(name) {
+            out.push_str(&template[cursor..start]);
+            out.push_str(value);
+            cursor = start + end + 2;
+        }
+    }
+    out.push_str(&template[cursor..]);
+    out
+}
+
 /// Return the per-process trigger signal (`<Function_XXXX_Start/>`).
 #[must_use]
 pub fn get_trigger_signal() -> &'static str {
@@ -223,9 +370,79 @@ fn format_param_detail(
     lines
 }
 
+type ToolProperties = serde_json::Map<String, serde_json::Value>;
+
+/// Validate a tool's parameter schema and return its properties and required list.
+fn tool_schema_parts(
+    tool: &CanonicalToolSpec,
+) -> Result<(Option<&ToolProperties>, Vec<String>), CanonicalError> {
+    let name = &tool.function.name;
+    let schema = &tool.function.parameters;
+
+    // --- properties ---
+    let props_raw = schema.get("properties");
+    let props = match props_raw {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::Object(m)) => Some(m),
+        Some(other) => {
+            return Err(CanonicalError::InvalidRequest(format!(
+                "Tool '{name}': 'properties' must be an object, got {}",
+                json_type_name(other)
+            )));
+        }
+    };
+
+    // --- required ---
+    let required_raw = schema.get("required");
+    let required_list: Vec<String> = match required_raw {
+        None | Some(serde_json::Value::Null) => Vec::new(),
+        Some(serde_json::Value::Array(arr)) => {
+            let mut out = Vec::new();
+            for item in arr {
+                match item.as_str() {
+                    Some(s) => out.push(s.to_string()),
+                    None => {
+                        return Err(CanonicalError::InvalidRequest(format!(
+                            "Tool '{name}': 'required' entries must be strings, got {item}"
+                        )));
+                    }
+                }
+            }
+            out
+        }
+        Some(other) => {
+            return Err(CanonicalError::InvalidRequest(format!(
+                "Tool '{name}': 'required' must be a list, got {}",
+                json_type_name(other)
+            )));
+        }
+    };
+
+    // Validate required keys exist in properties
+    let missing_keys: Vec<&str> = required_list
+        .iter()
+        .filter(|k| props.is_none_or(|m| !m.contains_key(k.as_str())))
+        .map(std::string::String::as_str)
+        .collect();
+    if !missing_keys.is_empty() {
+        return Err(CanonicalError::InvalidRequest(format!(
+            "Tool '{name}': required parameters {missing_keys:?} are not defined in properties"
+        )));
+    }
+
+    Ok((props, required_list))
+}
+
+fn param_type_name(p_info: &serde_json::Value) -> &str {
+    p_info
+        .as_object()
+        .and_then(|o| o.get("type"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("any")
+}
+
 /// Format the list of tools into the text block used inside the prompt.
 ///
-/// Returns `(tools_list_string, required_list_per_tool)`.
 /// On validation failure returns `CanonicalError::InvalidRequest`.
 fn format_tools_list(tools: &[CanonicalToolSpec]) -> Result<String, CanonicalError> {
     let mut tools_list_str: Vec<String> = Vec::new();
@@ -234,59 +451,7 @@ fn format_tools_list(tools: &[CanonicalToolSpec]) -> Result<String, CanonicalErr
         let func = &tool.function;
         let name = &func.name;
         let description = func.description.as_deref().unwrap_or("");
-
-        let schema = &func.parameters;
-
-        // --- properties ---
-        let props_raw = schema.get("properties");
-        let props = match props_raw {
-            None | Some(serde_json::Value::Null) => None,
-            Some(serde_json::Value::Object(m)) => Some(m),
-            Some(other) => {
-                return Err(CanonicalError::InvalidRequest(format!(
-                    "Tool '{name}': 'properties' must be an object, got {}",
-                    json_type_name(other)
-                )));
-            }
-        };
-
-        // --- required ---
-        let required_raw = schema.get("required");
-        let required_list: Vec<String> = match required_raw {
-            None | Some(serde_json::Value::Null) => Vec::new(),
-            Some(serde_json::Value::Array(arr)) => {
-                let mut out = Vec::new();
-                for item in arr {
-                    match item.as_str() {
-                        Some(s) => out.push(s.to_string()),
-                        None => {
-                            return Err(CanonicalError::InvalidRequest(format!(
-                                "Tool '{name}': 'required' entries must be strings, got {item}"
-                            )));
-                        }
-                    }
-                }
-                out
-            }
-            Some(other) => {
-                return Err(CanonicalError::InvalidRequest(format!(
-                    "Tool '{name}': 'required' must be a list, got {}",
-                    json_type_name(other)
-                )));
-            }
-        };
-
-        // Validate required keys exist in properties
-        let missing_keys: Vec<&str> = required_list
-            .iter()
-            .filter(|k| props.is_none_or(|m| !m.contains_key(k.as_str())))
-            .map(std::string::String::as_str)
-            .collect();
-        if !missing_keys.is_empty() {
-            return Err(CanonicalError::InvalidRequest(format!(
-                "Tool '{name}': required parameters {missing_keys:?} are not defined in properties"
-            )));
-        }
+        let (props, required_list) = tool_schema_parts(tool)?;
 
         // params summary: name (type), ...
         let params_summary = props.map_or_else(
@@ -297,14 +462,7 @@ fn format_tools_list(tools: &[CanonicalToolSpec]) -> Result<String, CanonicalErr
                 } else {
                     props
                         .iter()
-                        .map(|(p_name, p_info)| {
-                            let ptype = p_info
-                                .as_object()
-                                .and_then(|o| o.get("type"))
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("any");
-                            format!("{p_name} ({ptype})")
-                        })
+                        .map(|(p_name, p_info)| format!("{p_name} ({})", param_type_name(p_info)))
                         .collect::<Vec<_>>()
                         .join(", ")
                 }
@@ -351,6 +509,73 @@ fn format_tools_list(tools: &[CanonicalToolSpec]) -> Result<String, CanonicalErr
     Ok(tools_list_str.join("\n\n"))
 }
 
+/// Format the tools as one line each: `- name(param: type, opt?: type): description`.
+fn format_tools_list_compact(tools: &[CanonicalToolSpec]) -> Result<String, CanonicalError> {
+    let mut lines = Vec::with_capacity(tools.len());
+    for tool in tools {
+        let (props, required_list) = tool_schema_parts(tool)?;
+        let params = props
+            .into_iter()
+            .flat_map(|m| m.iter())
+            .map(|(p_name, p_info)| {
+                let optional = if required_list.iter().any(|r| r == p_name) {
+                    ""
+                } else {
+                    "?"
+                };
+                format!("{p_name}{optional}: {}", param_type_name(p_info))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut line = format!("- {}({params})", tool.function.name);
+        if let Some(description) = tool
+            .function
+            .description
+            .as_deref()
+            .filter(|d| !d.is_empty())
+        {
+            line.push_str(": ");
+            line.push_str(&description.replace('\n', " "));
+        }
+        lines.push(line);
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Format the tools as a pretty-printed JSON array of function specs.
+fn format_tools_list_json(tools: &[CanonicalToolSpec]) -> Result<String, CanonicalError> {
+    let mut specs = Vec::with_capacity(tools.len());
+    for tool in tools {
+        tool_schema_parts(tool)?;
+        let mut spec = serde_json::Map::new();
+        spec.insert(
+            "name".to_string(),
+            serde_json::Value::String(tool.function.name.clone()),
+        );
+        if let Some(description) = &tool.function.description {
+            spec.insert(
+                "description".to_string(),
+                serde_json::Value::String(description.clone()),
+            );
+        }
+        spec.insert("parameters".to_string(), tool.function.parameters.clone());
+        specs.push(serde_json::Value::Object(spec));
+    }
+    serde_json::to_string_pretty(&specs)
+        .map_err(|e| CanonicalError::Translation(format!("Failed to serialize tool list: {e}")))
+}
+
+fn render_tools_list(
+    tools: &[CanonicalToolSpec],
+    style: ToolListStyle,
+) -> Result<String, CanonicalError> {
+    match style {
+        ToolListStyle::Detailed => format_tools_list(tools),
+        ToolListStyle::Compact => format_tools_list_compact(tools),
+        ToolListStyle::Json => format_tools_list_json(tools),
+    }
+}
+
 /// Return a human-readable JSON type name (mirrors Python `type(x).__name__`).
 fn json_type_name(v: &serde_json::Value) -> &'static str {
     match v {
@@ -440,6 +665,282 @@ Now please be ready to strictly follow the above specifications.
     )
 }
 
+// ---------------------------------------------------------------------------
+// Localized built-in templates
+// ---------------------------------------------------------------------------
+
+const ZH_PROMPT_TEMPLATE: &str = r#"
+你可以使用以下工具来帮助解决问题：
+
+{tools_list}
+
+**重要说明：**
+1. 如有需要，你可以在一次回复中调用多个工具。
+2. 即使可以调用多个工具，你也必须遵守用户后续的约束和偏好（例如用户可能要求不使用工具、只使用一个工具或指定的工具/流程）。
+3. 对话上下文中可能已经包含之前工具调用的执行结果。请仔细查看历史记录，避免不必要的重复调用。
+4. 工具执行结果会以 <tool_result>...</tool_result> 等 XML 标签标出，便于识别。
+5. 这是唯一可用的工具调用格式，任何偏差都会导致调用失败。
+
+需要使用工具时，你**必须**严格遵循以下格式。工具调用语法的第一行和第二行不得包含任何额外的文字、解释或对话：
+
+1. 开始调用工具时，另起一行并准确输出：
+{trigger_signal}
+前后不要有空格，按原样输出。触发信号必须单独占一行，且只出现一次，不要为每个工具调用分别输出触发信号。
+
+2. 从第二行开始，**立即**输出完整的 <function_calls> XML 块。
+
+3. 多个工具调用应放在同一个 <function_calls> 包装中的多个 <function_call> 块内，而不是分开的多个块。触发信号只输出一次，然后是一个包含全部 <function_call> 子元素的 <function_calls>。
+
+4. 在 </function_calls> 结束标签之后不要添加任何文字或解释。
+
+参数键名严格规则：
+- 参数键名必须与定义完全一致（区分大小写和标点），不得重命名、增加或删除字符。
+- 如果键名以连字符开头（例如 "-i"、"-C"），JSON 键中必须保留该连字符，绝不能把 "-i" 写成 "i"，或把 "-C" 写成 "C"。
+- <tool> 标签必须包含列表中某个工具的准确名称，其他名称均无效。
+- <args_json> 标签必须包含一个 JSON 对象，其中含有该工具的全部必填参数。
+- 可以用 <![CDATA[...]]> 包裹 JSON 内容，以避免 XML 转义问题。
+
+正确示例（多个工具调用）：
+...回复内容（可选）...
+{trigger_signal}
+<function_calls>
+    <function_call>
+        <tool>Grep</tool>
+        <args_json><![CDATA[{"-i": true, "-C": 2, "path": "."}]]></args_json>
+    </function_call>
+    <function_call>
+        <tool>search</tool>
+        <args_json><![CDATA[{"keywords": ["Python Document", "how to use python"]}]]></args_json>
+    </function_call>
+  </function_calls>
+
+错误示例（多余文字 + 错误的键名——不要这样做）：
+...回复内容（可选）...
+{trigger_signal}
+我来为你调用工具。
+<function_calls>
+    <function_call>
+        <tool>Grep</tool>
+        <args>
+            <i>true</i>
+            <C>2</C>
+            <path>.</path>
+        </args>
+    </function_call>
+</function_calls>
+
+现在请准备好严格遵循以上规范。
+"#;
+
+const JA_PROMPT_TEMPLATE: &str = r#"
+問題解決のために、次のツールを利用できます：
+
+{tools_list}
+
+**重要な注意事項：**
+1. 必要に応じて、1 回の応答で複数のツールを呼び出せます。
+2. 複数のツールを呼び出せる場合でも、ユーザーの後からの制約や希望（ツールを使わない、1 つだけ使う、特定のツールや手順を使うなど）に必ず従ってください。
+3. 会話の文脈には、以前のツール呼び出しの実行結果がすでに含まれている場合があります。不要な重複呼び出しを避けるため、履歴をよく確認してください。
+4. ツールの実行結果は <tool_result>...</tool_result> などの XML タグで示されます。
+5. ツール呼び出しに使える形式はこれだけです。少しでも外れると呼び出しは失敗します。
+
+ツールを使う場合は、次の形式に**必ず**厳密に従ってください。ツール呼び出し構文の 1 行目と 2 行目には、余分なテキスト・説明・会話を含めないでください：
+
+1. ツール呼び出しを始めるときは、新しい行に次のとおり正確に出力してください：
+{trigger_signal}
+前後に空白を入れず、そのまま出力してください。トリガーシグナルは単独の行に 1 回だけ出力し、ツール呼び出しごとに出力しないでください。
+
+2. 2 行目から、完全な <function_calls> XML ブロックを**すぐに**続けてください。
+
+3. 複数のツールを呼び出す場合は、別々のブロックではなく、同じ <function_calls> の中に複数の <function_call> ブロックを入れてください。トリガーシグナルは 1 回だけ出力し、その後にすべての <function_call> を含む <function_calls> を 1 つ出力します。
+
+4. 終了タグ </function_calls> の後には、テキストや説明を追加しないでください。
+
+引数キーの厳格なルール：
+- パラメータのキーは定義どおり正確に使用してください（大文字小文字・記号を区別）。名前の変更や文字の追加・削除は禁止です。
+- キーがハイフンで始まる場合（例："-i"、"-C"）、JSON のキーでも先頭のハイフンを必ず残してください。"-i" を "i" に、"-C" を "C" に変えてはいけません。
+- <tool> タグには一覧にあるツール名を正確に記述してください。それ以外の名前は無効です。
+- <args_json> タグには、そのツールの必須引数をすべて含む JSON オブジェクトを 1 つ記述してください。
+- XML のエスケープ問題を避けるため、JSON を <![CDATA[...]]> で囲んでもかまいません。
+
+正しい例（複数のツール呼び出し）：
+...応答内容（任意）...
+{trigger_signal}
+<function_calls>
+    <function_call>
+        <tool>Grep</tool>
+        <args_json><![CDATA[{"-i": true, "-C": 2, "path": "."}]]></args_json>
+    </function_call>
+    <function_call>
+        <tool>search</tool>
+        <args_json><![CDATA[{"keywords": ["Python Document", "how to use python"]}]]></args_json>
+    </function_call>
+  </function_calls>
+
+誤った例（余分なテキスト + 誤ったキー名——このようにしないでください）：
+...応答内容（任意）...
+{trigger_signal}
+ツールを呼び出します。
+<function_calls>
+    <function_call>
+        <tool>Grep</tool>
+        <args>
+            <i>true</i>
+            <C>2</C>
+            <path>.</path>
+        </args>
+    </function_call>
+</function_calls>
+
+以上の仕様に厳密に従う準備をしてください。
+"#;
+
+const ES_PROMPT_TEMPLATE: &str = r#"
+Tienes acceso a las siguientes herramientas para ayudar a resolver problemas:
+
+{tools_list}
+
+**NOTAS IMPORTANTES DE CONTEXTO:**
+1. Puedes llamar a VARIAS herramientas en una sola respuesta si es necesario.
+2. Aunque puedas llamar a varias herramientas, DEBES respetar las restricciones y preferencias posteriores del usuario (por ejemplo, puede pedir que no uses herramientas, solo una, o una herramienta o flujo concreto).
+3. El contexto de la conversación puede contener ya resultados de llamadas anteriores. Revisa el historial con atención para evitar llamadas duplicadas innecesarias.
+4. Los resultados de las herramientas aparecen marcados con etiquetas XML como <tool_result>...</tool_result>.
+5. Este es el ÚNICO formato válido para llamar herramientas; cualquier desviación provocará un fallo.
+
+Cuando necesites usar herramientas, **DEBES** seguir estrictamente este formato. No incluyas texto, explicaciones ni diálogo adicional en la primera y la segunda línea de la llamada:
+
+1. Al empezar las llamadas, comienza en una línea nueva exactamente con:
+{trigger_signal}
+Sin espacios antes ni después, tal como aparece arriba. La señal de activación DEBE ir sola en su línea y aparecer una única vez; no la repitas por cada llamada.
+
+2. A partir de la segunda línea, escribe **inmediatamente** el bloque XML <function_calls> completo.
+
+3. Para varias llamadas, incluye varios bloques <function_call> dentro del mismo <function_calls>, no bloques separados. Escribe la señal una sola vez y después un único <function_calls> con todos los <function_call>.
+
+4. No añadas texto ni explicaciones después de la etiqueta de cierre </function_calls>.
+
+REGLAS ESTRICTAS PARA LAS CLAVES DE ARGUMENTOS:
+- DEBES usar las claves de los parámetros EXACTAMENTE como están definidas (distinguiendo mayúsculas y signos). No renombres, añadas ni quites caracteres.
+- Si una clave empieza con guion (p. ej., "-i", "-C"), DEBES conservar el guion inicial en la clave JSON. Nunca conviertas "-i" en "i" ni "-C" en "C".
+- La etiqueta <tool> debe contener el nombre exacto de una herramienta de la lista. Cualquier otro nombre no es válido.
+- La etiqueta <args_json> debe contener un único objeto JSON con todos los argumentos obligatorios de esa herramienta.
+- PUEDES envolver el JSON en <![CDATA[...]]> para evitar problemas de escape XML.
+
+Ejemplo CORRECTO (varias llamadas):
+...contenido de la respuesta (opcional)...
+{trigger_signal}
+<function_calls>
+    <function_call>
+        <tool>Grep</tool>
+        <args_json><![CDATA[{"-i": true, "-C": 2, "path": "."}]]></args_json>
+    </function_call>
+    <function_call>
+        <tool>search</tool>
+        <args_json><![CDATA[{"keywords": ["Python Document", "how to use python"]}]]></args_json>
+    </function_call>
+  </function_calls>
+
+Ejemplo INCORRECTO (texto extra + claves equivocadas — NO HAGAS ESTO):
+...contenido de la respuesta (opcional)...
+{trigger_signal}
+Voy a llamar a las herramientas por ti.
+<function_calls>
+    <function_call>
+        <tool>Grep</tool>
+        <args>
+            <i>true</i>
+            <C>2</C>
+            <path>.</path>
+        </args>
+    </function_call>
+</function_calls>
+
+Ahora prepárate para seguir estrictamente las especificaciones anteriores.
+"#;
+
+static LOCALIZED_PROMPT_TEMPLATES: LazyLock<[PromptTemplate; 3]> = LazyLock::new(|| {
+    [
+        (ZH_PROMPT_TEMPLATE, PromptLocale::Zh),
+        (JA_PROMPT_TEMPLATE, PromptLocale::Ja),
+        (ES_PROMPT_TEMPLATE, PromptLocale::Es),
+    ]
+    .map(|(text, locale)| PromptTemplate::with_locale(text, ToolListStyle::Detailed, locale))
+});
+
+/// Built-in FC prompt for `locale`; `None` selects the default English prompt.
+///
+/// [`PromptLocale::Auto`] must be resolved with [`detect_prompt_locale`] first.
+#[must_use]
+pub fn localized_prompt_template(locale: PromptLocale) -> Option<&'static PromptTemplate> {
+    let index = match locale {
+        PromptLocale::En | PromptLocale::Auto => return None,
+        PromptLocale::Zh => 0,
+        PromptLocale::Ja => 1,
+        PromptLocale::Es => 2,
+    };
+    LOCALIZED_PROMPT_TEMPLATES.get(index)
+}
+
+/// Guess the conversation language from raw request text.
+///
+/// Kana means Japanese, otherwise two or more Han characters mean Chinese;
+/// Spanish is recognised by `ñ`, `¿` or `¡`. Anything else is English.
+#[must_use]
+pub fn detect_prompt_locale(text: &[u8]) -> PromptLocale {
+    let text = String::from_utf8_lossy(text);
+    let mut han = 0usize;
+    let mut spanish = false;
+    for c in text.chars().filter(|c| !c.is_ascii()) {
+        match c {
+            '\u{3040}'..='\u{30ff}' => return PromptLocale::Ja,
+            '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}' => han += 1,
+            'ñ' | 'Ñ' | '¿' | '¡' => spanish = true,
+            _ => {}
+        }
+    }
+    if han >= 2 {
+        PromptLocale::Zh
+    } else if spanish {
+        PromptLocale::Es
+    } else {
+        PromptLocale::En
+    }
+}
+
+fn tool_choice_constraint(
+    locale: PromptLocale,
+    tool_choice: &CanonicalToolChoice,
+) -> Option<String> {
+    let text = match (locale, tool_choice) {
+        (_, CanonicalToolChoice::Auto) => return None,
+        (PromptLocale::Zh, CanonicalToolChoice::None) => "不要调用任何函数。".to_string(),
+        (PromptLocale::Zh, CanonicalToolChoice::Required) => "你必须至少调用一个函数。".to_string(),
+        (PromptLocale::Zh, CanonicalToolChoice::Specific(name)) => {
+            format!("你必须调用函数：{name}")
+        }
+        (PromptLocale::Ja, CanonicalToolChoice::None) => {
+            "関数を呼び出さないでください。".to_string()
+        }
+        (PromptLocale::Ja, CanonicalToolChoice::Required) => {
+            "少なくとも 1 つの関数を必ず呼び出してください。".to_string()
+        }
+        (PromptLocale::Ja, CanonicalToolChoice::Specific(name)) => {
+            format!("次の関数を必ず呼び出してください：{name}")
+        }
+        (PromptLocale::Es, CanonicalToolChoice::None) => "NO llames a ninguna función.".to_string(),
+        (PromptLocale::Es, CanonicalToolChoice::Required) => {
+            "DEBES llamar al menos a una función.".to_string()
+        }
+        (PromptLocale::Es, CanonicalToolChoice::Specific(name)) => {
+            format!("DEBES llamar a la función: {name}")
+        }
+        (_, CanonicalToolChoice::None) => "Do NOT call any function.".to_string(),
+        (_, CanonicalToolChoice::Required) => "You MUST call at least one function.".to_string(),
+        (_, CanonicalToolChoice::Specific(name)) => format!("You MUST call the function: {name}"),
+    };
+    Some(text)
+}
+
 // ---------------------------------------------------------------------------
 // Public API
 // ---------------------------------------------------------------------------
@@ -450,37 +951,39 @@ Now please be ready to strictly follow the above specifications.
 /// It returns the full prompt text ready to be prepended/appended to the
 /// system prompt.
 ///
-/// If `custom_template` is `Some`, it is used instead of the default template.
-/// The custom template must contain `{tools_list}` and `{trigger_signal}` placeholders.
+/// If `template` is `Some`, it is used instead of the default template and
+/// selects how `{tools_list}` is rendered.
 ///
 /// # Errors
 ///
-/// Returns `CanonicalError` when tool schema validation fails or prompt
-/// serialization fails.
+/// Returns `CanonicalError` when tool schema validation fails, the tools
+/// exceed the template's tool limits, or prompt serialization fails.
 pub fn generate_fc_prompt_artifacts(
     tools: &[CanonicalToolSpec],
     tool_choice: &CanonicalToolChoice,
-    custom_template: Option<&str>,
+    template: Option<&PromptTemplate>,
 ) -> Result<PromptArtifacts, CanonicalError> {
-    if let Some(cached) = PROMPT_CACHE.read().get(tools, tool_choice, custom_template) {
+    if let Some(cached) = PROMPT_CACHE.read().get(tools, tool_choice, template) {
         return Ok(cached);
     }
 
-    let prompt = Arc::<str>::from(generate_fc_prompt_uncached(
-        tools,
-        tool_choice,
-        custom_template,
-    )?);
+    let prompt = match template.and_then(PromptTemplate::tool_limits) {
+        Some(limits) => super::limits::render_within_limits(tools, limits, |tools| {
+            generate_fc_prompt_uncached(tools, tool_choice, template)
+        })?,
+        None => generate_fc_prompt_uncached(tools, tool_choice, template)?,
+    };
+    let prompt = Arc::<str>::from(prompt);
     let openai_system_message_json = encode_openai_system_message_json(prompt.as_ref())?;
     let artifacts = PromptArtifacts {
         prompt,
         openai_system_message_json,
     };
     let mut cache = PROMPT_CACHE.write();
-    if let Some(cached) = cache.get(tools, tool_choice, custom_template) {
+    if let Some(cached) = cache.get(tools, tool_choice, template) {
         return Ok(cached);
     }
-    cache.insert(tools, tool_choice, custom_template, &artifacts);
+    cache.insert(tools, tool_choice, template, &artifacts);
     Ok(artifacts)
 }
 
@@ -500,8 +1003,8 @@ fn encode_openai_system_message_json(prompt: &str) -> Result<Arc<[u8]>, Canonica
 /// It returns the full prompt text ready to be prepended/appended to the
 /// system prompt.
 ///
-/// If `custom_template` is `Some`, it is used instead of the default template.
-/// The custom template must contain `{tools_list}` and `{trigger_signal}` placeholders.
+/// If `template` is `Some`, it is used instead of the default template and
+/// selects how `{tools_list}` is rendered.
 ///
 /// # Errors
 ///
@@ -510,47 +1013,38 @@ fn encode_openai_system_message_json(prompt: &str) -> Result<Arc<[u8]>, Canonica
 pub fn generate_fc_prompt(
     tools: &[CanonicalToolSpec],
     tool_choice: &CanonicalToolChoice,
-    custom_template: Option<&str>,
+    template: Option<&PromptTemplate>,
 ) -> Result<String, CanonicalError> {
-    Ok(
-        generate_fc_prompt_artifacts(tools, tool_choice, custom_template)?
-            .prompt()
-            .to_string(),
-    )
+    Ok(generate_fc_prompt_artifacts(tools, tool_choice, template)?
+        .prompt()
+        .to_string())
 }
 
 fn generate_fc_prompt_uncached(
     tools: &[CanonicalToolSpec],
     tool_choice: &CanonicalToolChoice,
-    custom_template: Option<&str>,
+    template: Option<&PromptTemplate>,
 ) -> Result<String, CanonicalError> {
     let trigger_signal = get_trigger_signal();
 
-    // Build the tools list text
-    let tools_list = format_tools_list(tools)?;
-
     // Build prompt from template
-    let prompt = if let Some(tmpl) = custom_template {
+    let prompt = if let Some(tmpl) = template {
         // Custom template: interpolate trigger_signal first (keep {tools_list} literal),
         // then replace {tools_list}.
-        tmpl.replace("{trigger_signal}", trigger_signal)
+        let tools_list = render_tools_list(tools, tmpl.tool_list_style)?;
+        tmpl.text
+            .replace("{trigger_signal}", trigger_signal)
             .replace("{tools_list}", &tools_list)
     } else {
+        let tools_list = format_tools_list(tools)?;
         DEFAULT_PROMPT_TEMPLATE.replace("{tools_list}", &tools_list)
     };
 
     // Append tool_choice constraints
-    let prompt = match tool_choice {
-        CanonicalToolChoice::None => {
-            format!("{prompt}\n\nDo NOT call any function.")
-        }
-        CanonicalToolChoice::Auto => prompt,
-        CanonicalToolChoice::Required => {
-            format!("{prompt}\n\nYou MUST call at least one function.")
-        }
-        CanonicalToolChoice::Specific(name) => {
-            format!("{prompt}\n\nYou MUST call the function: {name}")
-        }
+    let locale = template.map_or(PromptLocale::En, |tmpl| tmpl.locale);
+    let prompt = match tool_choice_constraint(locale, tool_choice) {
+        Some(constraint) => format!("{prompt}\n\n{constraint}"),
+        None => prompt,
     };
 
     Ok(prompt)
@@ -655,12 +1149,86 @@ mod tests {
             "Search things",
             serde_json::json!({"type": "object", "properties": {}}),
         );
-        let tmpl = "TOOLS: {tools_list}\nSIGNAL: {trigger_signal}";
-        let prompt = generate_fc_prompt(&[tool], &CanonicalToolChoice::Auto, Some(tmpl)).unwrap();
+        let tmpl = PromptTemplate::new(
+            "TOOLS: {tools_list}\nSIGNAL: {trigger_signal}",
+            ToolListStyle::Detailed,
+        );
+        let prompt = generate_fc_prompt(&[tool], &CanonicalToolChoice::Auto, Some(&tmpl)).unwrap();
         assert!(prompt.starts_with("TOOLS: "));
         assert!(prompt.contains(get_trigger_signal()));
     }
 
+    #[test]
+    fn tool_list_styles_render_compact_and_json() {
+        let tool = make_tool(
+            "search",
+            "Search things",
+            serde_json::json!({
+                "type": "object",
+                "properties": {"query": {"type": "string"}, "limit": {"type": "integer"}},
+                "required": ["query"]
+            }),
+        );
+        let compact = PromptTemplate::new("{tools_list}|{trigger_signal}", ToolListStyle::Compact);
+        let prompt = generate_fc_prompt(
+            std::slice::from_ref(&tool),
+            &CanonicalToolChoice::Auto,
+            Some(&compact),
+        )
+        .unwrap();
+        assert!(prompt.starts_with("- search(limit?: integer, query: string): Search things|"));
+
+        let json = PromptTemplate::new("{tools_list}|{trigger_signal}", ToolListStyle::Json);
+        let prompt = generate_fc_prompt(&[tool], &CanonicalToolChoice::Auto, Some(&json)).unwrap();
+        let (list, _) = prompt.split_once('|').unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(list).unwrap();
+        assert_eq!(parsed[0]["name"], "search");
+        assert_eq!(parsed[0]["parameters"]["required"][0], "query");
+    }
+
+    #[test]
+    fn detect_prompt_locale_recognises_scripts() {
+        assert_eq!(
+            detect_prompt_locale("{\"content\":\"今日の天気は？\"}".as_bytes()),
+            PromptLocale::Ja
+        );
+        assert_eq!(
+            detect_prompt_locale("{\"content\":\"今天天气怎么样\"}".as_bytes()),
+            PromptLocale::Zh
+        );
+        assert_eq!(
+            detect_prompt_locale("{\"content\":\"¿Qué tiempo hace?\"}".as_bytes()),
+            PromptLocale::Es
+        );
+        assert_eq!(
+            detect_prompt_locale(b"{\"content\":\"weather?\"}"),
+            PromptLocale::En
+        );
+    }
+
+    #[test]
+    fn localized_template_localizes_tool_choice_constraint() {
+        let tool = make_tool(
+            "f",
+            "",
+            serde_json::json!({"type": "object", "properties": {}}),
+        );
+        let zh = localized_prompt_template(PromptLocale::Zh).unwrap();
+        let prompt = generate_fc_prompt(&[tool], &CanonicalToolChoice::Required, Some(zh)).unwrap();
+        assert!(prompt.contains("你可以使用以下工具"));
+        assert!(prompt.contains(get_trigger_signal()));
+        assert!(prompt.ends_with("你必须至少调用一个函数。"));
+        assert!(localized_prompt_template(PromptLocale::En).is_none());
+    }
+
+    #[test]
+    fn interpolate_template_fills_known_placeholders_only() {
+        let out = interpolate_template("{greeting} {tools_list} {\"k\": {x}}", |name| {
+            (name == "greeting").then_some("hi")
+        });
+        assert_eq!(out, "hi {tools_list} {\"k\": {x}}");
+    }
+
     #[test]
     fn missing_required_in_properties_is_error() {
         let tool = make_tool(
@@ -1,4 +1,6 @@
-use crate::config::FeaturesConfig;
+use std::time::Duration;
+
+use crate::config::{FcRetryAttemptConfig, FeaturesConfig};
 use crate::protocol::canonical::{CanonicalMessage, CanonicalPart, CanonicalRole};
 
 // ---------------------------------------------------------------------------
@@ -25,6 +27,20 @@ Please retry and output the function call in the correct XML format. Remember:
 
 Please provide the corrected function call now. DO NOT OUTPUT ANYTHING ELSE.";
 
+const TOOL_SCHEMA_RETRY_TEMPLATE: &str = "\
+Your previous response called a tool with arguments that do not match the tool's schema.
+
+**Your tool calls:**
+```
+{original_response}
+```
+
+**Validation errors:**
+{error_details}
+
+**Instructions:**
+Call the tool again with arguments that satisfy its schema exactly. DO NOT OUTPUT ANYTHING ELSE.";
+
 // ---------------------------------------------------------------------------
 // Retry decision
 // ---------------------------------------------------------------------------
@@ -69,6 +85,13 @@ pub fn build_retry_prompt(
         .replace("{original_response}", original_response)
 }
 
+/// Build the prompt asking the model to repeat native tool calls whose
+/// arguments broke a `strict` tool schema.
+#[must_use]
+pub fn build_tool_schema_retry_prompt(error_details: &str, tool_calls: &str) -> String {
+    build_retry_prompt(error_details, tool_calls, Some(TOOL_SCHEMA_RETRY_TEMPLATE))
+}
+
 // ---------------------------------------------------------------------------
 // Build retry messages
 // ---------------------------------------------------------------------------
@@ -123,6 +146,8 @@ pub struct RetryContext {
     pub current_attempt: u32,
     pub enable_retry: bool,
     pub retry_template: Option<String>,
+    pub attempts: Vec<FcRetryAttemptConfig>,
+    pub jitter_ms: u64,
 }
 
 impl RetryContext {
@@ -134,9 +159,33 @@ impl RetryContext {
             current_attempt: 0,
             enable_retry: features.enable_fc_error_retry,
             retry_template: features.fc_error_retry_prompt_template.clone(),
+            attempts: features.fc_error_retry_attempts.clone(),
+            jitter_ms: features.fc_error_retry_jitter_ms,
         }
     }
 
+    /// Overrides for the next retry; the last configured entry repeats.
+    #[must_use]
+    pub fn next_attempt(&self) -> Option<&FcRetryAttemptConfig> {
+        let index = usize::try_from(self.current_attempt).unwrap_or(usize::MAX);
+        self.attempts.get(index).or_else(|| self.attempts.last())
+    }
+
+    /// Prompt template for the next retry: its override, else the configured
+    /// template, else `None` for the built-in default.
+    #[must_use]
+    pub fn next_template(&self) -> Option<&str> {
+        self.next_attempt()
+            .and_then(|attempt| attempt.prompt_template.as_deref())
+            .or(self.retry_template.as_deref())
+    }
+
+    /// Random delay to wait before the next retry, if jitter is configured.
+    #[must_use]
+    pub fn next_jitter(&self) -> Option<Duration> {
+        (self.jitter_ms > 0).then(|| Duration::from_millis(fastrand::u64(0..=self.jitter_ms)))
+    }
+
     /// Returns `true` if another retry attempt should be made.
     ///
     /// Mirrors the same four-condition check from [`should_retry`] but uses
@@ -257,6 +306,8 @@ mod tests {
         assert_eq!(ctx.current_attempt, 0);
         assert!(ctx.enable_retry);
         assert!(ctx.retry_template.is_none());
+        assert!(ctx.next_attempt().is_none());
+        assert!(ctx.next_jitter().is_none());
     }
 
     #[test]
@@ -293,4 +344,29 @@ mod tests {
             Some("custom {error_details}")
         );
     }
+
+    #[test]
+    fn test_retry_context_attempt_overrides() {
+        let mut f = default_features();
+        f.fc_error_retry_prompt_template = Some("base {error_details}".into());
+        f.fc_error_retry_attempts = vec![
+            FcRetryAttemptConfig::default(),
+            FcRetryAttemptConfig {
+                prompt_template: Some("forceful {error_details}".into()),
+                temperature: Some(0.0),
+                alternate_upstream: true,
+            },
+        ];
+        f.fc_error_retry_jitter_ms = 50;
+        let mut ctx = RetryContext::new(&f);
+        assert_eq!(ctx.next_template(), Some("base {error_details}"));
+        assert!(!ctx.next_attempt().unwrap().alternate_upstream);
+
+        ctx.increment();
+        assert_eq!(ctx.next_template(), Some("forceful {error_details}"));
+        // The last entry covers every later retry.
+        ctx.increment();
+        assert_eq!(ctx.next_attempt().unwrap().temperature, Some(0.0));
+        assert!(ctx.next_jitter().unwrap() <= Duration::from_millis(50));
+    }
 }
@@ -0,0 +1,356 @@
+//! Named FC prompt templates and their per-upstream / per-model selection,
+//! falling back to the built-in prompt in the configured language.
+
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use crate::config::{
+    AppConfig, FeaturesConfig, PromptLocale, PromptTemplateConfig, PromptTemplateSelection,
+    ToolListStyle,
+};
+
+use super::prompt::{
+    detect_prompt_locale, interpolate_template, localized_prompt_template, template_placeholders,
+    PromptTemplate, BUILTIN_PLACEHOLDERS,
+};
+
+fn variable<'a>(
+    template: &'a PromptTemplateConfig,
+    selection: &'a PromptTemplateSelection,
+    name: &str,
+) -> Option<&'a str> {
+    if BUILTIN_PLACEHOLDERS.contains(&name) {
+        return None;
+    }
+    selection
+        .variables
+        .get(name)
+        .or_else(|| template.variables.get(name))
+        .map(String::as_str)
+}
+
+/// Placeholders of the selected template that neither a built-in nor a
+/// variable of the template or selection fills.
+#[must_use]
+pub fn unresolved_placeholders<'a>(
+    template: &'a PromptTemplateConfig,
+    selection: &'a PromptTemplateSelection,
+) -> Vec<&'a str> {
+    let mut unresolved: Vec<&str> = Vec::new();
+    for name in template_placeholders(&template.template) {
+        if !BUILTIN_PLACEHOLDERS.contains(&name)
+            && variable(template, selection, name).is_none()
+            && !unresolved.contains(&name)
+        {
+            unresolved.push(name);
+        }
+    }
+    unresolved
+}
+
+fn render(
+    features: &FeaturesConfig,
+    selection: &PromptTemplateSelection,
+) -> Option<Arc<PromptTemplate>> {
+    let template = features.prompt_templates.get(&selection.template)?;
+    let text = interpolate_template(&template.template, |name| {
+        variable(template, selection, name)
+    });
+    Some(Arc::new(limited(
+        PromptTemplate::new(text, template.tool_list_style),
+        features,
+    )))
+}
+
+fn limited(template: PromptTemplate, features: &FeaturesConfig) -> PromptTemplate {
+    match features.tool_definition_limits {
+        Some(limits) => template.with_tool_limits(limits),
+        None => template,
+    }
+}
+
+/// Resolved FC prompt templates, looked up per request.
+///
+/// A template selected for the requested model wins over the upstream's,
+/// which wins over the global `features.prompt_template`. Without a custom
+/// template the built-in prompt is used in the upstream's (or the global)
+/// `prompt_locale`.
+///
+/// With `features.tool_definition_limits` set, every template carries the
+/// limits, including copies of the built-in prompts.
+#[derive(Debug, Default)]
+pub struct PromptTemplates {
+    global: Option<Arc<PromptTemplate>>,
+    by_upstream: Vec<Option<Arc<PromptTemplate>>>,
+    by_model: FxHashMap<String, Arc<PromptTemplate>>,
+    locale_by_upstream: Vec<PromptLocale>,
+    /// Built-in prompts with tool limits attached, indexed by
+    /// [`LIMITED_BUILTIN_LOCALES`]; empty without limits.
+    limited_builtins: Vec<PromptTemplate>,
+}
+
+const LIMITED_BUILTIN_LOCALES: [PromptLocale; 4] = [
+    PromptLocale::En,
+    PromptLocale::Zh,
+    PromptLocale::Ja,
+    PromptLocale::Es,
+];
+
+impl PromptTemplates {
+    /// Render every selection; unknown names are rejected by config validation.
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Self {
+        let features = &config.features;
+        let global = features.prompt_template.as_ref().map(|text| {
+            Arc::new(limited(
+                PromptTemplate::new(text.as_str(), ToolListStyle::Detailed),
+                features,
+            ))
+        });
+        let by_upstream = config
+            .upstream_services
+            .iter()
+            .map(|upstream| {
+                features
+                    .upstream_prompt_templates
+                    .get(&upstream.name)
+                    .and_then(|selection| render(features, selection))
+            })
+            .collect();
+        let by_model = features
+            .model_prompt_templates
+            .iter()
+            .filter_map(|(model, selection)| Some((model.clone(), render(features, selection)?)))
+            .collect();
+        let locale_by_upstream = config
+            .upstream_services
+            .iter()
+            .map(|upstream| {
+                features
+                    .upstream_prompt_locales
+                    .get(&upstream.name)
+                    .copied()
+                    .unwrap_or(features.prompt_locale)
+            })
+            .collect();
+        let limited_builtins = match features.tool_definition_limits {
+            Some(limits) => LIMITED_BUILTIN_LOCALES
+                .iter()
+                .map(|&locale| PromptTemplate::builtin(locale).with_tool_limits(limits))
+                .collect(),
+            None => Vec::new(),
+        };
+        Self {
+            global,
+            by_upstream,
+            by_model,
+            locale_by_upstream,
+            limited_builtins,
+        }
+    }
+
+    /// Template for a request routed to `upstream_index` for `requested_model`.
+    ///
+    /// `body` is only scanned when the upstream's locale is `auto`.
+    #[must_use]
+    pub fn resolve(
+        &self,
+        upstream_index: usize,
+        requested_model: &str,
+        body: &[u8],
+    ) -> Option<&PromptTemplate> {
+        if !self.by_model.is_empty() {
+            if let Some(template) = self.by_model.get(requested_model) {
+                return Some(template);
+            }
+        }
+        let custom = self
+            .by_upstream
+            .get(upstream_index)
+            .and_then(Option::as_deref)
+            .or(self.global.as_deref());
+        if custom.is_some() {
+            return custom;
+        }
+        let locale = match self.locale_by_upstream.get(upstream_index) {
+            Some(PromptLocale::Auto) => detect_prompt_locale(body),
+            Some(&locale) => locale,
+            None if self.limited_builtins.is_empty() => return None,
+            None => PromptLocale::En,
+        };
+        if self.limited_builtins.is_empty() {
+            return localized_prompt_template(locale);
+        }
+        LIMITED_BUILTIN_LOCALES
+            .iter()
+            .position(|&builtin| builtin == locale)
+            .and_then(|index| self.limited_builtins.get(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::config::{
+        ClientAuthConfig, PathStyle, ServerConfig, StreamSupport, ToolSchemaValidation,
+        UpstreamAuthConfig, UpstreamServiceConfig,
+    };
+
+    fn selection(template: &str, variables: &[(&str, &str)]) -> PromptTemplateSelection {
+        PromptTemplateSelection {
+            template: template.to_string(),
+            variables: variables
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect(),
+        }
+    }
+
+    fn upstream(name: &str) -> UpstreamServiceConfig {
+        UpstreamServiceConfig {
+            name: name.to_string(),
+            provider: "openai".to_string(),
+            base_url: "https://api.example.com/v1".to_string(),
+            api_key: "sk-test".to_string(),
+            models: vec!["gpt-4o".to_string()],
+            description: String::new(),
+            is_default: false,
+            fc_mode: crate::config::FcMode::Inject,
+            api_version: None,
+            proxy: None,
+            proxy_stream: None,
+            proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_prefers_model_then_upstream_then_global() {
+        let mut features = FeaturesConfig {
+            prompt_template: Some("global {tools_list} {trigger_signal}".to_string()),
+            ..FeaturesConfig::default()
+        };
+        features.prompt_templates = BTreeMap::from([(
+            "localized".to_string(),
+            PromptTemplateConfig {
+                template: "Reply in {language}. {tools_list} {trigger_signal} {\"k\": 1}"
+                    .to_string(),
+                tool_list_style: ToolListStyle::Compact,
+                variables: BTreeMap::from([("language".to_string(), "English".to_string())]),
+            },
+        )]);
+        features.upstream_prompt_templates =
+            BTreeMap::from([("second".to_string(), selection("localized", &[]))]);
+        features.model_prompt_templates = BTreeMap::from([(
+            "smart-zh".to_string(),
+            selection("localized", &[("language", "Chinese")]),
+        )]);
+        let config = AppConfig {
+            server: ServerConfig::default(),
+            upstream_services: vec![upstream("first"), upstream("second")],
+            client_authentication: ClientAuthConfig {
+                allowed_keys: vec!["key".to_string()],
+                admin_keys: Vec::new(),
+                key_model_maps: Vec::new(),
+            },
+            features,
+            virtual_models: Vec::new(),
+            routing_rules: Vec::new(),
+            cascade_models: Vec::new(),
+            admin_authentication: None,
+            fallback_chains: Vec::new(),
+            tenants: Vec::new(),
+        };
+        let templates = PromptTemplates::new(&config);
+
+        let by_model = templates
+            .resolve(0, "smart-zh", b"")
+            .expect("model template");
+        assert_eq!(
+            by_model.text(),
+            "Reply in Chinese. {tools_list} {trigger_signal} {\"k\": 1}"
+        );
+        assert_eq!(by_model.tool_list_style(), ToolListStyle::Compact);
+
+        let by_upstream = templates
+            .resolve(1, "gpt-4o", b"")
+            .expect("upstream template");
+        assert!(by_upstream.text().starts_with("Reply in English."));
+
+        let global = templates
+            .resolve(0, "gpt-4o", b"")
+            .expect("global template");
+        assert_eq!(global.text(), "global {tools_list} {trigger_signal}");
+    }
+
+    #[test]
+    fn test_resolve_falls_back_to_localized_builtin_prompt() {
+        let mut features = FeaturesConfig {
+            prompt_locale: PromptLocale::Auto,
+            ..FeaturesConfig::default()
+        };
+        features
+            .upstream_prompt_locales
+            .insert("second".to_string(), PromptLocale::Es);
+        let config = AppConfig {
+            server: ServerConfig::default(),
+            upstream_services: vec![upstream("first"), upstream("second")],
+            client_authentication: ClientAuthConfig {
+                allowed_keys: vec!["key".to_string()],
+                admin_keys: Vec::new(),
+                key_model_maps: Vec::new(),
+            },
+            features,
+            virtual_models: Vec::new(),
+            routing_rules: Vec::new(),
+            cascade_models: Vec::new(),
+            admin_authentication: None,
+            fallback_chains: Vec::new(),
+            tenants: Vec::new(),
+        };
+        let templates = PromptTemplates::new(&config);
+
+        let detected = templates.resolve(0, "gpt-4o", "天気を教えて".as_bytes());
+        assert_eq!(
+            detected,
+            localized_prompt_template(PromptLocale::Ja),
+            "auto locale detects Japanese"
+        );
+        assert!(templates.resolve(0, "gpt-4o", b"hello").is_none());
+        assert_eq!(
+            templates.resolve(1, "gpt-4o", "天気".as_bytes()),
+            localized_prompt_template(PromptLocale::Es)
+        );
+    }
+
+    #[test]
+    fn test_unresolved_placeholders_reports_missing_variables() {
+        let template = PromptTemplateConfig {
+            template: "{tools_list} {language} {trigger_signal} {tone} {language}".to_string(),
+            tool_list_style: ToolListStyle::Detailed,
+            variables: BTreeMap::new(),
+        };
+        assert_eq!(
+            unresolved_placeholders(&template, &selection("t", &[("tone", "dry")])),
+            vec!["language"]
+        );
+        assert!(unresolved_placeholders(
+            &template,
+            &selection("t", &[("tone", "dry"), ("language", "en")])
+        )
+        .is_empty());
+    }
+}
@@ -69,7 +69,7 @@ pub fn validate_tool_call(
         return Ok(());
     }
 
-    let errors = validate_value(arguments, schema, name, 0);
+    let errors = validate_value(arguments, schema, schema, name, 0);
     if errors.is_empty() {
         Ok(())
     } else {
@@ -169,10 +169,14 @@ pub fn validate_parser_tool_calls(
 
 /// Recursively validate a JSON value against a JSON Schema subset.
 ///
+/// Covers the draft 2020-12 validation keywords tool schemas use; `$ref`
+/// resolves JSON pointers into `root`, the tool's parameter schema.
+///
 /// Ported from Python's `_validate_value_against_schema`.
 fn validate_value(
     value: &serde_json::Value,
     schema: &serde_json::Value,
+    root: &serde_json::Value,
     path: &str,
     depth: usize,
 ) -> Vec<ValidationError> {
@@ -180,15 +184,31 @@ fn validate_value(
         return vec![];
     }
 
+    if schema == &serde_json::Value::Bool(false) {
+        return vec![ValidationError {
+            path: path.to_string(),
+            message: "no value is allowed here".to_string(),
+        }];
+    }
     let Some(schema_obj) = schema.as_object() else {
         return vec![];
     };
 
-    if let Some(combinator_errors) = validate_combinators(value, schema_obj, path, depth) {
-        return combinator_errors;
+    let mut errors = Vec::new();
+    if let Some(target) = schema_obj
+        .get("$ref")
+        .and_then(serde_json::Value::as_str)
+        .and_then(|reference| resolve_ref(root, reference))
+    {
+        errors.extend(validate_value(value, target, root, path, depth + 1));
     }
 
-    let mut errors = Vec::new();
+    if let Some(combinator_errors) = validate_combinators(value, schema_obj, root, path, depth) {
+        errors.extend(combinator_errors);
+        return errors;
+    }
+
+    validate_conditionals(value, schema_obj, root, path, depth, &mut errors);
 
     if validate_const_enum(value, schema_obj, path, &mut errors) {
         return errors;
@@ -206,15 +226,55 @@ fn validate_value(
 
     validate_string_constraints(value, schema_obj, path, &mut errors);
     validate_numeric_constraints(value, schema_obj, path, &mut errors);
-    validate_object_constraints(value, schema_obj, path, depth, &mut errors);
-    validate_array_constraints(value, schema_obj, path, depth, &mut errors);
+    validate_object_constraints(value, schema_obj, root, path, depth, &mut errors);
+    validate_array_constraints(value, schema_obj, root, path, depth, &mut errors);
 
     errors
 }
 
+/// The subschema a local `$ref` (`#` or `#/json/pointer`) points to.
+fn resolve_ref<'a>(root: &'a serde_json::Value, reference: &str) -> Option<&'a serde_json::Value> {
+    let pointer = reference.strip_prefix('#')?;
+    if pointer.is_empty() {
+        Some(root)
+    } else {
+        root.pointer(pointer)
+    }
+}
+
+fn validate_conditionals(
+    value: &serde_json::Value,
+    schema_obj: &serde_json::Map<String, serde_json::Value>,
+    root: &serde_json::Value,
+    path: &str,
+    depth: usize,
+    errors: &mut Vec<ValidationError>,
+) {
+    if let Some(not) = schema_obj.get("not") {
+        if validate_value(value, not, root, path, depth + 1).is_empty() {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: "value must not match the 'not' schema".to_string(),
+            });
+        }
+    }
+
+    if let Some(condition) = schema_obj.get("if") {
+        let branch = if validate_value(value, condition, root, path, depth + 1).is_empty() {
+            schema_obj.get("then")
+        } else {
+            schema_obj.get("else")
+        };
+        if let Some(branch) = branch {
+            errors.extend(validate_value(value, branch, root, path, depth + 1));
+        }
+    }
+}
+
 fn validate_combinators(
     value: &serde_json::Value,
     schema_obj: &serde_json::Map<String, serde_json::Value>,
+    root: &serde_json::Value,
     path: &str,
     depth: usize,
 ) -> Option<Vec<ValidationError>> {
@@ -226,6 +286,7 @@ fn validate_combinators(
             errors.extend(validate_value(
                 value,
                 sub_schema,
+                root,
                 &format!("{path}.allOf[{idx}]"),
                 depth + 1,
             ));
@@ -238,7 +299,7 @@ fn validate_combinators(
         for sub in any_of {
             let empty_schema = serde_json::Value::Object(serde_json::Map::new());
             let sub_schema = if sub.is_null() { &empty_schema } else { sub };
-            option_errors.push(validate_value(value, sub_schema, path, depth + 1));
+            option_errors.push(validate_value(value, sub_schema, root, path, depth + 1));
         }
         let mut errors = Vec::new();
         if !option_errors.iter().any(std::vec::Vec::is_empty) {
@@ -255,7 +316,7 @@ fn validate_combinators(
         for sub in one_of {
             let empty_schema = serde_json::Value::Object(serde_json::Map::new());
             let sub_schema = if sub.is_null() { &empty_schema } else { sub };
-            option_errors.push(validate_value(value, sub_schema, path, depth + 1));
+            option_errors.push(validate_value(value, sub_schema, root, path, depth + 1));
         }
         let ok_count = option_errors.iter().filter(|e| e.is_empty()).count();
         let mut errors = Vec::new();
@@ -339,14 +400,12 @@ fn validate_type_constraints(
     errors: &mut Vec<ValidationError>,
 ) -> bool {
     match schema_type {
-        serde_json::Value::String(t) => {
-            if !type_ok(t, value) {
-                errors.push(ValidationError {
-                    path: path.to_string(),
-                    message: format!("expected type '{}', got '{}'", t, json_type_name(value)),
-                });
-                return true;
-            }
+        serde_json::Value::String(t) if !type_ok(t, value) => {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: format!("expected type '{}', got '{}'", t, json_type_name(value)),
+            });
+            return true;
         }
         serde_json::Value::Array(types) => {
             let matches = types
@@ -383,7 +442,7 @@ fn validate_string_constraints(
         .get("minLength")
         .and_then(serde_json::Value::as_u64)
     {
-        if (text.len() as u64) < min_len {
+        if (text.chars().count() as u64) < min_len {
             errors.push(ValidationError {
                 path: path.to_string(),
                 message: format!("string shorter than minLength={min_len}"),
@@ -395,7 +454,7 @@ fn validate_string_constraints(
         .get("maxLength")
         .and_then(serde_json::Value::as_u64)
     {
-        if (text.len() as u64) > max_len {
+        if (text.chars().count() as u64) > max_len {
             errors.push(ValidationError {
                 path: path.to_string(),
                 message: format!("string longer than maxLength={max_len}"),
@@ -470,11 +529,51 @@ fn validate_numeric_constraints(
             }
         }
     }
+
+    let Some(n) = value.as_f64() else {
+        return;
+    };
+    if let Some(min) = schema_obj
+        .get("exclusiveMinimum")
+        .and_then(serde_json::Value::as_f64)
+    {
+        if n <= min {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: format!("value {n} is not greater than exclusiveMinimum {min}"),
+            });
+        }
+    }
+    if let Some(max) = schema_obj
+        .get("exclusiveMaximum")
+        .and_then(serde_json::Value::as_f64)
+    {
+        if n >= max {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: format!("value {n} is not less than exclusiveMaximum {max}"),
+            });
+        }
+    }
+    if let Some(divisor) = schema_obj
+        .get("multipleOf")
+        .and_then(serde_json::Value::as_f64)
+        .filter(|divisor| *divisor > 0.0)
+    {
+        let quotient = n / divisor;
+        if (quotient - quotient.round()).abs() > 1e-9 {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: format!("value {n} is not a multiple of {divisor}"),
+            });
+        }
+    }
 }
 
 fn validate_object_constraints(
     value: &serde_json::Value,
     schema_obj: &serde_json::Map<String, serde_json::Value>,
+    root: &serde_json::Value,
     path: &str,
     depth: usize,
     errors: &mut Vec<ValidationError>,
@@ -503,17 +602,100 @@ fn validate_object_constraints(
         }
     }
 
+    if let Some(dependent) = schema_obj
+        .get("dependentRequired")
+        .and_then(serde_json::Value::as_object)
+    {
+        for (trigger, required) in dependent {
+            if !obj.contains_key(trigger) {
+                continue;
+            }
+            let required = required.as_array().map_or(&[][..], Vec::as_slice);
+            for key in required.iter().filter_map(serde_json::Value::as_str) {
+                if !obj.contains_key(key) {
+                    errors.push(ValidationError {
+                        path: path.to_string(),
+                        message: format!("property '{trigger}' requires property '{key}'"),
+                    });
+                }
+            }
+        }
+    }
+
+    let count = obj.len() as u64;
+    if let Some(min) = schema_obj
+        .get("minProperties")
+        .and_then(serde_json::Value::as_u64)
+    {
+        if count < min {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: format!("object has fewer than minProperties={min}"),
+            });
+        }
+    }
+    if let Some(max) = schema_obj
+        .get("maxProperties")
+        .and_then(serde_json::Value::as_u64)
+    {
+        if count > max {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: format!("object has more than maxProperties={max}"),
+            });
+        }
+    }
+
+    let pattern_properties: Vec<(Regex, &serde_json::Value)> = schema_obj
+        .get("patternProperties")
+        .and_then(serde_json::Value::as_object)
+        .map(|patterns| {
+            patterns
+                .iter()
+                .filter_map(|(pattern, schema)| Some((cached_regex(pattern)?, schema)))
+                .collect()
+        })
+        .unwrap_or_default();
+    let property_names = schema_obj.get("propertyNames");
     let additional = schema_obj.get("additionalProperties");
     for (key, item) in obj {
+        if let Some(names_schema) = property_names {
+            let key_value = serde_json::Value::String(key.clone());
+            if !validate_value(&key_value, names_schema, root, path, depth + 1).is_empty() {
+                errors.push(ValidationError {
+                    path: path.to_string(),
+                    message: format!("property name '{key}' does not match propertyNames"),
+                });
+            }
+        }
+
+        let mut matched = false;
+        for (regex, pattern_schema) in &pattern_properties {
+            if regex.is_match(key) {
+                matched = true;
+                errors.extend(validate_value(
+                    item,
+                    pattern_schema,
+                    root,
+                    &format!("{path}.{key}"),
+                    depth + 1,
+                ));
+            }
+        }
+
         if let Some(prop_schema) = properties.get(key) {
             errors.extend(validate_value(
                 item,
                 prop_schema,
+                root,
                 &format!("{path}.{key}"),
                 depth + 1,
             ));
             continue;
         }
+        if matched {
+            continue;
+        }
 
         match additional {
             Some(serde_json::Value::Bool(false)) => {
@@ -526,6 +708,7 @@ fn validate_object_constraints(
                 errors.extend(validate_value(
                     item,
                     additional_schema,
+                    root,
                     &format!("{path}.{key}"),
                     depth + 1,
                 ));
@@ -538,6 +721,7 @@ fn validate_object_constraints(
 fn validate_array_constraints(
     value: &serde_json::Value,
     schema_obj: &serde_json::Map<String, serde_json::Value>,
+    root: &serde_json::Value,
     path: &str,
     depth: usize,
     errors: &mut Vec<ValidationError>,
@@ -545,17 +729,89 @@ fn validate_array_constraints(
     let Some(arr) = value.as_array() else {
         return;
     };
+
+    let len = arr.len() as u64;
+    if let Some(min) = schema_obj
+        .get("minItems")
+        .and_then(serde_json::Value::as_u64)
+    {
+        if len < min {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: format!("array shorter than minItems={min}"),
+            });
+        }
+    }
+    if let Some(max) = schema_obj
+        .get("maxItems")
+        .and_then(serde_json::Value::as_u64)
+    {
+        if len > max {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: format!("array longer than maxItems={max}"),
+            });
+        }
+    }
+    if schema_obj.get("uniqueItems") == Some(&serde_json::Value::Bool(true)) {
+        let duplicate = arr
+            .iter()
+            .enumerate()
+            .any(|(idx, item)| arr[..idx].contains(item));
+        if duplicate {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: "array items must be unique".to_string(),
+            });
+        }
+    }
+    if let Some(contains) = schema_obj.get("contains") {
+        let matches = arr
+            .iter()
+            .filter(|item| validate_value(item, contains, root, path, depth + 1).is_empty())
+            .count() as u64;
+        let min = schema_obj
+            .get("minContains")
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(1);
+        let max = schema_obj
+            .get("maxContains")
+            .and_then(serde_json::Value::as_u64);
+        if matches < min || max.is_some_and(|max| matches > max) {
+            errors.push(ValidationError {
+                path: path.to_string(),
+                message: format!("array has {matches} items matching 'contains'"),
+            });
+        }
+    }
+
+    // Draft 2020-12: `prefixItems` validates leading items by position and
+    // `items` applies to the rest.
+    let prefix = schema_obj
+        .get("prefixItems")
+        .and_then(serde_json::Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+    for (idx, (item, item_schema)) in arr.iter().zip(prefix).enumerate() {
+        errors.extend(validate_value(
+            item,
+            item_schema,
+            root,
+            &format!("{path}[{idx}]"),
+            depth + 1,
+        ));
+    }
     let Some(items_schema) = schema_obj.get("items") else {
         return;
     };
-    if !items_schema.is_object() {
+    if !items_schema.is_object() && !items_schema.is_boolean() {
         return;
     }
 
-    for (idx, item) in arr.iter().enumerate() {
+    for (idx, item) in arr.iter().enumerate().skip(prefix.len()) {
         errors.extend(validate_value(
             item,
             items_schema,
+            root,
             &format!("{path}[{idx}]"),
             depth + 1,
         ));
@@ -809,6 +1065,67 @@ mod tests {
         assert!(validate_tool_call("test", &json!({"tags": ["a", 1]}), &tools).is_err());
     }
 
+    #[test]
+    fn test_draft_2020_12_refs_and_prefix_items() {
+        let tools = vec![make_tool(
+            "route",
+            json!({
+                "type": "object",
+                "$defs": {
+                    "point": {
+                        "type": "array",
+                        "prefixItems": [{"type": "number"}, {"type": "number"}],
+                        "items": false
+                    }
+                },
+                "properties": {
+                    "from": {"$ref": "#/$defs/point"},
+                    "to": {"$ref": "#/$defs/point"},
+                    "stops": {"type": "integer", "exclusiveMinimum": 0, "multipleOf": 2}
+                },
+                "required": ["from", "to"],
+                "dependentRequired": {"stops": ["via"]},
+                "patternProperties": {"^x-": {"type": "string"}},
+                "additionalProperties": false
+            }),
+        )];
+        let valid = json!({"from": [0, 1.5], "to": [2, 3], "x-note": "ok"});
+        assert!(validate_tool_call("route", &valid, &tools).is_ok());
+
+        let errs = validate_tool_call(
+            "route",
+            &json!({"from": [0, "a"], "to": [1, 2, 3], "stops": 3, "x-note": 1}),
+            &tools,
+        )
+        .unwrap_err();
+        let messages: Vec<String> = errs.iter().map(ToString::to_string).collect();
+        assert!(messages.contains(&"route.from[1]: expected type 'number', got 'string'".into()));
+        assert!(messages.contains(&"route.to[2]: no value is allowed here".into()));
+        assert!(messages.contains(&"route.stops: value 3 is not a multiple of 2".into()));
+        assert!(messages.contains(&"route: property 'stops' requires property 'via'".into()));
+        assert!(messages.contains(&"route.x-note: expected type 'string', got 'integer'".into()));
+    }
+
+    #[test]
+    fn test_if_then_else_and_not() {
+        let tools = vec![make_tool(
+            "ship",
+            json!({
+                "type": "object",
+                "properties": {"mode": {"type": "string"}, "days": {"type": "integer"}},
+                "if": {"properties": {"mode": {"const": "express"}}},
+                "then": {"properties": {"days": {"maximum": 2}}},
+                "not": {"required": ["rush"]}
+            }),
+        )];
+        assert!(validate_tool_call("ship", &json!({"mode": "express", "days": 1}), &tools).is_ok());
+        assert!(validate_tool_call("ship", &json!({"mode": "slow", "days": 9}), &tools).is_ok());
+        assert!(
+            validate_tool_call("ship", &json!({"mode": "express", "days": 5}), &tools).is_err()
+        );
+        assert!(validate_tool_call("ship", &json!({"rush": true}), &tools).is_err());
+    }
+
     #[test]
     fn test_batch_validation() {
         let tools = vec![make_tool(
@@ -176,6 +176,7 @@ fn parse_json_number_end(bytes: &[u8], start: usize) -> Result<usize, ()> {
     Ok(i)
 }
 
+#[cfg(feature = "server")]
 pub(crate) fn find_top_level_field_value_range(
     bytes: &[u8],
     field_name: &[u8],
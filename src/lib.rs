@@ -1,13 +1,24 @@
+#[cfg(feature = "server")]
 pub(crate) mod api;
+#[cfg(feature = "server")]
 pub mod auth;
+#[cfg(feature = "server")]
+pub mod batch;
+#[cfg(feature = "client")]
+pub mod client;
 pub mod config;
 pub mod error;
 pub mod fc;
+#[cfg(feature = "server")]
 pub mod observability;
 pub mod protocol;
+#[cfg(feature = "server")]
 pub mod routing;
+#[cfg(feature = "server")]
 pub mod state;
 pub mod stream;
+pub mod transcode;
+#[cfg(feature = "server")]
 pub mod transport;
 
 pub(crate) mod json_scan;
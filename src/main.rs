@@ -1,6 +1,11 @@
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring_accept;
+
 use std::io;
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::body::Body;
 use axum::http::Request;
@@ -11,24 +16,59 @@ use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::server::conn::auto::Builder as AutoBuilder;
 #[cfg(unix)]
 use socket2::{Domain, Protocol, Socket, Type};
-use toolify_rs::auth::build_allowed_key_set;
-use toolify_rs::config::{load_config, AppConfig, ServerConfig};
+use toolify_rs::auth::{
+    admin_body_digest, hash_api_key, hash_api_key_with_salt, sign_admin_request,
+    ADMIN_CONTENT_SHA256_HEADER, ADMIN_NONCE_HEADER, ADMIN_SIGNATURE_HEADER,
+    ADMIN_TIMESTAMP_HEADER,
+};
+use toolify_rs::batch::BatchStore;
+use toolify_rs::config::migrate::migrate_config_text;
+use toolify_rs::config::{
+    load_config_with_deprecations, parse_config, AppConfig, RuntimeTopology, ServerConfig,
+};
+#[cfg(feature = "analytics")]
+use toolify_rs::observability::analytics::AnalyticsStore;
 use toolify_rs::observability::init_tracing;
+use toolify_rs::observability::journal::{self, RequestJournal};
 use toolify_rs::routing::dispatch::{dispatch_request, normalize_base_path};
-use toolify_rs::routing::ModelRouter;
 use toolify_rs::state::AppState;
-use toolify_rs::transport::{HttpTransport, PreparedUpstream};
 
 const DEFAULT_LISTEN_BACKLOG: i32 = 1024;
 
 fn main() {
-    let config = load_config("config.yaml").unwrap_or_else(|e| {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("journal") {
+        std::process::exit(run_journal_command(&args[1..]));
+    }
+    if args.first().map(String::as_str) == Some("hash-key") {
+        std::process::exit(run_hash_key_command(&args[1..]));
+    }
+    if args.first().map(String::as_str) == Some("admin-sign") {
+        std::process::exit(run_admin_sign_command(&args[1..]));
+    }
+    if matches!(
+        args.first().map(String::as_str),
+        Some("migrate-config" | "--migrate-config")
+    ) {
+        std::process::exit(run_migrate_config_command(&args[1..]));
+    }
+
+    let (config, deprecations) = load_config_with_deprecations("config.yaml").unwrap_or_else(|e| {
         eprintln!("Failed to load configuration: {e}");
         eprintln!("Please copy 'config.example.yaml' to 'config.yaml' and modify as needed.");
         std::process::exit(1);
     });
 
     init_tracing(&config.features.log_level);
+    for deprecation in &deprecations {
+        tracing::warn!(
+            field = %deprecation.field,
+            replacement = %deprecation.replacement,
+            removed_in = deprecation.removed_in,
+            ignored = deprecation.ignored,
+            "deprecated config field: {deprecation}; run `toolify migrate-config config.yaml` to upgrade"
+        );
+    }
     let runtime = build_runtime(&config);
 
     runtime.block_on(async move {
@@ -70,35 +110,29 @@ async fn run(config: AppConfig) {
     let port = config.server.port;
     let base_path = normalize_base_path(&config.server.base_path);
 
-    let model_router = ModelRouter::new(&config);
-    let prepared_upstreams = config
-        .upstream_services
-        .iter()
-        .map(PreparedUpstream::new)
-        .collect();
-    let allowed_client_keys = build_allowed_key_set(&config);
-    let transport = HttpTransport::new_with_upstream_count_and_proxies(
-        &config.server,
-        config.upstream_services.len(),
-        config
-            .upstream_services
-            .iter()
-            .flat_map(|upstream| {
-                [
-                    upstream.proxy.as_deref(),
-                    upstream.proxy_stream.as_deref(),
-                    upstream.proxy_non_stream.as_deref(),
-                ]
-            })
-            .flatten(),
-    );
-    let state = Arc::new(AppState::new(
-        config,
-        transport,
-        model_router,
-        prepared_upstreams,
-        allowed_client_keys,
-    ));
+    let request_journal = open_request_journal(&config.server);
+    let batch_store = open_batch_store(&config.server);
+    #[cfg(feature = "analytics")]
+    let analytics = open_analytics(&config);
+    let mut app_state = AppState::from_config(config);
+    if let Some(request_journal) = request_journal {
+        app_state = app_state.with_request_journal(request_journal);
+    }
+    if let Some(batch_store) = batch_store {
+        app_state = app_state.with_batch_store(batch_store);
+    }
+    #[cfg(feature = "analytics")]
+    if let Some(analytics) = analytics {
+        app_state = app_state.with_analytics(analytics);
+    }
+    let state = Arc::new(app_state);
+    if state.config.server.warmup_upstreams {
+        let warmup_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            warmup_state.warm_up_upstreams().await;
+            tracing::info!("upstream warm-up finished");
+        });
+    }
     let dispatch_state = Arc::clone(&state);
     let dispatch_base_path = Arc::<str>::from(base_path.clone());
 
@@ -138,6 +172,21 @@ async fn run(config: AppConfig) {
         return;
     }
 
+    if state.config.server.runtime_topology == RuntimeTopology::PerListener {
+        spawn_listener_runtimes(
+            listeners,
+            &state.config.server,
+            &dispatch_state,
+            &dispatch_base_path,
+        )
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to start listener runtimes: {err}");
+            std::process::exit(1);
+        });
+        future::pending::<()>().await;
+        return;
+    }
+
     for listener in listeners {
         let loop_builder = conn_builder.clone();
         let request_state = Arc::clone(&dispatch_state);
@@ -149,12 +198,401 @@ async fn run(config: AppConfig) {
     future::pending::<()>().await;
 }
 
+/// Serve each listener from its own single-threaded runtime on a dedicated
+/// thread pinned to one of the cores this process may run on. Background
+/// work (journal, warm-up, model refresh) stays on the main runtime.
+fn spawn_listener_runtimes(
+    listeners: Vec<tokio::net::TcpListener>,
+    server: &ServerConfig,
+    dispatch_state: &Arc<AppState>,
+    dispatch_base_path: &Arc<str>,
+) -> io::Result<()> {
+    let cores = allowed_cores();
+    if cores.is_empty() {
+        tracing::warn!(
+            "server.runtime_topology is per_listener but thread affinity is unavailable on this platform; listener threads are not pinned"
+        );
+    }
+    for (index, listener) in listeners.into_iter().enumerate() {
+        let listener = listener.into_std()?;
+        let core = (!cores.is_empty()).then(|| cores[index % cores.len()]);
+        let max_blocking_threads = server.runtime_max_blocking_threads;
+        let request_state = Arc::clone(dispatch_state);
+        let request_base_path = Arc::clone(dispatch_base_path);
+        let mut thread = std::thread::Builder::new().name(format!("toolify-listener-{index}"));
+        if let Some(thread_stack_size_kb) = server.runtime_thread_stack_size_kb {
+            thread = thread.stack_size(thread_stack_size_kb * 1024);
+        }
+        thread.spawn(move || {
+            if let Some(core) = core {
+                match pin_current_thread(core) {
+                    Ok(()) => tracing::debug!("listener {index} pinned to core {core}"),
+                    Err(err) => {
+                        tracing::warn!("failed to pin listener {index} to core {core}: {err}")
+                    }
+                }
+            }
+            let mut runtime_builder = tokio::runtime::Builder::new_current_thread();
+            runtime_builder.enable_io();
+            runtime_builder.enable_time();
+            if let Some(max_blocking_threads) = max_blocking_threads {
+                runtime_builder.max_blocking_threads(max_blocking_threads);
+            }
+            let runtime = runtime_builder.build().unwrap_or_else(|e| {
+                eprintln!("Failed to initialize Tokio runtime for listener {index}: {e}");
+                std::process::exit(1);
+            });
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap_or_else(|err| {
+                    eprintln!("Failed to register listener {index}: {err}");
+                    std::process::exit(1);
+                });
+                serve_accept_loop(
+                    listener,
+                    AutoBuilder::new(TokioExecutor::new()),
+                    request_state,
+                    request_base_path,
+                )
+                .await;
+            });
+        })?;
+    }
+    Ok(())
+}
+
+/// Cores in this process's affinity mask, in ascending order.
+#[cfg(target_os = "linux")]
+fn allowed_cores() -> Vec<usize> {
+    // SAFETY: `cpu_set_t` is plain data, and `sched_getaffinity` writes at
+    // most `size_of::<cpu_set_t>()` bytes into it.
+    let set = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Vec::new();
+        }
+        set
+    };
+    (0..std::mem::size_of::<libc::cpu_set_t>() * 8)
+        // SAFETY: `cpu` is below the bit size of `set`.
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn allowed_cores() -> Vec<usize> {
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+fn pin_current_thread(core: usize) -> io::Result<()> {
+    // SAFETY: `cpu_set_t` is plain data, `core` comes from `allowed_cores`
+    // so it is within the set, and `sched_setaffinity` only reads the set.
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_core: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "thread affinity is only supported on Linux",
+    ))
+}
+
+fn open_request_journal(server: &ServerConfig) -> Option<Arc<RequestJournal>> {
+    let path = server.journal_path.as_deref()?;
+    let request_journal = Arc::new(RequestJournal::open(path).unwrap_or_else(|err| {
+        eprintln!("Failed to open request journal {path}: {err}");
+        std::process::exit(1);
+    }));
+    tokio::spawn(Arc::clone(&request_journal).run_maintenance(
+        Duration::from_millis(server.journal_flush_interval_ms),
+        Duration::from_secs(server.journal_compact_interval_secs),
+    ));
+    tracing::info!("request journal enabled at {path}");
+    Some(request_journal)
+}
+
+#[cfg(feature = "analytics")]
+fn open_analytics(config: &AppConfig) -> Option<Arc<AnalyticsStore>> {
+    let analytics = config.features.analytics.as_ref()?;
+    let store = Arc::new(AnalyticsStore::open(analytics).unwrap_or_else(|err| {
+        eprintln!(
+            "Failed to open analytics database {}: {err}",
+            analytics.path
+        );
+        std::process::exit(1);
+    }));
+    tokio::spawn(
+        Arc::clone(&store).run_maintenance(Duration::from_millis(analytics.flush_interval_ms)),
+    );
+    tracing::info!("analytics enabled at {}", analytics.path);
+    Some(store)
+}
+
+fn open_batch_store(server: &ServerConfig) -> Option<Arc<BatchStore>> {
+    let dir = server.batch_storage_dir.as_deref()?;
+    let store = BatchStore::open(dir).unwrap_or_else(|err| {
+        eprintln!("Failed to open batch storage {dir}: {err}");
+        std::process::exit(1);
+    });
+    tracing::info!("batch API enabled with storage at {dir}");
+    Some(Arc::new(store))
+}
+
+/// `toolify journal <replay|inspect|compact> <path>` and
+/// `toolify journal error <path> <reference>`: offline journal tooling.
+fn run_journal_command(args: &[String]) -> i32 {
+    let (Some(command), Some(path)) = (args.first(), args.get(1)) else {
+        eprintln!("usage: toolify journal <replay|inspect|compact> <path>");
+        eprintln!("       toolify journal error <path> <reference>");
+        return 2;
+    };
+    let path = Path::new(path);
+    let outcome = match command.as_str() {
+        "replay" => journal::replay(path).and_then(|summary| print_json(&summary)),
+        "compact" => journal::compact_file(path, None).and_then(|summary| print_json(&summary)),
+        "inspect" => journal::read_records(path).and_then(|(records, malformed)| {
+            for record in &records {
+                println!(
+                    "{}",
+                    serde_json::to_string(record).map_err(io::Error::other)?
+                );
+            }
+            eprintln!("{} records, {malformed} malformed lines", records.len());
+            Ok(())
+        }),
+        "error" => {
+            let Some(wanted) = args.get(2) else {
+                eprintln!("usage: toolify journal error <path> <reference>");
+                return 2;
+            };
+            journal::read_records(path).and_then(|(records, _)| {
+                let found = records.iter().find(|record| {
+                    matches!(
+                        record,
+                        journal::JournalRecord::UpstreamError { reference, .. } if reference == wanted
+                    )
+                });
+                match found {
+                    Some(record) => print_json(record),
+                    None => Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no upstream error with reference '{wanted}'"),
+                    )),
+                }
+            })
+        }
+        other => {
+            eprintln!(
+                "unknown journal command '{other}' (expected replay, inspect, compact, or error)"
+            );
+            return 2;
+        }
+    };
+    match outcome {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("journal {command} failed for {}: {err}", path.display());
+            1
+        }
+    }
+}
+
+/// `toolify hash-key [--salt <salt>] [key]`: print the salted hash of a
+/// client or admin key, read from stdin when not given, for use in
+/// `allowed_keys`/`admin_keys`. Hashed `allowed_keys` share one salt, so pass
+/// the salt of the existing entries with `--salt`.
+fn run_hash_key_command(args: &[String]) -> i32 {
+    let (salt, args) = match args {
+        [flag, salt, rest @ ..] if flag == "--salt" => (Some(salt.as_str()), rest),
+        _ => (None, args),
+    };
+    let key = match args.first() {
+        Some(key) => key.clone(),
+        None => {
+            let mut line = String::new();
+            if let Err(err) = io::stdin().read_line(&mut line) {
+                eprintln!("failed to read key from stdin: {err}");
+                return 1;
+            }
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if key.trim().is_empty() {
+        eprintln!(
+            "usage: toolify hash-key [--salt <salt>] [key]  (reads the key from stdin when omitted)"
+        );
+        return 2;
+    }
+    match salt {
+        Some("") => {
+            eprintln!("--salt must not be empty");
+            return 2;
+        }
+        Some(salt) => println!("{}", hash_api_key_with_salt(&key, salt)),
+        None => println!("{}", hash_api_key(&key)),
+    }
+    0
+}
+
+/// `toolify admin-sign <METHOD> <path> [body-file]`: print the headers of a
+/// signed admin request for `admin_authentication.replay_protection`, reading
+/// the admin token from stdin. `path` is the path and query exactly as sent;
+/// `body-file` holds the exact body to send, empty when omitted.
+fn run_admin_sign_command(args: &[String]) -> i32 {
+    let (Some(method), Some(path)) = (args.first(), args.get(1)) else {
+        eprintln!(
+            "usage: toolify admin-sign <METHOD> <path> [body-file]  (reads the admin token from stdin)"
+        );
+        return 2;
+    };
+    let body = match args.get(2) {
+        Some(body_path) => match std::fs::read(body_path) {
+            Ok(body) => body,
+            Err(err) => {
+                eprintln!("failed to read {body_path}: {err}");
+                return 1;
+            }
+        },
+        None => Vec::new(),
+    };
+    let mut token = String::new();
+    if let Err(err) = io::stdin().read_line(&mut token) {
+        eprintln!("failed to read token from stdin: {err}");
+        return 1;
+    }
+    let token = token.trim_end_matches(['\r', '\n']);
+    if token.is_empty() {
+        eprintln!("no admin token on stdin");
+        return 2;
+    }
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+        .to_string();
+    let nonce = format!("{:032x}", fastrand::u128(..));
+    let method = method.to_ascii_uppercase();
+    let body_sha256 = admin_body_digest(&body);
+    let signature = sign_admin_request(token, &timestamp, &nonce, &method, path, &body_sha256);
+    println!("{ADMIN_TIMESTAMP_HEADER}: {timestamp}");
+    println!("{ADMIN_NONCE_HEADER}: {nonce}");
+    println!("{ADMIN_CONTENT_SHA256_HEADER}: {body_sha256}");
+    println!("{ADMIN_SIGNATURE_HEADER}: {signature}");
+    0
+}
+
+/// `toolify migrate-config <path> [output]`: rename deprecated fields,
+/// writing `output` or, without one, rewriting `path` after saving a `.bak`
+/// copy.
+fn run_migrate_config_command(args: &[String]) -> i32 {
+    let Some(path) = args.first() else {
+        eprintln!("usage: toolify migrate-config <path> [output]");
+        return 2;
+    };
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("failed to read {path}: {err}");
+            return 1;
+        }
+    };
+    let migrated = match migrate_config_text(&contents) {
+        Ok(migrated) => migrated,
+        Err(err) => {
+            eprintln!("failed to parse {path}: {err}");
+            return 1;
+        }
+    };
+    for deprecation in &migrated.deprecations {
+        eprintln!("warning: {deprecation}");
+    }
+    let output = match args.get(1) {
+        Some(output) => output.clone(),
+        None if migrated.deprecations.is_empty() => {
+            eprintln!("{path} uses no deprecated fields");
+            return 0;
+        }
+        None => {
+            let backup = format!("{path}.bak");
+            if let Err(err) = std::fs::copy(path, &backup) {
+                eprintln!("failed to back up {path} to {backup}: {err}");
+                return 1;
+            }
+            eprintln!("saved the original config to {backup}");
+            path.clone()
+        }
+    };
+    if migrated.reformatted {
+        eprintln!("warning: comments and layout were not kept; review {output}");
+    }
+    if let Err(err) = std::fs::write(&output, &migrated.contents) {
+        eprintln!("failed to write {output}: {err}");
+        return 1;
+    }
+    eprintln!(
+        "wrote {output} ({} deprecated fields renamed)",
+        migrated.deprecations.len()
+    );
+    match parse_config(&migrated.contents) {
+        Ok(_) => 0,
+        Err(err) => {
+            eprintln!("the migrated config does not load yet: {err}");
+            1
+        }
+    }
+}
+
+fn print_json(value: &impl serde::Serialize) -> io::Result<()> {
+    let rendered = serde_json::to_string_pretty(value).map_err(io::Error::other)?;
+    println!("{rendered}");
+    Ok(())
+}
+
 async fn serve_accept_loop(
     listener: tokio::net::TcpListener,
     conn_builder: AutoBuilder<TokioExecutor>,
     dispatch_state: Arc<AppState>,
     dispatch_base_path: Arc<str>,
 ) {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    let listener = match uring_accept::UringAcceptor::start(listener) {
+        Ok(mut acceptor) => loop {
+            let stream = match acceptor.accept().await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("Accept error: {err}");
+                    continue;
+                }
+            };
+            let remote_addr = match stream.peer_addr() {
+                Ok(remote_addr) => remote_addr,
+                Err(err) => {
+                    tracing::debug!("dropping accepted connection without a peer: {err}");
+                    continue;
+                }
+            };
+            serve_connection(
+                stream,
+                remote_addr,
+                &conn_builder,
+                &dispatch_state,
+                &dispatch_base_path,
+            );
+        },
+        Err((listener, err)) => {
+            tracing::warn!("io_uring accept unavailable ({err}); using the tokio accept loop");
+            listener
+        }
+    };
+
     loop {
         let (stream, remote_addr) = match listener.accept().await {
             Ok((stream, remote_addr)) => (stream, remote_addr),
@@ -163,29 +601,44 @@ async fn serve_accept_loop(
                 continue;
             }
         };
+        serve_connection(
+            stream,
+            remote_addr,
+            &conn_builder,
+            &dispatch_state,
+            &dispatch_base_path,
+        );
+    }
+}
 
-        if let Err(err) = stream.set_nodelay(true) {
-            tracing::debug!("failed to enable TCP_NODELAY for {remote_addr}: {err}");
-        }
+fn serve_connection(
+    stream: tokio::net::TcpStream,
+    remote_addr: SocketAddr,
+    conn_builder: &AutoBuilder<TokioExecutor>,
+    dispatch_state: &Arc<AppState>,
+    dispatch_base_path: &Arc<str>,
+) {
+    if let Err(err) = stream.set_nodelay(true) {
+        tracing::debug!("failed to enable TCP_NODELAY for {remote_addr}: {err}");
+    }
 
-        let io = TokioIo::new(stream);
-        let conn_builder = conn_builder.clone();
-        let request_state = Arc::clone(&dispatch_state);
-        let request_base_path = Arc::clone(&dispatch_base_path);
-        let hyper_service = service_fn(move |request: Request<Incoming>| {
-            dispatch_request(
-                Arc::clone(&request_state),
-                Arc::clone(&request_base_path),
-                request.map(Body::new),
-            )
-        });
+    let io = TokioIo::new(stream);
+    let conn_builder = conn_builder.clone();
+    let request_state = Arc::clone(dispatch_state);
+    let request_base_path = Arc::clone(dispatch_base_path);
+    let hyper_service = service_fn(move |request: Request<Incoming>| {
+        dispatch_request(
+            Arc::clone(&request_state),
+            Arc::clone(&request_base_path),
+            request.map(Body::new),
+        )
+    });
 
-        tokio::spawn(async move {
-            if let Err(err) = conn_builder.serve_connection(io, hyper_service).await {
-                tracing::debug!("failed to serve connection from {remote_addr}: {err:#}");
-            }
-        });
-    }
+    tokio::spawn(async move {
+        if let Err(err) = conn_builder.serve_connection(io, hyper_service).await {
+            tracing::debug!("failed to serve connection from {remote_addr}: {err:#}");
+        }
+    });
 }
 
 async fn build_server_listeners(
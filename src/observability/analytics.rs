@@ -0,0 +1,421 @@
+//! Embedded SQLite request analytics (`analytics` feature).
+//!
+//! Every model API request becomes one row: who sent it, where it was
+//! routed, how long it took, and the tokens it used. Rows are buffered in
+//! memory and written in one transaction per flush interval, so the request
+//! path never waits on disk. The admin API runs a few canned reports over
+//! the table; nothing else reads it, and ad-hoc queries can open the file
+//! with any SQLite client.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::response::Response;
+use parking_lot::Mutex;
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+
+use super::journal::{ingress_label, tap_usage, unix_now_ms, UsageSink, UsageTally};
+use crate::config::{AnalyticsConfig, ModelPrice};
+use crate::protocol::canonical::IngressApi;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS requests (
+    id INTEGER PRIMARY KEY,
+    ts_ms INTEGER NOT NULL,
+    ingress TEXT NOT NULL,
+    client_key TEXT,
+    model TEXT NOT NULL,
+    upstream TEXT,
+    routing_rule TEXT,
+    stream INTEGER NOT NULL,
+    quality_retries INTEGER NOT NULL,
+    status INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    input_tokens INTEGER NOT NULL,
+    output_tokens INTEGER NOT NULL,
+    cached_input_tokens INTEGER NOT NULL,
+    reasoning_tokens INTEGER NOT NULL,
+    cost_usd REAL
+);
+CREATE INDEX IF NOT EXISTS requests_ts_ms ON requests (ts_ms);
+";
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Routing facts of one request, known once its response is ready.
+#[derive(Debug, Clone)]
+pub struct RequestFacts {
+    pub ingress: IngressApi,
+    /// Fingerprint of the client key, see [`crate::auth::key_fingerprint`].
+    pub client_key: Option<String>,
+    /// Model as the client requested it.
+    pub model: String,
+    pub upstream: Option<String>,
+    pub routing_rule: Option<String>,
+    pub stream: bool,
+    pub quality_retries: u32,
+}
+
+#[derive(Debug, Clone)]
+struct RequestRow {
+    ts_ms: u64,
+    facts: RequestFacts,
+    status: u16,
+    duration_ms: u64,
+    usage: UsageTally,
+    cost_usd: Option<f64>,
+}
+
+/// A canned report served by `GET /admin/analytics`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalyticsReport {
+    /// Requests and tokens per model, busiest first.
+    TopModels,
+    /// Requests, tokens, and priced spend per client key, costliest first.
+    KeySpend,
+    /// Error rate per upstream, worst first.
+    UpstreamErrors,
+}
+
+impl FromStr for AnalyticsReport {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "top_models" => Ok(Self::TopModels),
+            "key_spend" => Ok(Self::KeySpend),
+            "upstream_errors" => Ok(Self::UpstreamErrors),
+            other => Err(format!(
+                "unknown report '{other}'; expected top_models, key_spend, or upstream_errors"
+            )),
+        }
+    }
+}
+
+/// Shared writer and reader of the analytics database.
+pub struct AnalyticsStore {
+    connection: Mutex<Connection>,
+    pending: Mutex<Vec<RequestRow>>,
+    retention_days: u64,
+    prices: BTreeMap<String, ModelPrice>,
+}
+
+impl AnalyticsStore {
+    /// Open (or create) the database at `config.path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the SQLite error when the file cannot be opened or migrated.
+    pub fn open(config: &AnalyticsConfig) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(Path::new(&config.path))?, config)
+    }
+
+    /// A store backed by an in-memory database, for tests and tooling.
+    ///
+    /// # Errors
+    ///
+    /// Returns the SQLite error when the schema cannot be created.
+    pub fn open_in_memory(config: &AnalyticsConfig) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?, config)
+    }
+
+    fn with_connection(connection: Connection, config: &AnalyticsConfig) -> rusqlite::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+            pending: Mutex::new(Vec::new()),
+            retention_days: config.retention_days,
+            prices: config.prices.clone(),
+        })
+    }
+
+    /// The handle that records a request started at `started` once its
+    /// response ends.
+    #[must_use]
+    pub fn entry(self: &Arc<Self>, facts: RequestFacts, started: Instant) -> AnalyticsEntry {
+        AnalyticsEntry {
+            store: Arc::clone(self),
+            facts,
+            started,
+        }
+    }
+
+    fn cost_usd(&self, model: &str, usage: UsageTally) -> Option<f64> {
+        let price = self.prices.get(model)?;
+        #[allow(clippy::cast_precision_loss)]
+        let cost = (usage.input_tokens as f64 * price.input_per_mtok
+            + usage.output_tokens as f64 * price.output_per_mtok)
+            / 1_000_000.0;
+        Some(cost)
+    }
+
+    /// Write buffered rows in one transaction and drop rows past retention.
+    ///
+    /// # Errors
+    ///
+    /// Returns the SQLite error; the rows of a failed write are dropped.
+    pub fn flush(&self) -> rusqlite::Result<usize> {
+        let rows = std::mem::take(&mut *self.pending.lock());
+        let mut connection = self.connection.lock();
+        let transaction = connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(
+                "INSERT INTO requests (ts_ms, ingress, client_key, model, upstream, routing_rule,
+                    stream, quality_retries, status, duration_ms, input_tokens, output_tokens,
+                    cached_input_tokens, reasoning_tokens, cost_usd)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            )?;
+            for row in &rows {
+                insert.execute(params![
+                    row.ts_ms,
+                    ingress_label(row.facts.ingress),
+                    row.facts.client_key,
+                    row.facts.model,
+                    row.facts.upstream,
+                    row.facts.routing_rule,
+                    row.facts.stream,
+                    row.facts.quality_retries,
+                    row.status,
+                    row.duration_ms,
+                    row.usage.input_tokens,
+                    row.usage.output_tokens,
+                    row.usage.cached_input_tokens,
+                    row.usage.reasoning_tokens,
+                    row.cost_usd,
+                ])?;
+            }
+        }
+        if self.retention_days > 0 {
+            let cutoff = unix_now_ms().saturating_sub(self.retention_days * DAY_MS);
+            transaction.execute("DELETE FROM requests WHERE ts_ms < ?1", [cutoff])?;
+        }
+        transaction.commit()?;
+        Ok(rows.len())
+    }
+
+    /// Periodically write buffered rows.
+    pub async fn run_maintenance(self: Arc<Self>, flush_interval: Duration) {
+        let mut ticker = tokio::time::interval(flush_interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if self.pending.lock().is_empty() {
+                continue;
+            }
+            let store = Arc::clone(&self);
+            match tokio::task::spawn_blocking(move || store.flush()).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => tracing::warn!(error = %err, "analytics write failed"),
+                Err(err) => tracing::warn!(error = %err, "analytics flush task failed"),
+            }
+        }
+    }
+
+    /// Run `report` over rows from the last `since_ms` milliseconds, returning
+    /// at most `limit` result rows. Rows not yet flushed are not included.
+    ///
+    /// # Errors
+    ///
+    /// Returns the SQLite error when the query fails.
+    pub fn report(
+        &self,
+        report: AnalyticsReport,
+        since_ms: u64,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<Value>> {
+        let cutoff = unix_now_ms().saturating_sub(since_ms);
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let connection = self.connection.lock();
+        match report {
+            AnalyticsReport::TopModels => {
+                let mut query = connection.prepare_cached(
+                    "SELECT model, COUNT(*), SUM(input_tokens), SUM(output_tokens),
+                        CAST(AVG(duration_ms) AS INTEGER)
+                     FROM requests WHERE ts_ms >= ?1
+                     GROUP BY model ORDER BY COUNT(*) DESC, model LIMIT ?2",
+                )?;
+                let rows = query.query_map(params![cutoff, limit], |row| {
+                    Ok(json!({
+                        "model": row.get::<_, String>(0)?,
+                        "requests": row.get::<_, i64>(1)?,
+                        "input_tokens": row.get::<_, i64>(2)?,
+                        "output_tokens": row.get::<_, i64>(3)?,
+                        "avg_duration_ms": row.get::<_, i64>(4)?,
+                    }))
+                })?;
+                rows.collect()
+            }
+            AnalyticsReport::KeySpend => {
+                let mut query = connection.prepare_cached(
+                    "SELECT client_key, COUNT(*), SUM(input_tokens), SUM(output_tokens),
+                        SUM(cost_usd)
+                     FROM requests WHERE ts_ms >= ?1
+                     GROUP BY client_key
+                     ORDER BY COALESCE(SUM(cost_usd), 0) DESC,
+                        SUM(input_tokens) + SUM(output_tokens) DESC
+                     LIMIT ?2",
+                )?;
+                let rows = query.query_map(params![cutoff, limit], |row| {
+                    Ok(json!({
+                        "client_key": row.get::<_, Option<String>>(0)?,
+                        "requests": row.get::<_, i64>(1)?,
+                        "input_tokens": row.get::<_, i64>(2)?,
+                        "output_tokens": row.get::<_, i64>(3)?,
+                        "cost_usd": row.get::<_, Option<f64>>(4)?,
+                    }))
+                })?;
+                rows.collect()
+            }
+            AnalyticsReport::UpstreamErrors => {
+                let mut query = connection.prepare_cached(
+                    "SELECT upstream, COUNT(*), SUM(status >= 400 AND status != 499),
+                        SUM(status = 499)
+                     FROM requests WHERE ts_ms >= ?1
+                     GROUP BY upstream
+                     ORDER BY CAST(SUM(status >= 400 AND status != 499) AS REAL) / COUNT(*) DESC,
+                        COUNT(*) DESC
+                     LIMIT ?2",
+                )?;
+                let rows = query.query_map(params![cutoff, limit], |row| {
+                    let requests = row.get::<_, i64>(1)?;
+                    let errors = row.get::<_, i64>(2)?;
+                    #[allow(clippy::cast_precision_loss)]
+                    let error_rate = errors as f64 / requests.max(1) as f64;
+                    Ok(json!({
+                        "upstream": row.get::<_, Option<String>>(0)?,
+                        "requests": requests,
+                        "errors": errors,
+                        "client_disconnects": row.get::<_, i64>(3)?,
+                        "error_rate": error_rate,
+                    }))
+                })?;
+                rows.collect()
+            }
+        }
+    }
+}
+
+/// In-flight analytics row for one request; recorded exactly once.
+pub struct AnalyticsEntry {
+    store: Arc<AnalyticsStore>,
+    facts: RequestFacts,
+    started: Instant,
+}
+
+impl AnalyticsEntry {
+    /// Record a request that produced no response body.
+    pub fn finish(self, status: u16) {
+        self.finish_with_usage(status, UsageTally::default());
+    }
+
+    /// Wrap a response so the row is recorded once its body completes, with
+    /// status 499 when the client goes away first.
+    #[must_use]
+    pub fn tap_response(self, response: Response) -> Response {
+        tap_usage(response, self)
+    }
+}
+
+impl UsageSink for AnalyticsEntry {
+    fn finish_with_usage(self, status: u16, usage: UsageTally) {
+        let row = RequestRow {
+            ts_ms: unix_now_ms(),
+            status,
+            duration_ms: u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            usage,
+            cost_usd: self.store.cost_usd(&self.facts.model, usage),
+            facts: self.facts,
+        };
+        self.store.pending.lock().push(row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AnalyticsConfig {
+        AnalyticsConfig {
+            path: String::new(),
+            flush_interval_ms: 1000,
+            retention_days: 30,
+            prices: BTreeMap::from([(
+                "gpt-4o".to_string(),
+                ModelPrice {
+                    input_per_mtok: 2.5,
+                    output_per_mtok: 10.0,
+                },
+            )]),
+        }
+    }
+
+    fn facts(key: &str, model: &str, upstream: &str) -> RequestFacts {
+        RequestFacts {
+            ingress: IngressApi::OpenAiChat,
+            client_key: Some(key.to_string()),
+            model: model.to_string(),
+            upstream: Some(upstream.to_string()),
+            routing_rule: None,
+            stream: false,
+            quality_retries: 0,
+        }
+    }
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> UsageTally {
+        UsageTally {
+            input_tokens,
+            output_tokens,
+            ..UsageTally::default()
+        }
+    }
+
+    #[test]
+    fn test_reports_group_flushed_rows() {
+        let store = Arc::new(AnalyticsStore::open_in_memory(&config()).expect("open"));
+        let started = Instant::now();
+        for _ in 0..4 {
+            store
+                .entry(facts("aaa", "gpt-4o", "openai"), started)
+                .finish_with_usage(200, usage(1_000_000, 100_000));
+        }
+        store
+            .entry(facts("bbb", "claude", "anthropic"), started)
+            .finish_with_usage(200, usage(10, 5));
+        store
+            .entry(facts("bbb", "claude", "anthropic"), started)
+            .finish(529);
+        store
+            .entry(facts("bbb", "claude", "anthropic"), started)
+            .finish(499);
+        assert_eq!(store.flush().expect("flush"), 7);
+
+        let models = store
+            .report(AnalyticsReport::TopModels, DAY_MS, 10)
+            .expect("top models");
+        assert_eq!(models[0]["model"], "gpt-4o");
+        assert_eq!(models[0]["requests"], 4);
+        assert_eq!(models[1]["requests"], 3);
+
+        let spend = store
+            .report(AnalyticsReport::KeySpend, DAY_MS, 10)
+            .expect("key spend");
+        assert_eq!(spend[0]["client_key"], "aaa");
+        assert_eq!(spend[0]["cost_usd"], 14.0);
+        assert_eq!(spend[1]["cost_usd"], Value::Null);
+        assert_eq!(spend[1]["output_tokens"], 5);
+
+        let errors = store
+            .report(AnalyticsReport::UpstreamErrors, DAY_MS, 1)
+            .expect("upstream errors");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["upstream"], "anthropic");
+        assert_eq!(errors[0]["errors"], 1);
+        assert_eq!(errors[0]["client_disconnects"], 1);
+
+        assert!("nope".parse::<AnalyticsReport>().is_err());
+    }
+}
@@ -0,0 +1,941 @@
+//! Append-only request journal for crash-safe usage accounting.
+//!
+//! Every journaled request writes a `start` record when it is accepted and an
+//! `end` record (status + token usage) once its response body has been fully
+//! delivered or dropped. Records are JSON lines buffered in memory and flushed
+//! to disk on an interval; compaction folds completed pairs into per-model
+//! `totals` records so the file stays bounded. After a crash, [`replay`]
+//! recovers totals from whatever reached disk. Upstream errors hidden from
+//! clients behind a reference id are kept as `upstream_error` records, which
+//! compaction retains.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::response::Response;
+use futures_util::Stream;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::canonical::IngressApi;
+
+/// Cap on how much of a non-SSE response body is buffered for usage parsing.
+const MAX_JSON_USAGE_SCAN_BYTES: usize = 8 * 1024 * 1024;
+
+/// Cap on records kept in memory while flushing fails; the oldest go first.
+const MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+
+/// One line of the journal file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JournalRecord {
+    Start {
+        run: u64,
+        seq: u64,
+        ts_ms: u64,
+        ingress: String,
+        model: String,
+    },
+    End {
+        run: u64,
+        seq: u64,
+        ts_ms: u64,
+        status: u16,
+        #[serde(default)]
+        input_tokens: u64,
+        #[serde(default)]
+        output_tokens: u64,
+        #[serde(default)]
+        cached_input_tokens: u64,
+        #[serde(default)]
+        reasoning_tokens: u64,
+        duration_ms: u64,
+    },
+    /// An upstream error the client only saw by its `reference`.
+    UpstreamError {
+        run: u64,
+        seq: u64,
+        ts_ms: u64,
+        reference: String,
+        #[serde(flatten)]
+        detail: UpstreamErrorDetail,
+    },
+    Totals {
+        model: String,
+        requests: u64,
+        #[serde(default)]
+        incomplete: u64,
+        input_tokens: u64,
+        output_tokens: u64,
+        #[serde(default)]
+        cached_input_tokens: u64,
+        #[serde(default)]
+        reasoning_tokens: u64,
+    },
+}
+
+/// What an upstream error response said, kept for operators.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamErrorDetail {
+    pub upstream: String,
+    pub status: u16,
+    /// Request ids, rate limit, and retry headers of the response.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Start of the response body.
+    #[serde(default)]
+    pub body: String,
+}
+
+/// Aggregated usage for one model.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ModelTotals {
+    pub requests: u64,
+    /// Requests that started but never recorded an end (crash or still in flight).
+    pub incomplete: u64,
+    /// Input tokens, including those read from the prompt cache.
+    pub input_tokens: u64,
+    /// Output tokens, including reasoning tokens.
+    pub output_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub reasoning_tokens: u64,
+}
+
+/// Totals recovered from a journal file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct JournalSummary {
+    pub records: u64,
+    pub malformed_lines: u64,
+    pub models: BTreeMap<String, ModelTotals>,
+}
+
+/// Shared writer for the request journal.
+pub struct RequestJournal {
+    path: PathBuf,
+    run: u64,
+    next_seq: AtomicU64,
+    pending: Mutex<Vec<u8>>,
+    file_lock: Mutex<()>,
+    dropped_records: AtomicU64,
+}
+
+impl RequestJournal {
+    /// Open (or create) the journal at `path` for appending.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error when the journal file cannot be created.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            run: unix_now_ms(),
+            next_seq: AtomicU64::new(0),
+            pending: Mutex::new(Vec::with_capacity(16 * 1024)),
+            file_lock: Mutex::new(()),
+            dropped_records: AtomicU64::new(0),
+        })
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records dropped because the journal could not be written for too long.
+    #[must_use]
+    pub fn dropped_records(&self) -> u64 {
+        self.dropped_records.load(Ordering::Relaxed)
+    }
+
+    /// Record the start of a request and return the handle that records its end.
+    #[must_use]
+    pub fn begin(self: &Arc<Self>, ingress: IngressApi, model: &str) -> JournalEntry {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        self.append(&JournalRecord::Start {
+            run: self.run,
+            seq,
+            ts_ms: unix_now_ms(),
+            ingress: ingress_label(ingress).to_string(),
+            model: model.to_string(),
+        });
+        JournalEntry {
+            journal: Arc::clone(self),
+            seq,
+            started: Instant::now(),
+        }
+    }
+
+    fn append(&self, record: &JournalRecord) {
+        let Ok(mut line) = serde_json::to_vec(record) else {
+            return;
+        };
+        line.push(b'\n');
+        self.pending.lock().extend_from_slice(&line);
+    }
+
+    /// Write buffered records to disk and sync them.
+    ///
+    /// Records that did not reach the file stay buffered for the next flush.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error when appending to or syncing the journal fails.
+    pub fn flush(&self) -> io::Result<()> {
+        let _file_guard = self.file_lock.lock();
+        let buffered = std::mem::take(&mut *self.pending.lock());
+        if buffered.is_empty() {
+            return Ok(());
+        }
+        let mut written = 0;
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| {
+                while written < buffered.len() {
+                    match file.write(&buffered[written..]) {
+                        Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                        Ok(n) => written += n,
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+                file.sync_data()
+            });
+        if result.is_err() && written < buffered.len() {
+            let mid_record = written > 0 && buffered[written - 1] != b'\n';
+            self.requeue(&buffered[written..], mid_record);
+        }
+        result
+    }
+
+    /// Put `unwritten` back ahead of the records appended since, dropping the
+    /// oldest records beyond [`MAX_PENDING_BYTES`].
+    fn requeue(&self, unwritten: &[u8], mid_record: bool) {
+        let mut pending = self.pending.lock();
+        let mut requeued = Vec::with_capacity(unwritten.len() + pending.len());
+        requeued.extend_from_slice(unwritten);
+        requeued.extend_from_slice(&pending);
+        let excess = requeued.len().saturating_sub(MAX_PENDING_BYTES);
+        if excess > 0 {
+            // Cut after a newline so the records kept stay whole.
+            let cut = requeued[excess - 1..]
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .map_or(requeued.len(), |at| excess + at);
+            let dropped = requeued[..cut]
+                .iter()
+                .filter(|&&byte| byte == b'\n')
+                .count();
+            self.dropped_records
+                .fetch_add(dropped as u64, Ordering::Relaxed);
+            requeued.drain(..cut);
+            if mid_record {
+                // End the half-written record on disk so it does not swallow
+                // the next one; replay counts it as malformed.
+                requeued.insert(0, b'\n');
+            }
+        }
+        *pending = requeued;
+    }
+
+    /// Flush pending records, then compact the file in place.
+    ///
+    /// Starts from this process that have not ended yet are kept as-is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error when reading, rewriting, or renaming the journal fails.
+    pub fn compact(&self) -> io::Result<JournalSummary> {
+        self.flush()?;
+        let _file_guard = self.file_lock.lock();
+        compact_file(&self.path, Some(self.run))
+    }
+
+    /// Periodically flush buffered records and compact the journal.
+    pub async fn run_maintenance(
+        self: Arc<Self>,
+        flush_interval: Duration,
+        compact_interval: Duration,
+    ) {
+        let mut ticker = tokio::time::interval(flush_interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_compaction = Instant::now();
+        loop {
+            ticker.tick().await;
+            let compact_due =
+                !compact_interval.is_zero() && last_compaction.elapsed() >= compact_interval;
+            let journal = Arc::clone(&self);
+            let outcome = tokio::task::spawn_blocking(move || {
+                if compact_due {
+                    journal.compact().map(|_| ())
+                } else {
+                    journal.flush()
+                }
+            })
+            .await;
+            match outcome {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => {
+                    tracing::warn!(error = %err, path = %self.path.display(), "request journal write failed");
+                }
+                Err(err) => {
+                    tracing::warn!(error = %err, "request journal maintenance task failed");
+                }
+            }
+            if compact_due {
+                last_compaction = Instant::now();
+            }
+        }
+    }
+}
+
+/// In-flight journal record for one request; records the `end` line exactly once.
+pub struct JournalEntry {
+    journal: Arc<RequestJournal>,
+    seq: u64,
+    started: Instant,
+}
+
+impl JournalEntry {
+    /// Record the end of a request that produced no response body.
+    pub fn finish(self, status: u16) {
+        self.finish_with_usage(status, UsageTally::default());
+    }
+
+    /// Record the upstream error the client sees only as `reference`.
+    pub fn record_upstream_error(&self, reference: &str, detail: UpstreamErrorDetail) {
+        self.journal.append(&JournalRecord::UpstreamError {
+            run: self.journal.run,
+            seq: self.seq,
+            ts_ms: unix_now_ms(),
+            reference: reference.to_string(),
+            detail,
+        });
+    }
+
+    /// Wrap a response so the `end` record is written once its body completes.
+    ///
+    /// Token usage is read from the client-facing payload (JSON body or SSE
+    /// `data:` lines). A body dropped before completion is recorded with
+    /// status 499.
+    #[must_use]
+    pub fn tap_response(self, response: Response) -> Response {
+        tap_usage(response, self)
+    }
+}
+
+impl UsageSink for JournalEntry {
+    fn finish_with_usage(self, status: u16, usage: UsageTally) {
+        self.journal.append(&JournalRecord::End {
+            run: self.journal.run,
+            seq: self.seq,
+            ts_ms: unix_now_ms(),
+            status,
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cached_input_tokens: usage.cached_input_tokens,
+            reasoning_tokens: usage.reasoning_tokens,
+            duration_ms: u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        });
+    }
+}
+
+/// Receives the status and token usage of a response once its body ends.
+pub(crate) trait UsageSink {
+    fn finish_with_usage(self, status: u16, usage: UsageTally);
+}
+
+/// Wrap `response` so `sink` gets its usage once the body completes, or
+/// status 499 when the body is dropped first.
+pub(crate) fn tap_usage<T>(response: Response, sink: T) -> Response
+where
+    T: UsageSink + Send + 'static,
+{
+    let status = response.status().as_u16();
+    let is_sse = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let (parts, body) = response.into_parts();
+    let tap = UsageTapStream {
+        inner: body.into_data_stream(),
+        entry: Some(sink),
+        status,
+        scanner: UsageScanner::new(is_sse),
+    };
+    Response::from_parts(parts, axum::body::Body::from_stream(tap))
+}
+
+/// Token counts accumulated from a response, normalized across providers:
+/// input includes prompt cache reads and writes, output includes reasoning.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageTally {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub reasoning_tokens: u64,
+}
+
+impl UsageTally {
+    /// Merge usage found in one client-facing JSON payload.
+    ///
+    /// Streams report cumulative counts, so the maximum seen wins.
+    pub fn observe(&mut self, payload: &serde_json::Value) {
+        if let Some(items) = payload.as_array() {
+            for item in items {
+                self.observe(item);
+            }
+            return;
+        }
+        let candidates = [
+            payload.get("usage"),
+            payload.get("usageMetadata"),
+            payload.pointer("/response/usage"),
+            payload.pointer("/message/usage"),
+        ];
+        for usage in candidates.into_iter().flatten() {
+            // Anthropic reports cache reads/writes outside `input_tokens` and
+            // Gemini reports thinking outside `candidatesTokenCount`.
+            let cache_read = first_u64(usage, &["cache_read_input_tokens"]);
+            let thoughts = first_u64(usage, &["thoughtsTokenCount"]);
+            let input = first_u64(
+                usage,
+                &["prompt_tokens", "input_tokens", "promptTokenCount"],
+            ) + cache_read
+                + first_u64(usage, &["cache_creation_input_tokens"]);
+            let output = first_u64(
+                usage,
+                &["completion_tokens", "output_tokens", "candidatesTokenCount"],
+            ) + thoughts;
+            let cached = first_u64_at(
+                usage,
+                &[
+                    "/prompt_tokens_details/cached_tokens",
+                    "/input_tokens_details/cached_tokens",
+                    "/cachedContentTokenCount",
+                ],
+            )
+            .max(cache_read);
+            let reasoning = first_u64_at(
+                usage,
+                &[
+                    "/completion_tokens_details/reasoning_tokens",
+                    "/output_tokens_details/reasoning_tokens",
+                ],
+            )
+            .max(thoughts);
+            self.input_tokens = self.input_tokens.max(input);
+            self.output_tokens = self.output_tokens.max(output);
+            self.cached_input_tokens = self.cached_input_tokens.max(cached);
+            self.reasoning_tokens = self.reasoning_tokens.max(reasoning);
+        }
+    }
+}
+
+fn first_u64(value: &serde_json::Value, keys: &[&str]) -> u64 {
+    keys.iter()
+        .find_map(|key| value.get(*key).and_then(serde_json::Value::as_u64))
+        .unwrap_or(0)
+}
+
+fn first_u64_at(value: &serde_json::Value, pointers: &[&str]) -> u64 {
+    pointers
+        .iter()
+        .find_map(|pointer| value.pointer(pointer).and_then(serde_json::Value::as_u64))
+        .unwrap_or(0)
+}
+
+/// Incremental usage reader over a JSON or SSE response body.
+pub(crate) struct UsageScanner {
+    is_sse: bool,
+    buffer: Vec<u8>,
+    overflowed: bool,
+    usage: UsageTally,
+}
+
+impl UsageScanner {
+    pub(crate) fn new(is_sse: bool) -> Self {
+        Self {
+            is_sse,
+            buffer: Vec::new(),
+            overflowed: false,
+            usage: UsageTally::default(),
+        }
+    }
+
+    pub(crate) fn feed(&mut self, chunk: &[u8]) {
+        if !self.is_sse {
+            if self.buffer.len() + chunk.len() > MAX_JSON_USAGE_SCAN_BYTES {
+                self.overflowed = true;
+                self.buffer = Vec::new();
+            }
+            if !self.overflowed {
+                self.buffer.extend_from_slice(chunk);
+            }
+            return;
+        }
+
+        self.buffer.extend_from_slice(chunk);
+        let mut consumed = 0;
+        while let Some(offset) = memchr::memchr(b'\n', &self.buffer[consumed..]) {
+            let line_end = consumed + offset;
+            let line = &self.buffer[consumed..line_end];
+            if let Some(payload) = line.strip_prefix(b"data:") {
+                let payload = payload.trim_ascii();
+                // Every usage shape lives under a `usage`/`usageMetadata` key.
+                if payload.first() == Some(&b'{')
+                    && memchr::memmem::find(payload, b"\"usage").is_some()
+                {
+                    if let Ok(value) = serde_json::from_slice::<serde_json::Value>(payload) {
+                        self.usage.observe(&value);
+                    }
+                }
+            }
+            consumed = line_end + 1;
+        }
+        self.buffer.drain(..consumed);
+    }
+
+    pub(crate) fn finish(&mut self) -> UsageTally {
+        if !self.is_sse && !self.buffer.is_empty() {
+            if let Ok(value) = serde_json::from_slice::<serde_json::Value>(&self.buffer) {
+                self.usage.observe(&value);
+            }
+        }
+        self.usage
+    }
+}
+
+pin_project_lite::pin_project! {
+    struct UsageTapStream<S, T: UsageSink> {
+        #[pin]
+        inner: S,
+        entry: Option<T>,
+        status: u16,
+        scanner: UsageScanner,
+    }
+
+    impl<S, T: UsageSink> PinnedDrop for UsageTapStream<S, T> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if let Some(entry) = this.entry.take() {
+                // Client went away before the body completed.
+                entry.finish_with_usage(499, this.scanner.finish());
+            }
+        }
+    }
+}
+
+impl<S, T, E> Stream for UsageTapStream<S, T>
+where
+    S: Stream<Item = Result<bytes::Bytes, E>>,
+    T: UsageSink,
+{
+    type Item = Result<bytes::Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let polled = this.inner.poll_next(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(chunk))) => this.scanner.feed(chunk),
+            Poll::Ready(None) => {
+                if let Some(entry) = this.entry.take() {
+                    entry.finish_with_usage(*this.status, this.scanner.finish());
+                }
+            }
+            Poll::Ready(Some(Err(_))) | Poll::Pending => {}
+        }
+        polled
+    }
+}
+
+/// Read a journal file and recover per-model totals.
+///
+/// # Errors
+///
+/// Returns an I/O error when the journal cannot be read.
+pub fn replay(path: &Path) -> io::Result<JournalSummary> {
+    Ok(fold_records(read_records(path)?, None).0)
+}
+
+/// Read every well-formed record, counting lines that fail to parse.
+///
+/// A torn final line after a crash is counted as malformed rather than
+/// failing the whole read.
+///
+/// # Errors
+///
+/// Returns an I/O error when the journal cannot be read.
+pub fn read_records(path: &Path) -> io::Result<(Vec<JournalRecord>, u64)> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    let mut malformed = 0;
+    for line in reader.split(b'\n') {
+        let line = line?;
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        match serde_json::from_slice::<JournalRecord>(&line) {
+            Ok(record) => records.push(record),
+            Err(_) => malformed += 1,
+        }
+    }
+    Ok((records, malformed))
+}
+
+/// Compact a journal file in place, folding completed requests into totals.
+///
+/// Unmatched starts from `active_run` are preserved (still in flight); those
+/// from any other run are counted as incomplete.
+///
+/// # Errors
+///
+/// Returns an I/O error when reading, rewriting, or renaming the journal fails.
+pub fn compact_file(path: &Path, active_run: Option<u64>) -> io::Result<JournalSummary> {
+    let (summary, retained) = fold_records(read_records(path)?, active_run);
+
+    let tmp_path = path.with_extension("compact.tmp");
+    let mut out = Vec::new();
+    for (model, totals) in &summary.models {
+        let record = JournalRecord::Totals {
+            model: model.clone(),
+            requests: totals.requests,
+            incomplete: totals.incomplete,
+            input_tokens: totals.input_tokens,
+            output_tokens: totals.output_tokens,
+            cached_input_tokens: totals.cached_input_tokens,
+            reasoning_tokens: totals.reasoning_tokens,
+        };
+        serde_json::to_writer(&mut out, &record).map_err(io::Error::other)?;
+        out.push(b'\n');
+    }
+    for record in &retained {
+        serde_json::to_writer(&mut out, record).map_err(io::Error::other)?;
+        out.push(b'\n');
+    }
+    let mut tmp = File::create(&tmp_path)?;
+    tmp.write_all(&out)?;
+    tmp.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(summary)
+}
+
+/// Fold records into totals; returns the summary and the records to retain
+/// (upstream errors, then in-flight starts).
+fn fold_records(
+    (records, malformed_lines): (Vec<JournalRecord>, u64),
+    active_run: Option<u64>,
+) -> (JournalSummary, Vec<JournalRecord>) {
+    let mut summary = JournalSummary {
+        records: records.len() as u64,
+        malformed_lines,
+        models: BTreeMap::new(),
+    };
+    let mut open_starts: FxHashMap<(u64, u64), JournalRecord> = FxHashMap::default();
+    let mut start_order: Vec<(u64, u64)> = Vec::new();
+    let mut upstream_errors = Vec::new();
+
+    for record in records {
+        match record {
+            JournalRecord::Totals {
+                ref model,
+                requests,
+                incomplete,
+                input_tokens,
+                output_tokens,
+                cached_input_tokens,
+                reasoning_tokens,
+            } => {
+                let totals = summary.models.entry(model.clone()).or_default();
+                totals.requests += requests;
+                totals.incomplete += incomplete;
+                totals.input_tokens += input_tokens;
+                totals.output_tokens += output_tokens;
+                totals.cached_input_tokens += cached_input_tokens;
+                totals.reasoning_tokens += reasoning_tokens;
+            }
+            JournalRecord::UpstreamError { .. } => upstream_errors.push(record),
+            JournalRecord::Start { run, seq, .. } => {
+                start_order.push((run, seq));
+                open_starts.insert((run, seq), record);
+            }
+            JournalRecord::End {
+                run,
+                seq,
+                input_tokens,
+                output_tokens,
+                cached_input_tokens,
+                reasoning_tokens,
+                ..
+            } => {
+                let model = match open_starts.remove(&(run, seq)) {
+                    Some(JournalRecord::Start { model, .. }) => model,
+                    _ => String::new(),
+                };
+                let totals = summary.models.entry(model).or_default();
+                totals.requests += 1;
+                totals.input_tokens += input_tokens;
+                totals.output_tokens += output_tokens;
+                totals.cached_input_tokens += cached_input_tokens;
+                totals.reasoning_tokens += reasoning_tokens;
+            }
+        }
+    }
+
+    let mut retained = upstream_errors;
+    for key in start_order {
+        let Some(record) = open_starts.remove(&key) else {
+            continue;
+        };
+        if active_run == Some(key.0) {
+            retained.push(record);
+        } else if let JournalRecord::Start { model, .. } = record {
+            let totals = summary.models.entry(model).or_default();
+            totals.requests += 1;
+            totals.incomplete += 1;
+        }
+    }
+    (summary, retained)
+}
+
+pub(crate) fn ingress_label(ingress: IngressApi) -> &'static str {
+    match ingress {
+        IngressApi::OpenAiChat => "openai_chat",
+        IngressApi::OpenAiResponses => "openai_responses",
+        IngressApi::Anthropic => "anthropic",
+        IngressApi::Gemini => "gemini",
+    }
+}
+
+pub(crate) fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_journal_path(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "toolify-journal-{name}-{}-{}.jsonl",
+            std::process::id(),
+            unix_now_ms()
+        ));
+        path
+    }
+
+    #[test]
+    fn test_usage_tally_merges_stream_payloads() {
+        let mut tally = UsageTally::default();
+        tally.observe(&serde_json::json!({
+            "type": "message_start",
+            "message": { "usage": { "input_tokens": 12, "output_tokens": 1 } }
+        }));
+        tally.observe(&serde_json::json!({
+            "type": "message_delta",
+            "usage": { "output_tokens": 40 }
+        }));
+        assert_eq!(
+            tally,
+            UsageTally {
+                input_tokens: 12,
+                output_tokens: 40,
+                ..UsageTally::default()
+            }
+        );
+
+        let mut gemini = UsageTally::default();
+        gemini.observe(&serde_json::json!([
+            { "usageMetadata": { "promptTokenCount": 3, "candidatesTokenCount": 1 } },
+            { "usageMetadata": { "promptTokenCount": 3, "candidatesTokenCount": 9 } }
+        ]));
+        assert_eq!(gemini.output_tokens, 9);
+    }
+
+    #[test]
+    fn test_usage_tally_normalizes_cached_and_reasoning_tokens() {
+        let mut anthropic = UsageTally::default();
+        anthropic.observe(&serde_json::json!({
+            "usage": {
+                "input_tokens": 4,
+                "output_tokens": 2,
+                "cache_read_input_tokens": 100,
+                "cache_creation_input_tokens": 20
+            }
+        }));
+        assert_eq!(anthropic.input_tokens, 124);
+        assert_eq!(anthropic.cached_input_tokens, 100);
+
+        let mut gemini = UsageTally::default();
+        gemini.observe(&serde_json::json!({
+            "usageMetadata": {
+                "promptTokenCount": 30,
+                "candidatesTokenCount": 5,
+                "thoughtsTokenCount": 12,
+                "cachedContentTokenCount": 10
+            }
+        }));
+        assert_eq!(
+            gemini,
+            UsageTally {
+                input_tokens: 30,
+                output_tokens: 17,
+                cached_input_tokens: 10,
+                reasoning_tokens: 12,
+            }
+        );
+
+        let mut openai = UsageTally::default();
+        openai.observe(&serde_json::json!({
+            "usage": {
+                "prompt_tokens": 50,
+                "completion_tokens": 9,
+                "prompt_tokens_details": { "cached_tokens": 32 },
+                "completion_tokens_details": { "reasoning_tokens": 6 }
+            }
+        }));
+        assert_eq!(openai.input_tokens, 50);
+        assert_eq!(openai.cached_input_tokens, 32);
+        assert_eq!(openai.reasoning_tokens, 6);
+    }
+
+    #[test]
+    fn test_usage_scanner_handles_split_sse_lines() {
+        let mut scanner = UsageScanner::new(true);
+        scanner.feed(b"data: {\"usage\":{\"prompt_tokens\":5,");
+        scanner.feed(b"\"completion_tokens\":7}}\n\ndata: [DONE]\n\n");
+        assert_eq!(
+            scanner.finish(),
+            UsageTally {
+                input_tokens: 5,
+                output_tokens: 7,
+                ..UsageTally::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_compaction_keeps_upstream_errors() {
+        let path = temp_journal_path("errors");
+        let journal = Arc::new(RequestJournal::open(&path).unwrap());
+        let entry = journal.begin(IngressApi::OpenAiChat, "smart");
+        let detail = UpstreamErrorDetail {
+            upstream: "openai".to_string(),
+            status: 500,
+            headers: BTreeMap::from([("x-request-id".to_string(), "req_1".to_string())]),
+            body: "{\"error\":{\"message\":\"shard 7 down\"}}".to_string(),
+        };
+        entry.record_upstream_error("uerr_1", detail.clone());
+        entry.finish(500);
+
+        let summary = journal.compact().unwrap();
+        assert_eq!(summary.models["smart"].requests, 1);
+        let (records, malformed) = read_records(&path).unwrap();
+        assert_eq!(malformed, 0);
+        assert!(records.iter().any(|record| matches!(
+            record,
+            JournalRecord::UpstreamError { reference, detail: kept, .. }
+                if reference == "uerr_1" && *kept == detail
+        )));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_flush_replay_and_compact_recover_totals() {
+        let path = temp_journal_path("roundtrip");
+        let journal = Arc::new(RequestJournal::open(&path).unwrap());
+
+        let done = journal.begin(IngressApi::Anthropic, "smart");
+        done.finish_with_usage(
+            200,
+            UsageTally {
+                input_tokens: 10,
+                output_tokens: 4,
+                cached_input_tokens: 6,
+                reasoning_tokens: 0,
+            },
+        );
+        let _in_flight = journal.begin(IngressApi::OpenAiChat, "smart");
+        journal.flush().unwrap();
+
+        let summary = replay(&path).unwrap();
+        let totals = &summary.models["smart"];
+        assert_eq!(totals.requests, 2);
+        assert_eq!(totals.incomplete, 1);
+        assert_eq!(totals.input_tokens, 10);
+        assert_eq!(totals.cached_input_tokens, 6);
+
+        let compacted = journal.compact().unwrap();
+        assert_eq!(compacted.models["smart"].requests, 1);
+        let (records, malformed) = read_records(&path).unwrap();
+        assert_eq!(malformed, 0);
+        assert_eq!(records.len(), 2);
+        assert!(matches!(
+            records[0],
+            JournalRecord::Totals { requests: 1, .. }
+        ));
+        assert!(matches!(records[1], JournalRecord::Start { .. }));
+
+        assert_eq!(replay(&path).unwrap(), summary_with_records(&summary, 2));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_failed_flush_keeps_records_for_the_next_one() {
+        let dir = temp_journal_path("unwritable");
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("journal.jsonl");
+        let journal = Arc::new(RequestJournal::open(&path).unwrap());
+
+        let _first = journal.begin(IngressApi::OpenAiChat, "smart");
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(journal.flush().is_err());
+        let _second = journal.begin(IngressApi::Anthropic, "smart");
+
+        fs::create_dir(&dir).unwrap();
+        journal.flush().unwrap();
+        let summary = replay(&path).unwrap();
+        assert_eq!(summary.models["smart"].requests, 2);
+        assert_eq!(summary.malformed_lines, 0);
+        assert_eq!(journal.dropped_records(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn summary_with_records(summary: &JournalSummary, records: u64) -> JournalSummary {
+        JournalSummary {
+            records,
+            ..summary.clone()
+        }
+    }
+
+    #[test]
+    fn test_replay_tolerates_torn_final_line() {
+        let path = temp_journal_path("torn");
+        fs::write(
+            &path,
+            b"{\"kind\":\"totals\",\"model\":\"m\",\"requests\":3,\"input_tokens\":9,\"output_tokens\":6}\n{\"kind\":\"start\",\"run\":1,\"se",
+        )
+        .unwrap();
+        let summary = replay(&path).unwrap();
+        assert_eq!(summary.malformed_lines, 1);
+        assert_eq!(summary.models["m"].requests, 3);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,4 +1,10 @@
+#[cfg(feature = "analytics")]
+pub mod analytics;
+pub mod journal;
+pub mod tail;
 pub mod token_counter;
+pub mod traces;
+pub(crate) mod upstream_errors;
 
 use crate::protocol::canonical::CanonicalUsage;
 use tracing_subscriber::EnvFilter;
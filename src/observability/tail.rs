@@ -0,0 +1,417 @@
+//! Live view of request lifecycle events (`GET /admin/tail`).
+//!
+//! Model API requests report `started`, `routed`, `first_byte`, and then
+//! `finished` or `failed` to the [`LiveTail`]. Events are only built while
+//! someone is watching, so requests that start with nobody attached stay out
+//! of the view. A watcher that falls behind is told how many events it
+//! missed instead of slowing requests down.
+
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::response::Response;
+use bytes::Bytes;
+use futures_util::Stream;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::observability::journal::{ingress_label, unix_now_ms};
+use crate::protocol::canonical::IngressApi;
+
+/// Events buffered per watcher before it starts missing them.
+const TAIL_CAPACITY: usize = 1024;
+/// Idle time after which a watcher is sent an SSE comment.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TailEventKind {
+    Started,
+    /// The upstream that served the request answered.
+    Routed,
+    /// The first body bytes went to the client.
+    FirstByte,
+    Finished,
+    /// The request errored, its stream broke, or the client went away.
+    Failed,
+}
+
+impl TailEventKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Started => "started",
+            Self::Routed => "routed",
+            Self::FirstByte => "first_byte",
+            Self::Finished => "finished",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for TailEventKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "started" => Ok(Self::Started),
+            "routed" => Ok(Self::Routed),
+            "first_byte" => Ok(Self::FirstByte),
+            "finished" => Ok(Self::Finished),
+            "failed" => Ok(Self::Failed),
+            other => Err(format!(
+                "unknown event '{other}'; expected started, routed, first_byte, finished, or failed"
+            )),
+        }
+    }
+}
+
+/// One lifecycle event of one request.
+#[derive(Debug, Clone, Serialize)]
+pub struct TailEvent {
+    pub event: TailEventKind,
+    /// Sequence number shared by every event of the request.
+    pub request: u64,
+    pub ts_ms: u64,
+    pub ingress: &'static str,
+    /// Client-requested model.
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+    pub stream: bool,
+    /// Time since the request started.
+    pub elapsed_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Which events a watcher receives; unset fields match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TailFilter {
+    pub events: Option<Vec<TailEventKind>>,
+    pub ingress: Option<String>,
+    pub model: Option<String>,
+    pub upstream: Option<String>,
+}
+
+impl TailFilter {
+    /// Parse `events` (comma separated), `ingress`, `model`, and `upstream`
+    /// query parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message naming an unknown event.
+    pub fn from_query(query: &str) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match &*key {
+                "events" => {
+                    filter.events = Some(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|name| !name.is_empty())
+                            .map(str::parse)
+                            .collect::<Result<_, _>>()?,
+                    );
+                }
+                "ingress" => filter.ingress = Some(value.into_owned()),
+                "model" => filter.model = Some(value.into_owned()),
+                "upstream" => filter.upstream = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Events before `routed` have no upstream yet and pass an upstream
+    /// filter only once the request is routed there.
+    #[must_use]
+    pub fn matches(&self, event: &TailEvent) -> bool {
+        self.events
+            .as_ref()
+            .is_none_or(|events| events.contains(&event.event))
+            && self
+                .ingress
+                .as_deref()
+                .is_none_or(|ingress| ingress == event.ingress)
+            && self
+                .model
+                .as_deref()
+                .is_none_or(|model| model == event.model)
+            && self
+                .upstream
+                .as_deref()
+                .is_none_or(|upstream| event.upstream.as_deref() == Some(upstream))
+    }
+}
+
+/// Fan-out of request lifecycle events to live watchers.
+pub struct LiveTail {
+    sender: broadcast::Sender<Arc<TailEvent>>,
+    next_request: AtomicU64,
+}
+
+impl Default for LiveTail {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::Sender::new(TAIL_CAPACITY),
+            next_request: AtomicU64::new(1),
+        }
+    }
+}
+
+impl LiveTail {
+    /// Start reporting a request, or `None` while nobody is watching.
+    pub(crate) fn begin(
+        self: &Arc<Self>,
+        ingress: IngressApi,
+        model: &str,
+        stream: bool,
+    ) -> Option<TailRequest> {
+        if self.sender.receiver_count() == 0 {
+            return None;
+        }
+        let request = TailRequest {
+            tail: Arc::clone(self),
+            request: self.next_request.fetch_add(1, Ordering::Relaxed),
+            ingress,
+            model: model.to_string(),
+            upstream: None,
+            stream,
+            started: Instant::now(),
+        };
+        request.emit(TailEventKind::Started, None, None);
+        Some(request)
+    }
+
+    /// SSE frames of the events matching `filter` from now on.
+    ///
+    /// Each event is sent as `event: <kind>` with the JSON event as data. A
+    /// watcher that fell behind gets a `lagged` event with the number of
+    /// events it skipped, and an idle one a keep-alive comment.
+    pub fn watch(
+        &self,
+        filter: TailFilter,
+    ) -> impl Stream<Item = Result<Bytes, Infallible>> + Send + 'static {
+        futures_util::stream::unfold(
+            (self.sender.subscribe(), filter),
+            |(mut receiver, filter)| async move {
+                loop {
+                    let frame =
+                        match tokio::time::timeout(KEEPALIVE_INTERVAL, receiver.recv()).await {
+                            Err(_) => Bytes::from_static(b": keep-alive\n\n"),
+                            Ok(Ok(event)) if filter.matches(&event) => {
+                                let data = serde_json::to_string(&*event).unwrap_or_default();
+                                Bytes::from(format!(
+                                    "event: {}\ndata: {data}\n\n",
+                                    event.event.as_str()
+                                ))
+                            }
+                            Ok(Ok(_)) => continue,
+                            Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => Bytes::from(
+                                format!("event: lagged\ndata: {{\"skipped\":{skipped}}}\n\n"),
+                            ),
+                            Ok(Err(broadcast::error::RecvError::Closed)) => return None,
+                        };
+                    return Some((Ok(frame), (receiver, filter)));
+                }
+            },
+        )
+    }
+
+    /// Number of connected watchers.
+    #[must_use]
+    pub fn watcher_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+/// Reports the lifecycle of one request to the [`LiveTail`].
+pub(crate) struct TailRequest {
+    tail: Arc<LiveTail>,
+    request: u64,
+    ingress: IngressApi,
+    model: String,
+    upstream: Option<String>,
+    stream: bool,
+    started: Instant,
+}
+
+impl TailRequest {
+    fn emit(&self, event: TailEventKind, status: Option<u16>, error: Option<String>) {
+        let _ = self.tail.sender.send(Arc::new(TailEvent {
+            event,
+            request: self.request,
+            ts_ms: unix_now_ms(),
+            ingress: ingress_label(self.ingress),
+            model: self.model.clone(),
+            upstream: self.upstream.clone(),
+            stream: self.stream,
+            elapsed_ms: u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            status,
+            error,
+        }));
+    }
+
+    /// Report that `upstream` answered the request.
+    pub(crate) fn routed(&mut self, upstream: &str) {
+        self.upstream = Some(upstream.to_string());
+        self.emit(TailEventKind::Routed, None, None);
+    }
+
+    /// Report a request that ended in an error before any response.
+    pub(crate) fn failed(self, status: u16, error: String) {
+        self.emit(TailEventKind::Failed, Some(status), Some(error));
+    }
+
+    /// Report the first body bytes and the end of `response`.
+    pub(crate) fn tap_response(self, response: Response) -> Response {
+        let status = response.status().as_u16();
+        let (parts, body) = response.into_parts();
+        let tap = TailTapStream {
+            inner: body.into_data_stream(),
+            guard: TailGuard {
+                request: Some(self),
+                status,
+                first_byte: false,
+            },
+        };
+        Response::from_parts(parts, axum::body::Body::from_stream(tap))
+    }
+}
+
+/// Reports a body dropped before its end as a disconnected client.
+struct TailGuard {
+    request: Option<TailRequest>,
+    status: u16,
+    first_byte: bool,
+}
+
+impl Drop for TailGuard {
+    fn drop(&mut self) {
+        if let Some(request) = self.request.take() {
+            request.emit(
+                TailEventKind::Failed,
+                Some(self.status),
+                Some("client disconnected".to_string()),
+            );
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    struct TailTapStream<S> {
+        #[pin]
+        inner: S,
+        guard: TailGuard,
+    }
+}
+
+impl<S, E> Stream for TailTapStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: std::fmt::Display,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let polled = this.inner.poll_next(cx);
+        let guard = this.guard;
+        match &polled {
+            Poll::Ready(Some(Ok(chunk))) if !chunk.is_empty() && !guard.first_byte => {
+                guard.first_byte = true;
+                if let Some(request) = &guard.request {
+                    request.emit(TailEventKind::FirstByte, Some(guard.status), None);
+                }
+            }
+            Poll::Ready(Some(Err(err))) => {
+                if let Some(request) = guard.request.take() {
+                    request.emit(
+                        TailEventKind::Failed,
+                        Some(guard.status),
+                        Some(err.to_string()),
+                    );
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(request) = guard.request.take() {
+                    request.emit(TailEventKind::Finished, Some(guard.status), None);
+                }
+            }
+            Poll::Ready(Some(Ok(_))) | Poll::Pending => {}
+        }
+        polled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn frame_event(frame: &[u8]) -> (String, serde_json::Value) {
+        let frame = std::str::from_utf8(frame).unwrap();
+        let (event, data) = frame.trim_end().split_once('\n').unwrap();
+        (
+            event.trim_start_matches("event: ").to_string(),
+            serde_json::from_str(data.trim_start_matches("data: ")).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_watchers_see_filtered_lifecycle_events() {
+        let tail = Arc::new(LiveTail::default());
+        assert!(tail
+            .begin(IngressApi::OpenAiChat, "gpt-4o", false)
+            .is_none());
+
+        let all = tail.watch(TailFilter::default());
+        let failures = tail.watch(TailFilter::from_query("events=failed&model=gpt-4o").unwrap());
+        tokio::pin!(all, failures);
+        assert!(TailFilter::from_query("events=started,done").is_err());
+
+        let mut request = tail.begin(IngressApi::Anthropic, "claude", true).unwrap();
+        request.routed("primary");
+        let response = request.tap_response(Response::new(axum::body::Body::from("data: hi\n\n")));
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        tail.begin(IngressApi::OpenAiChat, "gpt-4o", false)
+            .unwrap()
+            .failed(502, "upstream down".to_string());
+
+        let mut events = Vec::new();
+        for _ in 0..6 {
+            events.push(frame_event(&all.next().await.unwrap().unwrap()));
+        }
+        let kinds: Vec<_> = events.iter().map(|(kind, _)| kind.as_str()).collect();
+        assert_eq!(
+            kinds,
+            [
+                "started",
+                "routed",
+                "first_byte",
+                "finished",
+                "started",
+                "failed"
+            ]
+        );
+        assert_eq!(events[3].1["upstream"], "primary");
+        assert_eq!(events[3].1["status"], 200);
+        assert_eq!(events[3].1["request"], events[0].1["request"]);
+
+        let (kind, failed) = frame_event(&failures.next().await.unwrap().unwrap());
+        assert_eq!(kind, "failed");
+        assert_eq!(failed["error"], "upstream down");
+        assert_eq!(failed["status"], 502);
+    }
+}
@@ -33,6 +33,11 @@ pub fn estimate_request_tokens(request: &CanonicalRequest) -> u64 {
                 | CanonicalPart::Refusal(text) => {
                     total += estimate_tokens(text, model);
                 }
+                CanonicalPart::ReasoningItem { summary, .. } => {
+                    for text in summary {
+                        total += estimate_tokens(text, model);
+                    }
+                }
                 CanonicalPart::ToolResult { content, .. } => {
                     total += estimate_tokens(content, model);
                 }
@@ -97,6 +102,9 @@ pub fn merge_usage(
         input_tokens,
         output_tokens,
         total_tokens,
+        cached_input_tokens: upstream.cached_input_tokens,
+        cache_creation_input_tokens: upstream.cache_creation_input_tokens,
+        reasoning_tokens: upstream.reasoning_tokens,
     }
 }
 
@@ -107,6 +115,8 @@ pub fn log_request_usage(model: &str, usage: &CanonicalUsage, duration: Duration
         input_tokens = usage.input_tokens.unwrap_or(0),
         output_tokens = usage.output_tokens.unwrap_or(0),
         total_tokens = usage.total_tokens.unwrap_or(0),
+        cached_input_tokens = usage.cached_input_tokens.unwrap_or(0),
+        reasoning_tokens = usage.reasoning_tokens.unwrap_or(0),
         duration_seconds = duration.as_secs_f64(),
         "request completed"
     );
@@ -140,6 +150,7 @@ mod tests {
             input_tokens: Some(100),
             output_tokens: Some(50),
             total_tokens: Some(150),
+            ..CanonicalUsage::default()
         };
         let merged = merge_usage(&upstream, 999, 999);
         assert_eq!(merged.input_tokens, Some(100));
@@ -153,6 +164,7 @@ mod tests {
             input_tokens: None,
             output_tokens: None,
             total_tokens: None,
+            ..CanonicalUsage::default()
         };
         let merged = merge_usage(&upstream, 40, 20);
         assert_eq!(merged.input_tokens, Some(40));
@@ -166,6 +178,7 @@ mod tests {
             input_tokens: Some(0),
             output_tokens: Some(0),
             total_tokens: Some(0),
+            ..CanonicalUsage::default()
         };
         let merged = merge_usage(&upstream, 30, 10);
         assert_eq!(merged.input_tokens, Some(30));
@@ -179,6 +192,7 @@ mod tests {
             input_tokens: Some(100),
             output_tokens: None,
             total_tokens: None,
+            ..CanonicalUsage::default()
         };
         let merged = merge_usage(&upstream, 50, 25);
         assert_eq!(merged.input_tokens, Some(100));
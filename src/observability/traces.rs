@@ -0,0 +1,363 @@
+//! Per-conversation traces for offline evaluation.
+//!
+//! With `features.conversation_traces` set, every compat request is recorded
+//! as a turn of the conversation named by its session hash (the sticky
+//! routing hash without its time bucket): the messages the request added to
+//! the history, the client-format response, and the model and upstream that
+//! served it. Streamed responses are recorded aggregated, once they end.
+//! `GET /admin/traces/{session}` returns a conversation as one JSON document.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use serde_json::{json, Map, Value};
+
+use super::journal::{ingress_label, unix_now_ms};
+use crate::config::ConversationTraceConfig;
+use crate::protocol::canonical::IngressApi;
+
+/// Response header naming the conversation a request was traced under.
+pub const TRACE_SESSION_HEADER: &str = "x-toolify-trace-session";
+
+struct Conversation {
+    started_ms: u64,
+    updated_ms: u64,
+    last_seen: Instant,
+    /// History length of the latest request; the next one records only the
+    /// messages past it.
+    history_len: usize,
+    system: Option<Value>,
+    turns: VecDeque<Value>,
+    dropped_turns: u64,
+}
+
+impl Conversation {
+    fn new(now: Instant) -> Self {
+        let now_ms = unix_now_ms();
+        Self {
+            started_ms: now_ms,
+            updated_ms: now_ms,
+            last_seen: now,
+            history_len: 0,
+            system: None,
+            turns: VecDeque::new(),
+            dropped_turns: 0,
+        }
+    }
+
+    fn document(&self, session: u64) -> Value {
+        let mut models: Vec<&Value> = Vec::new();
+        let mut upstreams: Vec<&Value> = Vec::new();
+        for turn in &self.turns {
+            if let Some(model) = turn.get("routed_model").or_else(|| turn.get("model")) {
+                if !models.contains(&model) {
+                    models.push(model);
+                }
+            }
+            if let Some(upstream) = turn.get("upstream").filter(|upstream| !upstream.is_null()) {
+                if !upstreams.contains(&upstream) {
+                    upstreams.push(upstream);
+                }
+            }
+        }
+        json!({
+            "session": session_id(session),
+            "started_ms": self.started_ms,
+            "updated_ms": self.updated_ms,
+            "dropped_turns": self.dropped_turns,
+            "models": models,
+            "upstreams": upstreams,
+            "turns": self.turns,
+        })
+    }
+}
+
+/// Recorded conversations, bounded in count, turns, and age.
+pub struct ConversationTraces {
+    max_conversations: usize,
+    max_turns: usize,
+    ttl: Duration,
+    conversations: Mutex<FxHashMap<u64, Conversation>>,
+}
+
+impl ConversationTraces {
+    #[must_use]
+    pub fn new(config: &ConversationTraceConfig) -> Self {
+        Self {
+            max_conversations: config.max_conversations,
+            max_turns: config.max_turns,
+            ttl: Duration::from_secs(config.ttl_secs),
+            conversations: Mutex::new(FxHashMap::default()),
+        }
+    }
+
+    /// Start a turn of conversation `session` for a request `body` in the
+    /// `ingress` format.
+    #[must_use]
+    pub fn begin(
+        self: &Arc<Self>,
+        session: u64,
+        ingress: IngressApi,
+        body: &[u8],
+        model: &str,
+        routed_model: &str,
+        stream: bool,
+    ) -> TraceTurn {
+        let mut request: Value = serde_json::from_slice(body).unwrap_or(Value::Null);
+        let mut history = request_history(ingress, &mut request);
+        let system = request_system(ingress, &mut request);
+        let now = Instant::now();
+
+        let mut record = Map::new();
+        record.insert("ts_ms".to_string(), json!(unix_now_ms()));
+        record.insert("ingress".to_string(), json!(ingress_label(ingress)));
+        record.insert("model".to_string(), json!(model));
+        if routed_model != model {
+            record.insert("routed_model".to_string(), json!(routed_model));
+        }
+        record.insert("stream".to_string(), json!(stream));
+        {
+            let mut conversations = self.conversations.lock();
+            self.make_room(&mut conversations, session, now);
+            let conversation = conversations
+                .entry(session)
+                .or_insert_with(|| Conversation::new(now));
+            let history_len = history.len();
+            // A shorter history was edited or restarted; record all of it.
+            if history_len >= conversation.history_len {
+                history.drain(..conversation.history_len);
+            }
+            conversation.history_len = history_len;
+            conversation.last_seen = now;
+            if system.is_some() && system != conversation.system {
+                record.insert("system".to_string(), system.clone().unwrap_or_default());
+                conversation.system = system;
+            }
+        }
+        record.insert("messages".to_string(), Value::Array(history));
+
+        TraceTurn {
+            traces: Arc::clone(self),
+            session,
+            record,
+            started: now,
+        }
+    }
+
+    /// Drop expired conversations and, when a new one does not fit, the
+    /// least recently updated.
+    fn make_room(
+        &self,
+        conversations: &mut FxHashMap<u64, Conversation>,
+        session: u64,
+        now: Instant,
+    ) {
+        if conversations
+            .get(&session)
+            .is_some_and(|conversation| self.is_expired(conversation, now))
+        {
+            conversations.remove(&session);
+        }
+        if conversations.contains_key(&session) || conversations.len() < self.max_conversations {
+            return;
+        }
+        conversations.retain(|_, conversation| !self.is_expired(conversation, now));
+        while conversations.len() >= self.max_conversations {
+            let Some(oldest) = conversations
+                .iter()
+                .min_by_key(|(_, conversation)| conversation.last_seen)
+                .map(|(session, _)| *session)
+            else {
+                break;
+            };
+            conversations.remove(&oldest);
+        }
+    }
+
+    fn is_expired(&self, conversation: &Conversation, now: Instant) -> bool {
+        now.saturating_duration_since(conversation.last_seen) >= self.ttl
+    }
+
+    fn push_turn(&self, session: u64, turn: Value) {
+        let mut conversations = self.conversations.lock();
+        // The conversation was evicted while the turn ran.
+        let Some(conversation) = conversations.get_mut(&session) else {
+            return;
+        };
+        conversation.turns.push_back(turn);
+        while conversation.turns.len() > self.max_turns {
+            conversation.turns.pop_front();
+            conversation.dropped_turns += 1;
+        }
+        conversation.updated_ms = unix_now_ms();
+        conversation.last_seen = Instant::now();
+    }
+
+    /// Live conversations, most recently updated first.
+    #[must_use]
+    pub fn list(&self) -> Vec<Value> {
+        let now = Instant::now();
+        let conversations = self.conversations.lock();
+        let mut live: Vec<(&u64, &Conversation)> = conversations
+            .iter()
+            .filter(|(_, conversation)| !self.is_expired(conversation, now))
+            .collect();
+        live.sort_by_key(|(_, conversation)| std::cmp::Reverse(conversation.updated_ms));
+        live.into_iter()
+            .map(|(session, conversation)| {
+                json!({
+                    "session": session_id(*session),
+                    "started_ms": conversation.started_ms,
+                    "updated_ms": conversation.updated_ms,
+                    "turns": conversation.turns.len(),
+                })
+            })
+            .collect()
+    }
+
+    /// The trace document of `session`, a hex session id.
+    #[must_use]
+    pub fn get(&self, session: &str) -> Option<Value> {
+        let session = u64::from_str_radix(session, 16).ok()?;
+        let conversations = self.conversations.lock();
+        conversations
+            .get(&session)
+            .filter(|conversation| !self.is_expired(conversation, Instant::now()))
+            .map(|conversation| conversation.document(session))
+    }
+}
+
+/// A turn in progress; recorded once [`TraceTurn::finish`] runs.
+pub struct TraceTurn {
+    traces: Arc<ConversationTraces>,
+    session: u64,
+    record: Map<String, Value>,
+    started: Instant,
+}
+
+impl TraceTurn {
+    /// Hex id of the conversation, as accepted by `GET /admin/traces/{session}`.
+    #[must_use]
+    pub fn session_id(&self) -> String {
+        session_id(self.session)
+    }
+
+    /// Name the upstream that served the turn.
+    pub fn set_upstream(&mut self, upstream: Option<&str>) {
+        self.record.insert("upstream".to_string(), json!(upstream));
+    }
+
+    /// Record the turn with its response status and client-format body.
+    pub fn finish(mut self, status: u16, response: Option<Value>) {
+        self.record.insert("status".to_string(), json!(status));
+        self.record.insert(
+            "duration_ms".to_string(),
+            json!(u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX)),
+        );
+        self.record
+            .insert("response".to_string(), response.unwrap_or_default());
+        self.traces
+            .push_turn(self.session, Value::Object(self.record));
+    }
+}
+
+fn session_id(session: u64) -> String {
+    format!("{session:016x}")
+}
+
+/// The conversation history carried by a request.
+fn request_history(ingress: IngressApi, request: &mut Value) -> Vec<Value> {
+    let field = match ingress {
+        IngressApi::OpenAiChat | IngressApi::Anthropic => "messages",
+        IngressApi::OpenAiResponses => "input",
+        IngressApi::Gemini => "contents",
+    };
+    match request.get_mut(field).map(Value::take) {
+        Some(Value::Array(messages)) => messages,
+        Some(Value::String(text)) => vec![json!({ "role": "user", "content": text })],
+        _ => Vec::new(),
+    }
+}
+
+/// The system prompt a request carries outside its history.
+fn request_system(ingress: IngressApi, request: &mut Value) -> Option<Value> {
+    let fields: &[&str] = match ingress {
+        IngressApi::OpenAiChat => &[],
+        IngressApi::Anthropic => &["system"],
+        IngressApi::OpenAiResponses => &["instructions"],
+        IngressApi::Gemini => &["systemInstruction", "system_instruction"],
+    };
+    fields
+        .iter()
+        .find_map(|field| request.get_mut(*field).map(Value::take))
+        .filter(|system| !system.is_null())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traces(max_conversations: usize, max_turns: usize) -> Arc<ConversationTraces> {
+        Arc::new(ConversationTraces::new(&ConversationTraceConfig {
+            max_conversations,
+            max_turns,
+            ttl_secs: 3600,
+        }))
+    }
+
+    fn chat_body(messages: &[(&str, &str)]) -> Vec<u8> {
+        let messages: Vec<Value> = messages
+            .iter()
+            .map(|(role, content)| json!({ "role": role, "content": content }))
+            .collect();
+        serde_json::to_vec(&json!({ "model": "m", "messages": messages })).unwrap()
+    }
+
+    #[test]
+    fn test_turns_record_only_new_messages() {
+        let traces = traces(4, 8);
+        let first = chat_body(&[("user", "hi")]);
+        let mut turn = traces.begin(7, IngressApi::OpenAiChat, &first, "m", "m", false);
+        turn.set_upstream(Some("primary"));
+        turn.finish(200, Some(json!({ "id": "r1" })));
+
+        let second = chat_body(&[("user", "hi"), ("assistant", "hello"), ("user", "bye")]);
+        traces
+            .begin(7, IngressApi::OpenAiChat, &second, "m", "m-large", true)
+            .finish(200, None);
+
+        let document = traces.get(&session_id(7)).unwrap();
+        let turns = document["turns"].as_array().unwrap();
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0]["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(turns[0]["response"]["id"], "r1");
+        assert_eq!(turns[1]["messages"][0]["content"], "hello");
+        assert_eq!(turns[1]["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(document["models"], json!(["m", "m-large"]));
+        assert_eq!(document["upstreams"], json!(["primary"]));
+    }
+
+    #[test]
+    fn test_limits_drop_oldest_turns_and_conversations() {
+        let traces = traces(2, 1);
+        let body = chat_body(&[("user", "hi")]);
+        for session in [1, 1, 2, 3] {
+            traces
+                .begin(session, IngressApi::OpenAiChat, &body, "m", "m", false)
+                .finish(200, None);
+        }
+        assert!(traces.get(&session_id(1)).is_none());
+        assert_eq!(traces.list().len(), 2);
+        let document = traces.get(&session_id(3)).unwrap();
+        assert_eq!(document["turns"].as_array().unwrap().len(), 1);
+
+        traces
+            .begin(3, IngressApi::OpenAiChat, &body, "m", "m", false)
+            .finish(200, None);
+        let document = traces.get(&session_id(3)).unwrap();
+        assert_eq!(document["dropped_turns"], 1);
+    }
+}
@@ -0,0 +1,120 @@
+//! Upstream error details behind client-facing reference ids.
+//!
+//! Every non-success upstream response is noted in the compat flow's request
+//! context with its status, a subset of its headers, and the start of its
+//! body. With
+//! `features.upstream_error_references`, a request failing with an upstream
+//! error answers with a reference id instead of the upstream's message, and
+//! the last noted detail goes to the request journal under that id.
+
+use std::collections::BTreeMap;
+
+use super::journal::UpstreamErrorDetail;
+use crate::state::request_context;
+
+/// Bytes of an upstream error body kept in the detail.
+const MAX_BODY_EXCERPT_BYTES: usize = 2048;
+
+/// Note a non-success upstream response. A no-op outside
+/// [`request_context::scope`].
+pub(crate) fn note_upstream_error(
+    status: http::StatusCode,
+    headers: &http::HeaderMap,
+    body: &[u8],
+) {
+    request_context::note_outcome(|outcome| {
+        outcome.upstream_error = Some(UpstreamErrorDetail {
+            upstream: String::new(),
+            status: status.as_u16(),
+            headers: kept_headers(headers),
+            body: body_excerpt(body),
+        });
+    });
+}
+
+/// Name the upstream of the last noted error, unless it already has one.
+pub(crate) fn note_failed_upstream(upstream: &str) {
+    request_context::note_outcome(|outcome| {
+        if let Some(detail) = outcome.upstream_error.as_mut() {
+            if detail.upstream.is_empty() {
+                detail.upstream = upstream.to_string();
+            }
+        }
+    });
+}
+
+/// A new reference id for an upstream error.
+#[must_use]
+pub(crate) fn new_reference() -> String {
+    format!("uerr_{:016x}", fastrand::u64(..))
+}
+
+/// Request id, rate limit, retry, and content type headers.
+fn kept_headers(headers: &http::HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            name == "content-type"
+                || name == "retry-after"
+                || name.ends_with("request-id")
+                || name.ends_with("requestid")
+                || name.contains("ratelimit")
+        })
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+fn body_excerpt(body: &[u8]) -> String {
+    let body = String::from_utf8_lossy(body);
+    if body.len() <= MAX_BODY_EXCERPT_BYTES {
+        return body.into_owned();
+    }
+    let mut end = MAX_BODY_EXCERPT_BYTES;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    body[..end].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_last_error_keeps_headers_subset_and_upstream() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-request-id", http::HeaderValue::from_static("req_9"));
+        headers.insert(
+            "x-ratelimit-remaining-requests",
+            http::HeaderValue::from_static("0"),
+        );
+        headers.insert("set-cookie", http::HeaderValue::from_static("secret=1"));
+        let long_body = "é".repeat(MAX_BODY_EXCERPT_BYTES);
+
+        let ((), outcome) = request_context::scope(Default::default(), async {
+            note_upstream_error(http::StatusCode::BAD_GATEWAY, &headers, b"first");
+            note_failed_upstream("primary");
+            note_upstream_error(
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                &headers,
+                long_body.as_bytes(),
+            );
+            note_failed_upstream("backup");
+            note_failed_upstream("ignored");
+        })
+        .await;
+        let detail = outcome.upstream_error.unwrap();
+        assert_eq!(detail.upstream, "backup");
+        assert_eq!(detail.status, 500);
+        assert_eq!(
+            detail.headers.keys().collect::<Vec<_>>(),
+            ["x-ratelimit-remaining-requests", "x-request-id"]
+        );
+        assert_eq!(detail.body.len(), MAX_BODY_EXCERPT_BYTES);
+
+        note_upstream_error(http::StatusCode::BAD_GATEWAY, &headers, b"unscoped");
+        let ((), outcome) = request_context::scope(Default::default(), async {}).await;
+        assert!(outcome.upstream_error.is_none());
+    }
+}
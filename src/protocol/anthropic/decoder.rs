@@ -510,6 +510,23 @@ mod tests {
         assert_eq!(canonical.generation.stop, Some(vec!["stop".to_string()]));
     }
 
+    #[test]
+    fn test_decode_system_blocks_flatten_in_order() {
+        let system = serde_json::json!([
+            {"type":"text","text":"first"},
+            {"type":"text","text":"second","cache_control":{"type":"ephemeral"}},
+            {"type":"text","text":"third"}
+        ]);
+        assert_eq!(
+            decode_system_prompt(Some(&system)).as_deref(),
+            Some("first\nsecond\nthird")
+        );
+        assert_eq!(
+            decode_system_prompt_owned(Some(system)).as_deref(),
+            Some("first\nsecond\nthird")
+        );
+    }
+
     #[test]
     fn test_decode_user_tool_result_maps_to_tool_role() {
         let req = AnthropicRequest {
@@ -3,7 +3,7 @@ use crate::protocol::anthropic::{AnthropicMessage, AnthropicRequest, AnthropicTo
 use crate::protocol::canonical::{
     provider_extensions_to_map, CanonicalPart, CanonicalRequest, CanonicalRole, CanonicalToolChoice,
 };
-use crate::protocol::mapping::canonical_role_to_anthropic;
+use crate::protocol::mapping::{anthropic_max_tokens, canonical_role_to_anthropic};
 
 /// Encode a canonical request into the Anthropic Messages API wire format.
 ///
@@ -15,14 +15,13 @@ pub fn encode_anthropic_request(
 ) -> Result<AnthropicRequest, CanonicalError> {
     // --- system ---
     let system = canonical
-        .system_prompt
-        .as_ref()
-        .map(|s| serde_json::Value::String(s.clone()));
+        .merged_system_prompt()
+        .map(serde_json::Value::String);
 
     // --- messages ---
     let mut messages = Vec::new();
     for msg in &canonical.messages {
-        // System messages should not appear in Anthropic messages array.
+        // System messages are merged into `system` above.
         if msg.role == CanonicalRole::System {
             continue;
         }
@@ -32,6 +31,13 @@ pub fn encode_anthropic_request(
 
         messages.push(AnthropicMessage { role, content });
     }
+    // A trailing assistant turn is a prefill, which may not end in whitespace.
+    if let Some(last) = messages.last_mut().filter(|m| m.role == "assistant") {
+        trim_prefill_trailing_whitespace(&mut last.content);
+        if last.content.as_array().is_some_and(Vec::is_empty) {
+            messages.pop();
+        }
+    }
 
     // --- tools ---
     let tools = if canonical.tools.is_empty() {
@@ -54,7 +60,10 @@ pub fn encode_anthropic_request(
     let tool_choice = encode_tool_choice(&canonical.tool_choice, &canonical.tools);
 
     // --- max_tokens (required for Anthropic) ---
-    let max_tokens = canonical.generation.max_tokens.unwrap_or(4096);
+    let max_tokens = anthropic_max_tokens(
+        canonical.generation.max_tokens,
+        thinking_budget_tokens(canonical),
+    );
 
     // --- stream ---
     let stream = if canonical.stream { Some(true) } else { None };
@@ -75,6 +84,16 @@ pub fn encode_anthropic_request(
 }
 
 /// Encode canonical parts into an Anthropic content JSON value (always an array).
+/// The extended thinking `budget_tokens` the request carries, if any.
+#[must_use]
+pub fn thinking_budget_tokens(canonical: &CanonicalRequest) -> Option<u64> {
+    canonical
+        .provider_extensions_ref()
+        .get("thinking")
+        .and_then(|thinking| thinking.get("budget_tokens"))
+        .and_then(serde_json::Value::as_u64)
+}
+
 fn encode_parts(role: CanonicalRole, parts: &[CanonicalPart]) -> serde_json::Value {
     let mut blocks = Vec::with_capacity(parts.len());
     // Anthropic requires `tool_result` blocks to come first in a user message.
@@ -97,6 +116,27 @@ fn encode_parts(role: CanonicalRole, parts: &[CanonicalPart]) -> serde_json::Val
     serde_json::Value::Array(blocks)
 }
 
+fn trim_prefill_trailing_whitespace(content: &mut serde_json::Value) {
+    let Some(blocks) = content.as_array_mut() else {
+        return;
+    };
+    let Some(last) = blocks.last_mut() else {
+        return;
+    };
+    if last.get("type").and_then(serde_json::Value::as_str) != Some("text") {
+        return;
+    }
+    let Some(text) = last.get("text").and_then(serde_json::Value::as_str) else {
+        return;
+    };
+    let trimmed = text.trim_end();
+    if trimmed.is_empty() {
+        blocks.pop();
+    } else if trimmed.len() < text.len() {
+        last["text"] = serde_json::Value::String(trimmed.to_string());
+    }
+}
+
 fn encode_part(part: &CanonicalPart, blocks: &mut Vec<serde_json::Value>) {
     match part {
         CanonicalPart::Text(text) | CanonicalPart::Refusal(text) => {
@@ -111,6 +151,8 @@ fn encode_part(part: &CanonicalPart, blocks: &mut Vec<serde_json::Value>) {
                 "thinking": text,
             }));
         }
+        // Responses reasoning state is opaque to other providers.
+        CanonicalPart::ReasoningItem { .. } => {}
         CanonicalPart::ToolCall {
             id,
             name,
@@ -219,4 +261,39 @@ mod tests {
             Some("text")
         );
     }
+
+    #[test]
+    fn test_trailing_assistant_prefill_is_trimmed() {
+        let message = |role, text: &str| CanonicalMessage {
+            role,
+            parts: vec![CanonicalPart::Text(text.into())].into(),
+            name: None,
+            tool_call_id: None,
+            provider_extensions: None,
+        };
+        let mut req = CanonicalRequest {
+            request_id: uuid::Uuid::from_u128(1),
+            ingress_api: IngressApi::Gemini,
+            model: "claude-sonnet-4-5".into(),
+            stream: false,
+            system_prompt: None,
+            messages: vec![
+                message(CanonicalRole::User, "Write a story"),
+                message(CanonicalRole::Assistant, "Once upon a time "),
+            ],
+            tools: Vec::new().into(),
+            tool_choice: CanonicalToolChoice::Auto,
+            generation: GenerationParams::default(),
+            provider_extensions: None,
+        };
+
+        let wire = encode_anthropic_request(&req).unwrap();
+        assert_eq!(wire.messages.len(), 2);
+        assert_eq!(wire.messages[1].content[0]["text"], "Once upon a time");
+
+        req.messages[1] = message(CanonicalRole::Assistant, "  \n");
+        let wire = encode_anthropic_request(&req).unwrap();
+        assert_eq!(wire.messages.len(), 1);
+        assert_eq!(wire.messages[0].role, "user");
+    }
 }
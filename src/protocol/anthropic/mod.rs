@@ -51,6 +51,10 @@ pub struct AnthropicResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop_sequence: Option<String>,
     pub usage: AnthropicUsage,
+    /// Upstream safety-filter details, set when a non-Anthropic provider
+    /// filtered the output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety: Option<serde_json::Value>,
 }
 
 /// A content block in an Anthropic response.
@@ -58,7 +62,11 @@ pub struct AnthropicResponse {
 #[serde(tag = "type")]
 pub enum AnthropicContentBlock {
     #[serde(rename = "text")]
-    Text { text: String },
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        citations: Vec<AnthropicCitation>,
+    },
     #[serde(rename = "thinking")]
     Thinking { thinking: String },
     #[serde(rename = "tool_use")]
@@ -74,6 +82,23 @@ pub enum AnthropicContentBlock {
     },
 }
 
+/// A citation supporting a text block. Web search results
+/// (`web_search_result_location`) carry `url`; the location fields of other
+/// citation types are kept in `extra`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicCitation {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cited_text: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
 /// Anthropic tool definition.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnthropicTool {
@@ -86,8 +111,13 @@ pub struct AnthropicTool {
 /// Anthropic usage.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnthropicUsage {
+    /// Input tokens not read from or written to the prompt cache.
     pub input_tokens: u64,
     pub output_tokens: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u64>,
 }
 
 /// Anthropic SSE stream event.
@@ -1,7 +1,97 @@
+use std::borrow::Cow;
+
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
 use crate::error::CanonicalError;
-use crate::protocol::anthropic::{AnthropicContentBlock, AnthropicResponse};
-use crate::protocol::canonical::{CanonicalPart, CanonicalResponse, CanonicalUsage};
-use crate::protocol::mapping::anthropic_stop_to_canonical;
+use crate::protocol::anthropic::{
+    AnthropicCitation, AnthropicContentBlock, AnthropicResponse, AnthropicUsage,
+};
+use crate::protocol::canonical::{
+    CanonicalCitation, CanonicalPart, CanonicalResponse, CanonicalUsage,
+};
+use crate::protocol::mapping::{anthropic_stop_to_canonical, anthropic_usage_counts_to_canonical};
+
+#[derive(Debug, Deserialize)]
+struct AnthropicFastResponse<'a> {
+    #[serde(borrow)]
+    id: Cow<'a, str>,
+    #[serde(borrow)]
+    model: Cow<'a, str>,
+    #[serde(borrow)]
+    content: Vec<AnthropicFastBlock<'a>>,
+    #[serde(default, borrow)]
+    stop_reason: Option<Cow<'a, str>>,
+    usage: AnthropicUsage,
+}
+
+/// A content block read field by field: a tagged enum would buffer every
+/// block before picking the variant, and could not borrow `input` raw.
+#[derive(Debug, Deserialize)]
+struct AnthropicFastBlock<'a> {
+    #[serde(rename = "type", borrow)]
+    kind: Cow<'a, str>,
+    #[serde(default, borrow)]
+    text: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    thinking: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    id: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    name: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    input: Option<&'a RawValue>,
+    #[serde(default)]
+    citations: Vec<AnthropicCitation>,
+}
+
+/// Attempt to decode an Anthropic Messages response directly from bytes,
+/// borrowing strings and `tool_use` inputs from `body`.
+///
+/// Tool inputs keep the upstream's JSON text rather than being re-serialized.
+/// Server tool calls and their results (e.g. web search) are left out; the
+/// text blocks cite what they found. Returns `None` when payload is not in
+/// the expected fast-path shape (including `tool_result` blocks).
+#[must_use]
+pub fn try_decode_anthropic_response_bytes(body: &[u8]) -> Option<CanonicalResponse> {
+    let parsed: AnthropicFastResponse<'_> = serde_json::from_slice(body).ok()?;
+
+    let mut content = Vec::with_capacity(parsed.content.len());
+    let mut citations = TextCitations::default();
+    for block in parsed.content {
+        let part = match block.kind.as_ref() {
+            "text" => {
+                let text = block.text?.into_owned();
+                citations.push(&text, &block.citations);
+                CanonicalPart::Text(text)
+            }
+            "server_tool_use" | "web_search_tool_result" => continue,
+            "thinking" => CanonicalPart::ReasoningText(block.thinking?.into_owned()),
+            "tool_use" => CanonicalPart::ToolCall {
+                id: block.id?.into_owned(),
+                name: block.name?.into_owned(),
+                arguments: block.input?.to_owned(),
+            },
+            _ => return None,
+        };
+        content.push(part);
+    }
+
+    let stop_reason = parsed.stop_reason.as_deref().map_or(
+        crate::protocol::canonical::CanonicalStopReason::EndOfTurn,
+        anthropic_stop_to_canonical,
+    );
+
+    Some(CanonicalResponse {
+        id: parsed.id.into_owned(),
+        model: parsed.model.into_owned(),
+        content,
+        stop_reason,
+        usage: decode_anthropic_usage(&parsed.usage),
+        citations: citations.citations,
+        provider_extensions: serde_json::Map::new(),
+    })
+}
 
 /// Decode an Anthropic Messages API response into canonical form.
 ///
@@ -14,9 +104,14 @@ pub fn decode_anthropic_response(
 ) -> Result<CanonicalResponse, CanonicalError> {
     // --- content blocks ---
     let mut content = Vec::new();
+    let mut citations = TextCitations::default();
     for block in &response.content {
         match block {
-            AnthropicContentBlock::Text { text } => {
+            AnthropicContentBlock::Text {
+                text,
+                citations: block_citations,
+            } => {
+                citations.push(text, block_citations);
                 content.push(CanonicalPart::Text(text.clone()));
             }
             AnthropicContentBlock::Thinking { thinking } => {
@@ -57,13 +152,7 @@ pub fn decode_anthropic_response(
     );
 
     // --- usage ---
-    let input_tokens = response.usage.input_tokens;
-    let output_tokens = response.usage.output_tokens;
-    let usage = CanonicalUsage {
-        input_tokens: Some(input_tokens),
-        output_tokens: Some(output_tokens),
-        total_tokens: Some(input_tokens + output_tokens),
-    };
+    let usage = decode_anthropic_usage(&response.usage);
 
     Ok(CanonicalResponse {
         id: response.id.clone(),
@@ -71,6 +160,7 @@ pub fn decode_anthropic_response(
         content,
         stop_reason,
         usage,
+        citations: citations.citations,
         provider_extensions: serde_json::Map::new(),
     })
 }
@@ -93,12 +183,18 @@ pub fn decode_anthropic_response_owned(
         stop_reason,
         stop_sequence: _,
         usage: usage_wire,
+        safety: _,
     } = response;
 
     let mut content = Vec::with_capacity(blocks.len());
+    let mut citations = TextCitations::default();
     for block in blocks {
         match block {
-            AnthropicContentBlock::Text { text } => {
+            AnthropicContentBlock::Text {
+                text,
+                citations: block_citations,
+            } => {
+                citations.push(&text, &block_citations);
                 content.push(CanonicalPart::Text(text));
             }
             AnthropicContentBlock::Thinking { thinking } => {
@@ -137,11 +233,7 @@ pub fn decode_anthropic_response_owned(
         anthropic_stop_to_canonical,
     );
 
-    let usage = CanonicalUsage {
-        input_tokens: Some(usage_wire.input_tokens),
-        output_tokens: Some(usage_wire.output_tokens),
-        total_tokens: Some(usage_wire.input_tokens + usage_wire.output_tokens),
-    };
+    let usage = decode_anthropic_usage(&usage_wire);
 
     Ok(CanonicalResponse {
         id,
@@ -149,10 +241,48 @@ pub fn decode_anthropic_response_owned(
         content,
         stop_reason,
         usage,
+        citations: citations.citations,
         provider_extensions: serde_json::Map::new(),
     })
 }
 
+/// Citations of text blocks, located by the characters of the text before
+/// each block.
+#[derive(Default)]
+struct TextCitations {
+    citations: Vec<CanonicalCitation>,
+    offset: usize,
+}
+
+impl TextCitations {
+    /// Add the web citations of a text block, each spanning the whole block.
+    fn push(&mut self, text: &str, citations: &[AnthropicCitation]) {
+        let start = self.offset;
+        self.offset += text.chars().count();
+        self.citations
+            .extend(citations.iter().filter_map(|citation| {
+                Some(CanonicalCitation {
+                    url: citation.url.clone()?,
+                    title: citation.title.clone(),
+                    cited_text: citation.cited_text.clone(),
+                    span: Some(start..self.offset),
+                })
+            }));
+    }
+}
+
+/// Decode Anthropic usage into canonical usage, counting prompt cache reads
+/// and writes as input.
+#[must_use]
+pub fn decode_anthropic_usage(usage: &AnthropicUsage) -> CanonicalUsage {
+    anthropic_usage_counts_to_canonical(
+        Some(usage.input_tokens),
+        Some(usage.output_tokens),
+        usage.cache_read_input_tokens,
+        usage.cache_creation_input_tokens,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -168,6 +298,7 @@ mod tests {
             content: vec![
                 AnthropicContentBlock::Text {
                     text: "Hello".to_string(),
+                    citations: Vec::new(),
                 },
                 AnthropicContentBlock::ToolUse {
                     id: "toolu_1".to_string(),
@@ -180,7 +311,10 @@ mod tests {
             usage: AnthropicUsage {
                 input_tokens: 10,
                 output_tokens: 5,
+                cache_creation_input_tokens: None,
+                cache_read_input_tokens: None,
             },
+            safety: None,
         };
 
         let borrowed = decode_anthropic_response(&response).unwrap();
@@ -210,4 +344,55 @@ mod tests {
         assert_eq!(borrowed.stop_reason, owned.stop_reason);
         assert_eq!(borrowed.usage.total_tokens, owned.usage.total_tokens);
     }
+
+    #[test]
+    fn test_try_decode_response_bytes_matches_owned_decode() {
+        let body = br#"{"id":"msg_1","type":"message","role":"assistant","model":"claude",
+            "content":[{"type":"thinking","thinking":"hmm","signature":"sig"},
+                {"type":"text","text":"say \"hi\""},
+                {"type":"tool_use","id":"toolu_1","name":"lookup","input":{"q":"rust"}}],
+            "stop_reason":"tool_use","usage":{"input_tokens":10,"output_tokens":5}}"#;
+        let fast = try_decode_anthropic_response_bytes(body).unwrap();
+        let wire: AnthropicResponse = serde_json::from_slice(body).unwrap();
+        let owned = decode_anthropic_response_owned(wire).unwrap();
+        assert_eq!(fast.id, owned.id);
+        assert_eq!(fast.model, owned.model);
+        assert_eq!(
+            format!("{:?}", fast.content),
+            format!("{:?}", owned.content)
+        );
+        assert_eq!(fast.stop_reason, owned.stop_reason);
+        assert_eq!(fast.usage.total_tokens, Some(15));
+
+        let tool_result = br#"{"id":"msg_2","type":"message","role":"assistant","model":"claude",
+            "content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"ok"}],
+            "usage":{"input_tokens":1,"output_tokens":1}}"#;
+        assert!(try_decode_anthropic_response_bytes(tool_result).is_none());
+    }
+
+    #[test]
+    fn test_decode_keeps_anthropic_stop_reason_nuances() {
+        use crate::protocol::canonical::CanonicalStopReason;
+
+        for (wire, expected) in [
+            ("stop_sequence", CanonicalStopReason::StopSequence),
+            ("refusal", CanonicalStopReason::Refusal),
+            ("pause_turn", CanonicalStopReason::PauseTurn),
+            (
+                "model_context_window_exceeded",
+                CanonicalStopReason::MaxTokens,
+            ),
+        ] {
+            let body = format!(
+                r#"{{"id":"msg_1","type":"message","role":"assistant","model":"claude",
+                "content":[{{"type":"text","text":"hi"}}],"stop_reason":"{wire}",
+                "usage":{{"input_tokens":1,"output_tokens":1}}}}"#
+            );
+            let fast = try_decode_anthropic_response_bytes(body.as_bytes()).unwrap();
+            assert_eq!(fast.stop_reason, expected, "{wire}");
+            let wire_response: AnthropicResponse = serde_json::from_str(&body).unwrap();
+            let owned = decode_anthropic_response_owned(wire_response).unwrap();
+            assert_eq!(owned.stop_reason, expected, "{wire}");
+        }
+    }
 }
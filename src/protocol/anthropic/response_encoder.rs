@@ -1,6 +1,11 @@
 use crate::error::CanonicalError;
-use crate::protocol::anthropic::{AnthropicContentBlock, AnthropicResponse, AnthropicUsage};
-use crate::protocol::canonical::{CanonicalPart, CanonicalResponse};
+use crate::protocol::anthropic::{
+    AnthropicCitation, AnthropicContentBlock, AnthropicResponse, AnthropicUsage,
+};
+use crate::protocol::canonical::{
+    byte_index_at_char, CanonicalCitation, CanonicalPart, CanonicalResponse,
+    REASONING_SUMMARY_SEPARATOR, SAFETY_EXTENSION_KEY,
+};
 use crate::protocol::mapping::canonical_stop_to_anthropic;
 use crate::util::next_generated_id;
 use std::sync::atomic::AtomicU64;
@@ -23,6 +28,7 @@ pub fn encode_anthropic_response(
 ) -> Result<AnthropicResponse, CanonicalError> {
     // --- content blocks ---
     let mut content = Vec::new();
+    let mut text_offset = 0;
     for part in &canonical.content {
         match part {
             CanonicalPart::ReasoningText(text) => {
@@ -30,6 +36,11 @@ pub fn encode_anthropic_response(
                     thinking: text.clone(),
                 });
             }
+            CanonicalPart::ReasoningItem { summary, .. } => {
+                content.push(AnthropicContentBlock::Thinking {
+                    thinking: summary.join(REASONING_SUMMARY_SEPARATOR),
+                });
+            }
             CanonicalPart::ToolCall {
                 id,
                 name,
@@ -55,8 +66,16 @@ pub fn encode_anthropic_response(
             CanonicalPart::ImageUrl { .. } => {
                 // Images are not part of response content blocks — skip
             }
-            CanonicalPart::Text(text) | CanonicalPart::Refusal(text) => {
-                content.push(AnthropicContentBlock::Text { text: text.clone() });
+            CanonicalPart::Text(text) => {
+                let start = text_offset;
+                text_offset += text.chars().count();
+                push_cited_text(&mut content, text, start, &canonical.citations);
+            }
+            CanonicalPart::Refusal(text) => {
+                content.push(AnthropicContentBlock::Text {
+                    text: text.clone(),
+                    citations: Vec::new(),
+                });
             }
         }
     }
@@ -66,8 +85,10 @@ pub fn encode_anthropic_response(
 
     // --- usage ---
     let usage = AnthropicUsage {
-        input_tokens: canonical.usage.input_tokens.unwrap_or(0),
+        input_tokens: canonical.usage.uncached_input_tokens(),
         output_tokens: canonical.usage.output_tokens.unwrap_or(0),
+        cache_creation_input_tokens: canonical.usage.cache_creation_input_tokens,
+        cache_read_input_tokens: canonical.usage.cached_input_tokens,
     };
 
     // --- generate id if empty ---
@@ -86,5 +107,62 @@ pub fn encode_anthropic_response(
         stop_reason,
         stop_sequence: None,
         usage,
+        safety: canonical
+            .provider_extensions
+            .get(SAFETY_EXTENSION_KEY)
+            .cloned(),
     })
 }
+
+/// Push `text`, which starts at character `start` of the response text, as
+/// text blocks split where the cited spans begin and end, each block carrying
+/// the citations covering it. Citations without a span go to the first text.
+fn push_cited_text(
+    content: &mut Vec<AnthropicContentBlock>,
+    text: &str,
+    start: usize,
+    citations: &[CanonicalCitation],
+) {
+    let end = start + text.chars().count();
+    if citations.is_empty() || start == end {
+        content.push(AnthropicContentBlock::Text {
+            text: text.to_string(),
+            citations: Vec::new(),
+        });
+        return;
+    }
+    let mut bounds = vec![start, end];
+    for span in citations
+        .iter()
+        .filter_map(|citation| citation.span.as_ref())
+    {
+        bounds.extend(
+            [span.start, span.end]
+                .into_iter()
+                .filter(|bound| (start + 1..end).contains(bound)),
+        );
+    }
+    bounds.sort_unstable();
+    bounds.dedup();
+    for segment in bounds.windows(2) {
+        let (from, to) = (segment[0], segment[1]);
+        let covering = citations.iter().filter(|citation| match &citation.span {
+            Some(span) => span.start <= from && to <= span.end,
+            None => from == 0,
+        });
+        let text =
+            &text[byte_index_at_char(text, from - start)..byte_index_at_char(text, to - start)];
+        content.push(AnthropicContentBlock::Text {
+            text: text.to_string(),
+            citations: covering
+                .map(|citation| AnthropicCitation {
+                    type_: "web_search_result_location".to_string(),
+                    url: Some(citation.url.clone()),
+                    title: citation.title.clone(),
+                    cited_text: citation.cited_text.clone(),
+                    extra: serde_json::Map::new(),
+                })
+                .collect(),
+        });
+    }
+}
@@ -1,7 +1,10 @@
+use crate::protocol::anthropic::response_decoder::decode_anthropic_usage;
 use crate::protocol::anthropic::{AnthropicContentBlock, AnthropicDelta, AnthropicStreamEvent};
-use crate::protocol::canonical::{CanonicalRole, CanonicalStreamEvent, CanonicalUsage};
+use crate::protocol::canonical::{
+    CanonicalRole, CanonicalStopReason, CanonicalStreamEvent, CanonicalUsage,
+};
 use crate::protocol::mapping::{anthropic_stop_to_canonical, canonical_stop_to_anthropic};
-use crate::util::{push_json_string_escaped, push_usize_decimal};
+use crate::util::{push_json_string_escaped, push_u64_decimal, push_usize_decimal};
 
 /// Parse an Anthropic SSE named event into a typed stream event.
 ///
@@ -49,18 +52,14 @@ pub fn decode_anthropic_stream_event_into(
             // Emit initial usage if present
             let usage = &message.usage;
             if usage.input_tokens > 0 || usage.output_tokens > 0 {
-                out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-                    input_tokens: Some(usage.input_tokens),
-                    output_tokens: Some(usage.output_tokens),
-                    total_tokens: Some(usage.input_tokens + usage.output_tokens),
-                }));
+                out.push(CanonicalStreamEvent::Usage(decode_anthropic_usage(usage)));
             }
         }
         AnthropicStreamEvent::ContentBlockStart {
             index,
             content_block,
         } => match content_block {
-            AnthropicContentBlock::Text { text } => {
+            AnthropicContentBlock::Text { text, .. } => {
                 if !text.is_empty() {
                     out.push(CanonicalStreamEvent::TextDelta(text.clone()));
                 }
@@ -99,11 +98,7 @@ pub fn decode_anthropic_stream_event_into(
         }
         AnthropicStreamEvent::MessageDelta { delta, usage } => {
             // Usage update
-            out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-                input_tokens: Some(usage.input_tokens),
-                output_tokens: Some(usage.output_tokens),
-                total_tokens: Some(usage.input_tokens + usage.output_tokens),
-            }));
+            out.push(CanonicalStreamEvent::Usage(decode_anthropic_usage(usage)));
             // Stop reason → MessageEnd
             if let Some(reason_str) = &delta.stop_reason {
                 out.push(CanonicalStreamEvent::MessageEnd {
@@ -139,18 +134,14 @@ pub fn decode_anthropic_stream_event_owned_into(
             });
             let usage = message.usage;
             if usage.input_tokens > 0 || usage.output_tokens > 0 {
-                out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-                    input_tokens: Some(usage.input_tokens),
-                    output_tokens: Some(usage.output_tokens),
-                    total_tokens: Some(usage.input_tokens + usage.output_tokens),
-                }));
+                out.push(CanonicalStreamEvent::Usage(decode_anthropic_usage(&usage)));
             }
         }
         AnthropicStreamEvent::ContentBlockStart {
             index,
             content_block,
         } => match content_block {
-            AnthropicContentBlock::Text { text } => {
+            AnthropicContentBlock::Text { text, .. } => {
                 if !text.is_empty() {
                     out.push(CanonicalStreamEvent::TextDelta(text));
                 }
@@ -184,11 +175,7 @@ pub fn decode_anthropic_stream_event_owned_into(
             // tracks block types and only emits ToolCallEnd for tool_use blocks.
         }
         AnthropicStreamEvent::MessageDelta { delta, usage } => {
-            out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-                input_tokens: Some(usage.input_tokens),
-                output_tokens: Some(usage.output_tokens),
-                total_tokens: Some(usage.input_tokens + usage.output_tokens),
-            }));
+            out.push(CanonicalStreamEvent::Usage(decode_anthropic_usage(&usage)));
             if let Some(reason_str) = delta.stop_reason {
                 out.push(CanonicalStreamEvent::MessageEnd {
                     stop_reason: anthropic_stop_to_canonical(&reason_str),
@@ -457,6 +444,35 @@ pub fn encode_canonical_event_to_anthropic_sse_into(
     }
 }
 
+/// Append a `message_delta` SSE frame carrying the stop reason and, when
+/// known, the accumulated token usage.
+pub fn push_anthropic_message_delta_sse_frame(
+    out: &mut String,
+    stop_reason: CanonicalStopReason,
+    usage: Option<&CanonicalUsage>,
+) {
+    out.push_str(
+        "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":",
+    );
+    push_json_string_escaped(out, canonical_stop_to_anthropic(stop_reason));
+    out.push_str(",\"stop_sequence\":null},\"usage\":{\"input_tokens\":");
+    push_u64_decimal(out, usage.map_or(0, CanonicalUsage::uncached_input_tokens));
+    out.push_str(",\"output_tokens\":");
+    push_u64_decimal(
+        out,
+        usage.and_then(|usage| usage.output_tokens).unwrap_or(0),
+    );
+    if let Some(created) = usage.and_then(|usage| usage.cache_creation_input_tokens) {
+        out.push_str(",\"cache_creation_input_tokens\":");
+        push_u64_decimal(out, created);
+    }
+    if let Some(cached) = usage.and_then(|usage| usage.cached_input_tokens) {
+        out.push_str(",\"cache_read_input_tokens\":");
+        push_u64_decimal(out, cached);
+    }
+    out.push_str("}}\n\n");
+}
+
 /// Encode a canonical stream event directly into a full Anthropic SSE frame.
 ///
 /// Returns `true` when a frame is produced and written into `out`.
@@ -528,11 +544,7 @@ pub fn encode_canonical_event_to_anthropic_sse_frame(
         }
         CanonicalStreamEvent::Usage(_) => false,
         CanonicalStreamEvent::MessageEnd { stop_reason } => {
-            out.push_str("event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":");
-            push_json_string_escaped(out, canonical_stop_to_anthropic(*stop_reason));
-            out.push_str(
-                ",\"stop_sequence\":null},\"usage\":{\"input_tokens\":0,\"output_tokens\":0}}\n\n",
-            );
+            push_anthropic_message_delta_sse_frame(out, *stop_reason, None);
             true
         }
         CanonicalStreamEvent::Done => {
@@ -1,6 +1,7 @@
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
+use std::ops::Range;
 use std::sync::Arc;
 
 pub type ProviderExtensions = serde_json::Map<String, serde_json::Value>;
@@ -35,12 +36,24 @@ pub enum CanonicalRole {
 }
 
 /// Reason the model stopped generating.
+///
+/// Finer than any one protocol: each encoder maps a reason its protocol
+/// cannot express to the closest one it can.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CanonicalStopReason {
     EndOfTurn,
+    /// A client stop sequence was generated.
+    StopSequence,
     ToolCalls,
     MaxTokens,
+    /// A provider safety filter blocked or cut the output.
     ContentFilter,
+    /// Gemini stopped the output for reciting training data.
+    Recitation,
+    /// Anthropic's classifiers declined the request.
+    Refusal,
+    /// Anthropic paused a long-running turn for the client to continue.
+    PauseTurn,
 }
 
 /// Tool choice specification.
@@ -53,11 +66,40 @@ pub enum CanonicalToolChoice {
 }
 
 /// Token usage information.
+///
+/// `input_tokens` counts every prompt token, including those read from or
+/// written to a prompt cache, and `output_tokens` includes reasoning tokens,
+/// whatever the provider's own convention.
 #[derive(Debug, Clone, Default)]
 pub struct CanonicalUsage {
     pub input_tokens: Option<u64>,
     pub output_tokens: Option<u64>,
     pub total_tokens: Option<u64>,
+    /// Input tokens read from the prompt cache.
+    pub cached_input_tokens: Option<u64>,
+    /// Input tokens written to the prompt cache (Anthropic).
+    pub cache_creation_input_tokens: Option<u64>,
+    /// Output tokens spent on reasoning.
+    pub reasoning_tokens: Option<u64>,
+}
+
+impl CanonicalUsage {
+    /// Input tokens neither read from nor written to the prompt cache.
+    #[must_use]
+    pub fn uncached_input_tokens(&self) -> u64 {
+        self.input_tokens
+            .unwrap_or(0)
+            .saturating_sub(self.cached_input_tokens.unwrap_or(0))
+            .saturating_sub(self.cache_creation_input_tokens.unwrap_or(0))
+    }
+
+    /// Output tokens other than reasoning tokens.
+    #[must_use]
+    pub fn visible_output_tokens(&self) -> u64 {
+        self.output_tokens
+            .unwrap_or(0)
+            .saturating_sub(self.reasoning_tokens.unwrap_or(0))
+    }
 }
 
 /// Generation parameters passed through to the upstream.
@@ -77,6 +119,13 @@ pub struct GenerationParams {
 pub enum CanonicalPart {
     Text(String),
     ReasoningText(String),
+    /// An `OpenAI` Responses `reasoning` item, kept whole so its id and
+    /// `encrypted_content` can be handed back to a Responses upstream.
+    ReasoningItem {
+        id: String,
+        summary: Vec<String>,
+        encrypted_content: Option<String>,
+    },
     ImageUrl {
         url: String,
         detail: Option<String>,
@@ -140,9 +189,47 @@ pub struct CanonicalResponse {
     pub content: Vec<CanonicalPart>,
     pub stop_reason: CanonicalStopReason,
     pub usage: CanonicalUsage,
+    /// Sources the response text cites, e.g. web search results.
+    pub citations: Vec<CanonicalCitation>,
     pub provider_extensions: ProviderExtensions,
 }
 
+/// A source cited by the response text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalCitation {
+    pub url: String,
+    pub title: Option<String>,
+    /// The passage of the source that is cited, when the provider quotes it.
+    pub cited_text: Option<String>,
+    /// Character range of the cited claim in the response's text parts
+    /// joined in order; `None` when the provider does not locate it.
+    pub span: Option<Range<usize>>,
+}
+
+/// Character index of byte offset `byte` in `text`, clamped to its length.
+#[must_use]
+pub fn char_index_at_byte(text: &str, byte: usize) -> usize {
+    text.char_indices()
+        .take_while(|(index, _)| *index < byte)
+        .count()
+}
+
+/// Byte offset of character index `index` in `text`, clamped to its length.
+#[must_use]
+pub fn byte_index_at_char(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(byte, _)| byte)
+}
+
+/// Joins the parts of a Responses reasoning summary when it is rendered as a
+/// single reasoning text for other protocols.
+pub const REASONING_SUMMARY_SEPARATOR: &str = "\n\n";
+
+/// Response extension key carrying provider safety-filter details (block
+/// reason and per-category ratings) when the output was filtered.
+pub const SAFETY_EXTENSION_KEY: &str = "safety";
+
 #[must_use]
 pub fn provider_extensions_from_map(map: ProviderExtensions) -> Option<Box<ProviderExtensions>> {
     if map.is_empty() {
@@ -171,6 +258,55 @@ impl CanonicalRequest {
         self.provider_extensions
             .get_or_insert_with(|| Box::new(ProviderExtensions::new()))
     }
+
+    /// Text of the in-conversation system/developer messages, in order.
+    fn system_messages_text(&self, separator: &str) -> Option<String> {
+        let mut out: Option<String> = None;
+        for msg in &self.messages {
+            if msg.role != CanonicalRole::System {
+                continue;
+            }
+            for part in &msg.parts {
+                if let CanonicalPart::Text(text) = part {
+                    match out.as_mut() {
+                        Some(joined) => {
+                            joined.push_str(separator);
+                            joined.push_str(text);
+                        }
+                        None => out = Some(text.clone()),
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// System prompt for providers without in-conversation system messages:
+    /// the top-level prompt followed by any system/developer message text.
+    #[must_use]
+    pub fn merged_system_prompt(&self) -> Option<String> {
+        match (self.system_prompt.as_ref(), self.system_messages_text("\n")) {
+            (Some(top), Some(messages)) => Some(format!("{top}\n{messages}")),
+            (top, messages) => messages.or_else(|| top.cloned()),
+        }
+    }
+
+    /// Move system/developer messages into `system_prompt`.
+    ///
+    /// With `messages_first` the folded message text precedes the existing
+    /// top-level prompt; otherwise it follows it.
+    pub fn fold_system_messages(&mut self, messages_first: bool, separator: &str) {
+        let Some(messages) = self.system_messages_text(separator) else {
+            return;
+        };
+        self.messages
+            .retain(|msg| msg.role != CanonicalRole::System);
+        self.system_prompt = Some(match self.system_prompt.take() {
+            Some(top) if messages_first => format!("{messages}{separator}{top}"),
+            Some(top) => format!("{top}{separator}{messages}"),
+            None => messages,
+        });
+    }
 }
 
 fn empty_extensions() -> &'static ProviderExtensions {
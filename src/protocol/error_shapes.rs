@@ -5,8 +5,13 @@ fn openai_error_type(cat: ErrorCategory) -> &'static str {
         ErrorCategory::InvalidRequest => "invalid_request_error",
         ErrorCategory::Authentication => "authentication_error",
         ErrorCategory::Permission => "permission_error",
+        ErrorCategory::NotFound => "not_found_error",
+        ErrorCategory::RequestTooLarge => "invalid_request_error",
         ErrorCategory::RateLimit => "rate_limit_error",
-        ErrorCategory::ServerError | ErrorCategory::Unknown => "server_error",
+        ErrorCategory::Timeout => "timeout_error",
+        ErrorCategory::Overloaded | ErrorCategory::ServerError | ErrorCategory::Unknown => {
+            "server_error"
+        }
     }
 }
 
@@ -15,7 +20,11 @@ fn openai_error_code(cat: ErrorCategory) -> &'static str {
         ErrorCategory::InvalidRequest => "invalid_request",
         ErrorCategory::Authentication => "invalid_api_key",
         ErrorCategory::Permission => "permission_denied",
+        ErrorCategory::NotFound => "not_found",
+        ErrorCategory::RequestTooLarge => "request_too_large",
         ErrorCategory::RateLimit => "rate_limit_exceeded",
+        ErrorCategory::Timeout => "timeout",
+        ErrorCategory::Overloaded => "overloaded",
         ErrorCategory::ServerError | ErrorCategory::Unknown => "server_error",
     }
 }
@@ -23,18 +32,26 @@ fn openai_error_code(cat: ErrorCategory) -> &'static str {
 fn anthropic_error_type(cat: ErrorCategory) -> &'static str {
     match cat {
         ErrorCategory::InvalidRequest => "invalid_request_error",
-        ErrorCategory::Authentication | ErrorCategory::Permission => "authentication_error",
+        ErrorCategory::Authentication => "authentication_error",
+        ErrorCategory::Permission => "permission_error",
+        ErrorCategory::NotFound => "not_found_error",
+        ErrorCategory::RequestTooLarge => "request_too_large",
         ErrorCategory::RateLimit => "rate_limit_error",
+        ErrorCategory::Timeout => "timeout_error",
+        ErrorCategory::Overloaded => "overloaded_error",
         ErrorCategory::ServerError | ErrorCategory::Unknown => "api_error",
     }
 }
 
 fn gemini_error_status(cat: ErrorCategory) -> &'static str {
     match cat {
-        ErrorCategory::InvalidRequest => "INVALID_ARGUMENT",
+        ErrorCategory::InvalidRequest | ErrorCategory::RequestTooLarge => "INVALID_ARGUMENT",
         ErrorCategory::Authentication => "UNAUTHENTICATED",
         ErrorCategory::Permission => "PERMISSION_DENIED",
+        ErrorCategory::NotFound => "NOT_FOUND",
         ErrorCategory::RateLimit => "RESOURCE_EXHAUSTED",
+        ErrorCategory::Timeout => "DEADLINE_EXCEEDED",
+        ErrorCategory::Overloaded => "UNAVAILABLE",
         ErrorCategory::ServerError | ErrorCategory::Unknown => "INTERNAL",
     }
 }
@@ -2,13 +2,13 @@ use std::collections::HashMap;
 
 use crate::error::CanonicalError;
 use crate::protocol::canonical::{
-    CanonicalPart, CanonicalRequest, CanonicalRole, CanonicalToolChoice,
+    CanonicalPart, CanonicalRequest, CanonicalRole, CanonicalToolChoice, ProviderKind,
 };
 use crate::protocol::gemini::{
     GeminiContent, GeminiFunctionCallingConfig, GeminiFunctionDeclaration, GeminiGenerationConfig,
     GeminiPart, GeminiRequest, GeminiToolConfig, GeminiToolDeclaration,
 };
-use crate::protocol::mapping::canonical_role_to_gemini;
+use crate::protocol::mapping::{canonical_role_to_gemini, provider_max_output_tokens};
 
 /// Encode a canonical request into a Gemini wire request for upstream.
 ///
@@ -21,11 +21,10 @@ pub fn encode_gemini_request(
 ) -> Result<GeminiRequest, CanonicalError> {
     // --- system instruction ---
     let system_instruction = canonical
-        .system_prompt
-        .as_ref()
+        .merged_system_prompt()
         .map(|prompt| GeminiContent {
             role: None,
-            parts: vec![GeminiPart::Text(prompt.clone())],
+            parts: vec![GeminiPart::Text(prompt)],
         });
 
     // --- contents ---
@@ -40,7 +39,7 @@ pub fn encode_gemini_request(
     }
 
     for msg in &canonical.messages {
-        // System messages go into systemInstruction, not contents.
+        // System messages are merged into systemInstruction above.
         if msg.role == CanonicalRole::System {
             continue;
         }
@@ -57,6 +56,8 @@ pub fn encode_gemini_request(
                     // Map reasoning text as regular text for Gemini.
                     parts.push(GeminiPart::Text(t.clone()));
                 }
+                // Responses reasoning state is opaque to other providers.
+                CanonicalPart::ReasoningItem { .. } => {}
                 CanonicalPart::ToolCall {
                     name, arguments, ..
                 } => {
@@ -170,7 +171,7 @@ pub fn encode_gemini_request(
             Some(GeminiGenerationConfig {
                 temperature: g.temperature,
                 top_p: g.top_p,
-                max_output_tokens: g.max_tokens,
+                max_output_tokens: provider_max_output_tokens(ProviderKind::Gemini, g.max_tokens),
                 stop_sequences: g.stop.clone(),
                 candidate_count: g.n,
             })
@@ -22,10 +22,11 @@ pub struct GeminiRequest {
 }
 
 /// A content message in Gemini format.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GeminiContent {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub role: Option<String>,
+    #[serde(default)]
     pub parts: Vec<GeminiPart>,
 }
 
@@ -59,17 +60,98 @@ pub struct GeminiResponse {
     pub usage_metadata: Option<GeminiUsageMetadata>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model_version: Option<String>,
+    /// Set when the prompt itself was blocked; such responses carry no candidates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_feedback: Option<GeminiPromptFeedback>,
 }
 
 /// A candidate in the response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeminiCandidate {
+    /// Absent when the candidate was blocked before producing any output.
+    #[serde(default)]
     pub content: GeminiContent,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub finish_reason: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub index: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety_ratings: Option<Vec<GeminiSafetyRating>>,
+    /// Search results the candidate is grounded on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grounding_metadata: Option<GeminiGroundingMetadata>,
+}
+
+/// Grounding sources of a candidate and the text segments they support.
+/// Search queries and the search entry point are kept in `extra`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGroundingMetadata {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grounding_chunks: Vec<GeminiGroundingChunk>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grounding_supports: Vec<GeminiGroundingSupport>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A grounding source; web search results carry `web`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeminiGroundingChunk {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web: Option<GeminiWebSource>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeminiWebSource {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// Grounding chunks that support a segment of the candidate text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGroundingSupport {
+    pub segment: GeminiSegment,
+    #[serde(default)]
+    pub grounding_chunk_indices: Vec<usize>,
+}
+
+/// Byte range `start_index..end_index` of content part `part_index`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSegment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part_index: Option<usize>,
+    #[serde(default)]
+    pub start_index: usize,
+    #[serde(default)]
+    pub end_index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Prompt-level safety feedback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiPromptFeedback {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety_ratings: Option<Vec<GeminiSafetyRating>>,
+}
+
+/// A per-category safety rating.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSafetyRating {
+    pub category: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probability: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<bool>,
 }
 
 /// Usage metadata.
@@ -82,6 +164,11 @@ pub struct GeminiUsageMetadata {
     pub candidates_token_count: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_token_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_content_token_count: Option<u64>,
+    /// Thinking tokens, not included in `candidates_token_count`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thoughts_token_count: Option<u64>,
 }
 
 /// A tool declaration (contains function declarations).
@@ -1,12 +1,20 @@
+use std::borrow::Cow;
 use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::AtomicU64;
 
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
 use crate::error::CanonicalError;
 use crate::protocol::canonical::{
-    CanonicalPart, CanonicalResponse, CanonicalStopReason, CanonicalUsage,
+    char_index_at_byte, CanonicalCitation, CanonicalPart, CanonicalResponse, CanonicalStopReason,
+    CanonicalUsage, SAFETY_EXTENSION_KEY,
+};
+use crate::protocol::gemini::{
+    GeminiCandidate, GeminiGroundingMetadata, GeminiPart, GeminiPromptFeedback, GeminiResponse,
+    GeminiSafetyRating, GeminiUsageMetadata,
 };
-use crate::protocol::gemini::{GeminiCandidate, GeminiPart, GeminiResponse, GeminiUsageMetadata};
-use crate::protocol::mapping::gemini_stop_to_canonical;
+use crate::protocol::mapping::{gemini_stop_to_canonical, gemini_usage_counts_to_canonical};
 use crate::util::next_generated_id;
 
 static GENERATED_GEMINI_ID_SEQ: AtomicU64 = AtomicU64::new(1);
@@ -15,6 +23,105 @@ fn next_generated_gemini_id() -> String {
     next_generated_id("gemini", &GENERATED_GEMINI_ID_SEQ)
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiFastResponse<'a> {
+    #[serde(default, borrow)]
+    candidates: Option<Vec<GeminiFastCandidate<'a>>>,
+    #[serde(default)]
+    usage_metadata: Option<GeminiUsageMetadata>,
+    #[serde(default, borrow)]
+    model_version: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiFastCandidate<'a> {
+    #[serde(borrow)]
+    content: GeminiFastContent<'a>,
+    #[serde(default, borrow)]
+    finish_reason: Option<Cow<'a, str>>,
+    #[serde(default)]
+    grounding_metadata: Option<serde::de::IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiFastContent<'a> {
+    #[serde(borrow)]
+    parts: Vec<GeminiFastPart<'a>>,
+}
+
+/// A text or function-call part. Unknown keys are rejected so the fast path
+/// accepts nothing the [`GeminiPart`] enum would refuse.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct GeminiFastPart<'a> {
+    #[serde(default, borrow)]
+    text: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    function_call: Option<GeminiFastFunctionCall<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiFastFunctionCall<'a> {
+    #[serde(borrow)]
+    name: Cow<'a, str>,
+    #[serde(borrow)]
+    args: &'a RawValue,
+}
+
+/// Attempt to decode a Gemini generateContent response directly from bytes,
+/// borrowing strings and function-call arguments from `body`.
+///
+/// Arguments keep the upstream's JSON text rather than being re-serialized.
+/// Returns `None` when payload is not in the expected fast-path shape
+/// (including `functionResponse` and `inlineData` parts), when the output was
+/// safety-filtered, or when it is grounded, so the full decoder can attach
+/// the filter details or citations.
+#[must_use]
+pub fn try_decode_gemini_response_bytes(body: &[u8]) -> Option<CanonicalResponse> {
+    let parsed: GeminiFastResponse<'_> = serde_json::from_slice(body).ok()?;
+    let candidate = parsed.candidates?.into_iter().next()?;
+    if candidate.grounding_metadata.is_some()
+        || is_filtered_stop(decode_stop_reason(
+            candidate.finish_reason.as_deref(),
+            false,
+        ))
+    {
+        return None;
+    }
+
+    let mut content = Vec::with_capacity(candidate.content.parts.len());
+    let mut call_counter: usize = 0;
+    for part in candidate.content.parts {
+        match (part.text, part.function_call) {
+            (Some(text), None) => content.push(CanonicalPart::Text(text.into_owned())),
+            (None, Some(call)) => {
+                content.push(CanonicalPart::ToolCall {
+                    id: format!("call_{call_counter}"),
+                    name: call.name.into_owned(),
+                    arguments: call.args.to_owned(),
+                });
+                call_counter += 1;
+            }
+            _ => return None,
+        }
+    }
+
+    Some(CanonicalResponse {
+        id: next_generated_gemini_id(),
+        model: parsed
+            .model_version
+            .map(Cow::into_owned)
+            .unwrap_or_default(),
+        content,
+        stop_reason: decode_stop_reason(candidate.finish_reason.as_deref(), call_counter > 0),
+        usage: decode_usage_owned(parsed.usage_metadata),
+        citations: Vec::new(),
+        provider_extensions: serde_json::Map::new(),
+    })
+}
+
 /// Decode a Gemini generateContent response into the canonical IR.
 ///
 /// # Errors
@@ -25,16 +132,24 @@ pub fn decode_gemini_response(
     response: &GeminiResponse,
     model: &str,
 ) -> Result<CanonicalResponse, CanonicalError> {
-    let candidate = response
-        .candidates
-        .as_ref()
-        .and_then(|c| c.first())
-        .ok_or_else(|| CanonicalError::Translation("Gemini response has no candidates".into()))?;
+    let Some(candidate) = response.candidates.as_ref().and_then(|c| c.first()) else {
+        return decode_blocked_prompt(
+            response.prompt_feedback.as_ref(),
+            decode_usage_ref(response.usage_metadata.as_ref()),
+            model.to_string(),
+        );
+    };
 
     let (parts, has_function_call) = decode_candidate_parts_ref(candidate)?;
+    let citations = decode_grounding(candidate);
 
     // --- stop reason ---
     let stop_reason = decode_stop_reason(candidate.finish_reason.as_deref(), has_function_call);
+    let provider_extensions = candidate_safety_extensions(
+        stop_reason,
+        candidate.finish_reason.as_deref(),
+        candidate.safety_ratings.as_deref(),
+    );
 
     // --- usage ---
     let usage = decode_usage_ref(response.usage_metadata.as_ref());
@@ -48,7 +163,8 @@ pub fn decode_gemini_response(
         content: parts,
         stop_reason,
         usage,
-        provider_extensions: serde_json::Map::new(),
+        citations,
+        provider_extensions,
     })
 }
 
@@ -65,14 +181,22 @@ pub fn decode_gemini_response_owned(
         candidates,
         usage_metadata,
         model_version,
+        prompt_feedback,
     } = response;
-    let mut candidates = candidates
-        .ok_or_else(|| CanonicalError::Translation("Gemini response has no candidates".into()))?;
-    let candidate = candidates
-        .drain(..)
-        .next()
-        .ok_or_else(|| CanonicalError::Translation("Gemini response has no candidates".into()))?;
+    let Some(candidate) = candidates.and_then(|c| c.into_iter().next()) else {
+        return decode_blocked_prompt(
+            prompt_feedback.as_ref(),
+            decode_usage_owned(usage_metadata),
+            model_version.unwrap_or_default(),
+        );
+    };
     let stop_reason = decode_stop_reason(candidate.finish_reason.as_deref(), false);
+    let provider_extensions = candidate_safety_extensions(
+        stop_reason,
+        candidate.finish_reason.as_deref(),
+        candidate.safety_ratings.as_deref(),
+    );
+    let citations = decode_grounding(&candidate);
     let (content, has_function_call) = decode_candidate_parts_owned(candidate)?;
     let stop_reason = if stop_reason == CanonicalStopReason::EndOfTurn && has_function_call {
         CanonicalStopReason::ToolCalls
@@ -87,28 +211,107 @@ pub fn decode_gemini_response_owned(
         content,
         stop_reason,
         usage,
-        provider_extensions: serde_json::Map::new(),
+        citations,
+        provider_extensions,
+    })
+}
+
+/// A response without candidates is only meaningful when the prompt itself
+/// was blocked; surface that as a content-filter finish instead of an error.
+fn decode_blocked_prompt(
+    prompt_feedback: Option<&GeminiPromptFeedback>,
+    usage: CanonicalUsage,
+    model: String,
+) -> Result<CanonicalResponse, CanonicalError> {
+    let Some((feedback, block_reason)) =
+        prompt_feedback.and_then(|feedback| Some((feedback, feedback.block_reason.as_deref()?)))
+    else {
+        return Err(CanonicalError::Translation(
+            "Gemini response has no candidates".into(),
+        ));
+    };
+    let mut provider_extensions = serde_json::Map::new();
+    provider_extensions.insert(
+        SAFETY_EXTENSION_KEY.into(),
+        safety_details("prompt", block_reason, feedback.safety_ratings.as_deref()),
+    );
+    Ok(CanonicalResponse {
+        id: next_generated_gemini_id(),
+        model,
+        content: Vec::new(),
+        stop_reason: CanonicalStopReason::ContentFilter,
+        usage,
+        citations: Vec::new(),
+        provider_extensions,
+    })
+}
+
+fn is_filtered_stop(stop_reason: CanonicalStopReason) -> bool {
+    matches!(
+        stop_reason,
+        CanonicalStopReason::ContentFilter | CanonicalStopReason::Recitation
+    )
+}
+
+fn candidate_safety_extensions(
+    stop_reason: CanonicalStopReason,
+    finish_reason: Option<&str>,
+    safety_ratings: Option<&[GeminiSafetyRating]>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut provider_extensions = serde_json::Map::new();
+    if let (true, Some(finish_reason)) = (is_filtered_stop(stop_reason), finish_reason) {
+        provider_extensions.insert(
+            SAFETY_EXTENSION_KEY.into(),
+            safety_details("candidate", finish_reason, safety_ratings),
+        );
+    }
+    provider_extensions
+}
+
+/// `{"source", "reason", "categories"}`, where `categories` lists the ratings
+/// Gemini flagged as blocking, or every rating when none is flagged.
+fn safety_details(
+    source: &str,
+    reason: &str,
+    safety_ratings: Option<&[GeminiSafetyRating]>,
+) -> serde_json::Value {
+    let ratings = safety_ratings.unwrap_or_default();
+    let blocked: Vec<&GeminiSafetyRating> = ratings
+        .iter()
+        .filter(|rating| rating.blocked == Some(true))
+        .collect();
+    let categories = if blocked.is_empty() {
+        serde_json::to_value(ratings)
+    } else {
+        serde_json::to_value(blocked)
+    }
+    .unwrap_or_default();
+    serde_json::json!({
+        "source": source,
+        "reason": reason,
+        "categories": categories,
     })
 }
 
+/// Decode Gemini usage metadata into canonical usage, counting thinking
+/// tokens as output.
+#[must_use]
+pub fn decode_gemini_usage(usage: &GeminiUsageMetadata) -> CanonicalUsage {
+    gemini_usage_counts_to_canonical(
+        usage.prompt_token_count,
+        usage.candidates_token_count,
+        usage.total_token_count,
+        usage.cached_content_token_count,
+        usage.thoughts_token_count,
+    )
+}
+
 fn decode_usage_ref(usage_metadata: Option<&GeminiUsageMetadata>) -> CanonicalUsage {
-    usage_metadata
-        .map(|usage| CanonicalUsage {
-            input_tokens: usage.prompt_token_count,
-            output_tokens: usage.candidates_token_count,
-            total_tokens: usage.total_token_count,
-        })
-        .unwrap_or_default()
+    usage_metadata.map(decode_gemini_usage).unwrap_or_default()
 }
 
 fn decode_usage_owned(usage_metadata: Option<GeminiUsageMetadata>) -> CanonicalUsage {
-    usage_metadata
-        .map(|usage| CanonicalUsage {
-            input_tokens: usage.prompt_token_count,
-            output_tokens: usage.candidates_token_count,
-            total_tokens: usage.total_token_count,
-        })
-        .unwrap_or_default()
+    decode_usage_ref(usage_metadata.as_ref())
 }
 
 fn decode_stop_reason(finish_reason: Option<&str>, has_function_call: bool) -> CanonicalStopReason {
@@ -129,6 +332,65 @@ fn decode_stop_reason(finish_reason: Option<&str>, has_function_call: bool) -> C
     )
 }
 
+/// The web sources of a grounded candidate, one citation per supported
+/// segment and source, plus one without a span for each unused source.
+fn decode_grounding(candidate: &GeminiCandidate) -> Vec<CanonicalCitation> {
+    let Some(GeminiGroundingMetadata {
+        grounding_chunks,
+        grounding_supports,
+        ..
+    }) = &candidate.grounding_metadata
+    else {
+        return Vec::new();
+    };
+    // Where each text part starts in the response text.
+    let mut offset = 0;
+    let part_starts: Vec<Option<(usize, &str)>> = candidate
+        .content
+        .parts
+        .iter()
+        .map(|part| match part {
+            GeminiPart::Text(text) => {
+                let start = offset;
+                offset += text.chars().count();
+                Some((start, text.as_str()))
+            }
+            _ => None,
+        })
+        .collect();
+    let citation = |chunk_index: usize, span| {
+        let web = grounding_chunks.get(chunk_index)?.web.as_ref()?;
+        Some(CanonicalCitation {
+            url: web.uri.clone(),
+            title: web.title.clone(),
+            cited_text: None,
+            span,
+        })
+    };
+    let mut cited = vec![false; grounding_chunks.len()];
+    let mut citations = Vec::new();
+    for support in grounding_supports {
+        let segment = &support.segment;
+        let Some(Some((start, text))) = part_starts.get(segment.part_index.unwrap_or(0)) else {
+            continue;
+        };
+        let span = start + char_index_at_byte(text, segment.start_index)
+            ..start + char_index_at_byte(text, segment.end_index);
+        for &chunk_index in &support.grounding_chunk_indices {
+            if let Some(citation) = citation(chunk_index, Some(span.clone())) {
+                cited[chunk_index] = true;
+                citations.push(citation);
+            }
+        }
+    }
+    for (chunk_index, cited) in cited.into_iter().enumerate() {
+        if !cited {
+            citations.extend(citation(chunk_index, None));
+        }
+    }
+    citations
+}
+
 fn decode_candidate_parts_ref(
     candidate: &GeminiCandidate,
 ) -> Result<(Vec<CanonicalPart>, bool), CanonicalError> {
@@ -252,13 +514,18 @@ mod tests {
                 },
                 finish_reason: Some("STOP".into()),
                 index: Some(0),
+                safety_ratings: None,
+                grounding_metadata: None,
             }]),
             usage_metadata: Some(GeminiUsageMetadata {
                 prompt_token_count: Some(10),
                 candidates_token_count: Some(5),
                 total_token_count: Some(15),
+                cached_content_token_count: None,
+                thoughts_token_count: None,
             }),
             model_version: None,
+            prompt_feedback: None,
         };
 
         let canonical = decode_gemini_response(&resp, "gemini-pro").unwrap();
@@ -286,9 +553,12 @@ mod tests {
                 },
                 finish_reason: Some("STOP".into()),
                 index: Some(0),
+                safety_ratings: None,
+                grounding_metadata: None,
             }]),
             usage_metadata: None,
             model_version: None,
+            prompt_feedback: None,
         };
 
         let canonical = decode_gemini_response(&resp, "gemini-pro").unwrap();
@@ -309,6 +579,7 @@ mod tests {
             candidates: None,
             usage_metadata: None,
             model_version: None,
+            prompt_feedback: None,
         };
 
         let result = decode_gemini_response(&resp, "gemini-pro");
@@ -338,9 +609,12 @@ mod tests {
                 },
                 finish_reason: Some("STOP".into()),
                 index: Some(0),
+                safety_ratings: None,
+                grounding_metadata: None,
             }]),
             usage_metadata: None,
             model_version: None,
+            prompt_feedback: None,
         };
 
         let canonical = decode_gemini_response(&resp, "gemini-pro").unwrap();
@@ -383,13 +657,18 @@ mod tests {
                 },
                 finish_reason: Some("STOP".into()),
                 index: Some(0),
+                safety_ratings: None,
+                grounding_metadata: None,
             }]),
             usage_metadata: Some(GeminiUsageMetadata {
                 prompt_token_count: Some(10),
                 candidates_token_count: Some(5),
                 total_token_count: Some(15),
+                cached_content_token_count: None,
+                thoughts_token_count: None,
             }),
             model_version: Some("gemini-pro".into()),
+            prompt_feedback: None,
         };
 
         let borrowed = decode_gemini_response(&response, "gemini-pro").unwrap();
@@ -403,4 +682,64 @@ mod tests {
         assert_eq!(borrowed.stop_reason, owned.stop_reason);
         assert_eq!(borrowed.usage.total_tokens, owned.usage.total_tokens);
     }
+
+    #[test]
+    fn test_try_decode_response_bytes_matches_owned_decode() {
+        let body = br#"{"candidates":[{"content":{"role":"model","parts":[
+                {"text":"line\nbreak"},
+                {"functionCall":{"name":"lookup","args":{"q":"rust"}}}]},
+            "finishReason":"STOP","index":0,"safetyRatings":[]}],
+            "usageMetadata":{"promptTokenCount":3,"candidatesTokenCount":4,"totalTokenCount":7},
+            "modelVersion":"gemini-pro"}"#;
+        let fast = try_decode_gemini_response_bytes(body).unwrap();
+        let wire: GeminiResponse = serde_json::from_slice(body).unwrap();
+        let owned = decode_gemini_response_owned(wire).unwrap();
+        assert_eq!(fast.model, owned.model);
+        assert_eq!(
+            format!("{:?}", fast.content),
+            format!("{:?}", owned.content)
+        );
+        assert_eq!(fast.stop_reason, CanonicalStopReason::ToolCalls);
+        assert_eq!(fast.stop_reason, owned.stop_reason);
+        assert_eq!(fast.usage.total_tokens, Some(7));
+
+        let unknown_part = br#"{"candidates":[{"content":{"parts":[
+            {"text":"x","thought":true}]}}]}"#;
+        assert!(try_decode_gemini_response_bytes(unknown_part).is_none());
+    }
+
+    #[test]
+    fn test_safety_filtered_candidate_carries_ratings() {
+        let body = br#"{"candidates":[{"content":{"role":"model","parts":[{"text":"par"}]},
+            "finishReason":"SAFETY","safetyRatings":[
+            {"category":"HARM_CATEGORY_HARASSMENT","probability":"LOW"},
+            {"category":"HARM_CATEGORY_DANGEROUS_CONTENT","probability":"HIGH","blocked":true}]}]}"#;
+        assert!(try_decode_gemini_response_bytes(body).is_none());
+
+        let wire: GeminiResponse = serde_json::from_slice(body).unwrap();
+        let borrowed = decode_gemini_response(&wire, "gemini-pro").unwrap();
+        let owned = decode_gemini_response_owned(wire).unwrap();
+        for decoded in [borrowed, owned] {
+            assert_eq!(decoded.stop_reason, CanonicalStopReason::ContentFilter);
+            assert_eq!(
+                decoded.provider_extensions[SAFETY_EXTENSION_KEY],
+                serde_json::json!({
+                    "source": "candidate",
+                    "reason": "SAFETY",
+                    "categories": [{
+                        "category": "HARM_CATEGORY_DANGEROUS_CONTENT",
+                        "probability": "HIGH",
+                        "blocked": true
+                    }]
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_candidates_without_block_reason_is_an_error() {
+        let wire: GeminiResponse = serde_json::from_str(r#"{"promptFeedback":{}}"#).unwrap();
+        assert!(decode_gemini_response(&wire, "gemini-pro").is_err());
+        assert!(decode_gemini_response_owned(wire).is_err());
+    }
 }
@@ -1,7 +1,11 @@
 use crate::error::CanonicalError;
-use crate::protocol::canonical::{CanonicalPart, CanonicalResponse};
+use crate::protocol::canonical::{
+    byte_index_at_char, CanonicalPart, CanonicalResponse, REASONING_SUMMARY_SEPARATOR,
+};
 use crate::protocol::gemini::{
-    GeminiCandidate, GeminiContent, GeminiPart, GeminiResponse, GeminiUsageMetadata,
+    GeminiCandidate, GeminiContent, GeminiGroundingChunk, GeminiGroundingMetadata,
+    GeminiGroundingSupport, GeminiPart, GeminiResponse, GeminiSegment, GeminiUsageMetadata,
+    GeminiWebSource,
 };
 use crate::protocol::mapping::canonical_stop_to_gemini;
 
@@ -16,11 +20,22 @@ pub fn encode_gemini_response(
 ) -> Result<GeminiResponse, CanonicalError> {
     // --- content parts ---
     let mut parts = Vec::with_capacity(canonical.content.len());
+    // Part index and starting character of each canonical text part.
+    let mut text_parts = Vec::new();
+    let mut text_offset = 0;
     for part in &canonical.content {
         match part {
-            CanonicalPart::Text(t) | CanonicalPart::ReasoningText(t) => {
+            CanonicalPart::Text(t) => {
+                text_parts.push((parts.len(), text_offset, t.as_str()));
+                text_offset += t.chars().count();
+                parts.push(GeminiPart::Text(t.clone()));
+            }
+            CanonicalPart::ReasoningText(t) => {
                 parts.push(GeminiPart::Text(t.clone()));
             }
+            CanonicalPart::ReasoningItem { summary, .. } => {
+                parts.push(GeminiPart::Text(summary.join(REASONING_SUMMARY_SEPARATOR)));
+            }
             CanonicalPart::ToolCall {
                 name, arguments, ..
             } => {
@@ -50,6 +65,8 @@ pub fn encode_gemini_response(
         },
         finish_reason,
         index: Some(0),
+        safety_ratings: None,
+        grounding_metadata: encode_grounding(canonical, &text_parts),
     };
 
     // --- usage ---
@@ -60,8 +77,10 @@ pub fn encode_gemini_response(
         if has_any {
             Some(GeminiUsageMetadata {
                 prompt_token_count: u.input_tokens,
-                candidates_token_count: u.output_tokens,
+                candidates_token_count: u.output_tokens.map(|_| u.visible_output_tokens()),
                 total_token_count: u.total_tokens,
+                cached_content_token_count: u.cached_input_tokens,
+                thoughts_token_count: u.reasoning_tokens,
             })
         } else {
             None
@@ -72,9 +91,65 @@ pub fn encode_gemini_response(
         candidates: Some(vec![candidate]),
         usage_metadata,
         model_version: None,
+        prompt_feedback: None,
     })
 }
 
+/// Citations as grounding chunks, one per distinct source, and supports
+/// for the segments they cite in the text parts `(part index, starting
+/// character, text)`.
+fn encode_grounding(
+    canonical: &CanonicalResponse,
+    text_parts: &[(usize, usize, &str)],
+) -> Option<GeminiGroundingMetadata> {
+    if canonical.citations.is_empty() {
+        return None;
+    }
+    let mut metadata = GeminiGroundingMetadata::default();
+    for citation in &canonical.citations {
+        let chunk_index = metadata
+            .grounding_chunks
+            .iter()
+            .position(|chunk| {
+                chunk
+                    .web
+                    .as_ref()
+                    .is_some_and(|web| web.uri == citation.url && web.title == citation.title)
+            })
+            .unwrap_or_else(|| {
+                metadata.grounding_chunks.push(GeminiGroundingChunk {
+                    web: Some(GeminiWebSource {
+                        uri: citation.url.clone(),
+                        title: citation.title.clone(),
+                    }),
+                });
+                metadata.grounding_chunks.len() - 1
+            });
+        let Some(span) = &citation.span else {
+            continue;
+        };
+        let Some(&(part_index, start, text)) = text_parts
+            .iter()
+            .rev()
+            .find(|(_, start, _)| *start <= span.start)
+        else {
+            continue;
+        };
+        let start_index = byte_index_at_char(text, span.start - start);
+        let end_index = byte_index_at_char(text, span.end.saturating_sub(start)).max(start_index);
+        metadata.grounding_supports.push(GeminiGroundingSupport {
+            segment: GeminiSegment {
+                part_index: Some(part_index),
+                start_index,
+                end_index,
+                text: Some(text[start_index..end_index].to_string()),
+            },
+            grounding_chunk_indices: vec![chunk_index],
+        });
+    }
+    Some(metadata)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,7 +166,9 @@ mod tests {
                 input_tokens: Some(10),
                 output_tokens: Some(5),
                 total_tokens: Some(15),
+                ..CanonicalUsage::default()
             },
+            citations: Vec::new(),
             provider_extensions: serde_json::Map::new(),
         };
 
@@ -121,6 +198,7 @@ mod tests {
             }],
             stop_reason: CanonicalStopReason::ToolCalls,
             usage: CanonicalUsage::default(),
+            citations: Vec::new(),
             provider_extensions: serde_json::Map::new(),
         };
 
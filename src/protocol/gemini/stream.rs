@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use rustc_hash::FxHashMap;
 
 use crate::protocol::canonical::{CanonicalStopReason, CanonicalStreamEvent, CanonicalUsage};
+use crate::protocol::gemini::response_decoder::decode_gemini_usage;
 use crate::protocol::gemini::{GeminiPart, GeminiResponse};
 use crate::protocol::mapping::{canonical_stop_to_gemini, gemini_stop_to_canonical};
 use crate::util::{
@@ -31,6 +32,7 @@ pub fn decode_gemini_stream_chunk_into(
     chunk: &GeminiResponse,
     out: &mut Vec<CanonicalStreamEvent>,
 ) {
+    push_prompt_block_end(chunk, out);
     if let Some(candidates) = &chunk.candidates {
         if let Some(candidate) = candidates.first() {
             let mut has_tool_calls = false;
@@ -83,11 +85,7 @@ pub fn decode_gemini_stream_chunk_into(
 
     // Handle usage metadata.
     if let Some(um) = &chunk.usage_metadata {
-        out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-            input_tokens: um.prompt_token_count,
-            output_tokens: um.candidates_token_count,
-            total_tokens: um.total_token_count,
-        }));
+        out.push(CanonicalStreamEvent::Usage(decode_gemini_usage(um)));
     }
 }
 
@@ -100,6 +98,7 @@ pub fn decode_gemini_stream_chunk_owned_into(
     chunk: GeminiResponse,
     out: &mut Vec<CanonicalStreamEvent>,
 ) {
+    push_prompt_block_end(&chunk, out);
     if let Some(candidates) = chunk.candidates {
         if let Some(candidate) = candidates.into_iter().next() {
             let mut has_tool_calls = false;
@@ -143,11 +142,21 @@ pub fn decode_gemini_stream_chunk_owned_into(
     }
 
     if let Some(usage) = chunk.usage_metadata {
-        out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-            input_tokens: usage.prompt_token_count,
-            output_tokens: usage.candidates_token_count,
-            total_tokens: usage.total_token_count,
-        }));
+        out.push(CanonicalStreamEvent::Usage(decode_gemini_usage(&usage)));
+    }
+}
+
+/// A blocked prompt arrives as a single chunk with `promptFeedback` and no
+/// candidates; end the message as content-filtered rather than empty.
+fn push_prompt_block_end(chunk: &GeminiResponse, out: &mut Vec<CanonicalStreamEvent>) {
+    let blocked = chunk
+        .prompt_feedback
+        .as_ref()
+        .is_some_and(|feedback| feedback.block_reason.is_some());
+    if blocked && chunk.candidates.as_ref().is_none_or(Vec::is_empty) {
+        out.push(CanonicalStreamEvent::MessageEnd {
+            stop_reason: CanonicalStopReason::ContentFilter,
+        });
     }
 }
 
@@ -217,10 +226,15 @@ where
 
 fn encode_gemini_text_delta_sse(text: &str) -> String {
     let mut out = String::with_capacity(64 + text.len());
+    push_gemini_text_delta_sse(&mut out, text);
+    out
+}
+
+/// Append a Gemini SSE chunk carrying `text` to `out`.
+pub fn push_gemini_text_delta_sse(out: &mut String, text: &str) {
     out.push_str("data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":");
-    push_json_string_escaped(&mut out, text);
+    push_json_string_escaped(out, text);
     out.push_str("}]},\"index\":0}]}\n\n");
-    out
 }
 
 fn encode_gemini_message_end_sse(stop_reason: &str) -> String {
@@ -252,12 +266,12 @@ fn encode_gemini_usage_sse(usage: &CanonicalUsage) -> String {
         push_u64_decimal(&mut out, prompt);
         wrote_any = true;
     }
-    if let Some(candidates) = usage.output_tokens {
+    if usage.output_tokens.is_some() {
         if wrote_any {
             out.push(',');
         }
         out.push_str("\"candidatesTokenCount\":");
-        push_u64_decimal(&mut out, candidates);
+        push_u64_decimal(&mut out, usage.visible_output_tokens());
         wrote_any = true;
     }
     if let Some(total) = usage.total_tokens {
@@ -266,6 +280,22 @@ fn encode_gemini_usage_sse(usage: &CanonicalUsage) -> String {
         }
         out.push_str("\"totalTokenCount\":");
         push_u64_decimal(&mut out, total);
+        wrote_any = true;
+    }
+    if let Some(cached) = usage.cached_input_tokens {
+        if wrote_any {
+            out.push(',');
+        }
+        out.push_str("\"cachedContentTokenCount\":");
+        push_u64_decimal(&mut out, cached);
+        wrote_any = true;
+    }
+    if let Some(thoughts) = usage.reasoning_tokens {
+        if wrote_any {
+            out.push(',');
+        }
+        out.push_str("\"thoughtsTokenCount\":");
+        push_u64_decimal(&mut out, thoughts);
     }
     out.push_str("}}\n\n");
     out
@@ -332,9 +362,12 @@ mod tests {
                 },
                 finish_reason: None,
                 index: Some(0),
+                safety_ratings: None,
+                grounding_metadata: None,
             }]),
             usage_metadata: None,
             model_version: None,
+            prompt_feedback: None,
         };
 
         let events = decode_gemini_stream_chunk(&chunk);
@@ -345,6 +378,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_stream_chunk_blocked_prompt_ends_filtered() {
+        let line = r#"data: {"promptFeedback":{"blockReason":"SAFETY"}}"#;
+        let chunk = parse_gemini_sse_line(line).unwrap();
+        let mut owned_events = Vec::new();
+        decode_gemini_stream_chunk_owned_into(chunk.clone(), &mut owned_events);
+        for events in [decode_gemini_stream_chunk(&chunk), owned_events] {
+            assert!(matches!(
+                events.as_slice(),
+                [CanonicalStreamEvent::MessageEnd {
+                    stop_reason: CanonicalStopReason::ContentFilter
+                }]
+            ));
+        }
+    }
+
     #[test]
     fn test_decode_stream_chunk_finish() {
         let chunk = GeminiResponse {
@@ -355,13 +404,18 @@ mod tests {
                 },
                 finish_reason: Some("STOP".into()),
                 index: Some(0),
+                safety_ratings: None,
+                grounding_metadata: None,
             }]),
             usage_metadata: Some(GeminiUsageMetadata {
                 prompt_token_count: Some(10),
                 candidates_token_count: Some(20),
                 total_token_count: Some(30),
+                cached_content_token_count: None,
+                thoughts_token_count: None,
             }),
             model_version: None,
+            prompt_feedback: None,
         };
 
         let events = decode_gemini_stream_chunk(&chunk);
@@ -0,0 +1,404 @@
+//! `toolify.v1` gRPC messages (`proto/toolify/v1/completion.proto`) and their
+//! mapping to and from the canonical model.
+//!
+//! The messages, the `Completion` server trait, and the client stub are
+//! generated by `build.rs`; the service itself lives in the gRPC ingress.
+
+use std::sync::Arc;
+
+use serde_json::Value;
+use smallvec::SmallVec;
+
+use crate::error::CanonicalError;
+use crate::protocol::canonical::{
+    CanonicalCitation, CanonicalMessage, CanonicalPart, CanonicalRequest, CanonicalResponse,
+    CanonicalRole, CanonicalStopReason, CanonicalStreamEvent, CanonicalToolChoice,
+    CanonicalToolFunction, CanonicalToolSpec, CanonicalUsage, IngressApi,
+};
+use crate::util::raw_value_from_string;
+
+#[allow(clippy::all, clippy::pedantic)]
+mod generated {
+    tonic::include_proto!("toolify.v1");
+}
+
+pub use generated::*;
+
+/// Decode a `CompleteRequest` into the canonical IR.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::InvalidRequest`] for unset roles, parts, or enum
+/// values and for tool call arguments or tool schemas that are not JSON.
+pub fn decode_grpc_request(
+    request: CompleteRequest,
+    stream: bool,
+    request_id: uuid::Uuid,
+) -> Result<CanonicalRequest, CanonicalError> {
+    let messages = request
+        .messages
+        .into_iter()
+        .enumerate()
+        .map(|(index, message)| decode_message(message, index))
+        .collect::<Result<Vec<_>, _>>()?;
+    let tools = request
+        .tools
+        .into_iter()
+        .map(decode_tool)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(CanonicalRequest {
+        request_id,
+        ingress_api: IngressApi::OpenAiChat,
+        model: request.model,
+        stream,
+        system_prompt: request.system_prompt,
+        messages,
+        tools: Arc::from(tools),
+        tool_choice: decode_tool_choice(request.tool_choice)?,
+        generation: request
+            .generation
+            .map(decode_generation)
+            .unwrap_or_default(),
+        provider_extensions: None,
+    })
+}
+
+fn decode_message(message: Message, index: usize) -> Result<CanonicalMessage, CanonicalError> {
+    let role = match Role::try_from(message.role) {
+        Ok(Role::System) => CanonicalRole::System,
+        Ok(Role::User) => CanonicalRole::User,
+        Ok(Role::Assistant) => CanonicalRole::Assistant,
+        Ok(Role::Tool) => CanonicalRole::Tool,
+        Ok(Role::Unspecified) | Err(_) => {
+            return Err(CanonicalError::InvalidRequest(format!(
+                "messages[{index}].role must be set"
+            )))
+        }
+    };
+    let parts = message
+        .parts
+        .into_iter()
+        .map(|part| decode_part(part, index))
+        .collect::<Result<SmallVec<_>, _>>()?;
+    Ok(CanonicalMessage {
+        role,
+        parts,
+        name: message.name,
+        tool_call_id: message.tool_call_id,
+        provider_extensions: None,
+    })
+}
+
+fn decode_part(part: Part, index: usize) -> Result<CanonicalPart, CanonicalError> {
+    let Some(kind) = part.kind else {
+        return Err(CanonicalError::InvalidRequest(format!(
+            "messages[{index}] has a part with no content"
+        )));
+    };
+    Ok(match kind {
+        part::Kind::Text(text) => CanonicalPart::Text(text),
+        part::Kind::ReasoningText(text) => CanonicalPart::ReasoningText(text),
+        part::Kind::ImageUrl(image) => CanonicalPart::ImageUrl {
+            url: image.url,
+            detail: image.detail,
+        },
+        part::Kind::ToolCall(call) => {
+            let arguments = if call.arguments_json.is_empty() {
+                "{}".to_string()
+            } else {
+                call.arguments_json
+            };
+            let arguments = raw_value_from_string(arguments, "gRPC tool call")
+                .map_err(|e| CanonicalError::InvalidRequest(e.to_string()))?;
+            CanonicalPart::ToolCall {
+                id: call.id,
+                name: call.name,
+                arguments,
+            }
+        }
+        part::Kind::ToolResult(result) => CanonicalPart::ToolResult {
+            tool_call_id: result.tool_call_id,
+            content: result.content,
+        },
+        part::Kind::Refusal(text) => CanonicalPart::Refusal(text),
+    })
+}
+
+fn decode_tool(tool: Tool) -> Result<CanonicalToolSpec, CanonicalError> {
+    let parameters = if tool.parameters_json.is_empty() {
+        Value::Object(serde_json::Map::new())
+    } else {
+        serde_json::from_str(&tool.parameters_json).map_err(|e| {
+            CanonicalError::InvalidRequest(format!(
+                "tools.{}.parameters_json is not JSON: {e}",
+                tool.name
+            ))
+        })?
+    };
+    Ok(CanonicalToolSpec {
+        function: CanonicalToolFunction {
+            name: tool.name,
+            description: tool.description,
+            parameters,
+        },
+    })
+}
+
+fn decode_tool_choice(choice: Option<ToolChoice>) -> Result<CanonicalToolChoice, CanonicalError> {
+    let Some(choice) = choice.and_then(|choice| choice.choice) else {
+        return Ok(CanonicalToolChoice::Auto);
+    };
+    match choice {
+        tool_choice::Choice::Function(name) => Ok(CanonicalToolChoice::Specific(name)),
+        tool_choice::Choice::Mode(mode) => match tool_choice::Mode::try_from(mode) {
+            Ok(tool_choice::Mode::Auto) => Ok(CanonicalToolChoice::Auto),
+            Ok(tool_choice::Mode::None) => Ok(CanonicalToolChoice::None),
+            Ok(tool_choice::Mode::Required) => Ok(CanonicalToolChoice::Required),
+            Err(_) => Err(CanonicalError::InvalidRequest(format!(
+                "unknown tool_choice mode {mode}"
+            ))),
+        },
+    }
+}
+
+fn decode_generation(generation: GenerationParams) -> crate::protocol::canonical::GenerationParams {
+    crate::protocol::canonical::GenerationParams {
+        temperature: generation.temperature,
+        max_tokens: generation.max_tokens,
+        top_p: generation.top_p,
+        frequency_penalty: generation.frequency_penalty,
+        presence_penalty: generation.presence_penalty,
+        n: generation.n,
+        stop: (!generation.stop.is_empty()).then_some(generation.stop),
+    }
+}
+
+/// Encode a canonical response as a `CompleteResponse`.
+#[must_use]
+pub fn encode_grpc_response(response: CanonicalResponse) -> CompleteResponse {
+    CompleteResponse {
+        id: response.id,
+        model: response.model,
+        content: response
+            .content
+            .into_iter()
+            .filter_map(encode_part)
+            .collect(),
+        stop_reason: encode_stop_reason(response.stop_reason).into(),
+        usage: Some(encode_usage(&response.usage)),
+        citations: response
+            .citations
+            .into_iter()
+            .map(encode_citation)
+            .collect(),
+    }
+}
+
+/// Encode a canonical stream event as a `StreamEvent`.
+///
+/// Returns `None` for `Done` and `Error`, which end the call instead: `Done`
+/// with status OK and `Error` with a failing status.
+#[must_use]
+pub fn encode_grpc_stream_event(event: CanonicalStreamEvent) -> Option<StreamEvent> {
+    let event = match event {
+        CanonicalStreamEvent::MessageStart { role } => {
+            stream_event::Event::MessageStart(MessageStart {
+                role: encode_role(role).into(),
+            })
+        }
+        CanonicalStreamEvent::TextDelta(text) => stream_event::Event::TextDelta(text),
+        CanonicalStreamEvent::ReasoningDelta(text) => stream_event::Event::ReasoningDelta(text),
+        CanonicalStreamEvent::ToolCallStart { index, id, name } => {
+            stream_event::Event::ToolCallStart(ToolCallStart {
+                index: encode_index(index),
+                id,
+                name,
+            })
+        }
+        CanonicalStreamEvent::ToolCallArgsDelta { index, delta } => {
+            stream_event::Event::ToolCallArgsDelta(ToolCallArgsDelta {
+                index: encode_index(index),
+                delta,
+            })
+        }
+        CanonicalStreamEvent::ToolCallEnd {
+            index,
+            call_id,
+            call_name,
+        } => stream_event::Event::ToolCallEnd(ToolCallEnd {
+            index: encode_index(index),
+            call_id,
+            call_name,
+        }),
+        CanonicalStreamEvent::ToolResult {
+            tool_call_id,
+            content,
+        } => stream_event::Event::ToolResult(ToolResult {
+            tool_call_id,
+            content,
+        }),
+        CanonicalStreamEvent::Usage(usage) => stream_event::Event::Usage(encode_usage(&usage)),
+        CanonicalStreamEvent::MessageEnd { stop_reason } => {
+            stream_event::Event::MessageEnd(MessageEnd {
+                stop_reason: encode_stop_reason(stop_reason).into(),
+            })
+        }
+        CanonicalStreamEvent::Done | CanonicalStreamEvent::Error { .. } => return None,
+    };
+    Some(StreamEvent { event: Some(event) })
+}
+
+fn encode_part(part: CanonicalPart) -> Option<Part> {
+    let kind = match part {
+        CanonicalPart::Text(text) => part::Kind::Text(text),
+        CanonicalPart::ReasoningText(text) => part::Kind::ReasoningText(text),
+        // Responses reasoning items only round-trip to a Responses upstream.
+        CanonicalPart::ReasoningItem { .. } => return None,
+        CanonicalPart::ImageUrl { url, detail } => part::Kind::ImageUrl(ImageUrl { url, detail }),
+        CanonicalPart::ToolCall {
+            id,
+            name,
+            arguments,
+        } => part::Kind::ToolCall(ToolCall {
+            id,
+            name,
+            arguments_json: arguments.get().to_string(),
+        }),
+        CanonicalPart::ToolResult {
+            tool_call_id,
+            content,
+        } => part::Kind::ToolResult(ToolResult {
+            tool_call_id,
+            content,
+        }),
+        CanonicalPart::Refusal(text) => part::Kind::Refusal(text),
+    };
+    Some(Part { kind: Some(kind) })
+}
+
+fn encode_role(role: CanonicalRole) -> Role {
+    match role {
+        CanonicalRole::System => Role::System,
+        CanonicalRole::User => Role::User,
+        CanonicalRole::Assistant => Role::Assistant,
+        CanonicalRole::Tool => Role::Tool,
+    }
+}
+
+fn encode_stop_reason(reason: CanonicalStopReason) -> StopReason {
+    match reason {
+        CanonicalStopReason::EndOfTurn => StopReason::EndOfTurn,
+        CanonicalStopReason::StopSequence => StopReason::StopSequence,
+        CanonicalStopReason::ToolCalls => StopReason::ToolCalls,
+        CanonicalStopReason::MaxTokens => StopReason::MaxTokens,
+        CanonicalStopReason::ContentFilter => StopReason::ContentFilter,
+        CanonicalStopReason::Recitation => StopReason::Recitation,
+        CanonicalStopReason::Refusal => StopReason::Refusal,
+        CanonicalStopReason::PauseTurn => StopReason::PauseTurn,
+    }
+}
+
+fn encode_usage(usage: &CanonicalUsage) -> Usage {
+    Usage {
+        input_tokens: usage.input_tokens,
+        output_tokens: usage.output_tokens,
+        total_tokens: usage.total_tokens,
+        cached_input_tokens: usage.cached_input_tokens,
+        cache_creation_input_tokens: usage.cache_creation_input_tokens,
+        reasoning_tokens: usage.reasoning_tokens,
+    }
+}
+
+fn encode_citation(citation: CanonicalCitation) -> Citation {
+    Citation {
+        url: citation.url,
+        title: citation.title,
+        cited_text: citation.cited_text,
+        span_start: citation.span.as_ref().map(|span| span.start as u64),
+        span_end: citation.span.map(|span| span.end as u64),
+    }
+}
+
+fn encode_index(index: usize) -> u32 {
+    u32::try_from(index).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_grpc_request_maps_parts_and_choice() {
+        let request = CompleteRequest {
+            model: "m".to_string(),
+            system_prompt: Some("be brief".to_string()),
+            messages: vec![Message {
+                role: Role::Assistant.into(),
+                parts: vec![Part {
+                    kind: Some(part::Kind::ToolCall(ToolCall {
+                        id: "call_1".to_string(),
+                        name: "lookup".to_string(),
+                        arguments_json: r#"{"q":"x"}"#.to_string(),
+                    })),
+                }],
+                name: None,
+                tool_call_id: None,
+            }],
+            tools: vec![Tool {
+                name: "lookup".to_string(),
+                description: None,
+                parameters_json: String::new(),
+            }],
+            tool_choice: Some(ToolChoice {
+                choice: Some(tool_choice::Choice::Function("lookup".to_string())),
+            }),
+            generation: Some(GenerationParams {
+                max_tokens: Some(16),
+                ..GenerationParams::default()
+            }),
+        };
+        let canonical = decode_grpc_request(request, true, uuid::Uuid::nil()).unwrap();
+        assert!(canonical.stream);
+        assert_eq!(canonical.system_prompt.as_deref(), Some("be brief"));
+        assert!(matches!(
+            &canonical.messages[0].parts[0],
+            CanonicalPart::ToolCall { arguments, .. } if arguments.get() == r#"{"q":"x"}"#
+        ));
+        assert_eq!(
+            canonical.tools[0].function.parameters,
+            serde_json::json!({})
+        );
+        assert_eq!(
+            canonical.tool_choice,
+            CanonicalToolChoice::Specific("lookup".to_string())
+        );
+        assert_eq!(canonical.generation.max_tokens, Some(16));
+        assert_eq!(canonical.generation.stop, None);
+
+        let unset_role = CompleteRequest {
+            messages: vec![Message::default()],
+            ..CompleteRequest::default()
+        };
+        let err = decode_grpc_request(unset_role, false, uuid::Uuid::nil()).unwrap_err();
+        assert!(matches!(err, CanonicalError::InvalidRequest(_)), "{err}");
+    }
+
+    #[test]
+    fn test_encode_grpc_stream_event_ends_on_done_and_error() {
+        let event = encode_grpc_stream_event(CanonicalStreamEvent::MessageEnd {
+            stop_reason: CanonicalStopReason::ToolCalls,
+        });
+        assert_eq!(
+            event.and_then(|event| event.event),
+            Some(stream_event::Event::MessageEnd(MessageEnd {
+                stop_reason: StopReason::ToolCalls.into(),
+            }))
+        );
+        assert!(encode_grpc_stream_event(CanonicalStreamEvent::Done).is_none());
+        assert!(encode_grpc_stream_event(CanonicalStreamEvent::Error {
+            status: 500,
+            message: "boom".to_string(),
+        })
+        .is_none());
+    }
+}
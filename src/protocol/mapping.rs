@@ -1,4 +1,6 @@
-use super::canonical::{CanonicalRole, CanonicalStopReason, CanonicalUsage};
+use super::canonical::{
+    CanonicalRole, CanonicalStopReason, CanonicalUsage, IngressApi, ProviderKind,
+};
 
 // ---------------------------------------------------------------------------
 // Role mappings
@@ -66,37 +68,74 @@ pub fn gemini_role_to_canonical(s: &str) -> CanonicalRole {
 #[must_use]
 pub fn canonical_stop_to_openai(reason: CanonicalStopReason) -> &'static str {
     match reason {
-        CanonicalStopReason::EndOfTurn => "stop",
+        CanonicalStopReason::EndOfTurn
+        | CanonicalStopReason::StopSequence
+        | CanonicalStopReason::PauseTurn => "stop",
         CanonicalStopReason::ToolCalls => "tool_calls",
         CanonicalStopReason::MaxTokens => "length",
-        CanonicalStopReason::ContentFilter => "content_filter",
+        CanonicalStopReason::ContentFilter
+        | CanonicalStopReason::Recitation
+        | CanonicalStopReason::Refusal => "content_filter",
     }
 }
 
 #[must_use]
 pub fn openai_stop_to_canonical(s: &str) -> CanonicalStopReason {
     match s {
-        "tool_calls" => CanonicalStopReason::ToolCalls,
+        "tool_calls" | "function_call" => CanonicalStopReason::ToolCalls,
         "length" => CanonicalStopReason::MaxTokens,
         "content_filter" => CanonicalStopReason::ContentFilter,
         _ => CanonicalStopReason::EndOfTurn,
     }
 }
 
+/// The Responses `incomplete_details.reason` for `reason`, or `None` when the
+/// response is `completed`.
+#[must_use]
+pub fn canonical_stop_to_responses_incomplete(reason: CanonicalStopReason) -> Option<&'static str> {
+    match reason {
+        CanonicalStopReason::MaxTokens => Some("max_output_tokens"),
+        CanonicalStopReason::ContentFilter
+        | CanonicalStopReason::Recitation
+        | CanonicalStopReason::Refusal => Some("content_filter"),
+        CanonicalStopReason::EndOfTurn
+        | CanonicalStopReason::StopSequence
+        | CanonicalStopReason::ToolCalls
+        | CanonicalStopReason::PauseTurn => None,
+    }
+}
+
+#[must_use]
+pub fn responses_incomplete_to_canonical(s: &str) -> CanonicalStopReason {
+    match s {
+        "content_filter" => CanonicalStopReason::ContentFilter,
+        _ => CanonicalStopReason::MaxTokens,
+    }
+}
+
 #[must_use]
 pub fn canonical_stop_to_anthropic(reason: CanonicalStopReason) -> &'static str {
     match reason {
-        CanonicalStopReason::EndOfTurn | CanonicalStopReason::ContentFilter => "end_turn", // Anthropic has no content_filter reason
+        CanonicalStopReason::EndOfTurn => "end_turn",
+        CanonicalStopReason::StopSequence => "stop_sequence",
         CanonicalStopReason::ToolCalls => "tool_use",
         CanonicalStopReason::MaxTokens => "max_tokens",
+        CanonicalStopReason::PauseTurn => "pause_turn",
+        // Anthropic reports any blocked output as a refusal.
+        CanonicalStopReason::ContentFilter
+        | CanonicalStopReason::Recitation
+        | CanonicalStopReason::Refusal => "refusal",
     }
 }
 
 #[must_use]
 pub fn anthropic_stop_to_canonical(s: &str) -> CanonicalStopReason {
     match s {
+        "stop_sequence" => CanonicalStopReason::StopSequence,
         "tool_use" => CanonicalStopReason::ToolCalls,
-        "max_tokens" => CanonicalStopReason::MaxTokens,
+        "max_tokens" | "model_context_window_exceeded" => CanonicalStopReason::MaxTokens,
+        "pause_turn" => CanonicalStopReason::PauseTurn,
+        "refusal" => CanonicalStopReason::Refusal,
         _ => CanonicalStopReason::EndOfTurn,
     }
 }
@@ -104,9 +143,14 @@ pub fn anthropic_stop_to_canonical(s: &str) -> CanonicalStopReason {
 #[must_use]
 pub fn canonical_stop_to_gemini(reason: CanonicalStopReason) -> &'static str {
     match reason {
-        CanonicalStopReason::EndOfTurn | CanonicalStopReason::ToolCalls => "STOP", // Gemini uses STOP even for tool calls
+        // Gemini uses STOP for tool calls and stop sequences too.
+        CanonicalStopReason::EndOfTurn
+        | CanonicalStopReason::StopSequence
+        | CanonicalStopReason::ToolCalls
+        | CanonicalStopReason::PauseTurn => "STOP",
         CanonicalStopReason::MaxTokens => "MAX_TOKENS",
-        CanonicalStopReason::ContentFilter => "SAFETY",
+        CanonicalStopReason::ContentFilter | CanonicalStopReason::Refusal => "SAFETY",
+        CanonicalStopReason::Recitation => "RECITATION",
     }
 }
 
@@ -114,11 +158,109 @@ pub fn canonical_stop_to_gemini(reason: CanonicalStopReason) -> &'static str {
 pub fn gemini_stop_to_canonical(s: &str) -> CanonicalStopReason {
     match s {
         "MAX_TOKENS" => CanonicalStopReason::MaxTokens,
-        "SAFETY" | "RECITATION" => CanonicalStopReason::ContentFilter,
+        "SAFETY" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" | "IMAGE_SAFETY" => {
+            CanonicalStopReason::ContentFilter
+        }
+        "RECITATION" => CanonicalStopReason::Recitation,
         _ => CanonicalStopReason::EndOfTurn,
     }
 }
 
+// ---------------------------------------------------------------------------
+// Output token limit mappings
+// ---------------------------------------------------------------------------
+
+/// Anthropic `max_tokens` when the client set no limit; the field is required.
+pub const ANTHROPIC_DEFAULT_MAX_TOKENS: u64 = 4096;
+/// Largest output limit any Anthropic model accepts.
+pub const ANTHROPIC_MAX_OUTPUT_TOKENS: u64 = 128_000;
+/// Largest `maxOutputTokens` any Gemini model accepts.
+pub const GEMINI_MAX_OUTPUT_TOKENS: u64 = 65_536;
+/// Smallest `max_output_tokens` the Responses API accepts.
+pub const RESPONSES_MIN_OUTPUT_TOKENS: u64 = 16;
+
+/// Translate the canonical output token limit into the value sent to
+/// `provider`.
+///
+/// OpenAI Chat `max_tokens` / `max_completion_tokens`, Anthropic `max_tokens`,
+/// Gemini `maxOutputTokens` and Responses `max_output_tokens` all cap the
+/// generated tokens, but only Anthropic requires one and each accepts a
+/// different range. `None` leaves the provider's own default in place.
+#[must_use]
+pub fn provider_max_output_tokens(provider: ProviderKind, max_tokens: Option<u64>) -> Option<u64> {
+    match provider {
+        ProviderKind::Anthropic => Some(anthropic_max_tokens(max_tokens, None)),
+        ProviderKind::Gemini | ProviderKind::GeminiOpenAi => {
+            max_tokens.map(|limit| limit.clamp(1, GEMINI_MAX_OUTPUT_TOKENS))
+        }
+        ProviderKind::OpenAiResponses => {
+            max_tokens.map(|limit| limit.max(RESPONSES_MIN_OUTPUT_TOKENS))
+        }
+        ProviderKind::OpenAi => max_tokens.map(|limit| limit.max(1)),
+    }
+}
+
+/// Anthropic `max_tokens` for a canonical limit.
+///
+/// Extended thinking counts against `max_tokens`, which must exceed the
+/// thinking budget; a defaulted limit leaves the usual room on top of it.
+#[must_use]
+pub fn anthropic_max_tokens(max_tokens: Option<u64>, thinking_budget: Option<u64>) -> u64 {
+    let limit = max_tokens.unwrap_or_else(|| {
+        thinking_budget.map_or(ANTHROPIC_DEFAULT_MAX_TOKENS, |budget| {
+            budget.saturating_add(ANTHROPIC_DEFAULT_MAX_TOKENS)
+        })
+    });
+    limit.clamp(1, ANTHROPIC_MAX_OUTPUT_TOKENS)
+}
+
+// ---------------------------------------------------------------------------
+// Sampling parameter ranges
+// ---------------------------------------------------------------------------
+
+/// Largest `top_p`; every protocol accepts `[0, 1]`.
+pub const MAX_TOP_P: f64 = 1.0;
+
+/// Largest `temperature` accepted by `provider` (the minimum is 0).
+#[must_use]
+pub const fn provider_max_temperature(provider: ProviderKind) -> f64 {
+    match provider {
+        ProviderKind::Anthropic => 1.0,
+        ProviderKind::OpenAi
+        | ProviderKind::OpenAiResponses
+        | ProviderKind::Gemini
+        | ProviderKind::GeminiOpenAi => 2.0,
+    }
+}
+
+/// Largest `temperature` a client of `ingress` can send.
+#[must_use]
+pub const fn ingress_max_temperature(ingress: IngressApi) -> f64 {
+    match ingress {
+        IngressApi::Anthropic => 1.0,
+        IngressApi::OpenAiChat | IngressApi::OpenAiResponses | IngressApi::Gemini => 2.0,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Optional parameter support
+// ---------------------------------------------------------------------------
+
+/// Optional client parameters carried in provider extensions that some
+/// upstreams reject or ignore.
+pub const DEGRADABLE_PARAMS: [&str; 4] = ["seed", "logprobs", "top_logprobs", "logit_bias"];
+
+/// Whether requests encoded for `provider` can carry `param`, one of
+/// [`DEGRADABLE_PARAMS`].
+#[must_use]
+pub fn provider_supports_param(provider: ProviderKind, param: &str) -> bool {
+    match provider {
+        ProviderKind::OpenAi | ProviderKind::GeminiOpenAi => true,
+        ProviderKind::OpenAiResponses => param == "top_logprobs",
+        ProviderKind::Anthropic | ProviderKind::Gemini => false,
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Usage mappings
 // ---------------------------------------------------------------------------
@@ -126,11 +268,18 @@ pub fn gemini_stop_to_canonical(s: &str) -> CanonicalStopReason {
 /// Convert canonical usage to OpenAI-style usage fields.
 #[must_use]
 pub fn canonical_usage_to_openai(usage: &CanonicalUsage) -> serde_json::Value {
-    serde_json::json!({
+    let mut value = serde_json::json!({
         "prompt_tokens": usage.input_tokens.unwrap_or(0),
         "completion_tokens": usage.output_tokens.unwrap_or(0),
         "total_tokens": usage.total_tokens.unwrap_or(0),
-    })
+    });
+    if let Some(cached) = usage.cached_input_tokens {
+        value["prompt_tokens_details"] = serde_json::json!({ "cached_tokens": cached });
+    }
+    if let Some(reasoning) = usage.reasoning_tokens {
+        value["completion_tokens_details"] = serde_json::json!({ "reasoning_tokens": reasoning });
+    }
+    value
 }
 
 /// Convert OpenAI-style usage JSON to canonical usage.
@@ -142,57 +291,132 @@ pub fn openai_usage_to_canonical(val: &serde_json::Value) -> CanonicalUsage {
             .get("completion_tokens")
             .and_then(serde_json::Value::as_u64),
         total_tokens: val.get("total_tokens").and_then(serde_json::Value::as_u64),
+        cached_input_tokens: val
+            .pointer("/prompt_tokens_details/cached_tokens")
+            .and_then(serde_json::Value::as_u64),
+        cache_creation_input_tokens: None,
+        reasoning_tokens: val
+            .pointer("/completion_tokens_details/reasoning_tokens")
+            .and_then(serde_json::Value::as_u64),
+    }
+}
+
+/// Canonical usage from Anthropic usage counts.
+///
+/// Anthropic's `input_tokens` leaves out tokens read from or written to the
+/// prompt cache; they are added back so `input_tokens` covers the prompt.
+#[must_use]
+pub fn anthropic_usage_counts_to_canonical(
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+    cache_read_input_tokens: Option<u64>,
+    cache_creation_input_tokens: Option<u64>,
+) -> CanonicalUsage {
+    let input_tokens = input_tokens.map(|input| {
+        input
+            .saturating_add(cache_read_input_tokens.unwrap_or(0))
+            .saturating_add(cache_creation_input_tokens.unwrap_or(0))
+    });
+    CanonicalUsage {
+        input_tokens,
+        output_tokens,
+        total_tokens: match (input_tokens, output_tokens) {
+            (Some(input), Some(output)) => input.checked_add(output),
+            _ => None,
+        },
+        cached_input_tokens: cache_read_input_tokens,
+        cache_creation_input_tokens,
+        reasoning_tokens: None,
     }
 }
 
 /// Convert canonical usage to Anthropic-style usage fields.
 #[must_use]
 pub fn canonical_usage_to_anthropic(usage: &CanonicalUsage) -> serde_json::Value {
-    serde_json::json!({
-        "input_tokens": usage.input_tokens.unwrap_or(0),
+    let mut value = serde_json::json!({
+        "input_tokens": usage.uncached_input_tokens(),
         "output_tokens": usage.output_tokens.unwrap_or(0),
-    })
+    });
+    if let Some(created) = usage.cache_creation_input_tokens {
+        value["cache_creation_input_tokens"] = created.into();
+    }
+    if let Some(cached) = usage.cached_input_tokens {
+        value["cache_read_input_tokens"] = cached.into();
+    }
+    value
 }
 
 /// Convert Anthropic-style usage JSON to canonical usage.
 #[must_use]
 pub fn anthropic_usage_to_canonical(val: &serde_json::Value) -> CanonicalUsage {
-    let input = val.get("input_tokens").and_then(serde_json::Value::as_u64);
-    let output = val.get("output_tokens").and_then(serde_json::Value::as_u64);
+    let count = |key: &str| val.get(key).and_then(serde_json::Value::as_u64);
+    anthropic_usage_counts_to_canonical(
+        count("input_tokens"),
+        count("output_tokens"),
+        count("cache_read_input_tokens"),
+        count("cache_creation_input_tokens"),
+    )
+}
+
+/// Canonical usage from Gemini usage counts.
+///
+/// Gemini leaves thinking tokens (`thoughtsTokenCount`) out of
+/// `candidatesTokenCount`; they are added back so `output_tokens` covers the
+/// whole answer.
+#[must_use]
+pub fn gemini_usage_counts_to_canonical(
+    prompt_token_count: Option<u64>,
+    candidates_token_count: Option<u64>,
+    total_token_count: Option<u64>,
+    cached_content_token_count: Option<u64>,
+    thoughts_token_count: Option<u64>,
+) -> CanonicalUsage {
+    let output_tokens = match (candidates_token_count, thoughts_token_count) {
+        (None, None) => None,
+        (candidates, thoughts) => Some(
+            candidates
+                .unwrap_or(0)
+                .saturating_add(thoughts.unwrap_or(0)),
+        ),
+    };
     CanonicalUsage {
-        input_tokens: input,
-        output_tokens: output,
-        total_tokens: match (input, output) {
-            (Some(i), Some(o)) => Some(i + o),
-            _ => None,
-        },
+        input_tokens: prompt_token_count,
+        output_tokens,
+        total_tokens: total_token_count,
+        cached_input_tokens: cached_content_token_count,
+        cache_creation_input_tokens: None,
+        reasoning_tokens: thoughts_token_count,
     }
 }
 
 /// Convert canonical usage to Gemini-style usage fields.
 #[must_use]
 pub fn canonical_usage_to_gemini(usage: &CanonicalUsage) -> serde_json::Value {
-    serde_json::json!({
+    let mut value = serde_json::json!({
         "promptTokenCount": usage.input_tokens.unwrap_or(0),
-        "candidatesTokenCount": usage.output_tokens.unwrap_or(0),
+        "candidatesTokenCount": usage.visible_output_tokens(),
         "totalTokenCount": usage.total_tokens.unwrap_or(0),
-    })
+    });
+    if let Some(cached) = usage.cached_input_tokens {
+        value["cachedContentTokenCount"] = cached.into();
+    }
+    if let Some(reasoning) = usage.reasoning_tokens {
+        value["thoughtsTokenCount"] = reasoning.into();
+    }
+    value
 }
 
 /// Convert Gemini-style usage JSON to canonical usage.
 #[must_use]
 pub fn gemini_usage_to_canonical(val: &serde_json::Value) -> CanonicalUsage {
-    CanonicalUsage {
-        input_tokens: val
-            .get("promptTokenCount")
-            .and_then(serde_json::Value::as_u64),
-        output_tokens: val
-            .get("candidatesTokenCount")
-            .and_then(serde_json::Value::as_u64),
-        total_tokens: val
-            .get("totalTokenCount")
-            .and_then(serde_json::Value::as_u64),
-    }
+    let count = |key: &str| val.get(key).and_then(serde_json::Value::as_u64);
+    gemini_usage_counts_to_canonical(
+        count("promptTokenCount"),
+        count("candidatesTokenCount"),
+        count("totalTokenCount"),
+        count("cachedContentTokenCount"),
+        count("thoughtsTokenCount"),
+    )
 }
 
 // ---------------------------------------------------------------------------
@@ -255,6 +479,17 @@ mod tests {
 
     // --- Stop reason bijectivity tests ---
 
+    const ALL_STOP_REASONS: [CanonicalStopReason; 8] = [
+        CanonicalStopReason::EndOfTurn,
+        CanonicalStopReason::StopSequence,
+        CanonicalStopReason::ToolCalls,
+        CanonicalStopReason::MaxTokens,
+        CanonicalStopReason::ContentFilter,
+        CanonicalStopReason::Recitation,
+        CanonicalStopReason::Refusal,
+        CanonicalStopReason::PauseTurn,
+    ];
+
     #[test]
     fn test_openai_stop_roundtrip() {
         for reason in [
@@ -269,13 +504,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_openai_stop_collapses_to_closest_finish_reason() {
+        let wire: Vec<_> = ALL_STOP_REASONS
+            .into_iter()
+            .map(canonical_stop_to_openai)
+            .collect();
+        assert_eq!(
+            wire,
+            [
+                "stop",
+                "stop",
+                "tool_calls",
+                "length",
+                "content_filter",
+                "content_filter",
+                "content_filter",
+                "stop"
+            ]
+        );
+        assert_eq!(
+            openai_stop_to_canonical("function_call"),
+            CanonicalStopReason::ToolCalls
+        );
+    }
+
     #[test]
     fn test_anthropic_stop_roundtrip() {
-        // ContentFilter maps to end_turn in Anthropic, so it won't roundtrip.
         for reason in [
             CanonicalStopReason::EndOfTurn,
+            CanonicalStopReason::StopSequence,
             CanonicalStopReason::ToolCalls,
             CanonicalStopReason::MaxTokens,
+            CanonicalStopReason::Refusal,
+            CanonicalStopReason::PauseTurn,
         ] {
             let wire = canonical_stop_to_anthropic(reason);
             let back = anthropic_stop_to_canonical(wire);
@@ -284,21 +546,106 @@ mod tests {
                 "Anthropic stop roundtrip failed for {reason:?}"
             );
         }
+        // Filtered output is an Anthropic refusal.
+        assert_eq!(
+            canonical_stop_to_anthropic(CanonicalStopReason::ContentFilter),
+            "refusal"
+        );
+        assert_eq!(
+            anthropic_stop_to_canonical("model_context_window_exceeded"),
+            CanonicalStopReason::MaxTokens
+        );
     }
 
     #[test]
-    fn test_gemini_stop_roundtrip_end_and_max() {
+    fn test_gemini_stop_roundtrip() {
+        for reason in [
+            CanonicalStopReason::EndOfTurn,
+            CanonicalStopReason::MaxTokens,
+            CanonicalStopReason::ContentFilter,
+            CanonicalStopReason::Recitation,
+        ] {
+            let wire = canonical_stop_to_gemini(reason);
+            let back = gemini_stop_to_canonical(wire);
+            assert_eq!(reason, back, "Gemini stop roundtrip failed for {reason:?}");
+        }
         assert_eq!(
-            gemini_stop_to_canonical(canonical_stop_to_gemini(CanonicalStopReason::EndOfTurn)),
-            CanonicalStopReason::EndOfTurn
+            canonical_stop_to_gemini(CanonicalStopReason::StopSequence),
+            "STOP"
         );
         assert_eq!(
-            gemini_stop_to_canonical(canonical_stop_to_gemini(CanonicalStopReason::MaxTokens)),
-            CanonicalStopReason::MaxTokens
+            canonical_stop_to_gemini(CanonicalStopReason::Refusal),
+            "SAFETY"
         );
+        for finish_reason in ["BLOCKLIST", "PROHIBITED_CONTENT", "SPII"] {
+            assert_eq!(
+                gemini_stop_to_canonical(finish_reason),
+                CanonicalStopReason::ContentFilter
+            );
+        }
+    }
+
+    #[test]
+    fn test_responses_incomplete_reason_roundtrip() {
+        for reason in [
+            CanonicalStopReason::MaxTokens,
+            CanonicalStopReason::ContentFilter,
+        ] {
+            let wire = canonical_stop_to_responses_incomplete(reason).expect("incomplete");
+            assert_eq!(responses_incomplete_to_canonical(wire), reason);
+        }
         assert_eq!(
-            gemini_stop_to_canonical(canonical_stop_to_gemini(CanonicalStopReason::ContentFilter)),
-            CanonicalStopReason::ContentFilter
+            canonical_stop_to_responses_incomplete(CanonicalStopReason::Recitation),
+            Some("content_filter")
+        );
+        for reason in [
+            CanonicalStopReason::EndOfTurn,
+            CanonicalStopReason::StopSequence,
+            CanonicalStopReason::ToolCalls,
+            CanonicalStopReason::PauseTurn,
+        ] {
+            assert_eq!(canonical_stop_to_responses_incomplete(reason), None);
+        }
+    }
+
+    // --- Output token limit tests ---
+
+    #[test]
+    fn test_anthropic_always_gets_max_tokens() {
+        assert_eq!(
+            provider_max_output_tokens(ProviderKind::Anthropic, None),
+            Some(ANTHROPIC_DEFAULT_MAX_TOKENS)
+        );
+        assert_eq!(
+            provider_max_output_tokens(ProviderKind::Anthropic, Some(0)),
+            Some(1)
+        );
+        assert_eq!(
+            provider_max_output_tokens(ProviderKind::Anthropic, Some(1_000_000)),
+            Some(ANTHROPIC_MAX_OUTPUT_TOKENS)
+        );
+        assert_eq!(anthropic_max_tokens(None, Some(10_000)), 14_096);
+        assert_eq!(anthropic_max_tokens(Some(20_000), Some(10_000)), 20_000);
+    }
+
+    #[test]
+    fn test_output_limits_are_clamped_per_provider() {
+        assert_eq!(provider_max_output_tokens(ProviderKind::OpenAi, None), None);
+        assert_eq!(
+            provider_max_output_tokens(ProviderKind::OpenAi, Some(0)),
+            Some(1)
+        );
+        assert_eq!(
+            provider_max_output_tokens(ProviderKind::Gemini, Some(u64::MAX)),
+            Some(GEMINI_MAX_OUTPUT_TOKENS)
+        );
+        assert_eq!(
+            provider_max_output_tokens(ProviderKind::GeminiOpenAi, Some(512)),
+            Some(512)
+        );
+        assert_eq!(
+            provider_max_output_tokens(ProviderKind::OpenAiResponses, Some(4)),
+            Some(RESPONSES_MIN_OUTPUT_TOKENS)
         );
     }
 
@@ -310,6 +657,7 @@ mod tests {
             input_tokens: Some(100),
             output_tokens: Some(50),
             total_tokens: Some(150),
+            ..CanonicalUsage::default()
         };
         let json = canonical_usage_to_openai(&usage);
         let back = openai_usage_to_canonical(&json);
@@ -324,6 +672,7 @@ mod tests {
             input_tokens: Some(200),
             output_tokens: Some(80),
             total_tokens: Some(280),
+            ..CanonicalUsage::default()
         };
         let json = canonical_usage_to_anthropic(&usage);
         let back = anthropic_usage_to_canonical(&json);
@@ -338,6 +687,7 @@ mod tests {
             input_tokens: Some(300),
             output_tokens: Some(120),
             total_tokens: Some(420),
+            ..CanonicalUsage::default()
         };
         let json = canonical_usage_to_gemini(&usage);
         let back = gemini_usage_to_canonical(&json);
@@ -346,6 +696,47 @@ mod tests {
         assert_eq!(back.total_tokens, Some(420));
     }
 
+    #[test]
+    fn test_cached_and_reasoning_usage_roundtrip_across_providers() {
+        let anthropic = anthropic_usage_to_canonical(&serde_json::json!({
+            "input_tokens": 5,
+            "output_tokens": 40,
+            "cache_read_input_tokens": 900,
+            "cache_creation_input_tokens": 100
+        }));
+        assert_eq!(anthropic.input_tokens, Some(1005));
+        assert_eq!(anthropic.total_tokens, Some(1045));
+        assert_eq!(anthropic.uncached_input_tokens(), 5);
+
+        let openai = canonical_usage_to_openai(&anthropic);
+        assert_eq!(openai["prompt_tokens"], 1005);
+        assert_eq!(openai["prompt_tokens_details"]["cached_tokens"], 900);
+        assert_eq!(
+            canonical_usage_to_anthropic(&anthropic),
+            serde_json::json!({
+                "input_tokens": 5,
+                "output_tokens": 40,
+                "cache_creation_input_tokens": 100,
+                "cache_read_input_tokens": 900
+            })
+        );
+
+        let gemini = gemini_usage_to_canonical(&serde_json::json!({
+            "promptTokenCount": 10,
+            "candidatesTokenCount": 7,
+            "thoughtsTokenCount": 30,
+            "totalTokenCount": 47
+        }));
+        assert_eq!(gemini.output_tokens, Some(37));
+        assert_eq!(gemini.reasoning_tokens, Some(30));
+        let openai = canonical_usage_to_openai(&gemini);
+        assert_eq!(openai["completion_tokens"], 37);
+        assert_eq!(openai["completion_tokens_details"]["reasoning_tokens"], 30);
+        let back = gemini_usage_to_canonical(&canonical_usage_to_gemini(&gemini));
+        assert_eq!(back.output_tokens, Some(37));
+        assert_eq!(back.reasoning_tokens, Some(30));
+    }
+
     #[test]
     fn test_default_usage() {
         let usage = CanonicalUsage::default();
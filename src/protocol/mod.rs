@@ -2,6 +2,8 @@ pub mod anthropic;
 pub mod canonical;
 pub(crate) mod error_shapes;
 pub mod gemini;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod mapping;
 pub mod openai_chat;
 pub mod openai_responses;
@@ -251,10 +251,8 @@ fn decode_message(msg: &super::OpenAiMessage) -> Result<CanonicalMessage, Canoni
     }
 
     match &msg.content {
-        Some(Value::String(s)) => {
-            if !s.is_empty() {
-                parts.push(CanonicalPart::Text(s.clone()));
-            }
+        Some(Value::String(s)) if !s.is_empty() => {
+            parts.push(CanonicalPart::Text(s.clone()));
         }
         Some(Value::Array(arr)) => {
             for part in arr {
@@ -314,6 +312,7 @@ fn decode_message_owned(msg: super::OpenAiMessage) -> Result<CanonicalMessage, C
         tool_calls,
         tool_call_id,
         refusal,
+        annotations: _,
     } = msg;
 
     let role = openai_role_to_canonical(&wire_role);
@@ -347,10 +346,8 @@ fn decode_message_owned(msg: super::OpenAiMessage) -> Result<CanonicalMessage, C
     }
 
     match content {
-        Some(Value::String(s)) => {
-            if !s.is_empty() {
-                parts.push(CanonicalPart::Text(s));
-            }
+        Some(Value::String(s)) if !s.is_empty() => {
+            parts.push(CanonicalPart::Text(s));
         }
         Some(Value::Array(arr)) => {
             for part in arr {
@@ -3,12 +3,12 @@ use serde_json::Value;
 use crate::error::CanonicalError;
 use crate::protocol::canonical::{
     provider_extensions_to_map, CanonicalMessage, CanonicalPart, CanonicalRequest, CanonicalRole,
-    CanonicalToolChoice, CanonicalToolSpec,
+    CanonicalToolChoice, CanonicalToolSpec, ProviderKind,
 };
-use crate::protocol::mapping::canonical_role_to_openai;
+use crate::protocol::mapping::{canonical_role_to_openai, provider_max_output_tokens};
 
 use super::{
-    OpenAiChatRequest, OpenAiMessage, OpenAiStop, OpenAiTool, OpenAiToolCall,
+    OpenAiChatRequest, OpenAiMessage, OpenAiStop, OpenAiStreamOptions, OpenAiTool, OpenAiToolCall,
     OpenAiToolCallFunction, OpenAiToolChoice, OpenAiToolChoiceFunction,
     OpenAiToolChoiceFunctionCall, OpenAiToolFunction,
 };
@@ -33,6 +33,7 @@ pub fn encode_openai_chat_request(
             tool_calls: None,
             tool_call_id: None,
             refusal: None,
+            annotations: None,
         });
     }
 
@@ -62,9 +63,16 @@ pub fn encode_openai_chat_request(
         tools,
         tool_choice,
         stream: if canonical.stream { Some(true) } else { None },
-        stream_options: None,
+        // Always ask for usage; it is dropped again for clients that did not
+        // request `stream_options.include_usage`.
+        stream_options: canonical.stream.then_some(OpenAiStreamOptions {
+            include_usage: Some(true),
+        }),
         temperature: canonical.generation.temperature,
-        max_tokens: canonical.generation.max_tokens,
+        max_tokens: provider_max_output_tokens(
+            ProviderKind::OpenAi,
+            canonical.generation.max_tokens,
+        ),
         max_completion_tokens: None,
         top_p: canonical.generation.top_p,
         frequency_penalty: canonical.generation.frequency_penalty,
@@ -87,6 +95,7 @@ fn encode_message(msg: &CanonicalMessage) -> OpenAiMessage {
             tool_calls: None,
             tool_call_id: Some(tool_call_id),
             refusal: None,
+            annotations: None,
         };
     }
 
@@ -129,7 +138,9 @@ fn encode_message(msg: &CanonicalMessage) -> OpenAiMessage {
             CanonicalPart::Refusal(r) => {
                 refusal = Some(r.clone());
             }
-            CanonicalPart::ReasoningText(_) | CanonicalPart::ToolResult { .. } => {}
+            CanonicalPart::ReasoningText(_)
+            | CanonicalPart::ReasoningItem { .. }
+            | CanonicalPart::ToolResult { .. } => {}
         }
     }
 
@@ -163,6 +174,7 @@ fn encode_message(msg: &CanonicalMessage) -> OpenAiMessage {
         tool_calls: tool_calls_field,
         tool_call_id: msg.tool_call_id.clone(),
         refusal,
+        annotations: None,
     }
 }
 
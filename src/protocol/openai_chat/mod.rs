@@ -87,6 +87,10 @@ pub struct OpenAiChatResponse {
     pub choices: Vec<OpenAiChoice>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage: Option<OpenAiUsage>,
+    /// Upstream safety-filter details, set when a non-OpenAI provider filtered
+    /// the output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety: Option<serde_json::Value>,
 }
 
 /// A single choice in the response.
@@ -112,6 +116,30 @@ pub struct OpenAiMessage {
     pub tool_call_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub refusal: Option<String>,
+    /// Citations of an assistant message, e.g. from web search.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Vec<OpenAiAnnotation>>,
+}
+
+/// An annotation on an assistant message; only `url_citation` carries a
+/// citation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAiAnnotation {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_citation: Option<OpenAiUrlCitation>,
+}
+
+/// A web source cited by the character range `start_index..end_index` of
+/// the message content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAiUrlCitation {
+    pub start_index: usize,
+    pub end_index: usize,
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
 }
 
 /// A tool call within a message.
@@ -154,6 +182,24 @@ pub struct OpenAiUsage {
     pub prompt_tokens: u64,
     pub completion_tokens: u64,
     pub total_tokens: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens_details: Option<OpenAiPromptTokensDetails>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens_details: Option<OpenAiCompletionTokensDetails>,
+}
+
+/// Breakdown of `prompt_tokens`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpenAiPromptTokensDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_tokens: Option<u64>,
+}
+
+/// Breakdown of `completion_tokens`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpenAiCompletionTokensDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<u64>,
 }
 
 /// A streaming chunk.
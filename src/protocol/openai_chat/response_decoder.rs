@@ -1,88 +1,97 @@
+use std::borrow::Cow;
+
 use crate::error::CanonicalError;
-use crate::protocol::canonical::{CanonicalPart, CanonicalResponse, CanonicalUsage};
+use crate::protocol::canonical::{
+    CanonicalCitation, CanonicalPart, CanonicalResponse, CanonicalUsage,
+};
 use crate::protocol::mapping::openai_stop_to_canonical;
 use crate::util::raw_value_from_string;
 use serde::Deserialize;
 
-use super::OpenAiChatResponse;
+use super::{OpenAiAnnotation, OpenAiChatResponse, OpenAiUsage};
 
 #[derive(Debug, Deserialize)]
-struct OpenAiTextOnlyFastResponse<'a> {
+struct OpenAiFastResponse<'a> {
     #[serde(borrow)]
-    id: &'a str,
+    id: Cow<'a, str>,
     #[serde(borrow)]
-    model: &'a str,
+    model: Cow<'a, str>,
     #[serde(borrow)]
-    choices: Vec<OpenAiTextOnlyFastChoice<'a>>,
-    usage: Option<OpenAiTextOnlyFastUsage>,
+    choices: Vec<OpenAiFastChoice<'a>>,
+    usage: Option<OpenAiUsage>,
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAiTextOnlyFastChoice<'a> {
+struct OpenAiFastChoice<'a> {
     #[serde(borrow)]
-    message: OpenAiTextOnlyFastMessage<'a>,
+    message: OpenAiFastMessage<'a>,
     #[serde(default, borrow)]
-    finish_reason: Option<&'a str>,
+    finish_reason: Option<Cow<'a, str>>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
-enum OpenAiTextOnlyFastContent<'a> {
-    Text(&'a str),
-    Parts(Vec<OpenAiTextOnlyFastContentPart<'a>>),
+enum OpenAiFastContent<'a> {
+    Text(#[serde(borrow)] Cow<'a, str>),
+    Parts(#[serde(borrow)] Vec<OpenAiFastContentPart<'a>>),
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAiTextOnlyFastContentPart<'a> {
+struct OpenAiFastContentPart<'a> {
     #[serde(default, borrow, rename = "type")]
-    part_type: Option<&'a str>,
+    part_type: Option<Cow<'a, str>>,
     #[serde(default, borrow)]
-    text: Option<&'a str>,
+    text: Option<Cow<'a, str>>,
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAiTextOnlyFastMessage<'a> {
+struct OpenAiFastMessage<'a> {
+    #[serde(default, borrow)]
+    content: Option<OpenAiFastContent<'a>>,
     #[serde(default, borrow)]
-    content: Option<OpenAiTextOnlyFastContent<'a>>,
+    refusal: Option<Cow<'a, str>>,
     #[serde(default, borrow)]
-    refusal: Option<&'a str>,
+    tool_calls: Option<Vec<OpenAiFastToolCall<'a>>>,
     #[serde(default)]
-    tool_calls: Option<serde::de::IgnoredAny>,
+    annotations: Option<Vec<OpenAiAnnotation>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAiFastToolCall<'a> {
+    #[serde(borrow)]
+    id: Cow<'a, str>,
+    #[serde(borrow)]
+    function: OpenAiFastFunction<'a>,
 }
 
 #[derive(Debug, Deserialize)]
-struct OpenAiTextOnlyFastUsage {
-    #[serde(rename = "prompt_tokens")]
-    prompt: u64,
-    #[serde(rename = "completion_tokens")]
-    completion: u64,
-    #[serde(rename = "total_tokens")]
-    total: u64,
+struct OpenAiFastFunction<'a> {
+    #[serde(borrow)]
+    name: Cow<'a, str>,
+    #[serde(borrow)]
+    arguments: Cow<'a, str>,
 }
 
-/// Attempt to decode a text-only `OpenAI` Chat response directly from bytes.
+/// Attempt to decode an `OpenAI` Chat response directly from bytes, borrowing
+/// strings from `body` instead of building the owned wire types.
 ///
 /// Returns `None` when payload is not in the expected fast-path shape.
 #[must_use]
-pub fn try_decode_openai_chat_text_response_bytes(body: &[u8]) -> Option<CanonicalResponse> {
-    let parsed: OpenAiTextOnlyFastResponse<'_> = serde_json::from_slice(body).ok()?;
-    let choice = parsed.choices.first()?;
-    if choice.message.tool_calls.is_some() {
-        return None;
-    }
+pub fn try_decode_openai_chat_response_bytes(body: &[u8]) -> Option<CanonicalResponse> {
+    let parsed: OpenAiFastResponse<'_> = serde_json::from_slice(body).ok()?;
+    let choice = parsed.choices.into_iter().next()?;
+    let citations = decode_openai_annotations(choice.message.annotations.as_deref());
 
     let mut content: Vec<CanonicalPart> = Vec::new();
     if let Some(refusal) = choice.message.refusal {
-        content.push(CanonicalPart::Refusal(refusal.to_owned()));
+        content.push(CanonicalPart::Refusal(refusal.into_owned()));
     }
 
-    match &choice.message.content {
-        Some(OpenAiTextOnlyFastContent::Text(text)) => {
-            if !text.is_empty() {
-                content.push(CanonicalPart::Text((*text).to_owned()));
-            }
+    match choice.message.content {
+        Some(OpenAiFastContent::Text(text)) if !text.is_empty() => {
+            content.push(CanonicalPart::Text(text.into_owned()));
         }
-        Some(OpenAiTextOnlyFastContent::Parts(parts)) => {
+        Some(OpenAiFastContent::Parts(parts)) => {
             for part in parts {
                 let Some(text) = part.text else {
                     continue;
@@ -90,34 +99,43 @@ pub fn try_decode_openai_chat_text_response_bytes(body: &[u8]) -> Option<Canonic
                 if text.is_empty() {
                     continue;
                 }
-                if let Some(part_type) = part.part_type {
-                    if part_type != "text" {
-                        continue;
-                    }
+                if part.part_type.as_deref().is_some_and(|kind| kind != "text") {
+                    continue;
                 }
-                content.push(CanonicalPart::Text(text.to_owned()));
+                content.push(CanonicalPart::Text(text.into_owned()));
             }
         }
-        None => {}
+        Some(OpenAiFastContent::Text(_)) | None => {}
+    }
+
+    for call in choice.message.tool_calls.into_iter().flatten() {
+        let arguments = raw_value_from_string(
+            call.function.arguments.into_owned(),
+            "OpenAI response tool call",
+        )
+        .ok()?;
+        content.push(CanonicalPart::ToolCall {
+            id: call.id.into_owned(),
+            name: call.function.name.into_owned(),
+            arguments,
+        });
     }
 
     let usage = parsed
         .usage
-        .map_or_else(CanonicalUsage::default, |usage| CanonicalUsage {
-            input_tokens: Some(usage.prompt),
-            output_tokens: Some(usage.completion),
-            total_tokens: Some(usage.total),
-        });
+        .as_ref()
+        .map_or_else(CanonicalUsage::default, decode_openai_usage);
 
     Some(CanonicalResponse {
-        id: parsed.id.to_owned(),
-        model: parsed.model.to_owned(),
+        id: parsed.id.into_owned(),
+        model: parsed.model.into_owned(),
         content,
-        stop_reason: choice.finish_reason.map_or(
+        stop_reason: choice.finish_reason.as_deref().map_or(
             crate::protocol::canonical::CanonicalStopReason::EndOfTurn,
             openai_stop_to_canonical,
         ),
         usage,
+        citations,
         provider_extensions: serde_json::Map::new(),
     })
 }
@@ -143,10 +161,8 @@ pub fn decode_openai_chat_response(
     }
 
     match &choice.message.content {
-        Some(serde_json::Value::String(s)) => {
-            if !s.is_empty() {
-                content.push(CanonicalPart::Text(s.clone()));
-            }
+        Some(serde_json::Value::String(s)) if !s.is_empty() => {
+            content.push(CanonicalPart::Text(s.clone()));
         }
         Some(serde_json::Value::Array(arr)) => {
             for part in arr {
@@ -175,14 +191,10 @@ pub fn decode_openai_chat_response(
         openai_stop_to_canonical,
     );
 
-    let usage = match &response.usage {
-        Some(u) => CanonicalUsage {
-            input_tokens: Some(u.prompt_tokens),
-            output_tokens: Some(u.completion_tokens),
-            total_tokens: Some(u.total_tokens),
-        },
-        None => CanonicalUsage::default(),
-    };
+    let usage = response
+        .usage
+        .as_ref()
+        .map_or_else(CanonicalUsage::default, decode_openai_usage);
 
     Ok(CanonicalResponse {
         id: response.id.clone(),
@@ -190,6 +202,7 @@ pub fn decode_openai_chat_response(
         content,
         stop_reason,
         usage,
+        citations: decode_openai_annotations(choice.message.annotations.as_deref()),
         provider_extensions: serde_json::Map::new(),
     })
 }
@@ -210,11 +223,13 @@ pub fn decode_openai_chat_response_owned(
         model,
         choices,
         usage,
+        safety: _,
     } = response;
     let choice = choices
         .into_iter()
         .next()
         .ok_or_else(|| CanonicalError::Translation("OpenAI response has no choices".to_string()))?;
+    let citations = decode_openai_annotations(choice.message.annotations.as_deref());
 
     let mut content: Vec<CanonicalPart> = Vec::new();
 
@@ -255,11 +270,9 @@ pub fn decode_openai_chat_response_owned(
         openai_stop_to_canonical,
     );
 
-    let usage = usage.map_or_else(CanonicalUsage::default, |usage| CanonicalUsage {
-        input_tokens: Some(usage.prompt_tokens),
-        output_tokens: Some(usage.completion_tokens),
-        total_tokens: Some(usage.total_tokens),
-    });
+    let usage = usage
+        .as_ref()
+        .map_or_else(CanonicalUsage::default, decode_openai_usage);
 
     Ok(CanonicalResponse {
         id,
@@ -267,10 +280,45 @@ pub fn decode_openai_chat_response_owned(
         content,
         stop_reason,
         usage,
+        citations,
         provider_extensions: serde_json::Map::new(),
     })
 }
 
+/// The `url_citation` annotations of a message as canonical citations.
+fn decode_openai_annotations(annotations: Option<&[OpenAiAnnotation]>) -> Vec<CanonicalCitation> {
+    annotations
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|annotation| annotation.url_citation.as_ref())
+        .map(|citation| CanonicalCitation {
+            url: citation.url.clone(),
+            title: Some(citation.title.clone()).filter(|title| !title.is_empty()),
+            cited_text: None,
+            span: Some(citation.start_index..citation.end_index),
+        })
+        .collect()
+}
+
+/// Decode `OpenAI` usage into canonical usage.
+#[must_use]
+pub fn decode_openai_usage(usage: &OpenAiUsage) -> CanonicalUsage {
+    CanonicalUsage {
+        input_tokens: Some(usage.prompt_tokens),
+        output_tokens: Some(usage.completion_tokens),
+        total_tokens: Some(usage.total_tokens),
+        cached_input_tokens: usage
+            .prompt_tokens_details
+            .as_ref()
+            .and_then(|details| details.cached_tokens),
+        cache_creation_input_tokens: None,
+        reasoning_tokens: usage
+            .completion_tokens_details
+            .as_ref()
+            .and_then(|details| details.reasoning_tokens),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -387,7 +435,7 @@ mod tests {
     }
 
     #[test]
-    fn test_try_decode_text_response_bytes() {
+    fn test_try_decode_response_bytes() {
         let body = br#"{
             "id":"chatcmpl-fast",
             "object":"chat.completion",
@@ -395,7 +443,7 @@ mod tests {
             "choices":[{"index":0,"message":{"role":"assistant","content":"fast path"},"finish_reason":"stop"}],
             "usage":{"prompt_tokens":1,"completion_tokens":2,"total_tokens":3}
         }"#;
-        let decoded = try_decode_openai_chat_text_response_bytes(body).unwrap();
+        let decoded = try_decode_openai_chat_response_bytes(body).unwrap();
         assert_eq!(decoded.id, "chatcmpl-fast");
         assert_eq!(decoded.model, "gpt-4o-mini");
         assert!(matches!(
@@ -405,25 +453,36 @@ mod tests {
     }
 
     #[test]
-    fn test_try_decode_text_response_bytes_rejects_tool_calls() {
+    fn test_try_decode_response_bytes_tool_calls_and_escapes() {
         let body = br#"{
             "id":"chatcmpl-tool",
             "object":"chat.completion",
             "model":"gpt-4o-mini",
-            "choices":[{"index":0,"message":{"role":"assistant","content":"x","tool_calls":[{"id":"c1","type":"function","function":{"name":"foo","arguments":"{}"}}]},"finish_reason":"tool_calls"}]
+            "choices":[{"index":0,"message":{"role":"assistant","content":"line\nbreak","tool_calls":[{"id":"c1","type":"function","function":{"name":"foo","arguments":"{\"q\":1}"}}]},"finish_reason":"tool_calls"}]
         }"#;
-        assert!(try_decode_openai_chat_text_response_bytes(body).is_none());
+        let decoded = try_decode_openai_chat_response_bytes(body).unwrap();
+        let wire: OpenAiChatResponse = serde_json::from_slice(body).unwrap();
+        let full = decode_openai_chat_response_owned(wire).unwrap();
+        assert_eq!(
+            format!("{:?}", decoded.content),
+            format!("{:?}", full.content)
+        );
+        assert!(matches!(
+            decoded.content.get(1),
+            Some(CanonicalPart::ToolCall { arguments, .. }) if arguments.get() == r#"{"q":1}"#
+        ));
+        assert_eq!(decoded.stop_reason, full.stop_reason);
     }
 
     #[test]
-    fn test_try_decode_text_response_bytes_array_content() {
+    fn test_try_decode_response_bytes_array_content() {
         let body = br#"{
             "id":"chatcmpl-array",
             "object":"chat.completion",
             "model":"gpt-4o-mini",
             "choices":[{"index":0,"message":{"role":"assistant","content":[{"type":"text","text":"hello"},{"type":"text","text":" world"}]},"finish_reason":"stop"}]
         }"#;
-        let decoded = try_decode_openai_chat_text_response_bytes(body).unwrap();
+        let decoded = try_decode_openai_chat_response_bytes(body).unwrap();
         assert_eq!(decoded.id, "chatcmpl-array");
         assert_eq!(decoded.content.len(), 2);
         assert!(matches!(
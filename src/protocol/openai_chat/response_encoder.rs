@@ -1,12 +1,13 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-
 use crate::error::CanonicalError;
-use crate::protocol::canonical::{CanonicalPart, CanonicalResponse};
+use crate::protocol::canonical::{
+    CanonicalPart, CanonicalResponse, CanonicalUsage, SAFETY_EXTENSION_KEY,
+};
 use crate::protocol::mapping::canonical_stop_to_openai;
 
 use super::{
-    OpenAiChatResponse, OpenAiChoice, OpenAiMessage, OpenAiToolCall, OpenAiToolCallFunction,
-    OpenAiUsage,
+    OpenAiAnnotation, OpenAiChatResponse, OpenAiChoice, OpenAiCompletionTokensDetails,
+    OpenAiMessage, OpenAiPromptTokensDetails, OpenAiToolCall, OpenAiToolCallFunction,
+    OpenAiUrlCitation, OpenAiUsage,
 };
 
 /// Encode a canonical response into the `OpenAI` Chat Completions wire format.
@@ -46,10 +47,12 @@ pub fn encode_openai_chat_response(
         }
     }
 
+    let text = text_parts.concat();
+    let annotations = encode_openai_annotations(canonical, text.chars().count());
     let content = if text_parts.is_empty() {
         None
     } else {
-        Some(serde_json::Value::String(text_parts.join("")))
+        Some(serde_json::Value::String(text))
     };
 
     let tool_calls_field = if tool_calls.is_empty() {
@@ -60,16 +63,9 @@ pub fn encode_openai_chat_response(
 
     let finish_reason = canonical_stop_to_openai(canonical.stop_reason).to_string();
 
-    let created = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+    let created = crate::util::unix_now_secs();
 
-    let usage = OpenAiUsage {
-        prompt_tokens: canonical.usage.input_tokens.unwrap_or(0),
-        completion_tokens: canonical.usage.output_tokens.unwrap_or(0),
-        total_tokens: canonical.usage.total_tokens.unwrap_or(0),
-    };
+    let usage = encode_openai_usage(&canonical.usage);
 
     Ok(OpenAiChatResponse {
         id: canonical.id.clone(),
@@ -85,13 +81,66 @@ pub fn encode_openai_chat_response(
                 tool_calls: tool_calls_field,
                 tool_call_id: None,
                 refusal,
+                annotations,
             },
             finish_reason: Some(finish_reason),
         }],
         usage: Some(usage),
+        safety: canonical
+            .provider_extensions
+            .get(SAFETY_EXTENSION_KEY)
+            .cloned(),
     })
 }
 
+/// Citations as `url_citation` annotations on a message of `text_chars`
+/// characters; citations without a span cite the whole message.
+fn encode_openai_annotations(
+    canonical: &CanonicalResponse,
+    text_chars: usize,
+) -> Option<Vec<OpenAiAnnotation>> {
+    if canonical.citations.is_empty() {
+        return None;
+    }
+    let annotations = canonical
+        .citations
+        .iter()
+        .map(|citation| {
+            let span = citation.span.clone().unwrap_or(0..text_chars);
+            OpenAiAnnotation {
+                type_: "url_citation".to_string(),
+                url_citation: Some(OpenAiUrlCitation {
+                    start_index: span.start.min(text_chars),
+                    end_index: span.end.min(text_chars),
+                    url: citation.url.clone(),
+                    title: citation.title.clone().unwrap_or_default(),
+                }),
+            }
+        })
+        .collect();
+    Some(annotations)
+}
+
+/// Encode canonical usage into `OpenAI` usage.
+#[must_use]
+pub fn encode_openai_usage(usage: &CanonicalUsage) -> OpenAiUsage {
+    OpenAiUsage {
+        prompt_tokens: usage.input_tokens.unwrap_or(0),
+        completion_tokens: usage.output_tokens.unwrap_or(0),
+        total_tokens: usage.total_tokens.unwrap_or(0),
+        prompt_tokens_details: usage.cached_input_tokens.map(|cached_tokens| {
+            OpenAiPromptTokensDetails {
+                cached_tokens: Some(cached_tokens),
+            }
+        }),
+        completion_tokens_details: usage.reasoning_tokens.map(|reasoning_tokens| {
+            OpenAiCompletionTokensDetails {
+                reasoning_tokens: Some(reasoning_tokens),
+            }
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,7 +157,9 @@ mod tests {
                 input_tokens: Some(10),
                 output_tokens: Some(5),
                 total_tokens: Some(15),
+                ..CanonicalUsage::default()
             },
+            citations: Vec::new(),
             provider_extensions: serde_json::Map::new(),
         };
         let wire = encode_openai_chat_response(&canonical, "gpt-4").unwrap();
@@ -136,6 +187,7 @@ mod tests {
             }],
             stop_reason: CanonicalStopReason::ToolCalls,
             usage: CanonicalUsage::default(),
+            citations: Vec::new(),
             provider_extensions: serde_json::Map::new(),
         };
         let wire = encode_openai_chat_response(&canonical, "gpt-4").unwrap();
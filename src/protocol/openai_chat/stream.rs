@@ -1,9 +1,8 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use crate::protocol::canonical::{CanonicalRole, CanonicalStreamEvent, CanonicalUsage};
+use crate::protocol::canonical::{CanonicalRole, CanonicalStreamEvent};
 use crate::protocol::mapping::{canonical_stop_to_openai, openai_stop_to_canonical};
 use crate::util::{parse_sse_data_json_line, push_json_string_escaped, push_u64_decimal};
 
+use super::response_decoder::decode_openai_usage;
 use super::OpenAiStreamChunk;
 
 const DONE_FRAME: &str = "data: [DONE]\n\n";
@@ -81,11 +80,7 @@ pub fn decode_openai_stream_chunk_into(
     }
 
     if let Some(usage) = chunk.usage {
-        out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-            input_tokens: Some(usage.prompt_tokens),
-            output_tokens: Some(usage.completion_tokens),
-            total_tokens: Some(usage.total_tokens),
-        }));
+        out.push(CanonicalStreamEvent::Usage(decode_openai_usage(&usage)));
     }
 }
 
@@ -97,10 +92,7 @@ pub fn encode_canonical_event_to_openai_sse(
     model: &str,
     id: &str,
 ) -> Option<String> {
-    let created = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+    let created = crate::util::unix_now_secs();
 
     encode_canonical_event_to_openai_sse_with_created(event, model, id, created)
 }
@@ -131,10 +123,7 @@ pub fn encode_canonical_event_to_openai_sse_with_created(
         }
         CanonicalStreamEvent::TextDelta(text) => {
             let mut out = String::with_capacity(128 + id.len() + model.len() + text.len());
-            push_openai_chunk_prefix(&mut out, id, model, created);
-            out.push_str(",\"choices\":[{\"index\":0,\"delta\":{\"content\":");
-            push_json_string_escaped(&mut out, text);
-            out.push_str("},\"finish_reason\":null}]}\n\n");
+            push_openai_text_delta_sse(&mut out, text, model, id, created);
             Some(out)
         }
         CanonicalStreamEvent::ToolCallStart {
@@ -183,6 +172,16 @@ pub fn encode_canonical_event_to_openai_sse_with_created(
             push_u64_decimal(&mut out, usage.output_tokens.unwrap_or(0));
             out.push_str(",\"total_tokens\":");
             push_u64_decimal(&mut out, usage.total_tokens.unwrap_or(0));
+            if let Some(cached) = usage.cached_input_tokens {
+                out.push_str(",\"prompt_tokens_details\":{\"cached_tokens\":");
+                push_u64_decimal(&mut out, cached);
+                out.push('}');
+            }
+            if let Some(reasoning) = usage.reasoning_tokens {
+                out.push_str(",\"completion_tokens_details\":{\"reasoning_tokens\":");
+                push_u64_decimal(&mut out, reasoning);
+                out.push('}');
+            }
             out.push_str("}}\n\n");
             Some(out)
         }
@@ -200,6 +199,20 @@ pub fn encode_canonical_event_to_openai_sse_with_created(
     }
 }
 
+/// Append an `OpenAI` SSE content delta chunk for `text` to `out`.
+pub fn push_openai_text_delta_sse(
+    out: &mut String,
+    text: &str,
+    model: &str,
+    id: &str,
+    created: u64,
+) {
+    push_openai_chunk_prefix(out, id, model, created);
+    out.push_str(",\"choices\":[{\"index\":0,\"delta\":{\"content\":");
+    push_json_string_escaped(out, text);
+    out.push_str("},\"finish_reason\":null}]}\n\n");
+}
+
 fn push_openai_chunk_prefix(out: &mut String, id: &str, model: &str, created: u64) {
     out.push_str("data: {\"id\":");
     push_json_string_escaped(out, id);
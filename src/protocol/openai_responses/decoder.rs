@@ -596,6 +596,13 @@ fn decode_input_item(item: &serde_json::Value) -> Result<CanonicalMessage, Canon
                 .to_string();
             Ok(build_function_call_output_message(call_id, output))
         }
+        "reasoning" => Ok(build_message(
+            CanonicalRole::Assistant,
+            item.as_object()
+                .map(decode_reasoning_item)
+                .into_iter()
+                .collect(),
+        )),
         _ => Err(CanonicalError::InvalidRequest(format!(
             "Unknown Responses API input item type: {item_type}"
         ))),
@@ -646,12 +653,41 @@ fn decode_input_item_owned(item: serde_json::Value) -> Result<CanonicalMessage,
             };
             Ok(build_function_call_output_message(call_id, output))
         }
+        "reasoning" => Ok(build_message(
+            CanonicalRole::Assistant,
+            vec![decode_reasoning_item(&obj)],
+        )),
         _ => Err(CanonicalError::InvalidRequest(format!(
             "Unknown Responses API input item type: {item_type}"
         ))),
     }
 }
 
+/// A `reasoning` item a client hands back from an earlier response.
+fn decode_reasoning_item(item: &serde_json::Map<String, serde_json::Value>) -> CanonicalPart {
+    let text_field = |key: &str| {
+        item.get(key)
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_string)
+    };
+    let summary = item
+        .get("summary")
+        .and_then(serde_json::Value::as_array)
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(serde_json::Value::as_str))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    CanonicalPart::ReasoningItem {
+        id: text_field("id").unwrap_or_default(),
+        summary,
+        encrypted_content: text_field("encrypted_content"),
+    }
+}
+
 fn parse_responses_call_arguments(
     value: Option<&serde_json::Value>,
 ) -> Result<Box<serde_json::value::RawValue>, CanonicalError> {
@@ -806,6 +842,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decode_reasoning_input_item() {
+        let req = ResponsesRequest {
+            model: "o3".into(),
+            input: serde_json::json!([
+                {
+                    "type": "reasoning",
+                    "id": "rs_1",
+                    "summary": [{"type": "summary_text", "text": "Plan"}],
+                    "encrypted_content": "gAAAA"
+                },
+                {"role": "user", "content": "continue"}
+            ]),
+            instructions: None,
+            tools: None,
+            tool_choice: None,
+            previous_response_id: None,
+            store: None,
+            stream: None,
+            temperature: None,
+            max_output_tokens: None,
+            top_p: None,
+            extra: serde_json::Map::new(),
+        };
+
+        let result = decode_responses_request(&req, uuid::Uuid::from_u128(1)).unwrap();
+        assert_eq!(result.messages.len(), 2);
+        assert_eq!(result.messages[0].role, CanonicalRole::Assistant);
+        assert!(matches!(
+            result.messages[0].parts.first(),
+            Some(CanonicalPart::ReasoningItem { id, summary, encrypted_content })
+                if id == "rs_1" && summary == &["Plan"] && encrypted_content.as_deref() == Some("gAAAA")
+        ));
+    }
+
     #[test]
     fn test_decode_function_call_item() {
         let req = ResponsesRequest {
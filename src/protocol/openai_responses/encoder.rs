@@ -1,8 +1,9 @@
 use crate::error::CanonicalError;
 use crate::protocol::canonical::{
     provider_extensions_to_map, CanonicalMessage, CanonicalPart, CanonicalRequest, CanonicalRole,
-    CanonicalToolChoice,
+    CanonicalToolChoice, ProviderKind,
 };
+use crate::protocol::mapping::provider_max_output_tokens;
 
 use super::{ResponsesRequest, ResponsesTool};
 
@@ -82,7 +83,10 @@ pub fn encode_responses_request(
         store,
         stream: if canonical.stream { Some(true) } else { None },
         temperature: canonical.generation.temperature,
-        max_output_tokens: canonical.generation.max_tokens,
+        max_output_tokens: provider_max_output_tokens(
+            ProviderKind::OpenAiResponses,
+            canonical.generation.max_tokens,
+        ),
         top_p: canonical.generation.top_p,
         extra,
     })
@@ -143,8 +147,33 @@ fn encode_message(msg: &CanonicalMessage) -> Vec<serde_json::Value> {
             }
         }
         CanonicalRole::Assistant => {
-            // Assistant messages: text parts become message items,
-            // tool call parts become function_call items.
+            // Assistant messages: reasoning items are handed back as-is, text
+            // parts become message items, tool call parts become
+            // function_call items.
+            for part in &msg.parts {
+                if let CanonicalPart::ReasoningItem {
+                    id,
+                    summary,
+                    encrypted_content,
+                } = part
+                {
+                    let summary: Vec<serde_json::Value> = summary
+                        .iter()
+                        .map(|text| serde_json::json!({"type": "summary_text", "text": text}))
+                        .collect();
+                    let mut item = serde_json::json!({
+                        "type": "reasoning",
+                        "id": id,
+                        "summary": summary,
+                    });
+                    if let Some(encrypted_content) = encrypted_content {
+                        item["encrypted_content"] =
+                            serde_json::Value::String(encrypted_content.clone());
+                    }
+                    items.push(item);
+                }
+            }
+
             let text_parts: Vec<&str> = msg
                 .parts
                 .iter()
@@ -68,10 +68,20 @@ pub struct ResponsesOutput {
     pub usage: Option<ResponsesUsage>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<String>,
+    /// Why an `incomplete` response stopped early.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incomplete_details: Option<ResponsesIncompleteDetails>,
     #[serde(flatten)]
     pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
+/// `incomplete_details` of a Responses API output.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResponsesIncompleteDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
 /// An output item in the Responses API.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -95,6 +105,24 @@ pub enum ResponsesOutputItem {
         call_id: String,
         output: String,
     },
+    #[serde(rename = "reasoning")]
+    Reasoning {
+        id: String,
+        #[serde(default)]
+        summary: Vec<ResponsesSummaryPart>,
+        /// Opaque reasoning state, returned when the request included
+        /// `reasoning.encrypted_content`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encrypted_content: Option<String>,
+    },
+}
+
+/// A summary part of a Responses `reasoning` item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ResponsesSummaryPart {
+    #[serde(rename = "summary_text")]
+    SummaryText { text: String },
 }
 
 /// A content part in a Responses message.
@@ -102,11 +130,34 @@ pub enum ResponsesOutputItem {
 #[serde(tag = "type")]
 pub enum ResponsesContentPart {
     #[serde(rename = "output_text")]
-    OutputText { text: String },
+    OutputText {
+        text: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        annotations: Vec<ResponsesAnnotation>,
+    },
     #[serde(rename = "refusal")]
     Refusal { refusal: String },
 }
 
+/// An annotation on an `output_text` part. `url_citation` cites `url` for the
+/// character range `start_index..end_index` of the text; the fields of other
+/// annotation types are kept in `extra`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponsesAnnotation {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_index: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_index: Option<usize>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
 /// Usage in the Responses API.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponsesUsage {
@@ -114,6 +165,24 @@ pub struct ResponsesUsage {
     pub output_tokens: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_tokens_details: Option<ResponsesInputTokensDetails>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_tokens_details: Option<ResponsesOutputTokensDetails>,
+}
+
+/// Breakdown of `input_tokens` in the Responses API.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResponsesInputTokensDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_tokens: Option<u64>,
+}
+
+/// Breakdown of `output_tokens` in the Responses API.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResponsesOutputTokensDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<u64>,
 }
 
 /// Responses API streaming event types.
@@ -126,6 +195,8 @@ pub enum ResponsesStreamEvent {
     ResponseInProgress { response: ResponsesOutput },
     #[serde(rename = "response.completed")]
     ResponseCompleted { response: ResponsesOutput },
+    #[serde(rename = "response.incomplete")]
+    ResponseIncomplete { response: ResponsesOutput },
     #[serde(rename = "response.output_item.added")]
     OutputItemAdded {
         output_index: usize,
@@ -167,6 +238,10 @@ pub enum ResponsesStreamEvent {
         output_index: usize,
         arguments: String,
     },
+    #[serde(rename = "response.reasoning_summary_text.delta")]
+    ReasoningSummaryTextDelta { output_index: usize, delta: String },
+    #[serde(rename = "response.reasoning_text.delta")]
+    ReasoningTextDelta { output_index: usize, delta: String },
     #[serde(rename = "error")]
     Error { message: String },
 }
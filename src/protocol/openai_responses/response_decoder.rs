@@ -1,10 +1,199 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use serde::de::{IgnoredAny, MapAccess, Visitor};
+use serde::Deserialize;
+
 use crate::error::CanonicalError;
 use crate::protocol::canonical::{
-    CanonicalPart, CanonicalResponse, CanonicalStopReason, CanonicalUsage,
+    CanonicalCitation, CanonicalPart, CanonicalResponse, CanonicalStopReason, CanonicalUsage,
 };
+use crate::protocol::mapping::responses_incomplete_to_canonical;
 use crate::util::raw_value_from_string;
 
-use super::{ResponsesContentPart, ResponsesOutput, ResponsesOutputItem};
+use super::{
+    ResponsesAnnotation, ResponsesContentPart, ResponsesIncompleteDetails, ResponsesOutput,
+    ResponsesOutputItem, ResponsesSummaryPart, ResponsesUsage,
+};
+
+/// A string borrowed from the response body unless it contains escapes.
+#[derive(Debug, Deserialize)]
+struct BorrowedStr<'a>(#[serde(borrow)] Cow<'a, str>);
+
+/// The top-level Responses object read key by key; the `#[serde(flatten)]`
+/// on [`ResponsesOutput`] buffers the whole body before splitting off extras.
+struct ResponsesFastOutput<'a> {
+    id: Cow<'a, str>,
+    model: Cow<'a, str>,
+    output: Vec<ResponsesFastItem<'a>>,
+    usage: Option<ResponsesUsage>,
+    incomplete_details: Option<ResponsesIncompleteDetails>,
+    extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<'de> Deserialize<'de> for ResponsesFastOutput<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct FastOutputVisitor;
+
+        impl<'de> Visitor<'de> for FastOutputVisitor {
+            type Value = ResponsesFastOutput<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a Responses API output object")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                use serde::de::Error;
+
+                let mut id = None;
+                let mut model = None;
+                let mut output = None;
+                let mut usage = None;
+                let mut incomplete_details = None;
+                let mut has_object = false;
+                let mut extra = serde_json::Map::new();
+                while let Some(BorrowedStr(key)) = map.next_key()? {
+                    match key.as_ref() {
+                        "id" => id = Some(map.next_value::<BorrowedStr<'de>>()?.0),
+                        "model" => model = Some(map.next_value::<BorrowedStr<'de>>()?.0),
+                        "output" => output = Some(map.next_value()?),
+                        "usage" => usage = map.next_value()?,
+                        "incomplete_details" => incomplete_details = map.next_value()?,
+                        "object" => {
+                            map.next_value::<IgnoredAny>()?;
+                            has_object = true;
+                        }
+                        "status" => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                        _ => {
+                            extra.insert(key.into_owned(), map.next_value()?);
+                        }
+                    }
+                }
+                if !has_object {
+                    return Err(A::Error::missing_field("object"));
+                }
+                Ok(ResponsesFastOutput {
+                    id: id.ok_or_else(|| A::Error::missing_field("id"))?,
+                    model: model.ok_or_else(|| A::Error::missing_field("model"))?,
+                    output: output.ok_or_else(|| A::Error::missing_field("output"))?,
+                    usage,
+                    incomplete_details,
+                    extra,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(FastOutputVisitor)
+    }
+}
+
+/// An output item read field by field instead of through the tagged
+/// [`ResponsesOutputItem`] enum, which buffers each item first.
+#[derive(Debug, Deserialize)]
+struct ResponsesFastItem<'a> {
+    #[serde(rename = "type", borrow)]
+    kind: Cow<'a, str>,
+    #[serde(default, borrow)]
+    content: Option<Vec<ResponsesFastPart<'a>>>,
+    #[serde(default, borrow)]
+    call_id: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    name: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    arguments: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    output: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponsesFastPart<'a> {
+    #[serde(rename = "type", borrow)]
+    kind: Cow<'a, str>,
+    #[serde(default, borrow)]
+    text: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    refusal: Option<Cow<'a, str>>,
+    #[serde(default)]
+    annotations: Vec<ResponsesAnnotation>,
+}
+
+/// Attempt to decode a Responses API output directly from bytes, borrowing
+/// strings from `body` instead of building the owned wire types.
+///
+/// Built-in tool calls such as `web_search_call` are left out; the text
+/// annotations cite what they found. Returns `None` when payload is not in
+/// the expected fast-path shape.
+#[must_use]
+pub fn try_decode_responses_output_bytes(body: &[u8]) -> Option<CanonicalResponse> {
+    let parsed: ResponsesFastOutput<'_> = serde_json::from_slice(body).ok()?;
+
+    let mut content = Vec::new();
+    let mut citations = TextCitations::default();
+    let mut has_tool_calls = false;
+    for item in parsed.output {
+        match item.kind.as_ref() {
+            "message" => {
+                for part in item.content? {
+                    content.push(match part.kind.as_ref() {
+                        "output_text" => {
+                            let text = part.text?.into_owned();
+                            citations.push(&text, &part.annotations);
+                            CanonicalPart::Text(text)
+                        }
+                        "refusal" => CanonicalPart::Refusal(part.refusal?.into_owned()),
+                        _ => return None,
+                    });
+                }
+            }
+            "function_call" => {
+                has_tool_calls = true;
+                let arguments =
+                    raw_value_from_string(item.arguments?.into_owned(), "Responses function_call")
+                        .or_else(|_| {
+                            raw_value_from_string("{}".to_string(), "Responses function_call")
+                        })
+                        .ok()?;
+                content.push(CanonicalPart::ToolCall {
+                    id: item.call_id?.into_owned(),
+                    name: item.name?.into_owned(),
+                    arguments,
+                });
+            }
+            "function_call_output" => {
+                has_tool_calls = true;
+                content.push(CanonicalPart::ToolResult {
+                    tool_call_id: item.call_id?.into_owned(),
+                    content: item.output?.into_owned(),
+                });
+            }
+            "web_search_call" => {}
+            _ => return None,
+        }
+    }
+
+    let usage = parsed
+        .usage
+        .as_ref()
+        .map_or_else(CanonicalUsage::default, decode_responses_usage);
+
+    Some(CanonicalResponse {
+        id: parsed.id.into_owned(),
+        model: parsed.model.into_owned(),
+        content,
+        stop_reason: responses_stop_reason(has_tool_calls, parsed.incomplete_details.as_ref()),
+        usage,
+        citations: citations.citations,
+        provider_extensions: parsed.extra,
+    })
+}
 
 /// Decode an `OpenAI` Responses API output into a canonical response.
 ///
@@ -16,6 +205,7 @@ pub fn decode_responses_output(
     output: &ResponsesOutput,
 ) -> Result<CanonicalResponse, CanonicalError> {
     let mut parts = Vec::new();
+    let mut citations = TextCitations::default();
     let mut has_function_call = false;
     let mut has_tool_result = false;
 
@@ -24,7 +214,8 @@ pub fn decode_responses_output(
             ResponsesOutputItem::Message { content, .. } => {
                 for cp in content {
                     match cp {
-                        ResponsesContentPart::OutputText { text } => {
+                        ResponsesContentPart::OutputText { text, annotations } => {
+                            citations.push(text, annotations);
                             parts.push(CanonicalPart::Text(text.clone()));
                         }
                         ResponsesContentPart::Refusal { refusal } => {
@@ -59,27 +250,30 @@ pub fn decode_responses_output(
                     content: output.clone(),
                 });
             }
+            ResponsesOutputItem::Reasoning {
+                id,
+                summary,
+                encrypted_content,
+            } => parts.push(CanonicalPart::ReasoningItem {
+                id: id.clone(),
+                summary: summary
+                    .iter()
+                    .map(|ResponsesSummaryPart::SummaryText { text }| text.clone())
+                    .collect(),
+                encrypted_content: encrypted_content.clone(),
+            }),
         }
     }
 
-    let stop_reason = if has_function_call || has_tool_result {
-        CanonicalStopReason::ToolCalls
-    } else {
-        CanonicalStopReason::EndOfTurn
-    };
+    let stop_reason = responses_stop_reason(
+        has_function_call || has_tool_result,
+        output.incomplete_details.as_ref(),
+    );
 
     let usage = output
         .usage
         .as_ref()
-        .map(|u| {
-            let total = u.total_tokens.unwrap_or(u.input_tokens + u.output_tokens);
-            CanonicalUsage {
-                input_tokens: Some(u.input_tokens),
-                output_tokens: Some(u.output_tokens),
-                total_tokens: Some(total),
-            }
-        })
-        .unwrap_or_default();
+        .map_or_else(CanonicalUsage::default, decode_responses_usage);
 
     Ok(CanonicalResponse {
         id: output.id.clone(),
@@ -87,6 +281,7 @@ pub fn decode_responses_output(
         content: parts,
         stop_reason,
         usage,
+        citations: citations.citations,
         provider_extensions: output.extra.clone(),
     })
 }
@@ -107,10 +302,12 @@ pub fn decode_responses_output_owned(
         output: output_items,
         usage,
         status: _,
+        incomplete_details,
         extra,
     } = output;
 
     let mut content = Vec::new();
+    let mut citations = TextCitations::default();
     let mut has_function_call = false;
     let mut has_tool_result = false;
 
@@ -119,7 +316,8 @@ pub fn decode_responses_output_owned(
             ResponsesOutputItem::Message { content: parts, .. } => {
                 for part in parts {
                     match part {
-                        ResponsesContentPart::OutputText { text } => {
+                        ResponsesContentPart::OutputText { text, annotations } => {
+                            citations.push(&text, &annotations);
                             content.push(CanonicalPart::Text(text));
                         }
                         ResponsesContentPart::Refusal { refusal } => {
@@ -156,25 +354,29 @@ pub fn decode_responses_output_owned(
                     content: value,
                 });
             }
+            ResponsesOutputItem::Reasoning {
+                id,
+                summary,
+                encrypted_content,
+            } => content.push(CanonicalPart::ReasoningItem {
+                id,
+                summary: summary
+                    .into_iter()
+                    .map(|ResponsesSummaryPart::SummaryText { text }| text)
+                    .collect(),
+                encrypted_content,
+            }),
         }
     }
 
-    let stop_reason = if has_function_call || has_tool_result {
-        CanonicalStopReason::ToolCalls
-    } else {
-        CanonicalStopReason::EndOfTurn
-    };
-
-    let usage = usage.map_or_else(CanonicalUsage::default, |usage| {
-        let total_tokens = usage
-            .total_tokens
-            .unwrap_or(usage.input_tokens + usage.output_tokens);
-        CanonicalUsage {
-            input_tokens: Some(usage.input_tokens),
-            output_tokens: Some(usage.output_tokens),
-            total_tokens: Some(total_tokens),
-        }
-    });
+    let stop_reason = responses_stop_reason(
+        has_function_call || has_tool_result,
+        incomplete_details.as_ref(),
+    );
+
+    let usage = usage
+        .as_ref()
+        .map_or_else(CanonicalUsage::default, decode_responses_usage);
 
     Ok(CanonicalResponse {
         id,
@@ -182,10 +384,76 @@ pub fn decode_responses_output_owned(
         content,
         stop_reason,
         usage,
+        citations: citations.citations,
         provider_extensions: extra,
     })
 }
 
+/// `url_citation` annotations of `output_text` parts, located by the
+/// characters of the text before each part.
+#[derive(Default)]
+struct TextCitations {
+    citations: Vec<CanonicalCitation>,
+    offset: usize,
+}
+
+impl TextCitations {
+    fn push(&mut self, text: &str, annotations: &[ResponsesAnnotation]) {
+        let start = self.offset;
+        self.offset += text.chars().count();
+        self.citations
+            .extend(annotations.iter().filter_map(|annotation| {
+                let span = match (annotation.start_index, annotation.end_index) {
+                    (Some(from), Some(to)) => Some(start + from..start + to),
+                    _ => None,
+                };
+                Some(CanonicalCitation {
+                    url: annotation.url.clone()?,
+                    title: annotation.title.clone(),
+                    cited_text: None,
+                    span,
+                })
+            }));
+    }
+}
+
+/// The stop reason of a Responses output: why it is `incomplete`, else tool
+/// calls when it made any.
+#[must_use]
+pub fn responses_stop_reason(
+    has_tool_calls: bool,
+    incomplete_details: Option<&ResponsesIncompleteDetails>,
+) -> CanonicalStopReason {
+    match incomplete_details.and_then(|details| details.reason.as_deref()) {
+        Some(reason) => responses_incomplete_to_canonical(reason),
+        None if has_tool_calls => CanonicalStopReason::ToolCalls,
+        None => CanonicalStopReason::EndOfTurn,
+    }
+}
+
+/// Decode Responses API usage into canonical usage.
+#[must_use]
+pub fn decode_responses_usage(usage: &ResponsesUsage) -> CanonicalUsage {
+    CanonicalUsage {
+        input_tokens: Some(usage.input_tokens),
+        output_tokens: Some(usage.output_tokens),
+        total_tokens: Some(
+            usage
+                .total_tokens
+                .unwrap_or(usage.input_tokens + usage.output_tokens),
+        ),
+        cached_input_tokens: usage
+            .input_tokens_details
+            .as_ref()
+            .and_then(|details| details.cached_tokens),
+        cache_creation_input_tokens: None,
+        reasoning_tokens: usage
+            .output_tokens_details
+            .as_ref()
+            .and_then(|details| details.reasoning_tokens),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,14 +469,18 @@ mod tests {
                 role: "assistant".into(),
                 content: vec![ResponsesContentPart::OutputText {
                     text: "Hello!".into(),
+                    annotations: Vec::new(),
                 }],
             }],
             usage: Some(super::super::ResponsesUsage {
                 input_tokens: 10,
                 output_tokens: 5,
                 total_tokens: Some(15),
+                input_tokens_details: None,
+                output_tokens_details: None,
             }),
             status: Some("completed".into()),
+            incomplete_details: None,
             extra: serde_json::Map::new(),
         };
 
@@ -240,6 +512,7 @@ mod tests {
             ],
             usage: None,
             status: None,
+            incomplete_details: None,
             extra: serde_json::Map::new(),
         };
 
@@ -248,6 +521,41 @@ mod tests {
         assert_eq!(result.content.len(), 1); // only the function call
     }
 
+    #[test]
+    fn test_decode_reasoning_item_roundtrips_encrypted_content() {
+        let body = br#"{"id":"resp_r","object":"response","model":"o3","status":"completed",
+            "output":[{"type":"reasoning","id":"rs_1","encrypted_content":"gAAAA",
+                "summary":[{"type":"summary_text","text":"Step one"},{"type":"summary_text","text":"Step two"}]},
+                {"type":"message","id":"msg_1","role":"assistant",
+                "content":[{"type":"output_text","text":"Done"}]}]}"#;
+        let output: ResponsesOutput = serde_json::from_slice(body).unwrap();
+        let result = decode_responses_output(&output).unwrap();
+        assert!(matches!(
+            result.content.first(),
+            Some(CanonicalPart::ReasoningItem { id, summary, encrypted_content })
+                if id == "rs_1"
+                    && summary == &["Step one", "Step two"]
+                    && encrypted_content.as_deref() == Some("gAAAA")
+        ));
+
+        let encoded =
+            super::super::response_encoder::encode_responses_output(&result, "o3").unwrap();
+        let encoded = serde_json::to_value(&encoded).unwrap();
+        assert_eq!(
+            encoded["output"][0],
+            serde_json::json!({
+                "type": "reasoning",
+                "id": "rs_1",
+                "summary": [
+                    {"type": "summary_text", "text": "Step one"},
+                    {"type": "summary_text", "text": "Step two"}
+                ],
+                "encrypted_content": "gAAAA"
+            })
+        );
+        assert_eq!(encoded["output"][1]["type"], "message");
+    }
+
     #[test]
     fn test_decode_function_call_output_response() {
         let output = ResponsesOutput {
@@ -269,6 +577,7 @@ mod tests {
             ],
             usage: None,
             status: None,
+            incomplete_details: None,
             extra: serde_json::Map::new(),
         };
 
@@ -293,14 +602,18 @@ mod tests {
                 role: "assistant".into(),
                 content: vec![ResponsesContentPart::OutputText {
                     text: "Hello!".into(),
+                    annotations: Vec::new(),
                 }],
             }],
             usage: Some(super::super::ResponsesUsage {
                 input_tokens: 10,
                 output_tokens: 5,
                 total_tokens: Some(15),
+                input_tokens_details: None,
+                output_tokens_details: None,
             }),
             status: Some("completed".into()),
+            incomplete_details: None,
             extra: serde_json::Map::new(),
         };
 
@@ -316,4 +629,32 @@ mod tests {
         assert_eq!(borrowed.stop_reason, owned.stop_reason);
         assert_eq!(borrowed.usage.total_tokens, owned.usage.total_tokens);
     }
+
+    #[test]
+    fn test_try_decode_output_bytes_matches_owned_decode() {
+        let body = br#"{"id":"resp_1","object":"response","created_at":1700000000,
+            "model":"gpt-4o","status":"completed","metadata":{"k":"v"},
+            "output":[
+                {"type":"message","id":"msg_1","role":"assistant","content":[
+                    {"type":"output_text","text":"tab\there","annotations":[]}]},
+                {"type":"function_call","id":"fc_1","call_id":"call_1","name":"lookup",
+                    "arguments":"{\"q\":\"rust\"}"}],
+            "usage":{"input_tokens":4,"output_tokens":6}}"#;
+        let fast = try_decode_responses_output_bytes(body).unwrap();
+        let wire: ResponsesOutput = serde_json::from_slice(body).unwrap();
+        let owned = decode_responses_output_owned(wire).unwrap();
+        assert_eq!(fast.id, owned.id);
+        assert_eq!(fast.model, owned.model);
+        assert_eq!(
+            format!("{:?}", fast.content),
+            format!("{:?}", owned.content)
+        );
+        assert_eq!(fast.stop_reason, owned.stop_reason);
+        assert_eq!(fast.usage.total_tokens, Some(10));
+        assert_eq!(fast.provider_extensions, owned.provider_extensions);
+
+        let reasoning = br#"{"id":"resp_2","object":"response","model":"gpt-4o",
+            "output":[{"type":"reasoning","id":"rs_1","summary":[]}]}"#;
+        assert!(try_decode_responses_output_bytes(reasoning).is_none());
+    }
 }
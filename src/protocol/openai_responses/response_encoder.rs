@@ -1,9 +1,14 @@
 use crate::error::CanonicalError;
-use crate::protocol::canonical::{CanonicalPart, CanonicalResponse};
+use crate::protocol::canonical::{CanonicalCitation, CanonicalPart, CanonicalResponse};
+use crate::protocol::mapping::canonical_stop_to_responses_incomplete;
 use crate::util::next_generated_id;
 use std::sync::atomic::AtomicU64;
 
-use super::{ResponsesContentPart, ResponsesOutput, ResponsesOutputItem, ResponsesUsage};
+use super::{
+    ResponsesAnnotation, ResponsesContentPart, ResponsesIncompleteDetails,
+    ResponsesInputTokensDetails, ResponsesOutput, ResponsesOutputItem,
+    ResponsesOutputTokensDetails, ResponsesSummaryPart, ResponsesUsage,
+};
 
 static GENERATED_RESP_MSG_ID_SEQ: AtomicU64 = AtomicU64::new(1);
 
@@ -22,16 +27,37 @@ pub fn encode_responses_output(
     model: &str,
 ) -> Result<ResponsesOutput, CanonicalError> {
     let mut output_items: Vec<ResponsesOutputItem> = Vec::new();
+    let mut reasoning_items: Vec<ResponsesOutputItem> = Vec::new();
 
     // Collect text and refusal parts into a message output item
     let mut content_parts: Vec<ResponsesContentPart> = Vec::new();
     let mut function_call_index: usize = 0;
     let mut function_result_index: usize = 0;
+    let mut text_offset = 0;
 
     for part in &canonical.content {
         match part {
             CanonicalPart::Text(text) => {
-                content_parts.push(ResponsesContentPart::OutputText { text: text.clone() });
+                let start = text_offset;
+                text_offset += text.chars().count();
+                content_parts.push(ResponsesContentPart::OutputText {
+                    text: text.clone(),
+                    annotations: encode_annotations(&canonical.citations, start..text_offset),
+                });
+            }
+            CanonicalPart::ReasoningItem {
+                id,
+                summary,
+                encrypted_content,
+            } => {
+                reasoning_items.push(ResponsesOutputItem::Reasoning {
+                    id: id.clone(),
+                    summary: summary
+                        .iter()
+                        .map(|text| ResponsesSummaryPart::SummaryText { text: text.clone() })
+                        .collect(),
+                    encrypted_content: encrypted_content.clone(),
+                });
             }
             CanonicalPart::Refusal(refusal) => {
                 content_parts.push(ResponsesContentPart::Refusal {
@@ -78,6 +104,8 @@ pub fn encode_responses_output(
         // Insert message before function calls
         output_items.insert(0, msg_item);
     }
+    // Reasoning precedes everything it led to, as Responses upstreams emit it.
+    output_items.splice(0..0, reasoning_items);
 
     let usage = if canonical.usage.input_tokens.is_some() || canonical.usage.output_tokens.is_some()
     {
@@ -87,22 +115,71 @@ pub fn encode_responses_output(
             input_tokens: input,
             output_tokens: output,
             total_tokens: canonical.usage.total_tokens.or(Some(input + output)),
+            input_tokens_details: canonical.usage.cached_input_tokens.map(|cached_tokens| {
+                ResponsesInputTokensDetails {
+                    cached_tokens: Some(cached_tokens),
+                }
+            }),
+            output_tokens_details: canonical.usage.reasoning_tokens.map(|reasoning_tokens| {
+                ResponsesOutputTokensDetails {
+                    reasoning_tokens: Some(reasoning_tokens),
+                }
+            }),
         })
     } else {
         None
     };
 
+    let incomplete_reason = canonical_stop_to_responses_incomplete(canonical.stop_reason);
     Ok(ResponsesOutput {
         id: canonical.id.clone(),
         object: "response".into(),
         model: model.to_string(),
         output: output_items,
         usage,
-        status: Some("completed".into()),
+        status: Some(
+            if incomplete_reason.is_some() {
+                "incomplete"
+            } else {
+                "completed"
+            }
+            .into(),
+        ),
+        incomplete_details: incomplete_reason.map(|reason| ResponsesIncompleteDetails {
+            reason: Some(reason.to_string()),
+        }),
         extra: canonical.provider_extensions.clone(),
     })
 }
 
+/// `url_citation` annotations for the text part at character range `part` of
+/// the response text: citations whose span starts in it, and citations
+/// without a span on the first part.
+fn encode_annotations(
+    citations: &[CanonicalCitation],
+    part: std::ops::Range<usize>,
+) -> Vec<ResponsesAnnotation> {
+    citations
+        .iter()
+        .filter_map(|citation| {
+            let span = match &citation.span {
+                Some(span) if part.contains(&span.start) => span.clone(),
+                Some(_) => return None,
+                None if part.start == 0 => part.clone(),
+                None => return None,
+            };
+            Some(ResponsesAnnotation {
+                type_: "url_citation".into(),
+                url: Some(citation.url.clone()),
+                title: citation.title.clone(),
+                start_index: Some(span.start - part.start),
+                end_index: Some(span.end.clamp(span.start, part.end) - part.start),
+                extra: serde_json::Map::new(),
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,7 +196,9 @@ mod tests {
                 input_tokens: Some(10),
                 output_tokens: Some(5),
                 total_tokens: Some(15),
+                ..CanonicalUsage::default()
             },
+            citations: Vec::new(),
             provider_extensions: serde_json::Map::new(),
         };
 
@@ -146,6 +225,7 @@ mod tests {
             ],
             stop_reason: CanonicalStopReason::ToolCalls,
             usage: CanonicalUsage::default(),
+            citations: Vec::new(),
             provider_extensions: serde_json::Map::new(),
         };
 
@@ -153,4 +233,37 @@ mod tests {
         // Message first, then function call
         assert_eq!(result.output.len(), 2);
     }
+
+    #[test]
+    fn test_encode_truncated_response_roundtrips_incomplete_reason() {
+        use crate::protocol::openai_responses::response_decoder::{
+            decode_responses_output_owned, try_decode_responses_output_bytes,
+        };
+
+        let canonical = CanonicalResponse {
+            id: "resp_trunc".into(),
+            model: "gpt-4o".into(),
+            content: vec![CanonicalPart::Text("partial".into())],
+            stop_reason: CanonicalStopReason::MaxTokens,
+            usage: CanonicalUsage::default(),
+            citations: Vec::new(),
+            provider_extensions: serde_json::Map::new(),
+        };
+
+        let result = encode_responses_output(&canonical, "gpt-4o").unwrap();
+        assert_eq!(result.status.as_deref(), Some("incomplete"));
+        assert_eq!(
+            result
+                .incomplete_details
+                .as_ref()
+                .and_then(|details| details.reason.as_deref()),
+            Some("max_output_tokens")
+        );
+
+        let body = serde_json::to_vec(&result).unwrap();
+        let fast = try_decode_responses_output_bytes(&body).unwrap();
+        assert_eq!(fast.stop_reason, CanonicalStopReason::MaxTokens);
+        let owned = decode_responses_output_owned(result).unwrap();
+        assert_eq!(owned.stop_reason, CanonicalStopReason::MaxTokens);
+    }
 }
@@ -5,8 +5,12 @@ use rustc_hash::FxHashMap;
 use crate::protocol::canonical::{
     CanonicalRole, CanonicalStopReason, CanonicalStreamEvent, CanonicalUsage,
 };
-use crate::util::{parse_sse_data_json_line, push_json_string_escaped, push_usize_decimal};
+use crate::protocol::mapping::canonical_stop_to_responses_incomplete;
+use crate::util::{
+    parse_sse_data_json_line, push_json_string_escaped, push_u64_decimal, push_usize_decimal,
+};
 
+use super::response_decoder::{decode_responses_usage, responses_stop_reason};
 use super::{ResponsesOutputItem, ResponsesStreamEvent};
 
 /// Parse a single SSE line pair into a `ResponsesStreamEvent`.
@@ -73,11 +77,15 @@ pub fn decode_responses_stream_event_into(
                 tool_call_id: call_id.clone(),
                 content: output.clone(),
             }),
-            ResponsesOutputItem::Message { .. } => {}
+            ResponsesOutputItem::Message { .. } | ResponsesOutputItem::Reasoning { .. } => {}
         },
         ResponsesStreamEvent::OutputTextDelta { delta, .. } => {
             out.push(CanonicalStreamEvent::TextDelta(delta.clone()));
         }
+        ResponsesStreamEvent::ReasoningSummaryTextDelta { delta, .. }
+        | ResponsesStreamEvent::ReasoningTextDelta { delta, .. } => {
+            out.push(CanonicalStreamEvent::ReasoningDelta(delta.clone()));
+        }
         ResponsesStreamEvent::FunctionCallArgumentsDelta {
             output_index,
             delta,
@@ -85,17 +93,11 @@ pub fn decode_responses_stream_event_into(
             index: *output_index,
             delta: delta.clone(),
         }),
-        ResponsesStreamEvent::ResponseCompleted { response } => {
+        ResponsesStreamEvent::ResponseCompleted { response }
+        | ResponsesStreamEvent::ResponseIncomplete { response } => {
             // Extract usage if present
             if let Some(ref usage) = response.usage {
-                let total = usage
-                    .total_tokens
-                    .unwrap_or(usage.input_tokens + usage.output_tokens);
-                out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-                    input_tokens: Some(usage.input_tokens),
-                    output_tokens: Some(usage.output_tokens),
-                    total_tokens: Some(total),
-                }));
+                out.push(CanonicalStreamEvent::Usage(decode_responses_usage(usage)));
             }
 
             // Determine stop reason from output items
@@ -107,11 +109,10 @@ pub fn decode_responses_stream_event_into(
                 .output
                 .iter()
                 .any(|item| matches!(item, ResponsesOutputItem::FunctionCallOutput { .. }));
-            let stop_reason = if has_fc || has_tool_result {
-                CanonicalStopReason::ToolCalls
-            } else {
-                CanonicalStopReason::EndOfTurn
-            };
+            let stop_reason = responses_stop_reason(
+                has_fc || has_tool_result,
+                response.incomplete_details.as_ref(),
+            );
 
             out.push(CanonicalStreamEvent::MessageEnd { stop_reason });
             out.push(CanonicalStreamEvent::Done);
@@ -168,11 +169,15 @@ pub fn decode_responses_stream_event_owned_into(
                 tool_call_id: call_id,
                 content: output,
             }),
-            ResponsesOutputItem::Message { .. } => {}
+            ResponsesOutputItem::Message { .. } | ResponsesOutputItem::Reasoning { .. } => {}
         },
         ResponsesStreamEvent::OutputTextDelta { delta, .. } => {
             out.push(CanonicalStreamEvent::TextDelta(delta));
         }
+        ResponsesStreamEvent::ReasoningSummaryTextDelta { delta, .. }
+        | ResponsesStreamEvent::ReasoningTextDelta { delta, .. } => {
+            out.push(CanonicalStreamEvent::ReasoningDelta(delta));
+        }
         ResponsesStreamEvent::FunctionCallArgumentsDelta {
             output_index,
             delta,
@@ -180,16 +185,10 @@ pub fn decode_responses_stream_event_owned_into(
             index: output_index,
             delta,
         }),
-        ResponsesStreamEvent::ResponseCompleted { response } => {
-            if let Some(usage) = response.usage {
-                let total = usage
-                    .total_tokens
-                    .unwrap_or(usage.input_tokens + usage.output_tokens);
-                out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-                    input_tokens: Some(usage.input_tokens),
-                    output_tokens: Some(usage.output_tokens),
-                    total_tokens: Some(total),
-                }));
+        ResponsesStreamEvent::ResponseCompleted { response }
+        | ResponsesStreamEvent::ResponseIncomplete { response } => {
+            if let Some(ref usage) = response.usage {
+                out.push(CanonicalStreamEvent::Usage(decode_responses_usage(usage)));
             }
 
             let mut has_fc = false;
@@ -198,18 +197,18 @@ pub fn decode_responses_stream_event_owned_into(
                 match item {
                     ResponsesOutputItem::FunctionCall { .. } => has_fc = true,
                     ResponsesOutputItem::FunctionCallOutput { .. } => has_tool_result = true,
-                    ResponsesOutputItem::Message { .. } => {}
+                    ResponsesOutputItem::Message { .. } | ResponsesOutputItem::Reasoning { .. } => {
+                    }
                 }
                 if has_fc && has_tool_result {
                     break;
                 }
             }
 
-            let stop_reason = if has_fc || has_tool_result {
-                CanonicalStopReason::ToolCalls
-            } else {
-                CanonicalStopReason::EndOfTurn
-            };
+            let stop_reason = responses_stop_reason(
+                has_fc || has_tool_result,
+                response.incomplete_details.as_ref(),
+            );
             out.push(CanonicalStreamEvent::MessageEnd { stop_reason });
             out.push(CanonicalStreamEvent::Done);
         }
@@ -475,9 +474,7 @@ where
         | CanonicalStreamEvent::MessageEnd { .. }
         | CanonicalStreamEvent::ReasoningDelta(_) => false,
         CanonicalStreamEvent::Done => {
-            out.push_str("event: response.completed\ndata: ");
-            push_response_envelope_data(out, model, response_id, "response.completed", "completed");
-            out.push_str("\n\n");
+            push_responses_completed_sse_frame(out, model, response_id, None);
             true
         }
         CanonicalStreamEvent::Error { message, .. } => {
@@ -508,6 +505,18 @@ fn push_response_envelope_data(
     response_id: &str,
     event_type: &str,
     status: &str,
+) {
+    push_response_envelope_data_with_usage(out, model, response_id, event_type, status, None, None);
+}
+
+fn push_response_envelope_data_with_usage(
+    out: &mut String,
+    model: &str,
+    response_id: &str,
+    event_type: &str,
+    status: &str,
+    incomplete_reason: Option<&str>,
+    usage: Option<&CanonicalUsage>,
 ) {
     out.push_str("{\"type\":");
     push_json_string_escaped(out, event_type);
@@ -517,9 +526,87 @@ fn push_response_envelope_data(
     push_json_string_escaped(out, model);
     out.push_str(",\"output\":[],\"status\":");
     push_json_string_escaped(out, status);
+    if let Some(reason) = incomplete_reason {
+        out.push_str(",\"incomplete_details\":{\"reason\":");
+        push_json_string_escaped(out, reason);
+        out.push('}');
+    }
+    if let Some(usage) = usage {
+        let input_tokens = usage.input_tokens.unwrap_or(0);
+        let output_tokens = usage.output_tokens.unwrap_or(0);
+        out.push_str(",\"usage\":{\"input_tokens\":");
+        push_u64_decimal(out, input_tokens);
+        out.push_str(",\"output_tokens\":");
+        push_u64_decimal(out, output_tokens);
+        out.push_str(",\"total_tokens\":");
+        push_u64_decimal(
+            out,
+            usage
+                .total_tokens
+                .unwrap_or_else(|| input_tokens.saturating_add(output_tokens)),
+        );
+        if let Some(cached) = usage.cached_input_tokens {
+            out.push_str(",\"input_tokens_details\":{\"cached_tokens\":");
+            push_u64_decimal(out, cached);
+            out.push('}');
+        }
+        if let Some(reasoning) = usage.reasoning_tokens {
+            out.push_str(",\"output_tokens_details\":{\"reasoning_tokens\":");
+            push_u64_decimal(out, reasoning);
+            out.push('}');
+        }
+        out.push('}');
+    }
     out.push_str("}}");
 }
 
+/// Append a `response.completed` SSE frame, embedding token usage when known.
+pub fn push_responses_completed_sse_frame(
+    out: &mut String,
+    model: &str,
+    response_id: &str,
+    usage: Option<&CanonicalUsage>,
+) {
+    out.push_str("event: response.completed\ndata: ");
+    push_response_envelope_data_with_usage(
+        out,
+        model,
+        response_id,
+        "response.completed",
+        "completed",
+        None,
+        usage,
+    );
+    out.push_str("\n\n");
+}
+
+/// Append the terminal SSE frame for a response that stopped for
+/// `stop_reason`: `response.incomplete` with its reason when the Responses
+/// API has one, else `response.completed`.
+pub fn push_responses_terminal_sse_frame(
+    out: &mut String,
+    model: &str,
+    response_id: &str,
+    stop_reason: CanonicalStopReason,
+    usage: Option<&CanonicalUsage>,
+) {
+    let Some(reason) = canonical_stop_to_responses_incomplete(stop_reason) else {
+        push_responses_completed_sse_frame(out, model, response_id, usage);
+        return;
+    };
+    out.push_str("event: response.incomplete\ndata: ");
+    push_response_envelope_data_with_usage(
+        out,
+        model,
+        response_id,
+        "response.incomplete",
+        "incomplete",
+        Some(reason),
+        usage,
+    );
+    out.push_str("\n\n");
+}
+
 #[inline]
 fn next_tool_result_sequence<S>(
     tool_result_seq: &mut HashMap<String, usize, S>,
@@ -606,8 +693,11 @@ mod tests {
                     input_tokens: 10,
                     output_tokens: 5,
                     total_tokens: Some(15),
+                    input_tokens_details: None,
+                    output_tokens_details: None,
                 }),
                 status: Some("completed".into()),
+                incomplete_details: None,
                 extra: serde_json::Map::new(),
             },
         };
@@ -616,6 +706,44 @@ mod tests {
         assert_eq!(canonical.len(), 3);
     }
 
+    #[test]
+    fn test_incomplete_response_stop_reason_roundtrip() {
+        let line = r#"data: {"type":"response.incomplete","response":{"id":"resp_1","object":"response","model":"gpt-4o","output":[],"status":"incomplete","incomplete_details":{"reason":"max_output_tokens"}}}"#;
+        let event = parse_responses_sse_line(line).unwrap();
+        assert!(matches!(
+            decode_responses_stream_event(&event).as_slice(),
+            [
+                CanonicalStreamEvent::MessageEnd {
+                    stop_reason: CanonicalStopReason::MaxTokens
+                },
+                CanonicalStreamEvent::Done
+            ]
+        ));
+
+        let mut frame = String::new();
+        push_responses_terminal_sse_frame(
+            &mut frame,
+            "gpt-4o",
+            "resp_1",
+            CanonicalStopReason::Recitation,
+            None,
+        );
+        assert!(frame.starts_with("event: response.incomplete\n"), "{frame}");
+        assert!(frame
+            .contains(r#""status":"incomplete","incomplete_details":{"reason":"content_filter"}"#));
+
+        frame.clear();
+        push_responses_terminal_sse_frame(
+            &mut frame,
+            "gpt-4o",
+            "resp_1",
+            CanonicalStopReason::StopSequence,
+            None,
+        );
+        assert!(frame.starts_with("event: response.completed\n"), "{frame}");
+        assert!(!frame.contains("incomplete_details"));
+    }
+
     #[test]
     fn test_encode_text_delta() {
         let event = CanonicalStreamEvent::TextDelta("world".into());
@@ -676,6 +804,7 @@ mod tests {
                 }],
                 usage: None,
                 status: Some("completed".into()),
+                incomplete_details: None,
                 extra: serde_json::Map::new(),
             },
         };
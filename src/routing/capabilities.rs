@@ -0,0 +1,156 @@
+//! Model capabilities: which models support image inputs, native tool
+//! calling, and JSON response formats.
+//!
+//! The compat flow detects what a request needs and passes it down in its
+//! [`RequestContext`]; route resolution drops candidates whose model lacks a
+//! required capability.
+//!
+//! [`RequestContext`]: crate::state::request_context::RequestContext
+
+use rustc_hash::FxHashMap;
+
+use crate::config::{AppConfig, ModelCapability};
+use crate::state::request_context;
+
+/// A set of [`ModelCapability`] values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    pub const VISION: Self = Self(1);
+    pub const TOOLS: Self = Self(1 << 1);
+    pub const JSON_MODE: Self = Self(1 << 2);
+
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    #[must_use]
+    pub const fn without(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Config names of the capabilities in the set.
+    #[must_use]
+    pub fn names(self) -> Vec<&'static str> {
+        [
+            (Self::VISION, "vision"),
+            (Self::TOOLS, "tools"),
+            (Self::JSON_MODE, "json_mode"),
+        ]
+        .into_iter()
+        .filter(|(capability, _)| self.0 & capability.0 != 0)
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+impl From<ModelCapability> for Capabilities {
+    fn from(capability: ModelCapability) -> Self {
+        match capability {
+            ModelCapability::Vision => Self::VISION,
+            ModelCapability::Tools => Self::TOOLS,
+            ModelCapability::JsonMode => Self::JSON_MODE,
+        }
+    }
+}
+
+/// The configured capabilities per upstream model.
+#[derive(Debug, Default)]
+pub struct ModelCapabilities {
+    by_model: FxHashMap<String, Capabilities>,
+}
+
+impl ModelCapabilities {
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            by_model: config
+                .features
+                .model_capabilities
+                .iter()
+                .map(|(model, capabilities)| {
+                    let set = capabilities
+                        .iter()
+                        .fold(Capabilities::NONE, |set, &capability| {
+                            set.union(capability.into())
+                        });
+                    (model.clone(), set)
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether any model declares its capabilities.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.by_model.is_empty()
+    }
+
+    /// Capabilities in `required` that `model` lacks; models without a
+    /// declaration lack none.
+    #[must_use]
+    pub fn missing(&self, model: &str, required: Capabilities) -> Capabilities {
+        self.by_model
+            .get(model)
+            .map_or(Capabilities::NONE, |&supported| required.without(supported))
+    }
+}
+
+/// Capabilities the current request needs; none outside a request context.
+#[must_use]
+pub fn required() -> Capabilities {
+    request_context::with(|context| context.required_capabilities).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_capabilities_per_model() {
+        let mut config: AppConfig = serde_yaml::from_str(
+            r"
+upstream_services: []
+client_authentication: { allowed_keys: [key] }
+",
+        )
+        .expect("config yaml");
+        config.features.model_capabilities.insert(
+            "text-only".to_string(),
+            vec![ModelCapability::Tools, ModelCapability::JsonMode],
+        );
+        let capabilities = ModelCapabilities::new(&config);
+        let required = Capabilities::VISION.union(Capabilities::TOOLS);
+        assert_eq!(
+            capabilities.missing("text-only", required),
+            Capabilities::VISION
+        );
+        assert!(capabilities.missing("undeclared", required).is_empty());
+        assert_eq!(required.names(), ["vision", "tools"]);
+    }
+
+    #[tokio::test]
+    async fn test_required_capabilities_are_scoped() {
+        assert!(required().is_empty());
+        assert_eq!(
+            request_context::scope(
+                request_context::RequestContext {
+                    required_capabilities: Capabilities::JSON_MODE,
+                    ..Default::default()
+                },
+                async { required() }
+            )
+            .await
+            .0,
+            Capabilities::JSON_MODE
+        );
+    }
+}
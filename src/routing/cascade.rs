@@ -0,0 +1,161 @@
+//! Cascade models: config-defined names served by a cheap draft model, with
+//! the request re-run on a verify model when the draft answer fails checks.
+
+use std::sync::Arc;
+
+use regex_lite::Regex;
+use rustc_hash::FxHashMap;
+
+use crate::config::{AppConfig, CascadeModelConfig};
+use crate::routing::virtual_models::VirtualModel;
+
+/// Which cascade tier produced a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CascadeTier {
+    Draft,
+    Verify,
+}
+
+impl CascadeTier {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Verify => "verify",
+        }
+    }
+}
+
+/// A resolved cascade model with its checks compiled.
+#[derive(Debug)]
+pub struct CascadeModel {
+    pub name: String,
+    /// Routes the request to the draft model while reporting `name`.
+    pub draft: Arc<VirtualModel>,
+    /// Routes the request to the verify model while reporting `name`.
+    pub verify: Arc<VirtualModel>,
+    min_chars: Option<usize>,
+    refusal_patterns: Vec<Regex>,
+    require_json: bool,
+}
+
+impl CascadeModel {
+    fn from_config(config: &CascadeModelConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            draft: Arc::new(VirtualModel::routing_only(&config.name, &config.draft)),
+            verify: Arc::new(VirtualModel::routing_only(&config.name, &config.verify)),
+            min_chars: config.checks.min_chars,
+            refusal_patterns: config
+                .checks
+                .refusal_patterns
+                .iter()
+                .filter_map(|pattern| Regex::new(pattern).ok())
+                .collect(),
+            require_json: config.checks.require_json,
+        }
+    }
+
+    /// Why the draft answer must be re-run on the verify model, if it must.
+    ///
+    /// Answers with tool calls are accepted as they are.
+    #[must_use]
+    pub fn draft_rejection(&self, text: &str, has_tool_calls: bool) -> Option<&'static str> {
+        if has_tool_calls {
+            return None;
+        }
+        if self
+            .min_chars
+            .is_some_and(|min_chars| text.chars().count() < min_chars)
+        {
+            return Some("too_short");
+        }
+        if self
+            .refusal_patterns
+            .iter()
+            .any(|pattern| pattern.is_match(text))
+        {
+            return Some("refusal");
+        }
+        if self.require_json && serde_json::from_str::<serde::de::IgnoredAny>(text.trim()).is_err()
+        {
+            return Some("invalid_json");
+        }
+        None
+    }
+}
+
+/// Index of configured cascade models by client-visible name.
+#[derive(Debug, Default)]
+pub struct CascadeModels {
+    by_name: FxHashMap<String, Arc<CascadeModel>>,
+}
+
+impl CascadeModels {
+    /// Build the index; invalid refusal patterns are rejected by config validation.
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Self {
+        let by_name = config
+            .cascade_models
+            .iter()
+            .map(|cascade| {
+                (
+                    cascade.name.clone(),
+                    Arc::new(CascadeModel::from_config(cascade)),
+                )
+            })
+            .collect();
+        Self { by_name }
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Arc<CascadeModel>> {
+        if self.by_name.is_empty() {
+            return None;
+        }
+        self.by_name.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CascadeChecks;
+
+    fn cascade(checks: CascadeChecks) -> CascadeModel {
+        CascadeModel::from_config(&CascadeModelConfig {
+            name: "auto".to_string(),
+            draft: "small".to_string(),
+            verify: "large".to_string(),
+            checks,
+        })
+    }
+
+    #[test]
+    fn test_draft_rejection_applies_checks() {
+        let model = cascade(CascadeChecks {
+            min_chars: Some(5),
+            refusal_patterns: vec![r"(?i)^i can(no|')t".to_string()],
+            require_json: true,
+        });
+        assert_eq!(model.draft.target, "small");
+        assert_eq!(model.verify.name, "auto");
+        assert_eq!(model.draft_rejection("{}", false), Some("too_short"));
+        assert_eq!(
+            model.draft_rejection("I can't help with that", false),
+            Some("refusal")
+        );
+        assert_eq!(
+            model.draft_rejection("not json at all", false),
+            Some("invalid_json")
+        );
+        assert_eq!(model.draft_rejection(r#" {"answer": 4} "#, false), None);
+        assert_eq!(model.draft_rejection("", true), None);
+    }
+
+    #[test]
+    fn test_default_checks_accept_any_answer() {
+        let model = cascade(CascadeChecks::default());
+        assert_eq!(model.draft_rejection("", false), None);
+    }
+}
@@ -0,0 +1,227 @@
+//! CORS for browser clients calling the proxy directly.
+//!
+//! Dispatch answers preflight `OPTIONS` requests itself and lets allowed
+//! origins read every other response. Each ingress API can override the
+//! top-level policy; routes outside the ingress APIs (models, files, admin)
+//! use the top-level one.
+
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+
+use crate::config::{CorsConfig, CorsIngressConfig, CORS_INGRESS_KEYS};
+use crate::protocol::canonical::IngressApi;
+
+/// One compiled CORS policy.
+#[derive(Debug)]
+struct CorsPolicy {
+    /// `None` allows any origin.
+    origins: Option<Vec<String>>,
+    /// `None` allows whatever headers the preflight asks for.
+    allow_headers: Option<HeaderValue>,
+    allow_methods: Option<HeaderValue>,
+    expose_headers: Option<HeaderValue>,
+    max_age: HeaderValue,
+}
+
+impl CorsPolicy {
+    fn new(config: &CorsConfig, overrides: Option<&CorsIngressConfig>) -> Self {
+        let origins = overrides
+            .and_then(|overrides| overrides.allowed_origins.as_ref())
+            .unwrap_or(&config.allowed_origins);
+        let headers = overrides
+            .and_then(|overrides| overrides.allowed_headers.as_ref())
+            .unwrap_or(&config.allowed_headers);
+        let methods = overrides
+            .and_then(|overrides| overrides.allowed_methods.as_ref())
+            .unwrap_or(&config.allowed_methods);
+        let expose = overrides
+            .and_then(|overrides| overrides.expose_headers.as_ref())
+            .unwrap_or(&config.expose_headers);
+        Self {
+            origins: (!origins.iter().any(|origin| origin == "*")).then(|| origins.clone()),
+            allow_headers: if headers.iter().any(|name| name == "*") {
+                None
+            } else {
+                joined(headers)
+            },
+            allow_methods: joined(methods),
+            expose_headers: joined(expose),
+            max_age: HeaderValue::from(config.max_age_secs),
+        }
+    }
+
+    fn allows(&self, origin: &HeaderValue) -> bool {
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        self.origins.as_ref().is_none_or(|origins| {
+            origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        })
+    }
+}
+
+/// The top-level CORS policy and its per-ingress overrides.
+#[derive(Debug)]
+pub struct CorsPolicies {
+    default: CorsPolicy,
+    ingress: [Option<CorsPolicy>; 4],
+}
+
+impl CorsPolicies {
+    #[must_use]
+    pub fn new(config: &CorsConfig) -> Self {
+        Self {
+            default: CorsPolicy::new(config, None),
+            ingress: CORS_INGRESS_KEYS.map(|key| {
+                config
+                    .ingress
+                    .get(key)
+                    .map(|overrides| CorsPolicy::new(config, Some(overrides)))
+            }),
+        }
+    }
+
+    fn policy(&self, ingress: Option<IngressApi>) -> &CorsPolicy {
+        let slot = match ingress {
+            Some(IngressApi::OpenAiChat) => 0,
+            Some(IngressApi::OpenAiResponses) => 1,
+            Some(IngressApi::Anthropic) => 2,
+            Some(IngressApi::Gemini) => 3,
+            None => return &self.default,
+        };
+        self.ingress[slot].as_ref().unwrap_or(&self.default)
+    }
+
+    /// Answer a preflight from `origin` for a route of `ingress`; origins
+    /// outside the policy are refused.
+    #[must_use]
+    pub fn preflight(
+        &self,
+        ingress: Option<IngressApi>,
+        origin: &HeaderValue,
+        request_headers: &HeaderMap,
+    ) -> Response {
+        let policy = self.policy(ingress);
+        if !policy.allows(origin) {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        let headers = response.headers_mut();
+        allow_origin(headers, origin);
+        if let Some(methods) = &policy.allow_methods {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, methods.clone());
+        }
+        let requested_headers = request_headers.get(header::ACCESS_CONTROL_REQUEST_HEADERS);
+        if let Some(allowed) = policy.allow_headers.as_ref().or(requested_headers) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed.clone());
+        }
+        headers.insert(header::ACCESS_CONTROL_MAX_AGE, policy.max_age.clone());
+        response
+    }
+
+    /// Let `origin` read `response`, a response of a route of `ingress`,
+    /// when the policy allows it.
+    pub fn apply(
+        &self,
+        ingress: Option<IngressApi>,
+        origin: &HeaderValue,
+        response: &mut Response,
+    ) {
+        let policy = self.policy(ingress);
+        if !policy.allows(origin) {
+            return;
+        }
+        let headers = response.headers_mut();
+        allow_origin(headers, origin);
+        if let Some(expose) = &policy.expose_headers {
+            headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, expose.clone());
+        }
+    }
+}
+
+fn allow_origin(headers: &mut HeaderMap, origin: &HeaderValue) {
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+    headers.append(header::VARY, HeaderValue::from_static("origin"));
+}
+
+fn joined(values: &[String]) -> Option<HeaderValue> {
+    if values.is_empty() {
+        return None;
+    }
+    HeaderValue::from_str(&values.join(", ")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CorsConfig {
+        serde_yaml::from_str(
+            r#"
+allowed_origins: ["https://app.example.com"]
+expose_headers: ["x-upstream-response-id"]
+ingress:
+  anthropic:
+    allowed_origins: ["*"]
+    allowed_headers: ["*"]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_preflight_uses_the_ingress_override() {
+        let cors = CorsPolicies::new(&config());
+        let origin = HeaderValue::from_static("https://other.example.com");
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(
+            header::ACCESS_CONTROL_REQUEST_HEADERS,
+            HeaderValue::from_static("x-api-key, content-type"),
+        );
+
+        let refused = cors.preflight(Some(IngressApi::OpenAiChat), &origin, &request_headers);
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+
+        let allowed = cors.preflight(Some(IngressApi::Anthropic), &origin, &request_headers);
+        assert_eq!(allowed.status(), StatusCode::NO_CONTENT);
+        let headers = allowed.headers();
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://other.example.com"
+        );
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "x-api-key, content-type"
+        );
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_METHODS],
+            "GET, POST, DELETE"
+        );
+        assert_eq!(headers[header::ACCESS_CONTROL_MAX_AGE], "600");
+    }
+
+    #[test]
+    fn test_apply_only_decorates_allowed_origins() {
+        let cors = CorsPolicies::new(&config());
+        let mut response = StatusCode::OK.into_response();
+        cors.apply(
+            None,
+            &HeaderValue::from_static("https://evil.example.com"),
+            &mut response,
+        );
+        assert!(response.headers().is_empty());
+
+        cors.apply(
+            Some(IngressApi::Gemini),
+            &HeaderValue::from_static("https://APP.example.com"),
+            &mut response,
+        );
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_EXPOSE_HEADERS],
+            "x-upstream-response-id"
+        );
+        assert_eq!(response.headers()[header::VARY], "origin");
+    }
+}
@@ -1,27 +1,123 @@
 use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 
 use axum::body::{self, Body};
 use axum::extract::State;
-use axum::http::{Method, Request, StatusCode};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, Method, Request, StatusCode};
 use axum::response::{IntoResponse, Response};
 
-use crate::api::{anthropic, gemini, health, models, openai_chat, openai_responses};
+use crate::api::files::FileAction;
+use crate::api::message_batches::{self, MessageBatchAction};
+use crate::api::operations::{body_requests_stream, long_poll_requested};
+use crate::api::{
+    admin, anthropic, batches, files, gemini, gemini_openai_compat, health, models, openai_chat,
+    openai_completions, openai_responses, operations, streams,
+};
+use crate::batch::BatchResultKind;
+use crate::error::{into_axum_response, CanonicalError};
+use crate::protocol::canonical::IngressApi;
+use crate::routing::path_matcher::{split_gemini_action, PathMatch, PathMatcher, PathRoute};
+use crate::routing::rules;
 use crate::state::AppState;
 
-const DEFAULT_BODY_LIMIT_BYTES: usize = 2 * 1024 * 1024;
+const GOOG_API_KEY_HEADER: &str = "x-goog-api-key";
 
 enum RouteMatch<'a> {
     Health,
+    Ready,
+    Healthz,
+    Readyz,
+    Startupz,
     Models,
+    Model {
+        id: &'a str,
+    },
+    GeminiModels,
+    GeminiModel {
+        name: &'a str,
+    },
+    AdminCooldowns,
+    AdminLatency,
+    AdminMetrics,
+    AdminConfigValidate,
+    AdminConfigApply,
+    AdminTraces,
+    AdminFcParseFailures,
+    AdminModelsRefresh,
+    AdminAnalytics,
+    AdminTail,
+    AdminTrace {
+        session: &'a str,
+    },
+    AdminResponseId {
+        id: &'a str,
+    },
+    BatchCreate,
+    BatchList,
+    BatchRetrieve {
+        batch_id: &'a str,
+    },
+    BatchCancel {
+        batch_id: &'a str,
+    },
+    BatchResults {
+        batch_id: &'a str,
+        kind: BatchResultKind,
+    },
+    StreamAttach {
+        session: &'a str,
+    },
+    Operation {
+        id: &'a str,
+    },
+    FileUpload,
+    FileList,
+    File {
+        file_id: &'a str,
+        action: FileAction,
+    },
     OpenAiChat,
+    OpenAiCompletions,
     OpenAiResponses,
     Anthropic,
-    Gemini { model_action: &'a str },
+    MessageBatchCreate,
+    MessageBatchList,
+    MessageBatch {
+        batch_id: &'a str,
+        action: MessageBatchAction,
+    },
+    Gemini {
+        model: &'a str,
+        is_stream: bool,
+    },
+    GeminiOpenAiCompat,
     MethodNotAllowed,
     NotFound,
 }
 
+impl RouteMatch<'_> {
+    /// The ingress API whose CORS policy covers the route.
+    fn ingress(&self) -> Option<IngressApi> {
+        match self {
+            Self::OpenAiChat | Self::OpenAiCompletions | Self::GeminiOpenAiCompat => {
+                Some(IngressApi::OpenAiChat)
+            }
+            Self::OpenAiResponses => Some(IngressApi::OpenAiResponses),
+            Self::Anthropic
+            | Self::MessageBatchCreate
+            | Self::MessageBatchList
+            | Self::MessageBatch { .. } => Some(IngressApi::Anthropic),
+            Self::Gemini { .. } | Self::GeminiModels | Self::GeminiModel { .. } => {
+                Some(IngressApi::Gemini)
+            }
+            _ => None,
+        }
+    }
+}
+
 /// Dispatch a raw HTTP request to the matching ingress handler.
 ///
 /// # Errors
@@ -32,45 +128,319 @@ pub async fn dispatch_request(
     base_path: Arc<str>,
     request: Request<Body>,
 ) -> Result<Response, Infallible> {
+    // Requests always run on the latest config generation.
+    let state = state.live();
+    // Tenants are served by their own state under their own base path.
+    let tenant = state
+        .tenant_for(request.uri().path())
+        .map(|(tenant, tenant_base_path)| (Arc::clone(tenant), Arc::clone(tenant_base_path)));
+    let (state, base_path) = tenant.unwrap_or((state, base_path));
+    #[cfg(feature = "grpc")]
+    if state.config.features.enable_grpc_ingress
+        && crate::api::grpc::is_grpc_request(request.headers())
+        // gRPC clients address `/package.Service/Method` without the HTTP base path.
+        && request
+            .uri()
+            .path()
+            .starts_with(crate::api::grpc::SERVICE_PATH_PREFIX)
+    {
+        if request.method() != Method::POST {
+            return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response());
+        }
+        return Ok(crate::api::grpc::serve(state, request).await);
+    }
     let (parts, body) = request.into_parts();
-    let route = match_route(&parts.method, parts.uri.path(), base_path.as_ref());
+    let routing_rule = strip_base_path(parts.uri.path(), &base_path)
+        .and_then(|path| state.routing_rules().evaluate(path, &parts.headers));
+    let Some(origin) = state.cors().and(parts.headers.get(header::ORIGIN)).cloned() else {
+        return Ok(rules::scope(routing_rule, route_request(state, &base_path, parts, body)).await);
+    };
+
+    // Browser clients: answer preflights here and let allowed origins read
+    // the response.
+    let rule_prefix = routing_rule
+        .and_then(|rule| state.routing_rules().path_prefix(rule))
+        .unwrap_or_default();
+    let preflight_method = parts
+        .headers
+        .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+        .and_then(|method| Method::from_bytes(method.as_bytes()).ok())
+        .filter(|_| parts.method == Method::OPTIONS);
+    let route_method = preflight_method.as_ref().unwrap_or(&parts.method);
+    let route = match_route(route_method, parts.uri.path(), &base_path, rule_prefix);
+    let ingress = route.ingress();
+    if preflight_method.is_some() {
+        return Ok(match route {
+            RouteMatch::NotFound => StatusCode::NOT_FOUND.into_response(),
+            RouteMatch::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+            _ => state
+                .cors()
+                .map(|cors| cors.preflight(ingress, &origin, &parts.headers))
+                .unwrap_or_default(),
+        });
+    }
+    let cors_state = Arc::clone(&state);
+    let mut response =
+        rules::scope(routing_rule, route_request(state, &base_path, parts, body)).await;
+    if let Some(cors) = cors_state.cors() {
+        cors.apply(ingress, &origin, &mut response);
+    }
+    Ok(response)
+}
+
+/// Run the handler for the request's route; the active routing rule's path
+/// prefix is stripped after the base path.
+async fn route_request(
+    state: Arc<AppState>,
+    base_path: &str,
+    parts: Parts,
+    body: Body,
+) -> Response {
+    let rule_prefix = rules::active_rule()
+        .and_then(|rule| state.routing_rules().path_prefix(rule))
+        .unwrap_or_default();
+    let route = match_route(&parts.method, parts.uri.path(), base_path, rule_prefix);
+    let ingress = route.ingress();
 
-    let response = match route {
+    match route {
         RouteMatch::Health => health::health_handler(State(state)).into_response(),
+        RouteMatch::Ready => health::ready_handler(State(state)),
+        RouteMatch::Healthz => health::healthz_handler(),
+        RouteMatch::Readyz => health::readyz_handler(State(state), &parts),
+        RouteMatch::Startupz => health::startupz_handler(State(state)),
+        // Google SDKs list models on `/v1/models` too; they send their key in
+        // `x-goog-api-key`.
+        RouteMatch::Models if parts.headers.contains_key(GOOG_API_KEY_HEADER) => {
+            models::gemini_list_handler(State(state), &parts.headers).await
+        }
         RouteMatch::Models => models::handler(State(state), &parts.headers).await,
-        RouteMatch::OpenAiChat => {
-            let body_bytes = match read_request_body(body).await {
+        RouteMatch::Model { id } => {
+            models::retrieve_handler(State(state), id, &parts.headers).await
+        }
+        RouteMatch::GeminiModels => models::gemini_list_handler(State(state), &parts.headers).await,
+        RouteMatch::GeminiModel { name } => {
+            models::gemini_get_handler(State(state), name, &parts.headers).await
+        }
+        RouteMatch::AdminCooldowns => admin::cooldowns_handler(State(state), &parts),
+        RouteMatch::AdminLatency => admin::latency_handler(State(state), &parts),
+        RouteMatch::AdminMetrics => admin::metrics_handler(State(state), &parts),
+        RouteMatch::AdminConfigValidate => {
+            let body_bytes = match read_request_body(&state, ingress, &parts.headers, body).await {
                 Ok(bytes) => bytes,
-                Err(response) => return Ok(response),
+                Err(response) => return response,
             };
-            openai_chat::handler(State(state), parts.headers, body_bytes).await
+            admin::config_validate_handler(State(state), &parts, &body_bytes).await
         }
-        RouteMatch::OpenAiResponses => {
-            let body_bytes = match read_request_body(body).await {
+        RouteMatch::AdminConfigApply => {
+            let body_bytes = match read_request_body(&state, ingress, &parts.headers, body).await {
                 Ok(bytes) => bytes,
-                Err(response) => return Ok(response),
+                Err(response) => return response,
             };
-            openai_responses::handler(State(state), parts.headers, body_bytes).await
+            admin::config_apply_handler(State(state), &parts, &body_bytes)
         }
-        RouteMatch::Anthropic => {
-            let body_bytes = match read_request_body(body).await {
+        RouteMatch::AdminTraces => admin::traces_handler(State(state), &parts),
+        RouteMatch::AdminFcParseFailures => admin::fc_parse_failures_handler(State(state), &parts),
+        RouteMatch::AdminModelsRefresh => admin::models_refresh_handler(State(state), &parts).await,
+        RouteMatch::AdminAnalytics => admin::analytics_handler(State(state), &parts).await,
+        RouteMatch::AdminTail => admin::tail_handler(State(state), &parts),
+        RouteMatch::AdminTrace { session } => admin::trace_handler(State(state), session, &parts),
+        RouteMatch::AdminResponseId { id } => admin::response_id_handler(State(state), id, &parts),
+        RouteMatch::BatchCreate => {
+            // Batch input files are uploaded inline with the create call.
+            let body_bytes = match read_upload_body(
+                &state,
+                IngressApi::OpenAiChat,
+                &parts.headers,
+                body,
+            )
+            .await
+            {
                 Ok(bytes) => bytes,
-                Err(response) => return Ok(response),
+                Err(response) => return response,
             };
-            anthropic::handler(State(state), parts.headers, body_bytes).await
+            batches::create_handler(State(state), parts.headers, body_bytes).await
         }
-        RouteMatch::Gemini { model_action } => {
-            let body_bytes = match read_request_body(body).await {
+        RouteMatch::StreamAttach { session } => {
+            streams::attach_handler(State(state), session, &parts.headers)
+        }
+        RouteMatch::Operation { id } => {
+            operations::poll_handler(State(state), id, &parts.headers).await
+        }
+        RouteMatch::BatchList => batches::list_handler(State(state), &parts.headers),
+        RouteMatch::BatchRetrieve { batch_id } => {
+            batches::retrieve_handler(State(state), batch_id, &parts.headers)
+        }
+        RouteMatch::BatchCancel { batch_id } => {
+            batches::cancel_handler(State(state), batch_id, &parts.headers).await
+        }
+        RouteMatch::BatchResults { batch_id, kind } => {
+            batches::results_handler(State(state), batch_id, kind, &parts.headers).await
+        }
+        RouteMatch::FileUpload => {
+            let body_bytes = match read_upload_body(
+                &state,
+                IngressApi::OpenAiChat,
+                &parts.headers,
+                body,
+            )
+            .await
+            {
                 Ok(bytes) => bytes,
-                Err(response) => return Ok(response),
+                Err(response) => return response,
+            };
+            files::upload_handler(State(state), parts.headers, body_bytes).await
+        }
+        RouteMatch::FileList => {
+            files::list_handler(State(state), &parts.headers, parts.uri.query()).await
+        }
+        RouteMatch::File { file_id, action } => {
+            files::file_handler(State(state), file_id, action, &parts.headers).await
+        }
+        RouteMatch::OpenAiChat => {
+            serve_model_request(
+                state,
+                ingress,
+                base_path,
+                parts,
+                body,
+                ModelRoute::OpenAiChat,
+            )
+            .await
+        }
+        RouteMatch::OpenAiCompletions => {
+            let route = ModelRoute::OpenAiCompletions;
+            serve_model_request(state, ingress, base_path, parts, body, route).await
+        }
+        RouteMatch::OpenAiResponses => {
+            let route = ModelRoute::OpenAiResponses;
+            serve_model_request(state, ingress, base_path, parts, body, route).await
+        }
+        RouteMatch::Anthropic => {
+            serve_model_request(
+                state,
+                ingress,
+                base_path,
+                parts,
+                body,
+                ModelRoute::Anthropic,
+            )
+            .await
+        }
+        RouteMatch::MessageBatchCreate => {
+            let body_bytes =
+                match read_upload_body(&state, IngressApi::Anthropic, &parts.headers, body).await {
+                    Ok(bytes) => bytes,
+                    Err(response) => return response,
+                };
+            message_batches::create_handler(State(state), parts.headers, body_bytes).await
+        }
+        RouteMatch::MessageBatchList => {
+            message_batches::list_handler(State(state), &parts.headers, parts.uri.query()).await
+        }
+        RouteMatch::MessageBatch { batch_id, action } => {
+            message_batches::batch_handler(State(state), batch_id, action, &parts.headers).await
+        }
+        RouteMatch::Gemini { model, is_stream } => {
+            let route = ModelRoute::Gemini {
+                model: model.to_string(),
+                is_stream,
             };
-            gemini::handler_from_action(state, model_action, parts.headers, body_bytes).await
+            serve_model_request(state, ingress, base_path, parts, body, route).await
+        }
+        RouteMatch::GeminiOpenAiCompat => {
+            let route = ModelRoute::GeminiOpenAiCompat;
+            serve_model_request(state, ingress, base_path, parts, body, route).await
         }
         RouteMatch::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED.into_response(),
         RouteMatch::NotFound => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// A model API route, whose non-streaming requests may be long-polled.
+enum ModelRoute {
+    OpenAiChat,
+    OpenAiCompletions,
+    OpenAiResponses,
+    Anthropic,
+    Gemini { model: String, is_stream: bool },
+    GeminiOpenAiCompat,
+}
+
+impl ModelRoute {
+    async fn serve(self, state: Arc<AppState>, parts: Parts, body: bytes::Bytes) -> Response {
+        let headers = parts.headers;
+        match self {
+            Self::OpenAiChat => openai_chat::handler(State(state), headers, body).await,
+            Self::OpenAiCompletions => {
+                openai_completions::handler(State(state), headers, body).await
+            }
+            Self::OpenAiResponses => openai_responses::handler(State(state), headers, body).await,
+            Self::Anthropic => anthropic::handler(State(state), headers, body).await,
+            Self::Gemini { model, is_stream } => {
+                gemini::handler_from_action(
+                    state,
+                    &model,
+                    is_stream,
+                    parts.uri.query(),
+                    headers,
+                    body,
+                )
+                .await
+            }
+            Self::GeminiOpenAiCompat => {
+                gemini_openai_compat::handler(State(state), headers, body).await
+            }
+        }
+    }
+}
+
+/// Read a model request's body and serve it, as a long-poll operation when
+/// the client asked for one and the request does not stream.
+async fn serve_model_request(
+    state: Arc<AppState>,
+    ingress: Option<IngressApi>,
+    base_path: &str,
+    parts: Parts,
+    body: Body,
+    route: ModelRoute,
+) -> Response {
+    let body_bytes = match read_request_body(&state, ingress, &parts.headers, body).await {
+        Ok(bytes) => bytes,
+        Err(response) => return response,
     };
+    if long_poll_requested(&state, &parts.headers) {
+        // Gemini picks streaming by path, the other APIs by a body field.
+        let streams = match &route {
+            ModelRoute::Gemini { is_stream, .. } => *is_stream,
+            _ => body_requests_stream(&body_bytes),
+        };
+        if !streams {
+            let handler_state = Arc::clone(&state);
+            let handler = || route.serve(handler_state, parts, body_bytes);
+            return serve_long_poll(&state, ingress, base_path, handler).await;
+        }
+    }
+    route.serve(state, parts, body_bytes).await
+}
 
-    Ok(response)
+/// Serve a non-streaming model request as a long-poll operation.
+///
+/// The handler future is built and boxed here rather than in the dispatch
+/// future, which stays as small as without long polling.
+fn serve_long_poll<'a, Fut>(
+    state: &'a AppState,
+    ingress: Option<IngressApi>,
+    base_path: &'a str,
+    handler: impl FnOnce() -> Fut,
+) -> Pin<Box<dyn Future<Output = Response> + Send + 'a>>
+where
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    let handler = Box::pin(handler());
+    match ingress {
+        Some(ingress) => Box::pin(operations::serve(state, ingress, base_path, handler)),
+        None => handler,
+    }
 }
 
 #[must_use]
@@ -85,72 +455,271 @@ pub fn normalize_base_path(base_path: &str) -> String {
     }
 }
 
-async fn read_request_body(body: Body) -> Result<bytes::Bytes, Response> {
-    body::to_bytes(body, DEFAULT_BODY_LIMIT_BYTES)
-        .await
-        .map_err(|_| {
-            (
-                StatusCode::PAYLOAD_TOO_LARGE,
-                "Request body too large (max 2MiB)",
-            )
-                .into_response()
-        })
+/// Buffer a request body of at most `server.max_request_bytes`.
+///
+/// A declared `Content-Length` over the limit is rejected before anything is
+/// read. Model API routes answer with their ingress's error shape; admin and
+/// gRPC routes get plain text.
+async fn read_request_body(
+    state: &AppState,
+    ingress: Option<IngressApi>,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<bytes::Bytes, Response> {
+    read_body_up_to(state, ingress, headers, body, state.max_request_bytes()).await
+}
+
+/// Buffer a batch or file upload of at most `server.max_upload_bytes`,
+/// answering an oversized one in `ingress`'s error shape.
+async fn read_upload_body(
+    state: &AppState,
+    ingress: IngressApi,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<bytes::Bytes, Response> {
+    read_body_up_to(
+        state,
+        Some(ingress),
+        headers,
+        body,
+        state.max_upload_bytes(),
+    )
+    .await
+}
+
+async fn read_body_up_to(
+    state: &AppState,
+    ingress: Option<IngressApi>,
+    headers: &HeaderMap,
+    body: Body,
+    limit: usize,
+) -> Result<bytes::Bytes, Response> {
+    let declared_too_large = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok()?.parse::<u64>().ok())
+        .is_some_and(|len| len > limit as u64);
+    if !declared_too_large {
+        if let Ok(bytes) = body::to_bytes(body, limit).await {
+            return Ok(bytes);
+        }
+    }
+    state.record_request_too_large(ingress);
+    let message = format!("request body exceeds {limit} bytes");
+    Err(match ingress {
+        Some(ingress) => into_axum_response(&CanonicalError::RequestTooLarge(message), ingress),
+        None => (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request body too large: {message}"),
+        )
+            .into_response(),
+    })
 }
 
-fn match_route<'a>(method: &Method, path: &'a str, base_path: &str) -> RouteMatch<'a> {
-    let Some(path) = strip_base_path(path, base_path) else {
+fn match_route<'a>(
+    method: &Method,
+    path: &'a str,
+    base_path: &str,
+    rule_prefix: &str,
+) -> RouteMatch<'a> {
+    let Some(PathMatch { route, tail }) = strip_base_path(path, base_path)
+        .and_then(|path| strip_base_path(path, rule_prefix))
+        .and_then(|path| PathMatcher::shared().lookup(path))
+    else {
         return RouteMatch::NotFound;
     };
 
-    match path {
-        "/" => {
-            if method == Method::GET {
-                RouteMatch::Health
-            } else {
-                RouteMatch::MethodNotAllowed
-            }
+    match route {
+        PathRoute::Health => only(method, &Method::GET, RouteMatch::Health),
+        PathRoute::Ready => only(method, &Method::GET, RouteMatch::Ready),
+        PathRoute::Healthz => only(method, &Method::GET, RouteMatch::Healthz),
+        PathRoute::Readyz => only(method, &Method::GET, RouteMatch::Readyz),
+        PathRoute::Startupz => only(method, &Method::GET, RouteMatch::Startupz),
+        PathRoute::Models => only(method, &Method::GET, RouteMatch::Models),
+        PathRoute::GeminiModels => only(method, &Method::GET, RouteMatch::GeminiModels),
+        PathRoute::AdminCooldowns => only(method, &Method::GET, RouteMatch::AdminCooldowns),
+        PathRoute::AdminLatency => only(method, &Method::GET, RouteMatch::AdminLatency),
+        PathRoute::AdminMetrics => only(method, &Method::GET, RouteMatch::AdminMetrics),
+        PathRoute::AdminConfigValidate => {
+            only(method, &Method::POST, RouteMatch::AdminConfigValidate)
         }
-        "/v1/models" => {
-            if method == Method::GET {
-                RouteMatch::Models
-            } else {
-                RouteMatch::MethodNotAllowed
-            }
+        PathRoute::AdminConfigApply => only(method, &Method::POST, RouteMatch::AdminConfigApply),
+        PathRoute::AdminTraces => only(method, &Method::GET, RouteMatch::AdminTraces),
+        PathRoute::AdminFcParseFailures => {
+            only(method, &Method::GET, RouteMatch::AdminFcParseFailures)
         }
-        "/v1/chat/completions" => {
-            if method == Method::POST {
-                RouteMatch::OpenAiChat
-            } else {
-                RouteMatch::MethodNotAllowed
-            }
+        PathRoute::AdminModelsRefresh => {
+            only(method, &Method::POST, RouteMatch::AdminModelsRefresh)
         }
-        "/v1/responses" => {
-            if method == Method::POST {
-                RouteMatch::OpenAiResponses
-            } else {
-                RouteMatch::MethodNotAllowed
-            }
+        PathRoute::AdminAnalytics => only(method, &Method::GET, RouteMatch::AdminAnalytics),
+        PathRoute::AdminTail => only(method, &Method::GET, RouteMatch::AdminTail),
+        PathRoute::Batches => match *method {
+            Method::POST => RouteMatch::BatchCreate,
+            Method::GET => RouteMatch::BatchList,
+            _ => RouteMatch::MethodNotAllowed,
+        },
+        PathRoute::Files => match *method {
+            Method::POST => RouteMatch::FileUpload,
+            Method::GET => RouteMatch::FileList,
+            _ => RouteMatch::MethodNotAllowed,
+        },
+        PathRoute::OpenAiChat => only(method, &Method::POST, RouteMatch::OpenAiChat),
+        PathRoute::GeminiOpenAiCompat => {
+            only(method, &Method::POST, RouteMatch::GeminiOpenAiCompat)
         }
-        "/v1/messages" => {
-            if method == Method::POST {
-                RouteMatch::Anthropic
-            } else {
-                RouteMatch::MethodNotAllowed
-            }
+        PathRoute::OpenAiCompletions => only(method, &Method::POST, RouteMatch::OpenAiCompletions),
+        PathRoute::OpenAiResponses => only(method, &Method::POST, RouteMatch::OpenAiResponses),
+        PathRoute::Anthropic => only(method, &Method::POST, RouteMatch::Anthropic),
+        PathRoute::MessageBatches => match *method {
+            Method::POST => RouteMatch::MessageBatchCreate,
+            Method::GET => RouteMatch::MessageBatchList,
+            _ => RouteMatch::MethodNotAllowed,
+        },
+        PathRoute::Batch => match_batch_route(method, tail),
+        PathRoute::MessageBatch => match_message_batch_route(method, tail),
+        PathRoute::File => match_file_route(method, tail),
+        PathRoute::AdminTrace => {
+            match_segment_route(method, tail, |session| RouteMatch::AdminTrace { session })
+        }
+        PathRoute::AdminResponseId => {
+            match_segment_route(method, tail, |id| RouteMatch::AdminResponseId { id })
+        }
+        PathRoute::StreamAttach => {
+            match_segment_route(method, tail, |session| RouteMatch::StreamAttach { session })
+        }
+        PathRoute::Operation => {
+            match_segment_route(method, tail, |id| RouteMatch::Operation { id })
+        }
+        PathRoute::GeminiModel => match_gemini_model_route(method, tail),
+        PathRoute::V1Model => match_v1_model_route(method, tail),
+    }
+}
+
+fn only<'a>(method: &Method, expected: &Method, route: RouteMatch<'a>) -> RouteMatch<'a> {
+    if method == expected {
+        route
+    } else {
+        RouteMatch::MethodNotAllowed
+    }
+}
+
+/// `GET` routes whose tail is a single non-empty path segment.
+fn match_segment_route<'a>(
+    method: &Method,
+    segment: &'a str,
+    route: impl FnOnce(&'a str) -> RouteMatch<'a>,
+) -> RouteMatch<'a> {
+    if method != Method::GET {
+        RouteMatch::MethodNotAllowed
+    } else if segment.is_empty() || segment.contains('/') {
+        RouteMatch::NotFound
+    } else {
+        route(segment)
+    }
+}
+
+/// `models/{model}:{action}` calls and `models/{model}` lookups on the
+/// Gemini `v1beta` API.
+fn match_gemini_model_route<'a>(method: &Method, model_action: &'a str) -> RouteMatch<'a> {
+    if model_action.is_empty() {
+        return RouteMatch::NotFound;
+    }
+    match *method {
+        Method::POST => {
+            let (model, is_stream) = split_gemini_action(model_action);
+            RouteMatch::Gemini { model, is_stream }
+        }
+        Method::GET if model_action.contains([':', '/']) => RouteMatch::NotFound,
+        Method::GET => RouteMatch::GeminiModel { name: model_action },
+        _ => RouteMatch::MethodNotAllowed,
+    }
+}
+
+/// `/v1/models/{model}` is `OpenAI` model retrieval; only Gemini
+/// `{model}:{action}` calls go to the Gemini ingress.
+fn match_v1_model_route<'a>(method: &Method, model: &'a str) -> RouteMatch<'a> {
+    match *method {
+        Method::POST if model.contains(':') => match_gemini_model_route(method, model),
+        Method::GET if !model.is_empty() => RouteMatch::Model { id: model },
+        Method::GET | Method::POST => RouteMatch::NotFound,
+        _ => RouteMatch::MethodNotAllowed,
+    }
+}
+
+fn match_batch_route<'a>(method: &Method, batch_path: &'a str) -> RouteMatch<'a> {
+    let (batch_id, action) = batch_path
+        .split_once('/')
+        .map_or((batch_path, None), |(id, action)| (id, Some(action)));
+    if batch_id.is_empty() {
+        return RouteMatch::NotFound;
+    }
+    let (expected_method, route) = match action {
+        None => (Method::GET, RouteMatch::BatchRetrieve { batch_id }),
+        Some("cancel") => (Method::POST, RouteMatch::BatchCancel { batch_id }),
+        Some("output") => (
+            Method::GET,
+            RouteMatch::BatchResults {
+                batch_id,
+                kind: BatchResultKind::Output,
+            },
+        ),
+        Some("errors") => (
+            Method::GET,
+            RouteMatch::BatchResults {
+                batch_id,
+                kind: BatchResultKind::Errors,
+            },
+        ),
+        Some(_) => return RouteMatch::NotFound,
+    };
+    if *method == expected_method {
+        route
+    } else {
+        RouteMatch::MethodNotAllowed
+    }
+}
+
+fn match_message_batch_route<'a>(method: &Method, batch_path: &'a str) -> RouteMatch<'a> {
+    let (batch_id, action) = match batch_path.split_once('/') {
+        None => match *method {
+            Method::GET => (batch_path, MessageBatchAction::Retrieve),
+            Method::DELETE => (batch_path, MessageBatchAction::Delete),
+            _ => return RouteMatch::MethodNotAllowed,
+        },
+        Some((batch_id, "cancel")) if *method == Method::POST => {
+            (batch_id, MessageBatchAction::Cancel)
+        }
+        Some((batch_id, "results")) if *method == Method::GET => {
+            (batch_id, MessageBatchAction::Results)
         }
-        _ => {
-            if let Some(model_action) = path.strip_prefix("/v1beta/models/") {
-                if method != Method::POST {
-                    RouteMatch::MethodNotAllowed
-                } else if model_action.is_empty() {
-                    RouteMatch::NotFound
-                } else {
-                    RouteMatch::Gemini { model_action }
-                }
-            } else {
-                RouteMatch::NotFound
+        Some((_, "cancel" | "results")) => return RouteMatch::MethodNotAllowed,
+        Some(_) => return RouteMatch::NotFound,
+    };
+    if batch_id.is_empty() {
+        RouteMatch::NotFound
+    } else {
+        RouteMatch::MessageBatch { batch_id, action }
+    }
+}
+
+fn match_file_route<'a>(method: &Method, file_path: &'a str) -> RouteMatch<'a> {
+    let (file_id, action) = match file_path.split_once('/') {
+        None => match *method {
+            Method::GET => (file_path, FileAction::Retrieve),
+            Method::DELETE => (file_path, FileAction::Delete),
+            _ => return RouteMatch::MethodNotAllowed,
+        },
+        Some((file_id, "content")) => {
+            if *method != Method::GET {
+                return RouteMatch::MethodNotAllowed;
             }
+            (file_id, FileAction::Content)
         }
+        Some(_) => return RouteMatch::NotFound,
+    };
+    if file_id.is_empty() {
+        RouteMatch::NotFound
+    } else {
+        RouteMatch::File { file_id, action }
     }
 }
 
@@ -0,0 +1,110 @@
+//! Fallback chains: per-model ordered lists of other models or aliases whose
+//! routes are tried once the model's own routes fail or are cooling down.
+
+use rustc_hash::FxHashMap;
+use smallvec::SmallVec;
+
+use crate::config::AppConfig;
+use crate::routing::RouteTarget;
+
+/// Index of configured fallback chains by requested model.
+#[derive(Debug, Default)]
+pub struct FallbackChains {
+    by_model: FxHashMap<String, Box<[String]>>,
+}
+
+impl FallbackChains {
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Self {
+        let by_model = config
+            .fallback_chains
+            .iter()
+            .map(|chain| (chain.model.clone(), chain.fallbacks.clone().into()))
+            .collect();
+        Self { by_model }
+    }
+
+    /// Models or aliases tried after `model`, in order.
+    #[must_use]
+    pub fn get(&self, model: &str) -> Option<&[String]> {
+        if self.by_model.is_empty() {
+            return None;
+        }
+        self.by_model.get(model).map(AsRef::as_ref)
+    }
+}
+
+/// Join the routes of each chain entry in chain order, with the routes
+/// `allows` rejects for their entry moved to the tail as best-effort probes.
+/// A route reached through several entries is kept at its first position.
+pub(crate) fn merge_chain_routes<'a>(
+    entry_routes: impl IntoIterator<Item = (&'a str, SmallVec<[RouteTarget<'a>; 4]>)>,
+    allows: impl Fn(&RouteTarget<'a>, &str) -> bool,
+) -> SmallVec<[RouteTarget<'a>; 4]> {
+    let mut allowed: SmallVec<[RouteTarget<'a>; 4]> = SmallVec::new();
+    let mut blocked: SmallVec<[RouteTarget<'a>; 4]> = SmallVec::new();
+    let routes = entry_routes
+        .into_iter()
+        .flat_map(|(entry, routes)| routes.into_iter().map(move |route| (entry, route)));
+    for (entry, route) in routes {
+        let seen = allowed.iter().chain(&blocked).any(|seen| {
+            seen.upstream_index == route.upstream_index && seen.actual_model == route.actual_model
+        });
+        if seen {
+            continue;
+        }
+        if allows(&route, entry) {
+            allowed.push(route);
+        } else {
+            blocked.push(route);
+        }
+    }
+    allowed.extend(blocked);
+    allowed
+}
+
+#[cfg(test)]
+mod tests {
+    use smallvec::smallvec;
+
+    use super::*;
+
+    fn route(upstream_index: usize, actual_model: &str) -> RouteTarget<'_> {
+        RouteTarget {
+            upstream_index,
+            actual_model,
+            known_model_id: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_keeps_chain_order_with_cooling_routes_last() {
+        let merged = merge_chain_routes(
+            [
+                (
+                    "haiku",
+                    smallvec![route(0, "claude-haiku"), route(1, "claude-haiku")],
+                ),
+                (
+                    "gpt-4o-mini",
+                    smallvec![route(2, "gpt-4o-mini"), route(1, "claude-haiku")],
+                ),
+                ("local-llama", smallvec![route(3, "llama")]),
+            ],
+            |route, entry| route.upstream_index != 0 || entry != "haiku",
+        );
+        let order: Vec<_> = merged
+            .iter()
+            .map(|route| (route.upstream_index, route.actual_model))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (1, "claude-haiku"),
+                (2, "gpt-4o-mini"),
+                (3, "llama"),
+                (0, "claude-haiku")
+            ]
+        );
+    }
+}
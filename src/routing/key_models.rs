@@ -0,0 +1,137 @@
+//! Key-scoped model maps: per client key renames of the requested model,
+//! applied before alias, virtual model, and cascade lookup so one tenant can
+//! be served a different model without changing its client.
+
+use std::sync::Arc;
+
+use http::HeaderMap;
+use rustc_hash::FxHashMap;
+
+use crate::auth::{extract_api_key, key_entry_matches};
+use crate::config::AppConfig;
+use crate::protocol::canonical::IngressApi;
+
+use super::virtual_models::VirtualModel;
+
+#[derive(Debug)]
+struct KeyModelMap {
+    /// The `allowed_keys` entry, plaintext or `sha256:` hash.
+    key: String,
+    /// Requested model to a routing-only virtual model for its replacement.
+    models: FxHashMap<String, Arc<VirtualModel>>,
+}
+
+/// Configured model maps, in config order.
+#[derive(Debug, Default)]
+pub struct KeyModelMaps {
+    maps: Vec<KeyModelMap>,
+}
+
+impl KeyModelMaps {
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Self {
+        let maps = config
+            .client_authentication
+            .key_model_maps
+            .iter()
+            .map(|map| KeyModelMap {
+                key: map.key.clone(),
+                models: map
+                    .models
+                    .iter()
+                    .map(|(requested, target)| {
+                        (
+                            requested.clone(),
+                            Arc::new(VirtualModel::routing_only(requested, target)),
+                        )
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self { maps }
+    }
+
+    /// The model served in place of `model` for the client key in `headers`.
+    ///
+    /// The key is only compared against maps that rename `model`, so
+    /// requests for other models pay no hashing cost.
+    #[must_use]
+    pub fn get(
+        &self,
+        ingress: IngressApi,
+        headers: &HeaderMap,
+        model: &str,
+    ) -> Option<&Arc<VirtualModel>> {
+        if self.maps.is_empty() {
+            return None;
+        }
+        let mut client_key = None;
+        for map in &self.maps {
+            let Some(mapped) = map.models.get(model) else {
+                continue;
+            };
+            let key = match client_key {
+                Some(key) => key,
+                None => *client_key.insert(extract_api_key(ingress, headers).ok()?),
+            };
+            if key_entry_matches(&map.key, key) {
+                return Some(mapped);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::hash_api_key;
+
+    fn maps(yaml: &str) -> KeyModelMaps {
+        let mut config: AppConfig = serde_yaml::from_str(
+            r#"
+upstream_services:
+  - name: openai
+    base_url: https://api.openai.com/v1
+    api_key: sk-test
+    models: [gpt-4o, gpt-4o-mini]
+client_authentication:
+  allowed_keys: [tenant-a, tenant-b]
+"#,
+        )
+        .unwrap();
+        config.client_authentication.key_model_maps = serde_yaml::from_str(yaml).unwrap();
+        KeyModelMaps::new(&config)
+    }
+
+    fn bearer(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::AUTHORIZATION,
+            format!("Bearer {key}").parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_only_the_mapped_key_is_renamed() {
+        let maps = maps(&format!(
+            "- key: tenant-a\n  models: {{gpt-4o: gpt-4o-mini}}\n- key: \"{}\"\n  models: {{gpt-4o-mini: gpt-4o}}\n",
+            hash_api_key("tenant-b")
+        ));
+        let chat = IngressApi::OpenAiChat;
+
+        let mapped = maps.get(chat, &bearer("tenant-a"), "gpt-4o").unwrap();
+        assert_eq!(mapped.name, "gpt-4o");
+        assert_eq!(mapped.target, "gpt-4o-mini");
+        assert!(maps.get(chat, &bearer("tenant-a"), "gpt-4o-mini").is_none());
+        assert!(maps.get(chat, &bearer("tenant-b"), "gpt-4o").is_none());
+        assert_eq!(
+            maps.get(chat, &bearer("tenant-b"), "gpt-4o-mini")
+                .unwrap()
+                .target,
+            "gpt-4o"
+        );
+        assert!(maps.get(chat, &HeaderMap::new(), "gpt-4o").is_none());
+    }
+}
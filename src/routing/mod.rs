@@ -1,6 +1,15 @@
+pub mod capabilities;
+pub mod cascade;
+pub mod cors;
 pub mod dispatch;
+pub mod fallback_chains;
+pub mod key_models;
+pub mod path_matcher;
 pub(crate) mod policy;
+pub mod quality_retry;
+pub mod rules;
 pub mod session;
+pub mod virtual_models;
 
 use std::sync::Arc;
 
@@ -364,7 +373,8 @@ fn intern_model_id(
 mod tests {
     use super::*;
     use crate::config::{
-        AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, ServerConfig, UpstreamServiceConfig,
+        AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, PathStyle, ServerConfig,
+        StreamSupport, ToolSchemaValidation, UpstreamAuthConfig, UpstreamServiceConfig,
     };
 
     fn make_upstream(name: &str, models: Vec<&str>, is_default: bool) -> UpstreamServiceConfig {
@@ -381,6 +391,17 @@ mod tests {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         }
     }
 
@@ -390,8 +411,16 @@ mod tests {
             upstream_services: services,
             client_authentication: ClientAuthConfig {
                 allowed_keys: vec!["key".to_string()],
+                admin_keys: Vec::new(),
+                key_model_maps: Vec::new(),
             },
             features: FeaturesConfig::default(),
+            virtual_models: Vec::new(),
+            routing_rules: Vec::new(),
+            cascade_models: Vec::new(),
+            admin_authentication: None,
+            fallback_chains: Vec::new(),
+            tenants: Vec::new(),
         }
     }
 
@@ -513,6 +542,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_latency_ordering_keeps_provider_tiers() {
+        let mut cross = make_upstream("c", vec!["gpt-4o"], false);
+        cross.provider = "anthropic".to_string();
+        let config = make_config(vec![
+            make_upstream("slow", vec!["gpt-4o"], false),
+            make_upstream("fast", vec!["gpt-4o"], false),
+            cross,
+            make_upstream("new", vec!["gpt-4o"], false),
+        ]);
+        let router = ModelRouter::new(&config);
+        let prepared: Vec<_> = config
+            .upstream_services
+            .iter()
+            .map(crate::transport::PreparedUpstream::new)
+            .collect();
+        let ttfb = |upstream_index: usize, _model: &str| match upstream_index {
+            0 => Some(500),
+            1 => Some(100),
+            2 => Some(10),
+            _ => None,
+        };
+
+        for hash in 0..32 {
+            let ordered = policy::resolve_routes_with_policy_all_allowed(
+                &router,
+                &prepared,
+                "gpt-4o",
+                hash,
+                Some(&ttfb),
+            )
+            .unwrap();
+            let order: Vec<usize> = ordered.iter().map(|route| route.upstream_index).collect();
+            let same_tier: Vec<usize> = order.iter().copied().filter(|idx| *idx != 2).collect();
+            assert_eq!(same_tier, vec![3, 1, 0]);
+            assert!(order.first() == Some(&2) || order.last() == Some(&2));
+        }
+    }
+
     #[test]
     fn test_no_match_returns_error() {
         let config = make_config(vec![
@@ -0,0 +1,341 @@
+//! Precompiled request path matcher.
+//!
+//! Every route the proxy serves is known up front, so the dispatcher looks
+//! request paths up in a radix tree built once per process instead of
+//! comparing them against each route in turn. Literal routes match the whole
+//! path; prefix routes (`/v1/files/`, `/v1beta/models/`, ...) match the
+//! longest registered prefix and hand back the rest of the path.
+
+use std::sync::LazyLock;
+
+/// A route family, before the request method is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathRoute {
+    Health,
+    Ready,
+    Healthz,
+    Readyz,
+    Startupz,
+    Models,
+    GeminiModels,
+    AdminCooldowns,
+    AdminLatency,
+    AdminMetrics,
+    AdminConfigValidate,
+    AdminConfigApply,
+    AdminTraces,
+    AdminFcParseFailures,
+    AdminModelsRefresh,
+    AdminAnalytics,
+    AdminTail,
+    Batches,
+    Files,
+    OpenAiChat,
+    GeminiOpenAiCompat,
+    OpenAiCompletions,
+    OpenAiResponses,
+    Anthropic,
+    MessageBatches,
+    /// `/v1/batches/{id}[/{action}]`
+    Batch,
+    /// `/v1/messages/batches/{id}[/{action}]`
+    MessageBatch,
+    /// `/v1/files/{id}[/content]`
+    File,
+    /// `/admin/traces/{session}`
+    AdminTrace,
+    /// `/admin/response-ids/{id}`
+    AdminResponseId,
+    /// `/v1/streams/{session}`
+    StreamAttach,
+    /// `/v1/operations/{id}`
+    Operation,
+    /// `/v1beta/models/{model}[:{action}]`
+    GeminiModel,
+    /// `/v1/models/{model}`: `OpenAI` model retrieval, or a Gemini
+    /// `{model}:{action}` call.
+    V1Model,
+}
+
+const LITERAL_ROUTES: &[(&str, PathRoute)] = &[
+    ("/", PathRoute::Health),
+    ("/health/ready", PathRoute::Ready),
+    ("/healthz", PathRoute::Healthz),
+    ("/readyz", PathRoute::Readyz),
+    ("/startupz", PathRoute::Startupz),
+    ("/v1/models", PathRoute::Models),
+    ("/v1beta/openai/models", PathRoute::Models),
+    ("/v1beta/models", PathRoute::GeminiModels),
+    ("/admin/cooldowns", PathRoute::AdminCooldowns),
+    ("/admin/latency", PathRoute::AdminLatency),
+    ("/admin/metrics", PathRoute::AdminMetrics),
+    ("/admin/config/validate", PathRoute::AdminConfigValidate),
+    ("/admin/config/apply", PathRoute::AdminConfigApply),
+    ("/admin/traces", PathRoute::AdminTraces),
+    ("/admin/fc-parse-failures", PathRoute::AdminFcParseFailures),
+    ("/admin/models/refresh", PathRoute::AdminModelsRefresh),
+    ("/admin/analytics", PathRoute::AdminAnalytics),
+    ("/admin/tail", PathRoute::AdminTail),
+    ("/v1/batches", PathRoute::Batches),
+    ("/v1/files", PathRoute::Files),
+    ("/v1/chat/completions", PathRoute::OpenAiChat),
+    (
+        "/v1beta/openai/chat/completions",
+        PathRoute::GeminiOpenAiCompat,
+    ),
+    ("/v1/completions", PathRoute::OpenAiCompletions),
+    ("/v1/responses", PathRoute::OpenAiResponses),
+    ("/v1/messages", PathRoute::Anthropic),
+    ("/v1/messages/batches", PathRoute::MessageBatches),
+];
+
+const PREFIX_ROUTES: &[(&str, PathRoute)] = &[
+    ("/v1/batches/", PathRoute::Batch),
+    ("/v1/messages/batches/", PathRoute::MessageBatch),
+    ("/v1/files/", PathRoute::File),
+    ("/admin/traces/", PathRoute::AdminTrace),
+    ("/admin/response-ids/", PathRoute::AdminResponseId),
+    ("/v1/streams/", PathRoute::StreamAttach),
+    ("/v1/operations/", PathRoute::Operation),
+    ("/v1beta/models/", PathRoute::GeminiModel),
+    ("/v1/models/", PathRoute::V1Model),
+];
+
+static SHARED: LazyLock<PathMatcher> = LazyLock::new(PathMatcher::new);
+
+/// A matched path: the route and, for prefix routes, the path after the prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathMatch<'a> {
+    pub route: PathRoute,
+    pub tail: &'a str,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    /// Path bytes on the edge from the parent.
+    label: Box<[u8]>,
+    literal: Option<PathRoute>,
+    prefix: Option<PathRoute>,
+    /// First byte of each child's label, parallel to `children`.
+    first_bytes: Vec<u8>,
+    children: Vec<usize>,
+}
+
+/// Radix tree over the proxy's literal and prefix routes.
+#[derive(Debug)]
+pub struct PathMatcher {
+    nodes: Vec<Node>,
+}
+
+impl Default for PathMatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PathMatcher {
+    /// Compile the proxy's route table.
+    #[must_use]
+    pub fn new() -> Self {
+        let mut matcher = Self {
+            nodes: vec![Node::default()],
+        };
+        for &(path, route) in LITERAL_ROUTES {
+            let node = matcher.insert(path.as_bytes());
+            matcher.nodes[node].literal = Some(route);
+        }
+        for &(path, route) in PREFIX_ROUTES {
+            let node = matcher.insert(path.as_bytes());
+            matcher.nodes[node].prefix = Some(route);
+        }
+        matcher
+    }
+
+    /// The matcher shared by every dispatched request.
+    #[must_use]
+    pub fn shared() -> &'static Self {
+        &SHARED
+    }
+
+    /// Match a path with the base path and any routing rule prefix removed.
+    ///
+    /// A literal route wins over a prefix route; among prefix routes the
+    /// longest one wins.
+    #[must_use]
+    pub fn lookup<'a>(&self, path: &'a str) -> Option<PathMatch<'a>> {
+        let bytes = path.as_bytes();
+        let mut node = &self.nodes[0];
+        let mut pos = 0;
+        let mut best = None;
+        loop {
+            if let Some(route) = node.prefix {
+                best = Some((route, pos));
+            }
+            let Some(&next) = bytes.get(pos) else {
+                if let Some(route) = node.literal {
+                    return Some(PathMatch { route, tail: "" });
+                }
+                break;
+            };
+            let Some(slot) = node.first_bytes.iter().position(|&byte| byte == next) else {
+                break;
+            };
+            let child = &self.nodes[node.children[slot]];
+            if !bytes[pos..].starts_with(&child.label) {
+                break;
+            }
+            pos += child.label.len();
+            node = child;
+        }
+        // Prefixes end in `/`, so `pos` is on a char boundary.
+        best.map(|(route, pos)| PathMatch {
+            route,
+            tail: &path[pos..],
+        })
+    }
+
+    /// Insert `path`, splitting edges as needed; returns its node.
+    fn insert(&mut self, path: &[u8]) -> usize {
+        let mut current = 0;
+        let mut rest = path;
+        while let Some(&first) = rest.first() {
+            let Some(slot) = self.nodes[current]
+                .first_bytes
+                .iter()
+                .position(|&byte| byte == first)
+            else {
+                let leaf = self.push(Node {
+                    label: rest.into(),
+                    ..Node::default()
+                });
+                self.attach(current, leaf);
+                return leaf;
+            };
+            let child = self.nodes[current].children[slot];
+            let label = &self.nodes[child].label;
+            let common = label
+                .iter()
+                .zip(rest)
+                .take_while(|(left, right)| left == right)
+                .count();
+            if common < label.len() {
+                // Split the edge: `current -> middle -> child`.
+                let (head, tail): (Box<[u8]>, Box<[u8]>) =
+                    (label[..common].into(), label[common..].into());
+                self.nodes[child].label = tail;
+                let middle = self.push(Node {
+                    label: head,
+                    ..Node::default()
+                });
+                self.nodes[current].children[slot] = middle;
+                self.attach(middle, child);
+                current = middle;
+            } else {
+                current = child;
+            }
+            rest = &rest[common..];
+        }
+        current
+    }
+
+    fn push(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn attach(&mut self, parent: usize, child: usize) {
+        let first = self.nodes[child].label[0];
+        self.nodes[parent].first_bytes.push(first);
+        self.nodes[parent].children.push(child);
+    }
+}
+
+/// A Gemini `models/{model}:{action}` path tail split into the model and
+/// whether the action streams.
+#[must_use]
+pub fn split_gemini_action(model_action: &str) -> (&str, bool) {
+    match model_action.rsplit_once(':') {
+        Some((model, action)) => (model, action == "streamGenerateContent"),
+        None => (model_action, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_route_is_reachable() {
+        let matcher = PathMatcher::new();
+        for &(path, route) in LITERAL_ROUTES {
+            assert_eq!(
+                matcher.lookup(path),
+                Some(PathMatch { route, tail: "" }),
+                "{path}"
+            );
+        }
+        for &(path, route) in PREFIX_ROUTES {
+            let full = format!("{path}abc/def");
+            assert_eq!(
+                matcher.lookup(&full),
+                Some(PathMatch {
+                    route,
+                    tail: "abc/def"
+                }),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_lookup_prefers_literal_then_longest_prefix() {
+        let matcher = PathMatcher::new();
+        assert_eq!(
+            matcher.lookup("/v1/messages/batches/msgbatch_1/results"),
+            Some(PathMatch {
+                route: PathRoute::MessageBatch,
+                tail: "msgbatch_1/results"
+            })
+        );
+        assert_eq!(
+            matcher.lookup("/v1/models/gemini-2.5-flash:generateContent"),
+            Some(PathMatch {
+                route: PathRoute::V1Model,
+                tail: "gemini-2.5-flash:generateContent"
+            })
+        );
+        assert_eq!(
+            matcher.lookup("/v1/files/"),
+            Some(PathMatch {
+                route: PathRoute::File,
+                tail: ""
+            })
+        );
+        for path in [
+            "",
+            "/v1",
+            "/v1/message",
+            "/v1/messagesx",
+            "/admin/trace",
+            "/héllo",
+        ] {
+            assert_eq!(matcher.lookup(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_split_gemini_action() {
+        assert_eq!(
+            split_gemini_action("gemini-2.5-flash:streamGenerateContent"),
+            ("gemini-2.5-flash", true)
+        );
+        assert_eq!(
+            split_gemini_action("gemini-2.5-flash:countTokens"),
+            ("gemini-2.5-flash", false)
+        );
+        assert_eq!(
+            split_gemini_action("tunedModels/x"),
+            ("tunedModels/x", false)
+        );
+    }
+}
@@ -36,13 +36,12 @@ fn sticky_bucket_now() -> u64 {
     })
 }
 
-#[must_use]
-pub(crate) fn route_sticky_hash(
+fn session_hasher(
     ingress: IngressApi,
     headers: &http::HeaderMap,
     model: &str,
     prompt_prefix: &[u8],
-) -> u64 {
+) -> rustc_hash::FxHasher {
     let mut hasher = rustc_hash::FxHasher::default();
     if let Some(client_key) = extract_api_key_bytes_for_hash(ingress, headers) {
         hasher.write(client_key);
@@ -52,22 +51,62 @@ pub(crate) fn route_sticky_hash(
     hasher.write_u8(0xfe);
     let prefix_len = prompt_prefix.len().min(ROUTE_STICKY_PREFIX_MAX_BYTES);
     hasher.write(&prompt_prefix[..prefix_len]);
+    hasher
+}
+
+#[must_use]
+pub(crate) fn route_sticky_hash(
+    ingress: IngressApi,
+    headers: &http::HeaderMap,
+    model: &str,
+    prompt_prefix: &[u8],
+) -> u64 {
+    let mut hasher = session_hasher(ingress, headers, model, prompt_prefix);
     hasher.write_u64(sticky_bucket_now());
     mix_u64(hasher.finish())
 }
 
+/// The sticky session hash without its time bucket, so every turn of a
+/// conversation maps to the same value.
+#[must_use]
+pub(crate) fn route_session_hash(
+    ingress: IngressApi,
+    headers: &http::HeaderMap,
+    model: &str,
+    prompt_prefix: &[u8],
+) -> u64 {
+    mix_u64(session_hasher(ingress, headers, model, prompt_prefix).finish())
+}
+
+/// Mean observed TTFB in milliseconds of an `(upstream_index, model_group)` route.
+pub(crate) type RouteTtfbFn<'f> = &'f dyn Fn(usize, &str) -> Option<u64>;
+
+/// Latency-aware ordering: fastest observed routes first.
+///
+/// The sort is stable and runs after the primary provider is fixed, so the
+/// provider tiers below keep their meaning. Routes without samples sort first
+/// so they get measured.
+fn sort_routes_by_ttfb(
+    routes: &mut [RouteTarget<'_>],
+    model: &str,
+    route_ttfb_ms: RouteTtfbFn<'_>,
+) {
+    routes.sort_by_cached_key(|route| route_ttfb_ms(route.upstream_index, model));
+}
+
 pub(crate) fn resolve_routes_with_policy<'a, F>(
     model_router: &'a ModelRouter,
     prepared_upstreams: &[PreparedUpstream],
     model: &'a str,
     request_hash: u64,
     session_class: SessionClass,
+    route_ttfb_ms: Option<RouteTtfbFn<'_>>,
     mut allows_route: F,
 ) -> Result<SmallVec<[RouteTarget<'a>; 4]>, CanonicalError>
 where
     F: FnMut(usize, &str) -> bool,
 {
-    let ordered = model_router.resolve_ordered(model, request_hash)?;
+    let mut ordered = model_router.resolve_ordered(model, request_hash)?;
     if ordered.is_empty() {
         return Err(CanonicalError::InvalidRequest(format!(
             "No upstream found for model '{model}'"
@@ -78,6 +117,9 @@ where
     }
 
     let primary_provider = prepared_upstreams[ordered[0].upstream_index].provider_kind();
+    if let Some(route_ttfb_ms) = route_ttfb_ms {
+        sort_routes_by_ttfb(&mut ordered, model, route_ttfb_ms);
+    }
 
     let mut final_order = SmallVec::<[RouteTarget<'a>; 4]>::with_capacity(ordered.len());
     let mut cross_allowed = SmallVec::<[RouteTarget<'a>; 4]>::new();
@@ -131,8 +173,9 @@ pub(crate) fn resolve_routes_with_policy_all_allowed<'a>(
     prepared_upstreams: &[PreparedUpstream],
     model: &'a str,
     request_hash: u64,
+    route_ttfb_ms: Option<RouteTtfbFn<'_>>,
 ) -> Result<SmallVec<[RouteTarget<'a>; 4]>, CanonicalError> {
-    let ordered = model_router.resolve_ordered(model, request_hash)?;
+    let mut ordered = model_router.resolve_ordered(model, request_hash)?;
     if ordered.is_empty() {
         return Err(CanonicalError::InvalidRequest(format!(
             "No upstream found for model '{model}'"
@@ -143,6 +186,9 @@ pub(crate) fn resolve_routes_with_policy_all_allowed<'a>(
     }
 
     let primary_provider = prepared_upstreams[ordered[0].upstream_index].provider_kind();
+    if let Some(route_ttfb_ms) = route_ttfb_ms {
+        sort_routes_by_ttfb(&mut ordered, model, route_ttfb_ms);
+    }
     let mut final_order = SmallVec::<[RouteTarget<'a>; 4]>::with_capacity(ordered.len());
     let mut saw_cross_provider = false;
     for route in &ordered {
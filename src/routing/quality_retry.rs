@@ -0,0 +1,163 @@
+//! Response-quality retries: a non-streaming answer that is empty, a bare
+//! refusal, or reports zero output tokens is re-run on the next failover
+//! candidate.
+//!
+//! The compat flow re-runs the request with the upstreams that gave such
+//! answers excluded from its [`RequestContext`]; route resolution skips them.
+//!
+//! [`RequestContext`]: crate::state::request_context::RequestContext
+
+use parking_lot::Mutex;
+use regex_lite::Regex;
+use rustc_hash::FxHashMap;
+
+use crate::config::AppConfig;
+
+/// Retries recorded for one upstream, model group, and reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityRetryCount {
+    pub upstream_index: usize,
+    pub model_group: String,
+    pub reason: &'static str,
+    pub count: u64,
+}
+
+/// The configured retry policy with its refusal patterns compiled.
+#[derive(Debug)]
+pub struct QualityRetryPolicy {
+    pub max_retries: usize,
+    refusal_patterns: Vec<Regex>,
+    retries: Mutex<FxHashMap<(usize, String, &'static str), u64>>,
+}
+
+impl QualityRetryPolicy {
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Option<Self> {
+        let quality_retry = config.features.quality_retry.as_ref()?;
+        Some(Self {
+            max_retries: usize::try_from(quality_retry.max_retries).unwrap_or(usize::MAX),
+            refusal_patterns: quality_retry
+                .refusal_patterns
+                .iter()
+                .filter_map(|pattern| Regex::new(&format!("^(?:{pattern})$")).ok())
+                .collect(),
+            retries: Mutex::new(FxHashMap::default()),
+        })
+    }
+
+    /// Why an answer must be retried, if it must.
+    ///
+    /// Answers with tool calls are accepted as they are.
+    #[must_use]
+    pub fn issue(
+        &self,
+        text: &str,
+        has_tool_calls: bool,
+        output_tokens: Option<u64>,
+    ) -> Option<&'static str> {
+        if has_tool_calls {
+            return None;
+        }
+        let text = text.trim();
+        if text.is_empty() {
+            return Some("empty");
+        }
+        if output_tokens == Some(0) {
+            return Some("zero_output_tokens");
+        }
+        if self
+            .refusal_patterns
+            .iter()
+            .any(|pattern| pattern.is_match(text))
+        {
+            return Some("refusal");
+        }
+        None
+    }
+
+    pub fn record_retry(&self, upstream_index: usize, model_group: &str, reason: &'static str) {
+        *self
+            .retries
+            .lock()
+            .entry((upstream_index, model_group.to_string(), reason))
+            .or_default() += 1;
+    }
+
+    /// Retry counts, ordered by upstream, model group, and reason.
+    #[must_use]
+    pub fn retry_counts(&self) -> Vec<QualityRetryCount> {
+        let mut counts: Vec<QualityRetryCount> = self
+            .retries
+            .lock()
+            .iter()
+            .map(
+                |(&(upstream_index, ref model_group, reason), &count)| QualityRetryCount {
+                    upstream_index,
+                    model_group: model_group.clone(),
+                    reason,
+                    count,
+                },
+            )
+            .collect();
+        counts.sort_by(|a, b| {
+            (a.upstream_index, &a.model_group, a.reason).cmp(&(
+                b.upstream_index,
+                &b.model_group,
+                b.reason,
+            ))
+        });
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(refusal_patterns: &[&str]) -> QualityRetryPolicy {
+        let mut config: AppConfig = serde_yaml::from_str(
+            r"
+upstream_services: []
+client_authentication: { allowed_keys: [key] }
+",
+        )
+        .expect("config yaml");
+        config.features.quality_retry = Some(crate::config::QualityRetryConfig {
+            max_retries: 2,
+            refusal_patterns: refusal_patterns.iter().map(ToString::to_string).collect(),
+        });
+        QualityRetryPolicy::new(&config).expect("policy")
+    }
+
+    #[test]
+    fn test_quality_issues() {
+        let policy = policy(&["(?i)i can(no|')t help with that\\.?"]);
+        assert_eq!(policy.issue("  \n", false, Some(3)), Some("empty"));
+        assert_eq!(policy.issue("", true, Some(0)), None);
+        assert_eq!(
+            policy.issue("hi", false, Some(0)),
+            Some("zero_output_tokens")
+        );
+        assert_eq!(
+            policy.issue("I can't help with that.", false, Some(6)),
+            Some("refusal")
+        );
+        // Only bare refusals count; an answer that goes on is kept.
+        assert_eq!(
+            policy.issue("I can't help with that. But here is why...", false, None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_retry_counts_are_grouped() {
+        let policy = policy(&[]);
+        policy.record_retry(1, "gpt-4o", "empty");
+        policy.record_retry(0, "gpt-4o", "refusal");
+        policy.record_retry(1, "gpt-4o", "empty");
+        let counts = policy.retry_counts();
+        assert_eq!(counts.len(), 2);
+        assert_eq!((counts[0].upstream_index, counts[0].count), (0, 1));
+        assert_eq!((counts[1].reason, counts[1].count), ("empty", 2));
+    }
+}
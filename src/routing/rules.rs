@@ -0,0 +1,287 @@
+//! Routing rules: config-defined overrides that pin requests matching a path
+//! prefix or header to one upstream or model group, or to a proxy.
+//!
+//! Dispatch evaluates the rules once per request and runs the handler inside
+//! [`scope`]; route resolution reads the active rule via [`active_rule`].
+//! Rules matching on the requested model are skipped there and consulted by
+//! [`RoutingRules::proxy`] once the body has been parsed.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use http::{HeaderMap, HeaderName};
+
+use crate::config::{AppConfig, RoutingRuleConfig};
+
+tokio::task_local! {
+    static ACTIVE_RULE: Option<usize>;
+}
+
+/// What a matching rule forces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleTarget {
+    /// Index of the only upstream the request may use.
+    Upstream(usize),
+    /// Model or alias group the request is routed as.
+    Model(String),
+}
+
+#[derive(Debug)]
+struct RoutingRule {
+    name: String,
+    path_prefix: Option<String>,
+    header: Option<(HeaderName, String)>,
+    model: Option<String>,
+    target: Option<RuleTarget>,
+    proxy: Option<Arc<str>>,
+}
+
+impl RoutingRule {
+    fn from_config(config: &RoutingRuleConfig, app: &AppConfig) -> Option<Self> {
+        let header = match &config.matcher.header {
+            Some(header) => Some((
+                HeaderName::from_bytes(header.name.as_bytes()).ok()?,
+                header.value.clone(),
+            )),
+            None => None,
+        };
+        let target = match (&config.upstream, &config.model) {
+            (Some(upstream), _) => Some(RuleTarget::Upstream(
+                app.upstream_services
+                    .iter()
+                    .position(|svc| &svc.name == upstream)?,
+            )),
+            (None, Some(model)) => Some(RuleTarget::Model(model.clone())),
+            (None, None) => None,
+        };
+        let proxy = config
+            .proxy
+            .as_deref()
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .map(Arc::from);
+        if target.is_none() && proxy.is_none() {
+            return None;
+        }
+        Some(Self {
+            name: config.name.clone(),
+            path_prefix: config.matcher.path_prefix.clone(),
+            header,
+            model: config.matcher.model.clone(),
+            target,
+            proxy,
+        })
+    }
+
+    fn matches(&self, path: &str, headers: &HeaderMap) -> bool {
+        let path_matches = self.path_prefix.as_deref().is_none_or(|prefix| {
+            path.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+        self.model.is_none() && path_matches && self.header_matches(headers)
+    }
+
+    fn header_matches(&self, headers: &HeaderMap) -> bool {
+        self.header.as_ref().is_none_or(|(name, value)| {
+            headers
+                .get(name)
+                .is_some_and(|actual| actual.as_bytes() == value.as_bytes())
+        })
+    }
+}
+
+/// The configured routing rules, in evaluation order.
+#[derive(Debug, Default)]
+pub struct RoutingRules {
+    rules: Vec<RoutingRule>,
+}
+
+impl RoutingRules {
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            rules: config
+                .routing_rules
+                .iter()
+                .filter_map(|rule| RoutingRule::from_config(rule, config))
+                .collect(),
+        }
+    }
+
+    /// Index of the first rule matching `path` (below the base path) and
+    /// `headers`.
+    #[must_use]
+    pub fn evaluate(&self, path: &str, headers: &HeaderMap) -> Option<usize> {
+        self.rules
+            .iter()
+            .position(|rule| rule.matches(path, headers))
+    }
+
+    #[must_use]
+    pub fn name(&self, rule: usize) -> Option<&str> {
+        self.rules.get(rule).map(|rule| rule.name.as_str())
+    }
+
+    /// Path prefix to strip for `rule`, if it matches on one.
+    #[must_use]
+    pub fn path_prefix(&self, rule: usize) -> Option<&str> {
+        self.rules.get(rule)?.path_prefix.as_deref()
+    }
+
+    #[must_use]
+    pub fn target(&self, rule: usize) -> Option<&RuleTarget> {
+        self.rules.get(rule)?.target.as_ref()
+    }
+
+    /// Proxy for a request to `model`: the active rule's proxy, else that of
+    /// the first model-matching rule whose header matcher also holds.
+    #[must_use]
+    pub fn proxy(
+        &self,
+        active_rule: Option<usize>,
+        model: &str,
+        headers: &HeaderMap,
+    ) -> Option<Arc<str>> {
+        if let Some(proxy) = active_rule.and_then(|rule| self.rules.get(rule)?.proxy.clone()) {
+            return Some(proxy);
+        }
+        self.rules
+            .iter()
+            .find(|rule| {
+                rule.proxy.is_some()
+                    && rule.model.as_deref() == Some(model)
+                    && rule.header_matches(headers)
+            })
+            .and_then(|rule| rule.proxy.clone())
+    }
+}
+
+/// Run `future` with `rule` as the active routing rule.
+pub async fn scope<F: Future>(rule: Option<usize>, future: F) -> F::Output {
+    ACTIVE_RULE.scope(rule, future).await
+}
+
+/// The routing rule matched by the current request, if any.
+///
+/// `None` outside [`scope`].
+#[must_use]
+pub fn active_rule() -> Option<usize> {
+    ACTIVE_RULE.try_with(|rule| *rule).ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{HeaderMatch, RoutingRuleMatch};
+
+    fn rules_config() -> AppConfig {
+        let mut config: AppConfig = serde_yaml::from_str(
+            r#"
+upstream_services:
+  - name: shared
+    provider: openai
+    base_url: https://shared.example/v1
+    api_key: k
+    models: [gpt-4o]
+    is_default: true
+  - name: research
+    provider: openai
+    base_url: https://research.example/v1
+    api_key: k
+    models: [gpt-4o]
+client_authentication:
+  allowed_keys: [c]
+"#,
+        )
+        .unwrap();
+        config.routing_rules = vec![
+            RoutingRuleConfig {
+                name: "research-path".to_string(),
+                matcher: RoutingRuleMatch {
+                    path_prefix: Some("/research".to_string()),
+                    header: None,
+                    model: None,
+                },
+                upstream: Some("research".to_string()),
+                model: None,
+                proxy: Some("http://research-egress:3128".to_string()),
+            },
+            RoutingRuleConfig {
+                name: "batch-team".to_string(),
+                matcher: RoutingRuleMatch {
+                    path_prefix: None,
+                    header: Some(HeaderMatch {
+                        name: "X-Team".to_string(),
+                        value: "batch".to_string(),
+                    }),
+                    model: None,
+                },
+                upstream: None,
+                model: Some("gpt-4o".to_string()),
+                proxy: None,
+            },
+            RoutingRuleConfig {
+                name: "claude-egress".to_string(),
+                matcher: RoutingRuleMatch {
+                    path_prefix: None,
+                    header: None,
+                    model: Some("claude".to_string()),
+                },
+                upstream: None,
+                model: None,
+                proxy: Some("socks5h://egress:1080".to_string()),
+            },
+        ];
+        config
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = RoutingRules::new(&rules_config());
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            rules.evaluate("/research/v1/chat/completions", &headers),
+            Some(0)
+        );
+        assert_eq!(rules.evaluate("/research", &headers), Some(0));
+        assert_eq!(rules.evaluate("/researchers/v1/models", &headers), None);
+        assert_eq!(rules.target(0), Some(&RuleTarget::Upstream(1)));
+        assert_eq!(rules.path_prefix(0), Some("/research"));
+
+        headers.insert("x-team", http::HeaderValue::from_static("batch"));
+        assert_eq!(rules.evaluate("/v1/chat/completions", &headers), Some(1));
+        assert_eq!(rules.evaluate("/research/v1/messages", &headers), Some(0));
+        assert_eq!(rules.name(1), Some("batch-team"));
+
+        headers.insert("x-team", http::HeaderValue::from_static("other"));
+        assert_eq!(rules.evaluate("/v1/chat/completions", &headers), None);
+    }
+
+    #[test]
+    fn test_rule_proxy_selection() {
+        let rules = RoutingRules::new(&rules_config());
+        let headers = HeaderMap::new();
+        assert_eq!(rules.evaluate("/v1/chat/completions", &headers), None);
+        assert_eq!(
+            rules.proxy(None, "claude", &headers).as_deref(),
+            Some("socks5h://egress:1080")
+        );
+        assert_eq!(rules.proxy(None, "gpt-4o", &headers), None);
+        assert_eq!(
+            rules.proxy(Some(0), "claude", &headers).as_deref(),
+            Some("http://research-egress:3128")
+        );
+        assert_eq!(
+            rules.proxy(Some(1), "claude", &headers).as_deref(),
+            Some("socks5h://egress:1080")
+        );
+        assert_eq!(rules.target(2), None);
+    }
+
+    #[tokio::test]
+    async fn test_active_rule_is_scoped() {
+        assert_eq!(active_rule(), None);
+        assert_eq!(scope(Some(3), async { active_rule() }).await, Some(3));
+        assert_eq!(scope(None, async { active_rule() }).await, None);
+    }
+}
@@ -1,3 +1,9 @@
+use std::borrow::Cow;
+use std::hash::Hasher;
+
+use serde_json::{Map, Value};
+
+use crate::config::{StickyHashConfig, StickyHashStrategy};
 use crate::json_scan::{find_top_level_field_value_range, skip_ws};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -53,6 +59,94 @@ pub fn route_prompt_prefix_bytes<'a>(
     trim_and_cap_prefix(&body[..ROUTE_STICKY_PREFIX_MAX_BYTES])
 }
 
+/// Request material hashed for sticky routing under `config`.
+///
+/// The `canonical` strategy yields a digest of the parsed body without the
+/// ignored fields, with object keys sorted and the conversation (`messages`
+/// / `input` / `contents`) cut to its first turn, so later turns of the same
+/// conversation keep their upstream. Bodies that are not a JSON object fall
+/// back to [`route_prompt_prefix_bytes`].
+#[must_use]
+pub fn route_sticky_material<'a>(
+    body: &'a [u8],
+    messages_range: Option<&std::ops::Range<usize>>,
+    config: &StickyHashConfig,
+) -> Cow<'a, [u8]> {
+    if config.strategy == StickyHashStrategy::Canonical {
+        if let Some(digest) = canonical_body_digest(body, &config.ignore_fields) {
+            return Cow::Owned(digest.to_le_bytes().to_vec());
+        }
+    }
+    Cow::Borrowed(route_prompt_prefix_bytes(body, messages_range))
+}
+
+fn canonical_body_digest(body: &[u8], ignore_fields: &[String]) -> Option<u64> {
+    let mut value: Value = serde_json::from_slice(body).ok()?;
+    let object = value.as_object_mut()?;
+    for path in ignore_fields {
+        remove_field_path(object, path);
+    }
+    for field in ["messages", "input", "contents"] {
+        if let Some(Value::Array(turns)) = object.get_mut(field) {
+            turns.truncate(1);
+        }
+    }
+    let mut hasher = rustc_hash::FxHasher::default();
+    hash_canonical_value(&value, &mut hasher);
+    Some(hasher.finish())
+}
+
+fn remove_field_path(object: &mut Map<String, Value>, path: &str) {
+    match path.split_once('.') {
+        Some((head, rest)) => {
+            if let Some(Value::Object(inner)) = object.get_mut(head) {
+                remove_field_path(inner, rest);
+            }
+        }
+        None => {
+            object.remove(path);
+        }
+    }
+}
+
+/// Feed `value` to `hasher` independently of its key order and whitespace.
+fn hash_canonical_value(value: &Value, hasher: &mut impl Hasher) {
+    match value {
+        Value::Null => hasher.write_u8(0),
+        Value::Bool(flag) => {
+            hasher.write_u8(1);
+            hasher.write_u8(u8::from(*flag));
+        }
+        Value::Number(number) => {
+            hasher.write_u8(2);
+            hasher.write(number.to_string().as_bytes());
+        }
+        Value::String(text) => {
+            hasher.write_u8(3);
+            hasher.write(text.as_bytes());
+            hasher.write_u8(0xff);
+        }
+        Value::Array(items) => {
+            hasher.write_u8(4);
+            hasher.write_usize(items.len());
+            for item in items {
+                hash_canonical_value(item, hasher);
+            }
+        }
+        Value::Object(fields) => {
+            hasher.write_u8(5);
+            hasher.write_usize(fields.len());
+            let mut sorted: Vec<_> = fields.iter().collect();
+            sorted.sort_unstable_by_key(|(key, _)| *key);
+            for (key, field) in sorted {
+                hasher.write(key.as_bytes());
+                hasher.write_u8(0xff);
+                hash_canonical_value(field, hasher);
+            }
+        }
+    }
+}
+
 #[must_use]
 pub fn classify_session_class(body: &[u8], has_messages_range: bool) -> SessionClass {
     for field in [
@@ -85,3 +179,51 @@ fn top_level_field_non_null(body: &[u8], field: &[u8]) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material(body: &str, config: &StickyHashConfig) -> Vec<u8> {
+        route_sticky_material(body.as_bytes(), None, config).into_owned()
+    }
+
+    #[test]
+    fn test_canonical_material_ignores_volatile_fields_and_key_order() {
+        let config = StickyHashConfig::default();
+        let first = material(
+            r#"{"model":"m","stream":false,"temperature":0.2,"metadata":{"timestamp":1,"tenant":"a"},
+               "messages":[{"role":"user","content":"hi"}]}"#,
+            &config,
+        );
+        let retry = material(
+            r#"{"messages":[{"content":"hi","role":"user"},{"role":"assistant","content":"yo"}],
+               "metadata":{"tenant":"a","timestamp":2},"temperature":0.9,"model":"m","stream":true}"#,
+            &config,
+        );
+        assert_eq!(first, retry);
+
+        let other_tenant = material(
+            r#"{"model":"m","metadata":{"tenant":"b"},"messages":[{"role":"user","content":"hi"}]}"#,
+            &config,
+        );
+        assert_ne!(first, other_tenant);
+    }
+
+    #[test]
+    fn test_prompt_prefix_strategy_and_non_object_bodies_use_raw_prefix() {
+        let prefix = StickyHashConfig {
+            strategy: StickyHashStrategy::PromptPrefix,
+            ..StickyHashConfig::default()
+        };
+        let body = r#"{"model":"m","messages":[{"role":"user","content":"hi"}]}"#;
+        assert_eq!(
+            material(body, &prefix),
+            br#"[{"role":"user","content":"hi"}]"#.to_vec()
+        );
+        assert_eq!(
+            material("[1, 2]", &StickyHashConfig::default()),
+            b"[1, 2]".to_vec()
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Virtual models: config-defined names that resolve to a real model or alias
+//! plus a fixed system prompt, parameter defaults, and output post-processing.
+
+use std::sync::Arc;
+
+use regex_lite::Regex;
+use rustc_hash::FxHashMap;
+
+use crate::config::{AppConfig, PostprocessStep, VirtualModelConfig, VirtualModelDefaults};
+
+#[derive(Debug)]
+enum CompiledStep {
+    RegexReplace { regex: Regex, replacement: String },
+    TrimAt(Vec<String>),
+}
+
+/// A resolved virtual model with its post-processing steps compiled.
+#[derive(Debug)]
+pub struct VirtualModel {
+    pub name: String,
+    pub target: String,
+    pub system_prompt: Option<String>,
+    pub defaults: VirtualModelDefaults,
+    steps: Vec<CompiledStep>,
+}
+
+impl VirtualModel {
+    fn from_config(config: &VirtualModelConfig) -> Self {
+        let steps = config
+            .postprocess
+            .iter()
+            .filter_map(|step| match step {
+                PostprocessStep::RegexReplace {
+                    pattern,
+                    replacement,
+                } => Regex::new(pattern)
+                    .ok()
+                    .map(|regex| CompiledStep::RegexReplace {
+                        regex,
+                        replacement: replacement.clone(),
+                    }),
+                PostprocessStep::TrimAt { markers } => Some(CompiledStep::TrimAt(
+                    markers
+                        .iter()
+                        .filter(|marker| !marker.is_empty())
+                        .cloned()
+                        .collect(),
+                )),
+            })
+            .collect();
+        Self {
+            name: config.name.clone(),
+            target: config.model.clone(),
+            system_prompt: config.system_prompt.clone(),
+            defaults: config.defaults.clone(),
+            steps,
+        }
+    }
+
+    /// A virtual model that only routes `name` to `target`, without a system
+    /// prompt, defaults, or post-processing.
+    #[must_use]
+    pub(crate) fn routing_only(name: &str, target: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            target: target.to_string(),
+            system_prompt: None,
+            defaults: VirtualModelDefaults::default(),
+            steps: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn has_postprocess(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    /// Post-process a complete assistant text.
+    #[must_use]
+    pub fn postprocess_text(&self, text: &str) -> String {
+        let mut out = text.to_string();
+        for step in &self.steps {
+            match step {
+                CompiledStep::RegexReplace { regex, replacement } => {
+                    out = regex.replace_all(&out, replacement.as_str()).into_owned();
+                }
+                CompiledStep::TrimAt(markers) => {
+                    if let Some(cut) = earliest_marker(&out, markers) {
+                        out.truncate(cut);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Post-process one streamed text delta.
+    ///
+    /// Steps see each delta on its own, so regex matches and trim markers that
+    /// span two deltas are not detected. Once a trim marker is hit, `trimmed`
+    /// is set and every later delta of the same output becomes empty.
+    #[must_use]
+    pub fn postprocess_delta(&self, delta: &str, trimmed: &mut bool) -> String {
+        if *trimmed {
+            return String::new();
+        }
+        let mut out = delta.to_string();
+        for step in &self.steps {
+            match step {
+                CompiledStep::RegexReplace { regex, replacement } => {
+                    out = regex.replace_all(&out, replacement.as_str()).into_owned();
+                }
+                CompiledStep::TrimAt(markers) => {
+                    if let Some(cut) = earliest_marker(&out, markers) {
+                        out.truncate(cut);
+                        *trimmed = true;
+                        break;
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn earliest_marker(text: &str, markers: &[String]) -> Option<usize> {
+    markers
+        .iter()
+        .filter_map(|marker| text.find(marker.as_str()))
+        .min()
+}
+
+/// Index of configured virtual models by client-visible name.
+#[derive(Debug, Default)]
+pub struct VirtualModels {
+    by_name: FxHashMap<String, Arc<VirtualModel>>,
+}
+
+impl VirtualModels {
+    /// Build the index; invalid regex steps are rejected by config validation.
+    #[must_use]
+    pub fn new(config: &AppConfig) -> Self {
+        let by_name = config
+            .virtual_models
+            .iter()
+            .map(|virtual_model| {
+                (
+                    virtual_model.name.clone(),
+                    Arc::new(VirtualModel::from_config(virtual_model)),
+                )
+            })
+            .collect();
+        Self { by_name }
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Arc<VirtualModel>> {
+        if self.by_name.is_empty() {
+            return None;
+        }
+        self.by_name.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn virtual_model(postprocess: Vec<PostprocessStep>) -> VirtualModel {
+        VirtualModel::from_config(&VirtualModelConfig {
+            name: "concise".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            system_prompt: None,
+            defaults: VirtualModelDefaults::default(),
+            postprocess,
+        })
+    }
+
+    #[test]
+    fn test_postprocess_text_applies_steps_in_order() {
+        let model = virtual_model(vec![
+            PostprocessStep::RegexReplace {
+                pattern: r"(?i)as an ai,?\s*".to_string(),
+                replacement: String::new(),
+            },
+            PostprocessStep::TrimAt {
+                markers: vec!["\nUser:".to_string(), "###".to_string()],
+            },
+        ]);
+        assert_eq!(
+            model.postprocess_text("As an AI, the answer is 4.\nUser: more ### x"),
+            "the answer is 4."
+        );
+    }
+
+    #[test]
+    fn test_postprocess_delta_suppresses_text_after_trim_marker() {
+        let model = virtual_model(vec![PostprocessStep::TrimAt {
+            markers: vec!["###".to_string()],
+        }]);
+        let mut trimmed = false;
+        assert_eq!(model.postprocess_delta("hello ", &mut trimmed), "hello ");
+        assert_eq!(
+            model.postprocess_delta("world###tail", &mut trimmed),
+            "world"
+        );
+        assert!(trimmed);
+        assert_eq!(model.postprocess_delta("more", &mut trimmed), "");
+    }
+}
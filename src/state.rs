@@ -1,34 +1,87 @@
 mod fc_policy;
+mod file_bindings;
+mod latency_stats;
 mod models_cache;
+mod moderation_cache;
+mod operations;
+pub(crate) mod request_context;
 mod request_id;
+mod request_limits;
+mod request_priority;
+mod response_ids;
 mod route_breaker;
+mod warmup;
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::Bytes;
+use parking_lot::RwLock;
 use smallvec::SmallVec;
 
-use crate::auth::{authenticate, AllowedClientKeys};
-use crate::config::AppConfig;
+use crate::auth::{authenticate, build_allowed_key_set, AdminAuthenticator, AllowedClientKeys};
+use crate::batch::BatchStore;
+use crate::config::{AppConfig, FcMode, StreamSupport, ToolSchemaValidation};
 use crate::error::CanonicalError;
+use crate::fc::parse_failures::FcParseFailures;
+use crate::fc::prompt::PromptTemplate;
+use crate::fc::PromptTemplates;
+#[cfg(feature = "analytics")]
+use crate::observability::analytics::AnalyticsStore;
+use crate::observability::journal::RequestJournal;
+use crate::observability::tail::LiveTail;
+use crate::observability::traces::ConversationTraces;
+use crate::observability::upstream_errors::note_failed_upstream;
 use crate::protocol::canonical::IngressApi;
+use crate::routing::capabilities::{self, Capabilities, ModelCapabilities};
+use crate::routing::cascade::{CascadeModel, CascadeModels};
+use crate::routing::cors::CorsPolicies;
+use crate::routing::fallback_chains::{merge_chain_routes, FallbackChains};
+use crate::routing::key_models::KeyModelMaps;
 use crate::routing::policy::{
     resolve_routes_with_policy as resolve_routes_with_policy_impl,
     resolve_routes_with_policy_all_allowed as resolve_routes_with_policy_all_allowed_impl,
-    route_sticky_hash as route_sticky_hash_impl,
+    route_session_hash as route_session_hash_impl, route_sticky_hash as route_sticky_hash_impl,
+    RouteTtfbFn,
 };
+use crate::routing::quality_retry::QualityRetryPolicy;
+use crate::routing::rules::{self, RoutingRules, RuleTarget};
 pub use crate::routing::session::SessionClass;
+use crate::routing::virtual_models::{VirtualModel, VirtualModels};
 use crate::routing::{ModelRouter, RouteTarget};
-use crate::transport::{HttpTransport, PreparedUpstream};
+use crate::stream::broadcast::StreamBroadcasts;
+use crate::stream::resumable::ResumableStreams;
+use crate::stream::text_pipeline::OutputPostprocess;
+use crate::transport::response_headers::ResponseHeaderForwarding;
+use crate::transport::{note_retry, HttpTransport, PreparedUpstream, RequestPriority};
 use crate::util::unix_now_secs;
 
 pub use fc_policy::FcDecision;
 use fc_policy::FcPolicyCache;
+use file_bindings::FileBindings;
+pub(crate) use latency_stats::note_served_upstream;
+use latency_stats::LatencyStatsRegistry;
+pub use latency_stats::RouteLatencyStats;
+pub use models_cache::UpstreamModelsStatus;
 use models_cache::{
     build_dynamic_models_response_body, build_initial_models_response_body, ModelsCache,
 };
+use moderation_cache::ModerationCache;
+pub(crate) use moderation_cache::ModerationVerdict;
+pub use operations::{Operation, OperationResult, Operations};
 use request_id::RequestIdGenerator;
+use request_limits::RequestSizeLimits;
+pub use request_limits::{GlobalRequestRejections, UpstreamRequestRejections};
+pub use request_priority::PriorityPermit;
+use request_priority::RequestPriorityPolicy;
+pub(crate) use response_ids::note_response_ids;
+use response_ids::ResponseIdMap;
+pub use response_ids::{ResponseIdMapping, ResponseIds};
+pub use route_breaker::RouteCooldownStatus;
 use route_breaker::{should_try_alternate_upstream, RouteBreakerRegistry};
+pub use warmup::UpstreamWarmup;
+use warmup::WarmupState;
 
 /// Shared application state accessible to all handlers.
 pub struct AppState {
@@ -44,20 +97,72 @@ pub struct AppState {
 
 struct RoutingState {
     upstream_names: Vec<Arc<str>>,
+    /// Upstreams configured with `stream_support: stream_only`.
+    stream_only_upstreams: Vec<usize>,
+    /// Upstreams configured with `stream_support: non_stream_only`.
+    non_stream_only_upstreams: Vec<usize>,
+    file_bindings: FileBindings,
+    /// Message batch ids to the Anthropic upstream that runs them.
+    message_batch_bindings: FileBindings,
+    key_model_maps: KeyModelMaps,
+    virtual_models: VirtualModels,
+    cascade_models: CascadeModels,
+    fallback_chains: FallbackChains,
+    routing_rules: RoutingRules,
+    cors: Option<CorsPolicies>,
+    model_capabilities: ModelCapabilities,
+    quality_retry: Option<QualityRetryPolicy>,
+    prompt_templates: PromptTemplates,
+    output_postprocess: Option<Arc<OutputPostprocess>>,
+    request_limits: RequestSizeLimits,
+    request_priority: Option<RequestPriorityPolicy>,
+    forward_response_headers: Option<Arc<ResponseHeaderForwarding>>,
+    tenants: Vec<Tenant>,
+}
+
+/// A `tenants` entry: requests under `base_path` are served by `state`.
+struct Tenant {
+    base_path: Arc<str>,
+    state: Arc<AppState>,
 }
 
 struct ResilienceState {
     fc_policy_cache: FcPolicyCache,
     route_breakers: RouteBreakerRegistry,
+    latency_stats: LatencyStatsRegistry,
 }
 
 struct CacheState {
     models_cache: ModelsCache,
+    moderation_cache: ModerationCache,
 }
 
 struct InfraState {
     allowed_client_keys: AllowedClientKeys,
+    admin_auth: AdminAuthenticator,
     request_ids: RequestIdGenerator,
+    response_ids: Arc<ResponseIdMap>,
+    journal: Option<Arc<RequestJournal>>,
+    #[cfg(feature = "analytics")]
+    analytics: Option<Arc<AnalyticsStore>>,
+    batch_store: Option<Arc<BatchStore>>,
+    live: Arc<LiveState>,
+    warmup: WarmupState,
+    stream_broadcasts: Arc<StreamBroadcasts>,
+    live_tail: Arc<LiveTail>,
+    conversation_traces: Option<Arc<ConversationTraces>>,
+    resumable_streams: Option<Arc<ResumableStreams>>,
+    operations: Option<Arc<Operations>>,
+    fc_parse_failures: Option<Arc<FcParseFailures>>,
+}
+
+/// The state generation that serves new requests once the config was swapped.
+///
+/// Shared by every generation so a swap is visible from any of them.
+#[derive(Default)]
+struct LiveState {
+    swapped: AtomicBool,
+    current: RwLock<Option<Arc<AppState>>>,
 }
 
 impl AppState {
@@ -70,12 +175,66 @@ impl AppState {
         allowed_client_keys: AllowedClientKeys,
     ) -> Self {
         let models_cache_ttl_secs = config.server.models_cache_ttl_secs;
+        let models_cache_negative_ttl_secs = config.server.models_cache_negative_ttl_secs;
+        let warmup_upstreams = config.server.warmup_upstreams;
+        let admin_auth = AdminAuthenticator::new(&config);
+        let moderation_cache_entries = config
+            .features
+            .moderation
+            .as_ref()
+            .map_or(0, |moderation| moderation.cache_entries);
         let models_response_body = build_initial_models_response_body(&config);
+        let conversation_traces = config
+            .features
+            .conversation_traces
+            .as_ref()
+            .map(|traces| Arc::new(ConversationTraces::new(traces)));
+        let resumable_streams = config
+            .features
+            .resumable_streams
+            .as_ref()
+            .map(|streams| Arc::new(ResumableStreams::new(streams)));
+        let operations = config
+            .features
+            .long_poll
+            .as_ref()
+            .map(|long_poll| Arc::new(Operations::new(long_poll)));
+        let fc_parse_failures = config
+            .features
+            .fc_parse_failures
+            .as_ref()
+            .map(|failures| Arc::new(FcParseFailures::new(failures)));
         let upstream_names: Vec<Arc<str>> = config
             .upstream_services
             .iter()
             .map(|upstream| Arc::from(upstream.name.as_str()))
             .collect();
+        let upstreams_with_support = |support: StreamSupport| -> Vec<usize> {
+            config
+                .upstream_services
+                .iter()
+                .enumerate()
+                .filter(|(_, upstream)| upstream.stream_support == support)
+                .map(|(upstream_index, _)| upstream_index)
+                .collect()
+        };
+        let stream_only_upstreams = upstreams_with_support(StreamSupport::StreamOnly);
+        let non_stream_only_upstreams = upstreams_with_support(StreamSupport::NonStreamOnly);
+        let key_model_maps = KeyModelMaps::new(&config);
+        let virtual_models = VirtualModels::new(&config);
+        let cascade_models = CascadeModels::new(&config);
+        let fallback_chains = FallbackChains::new(&config);
+        let routing_rules = RoutingRules::new(&config);
+        let cors = config.server.cors.as_ref().map(CorsPolicies::new);
+        let model_capabilities = ModelCapabilities::new(&config);
+        let quality_retry = QualityRetryPolicy::new(&config);
+        let prompt_templates = PromptTemplates::new(&config);
+        let output_postprocess =
+            OutputPostprocess::new(&config.features.output_postprocess).map(Arc::new);
+        let request_limits = RequestSizeLimits::new(&config);
+        let request_priority = RequestPriorityPolicy::new(&config);
+        let forward_response_headers =
+            ResponseHeaderForwarding::new(&config.features.forward_response_headers);
         let known_model_count = model_router.known_model_count();
         let upstream_count = prepared_upstreams.len();
         let fc_policy_cache = FcPolicyCache::new(&config, upstream_count, known_model_count);
@@ -85,21 +244,371 @@ impl AppState {
             transport,
             model_router,
             prepared_upstreams,
-            routing: RoutingState { upstream_names },
+            routing: RoutingState {
+                upstream_names,
+                stream_only_upstreams,
+                non_stream_only_upstreams,
+                file_bindings: FileBindings::new(),
+                message_batch_bindings: FileBindings::new(),
+                key_model_maps,
+                virtual_models,
+                cascade_models,
+                fallback_chains,
+                routing_rules,
+                cors,
+                model_capabilities,
+                quality_retry,
+                prompt_templates,
+                output_postprocess,
+                request_limits,
+                request_priority,
+                forward_response_headers,
+                tenants: Vec::new(),
+            },
             resilience: ResilienceState {
                 fc_policy_cache,
                 route_breakers: RouteBreakerRegistry::new(upstream_count),
+                latency_stats: LatencyStatsRegistry::new(upstream_count),
             },
             caches: CacheState {
-                models_cache: ModelsCache::new(models_response_body, models_cache_ttl_secs),
+                models_cache: ModelsCache::new(
+                    models_response_body,
+                    models_cache_ttl_secs,
+                    models_cache_negative_ttl_secs,
+                    upstream_count,
+                ),
+                moderation_cache: ModerationCache::new(moderation_cache_entries),
             },
             infra: InfraState {
                 allowed_client_keys,
+                admin_auth,
                 request_ids: RequestIdGenerator::new(),
+                response_ids: Arc::new(ResponseIdMap::new()),
+                journal: None,
+                #[cfg(feature = "analytics")]
+                analytics: None,
+                batch_store: None,
+                live: Arc::default(),
+                warmup: WarmupState::new(warmup_upstreams),
+                stream_broadcasts: Arc::default(),
+                live_tail: Arc::default(),
+                conversation_traces,
+                resumable_streams,
+                operations,
+                fc_parse_failures,
             },
         }
     }
 
+    /// Build the state for `config`, with a transport sized for its upstreams
+    /// and their proxies.
+    #[must_use]
+    pub fn from_config(config: AppConfig) -> Self {
+        let model_router = ModelRouter::new(&config);
+        let prepared_upstreams: Vec<PreparedUpstream> = config
+            .upstream_services
+            .iter()
+            .map(PreparedUpstream::new)
+            .collect();
+        let allowed_client_keys = build_allowed_key_set(&config);
+        let rule_proxies: Vec<&str> = config
+            .routing_rules
+            .iter()
+            .filter_map(|rule| rule.proxy.as_deref().map(str::trim))
+            .collect();
+        let transport = HttpTransport::new_with_upstream_count_and_proxies(
+            &config.server,
+            config.upstream_services.len(),
+            config
+                .upstream_services
+                .iter()
+                .flat_map(|upstream| {
+                    [
+                        upstream.proxy.as_deref(),
+                        upstream.proxy_stream.as_deref(),
+                        upstream.proxy_non_stream.as_deref(),
+                    ]
+                })
+                .flatten()
+                .chain(rule_proxies.iter().copied()),
+        )
+        .with_request_signers(&config.upstream_services)
+        .with_inline_file_uploads(
+            config.features.inline_file_upload.as_ref(),
+            &config.upstream_services,
+        )
+        .with_upstream_clients(&config.upstream_services, &rule_proxies)
+        .with_service_tiers(
+            config.features.request_priority.is_some(),
+            &prepared_upstreams,
+        );
+        let tenants = config
+            .tenants
+            .iter()
+            .filter_map(|tenant| match config.tenant_config(tenant) {
+                Ok(tenant_config) => {
+                    let state = Self::from_config(tenant_config);
+                    // Tenants share the process; readiness follows the root state.
+                    state.infra.warmup.finish(Vec::new());
+                    Some(Tenant {
+                        base_path: Arc::from(tenant.base_path.as_str()),
+                        state: Arc::new(state),
+                    })
+                }
+                Err(err) => {
+                    tracing::error!("skipping tenant '{}': {err}", tenant.name);
+                    None
+                }
+            })
+            .collect();
+        let mut state = Self::new(
+            config,
+            transport,
+            model_router,
+            prepared_upstreams,
+            allowed_client_keys,
+        );
+        state.routing.tenants = tenants;
+        state.share_infra_with_tenants();
+        state
+    }
+
+    /// The tenant serving `path`, with its base path; the longest matching
+    /// base path wins.
+    #[must_use]
+    pub fn tenant_for(&self, path: &str) -> Option<(&Arc<Self>, &Arc<str>)> {
+        self.routing
+            .tenants
+            .iter()
+            .filter(|tenant| {
+                path.strip_prefix(&*tenant.base_path)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|tenant| tenant.base_path.len())
+            .map(|tenant| (&tenant.state, &tenant.base_path))
+    }
+
+    /// Give every tenant this state's journal, analytics and batch stores,
+    /// stream broadcasts, live tail, and response id map.
+    fn share_infra_with_tenants(&mut self) {
+        for tenant in &mut self.routing.tenants {
+            // Tenant states are only shared once the root state is serving.
+            let Some(state) = Arc::get_mut(&mut tenant.state) else {
+                continue;
+            };
+            state.infra.journal.clone_from(&self.infra.journal);
+            #[cfg(feature = "analytics")]
+            state.infra.analytics.clone_from(&self.infra.analytics);
+            state.infra.batch_store.clone_from(&self.infra.batch_store);
+            state.infra.stream_broadcasts = Arc::clone(&self.infra.stream_broadcasts);
+            state.infra.live_tail = Arc::clone(&self.infra.live_tail);
+            state.infra.response_ids = Arc::clone(&self.infra.response_ids);
+        }
+    }
+
+    /// Take over the upstream bindings of `previous`, in this state and in
+    /// tenants kept under the same base path, remapping upstream indexes by
+    /// name.
+    fn inherit_upstream_bindings(&self, previous: &AppState) {
+        let remap = |upstream_index: usize| {
+            let name = previous.routing.upstream_names.get(upstream_index)?;
+            self.routing
+                .upstream_names
+                .iter()
+                .position(|upstream| upstream == name)
+        };
+        self.routing
+            .file_bindings
+            .inherit(&previous.routing.file_bindings, remap);
+        self.routing
+            .message_batch_bindings
+            .inherit(&previous.routing.message_batch_bindings, remap);
+        for tenant in &self.routing.tenants {
+            let previous_tenant = previous
+                .routing
+                .tenants
+                .iter()
+                .find(|previous_tenant| previous_tenant.base_path == tenant.base_path);
+            if let Some(previous_tenant) = previous_tenant {
+                tenant
+                    .state
+                    .inherit_upstream_bindings(&previous_tenant.state);
+            }
+        }
+    }
+
+    /// The state serving new requests: `self` until a config swap, then the
+    /// latest generation.
+    #[must_use]
+    pub fn live(self: &Arc<Self>) -> Arc<Self> {
+        if !self.infra.live.swapped.load(Ordering::Acquire) {
+            return Arc::clone(self);
+        }
+        self.infra
+            .live
+            .current
+            .read()
+            .clone()
+            .unwrap_or_else(|| Arc::clone(self))
+    }
+
+    /// Serve new requests from a state built for `config`.
+    ///
+    /// In-flight requests finish on the state they started with. The journal,
+    /// analytics store, batch store, stream broadcasts, live tail watchers,
+    /// recorded conversation traces (while tracing stays enabled), resumable
+    /// streams, long-poll operations, FC parse failure samples (while they
+    /// stay enabled), and used admin nonces carry over; route breakers,
+    /// latency stats, and caches start fresh. Uploaded files and message
+    /// batches stay pinned to their upstream while it is still configured
+    /// under the same name.
+    /// Tenants are rebuilt from `config` and share the carried over stores.
+    /// Returns the new generation.
+    pub fn swap_config(&self, config: AppConfig) -> Arc<Self> {
+        let mut next = Self::from_config(config);
+        next.infra.journal.clone_from(&self.infra.journal);
+        #[cfg(feature = "analytics")]
+        next.infra.analytics.clone_from(&self.infra.analytics);
+        next.infra.batch_store.clone_from(&self.infra.batch_store);
+        next.infra.stream_broadcasts = Arc::clone(&self.infra.stream_broadcasts);
+        next.infra.live_tail = Arc::clone(&self.infra.live_tail);
+        next.infra.admin_auth.inherit_nonces(&self.infra.admin_auth);
+        next.infra.response_ids = Arc::clone(&self.infra.response_ids);
+        if next.infra.conversation_traces.is_some() {
+            if let Some(traces) = &self.infra.conversation_traces {
+                next.infra.conversation_traces = Some(Arc::clone(traces));
+            }
+        }
+        if next.infra.resumable_streams.is_some() {
+            if let Some(streams) = &self.infra.resumable_streams {
+                next.infra.resumable_streams = Some(Arc::clone(streams));
+            }
+        }
+        if next.infra.operations.is_some() {
+            if let Some(operations) = &self.infra.operations {
+                next.infra.operations = Some(Arc::clone(operations));
+            }
+        }
+        if next.infra.fc_parse_failures.is_some() {
+            if let Some(failures) = &self.infra.fc_parse_failures {
+                next.infra.fc_parse_failures = Some(Arc::clone(failures));
+            }
+        }
+        next.inherit_upstream_bindings(self);
+        next.infra.live = Arc::clone(&self.infra.live);
+        next.share_infra_with_tenants();
+        // The process is already serving; warm-up of a swapped config does not gate readiness.
+        next.infra.warmup.finish(Vec::new());
+        let next = Arc::new(next);
+        *self.infra.live.current.write() = Some(Arc::clone(&next));
+        self.infra.live.swapped.store(true, Ordering::Release);
+        next
+    }
+
+    /// Record request start/end and usage to the given journal.
+    #[must_use]
+    pub fn with_request_journal(mut self, journal: Arc<RequestJournal>) -> Self {
+        self.infra.journal = Some(journal);
+        self.share_infra_with_tenants();
+        self
+    }
+
+    #[must_use]
+    pub fn request_journal(&self) -> Option<&Arc<RequestJournal>> {
+        self.infra.journal.as_ref()
+    }
+
+    /// Record one analytics row per model API request into `store`.
+    #[cfg(feature = "analytics")]
+    #[must_use]
+    pub fn with_analytics(mut self, store: Arc<AnalyticsStore>) -> Self {
+        self.infra.analytics = Some(store);
+        self.share_infra_with_tenants();
+        self
+    }
+
+    #[cfg(feature = "analytics")]
+    #[must_use]
+    pub fn analytics(&self) -> Option<&Arc<AnalyticsStore>> {
+        self.infra.analytics.as_ref()
+    }
+
+    /// Enable the `/v1/batches` API backed by the given store.
+    #[must_use]
+    pub fn with_batch_store(mut self, store: Arc<BatchStore>) -> Self {
+        self.infra.batch_store = Some(store);
+        self.share_infra_with_tenants();
+        self
+    }
+
+    #[must_use]
+    pub fn batch_store(&self) -> Option<&Arc<BatchStore>> {
+        self.infra.batch_store.as_ref()
+    }
+
+    /// Client streams in progress that other clients can attach to.
+    #[must_use]
+    pub fn stream_broadcasts(&self) -> &Arc<StreamBroadcasts> {
+        &self.infra.stream_broadcasts
+    }
+
+    /// Request lifecycle events for `/admin/tail` watchers.
+    #[must_use]
+    pub fn live_tail(&self) -> &Arc<LiveTail> {
+        &self.infra.live_tail
+    }
+
+    /// Recorded conversations, when `features.conversation_traces` is set.
+    #[must_use]
+    pub fn conversation_traces(&self) -> Option<&Arc<ConversationTraces>> {
+        self.infra.conversation_traces.as_ref()
+    }
+
+    /// Client streams that can be resumed, when `features.resumable_streams` is set.
+    #[must_use]
+    pub fn resumable_streams(&self) -> Option<&Arc<ResumableStreams>> {
+        self.infra.resumable_streams.as_ref()
+    }
+
+    /// Long-poll operations, when `features.long_poll` is set.
+    #[must_use]
+    pub fn operations(&self) -> Option<&Arc<Operations>> {
+        self.infra.operations.as_ref()
+    }
+
+    /// Unparseable tool blocks seen in streams, when
+    /// `features.fc_parse_failures` is set.
+    #[must_use]
+    pub fn fc_parse_failures(&self) -> Option<&Arc<FcParseFailures>> {
+        self.infra.fc_parse_failures.as_ref()
+    }
+
+    /// Remember that `upstream` served the response with `ids`.
+    pub fn record_response_ids(&self, ids: ResponseIds, upstream_index: usize) {
+        let upstream = self
+            .routing
+            .upstream_names
+            .get(upstream_index)
+            .map_or_else(|| Arc::from("<unknown-upstream>"), Arc::clone);
+        self.infra.response_ids.record(ids, upstream);
+    }
+
+    /// The recently served response whose client or upstream id is `id`.
+    #[must_use]
+    pub fn response_id_mapping(&self, id: &str) -> Option<ResponseIdMapping> {
+        self.infra.response_ids.lookup(id)
+    }
+
+    /// Cached moderation verdict for `text`.
+    #[must_use]
+    pub(crate) fn cached_moderation(&self, text: &str) -> Option<Arc<ModerationVerdict>> {
+        self.caches.moderation_cache.get(text)
+    }
+
+    pub(crate) fn cache_moderation(&self, text: &str, verdict: Arc<ModerationVerdict>) {
+        self.caches.moderation_cache.insert(text, verdict);
+    }
+
     pub fn next_request_seq(&self) -> u64 {
         self.infra.request_ids.next_seq()
     }
@@ -117,6 +626,51 @@ impl AppState {
         authenticate(ingress, headers, &self.infra.allowed_client_keys)
     }
 
+    /// The priority granted to a request's `x-priority` header; normal when
+    /// `features.request_priority` is not set.
+    #[must_use]
+    pub fn request_priority(
+        &self,
+        ingress: IngressApi,
+        headers: &http::HeaderMap,
+    ) -> RequestPriority {
+        self.routing
+            .request_priority
+            .as_ref()
+            .map_or_else(RequestPriority::default, |policy| {
+                policy.granted(ingress, headers)
+            })
+    }
+
+    /// Wait for a slot in the local admission queue, when
+    /// `features.request_priority.max_concurrent_requests` is set.
+    pub async fn admit_request(&self, priority: RequestPriority) -> Option<PriorityPermit> {
+        let gate = self.routing.request_priority.as_ref()?.gate()?;
+        Some(gate.acquire(priority).await)
+    }
+
+    /// `features.memory_budget.max_buffered_bytes`, when set.
+    #[must_use]
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.config
+            .features
+            .memory_budget
+            .as_ref()
+            .map(|budget| budget.max_buffered_bytes)
+    }
+
+    /// `features.forward_response_headers`, when any are configured.
+    #[must_use]
+    pub(crate) fn forward_response_headers(&self) -> Option<Arc<ResponseHeaderForwarding>> {
+        self.routing.forward_response_headers.clone()
+    }
+
+    /// Admin API credentials.
+    #[must_use]
+    pub fn admin_auth(&self) -> &AdminAuthenticator {
+        &self.infra.admin_auth
+    }
+
     #[must_use]
     pub fn request_uuid(&self, request_seq: u64) -> uuid::Uuid {
         self.infra.request_ids.request_uuid(request_seq)
@@ -133,6 +687,17 @@ impl AppState {
         route_sticky_hash_impl(ingress, headers, model, prompt_prefix)
     }
 
+    #[must_use]
+    pub fn route_session_hash(
+        &self,
+        ingress: IngressApi,
+        headers: &http::HeaderMap,
+        model: &str,
+        prompt_prefix: &[u8],
+    ) -> u64 {
+        route_session_hash_impl(ingress, headers, model, prompt_prefix)
+    }
+
     /// Resolve route with session-aware failover policy.
     ///
     /// - Portable: same-provider candidates first, then cross-provider.
@@ -162,16 +727,165 @@ impl AppState {
     ///   candidates so callers can degrade only after exhausting anchored routes.
     ///
     /// Breaker-open routes are kept at the tail of each tier as best-effort probes.
+    /// With `latency_aware_routing`, each tier is ordered by observed mean TTFB.
+    /// A routing rule matched by the request overrides the model or pins the
+    /// upstream. Candidates whose model lacks a capability the request needs
+    /// are dropped, as are upstreams excluded by a quality retry.
     ///
     /// # Errors
     ///
-    /// Returns `CanonicalError::InvalidRequest` when no route can be resolved.
+    /// Returns `CanonicalError::InvalidRequest` when no route can be resolved
+    /// or no candidate has the required capabilities.
     pub fn resolve_routes_with_policy<'a>(
         &'a self,
         model: &'a str,
         request_hash: u64,
         session_class: SessionClass,
     ) -> Result<SmallVec<[RouteTarget<'a>; 4]>, CanonicalError> {
+        let mut routes = self.resolve_rule_routes(model, request_hash, session_class)?;
+        if request_context::has_exclusions() {
+            routes.retain(|route| !request_context::is_excluded(route.upstream_index));
+            if routes.is_empty() {
+                return Err(CanonicalError::InvalidRequest(format!(
+                    "No remaining upstream for model '{model}'"
+                )));
+            }
+        }
+        let required = capabilities::required();
+        if !required.is_empty() {
+            let mut missing = Capabilities::NONE;
+            routes.retain(|route| {
+                let lacking = self.missing_capabilities(route, required);
+                missing = missing.union(lacking);
+                lacking.is_empty()
+            });
+            if routes.is_empty() {
+                return Err(CanonicalError::InvalidRequest(format!(
+                    "No upstream for model '{model}' supports {}",
+                    missing.names().join(", ")
+                )));
+            }
+        }
+        Ok(routes)
+    }
+
+    /// Capabilities in `required` that the model of `route` lacks. Tool
+    /// calling is only required from upstreams in native FC mode.
+    fn missing_capabilities(
+        &self,
+        route: &RouteTarget<'_>,
+        required: Capabilities,
+    ) -> Capabilities {
+        let required =
+            if self.config.upstream_services[route.upstream_index].fc_mode == FcMode::Native {
+                required
+            } else {
+                required.without(Capabilities::TOOLS)
+            };
+        self.routing
+            .model_capabilities
+            .missing(route.actual_model, required)
+    }
+
+    /// Response-quality retry policy, when configured.
+    #[must_use]
+    pub fn quality_retry(&self) -> Option<&QualityRetryPolicy> {
+        self.routing.quality_retry.as_ref()
+    }
+
+    /// Whether any model declares its capabilities.
+    #[must_use]
+    pub fn has_model_capabilities(&self) -> bool {
+        !self.routing.model_capabilities.is_empty()
+    }
+
+    fn resolve_rule_routes<'a>(
+        &'a self,
+        model: &'a str,
+        request_hash: u64,
+        session_class: SessionClass,
+    ) -> Result<SmallVec<[RouteTarget<'a>; 4]>, CanonicalError> {
+        match rules::active_rule().and_then(|rule| self.routing.routing_rules.target(rule)) {
+            Some(RuleTarget::Model(group)) => {
+                self.resolve_model_routes(group, request_hash, session_class)
+            }
+            Some(&RuleTarget::Upstream(upstream_index)) => {
+                let mut routes = self
+                    .resolve_model_routes(model, request_hash, session_class)
+                    .unwrap_or_default();
+                routes.retain(|route| route.upstream_index == upstream_index);
+                if routes.is_empty() {
+                    // The pinned upstream receives the model as requested.
+                    routes.push(RouteTarget {
+                        upstream_index,
+                        actual_model: model,
+                        known_model_id: None,
+                    });
+                }
+                Ok(routes)
+            }
+            None => self.resolve_model_routes(model, request_hash, session_class),
+        }
+    }
+
+    /// Routes of `model`, followed by those of its fallback chain entries.
+    ///
+    /// Routes cooling down for the requested model or for the entry they
+    /// were reached through go after every other route of the chain.
+    fn resolve_model_routes<'a>(
+        &'a self,
+        model: &'a str,
+        request_hash: u64,
+        session_class: SessionClass,
+    ) -> Result<SmallVec<[RouteTarget<'a>; 4]>, CanonicalError> {
+        let Some(fallbacks) = self.routing.fallback_chains.get(model) else {
+            return self.resolve_group_routes(model, request_hash, session_class);
+        };
+        let own_routes = self.resolve_group_routes(model, request_hash, session_class);
+        let mut entry_routes = Vec::with_capacity(fallbacks.len() + 1);
+        let first_err = match own_routes {
+            Ok(routes) => {
+                entry_routes.push((model, routes));
+                None
+            }
+            Err(err) => Some(err),
+        };
+        for fallback in fallbacks {
+            if let Ok(routes) = self.resolve_group_routes(fallback, request_hash, session_class) {
+                entry_routes.push((fallback.as_str(), routes));
+            }
+        }
+        let breakers = &self.resilience.route_breakers;
+        let routes = if breakers.has_any_entries() {
+            merge_chain_routes(entry_routes, |route, entry| {
+                breakers.allows_route(route.upstream_index, model)
+                    && breakers.allows_route(route.upstream_index, entry)
+            })
+        } else {
+            merge_chain_routes(entry_routes, |_, _| true)
+        };
+        match first_err {
+            Some(err) if routes.is_empty() => Err(err),
+            _ => Ok(routes),
+        }
+    }
+
+    fn resolve_group_routes<'a>(
+        &'a self,
+        model: &'a str,
+        request_hash: u64,
+        session_class: SessionClass,
+    ) -> Result<SmallVec<[RouteTarget<'a>; 4]>, CanonicalError> {
+        let route_ttfb_ms = |upstream_index: usize, model_group: &str| {
+            self.resilience
+                .latency_stats
+                .ttfb_ms_avg(upstream_index, model_group)
+        };
+        let route_ttfb_ms: Option<RouteTtfbFn<'_>> = self
+            .config
+            .features
+            .latency_aware_routing
+            .then_some(&route_ttfb_ms);
         if self.resilience.route_breakers.has_any_entries() {
             resolve_routes_with_policy_impl(
                 &self.model_router,
@@ -179,6 +893,7 @@ impl AppState {
                 model,
                 request_hash,
                 session_class,
+                route_ttfb_ms,
                 |upstream_index, model_group| {
                     self.resilience
                         .route_breakers
@@ -191,11 +906,13 @@ impl AppState {
                 &self.prepared_upstreams,
                 model,
                 request_hash,
+                route_ttfb_ms,
             )
         }
     }
 
     pub fn record_upstream_success(&self, upstream_index: usize, model_group: &str) {
+        note_served_upstream(upstream_index);
         self.resilience
             .route_breakers
             .record_success(upstream_index, model_group);
@@ -207,6 +924,8 @@ impl AppState {
         model_group: &str,
         err: &CanonicalError,
     ) {
+        note_retry();
+        note_failed_upstream(self.upstream_name(upstream_index));
         self.resilience
             .route_breakers
             .record_failure(upstream_index, model_group, err);
@@ -218,11 +937,167 @@ impl AppState {
         model_group: &str,
         result: &Result<T, CanonicalError>,
     ) {
+        if result.is_ok() {
+            note_served_upstream(upstream_index);
+        } else {
+            note_retry();
+            note_failed_upstream(self.upstream_name(upstream_index));
+        }
         self.resilience
             .route_breakers
             .record_outcome(upstream_index, model_group, result);
     }
 
+    /// Routes currently tracked by the breaker, including 429/529 cooldowns.
+    #[must_use]
+    pub fn route_cooldowns(&self) -> Vec<RouteCooldownStatus> {
+        self.resilience.route_breakers.cooldown_snapshot()
+    }
+
+    /// Pre-connect to every upstream through the client its traffic uses.
+    ///
+    /// Each upstream gets a bodyless `GET` to its `base_url` (once per distinct
+    /// stream/non-stream proxy), bounded by `server.warmup_timeout_secs`.
+    /// Unreachable upstreams are reported, not fatal; readiness is granted
+    /// once every probe has finished.
+    pub async fn warm_up_upstreams(&self) {
+        let timeout = Duration::from_secs(self.config.server.warmup_timeout_secs);
+        let probes = self
+            .prepared_upstreams
+            .iter()
+            .enumerate()
+            .flat_map(|(upstream_index, prepared)| {
+                let non_stream_proxy = prepared.proxy_for(false);
+                let stream_proxy = prepared.proxy_for(true);
+                let stream_proxy = (stream_proxy != non_stream_proxy).then_some(stream_proxy);
+                std::iter::once(non_stream_proxy)
+                    .chain(stream_proxy)
+                    .map(move |proxy| (upstream_index, prepared.base_url(), proxy))
+            })
+            .map(|(upstream_index, url, proxy)| async move {
+                let started = std::time::Instant::now();
+                let outcome =
+                    tokio::time::timeout(timeout, self.transport.warm_up(url, proxy.as_deref()))
+                        .await;
+                let (status, error) = match outcome {
+                    Ok(Ok(status)) => (Some(status.as_u16()), None),
+                    Ok(Err(err)) => (None, Some(err.to_string())),
+                    Err(_) => (None, Some("warm-up timed out".to_string())),
+                };
+                UpstreamWarmup {
+                    upstream_index,
+                    status,
+                    error,
+                    elapsed_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+                }
+            });
+        let results = futures_util::future::join_all(probes).await;
+        for result in &results {
+            if let Some(error) = &result.error {
+                tracing::warn!(
+                    upstream = %self.upstream_name(result.upstream_index),
+                    error = %error,
+                    "upstream warm-up failed"
+                );
+            }
+        }
+        self.infra.warmup.finish(results);
+    }
+
+    /// Whether startup warm-up has finished (always true when it is disabled).
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        !self.infra.warmup.is_pending()
+    }
+
+    /// Per-upstream warm-up outcomes; empty when warm-up is disabled.
+    #[must_use]
+    pub fn upstream_warmups(&self) -> Vec<UpstreamWarmup> {
+        self.infra.warmup.results()
+    }
+
+    /// Record TTFB and decode time of a completed streaming response.
+    pub fn record_route_latency(
+        &self,
+        upstream_index: usize,
+        model_group: &str,
+        ttfb: Duration,
+        decode: Duration,
+        output_tokens: u64,
+    ) {
+        self.resilience.latency_stats.record(
+            upstream_index,
+            model_group,
+            ttfb,
+            decode,
+            output_tokens,
+        );
+    }
+
+    /// Sliding-window TTFB and decode tokens/sec per upstream + model group.
+    #[must_use]
+    pub fn route_latency_stats(&self) -> Vec<RouteLatencyStats> {
+        self.resilience.latency_stats.snapshot()
+    }
+
+    /// Largest request body read from any client (`server.max_request_bytes`).
+    #[must_use]
+    pub fn max_request_bytes(&self) -> usize {
+        self.routing.request_limits.global()
+    }
+
+    /// Largest batch or file upload body (`server.max_upload_bytes`, or
+    /// `server.max_request_bytes` when unset).
+    #[must_use]
+    pub fn max_upload_bytes(&self) -> usize {
+        self.routing.request_limits.upload()
+    }
+
+    /// Count a request turned away for a body over `server.max_request_bytes`
+    /// or `server.max_upload_bytes`.
+    pub fn record_request_too_large(&self, ingress: Option<IngressApi>) {
+        self.routing.request_limits.record_global_rejection(ingress);
+    }
+
+    /// Whether a `len` byte body is within the `max_request_bytes` of
+    /// `upstream_index`.
+    #[must_use]
+    pub fn fits_upstream_request_size(&self, upstream_index: usize, len: usize) -> bool {
+        self.routing
+            .request_limits
+            .fits_upstream(upstream_index, len)
+    }
+
+    /// Reject a `len` byte body routed to `upstream_index` when it is over the
+    /// upstream's `max_request_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CanonicalError::RequestTooLarge`] and counts the rejection.
+    pub fn check_upstream_request_size(
+        &self,
+        upstream_index: usize,
+        len: usize,
+    ) -> Result<(), CanonicalError> {
+        self.routing.request_limits.check_upstream(
+            upstream_index,
+            self.upstream_name(upstream_index),
+            len,
+        )
+    }
+
+    /// Requests rejected by `server.max_request_bytes`, per ingress.
+    #[must_use]
+    pub fn global_request_rejections(&self) -> Vec<GlobalRequestRejections> {
+        self.routing.request_limits.global_rejections()
+    }
+
+    /// Requests rejected by an upstream's `max_request_bytes`.
+    #[must_use]
+    pub fn upstream_request_rejections(&self) -> Vec<UpstreamRequestRejections> {
+        self.routing.request_limits.upstream_rejections()
+    }
+
     #[must_use]
     pub fn fc_decision(&self, route: &RouteTarget<'_>, has_tools: bool) -> FcDecision {
         self.resilience.fc_policy_cache.decision(route, has_tools)
@@ -236,6 +1111,136 @@ impl AppState {
             .map_or("<unknown-upstream>", AsRef::as_ref)
     }
 
+    /// How `upstream_index` handles tool calls that break a `strict` tool
+    /// schema.
+    #[must_use]
+    pub fn tool_schema_validation(&self, upstream_index: usize) -> ToolSchemaValidation {
+        self.config
+            .upstream_services
+            .get(upstream_index)
+            .map_or(ToolSchemaValidation::Off, |upstream| {
+                upstream.tool_schema_validation
+            })
+    }
+
+    /// Virtual model configured under the client-visible `name`.
+    /// Whether `model` can route to a stream-only upstream, so non-streaming
+    /// requests must be streamed upstream and re-aggregated.
+    #[must_use]
+    pub fn requires_streaming_upstream(&self, model: &str) -> bool {
+        self.routes_to_any(model, &self.routing.stream_only_upstreams)
+    }
+
+    /// Whether `model` can route to a non-stream-only upstream, so streaming
+    /// requests must be served from a complete response.
+    #[must_use]
+    pub fn requires_non_streaming_upstream(&self, model: &str) -> bool {
+        self.routes_to_any(model, &self.routing.non_stream_only_upstreams)
+    }
+
+    fn routes_to_any(&self, model: &str, upstream_indices: &[usize]) -> bool {
+        upstream_indices.iter().any(|&upstream_index| {
+            self.model_router
+                .has_candidate_for_upstream(model, upstream_index)
+        })
+    }
+
+    /// Routing rules evaluated by dispatch.
+    #[must_use]
+    pub fn routing_rules(&self) -> &RoutingRules {
+        &self.routing.routing_rules
+    }
+
+    /// CORS policies applied by dispatch, if configured.
+    #[must_use]
+    pub fn cors(&self) -> Option<&CorsPolicies> {
+        self.routing.cors.as_ref()
+    }
+
+    /// The model served in place of `model` for the client key in
+    /// `headers`, if a key model map renames it.
+    #[must_use]
+    pub fn key_model(
+        &self,
+        ingress: IngressApi,
+        headers: &http::HeaderMap,
+        model: &str,
+    ) -> Option<&Arc<VirtualModel>> {
+        self.routing.key_model_maps.get(ingress, headers, model)
+    }
+
+    #[must_use]
+    pub fn virtual_model(&self, name: &str) -> Option<&Arc<VirtualModel>> {
+        self.routing.virtual_models.get(name)
+    }
+
+    /// Output post-processing applied to every response, if configured.
+    #[must_use]
+    pub fn output_postprocess(&self) -> Option<&Arc<OutputPostprocess>> {
+        self.routing.output_postprocess.as_ref()
+    }
+
+    /// Whether `model` falls back to other models when its routes fail.
+    #[must_use]
+    pub fn has_fallback_chain(&self, model: &str) -> bool {
+        self.routing.fallback_chains.get(model).is_some()
+    }
+
+    #[must_use]
+    pub fn cascade_model(&self, name: &str) -> Option<&Arc<CascadeModel>> {
+        self.routing.cascade_models.get(name)
+    }
+
+    /// FC prompt template for a request routed to `upstream_index` for
+    /// `requested_model`; `None` selects the built-in English prompt.
+    #[must_use]
+    pub fn fc_prompt_template(
+        &self,
+        upstream_index: usize,
+        requested_model: &str,
+        body: &[u8],
+    ) -> Option<&PromptTemplate> {
+        self.routing
+            .prompt_templates
+            .resolve(upstream_index, requested_model, body)
+    }
+
+    /// Remember which upstream stores an uploaded file.
+    pub fn bind_file_upstream(&self, file_id: &str, upstream_index: usize) {
+        self.routing.file_bindings.bind(file_id, upstream_index);
+    }
+
+    pub fn unbind_file_upstream(&self, file_id: &str) {
+        self.routing.file_bindings.unbind(file_id);
+    }
+
+    #[must_use]
+    pub fn file_upstream(&self, file_id: &str) -> Option<usize> {
+        self.routing.file_bindings.lookup(file_id)
+    }
+
+    /// Remember which upstream runs a message batch.
+    pub fn bind_message_batch_upstream(&self, batch_id: &str, upstream_index: usize) {
+        self.routing
+            .message_batch_bindings
+            .bind(batch_id, upstream_index);
+    }
+
+    pub fn unbind_message_batch_upstream(&self, batch_id: &str) {
+        self.routing.message_batch_bindings.unbind(batch_id);
+    }
+
+    #[must_use]
+    pub fn message_batch_upstream(&self, batch_id: &str) -> Option<usize> {
+        self.routing.message_batch_bindings.lookup(batch_id)
+    }
+
+    /// Upstream that stores a file referenced by the request body, if any.
+    #[must_use]
+    pub fn file_pinned_upstream(&self, body: &[u8]) -> Option<usize> {
+        self.routing.file_bindings.upstream_for_body(body)
+    }
+
     #[must_use]
     pub fn auto_inject_cached(&self, route: &RouteTarget<'_>) -> bool {
         self.resilience.fc_policy_cache.auto_inject_cached(route)
@@ -255,16 +1260,33 @@ impl AppState {
         self.caches.models_cache.body()
     }
 
-    pub async fn maybe_refresh_models_cache(&self) {
+    /// Start refreshing the models listing in the background once it is due.
+    ///
+    /// Callers keep serving the cached listing, so `/v1/models` never waits
+    /// on a slow or dead upstream.
+    pub fn maybe_refresh_models_cache(self: &Arc<Self>) {
         let now = unix_now_secs();
         if !self.caches.models_cache.try_begin_refresh(now) {
             return;
         }
+        let state = Arc::clone(self);
+        tokio::spawn(async move { state.refresh_models_cache(now).await });
+    }
 
-        if let Some(body) = build_dynamic_models_response_body(self).await {
-            self.caches.models_cache.set_body(body);
+    /// Drop the models listing's negative entries and refresh it now.
+    pub async fn bust_models_cache(&self) -> Vec<UpstreamModelsStatus> {
+        self.caches.models_cache.invalidate();
+        let now = unix_now_secs();
+        if self.caches.models_cache.try_begin_refresh(now) {
+            self.refresh_models_cache(now).await;
         }
+        self.caches.models_cache.status(unix_now_secs())
+    }
 
+    async fn refresh_models_cache(&self, now: u64) {
+        if let Some(body) = build_dynamic_models_response_body(self, now).await {
+            self.caches.models_cache.set_body(body);
+        }
         self.caches.models_cache.finish_refresh();
     }
 }
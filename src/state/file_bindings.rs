@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::RwLock;
+use rustc_hash::FxHashMap;
+
+/// Upper bound on tracked file ids; new uploads past this are not pinned.
+const MAX_FILE_BINDINGS: usize = 65_536;
+/// `OpenAI` file ids are `file-` followed by an opaque token.
+const FILE_ID_PREFIX: &[u8] = b"\"file-";
+
+/// Maps uploaded file ids to the upstream that stores them.
+pub(crate) struct FileBindings {
+    bindings: RwLock<FxHashMap<Box<str>, usize>>,
+    has_entries: AtomicBool,
+}
+
+impl FileBindings {
+    #[must_use]
+    pub(crate) fn new() -> Self {
+        Self {
+            bindings: RwLock::new(FxHashMap::default()),
+            has_entries: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn bind(&self, file_id: &str, upstream_index: usize) {
+        let mut bindings = self.bindings.write();
+        if bindings.len() >= MAX_FILE_BINDINGS && !bindings.contains_key(file_id) {
+            tracing::warn!(
+                file_id,
+                "file binding table is full; file will not be pinned"
+            );
+            return;
+        }
+        bindings.insert(Box::from(file_id), upstream_index);
+        self.has_entries.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn unbind(&self, file_id: &str) {
+        let mut bindings = self.bindings.write();
+        bindings.remove(file_id);
+        if bindings.is_empty() {
+            self.has_entries.store(false, Ordering::Release);
+        }
+    }
+
+    #[must_use]
+    pub(crate) fn lookup(&self, file_id: &str) -> Option<usize> {
+        if !self.has_entries.load(Ordering::Acquire) {
+            return None;
+        }
+        self.bindings.read().get(file_id).copied()
+    }
+
+    /// Take over the bindings of `previous`, moving each to the upstream index
+    /// `remap` gives its old one and dropping those it maps to `None`.
+    pub(crate) fn inherit(&self, previous: &FileBindings, remap: impl Fn(usize) -> Option<usize>) {
+        let mut bindings = self.bindings.write();
+        bindings.extend(previous.bindings.read().iter().filter_map(
+            |(file_id, &upstream_index)| Some((file_id.clone(), remap(upstream_index)?)),
+        ));
+        self.has_entries
+            .store(!bindings.is_empty(), Ordering::Release);
+    }
+
+    /// Upstream bound to the first known file id referenced in a request body.
+    #[must_use]
+    pub(crate) fn upstream_for_body(&self, body: &[u8]) -> Option<usize> {
+        if !self.has_entries.load(Ordering::Acquire) {
+            return None;
+        }
+        let bindings = self.bindings.read();
+        memchr::memmem::find_iter(body, FILE_ID_PREFIX).find_map(|start| {
+            let id_start = start + 1;
+            let id_len = memchr::memchr(b'"', &body[id_start..])?;
+            let file_id = std::str::from_utf8(&body[id_start..id_start + id_len]).ok()?;
+            bindings.get(file_id).copied()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_reference_resolves_bound_upstream() {
+        let bindings = FileBindings::new();
+        let body = br#"{"input":[{"role":"user","content":[{"type":"input_file","file_id":"file-abc"}]}]}"#;
+        assert_eq!(bindings.upstream_for_body(body), None);
+
+        bindings.bind("file-abc", 2);
+        bindings.bind("file-other", 1);
+        assert_eq!(bindings.upstream_for_body(body), Some(2));
+        assert_eq!(bindings.lookup("file-other"), Some(1));
+
+        bindings.unbind("file-abc");
+        assert_eq!(bindings.upstream_for_body(body), None);
+
+        let next = FileBindings::new();
+        next.inherit(&bindings, |upstream_index| {
+            (upstream_index == 1).then_some(0)
+        });
+        assert_eq!(next.lookup("file-other"), Some(0));
+    }
+}
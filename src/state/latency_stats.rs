@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+
+use super::request_context;
+
+/// Samples kept per route; older samples fall out of the window.
+const LATENCY_WINDOW_SAMPLES: usize = 64;
+/// Model groups tracked per upstream; further models are not recorded.
+const LATENCY_MAX_MODELS_PER_UPSTREAM: usize = 256;
+
+/// Note that `upstream_index` produced the response of the current attempt.
+///
+/// A no-op outside [`request_context::scope`].
+pub(crate) fn note_served_upstream(upstream_index: usize) {
+    request_context::note_attempt(|attempt| attempt.served_upstream = Some(upstream_index));
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LatencySample {
+    ttfb_ms: u64,
+    tokens_per_sec: Option<f64>,
+}
+
+/// Point-in-time latency view of one upstream + model group route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteLatencyStats {
+    pub upstream_index: usize,
+    pub model_group: String,
+    pub samples: usize,
+    pub ttfb_ms_avg: u64,
+    pub ttfb_ms_p90: u64,
+    /// Mean decode rate over samples that reported output tokens.
+    pub tokens_per_sec_avg: Option<f64>,
+}
+
+pub(crate) struct LatencyStatsRegistry {
+    shards: Vec<Mutex<FxHashMap<String, VecDeque<LatencySample>>>>,
+}
+
+impl LatencyStatsRegistry {
+    #[must_use]
+    pub(crate) fn new(upstream_count: usize) -> Self {
+        Self {
+            shards: (0..upstream_count)
+                .map(|_| Mutex::new(FxHashMap::default()))
+                .collect(),
+        }
+    }
+
+    /// Record one completed streaming response.
+    ///
+    /// `decode` is the time from the first to the last body chunk; with
+    /// `output_tokens` it yields the decode rate.
+    pub(crate) fn record(
+        &self,
+        upstream_index: usize,
+        model_group: &str,
+        ttfb: Duration,
+        decode: Duration,
+        output_tokens: u64,
+    ) {
+        let Some(shard) = self.shards.get(upstream_index) else {
+            return;
+        };
+        let decode_secs = decode.as_secs_f64();
+        #[allow(clippy::cast_precision_loss)]
+        let tokens_per_sec =
+            (output_tokens > 0 && decode_secs >= 0.001).then(|| output_tokens as f64 / decode_secs);
+        let sample = LatencySample {
+            ttfb_ms: u64::try_from(ttfb.as_millis()).unwrap_or(u64::MAX),
+            tokens_per_sec,
+        };
+
+        let mut windows = shard.lock();
+        let window = if let Some(window) = windows.get_mut(model_group) {
+            window
+        } else {
+            if windows.len() >= LATENCY_MAX_MODELS_PER_UPSTREAM {
+                return;
+            }
+            windows
+                .entry(model_group.to_string())
+                .or_insert_with(|| VecDeque::with_capacity(LATENCY_WINDOW_SAMPLES))
+        };
+        if window.len() == LATENCY_WINDOW_SAMPLES {
+            window.pop_front();
+        }
+        window.push_back(sample);
+    }
+
+    /// Mean TTFB of the route's window, `None` before the first sample.
+    #[must_use]
+    pub(crate) fn ttfb_ms_avg(&self, upstream_index: usize, model_group: &str) -> Option<u64> {
+        let windows = self.shards.get(upstream_index)?.lock();
+        windows.get(model_group).map(ttfb_avg)
+    }
+
+    /// Collect every tracked route.
+    #[must_use]
+    pub(crate) fn snapshot(&self) -> Vec<RouteLatencyStats> {
+        let mut stats = Vec::new();
+        for (upstream_index, shard) in self.shards.iter().enumerate() {
+            let windows = shard.lock();
+            stats.extend(windows.iter().map(|(model_group, window)| {
+                let mut ttfbs: Vec<u64> = window.iter().map(|sample| sample.ttfb_ms).collect();
+                ttfbs.sort_unstable();
+                let p90_index = (ttfbs.len() * 9).div_ceil(10).saturating_sub(1);
+                let rates: Vec<f64> = window
+                    .iter()
+                    .filter_map(|sample| sample.tokens_per_sec)
+                    .collect();
+                #[allow(clippy::cast_precision_loss)]
+                let tokens_per_sec_avg =
+                    (!rates.is_empty()).then(|| rates.iter().sum::<f64>() / rates.len() as f64);
+                RouteLatencyStats {
+                    upstream_index,
+                    model_group: model_group.clone(),
+                    samples: window.len(),
+                    ttfb_ms_avg: ttfb_avg(window),
+                    ttfb_ms_p90: ttfbs.get(p90_index).copied().unwrap_or(0),
+                    tokens_per_sec_avg,
+                }
+            }));
+        }
+        stats.sort_by(|a, b| {
+            (a.upstream_index, &a.model_group).cmp(&(b.upstream_index, &b.model_group))
+        });
+        stats
+    }
+}
+
+fn ttfb_avg(window: &VecDeque<LatencySample>) -> u64 {
+    if window.is_empty() {
+        return 0;
+    }
+    let total: u64 = window
+        .iter()
+        .map(|sample| sample.ttfb_ms)
+        .fold(0, u64::saturating_add);
+    total / window.len() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::request_context::RequestContext;
+
+    #[test]
+    fn test_window_keeps_latest_samples_and_reports_rates() {
+        let registry = LatencyStatsRegistry::new(2);
+        for ttfb_ms in 0..(LATENCY_WINDOW_SAMPLES as u64 + 10) {
+            registry.record(
+                1,
+                "m",
+                Duration::from_millis(ttfb_ms),
+                Duration::from_secs(2),
+                100,
+            );
+        }
+        registry.record(0, "m", Duration::from_millis(5), Duration::ZERO, 0);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].upstream_index, 0);
+        assert_eq!(snapshot[0].tokens_per_sec_avg, None);
+        let route = &snapshot[1];
+        assert_eq!(route.samples, LATENCY_WINDOW_SAMPLES);
+        assert_eq!(route.ttfb_ms_avg, 41);
+        assert_eq!(route.ttfb_ms_p90, 67);
+        assert_eq!(route.tokens_per_sec_avg, Some(50.0));
+        assert_eq!(registry.ttfb_ms_avg(1, "m"), Some(41));
+        assert_eq!(registry.ttfb_ms_avg(1, "other"), None);
+    }
+
+    #[tokio::test]
+    async fn test_served_upstream_is_scoped_to_the_attempt() {
+        note_served_upstream(3);
+        let (served, _) = request_context::scope(RequestContext::default(), async {
+            note_served_upstream(1);
+            note_served_upstream(2);
+            request_context::finish_attempt().served_upstream
+        })
+        .await;
+        assert_eq!(served, Some(2));
+        let (served, _) = request_context::scope(RequestContext::default(), async {
+            request_context::finish_attempt().served_upstream
+        })
+        .await;
+        assert_eq!(served, None);
+    }
+}
@@ -1,9 +1,10 @@
 use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
 
 use bytes::Bytes;
 use http::Method;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use serde_json::Value;
 
 use super::AppState;
@@ -11,24 +12,109 @@ use crate::config::{AppConfig, UpstreamServiceConfig};
 use crate::routing::ModelRouter;
 use crate::transport::{build_provider_headers_prepared, PreparedUpstream};
 
+/// Upper bound for one upstream's model listing request.
+const UPSTREAM_MODELS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Point-in-time view of one upstream's model listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamModelsStatus {
+    pub upstream_index: usize,
+    /// Models from the last successful listing, reused while the upstream is down.
+    pub listed_models: Option<usize>,
+    /// Seconds until a failed upstream is asked for its models again.
+    pub down_remaining_secs: u64,
+}
+
+#[derive(Default)]
+struct UpstreamListing {
+    models: Option<Vec<String>>,
+    down_until_unix: u64,
+}
+
 pub(crate) struct ModelsCache {
     body: RwLock<Bytes>,
     ttl_secs: u64,
+    negative_ttl_secs: u64,
     next_refresh_unix: AtomicU64,
     refreshing: AtomicBool,
+    upstreams: Mutex<Vec<UpstreamListing>>,
 }
 
 impl ModelsCache {
     #[must_use]
-    pub(crate) fn new(initial_body: Bytes, ttl_secs: u64) -> Self {
+    pub(crate) fn new(
+        initial_body: Bytes,
+        ttl_secs: u64,
+        negative_ttl_secs: u64,
+        upstream_count: usize,
+    ) -> Self {
         Self {
             body: RwLock::new(initial_body),
             ttl_secs,
+            negative_ttl_secs,
             next_refresh_unix: AtomicU64::new(0),
             refreshing: AtomicBool::new(false),
+            upstreams: Mutex::new(
+                std::iter::repeat_with(UpstreamListing::default)
+                    .take(upstream_count)
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Make the next request refresh the listing, asking every upstream again.
+    pub(crate) fn invalidate(&self) {
+        self.next_refresh_unix.store(0, Ordering::Relaxed);
+        for listing in self.upstreams.lock().iter_mut() {
+            listing.down_until_unix = 0;
         }
     }
 
+    /// Whether the upstream at `index` failed its last listing recently.
+    fn upstream_down(&self, index: usize, now: u64) -> bool {
+        self.upstreams
+            .lock()
+            .get(index)
+            .is_some_and(|listing| now < listing.down_until_unix)
+    }
+
+    /// Record a listing attempt; `None` marks the upstream down and keeps
+    /// its last successful listing.
+    fn record_upstream(&self, index: usize, models: Option<Vec<String>>, now: u64) {
+        let mut upstreams = self.upstreams.lock();
+        let Some(listing) = upstreams.get_mut(index) else {
+            return;
+        };
+        match models {
+            Some(models) => {
+                listing.models = Some(models);
+                listing.down_until_unix = 0;
+            }
+            None => listing.down_until_unix = now.saturating_add(self.negative_ttl_secs),
+        }
+    }
+
+    fn upstream_models(&self, index: usize) -> Option<Vec<String>> {
+        self.upstreams
+            .lock()
+            .get(index)
+            .and_then(|listing| listing.models.clone())
+    }
+
+    #[must_use]
+    pub(crate) fn status(&self, now: u64) -> Vec<UpstreamModelsStatus> {
+        self.upstreams
+            .lock()
+            .iter()
+            .enumerate()
+            .map(|(upstream_index, listing)| UpstreamModelsStatus {
+                upstream_index,
+                listed_models: listing.models.as_ref().map(Vec::len),
+                down_remaining_secs: listing.down_until_unix.saturating_sub(now),
+            })
+            .collect()
+    }
+
     #[must_use]
     pub(crate) fn body(&self) -> Bytes {
         self.body.read().clone()
@@ -66,17 +152,38 @@ pub(crate) fn build_initial_models_response_body(config: &AppConfig) -> Bytes {
     build_models_response_body_from_visible(&build_visible_models_from_config(config))
 }
 
-pub(crate) async fn build_dynamic_models_response_body(state: &AppState) -> Option<Bytes> {
+/// List models from every upstream not marked down, concurrently, and build
+/// the listing from the latest successful answer of each.
+pub(crate) async fn build_dynamic_models_response_body(
+    state: &AppState,
+    now: u64,
+) -> Option<Bytes> {
+    let cache = &state.caches.models_cache;
+    let fetches = state
+        .prepared_upstreams
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !cache.upstream_down(*index, now))
+        .map(|(index, prepared)| async move {
+            let models = tokio::time::timeout(
+                UPSTREAM_MODELS_TIMEOUT,
+                fetch_upstream_models(state, prepared),
+            )
+            .await
+            .ok()
+            .flatten();
+            (index, models)
+        });
+    for (index, models) in futures_util::future::join_all(fetches).await {
+        cache.record_upstream(index, models, now);
+    }
+
     let mut visible_models = BTreeMap::new();
     let mut any_dynamic_success = false;
 
     for (index, service) in state.config.upstream_services.iter().enumerate() {
         insert_config_visible_models(&mut visible_models, service);
-        let Some(prepared) = state.prepared_upstreams.get(index) else {
-            continue;
-        };
-
-        if let Some(models) = fetch_upstream_models(state, prepared, service).await {
+        if let Some(models) = cache.upstream_models(index) {
             any_dynamic_success = true;
             for model_id in models {
                 if !model_routes_to_upstream(&state.model_router, &model_id, index) {
@@ -92,6 +199,7 @@ pub(crate) async fn build_dynamic_models_response_body(state: &AppState) -> Opti
     if !any_dynamic_success {
         return None;
     }
+    insert_virtual_models(&mut visible_models, &state.config);
     Some(build_models_response_body_from_visible(&visible_models))
 }
 
@@ -100,9 +208,29 @@ fn build_visible_models_from_config(config: &AppConfig) -> BTreeMap<String, Stri
     for service in &config.upstream_services {
         insert_config_visible_models(&mut visible_models, service);
     }
+    insert_virtual_models(&mut visible_models, config);
     visible_models
 }
 
+/// List virtual models under the upstream that owns their target model, and
+/// cascade models under the owner of their verify model.
+fn insert_virtual_models(visible_models: &mut BTreeMap<String, String>, config: &AppConfig) {
+    let targets = config
+        .virtual_models
+        .iter()
+        .map(|virtual_model| (&virtual_model.name, &virtual_model.model))
+        .chain(
+            config
+                .cascade_models
+                .iter()
+                .map(|cascade| (&cascade.name, &cascade.verify)),
+        );
+    for (name, target) in targets {
+        let owned_by = visible_models.get(target).cloned().unwrap_or_default();
+        visible_models.entry(name.clone()).or_insert(owned_by);
+    }
+}
+
 fn insert_config_visible_models(
     visible_models: &mut BTreeMap<String, String>,
     service: &UpstreamServiceConfig,
@@ -152,17 +280,16 @@ fn model_routes_to_upstream(
 async fn fetch_upstream_models(
     state: &AppState,
     prepared: &PreparedUpstream,
-    service: &UpstreamServiceConfig,
 ) -> Option<Vec<String>> {
-    let url = build_models_url(&service.base_url);
+    let url = build_models_url(prepared.base_url());
     let response = state
         .transport
         .send_request(
             &url,
             Method::GET,
-            build_provider_headers_prepared(prepared),
+            &build_provider_headers_prepared(prepared),
             Bytes::new(),
-            prepared.proxy_for(false),
+            prepared.proxy_for(false).as_deref(),
         )
         .await
         .ok()?;
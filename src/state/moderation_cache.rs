@@ -0,0 +1,78 @@
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHasher};
+
+/// Outcome of moderating one piece of user input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ModerationVerdict {
+    pub(crate) flagged: bool,
+    /// Categories that triggered the verdict.
+    pub(crate) categories: Vec<String>,
+}
+
+/// Verdicts keyed by a hash of the moderated text and its length.
+pub(crate) struct ModerationCache {
+    verdicts: Mutex<FxHashMap<(u64, usize), Arc<ModerationVerdict>>>,
+    capacity: usize,
+}
+
+impl ModerationCache {
+    #[must_use]
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            verdicts: Mutex::new(FxHashMap::default()),
+            capacity,
+        }
+    }
+
+    #[must_use]
+    pub(crate) fn get(&self, text: &str) -> Option<Arc<ModerationVerdict>> {
+        self.verdicts.lock().get(&content_key(text)).cloned()
+    }
+
+    /// Cache `verdict`; the cache starts over once it holds `capacity` entries.
+    pub(crate) fn insert(&self, text: &str, verdict: Arc<ModerationVerdict>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut verdicts = self.verdicts.lock();
+        if verdicts.len() >= self.capacity {
+            verdicts.clear();
+        }
+        verdicts.insert(content_key(text), verdict);
+    }
+}
+
+fn content_key(text: &str) -> (u64, usize) {
+    let mut hasher = FxHasher::default();
+    text.hash(&mut hasher);
+    (hasher.finish(), text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdicts_are_cached_by_content_and_bounded() {
+        let cache = ModerationCache::new(2);
+        let verdict = Arc::new(ModerationVerdict {
+            flagged: true,
+            categories: vec!["violence".to_string()],
+        });
+        cache.insert("a", Arc::clone(&verdict));
+        cache.insert("b", Arc::clone(&verdict));
+        assert_eq!(cache.get("a").as_deref(), Some(&*verdict));
+        assert!(cache.get("c").is_none());
+
+        cache.insert("c", Arc::clone(&verdict));
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("c").is_some());
+
+        let disabled = ModerationCache::new(0);
+        disabled.insert("a", verdict);
+        assert!(disabled.get("a").is_none());
+    }
+}
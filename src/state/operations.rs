@@ -0,0 +1,186 @@
+//! Long-poll operations behind `features.long_poll`.
+//!
+//! A non-streaming request that runs past the threshold keeps running in a
+//! background task while its client is handed an operation id. The task
+//! leaves the finished response here, where polls of `/v1/operations/{id}`
+//! pick it up until the result expires.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::response::Response;
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use tokio::sync::watch;
+
+use crate::config::LongPollConfig;
+use crate::protocol::canonical::IngressApi;
+use crate::util::unix_now_secs;
+
+/// Operations kept at once; further slow requests are answered in place.
+const MAX_OPERATIONS: usize = 4096;
+
+/// A finished operation's response, buffered.
+pub struct OperationResult {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl OperationResult {
+    #[must_use]
+    pub fn to_response(&self) -> Response {
+        let mut response = Response::new(axum::body::Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+/// A request running in the background for a long-polling client.
+pub struct Operation {
+    id: String,
+    ingress: IngressApi,
+    started: Instant,
+    created_at: u64,
+    result: watch::Sender<Option<(Instant, Arc<OperationResult>)>>,
+}
+
+impl Operation {
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Ingress of the request; polls authenticate and fail in its format.
+    #[must_use]
+    pub fn ingress(&self) -> IngressApi {
+        self.ingress
+    }
+
+    /// Unix seconds when the request arrived.
+    #[must_use]
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// Time since the request arrived.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The result, once the request finished within `wait`.
+    pub async fn wait(&self, wait: Duration) -> Option<Arc<OperationResult>> {
+        let mut finished = self.result.subscribe();
+        let _ = tokio::time::timeout(wait, finished.wait_for(Option::is_some)).await;
+        let result = finished.borrow();
+        result.as_ref().map(|(_, result)| Arc::clone(result))
+    }
+
+    /// Store the finished request's response and wake waiting polls.
+    pub fn finish(&self, result: OperationResult) {
+        self.result
+            .send_replace(Some((Instant::now(), Arc::new(result))));
+    }
+
+    fn expired(&self, ttl: Duration) -> bool {
+        self.result
+            .borrow()
+            .as_ref()
+            .is_some_and(|(finished, _)| finished.elapsed() >= ttl)
+    }
+}
+
+/// Operations by id.
+pub struct Operations {
+    threshold: Duration,
+    poll_wait: Duration,
+    ttl: Duration,
+    entries: Mutex<FxHashMap<String, Arc<Operation>>>,
+}
+
+impl Operations {
+    #[must_use]
+    pub fn new(config: &LongPollConfig) -> Self {
+        Self {
+            threshold: Duration::from_millis(config.threshold_ms),
+            poll_wait: Duration::from_millis(config.poll_wait_ms),
+            ttl: Duration::from_secs(config.result_ttl_secs),
+            entries: Mutex::new(FxHashMap::default()),
+        }
+    }
+
+    /// How long a request runs before its client gets an operation id.
+    #[must_use]
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// How long a poll waits for an unfinished operation.
+    #[must_use]
+    pub fn poll_wait(&self) -> Duration {
+        self.poll_wait
+    }
+
+    /// Track a request that started `started` ago; `None` when too many
+    /// operations are kept.
+    #[must_use]
+    pub fn start(&self, ingress: IngressApi, started: Instant) -> Option<Arc<Operation>> {
+        let mut entries = self.entries.lock();
+        entries.retain(|_, operation| !operation.expired(self.ttl));
+        if entries.len() >= MAX_OPERATIONS {
+            return None;
+        }
+        let created_at = unix_now_secs().saturating_sub(started.elapsed().as_secs());
+        let operation = Arc::new(Operation {
+            id: format!("op_{:032x}", fastrand::u128(..)),
+            ingress,
+            started,
+            created_at,
+            result: watch::Sender::new(None),
+        });
+        entries.insert(operation.id.clone(), Arc::clone(&operation));
+        Some(operation)
+    }
+
+    /// The operation `id`, unless its result expired.
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<Arc<Operation>> {
+        let operation = self.entries.lock().get(id).map(Arc::clone)?;
+        (!operation.expired(self.ttl)).then_some(operation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_poll_waits_for_result_until_it_expires() {
+        let config: LongPollConfig = serde_yaml::from_str("{}").unwrap();
+        let operations = Operations::new(&config);
+        let operation = operations
+            .start(IngressApi::OpenAiChat, Instant::now())
+            .unwrap();
+        assert!(operation.id().starts_with("op_"));
+        assert!(operation.wait(Duration::from_millis(1)).await.is_none());
+
+        let background = Arc::clone(&operation);
+        tokio::spawn(async move {
+            background.finish(OperationResult {
+                status: StatusCode::OK,
+                headers: HeaderMap::new(),
+                body: Bytes::from_static(b"{}"),
+            });
+        });
+        let result = operation.wait(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(result.status, StatusCode::OK);
+        assert!(operations.get(operation.id()).is_some());
+        assert!(operations.get("op_unknown").is_none());
+        assert!(!operation.expired(operations.ttl));
+        assert!(operation.expired(Duration::ZERO));
+    }
+}
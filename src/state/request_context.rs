@@ -0,0 +1,171 @@
+//! The per-request context of the compat flow.
+//!
+//! The flow runs each request inside [`scope`] with what it learned from the
+//! client request: stop sequences, prefill, beta flags, priority, and so on.
+//! Encoders and the transport read those through [`with`] several layers
+//! down, and note what happened on the way (the upstream that served the
+//! answer, dropped parameters, retries) into the context, which [`scope`]
+//! hands back once the request is done.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::api::common::DroppedParams;
+use crate::observability::journal::UpstreamErrorDetail;
+use crate::routing::capabilities::Capabilities;
+use crate::transport::response_headers::ResponseHeaderForwarding;
+use crate::transport::RequestPriority;
+
+use super::ResponseIds;
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: ScopedContext;
+}
+
+/// What the compat flow passes down to the layers serving one request.
+#[derive(Default)]
+pub(crate) struct RequestContext {
+    /// Client stop sequences to emulate on streams.
+    pub(crate) stop_sequences: Option<Arc<[String]>>,
+    /// The client's trailing assistant turn, if it is a prefill.
+    pub(crate) prefill: Option<Arc<str>>,
+    /// Beta flags from the client's `anthropic-beta` headers.
+    pub(crate) anthropic_betas: Option<Arc<[String]>>,
+    /// The client's `OpenAI-Organization` header.
+    pub(crate) openai_organization: Option<http::HeaderValue>,
+    /// The priority granted to the request.
+    pub(crate) priority: RequestPriority,
+    /// Capabilities route candidates must support.
+    pub(crate) required_capabilities: Capabilities,
+    /// The proxy selected by routing rules, over the upstream's own.
+    pub(crate) proxy: Option<Arc<str>>,
+    /// Which upstream response headers reach the client.
+    pub(crate) forward_response_headers: Option<Arc<ResponseHeaderForwarding>>,
+}
+
+/// The context with what the layers serving the request note into it.
+struct ScopedContext {
+    context: RequestContext,
+    /// Upstreams the current attempt must not be routed to.
+    excluded_upstreams: RefCell<Arc<[usize]>>,
+    attempt: RefCell<AttemptOutcome>,
+    outcome: RefCell<RequestOutcome>,
+}
+
+/// What one attempt at the request noted; reset by [`begin_attempt`].
+#[derive(Debug, Default)]
+pub(crate) struct AttemptOutcome {
+    /// The upstream that produced the response.
+    pub(crate) served_upstream: Option<usize>,
+    /// Client parameters adjusted for the upstream.
+    pub(crate) dropped_params: DroppedParams,
+}
+
+/// What the request noted across all of its attempts.
+#[derive(Debug, Default)]
+pub(crate) struct RequestOutcome {
+    /// The client and upstream ids of the last response.
+    pub(crate) response_ids: Option<ResponseIds>,
+    /// The last non-success upstream response.
+    pub(crate) upstream_error: Option<UpstreamErrorDetail>,
+    /// Upstream requests re-sent or failed over.
+    pub(crate) retries: u32,
+    /// Headers kept from the last successful upstream response.
+    pub(crate) forwarded_headers: http::HeaderMap,
+}
+
+/// Run `future` with `context`, returning its output with what the request
+/// noted inside it.
+pub(crate) async fn scope<F: Future>(
+    context: RequestContext,
+    future: F,
+) -> (F::Output, RequestOutcome) {
+    let scoped = ScopedContext {
+        context,
+        excluded_upstreams: RefCell::new(Arc::from([])),
+        attempt: RefCell::default(),
+        outcome: RefCell::default(),
+    };
+    REQUEST_CONTEXT
+        .scope(scoped, async move {
+            let output = future.await;
+            let outcome = REQUEST_CONTEXT.with(|scoped| scoped.outcome.take());
+            (output, outcome)
+        })
+        .await
+}
+
+/// Read the context of the request being served; `None` outside [`scope`].
+pub(crate) fn with<R>(read: impl FnOnce(&RequestContext) -> R) -> Option<R> {
+    REQUEST_CONTEXT
+        .try_with(|scoped| read(&scoped.context))
+        .ok()
+}
+
+/// Start another attempt that skips `excluded_upstreams`, clearing what the
+/// previous attempt noted.
+pub(crate) fn begin_attempt(excluded_upstreams: Arc<[usize]>) {
+    let _ = REQUEST_CONTEXT.try_with(|scoped| {
+        *scoped.excluded_upstreams.borrow_mut() = excluded_upstreams;
+        scoped.attempt.take();
+    });
+}
+
+/// What the current attempt noted so far.
+pub(crate) fn finish_attempt() -> AttemptOutcome {
+    REQUEST_CONTEXT
+        .try_with(|scoped| scoped.attempt.take())
+        .unwrap_or_default()
+}
+
+/// Note something about the current attempt. A no-op outside [`scope`].
+pub(crate) fn note_attempt(note: impl FnOnce(&mut AttemptOutcome)) {
+    let _ = REQUEST_CONTEXT.try_with(|scoped| note(&mut scoped.attempt.borrow_mut()));
+}
+
+/// Note something about the whole request. A no-op outside [`scope`].
+pub(crate) fn note_outcome(note: impl FnOnce(&mut RequestOutcome)) {
+    let _ = REQUEST_CONTEXT.try_with(|scoped| note(&mut scoped.outcome.borrow_mut()));
+}
+
+/// Whether the current attempt must not be routed to `upstream_index`.
+pub(crate) fn is_excluded(upstream_index: usize) -> bool {
+    REQUEST_CONTEXT
+        .try_with(|scoped| scoped.excluded_upstreams.borrow().contains(&upstream_index))
+        .unwrap_or(false)
+}
+
+/// Whether the current attempt skips any upstream.
+pub(crate) fn has_exclusions() -> bool {
+    REQUEST_CONTEXT
+        .try_with(|scoped| !scoped.excluded_upstreams.borrow().is_empty())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_attempts_reset_their_notes_but_not_the_request() {
+        note_outcome(|outcome| outcome.retries += 1);
+        let (attempts, outcome) = scope(RequestContext::default(), async {
+            note_attempt(|attempt| attempt.served_upstream = Some(0));
+            note_outcome(|outcome| outcome.retries += 1);
+            begin_attempt(Arc::from([0]));
+            assert!(is_excluded(0));
+            assert!(!is_excluded(1));
+            let first = finish_attempt();
+            note_attempt(|attempt| attempt.served_upstream = Some(1));
+            note_outcome(|outcome| outcome.retries += 1);
+            (first, finish_attempt())
+        })
+        .await;
+        assert_eq!(attempts.0.served_upstream, None);
+        assert_eq!(attempts.1.served_upstream, Some(1));
+        assert_eq!(outcome.retries, 2);
+        assert!(!has_exclusions());
+        assert!(finish_attempt().served_upstream.is_none());
+    }
+}
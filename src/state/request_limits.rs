@@ -0,0 +1,196 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::config::AppConfig;
+use crate::error::CanonicalError;
+use crate::observability::journal::ingress_label;
+use crate::protocol::canonical::IngressApi;
+
+/// Rejection counter slots for the global limit: one per ingress API, then
+/// one for admin and gRPC requests.
+const GLOBAL_SLOTS: [Option<IngressApi>; 5] = [
+    Some(IngressApi::OpenAiChat),
+    Some(IngressApi::OpenAiResponses),
+    Some(IngressApi::Anthropic),
+    Some(IngressApi::Gemini),
+    None,
+];
+
+/// Request body rejections under the global limit for one ingress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobalRequestRejections {
+    /// Ingress API label, `other` for admin and gRPC requests.
+    pub ingress: &'static str,
+    pub count: u64,
+}
+
+/// Request body rejections under one upstream's limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpstreamRequestRejections {
+    pub upstream_index: usize,
+    pub count: u64,
+}
+
+/// Request body size limits and how often each one turned a request away.
+pub(crate) struct RequestSizeLimits {
+    global: usize,
+    /// Limit for batch and file uploads, which count as global rejections.
+    upload: usize,
+    /// Per-upstream limit, `None` when the global limit already covers it.
+    upstream: Box<[Option<usize>]>,
+    global_rejections: [AtomicU64; GLOBAL_SLOTS.len()],
+    upstream_rejections: Box<[AtomicU64]>,
+}
+
+impl RequestSizeLimits {
+    #[must_use]
+    pub(crate) fn new(config: &AppConfig) -> Self {
+        let global = config.server.max_request_bytes;
+        let upstream: Box<[Option<usize>]> = config
+            .upstream_services
+            .iter()
+            .map(|upstream| upstream.max_request_bytes.filter(|&limit| limit < global))
+            .collect();
+        let upstream_rejections = upstream.iter().map(|_| AtomicU64::new(0)).collect();
+        Self {
+            global,
+            upload: config.server.max_upload_bytes.unwrap_or(global),
+            upstream,
+            global_rejections: Default::default(),
+            upstream_rejections,
+        }
+    }
+
+    /// Largest body any request may carry.
+    #[must_use]
+    pub(crate) fn global(&self) -> usize {
+        self.global
+    }
+
+    /// Largest batch or file upload body.
+    #[must_use]
+    pub(crate) fn upload(&self) -> usize {
+        self.upload
+    }
+
+    /// Count a body over the global or upload limit sent to `ingress`.
+    pub(crate) fn record_global_rejection(&self, ingress: Option<IngressApi>) {
+        if let Some(slot) = GLOBAL_SLOTS.iter().position(|slot| *slot == ingress) {
+            self.global_rejections[slot].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether a `len` byte body may be sent to `upstream_index`.
+    #[must_use]
+    pub(crate) fn fits_upstream(&self, upstream_index: usize, len: usize) -> bool {
+        self.upstream
+            .get(upstream_index)
+            .copied()
+            .flatten()
+            .is_none_or(|limit| len <= limit)
+    }
+
+    /// Reject a `len` byte body for `upstream_index` when it is over the
+    /// upstream's limit, counting the rejection.
+    pub(crate) fn check_upstream(
+        &self,
+        upstream_index: usize,
+        upstream_name: &str,
+        len: usize,
+    ) -> Result<(), CanonicalError> {
+        if self.fits_upstream(upstream_index, len) {
+            return Ok(());
+        }
+        self.upstream_rejections[upstream_index].fetch_add(1, Ordering::Relaxed);
+        let limit = self.upstream[upstream_index].unwrap_or(self.global);
+        Err(CanonicalError::RequestTooLarge(format!(
+            "request body of {len} bytes exceeds the {limit} byte limit of upstream '{upstream_name}'"
+        )))
+    }
+
+    #[must_use]
+    pub(crate) fn global_rejections(&self) -> Vec<GlobalRequestRejections> {
+        GLOBAL_SLOTS
+            .iter()
+            .zip(&self.global_rejections)
+            .map(|(ingress, count)| GlobalRequestRejections {
+                ingress: ingress.map_or("other", ingress_label),
+                count: count.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Rejection counts of upstreams with their own limit.
+    #[must_use]
+    pub(crate) fn upstream_rejections(&self) -> Vec<UpstreamRequestRejections> {
+        self.upstream
+            .iter()
+            .zip(self.upstream_rejections.iter())
+            .enumerate()
+            .filter(|(_, (limit, _))| limit.is_some())
+            .map(|(upstream_index, (_, count))| UpstreamRequestRejections {
+                upstream_index,
+                count: count.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upstream_limits_below_global_are_enforced_and_counted() {
+        let config: AppConfig = serde_yaml::from_str(
+            r#"
+server:
+  max_request_bytes: 100
+upstream_services:
+  - name: small
+    provider: openai
+    base_url: https://small.example/v1
+    api_key: k
+    models: [a]
+    is_default: true
+    max_request_bytes: 10
+  - name: default
+    provider: openai
+    base_url: https://default.example/v1
+    api_key: k
+    models: [b]
+  - name: large
+    provider: openai
+    base_url: https://large.example/v1
+    api_key: k
+    models: [c]
+    max_request_bytes: 500
+client_authentication:
+  allowed_keys: [c]
+"#,
+        )
+        .unwrap();
+        let limits = RequestSizeLimits::new(&config);
+        assert!(limits.check_upstream(0, "small", 10).is_ok());
+        let err = limits.check_upstream(0, "small", 11).unwrap_err();
+        assert!(matches!(err, CanonicalError::RequestTooLarge(_)));
+        assert!(limits.check_upstream(1, "default", 100).is_ok());
+        assert!(limits.fits_upstream(2, 100));
+        assert_eq!(
+            limits.upstream_rejections(),
+            [UpstreamRequestRejections {
+                upstream_index: 0,
+                count: 1
+            }]
+        );
+
+        limits.record_global_rejection(Some(IngressApi::Anthropic));
+        limits.record_global_rejection(None);
+        let counts: Vec<_> = limits
+            .global_rejections()
+            .into_iter()
+            .filter(|rejections| rejections.count > 0)
+            .map(|rejections| rejections.ingress)
+            .collect();
+        assert_eq!(counts, ["anthropic", "other"]);
+    }
+}
@@ -0,0 +1,230 @@
+//! `x-priority` policy: who may ask for high priority, and the optional local
+//! admission queue that lets higher priority requests in first.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::response::Response;
+use bytes::Bytes;
+use http::HeaderMap;
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+use crate::auth::{extract_api_key, key_entry_matches};
+use crate::config::AppConfig;
+use crate::protocol::canonical::IngressApi;
+use crate::transport::RequestPriority;
+
+/// `features.request_priority`, prepared for lookups.
+pub(crate) struct RequestPriorityPolicy {
+    /// `allowed_keys` entries granted high priority, plaintext or `sha256:` hash.
+    high_priority_keys: Vec<String>,
+    gate: Option<Arc<PriorityGate>>,
+}
+
+impl RequestPriorityPolicy {
+    #[must_use]
+    pub(crate) fn new(config: &AppConfig) -> Option<Self> {
+        let priority = config.features.request_priority.as_ref()?;
+        Some(Self {
+            high_priority_keys: priority.high_priority_keys.clone(),
+            gate: priority
+                .max_concurrent_requests
+                .map(|limit| Arc::new(PriorityGate::new(limit))),
+        })
+    }
+
+    /// The priority granted to a request: the one its `x-priority` header
+    /// asks for, with high priority only for keys allowed to use it.
+    #[must_use]
+    pub(crate) fn granted(&self, ingress: IngressApi, headers: &HeaderMap) -> RequestPriority {
+        match RequestPriority::from_headers(headers).unwrap_or_default() {
+            RequestPriority::High if !self.allows_high(ingress, headers) => RequestPriority::Normal,
+            priority => priority,
+        }
+    }
+
+    fn allows_high(&self, ingress: IngressApi, headers: &HeaderMap) -> bool {
+        let Ok(key) = extract_api_key(ingress, headers) else {
+            return false;
+        };
+        self.high_priority_keys
+            .iter()
+            .any(|entry| key_entry_matches(entry, key))
+    }
+
+    /// The local admission queue, when `max_concurrent_requests` is set.
+    #[must_use]
+    pub(crate) fn gate(&self) -> Option<&Arc<PriorityGate>> {
+        self.gate.as_ref()
+    }
+}
+
+struct GateState {
+    in_flight: usize,
+    /// Waiting requests per priority, lowest first.
+    waiting: [VecDeque<oneshot::Sender<()>>; 3],
+}
+
+/// Admits at most `limit` requests at once; when a slot frees up it goes to
+/// the longest waiting request of the highest waiting priority.
+pub(crate) struct PriorityGate {
+    limit: usize,
+    state: Mutex<GateState>,
+}
+
+impl PriorityGate {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(GateState {
+                in_flight: 0,
+                waiting: Default::default(),
+            }),
+        }
+    }
+
+    /// Wait for a slot; it is held until the permit is dropped.
+    pub(crate) async fn acquire(self: &Arc<Self>, priority: RequestPriority) -> PriorityPermit {
+        let receiver = {
+            let mut state = self.state.lock();
+            if state.in_flight < self.limit {
+                state.in_flight += 1;
+                return PriorityPermit {
+                    gate: Arc::clone(self),
+                };
+            }
+            let (sender, receiver) = oneshot::channel();
+            state.waiting[priority as usize].push_back(sender);
+            receiver
+        };
+        let mut waiter = Waiter {
+            gate: self,
+            receiver,
+        };
+        // The gate only drops a sender unsent once its waiter is gone, so
+        // this resolves with the slot handed over by `release`.
+        let _ = (&mut waiter.receiver).await;
+        PriorityPermit {
+            gate: Arc::clone(self),
+        }
+    }
+
+    /// Hand the slot of a finished request to the next waiter, or free it.
+    fn release(&self) {
+        let mut state = self.state.lock();
+        while let Some(sender) = state.waiting.iter_mut().rev().find_map(VecDeque::pop_front) {
+            if sender.send(()).is_ok() {
+                return;
+            }
+        }
+        state.in_flight -= 1;
+    }
+}
+
+/// A request waiting for a slot; gives back a slot handed over after the
+/// request stopped waiting.
+struct Waiter<'a> {
+    gate: &'a Arc<PriorityGate>,
+    receiver: oneshot::Receiver<()>,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.receiver.close();
+        if self.receiver.try_recv().is_ok() {
+            self.gate.release();
+        }
+    }
+}
+
+/// A slot of the [`PriorityGate`], freed on drop.
+pub struct PriorityPermit {
+    gate: Arc<PriorityGate>,
+}
+
+impl PriorityPermit {
+    /// Keep the slot until the body of `response` ends or is dropped.
+    pub(crate) fn hold_until_body_ends(self, response: Response) -> Response {
+        let (parts, body) = response.into_parts();
+        let stream = PermitStream {
+            inner: body.into_data_stream(),
+            _permit: self,
+        };
+        Response::from_parts(parts, axum::body::Body::from_stream(stream))
+    }
+}
+
+impl Drop for PriorityPermit {
+    fn drop(&mut self) {
+        self.gate.release();
+    }
+}
+
+pin_project_lite::pin_project! {
+    struct PermitStream<S> {
+        #[pin]
+        inner: S,
+        _permit: PriorityPermit,
+    }
+}
+
+impl<S> futures_util::Stream for PermitStream<S>
+where
+    S: futures_util::Stream<Item = Result<Bytes, axum::Error>>,
+{
+    type Item = Result<Bytes, axum::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().inner.poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_gate_admits_highest_priority_waiter_first() {
+        let gate = Arc::new(PriorityGate::new(1));
+        let first = gate.acquire(RequestPriority::Normal).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut waiters = Vec::new();
+        for priority in [
+            RequestPriority::Low,
+            RequestPriority::Normal,
+            RequestPriority::High,
+        ] {
+            let (gate, order) = (Arc::clone(&gate), Arc::clone(&order));
+            waiters.push(tokio::spawn(async move {
+                let _permit = gate.acquire(priority).await;
+                order.lock().push(priority);
+            }));
+            tokio::task::yield_now().await;
+        }
+        // A waiter that gives up does not keep the slot it is handed.
+        let abandoned = {
+            let gate = Arc::clone(&gate);
+            tokio::spawn(async move { gate.acquire(RequestPriority::High).await })
+        };
+        tokio::task::yield_now().await;
+        abandoned.abort();
+
+        drop(first);
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock(),
+            [
+                RequestPriority::High,
+                RequestPriority::Normal,
+                RequestPriority::Low
+            ]
+        );
+        assert_eq!(gate.state.lock().in_flight, 0);
+    }
+}
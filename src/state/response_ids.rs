@@ -0,0 +1,115 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+
+use super::request_context;
+
+/// Ids kept in the table; expired mappings are dropped once it is full.
+const RESPONSE_ID_MAX_ENTRIES: usize = 16 * 1024;
+/// How long a mapping can be looked up after the response was served.
+const RESPONSE_ID_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Note that the response the client sees as `client_id` is `upstream_id`
+/// at the upstream.
+///
+/// A no-op outside [`request_context::scope`].
+pub(crate) fn note_response_ids(client_id: &str, upstream_id: &str) {
+    if client_id.is_empty() || upstream_id.is_empty() {
+        return;
+    }
+    request_context::note_outcome(|outcome| {
+        outcome.response_ids = Some(ResponseIds {
+            client_id: client_id.to_string(),
+            upstream_id: upstream_id.to_string(),
+        });
+    });
+}
+
+/// The id a client received for a response and the id the upstream gave it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseIds {
+    pub client_id: String,
+    pub upstream_id: String,
+}
+
+/// A served response, found by either of its ids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseIdMapping {
+    pub ids: Arc<ResponseIds>,
+    pub upstream: Arc<str>,
+}
+
+struct MappingEntry {
+    mapping: ResponseIdMapping,
+    expires_at: Instant,
+}
+
+/// Recently served responses keyed by both their client and upstream ids.
+pub(crate) struct ResponseIdMap {
+    entries: Mutex<FxHashMap<Box<str>, MappingEntry>>,
+}
+
+impl ResponseIdMap {
+    #[must_use]
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Mutex::new(FxHashMap::default()),
+        }
+    }
+
+    pub(crate) fn record(&self, ids: ResponseIds, upstream: Arc<str>) {
+        let now = Instant::now();
+        let ids = Arc::new(ids);
+        let mut entries = self.entries.lock();
+        if entries.len() + 2 > RESPONSE_ID_MAX_ENTRIES {
+            entries.retain(|_, entry| entry.expires_at > now);
+            if entries.len() + 2 > RESPONSE_ID_MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+        let mapping = ResponseIdMapping { ids, upstream };
+        for id in [&mapping.ids.client_id, &mapping.ids.upstream_id] {
+            entries.insert(
+                Box::from(id.as_str()),
+                MappingEntry {
+                    mapping: mapping.clone(),
+                    expires_at: now + RESPONSE_ID_TTL,
+                },
+            );
+        }
+    }
+
+    /// The mapping of the response whose client or upstream id is `id`.
+    #[must_use]
+    pub(crate) fn lookup(&self, id: &str) -> Option<ResponseIdMapping> {
+        let entries = self.entries.lock();
+        let entry = entries.get(id)?;
+        (entry.expires_at > Instant::now()).then(|| entry.mapping.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_noted_ids_are_found_by_either_id() {
+        let ((), outcome) = request_context::scope(Default::default(), async {
+            note_response_ids("chatcmpl-1", "");
+            note_response_ids("chatcmpl-1", "msg_01");
+        })
+        .await;
+        let ids = outcome.response_ids.unwrap();
+        assert_eq!(ids.upstream_id, "msg_01");
+
+        let map = ResponseIdMap::new();
+        map.record(ids, Arc::from("anthropic"));
+        let by_client = map.lookup("chatcmpl-1").unwrap();
+        assert_eq!(by_client.ids.upstream_id, "msg_01");
+        assert_eq!(&*by_client.upstream, "anthropic");
+        assert_eq!(map.lookup("msg_01"), Some(by_client));
+        assert!(map.lookup("chatcmpl-2").is_none());
+    }
+}
@@ -11,6 +11,18 @@ struct RouteBreakerState {
     consecutive_failures: u32,
     open_until_unix: u64,
     half_open_probe_in_flight: bool,
+    /// Last 429/529 status that put the route into cooldown, 0 when none.
+    throttle_status: u16,
+}
+
+/// Point-in-time view of a route that is failing, throttled, or cooling down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteCooldownStatus {
+    pub upstream_index: usize,
+    pub model_group: String,
+    pub consecutive_failures: u32,
+    pub throttle_status: Option<u16>,
+    pub cooldown_remaining_secs: u64,
 }
 
 pub(crate) struct RouteBreakerRegistry {
@@ -20,6 +32,8 @@ pub(crate) struct RouteBreakerRegistry {
 }
 
 const ROUTE_BREAKER_FAILURE_THRESHOLD: u32 = 5;
+const ROUTE_COOLDOWN_DEFAULT_SECS: u64 = 5;
+const ROUTE_COOLDOWN_MAX_SECS: u64 = 300;
 
 impl RouteBreakerRegistry {
     #[must_use]
@@ -85,6 +99,10 @@ impl RouteBreakerRegistry {
             let open_secs = route_breaker_open_secs(state.consecutive_failures);
             state.open_until_unix = now.saturating_add(open_secs);
         }
+        if let Some((status, cooldown_secs)) = throttle_cooldown(err) {
+            state.throttle_status = status;
+            state.open_until_unix = state.open_until_unix.max(now.saturating_add(cooldown_secs));
+        }
 
         if breaker_map.len() > 256 {
             breaker_map.retain(|_, breaker_state| {
@@ -117,6 +135,38 @@ impl RouteBreakerRegistry {
         self.allows_request(upstream_index, model_group)
     }
 
+    /// Collect every tracked route, with remaining cooldown relative to now.
+    #[must_use]
+    pub(crate) fn cooldown_snapshot(&self) -> Vec<RouteCooldownStatus> {
+        if !self.has_any_entries() {
+            return Vec::new();
+        }
+        let now = unix_now_secs();
+        let mut statuses = Vec::new();
+        for (upstream_index, shard) in self.shards.iter().enumerate() {
+            if !self.has_entries[upstream_index].load(Ordering::Acquire) {
+                continue;
+            }
+            let breaker_map = shard.read();
+            statuses.extend(
+                breaker_map
+                    .iter()
+                    .map(|(model_group, state)| RouteCooldownStatus {
+                        upstream_index,
+                        model_group: model_group.clone(),
+                        consecutive_failures: state.consecutive_failures,
+                        throttle_status: (state.throttle_status != 0)
+                            .then_some(state.throttle_status),
+                        cooldown_remaining_secs: state.open_until_unix.saturating_sub(now),
+                    }),
+            );
+        }
+        statuses.sort_by(|a, b| {
+            (a.upstream_index, &a.model_group).cmp(&(b.upstream_index, &b.model_group))
+        });
+        statuses
+    }
+
     #[must_use]
     pub(crate) fn has_any_entries(&self) -> bool {
         self.active_shards.load(Ordering::Acquire) != 0
@@ -187,7 +237,7 @@ impl RouteBreakerRegistry {
 #[must_use]
 pub(crate) fn should_try_alternate_upstream(err: &CanonicalError) -> bool {
     match err {
-        CanonicalError::Transport(_) => true,
+        CanonicalError::Transport(_) | CanonicalError::Timeout(_) => true,
         CanonicalError::Upstream { status, .. } => {
             matches!(*status, 408 | 425 | 429 | 500 | 502 | 503 | 504 | 529)
         }
@@ -198,7 +248,7 @@ pub(crate) fn should_try_alternate_upstream(err: &CanonicalError) -> bool {
 #[inline]
 fn should_record_breaker_failure(err: &CanonicalError) -> bool {
     match err {
-        CanonicalError::Transport(_) => true,
+        CanonicalError::Transport(_) | CanonicalError::Timeout(_) => true,
         CanonicalError::Upstream { status, .. } => {
             matches!(*status, 429 | 529) || (500..=599).contains(status)
         }
@@ -206,6 +256,26 @@ fn should_record_breaker_failure(err: &CanonicalError) -> bool {
     }
 }
 
+/// Cooldown for throttling responses (429 / Anthropic 529 overloaded).
+///
+/// Honors the upstream `retry-after` hint, bounded to keep a misbehaving
+/// upstream from parking a route for hours.
+#[inline]
+fn throttle_cooldown(err: &CanonicalError) -> Option<(u16, u64)> {
+    let CanonicalError::Upstream {
+        status: status @ (429 | 529),
+        retry_after,
+        ..
+    } = err
+    else {
+        return None;
+    };
+    let cooldown_secs = retry_after
+        .unwrap_or(ROUTE_COOLDOWN_DEFAULT_SECS)
+        .clamp(1, ROUTE_COOLDOWN_MAX_SECS);
+    Some((*status, cooldown_secs))
+}
+
 #[inline]
 fn route_breaker_open_secs(consecutive_failures: u32) -> u64 {
     match consecutive_failures.saturating_sub(ROUTE_BREAKER_FAILURE_THRESHOLD) {
@@ -215,3 +285,57 @@ fn route_breaker_open_secs(consecutive_failures: u32) -> u64 {
         _ => 120,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttled(status: u16, retry_after: Option<u64>) -> CanonicalError {
+        CanonicalError::Upstream {
+            status,
+            message: "slow down".to_string(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_puts_route_into_cooldown_immediately() {
+        let registry = RouteBreakerRegistry::new(2);
+        registry.record_failure(1, "m", &throttled(429, Some(30)));
+
+        assert!(!registry.allows_route(1, "m"));
+        assert!(registry.allows_route(1, "other"));
+        assert!(registry.allows_route(0, "m"));
+
+        let snapshot = registry.cooldown_snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].upstream_index, 1);
+        assert_eq!(snapshot[0].model_group, "m");
+        assert_eq!(snapshot[0].throttle_status, Some(429));
+        assert!(snapshot[0].cooldown_remaining_secs > 25);
+    }
+
+    #[test]
+    fn test_overloaded_cooldown_is_bounded() {
+        let registry = RouteBreakerRegistry::new(1);
+        registry.record_failure(0, "m", &throttled(529, Some(86_400)));
+
+        let snapshot = registry.cooldown_snapshot();
+        assert_eq!(snapshot[0].throttle_status, Some(529));
+        assert!(snapshot[0].cooldown_remaining_secs <= ROUTE_COOLDOWN_MAX_SECS);
+    }
+
+    #[test]
+    fn test_server_error_below_threshold_does_not_cool_down() {
+        let registry = RouteBreakerRegistry::new(1);
+        registry.record_failure(0, "m", &throttled(500, Some(30)));
+
+        assert!(registry.allows_route(0, "m"));
+        let snapshot = registry.cooldown_snapshot();
+        assert_eq!(snapshot[0].throttle_status, None);
+        assert_eq!(snapshot[0].cooldown_remaining_secs, 0);
+
+        registry.record_success(0, "m");
+        assert!(registry.cooldown_snapshot().is_empty());
+    }
+}
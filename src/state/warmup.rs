@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::RwLock;
+
+/// Outcome of warming up one upstream's connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamWarmup {
+    pub upstream_index: usize,
+    /// HTTP status of the warm-up request; any status means the connection is usable.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+/// Startup warm-up progress backing `/health/ready`.
+pub(crate) struct WarmupState {
+    pending: AtomicBool,
+    results: RwLock<Vec<UpstreamWarmup>>,
+}
+
+impl WarmupState {
+    #[must_use]
+    pub(crate) fn new(pending: bool) -> Self {
+        Self {
+            pending: AtomicBool::new(pending),
+            results: RwLock::new(Vec::new()),
+        }
+    }
+
+    #[must_use]
+    pub(crate) fn is_pending(&self) -> bool {
+        self.pending.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn finish(&self, results: Vec<UpstreamWarmup>) {
+        *self.results.write() = results;
+        self.pending.store(false, Ordering::Release);
+    }
+
+    #[must_use]
+    pub(crate) fn results(&self) -> Vec<UpstreamWarmup> {
+        self.results.read().clone()
+    }
+}
@@ -0,0 +1,261 @@
+//! Fan-out of in-progress client streams to additional subscribers.
+//!
+//! A stream published under a session key buffers every body chunk it sends
+//! to its client. Subscribers attaching while it runs receive the buffered
+//! prefix followed by live chunks, byte-for-byte identical to what the
+//! original client sees. The key is dropped when the stream ends.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::response::Response;
+use bytes::Bytes;
+use futures_util::Stream;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use tokio::sync::watch;
+
+/// Request header naming the session a stream is published under.
+pub const STREAM_SESSION_HEADER: &str = "x-toolify-session";
+/// Longest accepted session key.
+const MAX_SESSION_KEY_BYTES: usize = 256;
+/// Streams published at once; further streams are served but not published.
+const MAX_ACTIVE_BROADCASTS: usize = 1024;
+/// Buffered bytes per stream; past this the broadcast ends for subscribers.
+const MAX_BUFFERED_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Default)]
+struct HubFrames {
+    chunks: Vec<Bytes>,
+    buffered_bytes: usize,
+    done: bool,
+}
+
+struct StreamHub {
+    frames: Mutex<HubFrames>,
+    /// Bumped after every change of `frames` to wake subscribers.
+    version: watch::Sender<u64>,
+}
+
+impl StreamHub {
+    fn new() -> Self {
+        Self {
+            frames: Mutex::new(HubFrames::default()),
+            version: watch::Sender::new(0),
+        }
+    }
+
+    /// Buffer `chunk`; ignored once the hub has ended.
+    fn publish(&self, chunk: &Bytes) {
+        {
+            let mut frames = self.frames.lock();
+            if frames.done {
+                return;
+            }
+            if frames.buffered_bytes + chunk.len() > MAX_BUFFERED_BYTES {
+                frames.done = true;
+            } else {
+                frames.buffered_bytes += chunk.len();
+                frames.chunks.push(chunk.clone());
+            }
+        }
+        self.version.send_modify(|version| *version += 1);
+    }
+
+    fn finish(&self) {
+        self.frames.lock().done = true;
+        self.version.send_modify(|version| *version += 1);
+    }
+}
+
+/// In-progress streams by session key.
+#[derive(Default)]
+pub struct StreamBroadcasts {
+    hubs: Mutex<FxHashMap<String, Arc<StreamHub>>>,
+}
+
+impl StreamBroadcasts {
+    /// Publish the body of a successful SSE `response` under `session`.
+    ///
+    /// A newer stream for the same session replaces the previous one for new
+    /// subscribers. Other responses, and streams beyond the active limit, are
+    /// returned unchanged.
+    pub fn publish(self: &Arc<Self>, response: Response, session: &str) -> Response {
+        let is_sse = response
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !is_sse
+            || !response.status().is_success()
+            || session.is_empty()
+            || session.len() > MAX_SESSION_KEY_BYTES
+        {
+            return response;
+        }
+        let hub = Arc::new(StreamHub::new());
+        {
+            let mut hubs = self.hubs.lock();
+            if hubs.len() >= MAX_ACTIVE_BROADCASTS && !hubs.contains_key(session) {
+                return response;
+            }
+            if let Some(previous) = hubs.insert(session.to_string(), Arc::clone(&hub)) {
+                previous.finish();
+            }
+        }
+        let (parts, body) = response.into_parts();
+        let tap = BroadcastTapStream {
+            inner: body.into_data_stream(),
+            publisher: Publisher {
+                broadcasts: Arc::clone(self),
+                session: session.to_string(),
+                hub,
+            },
+        };
+        Response::from_parts(parts, axum::body::Body::from_stream(tap))
+    }
+
+    /// Attach to the stream in progress for `session`.
+    ///
+    /// The returned stream yields the buffered prefix, then live chunks, and
+    /// ends with the published stream. `None` when no stream is in progress.
+    #[must_use]
+    pub fn subscribe(
+        &self,
+        session: &str,
+    ) -> Option<impl Stream<Item = Result<Bytes, std::convert::Infallible>> + Send + 'static> {
+        let hub = Arc::clone(self.hubs.lock().get(session)?);
+        let updates = hub.version.subscribe();
+        Some(futures_util::stream::unfold(
+            (hub, updates, 0usize),
+            |(hub, mut updates, next)| async move {
+                loop {
+                    {
+                        let frames = hub.frames.lock();
+                        if let Some(chunk) = frames.chunks.get(next) {
+                            let chunk = chunk.clone();
+                            drop(frames);
+                            return Some((Ok(chunk), (hub, updates, next + 1)));
+                        }
+                        if frames.done {
+                            return None;
+                        }
+                    }
+                    if updates.changed().await.is_err() {
+                        return None;
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Number of streams currently published.
+    #[must_use]
+    pub fn active_count(&self) -> usize {
+        self.hubs.lock().len()
+    }
+}
+
+/// Ends the broadcast when the published stream finishes, fails, or is dropped.
+struct Publisher {
+    broadcasts: Arc<StreamBroadcasts>,
+    session: String,
+    hub: Arc<StreamHub>,
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        self.hub.finish();
+        let mut hubs = self.broadcasts.hubs.lock();
+        if hubs
+            .get(&self.session)
+            .is_some_and(|hub| Arc::ptr_eq(hub, &self.hub))
+        {
+            hubs.remove(&self.session);
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    struct BroadcastTapStream<S> {
+        #[pin]
+        inner: S,
+        publisher: Publisher,
+    }
+}
+
+impl<S, E> Stream for BroadcastTapStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let polled = this.inner.poll_next(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(chunk))) if !chunk.is_empty() => {
+                this.publisher.hub.publish(chunk);
+            }
+            Poll::Ready(None | Some(Err(_))) => this.publisher.hub.finish(),
+            Poll::Ready(Some(Ok(_))) | Poll::Pending => {}
+        }
+        polled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn sse_response(body: axum::body::Body) -> Response {
+        Response::builder()
+            .header(http::header::CONTENT_TYPE, "text/event-stream")
+            .body(body)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_late_subscriber_gets_prefix_then_live_chunks() {
+        let broadcasts = Arc::new(StreamBroadcasts::default());
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<Bytes>();
+        let upstream = futures_util::stream::unfold(rx, |mut rx| async move {
+            rx.recv()
+                .await
+                .map(|chunk| (Ok::<_, std::io::Error>(chunk), rx))
+        });
+        let response = broadcasts.publish(
+            sse_response(axum::body::Body::from_stream(upstream)),
+            "session-1",
+        );
+        let mut origin = response.into_body().into_data_stream();
+
+        tx.send(Bytes::from_static(b"data: 1\n\n")).unwrap();
+        assert_eq!(origin.next().await.unwrap().unwrap(), "data: 1\n\n");
+
+        let subscriber = broadcasts
+            .subscribe("session-1")
+            .expect("stream in progress");
+        assert!(broadcasts.subscribe("other").is_none());
+        tx.send(Bytes::from_static(b"data: 2\n\n")).unwrap();
+        drop(tx);
+        assert_eq!(origin.next().await.unwrap().unwrap(), "data: 2\n\n");
+        assert!(origin.next().await.is_none());
+
+        let received: Vec<Bytes> = subscriber.map(Result::unwrap).collect().await;
+        assert_eq!(received, vec!["data: 1\n\n", "data: 2\n\n"]);
+        drop(origin);
+        assert_eq!(broadcasts.active_count(), 0);
+        assert!(broadcasts.subscribe("session-1").is_none());
+    }
+
+    #[test]
+    fn test_non_stream_responses_are_not_published() {
+        let broadcasts = Arc::new(StreamBroadcasts::default());
+        let response = Response::new(axum::body::Body::from("{}"));
+        let _response = broadcasts.publish(response, "session-1");
+        assert_eq!(broadcasts.active_count(), 0);
+    }
+}
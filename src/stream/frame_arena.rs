@@ -0,0 +1,72 @@
+//! Reused output buffers for encoded client frames.
+//!
+//! Encoding each frame into a fresh `String` and handing it to the response
+//! body costs an allocation per frame. A [`FrameArena`] lives as long as one
+//! streamed response: frames are written into a reused scratch string, copied
+//! into a shared block, and split off it as `Bytes`. Once the body has sent
+//! and dropped the earlier frames, the block is reclaimed in place, so steady
+//! state streaming allocates nothing per frame.
+
+use bytes::{Bytes, BytesMut};
+
+/// Block size; one block holds many typical delta frames.
+const BLOCK_SIZE: usize = 16 * 1024;
+/// A scratch string grown past this by one large frame is not kept.
+const MAX_SCRATCH_CAPACITY: usize = 64 * 1024;
+
+#[derive(Debug, Default)]
+pub(crate) struct FrameArena {
+    scratch: String,
+    block: BytesMut,
+}
+
+impl FrameArena {
+    /// Run `write` on the cleared scratch string and return what it wrote,
+    /// or `None` when it reports that the event has no frame.
+    pub(crate) fn encode(&mut self, write: impl FnOnce(&mut String) -> bool) -> Option<Bytes> {
+        self.scratch.clear();
+        if !write(&mut self.scratch) {
+            return None;
+        }
+        let len = self.scratch.len();
+        if self.block.capacity() < len {
+            // Reuses the block when every frame split from it was dropped.
+            self.block.reserve(len.max(BLOCK_SIZE));
+        }
+        self.block.extend_from_slice(self.scratch.as_bytes());
+        if self.scratch.capacity() > MAX_SCRATCH_CAPACITY {
+            self.scratch = String::new();
+        }
+        Some(self.block.split().freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(text: &str) -> impl FnOnce(&mut String) -> bool + '_ {
+        move |out| {
+            out.push_str(text);
+            true
+        }
+    }
+
+    #[test]
+    fn test_frames_share_a_block_that_is_reclaimed_once_dropped() {
+        let mut arena = FrameArena::default();
+        let first = arena.encode(write("data: a\n\n")).unwrap();
+        let second = arena.encode(write("data: b\n\n")).unwrap();
+        assert_eq!(&second[..], b"data: b\n\n");
+        assert_eq!(second.as_ptr(), first[first.len()..].as_ptr());
+        assert!(arena.encode(|_| false).is_none());
+
+        let block_start = first.as_ptr();
+        drop((first, second));
+        let frame = "x".repeat(1024);
+        let reused = (0..BLOCK_SIZE / frame.len() + 1)
+            .map(|_| arena.encode(write(&frame)).unwrap().as_ptr())
+            .any(|ptr| ptr == block_start);
+        assert!(reused);
+    }
+}
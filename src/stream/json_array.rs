@@ -0,0 +1,262 @@
+//! Gemini JSON-array streams.
+//!
+//! `streamGenerateContent` answers with SSE frames for `alt=sse` and otherwise
+//! (`alt=json`) with one JSON array whose elements arrive as the model writes
+//! them: `[{...}\n,\r\n{...}\n]`. Upstream arrays are re-framed as SSE so the
+//! rest of the pipeline sees one wire format; clients that asked for
+//! `alt=json` get the SSE output folded back into an array.
+
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_util::{Stream, StreamExt};
+
+use super::sse_frame_stream;
+
+/// Splits a streamed top-level JSON array into its elements.
+#[derive(Debug, Default)]
+pub struct JsonArraySplitter {
+    started: bool,
+    finished: bool,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    element: Vec<u8>,
+}
+
+impl JsonArraySplitter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next `chunk` of the array, calling `emit` with every element
+    /// it completes. Line breaks outside strings are dropped, so elements
+    /// come out on one line.
+    pub fn feed(&mut self, chunk: &[u8], mut emit: impl FnMut(&[u8])) {
+        for &byte in chunk {
+            if self.finished {
+                return;
+            }
+            if self.in_string {
+                self.element.push(byte);
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+            match byte {
+                b'\n' | b'\r' => {}
+                b'[' if !self.started => self.started = true,
+                _ if !self.started => {}
+                b',' | b']' if self.depth == 0 => {
+                    self.flush(&mut emit);
+                    self.finished = byte == b']';
+                }
+                b' ' | b'\t' if self.depth == 0 => {}
+                _ => {
+                    self.element.push(byte);
+                    match byte {
+                        b'"' => self.in_string = true,
+                        b'{' | b'[' => self.depth += 1,
+                        b'}' | b']' => {
+                            self.depth = self.depth.saturating_sub(1);
+                            if self.depth == 0 {
+                                self.flush(&mut emit);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self, emit: &mut impl FnMut(&[u8])) {
+        if !self.element.is_empty() {
+            emit(&self.element);
+            self.element.clear();
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Framing {
+    /// No non-whitespace byte seen yet; the bytes so far are kept.
+    Detect(Vec<u8>),
+    Array(JsonArraySplitter),
+    Passthrough,
+}
+
+pin_project_lite::pin_project! {
+    /// A byte stream that re-frames a JSON-array body as SSE `data:` frames.
+    pub struct JsonArrayFrames<S> {
+        #[pin]
+        inner: S,
+        framing: Framing,
+    }
+}
+
+/// Re-frame `byte_stream` as SSE when it turns out to be a JSON array.
+///
+/// The framing is detected from the first non-whitespace byte: `[` starts an
+/// array, anything else passes through unchanged, as does every stream when
+/// `detect` is false.
+pub fn json_array_to_sse<S>(byte_stream: S, detect: bool) -> JsonArrayFrames<S> {
+    JsonArrayFrames {
+        inner: byte_stream,
+        framing: if detect {
+            Framing::Detect(Vec::new())
+        } else {
+            Framing::Passthrough
+        },
+    }
+}
+
+impl<S, E> Stream for JsonArrayFrames<S>
+where
+    S: Stream<Item = Result<bytes::Bytes, E>>,
+{
+    type Item = Result<bytes::Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            let chunk = match ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => chunk,
+                None => {
+                    if let Framing::Detect(held) = this.framing {
+                        let held = std::mem::take(held);
+                        *this.framing = Framing::Passthrough;
+                        if !held.is_empty() {
+                            return Poll::Ready(Some(Ok(bytes::Bytes::from(held))));
+                        }
+                    }
+                    return Poll::Ready(None);
+                }
+                other => return Poll::Ready(other),
+            };
+            let chunk = match this.framing {
+                Framing::Passthrough => return Poll::Ready(Some(Ok(chunk))),
+                Framing::Detect(held) => {
+                    let Some(first) = chunk.iter().find(|byte| !byte.is_ascii_whitespace()) else {
+                        held.extend_from_slice(&chunk);
+                        continue;
+                    };
+                    let is_array = *first == b'[';
+                    held.extend_from_slice(&chunk);
+                    let held = bytes::Bytes::from(std::mem::take(held));
+                    if !is_array {
+                        *this.framing = Framing::Passthrough;
+                        return Poll::Ready(Some(Ok(held)));
+                    }
+                    *this.framing = Framing::Array(JsonArraySplitter::new());
+                    held
+                }
+                Framing::Array(_) => chunk,
+            };
+            let Framing::Array(splitter) = this.framing else {
+                unreachable!("only arrays are split");
+            };
+            let mut frames = Vec::new();
+            splitter.feed(&chunk, |element| {
+                frames.extend_from_slice(b"data: ");
+                frames.extend_from_slice(element);
+                frames.extend_from_slice(b"\n\n");
+            });
+            if !frames.is_empty() {
+                return Poll::Ready(Some(Ok(bytes::Bytes::from(frames))));
+            }
+        }
+    }
+}
+
+/// Fold an SSE `byte_stream` into a JSON-array body, one element per frame.
+pub fn sse_to_json_array<S, E>(
+    byte_stream: S,
+) -> impl Stream<Item = Result<bytes::Bytes, std::convert::Infallible>> + Send
+where
+    S: Stream<Item = Result<bytes::Bytes, E>> + Send + 'static,
+    E: std::fmt::Debug + Send + 'static,
+{
+    let frames = Box::pin(sse_frame_stream(byte_stream));
+    futures_util::stream::unfold(
+        (frames, true, false),
+        |(mut frames, first, done)| async move {
+            if done {
+                return None;
+            }
+            let Some(frame) = frames.next().await else {
+                let close = if first { "[]" } else { "]" };
+                return Some((
+                    Ok(bytes::Bytes::from_static(close.as_bytes())),
+                    (frames, first, true),
+                ));
+            };
+            let separator = if first { "[" } else { ",\r\n" };
+            let element = format!("{separator}{}\n", frame.data);
+            Some((Ok(bytes::Bytes::from(element)), (frames, false, false)))
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn collect<S: Stream<Item = Result<bytes::Bytes, std::io::Error>>>(stream: S) -> String {
+        let chunks: Vec<_> = stream.collect().await;
+        chunks
+            .into_iter()
+            .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    fn chunks(parts: &[&'static str]) -> impl Stream<Item = Result<bytes::Bytes, std::io::Error>> {
+        futures_util::stream::iter(
+            parts
+                .iter()
+                .map(|part| Ok(bytes::Bytes::from_static(part.as_bytes())))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_json_array_split_across_chunks_becomes_sse_frames() {
+        let body = collect(json_array_to_sse(
+            chunks(&[
+                "\n[{\n  \"text\": \"a, ]\\\"}\"",
+                "\n}\n,\r\n{\"n\": [1, ",
+                "2]}\n]\n",
+            ]),
+            true,
+        ))
+        .await;
+        assert_eq!(
+            body,
+            "data: {  \"text\": \"a, ]\\\"}\"}\n\ndata: {\"n\": [1, 2]}\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sse_passes_through_and_folds_into_an_array() {
+        let sse = ["data: {\"a\":1}\n\n", "data: {\"b\":2}\n\n"];
+        assert_eq!(
+            collect(json_array_to_sse(chunks(&sse), true)).await,
+            sse.concat()
+        );
+
+        let array: Vec<_> = sse_to_json_array(chunks(&sse)).collect().await;
+        let array: String = array
+            .into_iter()
+            .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+            .collect();
+        assert_eq!(array, "[{\"a\":1}\n,\r\n{\"b\":2}\n]");
+        let parsed: serde_json::Value = serde_json::from_str(&array).unwrap();
+        assert_eq!(parsed[1]["b"], 2);
+    }
+}
@@ -0,0 +1,92 @@
+//! Process-wide accounting of per-request stream buffers.
+//!
+//! Stream frame buffers, function-call detector buffers, and streams
+//! aggregated into one response hold a [`BufferCharge`] that adds their size
+//! to one process-wide total. With `features.memory_budget`, new streaming
+//! requests are refused while the total is over the budget, so a burst of
+//! large streams cannot push the process into an OOM kill.
+//!
+//! The budget is partial: non-streaming responses buffered whole, and
+//! request bodies, are bounded by their own size limits and not charged.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+static BUFFERED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static REJECTIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Bytes currently held by charged buffers.
+#[must_use]
+pub fn buffered_bytes() -> usize {
+    BUFFERED_BYTES.load(Ordering::Relaxed)
+}
+
+/// Whether the charged buffers hold more than `max_buffered_bytes`.
+#[must_use]
+pub fn over_budget(max_buffered_bytes: usize) -> bool {
+    buffered_bytes() > max_buffered_bytes
+}
+
+/// Count a request refused for the memory budget.
+pub fn note_rejection() {
+    REJECTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Requests refused for the memory budget since the process started.
+#[must_use]
+pub fn rejections() -> u64 {
+    REJECTIONS.load(Ordering::Relaxed)
+}
+
+/// One buffer's share of [`buffered_bytes`], released on drop.
+#[derive(Debug, Default)]
+pub struct BufferCharge {
+    charged: usize,
+}
+
+impl BufferCharge {
+    /// Charge the buffer at its current allocation of `capacity` bytes.
+    ///
+    /// A buffer's capacity only changes when it reallocates or is taken, so
+    /// most calls leave the shared counter alone.
+    #[inline]
+    pub fn track(&mut self, capacity: usize) {
+        let charged = capacity;
+        if charged == self.charged {
+            return;
+        }
+        if charged > self.charged {
+            BUFFERED_BYTES.fetch_add(charged - self.charged, Ordering::Relaxed);
+        } else {
+            BUFFERED_BYTES.fetch_sub(self.charged - charged, Ordering::Relaxed);
+        }
+        self.charged = charged;
+    }
+}
+
+impl Drop for BufferCharge {
+    fn drop(&mut self) {
+        self.track(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charge_follows_the_tracked_capacity() {
+        // Other tests charge the shared counter concurrently, so only this
+        // charge's own bookkeeping is asserted.
+        let mut charge = BufferCharge::default();
+        charge.track(1);
+        assert_eq!(charge.charged, 1);
+        charge.track(4097);
+        assert_eq!(charge.charged, 4097);
+        charge.track(0);
+        assert_eq!(charge.charged, 0);
+
+        charge.track(3 * 4096);
+        assert!(buffered_bytes() >= 3 * 4096);
+        assert!(over_budget(4096));
+    }
+}
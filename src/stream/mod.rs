@@ -1,16 +1,35 @@
+#[cfg(feature = "server")]
+pub mod broadcast;
+mod frame_arena;
+pub mod json_array;
+pub mod memory_budget;
+pub mod prefill;
+#[cfg(feature = "server")]
+pub mod resumable;
 pub mod sse;
+pub mod stop_sequences;
+mod string_pool;
+pub mod text_pipeline;
+mod tool_args;
 pub mod transcoder;
+mod utf8_boundary;
 
 pub use sse::{sse_frame_stream, SseFrame, SseParser};
-pub use transcoder::StreamTranscoder;
+pub use transcoder::{StreamTranscoder, StreamTranscoderBuilder};
 
 use crate::error::CanonicalError;
 use crate::fc::detector::{DetectorAction, DetectorState, StreamingFcDetector};
+use crate::fc::parse_failures::FcParseFailureSink;
 use crate::fc::parser::parse_function_calls;
 use crate::fc::parser::ParsedToolCall;
 use crate::protocol::canonical::{CanonicalStopReason, CanonicalStreamEvent, CanonicalToolSpec};
 use crate::util::{next_call_id, push_json_string_escaped, push_u64_decimal};
 use memchr::{memchr, memchr2};
+use tool_args::ToolArgsStreamer;
+
+/// Tool blocks at least this large are parsed off the async runtime when a
+/// stream finalizes (see [`StreamingFcProcessor::finalize_into_bytes_offloaded`]).
+pub const BLOCKING_FC_PARSE_THRESHOLD_BYTES: usize = 64 * 1024;
 
 /// A parsed SSE frame from the upstream.
 #[derive(Debug, Clone, Default)]
@@ -240,6 +259,15 @@ pub struct StreamingFcProcessor {
     synthesize_termination: bool,
     /// Running tool-call index for emitted tool calls.
     tool_call_index: usize,
+    /// Drop the injected tool block when the upstream also returned native
+    /// tool calls in the same response.
+    dedup_native_tool_calls: bool,
+    native_tool_calls_seen: bool,
+    /// Streams injected tool calls while their block is still buffering.
+    tool_args: Option<ToolArgsStreamer>,
+    tool_arg_events: Vec<CanonicalStreamEvent>,
+    /// Records tool blocks that failed to parse and were flushed as text.
+    parse_failures: Option<FcParseFailureSink>,
 }
 
 impl StreamingFcProcessor {
@@ -263,6 +291,110 @@ impl StreamingFcProcessor {
             pending_stop_reason: None,
             synthesize_termination: fc_enabled,
             tool_call_index: 0,
+            dedup_native_tool_calls: false,
+            native_tool_calls_seen: false,
+            tool_args: None,
+            tool_arg_events: Vec::new(),
+            parse_failures: None,
+        }
+    }
+
+    /// Suppress injected-XML tool calls when native tool calls were observed
+    /// in the same response, so the client never sees both.
+    #[must_use]
+    pub fn with_native_tool_call_dedup(mut self, enabled: bool) -> Self {
+        self.dedup_native_tool_calls = enabled;
+        self
+    }
+
+    /// Stream injected tool calls as their XML arrives: each call starts once
+    /// its name is known and its CDATA arguments follow as deltas, instead of
+    /// the whole block being emitted when the stream ends.
+    #[must_use]
+    pub fn with_incremental_tool_args(mut self, enabled: bool) -> Self {
+        self.tool_args = enabled.then(ToolArgsStreamer::new);
+        self
+    }
+
+    /// Sample tool blocks that fail to parse before they are flushed as text.
+    #[must_use]
+    pub fn with_parse_failure_sink(mut self, sink: Option<FcParseFailureSink>) -> Self {
+        self.parse_failures = sink;
+        self
+    }
+
+    /// Record native tool calls forwarded before this processor saw the stream.
+    pub fn mark_native_tool_calls_seen(&mut self) {
+        self.native_tool_calls_seen = true;
+    }
+
+    fn record_parse_failure(&self, buffer: &str) {
+        if let Some(sink) = &self.parse_failures {
+            sink.record(buffer);
+        }
+    }
+
+    fn suppresses_injected_tool_calls(&self) -> bool {
+        self.dedup_native_tool_calls && self.native_tool_calls_seen
+    }
+
+    /// Queue events for the part of the tool block that arrived since the
+    /// last scan.
+    fn scan_tool_block(&mut self) {
+        if self.suppresses_injected_tool_calls()
+            || matches!(self.detector.state(), DetectorState::Detecting)
+        {
+            return;
+        }
+        if let Some(tool_args) = self.tool_args.as_mut() {
+            tool_args.advance(
+                self.detector.buffered(),
+                &mut self.tool_call_index,
+                &mut self.tool_arg_events,
+            );
+        }
+    }
+
+    /// Queue the end of a call still streaming its arguments.
+    fn close_streamed_tool_call(&mut self) {
+        if let Some(tool_args) = self.tool_args.as_mut() {
+            tool_args.close(&mut self.tool_arg_events);
+        }
+    }
+
+    fn streamed_tool_calls(&self) -> usize {
+        self.tool_args.as_ref().map_or(0, ToolArgsStreamer::started)
+    }
+
+    /// Close any streamed call and drop the parsed calls the client already
+    /// received; a failed parse then leaves nothing more to emit.
+    fn skip_streamed_tool_calls(
+        &mut self,
+        parsed: Result<Vec<ParsedToolCall>, CanonicalError>,
+    ) -> Result<Vec<ParsedToolCall>, CanonicalError> {
+        self.close_streamed_tool_call();
+        let streamed = self.streamed_tool_calls();
+        if streamed == 0 {
+            return parsed;
+        }
+        let mut calls = parsed.unwrap_or_default();
+        calls.drain(..streamed.min(calls.len()));
+        Ok(calls)
+    }
+
+    fn push_tool_arg_events_into(&mut self, output: &mut Vec<String>) {
+        for event in self.tool_arg_events.drain(..) {
+            if let Some(encoded) = self.transcoder.encode_client_event(&event) {
+                output.push(encoded);
+            }
+        }
+    }
+
+    fn push_tool_arg_events_into_bytes(&mut self, output: &mut Vec<bytes::Bytes>) {
+        for event in self.tool_arg_events.drain(..) {
+            if let Some(encoded) = self.transcoder.encode_client_event_bytes(&event) {
+                output.push(encoded);
+            }
         }
     }
 
@@ -385,46 +517,61 @@ impl StreamingFcProcessor {
         true
     }
 
+    /// Encode `event` for the client and keep its delta string for reuse.
+    fn emit(&mut self, event: CanonicalStreamEvent, output: &mut Vec<String>) {
+        if let Some(encoded) = self.transcoder.encode_client_event(&event) {
+            output.push(encoded);
+        }
+        self.transcoder.recycle_event(event);
+    }
+
+    /// Encode `event` for the client and keep its delta string for reuse.
+    fn emit_bytes(&mut self, event: CanonicalStreamEvent, output: &mut Vec<bytes::Bytes>) {
+        if let Some(encoded) = self.transcoder.encode_client_event_bytes(&event) {
+            output.push(encoded);
+        }
+        self.transcoder.recycle_event(event);
+    }
+
     fn process_decoded_events_into(&mut self, output: &mut Vec<String>) {
         output.clear();
         if self.decode_buffer.len() > output.capacity() {
             output.reserve(self.decode_buffer.len() - output.capacity());
         }
 
-        for event in self.decode_buffer.drain(..) {
+        let mut events = std::mem::take(&mut self.decode_buffer);
+        for event in events.drain(..) {
             match event {
                 CanonicalStreamEvent::TextDelta(text) if self.fc_enabled => {
                     let action = self.detector.feed_owned(text);
                     match action {
                         DetectorAction::PassThrough(pass_text) => {
                             if !pass_text.is_empty() {
-                                let ev = CanonicalStreamEvent::TextDelta(pass_text);
-                                if let Some(encoded) = self.transcoder.encode_client_event(&ev) {
-                                    output.push(encoded);
-                                }
+                                self.emit(CanonicalStreamEvent::TextDelta(pass_text), output);
                             }
                         }
                         DetectorAction::Buffer => {
-                            // Text is buffered in the detector; nothing to send.
+                            // Text is buffered in the detector; only streamed
+                            // tool-call events go out.
+                            self.scan_tool_block();
+                            self.push_tool_arg_events_into(output);
                         }
                         DetectorAction::TriggerFound { text_before } => {
                             // Send any text before the trigger to the client.
                             if !text_before.is_empty() {
-                                let ev = CanonicalStreamEvent::TextDelta(text_before);
-                                if let Some(encoded) = self.transcoder.encode_client_event(&ev) {
-                                    output.push(encoded);
-                                }
+                                self.emit(CanonicalStreamEvent::TextDelta(text_before), output);
                             }
-                            // The rest is buffered for XML parsing — nothing more to send.
+                            // The rest is buffered for XML parsing.
+                            self.scan_tool_block();
+                            self.push_tool_arg_events_into(output);
                         }
                         DetectorAction::BufferOverflow(overflow_text) => {
                             // Buffer exceeded limit — flush everything as text and
                             // disable FC for the rest of this response.
+                            self.close_streamed_tool_call();
+                            self.push_tool_arg_events_into(output);
                             if !overflow_text.is_empty() {
-                                let ev = CanonicalStreamEvent::TextDelta(overflow_text);
-                                if let Some(encoded) = self.transcoder.encode_client_event(&ev) {
-                                    output.push(encoded);
-                                }
+                                self.emit(CanonicalStreamEvent::TextDelta(overflow_text), output);
                             }
                             self.fc_enabled = false;
                             self.synthesize_termination = false;
@@ -442,21 +589,20 @@ impl StreamingFcProcessor {
                 CanonicalStreamEvent::Done if self.fc_enabled && self.synthesize_termination => {
                     // Suppress upstream done while FC is active. finalize() emits done once.
                 }
+                CanonicalStreamEvent::ToolCallStart { .. } => {
+                    self.native_tool_calls_seen = true;
+                    self.emit(event, output);
+                }
                 CanonicalStreamEvent::TextDelta(_) => {
                     // FC not enabled — forward as-is.
-                    if let Some(encoded) = self.transcoder.encode_client_event(&event) {
-                        output.push(encoded);
-                    }
+                    self.emit(event, output);
                 }
                 // All other events (MessageStart, ToolCallStart, Usage,
                 // MessageEnd, Done, Error, etc.) pass through directly.
-                _ => {
-                    if let Some(encoded) = self.transcoder.encode_client_event(&event) {
-                        output.push(encoded);
-                    }
-                }
+                _ => self.emit(event, output),
             }
         }
+        self.decode_buffer = events;
     }
 
     fn process_decoded_events_into_bytes(&mut self, output: &mut Vec<bytes::Bytes>) {
@@ -465,46 +611,45 @@ impl StreamingFcProcessor {
             output.reserve(self.decode_buffer.len() - output.capacity());
         }
 
-        for event in self.decode_buffer.drain(..) {
+        let mut events = std::mem::take(&mut self.decode_buffer);
+        for event in events.drain(..) {
             match event {
                 CanonicalStreamEvent::TextDelta(text) if self.fc_enabled => {
                     let action = self.detector.feed_owned(text);
                     match action {
                         DetectorAction::PassThrough(pass_text) => {
                             if !pass_text.is_empty() {
-                                let ev = CanonicalStreamEvent::TextDelta(pass_text);
-                                if let Some(encoded) =
-                                    self.transcoder.encode_client_event_bytes(&ev)
-                                {
-                                    output.push(encoded);
-                                }
+                                self.emit_bytes(CanonicalStreamEvent::TextDelta(pass_text), output);
                             }
                         }
                         DetectorAction::Buffer => {
-                            // Text is buffered in the detector; nothing to send.
+                            // Text is buffered in the detector; only streamed
+                            // tool-call events go out.
+                            self.scan_tool_block();
+                            self.push_tool_arg_events_into_bytes(output);
                         }
                         DetectorAction::TriggerFound { text_before } => {
                             // Send any text before the trigger to the client.
                             if !text_before.is_empty() {
-                                let ev = CanonicalStreamEvent::TextDelta(text_before);
-                                if let Some(encoded) =
-                                    self.transcoder.encode_client_event_bytes(&ev)
-                                {
-                                    output.push(encoded);
-                                }
+                                self.emit_bytes(
+                                    CanonicalStreamEvent::TextDelta(text_before),
+                                    output,
+                                );
                             }
-                            // The rest is buffered for XML parsing — nothing more to send.
+                            // The rest is buffered for XML parsing.
+                            self.scan_tool_block();
+                            self.push_tool_arg_events_into_bytes(output);
                         }
                         DetectorAction::BufferOverflow(overflow_text) => {
                             // Buffer exceeded limit — flush everything as text and
                             // disable FC for the rest of this response.
+                            self.close_streamed_tool_call();
+                            self.push_tool_arg_events_into_bytes(output);
                             if !overflow_text.is_empty() {
-                                let ev = CanonicalStreamEvent::TextDelta(overflow_text);
-                                if let Some(encoded) =
-                                    self.transcoder.encode_client_event_bytes(&ev)
-                                {
-                                    output.push(encoded);
-                                }
+                                self.emit_bytes(
+                                    CanonicalStreamEvent::TextDelta(overflow_text),
+                                    output,
+                                );
                             }
                             self.fc_enabled = false;
                             self.synthesize_termination = false;
@@ -522,21 +667,20 @@ impl StreamingFcProcessor {
                 CanonicalStreamEvent::Done if self.fc_enabled && self.synthesize_termination => {
                     // Suppress upstream done while FC is active. finalize() emits done once.
                 }
+                CanonicalStreamEvent::ToolCallStart { .. } => {
+                    self.native_tool_calls_seen = true;
+                    self.emit_bytes(event, output);
+                }
                 CanonicalStreamEvent::TextDelta(_) => {
                     // FC not enabled — forward as-is.
-                    if let Some(encoded) = self.transcoder.encode_client_event_bytes(&event) {
-                        output.push(encoded);
-                    }
+                    self.emit_bytes(event, output);
                 }
                 // All other events (MessageStart, ToolCallStart, Usage,
                 // MessageEnd, Done, Error, etc.) pass through directly.
-                _ => {
-                    if let Some(encoded) = self.transcoder.encode_client_event_bytes(&event) {
-                        output.push(encoded);
-                    }
-                }
+                _ => self.emit_bytes(event, output),
             }
         }
+        self.decode_buffer = events;
     }
 
     /// Process a single upstream SSE frame and return SSE strings for the client.
@@ -572,22 +716,46 @@ impl StreamingFcProcessor {
                     }
                 }
             }
+            output.extend(self.transcoder.finish());
             return;
         }
 
         match self.detector.state().clone() {
+            DetectorState::ToolParsing | DetectorState::Completed
+                if self.suppresses_injected_tool_calls() =>
+            {
+                // Native tool calls already reached the client: drop the
+                // injected block instead of emitting the calls twice.
+                let _ = self.detector.finalize();
+                self.close_streamed_tool_call();
+                self.push_tool_arg_events_into(output);
+                let end_ev = CanonicalStreamEvent::MessageEnd {
+                    stop_reason: self
+                        .pending_stop_reason
+                        .unwrap_or(CanonicalStopReason::ToolCalls),
+                };
+                if let Some(encoded) = self.transcoder.encode_client_event(&end_ev) {
+                    output.push(encoded);
+                }
+            }
             DetectorState::ToolParsing | DetectorState::Completed => {
                 // Get remaining buffer from the detector.
                 let remaining = self.detector.finalize().unwrap_or_default();
 
                 // Parse only buffered text from trigger onward.
-                match parse_function_calls(&remaining, self.detector.trigger_signal()) {
-                    Ok(parsed_calls) if !parsed_calls.is_empty() => {
+                let parsed = parse_function_calls(&remaining, self.detector.trigger_signal());
+                let parsed = self.skip_streamed_tool_calls(parsed);
+                self.push_tool_arg_events_into(output);
+                match parsed {
+                    Ok(parsed_calls)
+                        if !parsed_calls.is_empty() || self.streamed_tool_calls() > 0 =>
+                    {
                         self.emit_parsed_tool_calls_into(parsed_calls, output);
                     }
                     _ => {
                         // D5 fallback: parse failed — flush buffer as text.
                         if !remaining.is_empty() {
+                            self.record_parse_failure(&remaining);
                             let ev = CanonicalStreamEvent::TextDelta(remaining);
                             if let Some(encoded) = self.transcoder.encode_client_event(&ev) {
                                 output.push(encoded);
@@ -638,61 +806,95 @@ impl StreamingFcProcessor {
     pub fn finalize_into_bytes(&mut self, output: &mut Vec<bytes::Bytes>) {
         output.clear();
 
-        // FC detector may have been disabled mid-stream (e.g., overflow fallback).
-        // In that mode, upstream terminal events are already forwarded verbatim.
-        if !self.synthesize_termination {
-            if let Some(remaining) = self.detector.finalize() {
-                if !remaining.is_empty() {
-                    let ev = CanonicalStreamEvent::TextDelta(remaining);
-                    if let Some(encoded) = self.transcoder.encode_client_event_bytes(&ev) {
-                        output.push(encoded);
-                    }
-                }
+        if let Some(remaining) = self.take_tool_block() {
+            if self.suppresses_injected_tool_calls() {
+                self.drop_injected_tool_block_into_bytes(output);
+                return;
             }
+            // Parse only buffered text from trigger onward.
+            let parsed = parse_function_calls(&remaining, self.detector.trigger_signal());
+            self.finish_tool_block_into_bytes(remaining, parsed, output);
             return;
         }
+        self.finalize_text_into_bytes(output);
+    }
 
-        match self.detector.state().clone() {
-            DetectorState::ToolParsing | DetectorState::Completed => {
-                // Get remaining buffer from the detector.
-                let remaining = self.detector.finalize().unwrap_or_default();
+    /// [`Self::finalize_into_bytes`], but a tool block of at least
+    /// [`BLOCKING_FC_PARSE_THRESHOLD_BYTES`] is parsed on the blocking pool so
+    /// a giant argument payload cannot stall the stream's runtime worker.
+    #[cfg(feature = "server")]
+    pub async fn finalize_into_bytes_offloaded(&mut self, output: &mut Vec<bytes::Bytes>) {
+        output.clear();
 
-                // Parse only buffered text from trigger onward.
-                match parse_function_calls(&remaining, self.detector.trigger_signal()) {
-                    Ok(parsed_calls) if !parsed_calls.is_empty() => {
-                        self.emit_parsed_tool_calls_into_bytes(parsed_calls, output);
-                    }
-                    _ => {
-                        // D5 fallback: parse failed — flush buffer as text.
-                        if !remaining.is_empty() {
-                            let ev = CanonicalStreamEvent::TextDelta(remaining);
-                            if let Some(encoded) = self.transcoder.encode_client_event_bytes(&ev) {
-                                output.push(encoded);
-                            }
-                        }
-                        // End with stop reason.
-                        let end_ev = CanonicalStreamEvent::MessageEnd {
-                            stop_reason: self
-                                .pending_stop_reason
-                                .unwrap_or(CanonicalStopReason::EndOfTurn),
-                        };
-                        if let Some(encoded) = self.transcoder.encode_client_event_bytes(&end_ev) {
-                            output.push(encoded);
-                        }
-                    }
-                }
+        let Some(remaining) = self.take_tool_block() else {
+            self.finalize_text_into_bytes(output);
+            return;
+        };
+        if self.suppresses_injected_tool_calls() {
+            self.drop_injected_tool_block_into_bytes(output);
+            return;
+        }
+        let trigger_signal = self.detector.trigger_signal();
+        if remaining.len() < BLOCKING_FC_PARSE_THRESHOLD_BYTES {
+            let parsed = parse_function_calls(&remaining, trigger_signal);
+            self.finish_tool_block_into_bytes(remaining, parsed, output);
+            return;
+        }
+
+        let remaining = std::sync::Arc::new(remaining);
+        let block = std::sync::Arc::clone(&remaining);
+        let parsed =
+            tokio::task::spawn_blocking(move || parse_function_calls(&block, trigger_signal))
+                .await
+                .unwrap_or_else(|err| {
+                    Err(CanonicalError::FcParse(format!(
+                        "function call parsing task failed: {err}"
+                    )))
+                });
+        self.finish_tool_block_into_bytes(
+            std::sync::Arc::unwrap_or_clone(remaining),
+            parsed,
+            output,
+        );
+    }
+
+    /// The buffered text from the trigger onward, when the stream ends inside
+    /// a tool block that this processor must turn into tool calls.
+    fn take_tool_block(&mut self) -> Option<String> {
+        let in_tool_block = matches!(
+            self.detector.state(),
+            DetectorState::ToolParsing | DetectorState::Completed
+        );
+        if !self.synthesize_termination || !in_tool_block {
+            return None;
+        }
+        Some(self.detector.finalize().unwrap_or_default())
+    }
+
+    /// Emit the parsed tool calls, or the D5 fallback when parsing failed,
+    /// followed by Done.
+    fn finish_tool_block_into_bytes(
+        &mut self,
+        remaining: String,
+        parsed: Result<Vec<ParsedToolCall>, CanonicalError>,
+        output: &mut Vec<bytes::Bytes>,
+    ) {
+        let parsed = self.skip_streamed_tool_calls(parsed);
+        self.push_tool_arg_events_into_bytes(output);
+        match parsed {
+            Ok(parsed_calls) if !parsed_calls.is_empty() || self.streamed_tool_calls() > 0 => {
+                self.emit_parsed_tool_calls_into_bytes(parsed_calls, output);
             }
-            DetectorState::Detecting => {
-                // Flush any remaining partial buffer from the detector.
-                if let Some(remaining) = self.detector.finalize() {
-                    if !remaining.is_empty() {
-                        let ev = CanonicalStreamEvent::TextDelta(remaining);
-                        if let Some(encoded) = self.transcoder.encode_client_event_bytes(&ev) {
-                            output.push(encoded);
-                        }
+            _ => {
+                // D5 fallback: parse failed — flush buffer as text.
+                if !remaining.is_empty() {
+                    self.record_parse_failure(&remaining);
+                    let ev = CanonicalStreamEvent::TextDelta(remaining);
+                    if let Some(encoded) = self.transcoder.encode_client_event_bytes(&ev) {
+                        output.push(encoded);
                     }
                 }
-                // End with EndOfTurn.
+                // End with stop reason.
                 let end_ev = CanonicalStreamEvent::MessageEnd {
                     stop_reason: self
                         .pending_stop_reason
@@ -703,7 +905,64 @@ impl StreamingFcProcessor {
                 }
             }
         }
+        self.push_done_bytes(output);
+    }
+
+    /// Native tool calls already reached the client: drop the injected block
+    /// instead of emitting the calls twice.
+    fn drop_injected_tool_block_into_bytes(&mut self, output: &mut Vec<bytes::Bytes>) {
+        self.close_streamed_tool_call();
+        self.push_tool_arg_events_into_bytes(output);
+        let end_ev = CanonicalStreamEvent::MessageEnd {
+            stop_reason: self
+                .pending_stop_reason
+                .unwrap_or(CanonicalStopReason::ToolCalls),
+        };
+        if let Some(encoded) = self.transcoder.encode_client_event_bytes(&end_ev) {
+            output.push(encoded);
+        }
+        self.push_done_bytes(output);
+    }
 
+    /// Finalize a stream that did not end inside a tool block.
+    fn finalize_text_into_bytes(&mut self, output: &mut Vec<bytes::Bytes>) {
+        // FC detector may have been disabled mid-stream (e.g., overflow fallback).
+        // In that mode, upstream terminal events are already forwarded verbatim.
+        if !self.synthesize_termination {
+            if let Some(remaining) = self.detector.finalize() {
+                if !remaining.is_empty() {
+                    let ev = CanonicalStreamEvent::TextDelta(remaining);
+                    if let Some(encoded) = self.transcoder.encode_client_event_bytes(&ev) {
+                        output.push(encoded);
+                    }
+                }
+            }
+            output.extend(self.transcoder.finish().map(bytes::Bytes::from));
+            return;
+        }
+
+        // Flush any remaining partial buffer from the detector.
+        if let Some(remaining) = self.detector.finalize() {
+            if !remaining.is_empty() {
+                let ev = CanonicalStreamEvent::TextDelta(remaining);
+                if let Some(encoded) = self.transcoder.encode_client_event_bytes(&ev) {
+                    output.push(encoded);
+                }
+            }
+        }
+        // End with EndOfTurn.
+        let end_ev = CanonicalStreamEvent::MessageEnd {
+            stop_reason: self
+                .pending_stop_reason
+                .unwrap_or(CanonicalStopReason::EndOfTurn),
+        };
+        if let Some(encoded) = self.transcoder.encode_client_event_bytes(&end_ev) {
+            output.push(encoded);
+        }
+        self.push_done_bytes(output);
+    }
+
+    fn push_done_bytes(&mut self, output: &mut Vec<bytes::Bytes>) {
         // Always emit Done at the end.
         let done_ev = CanonicalStreamEvent::Done;
         if let Some(encoded) = self.transcoder.encode_client_event_bytes(&done_ev) {
@@ -841,8 +1100,11 @@ fn parsed_call_arguments_delta(
 
 #[cfg(test)]
 mod tests {
-    use super::{encode_sse_event, parse_sse_frame, parsed_call_arguments_delta};
-    use crate::protocol::canonical::CanonicalStreamEvent;
+    use super::{
+        encode_sse_event, parse_sse_frame, parsed_call_arguments_delta, SseEvent, StreamTranscoder,
+        StreamingFcProcessor,
+    };
+    use crate::protocol::canonical::{CanonicalStreamEvent, IngressApi, ProviderKind};
     use serde_json::{json, Value};
 
     #[test]
@@ -915,4 +1177,278 @@ mod tests {
         let delta = parsed_call_arguments_delta(&args, None);
         assert_eq!(delta, "{\"x\":1}");
     }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn finalize_offloaded_parses_giant_tool_block() {
+        const TRIGGER: &str = "<Function_AB12_Start/>";
+        let payload = "x".repeat(super::BLOCKING_FC_PARSE_THRESHOLD_BYTES * 2);
+        let content = format!(
+            "{TRIGGER}\n<function_calls><invoke name=\"big_tool\">\
+             <parameter name=\"blob\">{payload}</parameter></invoke></function_calls>"
+        );
+        let frame = openai_chunk_frame(&json!({ "content": content }));
+        let transcoder = StreamTranscoder::new(
+            ProviderKind::OpenAi,
+            IngressApi::OpenAiChat,
+            "m".to_string(),
+            "resp-1".to_string(),
+        );
+        let mut proc = StreamingFcProcessor::new(transcoder, true, &[], TRIGGER);
+        let mut output = Vec::new();
+        proc.process_frame_into_bytes(&frame, &mut output);
+
+        proc.finalize_into_bytes_offloaded(&mut output).await;
+        let body: String = output
+            .iter()
+            .map(|chunk| std::str::from_utf8(chunk).expect("utf8 sse"))
+            .collect();
+        assert!(body.contains("\"name\":\"big_tool\""));
+        assert!(body.contains(&payload));
+        assert!(body.contains("\"finish_reason\":\"tool_calls\""));
+        assert!(!body.contains("<function_calls>"));
+    }
+
+    #[test]
+    fn fc_text_deltas_are_cut_from_one_frame_block() {
+        const TRIGGER: &str = "<Function_AB12_Start/>";
+        let transcoder = StreamTranscoder::new(
+            ProviderKind::OpenAi,
+            IngressApi::OpenAiChat,
+            "m".to_string(),
+            "resp-1".to_string(),
+        );
+        let mut proc = StreamingFcProcessor::new(transcoder, true, &[], TRIGGER);
+        let mut output = Vec::new();
+        proc.process_frame_into_bytes(
+            &openai_chunk_frame(&json!({ "content": "Hello" })),
+            &mut output,
+        );
+        let first = output.pop().expect("first delta frame");
+        proc.process_frame_into_bytes(
+            &openai_chunk_frame(&json!({ "content": " world" })),
+            &mut output,
+        );
+        let second = output.pop().expect("second delta frame");
+
+        assert!(std::str::from_utf8(&second)
+            .expect("utf8 sse")
+            .contains("\"content\":\" world\""));
+        assert_eq!(second.as_ptr(), first[first.len()..].as_ptr());
+    }
+
+    #[test]
+    fn fc_raw_frames_split_at_every_byte_match_the_unsplit_stream() {
+        use futures_util::{FutureExt, StreamExt};
+
+        const TRIGGER: &str = "<Function_AB12_Start/>";
+        let mut upstream = String::new();
+        for (index, content) in [
+            "Sure. ",
+            "Let me check.\n",
+            TRIGGER,
+            "\n<function_calls><invoke name=\"lookup\">",
+            "<parameter name=\"q\">caf\u{e9} \u{2615}</parameter></invoke></function_calls>",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let data = openai_chunk_frame(&json!({ "content": content })).data;
+            // Alternate line endings the way mixed upstream proxies do.
+            let ending = ["\n\n", "\r\n\r\n", "\n\r\n"][index % 3];
+            upstream.push_str(&format!("data: {data}{ending}"));
+        }
+        upstream.push_str("data: [DONE]\n\n");
+
+        let run = |chunks: Vec<bytes::Bytes>| {
+            let transcoder = StreamTranscoder::new(
+                ProviderKind::OpenAi,
+                IngressApi::OpenAiChat,
+                "m".to_string(),
+                "resp-1".to_string(),
+            );
+            let mut proc = StreamingFcProcessor::new(transcoder, true, &[], TRIGGER);
+            let source = futures_util::stream::iter(
+                chunks.into_iter().map(Ok::<_, std::convert::Infallible>),
+            );
+            let frames: Vec<bytes::Bytes> = super::sse::sse_raw_frame_stream(source)
+                .collect()
+                .now_or_never()
+                .expect("ready source");
+            let (mut body, mut output) = (Vec::new(), Vec::new());
+            for frame in &frames {
+                proc.process_raw_frame_into_bytes(frame, &mut output);
+                body.append(&mut output);
+            }
+            proc.finalize_into_bytes(&mut output);
+            body.append(&mut output);
+            openai_body_summary(&body)
+        };
+
+        let bytes = upstream.as_bytes();
+        let expected = run(vec![bytes::Bytes::copy_from_slice(bytes)]);
+        assert_eq!(expected.0, "Sure. Let me check.\n");
+        assert!(expected.1.contains("lookup"), "{expected:?}");
+        assert!(expected.1.contains("caf\u{e9} \u{2615}"), "{expected:?}");
+        assert_eq!(expected.2, ["tool_calls"]);
+        for cut in 0..=bytes.len() {
+            let chunks = vec![
+                bytes::Bytes::copy_from_slice(&bytes[..cut]),
+                bytes::Bytes::copy_from_slice(&bytes[cut..]),
+            ];
+            assert_eq!(run(chunks), expected, "cut at {cut}");
+        }
+        let single_bytes = bytes
+            .iter()
+            .map(|&byte| bytes::Bytes::copy_from_slice(&[byte]))
+            .collect();
+        assert_eq!(run(single_bytes), expected);
+    }
+
+    /// Text, tool calls (name and arguments) and finish reasons of an
+    /// encoded `OpenAI` chat stream.
+    fn openai_body_summary(output: &[bytes::Bytes]) -> (String, String, Vec<String>) {
+        let body: String = output
+            .iter()
+            .map(|chunk| std::str::from_utf8(chunk).expect("utf8 sse"))
+            .collect();
+        let (mut text, mut calls, mut finish) = (String::new(), String::new(), Vec::new());
+        for event in super::SseParser::new().feed(&body) {
+            if event.data == "[DONE]" {
+                continue;
+            }
+            let chunk: Value = serde_json::from_str(&event.data).expect("json chunk");
+            let choice = &chunk["choices"][0];
+            if let Some(content) = choice["delta"]["content"].as_str() {
+                text.push_str(content);
+            }
+            for call in choice["delta"]["tool_calls"]
+                .as_array()
+                .into_iter()
+                .flatten()
+            {
+                calls.push_str(call["function"]["name"].as_str().unwrap_or_default());
+                calls.push_str(call["function"]["arguments"].as_str().unwrap_or_default());
+            }
+            if let Some(reason) = choice["finish_reason"].as_str() {
+                finish.push(reason.to_string());
+            }
+        }
+        (text, calls, finish)
+    }
+
+    fn openai_chunk_frame(delta: &Value) -> SseEvent {
+        let chunk = json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "m",
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": null }]
+        });
+        SseEvent {
+            event: None,
+            data: chunk.to_string(),
+            id: None,
+            retry: None,
+        }
+    }
+
+    fn native_and_injected_body(dedup: bool) -> String {
+        const TRIGGER: &str = "<Function_AB12_Start/>";
+        let transcoder = StreamTranscoder::new(
+            ProviderKind::OpenAi,
+            IngressApi::OpenAiChat,
+            "m".to_string(),
+            "resp-1".to_string(),
+        );
+        let mut proc = StreamingFcProcessor::new(transcoder, true, &[], TRIGGER)
+            .with_native_tool_call_dedup(dedup);
+        let native = openai_chunk_frame(&json!({ "tool_calls": [{
+            "index": 0,
+            "id": "call_native",
+            "type": "function",
+            "function": { "name": "native_tool", "arguments": "{}" }
+        }] }));
+        let injected = openai_chunk_frame(&json!({ "content": format!(
+            "{TRIGGER}\n<function_calls><invoke name=\"injected_tool\">\
+             <parameter name=\"q\">x</parameter></invoke></function_calls>"
+        ) }));
+
+        let mut body = String::new();
+        let mut output = Vec::new();
+        for frame in [&native, &injected] {
+            proc.process_frame_into_bytes(frame, &mut output);
+            body.extend(
+                output
+                    .iter()
+                    .map(|c| std::str::from_utf8(c).expect("utf8 sse")),
+            );
+        }
+        proc.finalize_into_bytes(&mut output);
+        body.extend(
+            output
+                .iter()
+                .map(|c| std::str::from_utf8(c).expect("utf8 sse")),
+        );
+        body
+    }
+
+    #[test]
+    fn native_tool_calls_suppress_injected_block_when_dedup_enabled() {
+        let body = native_and_injected_body(true);
+        assert!(body.contains("native_tool"));
+        assert!(!body.contains("injected_tool"));
+        assert!(body.contains("\"finish_reason\":\"tool_calls\""));
+        assert!(body.ends_with("data: [DONE]\n\n"));
+
+        let body = native_and_injected_body(false);
+        assert!(body.contains("native_tool"));
+        assert!(body.contains("injected_tool"));
+    }
+
+    #[test]
+    fn incremental_tool_args_stream_before_the_block_closes() {
+        const TRIGGER: &str = "<Function_AB12_Start/>";
+        let transcoder = StreamTranscoder::new(
+            ProviderKind::OpenAi,
+            IngressApi::OpenAiChat,
+            "m".to_string(),
+            "resp-1".to_string(),
+        );
+        let mut proc = StreamingFcProcessor::new(transcoder, true, &[], TRIGGER)
+            .with_incremental_tool_args(true);
+        let mut output = Vec::new();
+
+        let head = openai_chunk_frame(&json!({ "content": format!(
+            "{TRIGGER}\n<function_calls><function_call><tool>search</tool>\
+             <args_json><![CDATA[{{\"q\":\"rust"
+        ) }));
+        proc.process_frame_into_bytes(&head, &mut output);
+        let streamed: String = output
+            .iter()
+            .map(|c| std::str::from_utf8(c).expect("utf8 sse"))
+            .collect();
+        assert!(streamed.contains("\"name\":\"search\""));
+        assert!(streamed.contains(r#""arguments":"{\"q\":\"rust""#));
+
+        let tail = openai_chunk_frame(&json!({ "content": "\"}]]></args_json></function_call>\
+             <function_call><tool>fetch</tool><args_json>{\"u\":1}</args_json>\
+             </function_call></function_calls>" }));
+        proc.process_frame_into_bytes(&tail, &mut output);
+        let mut rest: String = output
+            .iter()
+            .map(|c| std::str::from_utf8(c).expect("utf8 sse"))
+            .collect();
+        assert!(rest.contains(r#""arguments":"\"}""#));
+        proc.finalize_into_bytes(&mut output);
+        rest.extend(
+            output
+                .iter()
+                .map(|c| std::str::from_utf8(c).expect("utf8 sse")),
+        );
+        assert!(!rest.contains("\"name\":\"search\""));
+        assert!(rest.contains("\"name\":\"fetch\""));
+        assert!(rest.contains("\"index\":1"));
+        assert!(rest.contains("\"finish_reason\":\"tool_calls\""));
+    }
 }
@@ -0,0 +1,243 @@
+//! Assistant prefill across protocols.
+//!
+//! Anthropic and Gemini clients steer a reply by ending the conversation with
+//! a partial assistant (`model`) turn, and those upstreams continue it. Other
+//! upstreams get the turn as a plain assistant message and often start their
+//! reply by repeating it. The compat flow passes the client's prefill
+//! down in its request context, and the [`PrefillEchoStripper`] of a
+//! [`super::StreamTranscoder`] drops the repeated text, even when it is split
+//! across chunks.
+
+use std::sync::Arc;
+
+use crate::protocol::canonical::{
+    CanonicalPart, CanonicalResponse, CanonicalStreamEvent, IngressApi, ProviderKind,
+};
+
+/// The client's prefill when a `provider` upstream may echo it back to an
+/// `ingress` client that expects only the continuation.
+#[cfg(feature = "server")]
+#[must_use]
+pub fn echoed_prefill(provider: ProviderKind, ingress: IngressApi) -> Option<Arc<str>> {
+    if !ingress_continues_prefill(ingress) || provider_continues_prefill(provider) {
+        return None;
+    }
+    crate::state::request_context::with(|context| context.prefill.clone())
+        .flatten()
+        .filter(|prefill| !prefill.is_empty())
+}
+
+/// Whether a trailing assistant turn from an `ingress` client is a prefill.
+#[must_use]
+pub const fn ingress_continues_prefill(ingress: IngressApi) -> bool {
+    matches!(ingress, IngressApi::Anthropic | IngressApi::Gemini)
+}
+
+/// Whether a `provider` upstream continues a trailing assistant turn rather
+/// than answering it with a fresh message.
+#[must_use]
+pub const fn provider_continues_prefill(provider: ProviderKind) -> bool {
+    matches!(provider, ProviderKind::Anthropic | ProviderKind::Gemini)
+}
+
+/// Drop a leading echo of `prefill` from the first text part of `response`.
+pub fn strip_prefill_echo(response: &mut CanonicalResponse, prefill: &str) {
+    let Some(index) = response
+        .content
+        .iter()
+        .position(|part| matches!(part, CanonicalPart::Text(_)))
+    else {
+        return;
+    };
+    let CanonicalPart::Text(text) = &mut response.content[index] else {
+        return;
+    };
+    if !text.starts_with(prefill) {
+        return;
+    }
+    text.drain(..prefill.len());
+    if text.is_empty() {
+        response.content.remove(index);
+    }
+}
+
+/// Drops a leading echo of the client's prefill from a canonical event stream.
+#[derive(Debug)]
+pub struct PrefillEchoStripper {
+    prefill: Arc<str>,
+    /// Leading text that so far matches the start of the prefill.
+    held: String,
+    settled: bool,
+}
+
+impl PrefillEchoStripper {
+    #[must_use]
+    pub fn new(prefill: Arc<str>) -> Self {
+        Self {
+            prefill,
+            held: String::new(),
+            settled: false,
+        }
+    }
+
+    /// Rewrite the decoded `events` in place.
+    ///
+    /// Leading text is held back while it matches the start of the prefill.
+    /// A full match is dropped; a mismatch, or any other content event,
+    /// releases the held text unchanged. Reasoning deltas pass through.
+    pub fn apply(&mut self, events: &mut Vec<CanonicalStreamEvent>) {
+        if self.settled {
+            return;
+        }
+        let decoded = std::mem::take(events);
+        for event in decoded {
+            if self.settled {
+                events.push(event);
+                continue;
+            }
+            match event {
+                CanonicalStreamEvent::TextDelta(delta) => {
+                    self.held.push_str(&delta);
+                    if let Some(rest) = self.held.strip_prefix(&*self.prefill) {
+                        if !rest.is_empty() {
+                            events.push(CanonicalStreamEvent::TextDelta(rest.to_string()));
+                        }
+                        self.held.clear();
+                        self.settled = true;
+                    } else if !self.prefill.starts_with(self.held.as_str()) {
+                        self.release_into(events);
+                    }
+                }
+                CanonicalStreamEvent::ReasoningDelta(_)
+                | CanonicalStreamEvent::Usage(_)
+                | CanonicalStreamEvent::MessageStart { .. } => events.push(event),
+                other => {
+                    self.release_into(events);
+                    events.push(other);
+                }
+            }
+        }
+    }
+
+    /// Release held-back text when the upstream ends without a terminal event.
+    #[must_use]
+    pub fn finish(&mut self) -> Option<CanonicalStreamEvent> {
+        self.settled = true;
+        (!self.held.is_empty())
+            .then(|| CanonicalStreamEvent::TextDelta(std::mem::take(&mut self.held)))
+    }
+
+    fn release_into(&mut self, events: &mut Vec<CanonicalStreamEvent>) {
+        self.settled = true;
+        if !self.held.is_empty() {
+            events.push(CanonicalStreamEvent::TextDelta(std::mem::take(
+                &mut self.held,
+            )));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::canonical::CanonicalStopReason;
+
+    fn text_of(events: &[CanonicalStreamEvent]) -> String {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                CanonicalStreamEvent::TextDelta(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_stripper_drops_echo_split_across_deltas() {
+        let mut stripper = PrefillEchoStripper::new(Arc::from("{\"city\":"));
+        let mut events = vec![CanonicalStreamEvent::TextDelta("{\"ci".to_string())];
+        stripper.apply(&mut events);
+        assert!(events.is_empty());
+
+        let mut events = vec![CanonicalStreamEvent::TextDelta(
+            "ty\": \"Paris\"}".to_string(),
+        )];
+        stripper.apply(&mut events);
+        assert_eq!(text_of(&events), " \"Paris\"}");
+
+        let mut events = vec![CanonicalStreamEvent::TextDelta("{\"city\":".to_string())];
+        stripper.apply(&mut events);
+        assert_eq!(text_of(&events), "{\"city\":");
+    }
+
+    #[test]
+    fn test_stripper_releases_continuation_that_diverges() {
+        let mut stripper = PrefillEchoStripper::new(Arc::from("Once upon"));
+        let mut events = vec![CanonicalStreamEvent::TextDelta("Once".to_string())];
+        stripper.apply(&mut events);
+        assert!(events.is_empty());
+
+        let mut events = vec![
+            CanonicalStreamEvent::TextDelta(" more".to_string()),
+            CanonicalStreamEvent::MessageEnd {
+                stop_reason: CanonicalStopReason::EndOfTurn,
+            },
+        ];
+        stripper.apply(&mut events);
+        assert_eq!(text_of(&events), "Once more");
+        assert!(matches!(
+            events.last(),
+            Some(CanonicalStreamEvent::MessageEnd { .. })
+        ));
+
+        let mut stripper = PrefillEchoStripper::new(Arc::from("Once upon"));
+        let mut events = vec![CanonicalStreamEvent::TextDelta("Once".to_string())];
+        stripper.apply(&mut events);
+        assert!(matches!(
+            stripper.finish(),
+            Some(CanonicalStreamEvent::TextDelta(text)) if text == "Once"
+        ));
+    }
+
+    #[test]
+    fn test_strip_prefill_echo_from_response() {
+        let mut response = CanonicalResponse {
+            id: "r1".into(),
+            model: "m".into(),
+            content: vec![CanonicalPart::Text("Sure, here it is".into())],
+            stop_reason: CanonicalStopReason::EndOfTurn,
+            usage: crate::protocol::canonical::CanonicalUsage::default(),
+            citations: Vec::new(),
+            provider_extensions: serde_json::Map::new(),
+        };
+        strip_prefill_echo(&mut response, "Sure,");
+        assert!(matches!(
+            response.content.as_slice(),
+            [CanonicalPart::Text(text)] if text == " here it is"
+        ));
+
+        strip_prefill_echo(&mut response, "Sure,");
+        assert!(matches!(
+            response.content.as_slice(),
+            [CanonicalPart::Text(text)] if text == " here it is"
+        ));
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn test_echoed_prefill_follows_ingress_and_provider() {
+        let context = crate::state::request_context::RequestContext {
+            prefill: Some(Arc::from("{")),
+            ..Default::default()
+        };
+        crate::state::request_context::scope(context, async {
+            assert!(echoed_prefill(ProviderKind::OpenAi, IngressApi::Anthropic).is_some());
+            assert!(echoed_prefill(ProviderKind::OpenAiResponses, IngressApi::Gemini).is_some());
+            assert!(echoed_prefill(ProviderKind::Anthropic, IngressApi::Gemini).is_none());
+            assert!(echoed_prefill(ProviderKind::Gemini, IngressApi::Anthropic).is_none());
+            assert!(echoed_prefill(ProviderKind::OpenAi, IngressApi::OpenAiChat).is_none());
+        })
+        .await;
+        assert!(echoed_prefill(ProviderKind::OpenAi, IngressApi::Anthropic).is_none());
+    }
+}
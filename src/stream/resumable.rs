@@ -0,0 +1,418 @@
+//! Resumable client streams (`Last-Event-ID`).
+//!
+//! Every frame of a published SSE response gets an `id: {stream}-{seq}`
+//! field, with `seq` counting up from 1, and the frame is kept in a
+//! per-stream ring buffer. A client that reconnects with the `Last-Event-ID`
+//! header receives the buffered frames after that id, then live frames.
+//!
+//! A client that disconnects does not cancel the upstream: the rest of the
+//! answer is drained into the buffer so a reconnect can pick it up. Finished
+//! streams stay resumable for the configured TTL.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::response::Response;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::{Stream, StreamExt};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use tokio::sync::watch;
+
+use crate::config::ResumableStreamConfig;
+use crate::stream::sse::sse_raw_frame_stream;
+
+/// Request header naming the last frame a reconnecting client received.
+pub const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+/// Streams kept at once; further streams are served without ids.
+const MAX_RESUMABLE_STREAMS: usize = 1024;
+
+type FrameStream = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+
+#[derive(Default)]
+struct RingFrames {
+    frames: VecDeque<Bytes>,
+    /// Sequence number of `frames[0]`.
+    first_seq: u64,
+    ended_at: Option<Instant>,
+}
+
+impl RingFrames {
+    fn next_seq(&self) -> u64 {
+        self.first_seq + self.frames.len() as u64
+    }
+}
+
+struct ResumableStream {
+    key: u64,
+    capacity: usize,
+    frames: Mutex<RingFrames>,
+    /// Bumped after every change of `frames` to wake resumed readers.
+    version: watch::Sender<u64>,
+}
+
+impl ResumableStream {
+    fn new(key: u64, capacity: usize) -> Self {
+        Self {
+            key,
+            capacity: capacity.max(1),
+            frames: Mutex::new(RingFrames {
+                first_seq: 1,
+                ..RingFrames::default()
+            }),
+            version: watch::Sender::new(0),
+        }
+    }
+
+    /// Number `raw` with the next id, buffer it, and return the numbered frame.
+    fn push(&self, raw: &[u8]) -> Bytes {
+        let frame = {
+            let mut ring = self.frames.lock();
+            let frame = with_event_id(raw, self.key, ring.next_seq());
+            ring.frames.push_back(frame.clone());
+            if ring.frames.len() > self.capacity {
+                ring.frames.pop_front();
+                ring.first_seq += 1;
+            }
+            frame
+        };
+        self.version.send_modify(|version| *version += 1);
+        frame
+    }
+
+    fn finish(&self) {
+        {
+            let mut ring = self.frames.lock();
+            if ring.ended_at.is_some() {
+                return;
+            }
+            ring.ended_at = Some(Instant::now());
+        }
+        self.version.send_modify(|version| *version += 1);
+    }
+
+    fn expired(&self, ttl: Duration, now: Instant) -> bool {
+        self.frames
+            .lock()
+            .ended_at
+            .is_some_and(|ended_at| now.duration_since(ended_at) >= ttl)
+    }
+}
+
+/// `raw` with its `id:` lines replaced by one naming `key` and `seq`.
+fn with_event_id(raw: &[u8], key: u64, seq: u64) -> Bytes {
+    let id = format!("id: {key:016x}-{seq}\n");
+    let mut frame = BytesMut::with_capacity(id.len() + raw.len());
+    frame.put_slice(id.as_bytes());
+    if memchr::memmem::find(raw, b"id:").is_none() {
+        frame.put_slice(raw);
+        return frame.freeze();
+    }
+    let mut rest = raw;
+    while !rest.is_empty() {
+        let line_end = memchr::memchr2(b'\n', b'\r', rest).map_or(rest.len(), |end| {
+            if rest[end] == b'\r' && rest.get(end + 1) == Some(&b'\n') {
+                end + 2
+            } else {
+                end + 1
+            }
+        });
+        let (line, tail) = rest.split_at(line_end);
+        if !line.starts_with(b"id:") {
+            frame.put_slice(line);
+        }
+        rest = tail;
+    }
+    frame.freeze()
+}
+
+fn parse_event_id(id: &str) -> Option<(u64, u64)> {
+    let (key, seq) = id.trim().split_once('-')?;
+    Some((u64::from_str_radix(key, 16).ok()?, seq.parse().ok()?))
+}
+
+/// Client streams that can be resumed, by stream key.
+pub struct ResumableStreams {
+    retry: Option<Bytes>,
+    buffer_frames: usize,
+    ttl: Duration,
+    streams: Mutex<FxHashMap<u64, Arc<ResumableStream>>>,
+}
+
+impl ResumableStreams {
+    #[must_use]
+    pub fn new(config: &ResumableStreamConfig) -> Self {
+        Self {
+            retry: (config.retry_ms > 0)
+                .then(|| Bytes::from(format!("retry: {}\n\n", config.retry_ms))),
+            buffer_frames: config.buffer_frames,
+            ttl: Duration::from_secs(config.ttl_secs),
+            streams: Mutex::default(),
+        }
+    }
+
+    fn prune(&self, streams: &mut FxHashMap<u64, Arc<ResumableStream>>) {
+        let now = Instant::now();
+        streams.retain(|_, stream| !stream.expired(self.ttl, now));
+    }
+
+    /// Number the frames of a successful SSE `response` and keep them for
+    /// reconnects. Other responses, and streams beyond the limit, are
+    /// returned unchanged.
+    pub fn publish(&self, response: Response) -> Response {
+        let is_sse = response
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !is_sse || !response.status().is_success() {
+            return response;
+        }
+        let stream = {
+            let mut streams = self.streams.lock();
+            self.prune(&mut streams);
+            if streams.len() >= MAX_RESUMABLE_STREAMS {
+                return response;
+            }
+            let mut key = fastrand::u64(..);
+            while streams.contains_key(&key) {
+                key = fastrand::u64(..);
+            }
+            let stream = Arc::new(ResumableStream::new(key, self.buffer_frames));
+            streams.insert(key, Arc::clone(&stream));
+            stream
+        };
+        let (mut parts, body) = response.into_parts();
+        parts.headers.remove(http::header::CONTENT_LENGTH);
+        let frames: FrameStream = Box::pin(sse_raw_frame_stream(body.into_data_stream()));
+        let tap = ResumableTapStream {
+            retry: self.retry.clone(),
+            inner: Some(frames),
+            stream,
+        };
+        Response::from_parts(
+            parts,
+            axum::body::Body::from_stream(tap.map(Ok::<_, std::convert::Infallible>)),
+        )
+    }
+
+    /// Continue the stream a client was reading when it received the frame
+    /// `last_event_id`.
+    ///
+    /// `None` for unknown ids and for ids whose following frames were
+    /// already dropped from the buffer.
+    #[must_use]
+    pub fn resume(&self, last_event_id: &str) -> Option<Response> {
+        let (key, last_seq) = parse_event_id(last_event_id)?;
+        let stream = {
+            let mut streams = self.streams.lock();
+            self.prune(&mut streams);
+            Arc::clone(streams.get(&key)?)
+        };
+        let next = last_seq.checked_add(1)?;
+        {
+            let ring = stream.frames.lock();
+            if next < ring.first_seq || next > ring.next_seq() {
+                return None;
+            }
+        }
+        let updates = stream.version.subscribe();
+        let replay = futures_util::stream::unfold(
+            (stream, updates, next),
+            |(stream, mut updates, next)| async move {
+                loop {
+                    {
+                        let ring = stream.frames.lock();
+                        if next < ring.first_seq {
+                            // Fell behind the buffer; the reader reconnects.
+                            return None;
+                        }
+                        let offset = usize::try_from(next - ring.first_seq).ok()?;
+                        if let Some(frame) = ring.frames.get(offset) {
+                            let frame = frame.clone();
+                            drop(ring);
+                            return Some((frame, (stream, updates, next + 1)));
+                        }
+                        if ring.ended_at.is_some() {
+                            return None;
+                        }
+                    }
+                    if updates.changed().await.is_err() {
+                        return None;
+                    }
+                }
+            },
+        );
+        let body = futures_util::stream::iter(self.retry.clone())
+            .chain(replay)
+            .map(Ok::<_, std::convert::Infallible>);
+        let mut response = Response::new(axum::body::Body::from_stream(body));
+        let headers = response.headers_mut();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("text/event-stream"),
+        );
+        headers.insert(
+            http::header::CACHE_CONTROL,
+            http::HeaderValue::from_static("no-cache"),
+        );
+        Some(response)
+    }
+
+    /// Number of streams currently resumable.
+    #[must_use]
+    pub fn active_count(&self) -> usize {
+        let mut streams = self.streams.lock();
+        self.prune(&mut streams);
+        streams.len()
+    }
+}
+
+/// The original client's view of a published stream.
+///
+/// Dropped before the upstream finished (the client went away), it drains
+/// the rest of the upstream into the buffer on a background task.
+struct ResumableTapStream {
+    retry: Option<Bytes>,
+    inner: Option<FrameStream>,
+    stream: Arc<ResumableStream>,
+}
+
+impl Stream for ResumableTapStream {
+    type Item = Bytes;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(retry) = self.retry.take() {
+            return Poll::Ready(Some(retry));
+        }
+        let Some(inner) = self.inner.as_mut() else {
+            return Poll::Ready(None);
+        };
+        match inner.as_mut().poll_next(cx) {
+            Poll::Ready(Some(raw)) => Poll::Ready(Some(self.stream.push(&raw))),
+            Poll::Ready(None) => {
+                self.inner = None;
+                self.stream.finish();
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for ResumableTapStream {
+    fn drop(&mut self) {
+        let Some(mut inner) = self.inner.take() else {
+            return;
+        };
+        let stream = Arc::clone(&self.stream);
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            stream.finish();
+            return;
+        };
+        runtime.spawn(async move {
+            while let Some(raw) = inner.next().await {
+                stream.push(&raw);
+            }
+            stream.finish();
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(buffer_frames: usize) -> ResumableStreamConfig {
+        ResumableStreamConfig {
+            retry_ms: 1500,
+            buffer_frames,
+            ttl_secs: 60,
+        }
+    }
+
+    fn sse_response(rx: tokio::sync::mpsc::UnboundedReceiver<&'static str>) -> Response {
+        let upstream = futures_util::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|chunk| {
+                (
+                    Ok::<_, std::io::Error>(Bytes::from_static(chunk.as_bytes())),
+                    rx,
+                )
+            })
+        });
+        Response::builder()
+            .header(http::header::CONTENT_TYPE, "text/event-stream")
+            .body(axum::body::Body::from_stream(upstream))
+            .unwrap()
+    }
+
+    async fn collect(response: Response) -> String {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    fn event_ids(body: &str) -> Vec<&str> {
+        body.lines()
+            .filter_map(|line| line.strip_prefix("id: "))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_frames_get_ids_and_resume_after_disconnect() {
+        let streams = ResumableStreams::new(&config(16));
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let response = streams.publish(sse_response(rx));
+        let mut origin = response.into_body().into_data_stream();
+
+        tx.send("id: upstream-1\ndata: 1\n\ndata: 2\n\n").unwrap();
+        assert_eq!(origin.next().await.unwrap().unwrap(), "retry: 1500\n\n");
+        let first = origin.next().await.unwrap().unwrap();
+        let first = std::str::from_utf8(&first).unwrap().to_string();
+        let id = event_ids(&first)[0].to_string();
+        assert!(id.ends_with("-1"), "{first}");
+        assert_eq!(first, format!("id: {id}\ndata: 1\n\n"));
+        let _second = origin.next().await.unwrap().unwrap();
+
+        // The client goes away; the rest of the answer is still buffered.
+        drop(origin);
+        tx.send("data: 3\n\n").unwrap();
+        drop(tx);
+
+        let resumed = streams.resume(&id).expect("stream is resumable");
+        let body = collect(resumed).await;
+        let key = id.trim_end_matches("-1");
+        assert_eq!(
+            body,
+            format!("retry: 1500\n\nid: {key}-2\ndata: 2\n\nid: {key}-3\ndata: 3\n\n")
+        );
+        assert_eq!(streams.active_count(), 1);
+        assert!(streams.resume("0000000000000000-1").is_none());
+        assert!(streams.resume("not-an-id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ids_evicted_from_the_ring_are_not_resumable() {
+        let streams = ResumableStreams::new(&config(2));
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        for frame in ["data: 1\n\n", "data: 2\n\n", "data: 3\n\n", "data: 4\n\n"] {
+            tx.send(frame).unwrap();
+        }
+        drop(tx);
+        let body = collect(streams.publish(sse_response(rx))).await;
+        let ids = event_ids(&body);
+        assert_eq!(ids.len(), 4);
+
+        assert!(streams.resume(ids[0]).is_none());
+        let replay = collect(streams.resume(ids[1]).expect("frames 3 and 4 kept")).await;
+        assert_eq!(event_ids(&replay), &ids[2..]);
+
+        let plain = Response::new(axum::body::Body::from("{}"));
+        let _plain = streams.publish(plain);
+        assert_eq!(streams.active_count(), 1);
+    }
+}
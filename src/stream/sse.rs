@@ -3,12 +3,13 @@
 /// Handles the low-level parsing of SSE frames from a byte stream,
 /// including buffering partial lines and handling field semantics per the
 /// [SSE specification](https://html.spec.whatwg.org/multipage/server-sent-events.html).
+use super::memory_budget::BufferCharge;
+use super::utf8_boundary::Utf8Boundary;
 use super::SseEvent;
 use bytes::BytesMut;
 use futures_util::Stream;
-use memchr::{memchr_iter, memmem};
+use memchr::memchr_iter;
 use smallvec::SmallVec;
-use std::sync::LazyLock;
 
 struct PendingEvents {
     events: SmallVec<[SseEvent; 8]>,
@@ -309,8 +310,9 @@ pub fn gemini_sse_frame(json: &str) -> String {
 
 /// Split a byte stream into SSE events using [`SseParser`].
 ///
-/// Bytes arriving from an HTTP response body are decoded as UTF-8,
-/// fed into the parser, and complete [`SseEvent`] frames are yielded.
+/// Bytes arriving from an HTTP response body are cut into frames, any
+/// character the upstream split between frames is rejoined (see
+/// [`Utf8Boundary`]), and complete [`SseEvent`] frames are yielded.
 ///
 /// This is the primary entry point for converting an HTTP response body
 /// stream into a stream of parsed SSE events.
@@ -323,99 +325,39 @@ where
 
     futures_util::stream::unfold(
         (
-            Box::pin(byte_stream),
+            Box::pin(sse_raw_frame_stream(byte_stream)),
             SseParser::new(),
-            Vec::<u8>::new(),
+            Utf8Boundary::default(),
             Vec::<SseEvent>::with_capacity(8),
             PendingEvents::with_capacity(8),
         ),
-        |(mut stream, mut parser, mut remainder, mut parsed, mut pending)| async move {
+        |(mut frames, mut parser, mut utf8, mut parsed, mut pending)| async move {
             loop {
                 if let Some(event) = pending.pop_front() {
-                    return Some((event, (stream, parser, remainder, parsed, pending)));
-                }
-
-                let chunk = stream.as_mut().next().await?;
-                if let Ok(bytes) = chunk {
-                    if remainder.is_empty() {
-                        match std::str::from_utf8(&bytes) {
-                            Ok(text) => parser.feed_into(text, &mut parsed),
-                            Err(e) => {
-                                let valid_up_to = e.valid_up_to();
-                                // Safety: valid_up_to is guaranteed to be a valid UTF-8 boundary.
-                                let text =
-                                    unsafe { std::str::from_utf8_unchecked(&bytes[..valid_up_to]) };
-                                parser.feed_into(text, &mut parsed);
-                                remainder.extend_from_slice(&bytes[valid_up_to..]);
-                            }
-                        }
-                    } else {
-                        remainder.extend_from_slice(&bytes);
-                        match std::str::from_utf8(remainder.as_slice()) {
-                            Ok(text) => {
-                                parser.feed_into(text, &mut parsed);
-                                remainder.clear();
-                            }
-                            Err(e) => {
-                                let valid_up_to = e.valid_up_to();
-                                // Safety: valid_up_to is guaranteed to be a valid UTF-8 boundary.
-                                let text = unsafe {
-                                    std::str::from_utf8_unchecked(&remainder[..valid_up_to])
-                                };
-                                parser.feed_into(text, &mut parsed);
-                                if valid_up_to > 0 {
-                                    if valid_up_to == remainder.len() {
-                                        remainder.clear();
-                                    } else {
-                                        let remain_len = remainder.len() - valid_up_to;
-                                        remainder.copy_within(valid_up_to.., 0);
-                                        remainder.truncate(remain_len);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    if !parsed.is_empty() {
-                        pending.extend_from_vec(&mut parsed);
-                        if let Some(first) = pending.pop_front() {
-                            return Some((first, (stream, parser, remainder, parsed, pending)));
-                        }
-                    }
+                    return Some((event, (frames, parser, utf8, parsed, pending)));
                 }
+                let frame = frames.as_mut().next().await?;
+                let frame = utf8.repair(&frame);
+                // Repaired frames are valid UTF-8; the lossy decode only borrows.
+                parser.feed_into(&String::from_utf8_lossy(&frame), &mut parsed);
+                pending.extend_from_vec(&mut parsed);
             }
         },
     )
 }
 
+/// The first blank line in `buffer`, as the offset of the `\n` that ends the
+/// line before it and the offset just past it.
+///
+/// Line endings follow [`SseParser`]: `\n` or `\r\n`, mixed freely, so
+/// `\n\r\n` and `\r\n\n` end a frame just like `\n\n` and `\r\n\r\n`.
 #[inline]
 fn find_sse_frame_terminator(buffer: &[u8]) -> Option<(usize, usize)> {
-    find_sse_frame_terminator_from(buffer, 0)
-}
-
-#[inline]
-fn find_sse_frame_terminator_from(buffer: &[u8], scan_from: usize) -> Option<(usize, usize)> {
-    static LF_LF_FINDER: LazyLock<memmem::Finder<'static>> =
-        LazyLock::new(|| memmem::Finder::new(b"\n\n"));
-    static CRLF_CRLF_FINDER: LazyLock<memmem::Finder<'static>> =
-        LazyLock::new(|| memmem::Finder::new(b"\r\n\r\n"));
-
-    let scan_from = scan_from.min(buffer.len());
-    let haystack = &buffer[scan_from..];
-    let lf_lf_pos = LF_LF_FINDER.find(haystack).map(|rel| scan_from + rel);
-    let crlf_crlf_pos = CRLF_CRLF_FINDER.find(haystack).map(|rel| scan_from + rel);
-
-    match (lf_lf_pos, crlf_crlf_pos) {
-        (Some(lf_pos), Some(crlf_pos)) => {
-            if lf_pos <= crlf_pos {
-                Some((lf_pos, 2))
-            } else {
-                Some((crlf_pos, 4))
-            }
-        }
-        (Some(lf_pos), None) => Some((lf_pos, 2)),
-        (None, Some(crlf_pos)) => Some((crlf_pos, 4)),
-        (None, None) => None,
-    }
+    memchr_iter(b'\n', buffer).find_map(|lf| match buffer.get(lf + 1..)? {
+        [b'\n', ..] => Some((lf, lf + 2)),
+        [b'\r', b'\n', ..] => Some((lf, lf + 3)),
+        _ => None,
+    })
 }
 
 /// Split a byte stream into raw SSE frame text chunks.
@@ -423,6 +365,9 @@ fn find_sse_frame_terminator_from(buffer: &[u8], scan_from: usize) -> Option<(us
 /// Each yielded item contains one complete SSE frame, including the trailing
 /// blank-line separator. This is useful for fast passthrough paths that need
 /// frame boundaries but can defer full SSE field parsing.
+///
+/// Frames that end inside an upstream chunk are zero-copy slices of it; only
+/// a frame spanning chunks is assembled, and only up to its terminator.
 pub fn sse_raw_frame_stream<S, E>(byte_stream: S) -> impl Stream<Item = bytes::Bytes> + Send
 where
     S: Stream<Item = Result<bytes::Bytes, E>> + Send + 'static,
@@ -430,45 +375,50 @@ where
 {
     use futures_util::StreamExt;
 
+    // `carry` holds the start of a frame whose terminator has not arrived;
+    // `tail` is the unconsumed rest of the latest upstream chunk.
     futures_util::stream::unfold(
-        (Box::pin(byte_stream), BytesMut::with_capacity(4096), 0usize),
-        |(mut stream, mut buffer, mut scan_from)| async move {
+        (
+            Box::pin(byte_stream),
+            BytesMut::new(),
+            bytes::Bytes::new(),
+            BufferCharge::default(),
+        ),
+        |(mut stream, mut carry, mut tail, mut charge)| async move {
             loop {
-                if let Some((frame_start, frame_len)) =
-                    find_sse_frame_terminator_from(&buffer, scan_from)
-                {
-                    let split = frame_start + frame_len;
-                    let frame = buffer.split_to(split).freeze();
-                    scan_from = 0;
-                    return Some((frame, (stream, buffer, scan_from)));
-                }
-
-                // On no-match, keep only a tiny overlap so next scan can catch
-                // frame terminators spanning chunk boundaries.
-                scan_from = buffer.len().saturating_sub(3);
-                if let Some(Ok(bytes)) = stream.as_mut().next().await {
-                    if buffer.is_empty() {
-                        let chunk = bytes.as_ref();
-                        if let Some((frame_start, frame_len)) = find_sse_frame_terminator(chunk) {
-                            let split = frame_start + frame_len;
-                            if split == chunk.len() {
-                                scan_from = 0;
-                                return Some((bytes, (stream, buffer, scan_from)));
-                            }
-
-                            let frame = bytes.slice(..split);
-                            buffer.extend_from_slice(&chunk[split..]);
-                            scan_from = buffer.len().saturating_sub(3);
-                            return Some((frame, (stream, buffer, scan_from)));
+                if !tail.is_empty() {
+                    let frame_end = if carry.is_empty() {
+                        find_sse_frame_terminator(&tail).map(|(_, end)| end)
+                    } else {
+                        straddling_terminator_end(&carry, &tail)
+                            .or_else(|| find_sse_frame_terminator(&tail).map(|(_, end)| end))
+                    };
+                    match frame_end {
+                        Some(end) if carry.is_empty() => {
+                            let frame = tail.split_to(end);
+                            return Some((frame, (stream, carry, tail, charge)));
+                        }
+                        Some(end) => {
+                            carry.extend_from_slice(&tail.split_to(end));
+                            let frame = carry.split().freeze();
+                            charge.track(carry.capacity());
+                            return Some((frame, (stream, carry, tail, charge)));
+                        }
+                        None => {
+                            carry.extend_from_slice(&tail);
+                            tail.clear();
+                            charge.track(carry.capacity());
                         }
                     }
+                }
 
-                    buffer.extend_from_slice(bytes.as_ref());
+                if let Some(Ok(bytes)) = stream.as_mut().next().await {
+                    tail = bytes;
                 } else {
-                    if !buffer.is_empty() {
-                        let frame = buffer.split().freeze();
-                        scan_from = 0;
-                        return Some((frame, (stream, buffer, scan_from)));
+                    if !carry.is_empty() {
+                        let frame = carry.split().freeze();
+                        charge.track(carry.capacity());
+                        return Some((frame, (stream, carry, tail, charge)));
                     }
                     return None;
                 }
@@ -477,6 +427,97 @@ where
     )
 }
 
+/// End offset in `tail` of a frame terminator that starts in the last bytes
+/// of `carry` and finishes in `tail`.
+#[inline]
+fn straddling_terminator_end(carry: &[u8], tail: &[u8]) -> Option<usize> {
+    // A terminator is at most `\n\r\n` after the previous line's content;
+    // one starting in `tail` is found by scanning `tail` itself.
+    let carried = carry.len().min(2);
+    let taken = tail.len().min(2);
+    let mut window = [0u8; 4];
+    window[..carried].copy_from_slice(&carry[carry.len() - carried..]);
+    window[carried..carried + taken].copy_from_slice(&tail[..taken]);
+    let (lf, end) = find_sse_frame_terminator(&window[..carried + taken])?;
+    (lf < carried).then(|| end - carried)
+}
+
+/// Stop joining frames into one output chunk once it reaches this size.
+const MAX_COALESCED_BYTES: usize = 64 * 1024;
+
+pin_project_lite::pin_project! {
+    /// Stream returned by [`coalesce_sse_frames`].
+    pub struct CoalescedFrames<S> {
+        #[pin]
+        frames: S,
+        done: bool,
+    }
+}
+
+/// Join encoded SSE frames that are ready together into one body chunk.
+///
+/// Frames produced from a single upstream chunk are all ready at once; writing
+/// them as one chunk saves a write (and often a TCP packet) per frame on
+/// chatty streams. A frame that is ready on its own is passed through as is.
+pub fn coalesce_sse_frames<S>(frames: S) -> CoalescedFrames<S>
+where
+    S: Stream<Item = bytes::Bytes>,
+{
+    CoalescedFrames {
+        frames,
+        done: false,
+    }
+}
+
+impl<S> Stream for CoalescedFrames<S>
+where
+    S: Stream<Item = bytes::Bytes>,
+{
+    type Item = bytes::Bytes;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use std::task::Poll;
+
+        let mut this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+        let first = match this.frames.as_mut().poll_next(cx) {
+            Poll::Ready(Some(frame)) => frame,
+            Poll::Ready(None) => {
+                *this.done = true;
+                return Poll::Ready(None);
+            }
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let mut joined: Option<BytesMut> = None;
+        while joined.as_ref().map_or(first.len(), BytesMut::len) < MAX_COALESCED_BYTES {
+            match this.frames.as_mut().poll_next(cx) {
+                Poll::Ready(Some(frame)) => {
+                    joined
+                        .get_or_insert_with(|| {
+                            let mut buffer =
+                                BytesMut::with_capacity((first.len() + frame.len()).max(4096));
+                            buffer.extend_from_slice(&first);
+                            buffer
+                        })
+                        .extend_from_slice(&frame);
+                }
+                Poll::Ready(None) => {
+                    *this.done = true;
+                    break;
+                }
+                Poll::Pending => break,
+            }
+        }
+        Poll::Ready(Some(joined.map_or(first, BytesMut::freeze)))
+    }
+}
+
 const DONE_FRAME: &str = "data: [DONE]\n\n";
 
 // ---------------------------------------------------------------------------
@@ -771,6 +812,19 @@ data: [DONE]\n\
                 Bytes::from_static(b"data: second\n\n")
             ]
         );
+
+        let source = futures_util::stream::iter(vec![Ok::<Bytes, std::convert::Infallible>(
+            Bytes::from_static(b"data: a\n\ndata: b\n\ndata: [DONE]\n\n"),
+        )]);
+        let frames: Vec<Bytes> = sse_raw_frame_stream(source).collect().await;
+        assert_eq!(
+            frames,
+            vec![
+                Bytes::from_static(b"data: a\n\n"),
+                Bytes::from_static(b"data: b\n\n"),
+                Bytes::from_static(b"data: [DONE]\n\n")
+            ]
+        );
     }
 
     #[tokio::test]
@@ -804,4 +858,164 @@ data: [DONE]\n\
             ]
         );
     }
+
+    #[tokio::test]
+    async fn test_sse_raw_frame_stream_slices_frames_without_copying() {
+        let chunk = Bytes::from_static(b"data: a\n\ndata: b\n\ndata: c");
+        let source_range = chunk.as_ptr_range();
+        let source = futures_util::stream::iter(vec![
+            Ok::<Bytes, std::convert::Infallible>(chunk),
+            Ok(Bytes::from_static(b"\r\n\r\ndata: d\r\n")),
+            Ok(Bytes::from_static(b"\r\n")),
+        ]);
+        let frames: Vec<Bytes> = sse_raw_frame_stream(source).collect().await;
+        assert_eq!(
+            frames,
+            vec![
+                Bytes::from_static(b"data: a\n\n"),
+                Bytes::from_static(b"data: b\n\n"),
+                Bytes::from_static(b"data: c\r\n\r\n"),
+                Bytes::from_static(b"data: d\r\n\r\n"),
+            ]
+        );
+        assert!(frames[..2]
+            .iter()
+            .all(|frame| source_range.contains(&frame.as_ptr())));
+    }
+
+    /// Frames with every line-ending mix upstreams send, a comment, multi-line
+    /// data and multi-byte UTF-8.
+    const SPLIT_CORPUS: &str = concat!(
+        "event: message_start\r\ndata: {\"a\":1}\r\n\r\n",
+        ": keepalive\n\n",
+        "data: {\"text\":\"h\u{e9}llo \u{1f30d}\"}\n\n",
+        "data: line one\ndata: line two\n\r\n",
+        "event: ping\r\ndata: {}\r\n\n",
+        "data: [DONE]\n\n",
+    );
+
+    fn event_fields(events: impl IntoIterator<Item = SseEvent>) -> Vec<(Option<String>, String)> {
+        events
+            .into_iter()
+            .map(|event| (event.event, event.data))
+            .collect()
+    }
+
+    /// `SPLIT_CORPUS` split at `cut` and again into single bytes, so every
+    /// byte boundary (inside terminators and UTF-8 sequences too) is a chunk
+    /// boundary in some run.
+    fn split_sources(cut: usize) -> [Vec<Result<Bytes, std::convert::Infallible>>; 2] {
+        let bytes = SPLIT_CORPUS.as_bytes();
+        [
+            vec![
+                Ok(Bytes::copy_from_slice(&bytes[..cut])),
+                Ok(Bytes::copy_from_slice(&bytes[cut..])),
+            ],
+            bytes
+                .iter()
+                .map(|&byte| Ok(Bytes::copy_from_slice(&[byte])))
+                .collect(),
+        ]
+    }
+
+    #[test]
+    fn test_sse_raw_frame_stream_frames_match_parser_at_every_split() {
+        use futures_util::FutureExt;
+
+        let expected = event_fields(SseParser::new().feed(SPLIT_CORPUS));
+        assert_eq!(expected.len(), 5);
+        assert_eq!(expected[2].1, "line one\nline two");
+        for cut in 0..=SPLIT_CORPUS.len() {
+            for source in split_sources(cut) {
+                let frames: Vec<Bytes> = sse_raw_frame_stream(futures_util::stream::iter(source))
+                    .collect()
+                    .now_or_never()
+                    .expect("ready source");
+                let joined: Vec<u8> = frames
+                    .iter()
+                    .flat_map(|frame| frame.iter().copied())
+                    .collect();
+                assert_eq!(joined, SPLIT_CORPUS.as_bytes(), "cut at {cut}");
+                let parsed = frames
+                    .iter()
+                    .filter_map(|frame| super::super::parse_sse_frame_bytes(frame));
+                assert_eq!(event_fields(parsed), expected, "cut at {cut}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sse_frame_stream_matches_parser_at_every_split() {
+        use futures_util::FutureExt;
+
+        let expected = event_fields(SseParser::new().feed(SPLIT_CORPUS));
+        for cut in 0..=SPLIT_CORPUS.len() {
+            for source in split_sources(cut) {
+                let events: Vec<SseEvent> = sse_frame_stream(futures_util::stream::iter(source))
+                    .collect()
+                    .now_or_never()
+                    .expect("ready source");
+                assert_eq!(event_fields(events), expected, "cut at {cut}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sse_frame_stream_rejoins_character_cut_between_frames() {
+        let source = futures_util::stream::iter(vec![
+            Ok::<Bytes, std::convert::Infallible>(Bytes::from_static(
+                b"data: {\"t\":\"a\xf0\x9f\"}\n\ndata: {\"t\":\"\x8c",
+            )),
+            Ok(Bytes::from_static(b"\x8db\"}\n\ndata: [DONE]\n\n")),
+        ]);
+        let events: Vec<SseEvent> = sse_frame_stream(source).collect().await;
+        let data: Vec<&str> = events.iter().map(|event| event.data.as_str()).collect();
+        assert_eq!(data, ["{\"t\":\"a\"}", "{\"t\":\"\u{1f30d}b\"}", "[DONE]"]);
+    }
+
+    #[test]
+    fn test_frame_terminator_accepts_mixed_line_endings() {
+        for (raw, end) in [
+            (&b"data: a\n\nrest"[..], 9),
+            (b"data: a\r\n\r\nrest", 11),
+            (b"data: a\n\r\nrest", 10),
+            (b"data: a\r\n\nrest", 10),
+        ] {
+            assert_eq!(
+                find_sse_frame_terminator(raw).map(|(_, end)| end),
+                Some(end)
+            );
+        }
+        assert!(find_sse_frame_terminator(b"data: a\r\ndata: b\r\n").is_none());
+        assert_eq!(
+            straddling_terminator_end(b"data: a\n\r", b"\ndata"),
+            Some(1)
+        );
+        assert_eq!(straddling_terminator_end(b"data: a\n", b"\r\n"), Some(2));
+        assert_eq!(straddling_terminator_end(b"data: a\r", b"\n\r\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_sse_frames_joins_frames_ready_together() {
+        let lone = Bytes::from_static(b"data: c\n\n");
+        let lone_ptr = lone.as_ptr();
+        let later = futures_util::stream::once(async move {
+            tokio::task::yield_now().await;
+            lone
+        });
+        let frames = futures_util::stream::iter(vec![
+            Bytes::from_static(b"data: a\n\n"),
+            Bytes::from_static(b"data: b\n\n"),
+        ])
+        .chain(later);
+        let chunks: Vec<Bytes> = coalesce_sse_frames(frames).collect().await;
+        assert_eq!(
+            chunks,
+            vec![
+                Bytes::from_static(b"data: a\n\ndata: b\n\n"),
+                Bytes::from_static(b"data: c\n\n"),
+            ]
+        );
+        assert_eq!(chunks[1].as_ptr(), lone_ptr);
+    }
 }
@@ -0,0 +1,263 @@
+//! Client-side stop sequence emulation.
+//!
+//! Some upstreams cannot carry the client's stop strings (the Responses API
+//! has no such parameter) or silently ignore them. The compat flow passes the
+//! client's stop sequences down in its request context, and the
+//! [`StopSequenceScanner`] of a [`super::StreamTranscoder`] cuts the decoded
+//! stream at the first match, even when it is split across chunks.
+
+use std::sync::Arc;
+
+#[cfg(feature = "server")]
+use crate::config::StopSequenceEmulation;
+use crate::protocol::canonical::{CanonicalStopReason, CanonicalStreamEvent, ProviderKind};
+
+/// The client's stop sequences when `mode` calls for emulating them on a
+/// stream from a `provider` upstream.
+#[cfg(feature = "server")]
+#[must_use]
+pub fn emulated_stop_sequences(
+    mode: StopSequenceEmulation,
+    provider: ProviderKind,
+) -> Option<Arc<[String]>> {
+    let emulate = match mode {
+        StopSequenceEmulation::Off => false,
+        StopSequenceEmulation::Always => true,
+        StopSequenceEmulation::Auto => !provider_supports_stop_sequences(provider),
+    };
+    if !emulate {
+        return None;
+    }
+    crate::state::request_context::with(|context| context.stop_sequences.clone())
+        .flatten()
+        .filter(|stops| !stops.is_empty())
+}
+
+/// Whether requests encoded for `provider` carry the client's stop sequences.
+#[must_use]
+pub const fn provider_supports_stop_sequences(provider: ProviderKind) -> bool {
+    !matches!(provider, ProviderKind::OpenAiResponses)
+}
+
+/// Cuts a canonical event stream at the first client stop sequence.
+#[derive(Debug)]
+pub struct StopSequenceScanner {
+    stops: Arc<[String]>,
+    /// Text that may be the start of a stop sequence split across deltas.
+    held: String,
+    stopped: bool,
+}
+
+impl StopSequenceScanner {
+    #[must_use]
+    pub fn new(stops: Arc<[String]>) -> Self {
+        Self {
+            stops,
+            held: String::new(),
+            stopped: false,
+        }
+    }
+
+    /// Whether a stop sequence was hit; every later event is dropped.
+    #[must_use]
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Rewrite the decoded `events` in place.
+    ///
+    /// Text that may begin a stop sequence is held back until the next delta
+    /// rules it out or the message ends. On a match the text before it is
+    /// kept and the stream ends with a stop-sequence `MessageEnd` and `Done`.
+    pub fn apply(&mut self, events: &mut Vec<CanonicalStreamEvent>) {
+        if self.stopped {
+            events.clear();
+            return;
+        }
+        if self.held.is_empty()
+            && !events.iter().any(|event| {
+                matches!(
+                    event,
+                    CanonicalStreamEvent::TextDelta(_)
+                        | CanonicalStreamEvent::MessageEnd { .. }
+                        | CanonicalStreamEvent::Done
+                        | CanonicalStreamEvent::Error { .. }
+                )
+            })
+        {
+            return;
+        }
+        let decoded = std::mem::take(events);
+        for event in decoded {
+            match event {
+                CanonicalStreamEvent::TextDelta(delta) => {
+                    self.held.push_str(&delta);
+                    if let Some(cut) = find_stop(&self.held, &self.stops) {
+                        self.held.truncate(cut);
+                        self.flush_into(events);
+                        self.stopped = true;
+                        events.push(CanonicalStreamEvent::MessageEnd {
+                            stop_reason: CanonicalStopReason::StopSequence,
+                        });
+                        events.push(CanonicalStreamEvent::Done);
+                        return;
+                    }
+                    let keep = partial_stop_suffix_len(&self.held, &self.stops);
+                    let tail = self.held.split_off(self.held.len() - keep);
+                    let text = std::mem::replace(&mut self.held, tail);
+                    if !text.is_empty() {
+                        events.push(CanonicalStreamEvent::TextDelta(text));
+                    }
+                }
+                CanonicalStreamEvent::MessageEnd { .. }
+                | CanonicalStreamEvent::Done
+                | CanonicalStreamEvent::Error { .. } => {
+                    self.flush_into(events);
+                    events.push(event);
+                }
+                other => events.push(other),
+            }
+        }
+    }
+
+    /// Release held-back text when the upstream ends without a terminal event.
+    #[must_use]
+    pub fn finish(&mut self) -> Option<CanonicalStreamEvent> {
+        (!self.stopped && !self.held.is_empty())
+            .then(|| CanonicalStreamEvent::TextDelta(std::mem::take(&mut self.held)))
+    }
+
+    fn flush_into(&mut self, events: &mut Vec<CanonicalStreamEvent>) {
+        if !self.held.is_empty() {
+            events.push(CanonicalStreamEvent::TextDelta(std::mem::take(
+                &mut self.held,
+            )));
+        }
+    }
+}
+
+/// Byte offset of the earliest stop sequence in `text`.
+pub(crate) fn find_stop(text: &str, stops: &[String]) -> Option<usize> {
+    stops
+        .iter()
+        .filter(|stop| !stop.is_empty())
+        .filter_map(|stop| text.find(stop.as_str()))
+        .min()
+}
+
+/// Length of the longest suffix of `text` that starts some stop sequence.
+pub(crate) fn partial_stop_suffix_len(text: &str, stops: &[String]) -> usize {
+    let longest = stops.iter().map(String::len).max().unwrap_or(0);
+    let mut len = longest.saturating_sub(1).min(text.len());
+    while len > 0 {
+        let start = text.len() - len;
+        if text.is_char_boundary(start) && stops.iter().any(|stop| stop.starts_with(&text[start..]))
+        {
+            return len;
+        }
+        len -= 1;
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_of(events: &[CanonicalStreamEvent]) -> String {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                CanonicalStreamEvent::TextDelta(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scanner_cuts_stop_split_across_deltas() {
+        let mut scanner = StopSequenceScanner::new(Arc::from(vec!["</answer>".to_string()]));
+        let mut events = vec![CanonicalStreamEvent::TextDelta("42</ans".to_string())];
+        scanner.apply(&mut events);
+        assert_eq!(text_of(&events), "42");
+
+        let mut events = vec![
+            CanonicalStreamEvent::TextDelta("wer> trailing".to_string()),
+            CanonicalStreamEvent::Usage(crate::protocol::canonical::CanonicalUsage::default()),
+        ];
+        scanner.apply(&mut events);
+        assert!(scanner.stopped());
+        assert!(matches!(
+            events.as_slice(),
+            [
+                CanonicalStreamEvent::MessageEnd {
+                    stop_reason: CanonicalStopReason::StopSequence
+                },
+                CanonicalStreamEvent::Done
+            ]
+        ));
+
+        let mut events = vec![CanonicalStreamEvent::TextDelta("more".to_string())];
+        scanner.apply(&mut events);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_scanner_releases_false_partial_match_at_message_end() {
+        let mut scanner = StopSequenceScanner::new(Arc::from(vec!["END".to_string()]));
+        let mut events = vec![CanonicalStreamEvent::TextDelta("the E".to_string())];
+        scanner.apply(&mut events);
+        assert_eq!(text_of(&events), "the ");
+
+        let mut events = vec![
+            CanonicalStreamEvent::TextDelta("N".to_string()),
+            CanonicalStreamEvent::MessageEnd {
+                stop_reason: CanonicalStopReason::MaxTokens,
+            },
+        ];
+        scanner.apply(&mut events);
+        assert_eq!(text_of(&events), "EN");
+        assert!(matches!(
+            events.last(),
+            Some(CanonicalStreamEvent::MessageEnd {
+                stop_reason: CanonicalStopReason::MaxTokens
+            })
+        ));
+        assert!(!scanner.stopped());
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn test_emulated_stop_sequences_follow_mode_and_provider() {
+        let stops: Arc<[String]> = Arc::from(vec!["END".to_string()]);
+        assert!(
+            emulated_stop_sequences(StopSequenceEmulation::Always, ProviderKind::OpenAi).is_none()
+        );
+        let context = crate::state::request_context::RequestContext {
+            stop_sequences: Some(Arc::clone(&stops)),
+            ..Default::default()
+        };
+        crate::state::request_context::scope(context, async {
+            assert!(
+                emulated_stop_sequences(StopSequenceEmulation::Auto, ProviderKind::OpenAi)
+                    .is_none()
+            );
+            assert!(emulated_stop_sequences(
+                StopSequenceEmulation::Auto,
+                ProviderKind::OpenAiResponses
+            )
+            .is_some());
+            assert!(emulated_stop_sequences(
+                StopSequenceEmulation::Always,
+                ProviderKind::Anthropic
+            )
+            .is_some());
+            assert!(emulated_stop_sequences(
+                StopSequenceEmulation::Off,
+                ProviderKind::OpenAiResponses
+            )
+            .is_none());
+        })
+        .await;
+    }
+}
@@ -0,0 +1,103 @@
+//! Recycled string buffers for decoded stream events.
+//!
+//! Every text, reasoning, and tool-argument delta decoded from an upstream
+//! frame owns a `String` that is dropped as soon as the frame is re-encoded.
+//! A [`StringPool`] lives as long as one streamed response: clearing the
+//! decode buffer hands those strings back, and the next frame's deltas are
+//! copied into them instead of fresh allocations.
+
+use std::borrow::Cow;
+
+use crate::protocol::canonical::CanonicalStreamEvent;
+
+/// Strings kept for reuse; a frame rarely decodes to more events than this.
+const MAX_POOLED_STRINGS: usize = 16;
+/// Larger buffers are freed rather than pinned for the rest of the stream.
+const MAX_POOLED_CAPACITY: usize = 4096;
+
+#[derive(Debug, Default)]
+pub(crate) struct StringPool {
+    free: Vec<String>,
+}
+
+impl StringPool {
+    /// Clear `events`, keeping the delta strings they own for reuse.
+    pub(crate) fn reclaim(&mut self, events: &mut Vec<CanonicalStreamEvent>) {
+        for event in events.drain(..) {
+            self.put_event(event);
+        }
+    }
+
+    /// Keep the delta string `event` owns, if any.
+    pub(crate) fn put_event(&mut self, event: CanonicalStreamEvent) {
+        match event {
+            CanonicalStreamEvent::TextDelta(text)
+            | CanonicalStreamEvent::ReasoningDelta(text)
+            | CanonicalStreamEvent::ToolCallArgsDelta { delta: text, .. } => self.put(text),
+            _ => {}
+        }
+    }
+
+    fn put(&mut self, mut text: String) {
+        if self.free.len() < MAX_POOLED_STRINGS
+            && text.capacity() > 0
+            && text.capacity() <= MAX_POOLED_CAPACITY
+        {
+            text.clear();
+            self.free.push(text);
+        }
+    }
+
+    /// An owned copy of `text`, in a recycled buffer when one is free.
+    pub(crate) fn copy(&mut self, text: &str) -> String {
+        match self.free.pop() {
+            Some(mut buffer) => {
+                buffer.push_str(text);
+                buffer
+            }
+            None => text.to_owned(),
+        }
+    }
+
+    /// Own `text`, copying borrowed input into a recycled buffer; strings the
+    /// parser already had to allocate (unescaped values) are kept as is.
+    pub(crate) fn own(&mut self, text: Cow<'_, str>) -> String {
+        match text {
+            Cow::Borrowed(text) => self.copy(text),
+            Cow::Owned(text) => text,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reclaimed_delta_buffers_are_reused() {
+        let mut pool = StringPool::default();
+        let first = pool.copy("hello");
+        let first_ptr = first.as_ptr();
+        let mut events = vec![
+            CanonicalStreamEvent::TextDelta(first),
+            CanonicalStreamEvent::Done,
+        ];
+
+        pool.reclaim(&mut events);
+        assert!(events.is_empty());
+        let reused = pool.own(Cow::Borrowed("hi"));
+        assert_eq!(reused, "hi");
+        assert_eq!(reused.as_ptr(), first_ptr);
+        assert_eq!(pool.copy("fresh"), "fresh");
+    }
+
+    #[test]
+    fn test_oversized_buffers_are_not_pooled() {
+        let mut pool = StringPool::default();
+        let mut events = vec![CanonicalStreamEvent::ReasoningDelta(
+            "x".repeat(MAX_POOLED_CAPACITY + 1),
+        )];
+        pool.reclaim(&mut events);
+        assert!(pool.free.is_empty());
+    }
+}
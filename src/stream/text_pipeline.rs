@@ -0,0 +1,331 @@
+//! Incremental post-processing of assistant text.
+//!
+//! [`OutputPostprocess`] holds the configured steps; each response runs its
+//! own [`TextPipeline`], which accepts text in arbitrary chunks and produces
+//! the same output as processing the complete text at once. Steps hold back
+//! text that a later chunk may still change: a possible stop-sequence prefix,
+//! a trailing whitespace run, or an unfinished line for regex steps.
+
+use std::sync::Arc;
+
+use regex_lite::Regex;
+
+use crate::config::OutputPostprocessStep;
+use crate::protocol::canonical::{CanonicalStopReason, CanonicalStreamEvent};
+
+use super::stop_sequences::{find_stop, partial_stop_suffix_len};
+
+#[derive(Debug)]
+enum CompiledStep {
+    EnforceStop,
+    TrimTrailingWhitespace,
+    RegexReplace { regex: Regex, replacement: String },
+}
+
+/// Compiled output post-processing steps.
+#[derive(Debug)]
+pub struct OutputPostprocess {
+    steps: Vec<CompiledStep>,
+}
+
+impl OutputPostprocess {
+    /// Compile `steps`; `None` when there are none. Invalid regex steps are
+    /// rejected by config validation.
+    #[must_use]
+    pub fn new(steps: &[OutputPostprocessStep]) -> Option<Self> {
+        let steps: Vec<CompiledStep> = steps
+            .iter()
+            .filter_map(|step| match step {
+                OutputPostprocessStep::EnforceStop => Some(CompiledStep::EnforceStop),
+                OutputPostprocessStep::TrimTrailingWhitespace => {
+                    Some(CompiledStep::TrimTrailingWhitespace)
+                }
+                OutputPostprocessStep::RegexReplace {
+                    pattern,
+                    replacement,
+                } => Regex::new(pattern)
+                    .ok()
+                    .map(|regex| CompiledStep::RegexReplace {
+                        regex,
+                        replacement: replacement.clone(),
+                    }),
+            })
+            .collect();
+        (!steps.is_empty()).then_some(Self { steps })
+    }
+}
+
+#[derive(Debug, Default)]
+struct StepState {
+    held: String,
+    stopped: bool,
+}
+
+/// Per-response state of an [`OutputPostprocess`] chain.
+#[derive(Debug)]
+pub struct TextPipeline {
+    postprocess: Arc<OutputPostprocess>,
+    states: Vec<StepState>,
+    /// The client's stop sequences, used by `enforce_stop` steps.
+    stop_sequences: Vec<String>,
+    stopped: bool,
+}
+
+impl TextPipeline {
+    #[must_use]
+    pub fn new(postprocess: Arc<OutputPostprocess>, stop_sequences: Vec<String>) -> Self {
+        let states = postprocess
+            .steps
+            .iter()
+            .map(|_| StepState::default())
+            .collect();
+        Self {
+            postprocess,
+            states,
+            stop_sequences: stop_sequences
+                .into_iter()
+                .filter(|stop| !stop.is_empty())
+                .collect(),
+            stopped: false,
+        }
+    }
+
+    /// Whether a stop sequence was hit; later text is dropped.
+    #[must_use]
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Process the next chunk, returning the text that is final so far.
+    pub fn push(&mut self, chunk: &str) -> String {
+        if self.stopped {
+            return String::new();
+        }
+        let mut text = chunk.to_string();
+        for (step, state) in self.postprocess.steps.iter().zip(&mut self.states) {
+            text = push_step(step, state, &self.stop_sequences, &text);
+            self.stopped |= state.stopped;
+        }
+        text
+    }
+
+    /// Release the text still held back at the end of the output.
+    pub fn finish(&mut self) -> String {
+        let mut text = String::new();
+        for (step, state) in self.postprocess.steps.iter().zip(&mut self.states) {
+            text = push_step(step, state, &self.stop_sequences, &text);
+            text.push_str(&finish_step(step, state));
+            self.stopped |= state.stopped;
+        }
+        text
+    }
+
+    /// Process a complete text.
+    pub fn process(&mut self, text: &str) -> String {
+        let mut out = self.push(text);
+        out.push_str(&self.finish());
+        out
+    }
+
+    /// Process one canonical stream event into `out`.
+    ///
+    /// Held-back text is released before the message ends, and a hit stop
+    /// sequence turns the stop reason into a stop-sequence stop.
+    pub fn apply_event(
+        &mut self,
+        event: CanonicalStreamEvent,
+        out: &mut Vec<CanonicalStreamEvent>,
+    ) {
+        match event {
+            CanonicalStreamEvent::TextDelta(delta) => {
+                let text = self.push(&delta);
+                if !text.is_empty() {
+                    out.push(CanonicalStreamEvent::TextDelta(text));
+                }
+            }
+            CanonicalStreamEvent::MessageEnd { stop_reason } => {
+                self.flush_into(out);
+                let stop_reason = if self.stopped {
+                    CanonicalStopReason::StopSequence
+                } else {
+                    stop_reason
+                };
+                out.push(CanonicalStreamEvent::MessageEnd { stop_reason });
+            }
+            CanonicalStreamEvent::Done | CanonicalStreamEvent::Error { .. } => {
+                self.flush_into(out);
+                out.push(event);
+            }
+            other => out.push(other),
+        }
+    }
+
+    fn flush_into(&mut self, out: &mut Vec<CanonicalStreamEvent>) {
+        let text = self.finish();
+        if !text.is_empty() {
+            out.push(CanonicalStreamEvent::TextDelta(text));
+        }
+    }
+}
+
+fn push_step(
+    step: &CompiledStep,
+    state: &mut StepState,
+    stop_sequences: &[String],
+    text: &str,
+) -> String {
+    if state.stopped {
+        return String::new();
+    }
+    match step {
+        CompiledStep::EnforceStop => {
+            if stop_sequences.is_empty() {
+                return text.to_string();
+            }
+            state.held.push_str(text);
+            if let Some(cut) = find_stop(&state.held, stop_sequences) {
+                state.stopped = true;
+                let mut out = std::mem::take(&mut state.held);
+                out.truncate(cut);
+                return out;
+            }
+            let keep = partial_stop_suffix_len(&state.held, stop_sequences);
+            let tail = state.held.split_off(state.held.len() - keep);
+            std::mem::replace(&mut state.held, tail)
+        }
+        CompiledStep::TrimTrailingWhitespace => {
+            state.held.push_str(text);
+            let end = state.held.trim_end().len();
+            let tail = state.held.split_off(end);
+            std::mem::replace(&mut state.held, tail)
+        }
+        CompiledStep::RegexReplace { regex, replacement } => {
+            state.held.push_str(text);
+            let Some(line_end) = state.held.rfind('\n') else {
+                return String::new();
+            };
+            let tail = state.held.split_off(line_end + 1);
+            let lines = std::mem::replace(&mut state.held, tail);
+            regex.replace_all(&lines, replacement.as_str()).into_owned()
+        }
+    }
+}
+
+fn finish_step(step: &CompiledStep, state: &mut StepState) -> String {
+    let held = std::mem::take(&mut state.held);
+    if state.stopped {
+        return String::new();
+    }
+    match step {
+        CompiledStep::EnforceStop => held,
+        CompiledStep::TrimTrailingWhitespace => String::new(),
+        CompiledStep::RegexReplace { regex, replacement } => {
+            regex.replace_all(&held, replacement.as_str()).into_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pipeline(steps: Vec<OutputPostprocessStep>, stops: &[&str]) -> TextPipeline {
+        TextPipeline::new(
+            Arc::new(OutputPostprocess::new(&steps).unwrap()),
+            stops.iter().map(|stop| (*stop).to_string()).collect(),
+        )
+    }
+
+    fn run_chunked(pipeline: &mut TextPipeline, chunks: &[&str]) -> String {
+        let mut out: String = chunks.iter().map(|chunk| pipeline.push(chunk)).collect();
+        out.push_str(&pipeline.finish());
+        out
+    }
+
+    #[test]
+    fn test_stop_sequence_split_across_chunks() {
+        let mut stop = pipeline(vec![OutputPostprocessStep::EnforceStop], &["END", "###"]);
+        assert_eq!(stop.push("hello E"), "hello ");
+        assert_eq!(stop.push("N"), "");
+        assert_eq!(stop.push("D and more"), "");
+        assert!(stop.stopped());
+        assert_eq!(stop.push("ignored"), "");
+        assert_eq!(stop.finish(), "");
+
+        let mut partial = pipeline(vec![OutputPostprocessStep::EnforceStop], &["END"]);
+        assert_eq!(run_chunked(&mut partial, &["ok E", "N"]), "ok EN");
+        assert!(!partial.stopped());
+    }
+
+    #[test]
+    fn test_chain_matches_whole_text_processing() {
+        let steps = vec![
+            OutputPostprocessStep::RegexReplace {
+                pattern: r"(?i)as an ai,?\s*".to_string(),
+                replacement: String::new(),
+            },
+            OutputPostprocessStep::EnforceStop,
+            OutputPostprocessStep::TrimTrailingWhitespace,
+        ];
+        let text = "As an AI, hi there.  \nAs an ai the end  \n\nUser: next";
+        let expected = pipeline(steps.clone(), &["User:"]).process(text);
+        assert_eq!(expected, "hi there.  \nthe end");
+        for size in 1..text.len() {
+            let chunks: Vec<&str> = text
+                .as_bytes()
+                .chunks(size)
+                .map(|chunk| std::str::from_utf8(chunk).unwrap())
+                .collect();
+            let mut chunked = pipeline(steps.clone(), &["User:"]);
+            assert_eq!(
+                run_chunked(&mut chunked, &chunks),
+                expected,
+                "chunk size {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_event_flushes_before_message_end() {
+        let mut trim = pipeline(vec![OutputPostprocessStep::EnforceStop], &["STOP"]);
+        let mut out = Vec::new();
+        trim.apply_event(
+            CanonicalStreamEvent::TextDelta("a ST".to_string()),
+            &mut out,
+        );
+        trim.apply_event(
+            CanonicalStreamEvent::MessageEnd {
+                stop_reason: CanonicalStopReason::MaxTokens,
+            },
+            &mut out,
+        );
+        assert!(matches!(&out[0], CanonicalStreamEvent::TextDelta(text) if text == "a "));
+        assert!(matches!(&out[1], CanonicalStreamEvent::TextDelta(text) if text == "ST"));
+        assert!(matches!(
+            out[2],
+            CanonicalStreamEvent::MessageEnd {
+                stop_reason: CanonicalStopReason::MaxTokens
+            }
+        ));
+
+        let mut stop = pipeline(vec![OutputPostprocessStep::EnforceStop], &["STOP"]);
+        out.clear();
+        stop.apply_event(
+            CanonicalStreamEvent::TextDelta("a STOP b".to_string()),
+            &mut out,
+        );
+        stop.apply_event(
+            CanonicalStreamEvent::MessageEnd {
+                stop_reason: CanonicalStopReason::MaxTokens,
+            },
+            &mut out,
+        );
+        assert_eq!(out.len(), 2);
+        assert!(matches!(
+            out[1],
+            CanonicalStreamEvent::MessageEnd {
+                stop_reason: CanonicalStopReason::StopSequence
+            }
+        ));
+    }
+}
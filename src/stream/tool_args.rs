@@ -0,0 +1,279 @@
+//! Incremental streaming of injected tool-call arguments.
+//!
+//! While the detector buffers a `<function_calls>` block, [`ToolArgsStreamer`]
+//! rescans it as it grows. A call starts on the client once its `<tool>` name
+//! and the opening of its `<args_json><![CDATA[{` are in, and the CDATA text
+//! is then forwarded as argument deltas, the way native providers stream
+//! `arguments`. A call in any other shape stops the scan; the processor emits
+//! it and every later call from the parsed block when the stream finalizes.
+
+use memchr::memmem;
+
+use crate::fc::parser::extract_first_call_id;
+use crate::protocol::canonical::CanonicalStreamEvent;
+use crate::util::next_call_id;
+
+const FUNCTION_CALL_OPEN: &str = "<function_call>";
+const FUNCTION_CALL_CLOSE: &str = "</function_call>";
+const TOOL_OPEN: &str = "<tool>";
+const TOOL_CLOSE: &str = "</tool>";
+const ARGS_OPEN: &str = "<args_json>";
+const CDATA_OPEN: &str = "<![CDATA[";
+const CDATA_CLOSE: &str = "]]>";
+
+enum Scan {
+    /// Looking for the next `<function_call>`.
+    Call,
+    /// Inside a call, waiting for `<args_json>`.
+    Header,
+    /// After `<args_json>`, waiting for the CDATA and the first JSON byte.
+    ArgsStart { id: String, name: String },
+    /// Forwarding CDATA text up to `]]>`.
+    Args { index: usize },
+    /// Arguments complete, waiting for `</function_call>`.
+    CallEnd { index: usize },
+    /// The block took a shape this scanner does not stream.
+    Stopped,
+}
+
+/// Streams tool calls out of a growing tool block.
+pub(crate) struct ToolArgsStreamer {
+    scan: Scan,
+    /// Byte offset into the block up to which text has been consumed.
+    cursor: usize,
+    started: usize,
+}
+
+impl ToolArgsStreamer {
+    #[must_use]
+    pub(crate) fn new() -> Self {
+        Self {
+            scan: Scan::Call,
+            cursor: 0,
+            started: 0,
+        }
+    }
+
+    /// Number of calls already started on the client.
+    #[must_use]
+    pub(crate) fn started(&self) -> usize {
+        self.started
+    }
+
+    /// Scan `block`, the buffered text from the trigger onward, and append
+    /// the events for what arrived since the last call. `next_index` is the
+    /// processor's running tool-call index.
+    pub(crate) fn advance(
+        &mut self,
+        block: &str,
+        next_index: &mut usize,
+        out: &mut Vec<CanonicalStreamEvent>,
+    ) {
+        loop {
+            let Some(rest) = block.get(self.cursor..) else {
+                return;
+            };
+            match std::mem::replace(&mut self.scan, Scan::Stopped) {
+                Scan::Call => {
+                    let Some(pos) = find(rest, FUNCTION_CALL_OPEN) else {
+                        self.scan = Scan::Call;
+                        return;
+                    };
+                    self.cursor += pos + FUNCTION_CALL_OPEN.len();
+                    self.scan = Scan::Header;
+                }
+                Scan::Header => {
+                    let call_end = find(rest, FUNCTION_CALL_CLOSE);
+                    let Some(pos) = find(rest, ARGS_OPEN) else {
+                        if call_end.is_none() {
+                            self.scan = Scan::Header;
+                        }
+                        return;
+                    };
+                    if call_end.is_some_and(|end| end < pos) {
+                        return;
+                    }
+                    let header = &rest[..pos];
+                    let Some(name) = tag_text(header, TOOL_OPEN, TOOL_CLOSE)
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                    else {
+                        return;
+                    };
+                    let id = extract_first_call_id(header).map_or_else(next_call_id, String::from);
+                    self.scan = Scan::ArgsStart {
+                        id,
+                        name: name.to_string(),
+                    };
+                    self.cursor += pos + ARGS_OPEN.len();
+                }
+                Scan::ArgsStart { id, name } => {
+                    let opening = rest.trim_start();
+                    let Some(json) = opening.strip_prefix(CDATA_OPEN) else {
+                        if CDATA_OPEN.starts_with(opening) {
+                            self.scan = Scan::ArgsStart { id, name };
+                        }
+                        return;
+                    };
+                    let json = json.trim_start();
+                    if json.is_empty() {
+                        self.scan = Scan::ArgsStart { id, name };
+                        return;
+                    }
+                    if !json.starts_with('{') {
+                        return;
+                    }
+                    let index = *next_index;
+                    *next_index += 1;
+                    self.started += 1;
+                    out.push(CanonicalStreamEvent::ToolCallStart { index, id, name });
+                    self.cursor = block.len() - json.len();
+                    self.scan = Scan::Args { index };
+                }
+                Scan::Args { index } => {
+                    if let Some(pos) = find(rest, CDATA_CLOSE) {
+                        push_args_delta(out, index, &rest[..pos]);
+                        self.cursor += pos + CDATA_CLOSE.len();
+                        self.scan = Scan::CallEnd { index };
+                        continue;
+                    }
+                    // Hold back a tail that may be the start of `]]>`.
+                    let held = if rest.ends_with("]]") {
+                        2
+                    } else {
+                        usize::from(rest.ends_with(']'))
+                    };
+                    let ready = rest.len() - held;
+                    push_args_delta(out, index, &rest[..ready]);
+                    self.cursor += ready;
+                    self.scan = Scan::Args { index };
+                    return;
+                }
+                Scan::CallEnd { index } => {
+                    let Some(pos) = find(rest, FUNCTION_CALL_CLOSE) else {
+                        self.scan = Scan::CallEnd { index };
+                        return;
+                    };
+                    out.push(tool_call_end(index));
+                    self.cursor += pos + FUNCTION_CALL_CLOSE.len();
+                    self.scan = Scan::Call;
+                }
+                Scan::Stopped => return,
+            }
+        }
+    }
+
+    /// End the call whose arguments are still streaming, if any, and stop
+    /// scanning.
+    pub(crate) fn close(&mut self, out: &mut Vec<CanonicalStreamEvent>) {
+        if let Scan::Args { index } | Scan::CallEnd { index } =
+            std::mem::replace(&mut self.scan, Scan::Stopped)
+        {
+            out.push(tool_call_end(index));
+        }
+    }
+}
+
+fn find(haystack: &str, needle: &str) -> Option<usize> {
+    memmem::find(haystack.as_bytes(), needle.as_bytes())
+}
+
+fn tag_text<'a>(text: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let start = find(text, open)? + open.len();
+    let end = find(&text[start..], close)?;
+    Some(&text[start..start + end])
+}
+
+fn push_args_delta(out: &mut Vec<CanonicalStreamEvent>, index: usize, delta: &str) {
+    if !delta.is_empty() {
+        out.push(CanonicalStreamEvent::ToolCallArgsDelta {
+            index,
+            delta: delta.to_string(),
+        });
+    }
+}
+
+fn tool_call_end(index: usize) -> CanonicalStreamEvent {
+    CanonicalStreamEvent::ToolCallEnd {
+        index,
+        call_id: None,
+        call_name: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_chunks(chunks: &[&str]) -> (Vec<CanonicalStreamEvent>, ToolArgsStreamer) {
+        let mut streamer = ToolArgsStreamer::new();
+        let mut block = String::new();
+        let mut next_index = 0;
+        let mut events = Vec::new();
+        for chunk in chunks {
+            block.push_str(chunk);
+            streamer.advance(&block, &mut next_index, &mut events);
+        }
+        (events, streamer)
+    }
+
+    fn args_of(events: &[CanonicalStreamEvent], wanted: usize) -> String {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                CanonicalStreamEvent::ToolCallArgsDelta { index, delta } if *index == wanted => {
+                    Some(delta.as_str())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_streams_cdata_arguments_across_chunks() {
+        let (events, streamer) = stream_chunks(&[
+            "<T/>\n<function_calls><function_call><id>call_1</id><to",
+            "ol>search</tool><args_json><![CDATA[{\"q\":",
+            "\"a]b\"]",
+            "]]]></args_json></function_call><function_call><tool>fetch</tool>",
+            "<args_json>\n<![CDATA[ {\"url\":\"x\"}]]></args_json></function_call></function_calls>",
+        ]);
+        assert_eq!(streamer.started(), 2);
+        assert!(matches!(
+            &events[0],
+            CanonicalStreamEvent::ToolCallStart { index: 0, id, name }
+                if id == "call_1" && name == "search"
+        ));
+        assert_eq!(args_of(&events, 0), "{\"q\":\"a]b\"]]");
+        assert_eq!(args_of(&events, 1), "{\"url\":\"x\"}");
+        let ends = events
+            .iter()
+            .filter(|event| matches!(event, CanonicalStreamEvent::ToolCallEnd { .. }))
+            .count();
+        assert_eq!(ends, 2);
+    }
+
+    #[test]
+    fn test_stops_at_calls_without_cdata_json() {
+        let (events, streamer) = stream_chunks(&[
+            "<T/><function_calls><function_call><tool>a</tool>",
+            "<args_json>{\"k\":1}</args_json></function_call>",
+        ]);
+        assert!(events.is_empty());
+        assert_eq!(streamer.started(), 0);
+    }
+
+    #[test]
+    fn test_close_ends_the_streaming_call() {
+        let (mut events, mut streamer) = stream_chunks(&[
+            "<T/><function_calls><function_call><tool>a</tool><args_json><![CDATA[{\"k\"",
+        ]);
+        streamer.close(&mut events);
+        assert!(matches!(
+            events.last(),
+            Some(CanonicalStreamEvent::ToolCallEnd { index: 0, .. })
+        ));
+        streamer.close(&mut events);
+        assert_eq!(events.len(), 3);
+    }
+}
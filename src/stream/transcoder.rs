@@ -1,12 +1,13 @@
 use memchr::{memchr, memchr2, memmem};
 use rustc_hash::FxHashMap;
 use std::borrow::Cow;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 
 use crate::json_scan::{parse_json_string_end, parse_json_value_end, skip_ws};
 use crate::protocol::anthropic::stream::{
     decode_anthropic_stream_event_owned_into, encode_canonical_event_to_anthropic_sse_frame,
-    parse_anthropic_sse_bytes, StatefulAnthropicStreamDecoder,
+    parse_anthropic_sse_bytes, push_anthropic_message_delta_sse_frame,
+    StatefulAnthropicStreamDecoder,
 };
 use crate::protocol::anthropic::AnthropicStreamEvent;
 use crate::protocol::canonical::{
@@ -15,20 +16,29 @@ use crate::protocol::canonical::{
 };
 use crate::protocol::gemini::stream::{
     decode_gemini_stream_chunk_owned_into, encode_canonical_event_to_gemini_sse_with_bindings,
+    push_gemini_text_delta_sse,
 };
 use crate::protocol::gemini::GeminiResponse;
 use crate::protocol::mapping::{
-    anthropic_stop_to_canonical, gemini_stop_to_canonical, openai_stop_to_canonical,
+    anthropic_stop_to_canonical, anthropic_usage_counts_to_canonical,
+    canonical_stop_to_responses_incomplete, gemini_stop_to_canonical,
+    gemini_usage_counts_to_canonical, openai_stop_to_canonical, responses_incomplete_to_canonical,
 };
 use crate::protocol::openai_chat::stream::{
     decode_openai_stream_chunk_into, encode_canonical_event_to_openai_sse_with_created,
+    push_openai_text_delta_sse,
 };
 use crate::protocol::openai_chat::OpenAiStreamChunk;
 use crate::protocol::openai_responses::stream::{
     decode_responses_stream_event_owned_into,
-    encode_canonical_event_to_responses_sse_frame_with_state,
+    encode_canonical_event_to_responses_sse_frame_with_state, push_responses_terminal_sse_frame,
 };
 use crate::protocol::openai_responses::ResponsesStreamEvent;
+use crate::stream::frame_arena::FrameArena;
+use crate::stream::prefill::PrefillEchoStripper;
+use crate::stream::stop_sequences::StopSequenceScanner;
+use crate::stream::string_pool::StringPool;
+use crate::stream::utf8_boundary::Utf8Boundary;
 use crate::stream::SseEvent;
 use crate::util::next_call_id;
 
@@ -54,9 +64,89 @@ pub struct StreamTranscoder {
     decode_buffer: Vec<CanonicalStreamEvent>,
     openai_message_started: bool,
     emit_usage: bool,
+    /// Usage accumulated from upstream `Usage` events, reported in the final
+    /// OpenAI Chat usage chunk, the Anthropic `message_delta`, and the
+    /// Responses `response.completed`.
+    usage: Option<CanonicalUsage>,
+    /// Anthropic stop reason held back until usage is known (see `finish`).
+    anthropic_pending_stop: Option<CanonicalStopReason>,
+    /// Stop reason reported in the Responses terminal event.
+    responses_stop: Option<CanonicalStopReason>,
+    /// Drops an upstream echo of the client's assistant prefill.
+    prefill_stripper: Option<PrefillEchoStripper>,
+    /// Emulates the client's stop sequences on decoded events.
+    stop_scanner: Option<StopSequenceScanner>,
+    /// Delta strings from earlier frames, reused by the fast decoders.
+    strings: StringPool,
+    /// Output block that encoded text delta frames are cut from.
+    frames: FrameArena,
+    /// Characters the upstream cut between frames.
+    utf8: Utf8Boundary,
+}
+
+/// Builder for a [`StreamTranscoder`], started with [`StreamTranscoder::builder`].
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct StreamTranscoderBuilder {
+    upstream_provider: ProviderKind,
+    client_api: IngressApi,
+    model: String,
+    response_id: String,
+    stop_sequences: Option<Arc<[String]>>,
+}
+
+impl StreamTranscoderBuilder {
+    /// Model name reported in client frames.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Response id reported in client frames that carry one.
+    pub fn response_id(mut self, response_id: impl Into<String>) -> Self {
+        self.response_id = response_id.into();
+        self
+    }
+
+    /// Cut the decoded stream at the first of `stops`; an empty list disables it.
+    pub fn stop_sequences<I, S>(mut self, stops: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let stops: Arc<[String]> = stops.into_iter().map(Into::into).collect();
+        self.stop_sequences = (!stops.is_empty()).then_some(stops);
+        self
+    }
+
+    #[must_use]
+    pub fn build(self) -> StreamTranscoder {
+        StreamTranscoder::new(
+            self.upstream_provider,
+            self.client_api,
+            self.model,
+            self.response_id,
+        )
+        .with_stop_sequences(self.stop_sequences)
+    }
 }
 
 impl StreamTranscoder {
+    /// Start building a transcoder from `upstream_provider` frames to
+    /// `client_api` frames.
+    pub fn builder(
+        upstream_provider: ProviderKind,
+        client_api: IngressApi,
+    ) -> StreamTranscoderBuilder {
+        StreamTranscoderBuilder {
+            upstream_provider,
+            client_api,
+            model: String::new(),
+            response_id: String::new(),
+            stop_sequences: None,
+        }
+    }
+
     #[must_use]
     pub fn new(
         upstream_provider: ProviderKind,
@@ -64,10 +154,7 @@ impl StreamTranscoder {
         model: String,
         response_id: String,
     ) -> Self {
-        let openai_created_unix_secs = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        let openai_created_unix_secs = crate::util::unix_now_secs();
         let anthropic_decoder = if upstream_provider == ProviderKind::Anthropic {
             Some(StatefulAnthropicStreamDecoder::new())
         } else {
@@ -132,6 +219,47 @@ impl StreamTranscoder {
             decode_buffer: Vec::with_capacity(8),
             openai_message_started: false,
             emit_usage: emits_usage_event(client_api),
+            usage: None,
+            anthropic_pending_stop: None,
+            responses_stop: None,
+            prefill_stripper: None,
+            stop_scanner: None,
+            strings: StringPool::default(),
+            frames: FrameArena::default(),
+            utf8: Utf8Boundary::default(),
+        }
+    }
+
+    /// Cut the decoded stream at the first of `stops`.
+    #[must_use]
+    pub fn with_stop_sequences(mut self, stops: Option<Arc<[String]>>) -> Self {
+        self.stop_scanner = stops.map(StopSequenceScanner::new);
+        self
+    }
+
+    /// Drop a leading echo of the client's assistant `prefill`.
+    #[must_use]
+    pub fn with_prefill_echo(mut self, prefill: Option<Arc<str>>) -> Self {
+        self.prefill_stripper = prefill.map(PrefillEchoStripper::new);
+        self
+    }
+
+    /// Whether an emulated stop sequence ended the stream; later upstream
+    /// frames decode to nothing.
+    #[must_use]
+    pub fn stop_sequence_hit(&self) -> bool {
+        self.stop_scanner
+            .as_ref()
+            .is_some_and(StopSequenceScanner::stopped)
+    }
+
+    #[inline]
+    fn scan_stop_sequences(&mut self, out: &mut Vec<CanonicalStreamEvent>) {
+        if let Some(stripper) = self.prefill_stripper.as_mut() {
+            stripper.apply(out);
+        }
+        if let Some(scanner) = self.stop_scanner.as_mut() {
+            scanner.apply(out);
         }
     }
 
@@ -151,8 +279,10 @@ impl StreamTranscoder {
         frame: &SseEvent,
         out: &mut Vec<CanonicalStreamEvent>,
     ) {
-        out.clear();
-        self.decode_upstream_event_data_into(frame.event.as_deref(), frame.data.as_bytes(), out);
+        self.strings.reclaim(out);
+        let data = self.utf8.repair(frame.data.as_bytes());
+        self.decode_upstream_event_data_into(frame.event.as_deref(), &data, out);
+        self.scan_stop_sequences(out);
     }
 
     /// Decode one complete raw SSE frame into canonical events.
@@ -165,7 +295,20 @@ impl StreamTranscoder {
         raw_frame: &[u8],
         out: &mut Vec<CanonicalStreamEvent>,
     ) -> bool {
-        out.clear();
+        let decoded = self.try_decode_upstream_raw_frame_unscanned(raw_frame, out);
+        if decoded {
+            self.scan_stop_sequences(out);
+        }
+        decoded
+    }
+
+    fn try_decode_upstream_raw_frame_unscanned(
+        &mut self,
+        raw_frame: &[u8],
+        out: &mut Vec<CanonicalStreamEvent>,
+    ) -> bool {
+        self.strings.reclaim(out);
+        let raw_frame = &*self.utf8.repair(raw_frame);
         if matches!(
             self.upstream_provider,
             ProviderKind::OpenAi | ProviderKind::GeminiOpenAi
@@ -224,6 +367,7 @@ impl StreamTranscoder {
                     data,
                     self.anthropic_decoder.as_mut(),
                     out,
+                    &mut self.strings,
                     emit_usage,
                 ) {
                     return;
@@ -241,7 +385,11 @@ impl StreamTranscoder {
                     out.push(CanonicalStreamEvent::Done);
                     return;
                 }
-                if try_fast_decode_gemini_stream_chunk(data, out, emit_usage) {
+                if try_fast_decode_gemini_stream_chunk(data, out, &mut self.strings, emit_usage) {
+                    return;
+                }
+                if let Some(event) = decode_stream_error_payload(data) {
+                    out.push(event);
                     return;
                 }
                 if let Ok(chunk) = serde_json::from_slice::<GeminiResponse>(data) {
@@ -253,7 +401,13 @@ impl StreamTranscoder {
                     out.push(CanonicalStreamEvent::Done);
                     return;
                 }
-                if try_fast_decode_responses_stream_event(event_type, data, out, emit_usage) {
+                if try_fast_decode_responses_stream_event(
+                    event_type,
+                    data,
+                    out,
+                    &mut self.strings,
+                    emit_usage,
+                ) {
                     return;
                 }
                 if let Ok(event) = serde_json::from_slice::<ResponsesStreamEvent>(data) {
@@ -284,8 +438,13 @@ impl StreamTranscoder {
         data: &[u8],
         out: &mut Vec<CanonicalStreamEvent>,
     ) -> bool {
-        out.clear();
-        self.decode_openai_data_frame_bytes_into(data, out, self.emit_usage)
+        self.strings.reclaim(out);
+        let data = &*self.utf8.repair(data);
+        let decoded = self.decode_openai_data_frame_bytes_into(data, out, self.emit_usage);
+        if decoded {
+            self.scan_stop_sequences(out);
+        }
+        decoded
     }
 
     /// Decode an OpenAI-compatible SSE data payload bytes into canonical events.
@@ -310,6 +469,7 @@ impl StreamTranscoder {
         if try_fast_decode_openai_stream_chunk(
             data,
             out,
+            &mut self.strings,
             &mut self.openai_message_started,
             emit_usage,
         ) {
@@ -325,6 +485,10 @@ impl StreamTranscoder {
             }
             return true;
         }
+        if let Some(event) = decode_stream_error_payload(data) {
+            out.push(event);
+            return true;
+        }
         false
     }
 
@@ -333,82 +497,303 @@ impl StreamTranscoder {
     /// Returns `None` for events that have no representation in the target protocol.
     pub fn encode_client_event(&mut self, event: &CanonicalStreamEvent) -> Option<String> {
         match self.client_api {
-            IngressApi::OpenAiChat => encode_canonical_event_to_openai_sse_with_created(
-                event,
-                &self.model,
-                &self.response_id,
-                self.openai_created_unix_secs,
-            ),
+            IngressApi::OpenAiChat => self.encode_openai_chat_client_event(event),
+            IngressApi::Anthropic => self.encode_anthropic_client_event(event),
+            IngressApi::Gemini => {
+                let bindings = self.gemini_call_name_bindings.as_mut()?;
+                encode_canonical_event_to_gemini_sse_with_bindings(event, bindings)
+            }
+            IngressApi::OpenAiResponses => self.encode_responses_client_event(event),
+        }
+    }
+
+    /// Flush frames held back until the end of the stream.
+    ///
+    /// The OpenAI Chat usage chunk and the Anthropic `message_delta` are
+    /// deferred until the upstream's terminal event so they report the final
+    /// usage; when the upstream ends without one, they are emitted here.
+    pub fn finish(&mut self) -> Option<String> {
+        let mut held_events: Vec<CanonicalStreamEvent> = self
+            .prefill_stripper
+            .as_mut()
+            .and_then(PrefillEchoStripper::finish)
+            .into_iter()
+            .collect();
+        if let Some(scanner) = self.stop_scanner.as_mut() {
+            scanner.apply(&mut held_events);
+            held_events.extend(scanner.finish());
+        }
+        let held = held_events
+            .iter()
+            .filter_map(|event| self.encode_client_event(event))
+            .reduce(|mut held, frame| {
+                held.push_str(&frame);
+                held
+            });
+        let tail = self.finish_client_stream();
+        match (held, tail) {
+            (Some(mut held), Some(tail)) => {
+                held.push_str(&tail);
+                Some(held)
+            }
+            (held, tail) => held.or(tail),
+        }
+    }
+
+    fn finish_client_stream(&mut self) -> Option<String> {
+        match self.client_api {
+            IngressApi::OpenAiChat => self.take_openai_usage_chunk(),
             IngressApi::Anthropic => {
-                if matches!(event, CanonicalStreamEvent::Usage(_)) {
-                    return None;
-                }
-                if let CanonicalStreamEvent::Done = event {
-                    return self.anthropic_done_sse.clone();
-                }
-                let mut frame = String::with_capacity(estimated_anthropic_frame_capacity(
-                    event,
-                    self.model.len(),
-                    self.response_id.len(),
-                ));
-                if encode_canonical_event_to_anthropic_sse_frame(
+                let stop_reason = self.anthropic_pending_stop.take()?;
+                let mut frame = String::with_capacity(160);
+                push_anthropic_message_delta_sse_frame(
+                    &mut frame,
+                    stop_reason,
+                    self.usage.as_ref(),
+                );
+                Some(frame)
+            }
+            IngressApi::Gemini | IngressApi::OpenAiResponses => None,
+        }
+    }
+
+    /// The single OpenAI Chat usage chunk (empty `choices`) for the usage
+    /// accumulated so far; emitted once, right before `[DONE]`.
+    fn take_openai_usage_chunk(&mut self) -> Option<String> {
+        let usage = self.usage.take()?;
+        encode_canonical_event_to_openai_sse_with_created(
+            &CanonicalStreamEvent::Usage(usage),
+            &self.model,
+            &self.response_id,
+            self.openai_created_unix_secs,
+        )
+    }
+
+    fn encode_openai_chat_client_event(&mut self, event: &CanonicalStreamEvent) -> Option<String> {
+        match event {
+            CanonicalStreamEvent::Usage(usage) => {
+                self.record_usage(usage);
+                None
+            }
+            CanonicalStreamEvent::Done => {
+                let done = encode_canonical_event_to_openai_sse_with_created(
                     event,
                     &self.model,
                     &self.response_id,
-                    &mut frame,
-                ) {
-                    Some(frame)
-                } else {
-                    None
+                    self.openai_created_unix_secs,
+                )?;
+                match self.take_openai_usage_chunk() {
+                    Some(mut frame) => {
+                        frame.push_str(&done);
+                        Some(frame)
+                    }
+                    None => Some(done),
                 }
             }
-            IngressApi::Gemini => {
-                let bindings = self.gemini_call_name_bindings.as_mut()?;
-                encode_canonical_event_to_gemini_sse_with_bindings(event, bindings)
+            _ => encode_canonical_event_to_openai_sse_with_created(
+                event,
+                &self.model,
+                &self.response_id,
+                self.openai_created_unix_secs,
+            ),
+        }
+    }
+
+    fn record_usage(&mut self, usage: &CanonicalUsage) {
+        let total = self.usage.get_or_insert_with(CanonicalUsage::default);
+        if usage.input_tokens.is_some() {
+            total.input_tokens = usage.input_tokens;
+        }
+        if usage.output_tokens.is_some() {
+            total.output_tokens = usage.output_tokens;
+        }
+        if usage.total_tokens.is_some() {
+            total.total_tokens = usage.total_tokens;
+        } else if let (Some(input), Some(output)) = (total.input_tokens, total.output_tokens) {
+            // Partial updates (Anthropic `message_delta`) carry no total.
+            total.total_tokens = input.checked_add(output);
+        }
+        if usage.cached_input_tokens.is_some() {
+            total.cached_input_tokens = usage.cached_input_tokens;
+        }
+        if usage.cache_creation_input_tokens.is_some() {
+            total.cache_creation_input_tokens = usage.cache_creation_input_tokens;
+        }
+        if usage.reasoning_tokens.is_some() {
+            total.reasoning_tokens = usage.reasoning_tokens;
+        }
+    }
+
+    fn encode_anthropic_client_event(&mut self, event: &CanonicalStreamEvent) -> Option<String> {
+        match event {
+            CanonicalStreamEvent::Usage(usage) => {
+                self.record_usage(usage);
+                return None;
             }
-            IngressApi::OpenAiResponses => {
-                if matches!(
-                    event,
-                    CanonicalStreamEvent::Usage(_)
-                        | CanonicalStreamEvent::MessageEnd { .. }
-                        | CanonicalStreamEvent::ReasoningDelta(_)
-                ) {
-                    return None;
-                }
-                if let CanonicalStreamEvent::Done = event {
+            CanonicalStreamEvent::MessageEnd { stop_reason } => {
+                self.anthropic_pending_stop = Some(*stop_reason);
+                return None;
+            }
+            _ => {}
+        }
+        let pending = self.finish_client_stream();
+        let encoded = if let CanonicalStreamEvent::Done = event {
+            self.anthropic_done_sse.clone()
+        } else {
+            let mut frame = String::with_capacity(estimated_anthropic_frame_capacity(
+                event,
+                self.model.len(),
+                self.response_id.len(),
+            ));
+            encode_canonical_event_to_anthropic_sse_frame(
+                event,
+                &self.model,
+                &self.response_id,
+                &mut frame,
+            )
+            .then_some(frame)
+        };
+        match (pending, encoded) {
+            (Some(mut pending), Some(encoded)) => {
+                pending.push_str(&encoded);
+                Some(pending)
+            }
+            (pending, encoded) => pending.or(encoded),
+        }
+    }
+
+    fn encode_responses_client_event(&mut self, event: &CanonicalStreamEvent) -> Option<String> {
+        match event {
+            CanonicalStreamEvent::Usage(usage) => {
+                self.record_usage(usage);
+                None
+            }
+            CanonicalStreamEvent::MessageEnd { stop_reason } => {
+                self.responses_stop = Some(*stop_reason);
+                None
+            }
+            CanonicalStreamEvent::ReasoningDelta(_) => None,
+            CanonicalStreamEvent::Done => {
+                let stop_reason = self
+                    .responses_stop
+                    .take()
+                    .unwrap_or(CanonicalStopReason::EndOfTurn);
+                if self.usage.is_none()
+                    && canonical_stop_to_responses_incomplete(stop_reason).is_none()
+                {
                     return self.responses_done_sse.clone();
                 }
+                let mut frame = String::with_capacity(
+                    estimated_responses_frame_capacity(
+                        event,
+                        self.model.len(),
+                        self.response_id.len(),
+                    ) + 96,
+                );
+                push_responses_terminal_sse_frame(
+                    &mut frame,
+                    &self.model,
+                    &self.response_id,
+                    stop_reason,
+                    self.usage.as_ref(),
+                );
+                Some(frame)
+            }
+            _ => {
                 let seq = self.responses_tool_result_seq.as_mut()?;
                 let mut frame = String::with_capacity(estimated_responses_frame_capacity(
                     event,
                     self.model.len(),
                     self.response_id.len(),
                 ));
-                if encode_canonical_event_to_responses_sse_frame_with_state(
+                encode_canonical_event_to_responses_sse_frame_with_state(
                     event,
                     &self.model,
                     &self.response_id,
                     seq,
                     &mut frame,
-                ) {
-                    Some(frame)
-                } else {
-                    None
-                }
+                )
+                .then_some(frame)
             }
         }
     }
 
     /// Encode a canonical stream event into the client's SSE format and return
     /// bytes ready for HTTP body streaming.
+    ///
+    /// Text deltas, the bulk of a stream, are cut from the transcoder's
+    /// [`FrameArena`] instead of allocating a frame each.
     #[inline]
     pub fn encode_client_event_bytes(
         &mut self,
         event: &CanonicalStreamEvent,
     ) -> Option<bytes::Bytes> {
+        if let CanonicalStreamEvent::TextDelta(text) = event {
+            let Self {
+                client_api,
+                model,
+                response_id,
+                openai_created_unix_secs,
+                responses_tool_result_seq,
+                anthropic_pending_stop,
+                frames,
+                ..
+            } = self;
+            match client_api {
+                IngressApi::OpenAiChat => {
+                    return frames.encode(|out| {
+                        push_openai_text_delta_sse(
+                            out,
+                            text,
+                            model,
+                            response_id,
+                            *openai_created_unix_secs,
+                        );
+                        true
+                    });
+                }
+                // A held `message_delta` goes out ahead of the text.
+                IngressApi::Anthropic if anthropic_pending_stop.is_none() => {
+                    return frames.encode(|out| {
+                        encode_canonical_event_to_anthropic_sse_frame(
+                            event,
+                            model,
+                            response_id,
+                            out,
+                        )
+                    });
+                }
+                IngressApi::Gemini => {
+                    return frames.encode(|out| {
+                        push_gemini_text_delta_sse(out, text);
+                        true
+                    });
+                }
+                IngressApi::OpenAiResponses => {
+                    if let Some(seq) = responses_tool_result_seq.as_mut() {
+                        return frames.encode(|out| {
+                            encode_canonical_event_to_responses_sse_frame_with_state(
+                                event,
+                                model,
+                                response_id,
+                                seq,
+                                out,
+                            )
+                        });
+                    }
+                }
+                IngressApi::Anthropic => {}
+            }
+        }
         self.encode_client_event(event).map(bytes::Bytes::from)
     }
 
+    /// Keep the delta string of an event the caller took out of a decode
+    /// buffer for the next frame's decoding.
+    pub(crate) fn recycle_event(&mut self, event: CanonicalStreamEvent) {
+        self.strings.put_event(event);
+    }
+
     /// Returns true when upstream and client use the same protocol
     /// and raw SSE bytes can be forwarded without decode/re-encode.
     #[must_use]
@@ -451,42 +836,14 @@ impl StreamTranscoder {
         match self.client_api {
             IngressApi::OpenAiChat => {
                 for event in decode_buffer.iter() {
-                    if let Some(encoded) = encode_canonical_event_to_openai_sse_with_created(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        self.openai_created_unix_secs,
-                    ) {
+                    if let Some(encoded) = self.encode_openai_chat_client_event(event) {
                         out.push(encoded);
                     }
                 }
             }
             IngressApi::Anthropic => {
-                let model_len = self.model.len();
-                let response_id_len = self.response_id.len();
-                let done_sse = self.anthropic_done_sse.as_deref();
                 for event in decode_buffer.iter() {
-                    if matches!(event, CanonicalStreamEvent::Usage(_)) {
-                        continue;
-                    }
-                    if let CanonicalStreamEvent::Done = event {
-                        if let Some(done) = done_sse {
-                            out.push(done.to_owned());
-                        }
-                        continue;
-                    }
-
-                    let mut frame = String::with_capacity(estimated_anthropic_frame_capacity(
-                        event,
-                        model_len,
-                        response_id_len,
-                    ));
-                    if encode_canonical_event_to_anthropic_sse_frame(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        &mut frame,
-                    ) {
+                    if let Some(frame) = self.encode_anthropic_client_event(event) {
                         out.push(frame);
                     }
                 }
@@ -504,40 +861,8 @@ impl StreamTranscoder {
                 }
             }
             IngressApi::OpenAiResponses => {
-                let model_len = self.model.len();
-                let response_id_len = self.response_id.len();
-                let done_sse = self.responses_done_sse.as_deref();
-                let Some(seq) = self.responses_tool_result_seq.as_mut() else {
-                    return;
-                };
                 for event in decode_buffer.iter() {
-                    if matches!(
-                        event,
-                        CanonicalStreamEvent::Usage(_)
-                            | CanonicalStreamEvent::MessageEnd { .. }
-                            | CanonicalStreamEvent::ReasoningDelta(_)
-                    ) {
-                        continue;
-                    }
-                    if let CanonicalStreamEvent::Done = event {
-                        if let Some(done) = done_sse {
-                            out.push(done.to_owned());
-                        }
-                        continue;
-                    }
-
-                    let mut frame = String::with_capacity(estimated_responses_frame_capacity(
-                        event,
-                        model_len,
-                        response_id_len,
-                    ));
-                    if encode_canonical_event_to_responses_sse_frame_with_state(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        seq,
-                        &mut frame,
-                    ) {
+                    if let Some(frame) = self.encode_responses_client_event(event) {
                         out.push(frame);
                     }
                 }
@@ -558,99 +883,9 @@ impl StreamTranscoder {
         if decode_buffer.len() > out.capacity() {
             out.reserve(decode_buffer.len() - out.capacity());
         }
-        match self.client_api {
-            IngressApi::OpenAiChat => {
-                for event in decode_buffer.iter() {
-                    if let Some(encoded) = encode_canonical_event_to_openai_sse_with_created(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        self.openai_created_unix_secs,
-                    ) {
-                        out.push(bytes::Bytes::from(encoded));
-                    }
-                }
-            }
-            IngressApi::Anthropic => {
-                let model_len = self.model.len();
-                let response_id_len = self.response_id.len();
-                let done_sse = self.anthropic_done_sse.as_deref();
-                for event in decode_buffer.iter() {
-                    if matches!(event, CanonicalStreamEvent::Usage(_)) {
-                        continue;
-                    }
-                    if let CanonicalStreamEvent::Done = event {
-                        if let Some(done) = done_sse {
-                            out.push(bytes::Bytes::copy_from_slice(done.as_bytes()));
-                        }
-                        continue;
-                    }
-
-                    let mut frame = String::with_capacity(estimated_anthropic_frame_capacity(
-                        event,
-                        model_len,
-                        response_id_len,
-                    ));
-                    if encode_canonical_event_to_anthropic_sse_frame(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        &mut frame,
-                    ) {
-                        out.push(bytes::Bytes::from(frame));
-                    }
-                }
-            }
-            IngressApi::Gemini => {
-                let Some(bindings) = self.gemini_call_name_bindings.as_mut() else {
-                    return;
-                };
-                for event in decode_buffer.iter() {
-                    if let Some(encoded) =
-                        encode_canonical_event_to_gemini_sse_with_bindings(event, bindings)
-                    {
-                        out.push(bytes::Bytes::from(encoded));
-                    }
-                }
-            }
-            IngressApi::OpenAiResponses => {
-                let model_len = self.model.len();
-                let response_id_len = self.response_id.len();
-                let done_sse = self.responses_done_sse.as_deref();
-                let Some(seq) = self.responses_tool_result_seq.as_mut() else {
-                    return;
-                };
-                for event in decode_buffer.iter() {
-                    if matches!(
-                        event,
-                        CanonicalStreamEvent::Usage(_)
-                            | CanonicalStreamEvent::MessageEnd { .. }
-                            | CanonicalStreamEvent::ReasoningDelta(_)
-                    ) {
-                        continue;
-                    }
-                    if let CanonicalStreamEvent::Done = event {
-                        if let Some(done) = done_sse {
-                            out.push(bytes::Bytes::copy_from_slice(done.as_bytes()));
-                        }
-                        continue;
-                    }
-
-                    let mut frame = String::with_capacity(estimated_responses_frame_capacity(
-                        event,
-                        model_len,
-                        response_id_len,
-                    ));
-                    if encode_canonical_event_to_responses_sse_frame_with_state(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        seq,
-                        &mut frame,
-                    ) {
-                        out.push(bytes::Bytes::from(frame));
-                    }
-                }
+        for event in decode_buffer.iter() {
+            if let Some(encoded) = self.encode_client_event_bytes(event) {
+                out.push(encoded);
             }
         }
     }
@@ -681,42 +916,14 @@ impl StreamTranscoder {
         match self.client_api {
             IngressApi::OpenAiChat => {
                 for event in decode_buffer.iter() {
-                    if let Some(encoded) = encode_canonical_event_to_openai_sse_with_created(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        self.openai_created_unix_secs,
-                    ) {
+                    if let Some(encoded) = self.encode_openai_chat_client_event(event) {
                         out.push(encoded);
                     }
                 }
             }
             IngressApi::Anthropic => {
-                let model_len = self.model.len();
-                let response_id_len = self.response_id.len();
-                let done_sse = self.anthropic_done_sse.as_deref();
                 for event in decode_buffer.iter() {
-                    if matches!(event, CanonicalStreamEvent::Usage(_)) {
-                        continue;
-                    }
-                    if let CanonicalStreamEvent::Done = event {
-                        if let Some(done) = done_sse {
-                            out.push(done.to_owned());
-                        }
-                        continue;
-                    }
-
-                    let mut frame = String::with_capacity(estimated_anthropic_frame_capacity(
-                        event,
-                        model_len,
-                        response_id_len,
-                    ));
-                    if encode_canonical_event_to_anthropic_sse_frame(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        &mut frame,
-                    ) {
+                    if let Some(frame) = self.encode_anthropic_client_event(event) {
                         out.push(frame);
                     }
                 }
@@ -734,40 +941,8 @@ impl StreamTranscoder {
                 }
             }
             IngressApi::OpenAiResponses => {
-                let model_len = self.model.len();
-                let response_id_len = self.response_id.len();
-                let done_sse = self.responses_done_sse.as_deref();
-                let Some(seq) = self.responses_tool_result_seq.as_mut() else {
-                    return true;
-                };
                 for event in decode_buffer.iter() {
-                    if matches!(
-                        event,
-                        CanonicalStreamEvent::Usage(_)
-                            | CanonicalStreamEvent::MessageEnd { .. }
-                            | CanonicalStreamEvent::ReasoningDelta(_)
-                    ) {
-                        continue;
-                    }
-                    if let CanonicalStreamEvent::Done = event {
-                        if let Some(done) = done_sse {
-                            out.push(done.to_owned());
-                        }
-                        continue;
-                    }
-
-                    let mut frame = String::with_capacity(estimated_responses_frame_capacity(
-                        event,
-                        model_len,
-                        response_id_len,
-                    ));
-                    if encode_canonical_event_to_responses_sse_frame_with_state(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        seq,
-                        &mut frame,
-                    ) {
+                    if let Some(frame) = self.encode_responses_client_event(event) {
                         out.push(frame);
                     }
                 }
@@ -798,99 +973,9 @@ impl StreamTranscoder {
         if decode_buffer.len() > out.capacity() {
             out.reserve(decode_buffer.len() - out.capacity());
         }
-        match self.client_api {
-            IngressApi::OpenAiChat => {
-                for event in decode_buffer.iter() {
-                    if let Some(encoded) = encode_canonical_event_to_openai_sse_with_created(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        self.openai_created_unix_secs,
-                    ) {
-                        out.push(bytes::Bytes::from(encoded));
-                    }
-                }
-            }
-            IngressApi::Anthropic => {
-                let model_len = self.model.len();
-                let response_id_len = self.response_id.len();
-                let done_sse = self.anthropic_done_sse.as_deref();
-                for event in decode_buffer.iter() {
-                    if matches!(event, CanonicalStreamEvent::Usage(_)) {
-                        continue;
-                    }
-                    if let CanonicalStreamEvent::Done = event {
-                        if let Some(done) = done_sse {
-                            out.push(bytes::Bytes::copy_from_slice(done.as_bytes()));
-                        }
-                        continue;
-                    }
-
-                    let mut frame = String::with_capacity(estimated_anthropic_frame_capacity(
-                        event,
-                        model_len,
-                        response_id_len,
-                    ));
-                    if encode_canonical_event_to_anthropic_sse_frame(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        &mut frame,
-                    ) {
-                        out.push(bytes::Bytes::from(frame));
-                    }
-                }
-            }
-            IngressApi::Gemini => {
-                let Some(bindings) = self.gemini_call_name_bindings.as_mut() else {
-                    return true;
-                };
-                for event in decode_buffer.iter() {
-                    if let Some(encoded) =
-                        encode_canonical_event_to_gemini_sse_with_bindings(event, bindings)
-                    {
-                        out.push(bytes::Bytes::from(encoded));
-                    }
-                }
-            }
-            IngressApi::OpenAiResponses => {
-                let model_len = self.model.len();
-                let response_id_len = self.response_id.len();
-                let done_sse = self.responses_done_sse.as_deref();
-                let Some(seq) = self.responses_tool_result_seq.as_mut() else {
-                    return true;
-                };
-                for event in decode_buffer.iter() {
-                    if matches!(
-                        event,
-                        CanonicalStreamEvent::Usage(_)
-                            | CanonicalStreamEvent::MessageEnd { .. }
-                            | CanonicalStreamEvent::ReasoningDelta(_)
-                    ) {
-                        continue;
-                    }
-                    if let CanonicalStreamEvent::Done = event {
-                        if let Some(done) = done_sse {
-                            out.push(bytes::Bytes::copy_from_slice(done.as_bytes()));
-                        }
-                        continue;
-                    }
-
-                    let mut frame = String::with_capacity(estimated_responses_frame_capacity(
-                        event,
-                        model_len,
-                        response_id_len,
-                    ));
-                    if encode_canonical_event_to_responses_sse_frame_with_state(
-                        event,
-                        &self.model,
-                        &self.response_id,
-                        seq,
-                        &mut frame,
-                    ) {
-                        out.push(bytes::Bytes::from(frame));
-                    }
-                }
+        for event in decode_buffer.iter() {
+            if let Some(encoded) = self.encode_client_event_bytes(event) {
+                out.push(encoded);
             }
         }
         true
@@ -905,9 +990,18 @@ impl StreamTranscoder {
     }
 }
 
+/// Every client protocol surfaces usage: OpenAI Chat as one final chunk,
+/// Gemini as `usageMetadata` chunks, Anthropic in `message_delta`, and
+/// Responses in `response.completed`.
 #[inline]
 const fn emits_usage_event(client_api: IngressApi) -> bool {
-    matches!(client_api, IngressApi::OpenAiChat | IngressApi::Gemini)
+    matches!(
+        client_api,
+        IngressApi::OpenAiChat
+            | IngressApi::Gemini
+            | IngressApi::Anthropic
+            | IngressApi::OpenAiResponses
+    )
 }
 
 #[inline]
@@ -1030,6 +1124,7 @@ fn estimated_responses_frame_capacity(
 fn try_fast_decode_openai_stream_chunk(
     bytes: &[u8],
     out: &mut Vec<CanonicalStreamEvent>,
+    strings: &mut StringPool,
     message_started: &mut bool,
     emit_usage: bool,
 ) -> bool {
@@ -1065,12 +1160,12 @@ fn try_fast_decode_openai_stream_chunk(
         };
         handled = true;
         if !content.is_empty() {
-            out.push(CanonicalStreamEvent::TextDelta(content.into_owned()));
+            out.push(CanonicalStreamEvent::TextDelta(strings.own(content)));
             produced = true;
         }
     } else if let Some(tool_calls_key_pos) = key_positions.tool_calls {
         let Some(tool_calls_produced) =
-            try_fast_decode_openai_tool_calls_chunk_at(bytes, tool_calls_key_pos, out)
+            try_fast_decode_openai_tool_calls_chunk_at(bytes, tool_calls_key_pos, out, strings)
         else {
             return false;
         };
@@ -1199,6 +1294,7 @@ fn try_fast_decode_anthropic_stream_event(
     data: &[u8],
     mut decoder: Option<&mut StatefulAnthropicStreamDecoder>,
     out: &mut Vec<CanonicalStreamEvent>,
+    strings: &mut StringPool,
     emit_usage: bool,
 ) -> bool {
     let bytes = data;
@@ -1211,14 +1307,14 @@ fn try_fast_decode_anthropic_stream_event(
                 let input_tokens = parse_u64_after_key(bytes, br#""input_tokens":"#);
                 let output_tokens = parse_u64_after_key(bytes, br#""output_tokens":"#);
                 if input_tokens.unwrap_or(0) > 0 || output_tokens.unwrap_or(0) > 0 {
-                    out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-                        input_tokens,
-                        output_tokens,
-                        total_tokens: match (input_tokens, output_tokens) {
-                            (Some(input), Some(output)) => input.checked_add(output),
-                            _ => None,
-                        },
-                    }));
+                    out.push(CanonicalStreamEvent::Usage(
+                        anthropic_usage_counts_to_canonical(
+                            input_tokens,
+                            output_tokens,
+                            parse_u64_after_key(bytes, br#""cache_read_input_tokens":"#),
+                            parse_u64_after_key(bytes, br#""cache_creation_input_tokens":"#),
+                        ),
+                    ));
                 }
             }
             true
@@ -1273,7 +1369,7 @@ fn try_fast_decode_anthropic_stream_event(
                     content_block_range.end,
                 ) {
                     if !text.is_empty() {
-                        out.push(CanonicalStreamEvent::TextDelta(text.into_owned()));
+                        out.push(CanonicalStreamEvent::TextDelta(strings.own(text)));
                     }
                 }
                 return true;
@@ -1287,7 +1383,7 @@ fn try_fast_decode_anthropic_stream_event(
                     content_block_range.end,
                 ) {
                     if !thinking.is_empty() {
-                        out.push(CanonicalStreamEvent::ReasoningDelta(thinking.into_owned()));
+                        out.push(CanonicalStreamEvent::ReasoningDelta(strings.own(thinking)));
                     }
                 }
                 return true;
@@ -1300,14 +1396,14 @@ fn try_fast_decode_anthropic_stream_event(
         }
         "content_block_delta" => {
             if ANTHROPIC_TEXT_DELTA_FINDER.find(bytes).is_some() {
-                if let Some(text) = parse_string_after_key(bytes, br#""text":"#) {
-                    out.push(CanonicalStreamEvent::TextDelta(text));
+                if let Some(text) = parse_string_after_key_cow(bytes, br#""text":"#) {
+                    out.push(CanonicalStreamEvent::TextDelta(strings.own(text)));
                     return true;
                 }
             }
             if ANTHROPIC_THINKING_DELTA_FINDER.find(bytes).is_some() {
-                if let Some(thinking) = parse_string_after_key(bytes, br#""thinking":"#) {
-                    out.push(CanonicalStreamEvent::ReasoningDelta(thinking));
+                if let Some(thinking) = parse_string_after_key_cow(bytes, br#""thinking":"#) {
+                    out.push(CanonicalStreamEvent::ReasoningDelta(strings.own(thinking)));
                     return true;
                 }
             }
@@ -1318,9 +1414,10 @@ fn try_fast_decode_anthropic_stream_event(
                 let Ok(index) = usize::try_from(index_u64) else {
                     return false;
                 };
-                let Some(delta) = parse_string_after_key(bytes, br#""partial_json":"#) else {
+                let Some(delta) = parse_string_after_key_cow(bytes, br#""partial_json":"#) else {
                     return false;
                 };
+                let delta = strings.own(delta);
                 out.push(CanonicalStreamEvent::ToolCallArgsDelta { index, delta });
                 return true;
             }
@@ -1347,14 +1444,14 @@ fn try_fast_decode_anthropic_stream_event(
                 let input_tokens = parse_u64_after_key(bytes, br#""input_tokens":"#);
                 let output_tokens = parse_u64_after_key(bytes, br#""output_tokens":"#);
                 if input_tokens.is_some() || output_tokens.is_some() {
-                    out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-                        input_tokens,
-                        output_tokens,
-                        total_tokens: match (input_tokens, output_tokens) {
-                            (Some(input), Some(output)) => input.checked_add(output),
-                            _ => None,
-                        },
-                    }));
+                    out.push(CanonicalStreamEvent::Usage(
+                        anthropic_usage_counts_to_canonical(
+                            input_tokens,
+                            output_tokens,
+                            parse_u64_after_key(bytes, br#""cache_read_input_tokens":"#),
+                            parse_u64_after_key(bytes, br#""cache_creation_input_tokens":"#),
+                        ),
+                    ));
                     produced = true;
                 }
             }
@@ -1391,6 +1488,7 @@ fn try_fast_decode_responses_stream_event(
     event_type_hint: Option<&str>,
     data: &[u8],
     out: &mut Vec<CanonicalStreamEvent>,
+    strings: &mut StringPool,
     emit_usage: bool,
 ) -> bool {
     let bytes = data;
@@ -1415,14 +1513,24 @@ fn try_fast_decode_responses_stream_event(
         | ResponsesEventType::ContentPartAdded
         | ResponsesEventType::ContentPartDone
         | ResponsesEventType::OutputTextDone
-        | ResponsesEventType::FunctionCallArgumentsDone => {
+        | ResponsesEventType::FunctionCallArgumentsDone
+        | ResponsesEventType::ReasoningPartBoundary => {
             // Known non-canonical events: short-circuit without serde fallback.
             true
         }
+        ResponsesEventType::ReasoningDelta => {
+            if let Some(delta) = parse_string_after_key_cow(bytes, br#""delta":"#) {
+                if !delta.is_empty() {
+                    out.push(CanonicalStreamEvent::ReasoningDelta(strings.own(delta)));
+                }
+                return true;
+            }
+            false
+        }
         ResponsesEventType::OutputTextDelta => {
             if let Some(delta) = parse_string_after_key_cow(bytes, br#""delta":"#) {
                 if !delta.is_empty() {
-                    out.push(CanonicalStreamEvent::TextDelta(delta.into_owned()));
+                    out.push(CanonicalStreamEvent::TextDelta(strings.own(delta)));
                     return true;
                 }
             }
@@ -1435,15 +1543,18 @@ fn try_fast_decode_responses_stream_event(
             let Ok(index) = usize::try_from(index) else {
                 return false;
             };
-            let Some(delta) = parse_string_after_key(bytes, br#""delta":"#) else {
+            let Some(delta) = parse_string_after_key_cow(bytes, br#""delta":"#) else {
                 return false;
             };
+            let delta = strings.own(delta);
             out.push(CanonicalStreamEvent::ToolCallArgsDelta { index, delta });
             true
         }
         ResponsesEventType::OutputItemAdded => decode_responses_output_item_added(bytes, out),
         ResponsesEventType::OutputItemDone => decode_responses_output_item_done(bytes, out),
-        ResponsesEventType::Completed => decode_responses_completed(bytes, out, emit_usage),
+        ResponsesEventType::Completed | ResponsesEventType::Incomplete => {
+            decode_responses_completed(bytes, out, emit_usage)
+        }
         ResponsesEventType::Error => {
             let Some(message) = parse_string_after_key(bytes, br#""message":"#) else {
                 return false;
@@ -1587,9 +1698,8 @@ fn decode_responses_completed(
     static RESP_TYPE_FUNCTION_CALL_ANY_FINDER: LazyLock<memmem::Finder<'static>> =
         LazyLock::new(|| memmem::Finder::new(br#""type":"function_call"#));
 
-    let (input_tokens, output_tokens, total_tokens) = if emit_usage {
-        let usage_range = parse_json_object_value_range_after_key(bytes, br#""usage""#);
-        if let Some(range) = usage_range {
+    if emit_usage {
+        if let Some(range) = parse_json_object_value_range_after_key(bytes, br#""usage""#) {
             let input_tokens =
                 parse_u64_after_key_in(bytes, br#""input_tokens":"#, range.start, range.end);
             let output_tokens =
@@ -1600,22 +1710,37 @@ fn decode_responses_completed(
                         (Some(input), Some(output)) => input.checked_add(output),
                         _ => None,
                     });
-            (input_tokens, output_tokens, total_tokens)
-        } else {
-            (None, None, None)
+            if input_tokens.is_some() || output_tokens.is_some() || total_tokens.is_some() {
+                out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
+                    input_tokens,
+                    output_tokens,
+                    total_tokens,
+                    cached_input_tokens: parse_u64_after_key_in(
+                        bytes,
+                        br#""cached_tokens":"#,
+                        range.start,
+                        range.end,
+                    ),
+                    cache_creation_input_tokens: None,
+                    reasoning_tokens: parse_u64_after_key_in(
+                        bytes,
+                        br#""reasoning_tokens":"#,
+                        range.start,
+                        range.end,
+                    ),
+                }));
+            }
         }
-    } else {
-        (None, None, None)
-    };
-    if emit_usage && (input_tokens.is_some() || output_tokens.is_some() || total_tokens.is_some()) {
-        out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-            input_tokens,
-            output_tokens,
-            total_tokens,
-        }));
     }
 
-    let stop_reason = if RESP_TYPE_FUNCTION_CALL_ANY_FINDER.find(bytes).is_some() {
+    let stop_reason = if let Some(range) =
+        parse_json_object_value_range_after_key(bytes, br#""incomplete_details""#)
+    {
+        parse_string_after_key(&bytes[range], br#""reason":"#)
+            .map_or(CanonicalStopReason::MaxTokens, |reason| {
+                responses_incomplete_to_canonical(&reason)
+            })
+    } else if RESP_TYPE_FUNCTION_CALL_ANY_FINDER.find(bytes).is_some() {
         CanonicalStopReason::ToolCalls
     } else {
         CanonicalStopReason::EndOfTurn
@@ -1635,9 +1760,12 @@ enum ResponsesEventType {
     FunctionCallArgumentsDone,
     OutputTextDelta,
     FunctionCallArgumentsDelta,
+    ReasoningDelta,
+    ReasoningPartBoundary,
     OutputItemAdded,
     OutputItemDone,
     Completed,
+    Incomplete,
     Error,
     Unknown,
 }
@@ -1653,9 +1781,17 @@ fn parse_responses_event_type_from_str(event_type: &str) -> ResponsesEventType {
         "response.function_call_arguments.done" => ResponsesEventType::FunctionCallArgumentsDone,
         "response.output_text.delta" => ResponsesEventType::OutputTextDelta,
         "response.function_call_arguments.delta" => ResponsesEventType::FunctionCallArgumentsDelta,
+        "response.reasoning_summary_text.delta" | "response.reasoning_text.delta" => {
+            ResponsesEventType::ReasoningDelta
+        }
+        "response.reasoning_summary_part.added"
+        | "response.reasoning_summary_part.done"
+        | "response.reasoning_summary_text.done"
+        | "response.reasoning_text.done" => ResponsesEventType::ReasoningPartBoundary,
         "response.output_item.added" => ResponsesEventType::OutputItemAdded,
         "response.output_item.done" => ResponsesEventType::OutputItemDone,
         "response.completed" => ResponsesEventType::Completed,
+        "response.incomplete" => ResponsesEventType::Incomplete,
         "error" => ResponsesEventType::Error,
         _ => ResponsesEventType::Unknown,
     }
@@ -1678,15 +1814,35 @@ fn parse_responses_event_type_from_data(bytes: &[u8]) -> ResponsesEventType {
         b"response.output_item.added" => ResponsesEventType::OutputItemAdded,
         b"response.output_item.done" => ResponsesEventType::OutputItemDone,
         b"response.completed" => ResponsesEventType::Completed,
+        b"response.incomplete" => ResponsesEventType::Incomplete,
         b"error" => ResponsesEventType::Error,
         _ => ResponsesEventType::Unknown,
     }
 }
 
 #[inline]
+/// Decode a `{"error":{...}}` payload, the shape OpenAI-compatible and Gemini
+/// upstreams use for errors raised after a stream has started.
+fn decode_stream_error_payload(data: &[u8]) -> Option<CanonicalStreamEvent> {
+    let payload: serde_json::Value = serde_json::from_slice(data).ok()?;
+    let error = payload.get("error")?;
+    let status = error
+        .get("code")
+        .and_then(serde_json::Value::as_u64)
+        .and_then(|code| u16::try_from(code).ok())
+        .filter(|code| (400..600).contains(code))
+        .unwrap_or(500);
+    let message = match error.get("message").and_then(serde_json::Value::as_str) {
+        Some(message) => message.to_string(),
+        None => error.to_string(),
+    };
+    Some(CanonicalStreamEvent::Error { status, message })
+}
+
 fn try_fast_decode_gemini_stream_chunk(
     data: &[u8],
     out: &mut Vec<CanonicalStreamEvent>,
+    strings: &mut StringPool,
     emit_usage: bool,
 ) -> bool {
     static GEMINI_CANDIDATES_FINDER: LazyLock<memmem::Finder<'static>> =
@@ -1736,9 +1892,9 @@ fn try_fast_decode_gemini_stream_chunk(
         });
         produced = true;
         has_tool_calls = true;
-    } else if let Some(text) = parse_string_after_key(bytes, br#""text":"#) {
+    } else if let Some(text) = parse_string_after_key_cow(bytes, br#""text":"#) {
         if !text.is_empty() {
-            out.push(CanonicalStreamEvent::TextDelta(text));
+            out.push(CanonicalStreamEvent::TextDelta(strings.own(text)));
             produced = true;
         }
     }
@@ -1775,11 +1931,25 @@ fn try_fast_decode_gemini_stream_chunk(
                 usage_range.end,
             );
             if input_tokens.is_some() || output_tokens.is_some() || total_tokens.is_some() {
-                out.push(CanonicalStreamEvent::Usage(CanonicalUsage {
-                    input_tokens,
-                    output_tokens,
-                    total_tokens,
-                }));
+                out.push(CanonicalStreamEvent::Usage(
+                    gemini_usage_counts_to_canonical(
+                        input_tokens,
+                        output_tokens,
+                        total_tokens,
+                        parse_u64_after_key_in(
+                            bytes,
+                            br#""cachedContentTokenCount":"#,
+                            usage_range.start,
+                            usage_range.end,
+                        ),
+                        parse_u64_after_key_in(
+                            bytes,
+                            br#""thoughtsTokenCount":"#,
+                            usage_range.start,
+                            usage_range.end,
+                        ),
+                    ),
+                ));
                 produced = true;
             }
         }
@@ -1829,12 +1999,9 @@ fn parse_openai_stop_reason_from_string_value(
     let value_end = parse_json_string_end(bytes, value_start).ok()?;
     let inner = &bytes[value_start + 1..value_end - 1];
     if memchr(b'\\', inner).is_none() {
-        return Some(match inner {
-            b"tool_calls" => CanonicalStopReason::ToolCalls,
-            b"length" => CanonicalStopReason::MaxTokens,
-            b"content_filter" => CanonicalStopReason::ContentFilter,
-            _ => CanonicalStopReason::EndOfTurn,
-        });
+        return std::str::from_utf8(inner)
+            .ok()
+            .map(openai_stop_to_canonical);
     }
 
     let decoded = serde_json::from_slice::<String>(&bytes[value_start..value_end]).ok()?;
@@ -1854,11 +2021,9 @@ fn parse_gemini_stop_reason_after_key(
     let value_end = parse_json_string_end(bytes, value_start).ok()?;
     let inner = &bytes[value_start + 1..value_end - 1];
     if memchr(b'\\', inner).is_none() {
-        return Some(match inner {
-            b"MAX_TOKENS" => CanonicalStopReason::MaxTokens,
-            b"SAFETY" | b"RECITATION" => CanonicalStopReason::ContentFilter,
-            _ => CanonicalStopReason::EndOfTurn,
-        });
+        return std::str::from_utf8(inner)
+            .ok()
+            .map(gemini_stop_to_canonical);
     }
 
     let decoded = serde_json::from_slice::<String>(&bytes[value_start..value_end]).ok()?;
@@ -1878,12 +2043,9 @@ fn parse_anthropic_stop_reason_after_key(
     let value_end = parse_json_string_end(bytes, value_start).ok()?;
     let inner = &bytes[value_start + 1..value_end - 1];
     if memchr(b'\\', inner).is_none() {
-        return Some(match inner {
-            b"tool_use" => CanonicalStopReason::ToolCalls,
-            b"max_tokens" | b"model_context_window_exceeded" => CanonicalStopReason::MaxTokens,
-            b"refusal" => CanonicalStopReason::ContentFilter,
-            _ => CanonicalStopReason::EndOfTurn,
-        });
+        return std::str::from_utf8(inner)
+            .ok()
+            .map(anthropic_stop_to_canonical);
     }
 
     let decoded = serde_json::from_slice::<String>(&bytes[value_start..value_end]).ok()?;
@@ -1895,6 +2057,7 @@ fn try_fast_decode_openai_tool_calls_chunk_at(
     bytes: &[u8],
     key_pos: usize,
     out: &mut Vec<CanonicalStreamEvent>,
+    strings: &mut StringPool,
 ) -> Option<bool> {
     let colon_pos = skip_ws(bytes, key_pos + br#""tool_calls""#.len());
     if bytes.get(colon_pos) != Some(&b':') {
@@ -1928,7 +2091,7 @@ fn try_fast_decode_openai_tool_calls_chunk_at(
         None
     };
     let arguments =
-        parse_string_after_key_in(bytes, br#""arguments":"#, call_obj_start, call_obj_end);
+        parse_string_after_key_in_cow(bytes, br#""arguments":"#, call_obj_start, call_obj_end);
 
     let mut produced = false;
     if let Some(id) = call_id {
@@ -1941,6 +2104,7 @@ fn try_fast_decode_openai_tool_calls_chunk_at(
     }
     if let Some(delta) = arguments {
         if !delta.is_empty() {
+            let delta = strings.own(delta);
             out.push(CanonicalStreamEvent::ToolCallArgsDelta { index, delta });
             produced = true;
         }
@@ -1978,6 +2142,19 @@ fn parse_openai_usage_after_key_pos(
         input_tokens: Some(input_tokens),
         output_tokens: Some(output_tokens),
         total_tokens: Some(total_tokens),
+        cached_input_tokens: parse_u64_after_key_in(
+            bytes,
+            br#""cached_tokens":"#,
+            usage_range.start,
+            usage_range.end,
+        ),
+        cache_creation_input_tokens: None,
+        reasoning_tokens: parse_u64_after_key_in(
+            bytes,
+            br#""reasoning_tokens":"#,
+            usage_range.start,
+            usage_range.end,
+        ),
     })
 }
 
@@ -2453,7 +2630,32 @@ mod tests {
                 id: None,
                 retry: None,
             }),
-            ProviderKind::OpenAi | ProviderKind::Gemini | ProviderKind::GeminiOpenAi => None,
+            ProviderKind::OpenAi | ProviderKind::GeminiOpenAi => Some(SseEvent {
+                event: None,
+                data: serde_json::json!({
+                    "error": {
+                        "message": "boom",
+                        "type": "server_error",
+                        "code": null
+                    }
+                })
+                .to_string(),
+                id: None,
+                retry: None,
+            }),
+            ProviderKind::Gemini => Some(SseEvent {
+                event: None,
+                data: serde_json::json!({
+                    "error": {
+                        "code": 503,
+                        "message": "boom",
+                        "status": "UNAVAILABLE"
+                    }
+                })
+                .to_string(),
+                id: None,
+                retry: None,
+            }),
         }
     }
 
@@ -2645,9 +2847,14 @@ mod tests {
                 match api {
                     IngressApi::OpenAiChat => {
                         assert!(
-                            chunks.iter().any(|chunk| chunk.contains("\"usage\"")),
-                            "missing openai usage output for provider={provider:?}"
+                            chunks.is_empty(),
+                            "openai target should defer usage to the end for provider={provider:?}"
                         );
+                        let tail = t.transcode_frame(&sample_done_frame(provider)).concat();
+                        let usage = tail.find("\"usage\"").unwrap_or_else(|| {
+                            panic!("missing openai usage output for provider={provider:?}")
+                        });
+                        assert!(usage < tail.find("[DONE]").unwrap_or(usize::MAX));
                     }
                     IngressApi::OpenAiResponses => {
                         assert!(
@@ -2674,6 +2881,210 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_stream_usage_reported_in_responses_completed() {
+        for provider in providers() {
+            let Some(usage_frame) = sample_usage_frame(provider) else {
+                continue;
+            };
+            let mut t = StreamTranscoder::new(
+                provider,
+                IngressApi::OpenAiResponses,
+                "m1".into(),
+                "id-1".into(),
+            );
+            assert!(t.transcode_frame(&usage_frame).is_empty());
+            let chunks = t.transcode_frame(&sample_done_frame(provider));
+            assert!(
+                chunks.iter().any(|chunk| {
+                    chunk.contains("event: response.completed")
+                    && chunk.contains(
+                        "\"usage\":{\"input_tokens\":10,\"output_tokens\":5,\"total_tokens\":15}"
+                    )
+                }),
+                "missing usage in response.completed for provider={provider:?}: {chunks:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_stream_usage_after_stop_reported_in_anthropic_message_delta() {
+        let mut t = StreamTranscoder::new(
+            ProviderKind::OpenAi,
+            IngressApi::Anthropic,
+            "m1".into(),
+            "id-1".into(),
+        );
+        let finish = SseEvent {
+            event: None,
+            data: serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion.chunk",
+                "model": "m1",
+                "choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]
+            })
+            .to_string(),
+            id: None,
+            retry: None,
+        };
+        assert!(t.transcode_frame(&finish).is_empty());
+        let usage_frame = sample_usage_frame(ProviderKind::OpenAi).unwrap();
+        assert!(t.transcode_frame(&usage_frame).is_empty());
+        let chunks = t.transcode_frame(&sample_done_frame(ProviderKind::OpenAi));
+        let joined = chunks.concat();
+        let delta = joined.find("event: message_delta").expect("message_delta");
+        let stop = joined.find("event: message_stop").expect("message_stop");
+        assert!(delta < stop);
+        assert!(joined.contains("\"usage\":{\"input_tokens\":10,\"output_tokens\":5}"));
+        assert!(t.finish().is_none());
+    }
+
+    #[test]
+    fn test_anthropic_cache_tokens_reach_openai_usage_details() {
+        let mut t = StreamTranscoder::new(
+            ProviderKind::Anthropic,
+            IngressApi::OpenAiChat,
+            "m1".into(),
+            "id-1".into(),
+        );
+        let frame = |event: &str, data: serde_json::Value| SseEvent {
+            event: Some(event.into()),
+            data: data.to_string(),
+            id: None,
+            retry: None,
+        };
+        let _ = t.transcode_frame(&frame(
+            "message_start",
+            serde_json::json!({
+                "type": "message_start",
+                "message": {
+                    "id": "msg_1",
+                    "type": "message",
+                    "role": "assistant",
+                    "model": "m1",
+                    "usage": {
+                        "input_tokens": 4,
+                        "output_tokens": 1,
+                        "cache_read_input_tokens": 90,
+                        "cache_creation_input_tokens": 6
+                    }
+                }
+            }),
+        ));
+        let _ = t.transcode_frame(&frame(
+            "message_delta",
+            serde_json::json!({
+                "type": "message_delta",
+                "delta": {"stop_reason": "end_turn", "stop_sequence": null},
+                "usage": {"output_tokens": 12}
+            }),
+        ));
+        let joined = t
+            .transcode_frame(&frame(
+                "message_stop",
+                serde_json::json!({"type": "message_stop"}),
+            ))
+            .concat();
+        assert!(
+            joined.contains(
+                "\"usage\":{\"prompt_tokens\":100,\"completion_tokens\":12,\"total_tokens\":112,\"prompt_tokens_details\":{\"cached_tokens\":90}}"
+            ),
+            "{joined}"
+        );
+    }
+
+    #[test]
+    fn test_anthropic_refusal_maps_to_openai_content_filter() {
+        let mut t = StreamTranscoder::new(
+            ProviderKind::Anthropic,
+            IngressApi::OpenAiChat,
+            "m1".into(),
+            "id-1".into(),
+        );
+        let joined = t
+            .transcode_frame(&SseEvent {
+                event: Some("message_delta".into()),
+                data: serde_json::json!({
+                    "type": "message_delta",
+                    "delta": {"stop_reason": "refusal", "stop_sequence": null},
+                    "usage": {"output_tokens": 1}
+                })
+                .to_string(),
+                id: None,
+                retry: None,
+            })
+            .concat();
+        assert!(
+            joined.contains("\"finish_reason\":\"content_filter\""),
+            "{joined}"
+        );
+    }
+
+    #[test]
+    fn test_gemini_recitation_reported_as_responses_incomplete() {
+        let mut t = StreamTranscoder::new(
+            ProviderKind::Gemini,
+            IngressApi::OpenAiResponses,
+            "m1".into(),
+            "id-1".into(),
+        );
+        let mut joined = t
+            .transcode_frame(&SseEvent {
+                event: None,
+                data: serde_json::json!({
+                    "candidates": [{
+                        "content": {"role": "model", "parts": [{"text": "quoted"}]},
+                        "finishReason": "RECITATION"
+                    }]
+                })
+                .to_string(),
+                id: None,
+                retry: None,
+            })
+            .concat();
+        joined.push_str(
+            &t.transcode_frame(&sample_done_frame(ProviderKind::Gemini))
+                .concat(),
+        );
+        assert!(joined.contains("event: response.incomplete"), "{joined}");
+        assert!(
+            joined.contains("\"incomplete_details\":{\"reason\":\"content_filter\"}"),
+            "{joined}"
+        );
+    }
+
+    #[test]
+    fn test_finish_flushes_anthropic_message_delta_without_done() {
+        let mut t = StreamTranscoder::new(
+            ProviderKind::Gemini,
+            IngressApi::Anthropic,
+            "m1".into(),
+            "id-1".into(),
+        );
+        let frame = SseEvent {
+            event: None,
+            data: serde_json::json!({
+                "candidates": [{
+                    "content": {"role": "model", "parts": [{"text": "hi"}]},
+                    "finishReason": "STOP"
+                }],
+                "usageMetadata": {
+                    "promptTokenCount": 7,
+                    "candidatesTokenCount": 3,
+                    "totalTokenCount": 10
+                }
+            })
+            .to_string(),
+            id: None,
+            retry: None,
+        };
+        let chunks = t.transcode_frame(&frame);
+        assert!(chunks.iter().all(|chunk| !chunk.contains("message_delta")));
+        let flushed = t.finish().expect("pending message_delta");
+        assert!(flushed.starts_with("event: message_delta"));
+        assert!(flushed.contains("\"usage\":{\"input_tokens\":7,\"output_tokens\":3}"));
+    }
+
     #[test]
     fn test_stream_reasoning_transcode_matrix_from_anthropic() {
         let frame = sample_reasoning_frame();
@@ -2710,7 +3121,7 @@ mod tests {
 
     #[test]
     fn test_stream_error_transcode_matrix() {
-        for provider in [ProviderKind::Anthropic, ProviderKind::OpenAiResponses] {
+        for provider in providers() {
             let frame = sample_error_frame(provider).expect("error frame");
             for api in ingress_apis() {
                 let mut t = StreamTranscoder::new(provider, api, "m1".into(), "id-1".into());
@@ -3262,6 +3673,50 @@ mod tests {
         assert!(matches!(&events[0], CanonicalStreamEvent::TextDelta(t) if t == "Bonjour"));
     }
 
+    #[test]
+    fn test_responses_reasoning_summary_reaches_anthropic_as_thinking() {
+        let mut t = StreamTranscoder::new(
+            ProviderKind::OpenAiResponses,
+            IngressApi::Anthropic,
+            "o3".into(),
+            "id-1".into(),
+        );
+        let boundary = SseEvent {
+            event: Some("response.reasoning_summary_part.added".into()),
+            data: serde_json::json!({
+                "type": "response.reasoning_summary_part.added",
+                "output_index": 0,
+                "summary_index": 0,
+                "part": {"type": "summary_text", "text": ""}
+            })
+            .to_string(),
+            id: None,
+            retry: None,
+        };
+        assert!(t.decode_upstream_frame(&boundary).is_empty());
+
+        let frame = SseEvent {
+            event: Some("response.reasoning_summary_text.delta".into()),
+            data: serde_json::json!({
+                "type": "response.reasoning_summary_text.delta",
+                "output_index": 0,
+                "summary_index": 0,
+                "delta": "Weighing options"
+            })
+            .to_string(),
+            id: None,
+            retry: None,
+        };
+        let chunks = t.transcode_frame(&frame);
+        assert!(
+            chunks
+                .iter()
+                .any(|chunk| chunk.contains("\"thinking_delta\"")
+                    && chunk.contains("Weighing options")),
+            "missing anthropic thinking delta: {chunks:?}"
+        );
+    }
+
     #[test]
     fn test_decode_responses_noncanonical_events_fast_skip() {
         let mut t = StreamTranscoder::new(
@@ -3413,4 +3868,44 @@ mod tests {
         let events = t.decode_upstream_frame(&frame);
         assert!(events.is_empty());
     }
+
+    #[test]
+    fn test_character_cut_between_raw_frames_decodes_whole() {
+        let emoji = "\u{1f30d}".as_bytes();
+        // The same cut as raw bytes and as the halves of an escaped pair.
+        let cuts: [(&[u8], &[u8]); 2] = [(&emoji[..2], &emoji[2..]), (br"\ud83c", br"\udf0d")];
+        for provider in providers() {
+            for (head, tail) in cuts {
+                let mut transcoder = StreamTranscoder::new(
+                    provider,
+                    IngressApi::OpenAiChat,
+                    "m1".into(),
+                    "id-1".into(),
+                );
+                let sample = sample_text_delta_frame(provider);
+                let mut text = String::new();
+                for piece in [[b"ma", head].concat(), [tail, b"x"].concat()] {
+                    let data = sample.data.replace("matrix", "\0");
+                    let (before, after) = data.split_once('\0').expect("text slot");
+                    let mut raw = Vec::new();
+                    if let Some(event) = &sample.event {
+                        raw.extend_from_slice(format!("event: {event}\n").as_bytes());
+                    }
+                    raw.extend_from_slice(b"data: ");
+                    raw.extend_from_slice(before.as_bytes());
+                    raw.extend_from_slice(&piece);
+                    raw.extend_from_slice(after.as_bytes());
+                    raw.extend_from_slice(b"\n\n");
+                    let mut events = Vec::new();
+                    assert!(transcoder.try_decode_upstream_raw_frame_into(&raw, &mut events));
+                    for event in events {
+                        if let CanonicalStreamEvent::TextDelta(delta) = event {
+                            text.push_str(&delta);
+                        }
+                    }
+                }
+                assert_eq!(text, "ma\u{1f30d}x", "{provider:?}");
+            }
+        }
+    }
 }
@@ -0,0 +1,241 @@
+//! UTF-8 boundary repair for upstream SSE frames.
+//!
+//! Some upstreams stream byte-level tokens and cut a multi-byte character
+//! between two frames: one JSON string ends with the first bytes of the
+//! character and the next string starts with the rest, either as raw bytes or
+//! as the two halves of a `\ud83d\ude00` surrogate pair. Neither frame decodes
+//! on its own, so the text would be lost or replaced. A [`Utf8Boundary`] lives
+//! as long as one upstream stream: it holds the unfinished tail of a string
+//! back and splices it onto the string that continues it. Bytes that can never
+//! form a character become U+FFFD, so the rest of the frame still decodes.
+
+use std::borrow::Cow;
+
+use memchr::{memchr, memchr3, memmem};
+
+const REPLACEMENT: &[u8] = "\u{fffd}".as_bytes();
+const REPLACEMENT_ESCAPE: &[u8] = br"\ufffd";
+
+#[derive(Debug, Default)]
+pub(crate) struct Utf8Boundary {
+    /// Unfinished end of the last string: raw UTF-8 lead bytes or a
+    /// high-surrogate escape.
+    carry: Vec<u8>,
+}
+
+impl Utf8Boundary {
+    /// `frame` with every JSON string made valid, borrowed when it already is.
+    ///
+    /// Works on whole raw SSE frames and on bare `data` payloads alike.
+    pub(crate) fn repair<'a>(&mut self, frame: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.carry.is_empty()
+            && std::str::from_utf8(frame).is_ok()
+            && !has_surrogate_escape(frame)
+        {
+            return Cow::Borrowed(frame);
+        }
+        let mut out = Vec::with_capacity(frame.len() + self.carry.len());
+        let mut rest = frame;
+        while let Some(quote) = memchr(b'"', rest) {
+            push_utf8(&rest[..quote], &mut out, None);
+            out.push(b'"');
+            let content = &rest[quote + 1..];
+            // JSON strings cannot span lines; an unterminated one ends there.
+            let end = string_content_end(content);
+            self.push_string(&content[..end], &mut out);
+            match content.get(end) {
+                Some(b'"') => {
+                    out.push(b'"');
+                    rest = &content[end + 1..];
+                }
+                _ => rest = &content[end..],
+            }
+        }
+        push_utf8(rest, &mut out, None);
+        Cow::Owned(out)
+    }
+
+    fn push_string(&mut self, content: &[u8], out: &mut Vec<u8>) {
+        let joined;
+        let content = if !self.carry.is_empty() && continues_character(content) {
+            let mut carried = std::mem::take(&mut self.carry);
+            carried.extend_from_slice(content);
+            joined = carried;
+            joined.as_slice()
+        } else {
+            content
+        };
+
+        let mut rest = content;
+        loop {
+            let Some(escape) = memchr(b'\\', rest) else {
+                push_utf8(rest, out, Some(&mut self.carry));
+                return;
+            };
+            push_utf8(&rest[..escape], out, None);
+            rest = &rest[escape..];
+            let Some(unit) = escaped_unit(rest) else {
+                let len = rest.len().min(2);
+                out.extend_from_slice(&rest[..len]);
+                rest = &rest[len..];
+                continue;
+            };
+            match unit {
+                0xD800..=0xDBFF if escaped_unit(&rest[6..]).is_some_and(is_low_surrogate) => {
+                    out.extend_from_slice(&rest[..12]);
+                    rest = &rest[12..];
+                }
+                0xD800..=0xDBFF if rest.len() == 6 => {
+                    self.carry.clear();
+                    self.carry.extend_from_slice(rest);
+                    return;
+                }
+                0xD800..=0xDFFF => {
+                    out.extend_from_slice(REPLACEMENT_ESCAPE);
+                    rest = &rest[6..];
+                }
+                _ => {
+                    out.extend_from_slice(&rest[..6]);
+                    rest = &rest[6..];
+                }
+            }
+        }
+    }
+}
+
+/// Copy `bytes` into `out`, replacing invalid sequences. With `carry`, an
+/// unfinished character at the end is held there instead.
+fn push_utf8(mut bytes: &[u8], out: &mut Vec<u8>, carry: Option<&mut Vec<u8>>) {
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(_) => {
+                out.extend_from_slice(bytes);
+                return;
+            }
+            Err(err) => {
+                let (valid, tail) = bytes.split_at(err.valid_up_to());
+                out.extend_from_slice(valid);
+                match err.error_len() {
+                    Some(len) => {
+                        out.extend_from_slice(REPLACEMENT);
+                        bytes = &tail[len..];
+                    }
+                    None => {
+                        match carry {
+                            Some(carry) => {
+                                carry.clear();
+                                carry.extend_from_slice(tail);
+                            }
+                            None => out.extend_from_slice(REPLACEMENT),
+                        }
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Offset of the closing quote of a string whose contents start `content`,
+/// or of the line break or end where an unterminated string stops.
+fn string_content_end(content: &[u8]) -> usize {
+    let mut pos = 0;
+    while let Some(rel) = memchr3(b'"', b'\\', b'\n', &content[pos..]) {
+        pos += rel;
+        if content[pos] != b'\\' {
+            return pos;
+        }
+        pos = (pos + 2).min(content.len());
+    }
+    content.len()
+}
+
+/// Whether a string starting with `content` finishes a character cut off at
+/// the end of an earlier string.
+fn continues_character(content: &[u8]) -> bool {
+    matches!(content.first(), Some(0x80..=0xBF))
+        || escaped_unit(content).is_some_and(is_low_surrogate)
+}
+
+/// The UTF-16 code unit of a `\uXXXX` escape at the start of `bytes`.
+fn escaped_unit(bytes: &[u8]) -> Option<u16> {
+    let hex = bytes.strip_prefix(br"\u")?.get(..4)?;
+    u16::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+}
+
+fn is_low_surrogate(unit: u16) -> bool {
+    (0xDC00..=0xDFFF).contains(&unit)
+}
+
+/// Whether `bytes` contain a `\uD800`-`\uDFFF` escape that may need pairing.
+fn has_surrogate_escape(bytes: &[u8]) -> bool {
+    memmem::find_iter(bytes, br"\u").any(|pos| {
+        matches!(bytes.get(pos + 2), Some(b'd' | b'D'))
+            && matches!(
+                bytes.get(pos + 3),
+                Some(b'8'..=b'9' | b'a'..=b'f' | b'A'..=b'F')
+            )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repair_all(frames: &[&[u8]]) -> Vec<String> {
+        let mut boundary = Utf8Boundary::default();
+        frames
+            .iter()
+            .map(|frame| String::from_utf8(boundary.repair(frame).into_owned()).expect("utf8"))
+            .collect()
+    }
+
+    #[test]
+    fn test_character_cut_between_frames_moves_to_the_next_string() {
+        let [first, second] = ["\u{1f30d}".as_bytes(); 2].map(|emoji| emoji.split_at(2));
+        let first = [br#"data: {"id":"c1","content":"hi "#, first.0, b"\"}\n\n"].concat();
+        let second = [br#"data: {"id":"c1","content":""#, second.1, b" ok\"}\n\n"].concat();
+        assert_eq!(
+            repair_all(&[&first, b"data: {\"ping\":1}\n\n", &second]),
+            [
+                "data: {\"id\":\"c1\",\"content\":\"hi \"}\n\n",
+                "data: {\"ping\":1}\n\n",
+                "data: {\"id\":\"c1\",\"content\":\"\u{1f30d} ok\"}\n\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_surrogate_pair_cut_between_frames_is_joined() {
+        assert_eq!(
+            repair_all(&[
+                br#"{"text":"a\ud83d"}"#,
+                br#"{"type":"x","text":"\ude00b"}"#,
+                br#"{"text":"\ud83d\ude00"}"#,
+            ]),
+            [
+                r#"{"text":"a"}"#,
+                r#"{"type":"x","text":"\ud83d\ude00b"}"#,
+                r#"{"text":"\ud83d\ude00"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bytes_that_never_form_a_character_are_replaced() {
+        let frames: [&[u8]; 3] = [
+            b"{\"a\":\"x\xffy\"}",
+            br#"{"a":"\udc00 \ud83dz"}"#,
+            b"{\"a\":\"\xe2\x82\"}",
+        ];
+        let repaired = repair_all(&frames);
+        assert_eq!(repaired[0], "{\"a\":\"x\u{fffd}y\"}");
+        assert_eq!(repaired[1], r#"{"a":"\ufffd \ufffdz"}"#);
+        // The held-back tail is never continued.
+        assert_eq!(repaired[2], r#"{"a":""}"#);
+
+        let mut boundary = Utf8Boundary::default();
+        let valid = "data: {\"content\":\"caf\u{e9} \u{2615}\"}".as_bytes();
+        assert!(matches!(boundary.repair(valid), Cow::Borrowed(_)));
+    }
+}
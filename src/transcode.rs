@@ -0,0 +1,86 @@
+//! Stable transcoding API for use without the HTTP server.
+//!
+//! Everything re-exported here follows semver: a breaking change to these
+//! types, their public fields, or these functions only ships with a
+//! semver-incompatible version bump. Other public items under
+//! [`crate::protocol`] and [`crate::stream`] are implementation details of the
+//! proxy and may change in any release.
+//!
+//! Non-streaming bodies go through [`encode_request`], [`decode_response`],
+//! and [`encode_response`]; streams go through a [`StreamTranscoder`] built
+//! with [`StreamTranscoder::builder`]. Request encoding and response decoding
+//! share the proxy's codec and need the `server` feature.
+
+pub use crate::error::CanonicalError;
+pub use crate::protocol::canonical::{
+    CanonicalMessage, CanonicalPart, CanonicalRequest, CanonicalResponse, CanonicalRole,
+    CanonicalStopReason, CanonicalStreamEvent, CanonicalToolChoice, CanonicalToolFunction,
+    CanonicalToolSpec, CanonicalUsage, GenerationParams, IngressApi, ProviderExtensions,
+    ProviderKind,
+};
+pub use crate::stream::{SseEvent, SseParser, StreamTranscoder, StreamTranscoderBuilder};
+
+/// Encode `request` as the request body `provider` expects.
+///
+/// Sampling parameters are clamped to the provider's range and optional
+/// parameters it does not accept are dropped, as the proxy does by default.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError`] when the request cannot be expressed for `provider`.
+#[cfg(feature = "server")]
+pub fn encode_request(
+    provider: ProviderKind,
+    request: &CanonicalRequest,
+) -> Result<bytes::Bytes, CanonicalError> {
+    crate::api::common::encode_for_provider(
+        provider,
+        request,
+        &crate::config::FeaturesConfig::default(),
+    )
+}
+
+/// Decode a non-streaming response body returned by `provider`.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError::Translation`] when `body` is not a valid
+/// `provider` response.
+#[cfg(feature = "server")]
+pub fn decode_response(
+    provider: ProviderKind,
+    body: &[u8],
+) -> Result<CanonicalResponse, CanonicalError> {
+    crate::api::common::decode_response_from_provider(provider, body)
+}
+
+/// Encode `response` as the non-streaming body a `client_api` client expects,
+/// reporting `model` where the format carries one.
+///
+/// # Errors
+///
+/// Returns [`CanonicalError`] when the response cannot be encoded.
+pub fn encode_response(
+    client_api: IngressApi,
+    response: &CanonicalResponse,
+    model: &str,
+) -> Result<bytes::Bytes, CanonicalError> {
+    use crate::protocol::{anthropic, gemini, openai_chat, openai_responses};
+
+    let body = match client_api {
+        IngressApi::OpenAiChat => serde_json::to_vec(
+            &openai_chat::response_encoder::encode_openai_chat_response(response, model)?,
+        ),
+        IngressApi::OpenAiResponses => serde_json::to_vec(
+            &openai_responses::response_encoder::encode_responses_output(response, model)?,
+        ),
+        IngressApi::Anthropic => serde_json::to_vec(
+            &anthropic::response_encoder::encode_anthropic_response(response, model)?,
+        ),
+        IngressApi::Gemini => {
+            serde_json::to_vec(&gemini::response_encoder::encode_gemini_response(response)?)
+        }
+    };
+    body.map(bytes::Bytes::from)
+        .map_err(|e| CanonicalError::Translation(format!("Serialization error: {e}")))
+}
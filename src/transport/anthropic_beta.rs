@@ -0,0 +1,102 @@
+//! Per-request `anthropic-beta` negotiation.
+//!
+//! The compat flow passes the beta flags the client asked for down in its
+//! request context. Only native Anthropic upstreams receive them, and only
+//! the flags listed in the upstream's `anthropic_betas` allowlist; every other
+//! upstream is sent its static headers unchanged.
+
+use std::sync::Arc;
+
+use crate::state::request_context;
+
+pub(crate) const ANTHROPIC_BETA: &str = "anthropic-beta";
+
+/// The beta flags named by the client's `anthropic-beta` headers, which may
+/// repeat and hold comma-separated lists.
+#[must_use]
+pub(crate) fn client_anthropic_betas(headers: &http::HeaderMap) -> Option<Arc<[String]>> {
+    let betas: Vec<String> = headers
+        .get_all(ANTHROPIC_BETA)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|beta| !beta.is_empty())
+        .map(str::to_string)
+        .collect();
+    (!betas.is_empty()).then(|| betas.into())
+}
+
+/// The `anthropic-beta` value for an upstream allowing `allowlist`, given
+/// the request context's client flags.
+#[must_use]
+pub(crate) fn negotiated_anthropic_beta(allowlist: &[String]) -> Option<http::HeaderValue> {
+    if allowlist.is_empty() {
+        return None;
+    }
+    request_context::with(|context| {
+        negotiate_anthropic_beta(context.anthropic_betas.as_deref(), allowlist)
+    })
+    .flatten()
+}
+
+/// The `anthropic-beta` value for an upstream allowing `allowlist`: the
+/// `client_betas` that the upstream allows, in the client's order.
+#[must_use]
+pub(crate) fn negotiate_anthropic_beta(
+    client_betas: Option<&[String]>,
+    allowlist: &[String],
+) -> Option<http::HeaderValue> {
+    let mut negotiated = String::new();
+    for beta in client_betas.unwrap_or_default() {
+        if allowlist.contains(beta) && !negotiated.split(',').any(|seen| seen == beta) {
+            if !negotiated.is_empty() {
+                negotiated.push(',');
+            }
+            negotiated.push_str(beta);
+        }
+    }
+    if negotiated.is_empty() {
+        return None;
+    }
+    http::HeaderValue::from_str(&negotiated).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_negotiates_allowed_client_betas() {
+        let mut headers = http::HeaderMap::new();
+        headers.append(
+            ANTHROPIC_BETA,
+            http::HeaderValue::from_static("prompt-caching-2024-07-31, context-1m-2025-08-07"),
+        );
+        headers.append(ANTHROPIC_BETA, http::HeaderValue::from_static("unlisted"));
+        let allowlist = vec![
+            "context-1m-2025-08-07".to_string(),
+            "prompt-caching-2024-07-31".to_string(),
+        ];
+
+        assert!(negotiated_anthropic_beta(&allowlist).is_none());
+        let betas = client_anthropic_betas(&headers);
+        assert_eq!(
+            negotiate_anthropic_beta(betas.as_deref(), &allowlist).unwrap(),
+            "prompt-caching-2024-07-31,context-1m-2025-08-07"
+        );
+        let context = request_context::RequestContext {
+            anthropic_betas: betas,
+            ..Default::default()
+        };
+        request_context::scope(context, async {
+            assert_eq!(
+                negotiated_anthropic_beta(&allowlist).unwrap(),
+                "prompt-caching-2024-07-31,context-1m-2025-08-07"
+            );
+            assert!(negotiated_anthropic_beta(&[]).is_none());
+            assert!(negotiated_anthropic_beta(&["other".to_string()]).is_none());
+        })
+        .await;
+    }
+}
@@ -0,0 +1,95 @@
+//! Upstream fault injection for resilience testing (`server.chaos`).
+//!
+//! Only active in unit tests and builds with the `chaos` feature; config
+//! validation rejects `server.chaos` everywhere else.
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+
+use crate::config::ChaosConfig;
+
+/// Transport error reported for an injected connect failure; retried like a
+/// real refused connection.
+const INJECTED_CONNECT_FAILURE: &str = "chaos: injected connection refused";
+
+pub(crate) struct Chaos {
+    config: ChaosConfig,
+}
+
+impl Chaos {
+    pub(crate) fn new(config: Option<&ChaosConfig>) -> Option<Self> {
+        if !cfg!(any(test, feature = "chaos")) {
+            return None;
+        }
+        config.cloned().map(|config| Self { config })
+    }
+
+    fn roll(rate: f64) -> bool {
+        rate > 0.0 && fastrand::f64() < rate
+    }
+
+    /// Whether SSE responses may be truncated, which needs the reqwest client.
+    pub(crate) fn truncates_streams(&self) -> bool {
+        self.config.truncate_stream_rate > 0.0
+    }
+
+    /// The transport error to fail this attempt with, if any.
+    pub(crate) fn connect_failure(&self) -> Option<String> {
+        Self::roll(self.config.connect_failure_rate).then(|| INJECTED_CONNECT_FAILURE.to_string())
+    }
+
+    /// Hold a response back before its first byte, if rolled.
+    pub(crate) async fn delay_first_byte(&self) {
+        if Self::roll(self.config.slow_first_byte_rate) {
+            tokio::time::sleep(Duration::from_millis(self.config.slow_first_byte_ms)).await;
+        }
+    }
+
+    /// Cut an SSE `response` off after `truncate_after_bytes`, if rolled; the
+    /// body then fails as if the upstream connection dropped.
+    pub(crate) fn truncate_stream(&self, response: reqwest::Response) -> reqwest::Response {
+        let is_sse = response
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !is_sse || !Self::roll(self.config.truncate_stream_rate) {
+            return response;
+        }
+
+        let status = response.status();
+        let version = response.version();
+        let mut headers = response.headers().clone();
+        headers.remove(http::header::CONTENT_LENGTH);
+        let body = futures_util::stream::unfold(
+            (
+                response.bytes_stream(),
+                Some(self.config.truncate_after_bytes),
+            ),
+            |(mut inner, remaining)| async move {
+                let remaining = remaining?;
+                if remaining == 0 {
+                    let dropped = std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "chaos: injected stream truncation",
+                    );
+                    return Some((Err(dropped), (inner, None)));
+                }
+                match inner.next().await? {
+                    Ok(mut chunk) => {
+                        chunk.truncate(remaining);
+                        let remaining = remaining - chunk.len();
+                        Some((Ok(chunk), (inner, Some(remaining))))
+                    }
+                    Err(err) => Some((Err(std::io::Error::other(err)), (inner, None))),
+                }
+            },
+        );
+        let mut truncated = http::Response::new(reqwest::Body::wrap_stream(body));
+        *truncated.status_mut() = status;
+        *truncated.version_mut() = version;
+        *truncated.headers_mut() = headers;
+        reqwest::Response::from(truncated)
+    }
+}
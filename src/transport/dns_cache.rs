@@ -0,0 +1,298 @@
+//! Cached DNS resolution for upstream connects.
+//!
+//! Every reqwest client and hyper passthrough connector of a transport
+//! resolves through one [`CachingResolver`]. Lookups are reused for
+//! `server.dns_cache_ttl_secs` and failures for
+//! `server.dns_negative_cache_ttl_secs`; the system resolver reports no record
+//! TTLs, so these stand in for them. Concurrent misses for one host share a
+//! single lookup, which runs in a `dns_resolve` span recording how long it
+//! took. A full cache evicts the entry closest to expiry.
+//!
+//! Connectors get every address of both families, so hyper-util's happy
+//! eyeballs races the second family once the first is slow to connect.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use hyper_util::client::legacy::connect::dns::Name;
+use parking_lot::{Mutex, RwLock};
+use rustc_hash::FxHashMap;
+use tracing::Instrument;
+
+const DNS_CACHE_MAX_ENTRIES: usize = 1024;
+
+/// Addresses, or the message of the failed lookup.
+type LookupResult = Result<Arc<[SocketAddr]>, Arc<str>>;
+
+struct CachedLookup {
+    addrs: LookupResult,
+    expires_at: Instant,
+}
+
+struct ResolverInner {
+    ttl: Duration,
+    negative_ttl: Duration,
+    entries: RwLock<FxHashMap<Box<str>, CachedLookup>>,
+    /// Lookups still running, awaited by every miss for the same host.
+    pending: Mutex<FxHashMap<Box<str>, Shared<BoxFuture<'static, LookupResult>>>>,
+}
+
+/// Resolver shared by the upstream connectors of one transport.
+#[derive(Clone)]
+pub(crate) struct CachingResolver {
+    inner: Arc<ResolverInner>,
+}
+
+impl CachingResolver {
+    /// A zero TTL disables caching of that kind of result.
+    #[must_use]
+    pub(crate) fn new(ttl: Duration, negative_ttl: Duration) -> Self {
+        Self {
+            inner: Arc::new(ResolverInner {
+                ttl,
+                negative_ttl,
+                entries: RwLock::new(FxHashMap::default()),
+                pending: Mutex::new(FxHashMap::default()),
+            }),
+        }
+    }
+
+    /// Addresses for `host`, with port 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns the lookup error, or a cached one while it is still fresh.
+    pub(crate) async fn lookup(&self, host: &str) -> io::Result<Arc<[SocketAddr]>> {
+        if let Some(cached) = self.cached(host) {
+            tracing::trace!(host, "dns cache hit");
+            return cached.map_err(|message| io::Error::other(message.to_string()));
+        }
+
+        let lookup = {
+            let mut pending = self.inner.pending.lock();
+            // A lookup may have finished since the cache was checked.
+            if let Some(cached) = self.cached(host) {
+                return cached.map_err(|message| io::Error::other(message.to_string()));
+            }
+            pending
+                .entry(Box::from(host))
+                .or_insert_with(|| self.clone().resolve(Box::from(host)).boxed().shared())
+                .clone()
+        };
+        lookup
+            .await
+            .map_err(|message| io::Error::other(message.to_string()))
+    }
+
+    /// Run the lookup for `host`, cache its outcome, and retire it from
+    /// the pending lookups.
+    async fn resolve(self, host: Box<str>) -> LookupResult {
+        let host = &*host;
+        let span = tracing::debug_span!(
+            "dns_resolve",
+            host,
+            elapsed_ms = tracing::field::Empty,
+            addrs = tracing::field::Empty,
+        );
+        let started = Instant::now();
+        let result = tokio::net::lookup_host((host, 0))
+            .instrument(span.clone())
+            .await
+            .and_then(|addrs| {
+                let addrs: Arc<[SocketAddr]> = addrs.collect();
+                if addrs.is_empty() {
+                    Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no addresses found for {host}"),
+                    ))
+                } else {
+                    Ok(addrs)
+                }
+            });
+        span.record(
+            "elapsed_ms",
+            u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        );
+        let result = match result {
+            Ok(addrs) => {
+                span.record("addrs", addrs.len());
+                Ok(addrs)
+            }
+            Err(err) => {
+                span.in_scope(|| tracing::debug!(host, error = %err, "dns lookup failed"));
+                Err(Arc::from(err.to_string()))
+            }
+        };
+        self.store(host, result.clone());
+        self.inner.pending.lock().remove(host);
+        result
+    }
+
+    fn cached(&self, host: &str) -> Option<LookupResult> {
+        let entries = self.inner.entries.read();
+        let entry = entries.get(host)?;
+        (entry.expires_at > Instant::now()).then(|| entry.addrs.clone())
+    }
+
+    fn store(&self, host: &str, addrs: LookupResult) {
+        let ttl = if addrs.is_ok() {
+            self.inner.ttl
+        } else {
+            self.inner.negative_ttl
+        };
+        if ttl.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.inner.entries.write();
+        if entries.len() >= DNS_CACHE_MAX_ENTRIES && !entries.contains_key(host) {
+            entries.retain(|_, entry| entry.expires_at > now);
+            if entries.len() >= DNS_CACHE_MAX_ENTRIES {
+                let soonest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires_at)
+                    .map(|(host, _)| host.clone());
+                if let Some(soonest) = soonest {
+                    entries.remove(&soonest);
+                }
+            }
+        }
+        entries.insert(
+            Box::from(host),
+            CachedLookup {
+                addrs,
+                expires_at: now + ttl,
+            },
+        );
+    }
+}
+
+/// Iterator over a shared address list.
+pub(crate) struct CachedAddrs {
+    addrs: Arc<[SocketAddr]>,
+    next: usize,
+}
+
+impl CachedAddrs {
+    fn new(addrs: Arc<[SocketAddr]>) -> Self {
+        Self { addrs, next: 0 }
+    }
+}
+
+impl Iterator for CachedAddrs {
+    type Item = SocketAddr;
+
+    fn next(&mut self) -> Option<SocketAddr> {
+        let addr = self.addrs.get(self.next).copied()?;
+        self.next += 1;
+        Some(addr)
+    }
+}
+
+impl reqwest::dns::Resolve for CachingResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addrs = resolver.lookup(name.as_str()).await?;
+            Ok(Box::new(CachedAddrs::new(addrs)) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+impl tower_service::Service<Name> for CachingResolver {
+    type Response = CachedAddrs;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addrs = resolver.lookup(name.as_str()).await?;
+            Ok(CachedAddrs::new(addrs))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lookups_are_cached_by_outcome() {
+        let resolver = CachingResolver::new(Duration::from_secs(60), Duration::from_secs(60));
+        let addrs = resolver.lookup("localhost").await.unwrap();
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+        assert!(resolver.cached("localhost").unwrap().is_ok());
+
+        assert!(resolver.lookup("toolify-dns-test.invalid").await.is_err());
+        assert!(resolver
+            .cached("toolify-dns-test.invalid")
+            .unwrap()
+            .is_err());
+        assert!(resolver.lookup("toolify-dns-test.invalid").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_share_one_lookup() {
+        let resolver = CachingResolver::new(Duration::ZERO, Duration::ZERO);
+        let (resolved, running) = tokio::sync::oneshot::channel::<LookupResult>();
+        resolver.inner.pending.lock().insert(
+            Box::from("slow.example"),
+            async move { running.await.unwrap() }.boxed().shared(),
+        );
+
+        let mut first = Box::pin(resolver.lookup("slow.example"));
+        let mut second = Box::pin(resolver.lookup("slow.example"));
+        assert!(futures_util::poll!(first.as_mut()).is_pending());
+        assert!(futures_util::poll!(second.as_mut()).is_pending());
+        assert_eq!(resolver.inner.pending.lock().len(), 1);
+
+        let addrs: Arc<[SocketAddr]> = Arc::from([SocketAddr::from(([192, 0, 2, 1], 0))]);
+        resolved.send(Ok(Arc::clone(&addrs))).unwrap();
+        let (first, second) = tokio::join!(first, second);
+        assert!(Arc::ptr_eq(&first.unwrap(), &addrs));
+        assert!(Arc::ptr_eq(&second.unwrap(), &addrs));
+    }
+
+    #[tokio::test]
+    async fn test_finished_lookups_leave_the_pending_set() {
+        let resolver = CachingResolver::new(Duration::ZERO, Duration::ZERO);
+        let (first, second) =
+            tokio::join!(resolver.lookup("localhost"), resolver.lookup("localhost"));
+        assert_eq!(first.unwrap(), second.unwrap());
+        assert!(resolver.inner.pending.lock().is_empty());
+    }
+
+    #[test]
+    fn test_full_cache_evicts_the_entry_closest_to_expiry() {
+        let resolver = CachingResolver::new(Duration::from_secs(60), Duration::from_secs(1));
+        resolver.store("soonest", Err(Arc::from("lookup failed")));
+        for index in 1..DNS_CACHE_MAX_ENTRIES {
+            resolver.store(&format!("host-{index}"), Ok(Arc::from([])));
+        }
+        resolver.store("newest", Ok(Arc::from([])));
+
+        let entries = resolver.inner.entries.read();
+        assert_eq!(entries.len(), DNS_CACHE_MAX_ENTRIES);
+        assert!(!entries.contains_key("soonest"));
+        assert!(entries.contains_key("host-1"));
+        assert!(entries.contains_key("newest"));
+    }
+
+    #[tokio::test]
+    async fn test_zero_ttl_disables_caching() {
+        let resolver = CachingResolver::new(Duration::ZERO, Duration::ZERO);
+        resolver.lookup("localhost").await.unwrap();
+        assert!(resolver.cached("localhost").is_none());
+    }
+}
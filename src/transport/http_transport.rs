@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use std::sync::Arc;
 use std::sync::{Once, OnceLock};
 use std::time::Duration;
@@ -12,22 +13,35 @@ use hyper_util::rt::{TokioExecutor, TokioTimer};
 use parking_lot::RwLock;
 use rustc_hash::FxHashMap;
 
-use crate::config::ServerConfig;
+use crate::config::{InlineFileUploadConfig, ServerConfig, UpstreamServiceConfig};
 use crate::error::CanonicalError;
 
+use super::chaos::Chaos;
+use super::dns_cache::CachingResolver;
+use super::inline_files::{InlineFileUploader, UploadRequest};
+use super::prepared_upstream::PreparedUpstream;
+use super::request_signing::HmacSigner;
+use super::response_headers::note_response_headers;
 use super::retry_policy::{
-    retry_delay, retry_transport_delay, should_retry_transport_message,
-    should_retry_upstream_status, PARSED_ENDPOINT_CACHE_MAX_ENTRIES, RETRY_MAX_ATTEMPTS,
+    is_timeout_transport_message, note_retry, retry_delay, retry_transport_delay,
+    should_retry_transport_message, should_retry_upstream_response,
+    PARSED_ENDPOINT_CACHE_MAX_ENTRIES, RETRY_MAX_ATTEMPTS,
 };
+use super::service_tier::ServiceTierEndpoint;
+use super::upstream_clients::UpstreamClients;
 
 static RUSTLS_PROVIDER_INIT: Once = Once::new();
 const REQWEST_PROXY_CLIENT_CACHE_MAX_ENTRIES: usize = 64;
 const H2_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(60);
 const H2_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(10);
 const H2_INITIAL_WINDOW_SIZE: u32 = 1_572_864;
+/// Delay before racing the other address family, as in reqwest's connector.
+const HAPPY_EYEBALLS_TIMEOUT: Duration = Duration::from_millis(300);
 
-type HyperPassthroughHttpsClient = HyperClient<HttpsConnector<HttpConnector>, Full<bytes::Bytes>>;
-type HyperPassthroughHttpClient = HyperClient<HttpConnector, Full<bytes::Bytes>>;
+type UpstreamConnector = HttpConnector<CachingResolver>;
+pub(crate) type HyperPassthroughHttpsClient =
+    HyperClient<HttpsConnector<UpstreamConnector>, Full<bytes::Bytes>>;
+pub(crate) type HyperPassthroughHttpClient = HyperClient<UpstreamConnector, Full<bytes::Bytes>>;
 
 fn build_reqwest_client(
     pool_max_idle_per_host: usize,
@@ -35,14 +49,23 @@ fn build_reqwest_client(
     timeout: Duration,
     use_env_proxy: bool,
     proxy_url: Option<&str>,
+    upstream: Option<&UpstreamClients>,
+    resolver: &CachingResolver,
 ) -> Result<reqwest::Client, CanonicalError> {
     let mut builder = reqwest::Client::builder()
+        .dns_resolver(Arc::new(resolver.clone()))
         .pool_max_idle_per_host(pool_max_idle_per_host)
         .pool_idle_timeout(pool_idle_timeout)
         .tcp_nodelay(true)
         .connect_timeout(Duration::from_secs(5))
         .redirect(reqwest::redirect::Policy::none())
         .timeout(timeout);
+    if let Some(upstream) = upstream {
+        if let Some(tls) = &upstream.tls {
+            builder = builder.use_preconfigured_tls(tls.clone());
+        }
+        builder = builder.local_address(upstream.local_address);
+    }
 
     if let Some(proxy_url) = proxy_url {
         let proxy = reqwest::Proxy::all(proxy_url)
@@ -57,6 +80,15 @@ fn build_reqwest_client(
         .map_err(|err| CanonicalError::Transport(format!("Failed to build HTTP client: {err}")))
 }
 
+/// Classify a final transport failure, surfacing timeouts distinctly.
+fn transport_error(message: String, timed_out: bool) -> CanonicalError {
+    if timed_out || is_timeout_transport_message(&message) {
+        CanonicalError::Timeout(message)
+    } else {
+        CanonicalError::Transport(message)
+    }
+}
+
 /// HTTP transport client for sending requests to upstream providers.
 pub struct HttpTransport {
     base_client: OnceLock<Arc<reqwest::Client>>,
@@ -75,6 +107,12 @@ pub struct HttpTransport {
     hyper_passthrough_https_client: OnceLock<HyperPassthroughHttpsClient>,
     hyper_passthrough_http_client: OnceLock<HyperPassthroughHttpClient>,
     hyper_passthrough_h2c_client: OnceLock<HyperPassthroughHttpClient>,
+    chaos: Option<Chaos>,
+    resolver: CachingResolver,
+    request_signers: Vec<HmacSigner>,
+    upstream_clients: Vec<UpstreamClients>,
+    inline_file_uploaders: Vec<InlineFileUploader>,
+    service_tier_endpoints: Vec<ServiceTierEndpoint>,
 }
 
 impl HttpTransport {
@@ -139,12 +177,20 @@ impl HttpTransport {
         let effective_pool_max_idle_per_host =
             Self::effective_pool_max_idle_per_host(config, upstream_count);
         let reqwest_use_env_proxy = config.http_use_env_proxy;
+        let chaos = Chaos::new(config.chaos.as_ref());
+        // Truncation rewraps reqwest bodies; hyper's `Incoming` cannot be rebuilt.
+        let chaos_needs_reqwest = chaos.as_ref().is_some_and(Chaos::truncates_streams);
+        let resolver = CachingResolver::new(
+            Duration::from_secs(config.dns_cache_ttl_secs),
+            Duration::from_secs(config.dns_negative_cache_ttl_secs),
+        );
         let preconfigured_proxy_clients = Self::build_preconfigured_proxy_clients(
             proxy_urls,
             effective_pool_max_idle_per_host,
             pool_idle_timeout,
             reqwest_timeout,
             reqwest_use_env_proxy,
+            &resolver,
         );
         Self {
             base_client: OnceLock::new(),
@@ -156,13 +202,242 @@ impl HttpTransport {
             reqwest_pool_idle_timeout: pool_idle_timeout,
             reqwest_timeout,
             reqwest_use_env_proxy,
-            hyper_passthrough_enabled: !reqwest_use_env_proxy,
+            hyper_passthrough_enabled: !reqwest_use_env_proxy && !chaos_needs_reqwest,
             hyper_passthrough_force_h2c_upstream: config.http_force_h2c_upstream,
             hyper_passthrough_pool_max_idle_per_host: effective_pool_max_idle_per_host,
             hyper_passthrough_pool_idle_timeout: pool_idle_timeout,
             hyper_passthrough_https_client: OnceLock::new(),
             hyper_passthrough_http_client: OnceLock::new(),
             hyper_passthrough_h2c_client: OnceLock::new(),
+            chaos,
+            resolver,
+            request_signers: Vec::new(),
+            upstream_clients: Vec::new(),
+            inline_file_uploaders: Vec::new(),
+            service_tier_endpoints: Vec::new(),
+        }
+    }
+
+    /// Connect to upstreams with a `tls` section or a `bind_address` through
+    /// their own clients, with TLS files loaded from disk now. `rule_proxies`
+    /// are the routing-rule proxies any upstream may be reached through.
+    #[must_use]
+    pub fn with_upstream_clients<'a, I>(mut self, upstreams: I, rule_proxies: &[&str]) -> Self
+    where
+        I: IntoIterator<Item = &'a UpstreamServiceConfig>,
+    {
+        for upstream in upstreams {
+            let mut clients = match UpstreamClients::load(upstream) {
+                None => continue,
+                Some(Ok(clients)) => clients,
+                Some(Err(err)) => {
+                    tracing::error!(
+                        upstream = %upstream.name,
+                        error = %err,
+                        "failed to load upstream connection settings; connecting without them"
+                    );
+                    continue;
+                }
+            };
+            let mut proxies = vec![None];
+            for proxy in [
+                upstream.proxy.as_deref(),
+                upstream.proxy_stream.as_deref(),
+                upstream.proxy_non_stream.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            .chain(rule_proxies.iter().copied())
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            {
+                if !proxies.contains(&Some(proxy)) {
+                    proxies.push(Some(proxy));
+                }
+            }
+            for proxy_url in proxies {
+                match build_reqwest_client(
+                    self.reqwest_pool_max_idle_per_host,
+                    self.reqwest_pool_idle_timeout,
+                    self.reqwest_timeout,
+                    self.reqwest_use_env_proxy,
+                    proxy_url,
+                    Some(&clients),
+                    &self.resolver,
+                ) {
+                    Ok(client) => clients
+                        .reqwest_clients
+                        .push((proxy_url.map(str::to_owned), Arc::new(client))),
+                    Err(err) => tracing::error!(
+                        upstream = %upstream.name,
+                        error = %err,
+                        "failed to build upstream client"
+                    ),
+                }
+            }
+            self.upstream_clients.push(clients);
+        }
+        self
+    }
+
+    fn upstream_clients_for(&self, url: &str) -> Option<&UpstreamClients> {
+        self.upstream_clients
+            .iter()
+            .find(|clients| clients.covers(url))
+    }
+
+    /// Sign requests to the `hmac` upstreams among `upstreams`.
+    #[must_use]
+    pub fn with_request_signers<'a, I>(mut self, upstreams: I) -> Self
+    where
+        I: IntoIterator<Item = &'a UpstreamServiceConfig>,
+    {
+        self.request_signers = upstreams
+            .into_iter()
+            .filter_map(HmacSigner::for_upstream)
+            .collect();
+        self
+    }
+
+    /// Upload large inline files of requests to the upstreams `config`
+    /// covers; disabled when `config` is `None`.
+    #[must_use]
+    pub fn with_inline_file_uploads<'a, I>(
+        mut self,
+        config: Option<&InlineFileUploadConfig>,
+        upstreams: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = &'a UpstreamServiceConfig>,
+    {
+        self.inline_file_uploaders = config.map_or_else(Vec::new, |config| {
+            upstreams
+                .into_iter()
+                .filter_map(|upstream| InlineFileUploader::for_upstream(upstream, config))
+                .collect()
+        });
+        self
+    }
+
+    /// Send the granted request priority as `service_tier` to the model
+    /// endpoints of `upstreams` whose provider has service tiers.
+    #[must_use]
+    pub fn with_service_tiers<'a, I>(mut self, enabled: bool, upstreams: I) -> Self
+    where
+        I: IntoIterator<Item = &'a PreparedUpstream>,
+    {
+        self.service_tier_endpoints = if enabled {
+            upstreams
+                .into_iter()
+                .filter_map(ServiceTierEndpoint::for_upstream)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self
+    }
+
+    fn apply_service_tier(&self, url: &str, body: bytes::Bytes) -> bytes::Bytes {
+        self.service_tier_endpoints
+            .iter()
+            .find_map(|endpoint| endpoint.apply(url, &body))
+            .unwrap_or(body)
+    }
+
+    /// Move the large inline files of a request body to its upstream's files
+    /// API, returning the headers and body that reference them instead.
+    async fn extract_inline_files(
+        &self,
+        url: &str,
+        method: &http::Method,
+        headers: &http::HeaderMap,
+        body: &bytes::Bytes,
+        proxy_url: Option<&str>,
+    ) -> Option<(http::HeaderMap, bytes::Bytes)> {
+        let uploader = self
+            .inline_file_uploaders
+            .iter()
+            .find(|uploader| uploader.covers(url))?;
+        if !uploader.wants(method, body) {
+            return None;
+        }
+        let body = uploader
+            .rewrite(body, |request| {
+                self.send_inline_upload(request, headers, proxy_url)
+            })
+            .await?;
+        Some((uploader.request_headers(headers), body))
+    }
+
+    /// Send one files API upload, without transport retries.
+    async fn send_inline_upload(
+        &self,
+        request: UploadRequest,
+        headers: &http::HeaderMap,
+        proxy_url: Option<&str>,
+    ) -> Result<bytes::Bytes, CanonicalError> {
+        let client = match self
+            .upstream_clients_for(&request.url)
+            .and_then(|clients| clients.reqwest_client(proxy_url))
+        {
+            Some(client) => client.clone(),
+            None => self.reqwest_client_for_proxy(proxy_url)?.as_ref().clone(),
+        };
+        let url = self.parsed_url(&request.url)?;
+        let mut upload_headers = headers.clone();
+        upload_headers.remove(http::header::CONTENT_LENGTH);
+        upload_headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_str(&request.content_type).map_err(|e| {
+                CanonicalError::Internal(format!("Invalid upload content type: {e}"))
+            })?,
+        );
+        for (name, value) in request.headers {
+            upload_headers.insert(name, http::HeaderValue::from_static(value));
+        }
+        if !self.request_signers.is_empty() {
+            self.sign_request(
+                url.as_str(),
+                &url[url::Position::BeforePath..],
+                &http::Method::POST,
+                &mut upload_headers,
+                &request.body,
+            );
+        }
+        let response = client
+            .post(url.as_ref().clone())
+            .headers(upload_headers)
+            .body(request.body)
+            .send()
+            .await
+            .map_err(|e| transport_error(e.to_string(), e.is_timeout()))?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| transport_error(e.to_string(), e.is_timeout()))?;
+        if !status.is_success() {
+            return Err(CanonicalError::Transport(format!(
+                "Files API upload failed with status {status}"
+            )));
+        }
+        Ok(body)
+    }
+
+    fn sign_request(
+        &self,
+        url: &str,
+        path_and_query: &str,
+        method: &http::Method,
+        headers: &mut http::HeaderMap,
+        body: &[u8],
+    ) {
+        if let Some(signer) = self
+            .request_signers
+            .iter()
+            .find(|signer| signer.covers(url))
+        {
+            signer.sign(headers, method, path_and_query, body);
         }
     }
 
@@ -173,6 +448,8 @@ impl HttpTransport {
             self.reqwest_timeout,
             self.reqwest_use_env_proxy,
             None,
+            None,
+            &self.resolver,
         ) {
             Ok(client) => Arc::new(client),
             Err(err) => {
@@ -198,6 +475,7 @@ impl HttpTransport {
         pool_idle_timeout: Option<Duration>,
         timeout: Duration,
         use_env_proxy: bool,
+        resolver: &CachingResolver,
     ) -> FxHashMap<String, Arc<reqwest::Client>>
     where
         I: IntoIterator<Item = S>,
@@ -215,6 +493,8 @@ impl HttpTransport {
                 timeout,
                 use_env_proxy,
                 Some(proxy_url),
+                None,
+                resolver,
             ) {
                 Ok(client) => {
                     clients.insert(proxy_url.to_owned(), Arc::new(client));
@@ -254,72 +534,133 @@ impl HttpTransport {
             return None;
         }
 
-        Some(self.hyper_passthrough_https_client.get_or_init(|| {
-            let mut connector = HttpConnector::new();
-            connector.enforce_http(false);
-            connector.set_nodelay(true);
-            connector.set_connect_timeout(Some(Duration::from_secs(5)));
-            let https = HttpsConnectorBuilder::new()
-                .with_webpki_roots()
-                .https_or_http()
-                .enable_http1()
-                .enable_http2()
-                .wrap_connector(connector);
-            let mut builder = HyperClient::builder(TokioExecutor::new());
-            builder.pool_max_idle_per_host(self.hyper_passthrough_pool_max_idle_per_host);
-            builder.pool_idle_timeout(self.hyper_passthrough_pool_idle_timeout);
-            builder.pool_timer(TokioTimer::new());
-            builder.timer(TokioTimer::new());
-            builder.http2_adaptive_window(true);
-            builder.http2_keep_alive_interval(H2_KEEP_ALIVE_INTERVAL);
-            builder.http2_keep_alive_timeout(H2_KEEP_ALIVE_TIMEOUT);
-            builder.http2_keep_alive_while_idle(true);
-            builder.build(https)
+        Some(
+            self.hyper_passthrough_https_client
+                .get_or_init(|| self.build_hyper_https_client(None, None)),
+        )
+    }
+
+    /// The hyper https client for `upstream`, with its TLS identity and
+    /// local address.
+    fn upstream_hyper_https_client<'a>(
+        &self,
+        upstream: &'a UpstreamClients,
+    ) -> Option<&'a HyperPassthroughHttpsClient> {
+        if !self.hyper_passthrough_enabled {
+            return None;
+        }
+
+        Some(upstream.hyper_https_client.get_or_init(|| {
+            self.build_hyper_https_client(upstream.tls.clone(), upstream.local_address)
         }))
     }
 
-    fn hyper_passthrough_http_client(&self) -> Option<&HyperPassthroughHttpClient> {
+    /// The hyper cleartext client for `upstream`, bound to its local address.
+    fn upstream_hyper_http_client<'a>(
+        &self,
+        upstream: &'a UpstreamClients,
+    ) -> Option<&'a HyperPassthroughHttpClient> {
         if !self.hyper_passthrough_enabled {
             return None;
         }
 
-        Some(self.hyper_passthrough_http_client.get_or_init(|| {
-            let mut connector = HttpConnector::new();
-            connector.enforce_http(true);
-            connector.set_nodelay(true);
-            connector.set_connect_timeout(Some(Duration::from_secs(5)));
-            let mut builder = HyperClient::builder(TokioExecutor::new());
-            builder.pool_max_idle_per_host(self.hyper_passthrough_pool_max_idle_per_host);
-            builder.pool_idle_timeout(self.hyper_passthrough_pool_idle_timeout);
-            builder.pool_timer(TokioTimer::new());
-            builder.build(connector)
+        Some(upstream.hyper_http_client.get_or_init(|| {
+            if self.hyper_passthrough_force_h2c_upstream {
+                self.build_hyper_h2c_client(upstream.local_address)
+            } else {
+                self.build_hyper_http_client(upstream.local_address)
+            }
         }))
     }
 
+    fn upstream_connector(&self, local_address: Option<IpAddr>) -> UpstreamConnector {
+        let mut connector = HttpConnector::new_with_resolver(self.resolver.clone());
+        connector.set_happy_eyeballs_timeout(Some(HAPPY_EYEBALLS_TIMEOUT));
+        connector.set_local_address(local_address);
+        connector
+    }
+
+    fn build_hyper_https_client(
+        &self,
+        tls: Option<rustls::ClientConfig>,
+        local_address: Option<IpAddr>,
+    ) -> HyperPassthroughHttpsClient {
+        let mut connector = self.upstream_connector(local_address);
+        connector.enforce_http(false);
+        connector.set_nodelay(true);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        let https = match tls {
+            Some(tls) => HttpsConnectorBuilder::new().with_tls_config(tls),
+            None => HttpsConnectorBuilder::new().with_webpki_roots(),
+        }
+        .https_or_http()
+        .enable_http1()
+        .enable_http2()
+        .wrap_connector(connector);
+        let mut builder = HyperClient::builder(TokioExecutor::new());
+        builder.pool_max_idle_per_host(self.hyper_passthrough_pool_max_idle_per_host);
+        builder.pool_idle_timeout(self.hyper_passthrough_pool_idle_timeout);
+        builder.pool_timer(TokioTimer::new());
+        builder.timer(TokioTimer::new());
+        builder.http2_adaptive_window(true);
+        builder.http2_keep_alive_interval(H2_KEEP_ALIVE_INTERVAL);
+        builder.http2_keep_alive_timeout(H2_KEEP_ALIVE_TIMEOUT);
+        builder.http2_keep_alive_while_idle(true);
+        builder.build(https)
+    }
+
+    fn hyper_passthrough_http_client(&self) -> Option<&HyperPassthroughHttpClient> {
+        if !self.hyper_passthrough_enabled {
+            return None;
+        }
+
+        Some(
+            self.hyper_passthrough_http_client
+                .get_or_init(|| self.build_hyper_http_client(None)),
+        )
+    }
+
+    fn build_hyper_http_client(&self, local_address: Option<IpAddr>) -> HyperPassthroughHttpClient {
+        let mut connector = self.upstream_connector(local_address);
+        connector.enforce_http(true);
+        connector.set_nodelay(true);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        let mut builder = HyperClient::builder(TokioExecutor::new());
+        builder.pool_max_idle_per_host(self.hyper_passthrough_pool_max_idle_per_host);
+        builder.pool_idle_timeout(self.hyper_passthrough_pool_idle_timeout);
+        builder.pool_timer(TokioTimer::new());
+        builder.build(connector)
+    }
+
     fn hyper_passthrough_h2c_client(&self) -> Option<&HyperPassthroughHttpClient> {
         if !self.hyper_passthrough_enabled {
             return None;
         }
 
-        Some(self.hyper_passthrough_h2c_client.get_or_init(|| {
-            let mut connector = HttpConnector::new();
-            connector.enforce_http(true);
-            connector.set_nodelay(true);
-            connector.set_connect_timeout(Some(Duration::from_secs(5)));
-            let mut builder = HyperClient::builder(TokioExecutor::new());
-            builder.pool_max_idle_per_host(self.hyper_passthrough_pool_max_idle_per_host);
-            builder.pool_idle_timeout(self.hyper_passthrough_pool_idle_timeout);
-            builder.pool_timer(TokioTimer::new());
-            builder.timer(TokioTimer::new());
-            builder.http2_only(true);
-            builder.http2_adaptive_window(false);
-            builder.http2_initial_connection_window_size(H2_INITIAL_WINDOW_SIZE);
-            builder.http2_initial_stream_window_size(H2_INITIAL_WINDOW_SIZE);
-            builder.http2_keep_alive_interval(H2_KEEP_ALIVE_INTERVAL);
-            builder.http2_keep_alive_timeout(H2_KEEP_ALIVE_TIMEOUT);
-            builder.http2_keep_alive_while_idle(true);
-            builder.build(connector)
-        }))
+        Some(
+            self.hyper_passthrough_h2c_client
+                .get_or_init(|| self.build_hyper_h2c_client(None)),
+        )
+    }
+
+    fn build_hyper_h2c_client(&self, local_address: Option<IpAddr>) -> HyperPassthroughHttpClient {
+        let mut connector = self.upstream_connector(local_address);
+        connector.enforce_http(true);
+        connector.set_nodelay(true);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        let mut builder = HyperClient::builder(TokioExecutor::new());
+        builder.pool_max_idle_per_host(self.hyper_passthrough_pool_max_idle_per_host);
+        builder.pool_idle_timeout(self.hyper_passthrough_pool_idle_timeout);
+        builder.pool_timer(TokioTimer::new());
+        builder.timer(TokioTimer::new());
+        builder.http2_only(true);
+        builder.http2_adaptive_window(false);
+        builder.http2_initial_connection_window_size(H2_INITIAL_WINDOW_SIZE);
+        builder.http2_initial_stream_window_size(H2_INITIAL_WINDOW_SIZE);
+        builder.http2_keep_alive_interval(H2_KEEP_ALIVE_INTERVAL);
+        builder.http2_keep_alive_timeout(H2_KEEP_ALIVE_TIMEOUT);
+        builder.http2_keep_alive_while_idle(true);
+        builder.build(connector)
     }
 
     fn reqwest_client_for_proxy(
@@ -344,6 +685,8 @@ impl HttpTransport {
             self.reqwest_timeout,
             self.reqwest_use_env_proxy,
             Some(proxy_url),
+            None,
+            &self.resolver,
         )
         .map(Arc::new)?;
 
@@ -479,12 +822,31 @@ impl HttpTransport {
         proxy_url: Option<&str>,
         preconfigured_proxy_client: Option<&reqwest::Client>,
     ) -> Result<reqwest::Response, CanonicalError> {
-        let dynamic_client = if preconfigured_proxy_client.is_none() {
+        let inline_files = if self.inline_file_uploaders.is_empty() {
+            None
+        } else {
+            self.extract_inline_files(url.as_str(), &method, headers, &body, proxy_url)
+                .await
+        };
+        let (headers, body) = match inline_files.as_ref() {
+            Some((headers, body)) => (headers, body.clone()),
+            None => (headers, body),
+        };
+        let body = if self.service_tier_endpoints.is_empty() {
+            body
+        } else {
+            self.apply_service_tier(url.as_str(), body)
+        };
+        let upstream_client = self
+            .upstream_clients_for(url.as_str())
+            .and_then(|clients| clients.reqwest_client(proxy_url));
+        let dynamic_client = if upstream_client.is_none() && preconfigured_proxy_client.is_none() {
             Some(self.reqwest_client_for_proxy(proxy_url)?)
         } else {
             None
         };
-        let client = preconfigured_proxy_client
+        let client = upstream_client
+            .or(preconfigured_proxy_client)
             .or(dynamic_client.as_deref())
             .ok_or_else(|| {
                 CanonicalError::Transport("No HTTP client available for upstream request".into())
@@ -493,12 +855,31 @@ impl HttpTransport {
         loop {
             let mut request = reqwest::Request::new(method.clone(), url.clone());
             *request.headers_mut() = headers.clone();
+            if !self.request_signers.is_empty() {
+                self.sign_request(
+                    url.as_str(),
+                    &url[url::Position::BeforePath..],
+                    &method,
+                    request.headers_mut(),
+                    &body,
+                );
+            }
             *request.body_mut() = Some(reqwest::Body::from(body.clone()));
 
+            if let Some(message) = self.chaos.as_ref().and_then(Chaos::connect_failure) {
+                if attempt >= RETRY_MAX_ATTEMPTS {
+                    return Err(transport_error(message, false));
+                }
+                tokio::time::sleep(retry_transport_delay(&message, attempt)).await;
+                attempt += 1;
+                note_retry();
+                continue;
+            }
+
             match client.execute(request).await {
                 Ok(response) => {
                     if attempt < RETRY_MAX_ATTEMPTS
-                        && should_retry_upstream_status(response.status())
+                        && should_retry_upstream_response(response.status(), response.headers())
                     {
                         let delay = retry_delay(response.headers(), attempt);
                         tracing::debug!(
@@ -510,14 +891,22 @@ impl HttpTransport {
                         drop(response);
                         tokio::time::sleep(delay).await;
                         attempt += 1;
+                        note_retry();
                         continue;
                     }
+                    if response.status().is_success() {
+                        note_response_headers(response.headers());
+                    }
+                    if let Some(chaos) = &self.chaos {
+                        chaos.delay_first_byte().await;
+                        return Ok(chaos.truncate_stream(response));
+                    }
                     return Ok(response);
                 }
                 Err(err) => {
                     let message = err.to_string();
                     if attempt >= RETRY_MAX_ATTEMPTS || !should_retry_transport_message(&message) {
-                        return Err(CanonicalError::Transport(message));
+                        return Err(transport_error(message, err.is_timeout()));
                     }
 
                     let delay = retry_transport_delay(&message, attempt);
@@ -529,6 +918,7 @@ impl HttpTransport {
                     );
                     tokio::time::sleep(delay).await;
                     attempt += 1;
+                    note_retry();
                 }
             }
         }
@@ -643,8 +1033,35 @@ impl HttpTransport {
             Https(&'a HyperPassthroughHttpsClient),
         }
 
+        // Signers, dedicated upstream clients, inline file uploaders and
+        // service tiers are matched by URL; skip formatting it when none is
+        // configured.
+        let uri_string = (!self.request_signers.is_empty()
+            || !self.upstream_clients.is_empty()
+            || !self.inline_file_uploaders.is_empty()
+            || !self.service_tier_endpoints.is_empty())
+        .then(|| uri.to_string());
+        let inline_files = match uri_string.as_deref() {
+            Some(uri) if !self.inline_file_uploaders.is_empty() => {
+                self.extract_inline_files(uri, &method, headers, &body, None)
+                    .await
+            }
+            _ => None,
+        };
+        let (headers, body) = match (inline_files.as_ref(), uri_string.as_deref()) {
+            (Some((headers, body)), _) => (headers, body.clone()),
+            (None, Some(uri)) if !self.service_tier_endpoints.is_empty() => {
+                (headers, self.apply_service_tier(uri, body))
+            }
+            (None, _) => (headers, body),
+        };
+        let upstream = uri_string
+            .as_deref()
+            .and_then(|uri| self.upstream_clients_for(uri));
         let client = if uri.scheme_str() == Some("http") {
-            let http_client = if self.hyper_passthrough_force_h2c_upstream {
+            let http_client = if let Some(upstream) = upstream {
+                self.upstream_hyper_http_client(upstream)
+            } else if self.hyper_passthrough_force_h2c_upstream {
                 self.hyper_passthrough_h2c_client()
             } else {
                 self.hyper_passthrough_http_client()
@@ -656,7 +1073,11 @@ impl HttpTransport {
             };
             HyperClientRef::Http(client)
         } else {
-            let Some(client) = self.hyper_passthrough_https_client() else {
+            let https_client = match upstream {
+                Some(upstream) => self.upstream_hyper_https_client(upstream),
+                None => self.hyper_passthrough_https_client(),
+            };
+            let Some(client) = https_client else {
                 return Err(CanonicalError::Transport(
                     "Hyper passthrough client is disabled".to_string(),
                 ));
@@ -670,6 +1091,26 @@ impl HttpTransport {
             *request.method_mut() = method.clone();
             *request.uri_mut() = uri.clone();
             *request.headers_mut() = headers.clone();
+            if let Some(uri_string) = &uri_string {
+                self.sign_request(
+                    uri_string,
+                    uri.path_and_query()
+                        .map_or("/", http::uri::PathAndQuery::as_str),
+                    &method,
+                    request.headers_mut(),
+                    &body,
+                );
+            }
+
+            if let Some(message) = self.chaos.as_ref().and_then(Chaos::connect_failure) {
+                if attempt >= RETRY_MAX_ATTEMPTS {
+                    return Err(transport_error(message, false));
+                }
+                tokio::time::sleep(retry_transport_delay(&message, attempt)).await;
+                attempt += 1;
+                note_retry();
+                continue;
+            }
 
             let result = match client {
                 HyperClientRef::Http(client) => client.request(request).await,
@@ -679,7 +1120,7 @@ impl HttpTransport {
             match result {
                 Ok(response) => {
                     if attempt < RETRY_MAX_ATTEMPTS
-                        && should_retry_upstream_status(response.status())
+                        && should_retry_upstream_response(response.status(), response.headers())
                     {
                         let delay = retry_delay(response.headers(), attempt);
                         tracing::debug!(
@@ -691,14 +1132,21 @@ impl HttpTransport {
                         drop(response);
                         tokio::time::sleep(delay).await;
                         attempt += 1;
+                        note_retry();
                         continue;
                     }
+                    if response.status().is_success() {
+                        note_response_headers(response.headers());
+                    }
+                    if let Some(chaos) = &self.chaos {
+                        chaos.delay_first_byte().await;
+                    }
                     return Ok(response);
                 }
                 Err(err) => {
                     let message = err.to_string();
                     if attempt >= RETRY_MAX_ATTEMPTS || !should_retry_transport_message(&message) {
-                        return Err(CanonicalError::Transport(message));
+                        return Err(transport_error(message, false));
                     }
 
                     let delay = retry_transport_delay(&message, attempt);
@@ -710,6 +1158,7 @@ impl HttpTransport {
                     );
                     tokio::time::sleep(delay).await;
                     attempt += 1;
+                    note_retry();
                 }
             }
         }
@@ -764,6 +1213,44 @@ impl HttpTransport {
     ) -> Result<http::Response<Incoming>, CanonicalError> {
         self.send_request_uri_str(url, method, headers, body).await
     }
+
+    /// Open a pooled connection (and H2 session) to `url` through the client
+    /// request traffic would use, by sending a bodyless `GET` and draining the
+    /// response so the connection returns to the pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CanonicalError::Transport`] when the upstream cannot be reached.
+    pub async fn warm_up(
+        &self,
+        url: &str,
+        proxy_url: Option<&str>,
+    ) -> Result<http::StatusCode, CanonicalError> {
+        use http_body_util::BodyExt as _;
+
+        let headers = http::HeaderMap::new();
+        if self.hyper_passthrough_enabled_for(proxy_url) {
+            let response = self
+                .send_request_uri_str(url, http::Method::GET, &headers, bytes::Bytes::new())
+                .await?;
+            let status = response.status();
+            let _ = response.into_body().collect().await;
+            return Ok(status);
+        }
+        let response = self
+            .send_request_with_client(
+                url,
+                http::Method::GET,
+                &headers,
+                bytes::Bytes::new(),
+                proxy_url,
+                self.preconfigured_proxy_client(proxy_url),
+            )
+            .await?;
+        let status = response.status();
+        let _ = response.bytes().await;
+        Ok(status)
+    }
 }
 
 #[cfg(test)]
@@ -861,6 +1348,134 @@ mod tests {
         assert_eq!(transport.dynamic_proxy_clients.read().len(), 1);
     }
 
+    async fn spawn_sse_upstream(body: &'static str) -> String {
+        let app = axum::Router::new().route(
+            "/v1/chat/completions",
+            axum::routing::post(move || async move {
+                ([(http::header::CONTENT_TYPE, "text/event-stream")], body)
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        format!("http://{addr}/v1/chat/completions")
+    }
+
+    fn chaos_transport(chaos: crate::config::ChaosConfig) -> HttpTransport {
+        HttpTransport::new(&ServerConfig {
+            chaos: Some(chaos),
+            ..ServerConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_chaos_connect_failures_exhaust_retries() {
+        let url = spawn_sse_upstream("data: ok\n\n").await;
+        let transport = chaos_transport(crate::config::ChaosConfig {
+            connect_failure_rate: 1.0,
+            ..Default::default()
+        });
+        let headers = http::HeaderMap::new();
+        let err = transport
+            .send_request(
+                &url,
+                http::Method::POST,
+                &headers,
+                bytes::Bytes::new(),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CanonicalError::Transport(message) if message.contains("chaos")));
+        let err = transport
+            .send_request_uri_str(&url, http::Method::POST, &headers, bytes::Bytes::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CanonicalError::Transport(_)));
+    }
+
+    #[tokio::test]
+    async fn test_chaos_truncates_sse_streams() {
+        let url = spawn_sse_upstream("data: first\n\ndata: second\n\ndata: [DONE]\n\n").await;
+        let transport = chaos_transport(crate::config::ChaosConfig {
+            truncate_stream_rate: 1.0,
+            truncate_after_bytes: 13,
+            ..Default::default()
+        });
+        assert!(!transport.hyper_passthrough_enabled());
+
+        let response = transport
+            .send_stream(
+                &url,
+                http::Method::POST,
+                &http::HeaderMap::new(),
+                bytes::Bytes::new(),
+                None,
+            )
+            .await
+            .unwrap();
+        let mut stream = response.bytes_stream();
+        let mut received = Vec::new();
+        let mut failed = false;
+        while let Some(chunk) = futures_util::StreamExt::next(&mut stream).await {
+            match chunk {
+                Ok(chunk) => received.extend_from_slice(&chunk),
+                Err(_) => failed = true,
+            }
+        }
+        assert_eq!(received, b"data: first\n\n");
+        assert!(failed);
+    }
+
+    #[tokio::test]
+    async fn test_bind_address_sets_connection_source() {
+        use axum::extract::ConnectInfo;
+
+        let app = axum::Router::new().route(
+            "/v1/models",
+            axum::routing::get(
+                |ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>| async move {
+                    peer.ip().to_string()
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+            )
+            .await;
+        });
+        let upstream: UpstreamServiceConfig = serde_yaml::from_str(&format!(
+            "{{name: bound, base_url: 'http://{addr}/v1', api_key: k, models: [m], bind_address: 127.0.0.2}}"
+        ))
+        .unwrap();
+        let transport = HttpTransport::new(&ServerConfig::default())
+            .with_upstream_clients(std::slice::from_ref(&upstream), &[]);
+        let url = format!("http://{addr}/v1/models");
+        let headers = http::HeaderMap::new();
+
+        let response = transport
+            .send_request(&url, http::Method::GET, &headers, bytes::Bytes::new(), None)
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "127.0.0.2");
+
+        let response = transport
+            .send_request_uri_str(&url, http::Method::GET, &headers, bytes::Bytes::new())
+            .await
+            .unwrap();
+        let body = http_body_util::BodyExt::collect(response.into_body())
+            .await
+            .unwrap()
+            .to_bytes();
+        assert_eq!(body.as_ref(), b"127.0.0.2");
+    }
+
     #[test]
     fn test_preconfigured_proxy_client_hit() {
         let transport = HttpTransport::new_with_upstream_count_and_proxies(
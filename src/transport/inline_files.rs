@@ -0,0 +1,631 @@
+//! Inline file extraction for upstreams with a files API.
+//!
+//! Base64 payloads of at least `min_bytes` in a request body are uploaded to
+//! the upstream's files API and the body references the stored file instead:
+//! Anthropic `source.type: file`, Gemini `fileData`, and Responses `file_id`.
+//! Uploads are cached by content hash, so a conversation that resends the
+//! same image uploads it once. A failed upload leaves the payload inline.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use ring::digest;
+use rustc_hash::FxHashMap;
+use serde_json::{json, Map, Value};
+
+use crate::config::{InlineFileUploadConfig, UpstreamServiceConfig};
+use crate::error::CanonicalError;
+use crate::protocol::canonical::ProviderKind;
+use crate::util::base64_decode;
+
+use super::anthropic_beta::ANTHROPIC_BETA;
+use super::prepared_upstream::upstream_base_url;
+
+/// Beta flag Anthropic requires for `source.type: file` and `/v1/files`.
+pub(crate) const ANTHROPIC_FILES_BETA: &str = "files-api-2025-04-14";
+const UPLOAD_CACHE_MAX_ENTRIES: usize = 4096;
+const MULTIPART_BOUNDARY: &str = "toolify-inline-file-boundary";
+
+/// A files API request for the transport to send.
+#[derive(Debug)]
+pub(crate) struct UploadRequest {
+    pub(crate) url: String,
+    pub(crate) content_type: String,
+    pub(crate) headers: Vec<(&'static str, &'static str)>,
+    pub(crate) body: bytes::Bytes,
+}
+
+/// Where an inline payload sits, by provider wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Site {
+    /// `{"type":"image"|"document","source":{"type":"base64"|"url",...}}`
+    AnthropicSource,
+    /// `{"inlineData":{"mimeType":...,"data":...}}`
+    GeminiInlineData(&'static str),
+    /// `{"type":"input_image","image_url":"data:..."}`
+    ResponsesImage,
+    /// `{"type":"input_file","file_data":"data:..."}`
+    ResponsesFile,
+}
+
+/// Uploads inline payloads sent under one upstream's base URL.
+pub(crate) struct InlineFileUploader {
+    base_url: String,
+    provider: ProviderKind,
+    min_bytes: usize,
+    ttl: Duration,
+    uploaded: parking_lot::Mutex<FxHashMap<[u8; 32], (String, Instant)>>,
+}
+
+impl InlineFileUploader {
+    /// The uploader for `upstream`, when `config` covers it and its provider
+    /// accepts file references.
+    #[must_use]
+    pub(crate) fn for_upstream(
+        upstream: &UpstreamServiceConfig,
+        config: &InlineFileUploadConfig,
+    ) -> Option<Self> {
+        let provider = match upstream.provider.as_str() {
+            "anthropic" => ProviderKind::Anthropic,
+            "gemini" => ProviderKind::Gemini,
+            "openai-responses" => ProviderKind::OpenAiResponses,
+            _ => return None,
+        };
+        if !config.upstreams.is_empty() && !config.upstreams.contains(&upstream.name) {
+            return None;
+        }
+        Some(Self {
+            base_url: upstream_base_url(upstream).into_owned(),
+            provider,
+            min_bytes: config.min_bytes,
+            ttl: Duration::from_secs(config.cache_ttl_secs),
+            uploaded: parking_lot::Mutex::new(FxHashMap::default()),
+        })
+    }
+
+    /// Whether `url` lies under this uploader's upstream base URL.
+    #[must_use]
+    pub(crate) fn covers(&self, url: &str) -> bool {
+        url.strip_prefix(self.base_url.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?', ':']))
+    }
+
+    /// Whether a request may carry a payload worth uploading.
+    #[must_use]
+    pub(crate) fn wants(&self, method: &http::Method, body: &[u8]) -> bool {
+        *method == http::Method::POST
+            && body.len() >= self.min_bytes
+            && body.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{')
+    }
+
+    /// Headers for the rewritten request: Anthropic needs the files beta.
+    #[must_use]
+    pub(crate) fn request_headers(&self, headers: &http::HeaderMap) -> http::HeaderMap {
+        let mut headers = headers.clone();
+        if self.provider == ProviderKind::Anthropic {
+            add_anthropic_files_beta(&mut headers);
+        }
+        headers
+    }
+
+    /// Rewrite `body` to reference uploaded files, sending each upload via
+    /// `upload`. Returns `None` when nothing was replaced.
+    pub(crate) async fn rewrite<F, Fut>(&self, body: &[u8], mut upload: F) -> Option<bytes::Bytes>
+    where
+        F: FnMut(UploadRequest) -> Fut,
+        Fut: Future<Output = Result<bytes::Bytes, CanonicalError>>,
+    {
+        let mut value: Value = serde_json::from_slice(body).ok()?;
+        let mut sites = Vec::new();
+        collect_sites(
+            &value,
+            self.provider,
+            self.min_bytes,
+            &mut String::new(),
+            &mut sites,
+        );
+        if sites.is_empty() {
+            return None;
+        }
+
+        let mut replaced = false;
+        for (pointer, site) in sites {
+            let Some((mime_type, data)) = value
+                .pointer(&pointer)
+                .and_then(Value::as_object)
+                .and_then(|object| inline_payload(object, site))
+            else {
+                continue;
+            };
+            let key = content_key(&mime_type, &data);
+            let reference = match self.cached(&key) {
+                Some(reference) => reference,
+                None => match self.upload(&mime_type, &data, &mut upload).await {
+                    Ok(reference) => {
+                        self.remember(key, reference.clone());
+                        reference
+                    }
+                    Err(err) => {
+                        tracing::warn!(
+                            upstream = %self.base_url,
+                            error = %err,
+                            "inline file upload failed; sending the payload inline"
+                        );
+                        continue;
+                    }
+                },
+            };
+            if let Some(object) = value.pointer_mut(&pointer).and_then(Value::as_object_mut) {
+                replace_site(object, site, &mime_type, reference);
+                replaced = true;
+            }
+        }
+        if !replaced {
+            return None;
+        }
+        serde_json::to_vec(&value).ok().map(bytes::Bytes::from)
+    }
+
+    fn cached(&self, key: &[u8; 32]) -> Option<String> {
+        let uploaded = self.uploaded.lock();
+        uploaded
+            .get(key)
+            .filter(|(_, at)| at.elapsed() < self.ttl)
+            .map(|(reference, _)| reference.clone())
+    }
+
+    fn remember(&self, key: [u8; 32], reference: String) {
+        let mut uploaded = self.uploaded.lock();
+        if uploaded.len() >= UPLOAD_CACHE_MAX_ENTRIES {
+            uploaded.retain(|_, (_, at)| at.elapsed() < self.ttl);
+            if uploaded.len() >= UPLOAD_CACHE_MAX_ENTRIES {
+                uploaded.clear();
+            }
+        }
+        uploaded.insert(key, (reference, Instant::now()));
+    }
+
+    async fn upload<F, Fut>(
+        &self,
+        mime_type: &str,
+        data: &str,
+        upload: &mut F,
+    ) -> Result<String, CanonicalError>
+    where
+        F: FnMut(UploadRequest) -> Fut,
+        Fut: Future<Output = Result<bytes::Bytes, CanonicalError>>,
+    {
+        let bytes = base64_decode(data).ok_or_else(|| {
+            CanonicalError::InvalidRequest("Inline file data is not valid base64".to_string())
+        })?;
+        let response = upload(self.upload_request(mime_type, &bytes)).await?;
+        let response: Value = serde_json::from_slice(&response)
+            .map_err(|e| CanonicalError::Transport(format!("Invalid files API response: {e}")))?;
+        let reference = match self.provider {
+            ProviderKind::Gemini => response.pointer("/file/uri"),
+            _ => response.get("id"),
+        };
+        reference
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                CanonicalError::Transport("Files API response has no file reference".to_string())
+            })
+    }
+
+    fn upload_request(&self, mime_type: &str, bytes: &[u8]) -> UploadRequest {
+        let filename = format!("inline.{}", file_extension(mime_type));
+        match self.provider {
+            ProviderKind::Gemini => {
+                let metadata = json!({ "file": { "displayName": filename } }).to_string();
+                let mut body = Vec::with_capacity(bytes.len() + metadata.len() + 256);
+                body.extend_from_slice(
+                    format!(
+                        "--{MULTIPART_BOUNDARY}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{metadata}\r\n--{MULTIPART_BOUNDARY}\r\nContent-Type: {mime_type}\r\n\r\n"
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(bytes);
+                body.extend_from_slice(format!("\r\n--{MULTIPART_BOUNDARY}--\r\n").as_bytes());
+                UploadRequest {
+                    url: gemini_upload_url(&self.base_url),
+                    content_type: format!("multipart/related; boundary={MULTIPART_BOUNDARY}"),
+                    headers: vec![("x-goog-upload-protocol", "multipart")],
+                    body: bytes::Bytes::from(body),
+                }
+            }
+            provider => {
+                let mut body = Vec::with_capacity(bytes.len() + 512);
+                if provider == ProviderKind::OpenAiResponses {
+                    let purpose = if mime_type.starts_with("image/") {
+                        "vision"
+                    } else {
+                        "user_data"
+                    };
+                    body.extend_from_slice(
+                        format!(
+                            "--{MULTIPART_BOUNDARY}\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\n{purpose}\r\n"
+                        )
+                        .as_bytes(),
+                    );
+                }
+                body.extend_from_slice(
+                    format!(
+                        "--{MULTIPART_BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\nContent-Type: {mime_type}\r\n\r\n"
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(bytes);
+                body.extend_from_slice(format!("\r\n--{MULTIPART_BOUNDARY}--\r\n").as_bytes());
+                let headers = if provider == ProviderKind::Anthropic {
+                    vec![(ANTHROPIC_BETA, ANTHROPIC_FILES_BETA)]
+                } else {
+                    Vec::new()
+                };
+                UploadRequest {
+                    url: format!("{}/files", self.base_url),
+                    content_type: format!("multipart/form-data; boundary={MULTIPART_BOUNDARY}"),
+                    headers,
+                    body: bytes::Bytes::from(body),
+                }
+            }
+        }
+    }
+}
+
+/// Append the files beta to `headers`' `anthropic-beta` list.
+pub(crate) fn add_anthropic_files_beta(headers: &mut http::HeaderMap) {
+    let existing = headers
+        .get(ANTHROPIC_BETA)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if existing
+        .split(',')
+        .any(|beta| beta.trim() == ANTHROPIC_FILES_BETA)
+    {
+        return;
+    }
+    let combined = if existing.is_empty() {
+        ANTHROPIC_FILES_BETA.to_string()
+    } else {
+        format!("{existing},{ANTHROPIC_FILES_BETA}")
+    };
+    if let Ok(value) = http::HeaderValue::from_str(&combined) {
+        headers.insert(ANTHROPIC_BETA, value);
+    }
+}
+
+/// `https://host/v1beta` -> `https://host/upload/v1beta/files`.
+fn gemini_upload_url(base_url: &str) -> String {
+    let path_start = base_url
+        .find("://")
+        .and_then(|scheme_end| {
+            base_url[scheme_end + 3..]
+                .find('/')
+                .map(|offset| scheme_end + 3 + offset)
+        })
+        .unwrap_or(base_url.len());
+    let (origin, path) = base_url.split_at(path_start);
+    format!("{origin}/upload{path}/files")
+}
+
+fn file_extension(mime_type: &str) -> &str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "application/pdf" => "pdf",
+        "text/plain" => "txt",
+        _ => mime_type
+            .split_once('/')
+            .map_or("bin", |(_, subtype)| subtype)
+            .split(['+', ';'])
+            .next()
+            .unwrap_or("bin"),
+    }
+}
+
+fn content_key(mime_type: &str, data: &str) -> [u8; 32] {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(mime_type.as_bytes());
+    context.update(&[0]);
+    context.update(data.as_bytes());
+    let mut key = [0_u8; 32];
+    key.copy_from_slice(context.finish().as_ref());
+    key
+}
+
+/// `data:{mime};base64,{data}` -> `(mime, data)`.
+fn parse_data_url(url: &str) -> Option<(&str, &str)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+    Some((mime_type, data))
+}
+
+fn site_of(object: &Map<String, Value>, provider: ProviderKind) -> Option<Site> {
+    let kind = object.get("type").and_then(Value::as_str);
+    match provider {
+        ProviderKind::Anthropic => matches!(kind, Some("image" | "document"))
+            .then_some(Site::AnthropicSource)
+            .filter(|_| object.get("source").is_some_and(Value::is_object)),
+        ProviderKind::Gemini => ["inlineData", "inline_data"]
+            .into_iter()
+            .find(|key| object.get(*key).is_some_and(Value::is_object))
+            .map(Site::GeminiInlineData),
+        ProviderKind::OpenAiResponses => match kind {
+            Some("input_image") => Some(Site::ResponsesImage),
+            Some("input_file") => Some(Site::ResponsesFile),
+            _ => None,
+        },
+        ProviderKind::OpenAi | ProviderKind::GeminiOpenAi => None,
+    }
+}
+
+/// The `(mime_type, base64)` payload held at `site`, if inline.
+fn inline_payload(object: &Map<String, Value>, site: Site) -> Option<(String, String)> {
+    let (mime_type, data) = match site {
+        Site::AnthropicSource => {
+            let source = object.get("source")?;
+            match source.get("type").and_then(Value::as_str)? {
+                "base64" => (
+                    source.get("media_type").and_then(Value::as_str)?,
+                    source.get("data").and_then(Value::as_str)?,
+                ),
+                "url" => parse_data_url(source.get("url").and_then(Value::as_str)?)?,
+                _ => return None,
+            }
+        }
+        Site::GeminiInlineData(key) => {
+            let inline = object.get(key)?;
+            (
+                inline
+                    .get("mimeType")
+                    .or_else(|| inline.get("mime_type"))
+                    .and_then(Value::as_str)?,
+                inline.get("data").and_then(Value::as_str)?,
+            )
+        }
+        Site::ResponsesImage => {
+            let image_url = object.get("image_url")?;
+            let url = image_url
+                .as_str()
+                .or_else(|| image_url.get("url").and_then(Value::as_str))?;
+            parse_data_url(url)?
+        }
+        Site::ResponsesFile => parse_data_url(object.get("file_data").and_then(Value::as_str)?)?,
+    };
+    Some((mime_type.to_string(), data.to_string()))
+}
+
+fn replace_site(object: &mut Map<String, Value>, site: Site, mime_type: &str, reference: String) {
+    match site {
+        Site::AnthropicSource => {
+            object.insert(
+                "source".to_string(),
+                json!({ "type": "file", "file_id": reference }),
+            );
+        }
+        Site::GeminiInlineData(key) => {
+            object.remove(key);
+            object.insert(
+                "fileData".to_string(),
+                json!({ "mimeType": mime_type, "fileUri": reference }),
+            );
+        }
+        Site::ResponsesImage => {
+            object.remove("image_url");
+            object.insert("file_id".to_string(), Value::String(reference));
+        }
+        Site::ResponsesFile => {
+            object.remove("file_data");
+            object.remove("filename");
+            object.insert("file_id".to_string(), Value::String(reference));
+        }
+    }
+}
+
+/// Collect JSON pointers to every object holding an inline payload of at
+/// least `min_bytes`.
+fn collect_sites(
+    value: &Value,
+    provider: ProviderKind,
+    min_bytes: usize,
+    pointer: &mut String,
+    sites: &mut Vec<(String, Site)>,
+) {
+    let len = pointer.len();
+    match value {
+        Value::Object(object) => {
+            if let Some(site) = site_of(object, provider) {
+                if inline_payload(object, site).is_some_and(|(_, data)| data.len() >= min_bytes) {
+                    sites.push((pointer.clone(), site));
+                    return;
+                }
+            }
+            for (key, child) in object {
+                pointer.push('/');
+                pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                collect_sites(child, provider, min_bytes, pointer, sites);
+                pointer.truncate(len);
+            }
+        }
+        Value::Array(items) => {
+            for (index, child) in items.iter().enumerate() {
+                pointer.push('/');
+                pointer.push_str(&index.to_string());
+                collect_sites(child, provider, min_bytes, pointer, sites);
+                pointer.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::{
+        FcMode, PathStyle, StreamSupport, ToolSchemaValidation, UpstreamAuthConfig,
+    };
+
+    const PNG: &str = "aGVsbG8gd29ybGQ=";
+
+    fn uploader(provider: &str, base_url: &str) -> InlineFileUploader {
+        let upstream = UpstreamServiceConfig {
+            name: "u".to_string(),
+            provider: provider.to_string(),
+            base_url: base_url.to_string(),
+            api_key: "k".to_string(),
+            models: Vec::new(),
+            description: String::new(),
+            is_default: true,
+            fc_mode: FcMode::Native,
+            api_version: None,
+            proxy: None,
+            proxy_stream: None,
+            proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
+        };
+        let config = InlineFileUploadConfig {
+            min_bytes: 8,
+            upstreams: Vec::new(),
+            cache_ttl_secs: 60,
+        };
+        InlineFileUploader::for_upstream(&upstream, &config).expect("eligible upstream")
+    }
+
+    async fn rewrite(
+        uploader: &InlineFileUploader,
+        body: &Value,
+        response: &str,
+        requests: &mut Vec<UploadRequest>,
+    ) -> Value {
+        let body = serde_json::to_vec(body).unwrap();
+        let rewritten = uploader
+            .rewrite(&body, |request| {
+                requests.push(request);
+                let response = bytes::Bytes::from(response.to_string());
+                async move { Ok(response) }
+            })
+            .await
+            .expect("body rewritten");
+        serde_json::from_slice(&rewritten).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_sources_are_uploaded_once_and_referenced() {
+        let uploader = uploader("anthropic", "http://upstream/v1");
+        let image = json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": "image/png", "data": PNG }
+        });
+        let body = json!({
+            "model": "claude",
+            "messages": [
+                { "role": "user", "content": [image.clone(), { "type": "text", "text": "hi" }] },
+                { "role": "user", "content": [{
+                    "type": "image",
+                    "source": { "type": "url", "url": format!("data:image/png;base64,{PNG}") }
+                }] }
+            ]
+        });
+        let mut requests = Vec::new();
+        let rewritten = rewrite(&uploader, &body, r#"{"id":"file_1"}"#, &mut requests).await;
+
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].url, "http://upstream/v1/files");
+        assert_eq!(
+            requests[0].headers,
+            [(ANTHROPIC_BETA, ANTHROPIC_FILES_BETA)]
+        );
+        let upload = String::from_utf8_lossy(&requests[0].body);
+        assert!(upload.contains("filename=\"inline.png\""));
+        assert!(upload.contains("hello world"));
+        let file_source = json!({ "type": "file", "file_id": "file_1" });
+        assert_eq!(
+            rewritten["messages"][0]["content"][0]["source"],
+            file_source
+        );
+        assert_eq!(rewritten["messages"][0]["content"][1]["text"], "hi");
+        assert_eq!(
+            rewritten["messages"][1]["content"][0]["source"],
+            file_source
+        );
+
+        let headers = uploader.request_headers(&http::HeaderMap::new());
+        assert_eq!(headers[ANTHROPIC_BETA], ANTHROPIC_FILES_BETA);
+    }
+
+    #[tokio::test]
+    async fn test_gemini_and_responses_payloads_become_file_references() {
+        let gemini = uploader("gemini", "https://host/v1beta");
+        let body = json!({
+            "contents": [{ "role": "user", "parts": [
+                { "inlineData": { "mimeType": "image/jpeg", "data": PNG } },
+                { "inlineData": { "mimeType": "image/jpeg", "data": "c20=" } }
+            ] }]
+        });
+        let mut requests = Vec::new();
+        let rewritten = rewrite(
+            &gemini,
+            &body,
+            r#"{"file":{"uri":"https://host/v1beta/files/abc"}}"#,
+            &mut requests,
+        )
+        .await;
+        assert_eq!(requests[0].url, "https://host/upload/v1beta/files");
+        assert_eq!(
+            rewritten["contents"][0]["parts"][0],
+            json!({ "fileData": { "mimeType": "image/jpeg", "fileUri": "https://host/v1beta/files/abc" } })
+        );
+        assert_eq!(
+            rewritten["contents"][0]["parts"][1]["inlineData"]["data"], "c20=",
+            "payloads under min_bytes stay inline"
+        );
+
+        let responses = uploader("openai-responses", "http://upstream/v1");
+        let body = json!({
+            "input": [{ "role": "user", "content": [{
+                "type": "input_image",
+                "image_url": format!("data:image/png;base64,{PNG}"),
+                "detail": "high"
+            }] }]
+        });
+        let mut requests = Vec::new();
+        let rewritten = rewrite(&responses, &body, r#"{"id":"file-9"}"#, &mut requests).await;
+        assert!(String::from_utf8_lossy(&requests[0].body).contains("vision"));
+        assert_eq!(
+            rewritten["input"][0]["content"][0],
+            json!({ "type": "input_image", "file_id": "file-9", "detail": "high" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_upload_leaves_body_unchanged() {
+        let uploader = uploader("anthropic", "http://upstream/v1");
+        let body = serde_json::to_vec(&json!({
+            "messages": [{ "role": "user", "content": [{
+                "type": "image",
+                "source": { "type": "base64", "media_type": "image/png", "data": PNG }
+            }] }]
+        }))
+        .unwrap();
+        let rewritten = uploader
+            .rewrite(&body, |_| async {
+                Err(CanonicalError::Transport("unreachable".to_string()))
+            })
+            .await;
+        assert!(rewritten.is_none());
+        assert!(uploader.covers("http://upstream/v1/messages"));
+        assert!(!uploader.covers("http://upstream/v10/messages"));
+    }
+}
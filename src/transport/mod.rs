@@ -1,9 +1,21 @@
+pub(crate) mod anthropic_beta;
+mod chaos;
+mod dns_cache;
 mod http_transport;
+mod inline_files;
+pub(crate) mod openai_organization;
 mod prepared_upstream;
+pub(crate) mod proxy_override;
+mod request_signing;
+pub(crate) mod response_headers;
 mod retry_policy;
+pub(crate) mod service_tier;
+mod upstream_clients;
 
 pub use http_transport::HttpTransport;
 pub use prepared_upstream::{
     build_provider_headers_prepared, build_upstream_url_prepared, static_parsed_upstream_uri,
-    static_parsed_upstream_url, PreparedUpstream,
+    static_parsed_upstream_url, upstream_base_url, PreparedUpstream,
 };
+pub(crate) use retry_policy::{note_retry, parse_retry_after_secs};
+pub use service_tier::RequestPriority;
@@ -0,0 +1,50 @@
+//! Per-request `OpenAI-Organization` passthrough.
+//!
+//! The compat flow passes the organization the client sent down in its
+//! request context. Only upstreams with `trust_client_organization` forward
+//! it, replacing their configured `organization`; every other upstream keeps
+//! its static headers.
+
+use crate::state::request_context;
+
+pub(crate) const OPENAI_ORGANIZATION: &str = "openai-organization";
+pub(crate) const OPENAI_PROJECT: &str = "openai-project";
+
+/// The client's non-empty `OpenAI-Organization` header.
+#[must_use]
+pub(crate) fn client_openai_organization(headers: &http::HeaderMap) -> Option<http::HeaderValue> {
+    headers
+        .get(OPENAI_ORGANIZATION)
+        .filter(|value| !value.as_bytes().trim_ascii().is_empty())
+        .cloned()
+}
+
+/// The organization the client sent for the request being served, if any.
+#[must_use]
+pub(crate) fn scoped_client_openai_organization() -> Option<http::HeaderValue> {
+    request_context::with(|context| context.openai_organization.clone()).flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scoped_client_organization() {
+        let mut headers = http::HeaderMap::new();
+        assert!(client_openai_organization(&headers).is_none());
+        headers.insert(OPENAI_ORGANIZATION, http::HeaderValue::from_static(" "));
+        assert!(client_openai_organization(&headers).is_none());
+        headers.insert(OPENAI_ORGANIZATION, http::HeaderValue::from_static("org-1"));
+
+        assert!(scoped_client_openai_organization().is_none());
+        let context = request_context::RequestContext {
+            openai_organization: client_openai_organization(&headers),
+            ..Default::default()
+        };
+        request_context::scope(context, async {
+            assert_eq!(scoped_client_openai_organization().unwrap(), "org-1");
+        })
+        .await;
+    }
+}
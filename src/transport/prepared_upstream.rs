@@ -1,13 +1,55 @@
 use std::borrow::Cow;
-
-use crate::config::UpstreamServiceConfig;
+use std::sync::Arc;
+
+use super::anthropic_beta::{negotiated_anthropic_beta, ANTHROPIC_BETA};
+use super::openai_organization::{
+    scoped_client_openai_organization, OPENAI_ORGANIZATION, OPENAI_PROJECT,
+};
+use super::proxy_override;
+use crate::config::{PathStyle, UpstreamAuthScheme, UpstreamServiceConfig};
 use crate::protocol::canonical::ProviderKind;
 use rustc_hash::{FxHashMap, FxHashSet};
 
+/// Endpoint paths users paste into `base_url` by mistake.
+const ENDPOINT_SUFFIXES: [&str; 4] = ["/chat/completions", "/responses", "/messages", "/models"];
+
+/// The base every endpoint path of `upstream` is appended to: `base_url`
+/// without a trailing slash, adjusted per its `path_style`.
+///
+/// With [`PathStyle::Auto`] a pasted endpoint path is dropped and a bare host
+/// gets the provider's API version path. URLs with a query or fragment are
+/// never adjusted.
+#[must_use]
+pub fn upstream_base_url(upstream: &UpstreamServiceConfig) -> Cow<'_, str> {
+    let configured = upstream.base_url.trim_end_matches('/');
+    if upstream.path_style == PathStyle::AsIs || configured.contains(['?', '#']) {
+        return Cow::Borrowed(configured);
+    }
+    if let Some(base) = ENDPOINT_SUFFIXES
+        .iter()
+        .find_map(|endpoint| configured.strip_suffix(endpoint))
+    {
+        return Cow::Borrowed(base.trim_end_matches('/'));
+    }
+    let has_path = configured
+        .split_once("://")
+        .is_some_and(|(_, rest)| rest.contains('/'));
+    if has_path {
+        return Cow::Borrowed(configured);
+    }
+    let version = match upstream.provider.as_str() {
+        "gemini" => "/v1beta",
+        "gemini-openai" => "/v1beta/openai",
+        _ => "/v1",
+    };
+    Cow::Owned(format!("{configured}{version}"))
+}
+
 /// Precomputed upstream metadata used by hot request paths.
 #[derive(Debug, Clone)]
 pub struct PreparedUpstream {
     provider_kind: ProviderKind,
+    base_url: Box<str>,
     openai_chat_url: String,
     openai_chat_url_parsed: Option<url::Url>,
     openai_chat_uri_parsed: Option<http::Uri>,
@@ -25,16 +67,28 @@ pub struct PreparedUpstream {
     gemini_stream_urls_parsed: FxHashMap<String, url::Url>,
     gemini_stream_uris_parsed: FxHashMap<String, http::Uri>,
     static_headers: http::HeaderMap,
-    proxy_default: Option<String>,
-    proxy_stream: Option<String>,
-    proxy_non_stream: Option<String>,
+    /// Client `anthropic-beta` flags forwarded to this upstream.
+    anthropic_betas: Box<[String]>,
+    /// Whether the client's `OpenAI-Organization` replaces the configured one.
+    trust_client_organization: bool,
+    proxy_default: Option<Arc<str>>,
+    proxy_stream: Option<Arc<str>>,
+    proxy_non_stream: Option<Arc<str>>,
 }
 
 impl PreparedUpstream {
     /// Build a prepared upstream cache from configuration.
     #[must_use]
     pub fn new(upstream: &UpstreamServiceConfig) -> Self {
-        let base = upstream.base_url.trim_end_matches('/').to_string();
+        let base = upstream_base_url(upstream);
+        if base != upstream.base_url.trim_end_matches('/') {
+            tracing::warn!(
+                upstream = %upstream.name,
+                configured = %upstream.base_url,
+                resolved = %base,
+                "adjusted upstream base_url path; set `path_style: as_is` to use it unchanged"
+            );
+        }
         let provider_kind = match upstream.provider.as_str() {
             "openai" => ProviderKind::OpenAi,
             "openai-responses" => ProviderKind::OpenAiResponses,
@@ -96,7 +150,8 @@ impl PreparedUpstream {
 
                 for model in gemini_models {
                     let non_stream_url = format!("{gemini_model_prefix}{model}:generateContent");
-                    let stream_url = format!("{gemini_model_prefix}{model}:streamGenerateContent");
+                    let stream_url =
+                        format!("{gemini_model_prefix}{model}:streamGenerateContent?alt=sse");
 
                     if let Ok(parsed) = url::Url::parse(&non_stream_url) {
                         gemini_non_stream_urls_parsed.insert(model.clone(), parsed);
@@ -119,6 +174,7 @@ impl PreparedUpstream {
 
         Self {
             provider_kind,
+            base_url: base.into(),
             openai_chat_url,
             openai_chat_url_parsed,
             openai_chat_uri_parsed,
@@ -136,6 +192,12 @@ impl PreparedUpstream {
             gemini_stream_urls_parsed,
             gemini_stream_uris_parsed,
             static_headers,
+            anthropic_betas: if provider_kind == ProviderKind::Anthropic {
+                upstream.anthropic_betas.clone().into_boxed_slice()
+            } else {
+                Box::default()
+            },
+            trust_client_organization: upstream.trust_client_organization,
             proxy_default,
             proxy_stream,
             proxy_non_stream,
@@ -147,6 +209,12 @@ impl PreparedUpstream {
         self.provider_kind
     }
 
+    /// See [`upstream_base_url`].
+    #[must_use]
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
     #[must_use]
     pub fn openai_chat_url_parsed(&self) -> Option<&url::Url> {
         self.openai_chat_url_parsed.as_ref()
@@ -192,7 +260,7 @@ impl PreparedUpstream {
                         Cow::Borrowed(url)
                     } else {
                         Cow::Owned(format!(
-                            "{}{}:streamGenerateContent",
+                            "{}{}:streamGenerateContent?alt=sse",
                             self.gemini_model_prefix, model
                         ))
                     }
@@ -269,17 +337,19 @@ impl PreparedUpstream {
         &self.static_headers
     }
 
+    /// The proxy for a request: the routing rule's override when one is in
+    /// scope, else this upstream's stream/non-stream proxy or its default.
     #[must_use]
-    pub fn proxy_for(&self, stream: bool) -> Option<&str> {
-        if stream {
-            self.proxy_stream
-                .as_deref()
-                .or(self.proxy_default.as_deref())
-        } else {
-            self.proxy_non_stream
-                .as_deref()
-                .or(self.proxy_default.as_deref())
+    pub fn proxy_for(&self, stream: bool) -> Option<Arc<str>> {
+        if let Some(proxy) = proxy_override::current() {
+            return Some(proxy);
         }
+        let specific = if stream {
+            &self.proxy_stream
+        } else {
+            &self.proxy_non_stream
+        };
+        specific.as_ref().or(self.proxy_default.as_ref()).cloned()
     }
 
     fn build_provider_headers(upstream: &UpstreamServiceConfig) -> http::HeaderMap {
@@ -291,37 +361,85 @@ impl PreparedUpstream {
             http::HeaderValue::from_static("application/json"),
         );
 
-        match upstream.provider.as_str() {
-            "openai" | "openai-responses" | "gemini-openai" => {
+        match upstream.auth.scheme {
+            None => Self::insert_provider_key(&mut headers, upstream),
+            Some(UpstreamAuthScheme::Bearer) => {
                 if let Ok(val) = http::HeaderValue::from_str(&format!("Bearer {key}")) {
                     headers.insert(http::header::AUTHORIZATION, val);
                 }
             }
-            "anthropic" => {
+            Some(UpstreamAuthScheme::ApiKeyHeader) => {
+                let name = upstream.auth.header.as_deref().map_or_else(
+                    || Self::provider_key_header(&upstream.provider),
+                    |name| {
+                        http::HeaderName::from_bytes(name.as_bytes())
+                            .unwrap_or_else(|_| Self::provider_key_header(&upstream.provider))
+                    },
+                );
                 if let Ok(val) = http::HeaderValue::from_str(key) {
-                    headers.insert("x-api-key", val);
+                    headers.insert(name, val);
                 }
+            }
+            // `hmac` requests are signed per attempt by the transport.
+            Some(UpstreamAuthScheme::Hmac | UpstreamAuthScheme::None) => {}
+        }
+
+        match upstream.provider.as_str() {
+            "openai" | "openai-responses" => {
+                let configured = [
+                    (OPENAI_ORGANIZATION, upstream.organization.as_deref()),
+                    (OPENAI_PROJECT, upstream.project.as_deref()),
+                ];
+                for (name, value) in configured {
+                    if let Some(Ok(val)) = value.map(http::HeaderValue::from_str) {
+                        headers.insert(name, val);
+                    }
+                }
+            }
+            "anthropic" => {
                 let version = upstream.api_version.as_deref().unwrap_or("2023-06-01");
                 if let Ok(val) = http::HeaderValue::from_str(version) {
                     headers.insert("anthropic-version", val);
                 }
             }
-            "gemini" => {
-                if let Ok(val) = http::HeaderValue::from_str(key) {
-                    headers.insert("x-goog-api-key", val);
-                }
-            }
-            _ => unreachable!("provider is validated at config load time"),
+            _ => {}
         }
 
         headers
     }
+
+    /// The provider's usual credential: a bearer token for OpenAI-compatible
+    /// upstreams, the key header otherwise.
+    fn insert_provider_key(headers: &mut http::HeaderMap, upstream: &UpstreamServiceConfig) {
+        let key = upstream.api_key.as_str();
+        let (name, value) = match upstream.provider.as_str() {
+            "anthropic" | "gemini" => (
+                Self::provider_key_header(&upstream.provider),
+                http::HeaderValue::from_str(key),
+            ),
+            _ => (
+                http::header::AUTHORIZATION,
+                http::HeaderValue::from_str(&format!("Bearer {key}")),
+            ),
+        };
+        if let Ok(value) = value {
+            headers.insert(name, value);
+        }
+    }
+
+    fn provider_key_header(provider: &str) -> http::HeaderName {
+        http::HeaderName::from_static(match provider {
+            "anthropic" => "x-api-key",
+            "gemini" => "x-goog-api-key",
+            _ => "api-key",
+        })
+    }
 }
 
-fn normalize_proxy(proxy: Option<&str>) -> Option<String> {
+fn normalize_proxy(proxy: Option<&str>) -> Option<Arc<str>> {
     proxy.and_then(|value| {
         let trimmed = value.trim();
-        (!trimmed.is_empty()).then(|| trimmed.to_string())
+        (!trimmed.is_empty()).then(|| Arc::from(trimmed))
     })
 }
 
@@ -358,14 +476,29 @@ pub fn static_parsed_upstream_uri<'a>(
 }
 
 /// Build provider headers while reusing startup-precomputed static headers when possible.
+///
+/// Native Anthropic upstreams also get the client's allowed `anthropic-beta` flags.
+/// Upstreams trusting the client organization get the client's
+/// `OpenAI-Organization`, which takes precedence over the configured one.
 #[must_use]
-pub fn build_provider_headers_prepared(prepared: &PreparedUpstream) -> &http::HeaderMap {
-    prepared.static_headers()
+pub fn build_provider_headers_prepared(prepared: &PreparedUpstream) -> Cow<'_, http::HeaderMap> {
+    let mut headers = Cow::Borrowed(prepared.static_headers());
+    if let Some(betas) = negotiated_anthropic_beta(&prepared.anthropic_betas) {
+        headers.to_mut().insert(ANTHROPIC_BETA, betas);
+    }
+    if prepared.trust_client_organization {
+        if let Some(organization) = scoped_client_openai_organization() {
+            headers.to_mut().insert(OPENAI_ORGANIZATION, organization);
+        }
+    }
+    headers
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{PathStyle, StreamSupport, ToolSchemaValidation, UpstreamAuthConfig};
+    use crate::state::request_context::{self, RequestContext};
 
     fn make_upstream(provider: &str) -> UpstreamServiceConfig {
         UpstreamServiceConfig {
@@ -381,6 +514,17 @@ mod tests {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         }
     }
 
@@ -426,7 +570,7 @@ mod tests {
         let url = prepared.request_url("gemini-pro", true);
         assert_eq!(
             url.as_ref(),
-            "https://api.example.com/v1/models/gemini-pro:streamGenerateContent"
+            "https://api.example.com/v1/models/gemini-pro:streamGenerateContent?alt=sse"
         );
     }
 
@@ -446,7 +590,7 @@ mod tests {
             .expect("configured gemini model should have parsed stream URL");
         assert_eq!(
             stream.as_str(),
-            "https://api.example.com/v1/models/gemini-pro:streamGenerateContent"
+            "https://api.example.com/v1/models/gemini-pro:streamGenerateContent?alt=sse"
         );
 
         let non_stream_uri = static_parsed_upstream_uri(&prepared, "gemini-pro", false)
@@ -459,7 +603,7 @@ mod tests {
             .expect("configured gemini model should have parsed stream URI");
         assert_eq!(
             stream_uri.to_string(),
-            "https://api.example.com/v1/models/gemini-pro:streamGenerateContent"
+            "https://api.example.com/v1/models/gemini-pro:streamGenerateContent?alt=sse"
         );
     }
 
@@ -489,6 +633,82 @@ mod tests {
         assert_eq!(url.as_ref(), "https://api.example.com/v1/chat/completions");
     }
 
+    #[test]
+    fn test_auto_path_style_fixes_bare_hosts_and_pasted_endpoints() {
+        let cases = [
+            (
+                "openai",
+                "https://api.example.com",
+                "https://api.example.com/v1",
+            ),
+            (
+                "anthropic",
+                "https://api.example.com/",
+                "https://api.example.com/v1",
+            ),
+            (
+                "gemini",
+                "https://api.example.com",
+                "https://api.example.com/v1beta",
+            ),
+            (
+                "gemini-openai",
+                "https://api.example.com",
+                "https://api.example.com/v1beta/openai",
+            ),
+            (
+                "openai",
+                "https://api.example.com/v1/chat/completions/",
+                "https://api.example.com/v1",
+            ),
+            (
+                "anthropic",
+                "https://api.example.com/v1/messages",
+                "https://api.example.com/v1",
+            ),
+            (
+                "openai",
+                "https://gateway.example.com/openai",
+                "https://gateway.example.com/openai",
+            ),
+            (
+                "openai",
+                "https://api.example.com?key=1",
+                "https://api.example.com?key=1",
+            ),
+        ];
+        for (provider, configured, resolved) in cases {
+            let mut upstream = make_upstream(provider);
+            upstream.base_url = configured.to_string();
+            assert_eq!(upstream_base_url(&upstream), resolved, "{configured}");
+        }
+
+        let mut upstream = make_upstream("openai-responses");
+        upstream.base_url = "https://api.example.com".to_string();
+        let prepared = PreparedUpstream::new(&upstream);
+        assert_eq!(prepared.base_url(), "https://api.example.com/v1");
+        assert_eq!(
+            prepared.request_url("gpt-4", false).as_ref(),
+            "https://api.example.com/v1/responses"
+        );
+    }
+
+    #[test]
+    fn test_as_is_path_style_keeps_base_url() {
+        // Existing configs without `path_style` keep their bare-host URL.
+        let upstream: UpstreamServiceConfig = serde_yaml::from_str(
+            "{name: gateway, provider: openai, base_url: \"http://localhost:8080/\"}",
+        )
+        .unwrap();
+        assert_eq!(upstream.path_style, PathStyle::AsIs);
+        assert_eq!(upstream_base_url(&upstream), "http://localhost:8080");
+        let prepared = PreparedUpstream::new(&upstream);
+        assert_eq!(
+            prepared.request_url("gpt-4", false).as_ref(),
+            "http://localhost:8080/chat/completions"
+        );
+    }
+
     #[test]
     fn test_headers_openai() {
         let upstream = make_upstream("openai");
@@ -532,6 +752,74 @@ mod tests {
         assert!(headers.get(http::header::AUTHORIZATION).is_none());
     }
 
+    #[tokio::test]
+    async fn test_headers_openai_organization_precedence() {
+        let mut upstream = make_upstream("openai");
+        upstream.organization = Some("org-config".to_string());
+        upstream.project = Some("proj-config".to_string());
+        let prepared = PreparedUpstream::new(&upstream);
+        let client_org = Some(http::HeaderValue::from_static("org-client"));
+
+        let context = RequestContext {
+            openai_organization: client_org.clone(),
+            ..RequestContext::default()
+        };
+        let (headers, _) = request_context::scope(context, async {
+            build_provider_headers_prepared(&prepared).into_owned()
+        })
+        .await;
+        assert_eq!(headers.get(OPENAI_ORGANIZATION).unwrap(), "org-config");
+        assert_eq!(headers.get(OPENAI_PROJECT).unwrap(), "proj-config");
+
+        upstream.trust_client_organization = true;
+        let prepared = PreparedUpstream::new(&upstream);
+        let context = RequestContext {
+            openai_organization: client_org,
+            ..RequestContext::default()
+        };
+        let (headers, _) = request_context::scope(context, async {
+            build_provider_headers_prepared(&prepared).into_owned()
+        })
+        .await;
+        assert_eq!(headers.get(OPENAI_ORGANIZATION).unwrap(), "org-client");
+        assert_eq!(headers.get(OPENAI_PROJECT).unwrap(), "proj-config");
+        assert_eq!(
+            build_provider_headers_prepared(&prepared)
+                .get(OPENAI_ORGANIZATION)
+                .unwrap(),
+            "org-config"
+        );
+    }
+
+    #[test]
+    fn test_headers_auth_schemes() {
+        let mut upstream = make_upstream("anthropic");
+        upstream.auth.scheme = Some(UpstreamAuthScheme::Bearer);
+        let headers = PreparedUpstream::new(&upstream).static_headers().clone();
+        assert_eq!(
+            headers.get(http::header::AUTHORIZATION).unwrap(),
+            "Bearer sk-test-key"
+        );
+        assert!(headers.get("x-api-key").is_none());
+        assert_eq!(headers.get("anthropic-version").unwrap(), "2023-06-01");
+
+        let mut upstream = make_upstream("openai");
+        upstream.auth.scheme = Some(UpstreamAuthScheme::ApiKeyHeader);
+        let headers = PreparedUpstream::new(&upstream).static_headers().clone();
+        assert_eq!(headers.get("api-key").unwrap(), "sk-test-key");
+        upstream.auth.header = Some("X-Internal-Key".to_string());
+        let headers = PreparedUpstream::new(&upstream).static_headers().clone();
+        assert_eq!(headers.get("x-internal-key").unwrap(), "sk-test-key");
+        assert!(headers.get(http::header::AUTHORIZATION).is_none());
+
+        for scheme in [UpstreamAuthScheme::Hmac, UpstreamAuthScheme::None] {
+            upstream.auth.scheme = Some(scheme);
+            let headers = PreparedUpstream::new(&upstream).static_headers().clone();
+            assert!(headers.get(http::header::AUTHORIZATION).is_none());
+            assert!(headers.get("x-internal-key").is_none());
+        }
+    }
+
     #[test]
     fn test_prepared_upstream_parsed_static_urls() {
         let openai = PreparedUpstream::new(&make_upstream("openai"));
@@ -558,10 +846,13 @@ mod tests {
         upstream.proxy_non_stream = Some("http://nonstream.proxy:8080".to_string());
         let prepared = PreparedUpstream::new(&upstream);
         assert_eq!(
-            prepared.proxy_for(false),
+            prepared.proxy_for(false).as_deref(),
             Some("http://nonstream.proxy:8080")
         );
-        assert_eq!(prepared.proxy_for(true), Some("http://stream.proxy:8080"));
+        assert_eq!(
+            prepared.proxy_for(true).as_deref(),
+            Some("http://stream.proxy:8080")
+        );
     }
 
     #[test]
@@ -569,7 +860,37 @@ mod tests {
         let mut upstream = make_upstream("openai");
         upstream.proxy = Some("http://default.proxy:8080".to_string());
         let prepared = PreparedUpstream::new(&upstream);
-        assert_eq!(prepared.proxy_for(false), Some("http://default.proxy:8080"));
-        assert_eq!(prepared.proxy_for(true), Some("http://default.proxy:8080"));
+        assert_eq!(
+            prepared.proxy_for(false).as_deref(),
+            Some("http://default.proxy:8080")
+        );
+        assert_eq!(
+            prepared.proxy_for(true).as_deref(),
+            Some("http://default.proxy:8080")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proxy_override_takes_precedence() {
+        let mut upstream = make_upstream("openai");
+        upstream.proxy_stream = Some("http://stream.proxy:8080".to_string());
+        let prepared = PreparedUpstream::new(&upstream);
+        let rule_proxy = Some(Arc::from("socks5h://rule.proxy:1080"));
+        let context = RequestContext {
+            proxy: rule_proxy,
+            ..RequestContext::default()
+        };
+        request_context::scope(context, async {
+            assert_eq!(
+                prepared.proxy_for(true).as_deref(),
+                Some("socks5h://rule.proxy:1080")
+            );
+            assert_eq!(
+                prepared.proxy_for(false).as_deref(),
+                Some("socks5h://rule.proxy:1080")
+            );
+        })
+        .await;
+        assert!(prepared.proxy_for(false).is_none());
     }
 }
@@ -0,0 +1,17 @@
+//! Per-request proxy override from routing rules.
+//!
+//! The compat flow passes the proxy its routing rules select down in its
+//! request context; [`PreparedUpstream::proxy_for`] prefers it over the
+//! upstream's own proxies.
+//!
+//! [`PreparedUpstream::proxy_for`]: super::PreparedUpstream::proxy_for
+
+use std::sync::Arc;
+
+use crate::state::request_context;
+
+/// The proxy selected for the request being served, if any.
+#[must_use]
+pub(crate) fn current() -> Option<Arc<str>> {
+    request_context::with(|context| context.proxy.clone()).flatten()
+}
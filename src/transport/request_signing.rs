@@ -0,0 +1,173 @@
+//! HMAC request signing for upstreams using the `hmac` auth scheme.
+//!
+//! The signed string is `{timestamp}\n{METHOD}\n{path?query}\n{body_sha256}`,
+//! keyed by the upstream's `api_key`. Each send attempt is signed afresh, so
+//! transport retries carry a current timestamp.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ring::{digest, hmac};
+
+use crate::config::{UpstreamAuthScheme, UpstreamServiceConfig};
+use crate::util::hex;
+
+use super::prepared_upstream::upstream_base_url;
+
+pub(crate) const TIMESTAMP_HEADER: &str = "x-timestamp";
+pub(crate) const CONTENT_SHA256_HEADER: &str = "x-content-sha256";
+pub(crate) const SIGNATURE_HEADER: &str = "x-signature";
+pub(crate) const KEY_ID_HEADER: &str = "x-key-id";
+
+/// Signs requests sent under one upstream's base URL.
+pub(crate) struct HmacSigner {
+    base_url: String,
+    key: hmac::Key,
+    key_id: Option<http::HeaderValue>,
+}
+
+impl HmacSigner {
+    /// The signer for `upstream`, when it uses the `hmac` auth scheme.
+    #[must_use]
+    pub(crate) fn for_upstream(upstream: &UpstreamServiceConfig) -> Option<Self> {
+        if upstream.auth.scheme != Some(UpstreamAuthScheme::Hmac) {
+            return None;
+        }
+        Some(Self {
+            base_url: upstream_base_url(upstream).into_owned(),
+            key: hmac::Key::new(hmac::HMAC_SHA256, upstream.api_key.as_bytes()),
+            key_id: upstream
+                .auth
+                .key_id
+                .as_deref()
+                .and_then(|key_id| http::HeaderValue::from_str(key_id).ok()),
+        })
+    }
+
+    /// Whether `url` lies under this signer's upstream base URL.
+    #[must_use]
+    pub(crate) fn covers(&self, url: &str) -> bool {
+        url.strip_prefix(self.base_url.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+    }
+
+    /// Add the timestamp, body digest, and signature headers for a request.
+    pub(crate) fn sign(
+        &self,
+        headers: &mut http::HeaderMap,
+        method: &http::Method,
+        path_and_query: &str,
+        body: &[u8],
+    ) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.sign_at(headers, method, path_and_query, body, timestamp);
+    }
+
+    fn sign_at(
+        &self,
+        headers: &mut http::HeaderMap,
+        method: &http::Method,
+        path_and_query: &str,
+        body: &[u8],
+        timestamp: u64,
+    ) {
+        let body_digest = hex(digest::digest(&digest::SHA256, body).as_ref());
+        let signed = format!("{timestamp}\n{method}\n{path_and_query}\n{body_digest}");
+        let signature = hex(hmac::sign(&self.key, signed.as_bytes()).as_ref());
+
+        headers.insert(TIMESTAMP_HEADER, http::HeaderValue::from(timestamp));
+        for (name, value) in [
+            (CONTENT_SHA256_HEADER, body_digest),
+            (SIGNATURE_HEADER, signature),
+        ] {
+            if let Ok(value) = http::HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        }
+        if let Some(key_id) = &self.key_id {
+            headers.insert(KEY_ID_HEADER, key_id.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PathStyle, StreamSupport, ToolSchemaValidation, UpstreamAuthConfig};
+
+    fn hmac_upstream() -> UpstreamServiceConfig {
+        UpstreamServiceConfig {
+            name: "internal".to_string(),
+            provider: "openai".to_string(),
+            base_url: "https://gateway.internal/v1/".to_string(),
+            api_key: "secret".to_string(),
+            models: vec!["m".to_string()],
+            description: String::new(),
+            is_default: false,
+            fc_mode: crate::config::FcMode::default(),
+            api_version: None,
+            proxy: None,
+            proxy_stream: None,
+            proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig {
+                scheme: Some(UpstreamAuthScheme::Hmac),
+                header: None,
+                key_id: Some("gw-1".to_string()),
+            },
+            tls: None,
+            bind_address: None,
+        }
+    }
+
+    #[test]
+    fn test_hmac_signer_only_for_hmac_scheme() {
+        let mut upstream = hmac_upstream();
+        assert!(HmacSigner::for_upstream(&upstream).is_some());
+        upstream.auth.scheme = Some(UpstreamAuthScheme::Bearer);
+        assert!(HmacSigner::for_upstream(&upstream).is_none());
+    }
+
+    #[test]
+    fn test_hmac_signer_covers_base_url_paths() {
+        let signer = HmacSigner::for_upstream(&hmac_upstream()).unwrap();
+        assert!(signer.covers("https://gateway.internal/v1/chat/completions"));
+        assert!(signer.covers("https://gateway.internal/v1"));
+        assert!(!signer.covers("https://gateway.internal/v10/chat/completions"));
+        assert!(!signer.covers("https://other.internal/v1/chat/completions"));
+    }
+
+    #[test]
+    fn test_hmac_signature_headers() {
+        let signer = HmacSigner::for_upstream(&hmac_upstream()).unwrap();
+        let mut headers = http::HeaderMap::new();
+        signer.sign_at(
+            &mut headers,
+            &http::Method::POST,
+            "/v1/chat/completions",
+            b"{}",
+            1_700_000_000,
+        );
+
+        let body_digest = "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a";
+        assert_eq!(headers.get(TIMESTAMP_HEADER).unwrap(), "1700000000");
+        assert_eq!(headers.get(CONTENT_SHA256_HEADER).unwrap(), body_digest);
+        assert_eq!(headers.get(KEY_ID_HEADER).unwrap(), "gw-1");
+        let expected = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, b"secret"),
+            format!("1700000000\nPOST\n/v1/chat/completions\n{body_digest}").as_bytes(),
+        );
+        assert_eq!(
+            headers.get(SIGNATURE_HEADER).unwrap(),
+            hex(expected.as_ref()).as_str()
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Upstream response headers forwarded to clients.
+//!
+//! The compat flow passes the `features.forward_response_headers` rules down
+//! in its request context. Every upstream response the transport hands back
+//! replaces the headers kept so far with its own matching ones, renamed as
+//! configured, so the served response's rate limit headers reach the client.
+
+use std::sync::Arc;
+
+use http::{HeaderMap, HeaderName};
+
+use crate::config::{ForwardResponseHeaderConfig, UNFORWARDABLE_RESPONSE_HEADERS};
+use crate::state::request_context;
+
+/// A header name, or a prefix of one when the configured name ends in `*`.
+#[derive(Debug)]
+struct HeaderPattern {
+    base: String,
+    wildcard: bool,
+}
+
+impl HeaderPattern {
+    fn new(pattern: &str) -> Self {
+        match pattern.strip_suffix('*') {
+            Some(prefix) => Self {
+                base: prefix.to_string(),
+                wildcard: true,
+            },
+            None => Self {
+                base: pattern.to_string(),
+                wildcard: false,
+            },
+        }
+    }
+
+    /// The part of `name` after the prefix, empty for an exact match.
+    fn matches<'a>(&self, name: &'a str) -> Option<&'a str> {
+        if self.wildcard {
+            name.strip_prefix(self.base.as_str())
+        } else {
+            (name == self.base).then_some("")
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ForwardRule {
+    name: HeaderPattern,
+    rename: Option<HeaderPattern>,
+}
+
+/// `features.forward_response_headers`, prepared for matching.
+#[derive(Debug)]
+pub(crate) struct ResponseHeaderForwarding {
+    rules: Box<[ForwardRule]>,
+}
+
+impl ResponseHeaderForwarding {
+    /// The configured rules; `None` when there are none.
+    #[must_use]
+    pub(crate) fn new(headers: &[ForwardResponseHeaderConfig]) -> Option<Arc<Self>> {
+        if headers.is_empty() {
+            return None;
+        }
+        let rules = headers
+            .iter()
+            .map(|header| ForwardRule {
+                name: HeaderPattern::new(&header.name),
+                rename: header.rename.as_deref().map(HeaderPattern::new),
+            })
+            .collect();
+        Some(Arc::new(Self { rules }))
+    }
+
+    /// The headers of `upstream` matched by a rule, under their client names.
+    /// The first matching rule wins.
+    fn select(&self, upstream: &HeaderMap) -> HeaderMap {
+        let mut forwarded = HeaderMap::new();
+        for (name, value) in upstream {
+            if UNFORWARDABLE_RESPONSE_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            let Some((rule, suffix)) = self
+                .rules
+                .iter()
+                .find_map(|rule| Some((rule, rule.name.matches(name.as_str())?)))
+            else {
+                continue;
+            };
+            // Validation pairs wildcard names with wildcard renames, so the
+            // suffix is empty for exact renames.
+            let client_name = match &rule.rename {
+                None => name.clone(),
+                Some(rename) => {
+                    match HeaderName::from_bytes(format!("{}{suffix}", rename.base).as_bytes()) {
+                        Ok(renamed) => renamed,
+                        Err(_) => continue,
+                    }
+                }
+            };
+            forwarded.append(client_name, value.clone());
+        }
+        forwarded
+    }
+}
+
+/// Note the headers of a successful upstream response. A no-op without
+/// rules or outside [`request_context::scope`].
+pub(crate) fn note_response_headers(headers: &HeaderMap) {
+    let forwarded = request_context::with(|context| {
+        context
+            .forward_response_headers
+            .as_ref()
+            .map(|rules| rules.select(headers))
+    });
+    if let Some(Some(forwarded)) = forwarded {
+        request_context::note_outcome(|outcome| outcome.forwarded_headers = forwarded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::request_context::RequestContext;
+
+    async fn forward_response_headers(
+        rules: Option<Arc<ResponseHeaderForwarding>>,
+        future: impl std::future::Future<Output = ()>,
+    ) -> ((), HeaderMap) {
+        let context = RequestContext {
+            forward_response_headers: rules,
+            ..RequestContext::default()
+        };
+        let ((), outcome) = request_context::scope(context, future).await;
+        ((), outcome.forwarded_headers)
+    }
+
+    #[tokio::test]
+    async fn test_last_response_headers_are_selected_and_renamed() {
+        let config: Vec<ForwardResponseHeaderConfig> = serde_yaml::from_str(
+            r#"
+- name: "x-ratelimit-*"
+- name: "anthropic-ratelimit-*"
+  rename: "x-upstream-ratelimit-*"
+- name: retry-after
+  rename: x-upstream-retry-after
+"#,
+        )
+        .unwrap();
+        let rules = ResponseHeaderForwarding::new(&config);
+
+        let mut first = HeaderMap::new();
+        first.insert("x-ratelimit-remaining-requests", "0".parse().unwrap());
+        let mut served = HeaderMap::new();
+        served.insert("x-ratelimit-remaining-tokens", "900".parse().unwrap());
+        served.insert(
+            "anthropic-ratelimit-requests-remaining",
+            "41".parse().unwrap(),
+        );
+        served.insert("retry-after", "3".parse().unwrap());
+        served.insert("set-cookie", "session=1".parse().unwrap());
+        served.insert("x-request-id", "req_1".parse().unwrap());
+
+        let ((), headers) = forward_response_headers(rules, async {
+            note_response_headers(&first);
+            note_response_headers(&served);
+        })
+        .await;
+        let mut names: Vec<_> = headers.keys().map(HeaderName::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "x-ratelimit-remaining-tokens",
+                "x-upstream-ratelimit-requests-remaining",
+                "x-upstream-retry-after"
+            ]
+        );
+        assert_eq!(headers["x-upstream-retry-after"], "3");
+
+        let ((), headers) = forward_response_headers(None, async {
+            note_response_headers(&served);
+        })
+        .await;
+        assert!(headers.is_empty());
+    }
+}
@@ -2,6 +2,8 @@ use std::time::{Duration, SystemTime};
 
 use http::header::RETRY_AFTER;
 
+use crate::state::request_context;
+
 pub(crate) const RETRY_MAX_ATTEMPTS: u32 = 2;
 pub(crate) const RETRY_BACKOFF_BASE_MS: u64 = 100;
 pub(crate) const RETRY_BACKOFF_MAX_MS: u64 = 1_000;
@@ -9,11 +11,29 @@ pub(crate) const RETRY_AFTER_MAX_SECS: u64 = 30;
 pub(crate) const PARSED_ENDPOINT_CACHE_MAX_ENTRIES: usize = 512;
 const RETRY_TRANSPORT_FAST_SECOND_MS: u64 = 10;
 
+/// Note that an upstream request is re-sent or failed over to another
+/// route. A no-op outside [`request_context::scope`].
+pub(crate) fn note_retry() {
+    request_context::note_outcome(|outcome| outcome.retries = outcome.retries.saturating_add(1));
+}
+
 #[inline]
 pub(crate) fn should_retry_upstream_status(status: http::StatusCode) -> bool {
     matches!(status.as_u16(), 429 | 503 | 529)
 }
 
+/// Retry throttled responses in place only for short waits; a longer
+/// `retry-after` is left to route cooldown and failover instead.
+#[inline]
+pub(crate) fn should_retry_upstream_response(
+    status: http::StatusCode,
+    headers: &http::HeaderMap,
+) -> bool {
+    should_retry_upstream_status(status)
+        && parse_retry_after_delay(headers)
+            .is_none_or(|delay| delay <= Duration::from_millis(RETRY_BACKOFF_MAX_MS))
+}
+
 #[inline]
 pub(crate) fn should_retry_transport_message(message: &str) -> bool {
     const NEEDLES: [&[u8]; 9] = [
@@ -33,6 +53,13 @@ pub(crate) fn should_retry_transport_message(message: &str) -> bool {
         .any(|needle| contains_ascii_case_insensitive(haystack, needle))
 }
 
+#[inline]
+pub(crate) fn is_timeout_transport_message(message: &str) -> bool {
+    let haystack = message.as_bytes();
+    contains_ascii_case_insensitive(haystack, b"timed out")
+        || contains_ascii_case_insensitive(haystack, b"timeout")
+}
+
 #[inline]
 pub(crate) fn retry_transport_delay(message: &str, attempt: u32) -> Duration {
     if has_fast_retry_transport_signature(message) {
@@ -94,6 +121,18 @@ pub(crate) fn retry_delay(headers: &http::HeaderMap, attempt: u32) -> Duration {
     parse_retry_after_delay(headers).unwrap_or_else(|| retry_backoff_delay(attempt))
 }
 
+#[inline]
+pub(crate) fn parse_retry_after_secs(headers: &http::HeaderMap) -> Option<u64> {
+    let raw = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = raw.parse::<u64>() {
+        return Some(seconds);
+    }
+
+    let target = httpdate::parse_http_date(raw).ok()?;
+    let delay = target.duration_since(SystemTime::now()).unwrap_or_default();
+    Some(delay.as_secs() + u64::from(delay.subsec_nanos() > 0))
+}
+
 #[inline]
 pub(crate) fn parse_retry_after_delay(headers: &http::HeaderMap) -> Option<Duration> {
     let raw = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
@@ -128,6 +167,17 @@ mod tests {
         assert!(!should_retry_upstream_status(http::StatusCode::BAD_REQUEST));
     }
 
+    #[test]
+    fn test_should_retry_upstream_response_defers_long_retry_after() {
+        let status = http::StatusCode::TOO_MANY_REQUESTS;
+        let mut headers = http::HeaderMap::new();
+        assert!(should_retry_upstream_response(status, &headers));
+        headers.insert(RETRY_AFTER, http::HeaderValue::from_static("1"));
+        assert!(should_retry_upstream_response(status, &headers));
+        headers.insert(RETRY_AFTER, http::HeaderValue::from_static("20"));
+        assert!(!should_retry_upstream_response(status, &headers));
+    }
+
     #[test]
     fn test_parse_retry_after_seconds() {
         let mut headers = http::HeaderMap::new();
@@ -148,6 +198,22 @@ mod tests {
         assert!(delay <= Duration::from_secs(RETRY_AFTER_MAX_SECS));
     }
 
+    #[test]
+    fn test_parse_retry_after_secs_is_uncapped() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(RETRY_AFTER, http::HeaderValue::from_static("120"));
+        assert_eq!(parse_retry_after_secs(&headers), Some(120));
+        headers.insert(RETRY_AFTER, http::HeaderValue::from_static("soon"));
+        assert_eq!(parse_retry_after_secs(&headers), None);
+    }
+
+    #[test]
+    fn test_is_timeout_transport_message() {
+        assert!(is_timeout_transport_message("operation timed out"));
+        assert!(is_timeout_transport_message("connect Timeout"));
+        assert!(!is_timeout_transport_message("connection refused"));
+    }
+
     #[test]
     fn test_parse_retry_after_invalid() {
         let mut headers = http::HeaderMap::new();
@@ -0,0 +1,187 @@
+//! Per-request priority and provider service tiers.
+//!
+//! The compat flow passes down the priority granted for the client's
+//! `x-priority` header in its request context. Requests to the model
+//! endpoints of providers with service tiers carry the matching
+//! `service_tier`, unless the client already chose one; other upstreams only
+//! see the priority through the local admission queue.
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use super::PreparedUpstream;
+use crate::json_scan::{find_top_level_field_value_range, skip_ws};
+use crate::protocol::canonical::ProviderKind;
+use crate::state::request_context;
+
+pub(crate) const PRIORITY_HEADER: &str = "x-priority";
+
+/// Priority a client asks for with `x-priority: low|normal|high`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum RequestPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl RequestPriority {
+    /// The priority named by the client's `x-priority` header, if valid.
+    #[must_use]
+    pub fn from_headers(headers: &http::HeaderMap) -> Option<Self> {
+        let value = headers.get(PRIORITY_HEADER)?.to_str().ok()?.trim();
+        [Self::Low, Self::Normal, Self::High]
+            .into_iter()
+            .find(|priority| value.eq_ignore_ascii_case(priority.as_str()))
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+        }
+    }
+
+    /// The `service_tier` value requesting this priority from `style`'s
+    /// provider; normal priority leaves the provider default.
+    fn service_tier(self, style: TierStyle) -> Option<&'static str> {
+        match (self, style) {
+            (Self::Normal, _) => None,
+            (Self::Low, TierStyle::OpenAi) => Some("flex"),
+            (Self::High, TierStyle::OpenAi) => Some("priority"),
+            (Self::Low, TierStyle::Anthropic) => Some("standard_only"),
+            (Self::High, TierStyle::Anthropic) => Some("auto"),
+        }
+    }
+}
+
+/// The priority granted to the request being served.
+#[must_use]
+pub(crate) fn scoped_priority() -> RequestPriority {
+    request_context::with(|context| context.priority).unwrap_or_default()
+}
+
+/// Which `service_tier` values an endpoint takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TierStyle {
+    /// `flex` / `default` / `priority` (Chat Completions and Responses).
+    OpenAi,
+    /// `standard_only` / `auto`, where `auto` may use Priority Tier capacity.
+    Anthropic,
+}
+
+/// A model endpoint whose requests take a `service_tier` field.
+#[derive(Debug, Clone)]
+pub(crate) struct ServiceTierEndpoint {
+    url: String,
+    style: TierStyle,
+}
+
+impl ServiceTierEndpoint {
+    /// The tiered endpoint of `upstream`, if its provider has service tiers.
+    #[must_use]
+    pub(crate) fn for_upstream(upstream: &PreparedUpstream) -> Option<Self> {
+        let style = match upstream.provider_kind() {
+            ProviderKind::OpenAi | ProviderKind::OpenAiResponses => TierStyle::OpenAi,
+            ProviderKind::Anthropic => TierStyle::Anthropic,
+            ProviderKind::Gemini | ProviderKind::GeminiOpenAi => return None,
+        };
+        Some(Self {
+            url: upstream.static_url()?.as_str().to_string(),
+            style,
+        })
+    }
+
+    /// `body` with the scoped priority's `service_tier` when it is sent to
+    /// this endpoint and the client did not pick a tier itself.
+    #[must_use]
+    pub(crate) fn apply(&self, url: &str, body: &Bytes) -> Option<Bytes> {
+        if url != self.url {
+            return None;
+        }
+        let tier = scoped_priority().service_tier(self.style)?;
+        with_service_tier(body, tier)
+    }
+}
+
+/// Insert `"service_tier": tier` into a JSON object that has none.
+fn with_service_tier(body: &[u8], tier: &str) -> Option<Bytes> {
+    if find_top_level_field_value_range(body, b"service_tier")
+        .ok()?
+        .is_some()
+    {
+        return None;
+    }
+    let open = skip_ws(body, 0);
+    if body.get(open) != Some(&b'{') {
+        return None;
+    }
+    let rest = &body[open + 1..];
+    let empty = rest.get(skip_ws(rest, 0)) == Some(&b'}');
+    let mut out = BytesMut::with_capacity(body.len() + tier.len() + 20);
+    out.put_slice(&body[..=open]);
+    out.put_slice(b"\"service_tier\":\"");
+    out.put_slice(tier.as_bytes());
+    out.put_slice(if empty { b"\"" } else { b"\"," });
+    out.put_slice(rest);
+    Some(out.freeze())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::request_context::RequestContext;
+
+    async fn scope<F: std::future::Future>(priority: RequestPriority, future: F) -> F::Output {
+        let context = RequestContext {
+            priority,
+            ..RequestContext::default()
+        };
+        request_context::scope(context, future).await.0
+    }
+
+    #[tokio::test]
+    async fn test_service_tier_follows_scoped_priority() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(PRIORITY_HEADER, http::HeaderValue::from_static(" HIGH "));
+        assert_eq!(
+            RequestPriority::from_headers(&headers),
+            Some(RequestPriority::High)
+        );
+        headers.insert(PRIORITY_HEADER, http::HeaderValue::from_static("urgent"));
+        assert_eq!(RequestPriority::from_headers(&headers), None);
+
+        let endpoint = ServiceTierEndpoint {
+            url: "https://api.openai.com/v1/chat/completions".to_string(),
+            style: TierStyle::OpenAi,
+        };
+        let body = Bytes::from_static(br#"{"model":"gpt-4o","messages":[]}"#);
+        assert!(endpoint.apply(&endpoint.url, &body).is_none());
+        scope(RequestPriority::High, async {
+            assert_eq!(
+                endpoint.apply(&endpoint.url, &body).unwrap(),
+                br#"{"service_tier":"priority","model":"gpt-4o","messages":[]}"#[..]
+            );
+            assert!(endpoint
+                .apply("https://api.openai.com/v1/moderations", &body)
+                .is_none());
+            let chosen = Bytes::from_static(br#"{"model":"m","service_tier":"flex"}"#);
+            assert!(endpoint.apply(&endpoint.url, &chosen).is_none());
+        })
+        .await;
+        scope(RequestPriority::Low, async {
+            let anthropic = ServiceTierEndpoint {
+                style: TierStyle::Anthropic,
+                ..endpoint.clone()
+            };
+            assert_eq!(
+                anthropic
+                    .apply(&endpoint.url, &Bytes::from_static(b" { } "))
+                    .unwrap(),
+                br#" {"service_tier":"standard_only" } "#[..]
+            );
+        })
+        .await;
+    }
+}
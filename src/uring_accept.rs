@@ -0,0 +1,222 @@
+//! io_uring accept path (Linux, `io-uring` feature).
+//!
+//! A dedicated thread keeps a multishot `accept` armed on the listener, so
+//! new connections arrive as ring completions rather than an epoll wakeup
+//! plus an `accept(2)` call each. Accepted sockets are handed to the tokio
+//! runtime and served like any other connection. Kernels without multishot
+//! accept (before 5.19) get a single-shot accept re-armed per connection.
+//! Dropping the acceptor signals an eventfd the ring polls, so the thread
+//! exits and closes the listener.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::Arc;
+use std::time::Duration;
+
+use io_uring::{cqueue, opcode, squeue, types, IoUring};
+use socket2::SockRef;
+use tokio::sync::mpsc;
+
+const RING_ENTRIES: u32 = 64;
+/// Accepted sockets waiting for the runtime; the ring thread blocks when full.
+const ACCEPT_QUEUE_DEPTH: usize = 1024;
+/// Pause after a batch with a failed accept (e.g. `EMFILE`) so the ring does
+/// not spin.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(10);
+const ACCEPT_FLAGS: i32 = libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC;
+/// `user_data` of accept entries.
+const ACCEPT_TOKEN: u64 = 0;
+/// `user_data` of the poll on the shutdown eventfd.
+const SHUTDOWN_TOKEN: u64 = 1;
+
+pub struct UringAcceptor {
+    accepted: mpsc::Receiver<io::Result<std::net::TcpStream>>,
+    /// Written on drop to stop the ring thread.
+    shutdown: File,
+}
+
+impl UringAcceptor {
+    /// Move `listener` onto an io_uring accept thread.
+    ///
+    /// # Errors
+    ///
+    /// Hands the listener back when the ring cannot be created, e.g. on
+    /// kernels without io_uring or where seccomp blocks it.
+    pub fn start(
+        listener: tokio::net::TcpListener,
+    ) -> Result<Self, (tokio::net::TcpListener, io::Error)> {
+        let ring = match IoUring::new(RING_ENTRIES) {
+            Ok(ring) => ring,
+            Err(err) => return Err((listener, err)),
+        };
+        let (shutdown, ring_shutdown) = match shutdown_eventfd() {
+            Ok(fds) => fds,
+            Err(err) => return Err((listener, err)),
+        };
+        // io_uring answers `EAGAIN` on non-blocking sockets instead of
+        // waiting for a connection.
+        if let Err(err) = SockRef::from(&listener).set_nonblocking(false) {
+            return Err((listener, err));
+        }
+        let listener = Arc::new(listener);
+        let ring_listener = Arc::clone(&listener);
+        let (sender, accepted) = mpsc::channel(ACCEPT_QUEUE_DEPTH);
+        let spawned = std::thread::Builder::new()
+            .name("toolify-uring-accept".to_string())
+            .spawn(move || run_ring(ring, &ring_listener, &ring_shutdown, &sender));
+        match (spawned, Arc::try_unwrap(listener)) {
+            (Ok(_), _) => Ok(Self { accepted, shutdown }),
+            // The failed spawn dropped the thread's share of the listener.
+            (Err(err), Ok(listener)) => {
+                let _ = SockRef::from(&listener).set_nonblocking(true);
+                Err((listener, err))
+            }
+            (Err(err), Err(_)) => unreachable!("listener still shared after failed spawn: {err}"),
+        }
+    }
+
+    /// The next accepted connection, registered with the tokio runtime.
+    pub async fn accept(&mut self) -> io::Result<tokio::net::TcpStream> {
+        match self.accepted.recv().await {
+            Some(stream) => tokio::net::TcpStream::from_std(stream?),
+            None => Err(io::Error::other("io_uring accept thread stopped")),
+        }
+    }
+}
+
+impl Drop for UringAcceptor {
+    fn drop(&mut self) {
+        if let Err(err) = self.shutdown.write_all(&1u64.to_ne_bytes()) {
+            tracing::error!("failed to stop the io_uring accept thread: {err}");
+        }
+    }
+}
+
+/// Both ends of the eventfd that tells the ring thread to stop.
+fn shutdown_eventfd() -> io::Result<(File, OwnedFd)> {
+    // SAFETY: `eventfd` takes no pointers; a non-negative result is a new
+    // descriptor that nothing else owns.
+    let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: see above.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let ring_fd = fd.try_clone()?;
+    Ok((File::from(fd), ring_fd))
+}
+
+/// Accept on `listener` until `shutdown` is signalled or the acceptor is
+/// dropped; the thread's handle keeps the socket open while the ring may
+/// still reference it.
+fn run_ring(
+    mut ring: IoUring,
+    listener: &tokio::net::TcpListener,
+    shutdown: &OwnedFd,
+    sender: &mpsc::Sender<io::Result<std::net::TcpStream>>,
+) {
+    let fd = types::Fd(listener.as_raw_fd());
+    let shutdown_poll = opcode::PollAdd::new(types::Fd(shutdown.as_raw_fd()), libc::POLLIN as u32)
+        .build()
+        .user_data(SHUTDOWN_TOKEN);
+    if !push(&mut ring, &shutdown_poll) {
+        return;
+    }
+    let mut multishot = true;
+    let mut armed = false;
+    loop {
+        if !armed {
+            let entry = if multishot {
+                opcode::AcceptMulti::new(fd).flags(ACCEPT_FLAGS).build()
+            } else {
+                opcode::Accept::new(fd, std::ptr::null_mut(), std::ptr::null_mut())
+                    .flags(ACCEPT_FLAGS)
+                    .build()
+            };
+            if !push(&mut ring, &entry.user_data(ACCEPT_TOKEN)) {
+                return;
+            }
+            armed = true;
+        }
+        if let Err(err) = ring.submit_and_wait(1) {
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            tracing::error!("io_uring accept ring failed: {err}");
+            return;
+        }
+        let completions: Vec<(u64, i32, u32)> = ring
+            .completion()
+            .map(|cqe| (cqe.user_data(), cqe.result(), cqe.flags()))
+            .collect();
+        if completions
+            .iter()
+            .any(|(token, _, _)| *token == SHUTDOWN_TOKEN)
+        {
+            return;
+        }
+        let mut failed = false;
+        for (_, result, flags) in completions {
+            if !multishot || !cqueue::more(flags) {
+                armed = false;
+            }
+            if result == -libc::EINVAL && multishot {
+                tracing::debug!("multishot accept unsupported; using single-shot accept");
+                multishot = false;
+                continue;
+            }
+            let accepted = if result >= 0 {
+                // SAFETY: a successful accept completion carries a new socket
+                // descriptor that nothing else owns.
+                let socket = unsafe { OwnedFd::from_raw_fd(result) };
+                Ok(std::net::TcpStream::from(socket))
+            } else {
+                failed = true;
+                Err(io::Error::from_raw_os_error(-result))
+            };
+            if sender.blocking_send(accepted).is_err() {
+                return;
+            }
+        }
+        if failed {
+            std::thread::sleep(ACCEPT_ERROR_BACKOFF);
+        }
+    }
+}
+
+fn push(ring: &mut IoUring, entry: &squeue::Entry) -> bool {
+    // SAFETY: entries reference only the listener and the shutdown eventfd,
+    // which outlive the ring thread, and null address pointers.
+    if unsafe { ring.submission().push(entry) }.is_ok() {
+        return true;
+    }
+    tracing::error!("io_uring submission queue is full");
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dropping_the_acceptor_closes_the_listener() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Kernels or sandboxes without io_uring keep the epoll path.
+        let Ok(mut acceptor) = UringAcceptor::start(listener) else {
+            return;
+        };
+        let _client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        acceptor.accept().await.unwrap();
+
+        drop(acceptor);
+        for _ in 0..100 {
+            if tokio::net::TcpStream::connect(addr).await.is_err() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("listener still open after the acceptor was dropped");
+    }
+}
@@ -1,12 +1,15 @@
 use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::error::CanonicalError;
 
 static CALL_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
 const HEX: &[u8; 16] = b"0123456789abcdef";
+#[cfg(feature = "server")]
 const SAMPLED_HASH_SAMPLE_LEN: usize = 32;
 
+#[cfg(feature = "server")]
 #[inline]
 pub(crate) fn mix_u64(mut x: u64) -> u64 {
     x ^= x >> 30;
@@ -16,6 +19,7 @@ pub(crate) fn mix_u64(mut x: u64) -> u64 {
     x ^ (x >> 31)
 }
 
+#[cfg(feature = "server")]
 #[inline]
 pub(crate) fn sampled_bytes_hash(bytes: &[u8]) -> u64 {
     const HASH_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
@@ -57,11 +61,19 @@ pub(crate) fn sampled_bytes_hash(bytes: &[u8]) -> u64 {
     mix_u64(hash)
 }
 
+/// Seconds since the unix epoch; 0 on `wasm32-unknown-unknown`, which has no clock.
 #[inline]
 pub(crate) fn unix_now_secs() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_or(0, |duration| duration.as_secs())
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs())
+    }
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    {
+        0
+    }
 }
 
 #[inline]
@@ -82,6 +94,53 @@ pub(crate) fn next_generated_id(prefix: &str, counter: &AtomicU64) -> String {
     out
 }
 
+/// Lowercase hex encoding of `bytes`.
+#[cfg(feature = "server")]
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        out.push(char::from(DIGITS[usize::from(byte >> 4)]));
+        out.push(char::from(DIGITS[usize::from(byte & 0x0f)]));
+    }
+    out
+}
+
+/// Decode standard or URL-safe base64, skipping padding and whitespace.
+///
+/// Returns `None` on any other byte.
+#[cfg(feature = "server")]
+pub(crate) fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    fn sextet(byte: u8) -> Option<u32> {
+        match byte {
+            b'A'..=b'Z' => Some(u32::from(byte - b'A')),
+            b'a'..=b'z' => Some(u32::from(byte - b'a') + 26),
+            b'0'..=b'9' => Some(u32::from(byte - b'0') + 52),
+            b'+' | b'-' => Some(62),
+            b'/' | b'_' => Some(63),
+            _ => None,
+        }
+    }
+
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut buffer = 0_u32;
+    let mut bits = 0_u32;
+    for byte in text.bytes() {
+        if byte == b'=' || byte.is_ascii_whitespace() {
+            continue;
+        }
+        buffer = (buffer << 6) | sextet(byte)?;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(feature = "server")]
 #[inline]
 pub(crate) fn format_request_seq_hex(prefix: &str, request_seq: u64) -> String {
     let mut out = String::with_capacity(prefix.len() + 16);
@@ -224,20 +283,30 @@ pub(crate) fn raw_value_from_string(
 
 #[cfg(test)]
 mod tests {
-    use super::{format_request_seq_hex, push_json_string_escaped};
+    use super::push_json_string_escaped;
 
+    #[cfg(feature = "server")]
     #[test]
     fn format_request_seq_hex_matches_formatter() {
         assert_eq!(
-            format_request_seq_hex("chatcmpl-", 0x1234_abcd_u64),
+            super::format_request_seq_hex("chatcmpl-", 0x1234_abcd_u64),
             "chatcmpl-000000001234abcd"
         );
         assert_eq!(
-            format_request_seq_hex("msg_", u64::MAX),
+            super::format_request_seq_hex("msg_", u64::MAX),
             "msg_ffffffffffffffff"
         );
     }
 
+    #[cfg(feature = "server")]
+    #[test]
+    fn base64_decode_handles_padding_and_url_safe_alphabet() {
+        assert_eq!(super::base64_decode("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(super::base64_decode("aGk\n").unwrap(), b"hi");
+        assert_eq!(super::base64_decode("-_8=").unwrap(), [0xfb, 0xff]);
+        assert!(super::base64_decode("a*b").is_none());
+    }
+
     #[test]
     fn push_json_string_escaped_matches_serde_json() {
         let inputs = [
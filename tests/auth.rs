@@ -1,3 +1,5 @@
+#![cfg(feature = "server")]
+
 use http::HeaderMap;
 use toolify_rs::auth::{authenticate, build_allowed_key_set};
 use toolify_rs::config::{AppConfig, ClientAuthConfig, FeaturesConfig, ServerConfig};
@@ -10,8 +12,16 @@ fn config_with_keys(keys: Vec<&str>) -> AppConfig {
         upstream_services: Vec::new(),
         client_authentication: ClientAuthConfig {
             allowed_keys: keys.into_iter().map(ToString::to_string).collect(),
+            admin_keys: Vec::new(),
+            key_model_maps: Vec::new(),
         },
         features: FeaturesConfig::default(),
+        virtual_models: Vec::new(),
+        routing_rules: Vec::new(),
+        cascade_models: Vec::new(),
+        admin_authentication: None,
+        fallback_chains: Vec::new(),
+        tenants: Vec::new(),
     }
 }
 
@@ -44,7 +44,8 @@ fn test_fc_injection_preprocesses_request() {
     let mut request = sample_request();
     let features = FeaturesConfig::default();
 
-    let saved_tools = apply_fc_inject_take_tools(&mut request, &features).expect("apply inject");
+    let saved_tools =
+        apply_fc_inject_take_tools(&mut request, &features, None).expect("apply inject");
     assert_eq!(saved_tools.len(), 1);
     assert!(request.tools.is_empty());
     assert!(matches!(request.tool_choice, CanonicalToolChoice::None));
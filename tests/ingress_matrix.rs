@@ -1,14 +1,19 @@
+#![cfg(feature = "server")]
+
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use axum::body::Body;
 use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::post;
 use axum::{Json, Router};
 use serde_json::json;
-use toolify_rs::auth::build_allowed_key_set;
+use toolify_rs::auth::{build_allowed_key_set, hash_api_key};
 use toolify_rs::config::{
-    AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, ServerConfig, UpstreamServiceConfig,
+    AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, PathStyle, ServerConfig, StreamSupport,
+    ToolSchemaValidation, UpstreamAuthConfig, UpstreamServiceConfig,
 };
 use toolify_rs::routing::dispatch::dispatch_request;
 use toolify_rs::routing::ModelRouter;
@@ -18,12 +23,30 @@ use toolify_rs::transport::{HttpTransport, PreparedUpstream};
 fn build_state_multi_from_services(
     upstream_services: Vec<UpstreamServiceConfig>,
     allowed_keys: Vec<String>,
+) -> Arc<AppState> {
+    build_state_with_admin_keys(upstream_services, allowed_keys, Vec::new())
+}
+
+fn build_state_with_admin_keys(
+    upstream_services: Vec<UpstreamServiceConfig>,
+    allowed_keys: Vec<String>,
+    admin_keys: Vec<String>,
 ) -> Arc<AppState> {
     let config = AppConfig {
         server: ServerConfig::default(),
         upstream_services,
-        client_authentication: ClientAuthConfig { allowed_keys },
+        client_authentication: ClientAuthConfig {
+            allowed_keys,
+            admin_keys,
+            key_model_maps: Vec::new(),
+        },
         features: FeaturesConfig::default(),
+        virtual_models: Vec::new(),
+        routing_rules: Vec::new(),
+        cascade_models: Vec::new(),
+        admin_authentication: None,
+        fallback_chains: Vec::new(),
+        tenants: Vec::new(),
     };
 
     let model_router = ModelRouter::new(&config);
@@ -43,6 +66,196 @@ fn build_state_multi_from_services(
     ))
 }
 
+/// Serve `app` on an ephemeral local port for the rest of the test.
+async fn spawn_upstream(app: Router) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind upstream");
+    let addr = listener.local_addr().expect("upstream addr");
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    addr
+}
+
+/// Send `method uri` to `state` with `key` in every ingress's API key
+/// header and a JSON content type.
+async fn send(state: &Arc<AppState>, method: &str, uri: &str, key: &str, body: Body) -> Response {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("authorization", format!("Bearer {key}"))
+        .header("x-api-key", key)
+        .header("x-goog-api-key", key)
+        .header("content-type", "application/json")
+        .body(body)
+        .expect("build request");
+    dispatch(state, request).await
+}
+
+/// Dispatch `request` to `state` served at the root path.
+async fn dispatch(state: &Arc<AppState>, request: Request<Body>) -> Response {
+    dispatch_request(Arc::clone(state), Arc::<str>::from(""), request)
+        .await
+        .expect("dispatch")
+}
+
+fn json_body(value: &serde_json::Value) -> Body {
+    Body::from(serde_json::to_vec(value).expect("serialize"))
+}
+
+async fn read_text(response: Response) -> String {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("read body");
+    String::from_utf8(body.to_vec()).expect("utf8 body")
+}
+
+async fn read_json(response: Response) -> serde_json::Value {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("read body");
+    serde_json::from_slice(&body).expect("json body")
+}
+
+/// One `upstream_services` entry of a [`TestConfig`], with the mock key and
+/// native function calling unless set otherwise.
+struct TestUpstream {
+    name: String,
+    provider: String,
+    base_url: String,
+    models: Vec<String>,
+    fc_mode: &'static str,
+    is_default: bool,
+    fields: String,
+}
+
+fn upstream(
+    name: &str,
+    provider: &str,
+    base_url: impl std::fmt::Display,
+    models: &[&str],
+) -> TestUpstream {
+    TestUpstream {
+        name: name.to_string(),
+        provider: provider.to_string(),
+        base_url: base_url.to_string(),
+        models: models.iter().map(ToString::to_string).collect(),
+        fc_mode: "native",
+        is_default: false,
+        fields: String::new(),
+    }
+}
+
+impl TestUpstream {
+    fn fc_mode(mut self, fc_mode: &'static str) -> Self {
+        self.fc_mode = fc_mode;
+        self
+    }
+
+    fn default_upstream(mut self) -> Self {
+        self.is_default = true;
+        self
+    }
+
+    /// Set another field of the entry, e.g. `stream_support: stream_only`.
+    fn with(mut self, field: &str, value: impl std::fmt::Display) -> Self {
+        self.fields.push_str(&format!("\n    {field}: {value}"));
+        self
+    }
+}
+
+/// A config around mock upstreams, written as YAML and loaded through
+/// `parse_config`. `client-key` is the allowed key unless set otherwise, and
+/// the first upstream is the default unless another one is marked.
+struct TestConfig {
+    upstreams: Vec<TestUpstream>,
+    allowed_keys: Vec<String>,
+    admin_keys: Vec<String>,
+    client_auth: String,
+    sections: String,
+}
+
+impl TestConfig {
+    fn new() -> Self {
+        Self {
+            upstreams: Vec::new(),
+            allowed_keys: vec!["client-key".to_string()],
+            admin_keys: Vec::new(),
+            client_auth: String::new(),
+            sections: String::new(),
+        }
+    }
+
+    fn upstream(mut self, upstream: TestUpstream) -> Self {
+        self.upstreams.push(upstream);
+        self
+    }
+
+    fn allowed_keys(mut self, keys: impl IntoIterator<Item = impl ToString>) -> Self {
+        self.allowed_keys = keys.into_iter().map(|key| key.to_string()).collect();
+        self
+    }
+
+    fn admin_keys(mut self, keys: impl IntoIterator<Item = impl ToString>) -> Self {
+        self.admin_keys = keys.into_iter().map(|key| key.to_string()).collect();
+        self
+    }
+
+    /// Append YAML to the `client_authentication` section, indented by two
+    /// spaces, e.g. `key_model_maps:`.
+    fn client_auth(mut self, yaml: &str) -> Self {
+        self.client_auth.push_str(yaml.trim_matches('\n'));
+        self.client_auth.push('\n');
+        self
+    }
+
+    /// Append top-level YAML, e.g. a `features:` section.
+    fn yaml(mut self, yaml: &str) -> Self {
+        self.sections.push_str(yaml.trim_matches('\n'));
+        self.sections.push('\n');
+        self
+    }
+
+    fn to_yaml(&self) -> String {
+        let quoted = |items: &[String]| serde_json::to_string(items).expect("serialize list");
+        let default_index = self
+            .upstreams
+            .iter()
+            .position(|upstream| upstream.is_default)
+            .unwrap_or(0);
+        let mut yaml = String::from("upstream_services:\n");
+        for (index, upstream) in self.upstreams.iter().enumerate() {
+            yaml.push_str(&format!(
+                "  - name: {}\n    provider: {}\n    base_url: {}\n    api_key: upstream-secret\n    models: {}\n    fc_mode: {}\n    is_default: {}{}\n",
+                upstream.name,
+                upstream.provider,
+                upstream.base_url,
+                quoted(&upstream.models),
+                upstream.fc_mode,
+                index == default_index,
+                upstream.fields,
+            ));
+        }
+        yaml.push_str(&format!(
+            "client_authentication:\n  allowed_keys: {}\n  admin_keys: {}\n",
+            quoted(&self.allowed_keys),
+            quoted(&self.admin_keys),
+        ));
+        yaml.push_str(&self.client_auth);
+        yaml.push_str(&self.sections);
+        yaml
+    }
+
+    fn build(&self) -> AppConfig {
+        toolify_rs::config::parse_config(&self.to_yaml()).expect("valid config")
+    }
+
+    fn state(&self) -> Arc<AppState> {
+        Arc::new(AppState::from_config(self.build()))
+    }
+}
+
 fn allowed_keys(prefix: &str) -> Vec<String> {
     (0..96).map(|idx| format!("{prefix}-{idx}")).collect()
 }
@@ -68,13 +281,7 @@ async fn test_openai_responses_same_request_failover_to_alternate_upstream() {
             }
         }),
     );
-    let fail_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind failing responses upstream");
-    let fail_addr = fail_listener.local_addr().expect("failing responses addr");
-    let fail_server = tokio::spawn(async move {
-        let _ = axum::serve(fail_listener, fail_app).await;
-    });
+    let fail_addr = spawn_upstream(fail_app).await;
 
     let success_hits_clone = Arc::clone(&success_hits);
     let success_app = Router::new().route(
@@ -103,15 +310,7 @@ async fn test_openai_responses_same_request_failover_to_alternate_upstream() {
             }
         }),
     );
-    let success_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind success responses upstream");
-    let success_addr = success_listener
-        .local_addr()
-        .expect("success responses addr");
-    let success_server = tokio::spawn(async move {
-        let _ = axum::serve(success_listener, success_app).await;
-    });
+    let success_addr = spawn_upstream(success_app).await;
 
     let keys = allowed_keys("client-key-responses");
     let upstream_services = vec![
@@ -128,6 +327,17 @@ async fn test_openai_responses_same_request_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
         UpstreamServiceConfig {
             name: "responses-1".to_string(),
@@ -142,6 +352,17 @@ async fn test_openai_responses_same_request_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
     ];
     let state = build_state_multi_from_services(upstream_services, keys.clone());
@@ -191,9 +412,6 @@ async fn test_openai_responses_same_request_failover_to_alternate_upstream() {
         observed_failover,
         "expected at least one request to fail on primary and succeed on alternate responses upstream"
     );
-
-    fail_server.abort();
-    success_server.abort();
 }
 
 #[tokio::test]
@@ -218,13 +436,7 @@ async fn test_anthropic_same_request_failover_to_alternate_upstream() {
             }
         }),
     );
-    let fail_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind failing anthropic upstream");
-    let fail_addr = fail_listener.local_addr().expect("failing anthropic addr");
-    let fail_server = tokio::spawn(async move {
-        let _ = axum::serve(fail_listener, fail_app).await;
-    });
+    let fail_addr = spawn_upstream(fail_app).await;
 
     let success_hits_clone = Arc::clone(&success_hits);
     let success_app = Router::new().route(
@@ -246,15 +458,7 @@ async fn test_anthropic_same_request_failover_to_alternate_upstream() {
             }
         }),
     );
-    let success_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind success anthropic upstream");
-    let success_addr = success_listener
-        .local_addr()
-        .expect("success anthropic addr");
-    let success_server = tokio::spawn(async move {
-        let _ = axum::serve(success_listener, success_app).await;
-    });
+    let success_addr = spawn_upstream(success_app).await;
 
     let keys = allowed_keys("client-key-anthropic");
     let upstream_services = vec![
@@ -271,6 +475,17 @@ async fn test_anthropic_same_request_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
         UpstreamServiceConfig {
             name: "anthropic-1".to_string(),
@@ -285,6 +500,17 @@ async fn test_anthropic_same_request_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
     ];
     let state = build_state_multi_from_services(upstream_services, keys.clone());
@@ -326,9 +552,6 @@ async fn test_anthropic_same_request_failover_to_alternate_upstream() {
         observed_failover,
         "expected at least one request to fail on primary and succeed on alternate anthropic upstream"
     );
-
-    fail_server.abort();
-    success_server.abort();
 }
 
 #[tokio::test]
@@ -352,13 +575,7 @@ async fn test_gemini_same_request_failover_to_alternate_upstream() {
             }
         }),
     );
-    let fail_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind failing gemini upstream");
-    let fail_addr = fail_listener.local_addr().expect("failing gemini addr");
-    let fail_server = tokio::spawn(async move {
-        let _ = axum::serve(fail_listener, fail_app).await;
-    });
+    let fail_addr = spawn_upstream(fail_app).await;
 
     let success_hits_clone = Arc::clone(&success_hits);
     let success_app = Router::new().route(
@@ -382,13 +599,7 @@ async fn test_gemini_same_request_failover_to_alternate_upstream() {
             }
         }),
     );
-    let success_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind success gemini upstream");
-    let success_addr = success_listener.local_addr().expect("success gemini addr");
-    let success_server = tokio::spawn(async move {
-        let _ = axum::serve(success_listener, success_app).await;
-    });
+    let success_addr = spawn_upstream(success_app).await;
 
     let keys = allowed_keys("client-key-gemini");
     let upstream_services = vec![
@@ -405,6 +616,17 @@ async fn test_gemini_same_request_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
         UpstreamServiceConfig {
             name: "gemini-1".to_string(),
@@ -419,6 +641,17 @@ async fn test_gemini_same_request_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
     ];
     let state = build_state_multi_from_services(upstream_services, keys.clone());
@@ -466,9 +699,6 @@ async fn test_gemini_same_request_failover_to_alternate_upstream() {
         observed_failover,
         "expected at least one request to fail on primary and succeed on alternate gemini upstream"
     );
-
-    fail_server.abort();
-    success_server.abort();
 }
 
 #[tokio::test]
@@ -516,13 +746,7 @@ async fn test_gemini_auto_mode_native_to_inject_retry() {
         }),
     );
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind gemini auto upstream");
-    let addr = listener.local_addr().expect("gemini auto addr");
-    let server = tokio::spawn(async move {
-        let _ = axum::serve(listener, app).await;
-    });
+    let addr = spawn_upstream(app).await;
 
     let upstream_services = vec![UpstreamServiceConfig {
         name: "gemini-auto".to_string(),
@@ -537,6 +761,17 @@ async fn test_gemini_auto_mode_native_to_inject_retry() {
         proxy: None,
         proxy_stream: None,
         proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
     }];
     let state = build_state_multi_from_services(upstream_services, vec!["client-key".to_string()]);
 
@@ -596,8 +831,6 @@ async fn test_gemini_auto_mode_native_to_inject_retry() {
             .is_some_and(|second| second.get("tools").is_none()),
         "second request should be inject mode payload without native tools field"
     );
-
-    server.abort();
 }
 
 #[tokio::test]
@@ -655,13 +888,7 @@ async fn test_openai_chat_auto_mode_native_to_inject_retry() {
         }),
     );
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind openai auto upstream");
-    let addr = listener.local_addr().expect("openai auto addr");
-    let server = tokio::spawn(async move {
-        let _ = axum::serve(listener, app).await;
-    });
+    let addr = spawn_upstream(app).await;
 
     let upstream_services = vec![UpstreamServiceConfig {
         name: "openai-auto".to_string(),
@@ -676,6 +903,17 @@ async fn test_openai_chat_auto_mode_native_to_inject_retry() {
         proxy: None,
         proxy_stream: None,
         proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
     }];
     let state = build_state_multi_from_services(upstream_services, vec!["client-key".to_string()]);
 
@@ -736,8 +974,6 @@ async fn test_openai_chat_auto_mode_native_to_inject_retry() {
             .is_some_and(|second| second.get("tools").is_none()),
         "second request should be inject mode payload without native tools field"
     );
-
-    server.abort();
 }
 
 #[tokio::test]
@@ -791,13 +1027,7 @@ async fn test_openai_responses_auto_mode_native_to_inject_retry() {
         }),
     );
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind responses auto upstream");
-    let addr = listener.local_addr().expect("responses auto addr");
-    let server = tokio::spawn(async move {
-        let _ = axum::serve(listener, app).await;
-    });
+    let addr = spawn_upstream(app).await;
 
     let upstream_services = vec![UpstreamServiceConfig {
         name: "responses-auto".to_string(),
@@ -812,6 +1042,17 @@ async fn test_openai_responses_auto_mode_native_to_inject_retry() {
         proxy: None,
         proxy_stream: None,
         proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
     }];
     let state = build_state_multi_from_services(upstream_services, vec!["client-key".to_string()]);
 
@@ -876,8 +1117,6 @@ async fn test_openai_responses_auto_mode_native_to_inject_retry() {
             .is_some_and(|second| second.get("tools").is_none()),
         "second request should be inject mode payload without native tools field"
     );
-
-    server.abort();
 }
 
 #[tokio::test]
@@ -925,13 +1164,7 @@ async fn test_anthropic_auto_mode_native_to_inject_retry() {
         }),
     );
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind anthropic auto upstream");
-    let addr = listener.local_addr().expect("anthropic auto addr");
-    let server = tokio::spawn(async move {
-        let _ = axum::serve(listener, app).await;
-    });
+    let addr = spawn_upstream(app).await;
 
     let upstream_services = vec![UpstreamServiceConfig {
         name: "anthropic-auto".to_string(),
@@ -946,6 +1179,17 @@ async fn test_anthropic_auto_mode_native_to_inject_retry() {
         proxy: None,
         proxy_stream: None,
         proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
     }];
     let state = build_state_multi_from_services(upstream_services, vec!["client-key".to_string()]);
 
@@ -1001,8 +1245,6 @@ async fn test_anthropic_auto_mode_native_to_inject_retry() {
             .is_some_and(|second| second.get("tools").is_none()),
         "second request should be inject mode payload without native tools field"
     );
-
-    server.abort();
 }
 
 #[tokio::test]
@@ -1026,13 +1268,7 @@ async fn test_gemini_fc_non_stream_failover_to_alternate_upstream() {
             }
         }),
     );
-    let fail_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind failing gemini upstream");
-    let fail_addr = fail_listener.local_addr().expect("failing gemini addr");
-    let fail_server = tokio::spawn(async move {
-        let _ = axum::serve(fail_listener, fail_app).await;
-    });
+    let fail_addr = spawn_upstream(fail_app).await;
 
     let success_hits_clone = Arc::clone(&success_hits);
     let success_app = Router::new().route(
@@ -1056,13 +1292,7 @@ async fn test_gemini_fc_non_stream_failover_to_alternate_upstream() {
             }
         }),
     );
-    let success_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("bind success gemini upstream");
-    let success_addr = success_listener.local_addr().expect("success gemini addr");
-    let success_server = tokio::spawn(async move {
-        let _ = axum::serve(success_listener, success_app).await;
-    });
+    let success_addr = spawn_upstream(success_app).await;
 
     let keys = allowed_keys("client-key-gemini-fc");
     let upstream_services = vec![
@@ -1079,6 +1309,17 @@ async fn test_gemini_fc_non_stream_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
         UpstreamServiceConfig {
             name: "gemini-fc-1".to_string(),
@@ -1093,6 +1334,17 @@ async fn test_gemini_fc_non_stream_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
     ];
     let state = build_state_multi_from_services(upstream_services, keys.clone());
@@ -1156,7 +1408,3793 @@ async fn test_gemini_fc_non_stream_failover_to_alternate_upstream() {
         observed_failover,
         "expected at least one request to fail on primary and succeed on alternate gemini upstream in FC non-stream path"
     );
+}
+
+#[tokio::test]
+async fn test_anthropic_upstream_rate_limit_propagates_retry_after() {
+    let app = Router::new().route(
+        "/v1/messages",
+        post(|| async {
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [("retry-after", "1")],
+                Json(json!({
+                    "type": "error",
+                    "error": { "type": "rate_limit_error", "message": "slow down" }
+                })),
+            )
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "anthropic-limited",
+            "anthropic",
+            format!("http://{addr}/v1"),
+            &["claude-3-5-haiku-latest"],
+        ))
+        .state();
+    let request_body = json!({
+        "model": "claude-3-5-haiku-latest",
+        "messages": [{ "role": "user", "content": "ping" }],
+        "stream": false
+    });
+
+    let response = send(
+        &state,
+        "POST",
+        "/v1/messages",
+        "client-key",
+        json_body(&request_body),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        response
+            .headers()
+            .get("retry-after")
+            .and_then(|value| value.to_str().ok()),
+        Some("1")
+    );
+
+    let payload = read_json(response).await;
+    assert_eq!(payload["type"], "error");
+    assert_eq!(payload["error"]["type"], "rate_limit_error");
+}
+
+#[tokio::test]
+async fn test_rate_limited_upstream_cools_down_and_is_reported_by_admin_api() {
+    let limited_hits = Arc::new(AtomicUsize::new(0));
+    let limited_hits_clone = Arc::clone(&limited_hits);
+    let limited_app = Router::new().route(
+        "/v1/messages",
+        post(move || {
+            let limited_hits = Arc::clone(&limited_hits_clone);
+            async move {
+                limited_hits.fetch_add(1, Ordering::Relaxed);
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [("retry-after", "60")],
+                    Json(json!({
+                        "type": "error",
+                        "error": { "type": "rate_limit_error", "message": "slow down" }
+                    })),
+                )
+            }
+        }),
+    );
+    let limited_addr = spawn_upstream(limited_app).await;
+
+    let healthy_app = Router::new().route(
+        "/v1/messages",
+        post(|| async {
+            Json(json!({
+                "id": "msg_mock",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-3-5-haiku-latest",
+                "content": [{ "type": "text", "text": "cooldown-failover-ok" }],
+                "stop_reason": "end_turn",
+                "stop_sequence": null,
+                "usage": { "input_tokens": 5, "output_tokens": 2 }
+            }))
+        }),
+    );
+    let healthy_addr = spawn_upstream(healthy_app).await;
+
+    let keys = allowed_keys("client-key-cooldown");
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "anthropic-0",
+            "anthropic",
+            format!("http://{limited_addr}/v1"),
+            &["claude-3-5-haiku-latest"],
+        ))
+        .upstream(upstream(
+            "anthropic-1",
+            "anthropic",
+            format!("http://{healthy_addr}/v1"),
+            &["claude-3-5-haiku-latest"],
+        ))
+        .allowed_keys(&keys)
+        .admin_keys(["admin-key"])
+        .state();
+    let request_body = json!({
+        "model": "claude-3-5-haiku-latest",
+        "messages": [{ "role": "user", "content": "ping" }],
+        "stream": false
+    });
+
+    for key in &keys {
+        let response = send(
+            &state,
+            "POST",
+            "/v1/messages",
+            key,
+            json_body(&request_body),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // The long retry-after is not slept on in place and the route stays in
+    // cooldown afterwards, so the throttled upstream sees a single request.
+    assert_eq!(limited_hits.load(Ordering::Relaxed), 1);
+
+    let response = send(&state, "GET", "/admin/cooldowns", &keys[0], Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send(
+        &state,
+        "GET",
+        "/admin/cooldowns",
+        "admin-key",
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let payload = read_json(response).await;
+    let routes = payload["data"].as_array().expect("cooldown list");
+    assert_eq!(routes.len(), 1);
+    assert_eq!(routes[0]["upstream"], "anthropic-0");
+    assert_eq!(routes[0]["model"], "claude-3-5-haiku-latest");
+    assert_eq!(routes[0]["throttle_status"], 429);
+    assert_eq!(routes[0]["cooling_down"], true);
+}
+
+#[tokio::test]
+async fn test_openai_chat_stream_usage_follows_include_usage_for_anthropic_upstream() {
+    let app = Router::new().route(
+        "/v1/messages",
+        post(|| async {
+            let sse = concat!(
+                "event: message_start\n",
+                "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"model\":\"claude-3-5-haiku-latest\",\"content\":[],\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n",
+                "event: content_block_delta\n",
+                "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"pong\"}}\n\n",
+                "event: message_delta\n",
+                "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\",\"stop_sequence\":null},\"usage\":{\"output_tokens\":4}}\n\n",
+                "event: message_stop\n",
+                "data: {\"type\":\"message_stop\"}\n\n",
+            );
+            ([("content-type", "text/event-stream")], sse)
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "anthropic-stream",
+            "anthropic",
+            format!("http://{addr}/v1"),
+            &["claude-3-5-haiku-latest"],
+        ))
+        .state();
+    for include_usage in [true, false] {
+        let request_body = json!({
+            "model": "claude-3-5-haiku-latest",
+            "messages": [{ "role": "user", "content": "ping" }],
+            "stream": true,
+            "stream_options": { "include_usage": include_usage }
+        });
+        let response = send(
+            &state,
+            "POST",
+            "/v1/chat/completions",
+            "client-key",
+            json_body(&request_body),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = read_text(response).await;
+        assert!(text.contains("pong"));
+        assert!(text.trim_end().ends_with("data: [DONE]"));
+        let usage_chunks: Vec<serde_json::Value> = text
+            .split("\n\n")
+            .filter_map(|frame| frame.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<serde_json::Value>(data).ok())
+            .filter(|chunk| chunk.get("usage").is_some_and(|usage| !usage.is_null()))
+            .collect();
+        if include_usage {
+            assert_eq!(usage_chunks.len(), 1, "one final usage chunk: {text}");
+            assert_eq!(usage_chunks[0]["choices"], json!([]));
+            assert_eq!(usage_chunks[0]["usage"]["prompt_tokens"], 12);
+            assert_eq!(usage_chunks[0]["usage"]["completion_tokens"], 4);
+        } else {
+            assert!(usage_chunks.is_empty(), "usage not requested: {text}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_admin_latency_and_metrics_report_streaming_routes() {
+    let app = Router::new().route(
+        "/v1/messages",
+        post(|| async {
+            let sse = concat!(
+                "event: message_start\n",
+                "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"model\":\"claude-3-5-haiku-latest\",\"content\":[],\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n",
+                "event: content_block_delta\n",
+                "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"pong\"}}\n\n",
+                "event: message_delta\n",
+                "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\",\"stop_sequence\":null},\"usage\":{\"output_tokens\":4}}\n\n",
+                "event: message_stop\n",
+                "data: {\"type\":\"message_stop\"}\n\n",
+            );
+            ([("content-type", "text/event-stream")], sse)
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "anthropic-stream",
+            "anthropic",
+            format!("http://{addr}/v1"),
+            &["claude-3-5-haiku-latest"],
+        ))
+        .admin_keys(["admin-key"])
+        .state();
+    let request_body = json!({
+        "model": "claude-3-5-haiku-latest",
+        "max_tokens": 64,
+        "messages": [{ "role": "user", "content": "ping" }],
+        "stream": true
+    });
+    for _ in 0..2 {
+        let response = send(
+            &state,
+            "POST",
+            "/v1/messages",
+            "client-key",
+            json_body(&request_body),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        read_text(response).await;
+    }
+
+    let response = send(&state, "GET", "/admin/latency", "admin-key", Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let payload = read_json(response).await;
+    let routes = payload["data"].as_array().expect("latency list");
+    assert_eq!(routes.len(), 1);
+    assert_eq!(routes[0]["upstream"], "anthropic-stream");
+    assert_eq!(routes[0]["model"], "claude-3-5-haiku-latest");
+    assert_eq!(routes[0]["samples"], 2);
+    assert!(routes[0]["ttfb_ms_avg"].is_u64());
+
+    let response = send(&state, "GET", "/admin/metrics", "admin-key", Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let text = read_text(response).await;
+    assert!(text.contains(
+        "toolify_upstream_ttfb_ms{upstream=\"anthropic-stream\",model=\"claude-3-5-haiku-latest\"}"
+    ));
+}
+
+#[tokio::test]
+async fn test_admin_config_validate_then_apply_swaps_routing() {
+    let app = Router::new().route(
+        "/v1/messages",
+        post(|| async {
+            Json(json!({
+                "id": "msg_mock",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-new",
+                "content": [{ "type": "text", "text": "swapped" }],
+                "stop_reason": "end_turn",
+                "stop_sequence": null,
+                "usage": { "input_tokens": 3, "output_tokens": 1 }
+            }))
+        }),
+    );
+    let addr = spawn_upstream(app).await;
 
-    fail_server.abort();
-    success_server.abort();
+    let config = TestConfig::new()
+        .upstream(upstream(
+            "anthropic-a",
+            "anthropic",
+            format!("http://{addr}/v1"),
+            &["claude-old"],
+        ))
+        .admin_keys(["admin-key"]);
+    let state = config.state();
+    let candidate = TestConfig {
+        upstreams: vec![upstream(
+            "anthropic-a",
+            "anthropic",
+            format!("http://{addr}/v1"),
+            &["claude-new"],
+        )],
+        ..config
+    }
+    .to_yaml();
+    let admin_post =
+        |uri: &'static str, body: String| send(&state, "POST", uri, "admin-key", Body::from(body));
+    let message = || {
+        send(
+            &state,
+            "POST",
+            "/v1/messages",
+            "client-key",
+            json_body(&json!({
+                "model": "claude-new",
+                "max_tokens": 16,
+                "messages": [{ "role": "user", "content": "ping" }]
+            })),
+        )
+    };
+
+    let response = admin_post("/admin/config/validate", "upstream_services: [".to_string()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = admin_post("/admin/config/validate", candidate.clone()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let payload = read_json(response).await;
+    assert_eq!(payload["valid"], true);
+    assert_eq!(payload["reachable"], true);
+    assert_eq!(payload["probes"][0]["upstream"], "anthropic-a");
+    assert_eq!(
+        payload["changes"][0]["path"],
+        "upstream_services[anthropic-a].models"
+    );
+    assert_eq!(payload["changes"][0]["to"], json!(["claude-new"]));
+    assert_eq!(payload["restart_required"], json!([]));
+
+    // Validation alone does not change routing.
+    let response = message().await;
+    assert_ne!(response.status(), StatusCode::OK);
+
+    let response = admin_post("/admin/config/apply", candidate.clone()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let payload = read_json(response).await;
+    assert_eq!(payload["applied"], true);
+
+    let response = message().await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let payload = read_json(response).await;
+    assert_eq!(payload["content"][0]["text"], "swapped");
+
+    let response = admin_post("/admin/config/apply", candidate).await;
+    let payload = read_json(response).await;
+    assert_eq!(payload["applied"], false);
+    assert_eq!(payload["changes"], json!([]));
+}
+
+#[tokio::test]
+async fn test_readiness_waits_for_upstream_warmup() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let app_hits = Arc::clone(&hits);
+    let app = Router::new().route(
+        "/v1",
+        axum::routing::get(move || {
+            let hits = Arc::clone(&app_hits);
+            async move {
+                hits.fetch_add(1, Ordering::Relaxed);
+                StatusCode::NOT_FOUND
+            }
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "warm",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .yaml("server:\n  warmup_upstreams: true")
+        .state();
+    let ready = || {
+        dispatch(
+            &state,
+            Request::get("/health/ready")
+                .body(Body::empty())
+                .expect("build ready request"),
+        )
+    };
+
+    let response = ready().await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    state.warm_up_upstreams().await;
+    assert_eq!(hits.load(Ordering::Relaxed), 1);
+
+    let response = ready().await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let payload = read_json(response).await;
+    assert_eq!(payload["status"], "ready");
+    assert_eq!(payload["warmup"][0]["upstream"], "warm");
+    assert_eq!(payload["warmup"][0]["reachable"], true);
+    assert_eq!(payload["warmup"][0]["status"], 404);
+}
+
+#[tokio::test]
+async fn test_kubernetes_probes_track_upstream_health() {
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai-0",
+            "openai",
+            "http://127.0.0.1:9/v1",
+            &["gpt-4o"],
+        ))
+        .upstream(upstream(
+            "openai-1",
+            "openai",
+            "http://127.0.0.1:9/v1",
+            &["gpt-4o"],
+        ))
+        .admin_keys(["admin-key"])
+        .state();
+    let probe = |path: &'static str, admin: bool| {
+        let state = Arc::clone(&state);
+        async move {
+            let response = if admin {
+                send(&state, "GET", path, "admin-key", Body::empty()).await
+            } else {
+                let request = Request::get(path)
+                    .body(Body::empty())
+                    .expect("build probe request");
+                dispatch(&state, request).await
+            };
+            (response.status(), read_json(response).await)
+        }
+    };
+    let throttled = toolify_rs::error::CanonicalError::Upstream {
+        status: 429,
+        message: "rate limited".to_string(),
+        retry_after: Some(60),
+    };
+
+    assert_eq!(probe("/healthz", false).await.0, StatusCode::OK);
+    assert_eq!(probe("/startupz", false).await.0, StatusCode::OK);
+
+    state.record_upstream_failure(0, "gpt-4o", &throttled);
+    let (status, payload) = probe("/readyz", false).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(payload["healthy_upstreams"], 1);
+    assert!(payload.get("upstreams").is_none());
+
+    let (_, payload) = probe("/readyz", true).await;
+    assert_eq!(payload["upstreams"][0]["upstream"], "openai-0");
+    assert_eq!(payload["upstreams"][0]["healthy"], false);
+    assert_eq!(payload["upstreams"][0]["cooling_down_models"][0], "gpt-4o");
+    assert_eq!(payload["upstreams"][1]["healthy"], true);
+
+    state.record_upstream_failure(1, "gpt-4o", &throttled);
+    let (status, payload) = probe("/readyz", false).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(payload["status"], "no_healthy_upstream");
+    assert_eq!(probe("/healthz", false).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_stream_broadcast_replays_prefix_then_live_frames() {
+    let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+    let release_rx = Arc::new(Mutex::new(Some(release_rx)));
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move || {
+            let release_rx = release_rx
+                .lock()
+                .expect("release lock")
+                .take()
+                .expect("single upstream call");
+            async move {
+                let first = "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"po\"},\"finish_reason\":null}]}\n\n";
+                let rest = "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"ng\"},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n";
+                let head = futures_util::stream::once(async move {
+                    Ok::<_, std::io::Error>(first)
+                });
+                let tail = futures_util::stream::once(async move {
+                    let _ = release_rx.await;
+                    Ok::<_, std::io::Error>(rest)
+                });
+                (
+                    [("content-type", "text/event-stream")],
+                    Body::from_stream(futures_util::StreamExt::chain(head, tail)),
+                )
+            }
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai-stream",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .yaml("features:\n  enable_stream_broadcast: true")
+        .state();
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("authorization", "Bearer client-key")
+        .header("content-type", "application/json")
+        .header("x-toolify-session", "session-1")
+        .body(json_body(&json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "ping" }],
+            "stream": true
+        })))
+        .expect("build request");
+    let response = dispatch(&state, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut origin = response.into_body().into_data_stream();
+    let mut origin_bytes = Vec::new();
+    let first = futures_util::StreamExt::next(&mut origin)
+        .await
+        .expect("first chunk")
+        .expect("first chunk ok");
+    origin_bytes.extend_from_slice(&first);
+
+    let attach = || {
+        send(
+            &state,
+            "GET",
+            "/v1/streams/session-1",
+            "client-key",
+            Body::empty(),
+        )
+    };
+    let subscriber = attach().await;
+    assert_eq!(subscriber.status(), StatusCode::OK);
+
+    release_tx.send(()).expect("release upstream tail");
+    while let Some(chunk) = futures_util::StreamExt::next(&mut origin).await {
+        origin_bytes.extend_from_slice(&chunk.expect("origin chunk"));
+    }
+    let replayed = axum::body::to_bytes(subscriber.into_body(), usize::MAX)
+        .await
+        .expect("read subscriber stream");
+    assert_eq!(replayed.as_ref(), origin_bytes.as_slice());
+    assert!(String::from_utf8_lossy(&replayed).contains("[DONE]"));
+    drop(origin);
+
+    assert_eq!(attach().await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_non_stream_request_is_aggregated_from_stream_only_upstream() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(body): Json<serde_json::Value>| async move {
+            if body["stream"] != true {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    [("content-type", "application/json")],
+                    "{\"error\":{\"message\":\"stream required\"}}".to_string(),
+                );
+            }
+            let sse = concat!(
+                "data: {\"id\":\"chatcmpl-7\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"po\"},\"finish_reason\":null}]}\n\n",
+                "data: {\"id\":\"chatcmpl-7\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"ng\"},\"finish_reason\":\"stop\"}]}\n\n",
+                "data: {\"id\":\"chatcmpl-7\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o\",\"choices\":[],\"usage\":{\"prompt_tokens\":5,\"completion_tokens\":2,\"total_tokens\":7}}\n\n",
+                "data: [DONE]\n\n",
+            );
+            (
+                StatusCode::OK,
+                [("content-type", "text/event-stream")],
+                sse.to_string(),
+            )
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(
+            upstream(
+                "stream-only",
+                "openai",
+                format!("http://{addr}/v1"),
+                &["gpt-4o"],
+            )
+            .with("stream_support", "stream_only"),
+        )
+        .state();
+    let post = |uri: &'static str, body: serde_json::Value| {
+        let state = Arc::clone(&state);
+        async move {
+            let response = send(&state, "POST", uri, "client-key", json_body(&body)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            read_json(response).await
+        }
+    };
+
+    let chat = post(
+        "/v1/chat/completions",
+        json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "ping" }],
+            "stream": false
+        }),
+    )
+    .await;
+    assert_eq!(chat["object"], "chat.completion");
+    assert_eq!(chat["choices"][0]["message"]["content"], "pong");
+    assert_eq!(chat["usage"]["total_tokens"], 7);
+
+    let anthropic = post(
+        "/v1/messages",
+        json!({
+            "model": "gpt-4o",
+            "max_tokens": 64,
+            "messages": [{ "role": "user", "content": "ping" }]
+        }),
+    )
+    .await;
+    assert_eq!(anthropic["type"], "message");
+    assert_eq!(anthropic["content"][0]["text"], "pong");
+    assert_eq!(anthropic["stop_reason"], "end_turn");
+    assert_eq!(anthropic["usage"]["output_tokens"], 2);
+}
+
+#[tokio::test]
+async fn test_stream_request_is_synthesized_from_non_stream_only_upstream() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(body): Json<serde_json::Value>| async move {
+            if body["stream"] == true {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": { "message": "streaming unsupported" } })),
+                );
+            }
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "id": "chatcmpl-5",
+                    "object": "chat.completion",
+                    "created": 1,
+                    "model": "gpt-4o",
+                    "choices": [{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "hello" },
+                        "finish_reason": "stop"
+                    }],
+                    "usage": { "prompt_tokens": 4, "completion_tokens": 1, "total_tokens": 5 }
+                })),
+            )
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(
+            upstream(
+                "non-stream-only",
+                "openai",
+                format!("http://{addr}/v1"),
+                &["gpt-4o"],
+            )
+            .with("stream_support", "non_stream_only"),
+        )
+        .yaml("features:\n  synthetic_stream_chunk_chars: 2\n  synthetic_stream_interval_ms: 0")
+        .state();
+    let post = |uri: &'static str, body: serde_json::Value| {
+        let state = Arc::clone(&state);
+        async move {
+            let response = send(&state, "POST", uri, "client-key", json_body(&body)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()["content-type"],
+                "text/event-stream",
+                "synthesized responses stream"
+            );
+            read_text(response).await
+        }
+    };
+
+    let chat = post(
+        "/v1/chat/completions",
+        json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "ping" }],
+            "stream": true
+        }),
+    )
+    .await;
+    let deltas: Vec<String> = chat
+        .split("\n\n")
+        .filter_map(|frame| frame.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .filter_map(|data| serde_json::from_str::<serde_json::Value>(data).ok())
+        .filter_map(|chunk| {
+            chunk["choices"][0]["delta"]["content"]
+                .as_str()
+                .map(str::to_string)
+        })
+        .collect();
+    assert_eq!(deltas, vec!["he", "ll", "o"]);
+    assert!(chat.contains("\"finish_reason\":\"stop\""));
+    assert!(!chat.contains("\"usage\""));
+    assert!(chat.trim_end().ends_with("data: [DONE]"));
+
+    let anthropic = post(
+        "/v1/messages",
+        json!({
+            "model": "gpt-4o",
+            "max_tokens": 64,
+            "messages": [{ "role": "user", "content": "ping" }],
+            "stream": true
+        }),
+    )
+    .await;
+    assert!(anthropic.contains("event: message_start"));
+    assert!(anthropic.contains("\"text\":\"he\""));
+    assert!(anthropic.contains("\"output_tokens\":1"));
+    assert!(anthropic.contains("event: message_stop"));
+}
+
+#[tokio::test]
+async fn test_moderation_blocks_or_annotates_flagged_input_with_cached_verdicts() {
+    let moderation_calls = Arc::new(AtomicUsize::new(0));
+    let calls = Arc::clone(&moderation_calls);
+    let app = Router::new()
+        .route(
+            "/v1/moderations",
+            post(move |Json(body): Json<serde_json::Value>| {
+                let calls = Arc::clone(&calls);
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    let flagged = body["input"] == "bad words";
+                    Json(json!({
+                        "id": "modr-1",
+                        "results": [{
+                            "flagged": flagged,
+                            "categories": { "harassment": flagged, "violence": false }
+                        }]
+                    }))
+                }
+            }),
+        )
+        .route(
+            "/v1/chat/completions",
+            post(|| async {
+                Json(json!({
+                    "id": "chatcmpl-6",
+                    "object": "chat.completion",
+                    "created": 1,
+                    "model": "gpt-4o",
+                    "choices": [{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "ok" },
+                        "finish_reason": "stop"
+                    }]
+                }))
+            }),
+        );
+    let addr = spawn_upstream(app).await;
+
+    let state_with_action = |action: &str| {
+        TestConfig::new()
+            .upstream(upstream(
+                "moderated",
+                "openai",
+                format!("http://{addr}/v1"),
+                &["gpt-4o"],
+            ))
+            .yaml(&format!(
+                r#"
+features:
+  moderation:
+    endpoint: http://{addr}/v1/moderations
+    api_key: moderation-secret
+    action: {action}
+    timeout_ms: 3000
+    cache_entries: 16
+"#
+            ))
+            .state()
+    };
+    let chat = |state: &Arc<AppState>, text: &str| {
+        let body = json_body(&json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": text }]
+        }));
+        let state = Arc::clone(state);
+        async move { send(&state, "POST", "/v1/chat/completions", "client-key", body).await }
+    };
+
+    let annotating = state_with_action("annotate");
+    let response = chat(&annotating, "bad words").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["x-toolify-moderation"],
+        "flagged; categories=harassment"
+    );
+    let response = chat(&annotating, "bad words").await;
+    assert!(response.headers().contains_key("x-toolify-moderation"));
+    assert_eq!(
+        moderation_calls.load(Ordering::SeqCst),
+        1,
+        "verdict is cached by content"
+    );
+    let response = chat(&annotating, "hello").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("x-toolify-moderation"));
+
+    let blocking = state_with_action("block");
+    let response = chat(&blocking, "bad words").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = chat(&blocking, "hello").await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_routing_rules_pin_upstream_or_model_group_by_path_and_header() {
+    let reply = |upstream: &'static str| {
+        post(move |Json(body): Json<serde_json::Value>| async move {
+            Json(json!({
+                "id": "chatcmpl-7",
+                "object": "chat.completion",
+                "created": 1,
+                "model": body["model"],
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": format!("{upstream}:{}", body["model"].as_str().unwrap_or_default())
+                    },
+                    "finish_reason": "stop"
+                }]
+            }))
+        })
+    };
+    let app = Router::new()
+        .route("/shared/v1/chat/completions", reply("shared"))
+        .route("/research/v1/chat/completions", reply("research"));
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "shared",
+            "openai",
+            format!("http://{addr}/shared/v1"),
+            &["gpt-4o", "cheap:gpt-4o-mini"],
+        ))
+        .upstream(upstream(
+            "research",
+            "openai",
+            format!("http://{addr}/research/v1"),
+            &["o1"],
+        ))
+        .yaml(
+            r#"
+routing_rules:
+  - name: research-path
+    match:
+      path_prefix: /research
+    upstream: research
+  - name: research-team
+    match:
+      header: { name: x-team, value: research }
+    upstream: research
+  - name: budget-team
+    match:
+      header: { name: x-team, value: budget }
+    model: cheap
+"#,
+        )
+        .state();
+    let ask = |uri: &str, team: Option<&str>| {
+        let mut request = Request::post(uri)
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json");
+        if let Some(team) = team {
+            request = request.header("x-team", team);
+        }
+        let request = request
+            .body(json_body(&json!({
+                "model": "gpt-4o",
+                "messages": [{ "role": "user", "content": "hi" }]
+            })))
+            .expect("build request");
+        let state = Arc::clone(&state);
+        async move {
+            let response = dispatch(&state, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            read_json(response).await["choices"][0]["message"]["content"]
+                .as_str()
+                .expect("content")
+                .to_string()
+        }
+    };
+
+    assert_eq!(ask("/v1/chat/completions", None).await, "shared:gpt-4o");
+    assert_eq!(
+        ask("/research/v1/chat/completions", None).await,
+        "research:gpt-4o"
+    );
+    assert_eq!(
+        ask("/v1/chat/completions", Some("research")).await,
+        "research:gpt-4o"
+    );
+    assert_eq!(
+        ask("/v1/chat/completions", Some("budget")).await,
+        "shared:gpt-4o-mini"
+    );
+    assert_eq!(
+        ask("/v1/chat/completions", Some("unknown")).await,
+        "shared:gpt-4o"
+    );
+}
+
+#[tokio::test]
+async fn test_cascade_model_escalates_rejected_drafts_to_verify_model() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(body): Json<serde_json::Value>| async move {
+            let model = body["model"].as_str().unwrap_or_default().to_string();
+            let prompt = body["messages"][0]["content"].as_str().unwrap_or_default();
+            let content = match (model.as_str(), prompt) {
+                ("small", "hard") => "I can't help with that.".to_string(),
+                (model, _) => format!("{model} answered"),
+            };
+            if body["stream"] == true {
+                let sse = format!(
+                    "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+                    json!({
+                        "id": "chatcmpl-8", "object": "chat.completion.chunk", "created": 1,
+                        "model": model,
+                        "choices": [{ "index": 0, "delta": { "role": "assistant", "content": content }, "finish_reason": null }]
+                    }),
+                    json!({
+                        "id": "chatcmpl-8", "object": "chat.completion.chunk", "created": 1,
+                        "model": model,
+                        "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }]
+                    }),
+                );
+                return axum::response::Response::builder()
+                    .header("content-type", "text/event-stream")
+                    .body(Body::from(sse))
+                    .expect("sse response");
+            }
+            axum::response::IntoResponse::into_response(Json(json!({
+                "id": "chatcmpl-8",
+                "object": "chat.completion",
+                "created": 1,
+                "model": model,
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": content },
+                    "finish_reason": "stop"
+                }]
+            })))
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "tiers",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["small", "large"],
+        ))
+        .yaml(
+            r#"
+features:
+  synthetic_stream_interval_ms: 0
+cascade_models:
+  - name: auto
+    draft: small
+    verify: large
+    checks:
+      min_chars: 5
+      refusal_patterns: ["(?i)^i can't"]
+"#,
+        )
+        .state();
+    let ask = |prompt: &str, stream: bool| {
+        let body = json_body(&json!({
+            "model": "auto",
+            "messages": [{ "role": "user", "content": prompt }],
+            "stream": stream
+        }));
+        let state = Arc::clone(&state);
+        async move {
+            let response = send(&state, "POST", "/v1/chat/completions", "client-key", body).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let tier = response.headers()["x-toolify-cascade-tier"]
+                .to_str()
+                .expect("tier header")
+                .to_string();
+            (tier, read_text(response).await)
+        }
+    };
+
+    let (tier, body) = ask("easy", false).await;
+    assert_eq!(tier, "draft");
+    let payload: serde_json::Value = serde_json::from_str(&body).expect("json");
+    assert_eq!(
+        payload["choices"][0]["message"]["content"],
+        "small answered"
+    );
+    assert_eq!(payload["model"], "auto");
+
+    let (tier, body) = ask("hard", false).await;
+    assert_eq!(tier, "verify");
+    let payload: serde_json::Value = serde_json::from_str(&body).expect("json");
+    assert_eq!(
+        payload["choices"][0]["message"]["content"],
+        "large answered"
+    );
+    assert_eq!(payload["model"], "auto");
+
+    let (tier, body) = ask("easy", true).await;
+    assert_eq!(tier, "draft");
+    assert!(body.contains("small answered"), "draft replayed as stream");
+    assert!(body.trim_end().ends_with("data: [DONE]"));
+
+    let (tier, body) = ask("hard", true).await;
+    assert_eq!(tier, "verify");
+    assert!(body.contains("large answered"));
+    assert!(!body.contains("I can't"));
+}
+
+#[tokio::test]
+async fn test_output_postprocess_enforces_stop_split_across_chunks() {
+    let pieces = ["Hello world. E", "ND ignored", " tail  "];
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move |Json(body): Json<serde_json::Value>| async move {
+            // The upstream ignores the client's `stop` and runs to its limit.
+            if body["stream"] == true {
+                let mut sse = String::new();
+                for (index, piece) in pieces.iter().enumerate() {
+                    let delta = if index == 0 {
+                        json!({ "role": "assistant", "content": piece })
+                    } else {
+                        json!({ "content": piece })
+                    };
+                    sse.push_str(&format!(
+                        "data: {}\n\n",
+                        json!({
+                            "id": "chatcmpl-9", "object": "chat.completion.chunk", "created": 1,
+                            "model": "gpt-4o",
+                            "choices": [{ "index": 0, "delta": delta, "finish_reason": null }]
+                        })
+                    ));
+                }
+                sse.push_str(&format!(
+                    "data: {}\n\ndata: [DONE]\n\n",
+                    json!({
+                        "id": "chatcmpl-9", "object": "chat.completion.chunk", "created": 1,
+                        "model": "gpt-4o",
+                        "choices": [{ "index": 0, "delta": {}, "finish_reason": "length" }]
+                    })
+                ));
+                return axum::response::Response::builder()
+                    .header("content-type", "text/event-stream")
+                    .body(Body::from(sse))
+                    .expect("sse response");
+            }
+            axum::response::IntoResponse::into_response(Json(json!({
+                "id": "chatcmpl-9",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "gpt-4o",
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": pieces.concat() },
+                    "finish_reason": "length"
+                }]
+            })))
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "plain",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .yaml(
+            r#"
+features:
+  output_postprocess:
+    - type: regex_replace
+      pattern: world
+      replacement: there
+    - type: enforce_stop
+    - type: trim_trailing_whitespace
+"#,
+        )
+        .state();
+    let chat = |stream: bool| {
+        let body = json_body(&json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "hi" }],
+            "stop": ["END"],
+            "stream": stream
+        }));
+        let state = Arc::clone(&state);
+        async move {
+            let response = send(&state, "POST", "/v1/chat/completions", "client-key", body).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            read_text(response).await
+        }
+    };
+
+    let payload: serde_json::Value = serde_json::from_str(&chat(false).await).expect("json");
+    assert_eq!(payload["choices"][0]["message"]["content"], "Hello there.");
+    assert_eq!(payload["choices"][0]["finish_reason"], "stop");
+
+    let body = chat(true).await;
+    let mut content = String::new();
+    let mut finish_reasons = Vec::new();
+    for data in body.lines().filter_map(|line| line.strip_prefix("data: ")) {
+        if data == "[DONE]" {
+            continue;
+        }
+        let chunk: serde_json::Value = serde_json::from_str(data).expect("chunk json");
+        if let Some(text) = chunk["choices"][0]["delta"]["content"].as_str() {
+            content.push_str(text);
+        }
+        if let Some(reason) = chunk["choices"][0]["finish_reason"].as_str() {
+            finish_reasons.push(reason.to_string());
+        }
+    }
+    assert_eq!(content, "Hello there.");
+    assert_eq!(finish_reasons, vec!["stop"]);
+    assert!(body.trim_end().ends_with("data: [DONE]"));
+}
+
+#[tokio::test]
+async fn test_stop_sequences_are_emulated_for_responses_upstream_streams() {
+    let app = Router::new().route(
+        "/v1/responses",
+        post(|Json(body): Json<serde_json::Value>| async move {
+            // The Responses API has no stop parameter to forward.
+            assert!(body.get("stop").is_none());
+            let envelope = |kind: &str, status: &str| {
+                json!({
+                    "type": kind,
+                    "response": {
+                        "id": "resp_1", "object": "response", "created_at": 1,
+                        "model": "gpt-4o", "status": status, "output": []
+                    }
+                })
+            };
+            let mut sse = format!(
+                "event: response.created\ndata: {}\n\n",
+                envelope("response.created", "in_progress")
+            );
+            for delta in ["alpha ST", "OP beta", " gamma"] {
+                sse.push_str(&format!(
+                    "event: response.output_text.delta\ndata: {}\n\n",
+                    json!({
+                        "type": "response.output_text.delta",
+                        "output_index": 0, "content_index": 0, "delta": delta
+                    })
+                ));
+            }
+            sse.push_str(&format!(
+                "event: response.completed\ndata: {}\n\n",
+                envelope("response.completed", "completed")
+            ));
+            axum::response::Response::builder()
+                .header("content-type", "text/event-stream")
+                .body(Body::from(sse))
+                .expect("sse response")
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "responses",
+            "openai-responses",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .state();
+    let response = send(
+        &state,
+        "POST",
+        "/v1/chat/completions",
+        "client-key",
+        json_body(&json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "hi" }],
+            "stop": "STOP",
+            "stream": true
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = read_text(response).await;
+
+    let mut content = String::new();
+    let mut finish_reasons = Vec::new();
+    for data in body.lines().filter_map(|line| line.strip_prefix("data: ")) {
+        if data == "[DONE]" {
+            continue;
+        }
+        let chunk: serde_json::Value = serde_json::from_str(data).expect("chunk json");
+        if let Some(text) = chunk["choices"][0]["delta"]["content"].as_str() {
+            content.push_str(text);
+        }
+        if let Some(reason) = chunk["choices"][0]["finish_reason"].as_str() {
+            finish_reasons.push(reason.to_string());
+        }
+    }
+    assert_eq!(content, "alpha ");
+    assert_eq!(finish_reasons, vec!["stop"]);
+    assert_eq!(body.matches("data: [DONE]").count(), 1);
+}
+
+#[tokio::test]
+async fn test_admin_traces_export_conversation_turns() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|| async {
+            Json(json!({
+                "id": "chatcmpl-trace",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "gpt-4o",
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "pong" },
+                    "finish_reason": "stop"
+                }],
+                "usage": { "prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4 }
+            }))
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai-primary",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .admin_keys(["admin-key"])
+        .yaml(
+            r#"
+features:
+  conversation_traces:
+    max_conversations: 8
+    max_turns: 8
+    ttl_secs: 3600
+"#,
+        )
+        .state();
+
+    // The session hash covers the first 256 bytes of the history, which a
+    // long system prompt keeps stable across turns.
+    let system = json!({ "role": "system", "content": "You are a careful agent. ".repeat(16) });
+    let turns = [
+        json!([system, { "role": "user", "content": "ping" }]),
+        json!([
+            system,
+            { "role": "user", "content": "ping" },
+            { "role": "assistant", "content": "pong" },
+            { "role": "user", "content": "again" }
+        ]),
+    ];
+    let mut sessions = Vec::new();
+    for messages in turns {
+        let body = json_body(&json!({ "model": "gpt-4o", "messages": messages }));
+        let response = send(&state, "POST", "/v1/chat/completions", "client-key", body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let session = response
+            .headers()
+            .get("x-toolify-trace-session")
+            .expect("trace session header")
+            .to_str()
+            .expect("session header text")
+            .to_string();
+        read_text(response).await;
+        sessions.push(session);
+    }
+    assert_eq!(sessions[0], sessions[1]);
+
+    let trace_uri = format!("/admin/traces/{}", sessions[0]);
+    let response = send(&state, "GET", &trace_uri, "admin-key", Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let trace = read_json(response).await;
+    let turns = trace["turns"].as_array().expect("turns");
+    assert_eq!(turns.len(), 2);
+    assert_eq!(turns[0]["upstream"], "openai-primary");
+    assert_eq!(
+        turns[0]["response"]["choices"][0]["message"]["content"],
+        "pong"
+    );
+    assert_eq!(
+        turns[1]["messages"].as_array().expect("new messages").len(),
+        2
+    );
+    assert_eq!(trace["models"], json!(["gpt-4o"]));
+
+    let response = send(
+        &state,
+        "GET",
+        "/admin/traces/ffffffffffffffff",
+        "admin-key",
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_transcoded_stream_exposes_and_maps_upstream_response_id() {
+    let app = Router::new().route(
+        "/v1/messages",
+        post(|| async {
+            let sse = concat!(
+                "event: message_start\n",
+                "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_upstream_1\",\"type\":\"message\",\"role\":\"assistant\",\"model\":\"claude-3-5-haiku-latest\",\"content\":[],\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n",
+                "event: content_block_delta\n",
+                "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"pong\"}}\n\n",
+                "event: message_delta\n",
+                "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\",\"stop_sequence\":null},\"usage\":{\"output_tokens\":4}}\n\n",
+                "event: message_stop\n",
+                "data: {\"type\":\"message_stop\"}\n\n",
+            );
+            ([("content-type", "text/event-stream")], sse)
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "anthropic-stream",
+            "anthropic",
+            format!("http://{addr}/v1"),
+            &["claude-3-5-haiku-latest"],
+        ))
+        .admin_keys(["admin-key"])
+        .state();
+
+    let response = send(
+        &state,
+        "POST",
+        "/v1/chat/completions",
+        "client-key",
+        json_body(&json!({
+            "model": "claude-3-5-haiku-latest",
+            "messages": [{ "role": "user", "content": "ping" }],
+            "stream": true
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response
+            .headers()
+            .get("x-upstream-response-id")
+            .expect("upstream response id header"),
+        "msg_upstream_1"
+    );
+    let body = read_text(response).await;
+    let first = body
+        .lines()
+        .find_map(|line| line.strip_prefix("data: ").map(str::to_string))
+        .expect("first chunk");
+    let chunk: serde_json::Value = serde_json::from_str(&first).expect("chunk json");
+    let client_id = chunk["id"]
+        .as_str()
+        .expect("client response id")
+        .to_string();
+    assert!(client_id.starts_with("chatcmpl-"));
+
+    let mapping_uri = format!("/admin/response-ids/{client_id}");
+    let response = send(&state, "GET", &mapping_uri, "admin-key", Body::empty()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let mapping = read_json(response).await;
+    assert_eq!(mapping["id"], client_id.as_str());
+    assert_eq!(mapping["upstream_id"], "msg_upstream_1");
+    assert_eq!(mapping["upstream"], "anthropic-stream");
+}
+
+#[tokio::test]
+async fn test_json_error_after_stream_start_reaches_client_as_error_event() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|| async {
+            let body = concat!(
+                "data: {\"id\":\"chatcmpl-up\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"par\"},\"finish_reason\":null}]}\n\n",
+                "{\"error\":{\"message\":\"upstream overloaded\",\"type\":\"server_error\",\"code\":503}}",
+            );
+            ([("content-type", "text/event-stream")], body)
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai-gateway",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o-mini"],
+        ))
+        .state();
+
+    let response = send(
+        &state,
+        "POST",
+        "/v1/messages",
+        "client-key",
+        json_body(&json!({
+            "model": "gpt-4o-mini",
+            "max_tokens": 64,
+            "messages": [{ "role": "user", "content": "ping" }],
+            "stream": true
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = read_text(response).await;
+    assert!(
+        body.contains("\"text\":\"par\""),
+        "missing text delta: {body}"
+    );
+    let error = body
+        .split("\n\n")
+        .find(|frame| frame.starts_with("event: error"))
+        .unwrap_or_else(|| panic!("missing error event: {body}"));
+    assert!(error.contains("upstream overloaded"), "{error}");
+}
+
+#[tokio::test]
+async fn test_cors_preflight_and_response_headers_for_allowed_origins() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|| async {
+            Json(json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "gpt-4o-mini",
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "pong" },
+                    "finish_reason": "stop"
+                }]
+            }))
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o-mini"],
+        ))
+        .yaml(
+            r#"
+server:
+  cors:
+    allowed_origins: ["https://app.example.com"]
+    allowed_headers: [authorization, content-type]
+    allowed_methods: [POST]
+    expose_headers: [x-upstream-response-id]
+    max_age_secs: 60
+"#,
+        )
+        .state();
+
+    let preflight = |origin: &'static str| {
+        Request::builder()
+            .method("OPTIONS")
+            .uri("/v1/chat/completions")
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .header(
+                "access-control-request-headers",
+                "authorization, content-type",
+            )
+            .body(Body::empty())
+            .expect("build preflight")
+    };
+    let response = dispatch(&state, preflight("https://app.example.com")).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(headers["access-control-allow-methods"], "POST");
+    assert_eq!(
+        headers["access-control-allow-headers"],
+        "authorization, content-type"
+    );
+    assert_eq!(headers["access-control-max-age"], "60");
+
+    let response = dispatch(&state, preflight("https://evil.example.com")).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("origin", "https://app.example.com")
+        .header("authorization", "Bearer client-key")
+        .header("content-type", "application/json")
+        .body(json_body(&json!({
+            "model": "gpt-4o-mini",
+            "messages": [{ "role": "user", "content": "ping" }]
+        })))
+        .expect("build request");
+    let response = dispatch(&state, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert_eq!(
+        response.headers()["access-control-expose-headers"],
+        "x-upstream-response-id"
+    );
+}
+
+#[tokio::test]
+async fn test_key_model_maps_downgrade_one_tenant_and_keep_the_requested_model() {
+    let reply = |upstream: &'static str| {
+        post(move |Json(body): Json<serde_json::Value>| async move {
+            Json(json!({
+                "id": "chatcmpl-8",
+                "object": "chat.completion",
+                "created": 1,
+                "model": body["model"],
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": format!("{upstream}:{}", body["model"].as_str().unwrap_or_default())
+                    },
+                    "finish_reason": "stop"
+                }]
+            }))
+        })
+    };
+    let app = Router::new()
+        .route("/premium/v1/chat/completions", reply("premium"))
+        .route("/budget/v1/chat/completions", reply("budget"));
+    let addr = spawn_upstream(app).await;
+
+    let hashed_tenant = hash_api_key("tenant-b");
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "premium",
+            "openai",
+            format!("http://{addr}/premium/v1"),
+            &["gpt-4o"],
+        ))
+        .upstream(upstream(
+            "budget",
+            "openai",
+            format!("http://{addr}/budget/v1"),
+            &["downgraded:gpt-4o-mini"],
+        ))
+        .allowed_keys(["tenant-a", hashed_tenant.as_str()])
+        .client_auth(&format!(
+            r#"
+  key_model_maps:
+    - key: "{hashed_tenant}"
+      models:
+        gpt-4o: downgraded
+"#
+        ))
+        .state();
+    let chat = |key: &str| {
+        let body = json_body(&json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "hi" }]
+        }));
+        let (state, key) = (Arc::clone(&state), key.to_string());
+        async move {
+            let response = send(&state, "POST", "/v1/chat/completions", &key, body).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            read_json(response).await
+        }
+    };
+
+    let premium = chat("tenant-a").await;
+    assert_eq!(
+        premium["choices"][0]["message"]["content"],
+        "premium:gpt-4o"
+    );
+    let downgraded = chat("tenant-b").await;
+    assert_eq!(
+        downgraded["choices"][0]["message"]["content"],
+        "budget:gpt-4o-mini"
+    );
+    assert_eq!(downgraded["model"], "gpt-4o");
+}
+
+#[tokio::test]
+async fn test_strict_tool_arguments_are_retried_or_rejected_per_upstream() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let retry_prompts = Arc::new(Mutex::new(Vec::new()));
+    let reply = |calls: Arc<AtomicUsize>, prompts: Arc<Mutex<Vec<String>>>| {
+        post(move |Json(body): Json<serde_json::Value>| async move {
+            // The first answer breaks the schema; re-asked, the model complies.
+            let arguments = if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                r#"{"city":7}"#
+            } else {
+                r#"{"city":"Paris"}"#
+            };
+            if let Some(prompt) = body["messages"].as_array().and_then(|messages| {
+                messages
+                    .iter()
+                    .skip(1)
+                    .rev()
+                    .find(|message| message["role"] == "user")
+            }) {
+                prompts
+                    .lock()
+                    .expect("prompts lock")
+                    .push(prompt["content"].as_str().unwrap_or_default().to_string());
+            }
+            Json(json!({
+                "id": "chatcmpl-9",
+                "object": "chat.completion",
+                "created": 1,
+                "model": body["model"],
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_1",
+                            "type": "function",
+                            "function": { "name": "get_weather", "arguments": arguments }
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }]
+            }))
+        })
+    };
+    let app = Router::new()
+        .route(
+            "/retry/v1/chat/completions",
+            reply(Arc::clone(&calls), Arc::clone(&retry_prompts)),
+        )
+        .route(
+            "/error/v1/chat/completions",
+            reply(
+                Arc::new(AtomicUsize::new(0)),
+                Arc::new(Mutex::new(Vec::new())),
+            ),
+        );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(
+            upstream(
+                "retry",
+                "openai",
+                format!("http://{addr}/retry/v1"),
+                &["gpt-retry"],
+            )
+            .with("tool_schema_validation", "retry"),
+        )
+        .upstream(
+            upstream(
+                "error",
+                "openai",
+                format!("http://{addr}/error/v1"),
+                &["gpt-error"],
+            )
+            .with("tool_schema_validation", "error"),
+        )
+        .allowed_keys(["strict-tools-0"])
+        .state();
+    let chat = |model: &str| {
+        let body = json_body(&json!({
+            "model": model,
+            "messages": [{ "role": "user", "content": "Weather in Paris?" }],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "strict": true,
+                    "parameters": {
+                        "type": "object",
+                        "properties": { "city": { "type": "string" } },
+                        "required": ["city"],
+                        "additionalProperties": false
+                    }
+                }
+            }]
+        }));
+        let state = Arc::clone(&state);
+        async move {
+            send(
+                &state,
+                "POST",
+                "/v1/chat/completions",
+                "strict-tools-0",
+                body,
+            )
+            .await
+        }
+    };
+
+    let response = chat("gpt-retry").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = read_json(response).await;
+    assert_eq!(
+        body["choices"][0]["message"]["tool_calls"][0]["function"]["arguments"],
+        r#"{"city":"Paris"}"#
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    let prompts = retry_prompts.lock().expect("prompts lock").clone();
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0].contains("expected type 'string'"));
+
+    let response = chat("gpt-error").await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(read_text(response).await.contains("strict tool schema"));
+}
+
+#[tokio::test]
+async fn test_gemini_json_array_streams_on_both_sides() {
+    let queries = Arc::new(Mutex::new(Vec::new()));
+    let queries_clone = Arc::clone(&queries);
+    let app = Router::new().route(
+        "/v1beta/models/gemini-2.5-flash:streamGenerateContent",
+        post(move |uri: axum::http::Uri| {
+            let queries = Arc::clone(&queries_clone);
+            async move {
+                queries
+                    .lock()
+                    .expect("queries lock")
+                    .push(uri.query().unwrap_or_default().to_string());
+                // An upstream that ignores `alt=sse` and streams a JSON array.
+                (
+                    [("content-type", "application/json")],
+                    concat!(
+                        "[{\n  \"candidates\": [{\"content\": {\"role\": \"model\", ",
+                        "\"parts\": [{\"text\": \"Hello, \"}]}, \"index\": 0}]\n}\n,\r\n",
+                        "{\n  \"candidates\": [{\"content\": {\"role\": \"model\", ",
+                        "\"parts\": [{\"text\": \"world\"}]}, \"finishReason\": \"STOP\", ",
+                        "\"index\": 0}]\n}\n]"
+                    ),
+                )
+            }
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "gemini",
+            "gemini",
+            format!("http://{addr}/v1beta"),
+            &["gemini-2.5-flash"],
+        ))
+        .allowed_keys(["gemini-array-0"])
+        .state();
+    let stream = |uri: &'static str, body: serde_json::Value| {
+        let state = Arc::clone(&state);
+        async move {
+            let response = send(&state, "POST", uri, "gemini-array-0", json_body(&body)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let content_type = response
+                .headers()
+                .get("content-type")
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            (content_type, read_text(response).await)
+        }
+    };
+    let gemini_body = json!({ "contents": [{ "role": "user", "parts": [{ "text": "hi" }] }] });
+
+    let (content_type, body) = stream(
+        "/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=json",
+        gemini_body.clone(),
+    )
+    .await;
+    assert_eq!(content_type, "application/json");
+    let chunks: serde_json::Value = serde_json::from_str(&body).expect("json array body");
+    assert_eq!(
+        chunks[1]["candidates"][0]["content"]["parts"][0]["text"],
+        "world"
+    );
+
+    let (content_type, body) = stream(
+        "/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse",
+        gemini_body,
+    )
+    .await;
+    assert_eq!(content_type, "text/event-stream");
+    assert!(body.starts_with("data: {"));
+
+    let (_, body) = stream(
+        "/v1/chat/completions",
+        json!({
+            "model": "gemini-2.5-flash",
+            "stream": true,
+            "messages": [{ "role": "user", "content": "hi" }]
+        }),
+    )
+    .await;
+    assert!(body.contains("\"content\":\"Hello, \""));
+    assert!(body.contains("\"content\":\"world\""));
+    assert!(body.contains("\"finish_reason\":\"stop\""));
+
+    assert!(queries
+        .lock()
+        .expect("queries lock")
+        .iter()
+        .all(|query| query == "alt=sse"));
+}
+
+#[tokio::test]
+async fn test_gemini_v1_paths_and_model_listing() {
+    let app = Router::new().route(
+        "/v1beta/models/gemini-2.5-flash:generateContent",
+        post(|| async {
+            Json(json!({
+                "candidates": [{
+                    "content": { "role": "model", "parts": [{ "text": "v1-ok" }] },
+                    "finishReason": "STOP",
+                    "index": 0
+                }]
+            }))
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "gemini",
+            "gemini",
+            format!("http://{addr}/v1beta"),
+            &["gemini-2.5-flash"],
+        ))
+        .allowed_keys(["gemini-v1-0"])
+        .state();
+    const GOOG_KEY: (&str, &str) = ("x-goog-api-key", "gemini-v1-0");
+    // Ingress is picked from the auth header, so each request carries one.
+    let call = |method: &str, uri: &str, key: (&str, &str), body: Body| {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(key.0, key.1)
+            .header("content-type", "application/json")
+            .body(body)
+            .expect("build request");
+        let state = Arc::clone(&state);
+        async move {
+            let response = dispatch(&state, request).await;
+            (response.status(), read_json(response).await)
+        }
+    };
+
+    let (status, body) = call(
+        "POST",
+        "/v1/models/gemini-2.5-flash:generateContent",
+        GOOG_KEY,
+        Body::from(r#"{"contents":[{"role":"user","parts":[{"text":"hi"}]}]}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["candidates"][0]["content"]["parts"][0]["text"],
+        "v1-ok"
+    );
+
+    for (uri, key) in [("/v1beta/models", GOOG_KEY), ("/v1/models", GOOG_KEY)] {
+        let (status, body) = call("GET", uri, key, Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["models"][0]["name"], "models/gemini-2.5-flash");
+        assert_eq!(
+            body["models"][0]["supportedGenerationMethods"][1],
+            "streamGenerateContent"
+        );
+    }
+    let (_, body) = call(
+        "GET",
+        "/v1/models",
+        ("authorization", "Bearer gemini-v1-0"),
+        Body::empty(),
+    )
+    .await;
+    assert!(body["models"].is_null());
+    assert_eq!(body["data"][0]["id"], "gemini-2.5-flash");
+
+    let (status, body) = call(
+        "GET",
+        "/v1beta/models/gemini-2.5-flash",
+        GOOG_KEY,
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["displayName"], "gemini-2.5-flash");
+    let (status, body) = call(
+        "GET",
+        "/v1beta/models/unknown-model",
+        GOOG_KEY,
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["status"], "NOT_FOUND");
+
+    // `GET /v1/models/{model}` stays `OpenAI` model retrieval.
+    const BEARER_KEY: (&str, &str) = ("authorization", "Bearer gemini-v1-0");
+    let (status, body) = call(
+        "GET",
+        "/v1/models/gemini-2.5-flash",
+        BEARER_KEY,
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["object"], "model");
+    assert_eq!(body["id"], "gemini-2.5-flash");
+    let (status, body) = call("GET", "/v1/models/unknown-model", BEARER_KEY, Body::empty()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["type"], "not_found_error");
+    let (status, _) = call(
+        "GET",
+        "/v1/models/gemini-2.5-flash",
+        GOOG_KEY,
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_anthropic_message_batches_pass_through_to_anthropic_upstream() {
+    let app = Router::new()
+        .route(
+            "/v1/messages/batches",
+            post(
+                |headers: axum::http::HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                    assert_eq!(headers["x-api-key"], "upstream-secret");
+                    assert_eq!(headers["anthropic-beta"], "message-batches-2024-09-24");
+                    assert_eq!(body["requests"][0]["custom_id"], "req-1");
+                    Json(json!({
+                        "id": "msgbatch_01",
+                        "type": "message_batch",
+                        "processing_status": "in_progress"
+                    }))
+                },
+            ),
+        )
+        .route(
+            "/v1/messages/batches/{id}",
+            axum::routing::get(|| async {
+                Json(json!({
+                    "id": "msgbatch_01",
+                    "type": "message_batch",
+                    "processing_status": "ended"
+                }))
+            }),
+        )
+        .route(
+            "/v1/messages/batches/{id}/results",
+            axum::routing::get(|| async {
+                (
+                    [("content-type", "application/x-jsonl")],
+                    "{\"custom_id\":\"req-1\",\"result\":{\"type\":\"succeeded\"}}\n",
+                )
+            }),
+        );
+    let addr = spawn_upstream(app).await;
+
+    let openai = || {
+        upstream("openai", "openai", format!("http://{addr}/v1"), &["gpt-4o"]).default_upstream()
+    };
+    let anthropic = || {
+        upstream(
+            "anthropic",
+            "anthropic",
+            format!("http://{addr}/v1"),
+            &["claude-sonnet-4"],
+        )
+        .with("anthropic_betas", "[message-batches-2024-09-24]")
+    };
+    let config = || TestConfig::new().allowed_keys(["batches-0"]);
+    // The client's beta list is filtered down to the upstream's allow-list.
+    let batch = |state: &Arc<AppState>, method: &str, uri: &str, body: Body| {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("x-api-key", "batches-0")
+            .header("anthropic-beta", "message-batches-2024-09-24,unlisted")
+            .header("content-type", "application/json")
+            .body(body)
+            .expect("build request");
+        let state = Arc::clone(state);
+        async move {
+            let response = dispatch(&state, request).await;
+            (response.status(), read_text(response).await)
+        }
+    };
+    let create_body = || {
+        Body::from(
+            json!({
+                "requests": [{
+                    "custom_id": "req-1",
+                    "params": {
+                        "model": "claude-sonnet-4",
+                        "max_tokens": 64,
+                        "messages": [{ "role": "user", "content": "hi" }]
+                    }
+                }]
+            })
+            .to_string(),
+        )
+    };
+
+    let state = config().upstream(openai()).upstream(anthropic()).state();
+    let (status, body) = batch(&state, "POST", "/v1/messages/batches", create_body()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("msgbatch_01"));
+    assert_eq!(state.message_batch_upstream("msgbatch_01"), Some(1));
+
+    let (status, body) = batch(
+        &state,
+        "GET",
+        "/v1/messages/batches/msgbatch_01",
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"ended\""));
+    let (status, body) = batch(
+        &state,
+        "GET",
+        "/v1/messages/batches/msgbatch_01/results",
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"succeeded\""));
+
+    // A config apply that reorders the upstreams keeps the batch on its
+    // Anthropic upstream.
+    let next = state.swap_config(config().upstream(anthropic()).upstream(openai()).build());
+    assert_eq!(next.message_batch_upstream("msgbatch_01"), Some(0));
+    let (status, body) = batch(
+        &next,
+        "GET",
+        "/v1/messages/batches/msgbatch_01",
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"ended\""));
+
+    let openai_only = config().upstream(openai()).state();
+    let (status, _) = batch(&openai_only, "POST", "/v1/messages/batches", create_body()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_anthropic_failover_to_openai_candidate_re_encodes_request() {
+    let anthropic_hits = Arc::new(AtomicUsize::new(0));
+    let openai_bodies: Arc<Mutex<Vec<serde_json::Value>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let anthropic_hits_clone = Arc::clone(&anthropic_hits);
+    let anthropic_app = Router::new().route(
+        "/v1/messages",
+        post(move || {
+            let anthropic_hits = Arc::clone(&anthropic_hits_clone);
+            async move {
+                anthropic_hits.fetch_add(1, Ordering::Relaxed);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    Json(json!({
+                        "type": "error",
+                        "error": { "type": "overloaded_error", "message": "overloaded" }
+                    })),
+                )
+            }
+        }),
+    );
+    let anthropic_addr = spawn_upstream(anthropic_app).await;
+
+    let openai_bodies_clone = Arc::clone(&openai_bodies);
+    let openai_app = Router::new().route(
+        "/v1/chat/completions",
+        post(move |Json(body): Json<serde_json::Value>| {
+            let openai_bodies = Arc::clone(&openai_bodies_clone);
+            async move {
+                let stream = body["stream"] == json!(true);
+                openai_bodies.lock().expect("bodies lock").push(body);
+                if stream {
+                    let chunk = |delta: serde_json::Value, finish: serde_json::Value| {
+                        json!({
+                            "id": "chatcmpl-x",
+                            "object": "chat.completion.chunk",
+                            "created": 1,
+                            "model": "claude-3-5-haiku-latest",
+                            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish }]
+                        })
+                    };
+                    let sse = format!(
+                        "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+                        chunk(
+                            json!({ "role": "assistant", "content": "openai-ok" }),
+                            json!(null)
+                        ),
+                        chunk(json!({}), json!("stop")),
+                    );
+                    return ([("content-type", "text/event-stream")], sse).into_response();
+                }
+                Json(json!({
+                    "id": "chatcmpl-x",
+                    "object": "chat.completion",
+                    "created": 1,
+                    "model": "claude-3-5-haiku-latest",
+                    "choices": [{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "openai-ok" },
+                        "finish_reason": "stop"
+                    }],
+                    "usage": { "prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4 }
+                }))
+                .into_response()
+            }
+        }),
+    );
+    let openai_addr = spawn_upstream(openai_app).await;
+
+    let keys = allowed_keys("client-key-cross");
+    let model = ["claude-3-5-haiku-latest"];
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "anthropic-0",
+            "anthropic",
+            format!("http://{anthropic_addr}/v1"),
+            &model,
+        ))
+        .upstream(upstream(
+            "openai-1",
+            "openai",
+            format!("http://{openai_addr}/v1"),
+            &model,
+        ))
+        .allowed_keys(&keys)
+        .state();
+
+    for stream in [false, true] {
+        let request_body = serde_json::to_vec(&json!({
+            "model": "claude-3-5-haiku-latest",
+            "system": "be brief",
+            "max_tokens": 32,
+            "stop_sequences": ["END", "STOP"],
+            "messages": [{ "role": "user", "content": "ping" }],
+            "stream": stream
+        }))
+        .expect("serialize request");
+
+        let mut observed_failover = false;
+        for key in &keys {
+            anthropic_hits.store(0, Ordering::Relaxed);
+            openai_bodies.lock().expect("bodies lock").clear();
+            let body = Body::from(request_body.clone());
+            let response = send(&state, "POST", "/v1/messages", key, body).await;
+            assert_eq!(response.status(), StatusCode::OK, "stream={stream}");
+            let text = read_text(response).await;
+            assert!(text.contains("openai-ok"), "stream={stream}: {text}");
+            if stream {
+                assert!(text.contains("event: message_start"), "{text}");
+            } else {
+                let payload: serde_json::Value = serde_json::from_str(&text).expect("json");
+                assert_eq!(payload["type"], "message");
+            }
+
+            let bodies = openai_bodies.lock().expect("bodies lock").clone();
+            assert_eq!(bodies.len(), 1);
+            let sent = &bodies[0];
+            assert!(sent.get("system").is_none(), "{sent}");
+            assert!(sent.get("stop_sequences").is_none(), "{sent}");
+            assert_eq!(sent["messages"][0]["role"], "system");
+            assert_eq!(sent["stop"], json!(["END", "STOP"]));
+            if anthropic_hits.load(Ordering::Relaxed) > 0 {
+                observed_failover = true;
+                break;
+            }
+        }
+        assert!(
+            observed_failover,
+            "stream={stream}: expected a request to fail over from anthropic to openai"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_inline_base64_image_is_uploaded_once_and_referenced() {
+    let uploads = Arc::new(AtomicUsize::new(0));
+    let uploads_clone = Arc::clone(&uploads);
+    let app = Router::new()
+        .route(
+            "/v1/files",
+            post(
+                move |headers: axum::http::HeaderMap, body: axum::body::Bytes| {
+                    let uploads = Arc::clone(&uploads_clone);
+                    async move {
+                        uploads.fetch_add(1, Ordering::Relaxed);
+                        assert_eq!(headers["x-api-key"], "upstream-secret");
+                        assert_eq!(headers["anthropic-beta"], "files-api-2025-04-14");
+                        assert!(String::from_utf8_lossy(&body).contains("inline-image-bytes"));
+                        Json(json!({ "id": "file_up", "type": "file" }))
+                    }
+                },
+            ),
+        )
+        .route(
+            "/v1/messages",
+            post(
+                |headers: axum::http::HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                    assert!(headers["anthropic-beta"]
+                        .to_str()
+                        .unwrap()
+                        .contains("files-api-2025-04-14"));
+                    assert_eq!(
+                        body["messages"][0]["content"][0]["source"],
+                        json!({ "type": "file", "file_id": "file_up" })
+                    );
+                    Json(json!({
+                        "id": "msg_1",
+                        "type": "message",
+                        "role": "assistant",
+                        "model": "claude-sonnet-4",
+                        "content": [{ "type": "text", "text": "a cat" }],
+                        "stop_reason": "end_turn",
+                        "stop_sequence": null,
+                        "usage": { "input_tokens": 5, "output_tokens": 2 }
+                    }))
+                },
+            ),
+        );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "anthropic",
+            "anthropic",
+            format!("http://{addr}/v1"),
+            &["claude-sonnet-4"],
+        ))
+        .allowed_keys(["inline-files-0"])
+        .yaml("features:\n  inline_file_upload:\n    min_bytes: 16\n    cache_ttl_secs: 3600")
+        .state();
+    // base64("inline-image-bytes")
+    let image = "aW5saW5lLWltYWdlLWJ5dGVz";
+    let request_body = json!({
+        "model": "claude-sonnet-4",
+        "max_tokens": 32,
+        "messages": [{ "role": "user", "content": [
+            { "type": "image", "source": { "type": "base64", "media_type": "image/png", "data": image } },
+            { "type": "text", "text": "what is this?" }
+        ] }]
+    })
+    .to_string();
+
+    for _ in 0..2 {
+        let body = Body::from(request_body.clone());
+        let response = send(&state, "POST", "/v1/messages", "inline-files-0", body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(read_text(response).await.contains("a cat"));
+    }
+    assert_eq!(uploads.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn test_stream_resumes_from_last_event_id_after_disconnect() {
+    let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+    let release_rx = Arc::new(Mutex::new(Some(release_rx)));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls_clone = Arc::clone(&calls);
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move || {
+            calls_clone.fetch_add(1, Ordering::Relaxed);
+            let release_rx = release_rx.lock().expect("release lock").take();
+            async move {
+                let first = "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"po\"},\"finish_reason\":null}]}\n\n";
+                let rest = "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"ng\"},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n";
+                let head = futures_util::stream::once(async move {
+                    Ok::<_, std::io::Error>(first)
+                });
+                let tail = futures_util::stream::once(async move {
+                    if let Some(release_rx) = release_rx {
+                        let _ = release_rx.await;
+                    }
+                    Ok::<_, std::io::Error>(rest)
+                });
+                (
+                    [("content-type", "text/event-stream")],
+                    Body::from_stream(futures_util::StreamExt::chain(head, tail)),
+                )
+            }
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai-stream",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .yaml(
+            r#"
+features:
+  resumable_streams:
+    retry_ms: 2000
+    buffer_frames: 64
+    ttl_secs: 60
+"#,
+        )
+        .state();
+    let stream_request = |last_event_id: Option<&str>| {
+        let mut builder = Request::post("/v1/chat/completions")
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json");
+        if let Some(last_event_id) = last_event_id {
+            builder = builder.header("last-event-id", last_event_id);
+        }
+        builder
+            .body(json_body(&json!({
+                "model": "gpt-4o",
+                "messages": [{ "role": "user", "content": "ping" }],
+                "stream": true
+            })))
+            .expect("build request")
+    };
+
+    let response = dispatch(&state, stream_request(None)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut origin = response.into_body().into_data_stream();
+    let mut received = String::new();
+    while !received.contains("\"po\"") {
+        let chunk = futures_util::StreamExt::next(&mut origin)
+            .await
+            .expect("origin chunk")
+            .expect("origin chunk ok");
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(received.starts_with("retry: 2000\n\n"), "{received}");
+    let last_event_id = received
+        .lines()
+        .filter_map(|line| line.strip_prefix("id: "))
+        .next_back()
+        .expect("frames carry ids")
+        .to_string();
+
+    // The client drops; the answer keeps arriving upstream.
+    drop(origin);
+    release_tx.send(()).expect("release upstream tail");
+
+    let resumed = dispatch(&state, stream_request(Some(&last_event_id))).await;
+    assert_eq!(resumed.status(), StatusCode::OK);
+    let resumed = read_text(resumed).await;
+    assert!(!resumed.contains("\"po\""), "{resumed}");
+    assert!(resumed.contains("\"ng\""), "{resumed}");
+    assert!(resumed.contains("[DONE]"), "{resumed}");
+    let (key, seq) = last_event_id.split_once('-').expect("stream-seq id");
+    let next_id = format!("id: {key}-{}", seq.parse::<u64>().expect("seq") + 1);
+    assert!(resumed.contains(&next_id), "{resumed}");
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+    // Unknown ids start a fresh answer.
+    let fresh = dispatch(&state, stream_request(Some("0-1"))).await;
+    assert!(read_text(fresh).await.contains("\"po\""));
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+}
+
+#[tokio::test]
+async fn test_tool_definition_limits_reject_or_truncate_before_injection() {
+    let system_prompts = Arc::new(Mutex::new(Vec::new()));
+    let captured = Arc::clone(&system_prompts);
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move |Json(body): Json<serde_json::Value>| {
+            let captured = Arc::clone(&captured);
+            async move {
+                captured.lock().expect("prompts lock").push(
+                    body["messages"][0]["content"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                );
+                Json(json!({
+                    "id": "chatcmpl-limits",
+                    "object": "chat.completion",
+                    "created": 1,
+                    "model": body["model"],
+                    "choices": [{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "ok" },
+                        "finish_reason": "stop"
+                    }]
+                }))
+            }
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(
+            upstream(
+                "inject",
+                "openai",
+                format!("http://{addr}/v1"),
+                &["gpt-inject"],
+            )
+            .fc_mode("inject"),
+        )
+        .allowed_keys(["tool-limits-0"])
+        .yaml(
+            r#"
+features:
+  tool_definition_limits:
+    max_tools: 1
+    max_schema_bytes: 1024
+    max_prompt_bytes: 8192
+    on_exceed: truncate_descriptions
+"#,
+        )
+        .state();
+    let tool = |name: &str, description: String| {
+        json!({
+            "type": "function",
+            "function": {
+                "name": name,
+                "description": description,
+                "parameters": {
+                    "type": "object",
+                    "properties": { "city": { "type": "string" } }
+                }
+            }
+        })
+    };
+    let chat = |tools: serde_json::Value| {
+        let body = json_body(&json!({
+            "model": "gpt-inject",
+            "messages": [{ "role": "user", "content": "Weather in Paris?" }],
+            "tools": tools
+        }));
+        let state = Arc::clone(&state);
+        async move {
+            send(
+                &state,
+                "POST",
+                "/v1/chat/completions",
+                "tool-limits-0",
+                body,
+            )
+            .await
+        }
+    };
+
+    let response = chat(json!([
+        tool("get_weather", "Weather.".to_string()),
+        tool("get_time", "Time.".to_string())
+    ]))
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(read_text(response).await.contains("at most 1"));
+    assert!(system_prompts.lock().expect("prompts lock").is_empty());
+
+    let response = chat(json!([tool("get_weather", "Forecast. ".repeat(2000))])).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let prompts = system_prompts.lock().expect("prompts lock").clone();
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0].len() <= 8 * 1024, "{}", prompts[0].len());
+    assert!(prompts[0].contains("get_weather"));
+    assert!(prompts[0].contains("Forecast. Forecast."));
+}
+
+#[tokio::test]
+async fn test_unparseable_stream_tool_block_is_sampled_for_admin_api() {
+    let trigger = toolify_rs::fc::prompt::get_trigger_signal();
+    let content = format!(
+        "Checking.\n{trigger}\n<function_calls><function_call>get weather for Paris secret-42"
+    );
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move || {
+            let chunk = json!({
+                "id": "chatcmpl-broken",
+                "object": "chat.completion.chunk",
+                "created": 1,
+                "model": "gpt-inject",
+                "choices": [{ "index": 0, "delta": { "content": content }, "finish_reason": null }]
+            });
+            async move {
+                let sse = format!("data: {chunk}\n\ndata: [DONE]\n\n");
+                ([("content-type", "text/event-stream")], sse)
+            }
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(
+            upstream(
+                "inject",
+                "openai",
+                format!("http://{addr}/v1"),
+                &["gpt-inject"],
+            )
+            .fc_mode("inject"),
+        )
+        .admin_keys(["admin-key"])
+        .yaml("features:\n  fc_parse_failures:\n    max_samples: 8\n    sample_bytes: 4096")
+        .state();
+
+    let response = send(
+        &state,
+        "POST",
+        "/v1/chat/completions",
+        "client-key",
+        json_body(&json!({
+            "model": "gpt-inject",
+            "stream": true,
+            "messages": [{ "role": "user", "content": "Weather in Paris?" }],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "parameters": { "type": "object", "properties": {} }
+                }
+            }]
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(read_text(response).await.contains("secret-42"));
+
+    let response = send(
+        &state,
+        "GET",
+        "/admin/fc-parse-failures",
+        "admin-key",
+        Body::empty(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let payload = read_json(response).await;
+    let samples = payload["data"].as_array().expect("sample list");
+    assert_eq!(samples.len(), 1);
+    assert_eq!(samples[0]["provider"], "openai");
+    assert_eq!(samples[0]["model"], "gpt-inject");
+    let sample = samples[0]["sample"].as_str().expect("sample text");
+    assert!(
+        sample.contains("<function_calls><function_call>"),
+        "{sample}"
+    );
+    assert!(!sample.contains("secret"), "{sample}");
+
+    let response = send(&state, "GET", "/admin/metrics", "admin-key", Body::empty()).await;
+    let text = read_text(response).await;
+    assert!(text
+        .contains("toolify_fc_parse_failures_total{provider=\"openai\",model=\"gpt-inject\"} 1"));
+}
+
+#[tokio::test]
+async fn test_gemini_safety_blocks_surface_as_filtered_finishes() {
+    let app = Router::new()
+        .route(
+            "/v1beta/models/gemini-blocked:generateContent",
+            post(|| async {
+                Json(json!({
+                    "promptFeedback": {
+                        "blockReason": "SAFETY",
+                        "safetyRatings": [
+                            { "category": "HARM_CATEGORY_HARASSMENT", "probability": "HIGH", "blocked": true },
+                            { "category": "HARM_CATEGORY_HATE_SPEECH", "probability": "NEGLIGIBLE" }
+                        ]
+                    },
+                    "usageMetadata": { "promptTokenCount": 4, "totalTokenCount": 4 }
+                }))
+            }),
+        )
+        .route(
+            "/v1beta/models/gemini-filtered:generateContent",
+            post(|| async {
+                Json(json!({
+                    "candidates": [{
+                        "finishReason": "SAFETY",
+                        "index": 0,
+                        "safetyRatings": [
+                            { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "MEDIUM" }
+                        ]
+                    }]
+                }))
+            }),
+        );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "gemini",
+            "gemini",
+            format!("http://{addr}/v1beta"),
+            &["gemini-blocked", "gemini-filtered"],
+        ))
+        .allowed_keys(["gemini-safety-0"])
+        .state();
+    let ask = |uri: &'static str, body: serde_json::Value| {
+        let state = Arc::clone(&state);
+        async move {
+            let response = send(&state, "POST", uri, "gemini-safety-0", json_body(&body)).await;
+            (response.status(), read_json(response).await)
+        }
+    };
+
+    let (status, body) = ask(
+        "/v1/chat/completions",
+        json!({
+            "model": "gemini-blocked",
+            "messages": [{ "role": "user", "content": "hi" }]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["choices"][0]["finish_reason"], "content_filter");
+    assert_eq!(body["safety"]["source"], "prompt");
+    assert_eq!(body["safety"]["reason"], "SAFETY");
+    assert_eq!(
+        body["safety"]["categories"],
+        json!([{ "category": "HARM_CATEGORY_HARASSMENT", "probability": "HIGH", "blocked": true }])
+    );
+
+    let (status, body) = ask(
+        "/v1/messages",
+        json!({
+            "model": "gemini-filtered",
+            "max_tokens": 32,
+            "messages": [{ "role": "user", "content": "hi" }]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["stop_reason"], "refusal");
+    assert_eq!(body["safety"]["source"], "candidate");
+    assert_eq!(
+        body["safety"]["categories"][0]["category"],
+        "HARM_CATEGORY_DANGEROUS_CONTENT"
+    );
+}
+
+#[tokio::test]
+async fn test_responses_reasoning_items_pass_through_or_are_stripped() {
+    let app = Router::new().route(
+        "/v1/responses",
+        post(|Json(body): Json<serde_json::Value>| async move {
+            let reasoning = json!({
+                "type": "reasoning", "id": "rs_1", "encrypted_content": "gAAAA",
+                "summary": [{ "type": "summary_text", "text": "Plan first" }]
+            });
+            let message = json!({
+                "type": "message", "id": "msg_1", "role": "assistant",
+                "content": [{ "type": "output_text", "text": "answer" }]
+            });
+            let response = json!({
+                "id": "resp_1", "object": "response", "created_at": 1,
+                "model": "o3", "status": "completed", "output": [reasoning.clone(), message]
+            });
+            if body["stream"] != json!(true) {
+                return Json(response).into_response();
+            }
+            let events = [
+                json!({ "type": "response.output_item.added", "output_index": 0, "item": reasoning }),
+                json!({
+                    "type": "response.reasoning_summary_text.delta",
+                    "output_index": 0, "summary_index": 0, "delta": "Plan first"
+                }),
+                json!({
+                    "type": "response.output_text.delta",
+                    "output_index": 1, "content_index": 0, "delta": "answer"
+                }),
+                json!({ "type": "response.completed", "response": response }),
+            ];
+            let sse: String = events
+                .iter()
+                .map(|event| format!("event: {}\ndata: {event}\n\n", event["type"].as_str().unwrap()))
+                .collect();
+            axum::response::Response::builder()
+                .header("content-type", "text/event-stream")
+                .body(Body::from(sse))
+                .expect("sse response")
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let build_state = |strip_responses_reasoning: bool| {
+        TestConfig::new()
+            .upstream(upstream(
+                "responses",
+                "openai-responses",
+                format!("http://{addr}/v1"),
+                &["o3"],
+            ))
+            .yaml(&format!(
+                "features:\n  strip_responses_reasoning: {strip_responses_reasoning}"
+            ))
+            .state()
+    };
+    let ask = |state: Arc<AppState>, uri: &'static str, body: serde_json::Value| async move {
+        let response = send(&state, "POST", uri, "client-key", json_body(&body)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        read_text(response).await
+    };
+    let responses_request =
+        |stream: bool| json!({ "model": "o3", "input": "hi", "stream": stream });
+
+    // Responses clients get the reasoning item back verbatim for round-tripping.
+    let kept = ask(
+        build_state(false),
+        "/v1/responses",
+        responses_request(false),
+    )
+    .await;
+    let kept: serde_json::Value = serde_json::from_str(&kept).expect("responses json");
+    assert_eq!(kept["output"][0]["type"], "reasoning");
+    assert_eq!(kept["output"][0]["encrypted_content"], "gAAAA");
+
+    // Other protocols see the summary as reasoning deltas.
+    let anthropic_stream = ask(
+        build_state(false),
+        "/v1/messages",
+        json!({
+            "model": "o3", "max_tokens": 64, "stream": true,
+            "messages": [{ "role": "user", "content": "hi" }]
+        }),
+    )
+    .await;
+    assert!(anthropic_stream.contains("\"thinking_delta\""));
+    assert!(anthropic_stream.contains("Plan first"));
+
+    let stripped = ask(build_state(true), "/v1/responses", responses_request(false)).await;
+    let stripped: serde_json::Value = serde_json::from_str(&stripped).expect("responses json");
+    assert_eq!(stripped["output"].as_array().map(Vec::len), Some(1));
+    assert_eq!(stripped["output"][0]["type"], "message");
+
+    let stripped_stream = ask(build_state(true), "/v1/responses", responses_request(true)).await;
+    assert!(!stripped_stream.contains("reasoning"));
+    assert!(stripped_stream.contains("answer"));
+
+    let anthropic_stream = ask(
+        build_state(true),
+        "/v1/messages",
+        json!({
+            "model": "o3", "max_tokens": 64, "stream": true,
+            "messages": [{ "role": "user", "content": "hi" }]
+        }),
+    )
+    .await;
+    assert!(!anthropic_stream.contains("\"thinking_delta\""));
+    assert!(anthropic_stream.contains("answer"));
+}
+
+#[tokio::test]
+async fn test_anthropic_prefill_echo_is_stripped_for_openai_upstream() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(body): Json<serde_json::Value>| async move {
+            // The prefill reaches the upstream as a trailing assistant message.
+            let messages = body["messages"].as_array().expect("messages");
+            assert_eq!(messages.last().expect("last")["role"], "assistant");
+            if body["stream"] != json!(true) {
+                return Json(json!({
+                    "id": "chatcmpl-1", "object": "chat.completion", "created": 1,
+                    "model": "gpt-4o",
+                    "choices": [{
+                        "index": 0, "finish_reason": "stop",
+                        "message": { "role": "assistant", "content": "{\"city\": \"Paris\"}" }
+                    }]
+                }))
+                .into_response();
+            }
+            let mut sse = String::new();
+            for delta in ["{\"ci", "ty\": \"Paris\"}"] {
+                sse.push_str(&format!(
+                    "data: {}\n\n",
+                    json!({
+                        "id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1,
+                        "model": "gpt-4o",
+                        "choices": [{ "index": 0, "delta": { "content": delta }, "finish_reason": null }]
+                    })
+                ));
+            }
+            sse.push_str(&format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({
+                    "id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1,
+                    "model": "gpt-4o",
+                    "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }]
+                })
+            ));
+            axum::response::Response::builder()
+                .header("content-type", "text/event-stream")
+                .body(Body::from(sse))
+                .expect("sse response")
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .state();
+    let message = |stream: bool| {
+        let body = json_body(&json!({
+            "model": "gpt-4o", "max_tokens": 64, "stream": stream,
+            "messages": [
+                { "role": "user", "content": "Where is the Eiffel Tower? Answer in JSON." },
+                { "role": "assistant", "content": "{\"city\":" }
+            ]
+        }));
+        let state = Arc::clone(&state);
+        async move {
+            let response = send(&state, "POST", "/v1/messages", "client-key", body).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            read_text(response).await
+        }
+    };
+
+    let body: serde_json::Value = serde_json::from_str(&message(false).await).expect("json body");
+    assert_eq!(body["content"][0]["text"], " \"Paris\"}");
+
+    let body = message(true).await;
+    let mut text = String::new();
+    for data in body.lines().filter_map(|line| line.strip_prefix("data: ")) {
+        let event: serde_json::Value = serde_json::from_str(data).expect("event json");
+        if let Some(delta) = event["delta"]["text"].as_str() {
+            text.push_str(delta);
+        }
+    }
+    assert_eq!(text, " \"Paris\"}");
+}
+
+#[tokio::test]
+async fn test_models_listing_negative_caches_down_upstream_until_admin_refresh() {
+    let good_hits = Arc::new(AtomicUsize::new(0));
+    let bad_hits = Arc::new(AtomicUsize::new(0));
+    let good_app = Router::new().route(
+        "/v1/models",
+        axum::routing::get({
+            let hits = Arc::clone(&good_hits);
+            move || async move {
+                hits.fetch_add(1, Ordering::Relaxed);
+                Json(json!({ "data": [{ "id": "dyn-model" }] }))
+            }
+        }),
+    );
+    let bad_app = Router::new().route(
+        "/v1/models",
+        axum::routing::get({
+            let hits = Arc::clone(&bad_hits);
+            move || async move {
+                hits.fetch_add(1, Ordering::Relaxed);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }),
+    );
+    let good = spawn_upstream(good_app).await;
+    let bad = spawn_upstream(bad_app).await;
+
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai-0",
+            "openai",
+            format!("http://{good}/v1"),
+            &["dyn-model"],
+        ))
+        .upstream(upstream(
+            "openai-1",
+            "openai",
+            format!("http://{bad}/v1"),
+            &["dyn-model"],
+        ))
+        .allowed_keys(["client-key-models-0"])
+        .admin_keys(["admin-key"])
+        .yaml("server:\n  models_cache_ttl_secs: 1")
+        .state();
+    // Bearer-only, so `/v1/models` answers with the OpenAI listing.
+    let call = |method: &str, uri: &str, key: &str| {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", format!("Bearer {key}"))
+            .body(Body::empty())
+            .expect("build request");
+        let state = Arc::clone(&state);
+        async move {
+            let response = dispatch(&state, request).await;
+            (response.status(), read_json(response).await)
+        }
+    };
+
+    let (status, _) = call("POST", "/admin/models/refresh", "client-key-models-0").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = call("POST", "/admin/models/refresh", "admin-key").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["upstream"], "openai-0");
+    assert_eq!(body["data"][0]["listed_models"], 1);
+    assert_eq!(body["data"][0]["down"], false);
+    assert_eq!(body["data"][1]["upstream"], "openai-1");
+    assert!(body["data"][1]["listed_models"].is_null());
+    assert_eq!(body["data"][1]["down"], true);
+    assert_eq!(good_hits.load(Ordering::Relaxed), 1);
+    assert_eq!(bad_hits.load(Ordering::Relaxed), 1);
+
+    // Once the listing is stale the cached body is served right away while a
+    // background refresh asks only the healthy upstream again.
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let (status, body) = call("GET", "/v1/models", "client-key-models-0").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][0]["id"], "dyn-model");
+    for _ in 0..50 {
+        if good_hits.load(Ordering::Relaxed) == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(good_hits.load(Ordering::Relaxed), 2);
+    assert_eq!(bad_hits.load(Ordering::Relaxed), 1);
+    // Let the background refresh record its result before busting.
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let (status, body) = call("POST", "/admin/models/refresh", "admin-key").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"][1]["down"], true);
+    assert_eq!(bad_hits.load(Ordering::Relaxed), 2);
+}
+
+#[tokio::test]
+async fn test_oversized_bodies_are_rejected_per_ingress_and_upstream_limit() {
+    let small_hits = Arc::new(AtomicUsize::new(0));
+    let reply = |hits: Arc<AtomicUsize>| {
+        post(move || async move {
+            hits.fetch_add(1, Ordering::Relaxed);
+            Json(json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "model": "m",
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "ok" },
+                    "finish_reason": "stop"
+                }]
+            }))
+        })
+    };
+    let small_app = Router::new().route("/v1/chat/completions", reply(Arc::clone(&small_hits)));
+    let open_app =
+        Router::new().route("/v1/chat/completions", reply(Arc::new(AtomicUsize::new(0))));
+    let small = spawn_upstream(small_app).await;
+    let open = spawn_upstream(open_app).await;
+
+    let state = TestConfig::new()
+        .upstream(
+            upstream(
+                "small",
+                "openai",
+                format!("http://{small}/v1"),
+                &["small-model"],
+            )
+            .with("max_request_bytes", 256),
+        )
+        .upstream(
+            upstream(
+                "open",
+                "openai",
+                format!("http://{open}/v1"),
+                &["open-model"],
+            )
+            .default_upstream(),
+        )
+        .admin_keys(["admin-key"])
+        .yaml("server:\n  max_request_bytes: 4096\n  max_upload_bytes: 16384")
+        .state();
+
+    let post = |uri: &str, model: &str, padding: usize, declare_length: bool| {
+        let body = serde_json::to_vec(&json!({
+            "model": model,
+            "max_tokens": 16,
+            "messages": [{ "role": "user", "content": "x".repeat(padding) }],
+            "contents": [{ "role": "user", "parts": [{ "text": "hi" }] }]
+        }))
+        .expect("serialize");
+        let mut request = Request::post(uri)
+            .header("authorization", "Bearer client-key")
+            .header("x-goog-api-key", "client-key")
+            .header("x-api-key", "client-key")
+            .header("content-type", "application/json");
+        if declare_length {
+            request = request.header("content-length", body.len());
+        }
+        let request = request.body(Body::from(body)).expect("build request");
+        let state = Arc::clone(&state);
+        async move {
+            let response = dispatch(&state, request).await;
+            let status = response.status();
+            let payload =
+                serde_json::from_str(&read_text(response).await).unwrap_or(serde_json::Value::Null);
+            (status, payload)
+        }
+    };
+
+    // Over the global limit: each ingress answers in its own error shape,
+    // whether or not the client declared the length up front.
+    let (status, payload) = post("/v1/messages", "open-model", 8192, true).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(payload["type"], "error");
+    assert_eq!(payload["error"]["type"], "request_too_large");
+    let (status, payload) = post("/v1/chat/completions", "open-model", 8192, false).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(payload["error"]["code"], "request_too_large");
+    let (status, payload) = post(
+        "/v1beta/models/open-model:generateContent",
+        "open-model",
+        8192,
+        false,
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(payload["error"]["code"], 413);
+
+    // Under the global limit but over the small upstream's own limit.
+    let (status, payload) = post("/v1/chat/completions", "small-model", 512, true).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{payload}");
+    assert!(payload["error"]["message"]
+        .as_str()
+        .is_some_and(|message| message.contains("upstream 'small'")));
+    assert_eq!(small_hits.load(Ordering::Relaxed), 0);
+    let (status, _) = post("/v1/chat/completions", "small-model", 16, true).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post("/v1/chat/completions", "open-model", 512, true).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Batch and file uploads go by the upload limit, in their API's shape.
+    let upload = |uri: &'static str, len: usize| {
+        let state = Arc::clone(&state);
+        async move {
+            let response = send(
+                &state,
+                "POST",
+                uri,
+                "client-key",
+                Body::from(vec![b'x'; len]),
+            )
+            .await;
+            let status = response.status();
+            let payload =
+                serde_json::from_str(&read_text(response).await).unwrap_or(serde_json::Value::Null);
+            (status, payload)
+        }
+    };
+    let (status, _) = upload("/v1/files", 8192).await;
+    assert_ne!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    let (status, payload) = upload("/v1/batches", 32768).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(payload["error"]["code"], "request_too_large");
+    let (status, payload) = upload("/v1/messages/batches", 32768).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(payload["error"]["type"], "request_too_large");
+
+    let response = send(&state, "GET", "/admin/metrics", "admin-key", Body::empty()).await;
+    let text = read_text(response).await;
+    for line in [
+        "toolify_request_too_large_total{limit=\"global\",ingress=\"anthropic\"} 2",
+        "toolify_request_too_large_total{limit=\"global\",ingress=\"openai_chat\"} 2",
+        "toolify_request_too_large_total{limit=\"global\",ingress=\"gemini\"} 1",
+        "toolify_request_too_large_total{limit=\"upstream\",upstream=\"small\"} 1",
+    ] {
+        assert!(text.contains(line), "{line}\n{text}");
+    }
+}
+
+#[tokio::test]
+async fn test_tenants_route_with_their_own_keys_and_upstreams() {
+    let mock = |text: &'static str| {
+        Router::new().route(
+            "/v1/messages",
+            post(move || async move {
+                Json(json!({
+                    "id": "msg_mock",
+                    "type": "message",
+                    "role": "assistant",
+                    "model": "claude-a",
+                    "content": [{ "type": "text", "text": text }],
+                    "stop_reason": "end_turn",
+                    "stop_sequence": null,
+                    "usage": { "input_tokens": 3, "output_tokens": 1 }
+                }))
+            }),
+        )
+    };
+    let addrs = [
+        spawn_upstream(mock("from-a")).await,
+        spawn_upstream(mock("from-b")).await,
+    ];
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "upstream-a",
+            "anthropic",
+            format!("http://{}/v1", addrs[0]),
+            &["claude-a"],
+        ))
+        .upstream(upstream(
+            "upstream-b",
+            "anthropic",
+            format!("http://{}/v1", addrs[1]),
+            &["claude-b"],
+        ))
+        .allowed_keys(["root-key"])
+        .yaml(
+            r#"
+tenants:
+  - name: team-b
+    base_path: /team-b
+    allowed_keys: [team-b-key]
+    upstream_services: [upstream-b]
+"#,
+        )
+        .state();
+    let message = |uri: &str, key: &str, model: &str| {
+        let body = json_body(&json!({
+            "model": model,
+            "max_tokens": 16,
+            "messages": [{ "role": "user", "content": "ping" }]
+        }));
+        let (state, uri, key) = (Arc::clone(&state), uri.to_string(), key.to_string());
+        async move { send(&state, "POST", &uri, &key, body).await }
+    };
+    let text =
+        |response: Response| async move { read_json(response).await["content"][0]["text"].clone() };
+
+    let response = message("/v1/messages", "root-key", "claude-a").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(text(response).await, "from-a");
+
+    let response = message("/team-b/v1/messages", "team-b-key", "claude-b").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(text(response).await, "from-b");
+    // upstream-a is outside the tenant's routing table.
+    let response = message("/team-b/v1/messages", "team-b-key", "claude-a").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = message("/team-b/v1/messages", "root-key", "claude-b").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = message("/v1/messages", "team-b-key", "claude-b").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_priority_header_maps_to_service_tier_for_allowed_keys() {
+    let tiers = Arc::new(Mutex::new(Vec::new()));
+    let tiers_clone = Arc::clone(&tiers);
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move |Json(body): Json<serde_json::Value>| {
+            let tiers = Arc::clone(&tiers_clone);
+            async move {
+                tiers.lock().unwrap().push(body["service_tier"].clone());
+                Json(json!({
+                    "id": "chatcmpl_mock",
+                    "object": "chat.completion",
+                    "created": 1,
+                    "model": "gpt-4o",
+                    "choices": [{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "ok" },
+                        "finish_reason": "stop"
+                    }]
+                }))
+            }
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .allowed_keys(["vip-key", "basic-key"])
+        .yaml(
+            r#"
+features:
+  request_priority:
+    high_priority_keys: [vip-key]
+    max_concurrent_requests: 1
+"#,
+        )
+        .state();
+
+    for (key, priority) in [
+        ("vip-key", "high"),
+        ("basic-key", "high"),
+        ("basic-key", "low"),
+        ("vip-key", "normal"),
+    ] {
+        let request = Request::post("/v1/chat/completions")
+            .header("authorization", format!("Bearer {key}"))
+            .header("x-priority", priority)
+            .header("content-type", "application/json")
+            .body(json_body(&json!({
+                "model": "gpt-4o",
+                "messages": [{ "role": "user", "content": "ping" }]
+            })))
+            .expect("build request");
+        let response = dispatch(&state, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        // The queue slot is held until the body is read.
+        read_text(response).await;
+    }
+    assert_eq!(
+        *tiers.lock().unwrap(),
+        [
+            json!("priority"),
+            serde_json::Value::Null,
+            json!("flex"),
+            serde_json::Value::Null
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_fallback_chain_moves_to_other_models_and_upstreams() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let handler = |upstream: &'static str, status: StatusCode| {
+        let seen = Arc::clone(&seen);
+        post(move |Json(body): Json<serde_json::Value>| {
+            let seen = Arc::clone(&seen);
+            async move {
+                let model = body["model"].as_str().unwrap_or_default().to_string();
+                seen.lock().unwrap().push(format!("{upstream}:{model}"));
+                if status != StatusCode::OK {
+                    return (status, Json(json!({ "error": { "message": "down" } })))
+                        .into_response();
+                }
+                Json(json!({
+                    "id": "chatcmpl_mock",
+                    "object": "chat.completion",
+                    "created": 1,
+                    "model": model,
+                    "choices": [{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": format!("{model} answered") },
+                        "finish_reason": "stop"
+                    }]
+                }))
+                .into_response()
+            }
+        })
+    };
+    let app = Router::new()
+        .route(
+            "/claude/v1/chat/completions",
+            handler("claude", StatusCode::SERVICE_UNAVAILABLE),
+        )
+        .route(
+            "/openai/v1/chat/completions",
+            handler("openai", StatusCode::SERVICE_UNAVAILABLE),
+        )
+        .route(
+            "/local/v1/chat/completions",
+            handler("local", StatusCode::OK),
+        );
+    let addr = spawn_upstream(app).await;
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "claude",
+            "openai",
+            format!("http://{addr}/claude/v1"),
+            &["haiku:claude-haiku"],
+        ))
+        .upstream(upstream(
+            "openai",
+            "openai",
+            format!("http://{addr}/openai/v1"),
+            &["gpt-4o-mini"],
+        ))
+        .upstream(upstream(
+            "local",
+            "openai",
+            format!("http://{addr}/local/v1"),
+            &["local-llama:llama-3.1-8b"],
+        ))
+        .yaml("fallback_chains:\n  - model: haiku\n    fallbacks: [gpt-4o-mini, local-llama]")
+        .state();
+
+    let response = send(
+        &state,
+        "POST",
+        "/v1/chat/completions",
+        "client-key",
+        json_body(&json!({
+            "model": "haiku",
+            "messages": [{ "role": "user", "content": "ping" }]
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let payload = read_json(response).await;
+    assert_eq!(
+        payload["choices"][0]["message"]["content"],
+        "llama-3.1-8b answered"
+    );
+    // Each upstream is retried before the chain moves on.
+    let mut seen = seen.lock().unwrap().clone();
+    seen.dedup();
+    assert_eq!(
+        seen,
+        [
+            "claude:claude-haiku",
+            "openai:gpt-4o-mini",
+            "local:llama-3.1-8b"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_stream_stats_frame_is_sent_before_done_on_request() {
+    let flaky = post(|| async {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": { "message": "down" } })),
+        )
+    });
+    let healthy = post(|Json(body): Json<serde_json::Value>| async move {
+        let chunk = |delta: serde_json::Value, finish: Option<&str>| {
+            json!({
+                "id": "chatcmpl-stats", "object": "chat.completion.chunk", "created": 1,
+                "model": body["model"],
+                "choices": [{ "index": 0, "delta": delta, "finish_reason": finish }]
+            })
+        };
+        let sse = format!(
+            "data: {}\n\ndata: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+            chunk(json!({ "role": "assistant", "content": "hi" }), None),
+            chunk(json!({}), Some("stop")),
+            json!({
+                "id": "chatcmpl-stats", "object": "chat.completion.chunk", "created": 1,
+                "model": body["model"], "choices": [],
+                "usage": { "prompt_tokens": 7, "completion_tokens": 1, "total_tokens": 8 }
+            }),
+        );
+        axum::response::Response::builder()
+            .header("content-type", "text/event-stream")
+            .body(Body::from(sse))
+            .expect("sse response")
+    });
+    let app = Router::new()
+        .route("/flaky/v1/chat/completions", flaky)
+        .route("/healthy/v1/chat/completions", healthy);
+    let addr = spawn_upstream(app).await;
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "flaky",
+            "openai",
+            format!("http://{addr}/flaky/v1"),
+            &["primary"],
+        ))
+        .upstream(upstream(
+            "healthy",
+            "openai",
+            format!("http://{addr}/healthy/v1"),
+            &["backup"],
+        ))
+        .yaml(
+            r#"
+features:
+  stream_stats_upstream: true
+fallback_chains:
+  - model: primary
+    fallbacks: [backup]
+"#,
+        )
+        .state();
+
+    for stats_requested in [true, false] {
+        let mut request = Request::post("/v1/chat/completions")
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json");
+        if stats_requested {
+            request = request.header("x-toolify-stream-stats", "true");
+        }
+        let request = request
+            .body(json_body(&json!({
+                "model": "primary",
+                "messages": [{ "role": "user", "content": "ping" }],
+                "stream": true
+            })))
+            .expect("build request");
+        let response = dispatch(&state, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = read_text(response).await;
+        assert!(body.trim_end().ends_with("data: [DONE]"));
+        if !stats_requested {
+            assert!(!body.contains("toolify_stats"), "stats are opt-in: {body}");
+            continue;
+        }
+        let frame = body
+            .split("\n\n")
+            .find(|frame| frame.contains("toolify_stats"))
+            .expect("stats frame");
+        let payload: serde_json::Value =
+            serde_json::from_str(frame.strip_prefix("data: ").expect("data frame"))
+                .expect("stats json");
+        let stats = &payload["toolify_stats"];
+        assert_eq!(stats["upstream"], "healthy");
+        // Two in-place retries of the 503, then the move to the fallback model.
+        assert_eq!(stats["retries"], 3);
+        assert_eq!(stats["usage"]["input_tokens"], 7);
+        assert_eq!(stats["usage"]["output_tokens"], 1);
+        assert!(stats["latency_ms"].as_u64().is_some());
+    }
+}
+
+#[tokio::test]
+async fn test_upstream_error_is_answered_with_a_journaled_reference() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|| async {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [("x-request-id", "req_upstream_1"), ("set-cookie", "lb=7")],
+                Json(json!({ "error": { "message": "shard db-7 at 10.0.3.4 refused" } })),
+            )
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+    let config = TestConfig::new()
+        .upstream(upstream(
+            "openai",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .yaml("features:\n  upstream_error_references: true")
+        .build();
+    let journal_path = std::env::temp_dir().join(format!(
+        "toolify-upstream-errors-{}.jsonl",
+        std::process::id()
+    ));
+    let journal = Arc::new(
+        toolify_rs::observability::journal::RequestJournal::open(&journal_path)
+            .expect("open journal"),
+    );
+    let state = Arc::new(AppState::from_config(config).with_request_journal(Arc::clone(&journal)));
+
+    let response = send(
+        &state,
+        "POST",
+        "/v1/chat/completions",
+        "client-key",
+        json_body(&json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "ping" }]
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let payload = read_json(response).await;
+    let message = payload["error"]["message"].as_str().expect("message");
+    assert!(!message.contains("10.0.3.4"), "{message}");
+    let reference = message
+        .split("reference: ")
+        .nth(1)
+        .and_then(|rest| rest.strip_suffix(')'))
+        .expect("reference in message");
+
+    journal.flush().expect("flush journal");
+    let (records, _) =
+        toolify_rs::observability::journal::read_records(&journal_path).expect("read journal");
+    let _ = std::fs::remove_file(&journal_path);
+    let detail = records
+        .iter()
+        .find_map(|record| match record {
+            toolify_rs::observability::journal::JournalRecord::UpstreamError {
+                reference: recorded,
+                detail,
+                ..
+            } if recorded == reference => Some(detail),
+            _ => None,
+        })
+        .expect("journaled upstream error");
+    assert_eq!(detail.upstream, "openai");
+    assert_eq!(detail.status, 500);
+    assert_eq!(detail.headers["x-request-id"], "req_upstream_1");
+    assert!(!detail.headers.contains_key("set-cookie"));
+    assert!(detail.body.contains("10.0.3.4"));
+}
+
+#[tokio::test]
+async fn test_configured_upstream_response_headers_reach_the_client() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(body): Json<serde_json::Value>| async move {
+            let headers = [
+                ("x-ratelimit-remaining-requests", "41"),
+                ("anthropic-ratelimit-tokens-remaining", "9000"),
+                ("retry-after", "2"),
+                ("set-cookie", "lb=7"),
+                ("x-internal-shard", "db-7"),
+            ];
+            if body["stream"] == json!(true) {
+                let chunk = json!({
+                    "id": "chatcmpl-headers", "object": "chat.completion.chunk", "created": 1,
+                    "model": body["model"],
+                    "choices": [{ "index": 0, "delta": { "content": "hi" }, "finish_reason": "stop" }]
+                });
+                let mut response = axum::response::Response::builder()
+                    .header("content-type", "text/event-stream");
+                for (name, value) in headers {
+                    response = response.header(name, value);
+                }
+                return response
+                    .body(Body::from(format!("data: {chunk}\n\ndata: [DONE]\n\n")))
+                    .expect("sse response");
+            }
+            let mut response = Json(json!({
+                "id": "chatcmpl-headers", "object": "chat.completion", "created": 1,
+                "model": body["model"],
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "hi" },
+                    "finish_reason": "stop"
+                }]
+            }))
+            .into_response();
+            for (name, value) in headers {
+                response.headers_mut().insert(name, value.parse().expect("header value"));
+            }
+            response
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .yaml(
+            r#"
+features:
+  forward_response_headers:
+    - name: "x-ratelimit-*"
+    - name: "anthropic-ratelimit-*"
+      rename: "x-upstream-ratelimit-*"
+    - name: retry-after
+"#,
+        )
+        .state();
+
+    for stream in [false, true] {
+        let body = json_body(&json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "ping" }],
+            "stream": stream
+        }));
+        let response = send(&state, "POST", "/v1/chat/completions", "client-key", body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers["x-ratelimit-remaining-requests"], "41",
+            "stream: {stream}"
+        );
+        assert_eq!(headers["x-upstream-ratelimit-tokens-remaining"], "9000");
+        assert_eq!(headers["retry-after"], "2");
+        assert!(!headers.contains_key("anthropic-ratelimit-tokens-remaining"));
+        assert!(!headers.contains_key("set-cookie"));
+        assert!(!headers.contains_key("x-internal-shard"));
+    }
+}
+
+#[tokio::test]
+async fn test_slow_request_is_served_through_long_poll_operation() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(body): Json<serde_json::Value>| async move {
+            let prompt = body["messages"][0]["content"].as_str().unwrap_or_default();
+            if prompt == "slow" {
+                tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+            }
+            Json(json!({
+                "id": "chatcmpl-long-poll", "object": "chat.completion", "created": 1,
+                "model": body["model"],
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": format!("re: {prompt}") },
+                    "finish_reason": "stop"
+                }]
+            }))
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .yaml("features:\n  long_poll:\n    threshold_ms: 100\n    poll_wait_ms: 50")
+        .state();
+    let call = |method: &str, uri: &str, body: Body| {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json")
+            .header("x-toolify-long-poll", "true")
+            .body(body)
+            .expect("build request");
+        let state = Arc::clone(&state);
+        async move { dispatch(&state, request).await }
+    };
+    let chat = |prompt: &str| {
+        json_body(&json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": prompt }]
+        }))
+    };
+
+    let fast = call("POST", "/v1/chat/completions", chat("fast")).await;
+    assert_eq!(fast.status(), StatusCode::OK);
+    assert_eq!(
+        read_json(fast).await["choices"][0]["message"]["content"],
+        "re: fast"
+    );
+
+    let accepted = call("POST", "/v1/chat/completions", chat("slow")).await;
+    assert_eq!(accepted.status(), StatusCode::ACCEPTED);
+    let location = accepted.headers()["location"]
+        .to_str()
+        .expect("location")
+        .to_string();
+    let operation = read_json(accepted).await;
+    assert_eq!(operation["status"], "in_progress");
+    assert_eq!(
+        location,
+        format!("/v1/operations/{}", operation["id"].as_str().expect("id"))
+    );
+
+    let mut heartbeats = 0;
+    let finished = loop {
+        let poll = call("GET", &location, Body::empty()).await;
+        if poll.status() != StatusCode::ACCEPTED {
+            break poll;
+        }
+        let heartbeat = read_json(poll).await;
+        assert!(heartbeat["elapsed_ms"].as_u64().expect("elapsed") >= 100);
+        heartbeats += 1;
+        assert!(heartbeats < 100, "operation never finished");
+    };
+    assert!(heartbeats > 0);
+    assert_eq!(finished.status(), StatusCode::OK);
+    assert_eq!(
+        read_json(finished).await["choices"][0]["message"]["content"],
+        "re: slow"
+    );
+
+    let missing = call("GET", "/v1/operations/op_missing", Body::empty()).await;
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_streaming_request_is_refused_over_memory_budget() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(body): Json<serde_json::Value>| async move {
+            Json(json!({
+                "id": "chatcmpl-budget", "object": "chat.completion", "created": 1,
+                "model": body["model"],
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "ok" },
+                    "finish_reason": "stop"
+                }]
+            }))
+        }),
+    );
+    let addr = spawn_upstream(app).await;
+    let state = TestConfig::new()
+        .upstream(upstream(
+            "openai",
+            "openai",
+            format!("http://{addr}/v1"),
+            &["gpt-4o"],
+        ))
+        .admin_keys(["admin-key"])
+        .yaml("features:\n  memory_budget:\n    max_buffered_bytes: 1")
+        .state();
+    let chat = |stream: bool| {
+        json_body(&json!({
+            "model": "gpt-4o",
+            "stream": stream,
+            "messages": [{ "role": "user", "content": "hi" }]
+        }))
+    };
+
+    // Another request's buffer, holding the process over the budget.
+    let mut held = toolify_rs::stream::memory_budget::BufferCharge::default();
+    held.track(4096);
+
+    let refused = send(
+        &state,
+        "POST",
+        "/v1/chat/completions",
+        "client-key",
+        chat(true),
+    )
+    .await;
+    assert_eq!(refused.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(refused.headers()["retry-after"], "1");
+    let answered = send(
+        &state,
+        "POST",
+        "/v1/chat/completions",
+        "client-key",
+        chat(false),
+    )
+    .await;
+    assert_eq!(answered.status(), StatusCode::OK);
+
+    let metrics = send(&state, "GET", "/admin/metrics", "admin-key", Body::empty()).await;
+    let text = read_text(metrics).await;
+    assert!(text.contains("toolify_memory_budget_bytes 1\n"), "{text}");
+    assert!(
+        !text.contains("toolify_memory_budget_rejections_total 0\n"),
+        "{text}"
+    );
+    drop(held);
 }
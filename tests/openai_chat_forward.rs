@@ -1,15 +1,19 @@
+#![cfg(feature = "server")]
+
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use axum::body::Body;
 use axum::http::{Request, StatusCode};
-use axum::response::Response;
+use axum::response::{IntoResponse, Response};
 use axum::routing::post;
 use axum::{Json, Router};
 use serde_json::json;
 use toolify_rs::auth::build_allowed_key_set;
 use toolify_rs::config::{
-    AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, ServerConfig, UpstreamServiceConfig,
+    AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, ModelCapability, PathStyle,
+    QualityRetryConfig, ServerConfig, StreamSupport, ToolSchemaValidation, UpstreamAuthConfig,
+    UpstreamServiceConfig,
 };
 use toolify_rs::routing::dispatch::dispatch_request;
 use toolify_rs::routing::ModelRouter;
@@ -32,11 +36,30 @@ fn build_state(base_url: String) -> Arc<AppState> {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         }],
         client_authentication: ClientAuthConfig {
             allowed_keys: vec!["client-key".to_string()],
+            admin_keys: Vec::new(),
+            key_model_maps: Vec::new(),
         },
         features: FeaturesConfig::default(),
+        virtual_models: Vec::new(),
+        routing_rules: Vec::new(),
+        cascade_models: Vec::new(),
+        admin_authentication: None,
+        fallback_chains: Vec::new(),
+        tenants: Vec::new(),
     };
 
     let model_router = ModelRouter::new(&config);
@@ -73,6 +96,17 @@ fn build_state_multi(base_urls: Vec<String>, allowed_keys: Vec<String>) -> Arc<A
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         })
         .collect();
 
@@ -82,12 +116,30 @@ fn build_state_multi(base_urls: Vec<String>, allowed_keys: Vec<String>) -> Arc<A
 fn build_state_multi_from_services(
     upstream_services: Vec<UpstreamServiceConfig>,
     allowed_keys: Vec<String>,
+) -> Arc<AppState> {
+    build_state_with_features(upstream_services, allowed_keys, FeaturesConfig::default())
+}
+
+fn build_state_with_features(
+    upstream_services: Vec<UpstreamServiceConfig>,
+    allowed_keys: Vec<String>,
+    features: FeaturesConfig,
 ) -> Arc<AppState> {
     let config = AppConfig {
         server: ServerConfig::default(),
         upstream_services,
-        client_authentication: ClientAuthConfig { allowed_keys },
-        features: FeaturesConfig::default(),
+        client_authentication: ClientAuthConfig {
+            allowed_keys,
+            admin_keys: Vec::new(),
+            key_model_maps: Vec::new(),
+        },
+        features,
+        virtual_models: Vec::new(),
+        routing_rules: Vec::new(),
+        cascade_models: Vec::new(),
+        admin_authentication: None,
+        fallback_chains: Vec::new(),
+        tenants: Vec::new(),
     };
 
     let model_router = ModelRouter::new(&config);
@@ -224,6 +276,17 @@ async fn test_openai_chat_single_alias_model_raw_passthrough() {
         proxy: None,
         proxy_stream: None,
         proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
     }];
     let state = build_state_multi_from_services(upstream_services, vec!["client-key".to_string()]);
 
@@ -262,6 +325,126 @@ async fn test_openai_chat_single_alias_model_raw_passthrough() {
     server.abort();
 }
 
+#[tokio::test]
+async fn test_openai_chat_alias_model_rewritten_in_responses() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(request): Json<serde_json::Value>| async move {
+            if request["stream"].as_bool() == Some(true) {
+                let sse = concat!(
+                    "data: {\"id\":\"chatcmpl_alias\",\"object\":\"chat.completion.chunk\",\"created\":1727000001,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"alias-ok\"},\"finish_reason\":null}]}\n\n",
+                    "data: {\"id\":\"chatcmpl_alias\",\"object\":\"chat.completion.chunk\",\"created\":1727000001,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+                    "data: [DONE]\n\n"
+                );
+                return Response::builder()
+                    .status(StatusCode::OK)
+                    .header("content-type", "text/event-stream")
+                    .body(Body::from(sse))
+                    .expect("stream response");
+            }
+            Json(json!({
+                "id": "chatcmpl_alias",
+                "object": "chat.completion",
+                "created": 1_727_000_001_u64,
+                "model": "gpt-4o-mini",
+                "choices": [
+                    {
+                        "index": 0,
+                        "message": {
+                            "role": "assistant",
+                            "content": "alias-ok"
+                        },
+                        "finish_reason": "stop"
+                    }
+                ]
+            }))
+            .into_response()
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind alias upstream");
+    let addr = listener.local_addr().expect("alias upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let upstream_services = vec![UpstreamServiceConfig {
+        name: "mock-openai-alias".to_string(),
+        provider: "openai".to_string(),
+        base_url: format!("http://{addr}/v1"),
+        api_key: "upstream-secret".to_string(),
+        models: vec!["smart:gpt-4o-mini".to_string()],
+        description: String::new(),
+        is_default: true,
+        fc_mode: FcMode::Native,
+        api_version: None,
+        proxy: None,
+        proxy_stream: None,
+        proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
+    }];
+    let state = build_state_with_features(
+        upstream_services,
+        vec!["client-key".to_string()],
+        FeaturesConfig {
+            rewrite_response_model: true,
+            ..FeaturesConfig::default()
+        },
+    );
+
+    for stream in [false, true] {
+        let body = serde_json::to_vec(&json!({
+            "model": "smart",
+            "messages": [
+                {
+                    "role": "user",
+                    "content": "ping"
+                }
+            ],
+            "stream": stream
+        }))
+        .expect("serialize alias request");
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .expect("build alias request");
+
+        let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+            .await
+            .expect("dispatch alias request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("read alias response body");
+        let body_text = String::from_utf8(body.to_vec()).expect("utf8 body");
+        assert!(body_text.contains("alias-ok"));
+        assert!(body_text.contains(r#""model":"smart""#));
+        assert!(!body_text.contains("gpt-4o-mini"));
+        if stream {
+            assert!(body_text.contains("[DONE]"));
+        }
+    }
+
+    server.abort();
+}
+
 #[tokio::test]
 async fn test_openai_chat_same_request_failover_to_alternate_upstream() {
     let fail_hits = Arc::new(AtomicUsize::new(0));
@@ -582,6 +765,17 @@ async fn test_openai_chat_transcode_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
         UpstreamServiceConfig {
             name: "mock-anthropic-1".to_string(),
@@ -596,6 +790,17 @@ async fn test_openai_chat_transcode_failover_to_alternate_upstream() {
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
     ];
     let state = build_state_multi_from_services(upstream_services, allowed_keys.clone());
@@ -734,6 +939,17 @@ async fn test_openai_chat_transcode_stream_failover_to_alternate_upstream_before
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
         UpstreamServiceConfig {
             name: "mock-anthropic-stream-1".to_string(),
@@ -748,6 +964,17 @@ async fn test_openai_chat_transcode_stream_failover_to_alternate_upstream_before
             proxy: None,
             proxy_stream: None,
             proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
         },
     ];
     let state = build_state_multi_from_services(upstream_services, allowed_keys.clone());
@@ -799,3 +1026,1290 @@ async fn test_openai_chat_transcode_stream_failover_to_alternate_upstream_before
     fail_server.abort();
     success_server.abort();
 }
+
+/// Hands generated-client calls straight to `dispatch_request`.
+#[cfg(feature = "grpc")]
+#[derive(Clone)]
+struct DispatchChannel(Arc<AppState>);
+
+#[cfg(feature = "grpc")]
+impl tower_service::Service<Request<tonic::body::Body>> for DispatchChannel {
+    type Response = Response;
+    type Error = std::convert::Infallible;
+    type Future =
+        std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response, Self::Error>> + Send>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<tonic::body::Body>) -> Self::Future {
+        let state = Arc::clone(&self.0);
+        Box::pin(dispatch_request(
+            state,
+            Arc::<str>::from(""),
+            request.map(Body::new),
+        ))
+    }
+}
+
+#[cfg(feature = "grpc")]
+#[tokio::test]
+async fn test_grpc_complete_and_complete_stream_share_chat_flow() {
+    use toolify_rs::protocol::grpc::completion_client::CompletionClient;
+    use toolify_rs::protocol::grpc::{
+        part, stream_event, CompleteRequest, Message, Part, Role, StopReason,
+    };
+
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(request): Json<serde_json::Value>| async move {
+            assert_eq!(request["messages"][0]["role"], "system");
+            if request["stream"].as_bool() == Some(true) {
+                let sse = if request["messages"][1]["content"] == "fail" {
+                    concat!(
+                        "data: {\"id\":\"chatcmpl_grpc\",\"object\":\"chat.completion.chunk\",\"created\":1727000001,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"grpc-\"},\"finish_reason\":null}]}\n\n",
+                        "data: {\"error\":{\"message\":\"upstream overloaded\",\"code\":503}}\n\n"
+                    )
+                } else {
+                    concat!(
+                        "data: {\"id\":\"chatcmpl_grpc\",\"object\":\"chat.completion.chunk\",\"created\":1727000001,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"grpc-ok\"},\"finish_reason\":null}]}\n\n",
+                        "data: {\"id\":\"chatcmpl_grpc\",\"object\":\"chat.completion.chunk\",\"created\":1727000001,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+                        "data: [DONE]\n\n"
+                    )
+                };
+                return Response::builder()
+                    .status(StatusCode::OK)
+                    .header("content-type", "text/event-stream")
+                    .body(Body::from(sse))
+                    .expect("stream response");
+            }
+            Json(json!({
+                "id": "chatcmpl_grpc",
+                "object": "chat.completion",
+                "created": 1_727_000_001_u64,
+                "model": "gpt-4o-mini",
+                "choices": [
+                    {
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "grpc-ok" },
+                        "finish_reason": "stop"
+                    }
+                ]
+            }))
+            .into_response()
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind grpc upstream");
+    let addr = listener.local_addr().expect("grpc upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let upstream_services = vec![UpstreamServiceConfig {
+        name: "mock-openai-grpc".to_string(),
+        provider: "openai".to_string(),
+        base_url: format!("http://{addr}/v1"),
+        api_key: "upstream-secret".to_string(),
+        models: vec!["gpt-4o-mini".to_string()],
+        description: String::new(),
+        is_default: true,
+        fc_mode: FcMode::Native,
+        api_version: None,
+        proxy: None,
+        proxy_stream: None,
+        proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
+    }];
+    let state = build_state_with_features(
+        upstream_services,
+        vec!["client-key".to_string()],
+        FeaturesConfig {
+            enable_grpc_ingress: true,
+            ..FeaturesConfig::default()
+        },
+    );
+    let mut client = CompletionClient::new(DispatchChannel(Arc::clone(&state)));
+    let call = |text: &str, key: &str| {
+        let mut request = tonic::Request::new(CompleteRequest {
+            model: "gpt-4o-mini".to_string(),
+            system_prompt: Some("be brief".to_string()),
+            messages: vec![Message {
+                role: Role::User.into(),
+                parts: vec![Part {
+                    kind: Some(part::Kind::Text(text.to_string())),
+                }],
+                ..Message::default()
+            }],
+            ..CompleteRequest::default()
+        });
+        request.metadata_mut().insert(
+            "authorization",
+            format!("Bearer {key}").parse().expect("metadata value"),
+        );
+        request
+    };
+
+    let response = client
+        .complete(call("ping", "client-key"))
+        .await
+        .expect("grpc complete")
+        .into_inner();
+    assert_eq!(
+        response.content,
+        vec![Part {
+            kind: Some(part::Kind::Text("grpc-ok".to_string())),
+        }]
+    );
+    assert_eq!(response.stop_reason(), StopReason::EndOfTurn);
+
+    let mut events = client
+        .complete_stream(call("ping", "client-key"))
+        .await
+        .expect("grpc complete stream")
+        .into_inner();
+    let mut received = Vec::new();
+    while let Some(event) = events.message().await.expect("grpc stream event") {
+        received.push(event.event.expect("stream event kind"));
+    }
+    assert!(received.contains(&stream_event::Event::TextDelta("grpc-ok".to_string())));
+    assert!(matches!(
+        received.last(),
+        Some(stream_event::Event::MessageEnd(end)) if end.stop_reason() == StopReason::EndOfTurn
+    ));
+
+    // An in-band stream error ends the call with its mapped status.
+    let mut events = client
+        .complete_stream(call("fail", "client-key"))
+        .await
+        .expect("grpc failing stream")
+        .into_inner();
+    let mut error = None;
+    loop {
+        match events.message().await {
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(status) => {
+                error = Some(status);
+                break;
+            }
+        }
+    }
+    let error = error.expect("stream error status");
+    assert_eq!(error.code(), tonic::Code::Unavailable);
+    assert!(error.message().contains("upstream overloaded"), "{error:?}");
+
+    let error = client
+        .complete(call("ping", "wrong-key"))
+        .await
+        .expect_err("unauthenticated grpc call");
+    assert_eq!(error.code(), tonic::Code::Unauthenticated);
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_legacy_completions_mapped_through_chat_flow() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(request): Json<serde_json::Value>| async move {
+            assert_eq!(request["messages"][0]["role"], "user");
+            assert_eq!(request["messages"][0]["content"], "Say");
+            if request["stream"].as_bool() == Some(true) {
+                let sse = concat!(
+                    "data: {\"id\":\"chatcmpl_legacy\",\"object\":\"chat.completion.chunk\",\"created\":1727000001,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\" hi\"},\"finish_reason\":null}]}\n\n",
+                    "data: {\"id\":\"chatcmpl_legacy\",\"object\":\"chat.completion.chunk\",\"created\":1727000001,\"model\":\"gpt-4o-mini\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+                    "data: [DONE]\n\n"
+                );
+                return Response::builder()
+                    .status(StatusCode::OK)
+                    .header("content-type", "text/event-stream")
+                    .body(Body::from(sse))
+                    .expect("stream response");
+            }
+            Json(json!({
+                "id": "chatcmpl_legacy",
+                "object": "chat.completion",
+                "created": 1_727_000_001_u64,
+                "model": "gpt-4o-mini",
+                "choices": [
+                    {
+                        "index": 0,
+                        "message": { "role": "assistant", "content": " hi" },
+                        "finish_reason": "stop"
+                    }
+                ],
+                "usage": { "prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2 }
+            }))
+            .into_response()
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind legacy upstream");
+    let addr = listener.local_addr().expect("legacy upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    let state = build_state(format!("http://{addr}/v1"));
+
+    for stream in [false, true] {
+        let body = serde_json::to_vec(&json!({
+            "model": "gpt-4o-mini",
+            "prompt": "Say",
+            "echo": true,
+            "max_tokens": 4,
+            "stream": stream
+        }))
+        .expect("serialize legacy request");
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/completions")
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .expect("build legacy request");
+        let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+            .await
+            .expect("dispatch legacy request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("read legacy response body");
+        let body_text = String::from_utf8(body.to_vec()).expect("utf8 body");
+        assert!(
+            body_text.contains(r#""object":"text_completion""#),
+            "{body_text}"
+        );
+        assert!(body_text.contains(r#""text":"Say hi""#), "{body_text}");
+        assert!(!body_text.contains("chat.completion"), "{body_text}");
+        if stream {
+            assert!(body_text.contains("[DONE]"), "{body_text}");
+        } else {
+            let json: serde_json::Value = serde_json::from_str(&body_text).expect("json body");
+            assert_eq!(json["usage"]["total_tokens"], 2);
+        }
+    }
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_gemini_openai_compat_path_folds_google_extensions() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(request): Json<serde_json::Value>| async move {
+            assert!(request.get("extra_body").is_none(), "{request}");
+            assert_eq!(request["reasoning_effort"], "low");
+            Json(json!({
+                "id": "chatcmpl_compat",
+                "object": "chat.completion",
+                "created": 1_727_000_001_u64,
+                "model": "gpt-4o-mini",
+                "choices": [
+                    {
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "compat-ok" },
+                        "finish_reason": "stop"
+                    }
+                ]
+            }))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind compat upstream");
+    let addr = listener.local_addr().expect("compat upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    let state = build_state(format!("http://{addr}/v1"));
+
+    let body = serde_json::to_vec(&json!({
+        "model": "gpt-4o-mini",
+        "messages": [{ "role": "user", "content": "ping" }],
+        "extra_body": {
+            "google": { "thinking_config": { "thinking_budget": 256, "include_thoughts": true } }
+        }
+    }))
+    .expect("serialize compat request");
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1beta/openai/chat/completions")
+        .header("authorization", "Bearer client-key")
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .expect("build compat request");
+    let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+        .await
+        .expect("dispatch compat request");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("read compat response body");
+    assert!(String::from_utf8_lossy(&body).contains("compat-ok"));
+
+    let request = Request::builder()
+        .method("GET")
+        .uri("/v1beta/openai/models")
+        .header("authorization", "Bearer client-key")
+        .body(Body::empty())
+        .expect("build compat models request");
+    let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+        .await
+        .expect("dispatch compat models request");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_batch_runs_requests_in_background_and_serves_results() {
+    use toolify_rs::batch::BatchStore;
+
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(request): Json<serde_json::Value>| async move {
+            let prompt = request["messages"][0]["content"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            if prompt == "fail" {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": { "message": "bad prompt" } })),
+                )
+                    .into_response();
+            }
+            Json(json!({
+                "id": format!("chatcmpl_{prompt}"),
+                "object": "chat.completion",
+                "created": 1_727_000_001_u64,
+                "model": "gpt-4o-mini",
+                "choices": [
+                    {
+                        "index": 0,
+                        "message": { "role": "assistant", "content": format!("echo {prompt}") },
+                        "finish_reason": "stop"
+                    }
+                ]
+            }))
+            .into_response()
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind batch upstream");
+    let addr = listener.local_addr().expect("batch upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let storage_dir = std::env::temp_dir().join(format!(
+        "toolify-batch-test-{}-{}",
+        std::process::id(),
+        fastrand::u64(..)
+    ));
+    let plain_state = build_state(format!("http://{addr}/v1"));
+    let config = plain_state.config.clone();
+    let model_router = ModelRouter::new(&config);
+    let prepared_upstreams = config
+        .upstream_services
+        .iter()
+        .map(PreparedUpstream::new)
+        .collect();
+    let allowed_client_keys = build_allowed_key_set(&config);
+    let state = Arc::new(
+        AppState::new(
+            config,
+            HttpTransport::new(&ServerConfig::default()),
+            model_router,
+            prepared_upstreams,
+            allowed_client_keys,
+        )
+        .with_batch_store(Arc::new(
+            BatchStore::open(&storage_dir).expect("open batch store"),
+        )),
+    );
+
+    let input = ["one", "two", "fail"]
+        .iter()
+        .map(|prompt| {
+            json!({
+                "custom_id": format!("req-{prompt}"),
+                "method": "POST",
+                "url": "/v1/chat/completions",
+                "body": {
+                    "model": "gpt-4o-mini",
+                    "messages": [{ "role": "user", "content": prompt }]
+                }
+            })
+            .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/batches")
+        .header("authorization", "Bearer client-key")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            json!({
+                "endpoint": "/v1/chat/completions",
+                "completion_window": "24h",
+                "metadata": { "job": "nightly-eval" },
+                "input_file_content": input
+            })
+            .to_string(),
+        ))
+        .expect("build batch create request");
+    let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+        .await
+        .expect("dispatch batch create");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("read batch create body");
+    let batch: serde_json::Value = serde_json::from_slice(&body).expect("batch json");
+    assert_eq!(batch["object"], "batch");
+    assert_eq!(batch["request_counts"]["total"], 3);
+    let batch_id = batch["id"].as_str().expect("batch id").to_string();
+
+    let mut batch = batch;
+    for _ in 0..200 {
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/batches/{batch_id}"))
+            .header("authorization", "Bearer client-key")
+            .body(Body::empty())
+            .expect("build batch retrieve request");
+        let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+            .await
+            .expect("dispatch batch retrieve");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("read batch retrieve body");
+        batch = serde_json::from_slice(&body).expect("batch json");
+        if batch["status"] == "completed" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(batch["status"], "completed", "{batch}");
+    assert_eq!(batch["request_counts"]["completed"], 2);
+    assert_eq!(batch["request_counts"]["failed"], 1);
+    assert_eq!(batch["metadata"]["job"], "nightly-eval");
+
+    for (kind, expected) in [("output", 2), ("errors", 1)] {
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/batches/{batch_id}/{kind}"))
+            .header("authorization", "Bearer client-key")
+            .body(Body::empty())
+            .expect("build batch results request");
+        let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+            .await
+            .expect("dispatch batch results");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("read batch results body");
+        let lines: Vec<serde_json::Value> = body
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).expect("result line json"))
+            .collect();
+        assert_eq!(lines.len(), expected, "{kind}");
+        if kind == "output" {
+            assert!(lines.iter().any(|line| line["custom_id"] == "req-one"
+                && line["response"]["body"]["choices"][0]["message"]["content"] == "echo one"));
+        } else {
+            assert_eq!(lines[0]["custom_id"], "req-fail");
+            assert_eq!(lines[0]["response"]["status_code"], 400);
+        }
+    }
+
+    server.abort();
+    let _ = std::fs::remove_dir_all(&storage_dir);
+}
+
+#[tokio::test]
+async fn test_uploaded_file_binds_to_upstream_and_pins_later_requests() {
+    async fn spawn_upstream(name: &'static str) -> String {
+        let app = Router::new()
+            .route(
+                "/v1/files",
+                post(move |headers: axum::http::HeaderMap| async move {
+                    let content_type = headers
+                        .get("content-type")
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    assert!(content_type.starts_with("multipart/form-data"));
+                    Json(json!({ "id": format!("file-{name}"), "object": "file" }))
+                }),
+            )
+            .route(
+                "/v1/files/{id}/content",
+                axum::routing::get(move || async move { format!("stored on {name}") }),
+            )
+            .route(
+                "/v1/files/{id}",
+                axum::routing::delete(move || async move {
+                    Json(json!({ "id": format!("file-{name}"), "deleted": true }))
+                }),
+            )
+            .route(
+                "/v1/chat/completions",
+                post(move || async move {
+                    Json(json!({
+                        "id": "chatcmpl_files",
+                        "object": "chat.completion",
+                        "created": 1_727_000_001_u64,
+                        "model": "gpt-4o-mini",
+                        "choices": [{
+                            "index": 0,
+                            "message": { "role": "assistant", "content": name },
+                            "finish_reason": "stop"
+                        }]
+                    }))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind files upstream");
+        let addr = listener.local_addr().expect("files upstream addr");
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        format!("http://{addr}/v1")
+    }
+
+    let service =
+        |name: &str, base_url: String, models: &[&str], is_default: bool| UpstreamServiceConfig {
+            name: name.to_string(),
+            provider: "openai".to_string(),
+            base_url,
+            api_key: "upstream-secret".to_string(),
+            models: models.iter().map(ToString::to_string).collect(),
+            description: String::new(),
+            is_default,
+            fc_mode: FcMode::Native,
+            api_version: None,
+            proxy: None,
+            proxy_stream: None,
+            proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
+        };
+    let state = build_state_multi_from_services(
+        vec![
+            service("a", spawn_upstream("a").await, &["gpt-4o-mini"], true),
+            service(
+                "b",
+                spawn_upstream("b").await,
+                &["gpt-4o-mini", "gpt-4.1"],
+                false,
+            ),
+        ],
+        vec!["client-key".to_string()],
+    );
+
+    let send = |request: Request<Body>| {
+        let state = Arc::clone(&state);
+        async move {
+            let response = dispatch_request(state, Arc::<str>::from(""), request)
+                .await
+                .expect("dispatch");
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            (status, body)
+        }
+    };
+
+    let (status, body) = send(
+        Request::builder()
+            .method("POST")
+            .uri("/v1/files")
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "multipart/form-data; boundary=x")
+            .header("x-toolify-model", "gpt-4.1")
+            .body(Body::from("--x--\r\n"))
+            .expect("upload request"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let file: serde_json::Value = serde_json::from_slice(&body).expect("file json");
+    assert_eq!(file["id"], "file-b");
+
+    let (status, body) = send(
+        Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "model": "gpt-4o-mini",
+                    "messages": [{
+                        "role": "user",
+                        "content": [{ "type": "file", "file": { "file_id": "file-b" } }]
+                    }]
+                })
+                .to_string(),
+            ))
+            .expect("chat request"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let chat: serde_json::Value = serde_json::from_slice(&body).expect("chat json");
+    assert_eq!(chat["choices"][0]["message"]["content"], "b");
+
+    let (status, body) = send(
+        Request::builder()
+            .method("GET")
+            .uri("/v1/files/file-b/content")
+            .header("authorization", "Bearer client-key")
+            .body(Body::empty())
+            .expect("content request"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_ref(), b"stored on b");
+
+    // A config apply that reorders the upstreams keeps the file on `b`.
+    let mut reordered = state.config.clone();
+    reordered.upstream_services.reverse();
+    let next = state.swap_config(reordered);
+    assert_eq!(next.file_upstream("file-b"), Some(0));
+    let response = dispatch_request(
+        Arc::clone(&next),
+        Arc::<str>::from(""),
+        Request::builder()
+            .method("GET")
+            .uri("/v1/files/file-b/content")
+            .header("authorization", "Bearer client-key")
+            .body(Body::empty())
+            .expect("content request"),
+    )
+    .await
+    .expect("dispatch");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    assert_eq!(body.as_ref(), b"stored on b");
+
+    let (status, _) = send(
+        Request::builder()
+            .method("DELETE")
+            .uri("/v1/files/file-b")
+            .header("authorization", "Bearer client-key")
+            .body(Body::empty())
+            .expect("delete request"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(next.file_upstream("file-b"), None);
+
+    // Files on an upstream the new config removes are no longer pinned.
+    next.bind_file_upstream("file-b", 0);
+    let mut without_b = next.config.clone();
+    without_b
+        .upstream_services
+        .retain(|upstream| upstream.name != "b");
+    assert_eq!(next.swap_config(without_b).file_upstream("file-b"), None);
+}
+
+#[tokio::test]
+async fn test_virtual_model_wraps_target_model_and_postprocesses_output() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(request): Json<serde_json::Value>| async move {
+            assert_eq!(request["model"], "gpt-4o-mini");
+            assert_eq!(request["messages"][0]["role"], "system");
+            assert_eq!(request["messages"][0]["content"], "Answer in one line.");
+            assert_eq!(request["temperature"], 0.1);
+            Json(json!({
+                "id": "chatcmpl_virtual",
+                "object": "chat.completion",
+                "created": 1_727_000_001_u64,
+                "model": "gpt-4o-mini",
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "Sure! 42\n---\nfooter" },
+                    "finish_reason": "stop"
+                }]
+            }))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind virtual upstream");
+    let addr = listener.local_addr().expect("virtual upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let mut config = build_state(format!("http://{addr}/v1")).config.clone();
+    config.virtual_models = serde_yaml::from_str(
+        r#"
+- name: one-liner
+  model: gpt-4o-mini
+  system_prompt: "Answer in one line."
+  defaults: { temperature: 0.1 }
+  postprocess:
+    - { type: regex_replace, pattern: "^Sure! ", replacement: "" }
+    - { type: trim_at, markers: ["\n---"] }
+"#,
+    )
+    .expect("virtual models yaml");
+    toolify_rs::config::validation::validate_config(&config).expect("valid config");
+    let model_router = ModelRouter::new(&config);
+    let prepared_upstreams = config
+        .upstream_services
+        .iter()
+        .map(PreparedUpstream::new)
+        .collect();
+    let allowed_client_keys = build_allowed_key_set(&config);
+    let state = Arc::new(AppState::new(
+        config,
+        HttpTransport::new(&ServerConfig::default()),
+        model_router,
+        prepared_upstreams,
+        allowed_client_keys,
+    ));
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("authorization", "Bearer client-key")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            json!({
+                "model": "one-liner",
+                "messages": [{ "role": "user", "content": "meaning of life?" }]
+            })
+            .to_string(),
+        ))
+        .expect("chat request");
+    let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+        .await
+        .expect("dispatch");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let chat: serde_json::Value = serde_json::from_slice(&body).expect("chat json");
+    assert_eq!(chat["model"], "one-liner");
+    assert_eq!(chat["choices"][0]["message"]["content"], "42");
+
+    let models: serde_json::Value =
+        serde_json::from_slice(&state.models_response_body()).expect("models json");
+    let ids: Vec<&str> = models["data"]
+        .as_array()
+        .expect("model list")
+        .iter()
+        .filter_map(|model| model["id"].as_str())
+        .collect();
+    assert!(ids.contains(&"one-liner"));
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_named_prompt_templates_selected_per_upstream_and_alias() {
+    let system_prompts = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let recorded = Arc::clone(&system_prompts);
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move |Json(request): Json<serde_json::Value>| {
+            let recorded = Arc::clone(&recorded);
+            async move {
+                assert_eq!(request["messages"][0]["role"], "system");
+                assert!(request.get("tools").is_none());
+                recorded.lock().expect("prompt log").push(
+                    request["messages"][0]["content"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                );
+                Json(json!({
+                    "id": "chatcmpl_template",
+                    "object": "chat.completion",
+                    "created": 1_727_000_001_u64,
+                    "model": "gpt-4o-mini",
+                    "choices": [{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "ok" },
+                        "finish_reason": "stop"
+                    }]
+                }))
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind template upstream");
+    let addr = listener.local_addr().expect("template upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let upstream_services = vec![UpstreamServiceConfig {
+        name: "mock-openai".to_string(),
+        provider: "openai".to_string(),
+        base_url: format!("http://{addr}/v1"),
+        api_key: "upstream-secret".to_string(),
+        models: vec![
+            "gpt-4o-mini".to_string(),
+            "smart-zh:gpt-4o-mini".to_string(),
+        ],
+        description: String::new(),
+        is_default: true,
+        fc_mode: FcMode::Inject,
+        api_version: None,
+        proxy: None,
+        proxy_stream: None,
+        proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
+    }];
+    let features: FeaturesConfig = serde_yaml::from_str(
+        r#"
+prompt_templates:
+  localized:
+    template: "Reply in {language}.\n{tools_list}\nCall tools after {trigger_signal}"
+    tool_list_style: compact
+    variables: { language: English }
+upstream_prompt_templates:
+  mock-openai: { template: localized }
+model_prompt_templates:
+  smart-zh: { template: localized, variables: { language: Chinese } }
+"#,
+    )
+    .expect("features yaml");
+    let state =
+        build_state_with_features(upstream_services, vec!["client-key".to_string()], features);
+    toolify_rs::config::validation::validate_config(&state.config).expect("valid config");
+
+    for model in ["gpt-4o-mini", "smart-zh"] {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "model": model,
+                    "messages": [{ "role": "user", "content": "weather in Paris?" }],
+                    "tools": [{
+                        "type": "function",
+                        "function": {
+                            "name": "get_weather",
+                            "description": "Look up the weather",
+                            "parameters": {
+                                "type": "object",
+                                "properties": { "city": { "type": "string" } },
+                                "required": ["city"]
+                            }
+                        }
+                    }]
+                })
+                .to_string(),
+            ))
+            .expect("chat request");
+        let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+            .await
+            .expect("dispatch");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let prompts = system_prompts.lock().expect("prompt log").clone();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[0].starts_with("Reply in English.\n- get_weather(city: string): Look up"));
+    assert!(prompts[1].starts_with("Reply in Chinese.\n- get_weather(city: string)"));
+    assert!(prompts[1].contains(toolify_rs::fc::prompt::get_trigger_signal()));
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_transcode_drops_unsupported_params_with_header() {
+    let app = Router::new().route(
+        "/v1/messages",
+        post(|Json(request): Json<serde_json::Value>| async move {
+            assert!(request.get("seed").is_none());
+            assert!(request.get("logprobs").is_none());
+            assert!(request.get("logit_bias").is_none());
+            assert!(request.get("frequency_penalty").is_none());
+            assert_eq!(request["temperature"], 1.0);
+            Json(json!({
+                "id": "msg_dropped",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-3-5-haiku-latest",
+                "content": [{ "type": "text", "text": "ok" }],
+                "stop_reason": "end_turn",
+                "stop_sequence": null,
+                "usage": { "input_tokens": 3, "output_tokens": 1 }
+            }))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind anthropic upstream");
+    let addr = listener.local_addr().expect("anthropic upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let state = build_state_multi_from_services(
+        vec![UpstreamServiceConfig {
+            name: "mock-anthropic".to_string(),
+            provider: "anthropic".to_string(),
+            base_url: format!("http://{addr}/v1"),
+            api_key: "upstream-secret".to_string(),
+            models: vec!["gpt-4o-mini:claude-3-5-haiku-latest".to_string()],
+            description: String::new(),
+            is_default: true,
+            fc_mode: FcMode::Native,
+            api_version: None,
+            proxy: None,
+            proxy_stream: None,
+            proxy_non_stream: None,
+            stream_support: StreamSupport::Both,
+            tool_schema_validation: ToolSchemaValidation::Off,
+            path_style: PathStyle::Auto,
+            anthropic_betas: Vec::new(),
+            organization: None,
+            project: None,
+            trust_client_organization: false,
+            max_request_bytes: None,
+            auth: UpstreamAuthConfig::default(),
+            tls: None,
+            bind_address: None,
+        }],
+        vec!["client-key".to_string()],
+    );
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("authorization", "Bearer client-key")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            json!({
+                "model": "gpt-4o-mini",
+                "messages": [{ "role": "user", "content": "ping" }],
+                "temperature": 1.6,
+                "seed": 7,
+                "logprobs": true,
+                "logit_bias": { "50256": -100 },
+                "frequency_penalty": 0.5
+            })
+            .to_string(),
+        ))
+        .expect("chat request");
+    let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), request)
+        .await
+        .expect("dispatch");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["x-toolify-dropped-params"],
+        "seed, logprobs, logit_bias"
+    );
+    assert_eq!(
+        response.headers()["x-toolify-unsupported-params"],
+        "seed=dropped, logprobs=dropped, logit_bias=dropped, frequency_penalty=dropped"
+    );
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_image_requests_route_to_vision_capable_models() {
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(|Json(request): Json<serde_json::Value>| async move {
+            let model = request["model"].as_str().unwrap_or_default().to_string();
+            Json(json!({
+                "id": "chatcmpl_vision",
+                "object": "chat.completion",
+                "created": 1,
+                "model": model,
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": model },
+                    "finish_reason": "stop"
+                }]
+            }))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind vision upstream");
+    let addr = listener.local_addr().expect("vision upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let upstream = |name: &str, model: &str| UpstreamServiceConfig {
+        name: name.to_string(),
+        provider: "openai".to_string(),
+        base_url: format!("http://{addr}/v1"),
+        api_key: "upstream-secret".to_string(),
+        models: vec![format!("smart:{model}")],
+        description: String::new(),
+        is_default: name == "text",
+        fc_mode: FcMode::Native,
+        api_version: None,
+        proxy: None,
+        proxy_stream: None,
+        proxy_non_stream: None,
+        stream_support: StreamSupport::Both,
+        tool_schema_validation: ToolSchemaValidation::Off,
+        path_style: PathStyle::Auto,
+        anthropic_betas: Vec::new(),
+        organization: None,
+        project: None,
+        trust_client_organization: false,
+        max_request_bytes: None,
+        auth: UpstreamAuthConfig::default(),
+        tls: None,
+        bind_address: None,
+    };
+    let mut features = FeaturesConfig::default();
+    features.model_capabilities.insert(
+        "text-model".to_string(),
+        vec![ModelCapability::Tools, ModelCapability::JsonMode],
+    );
+    let state = build_state_with_features(
+        vec![
+            upstream("text", "text-model"),
+            upstream("vision", "vision-model"),
+        ],
+        vec!["client-key".to_string()],
+        features.clone(),
+    );
+    let image_request = |question: &str| {
+        Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "model": "smart",
+                    "messages": [{ "role": "user", "content": [
+                        { "type": "text", "text": question },
+                        { "type": "image_url", "image_url": { "url": "data:image/png;base64,AAAA" } }
+                    ]}]
+                })
+                .to_string(),
+            ))
+            .expect("image request")
+    };
+
+    // Distinct prompts hash to different primaries within the alias group.
+    for question in ["what is this?", "describe it", "which colour?", "how many?"] {
+        let response = dispatch_request(
+            Arc::clone(&state),
+            Arc::<str>::from(""),
+            image_request(question),
+        )
+        .await
+        .expect("dispatch");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let chat: serde_json::Value = serde_json::from_slice(&body).expect("chat json");
+        assert_eq!(chat["choices"][0]["message"]["content"], "vision-model");
+    }
+
+    let text_only = build_state_with_features(
+        vec![upstream("text", "text-model")],
+        vec!["client-key".to_string()],
+        features,
+    );
+    let response = dispatch_request(
+        text_only,
+        Arc::<str>::from(""),
+        image_request("what is this?"),
+    )
+    .await
+    .expect("dispatch");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    assert!(String::from_utf8_lossy(&body).contains("supports vision"));
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn test_refusal_is_retried_on_next_candidate() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits_clone = Arc::clone(&hits);
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move || {
+            let hits = Arc::clone(&hits_clone);
+            async move {
+                // Only the first answer is a refusal.
+                let content = if hits.fetch_add(1, Ordering::Relaxed) == 0 {
+                    "I can't help with that."
+                } else {
+                    "42"
+                };
+                Json(json!({
+                    "id": "chatcmpl_quality",
+                    "object": "chat.completion",
+                    "created": 1,
+                    "model": "gpt-4o-mini",
+                    "choices": [{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": content },
+                        "finish_reason": "stop"
+                    }]
+                }))
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind quality upstream");
+    let addr = listener.local_addr().expect("quality upstream addr");
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+
+    let features = FeaturesConfig {
+        quality_retry: Some(QualityRetryConfig {
+            max_retries: 1,
+            refusal_patterns: vec!["(?i)i can't help with that\\.?".to_string()],
+        }),
+        ..FeaturesConfig::default()
+    };
+    let state = build_state_with_features(
+        (0..2)
+            .map(|index| UpstreamServiceConfig {
+                name: format!("mock-openai-{index}"),
+                provider: "openai".to_string(),
+                base_url: format!("http://{addr}/v1"),
+                api_key: "upstream-secret".to_string(),
+                models: vec!["gpt-4o-mini".to_string()],
+                description: String::new(),
+                is_default: index == 0,
+                fc_mode: FcMode::Native,
+                api_version: None,
+                proxy: None,
+                proxy_stream: None,
+                proxy_non_stream: None,
+                stream_support: StreamSupport::Both,
+                tool_schema_validation: ToolSchemaValidation::Off,
+                path_style: PathStyle::Auto,
+                anthropic_betas: Vec::new(),
+                organization: None,
+                project: None,
+                trust_client_organization: false,
+                max_request_bytes: None,
+                auth: UpstreamAuthConfig::default(),
+                tls: None,
+                bind_address: None,
+            })
+            .collect(),
+        vec!["client-key".to_string()],
+        features,
+    );
+    let chat_request = || {
+        Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header("authorization", "Bearer client-key")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "model": "gpt-4o-mini",
+                    "messages": [{ "role": "user", "content": "meaning of life?" }]
+                })
+                .to_string(),
+            ))
+            .expect("chat request")
+    };
+    let answer = |response: Response| async move {
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let chat: serde_json::Value = serde_json::from_slice(&body).expect("chat json");
+        chat["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let response = dispatch_request(Arc::clone(&state), Arc::<str>::from(""), chat_request())
+        .await
+        .expect("dispatch");
+    assert_eq!(answer(response).await, "42");
+    assert_eq!(hits.load(Ordering::Relaxed), 2);
+    let retries = state.quality_retry().expect("policy").retry_counts();
+    assert_eq!(retries.len(), 1);
+    assert_eq!((retries[0].reason, retries[0].count), ("refusal", 1));
+
+    // With a single candidate the rejected answer is surfaced.
+    hits.store(0, Ordering::Relaxed);
+    let mut single = state.config.clone();
+    single.upstream_services.truncate(1);
+    let single = build_state_with_features(
+        single.upstream_services,
+        vec!["client-key".to_string()],
+        single.features,
+    );
+    let response = dispatch_request(single, Arc::<str>::from(""), chat_request())
+        .await
+        .expect("dispatch");
+    assert_eq!(answer(response).await, "I can't help with that.");
+    assert_eq!(hits.load(Ordering::Relaxed), 1);
+
+    server.abort();
+}
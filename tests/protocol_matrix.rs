@@ -1,7 +1,9 @@
+#![cfg(feature = "server")]
+
 use serde_json::json;
 use toolify_rs::protocol::canonical::{
-    CanonicalPart, CanonicalRequest, CanonicalResponse, CanonicalStopReason, CanonicalToolChoice,
-    CanonicalUsage, ProviderKind,
+    CanonicalCitation, CanonicalPart, CanonicalRequest, CanonicalResponse, CanonicalStopReason,
+    CanonicalToolChoice, CanonicalUsage, ProviderKind,
 };
 use toolify_rs::protocol::{anthropic, gemini, openai_chat, openai_responses};
 use uuid::Uuid;
@@ -218,6 +220,7 @@ fn base_canonical_response() -> CanonicalResponse {
         ],
         stop_reason: CanonicalStopReason::ToolCalls,
         usage: CanonicalUsage::default(),
+        citations: Vec::new(),
         provider_extensions: serde_json::Map::new(),
     }
 }
@@ -292,3 +295,168 @@ fn test_response_matrix_across_ingress_formats() {
             .expect("gemini decode");
     assert_response_tool_semantics(&gemini_back);
 }
+
+#[test]
+fn test_citations_survive_every_response_format() {
+    let text = "Rust — 1.0 shipped in 2015.";
+    let located = CanonicalCitation {
+        url: "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html".to_string(),
+        title: Some("Announcing Rust 1.0".to_string()),
+        cited_text: Some("We are very proud to announce the 1.0 release".to_string()),
+        span: Some(7..26),
+    };
+    let unlocated = CanonicalCitation {
+        url: "https://www.rust-lang.org/".to_string(),
+        title: None,
+        cited_text: None,
+        span: None,
+    };
+    let canonical = CanonicalResponse {
+        content: vec![CanonicalPart::Text(text.to_string())],
+        stop_reason: CanonicalStopReason::EndOfTurn,
+        citations: vec![located.clone(), unlocated.clone()],
+        ..base_canonical_response()
+    };
+    let without_quote = CanonicalCitation {
+        cited_text: None,
+        ..located.clone()
+    };
+    let whole_text = CanonicalCitation {
+        span: Some(0..text.chars().count()),
+        ..unlocated.clone()
+    };
+
+    let chat_wire =
+        openai_chat::response_encoder::encode_openai_chat_response(&canonical, "gpt-4o")
+            .expect("chat encode");
+    let chat_back = openai_chat::response_decoder::decode_openai_chat_response(&chat_wire)
+        .expect("chat decode");
+    assert_eq!(
+        chat_back.citations,
+        [without_quote.clone(), whole_text.clone()]
+    );
+
+    let resp_wire =
+        openai_responses::response_encoder::encode_responses_output(&canonical, "gpt-4o")
+            .expect("responses encode");
+    let resp_back = openai_responses::response_decoder::decode_responses_output(&resp_wire)
+        .expect("responses decode");
+    assert_eq!(resp_back.citations, [without_quote, whole_text]);
+
+    // Anthropic cites whole text blocks, so the text is split at the span.
+    let anthropic_wire =
+        anthropic::response_encoder::encode_anthropic_response(&canonical, "claude-sonnet-4-5")
+            .expect("anthropic encode");
+    let blocks = serde_json::to_value(&anthropic_wire.content).expect("serialize blocks");
+    assert_eq!(blocks[0]["text"], "Rust — ");
+    assert_eq!(
+        blocks[0]["citations"][0]["url"],
+        "https://www.rust-lang.org/"
+    );
+    assert_eq!(blocks[1]["text"], "1.0 shipped in 2015");
+    assert_eq!(
+        blocks[1]["citations"][0]["type"],
+        "web_search_result_location"
+    );
+    assert_eq!(blocks[2]["text"], ".");
+    assert!(blocks[2].get("citations").is_none());
+    let anthropic_back = anthropic::response_decoder::decode_anthropic_response(&anthropic_wire)
+        .expect("anthropic decode");
+    assert_eq!(
+        anthropic_back.citations,
+        [
+            CanonicalCitation {
+                span: Some(0..7),
+                ..unlocated.clone()
+            },
+            located.clone()
+        ]
+    );
+
+    let gemini_wire =
+        gemini::response_encoder::encode_gemini_response(&canonical).expect("gemini encode");
+    let grounding = serde_json::to_value(&gemini_wire).expect("serialize gemini");
+    let segment =
+        &grounding["candidates"][0]["groundingMetadata"]["groundingSupports"][0]["segment"];
+    // Gemini segments count UTF-8 bytes.
+    assert_eq!(segment["startIndex"], 9);
+    assert_eq!(segment["text"], "1.0 shipped in 2015");
+    let gemini_back =
+        gemini::response_decoder::decode_gemini_response(&gemini_wire, "gemini-2.5-pro")
+            .expect("gemini decode");
+    assert_eq!(
+        gemini_back.citations,
+        [
+            CanonicalCitation {
+                cited_text: None,
+                ..located
+            },
+            unlocated
+        ]
+    );
+}
+
+#[test]
+fn test_anthropic_web_search_response_decodes_with_citations() {
+    let body = json!({
+        "id": "msg_search",
+        "type": "message",
+        "role": "assistant",
+        "model": "claude-sonnet-4-5",
+        "content": [
+            {
+                "type": "server_tool_use",
+                "id": "srvtoolu_1",
+                "name": "web_search",
+                "input": { "query": "rust 1.0 release" }
+            },
+            {
+                "type": "web_search_tool_result",
+                "tool_use_id": "srvtoolu_1",
+                "content": [{
+                    "type": "web_search_result",
+                    "url": "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html",
+                    "title": "Announcing Rust 1.0",
+                    "encrypted_content": "abc"
+                }]
+            },
+            { "type": "text", "text": "Per the announcement, " },
+            {
+                "type": "text",
+                "text": "Rust 1.0 shipped in May 2015.",
+                "citations": [{
+                    "type": "web_search_result_location",
+                    "url": "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html",
+                    "title": "Announcing Rust 1.0",
+                    "cited_text": "We are very proud to announce the 1.0 release of Rust",
+                    "encrypted_index": "xyz"
+                }]
+            }
+        ],
+        "stop_reason": "end_turn",
+        "usage": { "input_tokens": 10, "output_tokens": 12 }
+    });
+    let body = serde_json::to_vec(&body).expect("serialize body");
+    let canonical = anthropic::response_decoder::try_decode_anthropic_response_bytes(&body)
+        .expect("fast decode");
+    assert_eq!(canonical.content.len(), 2);
+    assert_eq!(canonical.citations.len(), 1);
+    assert_eq!(canonical.citations[0].span, Some(22..51));
+
+    let chat_wire =
+        openai_chat::response_encoder::encode_openai_chat_response(&canonical, "gpt-4o")
+            .expect("chat encode");
+    let message = serde_json::to_value(&chat_wire.choices[0].message).expect("serialize");
+    assert_eq!(
+        message["annotations"],
+        json!([{
+            "type": "url_citation",
+            "url_citation": {
+                "start_index": 22,
+                "end_index": 51,
+                "url": "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html",
+                "title": "Announcing Rust 1.0"
+            }
+        }])
+    );
+}
@@ -116,3 +116,33 @@ fn test_protocol_transcode_request_matrix() {
         assert_tool_semantics(&roundtripped);
     }
 }
+
+#[test]
+fn test_protocol_transcode_max_tokens_per_provider() {
+    let mut request = ingress_openai_chat_request();
+    assert_eq!(request.generation.max_tokens, None);
+
+    // Anthropic requires max_tokens even when the client set none.
+    let anthropic_wire = anthropic::encoder::encode_anthropic_request(&request).expect("encode");
+    assert_eq!(anthropic_wire.max_tokens, 4096);
+    let gemini_wire = gemini::encoder::encode_gemini_request(&request).expect("encode");
+    assert!(gemini_wire.generation_config.is_none());
+
+    request.generation.max_tokens = Some(1_000_000);
+    let anthropic_wire = anthropic::encoder::encode_anthropic_request(&request).expect("encode");
+    assert_eq!(anthropic_wire.max_tokens, 128_000);
+    let gemini_wire = gemini::encoder::encode_gemini_request(&request).expect("encode");
+    assert_eq!(
+        gemini_wire
+            .generation_config
+            .and_then(|config| config.max_output_tokens),
+        Some(65_536)
+    );
+
+    request.generation.max_tokens = Some(8);
+    let responses_wire =
+        openai_responses::encoder::encode_responses_request(&request).expect("encode");
+    assert_eq!(responses_wire.max_output_tokens, Some(16));
+    let chat_wire = openai_chat::encoder::encode_openai_chat_request(&request).expect("encode");
+    assert_eq!(chat_wire.max_tokens, Some(8));
+}
@@ -1,8 +1,11 @@
+#![cfg(feature = "server")]
+
 use std::sync::Arc;
 
 use toolify_rs::auth::build_allowed_key_set;
 use toolify_rs::config::{
-    AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, ServerConfig, UpstreamServiceConfig,
+    AppConfig, ClientAuthConfig, FcMode, FeaturesConfig, PathStyle, ServerConfig, StreamSupport,
+    ToolSchemaValidation, UpstreamAuthConfig, UpstreamServiceConfig,
 };
 use toolify_rs::error::CanonicalError;
 use toolify_rs::routing::ModelRouter;
@@ -26,6 +29,17 @@ fn build_state() -> Arc<AppState> {
                 proxy: None,
                 proxy_stream: None,
                 proxy_non_stream: None,
+                stream_support: StreamSupport::Both,
+                tool_schema_validation: ToolSchemaValidation::Off,
+                path_style: PathStyle::Auto,
+                anthropic_betas: Vec::new(),
+                organization: None,
+                project: None,
+                trust_client_organization: false,
+                max_request_bytes: None,
+                auth: UpstreamAuthConfig::default(),
+                tls: None,
+                bind_address: None,
             },
             UpstreamServiceConfig {
                 name: "anthropic-b".to_string(),
@@ -40,12 +54,31 @@ fn build_state() -> Arc<AppState> {
                 proxy: None,
                 proxy_stream: None,
                 proxy_non_stream: None,
+                stream_support: StreamSupport::Both,
+                tool_schema_validation: ToolSchemaValidation::Off,
+                path_style: PathStyle::Auto,
+                anthropic_betas: Vec::new(),
+                organization: None,
+                project: None,
+                trust_client_organization: false,
+                max_request_bytes: None,
+                auth: UpstreamAuthConfig::default(),
+                tls: None,
+                bind_address: None,
             },
         ],
         client_authentication: ClientAuthConfig {
             allowed_keys: vec!["client-key".to_string()],
+            admin_keys: Vec::new(),
+            key_model_maps: Vec::new(),
         },
         features: FeaturesConfig::default(),
+        virtual_models: Vec::new(),
+        routing_rules: Vec::new(),
+        cascade_models: Vec::new(),
+        admin_authentication: None,
+        fallback_chains: Vec::new(),
+        tenants: Vec::new(),
     };
 
     let model_router = ModelRouter::new(&config);
@@ -75,6 +108,7 @@ fn test_session_policy_portable_vs_anchored_order_when_primary_breaker_open() {
     let failure = CanonicalError::Upstream {
         status: 503,
         message: "temporarily unavailable".to_string(),
+        retry_after: None,
     };
     for _ in 0..5 {
         state.record_upstream_failure(0, model, &failure);
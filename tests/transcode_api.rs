@@ -0,0 +1,93 @@
+use toolify_rs::transcode::{IngressApi, ProviderKind, SseParser, StreamTranscoder};
+
+#[test]
+fn stream_transcoder_builder_transcodes_frames() {
+    let mut transcoder = StreamTranscoder::builder(ProviderKind::OpenAi, IngressApi::Anthropic)
+        .model("client-model")
+        .response_id("msg_1")
+        .build();
+    let mut parser = SseParser::new();
+    let frames = parser.feed(concat!(
+        "data: {\"id\":\"c1\",\"object\":\"chat.completion.chunk\",\"created\":0,\"model\":\"m\",",
+        "\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hi\"},",
+        "\"finish_reason\":null}]}\n\n",
+    ));
+
+    let out: String = frames
+        .iter()
+        .flat_map(|frame| transcoder.transcode_frame(frame))
+        .collect();
+    assert!(out.contains("event: message_start"));
+    assert!(out.contains("\"model\":\"client-model\""));
+    assert!(out.contains("\"text\":\"Hi\""));
+}
+
+#[cfg(feature = "server")]
+mod codec {
+    use serde_json::{json, Value};
+    use toolify_rs::transcode::{
+        decode_response, encode_request, encode_response, CanonicalMessage, CanonicalPart,
+        CanonicalRequest, CanonicalRole, CanonicalStopReason, CanonicalToolChoice,
+        GenerationParams, IngressApi, ProviderKind,
+    };
+
+    #[test]
+    fn non_streaming_response_transcodes_between_providers() {
+        let upstream = json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-x",
+            "content": [
+                { "type": "text", "text": "Checking." },
+                { "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": { "city": "SF" } }
+            ],
+            "stop_reason": "tool_use",
+            "usage": { "input_tokens": 3, "output_tokens": 5 }
+        });
+
+        let canonical = decode_response(ProviderKind::Anthropic, upstream.to_string().as_bytes())
+            .expect("decode");
+        assert_eq!(canonical.stop_reason, CanonicalStopReason::ToolCalls);
+
+        let body =
+            encode_response(IngressApi::OpenAiChat, &canonical, "client-model").expect("encode");
+        let body: Value = serde_json::from_slice(&body).expect("json");
+        assert_eq!(body["model"], "client-model");
+        assert_eq!(body["choices"][0]["message"]["content"], "Checking.");
+        let call = &body["choices"][0]["message"]["tool_calls"][0];
+        assert_eq!(call["function"]["name"], "get_weather");
+        assert_eq!(body["choices"][0]["finish_reason"], "tool_calls");
+    }
+
+    #[test]
+    fn request_encodes_for_provider() {
+        let request = CanonicalRequest {
+            request_id: uuid::Uuid::from_u128(1),
+            ingress_api: IngressApi::OpenAiChat,
+            model: "claude-x".to_string(),
+            stream: false,
+            system_prompt: Some("Be brief.".to_string()),
+            messages: vec![CanonicalMessage {
+                role: CanonicalRole::User,
+                parts: vec![CanonicalPart::Text("Hi".to_string())].into(),
+                name: None,
+                tool_call_id: None,
+                provider_extensions: None,
+            }],
+            tools: Vec::new().into(),
+            tool_choice: CanonicalToolChoice::Auto,
+            generation: GenerationParams {
+                max_tokens: Some(64),
+                ..GenerationParams::default()
+            },
+            provider_extensions: None,
+        };
+
+        let body = encode_request(ProviderKind::Anthropic, &request).expect("encode");
+        let body: Value = serde_json::from_slice(&body).expect("json");
+        assert_eq!(body["model"], "claude-x");
+        assert_eq!(body["system"], "Be brief.");
+        assert_eq!(body["messages"][0]["content"][0]["text"], "Hi");
+    }
+}
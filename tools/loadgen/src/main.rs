@@ -0,0 +1,314 @@
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use http::{header, HeaderValue, Method, Request, Uri};
+use http_body_util::{BodyExt, Full};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+
+const DEFAULT_PROXY_URL: &str = "http://127.0.0.1:18080/v1/chat/completions";
+const DEFAULT_API_KEY: &str = "sk-client";
+const DEFAULT_MODEL: &str = "m1";
+const DEFAULT_REQS: usize = 2_000;
+const DEFAULT_CONCURRENCY: usize = 16;
+const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
+#[derive(Copy, Clone)]
+enum LoadMode {
+    Nonstream,
+    Stream,
+    Mixed,
+}
+
+impl LoadMode {
+    /// Whether request `index` streams; mixed load alternates.
+    fn is_stream(self, index: usize) -> bool {
+        match self {
+            Self::Nonstream => false,
+            Self::Stream => true,
+            Self::Mixed => index % 2 == 1,
+        }
+    }
+}
+
+struct LoadConfig {
+    uri: Uri,
+    authorization: HeaderValue,
+    reqs: usize,
+    concurrency: usize,
+    mode: LoadMode,
+    http2: bool,
+    timeout: Duration,
+    stream_payload: Bytes,
+    nonstream_payload: Bytes,
+}
+
+/// One completed request.
+struct Sample {
+    stream: bool,
+    ttfb: Duration,
+    frames: u64,
+}
+
+#[derive(Default)]
+struct WorkerResult {
+    samples: Vec<Sample>,
+    errors: u64,
+}
+
+type HttpClient = Client<HttpConnector, Full<Bytes>>;
+
+#[tokio::main]
+async fn main() {
+    let config = Arc::new(load_config());
+    let mut connector = HttpConnector::new();
+    connector.set_nodelay(true);
+    let client: HttpClient = Client::builder(TokioExecutor::new())
+        .http2_only(config.http2)
+        .build(connector);
+
+    let next_index = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+    let workers: Vec<_> = (0..config.concurrency)
+        .map(|_| {
+            let config = Arc::clone(&config);
+            let client = client.clone();
+            let next_index = Arc::clone(&next_index);
+            tokio::spawn(async move { run_worker(&client, &config, &next_index).await })
+        })
+        .collect();
+
+    let mut result = WorkerResult::default();
+    for worker in workers {
+        match worker.await {
+            Ok(worker_result) => {
+                result.samples.extend(worker_result.samples);
+                result.errors += worker_result.errors;
+            }
+            Err(err) => eprintln!("loadgen worker panicked: {err}"),
+        }
+    }
+    let wall = started.elapsed();
+
+    println!(
+        "total reqs={} ok={} errors={} wall_ms={} rps={:.2}",
+        config.reqs,
+        result.samples.len(),
+        result.errors,
+        wall.as_millis(),
+        per_sec(result.samples.len() as u64, wall)
+    );
+    for (label, stream) in [("nonstream", false), ("stream", true)] {
+        let samples: Vec<&Sample> = result
+            .samples
+            .iter()
+            .filter(|sample| sample.stream == stream)
+            .collect();
+        if !samples.is_empty() {
+            print_summary(label, &samples, wall);
+        }
+    }
+    if result.errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+async fn run_worker(
+    client: &HttpClient,
+    config: &LoadConfig,
+    next_index: &AtomicUsize,
+) -> WorkerResult {
+    let mut result = WorkerResult::default();
+    loop {
+        let index = next_index.fetch_add(1, Ordering::Relaxed);
+        if index >= config.reqs {
+            return result;
+        }
+        let stream = config.mode.is_stream(index);
+        match tokio::time::timeout(config.timeout, send_request(client, config, stream)).await {
+            Ok(Ok(sample)) => result.samples.push(sample),
+            Ok(Err(err)) => {
+                result.errors += 1;
+                eprintln!("request {index} failed: {err}");
+            }
+            Err(_) => {
+                result.errors += 1;
+                eprintln!("request {index} timed out");
+            }
+        }
+    }
+}
+
+/// Send one request and read the whole response, timing the first body
+/// frame and counting SSE events.
+async fn send_request(
+    client: &HttpClient,
+    config: &LoadConfig,
+    stream: bool,
+) -> Result<Sample, String> {
+    let payload = if stream {
+        config.stream_payload.clone()
+    } else {
+        config.nonstream_payload.clone()
+    };
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(config.uri.clone())
+        .header(header::AUTHORIZATION, config.authorization.clone())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(payload))
+        .map_err(|err| err.to_string())?;
+
+    let started = Instant::now();
+    let response = client
+        .request(request)
+        .await
+        .map_err(|err| err.to_string())?;
+    let status = response.status();
+    let mut body = response.into_body();
+    let mut ttfb = None;
+    let mut counter = FrameCounter::default();
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|err| err.to_string())?;
+        let Some(data) = frame.data_ref() else {
+            continue;
+        };
+        if data.is_empty() {
+            continue;
+        }
+        ttfb.get_or_insert_with(|| started.elapsed());
+        counter.feed(data);
+    }
+    if !status.is_success() {
+        return Err(format!("status {status}"));
+    }
+    let ttfb = ttfb.unwrap_or_else(|| started.elapsed());
+    let frames = if stream { counter.events } else { 1 };
+    Ok(Sample {
+        stream,
+        ttfb,
+        frames,
+    })
+}
+
+/// Counts SSE events (blank-line terminated) across chunk boundaries.
+#[derive(Default)]
+struct FrameCounter {
+    events: u64,
+    last_newline: bool,
+}
+
+impl FrameCounter {
+    fn feed(&mut self, data: &[u8]) {
+        for &byte in data {
+            match byte {
+                b'\n' => {
+                    if self.last_newline {
+                        self.events += 1;
+                    }
+                    self.last_newline = !self.last_newline;
+                }
+                b'\r' => {}
+                _ => self.last_newline = false,
+            }
+        }
+    }
+}
+
+fn print_summary(label: &str, samples: &[&Sample], wall: Duration) {
+    let mut ttfbs: Vec<Duration> = samples.iter().map(|sample| sample.ttfb).collect();
+    ttfbs.sort_unstable();
+    let frames: u64 = samples.iter().map(|sample| sample.frames).sum();
+    println!(
+        "{label} ok={} ttfb_p50_ms={:.3} ttfb_p99_ms={:.3} ttfb_max_ms={:.3} frames={frames} frames_per_sec={:.2}",
+        samples.len(),
+        millis(percentile(&ttfbs, 50)),
+        millis(percentile(&ttfbs, 99)),
+        millis(ttfbs.last().copied().unwrap_or_default()),
+        per_sec(frames, wall)
+    );
+}
+
+/// Nearest-rank percentile of sorted `values`.
+fn percentile(values: &[Duration], pct: usize) -> Duration {
+    if values.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (values.len() * pct).div_ceil(100).max(1);
+    values[rank - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000.0
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn per_sec(count: u64, wall: Duration) -> f64 {
+    let secs = wall.as_secs_f64();
+    if secs > 0.0 {
+        count as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+fn load_config() -> LoadConfig {
+    let url = env::var("PROXY_URL").unwrap_or_else(|_| DEFAULT_PROXY_URL.to_string());
+    let uri = url
+        .parse::<Uri>()
+        .unwrap_or_else(|err| panic!("invalid PROXY_URL '{url}': {err}"));
+    if uri.scheme_str() != Some("http") {
+        panic!("PROXY_URL must be a plain http:// URL, got '{url}'");
+    }
+    let api_key = env::var("API_KEY").unwrap_or_else(|_| DEFAULT_API_KEY.to_string());
+    let authorization = HeaderValue::from_str(&format!("Bearer {api_key}"))
+        .unwrap_or_else(|err| panic!("invalid API_KEY: {err}"));
+    let model = env::var("MODEL").unwrap_or_else(|_| DEFAULT_MODEL.to_string());
+    let payload = |stream: bool| {
+        let stream_field = if stream { ",\"stream\":true" } else { "" };
+        Bytes::from(format!(
+            "{{\"model\":\"{model}\",\"messages\":[{{\"role\":\"user\",\"content\":\"hi\"}}]{stream_field}}}"
+        ))
+    };
+    LoadConfig {
+        uri,
+        authorization,
+        reqs: env_usize("REQS", DEFAULT_REQS),
+        concurrency: env_usize("CONCURRENCY", DEFAULT_CONCURRENCY).max(1),
+        mode: parse_mode(),
+        http2: matches!(env::var("LOADGEN_TRANSPORT").as_deref(), Ok("h2c")),
+        timeout: Duration::from_millis(env_u64("TIMEOUT_MS", DEFAULT_TIMEOUT_MS)),
+        stream_payload: payload(true),
+        nonstream_payload: payload(false),
+    }
+}
+
+fn env_usize(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(default)
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+fn parse_mode() -> LoadMode {
+    match env::var("LOADGEN_MODE").as_deref() {
+        Ok("stream") => LoadMode::Stream,
+        Ok("mixed") => LoadMode::Mixed,
+        Ok("nonstream") | Err(_) => LoadMode::Nonstream,
+        Ok(other) => {
+            eprintln!("unknown LOADGEN_MODE '{other}', fallback to nonstream");
+            LoadMode::Nonstream
+        }
+    }
+}
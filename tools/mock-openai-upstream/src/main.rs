@@ -1,19 +1,54 @@
 use std::convert::Infallible;
 use std::env;
+use std::io;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
 
 use bytes::Bytes;
 use http::{header, HeaderValue, Method, Request, Response, StatusCode, Version};
+use http_body_util::combinators::BoxBody;
 use http_body_util::BodyExt;
 use http_body_util::Full;
-use hyper::body::Incoming;
+use hyper::body::{Frame, Incoming};
 use hyper::service::service_fn;
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use hyper_util::server::conn::auto::Builder as AutoBuilder;
 use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+
+mod scenario;
+
+use scenario::{split_sse_events, Scenario, ScenarioStep, MALFORMED_FRAME, MALFORMED_JSON};
 
 const DEFAULT_UPSTREAM_PORT: u16 = 19_001;
+const SCRIPTED_ERROR_BODY: &[u8] =
+    br#"{"error":{"message":"mock scripted error","type":"mock_scripted_error"}}"#;
+
+const MIN_DROP_DELAY: Duration = Duration::from_millis(10);
+
+type MockBody = BoxBody<Bytes, io::Error>;
+
+/// Response body fed chunk by chunk from a scripted stream task.
+struct ChannelBody {
+    rx: mpsc::Receiver<Result<Bytes, io::Error>>,
+}
+
+impl hyper::body::Body for ChannelBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, io::Error>>> {
+        self.rx
+            .poll_recv(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map(Frame::data)))
+    }
+}
 
 #[derive(Copy, Clone)]
 enum MockMode {
@@ -91,6 +126,7 @@ struct MockState {
     mode: MockMode,
     scenario: MockScenario,
     transport: MockTransport,
+    script: Option<Scenario>,
     stats: ProtocolStats,
 }
 
@@ -100,10 +136,12 @@ async fn main() {
     let mode = parse_mode();
     let scenario = parse_scenario();
     let transport = parse_transport();
+    let script = load_script();
     let state = Arc::new(MockState {
         mode,
         scenario,
         transport,
+        script,
         stats: ProtocolStats::new(),
     });
 
@@ -171,6 +209,14 @@ fn parse_scenario() -> MockScenario {
     }
 }
 
+fn load_script() -> Option<Scenario> {
+    let path = env::var_os("MOCK_SCENARIO_FILE")?;
+    match Scenario::load(path.as_ref()) {
+        Ok(script) => Some(script),
+        Err(err) => panic!("MOCK_SCENARIO_FILE: {err}"),
+    }
+}
+
 fn parse_transport() -> MockTransport {
     match env::var("MOCK_TRANSPORT").as_deref() {
         Ok("h2c") => MockTransport::H2c,
@@ -182,7 +228,7 @@ fn parse_transport() -> MockTransport {
     }
 }
 
-async fn handle_request(request: Request<Incoming>, state: &Arc<MockState>) -> Response<Full<Bytes>> {
+async fn handle_request(request: Request<Incoming>, state: &Arc<MockState>) -> Response<MockBody> {
     let (parts, body) = request.into_parts();
     state.stats.record(parts.version);
     drain_request_body(body).await;
@@ -195,6 +241,9 @@ async fn handle_request(request: Request<Incoming>, state: &Arc<MockState>) -> R
     }
     if method == Method::POST && path == "/_mock/reset" {
         state.stats.reset();
+        if let Some(script) = &state.script {
+            script.reset();
+        }
         return simple_response_static(StatusCode::OK, "application/json", br#"{"ok":true}"#);
     }
     if method != Method::POST {
@@ -213,6 +262,10 @@ async fn handle_request(request: Request<Incoming>, state: &Arc<MockState>) -> R
         );
     };
 
+    if let Some(step) = state.script.as_ref().and_then(Scenario::next_step) {
+        return scripted_response(step, provider, state).await;
+    }
+
     if matches!(state.scenario, MockScenario::Error) {
         return simple_response_static(
             StatusCode::SERVICE_UNAVAILABLE,
@@ -251,7 +304,7 @@ fn provider_for_path(path: &str) -> Option<ProviderApi> {
     }
 }
 
-fn stats_response(state: &MockState) -> Response<Full<Bytes>> {
+fn stats_response(state: &MockState) -> Response<MockBody> {
     let (h1, h2, other) = state.stats.snapshot();
     let mode = match state.mode {
         MockMode::Nonstream => "nonstream",
@@ -267,8 +320,11 @@ fn stats_response(state: &MockState) -> Response<Full<Bytes>> {
         MockTransport::Auto => "auto",
         MockTransport::H2c => "h2c",
     };
+    let script_served = state.script.as_ref().map_or_else(String::new, |script| {
+        format!(",\"script_served\":{}", script.served())
+    });
     let body = format!(
-        "{{\"mode\":\"{mode}\",\"scenario\":\"{scenario}\",\"transport\":\"{transport}\",\"h1\":{h1},\"h2\":{h2},\"other\":{other}}}"
+        "{{\"mode\":\"{mode}\",\"scenario\":\"{scenario}\",\"transport\":\"{transport}\",\"h1\":{h1},\"h2\":{h2},\"other\":{other}{script_served}}}"
     );
     simple_response(
         StatusCode::OK,
@@ -277,8 +333,16 @@ fn stats_response(state: &MockState) -> Response<Full<Bytes>> {
     )
 }
 
-fn non_streaming_response(provider: ProviderApi, scenario: MockScenario) -> Response<Full<Bytes>> {
-    let body = match (provider, scenario) {
+fn non_streaming_response(provider: ProviderApi, scenario: MockScenario) -> Response<MockBody> {
+    simple_response_static(
+        StatusCode::OK,
+        "application/json",
+        non_streaming_body(provider, scenario),
+    )
+}
+
+fn non_streaming_body(provider: ProviderApi, scenario: MockScenario) -> &'static [u8] {
+    match (provider, scenario) {
         (ProviderApi::OpenAiChat, MockScenario::Text) => OPENAI_CHAT_NONSTREAM_TEXT,
         (ProviderApi::OpenAiChat, MockScenario::Code) => OPENAI_CHAT_NONSTREAM_CODE,
         (ProviderApi::OpenAiChat, MockScenario::Full) => OPENAI_CHAT_NONSTREAM_FULL,
@@ -292,12 +356,20 @@ fn non_streaming_response(provider: ProviderApi, scenario: MockScenario) -> Resp
         (ProviderApi::GeminiGenerateContent, MockScenario::Text) => GEMINI_NONSTREAM_TEXT,
         (ProviderApi::GeminiGenerateContent, MockScenario::Full) => GEMINI_NONSTREAM_FULL,
         (_, MockScenario::Error) => br#"{"error":"mock_injected_error"}"#,
-    };
-    simple_response_static(StatusCode::OK, "application/json", body)
+    }
 }
 
-fn streaming_response(provider: ProviderApi, scenario: MockScenario) -> Response<Full<Bytes>> {
-    let body = match (provider, scenario) {
+fn streaming_response(provider: ProviderApi, scenario: MockScenario) -> Response<MockBody> {
+    let body = streaming_body(provider, scenario);
+    let mut response = simple_response_static(StatusCode::OK, "text/event-stream", body);
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
+
+fn streaming_body(provider: ProviderApi, scenario: MockScenario) -> &'static [u8] {
+    match (provider, scenario) {
         (ProviderApi::OpenAiChat, MockScenario::Text) => OPENAI_CHAT_STREAM_TEXT,
         (ProviderApi::OpenAiChat, MockScenario::Code) => OPENAI_CHAT_STREAM_CODE,
         (ProviderApi::OpenAiChat, MockScenario::Full) => OPENAI_CHAT_STREAM_FULL,
@@ -311,8 +383,92 @@ fn streaming_response(provider: ProviderApi, scenario: MockScenario) -> Response
         (ProviderApi::GeminiGenerateContent, MockScenario::Text) => GEMINI_STREAM_TEXT,
         (ProviderApi::GeminiGenerateContent, MockScenario::Full) => GEMINI_STREAM_FULL,
         (_, MockScenario::Error) => b"data: {\"error\":\"mock_injected_error\"}\n\n",
+    }
+}
+
+async fn scripted_response(
+    step: &ScenarioStep,
+    provider: ProviderApi,
+    state: &MockState,
+) -> Response<MockBody> {
+    if step.latency_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(step.latency_ms)).await;
+    }
+    let status = step
+        .status
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::OK);
+    let stream = step
+        .stream
+        .unwrap_or(matches!(state.mode, MockMode::Stream));
+    let mut response = if !status.is_success() {
+        let body = step
+            .body
+            .clone()
+            .map_or_else(|| Bytes::from_static(SCRIPTED_ERROR_BODY), Bytes::from);
+        simple_response(status, "application/json", body)
+    } else if stream {
+        scripted_stream_response(step, provider, state.scenario)
+    } else {
+        let body = if step.malformed_frame {
+            Bytes::from_static(MALFORMED_JSON)
+        } else {
+            step.body.clone().map_or_else(
+                || Bytes::from_static(non_streaming_body(provider, state.scenario)),
+                Bytes::from,
+            )
+        };
+        simple_response(StatusCode::OK, "application/json", body)
     };
-    let mut response = simple_response_static(StatusCode::OK, "text/event-stream", body);
+    if let Some(retry_after) = step.retry_after {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    }
+    response
+}
+
+fn scripted_stream_response(
+    step: &ScenarioStep,
+    provider: ProviderApi,
+    scenario: MockScenario,
+) -> Response<MockBody> {
+    let mut chunks: Vec<Bytes> = match (&step.chunks, &step.body) {
+        (Some(chunks), _) => chunks.iter().cloned().map(Bytes::from).collect(),
+        (None, Some(body)) => split_sse_events(body.as_bytes()),
+        (None, None) => split_sse_events(streaming_body(provider, scenario)),
+    };
+    if step.malformed_frame {
+        chunks.insert(0, Bytes::from_static(MALFORMED_FRAME));
+    }
+    let chunk_delay = Duration::from_millis(step.chunk_delay_ms);
+    let drop_after = step.drop_after_chunks;
+
+    let (tx, rx) = mpsc::channel(4);
+    tokio::spawn(async move {
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            if index > 0 && !chunk_delay.is_zero() {
+                tokio::time::sleep(chunk_delay).await;
+            }
+            if drop_after == Some(index) {
+                // Let the chunks already sent reach the client before the
+                // connection is torn down.
+                tokio::time::sleep(MIN_DROP_DELAY.saturating_sub(chunk_delay)).await;
+                let dropped = io::Error::new(io::ErrorKind::ConnectionAborted, "scripted drop");
+                let _ = tx.send(Err(dropped)).await;
+                return;
+            }
+            if tx.send(Ok(chunk)).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let mut response = Response::new(ChannelBody { rx }.boxed());
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/event-stream"),
+    );
     response
         .headers_mut()
         .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
@@ -323,8 +479,8 @@ fn simple_response(
     status: StatusCode,
     content_type: &'static str,
     body: Bytes,
-) -> Response<Full<Bytes>> {
-    let mut response = Response::new(Full::new(body));
+) -> Response<MockBody> {
+    let mut response = Response::new(Full::new(body).map_err(|never| match never {}).boxed());
     *response.status_mut() = status;
     response
         .headers_mut()
@@ -336,7 +492,7 @@ fn simple_response_static(
     status: StatusCode,
     content_type: &'static str,
     body: &'static [u8],
-) -> Response<Full<Bytes>> {
+) -> Response<MockBody> {
     simple_response(status, content_type, Bytes::from_static(body))
 }
 
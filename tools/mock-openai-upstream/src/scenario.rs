@@ -0,0 +1,280 @@
+//! Scripted scenarios: a YAML or JSON file listing the responses served to
+//! successive requests, so failover and recovery paths can be exercised
+//! deterministically.
+//!
+//! ```yaml
+//! after_last: repeat_last   # repeat_last | cycle | canned
+//! steps:
+//!   - { status: 429, retry_after: 2 }
+//!   - { latency_ms: 300, stream: true, chunk_delay_ms: 50, drop_after_chunks: 1 }
+//!   - { stream: true, malformed_frame: true }
+//!   - { body: '{"choices":[{"index":0,"message":{"role":"assistant","content":"hi"}}]}' }
+//! ```
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bytes::Bytes;
+use serde::Deserialize;
+
+/// A malformed SSE frame: its JSON payload is cut off.
+pub const MALFORMED_FRAME: &[u8] = b"data: {\"id\":\"broken\",\"choices\":[{\"delta\":\n\n";
+/// A truncated non-stream JSON body.
+pub const MALFORMED_JSON: &[u8] = b"{\"id\":\"broken\",\"choices\":[";
+
+/// What to serve once every step has been used.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AfterLast {
+    /// Keep serving the last step.
+    #[default]
+    RepeatLast,
+    /// Start over from the first step.
+    Cycle,
+    /// Fall back to the canned `MOCK_SCENARIO` responses.
+    Canned,
+}
+
+/// One scripted response.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioStep {
+    /// Response status (default 200).
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// `Retry-After` header value, in seconds.
+    #[serde(default)]
+    pub retry_after: Option<u64>,
+    /// Delay before the response headers are sent.
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Serve a stream (`true`) or a JSON body (`false`); defaults to `MOCK_MODE`.
+    #[serde(default)]
+    pub stream: Option<bool>,
+    /// Raw response body, replacing the canned one.
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Stream events, each sent as one chunk; defaults to the canned stream
+    /// (or `body`) split into SSE events.
+    #[serde(default)]
+    pub chunks: Option<Vec<String>>,
+    /// Delay between stream chunks.
+    #[serde(default)]
+    pub chunk_delay_ms: u64,
+    /// Abort the stream after this many chunks, without a terminal event.
+    #[serde(default)]
+    pub drop_after_chunks: Option<usize>,
+    /// Send a malformed frame before the stream chunks, or truncated JSON in
+    /// place of the body.
+    #[serde(default)]
+    pub malformed_frame: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    #[serde(default)]
+    after_last: AfterLast,
+    steps: Vec<ScenarioStep>,
+}
+
+/// A loaded scenario with its position in the step sequence.
+#[derive(Debug)]
+pub struct Scenario {
+    after_last: AfterLast,
+    steps: Vec<ScenarioStep>,
+    served: AtomicUsize,
+}
+
+impl Scenario {
+    /// Load a scenario from a YAML or JSON file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let file: ScenarioFile = serde_yaml::from_str(&text)
+            .map_err(|err| format!("invalid scenario {}: {err}", path.display()))?;
+        if file.steps.is_empty() {
+            return Err(format!("scenario {} has no steps", path.display()));
+        }
+        Ok(Self {
+            after_last: file.after_last,
+            steps: file.steps,
+            served: AtomicUsize::new(0),
+        })
+    }
+
+    /// The step for the next request; `None` once the steps are used up and
+    /// the scenario falls back to canned responses.
+    pub fn next_step(&self) -> Option<&ScenarioStep> {
+        let index = self.served.fetch_add(1, Ordering::Relaxed);
+        match self.after_last {
+            _ if index < self.steps.len() => self.steps.get(index),
+            AfterLast::RepeatLast => self.steps.last(),
+            AfterLast::Cycle => self.steps.get(index % self.steps.len()),
+            AfterLast::Canned => None,
+        }
+    }
+
+    /// Requests served so far.
+    pub fn served(&self) -> usize {
+        self.served.load(Ordering::Relaxed)
+    }
+
+    /// Restart from the first step.
+    pub fn reset(&self) {
+        self.served.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Split an SSE body into its events, keeping each event's terminator.
+pub fn split_sse_events(body: &[u8]) -> Vec<Bytes> {
+    let mut events = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+    while index + 1 < body.len() {
+        if body[index] == b'\n' && body[index + 1] == b'\n' {
+            events.push(Bytes::copy_from_slice(&body[start..index + 2]));
+            start = index + 2;
+            index = start;
+        } else {
+            index += 1;
+        }
+    }
+    if start < body.len() {
+        events.push(Bytes::copy_from_slice(&body[start..]));
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn load_text(name: &str, text: &str) -> Result<Scenario, String> {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "mock-openai-scenario-{name}-{}.yaml",
+            std::process::id()
+        ));
+        std::fs::write(&path, text).unwrap();
+        let scenario = Scenario::load(&path);
+        let _ = std::fs::remove_file(&path);
+        scenario
+    }
+
+    #[test]
+    fn test_valid_scenario_serves_steps_in_order() {
+        let scenario = load_text(
+            "valid",
+            r#"
+after_last: cycle
+steps:
+  - { status: 429, retry_after: 2 }
+  - { latency_ms: 300, stream: true, chunk_delay_ms: 50, drop_after_chunks: 1 }
+  - { stream: true, malformed_frame: true, chunks: ["data: {}\n\n"] }
+"#,
+        )
+        .unwrap();
+
+        let first = scenario.next_step().unwrap();
+        assert_eq!(first.status, Some(429));
+        assert_eq!(first.retry_after, Some(2));
+        let second = scenario.next_step().unwrap();
+        assert_eq!((second.latency_ms, second.chunk_delay_ms), (300, 50));
+        assert_eq!(second.drop_after_chunks, Some(1));
+        let third = scenario.next_step().unwrap();
+        assert!(third.malformed_frame);
+        assert_eq!(
+            third.chunks.as_deref(),
+            Some(&["data: {}\n\n".to_string()][..])
+        );
+        assert_eq!(scenario.next_step().unwrap().status, Some(429));
+        assert_eq!(scenario.served(), 4);
+
+        scenario.reset();
+        assert_eq!(scenario.next_step().unwrap().status, Some(429));
+    }
+
+    #[test]
+    fn test_after_last_decides_what_follows_the_steps() {
+        let repeat = load_text("repeat", "steps: [{ status: 500 }, { status: 200 }]").unwrap();
+        repeat.next_step();
+        repeat.next_step();
+        assert_eq!(repeat.next_step().unwrap().status, Some(200));
+
+        let canned = load_text(
+            "canned",
+            r#"{ "after_last": "canned", "steps": [{ "status": 503 }] }"#,
+        )
+        .unwrap();
+        assert_eq!(canned.next_step().unwrap().status, Some(503));
+        assert!(canned.next_step().is_none());
+    }
+
+    #[test]
+    fn test_malformed_lines_are_rejected() {
+        let err = load_text("malformed", "steps:\n  - { status: 429\n  - [").unwrap_err();
+        assert!(err.starts_with("invalid scenario "), "{err}");
+
+        let err = load_text("not-a-list", "steps: { status: 429 }").unwrap_err();
+        assert!(err.starts_with("invalid scenario "), "{err}");
+    }
+
+    #[test]
+    fn test_empty_files_are_rejected() {
+        let err = load_text("empty", "").unwrap_err();
+        assert!(err.starts_with("invalid scenario "), "{err}");
+
+        let err = load_text("no-steps", "steps: []").unwrap_err();
+        assert!(err.ends_with("has no steps"), "{err}");
+    }
+
+    #[test]
+    fn test_unknown_directives_are_rejected() {
+        let err = load_text("unknown-step", "steps: [{ status: 200, jitter_ms: 5 }]").unwrap_err();
+        assert!(err.contains("jitter_ms"), "{err}");
+
+        let err = load_text("unknown-top", "repeat: true\nsteps: [{}]").unwrap_err();
+        assert!(err.contains("repeat"), "{err}");
+
+        let err = load_text("unknown-after-last", "after_last: shuffle\nsteps: [{}]").unwrap_err();
+        assert!(err.contains("shuffle"), "{err}");
+    }
+
+    #[test]
+    fn test_bad_delay_values_are_rejected() {
+        for (name, step) in [
+            ("negative-latency", "{ latency_ms: -5 }"),
+            ("text-latency", "{ latency_ms: slow }"),
+            ("fractional-chunk-delay", "{ chunk_delay_ms: 1.5 }"),
+            ("negative-retry-after", "{ retry_after: -1 }"),
+        ] {
+            let err = load_text(name, &format!("steps: [{step}]")).unwrap_err();
+            assert!(err.starts_with("invalid scenario "), "{name}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_missing_files_are_reported() {
+        let err = Scenario::load(&PathBuf::from("/nonexistent/scenario.yaml")).unwrap_err();
+        assert!(
+            err.starts_with("failed to read /nonexistent/scenario.yaml"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_split_sse_events_keeps_terminators_and_tail() {
+        let events = split_sse_events(b"data: 1\n\ndata: 2\n\ndata: [DONE]");
+        assert_eq!(
+            events,
+            [
+                Bytes::from_static(b"data: 1\n\n"),
+                Bytes::from_static(b"data: 2\n\n"),
+                Bytes::from_static(b"data: [DONE]"),
+            ]
+        );
+    }
+}